@@ -0,0 +1,67 @@
+import os
+from typing import List
+
+from numpy.typing import NDArray
+import imspy_connector
+ims = imspy_connector.py_chemistry
+
+
+def _threads(num_threads: int) -> int:
+    return os.cpu_count() if num_threads == -1 else num_threads
+
+
+def isoelectric_point(sequences: List[str], pka_set: str = "emboss", num_threads: int = -1) -> NDArray:
+    """Calculate the isoelectric point of peptides by bisection over the Henderson–Hasselbalch net charge.
+
+    Args:
+        sequences: peptide sequences, may contain modifications in the format [UNIMOD:ID], phosphorylation
+            adds acidic groups, carbamidomethylated cysteines and acetylated N-termini are not ionizable
+        pka_set: pKa values to use, either emboss or lehninger
+        num_threads: number of threads, -1 uses all cores
+
+    Returns:
+        NDArray: isoelectric point per sequence
+
+    Raises:
+        ValueError: if the pKa set is unknown
+    """
+    return ims.isoelectric_point(list(sequences), pka_set, _threads(num_threads))
+
+
+def gravy(sequences: List[str], num_threads: int = -1) -> NDArray:
+    """Calculate the grand average of hydropathy (GRAVY) of peptides with the Kyte-Doolittle scale.
+
+    Args:
+        sequences: peptide sequences, may contain modifications in the format [UNIMOD:ID]
+        num_threads: number of threads, -1 uses all cores
+
+    Returns:
+        NDArray: GRAVY per sequence
+    """
+    return ims.gravy(list(sequences), _threads(num_threads))
+
+
+def aromaticity(sequences: List[str], num_threads: int = -1) -> NDArray:
+    """Calculate the fraction of phenylalanine, tryptophan and tyrosine residues of peptides.
+
+    Args:
+        sequences: peptide sequences, may contain modifications in the format [UNIMOD:ID]
+        num_threads: number of threads, -1 uses all cores
+
+    Returns:
+        NDArray: aromaticity per sequence
+    """
+    return ims.aromaticity(list(sequences), _threads(num_threads))
+
+
+def aliphatic_index(sequences: List[str], num_threads: int = -1) -> NDArray:
+    """Calculate the aliphatic index of peptides, the relative volume of aliphatic side chains.
+
+    Args:
+        sequences: peptide sequences, may contain modifications in the format [UNIMOD:ID]
+        num_threads: number of threads, -1 uses all cores
+
+    Returns:
+        NDArray: aliphatic index per sequence
+    """
+    return ims.aliphatic_index(list(sequences), _threads(num_threads))
@@ -1,5 +1,6 @@
 import os
 import numpy as np
+import pandas as pd
 from numpy.typing import NDArray
 import imspy_connector
 ims = imspy_connector.py_chemistry
@@ -80,4 +81,68 @@ def one_over_k0_to_ccs_par(one_over_k0: NDArray, mz: NDArray, charge: NDArray, m
      if num_threads == -1:
           num_threads = os.cpu_count()
 
-     return ims.one_over_reduced_mobility_to_ccs_par(one_over_k0, mz, charge, mass_gas, temp, t_diff, num_threads)
\ No newline at end of file
+     return ims.one_over_reduced_mobility_to_ccs_par(one_over_k0, mz, charge, mass_gas, temp, t_diff, num_threads)
+
+def predict_inverse_mobility(mass: float, charge: int, coefficients: list[tuple[float, float]] = None) -> float:
+    """Predict the inverse reduced ion mobility (1/k0) of a peptide ion with a power-law CCS model.
+
+    Args:
+        mass: monoisotopic mass of the uncharged peptide
+        charge: charge state of the ion
+        coefficients: optional (a, b) per charge state starting at charge 1, ccs = a * mass ** b,
+            None uses the default fit to tryptic peptides
+
+    Returns:
+        float: inverse reduced ion mobility
+    """
+    return ims.predict_inverse_mobility(mass, charge, coefficients)
+
+
+def predict_inverse_mobilities_par(masses: NDArray, charges: NDArray, coefficients: list[tuple[float, float]] = None,
+                                   num_threads: int = -1) -> NDArray:
+    """Predict the inverse reduced ion mobility (1/k0) of many peptide ions in parallel.
+
+    Args:
+        masses: monoisotopic masses of the uncharged peptides
+        charges: charge states of the ions
+        coefficients: optional (a, b) per charge state starting at charge 1, ccs = a * mass ** b
+        num_threads: number of threads
+
+    Returns:
+        NDArray: inverse reduced ion mobility
+    """
+
+    if num_threads == -1:
+        num_threads = os.cpu_count()
+
+    return np.array(ims.predict_inverse_mobilities_par(list(masses), [int(c) for c in charges], num_threads, coefficients))
+
+
+def estimate_im_peaks(mobilities: list[NDArray], intensities: list[NDArray], num_threads: int = -1) -> pd.DataFrame:
+    """Estimate apex, width and shape of the most intense peak of many mobilograms in parallel.
+
+    The apex is refined by parabolic interpolation, plateaus of equal maxima use their center, and the half
+    maximum crossings are interpolated linearly. Peaks that do not fall below half maximum before the edge of
+    the scan range are flagged as truncated.
+
+    Args:
+        mobilities: inverse ion mobility of every point, one array per mobilogram
+        intensities: intensity of every point, one array per mobilogram
+        num_threads: number of threads, -1 uses all cores
+
+    Returns:
+        pd.DataFrame: apex_mobility, fwhm, area, asymmetry and truncated per mobilogram, NaN for
+            mobilograms without positive intensity
+
+    Raises:
+        ValueError: if a mobilogram has a different number of mobilities and intensities
+    """
+
+    if num_threads == -1:
+        num_threads = os.cpu_count()
+
+    return pd.DataFrame(ims.estimate_im_peaks(
+        [np.asarray(m, dtype=np.float64).tolist() for m in mobilities],
+        [np.asarray(i, dtype=np.float64).tolist() for i in intensities],
+        num_threads,
+    ))
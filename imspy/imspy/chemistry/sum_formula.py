@@ -1,3 +1,5 @@
+from typing import Dict
+
 import imspy_connector
 
 from imspy.data.spectrum import MzSpectrum
@@ -36,3 +38,48 @@ class SumFormula(RustWrapperObject):
 
     def generate_isotope_distribution(self, charge: int) -> 'MzSpectrum':
         return MzSpectrum.from_py_ptr(self.__ptr.generate_isotope_distribution(charge))
+
+
+def parse_formula(formula: str) -> Dict[str, int]:
+    """Parse an elemental formula into an atomic composition.
+
+    Args:
+        formula (str): Formula, e.g. 'C6H12O6'. Counts may be negative for losses, e.g. 'H-2O-1', or be written in
+            parentheses as in Unimod, e.g. 'H(-1)N(-1)O'. Heavy isotopes are written in brackets, e.g. '[13C]2'.
+
+    Returns:
+        Dict[str, int]: Count per element, heavy isotopes as '2H', '13C' and '15N'.
+
+    Raises:
+        ValueError: If the formula contains an unknown element or isotope or is malformed.
+    """
+    return ims.parse_formula(formula)
+
+
+def to_hill_notation(composition: Dict[str, int]) -> str:
+    """Formula of an atomic composition in Hill notation, parses back to the composition with `parse_formula`.
+
+    Args:
+        composition (Dict[str, int]): Count per element, heavy isotopes as '2H', '13C' and '15N'.
+
+    Returns:
+        str: Formula, C and H first if the composition contains carbon, all other elements alphabetically.
+    """
+    return ims.to_hill_notation(composition)
+
+
+def formula_mass(formula: str, average: bool = False) -> float:
+    """Mass of an elemental formula, see `parse_formula` for the notation.
+
+    Args:
+        formula (str): Formula.
+        average (bool): If true, the average mass from the natural isotope abundances, otherwise the monoisotopic
+            mass.
+
+    Returns:
+        float: Mass in Da.
+
+    Raises:
+        ValueError: If the formula contains an unknown element or isotope or is malformed.
+    """
+    return ims.formula_mass(formula, average)
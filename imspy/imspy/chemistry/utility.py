@@ -1,3 +1,7 @@
+from typing import List, Optional, Tuple
+
+import numpy as np
+import pandas as pd
 import imspy_connector
 
 from imspy.data.spectrum import MzSpectrum
@@ -18,6 +22,107 @@ def calculate_mz(mass: float, charge: int) -> float:
     return ims.calculate_mz(mass, charge)
 
 
+def parse_proforma(proforma: str) -> Tuple[str, Optional[int]]:
+    """Convert a ProForma 2.0 peptidoform to a sequence with modifications in the format [UNIMOD:ID].
+
+    Args:
+        proforma (str): ProForma string, e.g. [Acetyl]-EM[Oxidation]EVEES[+79.966]PEK/2.
+
+    Returns:
+        Tuple[str, Optional[int]]: Sequence and the charge of the suffix, if any.
+
+    Raises:
+        ValueError: If the string is invalid or uses ProForma features without a unimod equivalent.
+    """
+    return ims.parse_proforma(proforma)
+
+
+def to_proforma(sequence: str, charge: Optional[int] = None) -> str:
+    """Convert a sequence with modifications in the format [UNIMOD:ID] to ProForma 2.0 notation.
+
+    Args:
+        sequence (str): Peptide sequence.
+        charge (Optional[int]): Charge written as suffix.
+
+    Returns:
+        str: ProForma string.
+    """
+    return ims.to_proforma(sequence, charge)
+
+
+def calculate_mz_with_adduct(mono_isotopic_masses: np.ndarray, adduct: str) -> np.ndarray:
+    """Calculate m/z values of adduct ions.
+
+    Args:
+        mono_isotopic_masses (np.ndarray): Monoisotopic masses of the neutral molecules.
+        adduct (str): Adduct notation, e.g. [M+H]+, [M+2H]2+, [M+Na]+, [M+K]+, [M+NH4]+ or [M-H]-.
+
+    Returns:
+        np.ndarray: m/z values.
+
+    Raises:
+        ValueError: If the adduct cannot be parsed.
+    """
+    return ims.calculate_mz_with_adduct(np.asarray(mono_isotopic_masses, dtype=np.float64), adduct)
+
+
+def neutral_mass_from_mz(mz: np.ndarray, adduct: str) -> np.ndarray:
+    """Calculate monoisotopic masses of the neutral molecules from m/z values of adduct ions.
+
+    Args:
+        mz (np.ndarray): m/z values.
+        adduct (str): Adduct notation, e.g. [M+H]+, [M+2H]2+, [M+Na]+, [M+K]+, [M+NH4]+ or [M-H]-.
+
+    Returns:
+        np.ndarray: Neutral monoisotopic masses.
+
+    Raises:
+        ValueError: If the adduct cannot be parsed.
+    """
+    return ims.neutral_mass_from_mz(np.asarray(mz, dtype=np.float64), adduct)
+
+
+def calculate_monoisotopic_masses(
+        sequences: List[str],
+        label: Optional[str] = None,
+        num_threads: int = 4) -> np.ndarray:
+    """Calculate the monoisotopic masses of peptides in parallel, optionally with a stable isotope label.
+
+    Args:
+        sequences (List[str]): Peptide sequences, may contain modifications in the format [UNIMOD:ID].
+        label (Optional[str]): Label scheme, one of silac_medium (Lys4/Arg6), silac_heavy (Lys8/Arg10), 15n,
+            dimethyl_light, dimethyl_intermediate or dimethyl_heavy, None for unlabeled peptides.
+        num_threads (int): Number of threads.
+
+    Returns:
+        np.ndarray: Monoisotopic mass per peptide.
+
+    Raises:
+        ValueError: If the label scheme is unknown.
+    """
+    return ims.calculate_monoisotopic_masses(list(sequences), label, num_threads)
+
+
+def calculate_average_masses(
+        sequences: List[str],
+        label: Optional[str] = None,
+        num_threads: int = 4) -> np.ndarray:
+    """Calculate the average masses of peptides in parallel from their atomic compositions.
+
+    Args:
+        sequences (List[str]): Peptide sequences, may contain modifications in the format [UNIMOD:ID].
+        label (Optional[str]): Label scheme, see calculate_monoisotopic_masses, None for unlabeled peptides.
+        num_threads (int): Number of threads.
+
+    Returns:
+        np.ndarray: Average mass per peptide.
+
+    Raises:
+        ValueError: If the label scheme is unknown or a modification has no known atomic composition.
+    """
+    return ims.calculate_average_masses(list(sequences), label, num_threads)
+
+
 def calculate_transmission_dependent_fragment_ion_isotope_distribution(
         target_spec: MzSpectrum,
         complement_spec: MzSpectrum,
@@ -41,3 +146,109 @@ def calculate_transmission_dependent_fragment_ion_isotope_distribution(
             transmitted_isotopes.get_py_ptr(), max_isotope
         )
     )
+
+
+DECONVOLUTED_PEAK_COLUMNS = ['mono_mz', 'monoisotopic_mass', 'charge', 'intensity', 'score', 'num_peaks']
+
+
+def deisotope_spectrum(
+        spectrum: MzSpectrum,
+        max_charge: int = 4,
+        ppm: float = 10.0,
+        min_cosine_to_averagine: float = 0.8) -> pd.DataFrame:
+    """Deisotope and charge deconvolute a centroided spectrum by scoring isotope envelopes against averagine.
+
+    Args:
+        spectrum (MzSpectrum): Centroided spectrum.
+        max_charge (int): Maximum charge state.
+        ppm (float): m/z tolerance for isotope peak matching in ppm.
+        min_cosine_to_averagine (float): Minimum cosine similarity of an envelope to the averagine distribution.
+
+    Returns:
+        pd.DataFrame: Deconvoluted peaks with mono m/z, monoisotopic mass, charge, intensity, score and number of peaks.
+    """
+    columns = ims.deisotope_mz_spectrum(spectrum.get_py_ptr(), max_charge, ppm, min_cosine_to_averagine)
+    return pd.DataFrame(dict(zip(DECONVOLUTED_PEAK_COLUMNS, columns)))
+
+
+def deisotope_spectra(
+        spectra: List[MzSpectrum],
+        max_charge: int = 4,
+        ppm: float = 10.0,
+        min_cosine_to_averagine: float = 0.8,
+        num_threads: int = 4) -> List[pd.DataFrame]:
+    """Deisotope and charge deconvolute a list of centroided spectra in parallel.
+
+    Args:
+        spectra (List[MzSpectrum]): Centroided spectra.
+        max_charge (int): Maximum charge state.
+        ppm (float): m/z tolerance for isotope peak matching in ppm.
+        min_cosine_to_averagine (float): Minimum cosine similarity of an envelope to the averagine distribution.
+        num_threads (int): Number of threads.
+
+    Returns:
+        List[pd.DataFrame]: Deconvoluted peaks per spectrum.
+    """
+    result = ims.deisotope_mz_spectra([spectrum.get_py_ptr() for spectrum in spectra], max_charge, ppm,
+                                      min_cosine_to_averagine, num_threads)
+    return [pd.DataFrame(dict(zip(DECONVOLUTED_PEAK_COLUMNS, columns))) for columns in result]
+
+
+ISOTOPE_FIT_COLUMNS = ['chi_square', 'num_matched', 'shifted_chi_square', 'shifted_num_matched', 'is_mispick',
+                       'ratios']
+
+
+def score_isotope_fits(
+        spectrum: MzSpectrum,
+        mono_mzs: List[float],
+        charges: List[int],
+        n_isotopes: int = 4,
+        ppm: float = 10.0,
+        num_threads: int = 4) -> pd.DataFrame:
+    """Score candidate isotope envelopes (monoisotopic m/z, charge) of a spectrum against the averagine model,
+    e.g. to rescore the precursors of a DDA precursor table.
+
+    Args:
+        spectrum (MzSpectrum): Observed spectrum.
+        mono_mzs (List[float]): Monoisotopic m/z per candidate.
+        charges (List[int]): Charge per candidate.
+        n_isotopes (int): Number of isotopes to score, including the monoisotopic peak.
+        ppm (float): m/z tolerance for isotope peak matching in ppm.
+        num_threads (int): Number of threads.
+
+    Returns:
+        pd.DataFrame: One row per candidate, is_mispick marks candidates where the envelope starting one isotope
+        lower fits better.
+
+    Raises:
+        ValueError: If mono_mzs and charges differ in length, a charge is not positive or n_isotopes is 0.
+    """
+    result = ims.score_isotope_fits(spectrum.get_py_ptr(), list(mono_mzs), list(charges), n_isotopes, ppm,
+                                    num_threads)
+    return pd.DataFrame(dict(zip(ISOTOPE_FIT_COLUMNS, result)))
+
+
+def isotope_distribution_cache_stats() -> dict:
+    """Statistics of the cache that peptide and fragment ions share their isotope distributions in, ions with the
+    same atomic composition calculate their distribution once.
+
+    Returns:
+        dict: Number of hits and misses, cached compositions (entries) and maximum number of cached compositions
+        (max_size).
+    """
+    hits, misses, entries, max_size = ims.isotope_distribution_cache_stats()
+    return {'hits': hits, 'misses': misses, 'entries': entries, 'max_size': max_size}
+
+
+def set_isotope_distribution_cache_size(max_size: int) -> None:
+    """Set the maximum number of cached compositions, 0 disables the cache.
+
+    Args:
+        max_size (int): Maximum number of cached compositions.
+    """
+    ims.set_isotope_distribution_cache_size(max_size)
+
+
+def clear_isotope_distribution_cache() -> None:
+    """Drop all cached isotope distributions and reset the statistics."""
+    ims.clear_isotope_distribution_cache()
@@ -1,5 +1,7 @@
 from abc import ABC, abstractmethod
-from typing import List, Tuple, Union
+from typing import Dict, List, Optional, Tuple, Union
+
+from numpy.typing import NDArray
 
 import imspy_connector
 
@@ -180,7 +182,11 @@ class PeptideProductIon(RustWrapperObject):
 
     @property
     def mono_isotopic_mass(self) -> float:
-        return self.__py_ptr.mono_isotopic_mass()
+        return self.__py_ptr.mono_isotopic_mass
+
+    @property
+    def average_mass(self) -> float:
+        return self.__py_ptr.average_mass
 
     @property
     def mz(self) -> float:
@@ -207,7 +213,7 @@ class PeptideProductIon(RustWrapperObject):
             abundance_threshold: float = 1e-8,
             max_result: int = 200,
             intensity_min: float = 1e-4
-    ) -> List[Tuple[float, float]]:
+    ) -> Tuple[NDArray, NDArray]:
         """Calculate the isotope distribution of the product ion.
 
         Args:
@@ -217,7 +223,7 @@ class PeptideProductIon(RustWrapperObject):
             intensity_min: The minimum intensity of the isotope distribution.
 
         Returns:
-            The isotope distribution of the product ion.
+            The m/z values and relative abundances of the isotope peaks of the product ion.
         """
         return self.__py_ptr.isotope_distribution(mass_tolerance, abundance_threshold, max_result, intensity_min)
 
@@ -248,6 +254,10 @@ class PeptideSequence(RustWrapperObject):
     def mono_isotopic_mass(self) -> float:
         return self.__py_ptr.mono_isotopic_mass
 
+    @property
+    def average_mass(self) -> float:
+        return self.__py_ptr.average_mass
+
     @property
     def atomic_composition(self):
         return self.__py_ptr.atomic_composition()
@@ -259,9 +269,64 @@ class PeptideSequence(RustWrapperObject):
     def to_tokens(self, group_modifications: bool = True) -> List[str]:
         return self.__py_ptr.to_tokens(group_modifications)
 
+    @property
+    def composition(self) -> Dict[str, int]:
+        """Number of occurrences of every amino acid, modifications are ignored."""
+        return self.__py_ptr.composition_counts()
+
+    @property
+    def tokens(self) -> List[Tuple[Union[None, str], int, List[Tuple[int, float]]]]:
+        """Residues of the sequence, see `tokens_with_positions`."""
+        return self.tokens_with_positions()
+
+    def tokens_with_positions(self) -> List[Tuple[Union[None, str], int, List[Tuple[int, float]]]]:
+        """Split the sequence into residues with their positions and modifications.
+
+        Modifications in front of the first residue are reported at position 0 with residue None,
+        C-terminal modifications are stored on the last residue and reported at its position.
+
+        Returns:
+            One (residue, 1-based position, [(unimod id, modification mass), ...]) tuple per residue.
+        """
+        return self.__py_ptr.tokens_with_positions()
+
+    def modification_sites(self) -> List[Tuple[Union[None, str], int, List[Tuple[int, float]]]]:
+        """Modified residues only, see `tokens_with_positions`.
+
+        Returns:
+            One (residue, 1-based position, [(unimod id, modification mass), ...]) tuple per modified residue.
+        """
+        return self.__py_ptr.modification_sites()
+
     def to_sage_representation(self) -> Tuple[str, List[float]]:
         return self.__py_ptr.to_sage_representation()
 
+    def to_proforma(self) -> str:
+        """Write the sequence in ProForma 2.0 notation, e.g. [Acetyl]-EM[Oxidation]EVEES[Phospho]PEK.
+
+        Returns:
+            The ProForma string.
+        """
+        return self.__py_ptr.to_proforma()
+
+    @classmethod
+    def from_proforma(cls, proforma: str) -> 'PeptideSequence':
+        """Create a peptide sequence from ProForma 2.0 notation, a charge suffix is ignored.
+
+        Args:
+            proforma: ProForma string with unimod names, accessions or mass deltas, e.g. ELVIS[+79.966]K/2.
+
+        Returns:
+            The peptide sequence with modifications in the format [UNIMOD:ID].
+
+        Raises:
+            ValueError: if the string is invalid or uses ProForma features without a unimod equivalent
+        """
+        instance = cls.__new__(cls)
+        instance.known_fragment_types = ['a', 'b', 'c', 'x', 'y', 'z']
+        instance.__py_ptr = ims.PyPeptideSequence.from_proforma(proforma)
+        return instance
+
     def to_json(self) -> str:
         return self.__py_ptr.to_json()
 
@@ -365,7 +430,8 @@ class PeptideSequence(RustWrapperObject):
             charge: int,
             fragment_type: str = "b",
             normalize: bool = True,
-            half_charge_one: bool = True
+            half_charge_one: bool = True,
+            shape: Optional[Tuple[int, int, int]] = None,
     ) -> PeptideProductIonSeriesCollection:
         """Associate the peptide sequence with predicted intensities from Prosit intensity prediction.
 
@@ -376,16 +442,22 @@ class PeptideSequence(RustWrapperObject):
             fragment_type: The type of the product ions, must be one of 'a', 'b', 'c', 'x', 'y', 'z'.
             normalize: Whether to normalize the intensities.
             half_charge_one: Whether to use half charge one.
+            shape: (positions, series, charges) of the flat intensities, defaults to the Prosit shape (29, 2, 3).
+                Ions beyond the positions of the model get zero intensity.
 
         Returns:
             The b and y product ion series of the peptide sequence.
+
+        Raises:
+            ValueError: If the number of flat intensities does not match the shape.
         """
         fragment_type = fragment_type.lower()
         assert fragment_type in self.known_fragment_types, (f"Invalid fragment type: {fragment_type}, "
                                                             f"must be one of {self.known_fragment_types}")
 
         result = self.__py_ptr.associate_with_predicted_intensities(flat_intensities,
-                                                                    charge, fragment_type, normalize, half_charge_one)
+                                                                    charge, fragment_type, normalize, half_charge_one,
+                                                                    shape)
 
         return PeptideProductIonSeriesCollection.from_py_ptr(result)
 
@@ -431,10 +503,71 @@ class PeptideIon(RustWrapperObject):
     def mz(self) -> float:
         return self.__py_ptr.mz
 
+    @property
+    def mono_isotopic_mass(self) -> float:
+        return self.__py_ptr.mono_isotopic_mass
+
     @property
     def atomic_composition(self):
         return self.__py_ptr.atomic_composition()
 
+    def isotope_distribution(
+            self,
+            mass_tolerance: float = 1e-3,
+            abundance_threshold: float = 1e-8,
+            max_result: int = 200,
+            intensity_min: float = 1e-4
+    ) -> Tuple[NDArray, NDArray]:
+        """Calculate the isotope distribution of the peptide ion, including label and adduct atoms.
+
+        Args:
+            mass_tolerance: The mass tolerance for the isotope distribution calculation.
+            abundance_threshold: The abundance threshold for the isotope distribution calculation.
+            max_result: The maximum number of results to return.
+            intensity_min: The minimum intensity of the isotope distribution.
+
+        Returns:
+            The m/z values and relative abundances of the isotope peaks of the peptide ion.
+        """
+        return self.__py_ptr.isotope_distribution(mass_tolerance, abundance_threshold, max_result, intensity_min)
+
+    @property
+    def label(self) -> Union[None, str]:
+        return self.__py_ptr.label
+
+    def with_label(self, label: str) -> 'PeptideIon':
+        """Copy the ion with a stable isotope label, m/z and isotopic spectrum of the copy include the label.
+
+        Args:
+            label: one of silac_medium (Lys4/Arg6), silac_heavy (Lys8/Arg10), 15n, dimethyl_light,
+                dimethyl_intermediate or dimethyl_heavy
+
+        Returns:
+            The labeled peptide ion.
+
+        Raises:
+            ValueError: if the label scheme is unknown
+        """
+        return PeptideIon.from_py_ptr(self.__py_ptr.with_label(label))
+
+    @property
+    def adduct(self) -> Union[None, str]:
+        return self.__py_ptr.adduct
+
+    def with_adduct(self, adduct: str) -> 'PeptideIon':
+        """Copy the ion formed as an adduct instead of by protonation, the charge becomes the adduct charge.
+
+        Args:
+            adduct: adduct notation, e.g. [M+H]+, [M+2H]2+, [M+Na]+, [M+K]+, [M+NH4]+ or [M-H]-
+
+        Returns:
+            The peptide ion with the adduct, its m/z and isotopic spectrum include the adduct atoms.
+
+        Raises:
+            ValueError: if the adduct cannot be parsed
+        """
+        return PeptideIon.from_py_ptr(self.__py_ptr.with_adduct(adduct))
+
     def calculate_isotopic_spectrum(
             self,
             mass_tolerance: float = 1e-3,
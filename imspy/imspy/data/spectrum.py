@@ -1,7 +1,7 @@
 from __future__ import annotations
 import json
 import numpy as np
-from typing import List, Tuple, Callable
+from typing import List, Optional, Tuple, Callable
 import pandas as pd
 from numpy.typing import NDArray
 from scipy.signal import find_peaks
@@ -123,6 +123,27 @@ class IndexedMzSpectrum(RustWrapperObject):
     def __repr__(self):
         return f"IndexedMzSpectrum(num_peaks={len(self.index)})"
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.get_py_ptr())
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, IndexedMzSpectrum):
+            return NotImplemented
+        return self.get_py_ptr() == other.get_py_ptr()
+
+    def approx_equal(self, other: 'IndexedMzSpectrum', tolerance: float = 1e-6) -> bool:
+        """Compare to another spectrum, floating point values may differ by at most tolerance.
+
+        Args:
+            other (IndexedMzSpectrum): Spectrum to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.get_py_ptr().approx_equal(other.get_py_ptr(), tolerance)
+
 
 class MzSpectrum(RustWrapperObject):
     
@@ -204,6 +225,33 @@ class MzSpectrum(RustWrapperObject):
     def __repr__(self):
         return f"MzSpectrum(num_peaks={len(self.mz)})"
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.get_py_ptr())
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, MzSpectrum):
+            return NotImplemented
+        return self.get_py_ptr() == other.get_py_ptr()
+
+    def approx_equal(self, other: 'MzSpectrum', tolerance: float = 1e-6) -> bool:
+        """Compare to another spectrum, floating point values may differ by at most tolerance.
+
+        Args:
+            other (MzSpectrum): Spectrum to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+
+        Examples:
+            >>> spectrum = MzSpectrum(np.array([100.0, 200.0]), np.array([10.0, 20.0]))
+            >>> shifted = MzSpectrum(np.array([100.0, 200.00001]), np.array([10.0, 20.0]))
+            >>> len(spectrum), spectrum == shifted, spectrum.approx_equal(shifted, tolerance=1e-4)
+            (2, False, True)
+        """
+        return self.get_py_ptr().approx_equal(other.get_py_ptr(), tolerance)
+
     def __add__(self, other: MzSpectrum) -> MzSpectrum:
         """Overwrite + operator for adding of spectra
 
@@ -226,6 +274,56 @@ class MzSpectrum(RustWrapperObject):
         """
         return self.from_py_ptr(self.__spec_ptr * scale)
 
+    def __rmul__(self, scale) -> MzSpectrum:
+        return self.__mul__(scale)
+
+    def add(self, other: MzSpectrum, resolution: int = 6) -> MzSpectrum:
+        """Add spectra, summing the intensities of peaks that fall into the same m/z bin.
+
+        Args:
+            other (MzSpectrum): Other spectrum.
+            resolution (int, optional): Decimal places of the m/z bins. Defaults to 6, like the + operator.
+
+        Returns:
+            MzSpectrum: Sum of spectra, sorted by m/z
+        """
+        return self.from_py_ptr(self.__spec_ptr.add(other.__spec_ptr, resolution))
+
+    def top_n(self, n: int) -> MzSpectrum:
+        """Keep the n most intense peaks, ties at the cutoff are resolved in favor of the lower m/z.
+
+        Args:
+            n (int): Number of peaks to keep.
+
+        Returns:
+            MzSpectrum: Spectrum with the kept peaks in their original order
+        """
+        return self.from_py_ptr(self.__spec_ptr.top_n(n))
+
+    def filter_relative_intensity(self, min_fraction_of_base_peak: float) -> MzSpectrum:
+        """Drop peaks below a fraction of the base peak intensity.
+
+        Args:
+            min_fraction_of_base_peak (float): Minimum intensity relative to the most intense peak, e.g. 0.01.
+
+        Returns:
+            MzSpectrum: Filtered spectrum
+        """
+        return self.from_py_ptr(self.__spec_ptr.filter_relative_intensity(min_fraction_of_base_peak))
+
+    def subtract(self, other: MzSpectrum, tolerance: float = 0.01) -> MzSpectrum:
+        """Subtract a background spectrum, negative intensities are clamped to zero.
+
+        Args:
+            other (MzSpectrum): Background spectrum.
+            tolerance (float, optional): Background peaks within this m/z distance are subtracted from a peak.
+                Defaults to 0.01.
+
+        Returns:
+            MzSpectrum: Spectrum with the peaks of this spectrum, sorted by m/z
+        """
+        return self.from_py_ptr(self.__spec_ptr.subtract(other.__spec_ptr, tolerance))
+
     def to_windows(self, window_length: float = 10, overlapping: bool = True, min_num_peaks: int = 5,
                    min_intensity: float = 1) -> Tuple[NDArray, List[MzSpectrum]]:
         """Convert the spectrum to a list of windows.
@@ -281,6 +379,80 @@ class MzSpectrum(RustWrapperObject):
             MzSpectrumVectorized: Vectorized spectrum.
         """
         return MzSpectrumVectorized.from_py_ptr(self.__spec_ptr.vectorized(resolution))
+
+    def vectorize(self, binning: str = "ppm", width: float = 10.0, mz_min: float = 50.0,
+                  mz_max: float = 2000.0) -> MzSpectrumVectorized:
+        """Convert the spectrum to a vectorized spectrum with bins of constant width in Da or ppm.
+
+        Ppm bins grow with m/z, bin i spans mz_min * (1 + width * 1e-6) ** i up to the next edge,
+        peaks outside of [mz_min, mz_max] are dropped.
+
+        Args:
+            binning (str, optional): "decimal", "da" or "ppm". Defaults to "ppm".
+            width (float, optional): Decimal places, bin width in Da or bin width in ppm. Defaults to 10.0.
+            mz_min (float, optional): Lower bound of the m/z range of ppm bins. Defaults to 50.0.
+            mz_max (float, optional): Upper bound of the m/z range of ppm bins. Defaults to 2000.0.
+
+        Returns:
+            MzSpectrumVectorized: Vectorized spectrum, see MzSpectrumVectorized.bin_centers.
+
+        Raises:
+            ValueError: If the binning is unknown or its parameters are invalid.
+        """
+        return MzSpectrumVectorized.from_py_ptr(self.__spec_ptr.vectorize(binning, width, mz_min, mz_max))
+
+    def resample(self, grid: NDArray[np.float64], method: str = "linear", ppm: float = 10.0) -> NDArray[np.float64]:
+        """Resample the spectrum onto a fixed m/z grid, e.g. to featurize spectra for deep learning models.
+
+        Args:
+            grid (NDArray[np.float64]): m/z values of the grid points in strictly increasing order.
+            method (str, optional): "nearest_bin" sums every peak into its closest grid point, "linear" interpolates
+                between the peaks around every grid point (profile spectra), "gaussian" spreads every peak with a
+                standard deviation of ppm of its m/z. Defaults to "linear".
+            ppm (float, optional): Width of the gaussian peaks. Defaults to 10.0.
+
+        Returns:
+            NDArray[np.float64]: One intensity per grid point.
+
+        Raises:
+            ValueError: If the grid is not strictly increasing or the method is unknown.
+        """
+        return self.__spec_ptr.resample(np.asarray(grid, dtype=np.float64), method, ppm)
+
+    def signal_to_noise(self, window: float = 100.0, method: str = "mad") -> NDArray[np.float64]:
+        """Signal-to-noise ratio of every peak relative to the noise of the peaks around it.
+
+        Windows with fewer than 10 peaks use the noise of the whole spectrum instead.
+
+        Args:
+            window (float, optional): Width of the sliding window in Da. Defaults to 100.0.
+            method (str, optional): "mad" or "lowest_quartile", see `TimsFrame.estimate_noise`. Defaults to "mad".
+
+        Returns:
+            NDArray[np.float64]: One ratio per peak.
+        """
+        return self.__spec_ptr.signal_to_noise(window, method)
+
+    def spectral_entropy(self) -> float:
+        """Shannon entropy of the intensities normalized to a sum of 1.
+
+        Returns:
+            float: Entropy in nats, 0 for spectra without intensity.
+        """
+        return ims.spectral_entropy(self.__spec_ptr)
+
+    def entropy_similarity(self, other: 'MzSpectrum', resolution: int = 2) -> float:
+        """Entropy similarity (Li et al., Nature Methods 2021) to another spectrum.
+
+        Args:
+            other (MzSpectrum): Spectrum to compare with.
+            resolution (int, optional): Decimal places of the m/z bins that peaks are matched in, the same binning
+                as for the cosine similarity. Defaults to 2.
+
+        Returns:
+            float: Similarity between 0 for spectra without shared peaks and 1 for identical spectra.
+        """
+        return ims.entropy_similarity(self.__spec_ptr, other.get_py_ptr(), resolution)
     
     def to_jsons(self) -> str:
         """
@@ -334,18 +506,26 @@ class MzSpectrum(RustWrapperObject):
 
     
 class MzSpectrumVectorized(RustWrapperObject):
-    def __init__(self, indices: NDArray[np.int32], values: NDArray[np.float64], resolution: int):
+    def __init__(self, indices: NDArray[np.int32], values: NDArray[np.float64], resolution: int,
+                 binning: Optional[str] = None, width: Optional[float] = None, mz_min: float = 50.0,
+                 mz_max: float = 2000.0):
         """MzSpectrum class.
 
         Args:
             mz (NDArray[np.float64]): m/z.
             values (NDArray[np.float64]): Intensity.
+            resolution (int): Decimal places of the bins, used if no binning is given.
+            binning (str, optional): "decimal", "da" or "ppm", see MzSpectrum.vectorize. Defaults to None.
+            width (float, optional): Width of the binning, defaults to the resolution.
+            mz_min (float, optional): Lower bound of the m/z range of ppm bins. Defaults to 50.0.
+            mz_max (float, optional): Upper bound of the m/z range of ppm bins. Defaults to 2000.0.
 
         Raises:
             AssertionError: If the length of the mz and intensity arrays are not equal.
+            ValueError: If the binning is unknown or its parameters are invalid.
         """
         assert len(indices) == len(values), "The length of the mz and intensity arrays must be equal."
-        self.__spec_ptr = ims.PyMzSpectrumVectorized(indices, values, resolution)
+        self.__spec_ptr = ims.PyMzSpectrumVectorized(indices, values, resolution, binning, width, mz_min, mz_max)
 
     @classmethod
     def from_py_ptr(cls, spec: ims.PyMzSpectrumVectorized):
@@ -370,6 +550,23 @@ class MzSpectrumVectorized(RustWrapperObject):
         """
         return self.__spec_ptr.resolution
 
+    @property
+    def binning(self) -> Tuple[str, float, Optional[float], Optional[float]]:
+        """Binning of the indices.
+
+        Returns:
+            Tuple[str, float, Optional[float], Optional[float]]: Name, width and m/z range of ppm bins.
+        """
+        return self.__spec_ptr.binning
+
+    def bin_centers(self) -> NDArray[np.float64]:
+        """m/z at the center of the bin of every index, the geometric center for ppm bins.
+
+        Returns:
+            NDArray[np.float64]: m/z of every index.
+        """
+        return self.__spec_ptr.bin_centers()
+
     @property
     def indices(self) -> NDArray[np.int32]:
         """m/z.
@@ -401,11 +598,32 @@ class MzSpectrumVectorized(RustWrapperObject):
         # then get the peak integrals
         integrals = integrate_method(peaks, peak_info)
         # then create a new spectrum with the peak indices and the integrals
-        return MzSpectrum.from_py_ptr(ims.PyMzSpectrum(dense_spectrum.indices[peaks] / np.power(10, dense_spectrum.resolution), integrals))
+        return MzSpectrum.from_py_ptr(ims.PyMzSpectrum(dense_spectrum.bin_centers()[peaks], integrals))
 
     def __repr__(self):
         return f"MzSpectrumVectorized(num_values={len(self.values)})"
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.get_py_ptr())
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, MzSpectrumVectorized):
+            return NotImplemented
+        return self.get_py_ptr() == other.get_py_ptr()
+
+    def approx_equal(self, other: 'MzSpectrumVectorized', tolerance: float = 1e-6) -> bool:
+        """Compare to another spectrum, floating point values may differ by at most tolerance.
+
+        Args:
+            other (MzSpectrumVectorized): Spectrum to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.get_py_ptr().approx_equal(other.get_py_ptr(), tolerance)
+
     def get_py_ptr(self) -> ims.PyMzSpectrumVectorized:
         """Get the spec_ptr.
 
@@ -550,6 +768,27 @@ class TimsSpectrum(RustWrapperObject):
                 f"scan={self.scan}, mobility={np.round(self.mobility, 2)}, ms_type={self.ms_type}, "
                 f"num_peaks={len(self.index)})")
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.get_py_ptr())
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsSpectrum):
+            return NotImplemented
+        return self.get_py_ptr() == other.get_py_ptr()
+
+    def approx_equal(self, other: 'TimsSpectrum', tolerance: float = 1e-6) -> bool:
+        """Compare to another spectrum, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsSpectrum): Spectrum to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.get_py_ptr().approx_equal(other.get_py_ptr(), tolerance)
+
     def filter(self, mz_min: float = 0.0, mz_max: float = 2000.0, intensity_min: float = 0.0,
                intensity_max: float = 1e9) -> 'TimsSpectrum':
         """Filter the spectrum for a given m/z range and intensity range.
@@ -584,3 +823,30 @@ class TimsSpectrum(RustWrapperObject):
             TimsSpectrum: Sum of spectra
         """
         return self.from_py_ptr(self.__spec_ptr + other.__spec_ptr)
+
+
+def entropy_similarity_pairs(spectra: List[MzSpectrum], pairs: NDArray[np.int64], resolution: int = 2,
+                             num_threads: int = 4) -> NDArray[np.float64]:
+    """Entropy similarity of many pairs of spectra in parallel, e.g. of observed and predicted spectra of PSMs.
+
+    Args:
+        spectra (List[MzSpectrum]): Spectra referenced by the pairs.
+        pairs (NDArray[np.int64]): Array of shape (n, 2) with the indices of the two spectra to compare.
+        resolution (int, optional): Decimal places of the m/z bins, see `MzSpectrum.entropy_similarity`. Defaults to 2.
+        num_threads (int, optional): Number of threads. Defaults to 4.
+
+    Returns:
+        NDArray[np.float64]: Similarity of every pair.
+
+    Raises:
+        ValueError: If pairs is not of shape (n, 2) or references a spectrum outside of spectra.
+    """
+    pairs = np.asarray(pairs, dtype=np.int64)
+    if pairs.size == 0:
+        pairs = pairs.reshape(0, 2)
+    if pairs.ndim != 2 or pairs.shape[1] != 2:
+        raise ValueError(f"pairs must be of shape (n, 2), got {pairs.shape}")
+    if (pairs < 0).any():
+        raise ValueError("pair indices must not be negative")
+    return ims.entropy_similarity_pairs([spectrum.get_py_ptr() for spectrum in spectra],
+                                        pairs[:, 0].tolist(), pairs[:, 1].tolist(), resolution, num_threads)
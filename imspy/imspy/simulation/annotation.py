@@ -48,8 +48,9 @@ class SourceType(RustWrapperObject):
 
 # charge_state: i32, peptide_id: i32, isotope_peak: i32
 class SignalAttributes(RustWrapperObject):
-    def __init__(self, charge_state: int, peptide_id: int, isotope_peak: int, description: Union[None, str] = None):
-        self.__py_ptr = ims.PySignalAttributes(charge_state, peptide_id, isotope_peak, description)
+    def __init__(self, charge_state: int, peptide_id: int, isotope_peak: int, description: Union[None, str] = None,
+                 ion_id: Union[None, int] = None):
+        self.__py_ptr = ims.PySignalAttributes(charge_state, peptide_id, isotope_peak, description, ion_id)
 
     @property
     def charge_state(self):
@@ -59,6 +60,10 @@ class SignalAttributes(RustWrapperObject):
     def peptide_id(self):
         return self.__py_ptr.peptide_id
 
+    @property
+    def ion_id(self) -> int:
+        return self.__py_ptr.ion_id
+
     @property
     def isotope_peak(self):
         return self.__py_ptr.isotope_peak
@@ -69,7 +74,7 @@ class SignalAttributes(RustWrapperObject):
 
     def __repr__(self):
         return (f"SignalAnnotation(charge_state={self.charge_state}, peptide_id={self.peptide_id}, "
-                f"isotope_peak={self.isotope_peak}, description={self.description})")
+                f"ion_id={self.ion_id}, isotope_peak={self.isotope_peak}, description={self.description})")
 
     @classmethod
     def from_py_ptr(cls, signal_attributes: ims.PySignalAttributes) -> 'SignalAttributes':
@@ -169,6 +174,27 @@ class MzSpectrumAnnotated(RustWrapperObject):
     def __repr__(self) -> str:
         return f"MzSpectrumAnnotated(mz={self.mz}, intensity={self.intensity}, annotations={self.annotations})"
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__py_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, MzSpectrumAnnotated):
+            return NotImplemented
+        return self.__py_ptr == other.__py_ptr
+
+    def approx_equal(self, other: 'MzSpectrumAnnotated', tolerance: float = 1e-6) -> bool:
+        """Compare to another spectrum, floating point values may differ by at most tolerance.
+
+        Args:
+            other (MzSpectrumAnnotated): Spectrum to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__py_ptr.approx_equal(other.__py_ptr, tolerance)
+
     @classmethod
     def from_py_ptr(cls, mz_spectrum_annotated: ims.PyMzSpectrumAnnotated) -> 'MzSpectrumAnnotated':
         instance = cls.__new__(cls)
@@ -273,6 +299,29 @@ class TimsFrameAnnotated(RustWrapperObject):
             'isotope_peak': self.isotope_peaks_first_only
         })
 
+    def annotation_columns(self) -> dict:
+        """Get all peak contributions in columnar form.
+
+        The contributions of peak ``i`` are stored at ``offsets[i]:offsets[i + 1]`` of every other column,
+        noise contributions have ids of -1 and an empty fragment kind, precursor signal has kind ``precursor``.
+
+        Returns:
+            dict: offsets, intensity_contribution, source_type, peptide_id, ion_id, charge_state,
+                isotope_peak, fragment_kind and fragment_ordinal
+        """
+        return self.__py_ptr.annotation_columns()
+
+    def annotation_df(self) -> pd.DataFrame:
+        """Get all peak contributions as a long table, one row per contribution.
+
+        Returns:
+            pd.DataFrame: contributions with the index of the peak they belong to
+        """
+        columns = self.annotation_columns()
+        offsets = columns.pop('offsets')
+        peak_index = np.repeat(np.arange(len(offsets) - 1), np.diff(offsets))
+        return pd.DataFrame({'peak_index': peak_index, **columns})
+
     def filter(self,
                mz_min: float = 0.0,
                mz_max: float = 1700.0,
@@ -302,6 +351,27 @@ class TimsFrameAnnotated(RustWrapperObject):
                 f"ms_type={self.ms_type}, num_peaks={len(self.mz)}, "
                 f"sum_intensity={sum(np.round(self.intensity))})")
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__py_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsFrameAnnotated):
+            return NotImplemented
+        return self.__py_ptr == other.__py_ptr
+
+    def approx_equal(self, other: 'TimsFrameAnnotated', tolerance: float = 1e-6) -> bool:
+        """Compare to another frame, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsFrameAnnotated): Frame to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__py_ptr.approx_equal(other.__py_ptr, tolerance)
+
     @classmethod
     def from_py_ptr(cls, tims_frame_annotated: ims.PyTimsFrameAnnotated) -> 'TimsFrameAnnotated':
         instance = cls.__new__(cls)
@@ -0,0 +1,103 @@
+from typing import Optional
+
+import numpy as np
+import pandas as pd
+from numpy.typing import NDArray
+
+import imspy_connector
+ims = imspy_connector.py_simulation
+
+
+class TofCalibration:
+    def __init__(self, intercept: float, slope: float, curvature: float = 0.0, tof_max_index: int = 400_000):
+        """Invertible tof calibration of simulated frames, sqrt(mz) = intercept + slope * tof + curvature * tof^2.
+
+        Args:
+            intercept (float): sqrt(mz) at tof index 0.
+            slope (float): Linear coefficient.
+            curvature (float): Quadratic coefficient.
+            tof_max_index (int): Number of tof indices.
+
+        Raises:
+            ValueError: If mz does not increase with the tof index over the whole tof range.
+        """
+        self.__py_ptr = ims.PyTofCalibration(intercept, slope, curvature, tof_max_index)
+
+    @classmethod
+    def from_boundaries(cls, mz_min: float = 100.0, mz_max: float = 1700.0,
+                        tof_max_index: int = 400_000) -> 'TofCalibration':
+        """Linear calibration spanning an mz range, the default of simulated frames.
+
+        Args:
+            mz_min (float): mz at tof index 0.
+            mz_max (float): mz at tof index tof_max_index.
+            tof_max_index (int): Number of tof indices.
+
+        Returns:
+            TofCalibration: The calibration.
+        """
+        return cls.from_py_ptr(ims.PyTofCalibration.from_boundaries(mz_min, mz_max, tof_max_index))
+
+    @classmethod
+    def from_dataset(cls, dataset, frame_id: int = 1, tof_max_index: Optional[int] = None) -> 'TofCalibration':
+        """Quadratic calibration fitted to the tof to mz conversion of a reference dataset.
+
+        Args:
+            dataset (TimsDataset): The reference dataset.
+            frame_id (int): Frame whose calibration is used.
+            tof_max_index (Optional[int]): Number of tof indices, read from the dataset if None.
+
+        Returns:
+            TofCalibration: The fitted calibration.
+        """
+        if tof_max_index is None:
+            tof_max_index = dataset.tof_max_index
+        return cls.from_py_ptr(ims.PyTofCalibration.from_dataset(dataset.get_dataset_ptr(), frame_id, tof_max_index))
+
+    @property
+    def intercept(self) -> float:
+        return self.__py_ptr.intercept
+
+    @property
+    def slope(self) -> float:
+        return self.__py_ptr.slope
+
+    @property
+    def curvature(self) -> float:
+        return self.__py_ptr.curvature
+
+    @property
+    def tof_max_index(self) -> int:
+        return self.__py_ptr.tof_max_index
+
+    def mz_to_tof(self, mz: NDArray) -> NDArray:
+        """Nearest tof indices of mz values, clamped to the tof range."""
+        return np.array(self.__py_ptr.mz_to_tof(np.asarray(mz, dtype=np.float64).tolist()), dtype=np.int32)
+
+    def tof_to_mz(self, tof: NDArray) -> NDArray:
+        """mz values of tof indices."""
+        return np.array(self.__py_ptr.tof_to_mz(np.asarray(tof, dtype=np.int32).tolist()), dtype=np.float64)
+
+    def curve(self, num_points: int = 1_000) -> pd.DataFrame:
+        """Calibration curve for plotting.
+
+        Args:
+            num_points (int): Number of evenly spaced tof indices.
+
+        Returns:
+            pd.DataFrame: Columns tof and mz.
+        """
+        tof = np.linspace(0, self.tof_max_index - 1, num_points).astype(np.int32)
+        return pd.DataFrame({'tof': tof, 'mz': self.tof_to_mz(tof)})
+
+    def __repr__(self):
+        return repr(self.__py_ptr)
+
+    @classmethod
+    def from_py_ptr(cls, py_ptr: ims.PyTofCalibration) -> 'TofCalibration':
+        instance = cls.__new__(cls)
+        instance.__py_ptr = py_ptr
+        return instance
+
+    def get_py_ptr(self) -> ims.PyTofCalibration:
+        return self.__py_ptr
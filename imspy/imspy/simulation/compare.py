@@ -0,0 +1,35 @@
+from typing import Tuple
+
+import pandas as pd
+
+from imspy.timstof.data import TimsDataset
+
+import imspy_connector
+ims = imspy_connector.py_dataset
+
+
+def compare_runs(real: TimsDataset, synthetic: TimsDataset, ms_ms_type: int = 0, rt_tolerance: float = 1.0,
+                 mz_resolution: int = 1, batch_size: int = 256, num_threads: int = 4) -> Tuple[pd.DataFrame, float]:
+    """Compare a simulated run with a real acquisition of the same sample, e.g. to plot calibration curves.
+
+    Every frame of the real run is matched to the synthetic frame closest in retention time, frame pairs are
+    loaded batch by batch and compared in parallel.
+
+    Args:
+        real (TimsDataset): The acquired run.
+        synthetic (TimsDataset): The simulated run.
+        ms_ms_type (int, optional): MsMsType of the compared frames, 0 compares precursor frames. Defaults to 0.
+        rt_tolerance (float, optional): Maximum retention time difference of matched frames in seconds. Defaults to 1.0.
+        mz_resolution (int, optional): Decimal places of the m/z bins of the spectral similarity. Defaults to 1.
+        batch_size (int, optional): Number of frame pairs loaded at once. Defaults to 256.
+        num_threads (int, optional): Number of threads. Defaults to 4.
+
+    Returns:
+        Tuple[pd.DataFrame, float]: One row per frame pair with frame ids, retention times and TICs of both runs,
+            the cosine similarity of the frame spectra and the correlation of their mobility profiles, which is NaN
+            for frames with the same intensity on all scans, and the correlation of the TICs over the run.
+    """
+    comparison = ims.compare_runs(real.get_dataset_ptr(), synthetic.get_dataset_ptr(), ms_ms_type, rt_tolerance,
+                                  mz_resolution, batch_size, num_threads)
+    tic_correlation = comparison.pop('tic_correlation')
+    return pd.DataFrame(comparison), tic_correlation
@@ -0,0 +1,83 @@
+from typing import List, Tuple
+
+import numpy as np
+import pandas as pd
+
+import imspy_connector
+ims = imspy_connector.py_simulation
+
+WINDOW_COLUMNS = ['window_group', 'scan_start', 'scan_end', 'isolation_mz', 'isolation_width', 'collision_energy']
+
+
+def generate_dia_scheme(mz_min: float = 400.0, mz_max: float = 1200.0, window_width: float = 25.0,
+                        overlap: float = 1.0, scan_count: int = 918, windows_per_frame: int = 2,
+                        mobility_mz_correlation: bool = True) -> Tuple[pd.DataFrame, pd.DataFrame]:
+    """Generate a diaPASEF window scheme.
+
+    The m/z range is tiled by windows sharing `overlap` Da, the windows are distributed over window groups in turn
+    and the windows of higher m/z are isolated at lower scans. With mobility_mz_correlation, the scan ranges follow
+    the m/z-mobility diagonal, otherwise all windows of a group get scan ranges of the same size.
+
+    Args:
+        mz_min (float): Lower bound of the first window.
+        mz_max (float): m/z covered by the last window.
+        window_width (float): Isolation width of the windows in Da.
+        overlap (float): m/z shared by consecutive windows in Da, smaller than window_width.
+        scan_count (int): Number of scans of a frame, scans are numbered from 0.
+        windows_per_frame (int): Number of windows isolated in one fragment frame.
+        mobility_mz_correlation (bool): Place the scan ranges along the m/z-mobility diagonal.
+
+    Returns:
+        Tuple[pd.DataFrame, pd.DataFrame]: The windows in the layout of the dia_ms_ms_windows table, and the
+            window groups of the fragment frames of one cycle (columns frame and window_group, frame 1 being the
+            precursor frame).
+
+    Raises:
+        ValueError: If the parameters do not describe a scheme.
+    """
+    columns, cycle = ims.generate_dia_scheme(mz_min, mz_max, window_width, overlap, scan_count, windows_per_frame,
+                                             mobility_mz_correlation)
+    windows = pd.DataFrame(dict(zip(WINDOW_COLUMNS, columns)))
+    cycle = pd.DataFrame({'frame': np.arange(2, len(cycle) + 2), 'window_group': cycle})
+    return windows, cycle
+
+
+def validate_dia_scheme(windows: pd.DataFrame) -> List[str]:
+    """Check a DIA window scheme for m/z gaps, windows of a group sharing scans and scans a group does not cover.
+
+    Args:
+        windows (pd.DataFrame): Windows in the layout of the dia_ms_ms_windows table.
+
+    Returns:
+        List[str]: Description of every issue found, empty for a valid scheme.
+    """
+    return ims.validate_dia_scheme(*[windows[name].to_numpy().tolist() for name in WINDOW_COLUMNS])
+
+
+def plot_dia_scheme(windows: pd.DataFrame, ax=None):
+    """Draw the windows of a DIA scheme as m/z x scan rectangles, colored by window group.
+
+    Args:
+        windows (pd.DataFrame): Windows in the layout of the dia_ms_ms_windows table.
+        ax (matplotlib.axes.Axes, optional): Axes to draw on, a new figure is created if None.
+
+    Returns:
+        matplotlib.axes.Axes: The axes with the scheme.
+    """
+    import matplotlib.pyplot as plt
+    from matplotlib.patches import Rectangle
+
+    if ax is None:
+        _, ax = plt.subplots(figsize=(8, 6))
+    groups = np.unique(windows.window_group)
+    colors = plt.cm.viridis(np.linspace(0.0, 1.0, max(len(groups), 1)))
+    for group, color in zip(groups, colors):
+        for row in windows[windows.window_group == group].itertuples():
+            ax.add_patch(Rectangle((row.isolation_mz - row.isolation_width / 2, row.scan_start), row.isolation_width,
+                                   row.scan_end - row.scan_start + 1, facecolor=color, edgecolor='black', alpha=0.5))
+    ax.set_xlim((windows.isolation_mz - windows.isolation_width / 2).min(),
+                (windows.isolation_mz + windows.isolation_width / 2).max())
+    ax.set_ylim(windows.scan_end.max() + 1, windows.scan_start.min())
+    ax.set_xlabel('m/z')
+    ax.set_ylabel('scan')
+    return ax
@@ -1,7 +1,7 @@
 import sqlite3
 import os
 from abc import ABC
-from typing import List, Tuple, Dict
+from typing import Callable, Iterator, List, Tuple, Dict, Optional
 
 import numpy as np
 from numpy.typing import NDArray
@@ -15,26 +15,56 @@ from imspy.timstof.frame import TimsFrame
 
 import imspy_connector
 
+from imspy.timstof.collision import TimsTofCollisionEnergyDIA
 from imspy.timstof.quadrupole import PasefMeta
+from imspy.simulation.scheduler import PasefScheduler
 
 ims = imspy_connector.py_simulation
 
 
 class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
-    def __init__(self, db_path: str, with_annotations: bool = False, num_threads: int = -1):
+    def __init__(self, db_path: str, with_annotations: bool = False, num_threads: int = -1,
+                 duplicate_strategy: str = 'error'):
         """Initializes the TimsTofSyntheticFrameBuilderDDA.
 
         Args:
             db_path (str): Path to the raw data file.
             with_annotations (bool): If true, frame annotations can be created during frame building, but this will slow down the process and needs a lot of extra memory, use with caution.
             num_threads (int): Number of threads.
+            duplicate_strategy (str): How duplicate peptides (same sequence and decoy flag) are handled, one of
+                'keep_first', 'sum_events' or 'error'.
         """
         self.path = db_path
 
         if num_threads == -1:
             num_threads = os.cpu_count()
 
-        self.__py_ptr = ims.PyTimsTofSyntheticsFrameBuilderDDA(db_path, with_annotations, num_threads)
+        self.__py_ptr = ims.PyTimsTofSyntheticsFrameBuilderDDA(db_path, with_annotations, num_threads,
+                                                                duplicate_strategy)
+
+    @staticmethod
+    def simulate_selection_scheme(db_path: str, scheduler: Optional[PasefScheduler] = None,
+                                  isolation_width: float = 3.0, collision_energy: float = 30.0,
+                                  duplicate_strategy: str = 'error') -> Tuple[int, int]:
+        """Simulate topN DDA-PASEF precursor selection and write it to the synthetics database.
+
+        Every simulated ion of a precursor frame is a selection candidate, the selection is done by the scheduler.
+        Sets the frame types (0 for precursor, 8 for fragment frames) and replaces the precursors and
+        pasef_meta tables, a builder created afterwards fragments the selected precursors.
+
+        Args:
+            db_path (str): Path to the synthetics database.
+            scheduler (PasefScheduler): TopN selection with dynamic exclusion, defaults to PasefScheduler().
+            isolation_width (float): Isolation width in m/z.
+            collision_energy (float): Collision energy.
+            duplicate_strategy (str): How duplicate peptides are handled, see __init__.
+
+        Returns:
+            Tuple[int, int]: Number of selected precursors and number of PASEF entries.
+        """
+        return ims.PyTimsTofSyntheticsFrameBuilderDDA.simulate_selection_scheme(
+            db_path, scheduler.get_py_ptr() if scheduler is not None else None, isolation_width, collision_energy,
+            duplicate_strategy)
 
     def get_pasef_meta(self) -> List[PasefMeta]:
         return [PasefMeta.from_py_ptr(meta) for meta in self.__py_ptr.get_pasef_meta()]
@@ -57,7 +87,13 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
                     precursor_noise_ppm: float = 5.,
                     mz_noise_fragment: bool = False,
                     fragment_noise_ppm: float = 5.,
-                    right_drag: bool = True) -> TimsFrame:
+                    right_drag: bool = True,
+                    mz_min: Optional[float] = None,
+                    mz_max: Optional[float] = None,
+                    intensity_min: Optional[float] = None,
+                    intensity_max: Optional[float] = None,
+                    seed: Optional[int] = None,
+                    quantization: str = "round") -> TimsFrame:
         """Build a frame.
 
         Args:
@@ -70,12 +106,20 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
             mz_noise_fragment (bool): if true, noise will be added to the fragment m/z values.
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
+            mz_min (Optional[float]): Minimum m/z of the frame, defaults to 100.0 for fragment frames.
+            mz_max (Optional[float]): Maximum m/z of the frame, defaults to 1700.0 for fragment frames.
+            intensity_min (Optional[float]): Minimum intensity of a peak, defaults to 1.0.
+            intensity_max (Optional[float]): Maximum intensity of a peak, defaults to 1e9.
+            seed (Optional[int]): If set, the m/z noise is deterministic and independent of the number of threads.
+            quantization (str): How intensities are turned into counts, one of 'round', 'floor', 'stochastic'
+            (rounds up with a probability equal to the fractional part) or 'none' (keeps float intensities).
 
         Returns:
             TimsFrame: Frame.
         """
         frame = self.__py_ptr.build_frame(frame_id, fragment, mz_noise_precursor, mz_noise_uniform,
-                                          precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag)
+                                          precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag,
+                                          mz_min, mz_max, intensity_min, intensity_max, seed, quantization)
 
         return TimsFrame.from_py_ptr(frame)
 
@@ -88,7 +132,13 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
                      mz_noise_fragment: bool = False,
                      fragment_noise_ppm: float = 5.,
                      right_drag: bool = True,
-                     num_threads: int = 4) -> List[TimsFrame]:
+                     num_threads: int = 4,
+                     mz_min: Optional[float] = None,
+                     mz_max: Optional[float] = None,
+                     intensity_min: Optional[float] = None,
+                     intensity_max: Optional[float] = None,
+                     seed: Optional[int] = None,
+                     quantization: str = "round") -> List[TimsFrame]:
         """Build frames.
 
         Args:
@@ -102,19 +152,30 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
             num_threads (int): Number of threads.
+            mz_min (Optional[float]): Minimum m/z of the frame, defaults to 100.0 for fragment frames.
+            mz_max (Optional[float]): Maximum m/z of the frame, defaults to 1700.0 for fragment frames.
+            intensity_min (Optional[float]): Minimum intensity of a peak, defaults to 1.0.
+            intensity_max (Optional[float]): Maximum intensity of a peak, defaults to 1e9.
+            seed (Optional[int]): If set, the m/z noise is deterministic and independent of the number of threads.
+            quantization (str): How intensities are turned into counts, one of 'round', 'floor', 'stochastic'
+            (rounds up with a probability equal to the fractional part) or 'none' (keeps float intensities).
 
         Returns:
             List[TimsFrame]: Frames.
         """
         frames = self.__py_ptr.build_frames(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform,
                                             precursor_noise_ppm,
-                                            mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads)
+                                            mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads,
+                                            mz_min, mz_max, intensity_min, intensity_max, seed, quantization)
         return [TimsFrame.from_py_ptr(frame) for frame in frames]
 
     def build_frame_annotated(self, frame_id: int, fragment: bool = True, mz_noise_precursor: bool = False,
                               mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5.,
                               mz_noise_fragment: bool = False,
-                              fragment_noise_ppm: float = 5., right_drag: bool = True) -> TimsFrameAnnotated:
+                              fragment_noise_ppm: float = 5., right_drag: bool = True,
+                              mz_min: Optional[float] = None, mz_max: Optional[float] = None,
+                              intensity_min: Optional[float] = None, intensity_max: Optional[float] = None,
+                              seed: Optional[int] = None, quantization: str = "round") -> TimsFrameAnnotated:
         """Build a frame. The frame will be annotated.
 
         Args:
@@ -127,17 +188,24 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
             mz_noise_fragment (bool): if true, noise will be added to the fragment m/z values.
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
+            mz_min, mz_max, intensity_min, intensity_max, seed, quantization: See build_frame.
 
         Returns:
             TimsFrameAnnotated: Frame.
         """
-        frame = self.__py_ptr.build_frame_annotated(frame_id, fragment, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag)
+        frame = self.__py_ptr.build_frame_annotated(frame_id, fragment, mz_noise_precursor, mz_noise_uniform,
+                                                    precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm,
+                                                    right_drag, mz_min, mz_max, intensity_min, intensity_max, seed,
+                                                    quantization)
         return TimsFrameAnnotated.from_py_ptr(frame)
 
     def build_frames_annotated(self, frame_ids: List[int], fragment: bool = True, mz_noise_precursor: bool = False,
                                mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5.,
                                mz_noise_fragment: bool = False, fragment_noise_ppm: float = 5.,
-                               right_drag: bool = True, num_threads: int = 4) -> List[TimsFrameAnnotated]:
+                               right_drag: bool = True, num_threads: int = 4,
+                               mz_min: Optional[float] = None, mz_max: Optional[float] = None,
+                               intensity_min: Optional[float] = None, intensity_max: Optional[float] = None,
+                               seed: Optional[int] = None, quantization: str = "round") -> List[TimsFrameAnnotated]:
         """Build frames. The frames will be annotated.
 
         Args:
@@ -151,11 +219,15 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
             num_threads (int): Number of threads.
+            mz_min, mz_max, intensity_min, intensity_max, seed, quantization: See build_frame.
 
         Returns:
             List[TimsFrameAnnotated]: Frames.
         """
-        frames = self.__py_ptr.build_frames_annotated(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads)
+        frames = self.__py_ptr.build_frames_annotated(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform,
+                                                      precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm,
+                                                      right_drag, num_threads, mz_min, mz_max, intensity_min,
+                                                      intensity_max, seed, quantization)
         return [TimsFrameAnnotated.from_py_ptr(frame) for frame in frames]
 
     def get_collision_energy(self, frame_id: int, scan_id: int) -> float:
@@ -164,6 +236,20 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
     def get_collision_energies(self, frame_ids: List[int], scan_ids: List[int]) -> List[float]:
         return self.__py_ptr.get_collision_energies(frame_ids, scan_ids)
 
+    def fragment_lookup_stats(self) -> Dict[str, int]:
+        """Get the number of fragment ion lookups by collision energy since construction or the last reset.
+
+        Returns:
+            Dict[str, int]: exact hits, interpolated lookups (including lookups outside the precomputed
+                collision energy range, which use the nearest one) and missing peptide ions.
+        """
+        exact, interpolated, missing = self.__py_ptr.fragment_lookup_stats()
+        return {'exact': exact, 'interpolated': interpolated, 'missing': missing}
+
+    def reset_fragment_lookup_stats(self) -> None:
+        """Reset the fragment ion lookup counts."""
+        self.__py_ptr.reset_fragment_lookup_stats()
+
     def __repr__(self):
         return f"TimsTofSyntheticFrameBuilderDDA(path={self.path})"
 
@@ -185,19 +271,65 @@ class TimsTofSyntheticFrameBuilderDDA(RustWrapperObject):
         return self.__py_ptr
 
 class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
-    def __init__(self, db_path: str, with_annotations: bool = False, num_threads: int = -1):
+    def __init__(self, db_path: str, with_annotations: bool = False, num_threads: int = -1,
+                 duplicate_strategy: str = 'error', legacy_transmission: bool = False,
+                 transmission_profile: Optional[str] = None, transmission_k: Optional[float] = None,
+                 add_detection_noise: bool = False, background_noise_density: float = 0.0, mode: str = 'full'):
         """Initializes the TimsTofSyntheticFrameBuilderDIA.
 
         Args:
             db_path (str): Path to the raw data file.
             with_annotations (bool): If true, frame annotations can be created during frame building, but this will slow down the process and needs a lot of extra memory, use with caution.
             num_threads (int): Number of threads.
+            duplicate_strategy (str): How duplicate peptides (same sequence and decoy flag) are handled, one of
+                'keep_first', 'sum_events' or 'error'.
+            legacy_transmission (bool): If true, a precursor is fully fragmented as soon as any isotope peak is
+                transmitted, otherwise fragment intensities are scaled by the transmitted fraction of its isotopic envelope.
+            transmission_profile (Optional[str]): Shape of the quadrupole isolation window, 'rectangular' or 'sigmoid',
+                defaults to 'sigmoid'.
+            transmission_k (Optional[float]): Steepness of the sigmoid window edges, defaults to 15.0.
+            add_detection_noise (bool): If true, peak intensities are sampled from a Poisson distribution with the
+                expected number of events as mean.
+            background_noise_density (float): Expected number of random chemical background peaks per frame,
+                0.0 disables background noise.
+            mode (str): Frames the builder is created for, 'full' for all frames, 'ms1' for precursor frames only,
+                which skips loading the fragment ions, or 'ms2' for fragment frames only. build_frames skips
+                frames of the other MS level in 'ms1' and 'ms2' mode.
         """
         self.path = db_path
 
         if num_threads == -1:
             num_threads = os.cpu_count()
-        self.__py_ptr = ims.PyTimsTofSyntheticsFrameBuilderDIA(db_path, with_annotations, num_threads)
+        self.__py_ptr = ims.PyTimsTofSyntheticsFrameBuilderDIA(db_path, with_annotations, num_threads,
+                                                                duplicate_strategy, legacy_transmission,
+                                                                transmission_profile, transmission_k,
+                                                                add_detection_noise, background_noise_density, mode)
+
+    @property
+    def mode(self) -> str:
+        """Frames the builder was created for, 'full', 'ms1' or 'ms2'."""
+        return self.__py_ptr.mode
+
+    def set_fragment_intensity_model(
+            self,
+            model: Optional[Callable[[str, int, float], NDArray]] = None,
+    ) -> None:
+        """Set the model that predicts fragment intensities of ions without precomputed intensities, e.g. if the
+        database has no fragment_ions table. Without a model, a mobile proton heuristic is used.
+
+        Args:
+            model (Optional[Callable[[str, int, float], NDArray]]): Called with sequence, precursor charge and
+                collision energy in eV, returns a flat Prosit intensity array of 174 values. None restores the
+                mobile proton heuristic.
+        """
+        if model is None:
+            self.__py_ptr.set_fragment_intensity_model(None)
+            return
+
+        def predict_flat(sequence: str, charge: int, collision_energy: float) -> List[float]:
+            return np.asarray(model(sequence, charge, collision_energy), dtype=np.float64).tolist()
+
+        self.__py_ptr.set_fragment_intensity_model(predict_flat)
 
     def build_frame(self,
                     frame_id: int,
@@ -207,7 +339,13 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
                     precursor_noise_ppm: float = 5.,
                     mz_noise_fragment: bool = False,
                     fragment_noise_ppm: float = 5.,
-                    right_drag: bool = True) -> TimsFrame:
+                    right_drag: bool = True,
+                    mz_min: Optional[float] = None,
+                    mz_max: Optional[float] = None,
+                    intensity_min: Optional[float] = None,
+                    intensity_max: Optional[float] = None,
+                    seed: Optional[int] = None,
+                    quantization: str = "round") -> TimsFrame:
         """Build a frame.
 
         Args:
@@ -220,12 +358,24 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
             mz_noise_fragment (bool): if true, noise will be added to the fragment m/z values.
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
+            mz_min (Optional[float]): Minimum m/z of the frame, defaults to 100.0 for fragment frames.
+            mz_max (Optional[float]): Maximum m/z of the frame, defaults to 1700.0 for fragment frames.
+            intensity_min (Optional[float]): Minimum intensity of a peak, defaults to 1.0.
+            intensity_max (Optional[float]): Maximum intensity of a peak, defaults to 1e9.
+            seed (Optional[int]): If set, the m/z noise is deterministic and independent of the number of threads.
+            quantization (str): How intensities are turned into counts, one of 'round', 'floor', 'stochastic'
+            (rounds up with a probability equal to the fractional part) or 'none' (keeps float intensities).
 
         Returns:
             TimsFrame: Frame.
+
+        Raises:
+            RuntimeError: If the synthetics database has no retention time for a frame or no mobility for a scan,
+            args[1] lists (frame_id, missing, scan) for every frame that could not be built.
         """
         frame = self.__py_ptr.build_frame(frame_id, fragment, mz_noise_precursor, mz_noise_uniform,
-                                          precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag)
+                                          precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag,
+                                          mz_min, mz_max, intensity_min, intensity_max, seed, quantization)
 
         return TimsFrame.from_py_ptr(frame)
 
@@ -238,7 +388,13 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
                      mz_noise_fragment: bool = False,
                      fragment_noise_ppm: float = 5.,
                      right_drag: bool = True,
-                     num_threads: int = 4) -> List[TimsFrame]:
+                     num_threads: int = 4,
+                     mz_min: Optional[float] = None,
+                     mz_max: Optional[float] = None,
+                     intensity_min: Optional[float] = None,
+                     intensity_max: Optional[float] = None,
+                     seed: Optional[int] = None,
+                     quantization: str = "round") -> List[TimsFrame]:
         """Build frames.
 
         Args:
@@ -252,19 +408,110 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
             num_threads (int): Number of threads.
+            mz_min (Optional[float]): Minimum m/z of the frame, defaults to 100.0 for fragment frames.
+            mz_max (Optional[float]): Maximum m/z of the frame, defaults to 1700.0 for fragment frames.
+            intensity_min (Optional[float]): Minimum intensity of a peak, defaults to 1.0.
+            intensity_max (Optional[float]): Maximum intensity of a peak, defaults to 1e9.
+            seed (Optional[int]): If set, the m/z noise is deterministic and independent of the number of threads.
+            quantization (str): How intensities are turned into counts, one of 'round', 'floor', 'stochastic'
+            (rounds up with a probability equal to the fractional part) or 'none' (keeps float intensities).
 
         Returns:
-            List[TimsFrame]: Frames.
+            List[TimsFrame]: Frames, without frames of the other MS level if the builder was created in 'ms1' or
+            'ms2' mode.
+
+        Raises:
+            RuntimeError: If the synthetics database has no retention time for a frame or no mobility for a scan,
+            args[1] lists (frame_id, missing, scan) for every frame that could not be built.
         """
         frames = self.__py_ptr.build_frames(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform,
                                             precursor_noise_ppm,
-                                            mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads)
+                                            mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads,
+                                            mz_min, mz_max, intensity_min, intensity_max, seed, quantization)
+        return [TimsFrame.from_py_ptr(frame) for frame in frames]
+
+    def frames_for_window_group(self, window_group: int) -> List[int]:
+        """Fragment frames acquired with a window group.
+
+        Args:
+            window_group (int): Window group.
+
+        Returns:
+            List[int]: Ascending frame IDs, empty for unknown window groups.
+        """
+        return self.__py_ptr.frames_for_window_group(window_group)
+
+    def build_window_group(self,
+                           window_group: int,
+                           fragment: bool = True,
+                           mz_noise_fragment: bool = False,
+                           mz_noise_uniform: bool = False,
+                           fragment_noise_ppm: float = 5.,
+                           right_drag: bool = True,
+                           num_threads: int = 4,
+                           mz_min: Optional[float] = None,
+                           mz_max: Optional[float] = None,
+                           intensity_min: Optional[float] = None,
+                           intensity_max: Optional[float] = None,
+                           seed: Optional[int] = None,
+                           quantization: str = "round") -> List[TimsFrame]:
+        """Build all fragment frames of a window group, see `build_frames` for the remaining arguments.
+
+        Args:
+            window_group (int): Window group.
+
+        Returns:
+            List[TimsFrame]: Frames sorted by frame ID, empty for unknown window groups.
+        """
+        frames = self.__py_ptr.build_window_group(window_group, fragment, mz_noise_fragment, mz_noise_uniform,
+                                                  fragment_noise_ppm, right_drag, num_threads,
+                                                  mz_min, mz_max, intensity_min, intensity_max, seed, quantization)
         return [TimsFrame.from_py_ptr(frame) for frame in frames]
 
+    def build_frames_batched(self,
+                             frame_ids: List[int],
+                             batch_size: int = 50,
+                             fragment: bool = True,
+                             mz_noise_precursor: bool = False,
+                             mz_noise_uniform: bool = False,
+                             precursor_noise_ppm: float = 5.,
+                             mz_noise_fragment: bool = False,
+                             fragment_noise_ppm: float = 5.,
+                             right_drag: bool = True,
+                             num_threads: int = 4,
+                             mz_min: Optional[float] = None,
+                             mz_max: Optional[float] = None,
+                             intensity_min: Optional[float] = None,
+                             intensity_max: Optional[float] = None,
+                             seed: Optional[int] = None,
+                             quantization: str = "round") -> Iterator[List[TimsFrame]]:
+        """Lazily build frames in batches, only one batch is held in memory at a time.
+
+        Args:
+            frame_ids (List[int]): Frame IDs, batches are yielded in ascending frame id order.
+            batch_size (int): Number of frames per batch.
+            num_threads (int): Number of threads used within a batch.
+
+            See build_frames for the remaining arguments.
+
+        Yields:
+            List[TimsFrame]: The next batch of frames, sorted by frame id.
+        """
+        assert batch_size > 0, "batch_size must be greater than 0"
+        frame_ids = sorted(frame_ids)
+        for i in range(0, len(frame_ids), batch_size):
+            yield self.build_frames(frame_ids[i:i + batch_size], fragment, mz_noise_precursor, mz_noise_uniform,
+                                    precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag,
+                                    num_threads, mz_min, mz_max, intensity_min, intensity_max, seed,
+                                    quantization)
+
     def build_frame_annotated(self, frame_id: int, fragment: bool = True, mz_noise_precursor: bool = False,
                               mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5.,
                               mz_noise_fragment: bool = False,
-                              fragment_noise_ppm: float = 5., right_drag: bool = True) -> TimsFrameAnnotated:
+                              fragment_noise_ppm: float = 5., right_drag: bool = True,
+                              mz_min: Optional[float] = None, mz_max: Optional[float] = None,
+                              intensity_min: Optional[float] = None, intensity_max: Optional[float] = None,
+                              seed: Optional[int] = None, quantization: str = "round") -> TimsFrameAnnotated:
         """Build a frame. The frame will be annotated.
 
         Args:
@@ -277,17 +524,28 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
             mz_noise_fragment (bool): if true, noise will be added to the fragment m/z values.
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
+            mz_min, mz_max, intensity_min, intensity_max, seed, quantization: See build_frame.
 
         Returns:
             TimsFrameAnnotated: Frame.
+
+        Raises:
+            RuntimeError: If a fragment frame is built with fragmentation by a builder created without annotations,
+            args[1] lists (frame_id, missing, scan) for every frame that could not be built.
         """
-        frame = self.__py_ptr.build_frame_annotated(frame_id, fragment, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag)
+        frame = self.__py_ptr.build_frame_annotated(frame_id, fragment, mz_noise_precursor, mz_noise_uniform,
+                                                    precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm,
+                                                    right_drag, mz_min, mz_max, intensity_min, intensity_max, seed,
+                                                    quantization)
         return TimsFrameAnnotated.from_py_ptr(frame)
 
     def build_frames_annotated(self, frame_ids: List[int], fragment: bool = True, mz_noise_precursor: bool = False,
                                mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5.,
                                mz_noise_fragment: bool = False, fragment_noise_ppm: float = 5.,
-                               right_drag: bool = True, num_threads: int = 4) -> List[TimsFrameAnnotated]:
+                               right_drag: bool = True, num_threads: int = 4,
+                               mz_min: Optional[float] = None, mz_max: Optional[float] = None,
+                               intensity_min: Optional[float] = None, intensity_max: Optional[float] = None,
+                               seed: Optional[int] = None, quantization: str = "round") -> List[TimsFrameAnnotated]:
         """Build frames. The frames will be annotated.
 
         Args:
@@ -301,19 +559,132 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
             fragment_noise_ppm (float): PPM of the fragment noise.
             right_drag (bool): if true, the noise will be shifted to the right.
             num_threads (int): Number of threads.
+            mz_min, mz_max, intensity_min, intensity_max, seed, quantization: See build_frame.
 
         Returns:
             List[TimsFrameAnnotated]: Frames.
+
+        Raises:
+            RuntimeError: If a fragment frame is built with fragmentation by a builder created without annotations,
+            args[1] lists (frame_id, missing, scan) for every frame that could not be built.
         """
-        frames = self.__py_ptr.build_frames_annotated(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads)
+        frames = self.__py_ptr.build_frames_annotated(frame_ids, fragment, mz_noise_precursor, mz_noise_uniform,
+                                                      precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm,
+                                                      right_drag, num_threads, mz_min, mz_max, intensity_min,
+                                                      intensity_max, seed, quantization)
         return [TimsFrameAnnotated.from_py_ptr(frame) for frame in frames]
 
+    def write_dataset(self,
+                      path: str,
+                      batch_size: int = 256,
+                      num_threads: int = 4,
+                      fragment: bool = True,
+                      mz_noise_precursor: bool = False,
+                      mz_noise_uniform: bool = False,
+                      precursor_noise_ppm: float = 5.,
+                      mz_noise_fragment: bool = False,
+                      fragment_noise_ppm: float = 5.,
+                      right_drag: bool = True,
+                      mz_min: Optional[float] = None,
+                      mz_max: Optional[float] = None,
+                      intensity_min: Optional[float] = None,
+                      intensity_max: Optional[float] = None,
+                      seed: Optional[int] = None,
+                      quantization: str = "round") -> int:
+        """Simulate all frames and write them to a Bruker .d folder (analysis.tdf and analysis.tdf_bin).
+
+        Args:
+            path (str): Path of the .d folder, existing analysis files are replaced.
+            batch_size (int): Number of frames built and written at once.
+            num_threads (int): Number of threads.
+            mz_min (float, optional): Minimum m/z of the frames and lower end of the acquisition range written to
+                the dataset, defaults to 100.0.
+            mz_max (float, optional): Maximum m/z of the frames and upper end of the acquisition range written to
+                the dataset, defaults to 1700.0.
+
+            See build_frames for the remaining arguments.
+
+        Returns:
+            int: Number of written frames.
+
+        Raises:
+            ValueError: If quantization is not one of 'round', 'floor', 'stochastic'.
+        """
+        return self.__py_ptr.write_dataset(path, batch_size, num_threads, fragment, mz_noise_precursor,
+                                           mz_noise_uniform, precursor_noise_ppm, mz_noise_fragment,
+                                           fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min,
+                                           intensity_max, seed, quantization)
+
     def get_collision_energy(self, frame_id: int, scan_id: int) -> float:
         return self.__py_ptr.get_collision_energy(frame_id, scan_id)
 
     def get_collision_energies(self, frame_ids: List[int], scan_ids: List[int]) -> List[float]:
         return self.__py_ptr.get_collision_energies(frame_ids, scan_ids)
 
+    def get_collision_energies_for_frame(self, frame_id: int, scan_ids: List[int]) -> List[float]:
+        """Get the collision energies of one frame at several scans.
+
+        Args:
+            frame_id (int): Fragment frame.
+            scan_ids (List[int]): Scans.
+
+        Returns:
+            List[float]: Collision energy per scan, 0.0 for scans outside the windows of the frame.
+        """
+        return self.__py_ptr.get_collision_energies_for_frame(frame_id, scan_ids)
+
+    def export_ce_table(self) -> pd.DataFrame:
+        """Get the collision energy of every scan covered by a window.
+
+        Returns:
+            pd.DataFrame: window_group, scan and collision_energy, ordered by window group and scan.
+        """
+        return pd.DataFrame(self.__py_ptr.export_ce_table(), columns=['window_group', 'scan', 'collision_energy'])
+
+    def set_collision_energy(self, collision_energy: TimsTofCollisionEnergyDIA) -> None:
+        """Replace the collision energies read from the database, e.g. by a per window ramp built with
+        `TimsTofCollisionEnergyDIA.with_ramp`.
+
+        Args:
+            collision_energy (TimsTofCollisionEnergyDIA): Collision energies used for fragmentation.
+        """
+        self.__py_ptr.set_collision_energy(collision_energy.get_py_ptr())
+
+    def set_collision_energy_ramp(self, inv_mobility: List[float], collision_energy: List[float]) -> None:
+        """Take the collision energy of each scan from its inverse mobility, interpolated linearly between
+        the breakpoints and held constant beyond the first and last one. Use
+        `TimsTofSyntheticsDataHandle.write_collision_energy_ramp` to store the ramp with the simulation.
+
+        Args:
+            inv_mobility (List[float]): 1/K0 of the breakpoints.
+            collision_energy (List[float]): Collision energy at each breakpoint.
+        """
+        self.__py_ptr.set_collision_energy_ramp(list(inv_mobility), list(collision_energy))
+
+    def get_collision_energy_ramp(self) -> Optional[List[Tuple[float, float]]]:
+        """Get the collision energy ramp used for fragmentation.
+
+        Returns:
+            Optional[List[Tuple[float, float]]]: (1/K0, collision energy) breakpoints ordered by 1/K0,
+                None if collision energies follow the window groups.
+        """
+        return self.__py_ptr.get_collision_energy_ramp()
+
+    def fragment_lookup_stats(self) -> Dict[str, int]:
+        """Get the number of fragment ion lookups by collision energy since construction or the last reset.
+
+        Returns:
+            Dict[str, int]: exact hits, interpolated lookups (including lookups outside the precomputed
+                collision energy range, which use the nearest one), missing peptide ions and predicted lookups,
+                the missing lookups that were answered by the fragment intensity model.
+        """
+        exact, interpolated, missing, predicted = self.__py_ptr.fragment_lookup_stats()
+        return {'exact': exact, 'interpolated': interpolated, 'missing': missing, 'predicted': predicted}
+
+    def reset_fragment_lookup_stats(self) -> None:
+        """Reset the fragment ion lookup counts."""
+        self.__py_ptr.reset_fragment_lookup_stats()
+
     def get_fragment_ions_map(self):
         ions_map = self.__py_ptr.get_fragment_ions_map()
         ret_map = {}
@@ -352,28 +723,79 @@ class TimsTofSyntheticFrameBuilderDIA(RustWrapperObject):
 
 
 class TimsTofSyntheticPrecursorFrameBuilder(RustWrapperObject):
-    def __init__(self, db_path: str):
-        self.__py_ptr = ims.PyTimsTofSyntheticsPrecursorFrameBuilder(db_path)
+    def __init__(self, db_path: str, duplicate_strategy: str = 'error', add_detection_noise: bool = False,
+                 background_noise_density: float = 0.0, num_threads: Optional[int] = None, lazy: bool = False,
+                 on_step: Optional[Callable[[str, float], None]] = None):
+        """Read a simulation database and build the lookup tables frames are built from.
+
+        Args:
+            db_path (str): Path to the simulation database.
+            duplicate_strategy (str, optional): How duplicate peptides are handled. Defaults to 'error'.
+            add_detection_noise (bool, optional): Add detection noise to built frames. Defaults to False.
+            background_noise_density (float, optional): Density of background noise peaks. Defaults to 0.0.
+            num_threads (int, optional): Threads used to build the lookup tables. Defaults to the size of the
+                global rayon thread pool.
+            lazy (bool, optional): Build the ion lookup on the first built frame instead of here. Defaults to False.
+            on_step (Callable[[str, float], None], optional): Called with the name and duration in seconds of every
+                construction step. Defaults to None.
+        """
+        self.__py_ptr = ims.PyTimsTofSyntheticsPrecursorFrameBuilder(db_path, duplicate_strategy, add_detection_noise,
+                                                                      background_noise_density, num_threads, lazy,
+                                                                      on_step)
 
-    def build_precursor_frame(self, frame_id: int, mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True) -> TimsFrame:
-        frame = self.__py_ptr.build_precursor_frame(frame_id, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag)
+    def build_precursor_frame(self, frame_id: int, mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True,
+                              seed: Optional[int] = None) -> TimsFrame:
+        frame = self.__py_ptr.build_precursor_frame(frame_id, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag, seed)
         return TimsFrame.from_py_ptr(frame)
 
     def build_precursor_frames(self, frame_ids: List[int], mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5.,
                                right_drag: bool = True,
-                               num_threads: int = 4):
+                               num_threads: int = 4,
+                               seed: Optional[int] = None):
         frames = self.__py_ptr.build_precursor_frames(frame_ids, mz_noise_precursor, mz_noise_uniform,
                                                       precursor_noise_ppm,
                                                       right_drag,
-                                                      num_threads)
+                                                      num_threads,
+                                                      seed)
         return [TimsFrame.from_py_ptr(frame) for frame in frames]
 
-    def build_precursor_frame_annotated(self, frame_id: int, mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True) -> TimsFrameAnnotated:
-        frame = self.__py_ptr.build_precursor_frame_annotated(frame_id, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag)
+    def build_precursor_frames_batched(self, frame_ids: List[int], batch_size: int = 50,
+                                       mz_noise_precursor: bool = False, mz_noise_uniform: bool = False,
+                                       precursor_noise_ppm: float = 5., right_drag: bool = True,
+                                       num_threads: int = 4, seed: Optional[int] = None) -> Iterator[List[TimsFrame]]:
+        """Lazily build precursor frames in batches, only one batch is held in memory at a time.
+
+        Batches are built in rust on request, the GIL is released while a batch is built.
+
+        Args:
+            frame_ids: Frame ids to build, batches are yielded in ascending frame id order
+            batch_size: Number of frames per batch
+            mz_noise_precursor: Whether to add m/z noise to the precursor peaks
+            mz_noise_uniform: Whether the m/z noise is uniform (otherwise normal)
+            precursor_noise_ppm: The m/z noise in ppm
+            right_drag: Whether the uniform noise is dragged to the right
+            num_threads: Number of threads used within a batch
+            seed: If set, the m/z noise is deterministic and independent of batch size and thread count
+
+        Yields:
+            List[TimsFrame]: The next batch of frames, sorted by frame id
+
+        Raises:
+            ValueError: If batch_size is not greater than 0
+        """
+        batches = self.__py_ptr.precursor_frame_batches(frame_ids, batch_size, mz_noise_precursor, mz_noise_uniform,
+                                                        precursor_noise_ppm, right_drag, num_threads, seed)
+        for batch in batches:
+            yield [TimsFrame.from_py_ptr(frame) for frame in batch]
+
+    def build_precursor_frame_annotated(self, frame_id: int, mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True,
+                                        seed: Optional[int] = None) -> TimsFrameAnnotated:
+        frame = self.__py_ptr.build_precursor_frame_annotated(frame_id, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag, seed)
         return TimsFrameAnnotated.from_py_ptr(frame)
 
-    def build_precursor_frames_annotated(self, frame_ids: List[int], mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True, num_threads: int = 4) -> List[TimsFrameAnnotated]:
-        frames = self.__py_ptr.build_precursor_frames_annotated(frame_ids, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag, num_threads)
+    def build_precursor_frames_annotated(self, frame_ids: List[int], mz_noise_precursor: bool = False, mz_noise_uniform: bool = False, precursor_noise_ppm: float = 5., right_drag: bool = True, num_threads: int = 4,
+                                         seed: Optional[int] = None) -> List[TimsFrameAnnotated]:
+        frames = self.__py_ptr.build_precursor_frames_annotated(frame_ids, mz_noise_precursor, mz_noise_uniform, precursor_noise_ppm, right_drag, num_threads, seed)
         return [TimsFrameAnnotated.from_py_ptr(frame) for frame in frames]
 
     def frame_to_abundances(self):
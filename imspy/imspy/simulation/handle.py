@@ -1,10 +1,53 @@
+import json
 import os
+import warnings
+from typing import Any, Dict, List, Optional, Tuple
+
+import numpy as np
 import pandas as pd
 
+from imspy.simulation.calibration import TofCalibration
+
 import imspy_connector
 ims = imspy_connector.py_simulation
 
 
+def migrate_to_binary(db_path: str, path_out: str) -> int:
+    """
+    Copy a synthetics database with the spectra, scan distributions and fragment intensities of the ions and
+    fragment_ions tables stored as binary instead of JSON, which makes the database smaller and faster to load.
+    The frame builders read both formats, other readers of these tables expect JSON.
+    Args:
+        db_path: Path of the JSON based database.
+        path_out: Path of the new database, must not exist.
+
+    Returns:
+        int: Number of converted rows.
+    """
+    return ims.migrate_synthetics_to_binary(db_path, path_out)
+
+
+def _column(table: pd.DataFrame, name: str) -> list:
+    """Values of a column as python list, the connector converts lists faster than numpy scalars."""
+    return table[name].to_numpy().tolist()
+
+
+def _list_column(table: pd.DataFrame, name: str) -> List[list]:
+    """Values of a list column, lists stored as JSON strings are parsed."""
+    return [json.loads(value) if isinstance(value, str) else np.asarray(value).tolist() for value in table[name]]
+
+
+def _chunks(table: pd.DataFrame, chunk_size: int):
+    """Consecutive row ranges of a table with at most chunk_size rows."""
+    for start in range(0, len(table), chunk_size):
+        yield table.iloc[start:start + chunk_size]
+
+
+def _optional_bool_column(table: pd.DataFrame, name: str) -> List[Optional[bool]]:
+    """Values of a boolean column that may be missing, missing values become None."""
+    return [None if pd.isna(value) else bool(value) for value in table[name]]
+
+
 class TimsTofSyntheticsDataHandleRust:
     def __init__(self, path: str):
         self.path = path
@@ -16,29 +59,414 @@ class TimsTofSyntheticsDataHandleRust:
     def __repr__(self):
         return f"TimsTofSyntheticsDataHandleRust(path={self.path})"
 
-    def get_transmitted_ions(self, num_threads: int=-1, dda: bool=False) -> pd.DataFrame:
+    def storage_format(self, table: str) -> str:
+        """
+        Get how the list and spectrum columns of a table are stored.
+        Args:
+            table: Name of the table, e.g. ions or fragment_ions.
+
+        Returns:
+            str: json or binary, see migrate_to_binary.
+        """
+        return self.__handle.storage_format(table)
+
+    def read_tof_calibration(self) -> TofCalibration:
+        """
+        Get the calibration the frame builders convert simulated m/z values to tof indices with.
+
+        Returns:
+            TofCalibration: The stored calibration, the default one spanning m/z 100 to 1700 if none is stored.
+        """
+        return TofCalibration.from_py_ptr(self.__handle.read_tof_calibration())
+
+    def write_tof_calibration(self, calibration: TofCalibration) -> None:
+        """
+        Store the tof calibration, frame builders created afterwards use it.
+        Args:
+            calibration: The calibration, e.g. fitted to a reference dataset with TofCalibration.from_dataset.
+        """
+        self.__handle.write_tof_calibration(calibration.get_py_ptr())
+
+    def read_collision_energy_ramp(self) -> Optional[List[Tuple[float, float]]]:
+        """
+        Get the collision energy ramp over the inverse mobility.
+
+        Returns:
+            Optional[List[Tuple[float, float]]]: (1/K0, collision energy) breakpoints ordered by 1/K0,
+                None if collision energies follow the window groups.
+        """
+        return self.__handle.read_collision_energy_ramp()
+
+    def write_collision_energy_ramp(self, inv_mobility: List[float], collision_energy: List[float]) -> None:
+        """
+        Store a collision energy ramp, DIA frame builders created afterwards interpolate the collision energy
+        of each scan from its inverse mobility instead of using the window group collision energies.
+        Args:
+            inv_mobility: 1/K0 of the breakpoints.
+            collision_energy: Collision energy at each breakpoint, held constant beyond the first and last one.
+        """
+        self.__handle.write_collision_energy_ramp(list(inv_mobility), list(collision_energy))
+
+    def clear_collision_energy_ramp(self) -> None:
+        """
+        Remove the collision energy ramp, DIA frame builders created afterwards use the window group
+        collision energies again.
+        """
+        self.__handle.clear_collision_energy_ramp()
+
+    def frames_for_window_group(self, window_group: int) -> List[int]:
+        """
+        Get the fragment frames acquired with a window group.
+        Args:
+            window_group: Window group.
+
+        Returns:
+            List[int]: Ascending frame ids, empty for unknown window groups.
+        """
+        return self.__handle.frames_for_window_group(window_group)
+
+    def window_group_for_frame(self, frame_id: int) -> Optional[int]:
+        """
+        Get the window group a frame was acquired with.
+        Args:
+            frame_id: Frame id.
+
+        Returns:
+            Optional[int]: Window group, None for precursor and unknown frames.
+        """
+        return self.__handle.window_group_for_frame(frame_id)
+
+    def export_spectral_library(self, path: str, format: str = 'diann', include_decoys: bool = False) -> int:
+        """
+        Write a tab separated spectral library with one row per fragment, e.g. to search the simulated run.
+        Fragment m/z are calculated from the sequences, intensities are averaged over the collision energies
+        of a precursor and given relative to its most intense fragment.
+        Args:
+            path: File to create, an existing file is replaced.
+            format: 'diann' for DIA-NN or 'openswath' for OpenSWATH column names.
+            include_decoys: Whether decoy peptides are written, marked in the Decoy column.
+
+        Returns:
+            int: Number of written precursors, precursors without fragment intensities are skipped.
+        """
+        return self.__handle.export_spectral_library(path, format, include_decoys)
+
+    def collapse_duplicate_peptides(self, strategy: str = 'sum_events') -> int:
+        """
+        Collapse peptides with the same sequence and decoy flag in the database. Rows of dropped duplicates are
+        deleted together with their ions and fragment ions in a single transaction.
+        Args:
+            strategy: 'keep_first' keeps the first row, 'sum_events' merges the events and frame abundances
+                of all duplicates into it, 'error' only checks that there are no duplicates.
+
+        Returns:
+            int: Number of deleted peptides.
+
+        Raises:
+            ValueError: If the strategy is unknown, or 'error' and a duplicate is found.
+        """
+        return self.__handle.collapse_duplicate_peptides(strategy)
+
+    def validate_schema(self) -> Dict[str, Any]:
+        """
+        Check that all tables the frame builders read exist and have the expected columns.
+        Columns are read by position, columns whose name differs between imspy versions are only checked to exist.
+
+        Returns:
+            Dict[str, Any]: Dictionary with the following keys:
+                - valid: True if nothing is missing.
+                - missing_tables: Names of missing tables, fragment_ions is only checked if present.
+                - column_mismatches: One dict per missing or renamed column with keys table, index,
+                  expected (None if any name is accepted) and found (None if the column is missing).
+        """
+        return self.__handle.validate_schema()
+
+    def get_transmitted_ions(
+            self,
+            num_threads: int = -1,
+            dda: bool = False,
+            ion_id_filter: Optional[List[int]] = None,
+            limit: Optional[int] = None,
+    ) -> pd.DataFrame:
         """
         Get transmitted ions, needed to create fragment ion table for simulation.
         Args:
             num_threads: Number of threads to use for the calculation.
             dda: if true, the ions are sampled from a DDA experiment, otherwise from a DIA experiment.
+            ion_id_filter: Optional list of ion ids, only these ions are considered.
+            limit: Optional maximum number of ions to consider, applied after the ion id filter.
 
         Returns:
-            pd.DataFrame: DataFrame with the following columns:
+            pd.DataFrame: DataFrame with one row per fragment frame, ion and collision energy, sorted by
+            frame_id, peptide_id and charge, with the following columns:
+                - frame_id: Fragment frame the ion is transmitted in.
                 - peptide_id: Peptide ID.
                 - ion_id: Ion ID.
                 - sequence: Peptide sequence.
                 - charge: Ion charge.
                 - collision_energy: Collision energy.
+                - scan_start: First scan the ion is transmitted in.
+                - scan_end: Last scan the ion is transmitted in.
+                - mobility: Inverse mobility of the ion.
+                - precursor_mz: Monoisotopic m/z of the ion.
+                - transmitted_fraction: Fraction of the isotope envelope intensity that is transmitted,
+                  averaged over the transmitted scans weighted by the scan abundance.
+        """
+        if num_threads == -1:
+            num_threads = os.cpu_count()
+
+        columns, num_skipped = self.__handle.get_transmitted_ions(
+            num_threads, dda, ion_id_filter, limit
+        )
+
+        if num_skipped > 0:
+            warnings.warn(f"Skipped {num_skipped} ions that reference a peptide id missing from the peptides table.")
+
+        return pd.DataFrame(columns)
+
+    def write_ion_mobilities(
+            self,
+            num_threads: int = -1,
+            coefficients: Optional[List[Tuple[float, float]]] = None,
+    ) -> None:
+        """
+        Predict the inverse mobility of every ion from its mass and charge and write it to the ions table.
+        Args:
+            num_threads: Number of threads to use for the calculation.
+            coefficients: Optional (a, b) per charge state starting at charge 1 of the power-law CCS model
+                ccs = a * mass ** b, None uses the default fit to tryptic peptides.
         """
         if num_threads == -1:
             num_threads = os.cpu_count()
 
-        peptide_ids, ion_ids, sequences, charges, collision_energies = self.__handle.get_transmitted_ions(num_threads, dda)
-        return pd.DataFrame({
-            'peptide_id': peptide_ids,
-            'ion_id': ion_ids,
-            'sequence': sequences,
-            'charge': charges,
-            'collision_energy': collision_energies
-        })
+        self.__handle.write_ion_mobilities(num_threads, coefficients)
+
+    def write_scan_distributions(
+            self,
+            im_sigma: float,
+            truncation_z: float = 3.0,
+            num_threads: int = -1,
+            im_sigma_high: Optional[float] = None,
+    ) -> int:
+        """
+        Simulate the scan distribution of every ion from its inverse mobility and write it to the ions table.
+        Args:
+            im_sigma: Width of the ion mobility peaks, towards lower inverse mobility if im_sigma_high is set.
+            truncation_z: Peaks are cut off at this many standard deviations around their apex.
+            num_threads: Number of threads to use for the calculation.
+            im_sigma_high: Optional width towards higher inverse mobility for asymmetric peaks.
+
+        Returns:
+            int: Number of ions outside of the scanned mobility range, these get no scans.
+        """
+        if num_threads == -1:
+            num_threads = os.cpu_count()
+
+        num_empty = self.__handle.write_scan_distributions(im_sigma, truncation_z, num_threads, im_sigma_high)
+
+        if num_empty > 0:
+            warnings.warn(f"{num_empty} ions lie outside of the scanned mobility range and got no scans.")
+
+        return num_empty
+
+    def update_scan_distributions(
+            self,
+            ion_ids: List[int],
+            occurrences: List[List[int]],
+            abundances: List[List[float]],
+    ) -> None:
+        """
+        Write precomputed scan occurrences and abundances of ions to the ions table in a single transaction.
+        Args:
+            ion_ids: Ids of the ions to update.
+            occurrences: Scans of every ion.
+            abundances: Abundance of every ion in each of its scans.
+        """
+        self.__handle.update_scan_distributions(ion_ids, occurrences, abundances)
+
+    def write_frame_distributions(
+            self,
+            peptide_ids: List[int],
+            occurrences: List[List[int]],
+            abundances: List[List[float]],
+    ) -> None:
+        """
+        Write precomputed frame occurrences and abundances of peptides to the peptides table in a single transaction,
+        the first and last frame of every peptide are set from its occurrences.
+        Args:
+            peptide_ids: Ids of the peptides to update.
+            occurrences: Frames of every peptide.
+            abundances: Abundance of every peptide in each of its frames.
+        """
+        self.__handle.write_frame_distributions(peptide_ids, occurrences, abundances)
+
+    def populate_elution_profiles(
+            self,
+            target_p: float = 0.999,
+            step_size: float = 0.001,
+            num_threads: int = -1,
+            sigma: Optional[float] = None,
+            lambda_: Optional[float] = None,
+    ) -> int:
+        """
+        Simulate the elution profile of every peptide as exponentially modified Gaussian over the frames of the run
+        and write its frame occurrences and abundances to the peptides table.
+        Args:
+            target_p: Probability mass of a peak that is covered by its frames.
+            step_size: Step size of the search for the peak bounds in seconds.
+            num_threads: Number of threads to use for the calculation.
+            sigma: Width of all elution peaks in seconds, None uses the rt_sigma column of the peptides table.
+            lambda_: Rate of the exponential tail of all elution peaks, None uses the rt_lambda column.
+
+        Returns:
+            int: Number of peptides eluting outside of the run, their abundance is 0 in all frames.
+        """
+        if num_threads == -1:
+            num_threads = os.cpu_count()
+
+        num_outside = self.__handle.populate_elution_profiles(target_p, step_size, num_threads, sigma, lambda_)
+
+        if num_outside > 0:
+            warnings.warn(f"{num_outside} peptides elute outside of the run.")
+
+        return num_outside
+
+    def create_schema(self) -> None:
+        """
+        Create the tables read by the frame builders with the column names and types written by imspy,
+        existing tables are kept.
+        """
+        self.__handle.create_schema()
+
+    def insert_frames(self, frames: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append frames to the frames table, every chunk is written in its own transaction.
+        Args:
+            frames: Table with columns frame_id, time and ms_type.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(frames, chunk_size):
+            self.__handle.insert_frames(
+                _column(chunk, 'frame_id'), _column(chunk, 'time'), _column(chunk, 'ms_type'),
+            )
+
+    def insert_scans(self, scans: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append scans to the scans table, every chunk is written in its own transaction.
+        Args:
+            scans: Table with columns scan and mobility.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(scans, chunk_size):
+            self.__handle.insert_scans(_column(chunk, 'scan'), _column(chunk, 'mobility'))
+
+    def insert_peptides(self, peptides: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append peptides to the peptides table, every chunk is written in its own transaction.
+        rt_sigma and rt_lambda are left empty.
+        Args:
+            peptides: Table with the columns of the peptides table as written by imspy, frame_occurrence and
+                frame_abundance may hold lists or JSON strings.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(peptides, chunk_size):
+            self.__handle.insert_peptides(
+                _column(chunk, 'protein_id'),
+                _column(chunk, 'peptide_id'),
+                _column(chunk, 'sequence'),
+                _column(chunk, 'protein'),
+                _column(chunk, 'decoy'),
+                _column(chunk, 'missed_cleavages'),
+                _optional_bool_column(chunk, 'n_term'),
+                _optional_bool_column(chunk, 'c_term'),
+                _column(chunk, 'monoisotopic-mass'),
+                _column(chunk, 'retention_time_gru_predictor'),
+                _column(chunk, 'events'),
+                _column(chunk, 'frame_occurrence_start'),
+                _column(chunk, 'frame_occurrence_end'),
+                _list_column(chunk, 'frame_occurrence'),
+                _list_column(chunk, 'frame_abundance'),
+            )
+
+    def insert_ions(self, ions: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append ions to the ions table, every chunk is written in its own transaction, the m/z is calculated
+        from sequence and charge.
+        Args:
+            ions: Table with the columns of the ions table as written by imspy, simulated_spectrum holds
+                MzSpectrum objects, scan_occurrence and scan_abundance may hold lists or JSON strings.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(ions, chunk_size):
+            spectra = chunk['simulated_spectrum'].tolist()
+            self.__handle.insert_ions(
+                _column(chunk, 'ion_id'),
+                _column(chunk, 'peptide_id'),
+                _column(chunk, 'sequence'),
+                _column(chunk, 'charge'),
+                _column(chunk, 'relative_abundance'),
+                _column(chunk, 'inv_mobility_gru_predictor'),
+                [np.asarray(spectrum.mz).tolist() for spectrum in spectra],
+                [np.asarray(spectrum.intensity).tolist() for spectrum in spectra],
+                _list_column(chunk, 'scan_occurrence'),
+                _list_column(chunk, 'scan_abundance'),
+            )
+
+    def insert_fragment_ions(self, fragment_ions: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append fragment intensities to the fragment_ions table, every chunk is written in its own transaction.
+        Args:
+            fragment_ions: Table with columns peptide_id, ion_id, collision_energy, charge, indices and values,
+                indices and values may hold lists or JSON strings.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(fragment_ions, chunk_size):
+            self.__handle.insert_fragment_ions(
+                _column(chunk, 'peptide_id'),
+                _column(chunk, 'ion_id'),
+                _column(chunk, 'collision_energy'),
+                _column(chunk, 'charge'),
+                _list_column(chunk, 'indices'),
+                _list_column(chunk, 'values'),
+            )
+
+    def insert_window_group_settings(self, settings: pd.DataFrame) -> None:
+        """
+        Append DIA window groups to the dia_ms_ms_windows table.
+        Args:
+            settings: Table with columns window_group, scan_start, scan_end, isolation_mz, isolation_width
+                and collision_energy.
+        """
+        self.__handle.insert_window_group_settings(
+            *[_column(settings, name) for name in
+              ['window_group', 'scan_start', 'scan_end', 'isolation_mz', 'isolation_width', 'collision_energy']]
+        )
+
+    def insert_dia_scheme(self, windows: pd.DataFrame, cycle: pd.DataFrame) -> None:
+        """
+        Replace the DIA window scheme, e.g. one from `imspy.simulation.dia_scheme.generate_dia_scheme`. The window
+        groups of one cycle are assigned in turn to all fragment frames of the frames table, ordered by frame id.
+        Args:
+            windows: Table with columns window_group, scan_start, scan_end, isolation_mz, isolation_width
+                and collision_energy.
+            cycle: Window groups of the fragment frames of one cycle in acquisition order, column window_group.
+
+        Raises:
+            ValueError: If the scheme has gaps, windows sharing scans or the cycle refers to unknown window groups.
+        """
+        self.__handle.insert_dia_scheme(
+            *[_column(windows, name) for name in
+              ['window_group', 'scan_start', 'scan_end', 'isolation_mz', 'isolation_width', 'collision_energy']],
+            _column(cycle, 'window_group'),
+        )
+
+    def insert_frame_to_window_group(self, frame_to_window_group: pd.DataFrame, chunk_size: int = 100_000) -> None:
+        """
+        Append the window groups of fragment frames to the dia_ms_ms_info table, every chunk is written in its
+        own transaction.
+        Args:
+            frame_to_window_group: Table with columns frame and window_group.
+            chunk_size: Number of rows written per transaction.
+        """
+        for chunk in _chunks(frame_to_window_group, chunk_size):
+            self.__handle.insert_frame_to_window_group(_column(chunk, 'frame'), _column(chunk, 'window_group'))
@@ -1,5 +1,5 @@
 from __future__ import annotations
-from typing import Optional, Tuple, List, Union
+from typing import Dict, Optional, Tuple, List, Union
 import numpy as np
 from numpy.typing import NDArray
 from numpy.typing import ArrayLike
@@ -221,14 +221,38 @@ class AveragineGenerator(IsotopePatternGenerator):
 
 
 def generate_isotope_pattern_rust(mass: float, charge: int, min_intensity: float = 150, k: int = 7, resolution: int = 3,
-                                  centroid: bool = True):
+                                  centroid: bool = True, atomic_composition: Dict[str, int] = None,
+                                  fine_structure: bool = False, fine_structure_sigma: float = 0.0):
+    """Generate an isotope pattern, either from averagine or from an explicit atomic composition.
+
+    Args:
+        mass: Monoisotopic mass
+        charge: Charge state
+        min_intensity: Minimum peak intensity
+        k: Number of isotopes
+        resolution: Resolution of the m/z axis
+        centroid: Whether to centroid the spectrum
+        atomic_composition: Optional atomic composition, e.g. {'C': 40, 'H': 65, 'S': 3, ...}, replaces averagine scaling
+        fine_structure: Keep isotopologues separated, only used together with an atomic composition
+        fine_structure_sigma: Width of every isotopologue peak in m/z if fine_structure is set, 0 returns them as
+            centroided peaks at their exact m/z
+
+    Returns:
+        MzSpectrum: The isotope pattern
+    """
+    if atomic_composition is not None:
+        atomic_composition = list(atomic_composition.items())
+
     return MzSpectrum.from_py_ptr(ims.generate_precursor_spectrum(
         mass=mass,
         charge=charge,
         min_intensity=min_intensity,
         k=k,
         resolution=resolution,
-        centroid=centroid
+        centroid=centroid,
+        atomic_composition=atomic_composition,
+        fine_structure=fine_structure,
+        fine_structure_sigma=fine_structure_sigma
     ))
 
 
@@ -243,3 +267,48 @@ def generate_isotope_patterns_rust(masses: NDArray, charges: NDArray, min_intens
         centroid=centroid,
         num_threads=num_threads,
     )]
+
+
+def generate_isotope_pattern_from_mz(mz: float, charge: int, min_intensity: float = 0.01, k: int = 7,
+                                     resolution: int = 3, centroid: bool = True,
+                                     relative_intensity: bool = True) -> Tuple[float, MzSpectrum]:
+    """Generate the averagine isotope pattern of a precursor observed at a given m/z and charge.
+
+    Args:
+        mz: Observed monoisotopic m/z
+        charge: Charge state
+        min_intensity: Minimum peak intensity, a fraction of the base peak if relative_intensity is set
+        k: Number of isotopes
+        resolution: Resolution of the m/z axis
+        centroid: Whether to centroid the spectrum
+        relative_intensity: Interpret min_intensity relative to the base peak instead of as absolute intensity
+
+    Returns:
+        Tuple[float, MzSpectrum]: The neutral monoisotopic mass and the isotope pattern on the m/z axis
+    """
+    mass, spectrum = ims.generate_precursor_spectrum_from_mz(mz, charge, min_intensity, k, resolution, centroid,
+                                                              relative_intensity=relative_intensity)
+    return mass, MzSpectrum.from_py_ptr(spectrum)
+
+
+def generate_isotope_patterns_from_mz(mzs: NDArray, charges: NDArray, min_intensity: float = 0.01, k: int = 7,
+                                      resolution: int = 3, centroid: bool = True, relative_intensity: bool = True,
+                                      num_threads: Optional[int] = None) -> Tuple[NDArray, List[MzSpectrum]]:
+    """Generate the averagine isotope patterns of precursors observed at given m/z values and charges in parallel.
+
+    Args:
+        mzs: Observed monoisotopic m/z values
+        charges: Charge states
+        min_intensity: Minimum peak intensity, a fraction of the base peak if relative_intensity is set
+        k: Number of isotopes
+        resolution: Resolution of the m/z axis
+        centroid: Whether to centroid the spectra
+        relative_intensity: Interpret min_intensity relative to the base peak instead of as absolute intensity
+        num_threads: Number of threads, None uses the shared thread pool
+
+    Returns:
+        Tuple[NDArray, List[MzSpectrum]]: The neutral monoisotopic masses and the isotope patterns
+    """
+    result = ims.generate_precursor_spectra_from_mz(list(mzs), list(charges), min_intensity, k, resolution, centroid,
+                                                    relative_intensity=relative_intensity, num_threads=num_threads)
+    return np.array([mass for mass, _ in result]), [MzSpectrum.from_py_ptr(spectrum) for _, spectrum in result]
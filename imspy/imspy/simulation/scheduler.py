@@ -0,0 +1,58 @@
+import numpy as np
+import pandas as pd
+
+import imspy_connector
+ims = imspy_connector.py_simulation
+
+
+class PasefScheduler:
+    def __init__(self, top_n: int = 10, max_precursors_per_frame: int = 12, ms2_frames_per_cycle: int = 4,
+                 intensity_threshold: float = 500.0, exclusion_duration: float = 24.0,
+                 exclusion_mz_ppm: float = 20.0, exclusion_mobility: float = 0.015):
+        """TopN DDA-PASEF precursor selection with dynamic exclusion, to experiment with acquisition strategies.
+
+        Frames are split into cycles of one precursor frame followed by `ms2_frames_per_cycle` fragment frames.
+        In every precursor frame, the most intense candidates are placed on the fragment frames of the cycle,
+        precursors sharing a fragment frame must not overlap in scans.
+
+        Args:
+            top_n (int): Maximum number of precursors selected per precursor frame.
+            max_precursors_per_frame (int): Maximum number of precursors isolated in one fragment frame.
+            ms2_frames_per_cycle (int): Number of PASEF fragment frames after every precursor frame.
+            intensity_threshold (float): Minimum intensity of a selected candidate.
+            exclusion_duration (float): Seconds an ion is excluded from selection after it was selected.
+            exclusion_mz_ppm (float): Candidates within this m/z tolerance in ppm of an excluded ion are not picked.
+            exclusion_mobility (float): Candidates within this 1/K0 tolerance of an excluded ion are not picked.
+        """
+        self.__py_ptr = ims.PyPasefScheduler(top_n, max_precursors_per_frame, ms2_frames_per_cycle,
+                                             intensity_threshold, exclusion_duration, exclusion_mz_ppm,
+                                             exclusion_mobility)
+
+    def schedule(self, frames: pd.DataFrame, candidates: pd.DataFrame) -> pd.DataFrame:
+        """Schedule the fragment frames of a run.
+
+        Args:
+            frames (pd.DataFrame): All frames of the run in acquisition order, with columns frame_id and
+                retention_time in seconds.
+            candidates (pd.DataFrame): Ions that can be selected, with columns frame_id, peptide_id, mz, mobility,
+                scan_start, scan_end (inclusive) and intensity. Only candidates of precursor frames are considered.
+
+        Returns:
+            pd.DataFrame: One row per selected precursor with the fragment frame_id, precursor_frame_id, peptide_id,
+                mz, mobility, scan_start, scan_end and intensity, ordered by fragment frame and scan.
+
+        Raises:
+            ValueError: If a candidate belongs to a frame missing in frames.
+        """
+        columns = self.__py_ptr.schedule(
+            frames.frame_id.astype(np.uint32).tolist(), frames.retention_time.astype(np.float64).tolist(),
+            candidates.frame_id.astype(np.uint32).tolist(), candidates.peptide_id.astype(np.uint32).tolist(),
+            candidates.mz.astype(np.float64).tolist(), candidates.mobility.astype(np.float64).tolist(),
+            candidates.scan_start.astype(np.uint32).tolist(), candidates.scan_end.astype(np.uint32).tolist(),
+            candidates.intensity.astype(np.float64).tolist())
+        names = ['frame_id', 'precursor_frame_id', 'peptide_id', 'mz', 'mobility', 'scan_start', 'scan_end',
+                 'intensity']
+        return pd.DataFrame(dict(zip(names, columns)))
+
+    def get_py_ptr(self):
+        return self.__py_ptr
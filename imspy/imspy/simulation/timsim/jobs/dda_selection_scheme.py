@@ -1,45 +1,49 @@
 from pathlib import Path
-from typing import Tuple
+from typing import Optional, Tuple
 
-import numpy as np
 import pandas as pd
 
 from imspy.simulation.acquisition import TimsTofAcquisitionBuilder
-from imspy.simulation.experiment import TimsTofSyntheticPrecursorFrameBuilder
+from imspy.simulation.experiment import TimsTofSyntheticFrameBuilderDDA
+from imspy.simulation.scheduler import PasefScheduler
 
 def simulate_dda_pasef_selection_scheme(
         acquisition_builder: TimsTofAcquisitionBuilder,
         verbose: bool,
+        scheduler: Optional[PasefScheduler] = None,
+        isolation_width: float = 3.0,
+        collision_energy: float = 30.0,
 ) -> Tuple[pd.DataFrame, pd.DataFrame]:
     """Simulate DDA selection scheme.
 
     Args:
         acquisition_builder: Acquisition builder object.
         verbose: Verbosity flag.
+        scheduler: TopN selection with dynamic exclusion, defaults to PasefScheduler().
+        isolation_width: Isolation width in m/z.
+        collision_energy: Collision energy.
 
     Returns:
         Tuple of two pandas DataFrames, one holding the DDA PASEF selection scheme and one holding selected precursor information.
     """
 
-    # set frame types to unknown
-    frame_types = np.array(
-        acquisition_builder.frame_table.frame_id.apply(lambda fid: -1).values
+    db_path = str(Path(acquisition_builder.path) / 'synthetic_data.db')
+
+    # sets the frame types and writes the precursors and pasef_meta tables to the blueprint
+    num_precursors, num_pasef_entries = TimsTofSyntheticFrameBuilderDDA.simulate_selection_scheme(
+        db_path,
+        scheduler=scheduler,
+        isolation_width=isolation_width,
+        collision_energy=collision_energy,
     )
 
-    # sets the frame types and saves the updated frame table to the blueprint
-    acquisition_builder.calculate_frame_types(frame_types=frame_types)
+    if verbose:
+        print(f"Selected {num_precursors} precursors in {num_pasef_entries} PASEF windows.")
 
-    precursor_frame_builder = TimsTofSyntheticPrecursorFrameBuilder(str(Path(acquisition_builder.path) / 'synthetic_data.db'))
+    # frame types were updated in the database
+    acquisition_builder.frame_table = acquisition_builder.synthetics_handle.get_table('frames')
 
-    # TODO: After the precursor table and pasef_meta table are created, the frame_types need to be set to 0 for MS1 frames and 8 for MS2 frames
-    """
-    # set frame types to unknown
-    frame_types = np.array(
-        acquisition_builder.frame_table.frame_id.apply(lambda fid: -1).values
-    )
-    
-    # sets the frame types and saves the updated frame table to the blueprint
-    acquisition_builder.calculate_frame_types(frame_types=frame_types)
-    """
+    pasef_meta = acquisition_builder.synthetics_handle.get_table('pasef_meta')
+    precursors = acquisition_builder.synthetics_handle.get_table('precursors')
 
-    raise NotImplementedError("Generation of DDA PASEF tables precursor and pasef_meta not yet implemented.")
+    return pasef_meta, precursors
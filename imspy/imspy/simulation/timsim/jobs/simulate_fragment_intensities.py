@@ -50,6 +50,11 @@ def simulate_fragment_intensities(
 
     transmitted_fragment_ions = native_handle.get_transmitted_ions(num_threads=num_threads, dda=dda)
 
+    # intensities only depend on the ion and collision energy, not on the frame it is transmitted in
+    transmitted_fragment_ions = transmitted_fragment_ions[
+        ['peptide_id', 'ion_id', 'sequence', 'charge', 'collision_energy']
+    ].drop_duplicates().reset_index(drop=True)
+
     IntensityPredictor = Prosit2023TimsTofWrapper()
 
     i_pred = IntensityPredictor.simulate_ion_intensities_pandas_batched(transmitted_fragment_ions,
@@ -1,3 +1,5 @@
+from typing import List, Optional, Tuple
+
 import numpy as np
 import pandas as pd
 from imspy.simulation.utility import python_list_to_json_string, add_uniform_noise
@@ -36,6 +38,9 @@ def simulate_frame_distributions_emg(
         from_existing: bool = False,
         sigmas: np.ndarray = None,
         lambdas: np.ndarray = None,
+        rt_jitter_std: float = 0.0,
+        gradient_warp: Optional[Tuple[List[float], List[float]]] = None,
+        rt_seed: Optional[int] = None,
 ) -> pd.DataFrame:
     """Simulate frame distributions for peptides.
 
@@ -52,11 +57,15 @@ def simulate_frame_distributions_emg(
         verbose: Verbosity.
         add_noise: Add noise.
         normalize: Normalize frame abundance.
-        n_steps: number of steps.
+        n_steps: ignored, the EMG probability mass is computed in closed form, kept for compatibility.
         num_threads: number of threads.
         from_existing: Use existing parameters.
         sigmas: sigmas.
         lambdas: lambdas.
+        rt_jitter_std: Standard deviation of the per peptide retention time jitter in seconds, 0 disables it.
+        gradient_warp: Control points of a monotone piecewise-linear warp of the retention time axis,
+            given as (original retention times, warped retention times), None disables it.
+        rt_seed: Seed of the retention time jitter, required if the jitter is enabled.
 
     Returns:
         pd.DataFrame: Peptide DataFrame with frame distributions.
@@ -77,6 +86,8 @@ def simulate_frame_distributions_emg(
     peptide_rt['rt_sigma'] = sigmas
     peptide_rt['rt_lambda'] = lambdas
 
+    warp_from, warp_to = gradient_warp if gradient_warp is not None else (None, None)
+
     if verbose:
         print("Calculating frame occurrences...")
 
@@ -89,6 +100,10 @@ def simulate_frame_distributions_emg(
         step_size,
         num_threads=num_threads,
         n_steps=n_steps,
+        rt_jitter_std=rt_jitter_std,
+        warp_from=warp_from,
+        warp_to=warp_to,
+        seed=rt_seed,
     )
 
     if verbose:
@@ -104,6 +119,10 @@ def simulate_frame_distributions_emg(
         rt_cycle_length,
         num_threads=num_threads,
         n_steps=n_steps,
+        rt_jitter_std=rt_jitter_std,
+        warp_from=warp_from,
+        warp_to=warp_to,
+        seed=rt_seed,
     )
 
     if verbose:
@@ -8,7 +8,7 @@ import toml
 import numpy as np
 import importlib.resources as resources
 
-from typing import List, Tuple, Any, Dict
+from typing import List, Tuple, Any, Dict, Optional
 from numpy.typing import NDArray
 
 from importlib.abc import Traversable
@@ -23,6 +23,8 @@ from imspy.chemistry.utility import calculate_mz
 
 import imspy_connector
 ims = imspy_connector.py_chemistry
+ims_utility = imspy_connector.py_utility
+ims_peptide = imspy_connector.py_peptide
 
 
 def get_fasta_file_paths(fasta_path) -> Dict[str, str]:
@@ -85,7 +87,8 @@ def sequences_to_all_ions(
         intensities_flat: List[List[float]],
         normalized: bool = True,
         half_charge_one: bool = True,
-        num_threads: int = 4) -> List[str]:
+        num_threads: int = 4,
+        fragment_types: Optional[List[str]] = None) -> List[str]:
     """
     Simulate ion intensities for a list of peptide sequences, charges, and collision energies.
     Args:
@@ -95,11 +98,14 @@ def sequences_to_all_ions(
         normalized: Whether to normalize the intensities
         half_charge_one: Whether to divide the intensity by 2 if the charge is 1
         num_threads: Number of threads to use for the calculation
+        fragment_types: Fragment types to include, e.g. ['b', 'y', 'c', 'z'] for EThcD, c/z ions reuse the
+            predicted b/y intensities. Defaults to b and y ions.
 
     Returns:
         NDArray: Array of ion intensities
     """
-    return ims.sequence_to_all_ions_par(sequences, charges, intensities_flat, normalized, half_charge_one, num_threads)
+    return ims.sequence_to_all_ions_par(sequences, charges, intensities_flat, normalized, half_charge_one, num_threads,
+                                        fragment_types=fragment_types)
 
 
 def sequence_to_all_ions(
@@ -107,7 +113,8 @@ def sequence_to_all_ions(
         charge: int,
         intensities_flat: List[float],
         normalized: bool = True,
-        half_charge_one: bool = True) -> List[str]:
+        half_charge_one: bool = True,
+        fragment_types: Optional[List[str]] = None) -> List[str]:
     """
     Simulate ion intensities for a peptide sequence, charge, and collision energy.
     Args:
@@ -116,11 +123,60 @@ def sequence_to_all_ions(
         intensities_flat: List of intensities
         normalized: Whether to normalize the intensities
         half_charge_one: Whether to divide the intensity by 2 if the charge is 1
+        fragment_types: Fragment types to include, e.g. ['b', 'y', 'c', 'z'] for EThcD, c/z ions reuse the
+            predicted b/y intensities. Defaults to b and y ions.
 
     Returns:
         NDArray: Array of ion intensities
     """
-    return ims.sequence_to_all_ions_ims(sequence, charge, intensities_flat, normalized, half_charge_one)
+    return ims.sequence_to_all_ions_ims(sequence, charge, intensities_flat, normalized, half_charge_one,
+                                        fragment_types=fragment_types)
+
+
+def simulate_peptide(
+        sequence: str,
+        charge: int,
+        collision_energy: float,
+        fragment_intensities: List[float] = None,
+        gradient_length: float = 60.0,
+        mass_tolerance: float = 1e-3,
+        abundance_threshold: float = 1e-8,
+        max_result: int = 200,
+        intensity_min: float = 1e-4) -> Dict[str, Any]:
+    """
+    Simulate a single peptide ion end-to-end: precursor isotope spectrum, fragment spectrum,
+    retention time, CCS and inverse mobility.
+    Args:
+        sequence: Peptide sequence, may contain modifications in the format [UNIMOD:ID]
+        charge: Precursor charge
+        collision_energy: Collision energy
+        fragment_intensities: Optional flat prosit intensity array, if None the mobile proton fragment model is used
+        gradient_length: Gradient length in minutes
+        mass_tolerance: Mass tolerance for the isotope calculation
+        abundance_threshold: Abundance threshold for the isotope calculation
+        max_result: Maximum number of isotope peaks
+        intensity_min: Minimum relative intensity of an isotope peak
+
+    Returns:
+        Dict[str, Any]: Precursor and fragment spectra, fragment ions, retention time, CCS and inverse mobility
+    """
+    from imspy.data.spectrum import MzSpectrum
+    from imspy.data.peptide import PeptideProductIonSeriesCollection
+
+    options = {
+        "fragment_intensities": fragment_intensities,
+        "gradient_length": gradient_length,
+        "mass_tolerance": mass_tolerance,
+        "abundance_threshold": abundance_threshold,
+        "max_result": max_result,
+        "intensity_min": intensity_min,
+    }
+
+    result = imspy_connector.py_simulation.simulate_peptide(sequence, charge, collision_energy, options)
+    result["precursor_spectrum"] = MzSpectrum.from_py_ptr(result["precursor_spectrum"])
+    result["fragment_spectrum"] = MzSpectrum.from_py_ptr(result["fragment_spectrum"])
+    result["fragment_ions"] = PeptideProductIonSeriesCollection.from_py_ptr(result["fragment_ions"])
+    return result
 
 
 def get_acquisition_builder_resource_path(acquisition_mode: str = 'dia') -> Traversable:
@@ -240,9 +296,16 @@ def calculate_b_y_ion_series_ims(sequence: str, modifications: NDArray, charge:
         charge: the charge state of the peptide precursor
 
     Returns:
-        b ion series, y ion series
+        b ion series, y ion series, the i-th y ion is complementary to the i-th b ion
     """
-    return ims.calculate_b_y_ion_series(sequence, modifications, charge)
+    n_ions, c_ions = ims_peptide.PyPeptideSequence(sequence).calculate_product_ion_series(charge, 'b')
+    # modification masses are given per position, b ions carry the prefix, y ions the suffix of them
+    modifications = np.asarray(modifications, dtype=np.float64)
+    b_shifts = np.cumsum(modifications)[:-1]
+    y_shifts = np.cumsum(modifications[::-1])[:-1]
+    b_ions = [ion.mz + shift / charge for ion, shift in zip(n_ions, b_shifts)]
+    y_ions = [ion.mz + shift / charge for ion, shift in zip(c_ions, y_shifts)]
+    return b_ions, y_ions[::-1]
 
 
 def get_native_dataset_path(ds_name: str = 'NATIVE.d') -> str:
@@ -292,6 +355,119 @@ def accumulated_intensity_cdf_numba(sample_start, sample_end, mean, std_dev):
     return cdf_end - cdf_start
 
 
+def accumulated_intensity_cdf_normal(sample_start: float, sample_end: float, mean: float, std_dev: float) -> float:
+    """Calculate the accumulated intensity of a normal distribution between two points, implemented in rust.
+
+    Args:
+        sample_start: lower bound of the interval
+        sample_end: upper bound of the interval
+        mean: mean of the normal distribution
+        std_dev: standard deviation of the normal distribution
+
+    Returns:
+        float: probability mass between sample_start and sample_end
+    """
+    return ims_utility.accumulated_intensity_cdf_normal(sample_start, sample_end, mean, std_dev)
+
+
+def emg_cdf_range(lower_limit: float, upper_limit: float, mu: float, sigma: float, lambda_: float,
+                  n_steps: Optional[int] = None) -> float:
+    """Calculate the accumulated intensity of an exponentially modified gaussian between two points.
+
+    Args:
+        lower_limit: lower bound of the interval
+        upper_limit: upper bound of the interval
+        mu: mean of the gaussian component
+        sigma: standard deviation of the gaussian component
+        lambda_: rate of the exponential component
+        n_steps: ignored, the EMG probability mass is computed in closed form, kept for compatibility
+
+    Returns:
+        float: probability mass between lower_limit and upper_limit
+    """
+    return ims_utility.emg_cdf_range(lower_limit, upper_limit, mu, sigma, lambda_, n_steps)
+
+
+def calculate_frame_occurrences_emg(
+        retention_times: NDArray,
+        rts: NDArray,
+        sigmas: NDArray,
+        lambdas: NDArray,
+        target_p: float,
+        step_size: float,
+        num_threads: int = -1,
+        n_steps: Optional[int] = None,
+) -> List[NDArray]:
+    """Calculate the frames covered by the elution profiles of many peptides, modeled as exponentially modified gaussians.
+
+    Args:
+        retention_times: retention time of every frame
+        rts: retention time (mu of the gaussian component) per peptide
+        sigmas: standard deviation of the gaussian component per peptide
+        lambdas: rate of the exponential component per peptide
+        target_p: probability mass of the elution profile to cover
+        step_size: step size used to widen the bounds of the elution profile
+        num_threads: number of threads, -1 uses all cores
+        n_steps: ignored, the EMG probability mass is computed in closed form, kept for compatibility
+
+    Returns:
+        List[NDArray]: frame ids (int32) per peptide
+
+    Raises:
+        ValueError: if rts, sigmas and lambdas differ in length or the bounds cannot be found
+    """
+    num_threads = os.cpu_count() if num_threads == -1 else num_threads
+    return ims_utility.calculate_frame_occurrences_emg(
+        np.asarray(retention_times, dtype=np.float64),
+        np.asarray(rts, dtype=np.float64),
+        np.asarray(sigmas, dtype=np.float64),
+        np.asarray(lambdas, dtype=np.float64),
+        target_p, step_size, num_threads, n_steps,
+    )
+
+
+def calculate_frame_abundances_emg(
+        frame_ids: NDArray,
+        retention_times: NDArray,
+        frame_occurrences: List[NDArray],
+        rts: NDArray,
+        sigmas: NDArray,
+        lambdas: NDArray,
+        rt_cycle_length: float,
+        num_threads: int = -1,
+        n_steps: Optional[int] = None,
+) -> List[NDArray]:
+    """Calculate the relative abundance of many peptides in the frames they occur in.
+
+    Args:
+        frame_ids: id of every frame
+        retention_times: retention time of every frame
+        frame_occurrences: frame ids per peptide, e.g. from calculate_frame_occurrences_emg
+        rts: retention time (mu of the gaussian component) per peptide
+        sigmas: standard deviation of the gaussian component per peptide
+        lambdas: rate of the exponential component per peptide
+        rt_cycle_length: length of one frame cycle in seconds
+        num_threads: number of threads, -1 uses all cores
+        n_steps: ignored, the EMG probability mass is computed in closed form, kept for compatibility
+
+    Returns:
+        List[NDArray]: abundance per frame occurrence per peptide
+
+    Raises:
+        ValueError: if the peptide or frame arrays differ in length
+    """
+    num_threads = os.cpu_count() if num_threads == -1 else num_threads
+    return ims_utility.calculate_frame_abundances_emg(
+        np.asarray(frame_ids, dtype=np.int32),
+        np.asarray(retention_times, dtype=np.float64),
+        [np.asarray(occurrence, dtype=np.int32) for occurrence in frame_occurrences],
+        np.asarray(rts, dtype=np.float64),
+        np.asarray(sigmas, dtype=np.float64),
+        np.asarray(lambdas, dtype=np.float64),
+        rt_cycle_length, num_threads, n_steps,
+    )
+
+
 @jit(nopython=True)
 def irt_to_rts_numba(irt: NDArray, new_min=0, new_max=120):
     """
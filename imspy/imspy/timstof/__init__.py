@@ -1,4 +1,5 @@
 from .data import TimsDataset
 from .dia import TimsDatasetDIA
 from .dda import TimsDatasetDDA, FragmentDDA
+from .prm import TimsDatasetPRM, TargetPRM
 from .dbsearch.utility import extract_timstof_dda_data
@@ -0,0 +1,96 @@
+import numpy as np
+from numpy.typing import NDArray
+
+from imspy.simulation.annotation import RustWrapperObject
+from imspy.timstof.data import TimsDataset
+
+import imspy_connector
+ims = imspy_connector.py_dataset
+
+
+class RtWarp(RustWrapperObject):
+    """Monotone piecewise-linear mapping of retention times from run a to run b.
+
+    Between knots retention times are interpolated linearly, outside of the knots they are shifted by the
+    offset of the closest knot.
+    """
+
+    def __init__(self, rt_a: NDArray, rt_b: NDArray):
+        """
+        Args:
+            rt_a: retention times of the knots in run a
+            rt_b: retention times of the knots in run b
+
+        Raises:
+            ValueError: if the knots are not strictly increasing in both runs
+        """
+        self.__ptr = ims.PyRtWarp(list(np.asarray(rt_a, dtype=np.float64)), list(np.asarray(rt_b, dtype=np.float64)))
+
+    @classmethod
+    def from_py_ptr(cls, py_ptr):
+        instance = cls.__new__(cls)
+        instance.__ptr = py_ptr
+        return instance
+
+    def get_py_ptr(self):
+        return self.__ptr
+
+    @property
+    def rt_a(self) -> NDArray:
+        return self.__ptr.rt_a
+
+    @property
+    def rt_b(self) -> NDArray:
+        return self.__ptr.rt_b
+
+    def map(self, rt: NDArray) -> NDArray:
+        """Map retention times of run a to run b."""
+        return self.__ptr.map(np.asarray(rt, dtype=np.float64))
+
+    def inverse(self, rt: NDArray) -> NDArray:
+        """Map retention times of run b to run a."""
+        return self.__ptr.inverse(np.asarray(rt, dtype=np.float64))
+
+    def __repr__(self):
+        return f"RtWarp(num_knots={len(self.rt_a)})"
+
+
+def align_retention_times(rt_a: NDArray, mz_a: NDArray, rt_b: NDArray, mz_b: NDArray, ppm: float = 10.0) -> RtWarp:
+    """Fit a retention time warp through anchor features of two runs.
+
+    Anchors are matched by m/z, anchors with more than one candidate within tolerance are skipped. The warp
+    goes through the medians of about sqrt(n) bins of matches and is kept monotone.
+
+    Args:
+        rt_a: retention times of the anchors of run a
+        mz_a: m/z values of the anchors of run a
+        rt_b: retention times of the anchors of run b
+        mz_b: m/z values of the anchors of run b
+        ppm: m/z tolerance of a match in ppm
+
+    Returns:
+        RtWarp: mapping from run a to run b
+    """
+    rt_a, mz_a, rt_b, mz_b = (list(np.asarray(values, dtype=np.float64)) for values in (rt_a, mz_a, rt_b, mz_b))
+    return RtWarp.from_py_ptr(ims.align_retention_times(rt_a, mz_a, rt_b, mz_b, ppm))
+
+
+def align_runs(a: TimsDataset, b: TimsDataset, rt_bin_width: float = 30.0, top_k: int = 50, ppm: float = 10.0,
+               num_threads: int = 4) -> RtWarp:
+    """Align the retention times of two runs, e.g. a simulated run and a real acquisition or two replicates.
+
+    The top_k most intense MS1 peaks of every retention time bin are used as anchors of both runs.
+
+    Args:
+        a: run mapped from
+        b: run mapped to
+        rt_bin_width: width of a retention time bin in seconds
+        top_k: number of anchors per bin
+        ppm: m/z tolerance of anchor extraction and matching in ppm
+        num_threads: number of threads used to load frames
+
+    Returns:
+        RtWarp: mapping from run a to run b
+    """
+    return RtWarp.from_py_ptr(ims.align_runs(a.get_dataset_ptr(), b.get_dataset_ptr(), rt_bin_width, top_k, ppm,
+                                             num_threads))
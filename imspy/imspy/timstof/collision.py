@@ -1,4 +1,7 @@
 from abc import abstractmethod
+
+import pandas as pd
+
 import imspy_connector
 ims = imspy_connector.py_quadrupole
 
@@ -30,3 +33,52 @@ class TimsTofCollisionEnergyDIA(TimsTofCollisionEnergy):
     def get_collision_energies(self, frame_ids: list[int], scan_ids: list[int]) -> list[float]:
         return self.__ptr.get_collision_energies(frame_ids, scan_ids)
 
+    def get_collision_energies_for_frame(self, frame_id: int, scan_ids: list[int]) -> list[float]:
+        """Collision energies of one frame at several scans
+
+        Args:
+            frame_id: fragment frame
+            scan_ids: scans
+
+        Returns:
+            list[float]: collision energy per scan, 0.0 for scans outside the windows of the frame
+        """
+        return self.__ptr.get_collision_energies_for_frame(frame_id, scan_ids)
+
+    def export_ce_table(self) -> pd.DataFrame:
+        """Collision energy of every scan covered by a window, e.g. to verify a ramp
+
+        Returns:
+            pd.DataFrame: window_group, scan and collision_energy, ordered by window group and scan
+        """
+        return pd.DataFrame(self.__ptr.export_ce_table(), columns=['window_group', 'scan', 'collision_energy'])
+
+    @classmethod
+    def with_ramp(cls, frame: list[int], frame_window_group: list[int], window_group: list[int],
+                  scan_start: list[int], scan_end: list[int], ce_start: list[float],
+                  ce_end: list[float]) -> 'TimsTofCollisionEnergyDIA':
+        """Collision energies ramped linearly from the first to the last scan of each window
+
+        Args:
+            frame: fragment frames
+            frame_window_group: window group of each frame
+            window_group: window group of each window
+            scan_start: first scan of each window
+            scan_end: last scan of each window, inclusive
+            ce_start: collision energy at the first scan of each window
+            ce_end: collision energy at the last scan of each window
+
+        Returns:
+            TimsTofCollisionEnergyDIA: ramped collision energies
+        """
+        return cls.from_py_ptr(ims.PyTimsTofCollisionEnergyDIA.with_ramp(frame, frame_window_group, window_group,
+                                                                         scan_start, scan_end, ce_start, ce_end))
+
+    @classmethod
+    def from_py_ptr(cls, ptr: ims.PyTimsTofCollisionEnergyDIA) -> 'TimsTofCollisionEnergyDIA':
+        instance = cls.__new__(cls)
+        instance.__ptr = ptr
+        return instance
+
+    def get_py_ptr(self) -> ims.PyTimsTofCollisionEnergyDIA:
+        return self.__ptr
@@ -4,7 +4,7 @@ import numpy as np
 import pandas as pd
 import sqlite3
 
-from typing import Dict, List
+from typing import Dict, Iterator, List, Optional, Tuple
 
 from numpy.typing import NDArray
 import opentims_bruker_bridge as obb
@@ -12,8 +12,9 @@ import opentims_bruker_bridge as obb
 from abc import ABC
 
 from imspy.simulation.annotation import RustWrapperObject
-from imspy.timstof.frame import TimsFrame
+from imspy.timstof.frame import TimsFrame, RawTimsFrame
 from imspy.timstof.slice import TimsSlice
+from imspy.data.spectrum import TimsSpectrum
 
 import imspy_connector
 ims = imspy_connector.py_dataset
@@ -232,6 +233,14 @@ class TimsDataset(ABC):
     def mz_upper(self):
         return float(self.global_meta_data["MzAcqRangeUpper"])
 
+    @property
+    def tof_max_index(self) -> int:
+        return int(self.global_meta_data["DigitizerNumSamples"]) + 1
+
+    def get_dataset_ptr(self):
+        """Get the connector dataset shared by all acquisition modes, e.g. to convert indices."""
+        return self.__dataset
+
     @property
     def average_cycle_length(self) -> float:
         return np.mean(np.diff(self.meta_data.Time.values))
@@ -251,17 +260,155 @@ class TimsDataset(ABC):
         """
         return TimsFrame.from_py_ptr(self.__dataset.get_frame(frame_id))
 
-    def get_tims_slice(self, frame_ids: NDArray[np.int32], num_threads: int = 8) -> TimsSlice:
+    def describe(self, frame_ids: NDArray[np.int32] = None, num_threads: int = 4) -> Dict:
+        """Summarize the dataset for quality control, frames are read chunk by chunk and a KeyboardInterrupt
+        is raised between chunks.
+
+        Args:
+            frame_ids (NDArray[np.int32], optional): Frames to summarize. Defaults to all frames.
+            num_threads (int, optional): Number of threads. Defaults to 4. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            Dict: Summary as returned by `TimsSlice.summary`.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        if frame_ids is not None:
+            frame_ids = [int(frame_id) for frame_id in frame_ids]
+
+        return self.__dataset.describe(frame_ids, num_threads)
+
+    def global_mz_axis(self, resolution: int = 2) -> Tuple[float, float, int]:
+        """Get a m/z axis covering the whole run, frames and slices vectorized on it share their columns,
+        see `TimsFrame.vectorize_on_axis`.
+
+        Args:
+            resolution (int, optional): Decimal places of the m/z bins. Defaults to 2.
+
+        Returns:
+            Tuple[float, float, int]: m/z of the first and last bin and the number of bins, taken from the
+                acquisition range of the global metadata, or from the m/z values of all frames if it is missing.
+        """
+        return self.__dataset.global_mz_axis(resolution)
+
+    def get_raw_frame(self, frame_id: int) -> RawTimsFrame:
+        """Get a frame as stored in the TDF, before any index conversion.
+
+        Args:
+            frame_id (int): Frame ID.
+
+        Returns:
+            RawTimsFrame: Number of peaks per scan, tof indices and intensities.
+        """
+        return RawTimsFrame.from_py_ptr(self.__dataset.get_raw_frame(frame_id))
+
+    def get_spectrum(self, frame_id: int, scan: int) -> Optional[TimsSpectrum]:
+        """Get the spectrum of a single scan without decoding the rest of the frame.
+
+        Args:
+            frame_id (int): Frame ID.
+            scan (int): Zero based scan.
+
+        Returns:
+            Optional[TimsSpectrum]: Spectrum, None if the scan has no peaks or the frame does not exist.
+        """
+        spectrum = self.__dataset.get_spectrum(frame_id, scan)
+        return None if spectrum is None else TimsSpectrum.from_py_ptr(spectrum)
+
+    def get_spectra(self, frame_id: int, scan_begin: int = 0, scan_end: Optional[int] = None,
+                    min_peaks: int = 1) -> List[TimsSpectrum]:
+        """Get the spectra of a range of scans, peaks of other scans are not decoded.
+
+        Args:
+            frame_id (int): Frame ID.
+            scan_begin (int, optional): First zero based scan. Defaults to 0.
+            scan_end (int, optional): Scan after the last scan, all remaining scans if None. Defaults to None.
+            min_peaks (int, optional): Minimum number of peaks of a returned spectrum. Defaults to 1.
+
+        Returns:
+            List[TimsSpectrum]: One spectrum per scan ordered by scan, empty if the frame does not exist.
+        """
+        return [TimsSpectrum.from_py_ptr(spectrum)
+                for spectrum in self.__dataset.get_spectra(frame_id, scan_begin, scan_end, min_peaks)]
+
+    def get_converter_ptr(self):
+        """Get the Rust dataset handle used for index conversion, e.g. by RawTimsFrame.to_tims_frame.
+
+        Returns:
+            ims.PyTimsDataset: Dataset handle.
+        """
+        return self.__dataset
+
+    def get_tims_slice(self, frame_ids: NDArray[np.int32], num_threads: int = 8,
+                       max_in_flight: Optional[int] = None) -> TimsSlice:
         """Get a TimsFrame.
 
         Args:
             frame_ids (int): Frame ID.
             num_threads (int): Number of threads.
+            max_in_flight (Optional[int]): Maximum number of decoded frames waiting to be collected, None decodes
+                all frames in parallel. This only bounds the decoding backlog, the slice is not memory-bounded:
+                it still holds every requested frame, use iter_tims_slices to process large runs in bounded memory.
 
         Returns:
             TimsFrame: TimsFrame.
+
+        Raises:
+            ValueError: If max_in_flight is 0.
+        """
+        return TimsSlice.from_py_tims_slice(self.__dataset.get_slice(frame_ids, num_threads, max_in_flight))
+
+    def iter_tims_slices(self, frame_ids: NDArray[np.int32], batch_size: int = 256, num_threads: int = 8,
+                         max_in_flight: Optional[int] = None) -> Iterator[TimsSlice]:
+        """Decode frames batch by batch, only the frames of the current batch are held in memory.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame IDs, batches keep their order.
+            batch_size (int): Number of frames per slice.
+            num_threads (int): Number of threads.
+            max_in_flight (Optional[int]): See get_tims_slice.
+
+        Yields:
+            TimsSlice: One slice per batch of frame IDs.
+
+        Raises:
+            ValueError: If batch_size is not positive or max_in_flight is 0.
+        """
+        if batch_size <= 0:
+            raise ValueError("batch_size must be positive")
+        frame_ids = list(frame_ids)
+        for start in range(0, len(frame_ids), batch_size):
+            yield self.get_tims_slice(frame_ids[start:start + batch_size], num_threads, max_in_flight)
+
+    def get_tims_frame_projected(self, frame_id: int, columns: List[str]) -> Dict[str, NDArray]:
+        """Get only the requested columns of a frame, index conversions for columns that are not requested are skipped.
+
+        Args:
+            frame_id (int): Frame ID.
+            columns (List[str]): Columns to decode, any of 'scan', 'mobility', 'tof', 'mz', 'intensity'.
+
+        Returns:
+            Dict[str, NDArray]: frame_id, retention_time and one numpy array per requested column.
+        """
+        return self.__dataset.get_frame_projected(frame_id, columns)
+
+    def get_tims_slice_projected(self, frame_ids: NDArray[np.int32], columns: List[str],
+                                 num_threads: int = 8) -> List[Dict[str, NDArray]]:
+        """Get only the requested columns of a collection of frames.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame IDs.
+            columns (List[str]): Columns to decode, any of 'scan', 'mobility', 'tof', 'mz', 'intensity'.
+            num_threads (int): Number of threads.
+
+        Returns:
+            List[Dict[str, NDArray]]: One dict per frame, see get_tims_frame_projected.
         """
-        return TimsSlice.from_py_tims_slice(self.__dataset.get_slice(frame_ids, num_threads))
+        return self.__dataset.get_slice_projected(frame_ids, columns, num_threads)
 
     def tof_to_mz(self, frame_id: int, tof_values: NDArray[np.int32]) -> NDArray[np.float64]:
         """Convert TOF values to m/z values.
@@ -311,6 +458,90 @@ class TimsDataset(ABC):
         """
         return self.__dataset.inverse_mobility_to_scan(frame_id, im_values)
 
+    def tof_to_mz_batched(self, frame_ids: NDArray[np.int32], tof_values: NDArray[np.int32],
+                          num_threads: int = 4) -> NDArray[np.float64]:
+        """Convert TOF values to m/z values for many frames, e.g. full columns of a feature table.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame ID of every value.
+            tof_values (NDArray[np.int32]): TOF values.
+            num_threads (int, optional): Number of threads. Defaults to 4. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            NDArray[np.float64]: m/z values, in the order of the input.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return self.__dataset.tof_to_mz_batched(np.asarray(frame_ids, dtype=np.uint32),
+                                                np.asarray(tof_values, dtype=np.uint32), num_threads)
+
+    def mz_to_tof_batched(self, frame_ids: NDArray[np.int32], mz_values: NDArray[np.float64],
+                          num_threads: int = 4) -> NDArray[np.int32]:
+        """Convert m/z values to TOF values for many frames, e.g. full columns of a feature table.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame ID of every value.
+            mz_values (NDArray[np.float64]): m/z values.
+            num_threads (int, optional): Number of threads. Defaults to 4. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            NDArray[np.int32]: TOF values, in the order of the input.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return self.__dataset.mz_to_tof_batched(np.asarray(frame_ids, dtype=np.uint32),
+                                                np.asarray(mz_values, dtype=np.float64), num_threads)
+
+    def scan_to_inverse_mobility_batched(self, frame_ids: NDArray[np.int32], scan_values: NDArray[np.int32],
+                                         num_threads: int = 4) -> NDArray[np.float64]:
+        """Convert scan values to inverse mobility values for many frames, e.g. full columns of a feature table.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame ID of every value.
+            scan_values (NDArray[np.int32]): Scan values.
+            num_threads (int, optional): Number of threads. Defaults to 4. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            NDArray[np.float64]: Inverse mobility values, in the order of the input.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return self.__dataset.scan_to_inverse_mobility_batched(np.asarray(frame_ids, dtype=np.uint32),
+                                                               np.asarray(scan_values, dtype=np.uint32), num_threads)
+
+    def inverse_mobility_to_scan_batched(self, frame_ids: NDArray[np.int32], im_values: NDArray[np.float64],
+                                         num_threads: int = 4) -> NDArray[np.int32]:
+        """Convert inverse mobility values to scan values for many frames, e.g. full columns of a feature table.
+
+        Args:
+            frame_ids (NDArray[np.int32]): Frame ID of every value.
+            im_values (NDArray[np.float64]): Inverse mobility values.
+            num_threads (int, optional): Number of threads. Defaults to 4. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            NDArray[np.int32]: Scan values, in the order of the input.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return self.__dataset.inverse_mobility_to_scan_batched(np.asarray(frame_ids, dtype=np.uint32),
+                                                               np.asarray(im_values, dtype=np.float64), num_threads)
+
     def compress_zstd(self, values: NDArray[np.uint8]) -> NDArray[np.uint8]:
         """Compress values using ZSTD.
 
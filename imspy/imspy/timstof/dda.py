@@ -1,8 +1,9 @@
 import sqlite3
-from typing import List, Optional
+from typing import Dict, List, Optional
 
 import numpy as np
 import pandas as pd
+from numpy.typing import NDArray
 
 from imspy.simulation.annotation import RustWrapperObject
 from imspy.timstof.data import TimsDataset
@@ -190,6 +191,46 @@ class TimsDatasetDDA(TimsDataset, RustWrapperObject):
         
         return pd.merge(time, B, left_on=['frame_id'], right_on=['frame_id'], how='inner')
 
+    def write_fragments_parquet(self, path: str, num_threads: int = 1, compression: str = 'zstd'):
+        """Write the peaks of all PASEF fragments to a parquet file, joined with the meta data of their precursor.
+
+        Args:
+            path (str): Parquet file to create.
+            num_threads (int, optional): Number of threads. Defaults to 1. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+            compression (str, optional): One of 'uncompressed', 'snappy' or 'zstd'. Defaults to 'zstd'.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        self.__dataset.write_fragments_parquet(path, num_threads, compression)
+
+    def write_mzml(self, path: str, merge_precursors: bool = True, num_threads: int = 1, compress: bool = True) -> int:
+        """Write precursor frames and PASEF fragment spectra to an indexed mzML file, ordered by frame id.
+
+        Precursor frames are written as MS1 spectra with an ion mobility array, fragment spectra as MS2 spectra
+        carrying isolation window, selected ion m/z, charge and collision energy of their precursor.
+
+        Args:
+            path (str): mzML file to create.
+            merge_precursors (bool, optional): Sum the fragments of all PASEF frames of a precursor into one spectrum
+                instead of writing one spectrum per frame. Defaults to True.
+            num_threads (int, optional): Number of threads. Defaults to 1. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+            compress (bool, optional): zlib compress the binary arrays. Defaults to True.
+
+        Returns:
+            int: Number of written spectra.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return self.__dataset.write_mzml(path, merge_precursors, num_threads, compress)
+
     def get_precursor_frames(self, min_intensity: float = 75, max_peaks: int = 500, num_threads: int = 4) -> List[TimsFrame]:
         """
         Get precursor frames.
@@ -268,6 +309,59 @@ class TimsDatasetDDA(TimsDataset, RustWrapperObject):
         """
         return [PrecursorDDA.from_py_ptr(precursor) for precursor in self.__dataset.get_selected_precursors()]
 
+    def compute_precursor_purity(self, ppm: float = 10.0, num_isotopes: int = 3,
+                                 num_threads: int = 4) -> pd.DataFrame:
+        """Compute the isolation purity of every selected precursor.
+
+        The purity is the fraction of the intensity within the isolation window (m/z and scan range of the
+        PASEF events) of the parent MS1 frame that belongs to the isotope envelope of the precursor.
+
+        Args:
+            ppm (float, optional): m/z tolerance of an isotope match. Defaults to 10.0.
+            num_isotopes (int, optional): Number of isotopes including the monoisotopic peak. Defaults to 3.
+            num_threads (int, optional): Number of threads. Defaults to 4.
+
+        Returns:
+            pd.DataFrame: precursor_id, purity, total_window_intensity and envelope_intensity, purity is NaN
+                for empty windows.
+
+        Raises:
+            ValueError: If a parameter is not positive.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return pd.DataFrame(self.__dataset.compute_precursor_purity(ppm, num_isotopes, num_threads))
+
+    def refine_precursors(self, ppm: float = 10.0, max_charge: int = 4, num_threads: int = 4) -> pd.DataFrame:
+        """Re-estimate the monoisotopic m/z and charge of every selected precursor from its isotope envelope.
+
+        Within the scans of the PASEF events of a precursor in its parent MS1 frame, envelopes of charge 1 to
+        max_charge whose monoisotopic peak lies 0 to 2 isotopes below the reported one are fitted against the
+        averagine model, which corrects monoisotopic peaks picked one isotope too high and missing charges.
+
+        Args:
+            ppm (float, optional): m/z tolerance of an isotope match. Defaults to 10.0.
+            max_charge (int, optional): Highest charge of a candidate envelope. Defaults to 4.
+            num_threads (int, optional): Number of threads. Defaults to 4.
+
+        Returns:
+            pd.DataFrame: precursor_id, original_mono_mz, original_charge, mono_mz, charge, score (chi square of the
+                fit, NaN without envelope), num_matched and status (confirmed, refined, ambiguous or no_envelope),
+                missing m/z are NaN and missing charges 0. Ambiguous and no_envelope keep the original values.
+
+        Raises:
+            ValueError: If a parameter is not positive.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return pd.DataFrame(self.__dataset.refine_precursors(ppm, max_charge, num_threads))
+
     def __repr__(self):
         return (f"TimsDatasetDDA(data_path={self.data_path}, num_frames={self.frame_count}, "
                 f"fragmented_precursors={self.fragmented_precursors.shape[0]})")
@@ -283,8 +377,11 @@ class TimsDatasetDDA(TimsDataset, RustWrapperObject):
 
 
 class FragmentDDA(RustWrapperObject):
-    def __init__(self, frame_id: int, precursor_id: int, collision_energy: float, selected_fragment: TimsFrame):
-        self._fragment_ptr = ims.PyTimsFragmentDDA(frame_id, precursor_id, collision_energy, selected_fragment.get_py_ptr())
+    def __init__(self, frame_id: int, precursor_id: int, collision_energy: float, selected_fragment: TimsFrame,
+                 isolation_mz: Optional[float] = None, isolation_width: Optional[float] = None,
+                 scan_begin: int = 0, scan_end: int = 0):
+        self._fragment_ptr = ims.PyTimsFragmentDDA(frame_id, precursor_id, collision_energy, selected_fragment.get_py_ptr(),
+                                                   isolation_mz, isolation_width, scan_begin, scan_end)
 
     @classmethod
     def from_py_ptr(cls, fragment: ims.PyTimsFragmentDDA):
@@ -304,14 +401,47 @@ class FragmentDDA(RustWrapperObject):
     def collision_energy(self) -> float:
         return self._fragment_ptr.collision_energy
 
+    @property
+    def isolation_mz(self) -> float:
+        """Isolation m/z of the PASEF event, NaN if unknown."""
+        return self._fragment_ptr.isolation_mz
+
+    @property
+    def isolation_width(self) -> float:
+        """Isolation width of the PASEF event, NaN if unknown."""
+        return self._fragment_ptr.isolation_width
+
+    @property
+    def scan_begin(self) -> int:
+        """First scan of the PASEF event."""
+        return self._fragment_ptr.scan_begin
+
+    @property
+    def scan_end(self) -> int:
+        """Last scan of the PASEF event."""
+        return self._fragment_ptr.scan_end
+
     @property
     def selected_fragment(self) -> TimsFrame:
         return TimsFrame.from_py_ptr(self._fragment_ptr.selected_fragment)
 
     def __repr__(self):
         return f"FragmentDDA(frame_id={self.frame_id}, precursor_id={self.precursor_id}, " \
-               f"collision_energy={self.collision_energy}, " \
+               f"collision_energy={self.collision_energy}, isolation_mz={self.isolation_mz}, " \
                f"selected_fragment={self.selected_fragment})"
 
     def get_py_ptr(self):
         return self._fragment_ptr
+
+
+def fragments_to_dict(fragments: List[FragmentDDA]) -> Dict[str, NDArray]:
+    """Collect the PASEF metadata of fragments as columns.
+
+    Args:
+        fragments (List[FragmentDDA]): Fragments, e.g. from TimsDatasetDDA.get_pasef_fragments.
+
+    Returns:
+        Dict[str, NDArray]: frame_id, precursor_id, scan_begin, scan_end, collision_energy, isolation_mz,
+            isolation_width and num_peaks with one entry per fragment.
+    """
+    return ims.fragments_to_dict([fragment.get_py_ptr() for fragment in fragments])
@@ -1,11 +1,13 @@
 import pandas as pd
 
-from typing import List, Tuple, Optional
+from typing import List, Tuple, Optional, Union
 from numpy.typing import NDArray
 
 from tensorflow import sparse as sp
 
 import numpy as np
+import pyarrow as pa
+from scipy.sparse import csr_matrix
 from imspy.data.spectrum import TimsSpectrum, IndexedMzSpectrum
 from imspy.simulation.annotation import TimsFrameAnnotated, RustWrapperObject
 from imspy.utility.utilities import re_index_indices
@@ -15,9 +17,18 @@ ims = imspy_connector.py_tims_frame
 
 
 class TimsFrame(RustWrapperObject):
+    """A frame of a timsTOF dataset, one entry per peak.
+
+    The arrays returned by ``scan``, ``mobility``, ``tof``, ``mz`` and ``intensity`` share memory with the frame
+    instead of copying it, as do the arrays of all other frame, slice and spectrum classes. They are read-only,
+    writing to them raises a ``ValueError``, use ``np.copy`` to get a writable array. Each array keeps the
+    underlying frame alive, so it stays valid after the frame itself goes out of scope. Setting ``tof`` replaces
+    the values in place, arrays returned before see the new values.
+    """
+
     def __init__(self, frame_id: int, ms_type: int, retention_time: float, scan: NDArray[np.int32],
                  mobility: NDArray[np.float64], tof: NDArray[np.int32],
-                 mz: NDArray[np.float64], intensity: NDArray[np.float64]):
+                 mz: NDArray[np.float64], intensity: NDArray[np.float64], validate: bool = True):
         """TimsFrame class.
 
         Args:
@@ -29,15 +40,35 @@ class TimsFrame(RustWrapperObject):
             tof (NDArray[np.int32]): Time of flight.
             mz (NDArray[np.float64]): m/z.
             intensity (NDArray[np.float64]): Intensity.
+            validate (bool): If true, check that scans are sorted, m/z values are sorted within each scan and
+                intensities are non-negative, see ``sort_by_scan_then_mz`` to repair the order.
 
         Raises:
             AssertionError: If the length of the scan, mobility, tof, mz and intensity arrays are not equal.
+            ValueError: If validate is true and the arrays do not describe a valid frame.
         """
 
         assert len(scan) == len(mobility) == len(tof) == len(mz) == len(intensity), \
             "The length of the scan, mobility, tof, mz and intensity arrays must be equal."
 
-        self.__frame_ptr = ims.PyTimsFrame(frame_id, ms_type, retention_time, scan, mobility, tof, mz, intensity)
+        self.__frame_ptr = ims.PyTimsFrame(frame_id, ms_type, retention_time, scan, mobility, tof, mz, intensity,
+                                           validate)
+
+    def validate(self) -> None:
+        """Check that scans are sorted, m/z values are sorted within each scan and intensities are non-negative.
+
+        Raises:
+            ValueError: If the frame is invalid, the message names the first offending peak.
+        """
+        self.__frame_ptr.validate()
+
+    def sort_by_scan_then_mz(self) -> 'TimsFrame':
+        """Sort all arrays together by scan and then m/z, e.g. to repair a frame assembled by hand.
+
+        Returns:
+            TimsFrame: Sorted copy of the frame.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.sort_by_scan_then_mz())
 
     def __add__(self, other: 'TimsFrame') -> 'TimsFrame':
         """Add two TimsFrames together.
@@ -129,6 +160,14 @@ class TimsFrame(RustWrapperObject):
 
     @tof.setter
     def tof(self, tof: NDArray[np.int32]):
+        """Replace the time of flight values in place.
+
+        Args:
+            tof (NDArray[np.int32]): Time of flight, one value per peak.
+
+        Raises:
+            ValueError: If the number of values does not match the number of peaks.
+        """
         self.__frame_ptr.tof = tof
 
     @property
@@ -206,6 +245,9 @@ class TimsFrame(RustWrapperObject):
     def to_resolution(self, resolution: int) -> 'TimsFrame':
         """Convert the frame to a given resolution.
 
+        Peaks of a scan in the same m/z bin are merged, intensities are summed and tof and mobility are the
+        intensity weighted means of the merged peaks, so the total intensity is preserved.
+
         Args:
             resolution (int): Resolution.
 
@@ -214,6 +256,23 @@ class TimsFrame(RustWrapperObject):
         """
         return TimsFrame.from_py_ptr(self.__frame_ptr.to_resolution(resolution))
 
+    def downsample_scans(self, factor: int) -> 'TimsFrame':
+        """Merge every factor adjacent scans into one, scan s becomes scan s // factor.
+
+        Peaks with the same tof in a merged scan are summed, the mobility of a merged scan is the mean mobility
+        of the scans it contains, so the total intensity is preserved.
+
+        Args:
+            factor (int): Number of scans merged into one.
+
+        Returns:
+            TimsFrame: Frame with merged scans, sorted by scan and m/z.
+
+        Raises:
+            ValueError: If factor is 0.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.downsample_scans(factor))
+
     def vectorized(self, resolution: int = 2) -> 'TimsFrameVectorized':
         """Convert the frame to a vectorized frame.
 
@@ -225,6 +284,51 @@ class TimsFrame(RustWrapperObject):
         """
         return TimsFrameVectorized.from_py_ptr(self.__frame_ptr.vectorized(resolution))
 
+    def vectorize_on_axis(self, axis: Tuple[float, float, int], resolution: int = 2) \
+            -> Tuple[NDArray[np.int32], NDArray[np.uint32], NDArray[np.float64]]:
+        """Bin the frame on a m/z axis shared by all frames of a run, peaks of the same scan and bin are summed
+        and peaks outside of the axis are dropped.
+
+        Args:
+            axis (Tuple[float, float, int]): m/z axis as returned by `TimsDataset.global_mz_axis`.
+            resolution (int, optional): Resolution the axis was created with. Defaults to 2.
+
+        Returns:
+            Tuple[NDArray[np.int32], NDArray[np.uint32], NDArray[np.float64]]: Scans, positions on the axis
+                (0 to n_bins - 1) and intensities of the non-empty bins.
+        """
+        mz_min, mz_max, _ = axis
+        return self.__frame_ptr.vectorize_on_axis(resolution, mz_min, mz_max)
+
+    def resample_scans(self, grid: NDArray[np.float64], method: str = 'linear', ppm: float = 10.0) \
+            -> Tuple[NDArray[np.int32], NDArray[np.float64]]:
+        """Resample the spectrum of every scan onto a fixed m/z grid, see `MzSpectrum.resample`.
+
+        Args:
+            grid (NDArray[np.float64]): m/z values of the grid points in strictly increasing order.
+            method (str, optional): 'nearest_bin', 'linear' or 'gaussian'. Defaults to 'linear'.
+            ppm (float, optional): Width of the gaussian peaks. Defaults to 10.0.
+
+        Returns:
+            Tuple[NDArray[np.int32], NDArray[np.float64]]: Scans holding a peak in ascending order and a
+                scans x grid matrix of resampled intensities.
+
+        Raises:
+            ValueError: If the grid is not strictly increasing or the method is unknown.
+        """
+        return self.__frame_ptr.resample_scans(np.asarray(grid, dtype=np.float64), method, ppm)
+
+    def to_csr(self, resolution: int = 2) -> 'TimsFrameCsr':
+        """Bin the frame to a given resolution and store it as a sparse scan x m/z index matrix.
+
+        Args:
+            resolution (int, optional): Number of decimal places of the m/z bins. Defaults to 2.
+
+        Returns:
+            TimsFrameCsr: Sparse frame.
+        """
+        return TimsFrameCsr.from_py_ptr(self.__frame_ptr.to_csr(resolution))
+
     def to_tims_spectra(self) -> List['TimsSpectrum']:
         """Convert the frame to a list of TimsSpectrum.
 
@@ -234,7 +338,8 @@ class TimsFrame(RustWrapperObject):
         return [TimsSpectrum.from_py_tims_spectrum(spec) for spec in self.__frame_ptr.to_tims_spectra()]
 
     def to_windows(self, window_length: float = 10, overlapping: bool = True, min_num_peaks: int = 5,
-                   min_intensity: float = 1) -> List[TimsSpectrum]:
+                   min_intensity: float = 1, with_metadata: bool = False
+                   ) -> Union[List[TimsSpectrum], Tuple[pd.DataFrame, List[TimsSpectrum]]]:
         """Convert the frame to a list of windows.
 
         Args:
@@ -242,13 +347,39 @@ class TimsFrame(RustWrapperObject):
             overlapping (bool, optional): Whether the windows should overlap. Defaults to True.
             min_num_peaks (int, optional): Minimum number of peaks in a window. Defaults to 5.
             min_intensity (float, optional): Minimum intensity of a peak in a window. Defaults to 1.
+            with_metadata (bool, optional): Whether to also return the window index, m/z bounds and scan
+                of every window. Defaults to False.
 
         Returns:
-            List[MzSpectrum]: List of windows.
+            List[TimsSpectrum]: List of windows, with_metadata additionally returns a DataFrame with
+                columns window_index, mz_min, mz_max, scan_min and scan_max, one row per window.
         """
+        if with_metadata:
+            metadata, windows = self.__frame_ptr.to_windows(window_length, overlapping, min_num_peaks,
+                                                            min_intensity, True)
+            return pd.DataFrame(metadata), [TimsSpectrum.from_py_tims_spectrum(spec) for spec in windows]
+
         return [TimsSpectrum.from_py_tims_spectrum(spec) for spec in self.__frame_ptr.to_windows(
             window_length, overlapping, min_num_peaks, min_intensity)]
 
+    def to_mobility_windows(self, im_window_length: float = 0.05, overlapping: bool = True, min_num_peaks: int = 5,
+                            min_intensity: float = 1) -> Tuple[pd.DataFrame, List['TimsFrame']]:
+        """Split the frame into windows along the inverse mobility axis.
+
+        Args:
+            im_window_length (float, optional): Window length in inverse mobility. Defaults to 0.05.
+            overlapping (bool, optional): Whether the windows should overlap. Defaults to True.
+            min_num_peaks (int, optional): Minimum number of peaks in a window. Defaults to 5.
+            min_intensity (float, optional): Minimum intensity of a peak in a window. Defaults to 1.
+
+        Returns:
+            Tuple[pd.DataFrame, List[TimsFrame]]: DataFrame with columns window_index, mobility_min, mobility_max,
+                scan_min and scan_max, and the frame of every window.
+        """
+        metadata, windows = self.__frame_ptr.to_mobility_windows(im_window_length, overlapping, min_num_peaks,
+                                                                 min_intensity)
+        return pd.DataFrame(metadata), [TimsFrame.from_py_ptr(frame) for frame in windows]
+
     @classmethod
     def from_windows(cls, windows: List[TimsSpectrum]) -> 'TimsFrame':
         """Create a TimsFrame from a list of windows.
@@ -310,6 +441,188 @@ class TimsFrame(RustWrapperObject):
         """
         return self.__frame_ptr.get_mobility_mean_and_variance()
 
+    def get_ccs(self, charges: Union[int, NDArray[np.int32]], temperature: float = 31.85,
+                mass_gas: float = 28.013) -> NDArray[np.float64]:
+        """Get the collision cross-section of every peak from its inverse mobility and m/z.
+
+        Args:
+            charges: Assumed charge state, either one for all peaks or one per peak.
+            temperature: Temperature of the drift gas in C°.
+            mass_gas: Mass of the drift gas, defaults to nitrogen.
+
+        Returns:
+            NDArray[np.float64]: Collision cross-section of every peak in Å².
+        """
+        charges = [int(c) for c in np.atleast_1d(charges)]
+        return self.__frame_ptr.get_ccs(charges, temperature, mass_gas)
+
+    def to_arrow(self) -> pa.RecordBatch:
+        """Convert the frame to an arrow record batch without copying its peaks. The scan, mobility, tof, mz and
+        intensity columns are read-only views of the buffers of the frame and keep them alive, only frame_id and rt
+        are filled per call.
+
+        Columns are frame_id, rt, scan, mobility, tof, mz and intensity, like the parquet export.
+
+        Returns:
+            pa.RecordBatch: Peaks of the frame.
+        """
+        return self.__frame_ptr.to_arrow()
+
+    def to_mzml_spectrum_xml(self, index: int = 0, compress: bool = True) -> str:
+        """Render the frame as mzML <spectrum> element, peaks sorted by m/z with an ion mobility array.
+
+        Args:
+            index (int, optional): Position of the spectrum in the spectrum list. Defaults to 0.
+            compress (bool, optional): zlib compress the binary arrays. Defaults to True.
+
+        Returns:
+            str: The <spectrum> element.
+        """
+        return self.__frame_ptr.to_mzml_spectrum_xml(index, compress)
+
+    def to_bytes(self) -> bytes:
+        """Serialize the frame to a compact binary representation, e.g. to store it in a cache.
+
+        Returns:
+            bytes: Binary representation, starting with a format version byte.
+        """
+        return self.__frame_ptr.to_bytes()
+
+    @classmethod
+    def from_bytes(cls, data: bytes) -> 'TimsFrame':
+        """Create a TimsFrame from bytes written by `to_bytes`.
+
+        Args:
+            data (bytes): Binary representation of the frame.
+
+        Returns:
+            TimsFrame: Frame.
+
+        Raises:
+            ValueError: If the bytes are not a frame or were written by an incompatible version.
+        """
+        return cls.from_py_ptr(ims.PyTimsFrame.from_bytes(data))
+
+    def smooth_mobility(self, window_scans: int = 5, method: str = 'savitzky_golay', polynomial_order: int = 2,
+                        resolution: int = 2) -> 'TimsFrame':
+        """Smooth the intensity of every m/z bin across adjacent scans, windows shrink at the frame boundaries.
+
+        Args:
+            window_scans (int, optional): Number of scans of a full window. Defaults to 5.
+            method (str, optional): 'moving_average' or 'savitzky_golay'. Defaults to 'savitzky_golay'.
+            polynomial_order (int, optional): Order of the Savitzky-Golay polynomial. Defaults to 2.
+            resolution (int, optional): Decimal places of the m/z bins. Defaults to 2.
+
+        Returns:
+            TimsFrame: Smoothed frame, intensity is only placed on scans that hold a peak in this frame.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.smooth_mobility(window_scans, method, polynomial_order, resolution))
+
+    def estimate_noise(self, method: str = 'mad') -> NDArray[np.float64]:
+        """Estimate the noise level of every scan, scans with fewer than 10 peaks get the noise of the whole frame.
+
+        Args:
+            method (str, optional): 'mad' (median absolute deviation) or 'lowest_quartile' (mean of the lowest
+                quartile of the intensities). Defaults to 'mad'.
+
+        Returns:
+            NDArray[np.float64]: Noise level of every scan holding a peak, in the order of np.unique(self.scan).
+        """
+        return self.__frame_ptr.estimate_noise(method)
+
+    def filter_snr(self, min_snr: float, method: str = 'mad') -> 'TimsFrame':
+        """Remove peaks whose intensity is below min_snr times the noise level of their scan.
+
+        Args:
+            min_snr (float): Smallest signal-to-noise ratio of the kept peaks.
+            method (str, optional): Noise estimator, see `estimate_noise`. Defaults to 'mad'.
+
+        Returns:
+            TimsFrame: Frame with the peaks above the threshold.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.filter_snr(min_snr, method))
+
+    def normalize(self, method: str = 'tic', target: float = 1.0) -> 'TimsFrame':
+        """Normalize the intensities, e.g. to compare simulated and measured frames.
+
+        Args:
+            method (str, optional): 'tic' scales the summed intensity, 'base_peak' the maximum, 'per_scan_tic' the
+                summed intensity of every scan and 'quantile' the median of the nonzero intensities. Defaults to 'tic'.
+            target (float, optional): Value the reference intensity is scaled to. Defaults to 1.0.
+
+        Returns:
+            TimsFrame: Normalized copy, scan, tof, m/z and mobility are untouched and empty frames are unchanged.
+
+        Raises:
+            ValueError: If the method is unknown.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.normalize(method, target))
+
+    def mobilogram(self, mz: float, ppm: float = 10.0, window_scans: Optional[int] = None,
+                   method: str = 'savitzky_golay', polynomial_order: int = 2) -> Tuple[NDArray[np.float64], NDArray[np.float64]]:
+        """Extract the mobilogram of an m/z value.
+
+        Args:
+            mz (float): m/z to extract.
+            ppm (float, optional): Tolerance around mz. Defaults to 10.0.
+            window_scans (int, optional): Smooth with windows of this many scans. Defaults to None, no smoothing.
+            method (str, optional): 'moving_average' or 'savitzky_golay'. Defaults to 'savitzky_golay'.
+            polynomial_order (int, optional): Order of the Savitzky-Golay polynomial. Defaults to 2.
+
+        Returns:
+            Tuple[NDArray[np.float64], NDArray[np.float64]]: Inverse mobility and intensity per scan.
+        """
+        return self.__frame_ptr.mobilogram(mz, ppm, window_scans, method, polynomial_order)
+
+    def top_n_global(self, n: int) -> 'TimsFrame':
+        """Keep the n most intense peaks of the frame, ties at the cutoff are resolved in favor of the lower m/z.
+
+        Args:
+            n (int): Number of peaks to keep.
+
+        Returns:
+            TimsFrame: Frame with the kept peaks in their original order.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.top_n_global(n))
+
+    def top_n_per_scan(self, n: int) -> 'TimsFrame':
+        """Keep the n most intense peaks of every scan, ties at the cutoff are resolved in favor of the lower m/z.
+
+        Args:
+            n (int): Number of peaks to keep per scan.
+
+        Returns:
+            TimsFrame: Frame with the kept peaks in their original order.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.top_n_per_scan(n))
+
+    def summary(self) -> dict:
+        """Get quick numbers about the frame for quality control.
+
+        Returns:
+            dict: frame_id, ms_type, retention_time, num_peaks, total_intensity, max_intensity, median_intensity
+                and the (min, max) tuples mz_range, scan_range and mobility_range, which are None for empty frames.
+        """
+        return self.__frame_ptr.summary()
+
+    def to_raw(self, dataset: 'TimsDataset') -> 'RawTimsFrame':
+        """Convert the frame back into the raw run-length representation of a TDF frame.
+
+        Args:
+            dataset (TimsDataset): Dataset the frame belongs to, its calibration computes scan and tof indices
+                the frame does not carry.
+
+        Returns:
+            RawTimsFrame: Peaks ordered by scan and tof, scan holds the number of peaks per scan.
+        """
+        return RawTimsFrame.from_py_ptr(self.__frame_ptr.to_raw(dataset.get_converter_ptr()))
+
+    def __getstate__(self) -> bytes:
+        return self.to_bytes()
+
+    def __setstate__(self, state: bytes):
+        self.__frame_ptr = ims.PyTimsFrame.from_bytes(state)
+
     def get_py_ptr(self):
         return self.__frame_ptr
 
@@ -317,6 +630,35 @@ class TimsFrame(RustWrapperObject):
         return (f"TimsFrame(frame_id={self.__frame_ptr.frame_id}, ms_type={self.__frame_ptr.ms_type}, "
                 f"num_peaks={len(self.__frame_ptr.mz)}, intensity_sum={np.round(np.sum(self.__frame_ptr.intensity))})")
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__frame_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsFrame):
+            return NotImplemented
+        return self.__frame_ptr == other.__frame_ptr
+
+    def approx_equal(self, other: 'TimsFrame', tolerance: float = 1e-6) -> bool:
+        """Compare to another frame, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsFrame): Frame to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+
+        Examples:
+            >>> frame = TimsFrame(1, 0, 10.0, np.array([1], dtype=np.int32), np.array([1.2]),
+            ...                   np.array([1000], dtype=np.int32), np.array([500.0]), np.array([100.0]))
+            >>> recalibrated = TimsFrame(1, 0, 10.0, np.array([1], dtype=np.int32), np.array([1.2]),
+            ...                          np.array([1000], dtype=np.int32), np.array([500.0001]), np.array([100.0]))
+            >>> len(frame), frame == recalibrated, frame.approx_equal(recalibrated, tolerance=1e-3)
+            (1, False, True)
+        """
+        return self.__frame_ptr.approx_equal(other.__frame_ptr, tolerance)
+
     def random_subsample_frame(self, take_probability: float) -> 'TimsFrame':
         """Randomly subsample the frame.
 
@@ -479,6 +821,27 @@ class TimsFrameVectorized(RustWrapperObject):
         return (f"TimsFrameVectorized(frame_id={self.__frame_ptr.frame_id}, ms_type={self.__frame_ptr.ms_type}, "
                 f"num_peaks={len(self.__frame_ptr.indices)})")
 
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__frame_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsFrameVectorized):
+            return NotImplemented
+        return self.__frame_ptr == other.__frame_ptr
+
+    def approx_equal(self, other: 'TimsFrameVectorized', tolerance: float = 1e-6) -> bool:
+        """Compare to another frame, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsFrameVectorized): Frame to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__frame_ptr.approx_equal(other.__frame_ptr, tolerance)
+
     def get_tensor_repr(self, dense=True, zero_indexed=True, re_index=True, scan_max=None, index_max=None):
         s = self.scan
         f = self.indices
@@ -537,6 +900,22 @@ class TimsFrameVectorized(RustWrapperObject):
         return TimsFrameVectorized.from_py_ptr(self.__frame_ptr.filter_ranged(
             mz_min, mz_max, scan_min, scan_max, mobility_min, mobility_max, intensity_min, intensity_max))
 
+    def to_csr(self) -> 'TimsFrameCsr':
+        """Convert the frame to a sparse matrix in CSR layout, one row per scan.
+
+        Returns:
+            TimsFrameCsr: Sparse frame.
+        """
+        return TimsFrameCsr.from_py_ptr(self.__frame_ptr.to_csr())
+
+    def to_scipy_csr(self) -> csr_matrix:
+        """Convert the frame to a scipy sparse matrix, row r holds scan `scan_min + r` of the CSR frame.
+
+        Returns:
+            csr_matrix: Sparse scan x m/z index matrix.
+        """
+        return self.to_csr().to_scipy_csr()
+
     def get_py_ptr(self):
         return self.__frame_ptr
 
@@ -550,3 +929,264 @@ class TimsFrameVectorized(RustWrapperObject):
             NDArray[np.float64]: Arrays at the index.
         """
         return self.__frame_ptr.get_arrays_at_index(index)
+
+
+class TimsFrameCsr(RustWrapperObject):
+    """Frame binned to a given m/z resolution, stored as a sparse matrix with one row per scan.
+
+    Row r holds scan `scan_min + r`, columns are m/z indices, i.e. m/z values times 10^resolution.
+    """
+
+    def __init__(self, *args, **kwargs):
+        raise NotImplementedError("TimsFrameCsr is created by TimsFrame.to_csr or TimsFrameVectorized.to_csr.")
+
+    @classmethod
+    def from_py_ptr(cls, frame: ims.PyTimsFrameCsr):
+        """Create a TimsFrameCsr from a PyTimsFrameCsr.
+
+        Args:
+            frame (ims.PyTimsFrameCsr): PyTimsFrameCsr to create the TimsFrameCsr from.
+
+        Returns:
+            TimsFrameCsr: TimsFrameCsr created from the PyTimsFrameCsr.
+        """
+        instance = cls.__new__(cls)
+        instance.__frame_ptr = frame
+        return instance
+
+    @property
+    def frame_id(self) -> int:
+        """Frame ID.
+
+        Returns:
+            int: Frame ID.
+        """
+        return self.__frame_ptr.frame_id
+
+    @property
+    def retention_time(self) -> float:
+        """Retention time.
+
+        Returns:
+            float: Retention time.
+        """
+        return self.__frame_ptr.retention_time
+
+    @property
+    def resolution(self) -> int:
+        """Number of decimal places of the m/z bins.
+
+        Returns:
+            int: Resolution.
+        """
+        return self.__frame_ptr.resolution
+
+    @property
+    def scan_min(self) -> int:
+        """Scan of the first row.
+
+        Returns:
+            int: Scan of the first row.
+        """
+        return self.__frame_ptr.scan_min
+
+    @property
+    def shape(self) -> Tuple[int, int]:
+        """Number of rows and columns of the matrix.
+
+        Returns:
+            Tuple[int, int]: Number of scans and m/z indices.
+        """
+        return self.__frame_ptr.shape
+
+    @property
+    def indptr(self) -> NDArray[np.int64]:
+        """Start of every row in indices and data, followed by the number of entries.
+
+        Returns:
+            NDArray[np.int64]: Row pointers.
+        """
+        return self.__frame_ptr.indptr
+
+    @property
+    def indices(self) -> NDArray[np.int64]:
+        """m/z index of every entry.
+
+        Returns:
+            NDArray[np.int64]: m/z indices.
+        """
+        return self.__frame_ptr.indices
+
+    @property
+    def data(self) -> NDArray[np.float64]:
+        """Intensity of every entry.
+
+        Returns:
+            NDArray[np.float64]: Intensities.
+        """
+        return self.__frame_ptr.data
+
+    @property
+    def tof(self) -> NDArray[np.int32]:
+        """Mean time of flight of every entry.
+
+        Returns:
+            NDArray[np.int32]: Time of flight.
+        """
+        return self.__frame_ptr.tof
+
+    @property
+    def mobility(self) -> NDArray[np.float64]:
+        """Inverse mobility of every row, NaN for scans without peaks.
+
+        Returns:
+            NDArray[np.float64]: Inverse mobility.
+        """
+        return self.__frame_ptr.mobility
+
+    def to_scipy_csr(self) -> csr_matrix:
+        """Wrap the arrays of the frame in a scipy sparse matrix without copying them.
+
+        Returns:
+            csr_matrix: Sparse scan x m/z index matrix.
+        """
+        return csr_matrix((self.data, self.indices, self.indptr), shape=self.shape, copy=False)
+
+    def to_tims_frame(self) -> TimsFrame:
+        """Convert the sparse frame back to a TimsFrame with m/z values at the bin centers.
+
+        Returns:
+            TimsFrame: Frame.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.to_tims_frame())
+
+    def to_dense_slice(self, scan_min: int, scan_max: int, mz_index_min: int, mz_index_max: int) -> NDArray[np.float64]:
+        """Dense block of the frame, scans outside of the frame and empty bins are zero.
+
+        Args:
+            scan_min (int): First scan of the block.
+            scan_max (int): Scan after the last scan of the block.
+            mz_index_min (int): First m/z index of the block.
+            mz_index_max (int): m/z index after the last one of the block.
+
+        Returns:
+            NDArray[np.float64]: Intensities with shape (scan_max - scan_min, mz_index_max - mz_index_min).
+        """
+        dense = self.__frame_ptr.to_dense_slice(scan_min, scan_max, mz_index_min, mz_index_max)
+        return dense.reshape(max(scan_max - scan_min, 0), max(mz_index_max - mz_index_min, 0))
+
+    def get_py_ptr(self):
+        return self.__frame_ptr
+
+    def __repr__(self):
+        return repr(self.__frame_ptr)
+
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__frame_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsFrameCsr):
+            return NotImplemented
+        return self.__frame_ptr == other.__frame_ptr
+
+    def approx_equal(self, other: 'TimsFrameCsr', tolerance: float = 1e-6) -> bool:
+        """Compare to another frame, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsFrameCsr): Frame to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__frame_ptr.approx_equal(other.__frame_ptr, tolerance)
+
+
+class RawTimsFrame(RustWrapperObject):
+    """Frame as stored in a TDF: number of peaks per scan, tof indices and intensities, not calibrated."""
+
+    def __init__(self, frame_id: int, ms_type: int, retention_time: float, scan: NDArray[np.uint32],
+                 tof: NDArray[np.uint32], intensity: NDArray[np.float64]):
+        """RawTimsFrame class.
+
+        Args:
+            frame_id (int): Frame ID.
+            ms_type (int): MS type.
+            retention_time (float): Retention time.
+            scan (NDArray[np.uint32]): Number of peaks per scan.
+            tof (NDArray[np.uint32]): Time of flight index per peak.
+            intensity (NDArray[np.float64]): Intensity per peak.
+        """
+        self.__frame_ptr = ims.PyRawTimsFrame(frame_id, ms_type, retention_time, scan.astype(np.uint32),
+                                              tof.astype(np.uint32), intensity.astype(np.float64))
+
+    @classmethod
+    def from_py_ptr(cls, frame: ims.PyRawTimsFrame):
+        instance = cls.__new__(cls)
+        instance.__frame_ptr = frame
+        return instance
+
+    @property
+    def frame_id(self) -> int:
+        return self.__frame_ptr.frame_id
+
+    @property
+    def ms_type(self) -> str:
+        return self.__frame_ptr.ms_type
+
+    @property
+    def retention_time(self) -> float:
+        return self.__frame_ptr.retention_time
+
+    @property
+    def scan(self) -> NDArray[np.uint32]:
+        return self.__frame_ptr.scan
+
+    @property
+    def tof(self) -> NDArray[np.uint32]:
+        return self.__frame_ptr.tof
+
+    @property
+    def intensity(self) -> NDArray[np.float64]:
+        return self.__frame_ptr.intensity
+
+    def to_tims_frame(self, dataset: 'TimsDataset', retention_time: Optional[float] = None) -> TimsFrame:
+        """Calibrate the frame, the same conversion the dataset applies when reading frames.
+
+        Args:
+            dataset (TimsDataset): Dataset the frame belongs to.
+            retention_time (float, optional): Retention time of the calibrated frame. Defaults to the one of the raw frame.
+
+        Returns:
+            TimsFrame: Frame with one scan, mobility, tof and m/z value per peak.
+        """
+        return TimsFrame.from_py_ptr(self.__frame_ptr.to_tims_frame(dataset.get_converter_ptr(), retention_time))
+
+    def get_py_ptr(self):
+        return self.__frame_ptr
+
+    def __repr__(self):
+        return (f"RawTimsFrame(frame_id={self.frame_id}, ms_type={self.ms_type}, "
+                f"num_scans={len(self.scan)}, num_peaks={len(self.tof)})")
+
+    def __len__(self) -> int:
+        """Number of peaks."""
+        return len(self.__frame_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, RawTimsFrame):
+            return NotImplemented
+        return self.__frame_ptr == other.__frame_ptr
+
+    def approx_equal(self, other: 'RawTimsFrame', tolerance: float = 1e-6) -> bool:
+        """Compare to another frame, floating point values may differ by at most tolerance.
+
+        Args:
+            other (RawTimsFrame): Frame to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__frame_ptr.approx_equal(other.__frame_ptr, tolerance)
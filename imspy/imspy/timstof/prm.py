@@ -0,0 +1,181 @@
+import sqlite3
+from typing import List, Optional
+
+import pandas as pd
+
+from imspy.simulation.annotation import RustWrapperObject
+from imspy.timstof.data import TimsDataset
+from imspy.timstof.frame import TimsFrame
+
+import imspy_connector
+ims = imspy_connector.py_prm
+import warnings
+
+
+class TargetPRM(RustWrapperObject):
+    def __init__(self, *args, **kwargs):
+        raise NotImplementedError("TargetPRM is read from a prm-PASEF dataset, use TimsDatasetPRM.get_targets.")
+
+    @classmethod
+    def from_py_ptr(cls, target: ims.PyPRMTarget):
+        instance = cls.__new__(cls)
+        instance._target_ptr = target
+        return instance
+
+    @property
+    def target_id(self) -> int:
+        return self._target_ptr.target_id
+
+    @property
+    def external_id(self) -> Optional[str]:
+        return self._target_ptr.external_id
+
+    @property
+    def description(self) -> Optional[str]:
+        return self._target_ptr.description
+
+    @property
+    def precursor_mz(self) -> float:
+        return self._target_ptr.precursor_mz
+
+    @property
+    def charge(self) -> Optional[int]:
+        return self._target_ptr.charge
+
+    @property
+    def one_over_k0(self) -> float:
+        return self._target_ptr.one_over_k0
+
+    @property
+    def retention_time(self) -> float:
+        return self._target_ptr.retention_time
+
+    @property
+    def rt_begin(self) -> Optional[float]:
+        return self._target_ptr.rt_begin
+
+    @property
+    def rt_end(self) -> Optional[float]:
+        return self._target_ptr.rt_end
+
+    @property
+    def frame_ids(self) -> List[int]:
+        return self._target_ptr.frame_ids
+
+    def __repr__(self):
+        return (f"TargetPRM(target_id={self.target_id}, external_id={self.external_id}, "
+                f"precursor_mz={self.precursor_mz}, charge={self.charge}, one_over_k0={self.one_over_k0}, "
+                f"retention_time={self.retention_time}, rt_begin={self.rt_begin}, rt_end={self.rt_end}, "
+                f"num_frames={len(self.frame_ids)})")
+
+    def get_py_ptr(self):
+        return self._target_ptr
+
+
+class TimsDatasetPRM(TimsDataset, RustWrapperObject):
+
+    def __init__(self, data_path: str, in_memory: bool = False, use_bruker_sdk: bool = True):
+        super().__init__(data_path=data_path, in_memory=in_memory, use_bruker_sdk=use_bruker_sdk)
+        self.__dataset = ims.PyTimsDatasetPRM(self.data_path, self.binary_path, in_memory, self.use_bruker_sdk)
+        self.meta_data = self.meta_data.rename(columns={"Id": "frame_id"})
+        self.prm_meta_data = self._load_prm_meta_data().rename(
+            columns={
+                'Frame': 'frame_id',
+                'ScanNumBegin': 'scan_begin',
+                'ScanNumEnd': 'scan_end',
+                'IsolationMz': 'isolation_mz',
+                'IsolationWidth': 'isolation_width',
+                'CollisionEnergy': 'collision_energy',
+                'Target': 'target_id'
+            }
+        )
+
+    def _load_prm_meta_data(self):
+        """Get prm-PASEF meta data, which scans of which frame were isolated for which target.
+
+        Returns:
+            pd.DataFrame: prm-PASEF meta data.
+        """
+        return pd.read_sql_query("SELECT * from PrmFrameMsMsInfo",
+                                 sqlite3.connect(self.data_path + "/analysis.tdf"))
+
+    def get_targets(self) -> List[TargetPRM]:
+        """Get the target list with the frames every target was fragmented in.
+
+        Returns:
+            List[TargetPRM]: Targets of the acquisition.
+        """
+        return [TargetPRM.from_py_ptr(target) for target in self.__dataset.get_targets()]
+
+    @property
+    def targets(self) -> pd.DataFrame:
+        """Get the target list as table.
+
+        Returns:
+            pd.DataFrame: One row per target, the retention time window is taken from the frames it was fragmented in.
+        """
+        targets = self.get_targets()
+        return pd.DataFrame({
+            'target_id': [t.target_id for t in targets],
+            'external_id': [t.external_id for t in targets],
+            'precursor_mz': [t.precursor_mz for t in targets],
+            'charge': [t.charge for t in targets],
+            'one_over_k0': [t.one_over_k0 for t in targets],
+            'retention_time': [t.retention_time for t in targets],
+            'rt_begin': [t.rt_begin for t in targets],
+            'rt_end': [t.rt_end for t in targets],
+            'num_frames': [len(t.frame_ids) for t in targets],
+        })
+
+    def get_target_fragment_spectra(self, target_id: int, num_threads: int = 1) -> List[TimsFrame]:
+        """Get the fragment spectra of a target, restricted to the isolated scan range of every frame.
+
+        Args:
+            target_id (int): Id of the target.
+            num_threads (int, optional): Number of threads. Defaults to 1. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            List[TimsFrame]: Fragment frames ordered by frame id.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        return [TimsFrame.from_py_ptr(frame)
+                for frame in self.__dataset.get_target_fragment_spectra(target_id, num_threads)]
+
+    def get_target_xic(self, target_id: int, fragment_mz: List[float], tolerance_ppm: float = 10.0,
+                       num_threads: int = 1) -> pd.DataFrame:
+        """Get extracted ion chromatograms of fragment m/z values of a target.
+
+        Args:
+            target_id (int): Id of the target.
+            fragment_mz (List[float]): Fragment m/z values to extract.
+            tolerance_ppm (float, optional): Peaks within this tolerance are summed up. Defaults to 10.0.
+            num_threads (int, optional): Number of threads. Defaults to 1. CAUTION: As long as connection to
+                datasets is established via bruker so / dll, using multiple threads is unstable.
+
+        Returns:
+            pd.DataFrame: Column retention_time followed by one intensity column per fragment m/z.
+        """
+        if self.use_bruker_sdk and num_threads > 1:
+            warnings.warn("Using multiple threads is currently not supported when using Bruker SDK, "
+                          "setting num_threads to 1.")
+            num_threads = 1
+
+        retention_time, traces = self.__dataset.get_target_xic(target_id, fragment_mz, tolerance_ppm, num_threads)
+        table = pd.DataFrame({'retention_time': retention_time})
+        for mz, trace in zip(fragment_mz, traces):
+            table[mz] = trace
+        return table
+
+    def get_py_ptr(self):
+        return self.__dataset
+
+    @classmethod
+    def from_py_ptr(cls, ptr):
+        instance = cls.__new__(cls)
+        instance.__dataset = ptr
+        return instance
@@ -3,6 +3,7 @@ from typing import Union, List
 from imspy.data.spectrum import MzSpectrum
 from imspy.timstof.frame import TimsFrame
 from numpy.typing import NDArray
+import pandas as pd
 
 import imspy_connector
 ims = imspy_connector.py_quadrupole
@@ -114,6 +115,64 @@ class TimsTofQuadrupoleDIA:
             frame, frame_window_group, window_group, scan_start, scan_end, isolation_mz, isolation_width, k
         )
 
+    @classmethod
+    def from_dataset(cls, dataset, k: float | None = None) -> 'TimsTofQuadrupoleDIA':
+        """Isolation windows of a DIA acquisition, read from DiaFrameMsMsInfo and DiaFrameMsMsWindows
+
+        Args:
+            dataset: TimsDatasetDIA
+            k: steepness of the sigmoid window edges, 15.0 if None
+
+        Returns:
+            TimsTofQuadrupoleDIA: transmission of the acquisition
+        """
+        return cls.from_py_ptr(ims.PyTimsTransmissionDIA.from_dataset(dataset.get_py_ptr(), k))
+
+    @classmethod
+    def from_synthetics_handle(cls, handle) -> 'TimsTofQuadrupoleDIA':
+        """Isolation windows of a simulated DIA acquisition
+
+        Args:
+            handle: TimsTofSyntheticsDataHandleRust of the synthetics database
+
+        Returns:
+            TimsTofQuadrupoleDIA: transmission of the simulated acquisition
+        """
+        return cls.from_py_ptr(ims.PyTimsTransmissionDIA.from_synthetics_handle(handle.get_py_ptr()))
+
+    def isolation_window_for(self, window_group: int, scan: int) -> tuple[float, float] | None:
+        """Isolation window a window group uses at a scan
+
+        Args:
+            window_group: window group
+            scan: scan
+
+        Returns:
+            tuple[float, float] | None: center and width of the window, None if the group does not isolate at this scan
+        """
+        return self.handle.isolation_window_for(window_group, scan)
+
+    def window_groups_for_mz(self, mz: float, scan: int) -> list[int]:
+        """Window groups sampling an ion, all of them if windows overlap
+
+        Args:
+            mz: m/z of the ion
+            scan: scan of the ion
+
+        Returns:
+            list[int]: ascending window groups, empty if no window contains the ion
+        """
+        return self.handle.window_groups_for_mz(mz, scan)
+
+    def all_windows(self) -> pd.DataFrame:
+        """All isolation windows, ordered by window group and first scan
+
+        Returns:
+            pd.DataFrame: window_group, scan_start, scan_end (inclusive), isolation_mz and isolation_width per window
+        """
+        return pd.DataFrame(self.handle.all_windows(),
+                            columns=['window_group', 'scan_start', 'scan_end', 'isolation_mz', 'isolation_width'])
+
     def apply_transmission(self, frame_id: int, scan_id: int, mz: NDArray) -> NDArray:
         return self.handle.apply_transmission(frame_id, scan_id, mz)
 
@@ -1,6 +1,7 @@
 import numpy as np
 import pandas as pd
-from typing import List
+import pyarrow as pa
+from typing import Dict, List, Optional, Tuple
 
 from numpy.typing import NDArray
 from tensorflow import sparse as sp
@@ -90,6 +91,27 @@ class TimsSlice:
     def __repr__(self):
         return f"TimsSlice({self.first_frame_id}, {self.last_frame_id})"
 
+    def __len__(self) -> int:
+        """Number of frames."""
+        return len(self.__slice_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsSlice):
+            return NotImplemented
+        return self.__slice_ptr == other.__slice_ptr
+
+    def approx_equal(self, other: 'TimsSlice', tolerance: float = 1e-6) -> bool:
+        """Compare to another slice, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsSlice): Slice to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__slice_ptr.approx_equal(other.__slice_ptr, tolerance)
+
     @property
     def precursors(self):
         return TimsSlice.from_py_tims_slice(self.__slice_ptr.get_precursors())
@@ -182,7 +204,7 @@ class TimsSlice:
         return [TimsFrame.from_py_ptr(frame) for frame in self.__slice_ptr.get_frames()]
 
     def to_resolution(self, resolution: int, num_threads: int = 4) -> 'TimsSlice':
-        """Convert the slice to a given resolution.
+        """Convert the slice to a given resolution, frames are binned in parallel, see TimsFrame.to_resolution.
 
         Args:
             resolution (int): Resolution.
@@ -193,6 +215,19 @@ class TimsSlice:
         """
         return TimsSlice.from_py_tims_slice(self.__slice_ptr.to_resolution(resolution, num_threads))
 
+    def normalize_frames(self, method: str = 'tic', target: float = 1.0, num_threads: int = 4) -> 'TimsSlice':
+        """Normalize the intensities of every frame separately, see TimsFrame.normalize.
+
+        Args:
+            method (str, optional): 'tic', 'base_peak', 'per_scan_tic' or 'quantile'. Defaults to 'tic'.
+            target (float, optional): Value the reference intensity is scaled to. Defaults to 1.0.
+            num_threads (int, optional): Number of threads to use. Defaults to 4.
+
+        Returns:
+            TimsSlice: Slice of normalized frames.
+        """
+        return TimsSlice.from_py_tims_slice(self.__slice_ptr.normalize_frames(method, target, num_threads))
+
     def to_windows(self, window_length: float = 10, overlapping: bool = True, min_num_peaks: int = 5,
                    min_intensity: float = 1, num_threads: int = 4) -> List[TimsSpectrum]:
         """Convert the slice to a list of windows.
@@ -227,6 +262,54 @@ class TimsSlice:
 
         return scan_list, window_indices_list, values_list
 
+    def to_dense_matrix(self, mz_min: float = 100.0, mz_max: float = 1700.0, mz_bin_width: float = 1.0,
+                        im_min: float = 0.6, im_max: float = 1.6, im_bin_count: int = 100,
+                        num_threads: int = 4) -> Tuple[NDArray, NDArray, NDArray]:
+        """Accumulate the slice into a dense m/z x inverse mobility intensity grid, e.g. for heatmap plotting.
+
+        Args:
+            mz_min (float, optional): Lower bound of the m/z axis. Defaults to 100.0.
+            mz_max (float, optional): Upper bound of the m/z axis. Defaults to 1700.0.
+            mz_bin_width (float, optional): Width of a m/z bin. Defaults to 1.0.
+            im_min (float, optional): Lower bound of the inverse mobility axis. Defaults to 0.6.
+            im_max (float, optional): Upper bound of the inverse mobility axis. Defaults to 1.6.
+            im_bin_count (int, optional): Number of inverse mobility bins. Defaults to 100.
+            num_threads (int, optional): Number of threads to use. Defaults to 4.
+
+        Returns:
+            Tuple[NDArray, NDArray, NDArray]: Matrix of shape (len(mz_axis), len(im_axis)), m/z bin centers
+            and inverse mobility bin centers.
+
+        Raises:
+            ValueError: If a range is empty or the bin width or count is not positive.
+        """
+        return self.__slice_ptr.to_dense_matrix(mz_min, mz_max, mz_bin_width, im_min, im_max, im_bin_count,
+                                                num_threads)
+
+    def detect_features(self, ppm_tol: float = 10.0, im_tol: float = 0.01, rt_gap_frames: int = 1,
+                        min_intensity: float = 1.0, min_points: int = 5, assign_charges: bool = False,
+                        rt_tol: float = 2.0, max_charge: int = 4, num_threads: int = 4) -> Dict[str, NDArray]:
+        """Detect features in retention time, inverse mobility and m/z by clustering tolerance-linked peaks.
+
+        Args:
+            ppm_tol (float, optional): m/z tolerance in ppm. Defaults to 10.0.
+            im_tol (float, optional): Inverse mobility tolerance. Defaults to 0.01.
+            rt_gap_frames (int, optional): Number of frames a feature may be missing in between. Defaults to 1.
+            min_intensity (float, optional): Minimum peak intensity. Defaults to 1.0.
+            min_points (int, optional): Minimum number of peaks per feature. Defaults to 5.
+            assign_charges (bool, optional): Assign charge states via co-eluting isotope features. Defaults to False.
+            rt_tol (float, optional): Apex retention time tolerance for charge assignment. Defaults to 2.0.
+            max_charge (int, optional): Maximum charge state for charge assignment. Defaults to 4.
+            num_threads (int, optional): Number of threads to use. Defaults to 4.
+
+        Returns:
+            Dict[str, NDArray]: Feature columns (apex_mz, apex_retention_time, apex_mobility, apex_frame_id,
+            total_intensity, charge, num_points), charge is 0 if unassigned, and member peak columns
+            (peak_feature_index, peak_frame_index, peak_index).
+        """
+        return self.__slice_ptr.detect_features(ppm_tol, im_tol, rt_gap_frames, min_intensity, min_points,
+                                                num_threads, assign_charges, rt_tol, max_charge)
+
     @property
     def df(self) -> pd.DataFrame:
         """Get the data as a pandas DataFrame.
@@ -234,8 +317,110 @@ class TimsSlice:
         Returns:
             pd.DataFrame: Data.
         """
+        return pd.DataFrame(self.to_arrays())
+
+    def to_arrays(self, ms_type: Optional[int] = None, intensity_min: Optional[float] = None,
+                  num_threads: int = 4) -> Dict[str, NDArray]:
+        """Get all peaks as one array per column, flattened in parallel. Filters are applied while flattening,
+        so peaks that are dropped are never copied.
+
+        Args:
+            ms_type (Optional[int]): MsMsType code of the frames to keep, e.g. 0 for precursor frames, all if None.
+            intensity_min (Optional[float]): Minimum intensity of the peaks to keep, all if None.
+            num_threads (int): Number of threads.
+
+        Returns:
+            Dict[str, NDArray]: Columns frame, scan, tof (uint32), retention_time, mobility, mz and intensity
+            (float64), one row per peak.
+        """
         columns = ['frame', 'scan', 'tof', 'retention_time', 'mobility', 'mz', 'intensity']
-        return pd.DataFrame({c: v for c, v in zip(columns, self.__slice_ptr.to_arrays())})
+        return dict(zip(columns, self.__slice_ptr.to_arrays(ms_type, intensity_min, num_threads)))
+
+    def to_arrow(self) -> pa.Table:
+        """Convert the slice to an arrow table with one record batch per frame without copying its peaks, e.g. to
+        hand it to polars via `polars.from_arrow`. The scan, mobility, tof, mz and intensity columns are read-only
+        views of the buffers of the slice and keep them alive, only frame_id and rt are filled per call.
+
+        Returns:
+            pa.Table: Peaks of the slice, with the columns of `to_parquet`.
+        """
+        return self.__slice_ptr.to_arrow()
+
+    def summary(self, num_threads: int = 4) -> Dict:
+        """Get quick numbers about the slice for quality control, frames are summarized in parallel.
+
+        Args:
+            num_threads (int, optional): Number of threads. Defaults to 4.
+
+        Returns:
+            Dict: num_frames, num_peaks, total_intensity, max_intensity, the (min, max) tuples rt_range, mz_range,
+                scan_range and mobility_range, ms_type_counts with the number of frames per ms type and
+                frames with the summary of every frame, see `TimsFrame.summary`.
+        """
+        return self.__slice_ptr.summary(num_threads)
+
+    def to_parquet(self, path: str, compression: str = 'zstd'):
+        """Write all peaks of the slice to a parquet file with columns frame_id, rt, scan, mobility, tof, mz and intensity.
+
+        Args:
+            path (str): Parquet file to create.
+            compression (str, optional): One of 'uncompressed', 'snappy' or 'zstd'. Defaults to 'zstd'.
+        """
+        self.__slice_ptr.to_parquet(path, compression)
+
+    def save(self, path: str):
+        """Save the slice to a cache file, frames are stored as zstd compressed blocks with a checksum each.
+
+        Args:
+            path (str): Cache file to create.
+        """
+        self.__slice_ptr.save(path)
+
+    @classmethod
+    def load(cls, path: str, num_threads: int = 4) -> 'TimsSlice':
+        """Load a slice saved with `save`, without going back to the raw data.
+
+        Args:
+            path (str): Cache file.
+            num_threads (int, optional): Number of threads frames are decompressed with. Defaults to 4.
+
+        Returns:
+            TimsSlice: Slice.
+
+        Raises:
+            OSError: If the file is not a slice cache, was written by an incompatible version or is corrupted.
+        """
+        return cls.from_py_tims_slice(ims.PyTimsSlice.load(path, num_threads))
+
+    def to_bytes(self) -> bytes:
+        """Serialize the slice to a compact binary representation, e.g. to store it in a cache.
+
+        Returns:
+            bytes: Binary representation, starting with a format version byte.
+        """
+        return self.__slice_ptr.to_bytes()
+
+    @classmethod
+    def from_bytes(cls, data: bytes) -> 'TimsSlice':
+        """Create a TimsSlice from bytes written by `to_bytes`.
+
+        Args:
+            data (bytes): Binary representation of the slice.
+
+        Returns:
+            TimsSlice: Slice.
+
+        Raises:
+            ValueError: If the bytes are not a slice or were written by an incompatible version.
+        """
+        return cls.from_py_tims_slice(ims.PyTimsSlice.from_bytes(data))
+
+    def __getstate__(self) -> bytes:
+        return self.to_bytes()
+
+    def __setstate__(self, state: bytes):
+        self.__slice_ptr = ims.PyTimsSlice.from_bytes(state)
+        self.__current_index = 0
 
     def __iter__(self):
         return self
@@ -264,11 +449,42 @@ class TimsSlice:
         """
         return TimsSliceVectorized.from_vectorized_py_tims_slice(self.__slice_ptr.vectorized(resolution, num_threads))
 
+    def vectorize_on_axis(self, axis: Tuple[float, float, int], resolution: int = 2, num_threads: int = 4) \
+            -> List[Tuple[NDArray[np.int32], NDArray[np.uint32], NDArray[np.float64]]]:
+        """Bin every frame on the same m/z axis, see `TimsFrame.vectorize_on_axis`.
+
+        Args:
+            axis (Tuple[float, float, int]): m/z axis as returned by `TimsDataset.global_mz_axis`.
+            resolution (int, optional): Resolution the axis was created with. Defaults to 2.
+            num_threads (int, optional): Number of threads to use. Defaults to 4.
+
+        Returns:
+            List[Tuple[NDArray[np.int32], NDArray[np.uint32], NDArray[np.float64]]]: Scans, positions on the axis
+                and intensities of every frame.
+        """
+        mz_min, mz_max, _ = axis
+        return [(np.array(scan, dtype=np.int32), np.array(index, dtype=np.uint32), np.array(intensity))
+                for scan, index, intensity in self.__slice_ptr.vectorize_on_axis(resolution, mz_min, mz_max, num_threads)]
+
     def get_tims_planes(self, tof_max_value: int = 400_000, num_chunks: int = 7, num_threads: int = 4) -> List[
         'TimsPlane']:
         return [TimsPlane.from_py_tims_plane(plane) for plane in
                 self.__slice_ptr.to_tims_planes(tof_max_value, num_chunks, num_threads)]
 
+    def get_planes(self, mz_list: List[float], ppm: float = 10.0, num_threads: int = 4) -> List['TimsPlane']:
+        """Extract the retention time x mobility intensity surface of an m/z window around every target.
+
+        Args:
+            mz_list (List[float]): Centers of the m/z windows.
+            ppm (float, optional): Half width of the windows in ppm of their center. Defaults to 10.0.
+            num_threads (int, optional): Number of threads to use. Defaults to 4.
+
+        Returns:
+            List[TimsPlane]: One plane per target in the order of mz_list, intensities are summed per frame and scan.
+        """
+        return [TimsPlane.from_py_tims_plane(plane) for plane in
+                self.__slice_ptr.get_planes(list(mz_list), ppm, num_threads)]
+
 
 class TimsSliceVectorized:
     def __init__(self):
@@ -354,6 +570,27 @@ class TimsSliceVectorized:
     def __repr__(self):
         return f"TimsSliceVectorized({self.first_frame_id}, {self.last_frame_id})"
 
+    def __len__(self) -> int:
+        """Number of frames."""
+        return len(self.__slice_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsSliceVectorized):
+            return NotImplemented
+        return self.__slice_ptr == other.__slice_ptr
+
+    def approx_equal(self, other: 'TimsSliceVectorized', tolerance: float = 1e-6) -> bool:
+        """Compare to another slice, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsSliceVectorized): Slice to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__slice_ptr.approx_equal(other.__slice_ptr, tolerance)
+
     def get_tensor_repr(self, dense=True, zero_index=True, re_index=True, frame_max=None, scan_max=None,
                         index_max=None):
 
@@ -493,3 +730,37 @@ class TimsPlane:
                 f" tof_mean: {np.round(self.tof_mean, 4)}, "
                 f"tof_std: {np.round(self.tof_std, 4)}, "
                 f"num_points: {len(self.frame_ids)})")
+
+    def to_dense(self, rt_bins: int = 100, im_bins: int = 100) -> Tuple[NDArray, NDArray, NDArray]:
+        """Bin the plane into a dense retention time x inverse mobility grid, e.g. for heatmap plotting.
+
+        Args:
+            rt_bins (int, optional): Number of retention time bins spanning the plane. Defaults to 100.
+            im_bins (int, optional): Number of inverse mobility bins spanning the plane. Defaults to 100.
+
+        Returns:
+            Tuple[NDArray, NDArray, NDArray]: Matrix of shape (rt_bins, im_bins), retention time bin centers
+            and inverse mobility bin centers.
+        """
+        return self.__plane_ptr.to_dense(rt_bins, im_bins)
+
+    def __len__(self) -> int:
+        """Number of points."""
+        return len(self.__plane_ptr)
+
+    def __eq__(self, other) -> bool:
+        if not isinstance(other, TimsPlane):
+            return NotImplemented
+        return self.__plane_ptr == other.__plane_ptr
+
+    def approx_equal(self, other: 'TimsPlane', tolerance: float = 1e-6) -> bool:
+        """Compare to another plane, floating point values may differ by at most tolerance.
+
+        Args:
+            other (TimsPlane): Plane to compare to.
+            tolerance (float): Maximum absolute difference of floating point values.
+
+        Returns:
+            bool: True if all integer fields are equal and all floating point values are within tolerance.
+        """
+        return self.__plane_ptr.approx_equal(other.__plane_ptr, tolerance)
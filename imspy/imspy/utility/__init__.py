@@ -1,2 +1,3 @@
 from .utilities import re_index_indices
 from .sequence import tokenize_unimod_sequence
+from .threads import set_num_threads, get_num_threads
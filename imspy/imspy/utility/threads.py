@@ -0,0 +1,25 @@
+import imspy_connector
+
+ims = imspy_connector
+
+
+def set_num_threads(num_threads: int) -> None:
+    """Set the number of threads of the shared pool used by all calls without an explicit `num_threads`.
+
+    Precedence rules: a call made from within another parallel computation runs on the threads of the caller,
+    an explicit `num_threads` runs on a pool of that size (created once and reused), `num_threads=None` runs on
+    the shared pool configured here, and without a shared pool on the global rayon pool.
+
+    Args:
+        num_threads (int): Number of threads, 0 resets to the global rayon pool.
+    """
+    ims.set_num_threads(num_threads)
+
+
+def get_num_threads() -> int:
+    """Get the number of threads used by calls without an explicit `num_threads`.
+
+    Returns:
+        int: Size of the shared pool if configured, otherwise of the global rayon pool.
+    """
+    return ims.get_num_threads()
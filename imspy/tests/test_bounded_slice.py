@@ -0,0 +1,33 @@
+import numpy as np
+import pytest
+
+from imspy.simulation.utility import get_native_dataset_path
+from imspy.timstof.data import TimsDataset
+
+
+@pytest.fixture(scope="module")
+def dataset():
+    return TimsDataset(get_native_dataset_path(), in_memory=False, use_bruker_sdk=False)
+
+
+def test_bounded_slice_matches_slice(dataset):
+    expected = dataset.get_tims_slice([1] * 4, num_threads=2)
+    bounded = dataset.get_tims_slice([1] * 4, num_threads=2, max_in_flight=1)
+
+    assert len(bounded.frames) == 4
+    for frame, expected_frame in zip(bounded.frames, expected.frames):
+        np.testing.assert_array_equal(frame.tof, expected_frame.tof)
+        np.testing.assert_array_equal(frame.intensity, expected_frame.intensity)
+
+
+def test_bounded_slice_rejects_zero(dataset):
+    with pytest.raises(ValueError):
+        dataset.get_tims_slice([1], max_in_flight=0)
+
+
+def test_slice_batches_cover_all_frames(dataset):
+    frame_ids = [1, 2, 3, 4, 5]
+    batches = list(dataset.iter_tims_slices(frame_ids, batch_size=2, num_threads=2, max_in_flight=1))
+
+    assert [len(batch.frames) for batch in batches] == [2, 2, 1]
+    assert [frame.frame_id for batch in batches for frame in batch.frames] == frame_ids
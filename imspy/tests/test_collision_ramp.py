@@ -0,0 +1,22 @@
+import pytest
+
+from imspy.timstof.collision import TimsTofCollisionEnergyDIA
+
+
+def test_ramp_is_linear_within_windows():
+    ce = TimsTofCollisionEnergyDIA.with_ramp([2, 3], [1, 2], [1, 2], [100, 0], [200, 10], [40.0, 25.0], [20.0, 25.0])
+    assert ce.get_collision_energies_for_frame(2, [100, 150, 200, 300]) == pytest.approx([40.0, 30.0, 20.0, 0.0])
+    assert ce.get_collision_energies_for_frame(3, [5]) == [25.0]
+    assert ce.get_collision_energies_for_frame(4, [5, 6]) == [0.0, 0.0]
+
+
+def test_export_ce_table():
+    table = TimsTofCollisionEnergyDIA.with_ramp([2], [1], [1], [100], [200], [40.0], [20.0]).export_ce_table()
+    assert len(table) == 101
+    assert list(table.scan[:2]) == [100, 101]
+    assert table.collision_energy.iloc[-1] == pytest.approx(20.0)
+
+
+def test_constant_collision_energy():
+    ce = TimsTofCollisionEnergyDIA([2], [1], [1], [100], [200], [30.0])
+    assert ce.get_collision_energies_for_frame(2, [100, 200]) == [30.0, 30.0]
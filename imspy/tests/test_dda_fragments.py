@@ -0,0 +1,42 @@
+import math
+
+import numpy as np
+
+from imspy.timstof.dda import FragmentDDA, fragments_to_dict
+from imspy.timstof.frame import TimsFrame
+
+
+def make_frame(frame_id: int, num_peaks: int) -> TimsFrame:
+    scan = np.arange(num_peaks, dtype=np.int32)
+    return TimsFrame(frame_id, 8, frame_id * 0.1, scan, 1.0 + scan * 0.01, np.full(num_peaks, 1000, dtype=np.int32),
+                     np.linspace(200.0, 900.0, num_peaks), np.ones(num_peaks))
+
+
+def test_fragment_carries_pasef_metadata():
+    fragment = FragmentDDA(2, 7, 30.0, make_frame(2, 3), isolation_mz=650.3, isolation_width=2.0,
+                           scan_begin=100, scan_end=140)
+    assert fragment.isolation_mz == 650.3
+    assert fragment.isolation_width == 2.0
+    assert (fragment.scan_begin, fragment.scan_end) == (100, 140)
+
+
+def test_constructor_defaults_metadata():
+    fragment = FragmentDDA(2, 7, 30.0, make_frame(2, 3))
+    assert math.isnan(fragment.isolation_mz)
+    assert math.isnan(fragment.isolation_width)
+    assert (fragment.scan_begin, fragment.scan_end) == (0, 0)
+
+
+def test_fragments_to_dict():
+    fragments = [
+        FragmentDDA(2, 7, 30.0, make_frame(2, 3), isolation_mz=650.3, isolation_width=2.0, scan_begin=100, scan_end=140),
+        FragmentDDA(3, 8, 42.0, make_frame(3, 5)),
+    ]
+    columns = fragments_to_dict(fragments)
+    np.testing.assert_array_equal(columns["frame_id"], [2, 3])
+    np.testing.assert_array_equal(columns["precursor_id"], [7, 8])
+    np.testing.assert_array_equal(columns["collision_energy"], [30.0, 42.0])
+    np.testing.assert_array_equal(columns["isolation_mz"], [650.3, np.nan])
+    np.testing.assert_array_equal(columns["scan_end"], [140, 0])
+    np.testing.assert_array_equal(columns["num_peaks"], [3, 5])
+    assert len(fragments_to_dict([])["frame_id"]) == 0
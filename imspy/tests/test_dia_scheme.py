@@ -0,0 +1,31 @@
+import pytest
+
+from imspy.simulation.dia_scheme import generate_dia_scheme, validate_dia_scheme
+
+
+@pytest.mark.parametrize("mobility_mz_correlation", [True, False])
+def test_generated_scheme_is_valid(mobility_mz_correlation):
+    windows, cycle = generate_dia_scheme(400.0, 1000.0, 25.0, 1.0, 918, 3, mobility_mz_correlation)
+    assert len(windows) == 25
+    assert list(cycle.frame) == list(range(2, 11))
+    assert set(cycle.window_group) == set(windows.window_group)
+    assert validate_dia_scheme(windows) == []
+    # every group uses all scans of a frame once
+    scans = (windows.scan_end - windows.scan_start + 1).groupby(windows.window_group).sum()
+    assert (scans == 918).all()
+
+
+def test_validate_reports_gap_and_overlap():
+    windows, _ = generate_dia_scheme(400.0, 1000.0, 50.0, 0.0, 100, 2)
+    broken = windows.drop(index=3).reset_index(drop=True)
+    broken.loc[0, 'scan_start'] = broken.loc[1, 'scan_start']
+    issues = validate_dia_scheme(broken)
+    assert any('share scans' in issue for issue in issues)
+    assert any('not isolated' in issue for issue in issues)
+
+
+def test_invalid_parameters_raise():
+    with pytest.raises(ValueError):
+        generate_dia_scheme(1000.0, 400.0)
+    with pytest.raises(ValueError):
+        generate_dia_scheme(window_width=10.0, overlap=10.0)
@@ -0,0 +1,28 @@
+import numpy as np
+
+from imspy.timstof.quadrupole import TimsTofQuadrupoleDIA
+
+
+def make_transmission() -> TimsTofQuadrupoleDIA:
+    return TimsTofQuadrupoleDIA(np.array([2, 3]), np.array([1, 2]), np.array([1, 1, 2]), np.array([0, 400, 0]),
+                                np.array([399, 899, 899]), np.array([500.0, 700.0, 510.0]),
+                                np.array([25.0, 25.0, 50.0]))
+
+
+def test_isolation_window_for():
+    transmission = make_transmission()
+    assert transmission.isolation_window_for(1, 450) == (700.0, 25.0)
+    assert transmission.isolation_window_for(1, 950) is None
+
+
+def test_window_groups_for_mz():
+    transmission = make_transmission()
+    assert transmission.window_groups_for_mz(505.0, 100) == [1, 2]
+    assert transmission.window_groups_for_mz(505.0, 500) == [2]
+    assert transmission.window_groups_for_mz(900.0, 100) == []
+
+
+def test_all_windows():
+    windows = make_transmission().all_windows()
+    assert list(windows.window_group) == [1, 1, 2]
+    assert list(windows.scan_end) == [399, 899, 899]
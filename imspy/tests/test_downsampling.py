@@ -0,0 +1,45 @@
+import numpy as np
+import pytest
+
+from imspy.timstof.frame import TimsFrame
+from imspy.timstof.slice import TimsSlice
+
+
+def make_frame(frame_id: int) -> TimsFrame:
+    rng = np.random.default_rng(frame_id)
+    scan = np.sort(rng.integers(0, 40, 300)).astype(np.int32)
+    mz = rng.uniform(100.0, 1700.0, 300)
+    order = np.lexsort((mz, scan))
+    scan, mz = scan[order], mz[order]
+    return TimsFrame(frame_id, 0, frame_id * 0.1, scan, 1.6 - scan * 0.01, (mz * 100).astype(np.int32), mz,
+                     rng.integers(1, 1000, 300).astype(np.float64))
+
+
+def test_to_resolution_preserves_intensity():
+    frame = make_frame(1)
+    for resolution in [0, 1, 2]:
+        binned = frame.to_resolution(resolution)
+        assert binned.intensity.sum() == frame.intensity.sum()
+        assert len(binned.intensity) <= len(frame.intensity)
+
+
+def test_slice_to_resolution_bins_every_frame():
+    frames = [make_frame(1), make_frame(2)]
+    binned = TimsSlice.from_frames(frames).to_resolution(1, num_threads=2)
+
+    for frame, binned_frame in zip(frames, binned.frames):
+        assert binned_frame.intensity.sum() == frame.intensity.sum()
+        np.testing.assert_array_equal(binned_frame.mz, frame.to_resolution(1).mz)
+
+
+def test_downsample_scans_preserves_intensity():
+    frame = make_frame(3)
+    merged = frame.downsample_scans(4)
+
+    assert merged.intensity.sum() == frame.intensity.sum()
+    np.testing.assert_array_equal(np.unique(merged.scan), np.unique(frame.scan // 4))
+    for scan in np.unique(merged.scan):
+        assert len(np.unique(merged.mobility[merged.scan == scan])) == 1
+
+    with pytest.raises(ValueError):
+        frame.downsample_scans(0)
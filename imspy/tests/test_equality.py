@@ -0,0 +1,34 @@
+import pickle
+
+import numpy as np
+
+from imspy.data.spectrum import MzSpectrum
+from imspy.timstof.frame import TimsFrame
+
+
+def make_frame(mz: float = 500.0) -> TimsFrame:
+    return TimsFrame(1, 0, 10.0, np.array([1, 2], dtype=np.int32), np.array([1.2, 1.1]),
+                     np.array([1000, 1001], dtype=np.int32), np.array([mz, 600.0]), np.array([100.0, 50.0]))
+
+
+def test_spectrum_equality():
+    spectrum = MzSpectrum(np.array([100.0, 200.0]), np.array([10.0, 20.0]))
+    assert len(spectrum) == 2
+    assert spectrum == MzSpectrum(np.array([100.0, 200.0]), np.array([10.0, 20.0]))
+    assert spectrum != MzSpectrum(np.array([100.0, 200.0]), np.array([10.0, 21.0]))
+    assert spectrum != "not a spectrum"
+
+
+def test_frame_equality():
+    frame = make_frame()
+    assert len(frame) == 2
+    assert frame == pickle.loads(pickle.dumps(frame))
+    assert frame != make_frame(500.0001)
+    assert frame.approx_equal(make_frame(500.0001), tolerance=1e-3)
+    assert not frame.approx_equal(make_frame(500.1), tolerance=1e-3)
+
+
+def test_connector_repr():
+    frame = make_frame().get_py_ptr()
+    assert repr(frame) == "TimsFrame(frame_id=1, ms_type=Precursor, retention_time=10.000, peaks=2, mz_range=(500.0000, 600.0000))"
+    assert repr(MzSpectrum(np.array([]), np.array([])).get_py_ptr()) == "MzSpectrum(peaks=0, mz_range=None)"
@@ -0,0 +1,30 @@
+import pytest
+
+from imspy.chemistry.sum_formula import SumFormula, formula_mass, parse_formula, to_hill_notation
+
+
+@pytest.mark.parametrize("formula", ["C6H12O6", "H2O", "CH-2N2O3PS", "C-6[13C]6H-4[2H]4N-2[15N]2", "ClNa"])
+def test_formula_round_trip(formula):
+    composition = parse_formula(formula)
+    assert to_hill_notation(composition) == formula
+    assert parse_formula(to_hill_notation(composition)) == composition
+
+
+def test_formula_losses_and_isotopes():
+    assert parse_formula("H(-1) N(-1) O") == {"H": -1, "N": -1, "O": 1}
+    assert parse_formula("C-2[13C]2") == {"C": -2, "13C": 2}
+    assert formula_mass("H-2O-1") == pytest.approx(-18.010565, abs=1e-5)
+    assert formula_mass("C6H12O6") == pytest.approx(180.063388, abs=1e-5)
+    assert formula_mass("C6H12O6", average=True) == pytest.approx(180.156, abs=1e-2)
+
+
+@pytest.mark.parametrize("formula", ["Xy2", "[14C]", "C6+H", "c6"])
+def test_invalid_formula(formula):
+    with pytest.raises(ValueError):
+        parse_formula(formula)
+
+
+@pytest.mark.parametrize("formula", ["Xy2", "C6+H"])
+def test_invalid_sum_formula(formula):
+    with pytest.raises(ValueError):
+        SumFormula(formula)
@@ -0,0 +1,33 @@
+import numpy as np
+import pytest
+
+from imspy.timstof.frame import TimsFrame
+
+
+def scrambled_arrays():
+    rng = np.random.default_rng(3)
+    scan = rng.integers(0, 50, 200).astype(np.int32)
+    mz = rng.uniform(100.0, 1700.0, 200)
+    return scan, 1.6 - scan * 0.01, (mz * 100).astype(np.int32), mz, rng.uniform(0.0, 100.0, 200)
+
+
+def test_scrambled_input_raises():
+    with pytest.raises(ValueError, match="not sorted"):
+        TimsFrame(1, 0, 10.0, *scrambled_arrays())
+
+
+def test_repair_scrambled_input():
+    scan, mobility, tof, mz, intensity = scrambled_arrays()
+    frame = TimsFrame(1, 0, 10.0, scan, mobility, tof, mz, intensity, validate=False).sort_by_scan_then_mz()
+    frame.validate()
+
+    order = np.lexsort((mz, scan))
+    np.testing.assert_array_equal(frame.scan, scan[order])
+    np.testing.assert_array_equal(frame.mz, mz[order])
+    np.testing.assert_array_equal(frame.intensity, intensity[order])
+
+
+def test_negative_intensity_raises():
+    with pytest.raises(ValueError, match="non-negative"):
+        TimsFrame(1, 0, 10.0, np.array([1, 2], dtype=np.int32), np.array([1.2, 1.1]),
+                  np.array([1, 2], dtype=np.int32), np.array([100.0, 200.0]), np.array([1.0, -1.0]))
@@ -0,0 +1,79 @@
+import _thread
+import threading
+import time
+
+import numpy as np
+import pytest
+
+from imspy.simulation.utility import get_native_dataset_path
+from imspy.timstof.data import TimsDataset
+
+# NATIVE.d holds a single frame, reading it repeatedly keeps the rust side busy long enough
+FRAME_IDS = [1] * 64
+
+
+@pytest.fixture(scope="module")
+def dataset():
+    return TimsDataset(get_native_dataset_path(), in_memory=False, use_bruker_sdk=False)
+
+
+class Ticker(threading.Thread):
+    """Records a timestamp every millisecond, which is only possible while no other thread holds the GIL."""
+
+    def __init__(self):
+        super().__init__(daemon=True)
+        self.ticks = []
+        self.stopped = threading.Event()
+
+    def run(self):
+        while not self.stopped.is_set():
+            self.ticks.append(time.perf_counter())
+            time.sleep(0.001)
+
+
+def test_python_threads_run_during_slice_reads(dataset):
+    ticker = Ticker()
+    ticker.start()
+    start = time.perf_counter()
+    dataset.get_tims_slice(FRAME_IDS, num_threads=1)
+    end = time.perf_counter()
+    ticker.stopped.set()
+    ticker.join()
+
+    # with the GIL held, at most one tick could slip in between taking the start time and entering rust
+    assert sum(start < tick < end for tick in ticker.ticks) > 1
+
+
+def test_concurrent_reads(dataset):
+    expected = dataset.get_tims_frame(1)
+    results = [None, None]
+
+    def read(index):
+        results[index] = dataset.get_tims_slice(FRAME_IDS, num_threads=2)
+
+    readers = [threading.Thread(target=read, args=(index,)) for index in range(2)]
+    for reader in readers:
+        reader.start()
+    for reader in readers:
+        reader.join()
+
+    for tims_slice in results:
+        frames = tims_slice.frames
+        assert len(frames) == len(FRAME_IDS)
+        np.testing.assert_array_equal(frames[-1].intensity, expected.intensity)
+
+
+def test_keyboard_interrupt_between_chunks(dataset):
+    # describe reads 256 frames per chunk, the interrupt arrives while the first chunk is read
+    timer = threading.Timer(0.01, _thread.interrupt_main)
+    timer.start()
+    start = time.perf_counter()
+    with pytest.raises(KeyboardInterrupt):
+        dataset.describe(np.array(FRAME_IDS * 64), num_threads=1)
+    timer.join()
+
+    # a full describe takes 16 chunks, the interrupt is raised after the chunk it arrived in
+    interrupted = time.perf_counter() - start
+    start = time.perf_counter()
+    dataset.describe(np.array(FRAME_IDS * 64), num_threads=1)
+    assert interrupted < time.perf_counter() - start
@@ -0,0 +1,25 @@
+import numpy as np
+import pytest
+
+from imspy.chemistry.mobility import estimate_im_peaks
+
+
+def test_estimate_im_peaks():
+    mobility = np.linspace(1.3, 0.7, 61)
+    gaussian = 1e4 * np.exp(-(mobility - 1.003) ** 2 / (2 * 0.02 ** 2))
+    truncated = np.where(mobility >= 1.25, 100.0, 0.0) + np.linspace(0.0, 50.0, 61)[::-1]
+
+    peaks = estimate_im_peaks([mobility, mobility, mobility], [gaussian, truncated, np.zeros(61)], num_threads=2)
+
+    assert len(peaks) == 3
+    assert peaks.apex_mobility[0] == pytest.approx(1.003, abs=1e-3)
+    assert peaks.fwhm[0] == pytest.approx(2.3548 * 0.02, abs=2e-3)
+    assert peaks.asymmetry[0] == pytest.approx(1.0, abs=0.1)
+    assert not peaks.truncated[0]
+    assert peaks.truncated[1]
+    assert np.isnan(peaks.apex_mobility[2]) and not peaks.truncated[2]
+
+
+def test_estimate_im_peaks_rejects_mismatched_lengths():
+    with pytest.raises(ValueError):
+        estimate_im_peaks([np.arange(3.0)], [np.arange(2.0)])
@@ -0,0 +1,49 @@
+import numpy as np
+import pytest
+
+from imspy.data.spectrum import MzSpectrum
+from imspy.timstof.frame import TimsFrame
+
+
+def make_noisy_frame(rng: np.random.Generator):
+    """Uniform noise of 1 to 100 in 20 scans with 3 signal peaks each, the last scan holds only one signal peak."""
+    scan = np.concatenate([np.repeat(np.arange(10, 30, dtype=np.int32), 53), np.array([30], dtype=np.int32)])
+    intensity = rng.uniform(1.0, 100.0, scan.size)
+    is_signal = np.zeros(scan.size, dtype=bool)
+    is_signal[np.arange(50, 20 * 53, 53)[:, None] + np.arange(3)] = True
+    is_signal[-1] = True
+    intensity[is_signal] = rng.uniform(5000.0, 10000.0, is_signal.sum())
+    mz = rng.uniform(100.0, 1700.0, scan.size)
+    frame = TimsFrame(1, 0, 10.0, scan, 1.6 - scan * 0.01, (mz * 100).astype(np.int32), mz, intensity)
+    return frame, intensity[is_signal]
+
+
+@pytest.mark.parametrize("method", ["mad", "lowest_quartile"])
+def test_filter_snr_removes_uniform_noise(method):
+    frame, signal = make_noisy_frame(np.random.default_rng(3))
+
+    noise = frame.estimate_noise(method)
+    assert noise.shape == (21,)
+    assert np.all((noise > 5.0) & (noise < 40.0))
+
+    filtered = frame.filter_snr(30.0, method)
+    np.testing.assert_array_equal(np.sort(filtered.intensity), np.sort(signal))
+
+
+def test_spectrum_signal_to_noise():
+    rng = np.random.default_rng(5)
+    mz = np.sort(rng.uniform(100.0, 1000.0, 300))
+    intensity = rng.uniform(1.0, 100.0, 300)
+    intensity[::50] = 10000.0
+    snr = MzSpectrum(mz, intensity).signal_to_noise(100.0)
+    assert snr.shape == mz.shape
+    assert np.all(snr[::50] > 100.0)
+    assert np.all(np.delete(snr, np.s_[::50]) < 10.0)
+
+
+def test_unknown_noise_estimator_raises():
+    frame, _ = make_noisy_frame(np.random.default_rng(1))
+    with pytest.raises(ValueError):
+        frame.estimate_noise("rms")
+    with pytest.raises(ValueError):
+        frame.filter_snr(3.0, "rms")
@@ -0,0 +1,41 @@
+import numpy as np
+import pytest
+
+from imspy.timstof.frame import TimsFrame
+from imspy.timstof.slice import TimsSlice
+
+
+def make_frame(frame_id: int, num_peaks: int) -> TimsFrame:
+    scan = np.repeat(np.arange(num_peaks // 4, dtype=np.int32), 4)[:num_peaks]
+    intensity = np.arange(num_peaks, dtype=np.float64) * frame_id
+    return TimsFrame(frame_id, 0, frame_id * 0.1, scan, 1.6 - scan * 0.01, np.arange(num_peaks, dtype=np.int32),
+                     100.0 + np.arange(num_peaks) * 0.5, intensity)
+
+
+def test_normalize_methods():
+    frame = make_frame(3, 40)
+
+    np.testing.assert_allclose(frame.normalize('tic', 1e4).intensity.sum(), 1e4)
+    assert frame.normalize('base_peak').intensity.max() == pytest.approx(1.0)
+    np.testing.assert_allclose(np.median(frame.normalize('quantile', 5.0).intensity[1:]), 5.0)
+
+    per_scan = frame.normalize('per_scan_tic', 2.0)
+    np.testing.assert_allclose(np.bincount(per_scan.scan, weights=per_scan.intensity), 2.0)
+    np.testing.assert_array_equal(per_scan.tof, frame.tof)
+    np.testing.assert_array_equal(per_scan.scan, frame.scan)
+
+
+def test_normalize_keeps_empty_frame_and_rejects_unknown_method():
+    empty = make_frame(0, 8)
+    np.testing.assert_array_equal(empty.normalize('tic').intensity, np.zeros(8))
+
+    with pytest.raises(ValueError, match="unknown normalization method"):
+        empty.normalize('z_score')
+
+
+def test_normalize_frames():
+    frames = [make_frame(frame_id, 20 * frame_id) for frame_id in range(1, 6)]
+    normalized = TimsSlice.from_frames(frames).normalize_frames('base_peak', 100.0, num_threads=2)
+
+    for frame in normalized.frames:
+        assert frame.intensity.max() == pytest.approx(100.0)
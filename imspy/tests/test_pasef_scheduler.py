@@ -0,0 +1,25 @@
+import pandas as pd
+import pytest
+
+from imspy.simulation.scheduler import PasefScheduler
+
+
+def make_run(num_frames: int):
+    frames = pd.DataFrame({'frame_id': range(1, num_frames + 1), 'retention_time': range(1, num_frames + 1)})
+    candidates = pd.concat([pd.DataFrame({
+        'frame_id': frame_id, 'peptide_id': [1, 2], 'mz': [500.0, 600.0], 'mobility': [1.1, 0.9],
+        'scan_start': [100, 300], 'scan_end': [120, 320], 'intensity': [1e4, 1e3]}) for frame_id in frames.frame_id])
+    return frames, candidates
+
+
+def test_exclusion_alternates_precursors():
+    scheduler = PasefScheduler(top_n=1, ms2_frames_per_cycle=1, intensity_threshold=0.0, exclusion_duration=3.0)
+    schedule = scheduler.schedule(*make_run(6))
+    assert list(schedule.frame_id) == [2, 4, 6]
+    assert list(schedule.peptide_id) == [1, 2, 1]
+
+
+def test_unknown_candidate_frame():
+    frames, candidates = make_run(2)
+    with pytest.raises(ValueError, match="unknown frame"):
+        PasefScheduler().schedule(frames.iloc[:1], candidates)
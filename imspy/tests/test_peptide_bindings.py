@@ -0,0 +1,56 @@
+import imspy_connector
+import numpy as np
+import pytest
+
+from imspy.data.peptide import PeptideIon, PeptideProductIon, PeptideSequence
+from imspy.simulation.utility import calculate_b_y_fragment_mz, calculate_b_y_ion_series_ims
+
+
+def test_b_y_ion_series_matches_python_reference():
+    sequence = "PEPTIDEK"
+    modifications = np.array([0.0, 0.0, 0.0, 79.966331, 0.0, 0.0, 0.0, 0.0])
+    b_ions, y_ions = calculate_b_y_ion_series_ims(sequence, modifications, charge=2)
+
+    assert len(b_ions) == len(y_ions) == len(sequence) - 1
+    for i in range(1, len(sequence)):
+        expected_b = calculate_b_y_fragment_mz(sequence[:i], modifications[:i], is_y=False, charge=2)
+        expected_y = calculate_b_y_fragment_mz(sequence[i:], modifications[i:], is_y=True, charge=2)
+        assert b_ions[i - 1] == pytest.approx(expected_b, abs=1e-4)
+        assert y_ions[i - 1] == pytest.approx(expected_y, abs=1e-4)
+
+
+def test_product_ions_of_sequence():
+    b_ions, y_ions = PeptideSequence("PEPTIDEK").calculate_product_ion_series(charge=1, fragment_type='b')
+    assert [ion.kind for ion in b_ions] == ['b'] * 7
+    assert [ion.kind for ion in y_ions] == ['y'] * 7
+    assert b_ions[0].sequence == "P"
+    assert b_ions[0].mono_isotopic_mass == pytest.approx(PeptideProductIon('b', "P").mono_isotopic_mass)
+
+
+def test_invalid_fragment_type_raises():
+    with pytest.raises(ValueError):
+        imspy_connector.py_peptide.PyPeptideProductIon('q', "PEPTIDE", 1, 1.0)
+    with pytest.raises(ValueError):
+        PeptideSequence("PEPTIDE").get_py_ptr().calculate_product_ion_series(1, 'q')
+
+
+def test_isotope_distributions_are_arrays():
+    ion = PeptideIon("PEPTIDEK", 2, 1.0)
+    mz, abundance = ion.isotope_distribution()
+    assert isinstance(mz, np.ndarray) and isinstance(abundance, np.ndarray)
+    assert len(mz) == len(abundance) > 1
+    assert mz[np.argmax(abundance)] == pytest.approx(ion.mz, abs=1e-3)
+    assert ion.mono_isotopic_mass == pytest.approx(ion.sequence.mono_isotopic_mass)
+
+    mz, abundance = PeptideProductIon('y', "TIDEK", charge=1).isotope_distribution()
+    assert len(mz) == len(abundance) > 1
+
+
+def test_fragment_ion_labels():
+    ions = imspy_connector.py_chemistry.calculate_b_y_ion_series("PEPTIDE", fragment_types=["C", "z"], max_fragment_charge=2)
+    labels = [label for label, _ in ions]
+    assert labels[:7] == ["c1+1", "c2+1", "c3+1", "c4+1", "c5+1", "c6+1", "z1+1"]
+    assert labels[-1] == "z6+2"
+    assert [label for label, _ in imspy_connector.py_chemistry.calculate_b_y_ion_series("PEPTIDE")][::6] == ["b1+1", "y1+1"]
+    with pytest.raises(ValueError):
+        imspy_connector.py_chemistry.calculate_b_y_ion_series("PEPTIDE", fragment_types=["q"])
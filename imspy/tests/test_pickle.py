@@ -0,0 +1,55 @@
+import copy
+import pickle
+
+import numpy as np
+import pytest
+
+from imspy.data.spectrum import MzSpectrum, TimsSpectrum
+from imspy.simulation.utility import get_native_dataset_path
+from imspy.timstof.data import TimsDataset
+from imspy.timstof.frame import TimsFrame
+
+NUM_PEAKS = 1_200_000
+
+
+@pytest.fixture(scope="module")
+def large_frame():
+    rng = np.random.default_rng(7)
+    scan = rng.integers(0, 900, NUM_PEAKS).astype(np.int32)
+    mz = rng.uniform(100.0, 1700.0, NUM_PEAKS)
+    order = np.lexsort((mz, scan))
+    scan, mz = scan[order], mz[order]
+    return TimsFrame(3, 9, 42.5, scan, 1.6 - scan * 0.001, rng.integers(0, 400_000, NUM_PEAKS).astype(np.int32),
+                     mz, rng.uniform(0.0, 1e4, NUM_PEAKS))
+
+
+@pytest.mark.parametrize("protocol", [2, pickle.HIGHEST_PROTOCOL])
+def test_large_frame_round_trip(large_frame, protocol):
+    restored = pickle.loads(pickle.dumps(large_frame, protocol=protocol))
+    assert len(restored) == NUM_PEAKS
+    assert restored == large_frame
+    assert pickle.loads(pickle.dumps(large_frame.get_py_ptr(), protocol=protocol)) == large_frame.get_py_ptr()
+
+
+def test_large_frame_copy(large_frame):
+    assert copy.copy(large_frame) == large_frame
+    assert copy.deepcopy(large_frame) == large_frame
+    assert copy.deepcopy(large_frame.vectorized(2)) == large_frame.vectorized(2)
+
+
+def test_spectrum_round_trip():
+    spectrum = MzSpectrum(np.array([100.0, 200.0]), np.array([10.0, 20.0]))
+    assert pickle.loads(pickle.dumps(spectrum)) == spectrum
+    assert copy.deepcopy(spectrum) == spectrum
+
+    tims_spectrum = TimsSpectrum(1, 50, 10.0, 1.1, 0, np.array([1, 2], dtype=np.int32), np.array([100.0, 100.1]),
+                                 np.array([5.0, 7.0]))
+    assert pickle.loads(pickle.dumps(tims_spectrum)) == tims_spectrum
+
+
+def test_dataset_is_not_picklable():
+    dataset = TimsDataset(get_native_dataset_path(), in_memory=False, use_bruker_sdk=False)
+    with pytest.raises(TypeError, match="cannot be pickled"):
+        pickle.dumps(dataset)
+    with pytest.raises(TypeError, match="cannot be pickled"):
+        copy.deepcopy(dataset)
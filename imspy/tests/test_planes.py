@@ -0,0 +1,32 @@
+import numpy as np
+
+from imspy.timstof.frame import TimsFrame
+from imspy.timstof.slice import TimsSlice
+
+
+def make_frame(frame_id: int) -> TimsFrame:
+    scan = np.array([1, 1, 2, 3], dtype=np.int32)
+    return TimsFrame(frame_id, 0, frame_id * 1.5, scan, 1.3 - scan * 0.1, np.array([10, 20, 11, 12], dtype=np.int32),
+                     np.array([500.001, 600.0, 499.999, 500.0]), np.array([1.0, 2.0, 4.0, 8.0]) * frame_id)
+
+
+def test_get_planes():
+    slice = TimsSlice.from_frames([make_frame(frame_id) for frame_id in range(1, 4)])
+    planes = slice.get_planes([500.0, 600.0, 900.0], ppm=10.0)
+
+    assert len(planes) == 3
+    np.testing.assert_array_equal(planes[0].frame_ids, np.repeat([1, 2, 3], 3))
+    np.testing.assert_array_equal(planes[0].scans, np.tile([1, 2, 3], 3))
+    assert planes[0].intensities.sum() == 13.0 * 6
+    assert len(planes[1]) == 3 and len(planes[2]) == 0
+
+
+def test_plane_to_dense():
+    slice = TimsSlice.from_frames([make_frame(frame_id) for frame_id in range(1, 4)])
+    plane = slice.get_planes([500.0])[0]
+
+    matrix, rt_axis, im_axis = plane.to_dense(rt_bins=3, im_bins=3)
+    assert matrix.shape == (3, 3) and len(rt_axis) == 3 and len(im_axis) == 3
+    assert matrix.sum() == plane.intensities.sum()
+    # the first frame at the lowest retention time, scan 1 has the highest mobility
+    np.testing.assert_array_equal(matrix[0], [8.0, 4.0, 1.0])
@@ -0,0 +1,24 @@
+import numpy as np
+import pytest
+
+from imspy.timstof.alignment import RtWarp, align_retention_times
+
+
+def test_rt_warp_map_and_inverse():
+    warp = RtWarp(np.array([0.0, 100.0, 200.0]), np.array([5.0, 110.0, 230.0]))
+
+    np.testing.assert_allclose(warp.map(np.array([-10.0, 50.0, 300.0])), [-5.0, 57.5, 330.0])
+    np.testing.assert_allclose(warp.inverse(warp.map(np.linspace(0.0, 200.0, 11))), np.linspace(0.0, 200.0, 11))
+
+    with pytest.raises(ValueError):
+        RtWarp(np.array([0.0, 1.0]), np.array([5.0, 5.0]))
+
+
+def test_align_retention_times():
+    rt_a = np.arange(100) * 10.0
+    mz_a = 400.0 + np.arange(100) * 7.3
+
+    warp = align_retention_times(rt_a, mz_a, rt_a * 1.1 + 20.0, mz_a, ppm=10.0)
+
+    assert np.all(np.diff(warp.rt_b) > 0)
+    np.testing.assert_allclose(warp.map(np.array([500.0])), [570.0])
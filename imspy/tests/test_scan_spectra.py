@@ -0,0 +1,37 @@
+import numpy as np
+import pytest
+
+from imspy.simulation.utility import get_native_dataset_path
+from imspy.timstof.data import TimsDataset
+
+
+@pytest.fixture(scope="module", params=[False, True])
+def dataset(request):
+    return TimsDataset(get_native_dataset_path(), in_memory=request.param, use_bruker_sdk=False)
+
+
+def test_spectrum_matches_exploded_frame(dataset):
+    frame = dataset.get_tims_frame(1)
+    scans = np.unique(frame.scan)
+    scan = int(scans[len(scans) // 2])
+
+    spectrum = dataset.get_spectrum(1, scan)
+    in_scan = frame.scan == scan
+    assert spectrum.scan == scan
+    np.testing.assert_array_equal(spectrum.index, frame.tof[in_scan])
+    np.testing.assert_array_equal(spectrum.mz, frame.mz[in_scan])
+    np.testing.assert_array_equal(spectrum.intensity, frame.intensity[in_scan])
+
+
+def test_spectra_of_scan_range(dataset):
+    frame = dataset.get_tims_frame(1)
+    spectra = dataset.get_spectra(1, 100, 200)
+    assert [s.scan for s in spectra] == [s for s in np.unique(frame.scan) if 100 <= s < 200]
+    assert len(dataset.get_spectra(1)) == len(np.unique(frame.scan))
+    assert all(len(s) >= 10 for s in dataset.get_spectra(1, 100, 200, min_peaks=10))
+
+
+def test_missing_scans_and_frames(dataset):
+    assert dataset.get_spectrum(1, 100_000) is None
+    assert dataset.get_spectra(0) == []
+    assert dataset.get_spectra(100_000, 0, 10) == []
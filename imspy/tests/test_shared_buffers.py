@@ -0,0 +1,105 @@
+import gc
+
+import numpy as np
+import pytest
+
+from imspy.data.spectrum import MzSpectrum
+from imspy.timstof.frame import TimsFrame
+
+
+def buffer_address(array: np.ndarray) -> int:
+    return array.__array_interface__["data"][0]
+
+
+def make_frame(num_peaks: int = 1_000) -> TimsFrame:
+    scan = np.arange(num_peaks, dtype=np.int32) // 10
+    return TimsFrame(1, 0, 10.0, scan, 1.6 - scan * 0.001, np.arange(num_peaks, dtype=np.int32),
+                     np.linspace(100.0, 1700.0, num_peaks), np.full(num_peaks, 50.0))
+
+
+def test_getters_share_the_frame_buffer():
+    frame = make_frame()
+    ptr = frame.get_py_ptr()
+    assert buffer_address(ptr.mz) == buffer_address(ptr.mz)
+    assert buffer_address(frame.intensity) == buffer_address(frame.intensity)
+    assert ptr.mz.base is ptr
+
+
+def test_arrays_are_read_only():
+    frame = make_frame()
+    assert not frame.mz.flags.writeable
+    with pytest.raises(ValueError):
+        frame.intensity[0] = 1.0
+
+    writable = np.copy(frame.intensity)
+    writable[0] = 1.0
+    assert frame.intensity[0] == 50.0
+
+
+def test_arrays_outlive_the_frame():
+    frame = make_frame()
+    mz, expected = frame.mz, np.linspace(100.0, 1700.0, 1_000)
+    del frame
+    gc.collect()
+    # allocate enough to reuse the memory of the frame, had it been freed
+    garbage = [np.ones(1_000) for _ in range(100)]
+    np.testing.assert_array_equal(mz, expected)
+    assert len(garbage) == 100
+
+    spectrum_mz = MzSpectrum(np.array([100.0, 200.0]), np.array([1.0, 2.0])).mz
+    gc.collect()
+    np.testing.assert_array_equal(spectrum_mz, [100.0, 200.0])
+
+
+def test_tof_setter_writes_in_place():
+    frame = make_frame(3)
+    tof = frame.tof
+    frame.tof = np.array([7, 8, 9], dtype=np.int32)
+    np.testing.assert_array_equal(tof, [7, 8, 9])
+    assert buffer_address(tof) == buffer_address(frame.tof)
+
+    frame.tof = frame.tof
+    np.testing.assert_array_equal(frame.tof, [7, 8, 9])
+    with pytest.raises(ValueError, match="one per peak"):
+        frame.tof = np.array([1], dtype=np.int32)
+
+
+def test_csr_indptr_is_shared():
+    csr = make_frame().to_csr(resolution=2)
+    ptr = csr.get_py_ptr()
+    assert buffer_address(ptr.indptr) == buffer_address(ptr.indptr)
+    assert ptr.indptr.base is ptr
+    assert ptr.indptr.dtype == np.int64
+    assert not csr.indptr.flags.writeable
+    np.testing.assert_array_equal(np.diff(csr.indptr), np.full(100, 10))
+
+
+def arrow_address(column) -> int:
+    # buffer 0 is the validity bitmap, buffer 1 the values
+    return column.buffers()[1].address
+
+
+def test_frame_to_arrow_shares_the_frame_buffer():
+    pytest.importorskip("pyarrow")
+    frame = make_frame()
+    batch = frame.to_arrow()
+    assert arrow_address(batch.column("mz")) == buffer_address(frame.mz)
+    assert arrow_address(batch.column("intensity")) == buffer_address(frame.intensity)
+    assert arrow_address(batch.column("scan")) == buffer_address(frame.scan)
+
+    # the batch keeps the frame buffers alive
+    del frame
+    gc.collect()
+    np.testing.assert_array_equal(batch.column("mz").to_numpy(), np.linspace(100.0, 1700.0, 1_000))
+
+
+def test_slice_to_arrow_shares_the_slice_buffers():
+    pytest.importorskip("pyarrow")
+    from imspy.timstof.slice import TimsSlice
+
+    tims_slice = TimsSlice.from_frames([make_frame(10), make_frame(20)])
+    first, second = tims_slice.to_arrow(), tims_slice.to_arrow()
+    assert first.num_rows == 30
+    for column in ["mz", "intensity", "mobility", "tof"]:
+        for a, b in zip(first.column(column).chunks, second.column(column).chunks):
+            assert arrow_address(a) == arrow_address(b)
@@ -0,0 +1,31 @@
+import numpy as np
+
+from imspy.timstof.frame import TimsFrame
+from imspy.timstof.slice import TimsSlice
+
+
+def make_frame(frame_id: int, ms_type: int, num_peaks: int) -> TimsFrame:
+    scan = np.arange(num_peaks, dtype=np.int32)
+    return TimsFrame(frame_id, ms_type, frame_id * 0.1, scan, 1.6 - scan * 0.001, scan * 3,
+                     100.0 + scan * 0.5, np.arange(num_peaks, dtype=np.float64))
+
+
+def test_to_arrays_matches_frames():
+    frames = [make_frame(frame_id, 0 if frame_id % 2 else 9, 50 * frame_id) for frame_id in range(1, 9)]
+    columns = TimsSlice.from_frames(frames).to_arrays(num_threads=3)
+
+    assert list(columns) == ['frame', 'scan', 'tof', 'retention_time', 'mobility', 'mz', 'intensity']
+    assert columns['frame'].dtype == np.uint32 and columns['mz'].dtype == np.float64
+    np.testing.assert_array_equal(columns['frame'], np.concatenate([np.full(len(f.mz), f.frame_id) for f in frames]))
+    np.testing.assert_array_equal(columns['mz'], np.concatenate([f.mz for f in frames]))
+    np.testing.assert_array_equal(columns['retention_time'],
+                                  np.concatenate([np.full(len(f.mz), f.retention_time) for f in frames]))
+
+
+def test_to_arrays_filters_while_flattening():
+    frames = [make_frame(frame_id, 0 if frame_id % 2 else 9, 100) for frame_id in range(1, 5)]
+    columns = TimsSlice.from_frames(frames).to_arrays(ms_type=0, intensity_min=90.0)
+
+    np.testing.assert_array_equal(columns['frame'], np.repeat([1, 3], 10))
+    np.testing.assert_array_equal(columns['intensity'], np.tile(np.arange(90.0, 100.0), 2))
+    assert len(TimsSlice.from_frames(frames).to_arrays(ms_type=8)['mz']) == 0
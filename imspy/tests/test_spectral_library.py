@@ -0,0 +1,65 @@
+import sqlite3
+
+import pandas as pd
+import pytest
+
+from imspy.simulation.handle import TimsTofSyntheticsDataHandleRust
+
+
+@pytest.fixture
+def synthetics_db(tmp_path):
+    path = str(tmp_path / "synthetic_data.db")
+    connection = sqlite3.connect(path)
+    connection.executescript("""
+        CREATE TABLE peptides (
+            protein_id INTEGER, peptide_id INTEGER, sequence TEXT, proteins TEXT, decoy BOOLEAN,
+            missed_cleavages INTEGER, n_term BOOLEAN, c_term BOOLEAN, monoisotopic_mass REAL,
+            retention_time REAL, events REAL, frame_occurrence_start INTEGER, frame_occurrence_end INTEGER,
+            frame_start INTEGER, frame_end INTEGER, frame_occurrence TEXT, frame_abundance TEXT
+        );
+        INSERT INTO peptides VALUES
+            (0, 1, 'PEPC[UNIMOD:4]IDEK', 'P1', 0, 0, 0, 0, 987.43, 12.5, 100.0, 0, 0, 1, 3, '[1, 2, 3]', '[0.25, 0.5, 0.25]'),
+            (0, 2, 'KEDIC[UNIMOD:4]PEP', 'P1', 1, 0, 0, 0, 987.43, 14.0, 100.0, 0, 0, 1, 3, '[1, 2, 3]', '[0.25, 0.5, 0.25]');
+        CREATE TABLE ions (
+            ion_id INTEGER, peptide_id INTEGER, sequence TEXT, charge INTEGER, mz REAL,
+            relative_abundance REAL, inv_mobility_gru_predictor REAL, inv_mobility_gru_predictor_std REAL,
+            simulated_spectrum TEXT, scan_occurrence TEXT, scan_abundance TEXT
+        );
+        INSERT INTO ions VALUES
+            (1, 1, 'PEPC[UNIMOD:4]IDEK', 2, 494.72, 1.0, 1.02, 0.0, '', '[]', '[]'),
+            (2, 2, 'KEDIC[UNIMOD:4]PEP', 2, 494.72, 1.0, 1.01, 0.0, '', '[]', '[]');
+        CREATE TABLE fragment_ions (
+            peptide_id INTEGER, ion_id INTEGER, collision_energy REAL, charge INTEGER, indices TEXT, `values` TEXT
+        );
+        INSERT INTO fragment_ions VALUES
+            (1, 1, 0.3, 2, '[1, 2, 30]', '[0.2, 1.0, 0.4]'),
+            (2, 2, 0.3, 2, '[3]', '[1.0]');
+    """)
+    connection.close()
+    return path
+
+
+def test_export_diann_library(synthetics_db, tmp_path):
+    handle = TimsTofSyntheticsDataHandleRust(synthetics_db)
+    path = str(tmp_path / "library.tsv")
+
+    assert handle.export_spectral_library(path) == 1
+    library = pd.read_csv(path, sep="\t")
+
+    assert list(library.columns[:3]) == ["ModifiedPeptide", "PrecursorMz", "PrecursorCharge"]
+    assert len(library) == 3 and (library.ModifiedPeptide == "PEPC(UniMod:4)IDEK").all()
+    assert library.RelativeIntensity.max() == 1.0
+    assert set(zip(library.FragmentType, library.FragmentSeriesNumber)) == {("y", 2), ("y", 3), ("b", 2)}
+    assert (library.Decoy == 0).all()
+
+
+def test_export_openswath_library_with_decoys(synthetics_db, tmp_path):
+    handle = TimsTofSyntheticsDataHandleRust(synthetics_db)
+    path = str(tmp_path / "library.tsv")
+
+    assert handle.export_spectral_library(path, format="openswath", include_decoys=True) == 2
+    library = pd.read_csv(path, sep="\t")
+    assert "ProductMz" in library.columns and library.Decoy.sum() == 1
+
+    with pytest.raises(ValueError, match="unknown spectral library format"):
+        handle.export_spectral_library(path, format="msp")
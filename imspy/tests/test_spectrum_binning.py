@@ -0,0 +1,76 @@
+import numpy as np
+import pytest
+
+from imspy.data.spectrum import MzSpectrum, MzSpectrumVectorized
+
+
+def make_spectrum() -> MzSpectrum:
+    rng = np.random.default_rng(7)
+    mz = np.sort(rng.uniform(60.0, 1900.0, 500))
+    return MzSpectrum(mz, rng.uniform(1.0, 100.0, 500))
+
+
+def test_decimal_binning_matches_vectorized():
+    spectrum = make_spectrum()
+    by_resolution = spectrum.vectorized(2)
+    by_binning = spectrum.vectorize("decimal", 2)
+    np.testing.assert_array_equal(by_binning.indices, by_resolution.indices)
+    np.testing.assert_array_equal(by_binning.values, by_resolution.values)
+    np.testing.assert_allclose(by_binning.bin_centers(), by_resolution.indices / 100.0)
+    assert by_binning.binning == ("decimal", 2.0, None, None)
+
+
+def test_ppm_bins_contain_their_peaks():
+    spectrum = make_spectrum()
+    vectorized = spectrum.vectorize("ppm", 20.0, mz_min=50.0, mz_max=2000.0)
+    assert vectorized.binning == ("ppm", 20.0, 50.0, 2000.0)
+    assert vectorized.values.sum() == pytest.approx(spectrum.intensity.sum())
+
+    # every peak lies within half a bin (10 ppm) of the center of its bin
+    centers = vectorized.bin_centers()
+    for mz in spectrum.mz[::25]:
+        closest = centers[np.argmin(np.abs(centers - mz))]
+        assert abs(closest - mz) <= mz * 10.1e-6
+
+
+def test_fixed_da_binning():
+    spectrum = MzSpectrum(np.array([100.0, 100.2, 100.6]), np.array([1.0, 2.0, 3.0]))
+    vectorized = spectrum.vectorize("da", 0.5)
+    np.testing.assert_array_equal(vectorized.indices, [200, 201])
+    np.testing.assert_array_equal(vectorized.values, [3.0, 3.0])
+    np.testing.assert_allclose(vectorized.bin_centers(), [100.0, 100.5])
+
+
+def test_constructor_carries_binning():
+    vectorized = MzSpectrumVectorized(np.array([3, 4], dtype=np.int32), np.array([1.0, 2.0]), 0,
+                                      binning="ppm", width=10.0, mz_min=100.0, mz_max=1000.0)
+    assert vectorized.binning == ("ppm", 10.0, 100.0, 1000.0)
+    assert vectorized.bin_centers()[0] > 100.0
+
+
+def test_invalid_binning_raises():
+    spectrum = make_spectrum()
+    with pytest.raises(ValueError):
+        spectrum.vectorize("log", 1.0)
+    with pytest.raises(ValueError):
+        spectrum.vectorize("ppm", -1.0)
+    with pytest.raises(ValueError):
+        spectrum.vectorize("ppm", 10.0, mz_min=2000.0, mz_max=50.0)
+
+
+def test_resample_has_one_intensity_per_grid_point():
+    spectrum = make_spectrum()
+    grid = np.linspace(50.0, 2000.0, 1000)
+    for method in ("nearest_bin", "linear", "gaussian"):
+        assert spectrum.resample(grid, method).shape == grid.shape
+
+    nearest = spectrum.resample(grid, "nearest_bin")
+    assert nearest.sum() == pytest.approx(spectrum.intensity.sum())
+
+
+def test_resample_rejects_unsorted_grid():
+    spectrum = make_spectrum()
+    with pytest.raises(ValueError):
+        spectrum.resample(np.array([200.0, 100.0]))
+    with pytest.raises(ValueError):
+        spectrum.resample(np.array([100.0, 200.0]), "cubic")
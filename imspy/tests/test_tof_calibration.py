@@ -0,0 +1,19 @@
+import numpy as np
+import pytest
+
+from imspy.simulation.calibration import TofCalibration
+
+
+def test_round_trip_within_one_index():
+    calibration = TofCalibration.from_boundaries(100.0, 1700.0, 400_000)
+    mz = np.array([150.0, 500.25, 1234.5678])
+    tof = calibration.mz_to_tof(mz)
+    assert tof.dtype == np.int32 and np.all(np.diff(tof) > 0)
+    np.testing.assert_allclose(calibration.tof_to_mz(tof), mz, rtol=2e-5)
+
+
+def test_curve_and_validation():
+    curve = TofCalibration(10.0, 7.8e-5, 2e-12).curve(100)
+    assert len(curve) == 100 and curve.mz.is_monotonic_increasing
+    with pytest.raises(ValueError, match="increase"):
+        TofCalibration(10.0, 7.8e-5, -1e-10)
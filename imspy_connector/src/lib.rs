@@ -3,6 +3,7 @@ use pyo3::wrap_pymodule;
 
 pub mod py_amino_acids;
 pub mod py_annotation;
+pub mod py_buffer;
 pub mod py_constants;
 pub mod py_chemistry;
 pub mod py_dataset;
@@ -12,6 +13,7 @@ pub mod py_elements;
 pub mod py_mz_spectrum;
 pub mod py_quadrupole;
 pub mod py_peptide;
+pub mod py_prm;
 pub mod py_simulation;
 pub mod py_tims_frame;
 pub mod py_tims_slice;
@@ -19,6 +21,22 @@ pub mod py_unimod;
 pub mod py_utility;
 pub mod py_sumformula;
 
+/// Set the number of threads of the shared pool used by all calls that do not pass `num_threads`.
+///
+/// Precedence: calls made from within a parallel computation run on the pool of the caller,
+/// an explicit `num_threads` runs on a (reused) pool of that size, `None` runs on the shared pool
+/// configured here, and without a shared pool (or after `set_num_threads(0)`) on the global rayon pool.
+#[pyfunction]
+fn set_num_threads(num_threads: usize) {
+    mscore::parallel::set_num_threads(num_threads)
+}
+
+/// Get the number of threads used by calls that do not pass `num_threads`.
+#[pyfunction]
+fn get_num_threads() -> usize {
+    mscore::parallel::get_num_threads()
+}
+
 #[pymodule]
 fn imspy_connector(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_wrapped(wrap_pymodule!(py_annotation::py_annotation))?;
@@ -32,12 +50,15 @@ fn imspy_connector(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_wrapped(wrap_pymodule!(py_mz_spectrum::py_spectrum))?;
     m.add_wrapped(wrap_pymodule!(py_quadrupole::py_quadrupole))?;
     m.add_wrapped(wrap_pymodule!(py_peptide::py_peptide))?;
+    m.add_wrapped(wrap_pymodule!(py_prm::py_prm))?;
     m.add_wrapped(wrap_pymodule!(py_simulation::py_simulation))?;
     m.add_wrapped(wrap_pymodule!(py_tims_frame::py_tims_frame))?;
     m.add_wrapped(wrap_pymodule!(py_tims_slice::py_tims_slice))?;
     m.add_wrapped(wrap_pymodule!(py_unimod::py_unimod))?;
     m.add_wrapped(wrap_pymodule!(py_utility::py_utility))?;
     m.add_wrapped(wrap_pymodule!(py_sumformula::py_sum_formula))?;
+    m.add_function(wrap_pyfunction!(set_num_threads, m)?)?;
+    m.add_function(wrap_pyfunction!(get_num_threads, m)?)?;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,5 +1,11 @@
-use mscore::data::spectrum::MsType;
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
+use mscore::data::spectrum::{ApproxEq, MsType};
+use crate::py_mz_spectrum::format_mz_range;
 use pyo3::prelude::*;
+use crate::py_buffer::{overwrite_shared, shared_array};
+use pyo3::types::{PyBytes, PyDict};
+use mscore::data::serialization::BinarySerializable;
 use mscore::simulation::annotation::{SourceType, SignalAttributes, ContributionSource, MzSpectrumAnnotated, PeakAnnotation, TimsFrameAnnotated, TimsSpectrumAnnotated};
 use numpy::{IntoPyArray, PyArray1, PyArrayMethods};
 
@@ -12,15 +18,16 @@ pub struct PyTimsSpectrumAnnotated {
 #[pymethods]
 impl PyTimsSpectrumAnnotated {
     #[new]
-    pub unsafe fn new(frame_id: i32,
-                      retention_time: f64,
-                      scan_id: u32,
-                      inv_mobility: f64,
-                      ms_type: i32,
-                      tof: &Bound<'_, PyArray1<u32>>,
-                      mz: &Bound<'_, PyArray1<f64>>,
-                      intensity: &Bound<'_, PyArray1<f64>>,
-                      annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(frame_id: i32,
+               retention_time: f64,
+               scan_id: u32,
+               inv_mobility: f64,
+               ms_type: i32,
+               tof: &Bound<'_, PyArray1<u32>>,
+               mz: &Bound<'_, PyArray1<f64>>,
+               intensity: &Bound<'_, PyArray1<f64>>,
+               annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
 
         let ms_type = MsType::new(ms_type);
         let annotations = annotations.iter().map(|x| PeakAnnotation {
@@ -34,10 +41,10 @@ impl PyTimsSpectrumAnnotated {
                 scan: scan_id,
                 mobility: inv_mobility,
                 ms_type,
-                tof: tof.as_slice()?.to_vec(),
+                tof: tof.to_vec()?,
                 spectrum: MzSpectrumAnnotated {
-                    mz: mz.as_slice()?.to_vec(),
-                    intensity: intensity.as_slice()?.to_vec(),
+                    mz: mz.to_vec()?,
+                    intensity: intensity.to_vec()?,
                     annotations,
                 }
             },
@@ -61,8 +68,9 @@ impl PyTimsSpectrumAnnotated {
         PyMzSpectrumAnnotated { inner: self.inner.spectrum.clone() }
     }
     #[setter]
-    pub unsafe fn set_tof(&mut self, tof: &Bound<'_, PyArray1<u32>>) {
-        self.inner.tof = tof.as_slice().unwrap().to_vec();
+    pub fn set_tof(slf: &Bound<'_, Self>, tof: &Bound<'_, PyArray1<u32>>) -> PyResult<()> {
+        let tof = tof.to_vec()?;
+        overwrite_shared(&mut slf.borrow_mut().inner.tof, tof)
     }
 
     pub fn __add__(&self, other: PyTimsSpectrumAnnotated) -> PyResult<PyTimsSpectrumAnnotated> {
@@ -72,6 +80,52 @@ impl PyTimsSpectrumAnnotated {
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> PyTimsSpectrumAnnotated {
         PyTimsSpectrumAnnotated { inner: self.inner.clone().filter_ranged(mz_min, mz_max, intensity_min, intensity_max) }
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsSpectrumAnnotated(frame_id={}, scan={}, ms_type={}, retention_time={:.3}, mobility={:.4}, peaks={}, mz_range={})",
+            self.inner.frame_id, self.inner.scan, self.inner.ms_type, self.inner.retention_time, self.inner.mobility,
+            self.inner.spectrum.mz.len(), format_mz_range(&self.inner.spectrum.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.spectrum.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsSpectrumAnnotated { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsSpectrumAnnotated>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
@@ -83,15 +137,16 @@ pub struct PyTimsFrameAnnotated {
 #[pymethods]
 impl PyTimsFrameAnnotated {
     #[new]
-    pub unsafe fn new(frame_id: i32,
-                      retention_time: f64,
-                      ms_type: i32,
-                      tof: &Bound<'_, PyArray1<u32>>,
-                      mz: &Bound<'_, PyArray1<f64>>,
-                      scan: &Bound<'_, PyArray1<u32>>,
-                      inv_mobility: &Bound<'_, PyArray1<f64>>,
-                      intensity: &Bound<'_, PyArray1<f64>>,
-                      annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(frame_id: i32,
+               retention_time: f64,
+               ms_type: i32,
+               tof: &Bound<'_, PyArray1<u32>>,
+               mz: &Bound<'_, PyArray1<f64>>,
+               scan: &Bound<'_, PyArray1<u32>>,
+               inv_mobility: &Bound<'_, PyArray1<f64>>,
+               intensity: &Bound<'_, PyArray1<f64>>,
+               annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
 
         assert!(tof.len()? == mz.len()? && mz.len()? == scan.len()? && scan.len()? == inv_mobility.len()? && inv_mobility.len()? == intensity.len()? && intensity.len()? == annotations.len());
 
@@ -105,11 +160,11 @@ impl PyTimsFrameAnnotated {
                 frame_id,
                 retention_time,
                 ms_type,
-                tof: tof.as_slice()?.to_vec(),
-                mz: mz.as_slice()?.to_vec(),
-                scan: scan.as_slice()?.to_vec(),
-                inv_mobility: inv_mobility.as_slice()?.to_vec(),
-                intensity: intensity.as_slice()?.to_vec(),
+                tof: tof.to_vec()?,
+                mz: mz.to_vec()?,
+                scan: scan.to_vec()?,
+                inv_mobility: inv_mobility.to_vec()?,
+                intensity: intensity.to_vec()?,
                 annotations,
             },
         })
@@ -121,19 +176,19 @@ impl PyTimsFrameAnnotated {
     #[getter]
     pub fn retention_time(&self) -> f64 { self.inner.retention_time }
     #[getter]
-    pub fn tof(&self, py: Python) -> Py<PyArray1<u32>> { self.inner.tof.clone().into_pyarray_bound(py).unbind() }
+    pub fn tof(slf: &Bound<'_, Self>) -> Py<PyArray1<u32>> { shared_array(&slf.borrow().inner.tof, slf.as_any()).unbind() }
 
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> { self.inner.mz.clone().into_pyarray_bound(py).unbind() }
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> { shared_array(&slf.borrow().inner.mz, slf.as_any()).unbind() }
 
     #[getter]
-    pub fn scan(&self, py: Python) -> Py<PyArray1<u32>> { self.inner.scan.clone().into_pyarray_bound(py).unbind() }
+    pub fn scan(slf: &Bound<'_, Self>) -> Py<PyArray1<u32>> { shared_array(&slf.borrow().inner.scan, slf.as_any()).unbind() }
 
     #[getter]
-    pub fn inv_mobility(&self, py: Python) -> Py<PyArray1<f64>> { self.inner.inv_mobility.clone().into_pyarray_bound(py).unbind() }
+    pub fn inv_mobility(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> { shared_array(&slf.borrow().inner.inv_mobility, slf.as_any()).unbind() }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> { self.inner.intensity.clone().into_pyarray_bound(py).unbind() }
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> { shared_array(&slf.borrow().inner.intensity, slf.as_any()).unbind() }
 
     #[getter]
     pub fn annotations(&self) -> Vec<PyPeakAnnotation> {
@@ -175,9 +230,25 @@ impl PyTimsFrameAnnotated {
         self.inner.ms_type.ms_type_numeric()
     }
 
+    pub fn annotation_columns(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let columns = self.inner.annotation_columns();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("offsets", columns.offsets.into_pyarray_bound(py))?;
+        dict.set_item("intensity_contribution", columns.intensity_contribution.into_pyarray_bound(py))?;
+        dict.set_item("source_type", columns.source_type.into_pyarray_bound(py))?;
+        dict.set_item("peptide_id", columns.peptide_id.into_pyarray_bound(py))?;
+        dict.set_item("ion_id", columns.ion_id.into_pyarray_bound(py))?;
+        dict.set_item("charge_state", columns.charge_state.into_pyarray_bound(py))?;
+        dict.set_item("isotope_peak", columns.isotope_peak.into_pyarray_bound(py))?;
+        dict.set_item("fragment_kind", columns.fragment_kind)?;
+        dict.set_item("fragment_ordinal", columns.fragment_ordinal.into_pyarray_bound(py))?;
+        Ok(dict.unbind())
+    }
+
     #[setter]
-    pub unsafe fn set_tof(&mut self, tof: &Bound<'_, PyArray1<u32>>) {
-        self.inner.tof = tof.as_slice().unwrap().to_vec();
+    pub fn set_tof(slf: &Bound<'_, Self>, tof: &Bound<'_, PyArray1<u32>>) -> PyResult<()> {
+        let tof = tof.to_vec()?;
+        overwrite_shared(&mut slf.borrow_mut().inner.tof, tof)
     }
 
     pub fn __add__(&self, other: PyTimsFrameAnnotated) -> PyResult<PyTimsFrameAnnotated> {
@@ -188,10 +259,55 @@ impl PyTimsFrameAnnotated {
         self.inner.to_tims_spectra_annotated().iter().map(|x| PyTimsSpectrumAnnotated { inner: x.clone() }).collect()
     }
 
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, inv_mobility_min: f64, inv_mobility_max: f64, scan_min: u32, scan_max: u32, intensity_min: f64, intensity_max: f64) -> PyTimsFrameAnnotated {
         PyTimsFrameAnnotated { inner: self.inner.clone().filter_ranged(mz_min, mz_max, inv_mobility_min, inv_mobility_max, scan_min, scan_max, intensity_min, intensity_max) }
     }
 
+    pub fn __repr__(&self) -> String {
+        format!("TimsFrameAnnotated(frame_id={}, ms_type={}, retention_time={:.3}, peaks={}, mz_range={})",
+            self.inner.frame_id, self.inner.ms_type, self.inner.retention_time, self.inner.mz.len(), format_mz_range(&self.inner.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsFrameAnnotated { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsFrameAnnotated>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
@@ -203,24 +319,24 @@ pub struct PyMzSpectrumAnnotated {
 #[pymethods]
 impl PyMzSpectrumAnnotated {
     #[new]
-    pub unsafe fn new(mz: &Bound<'_, PyArray1<f64>>, intensity:&Bound<'_, PyArray1<f64>>, annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
+    pub fn new(mz: &Bound<'_, PyArray1<f64>>, intensity:&Bound<'_, PyArray1<f64>>, annotations: Vec<PyPeakAnnotation>) -> PyResult<Self> {
         assert!(mz.len()? == intensity.len()? && intensity.len()? == annotations.len());
         let annotations = annotations.iter().map(|x| PeakAnnotation {
             contributions: x.inner.clone()
         }).collect();
         Ok(PyMzSpectrumAnnotated {
             inner: MzSpectrumAnnotated {
-                mz: mz.as_slice()?.to_vec(),
-                intensity: intensity.as_slice()?.to_vec(),
+                mz: mz.to_vec()?,
+                intensity: intensity.to_vec()?,
                 annotations,
             },
         })
     }
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> { self.inner.mz.clone().into_pyarray_bound(py).unbind() }
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> { shared_array(&slf.borrow().inner.mz, slf.as_any()).unbind() }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> { self.inner.intensity.clone().into_pyarray_bound(py).unbind() }
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> { shared_array(&slf.borrow().inner.intensity, slf.as_any()).unbind() }
 
     #[getter]
     pub fn annotations(&self) -> Vec<PyPeakAnnotation> {
@@ -238,6 +354,50 @@ impl PyMzSpectrumAnnotated {
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> PyMzSpectrumAnnotated {
         PyMzSpectrumAnnotated { inner: self.inner.clone().filter_ranged(mz_min, mz_max, intensity_min, intensity_max) }
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("MzSpectrumAnnotated(peaks={}, mz_range={})", self.inner.mz.len(), format_mz_range(&self.inner.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyMzSpectrumAnnotated { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyMzSpectrumAnnotated>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
@@ -285,12 +445,13 @@ pub struct PySignalAttributes {
 #[pymethods]
 impl PySignalAttributes {
     #[new]
-    #[pyo3(signature = (charge_state, peptide_id, isotope_peak, description=None))]
-    pub fn new(charge_state: i32, peptide_id: i32, isotope_peak: i32, description: Option<String>) -> PyResult<Self> {
+    #[pyo3(signature = (charge_state, peptide_id, isotope_peak, description=None, ion_id=None))]
+    pub fn new(charge_state: i32, peptide_id: i32, isotope_peak: i32, description: Option<String>, ion_id: Option<i32>) -> PyResult<Self> {
         Ok(PySignalAttributes {
             inner: SignalAttributes {
                 charge_state,
                 peptide_id,
+                ion_id: ion_id.unwrap_or(-1),
                 isotope_peak,
                 description,
             },
@@ -301,6 +462,8 @@ impl PySignalAttributes {
     #[getter]
     pub fn peptide_id(&self) -> i32 { self.inner.peptide_id }
     #[getter]
+    pub fn ion_id(&self) -> i32 { self.inner.ion_id }
+    #[getter]
     pub fn isotope_peak(&self) -> i32 { self.inner.isotope_peak }
     #[getter]
     pub fn description(&self) -> Option<String> { self.inner.description.clone() }
@@ -0,0 +1,67 @@
+use arrow::buffer::{Buffer, ScalarBuffer};
+use arrow::datatypes::ArrowNativeType;
+use numpy::ndarray::ArrayView1;
+use numpy::{Element, PyArray1, PyArrayMethods};
+use pyo3::prelude::*;
+use std::panic::RefUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// Read-only numpy view of a buffer owned by a wrapper object, without copying it.
+///
+/// The array holds a reference to `owner` as its base object, so the buffer stays valid after the wrapper
+/// itself goes out of scope in Python. Wrappers exporting buffers this way must never reallocate or drop
+/// them, values may only be replaced in place with `overwrite_shared`.
+///
+/// Arguments:
+///
+/// * `data` - buffer owned by `owner`
+/// * `owner` - wrapper object owning `data`
+///
+/// Returns:
+///
+/// * read-only array sharing memory with `data`
+pub fn shared_array<'py, T: Element>(data: &[T], owner: &Bound<'py, PyAny>) -> Bound<'py, PyArray1<T>> {
+    // SAFETY: `owner` owns `data`, outlives the array as its base object and never reallocates `data`
+    let array = unsafe { PyArray1::borrow_from_array_bound(&ArrayView1::from(data), owner.clone()) };
+    array.readwrite().make_nonwriteable();
+    array
+}
+
+/// Keeps the wrapper object owning a buffer alive for as long as arrow references the buffer
+struct BufferOwner(#[allow(dead_code)] Py<PyAny>);
+
+// the owner is only held and released, never accessed, so a panic cannot observe it in a broken state
+impl RefUnwindSafe for BufferOwner {}
+
+/// Arrow buffer over a buffer owned by a wrapper object, without copying it, see `shared_array`.
+///
+/// The buffer holds a reference to `owner` until arrow, or pyarrow after an export through the C data
+/// interface, releases it. The same rules as for `shared_array` apply to the wrapper.
+///
+/// Arguments:
+///
+/// * `data` - buffer owned by `owner`
+/// * `owner` - wrapper object owning `data`
+///
+/// Returns:
+///
+/// * buffer sharing memory with `data`
+pub fn shared_arrow_buffer<T: ArrowNativeType>(data: &[T], owner: &Bound<'_, PyAny>) -> ScalarBuffer<T> {
+    // vectors never hand out null pointers, not even when empty
+    let ptr = NonNull::new(data.as_ptr() as *mut u8).expect("buffer pointer is never null");
+    let owner = Arc::new(BufferOwner(owner.clone().unbind()));
+    // SAFETY: `owner` owns `data`, is kept alive by the buffer and never reallocates `data`
+    let buffer = unsafe { Buffer::from_custom_allocation(ptr, std::mem::size_of_val(data), owner) };
+    ScalarBuffer::new(buffer, 0, data.len())
+}
+
+/// Replace the values of a buffer exported by `shared_array` in place, arrays handed out before see the new values
+pub fn overwrite_shared<T: Copy>(data: &mut [T], values: Vec<T>) -> PyResult<()> {
+    if values.len() != data.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected {} values, one per peak, got {}", data.len(), values.len())));
+    }
+    data.copy_from_slice(&values);
+    Ok(())
+}
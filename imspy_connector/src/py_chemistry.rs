@@ -1,34 +1,156 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use numpy::{IntoPyArray, PyArray1, PyArrayMethods};
 
-use mscore::algorithm::isotope::{generate_averagine_spectra, generate_averagine_spectrum};
+use mscore::algorithm::deisotope::{deisotope_spectra, deisotope_spectrum, DeconvolutedPeak};
+use mscore::algorithm::isotope::{IsotopeDistributionCache, generate_averagine_spectra, generate_averagine_spectra_from_mz, generate_averagine_spectrum, generate_averagine_spectrum_from_mz};
+use mscore::algorithm::mobility::MobilityModel;
+use mscore::algorithm::peak_shape::{estimate_im_peaks_par, ImPeakEstimate};
+use mscore::chemistry::descriptors::{self, PkaSet};
+use mscore::chemistry::formulas::Adduct;
+use mscore::chemistry::label::LabelScheme;
+use mscore::data::peptide::{FragmentType, PeptideSequence};
 use mscore::data::spectrum::MzSpectrum;
 use crate::py_mz_spectrum::PyMzSpectrum;
 use crate::py_peptide::{PyPeptideSequence};
 
 #[pyfunction]
-pub fn generate_precursor_spectrum(mass: f64, charge: i32, min_intensity: i32, k: i32, resolution: i32, centroid: bool) -> PyMzSpectrum {
-    PyMzSpectrum { inner: generate_averagine_spectrum(mass, charge, min_intensity, k, resolution, centroid, None) }
+#[pyo3(signature = (mass, charge, min_intensity, k, resolution, centroid, atomic_composition=None, fine_structure=false, fine_structure_sigma=0.0))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn generate_precursor_spectrum(mass: f64, charge: i32, min_intensity: i32, k: i32, resolution: i32, centroid: bool, atomic_composition: Option<Vec<(String, i32)>>, fine_structure: bool, fine_structure_sigma: f64) -> PyResult<PyMzSpectrum> {
+    if fine_structure_sigma.is_nan() || fine_structure_sigma < 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("fine_structure_sigma must not be negative, got {}", fine_structure_sigma)));
+    }
+    let atomic_composition: Option<HashMap<String, i32>> = atomic_composition.map(|composition| composition.into_iter().collect());
+    let fine_structure = fine_structure.then_some(fine_structure_sigma);
+    Ok(PyMzSpectrum { inner: generate_averagine_spectrum(mass, charge, min_intensity, k, resolution, centroid, None, atomic_composition.as_ref(), fine_structure) })
 }
 
 #[pyfunction]
+#[pyo3(signature = (masses, charges, min_intensity, k, resolution, centroid, num_threads=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
 pub fn generate_precursor_spectra(
+    py: Python,
     masses: Vec<f64>,
     charges: Vec<i32>,
     min_intensity: i32,
     k: i32,
     resolution: i32,
     centroid: bool,
-    num_threads: usize
+    num_threads: Option<usize>
 ) -> Vec<PyMzSpectrum> {
-    let result = generate_averagine_spectra(masses, charges, min_intensity, k, resolution, centroid, num_threads, None);
+    let result = py.allow_threads(|| generate_averagine_spectra(masses, charges, min_intensity, k, resolution, centroid, num_threads, None));
     result.into_iter().map(|spectrum| PyMzSpectrum { inner: spectrum }).collect()
 }
 
+/// neutral monoisotopic mass and averagine spectrum of a precursor observed at the given m/z and charge
+#[pyfunction]
+#[pyo3(signature = (mz, charge, min_intensity, k, resolution, centroid, relative_intensity=false))]
+pub fn generate_precursor_spectrum_from_mz(mz: f64, charge: i32, min_intensity: f64, k: i32, resolution: i32, centroid: bool, relative_intensity: bool) -> (f64, PyMzSpectrum) {
+    let (mass, spectrum) = generate_averagine_spectrum_from_mz(mz, charge, min_intensity, relative_intensity, k, resolution, centroid, None);
+    (mass, PyMzSpectrum { inner: spectrum })
+}
+
+#[pyfunction]
+#[pyo3(signature = (mzs, charges, min_intensity, k, resolution, centroid, relative_intensity=false, num_threads=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn generate_precursor_spectra_from_mz(py: Python, mzs: Vec<f64>, charges: Vec<i32>, min_intensity: f64, k: i32, resolution: i32, centroid: bool, relative_intensity: bool, num_threads: Option<usize>) -> PyResult<Vec<(f64, PyMzSpectrum)>> {
+    if mzs.len() != charges.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected one charge per m/z, got {} charges for {} m/z values", charges.len(), mzs.len())));
+    }
+    let result = py.allow_threads(|| generate_averagine_spectra_from_mz(&mzs, &charges, min_intensity, relative_intensity, k, resolution, centroid, num_threads, None));
+    Ok(result.into_iter().map(|(mass, spectrum)| (mass, PyMzSpectrum { inner: spectrum })).collect())
+}
+
+/// Columns of deconvoluted peaks
+type DeconvolutedColumns = (Vec<f64>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<f64>, Vec<usize>);
+
+/// columnar representation of deconvoluted peaks: mono m/z, monoisotopic mass, charge, intensity, score, number of peaks
+fn deconvoluted_peaks_to_columns(peaks: Vec<DeconvolutedPeak>) -> DeconvolutedColumns {
+    (
+        peaks.iter().map(|p| p.mono_mz).collect(),
+        peaks.iter().map(|p| p.monoisotopic_mass).collect(),
+        peaks.iter().map(|p| p.charge).collect(),
+        peaks.iter().map(|p| p.intensity).collect(),
+        peaks.iter().map(|p| p.score).collect(),
+        peaks.iter().map(|p| p.num_peaks).collect(),
+    )
+}
+
+#[pyfunction]
+pub fn deisotope_mz_spectrum(spectrum: PyMzSpectrum, max_charge: i32, ppm: f64, min_cosine_to_averagine: f64) -> DeconvolutedColumns {
+    deconvoluted_peaks_to_columns(deisotope_spectrum(&spectrum.inner, max_charge, ppm, min_cosine_to_averagine))
+}
+
+#[pyfunction]
+pub fn deisotope_mz_spectra(py: Python, spectra: Vec<PyMzSpectrum>, max_charge: i32, ppm: f64, min_cosine_to_averagine: f64, num_threads: usize) -> Vec<DeconvolutedColumns> {
+    let spectra: Vec<MzSpectrum> = spectra.into_iter().map(|spectrum| spectrum.inner).collect();
+    py.allow_threads(|| deisotope_spectra(&spectra, max_charge, ppm, min_cosine_to_averagine, num_threads))
+        .into_iter()
+        .map(deconvoluted_peaks_to_columns)
+        .collect()
+}
+
+/// Columns of the isotope fits scored per monoisotopic m/z
+type IsotopeFitColumns = (Vec<f64>, Vec<usize>, Vec<f64>, Vec<usize>, Vec<bool>, Vec<Vec<f64>>);
+
+#[pyfunction]
+pub fn score_isotope_fits(py: Python, spectrum: PyMzSpectrum, mono_mzs: Vec<f64>, charges: Vec<i32>, n_isotopes: usize, ppm: f64, num_threads: usize) -> PyResult<IsotopeFitColumns> {
+    let scores = py
+        .allow_threads(|| mscore::algorithm::isotope::score_isotope_fits(&spectrum.inner, &mono_mzs, &charges, n_isotopes, ppm, num_threads))
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    Ok((
+        scores.iter().map(|s| s.chi_square).collect(),
+        scores.iter().map(|s| s.num_matched).collect(),
+        scores.iter().map(|s| s.shifted_chi_square).collect(),
+        scores.iter().map(|s| s.shifted_num_matched).collect(),
+        scores.iter().map(|s| s.is_mispick).collect(),
+        scores.into_iter().map(|s| s.ratios).collect(),
+    ))
+}
+
+#[pyfunction]
+#[pyo3(signature = (peptide_sequence, label=None))]
+pub fn calculate_monoisotopic_mass(peptide_sequence: PyPeptideSequence, label: Option<&str>) -> PyResult<f64> {
+    let label = parse_label_scheme(label)?;
+    Ok(mscore::algorithm::peptide::calculate_peptide_mono_isotopic_mass(&peptide_sequence.inner, label))
+}
+
 #[pyfunction]
-pub fn calculate_monoisotopic_mass(peptide_sequence: PyPeptideSequence) -> f64 {
-    mscore::algorithm::peptide::calculate_peptide_mono_isotopic_mass(&peptide_sequence.inner)
+#[pyo3(signature = (sequences, label=None, num_threads=4))]
+pub fn calculate_monoisotopic_masses(py: Python, sequences: Vec<String>, label: Option<&str>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+    let label = parse_label_scheme(label)?;
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    let masses = py.allow_threads(|| mscore::algorithm::peptide::calculate_peptide_mono_isotopic_masses_par(sequences, label, num_threads));
+    Ok(masses.into_pyarray_bound(py).unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (peptide_sequence, label=None))]
+pub fn calculate_average_mass(peptide_sequence: PyPeptideSequence, label: Option<&str>) -> PyResult<f64> {
+    let label = parse_label_scheme(label)?;
+    mscore::algorithm::peptide::calculate_peptide_average_mass(&peptide_sequence.inner, label)
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequences, label=None, num_threads=4))]
+pub fn calculate_average_masses(py: Python, sequences: Vec<String>, label: Option<&str>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+    let label = parse_label_scheme(label)?;
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    let masses = py.allow_threads(|| mscore::algorithm::peptide::calculate_peptide_average_masses_par(sequences, label, num_threads))
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    Ok(masses.into_pyarray_bound(py).unbind())
+}
+
+/// label scheme by name, None means unlabeled
+pub fn parse_label_scheme(label: Option<&str>) -> PyResult<Option<LabelScheme>> {
+    label.map(LabelScheme::from_name).transpose().map_err(pyo3::exceptions::PyValueError::new_err)
 }
 
 #[pyfunction]
@@ -39,28 +161,70 @@ pub fn simulate_charge_state_for_sequence(sequence: &str, max_charge: Option<usi
 
 #[pyfunction]
 #[pyo3(signature = (sequences, num_threads, max_charge=None, charge_probability=None))]
-pub fn simulate_charge_states_for_sequences(sequences: Vec<&str>, num_threads: usize, max_charge: Option<usize>, charge_probability: Option<f64>) -> Vec<Vec<f64>> {
-    mscore::algorithm::peptide::simulate_charge_states_for_sequences(sequences, num_threads, max_charge, charge_probability)
+pub fn simulate_charge_states_for_sequences(py: Python, sequences: Vec<&str>, num_threads: usize, max_charge: Option<usize>, charge_probability: Option<f64>) -> Vec<Vec<f64>> {
+    py.allow_threads(|| mscore::algorithm::peptide::simulate_charge_states_for_sequences(sequences, num_threads, max_charge, charge_probability))
 }
 
 #[pyfunction]
 pub fn find_unimod_annotations(sequence: &str) -> (String, Vec<f64>) {
     mscore::chemistry::utility::find_unimod_patterns(sequence)
 }
+/// fragment types by name, case insensitive, None means b and y ions
+fn parse_fragment_types(fragment_types: Option<Vec<String>>) -> PyResult<Vec<FragmentType>> {
+    let fragment_types = match fragment_types {
+        Some(names) => names.iter().map(|name| FragmentType::from_name(name)).collect::<Result<Vec<_>, _>>()
+            .map_err(pyo3::exceptions::PyValueError::new_err)?,
+        None => vec![FragmentType::B, FragmentType::Y],
+    };
+    if fragment_types.is_empty() {
+        return Err(pyo3::exceptions::PyValueError::new_err("at least one fragment type is required"));
+    }
+    Ok(fragment_types)
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequence, charge, intensities, normalize, half_charge_one, peptide_id=None, fragment_types=None))]
+pub fn sequence_to_all_ions_ims(sequence: &str, charge: i32, intensities: Vec<f64>, normalize: bool, half_charge_one: bool, peptide_id: Option<i32>, fragment_types: Option<Vec<String>>) -> PyResult<String> {
+    let fragment_types = parse_fragment_types(fragment_types)?;
+    rustdf::sim::utility::sequence_to_all_ions(sequence, charge, &intensities, normalize, half_charge_one, peptide_id, &fragment_types)
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+/// Fragment ions of a peptide as (label, m/z) pairs, e.g. ("c3+2", 187.07), for fragment charges 1 to `max_fragment_charge`
+#[pyfunction]
+#[pyo3(signature = (sequence, fragment_types=None, max_fragment_charge=1))]
+pub fn calculate_b_y_ion_series(sequence: &str, fragment_types: Option<Vec<String>>, max_fragment_charge: i32) -> PyResult<Vec<(String, f64)>> {
+    let fragment_types = parse_fragment_types(fragment_types)?;
+    let ions = PeptideSequence::new(sequence.to_string(), None).calculate_fragment_ions(&fragment_types, max_fragment_charge);
+    Ok(ions.iter().map(|ion| (ion.label(), ion.mz())).collect())
+}
+
 #[pyfunction]
-#[pyo3(signature = (sequence, charge, intensities, normalize, half_charge_one, peptide_id=None))]
-pub fn sequence_to_all_ions_ims(sequence: &str, charge: i32, intensities: Vec<f64>, normalize: bool, half_charge_one: bool, peptide_id: Option<i32>) -> String {
-    rustdf::sim::utility::sequence_to_all_ions(sequence, charge, &intensities, normalize, half_charge_one, peptide_id)
+#[pyo3(signature = (flat_array, shape=None))]
+pub fn reshape_prosit_array(flat_array: Vec<f64>, shape: Option<(usize, usize, usize)>) -> PyResult<Vec<Vec<Vec<f64>>>> {
+    rustdf::sim::utility::reshape_prosit_array(flat_array, shape)
+        .map_err(pyo3::exceptions::PyValueError::new_err)
 }
 
 #[pyfunction]
-pub fn reshape_prosit_array(flat_array: Vec<f64>) -> Vec<Vec<Vec<f64>>> {
-    rustdf::sim::utility::reshape_prosit_array(flat_array)
+#[pyo3(signature = (sequences, charges, intensities, normalize, half_charge_one, num_threads, peptide_ids=None, fragment_types=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn sequence_to_all_ions_par(py: Python, sequences: Vec<&str>, charges: Vec<i32>, intensities: Vec<Vec<f64>>, normalize: bool, half_charge_one: bool, num_threads: usize, peptide_ids: Option<Vec<Option<i32>>>, fragment_types: Option<Vec<String>>) -> PyResult<Vec<String>> {
+    let fragment_types = parse_fragment_types(fragment_types)?;
+    let peptide_ids = peptide_ids.unwrap_or_else(|| vec![None; sequences.len()]);
+    py.allow_threads(|| rustdf::sim::utility::sequence_to_all_ions_par(sequences, charges, intensities, normalize, half_charge_one, num_threads, peptide_ids, &fragment_types))
+        .map_err(pyo3::exceptions::PyValueError::new_err)
 }
 
 #[pyfunction]
-pub fn sequence_to_all_ions_par(sequences: Vec<&str>, charges: Vec<i32>, intensities: Vec<Vec<f64>>, normalize: bool, half_charge_one: bool, num_threads: usize, peptide_ids: Vec<Option<i32>>) -> Vec<String> {
-    rustdf::sim::utility::sequence_to_all_ions_par(sequences, charges, intensities, normalize, half_charge_one, num_threads, peptide_ids)
+pub fn parse_proforma(proforma: &str) -> PyResult<(String, Option<i32>)> {
+    mscore::data::proforma::parse_proforma(proforma).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequence, charge=None))]
+pub fn to_proforma(sequence: &str, charge: Option<i32>) -> String {
+    mscore::data::proforma::to_proforma(sequence, charge)
 }
 
 #[pyfunction]
@@ -68,10 +232,28 @@ pub fn unimod_sequence_to_tokens(sequence: &str) -> Vec<String> {
     mscore::chemistry::utility::unimod_sequence_to_tokens(sequence, false)
 }
 
+/// hits, misses, cached compositions and max size of the isotope distribution cache shared by peptide ions
+#[pyfunction]
+pub fn isotope_distribution_cache_stats() -> (usize, usize, usize, usize) {
+    let stats = IsotopeDistributionCache::shared().stats();
+    (stats.hits, stats.misses, stats.entries, stats.max_size)
+}
+
 #[pyfunction]
-pub fn generate_isotope_distribution(atomic_composition: Vec<(String, f64)>, mass_tolerance: f64, abundance_threshold: f64, max_result: i32) -> Vec<(f64, f64)> {
+pub fn set_isotope_distribution_cache_size(max_size: usize) {
+    IsotopeDistributionCache::shared().set_max_size(max_size)
+}
+
+#[pyfunction]
+pub fn clear_isotope_distribution_cache() {
+    IsotopeDistributionCache::shared().clear()
+}
+
+#[pyfunction]
+#[pyo3(signature = (atomic_composition, mass_tolerance, abundance_threshold, max_result, fine_structure=false, fine_structure_resolution=None))]
+pub fn generate_isotope_distribution(atomic_composition: Vec<(String, f64)>, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, fine_structure: bool, fine_structure_resolution: Option<f64>) -> Vec<(f64, f64)> {
     mscore::algorithm::isotope::generate_isotope_distribution(&atomic_composition.iter().map(|(k, v)| (k.to_string(), *v as i32)).collect(),
-        mass_tolerance, abundance_threshold, max_result)
+        mass_tolerance, abundance_threshold, max_result, fine_structure, fine_structure_resolution)
 }
 
 #[pyfunction]
@@ -80,13 +262,14 @@ pub fn one_over_reduced_mobility_to_ccs(one_over_k0: f64, mz: f64, charge: u32,
 }
 
 #[pyfunction]
-pub fn one_over_reduced_mobility_to_ccs_par(one_over_k0: Vec<f64>, mz: Vec<f64>, charge: Vec<u32>, mass_gas: f64, temp: f64, t_diff: f64, num_threads: usize) -> Vec<f64> {
-    let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
-    thread_pool.install(|| {
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn one_over_reduced_mobility_to_ccs_par(py: Python, one_over_k0: Vec<f64>, mz: Vec<f64>, charge: Vec<u32>, mass_gas: f64, temp: f64, t_diff: f64, num_threads: usize) -> Vec<f64> {
+    let thread_pool = mscore::parallel::pool(num_threads);
+    py.allow_threads(|| thread_pool.install(|| {
         one_over_k0.par_iter().zip(mz.par_iter()).zip(charge.par_iter()).map(|((k0, mz), charge)| {
             mscore::chemistry::formulas::one_over_reduced_mobility_to_ccs(*k0, *mz, *charge, mass_gas, temp, t_diff)
         }).collect()
-    })
+    }))
 }
 
 #[pyfunction]
@@ -95,13 +278,115 @@ pub fn ccs_to_one_over_reduced_mobility(ccs: f64, mz: f64, charge: u32, mass_gas
 }
 
 #[pyfunction]
-pub fn ccs_to_one_over_reduced_mobility_par(ccs: Vec<f64>, mz: Vec<f64>, charge: Vec<u32>, mass_gas: f64, temp: f64, t_diff: f64, num_threads: usize) -> Vec<f64> {
-    let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
-    thread_pool.install(|| {
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn ccs_to_one_over_reduced_mobility_par(py: Python, ccs: Vec<f64>, mz: Vec<f64>, charge: Vec<u32>, mass_gas: f64, temp: f64, t_diff: f64, num_threads: usize) -> Vec<f64> {
+    let thread_pool = mscore::parallel::pool(num_threads);
+    py.allow_threads(|| thread_pool.install(|| {
         ccs.par_iter().zip(mz.par_iter()).zip(charge.par_iter()).map(|((ccs, mz), charge)| {
             mscore::chemistry::formulas::ccs_to_one_over_reduced_mobility(*ccs, *mz, *charge, mass_gas, temp, t_diff)
         }).collect()
-    })
+    }))
+}
+
+/// mobility model with the given (a, b) coefficients per charge state, None falls back to the default model
+pub fn parse_mobility_model(coefficients: Option<Vec<(f64, f64)>>) -> PyResult<MobilityModel> {
+    match coefficients {
+        Some(coefficients) => MobilityModel::new(coefficients).map_err(pyo3::exceptions::PyValueError::new_err),
+        None => Ok(MobilityModel::default()),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (mass, charge, coefficients=None))]
+pub fn predict_inverse_mobility(mass: f64, charge: i32, coefficients: Option<Vec<(f64, f64)>>) -> PyResult<f64> {
+    let model = parse_mobility_model(coefficients)?;
+    Ok(mscore::algorithm::mobility::predict_inverse_mobility(mass, charge, &model))
+}
+
+#[pyfunction]
+#[pyo3(signature = (masses, charges, num_threads, coefficients=None))]
+pub fn predict_inverse_mobilities_par(py: Python, masses: Vec<f64>, charges: Vec<i32>, num_threads: usize, coefficients: Option<Vec<(f64, f64)>>) -> PyResult<Vec<f64>> {
+    let model = parse_mobility_model(coefficients)?;
+    Ok(py.allow_threads(|| mscore::algorithm::mobility::predict_inverse_mobilities_par(&masses, &charges, &model, num_threads)))
+}
+
+/// Apex, FWHM, area, asymmetry and truncation of every mobilogram as dict of numpy arrays, NaN where a
+/// mobilogram has no positive intensity
+#[pyfunction]
+#[pyo3(signature = (mobilities, intensities, num_threads=4))]
+pub fn estimate_im_peaks(py: Python<'_>, mobilities: Vec<Vec<f64>>, intensities: Vec<Vec<f64>>, num_threads: usize) -> PyResult<Py<PyDict>> {
+    if mobilities.len() != intensities.len() || mobilities.iter().zip(&intensities).any(|(m, i)| m.len() != i.len()) {
+        return Err(pyo3::exceptions::PyValueError::new_err("mobilities and intensities must have the same shape"));
+    }
+    let mobilograms: Vec<(Vec<f64>, Vec<f64>)> = mobilities.into_iter().zip(intensities).collect();
+    let peaks = py.allow_threads(|| estimate_im_peaks_par(&mobilograms, num_threads));
+
+    let column = |value: fn(&ImPeakEstimate) -> f64| -> Vec<f64> {
+        peaks.iter().map(|peak| peak.as_ref().map_or(f64::NAN, value)).collect()
+    };
+    let dict = PyDict::new_bound(py);
+    dict.set_item("apex_mobility", column(|peak| peak.apex_mobility).into_pyarray_bound(py))?;
+    dict.set_item("fwhm", column(|peak| peak.fwhm).into_pyarray_bound(py))?;
+    dict.set_item("area", column(|peak| peak.area).into_pyarray_bound(py))?;
+    dict.set_item("asymmetry", column(|peak| peak.asymmetry).into_pyarray_bound(py))?;
+    let truncated: Vec<bool> = peaks.iter().map(|peak| peak.is_some_and(|peak| peak.truncated)).collect();
+    dict.set_item("truncated", truncated.into_pyarray_bound(py))?;
+    Ok(dict.unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (one_over_k0, mz, charge, temperature=None, mass_gas=None, num_threads=4))]
+pub fn one_over_k0_to_ccs(py: Python, one_over_k0: &Bound<'_, PyArray1<f64>>, mz: &Bound<'_, PyArray1<f64>>, charge: &Bound<'_, PyArray1<u32>>, temperature: Option<f64>, mass_gas: Option<f64>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+    let (one_over_k0, mz, charge) = (one_over_k0.to_vec()?, mz.to_vec()?, charge.to_vec()?);
+    check_mobility_lengths(one_over_k0.len(), mz.len(), charge.len())?;
+    let ccs = py.allow_threads(|| mscore::chemistry::mobility::one_over_k0_to_ccs_par(&one_over_k0, &mz, &charge, temperature, mass_gas, num_threads));
+    Ok(ccs.into_pyarray_bound(py).unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (ccs, mz, charge, temperature=None, mass_gas=None, num_threads=4))]
+pub fn ccs_to_one_over_k0(py: Python, ccs: &Bound<'_, PyArray1<f64>>, mz: &Bound<'_, PyArray1<f64>>, charge: &Bound<'_, PyArray1<u32>>, temperature: Option<f64>, mass_gas: Option<f64>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+    let (ccs, mz, charge) = (ccs.to_vec()?, mz.to_vec()?, charge.to_vec()?);
+    check_mobility_lengths(ccs.len(), mz.len(), charge.len())?;
+    let one_over_k0 = py.allow_threads(|| mscore::chemistry::mobility::ccs_to_one_over_k0_par(&ccs, &mz, &charge, temperature, mass_gas, num_threads));
+    Ok(one_over_k0.into_pyarray_bound(py).unbind())
+}
+
+fn check_mobility_lengths(values: usize, mz: usize, charge: usize) -> PyResult<()> {
+    if values != mz || values != charge {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected arrays of equal length, got {}, {} (mz) and {} (charge)", values, mz, charge)));
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequences, pka_set="emboss", num_threads=4))]
+pub fn isoelectric_point(py: Python, sequences: Vec<String>, pka_set: &str, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+    let pka = PkaSet::from_name(pka_set).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    Ok(py.allow_threads(|| descriptors::isoelectric_point_par(sequences, &pka, num_threads)).into_pyarray_bound(py).unbind())
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequences, num_threads=4))]
+pub fn gravy(py: Python, sequences: Vec<String>, num_threads: usize) -> Py<PyArray1<f64>> {
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    py.allow_threads(|| descriptors::gravy_par(sequences, num_threads)).into_pyarray_bound(py).unbind()
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequences, num_threads=4))]
+pub fn aromaticity(py: Python, sequences: Vec<String>, num_threads: usize) -> Py<PyArray1<f64>> {
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    py.allow_threads(|| descriptors::aromaticity_par(sequences, num_threads)).into_pyarray_bound(py).unbind()
+}
+
+#[pyfunction]
+#[pyo3(signature = (sequences, num_threads=4))]
+pub fn aliphatic_index(py: Python, sequences: Vec<String>, num_threads: usize) -> Py<PyArray1<f64>> {
+    let sequences: Vec<&str> = sequences.iter().map(|s| s.as_str()).collect();
+    py.allow_threads(|| descriptors::aliphatic_index_par(sequences, num_threads)).into_pyarray_bound(py).unbind()
 }
 
 #[pyfunction]
@@ -109,15 +394,33 @@ pub fn calculate_mz(mono_isotopic_mass: f64, charge: i32) -> f64 {
     mscore::chemistry::formulas::calculate_mz(mono_isotopic_mass, charge)
 }
 
+#[pyfunction]
+pub fn calculate_mz_with_adduct(py: Python, mono_isotopic_masses: &Bound<'_, PyArray1<f64>>, adduct: &str) -> PyResult<Py<PyArray1<f64>>> {
+    let adduct = Adduct::from_name(adduct).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let mz: Vec<f64> = mono_isotopic_masses.to_vec()?.iter()
+        .map(|mass| mscore::chemistry::formulas::calculate_mz_with_adduct(*mass, &adduct))
+        .collect();
+    Ok(mz.into_pyarray_bound(py).unbind())
+}
+
+#[pyfunction]
+pub fn neutral_mass_from_mz(py: Python, mz: &Bound<'_, PyArray1<f64>>, adduct: &str) -> PyResult<Py<PyArray1<f64>>> {
+    let adduct = Adduct::from_name(adduct).map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let masses: Vec<f64> = mz.to_vec()?.iter()
+        .map(|mz| mscore::chemistry::formulas::neutral_mass_from_mz(*mz, &adduct))
+        .collect();
+    Ok(masses.into_pyarray_bound(py).unbind())
+}
+
 #[pyfunction]
 #[pyo3(signature = (sequence, charge, peptide_id=None))]
 pub fn simulate_precursor_spectrum(sequence: &str, charge: i32, peptide_id: Option<i32>) -> PyMzSpectrum {
-    PyMzSpectrum { inner: mscore::algorithm::isotope::generate_precursor_spectrum(&sequence, charge, peptide_id) }
+    PyMzSpectrum { inner: mscore::algorithm::isotope::generate_precursor_spectrum(sequence, charge, peptide_id) }
 }
 
 #[pyfunction]
-pub fn simulate_precursor_spectra(sequences: Vec<&str>, charges: Vec<i32>, num_threads: usize, peptide_ids: Vec<Option<i32>>) -> Vec<PyMzSpectrum> {
-    let spectra = mscore::algorithm::isotope::generate_precursor_spectra(&sequences, &charges, num_threads, peptide_ids);
+pub fn simulate_precursor_spectra(py: Python, sequences: Vec<&str>, charges: Vec<i32>, num_threads: usize, peptide_ids: Vec<Option<i32>>) -> Vec<PyMzSpectrum> {
+    let spectra = py.allow_threads(|| mscore::algorithm::isotope::generate_precursor_spectra(&sequences, &charges, num_threads, peptide_ids));
     spectra.into_iter().map(|spectrum| PyMzSpectrum { inner: spectrum }).collect()
 }
 
@@ -140,20 +443,45 @@ pub fn calculate_transmission_dependent_fragment_ion_isotope_distribution(target
 pub fn py_chemistry(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(generate_precursor_spectrum, m)?)?;
     m.add_function(wrap_pyfunction!(generate_precursor_spectra, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_precursor_spectrum_from_mz, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_precursor_spectra_from_mz, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_monoisotopic_mass, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_average_mass, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_average_masses, m)?)?;
+    m.add_function(wrap_pyfunction!(deisotope_mz_spectrum, m)?)?;
+    m.add_function(wrap_pyfunction!(deisotope_mz_spectra, m)?)?;
+    m.add_function(wrap_pyfunction!(score_isotope_fits, m)?)?;
     m.add_function(wrap_pyfunction!(simulate_charge_state_for_sequence, m)?)?;
     m.add_function(wrap_pyfunction!(simulate_charge_states_for_sequences, m)?)?;
+    m.add_function(wrap_pyfunction!(predict_inverse_mobility, m)?)?;
+    m.add_function(wrap_pyfunction!(predict_inverse_mobilities_par, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_im_peaks, m)?)?;
     m.add_function(wrap_pyfunction!(find_unimod_annotations, m)?)?;
     m.add_function(wrap_pyfunction!(sequence_to_all_ions_ims, m)?)?;
     m.add_function(wrap_pyfunction!(reshape_prosit_array, m)?)?;
     m.add_function(wrap_pyfunction!(sequence_to_all_ions_par, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_b_y_ion_series, m)?)?;
     m.add_function(wrap_pyfunction!(unimod_sequence_to_tokens, m)?)?;
     m.add_function(wrap_pyfunction!(generate_isotope_distribution, m)?)?;
+    m.add_function(wrap_pyfunction!(isotope_distribution_cache_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(set_isotope_distribution_cache_size, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_isotope_distribution_cache, m)?)?;
     m.add_function(wrap_pyfunction!(one_over_reduced_mobility_to_ccs, m)?)?;
     m.add_function(wrap_pyfunction!(one_over_reduced_mobility_to_ccs_par, m)?)?;
     m.add_function(wrap_pyfunction!(ccs_to_one_over_reduced_mobility, m)?)?;
     m.add_function(wrap_pyfunction!(ccs_to_one_over_reduced_mobility_par, m)?)?;
+    m.add_function(wrap_pyfunction!(one_over_k0_to_ccs, m)?)?;
+    m.add_function(wrap_pyfunction!(ccs_to_one_over_k0, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_monoisotopic_masses, m)?)?;
+    m.add_function(wrap_pyfunction!(isoelectric_point, m)?)?;
+    m.add_function(wrap_pyfunction!(gravy, m)?)?;
+    m.add_function(wrap_pyfunction!(aromaticity, m)?)?;
+    m.add_function(wrap_pyfunction!(aliphatic_index, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_mz, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_proforma, m)?)?;
+    m.add_function(wrap_pyfunction!(to_proforma, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_mz_with_adduct, m)?)?;
+    m.add_function(wrap_pyfunction!(neutral_mass_from_mz, m)?)?;
     m.add_function(wrap_pyfunction!(simulate_precursor_spectrum, m)?)?;
     m.add_function(wrap_pyfunction!(simulate_precursor_spectra, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_transmission_dependent_fragment_ion_isotope_distribution, m)?)?;
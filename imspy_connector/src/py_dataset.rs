@@ -1,21 +1,70 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use mscore::timstof::frame::TimsFrame;
 use pyo3::prelude::*;
 use rustdf::data::dataset::TimsDataset;
 use rustdf::data::utility::{zstd_compress, zstd_decompress, reconstruct_compressed_data, compress_collection, parse_decompressed_bruker_binary_data};
 
-use crate::py_tims_frame::{PyTimsFrame};
-use crate::py_tims_slice::PyTimsSlice;
-use numpy::{IntoPyArray, PyArray1};
-use pyo3::types::PyList;
+use crate::py_mz_spectrum::PyTimsSpectrum;
+use crate::py_tims_frame::{PyRawTimsFrame, PyTimsFrame};
+use crate::py_tims_slice::{slice_summary_to_dict, PyTimsSlice};
+use mscore::timstof::slice::SliceSummary;
+use numpy::{IntoPyArray, PyArray1, PyArrayMethods};
+use pyo3::types::{PyDict, PyList};
 use pyo3::{PyResult, Python, PyObject};
+use rustdf::algorithm::align::{align_datasets, align_rt, RtWarp};
+use rustdf::compare::RunComparisonParams;
 use rustdf::data::acquisition::AcquisitionMode;
-use rustdf::data::handle::TimsData;
+use rustdf::data::handle::{get_slice_bounded, BatchedIndexConverter, TimsData};
+use rustdf::data::projection::{FrameColumns, PartialTimsFrame};
+
+fn parse_frame_columns(columns: &[String]) -> PyResult<FrameColumns> {
+    columns.iter().try_fold(FrameColumns::NONE, |acc, name| {
+        FrameColumns::from_name(name)
+            .map(|column| acc | column)
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err(format!("Unknown frame column: {}", name)))
+    })
+}
+
+fn check_batch_lengths(frame_ids: &[u32], values_len: usize) -> PyResult<()> {
+    if frame_ids.len() != values_len {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "frame_ids has {} entries but {} values were given", frame_ids.len(), values_len
+        )));
+    }
+    Ok(())
+}
+
+fn partial_frame_to_dict(py: Python<'_>, frame: PartialTimsFrame) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("frame_id", frame.frame_id)?;
+    dict.set_item("retention_time", frame.retention_time)?;
+    if let Some(scan) = frame.scan {
+        dict.set_item("scan", scan.into_pyarray_bound(py))?;
+    }
+    if let Some(mobility) = frame.mobility {
+        dict.set_item("mobility", mobility.into_pyarray_bound(py))?;
+    }
+    if let Some(tof) = frame.tof {
+        dict.set_item("tof", tof.into_pyarray_bound(py))?;
+    }
+    if let Some(mz) = frame.mz {
+        dict.set_item("mz", mz.into_pyarray_bound(py))?;
+    }
+    if let Some(intensity) = frame.intensity {
+        dict.set_item("intensity", intensity.into_pyarray_bound(py))?;
+    }
+    Ok(dict.unbind())
+}
 
 #[pyclass]
 pub struct PyTimsDataset {
-    inner: TimsDataset,
+    pub inner: TimsDataset,
 }
 
+/// Scans, tofs and intensities of a decompressed frame
+type ScanTofIntensityArrays = (Py<PyArray1<u32>>, Py<PyArray1<u32>>, Py<PyArray1<u32>>);
+
 #[pymethods]
 impl PyTimsDataset {
     #[new]
@@ -24,12 +73,80 @@ impl PyTimsDataset {
         PyTimsDataset { inner: dataset }
     }
 
-    pub fn get_frame(&self, frame_id: u32) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.get_frame(frame_id) }
+    pub fn get_frame(&self, py: Python<'_>, frame_id: u32) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.get_frame(frame_id)) }
+    }
+
+    pub fn get_raw_frame(&self, py: Python<'_>, frame_id: u32) -> PyRawTimsFrame {
+        let inner = &self.inner;
+        PyRawTimsFrame { inner: py.allow_threads(|| inner.get_raw_frame(frame_id)) }
+    }
+
+    pub fn get_spectrum(&self, py: Python<'_>, frame_id: u32, scan: u32) -> Option<PyTimsSpectrum> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.get_spectrum(frame_id, scan)).map(|inner| PyTimsSpectrum { inner })
     }
 
-    pub fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.get_slice(frame_ids, num_threads) }
+    /// spectra of the scans in [scan_begin, scan_end), all scans of the frame if `scan_end` is None
+    #[pyo3(signature = (frame_id, scan_begin=0, scan_end=None, min_peaks=1))]
+    pub fn get_spectra(&self, py: Python<'_>, frame_id: u32, scan_begin: u32, scan_end: Option<u32>, min_peaks: usize) -> Vec<PyTimsSpectrum> {
+        let inner = &self.inner;
+        let scans = scan_begin..scan_end.unwrap_or(u32::MAX);
+        py.allow_threads(|| inner.get_spectra(frame_id, scans, min_peaks))
+            .into_iter()
+            .map(|inner| PyTimsSpectrum { inner })
+            .collect()
+    }
+
+    /// with `max_in_flight`, at most that many decoded frames wait to be collected, see `get_slice_bounded`,
+    /// this limits the decoding backlog only: the slice is not memory-bounded, it still holds every requested frame,
+    /// use `iter_tims_slices` of the python `TimsDataset` to process large runs in bounded memory
+    #[pyo3(signature = (frame_ids, num_threads, max_in_flight=None))]
+    pub fn get_slice(&self, py: Python<'_>, frame_ids: Vec<u32>, num_threads: usize, max_in_flight: Option<usize>) -> PyResult<PyTimsSlice> {
+        let inner = &self.inner;
+        let slice = match max_in_flight {
+            Some(0) => return Err(pyo3::exceptions::PyValueError::new_err("max_in_flight must be positive")),
+            Some(max_in_flight) => py.allow_threads(|| get_slice_bounded(inner, frame_ids, num_threads, max_in_flight)),
+            None => py.allow_threads(|| inner.get_slice(frame_ids, num_threads)),
+        };
+        Ok(PyTimsSlice { inner: slice })
+    }
+
+    pub fn get_frame_projected(&self, py: Python<'_>, frame_id: u32, columns: Vec<String>) -> PyResult<Py<PyDict>> {
+        let columns = parse_frame_columns(&columns)?;
+        let inner = &self.inner;
+        partial_frame_to_dict(py, py.allow_threads(|| inner.get_frame_projected(frame_id, columns)))
+    }
+
+    pub fn get_slice_projected(&self, py: Python<'_>, frame_ids: Vec<u32>, columns: Vec<String>, num_threads: usize) -> PyResult<Vec<Py<PyDict>>> {
+        let columns = parse_frame_columns(&columns)?;
+        let inner = &self.inner;
+        py.allow_threads(|| inner.get_slice_projected(frame_ids, columns, num_threads))
+            .into_iter()
+            .map(|frame| partial_frame_to_dict(py, frame))
+            .collect()
+    }
+
+    /// summary of all frames, or of the given frames, read in chunks so the data is never held in memory at once,
+    /// a KeyboardInterrupt is raised between chunks
+    #[pyo3(signature = (frame_ids=None, num_threads=4))]
+    pub fn describe(&self, py: Python<'_>, frame_ids: Option<Vec<u32>>, num_threads: usize) -> PyResult<Py<PyDict>> {
+        let frame_ids = frame_ids.unwrap_or_else(|| (1..=self.inner.get_frame_count() as u32).collect());
+        let inner = &self.inner;
+        let mut frames = Vec::with_capacity(frame_ids.len());
+        for chunk in frame_ids.chunks(256) {
+            frames.extend(py.allow_threads(|| inner.get_slice(chunk.to_vec(), num_threads).summary(num_threads).frames));
+            py.check_signals()?;
+        }
+        slice_summary_to_dict(py, &SliceSummary::from_frame_summaries(frames))
+    }
+
+    /// m/z axis covering the whole run as (mz_min, mz_max, n_bins), from the acquisition range of the metadata if present
+    pub fn global_mz_axis(&self, py: Python<'_>, resolution: i32) -> (f64, f64, usize) {
+        let inner = &self.inner;
+        let axis = py.allow_threads(|| inner.global_mz_axis(resolution));
+        (axis.mz_min, axis.mz_max, axis.n_bins)
     }
 
     pub fn get_acquisition_mode(&self) -> String {
@@ -68,16 +185,47 @@ impl PyTimsDataset {
         self.inner.loader.get_index_converter().inverse_mobility_to_scan(frame_id, &inverse_mobility_values.clone())
     }
 
+    /// convert values spread over many frames, one call per distinct frame, results keep the input order
+    #[pyo3(signature = (frame_ids, tof_values, num_threads=4))]
+    pub fn tof_to_mz_batched(&self, py: Python<'_>, frame_ids: Vec<u32>, tof_values: Vec<u32>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+        check_batch_lengths(&frame_ids, tof_values.len())?;
+        let inner = &self.inner;
+        let mz_values = py.allow_threads(|| inner.loader.get_index_converter().tof_to_mz_batched(&frame_ids, &tof_values, num_threads));
+        Ok(mz_values.into_pyarray_bound(py).unbind())
+    }
+
+    #[pyo3(signature = (frame_ids, mz_values, num_threads=4))]
+    pub fn mz_to_tof_batched(&self, py: Python<'_>, frame_ids: Vec<u32>, mz_values: Vec<f64>, num_threads: usize) -> PyResult<Py<PyArray1<u32>>> {
+        check_batch_lengths(&frame_ids, mz_values.len())?;
+        let inner = &self.inner;
+        let tof_values = py.allow_threads(|| inner.loader.get_index_converter().mz_to_tof_batched(&frame_ids, &mz_values, num_threads));
+        Ok(tof_values.into_pyarray_bound(py).unbind())
+    }
+
+    #[pyo3(signature = (frame_ids, scan_values, num_threads=4))]
+    pub fn scan_to_inverse_mobility_batched(&self, py: Python<'_>, frame_ids: Vec<u32>, scan_values: Vec<u32>, num_threads: usize) -> PyResult<Py<PyArray1<f64>>> {
+        check_batch_lengths(&frame_ids, scan_values.len())?;
+        let inner = &self.inner;
+        let inverse_mobility_values = py.allow_threads(|| inner.loader.get_index_converter().scan_to_inverse_mobility_batched(&frame_ids, &scan_values, num_threads));
+        Ok(inverse_mobility_values.into_pyarray_bound(py).unbind())
+    }
+
+    #[pyo3(signature = (frame_ids, inverse_mobility_values, num_threads=4))]
+    pub fn inverse_mobility_to_scan_batched(&self, py: Python<'_>, frame_ids: Vec<u32>, inverse_mobility_values: Vec<f64>, num_threads: usize) -> PyResult<Py<PyArray1<u32>>> {
+        check_batch_lengths(&frame_ids, inverse_mobility_values.len())?;
+        let inner = &self.inner;
+        let scan_values = py.allow_threads(|| inner.loader.get_index_converter().inverse_mobility_to_scan_batched(&frame_ids, &inverse_mobility_values, num_threads));
+        Ok(scan_values.into_pyarray_bound(py).unbind())
+    }
+
     #[staticmethod]
     pub fn compress_bytes_zstd(bytes: Vec<u8>, compression_level: i32) -> Vec<u8> {
-        let result = zstd_compress(&bytes, compression_level).unwrap();
-        result
+        zstd_compress(&bytes, compression_level).unwrap()
     }
 
     #[staticmethod]
     pub fn decompress_bytes_zstd(bytes: Vec<u8>) -> Vec<u8> {
-        let result = zstd_decompress(&bytes).unwrap();
-        result
+        zstd_decompress(&bytes).unwrap()
     }
 
     pub fn scan_tof_intensities_to_compressed_u8(&self, py: Python<'_>, scan_values: Vec<u32>, tof_values: Vec<u32>, intensity_values: Vec<u32>, total_scans: u32, compression_level: i32) -> Py<PyArray1<u8>> {
@@ -86,13 +234,13 @@ impl PyTimsDataset {
     }
 
     #[staticmethod]
-    pub fn u8_to_scan_tof_intensities(py: Python<'_>, data: Vec<u8>) -> PyResult<(Py<PyArray1<u32>>, Py<PyArray1<u32>>, Py<PyArray1<u32>>)> {
+    pub fn u8_to_scan_tof_intensities(py: Python<'_>, data: Vec<u8>) -> PyResult<ScanTofIntensityArrays> {
         let (scan_counts, tof, intensities) = parse_decompressed_bruker_binary_data(&data).unwrap();
 
         // Expand the scan counts
         let mut expanded_scans = Vec::new();
         for (index, &count) in scan_counts.iter().enumerate() {
-            expanded_scans.extend(std::iter::repeat(index as u32).take(count as usize));
+            expanded_scans.extend(std::iter::repeat_n(index as u32, count as usize));
         }
 
         Ok((
@@ -116,7 +264,7 @@ impl PyTimsDataset {
         let mz = frame.inner.ims_frame.mz.clone();
         let tof = self.inner.loader.get_index_converter().mz_to_tof(frame_id as u32, &mz);
         let inv_mob = frame.inner.ims_frame.mobility.clone();
-        let scan = self.inner.loader.get_index_converter().inverse_mobility_to_scan(1, &inv_mob).iter().map(|x| *x as u32).collect::<Vec<_>>();
+        let scan = self.inner.loader.get_index_converter().inverse_mobility_to_scan(1, &inv_mob);
 
         let compressed_frame = reconstruct_compressed_data(
             scan,
@@ -162,7 +310,7 @@ impl PyTimsDataset {
         }
 
         // compress the frames
-        let compressed_frames = compress_collection(filled_tims_frames, total_scans, compression_level, num_threads);
+        let compressed_frames = py.allow_threads(|| compress_collection(filled_tims_frames, total_scans, compression_level, num_threads));
 
         // convert the compressed frames to a python list of numpy arrays
         let py_list = PyList::empty_bound(py);
@@ -174,6 +322,10 @@ impl PyTimsDataset {
 
         Ok(py_list.into())
     }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsDataset cannot be pickled, it holds open handles to the raw data, open the dataset from its path in every process instead"))
+    }
 }
 
 #[pyclass]
@@ -241,8 +393,92 @@ pub fn get_data_for_compression(tofs: Vec<u32>, scans: Vec<u32>, intensities: Ve
 }
 
 #[pyfunction]
-pub fn get_data_for_compression_par(tofs: Vec<Vec<u32>>, scans: Vec<Vec<u32>>, intensities: Vec<Vec<u32>>, max_scans: u32, num_threads: usize) -> Vec<Vec<u8>> {
-    rustdf::data::utility::get_data_for_compression_par(tofs, scans, intensities, max_scans, num_threads)
+pub fn get_data_for_compression_par(py: Python<'_>, tofs: Vec<Vec<u32>>, scans: Vec<Vec<u32>>, intensities: Vec<Vec<u32>>, max_scans: u32, num_threads: usize) -> Vec<Vec<u8>> {
+    py.allow_threads(|| rustdf::data::utility::get_data_for_compression_par(tofs, scans, intensities, max_scans, num_threads))
+}
+
+/// match the frames of two runs by retention time and compare every pair, returns a dict of numpy arrays
+/// with one entry per pair and the TIC correlation over the run
+#[pyfunction]
+#[pyo3(signature = (real, synthetic, ms_ms_type=0, rt_tolerance=1.0, mz_resolution=1, batch_size=256, num_threads=4))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn compare_runs(py: Python<'_>, real: &PyTimsDataset, synthetic: &PyTimsDataset, ms_ms_type: i64, rt_tolerance: f64, mz_resolution: i32, batch_size: usize, num_threads: usize) -> PyResult<Py<PyDict>> {
+    let params = RunComparisonParams { ms_ms_type, rt_tolerance, mz_resolution, batch_size, num_threads };
+    let (real, synthetic) = (&real.inner, &synthetic.inner);
+    let comparison = py.allow_threads(|| rustdf::compare::compare_runs(real, synthetic, &params).map_err(|e| e.to_string()))
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("real_frame_id", comparison.real_frame_id.into_pyarray_bound(py))?;
+    dict.set_item("synthetic_frame_id", comparison.synthetic_frame_id.into_pyarray_bound(py))?;
+    dict.set_item("real_retention_time", comparison.real_retention_time.into_pyarray_bound(py))?;
+    dict.set_item("synthetic_retention_time", comparison.synthetic_retention_time.into_pyarray_bound(py))?;
+    dict.set_item("real_tic", comparison.real_tic.into_pyarray_bound(py))?;
+    dict.set_item("synthetic_tic", comparison.synthetic_tic.into_pyarray_bound(py))?;
+    dict.set_item("spectral_similarity", comparison.spectral_similarity.into_pyarray_bound(py))?;
+    dict.set_item("mobility_correlation", comparison.mobility_correlation.into_pyarray_bound(py))?;
+    dict.set_item("tic_correlation", comparison.tic_correlation)?;
+    Ok(dict.unbind())
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyRtWarp {
+    pub inner: RtWarp,
+}
+
+#[pymethods]
+impl PyRtWarp {
+    #[new]
+    pub fn new(rt_a: Vec<f64>, rt_b: Vec<f64>) -> PyResult<Self> {
+        if rt_a.len() != rt_b.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err("rt_a and rt_b must have the same length"));
+        }
+        let inner = RtWarp::new(rt_a.into_iter().zip(rt_b).collect()).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyRtWarp { inner })
+    }
+
+    #[getter]
+    pub fn rt_a(&self, py: Python) -> Py<PyArray1<f64>> {
+        self.inner.rt_a.clone().into_pyarray_bound(py).unbind()
+    }
+
+    #[getter]
+    pub fn rt_b(&self, py: Python) -> Py<PyArray1<f64>> {
+        self.inner.rt_b.clone().into_pyarray_bound(py).unbind()
+    }
+
+    pub fn map(&self, py: Python, rt: &Bound<'_, PyArray1<f64>>) -> PyResult<Py<PyArray1<f64>>> {
+        let mapped: Vec<f64> = rt.to_vec()?.into_iter().map(|value| self.inner.map(value)).collect();
+        Ok(mapped.into_pyarray_bound(py).unbind())
+    }
+
+    pub fn inverse(&self, py: Python, rt: &Bound<'_, PyArray1<f64>>) -> PyResult<Py<PyArray1<f64>>> {
+        let mapped: Vec<f64> = rt.to_vec()?.into_iter().map(|value| self.inner.inverse(value)).collect();
+        Ok(mapped.into_pyarray_bound(py).unbind())
+    }
+}
+
+/// Fit a retention time warp through anchors of two runs matched by m/z
+#[pyfunction]
+#[pyo3(signature = (rt_a, mz_a, rt_b, mz_b, ppm=10.0))]
+pub fn align_retention_times(rt_a: Vec<f64>, mz_a: Vec<f64>, rt_b: Vec<f64>, mz_b: Vec<f64>, ppm: f64) -> PyResult<PyRtWarp> {
+    if rt_a.len() != mz_a.len() || rt_b.len() != mz_b.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err("retention times and m/z values of the anchors must have the same length"));
+    }
+    let anchors_a: Vec<(f64, f64)> = rt_a.into_iter().zip(mz_a).collect();
+    let anchors_b: Vec<(f64, f64)> = rt_b.into_iter().zip(mz_b).collect();
+    Ok(PyRtWarp { inner: align_rt(&anchors_a, &anchors_b, ppm) })
+}
+
+/// Retention time warp from run a to run b, anchored on the most intense MS1 peaks of both runs
+#[pyfunction]
+#[pyo3(signature = (a, b, rt_bin_width=30.0, top_k=50, ppm=10.0, num_threads=4))]
+pub fn align_runs(py: Python<'_>, a: &PyTimsDataset, b: &PyTimsDataset, rt_bin_width: f64, top_k: usize, ppm: f64, num_threads: usize) -> PyResult<PyRtWarp> {
+    let (a, b) = (&a.inner, &b.inner);
+    let inner = py.allow_threads(|| align_datasets(a, b, rt_bin_width, top_k, ppm, num_threads).map_err(|e| e.to_string()))
+        .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+    Ok(PyRtWarp { inner })
 }
 
 #[pymodule]
@@ -254,5 +490,9 @@ pub fn py_dataset(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(get_realdata, m)?)?;
     m.add_function(wrap_pyfunction!(get_data_for_compression, m)?)?;
     m.add_function(wrap_pyfunction!(get_data_for_compression_par, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_runs, m)?)?;
+    m.add_class::<PyRtWarp>()?;
+    m.add_function(wrap_pyfunction!(align_retention_times, m)?)?;
+    m.add_function(wrap_pyfunction!(align_runs, m)?)?;
     Ok(())
 }
\ No newline at end of file
@@ -1,8 +1,15 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use pyo3::prelude::*;
 
-use rustdf::data::dda::{PASEFDDAFragment, TimsDatasetDDA};
+use numpy::IntoPyArray;
+use pyo3::types::PyDict;
+use rustdf::data::dda::{PASEFDDAFragment, PrecursorPurity, RefinedPrecursor, TimsDatasetDDA};
 use rustdf::data::handle::TimsData;
 use rustdf::data::meta::{DDAPrecursor};
+use rustdf::export::mzml::write_dda_mzml;
+use rustdf::export::parquet::{parse_compression, write_pasef_fragments_parquet};
+use std::path::Path;
 use crate::py_tims_frame::PyTimsFrame;
 use crate::py_tims_slice::PyTimsSlice;
 
@@ -15,6 +22,7 @@ pub struct PyDDAPrecursor {
 impl PyDDAPrecursor {
     #[new]
     #[pyo3(signature = (frame_id, precursor_id, highest_intensity_mz, average_mz, inverse_ion_mobility, collision_energy, precuror_total_intensity, isolation_mz, isolation_width, mono_mz=None, charge=None))]
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
     pub fn new(
         frame_id: i64,
         precursor_id: i64,
@@ -86,16 +94,19 @@ pub struct PyTimsDatasetDDA {
 #[pymethods]
 impl PyTimsDatasetDDA {
     #[new]
-    pub fn new(data_path: &str, bruker_lib_path: &str, in_memory: bool, use_bruker_sdk: bool) -> Self {
-        let dataset = TimsDatasetDDA::new(bruker_lib_path, data_path, in_memory, use_bruker_sdk);
-        PyTimsDatasetDDA { inner: dataset }
+    pub fn new(data_path: &str, bruker_lib_path: &str, in_memory: bool, use_bruker_sdk: bool) -> PyResult<Self> {
+        let dataset = TimsDatasetDDA::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsDatasetDDA { inner: dataset })
     }
-    pub fn get_frame(&self, frame_id: u32) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.get_frame(frame_id) }
+    pub fn get_frame(&self, py: Python, frame_id: u32) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.get_frame(frame_id)) }
     }
 
-    pub fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.get_slice(frame_ids, num_threads) }
+    pub fn get_slice(&self, py: Python, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.get_slice(frame_ids, num_threads)) }
     }
 
     pub fn get_acquisition_mode(&self) -> String {
@@ -110,19 +121,93 @@ impl PyTimsDatasetDDA {
         self.inner.get_data_path()
     }
 
-    pub fn get_pasef_fragments(&self, num_threads: usize) -> Vec<PyTimsFragmentDDA> {
-        let pasef_fragments = self.inner.get_pasef_fragments(num_threads);
-        pasef_fragments.iter().map(|pasef_fragment| PyTimsFragmentDDA { inner: pasef_fragment.clone() }).collect()
+    #[pyo3(signature = (num_threads=None))]
+    pub fn get_pasef_fragments(&self, py: Python, num_threads: Option<usize>) -> Vec<PyTimsFragmentDDA> {
+        let inner = &self.inner;
+        let pasef_fragments = py.allow_threads(|| inner.get_pasef_fragments(num_threads));
+        pasef_fragments.into_iter().map(|pasef_fragment| PyTimsFragmentDDA { inner: pasef_fragment }).collect()
     }
 
-    pub fn get_selected_precursors(&self) -> Vec<PyDDAPrecursor> {
-        let pasef_precursor_meta = self.inner.get_selected_precursors();
+    pub fn get_selected_precursors(&self, py: Python) -> Vec<PyDDAPrecursor> {
+        let inner = &self.inner;
+        let pasef_precursor_meta = py.allow_threads(|| inner.get_selected_precursors());
         pasef_precursor_meta.iter().map(|precursor_meta| PyDDAPrecursor { inner: precursor_meta.clone() }).collect()
     }
 
-    pub fn get_precursor_frames(&self, min_intensity: f64, max_peaks: usize, num_threads: usize) -> Vec<PyTimsFrame> {
-        let precursor_frames = self.inner.get_precursor_frames(min_intensity, max_peaks, num_threads);
-        precursor_frames.iter().map(|frame| PyTimsFrame { inner: frame.clone() }).collect()
+    /// isolation purity of every selected precursor as columns, see `TimsDatasetDDA::compute_precursor_purity`
+    #[pyo3(signature = (ppm=10.0, num_isotopes=3, num_threads=4))]
+    pub fn compute_precursor_purity(&self, py: Python, ppm: f64, num_isotopes: usize, num_threads: usize) -> PyResult<Py<PyDict>> {
+        if ppm <= 0.0 || num_isotopes == 0 || num_threads == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("ppm, num_isotopes and num_threads must be positive"));
+        }
+        let inner = &self.inner;
+        let purities = py.allow_threads(|| inner.compute_precursor_purity(ppm, num_isotopes, num_threads));
+
+        let dict = PyDict::new_bound(py);
+        let precursor_id: Vec<i64> = purities.iter().map(|p| p.precursor_id).collect();
+        dict.set_item("precursor_id", precursor_id.into_pyarray_bound(py))?;
+        let column = |value: fn(&PrecursorPurity) -> f64| -> Vec<f64> { purities.iter().map(value).collect() };
+        dict.set_item("purity", column(|p| p.purity).into_pyarray_bound(py))?;
+        dict.set_item("total_window_intensity", column(|p| p.total_window_intensity).into_pyarray_bound(py))?;
+        dict.set_item("envelope_intensity", column(|p| p.envelope_intensity).into_pyarray_bound(py))?;
+        Ok(dict.unbind())
+    }
+
+    /// monoisotopic m/z and charge of every selected precursor re-estimated from its isotope envelope as columns,
+    /// missing m/z are NaN and missing charges 0, see `TimsDatasetDDA::refine_precursors`
+    #[pyo3(signature = (ppm=10.0, max_charge=4, num_threads=4))]
+    pub fn refine_precursors(&self, py: Python, ppm: f64, max_charge: i32, num_threads: usize) -> PyResult<Py<PyDict>> {
+        if ppm <= 0.0 || max_charge <= 0 || num_threads == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("ppm, max_charge and num_threads must be positive"));
+        }
+        let inner = &self.inner;
+        let refined = py.allow_threads(|| inner.refine_precursors(ppm, max_charge, num_threads));
+
+        let dict = PyDict::new_bound(py);
+        let precursor_id: Vec<i64> = refined.iter().map(|p| p.precursor_id).collect();
+        dict.set_item("precursor_id", precursor_id.into_pyarray_bound(py))?;
+        let mz_column = |value: fn(&RefinedPrecursor) -> Option<f64>| -> Vec<f64> { refined.iter().map(|p| value(p).unwrap_or(f64::NAN)).collect() };
+        let charge_column = |value: fn(&RefinedPrecursor) -> Option<i64>| -> Vec<i64> { refined.iter().map(|p| value(p).unwrap_or(0)).collect() };
+        dict.set_item("original_mono_mz", mz_column(|p| p.original_mono_mz).into_pyarray_bound(py))?;
+        dict.set_item("original_charge", charge_column(|p| p.original_charge).into_pyarray_bound(py))?;
+        dict.set_item("mono_mz", mz_column(|p| p.mono_mz).into_pyarray_bound(py))?;
+        dict.set_item("charge", charge_column(|p| p.charge).into_pyarray_bound(py))?;
+        let score: Vec<f64> = refined.iter().map(|p| p.score).collect();
+        dict.set_item("score", score.into_pyarray_bound(py))?;
+        let num_matched: Vec<u64> = refined.iter().map(|p| p.num_matched as u64).collect();
+        dict.set_item("num_matched", num_matched.into_pyarray_bound(py))?;
+        let status: Vec<String> = refined.iter().map(|p| p.status.to_string()).collect();
+        dict.set_item("status", status)?;
+        Ok(dict.unbind())
+    }
+
+    pub fn get_precursor_frames(&self, py: Python, min_intensity: f64, max_peaks: usize, num_threads: usize) -> Vec<PyTimsFrame> {
+        let inner = &self.inner;
+        let precursor_frames = py.allow_threads(|| inner.get_precursor_frames(min_intensity, max_peaks, num_threads));
+        precursor_frames.into_iter().map(|frame| PyTimsFrame { inner: frame }).collect()
+    }
+
+    #[pyo3(signature = (path, num_threads, compression="zstd"))]
+    pub fn write_fragments_parquet(&self, py: Python, path: &str, num_threads: usize, compression: &str) -> PyResult<()> {
+        let compression = parse_compression(compression).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let inner = &self.inner;
+        py.allow_threads(|| {
+            let fragments = inner.get_pasef_fragments(num_threads);
+            let precursors = inner.get_selected_precursors();
+            write_pasef_fragments_parquet(&fragments, &precursors, Path::new(path), compression).map_err(|e| e.to_string())
+        }).map_err(pyo3::exceptions::PyIOError::new_err)
+    }
+
+    /// Write precursor frames and fragment spectra to an indexed mzML file, returns the number of spectra
+    #[pyo3(signature = (path, merge_precursors=true, num_threads=4, compress=true))]
+    pub fn write_mzml(&self, py: Python, path: &str, merge_precursors: bool, num_threads: usize, compress: bool) -> PyResult<usize> {
+        let inner = &self.inner;
+        py.allow_threads(|| write_dda_mzml(inner, Path::new(path), merge_precursors, compress, num_threads).map_err(|e| e.to_string()))
+            .map_err(pyo3::exceptions::PyIOError::new_err)
+    }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsDatasetDDA cannot be pickled, it holds open handles to the raw data, open the dataset from its path in every process instead"))
     }
 }
 
@@ -134,13 +219,16 @@ pub struct PyTimsFragmentDDA {
 #[pymethods]
 impl PyTimsFragmentDDA {
     #[new]
-    pub fn new(frame_id: u32, precursor_id: u32, collision_energy: f64, selected_fragment: &PyTimsFrame) -> PyResult<Self> {
+    #[pyo3(signature = (frame_id, precursor_id, collision_energy, selected_fragment, isolation_mz=None, isolation_width=None, scan_begin=0, scan_end=0))]
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(frame_id: u32, precursor_id: u32, collision_energy: f64, selected_fragment: &PyTimsFrame, isolation_mz: Option<f64>, isolation_width: Option<f64>, scan_begin: u32, scan_end: u32) -> PyResult<Self> {
 
         let pasef_fragment = PASEFDDAFragment {
-            frame_id,
-            precursor_id,
-            collision_energy,
-            selected_fragment: selected_fragment.inner.clone(),
+            isolation_mz: isolation_mz.unwrap_or(f64::NAN),
+            isolation_width: isolation_width.unwrap_or(f64::NAN),
+            scan_begin,
+            scan_end,
+            ..PASEFDDAFragment::new(frame_id, precursor_id, collision_energy, selected_fragment.inner.clone())
         };
 
         Ok(PyTimsFragmentDDA { inner: pasef_fragment })
@@ -157,6 +245,36 @@ impl PyTimsFragmentDDA {
 
     #[getter]
     pub fn collision_energy(&self) -> f64 { self.inner.collision_energy }
+
+    #[getter]
+    pub fn isolation_mz(&self) -> f64 { self.inner.isolation_mz }
+
+    #[getter]
+    pub fn isolation_width(&self) -> f64 { self.inner.isolation_width }
+
+    #[getter]
+    pub fn scan_begin(&self) -> u32 { self.inner.scan_begin }
+
+    #[getter]
+    pub fn scan_end(&self) -> u32 { self.inner.scan_end }
+}
+
+/// metadata of a list of fragments as columns, one entry per fragment
+#[pyfunction]
+pub fn fragments_to_dict(py: Python, fragments: Vec<PyRef<PyTimsFragmentDDA>>) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new_bound(py);
+    let column = |value: fn(&PASEFDDAFragment) -> u32| -> Vec<u32> { fragments.iter().map(|f| value(&f.inner)).collect() };
+    dict.set_item("frame_id", column(|f| f.frame_id).into_pyarray_bound(py))?;
+    dict.set_item("precursor_id", column(|f| f.precursor_id).into_pyarray_bound(py))?;
+    dict.set_item("scan_begin", column(|f| f.scan_begin).into_pyarray_bound(py))?;
+    dict.set_item("scan_end", column(|f| f.scan_end).into_pyarray_bound(py))?;
+    let column = |value: fn(&PASEFDDAFragment) -> f64| -> Vec<f64> { fragments.iter().map(|f| value(&f.inner)).collect() };
+    dict.set_item("collision_energy", column(|f| f.collision_energy).into_pyarray_bound(py))?;
+    dict.set_item("isolation_mz", column(|f| f.isolation_mz).into_pyarray_bound(py))?;
+    dict.set_item("isolation_width", column(|f| f.isolation_width).into_pyarray_bound(py))?;
+    let num_peaks: Vec<u64> = fragments.iter().map(|f| f.inner.selected_fragment.ims_frame.mz.len() as u64).collect();
+    dict.set_item("num_peaks", num_peaks.into_pyarray_bound(py))?;
+    Ok(dict.unbind())
 }
 
 #[pymodule]
@@ -164,5 +282,6 @@ pub fn py_dda(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyTimsDatasetDDA>()?;
     m.add_class::<PyTimsFragmentDDA>()?;
     m.add_class::<PyDDAPrecursor>()?;
+    m.add_function(wrap_pyfunction!(fragments_to_dict, m)?)?;
     Ok(())
 }
\ No newline at end of file
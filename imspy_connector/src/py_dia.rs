@@ -1,3 +1,5 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use pyo3::prelude::*;
 
 use rustdf::data::dia::TimsDatasetDIA;
@@ -7,22 +9,25 @@ use crate::py_tims_slice::PyTimsSlice;
 
 #[pyclass]
 pub struct PyTimsDatasetDIA {
-    inner: TimsDatasetDIA,
+    pub inner: TimsDatasetDIA,
 }
 
 #[pymethods]
 impl PyTimsDatasetDIA {
     #[new]
-    pub fn new(data_path: &str, bruker_lib_path: &str, in_memory: bool, use_bruker_sdk: bool) -> Self {
-        let dataset = TimsDatasetDIA::new(bruker_lib_path, data_path, in_memory, use_bruker_sdk);
-        PyTimsDatasetDIA { inner: dataset }
+    pub fn new(data_path: &str, bruker_lib_path: &str, in_memory: bool, use_bruker_sdk: bool) -> PyResult<Self> {
+        let dataset = TimsDatasetDIA::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsDatasetDIA { inner: dataset })
     }
-    pub fn get_frame(&self, frame_id: u32) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.get_frame(frame_id) }
+    pub fn get_frame(&self, py: Python, frame_id: u32) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.get_frame(frame_id)) }
     }
 
-    pub fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.get_slice(frame_ids, num_threads) }
+    pub fn get_slice(&self, py: Python, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.get_slice(frame_ids, num_threads)) }
     }
 
     pub fn get_acquisition_mode(&self) -> String {
@@ -37,12 +42,18 @@ impl PyTimsDatasetDIA {
         self.inner.get_data_path()
     }
     
-    pub fn sample_precursor_signal(&self, num_frames: usize, max_intensity: f64, take_probability: f64) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.sample_precursor_signal(num_frames, max_intensity, take_probability) }
+    pub fn sample_precursor_signal(&self, py: Python, num_frames: usize, max_intensity: f64, take_probability: f64) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.sample_precursor_signal(num_frames, max_intensity, take_probability)) }
     }
     
-    pub fn sample_fragment_signal(&self, num_frames: usize, window_group: u32, max_intensity: f64, take_probability: f64) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.sample_fragment_signal(num_frames, window_group, max_intensity, take_probability) }
+    pub fn sample_fragment_signal(&self, py: Python, num_frames: usize, window_group: u32, max_intensity: f64, take_probability: f64) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.sample_fragment_signal(num_frames, window_group, max_intensity, take_probability)) }
+    }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsDatasetDIA cannot be pickled, it holds open handles to the raw data, open the dataset from its path in every process instead"))
     }
 }
 
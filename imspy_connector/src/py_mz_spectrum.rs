@@ -1,9 +1,24 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use pyo3::prelude::*;
+use crate::py_buffer::{shared_array};
 use numpy::{PyArray1, IntoPyArray, PyArrayMethods};
-use mscore::data::spectrum::{ToResolution, Vectorized};
-use mscore::data::spectrum::{MzSpectrum, IndexedMzSpectrum, MsType, MzSpectrumVectorized};
+use mscore::data::spectrum::{ApproxEq, ToResolution, Vectorized};
+use mscore::data::spectrum::{Binning, MzSpectrum, IndexedMzSpectrum, MsType, MzSpectrumVectorized, ResampleMethod};
 use mscore::timstof::spectrum::{TimsSpectrum};
-use pyo3::types::{PyList, PyTuple};
+use pyo3::types::{PyBytes, PyList, PyTuple};
+use mscore::data::serialization::BinarySerializable;
+use mscore::algorithm::noise::NoiseEstimator;
+use mscore::algorithm::similarity;
+
+/// m/z range shown by `__repr__`, `None` for empty spectra
+pub fn format_mz_range(mz: &[f64]) -> String {
+    if mz.is_empty() {
+        return "None".to_string();
+    }
+    let (min, max) = mz.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &mz| (min.min(mz), max.max(mz)));
+    format!("({:.4}, {:.4})", min, max)
+}
 
 #[pyclass]
 #[derive(Clone)]
@@ -36,16 +51,16 @@ pub struct PyMzSpectrum {
 
 impl PyMzSpectrum {
     #[new]
-    pub unsafe fn new(mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
+    pub fn new(mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
         Ok(PyMzSpectrum {
             inner: MzSpectrum {
-                mz: mz.as_slice()?.to_vec(),
-                intensity: intensity.as_slice()?.to_vec(),
+                mz: mz.to_vec()?,
+                intensity: intensity.to_vec()?,
             }
         })
     }
     #[staticmethod]
-    pub unsafe fn from_mzspectra_list(list: Vec<PyMzSpectrum>, resolution: i32) -> PyResult<Self> {
+    pub fn from_mzspectra_list(list: Vec<PyMzSpectrum>, resolution: i32) -> PyResult<Self> {
         if list.is_empty(){
             Ok(PyMzSpectrum { 
                 inner: MzSpectrum {
@@ -64,12 +79,12 @@ impl PyMzSpectrum {
     }
 
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.mz.clone().into_pyarray_bound(py).unbind()
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.mz, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.intensity, slf.as_any()).unbind()
     }
     pub fn to_windows(&self, py: Python, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> PyResult<PyObject> {
         let spectra = self.inner.to_windows(window_length, overlapping, min_peaks, min_intensity);
@@ -100,6 +115,27 @@ impl PyMzSpectrum {
         Ok(py_vectorized)
     }
 
+    #[pyo3(signature = (binning, width, mz_min=50.0, mz_max=2000.0))]
+    pub fn vectorize(&self, binning: &str, width: f64, mz_min: f64, mz_max: f64) -> PyResult<PyMzSpectrumVectorized> {
+        let binning = Binning::from_name(binning, width, mz_min, mz_max).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyMzSpectrumVectorized { inner: self.inner.vectorize(binning) })
+    }
+
+    /// intensities of the spectrum resampled onto a strictly increasing m/z grid, one per grid point
+    #[pyo3(signature = (grid, method="linear", ppm=10.0))]
+    pub fn resample(&self, py: Python, grid: &Bound<'_, PyArray1<f64>>, method: &str, ppm: f64) -> PyResult<Py<PyArray1<f64>>> {
+        let method = ResampleMethod::from_name(method, ppm).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let resampled = self.inner.resample(&grid.to_vec()?, method).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(resampled.into_pyarray_bound(py).unbind())
+    }
+
+    /// signal-to-noise ratio of every peak relative to the noise of the peaks within window / 2 Da of it
+    #[pyo3(signature = (window=100.0, method="mad"))]
+    pub fn signal_to_noise(&self, py: Python, window: f64, method: &str) -> PyResult<Py<PyArray1<f64>>> {
+        let method = NoiseEstimator::from_name(method).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(self.inner.signal_to_noise(window, method).into_pyarray_bound(py).unbind())
+    }
+
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> PyResult<PyMzSpectrum> {
         let filtered = self.inner.filter_ranged(mz_min, mz_max, intensity_min, intensity_max);
         let py_filtered = PyMzSpectrum {
@@ -113,6 +149,23 @@ impl PyMzSpectrum {
     pub fn __mul__(&self, scale: f64) -> PyResult<PyMzSpectrum> {
         Ok(PyMzSpectrum { inner: self.inner.clone() * scale })
     }
+    pub fn __rmul__(&self, scale: f64) -> PyResult<PyMzSpectrum> {
+        self.__mul__(scale)
+    }
+    #[pyo3(signature = (other, resolution=6))]
+    pub fn add(&self, other: &PyMzSpectrum, resolution: i32) -> PyMzSpectrum {
+        PyMzSpectrum { inner: self.inner.add_with_resolution(&other.inner, resolution) }
+    }
+    pub fn top_n(&self, n: usize) -> PyMzSpectrum {
+        PyMzSpectrum { inner: self.inner.top_n(n) }
+    }
+    pub fn filter_relative_intensity(&self, min_fraction_of_base_peak: f64) -> PyMzSpectrum {
+        PyMzSpectrum { inner: self.inner.filter_relative_intensity(min_fraction_of_base_peak) }
+    }
+    #[pyo3(signature = (other, tolerance=0.01))]
+    pub fn subtract(&self, other: &PyMzSpectrum, tolerance: f64) -> PyMzSpectrum {
+        PyMzSpectrum { inner: self.inner.sub_baseline(&other.inner, tolerance) }
+    }
 
     pub fn to_centroided(&self, baseline_noise_level: i32, sigma: f64, normalize: bool) -> PyMzSpectrum {
         PyMzSpectrum { inner: self.inner.to_centroid(baseline_noise_level, sigma, normalize) }
@@ -128,6 +181,50 @@ impl PyMzSpectrum {
     pub fn add_mz_noise_normal(&self, noise_ppm: f64) -> PyMzSpectrum {
         PyMzSpectrum { inner: self.inner.add_mz_noise_normal(noise_ppm) }
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("MzSpectrum(peaks={}, mz_range={})", self.inner.mz.len(), format_mz_range(&self.inner.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyMzSpectrum { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyMzSpectrum>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
@@ -139,12 +236,18 @@ pub struct PyMzSpectrumVectorized {
 #[pymethods]
 impl PyMzSpectrumVectorized {
     #[new]
-    pub unsafe fn new(indices: &Bound<'_, PyArray1<i32>>, values: &Bound<'_, PyArray1<f64>>, resolution: i32) -> PyResult<Self> {
+    #[pyo3(signature = (indices, values, resolution, binning=None, width=None, mz_min=50.0, mz_max=2000.0))]
+    pub fn new(indices: &Bound<'_, PyArray1<i32>>, values: &Bound<'_, PyArray1<f64>>, resolution: i32, binning: Option<&str>, width: Option<f64>, mz_min: f64, mz_max: f64) -> PyResult<Self> {
+        let binning = match binning {
+            Some(name) => Binning::from_name(name, width.unwrap_or(resolution as f64), mz_min, mz_max).map_err(pyo3::exceptions::PyValueError::new_err)?,
+            None => Binning::Decimal(resolution),
+        };
         Ok(PyMzSpectrumVectorized {
             inner: MzSpectrumVectorized {
-                resolution,
-                indices: indices.as_slice()?.to_vec(),
-                values: values.as_slice()?.to_vec(),
+                resolution: binning.resolution(),
+                binning,
+                indices: indices.to_vec()?,
+                values: values.to_vec()?,
             },
         })
     }
@@ -159,18 +262,77 @@ impl PyMzSpectrumVectorized {
         self.inner.resolution
     }
 
+    /// name and parameters of the binning, (name, width, mz_min, mz_max) with None for unused parameters
     #[getter]
-    pub fn indices(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.indices.clone().into_pyarray_bound(py).unbind()
+    pub fn binning(&self) -> (&'static str, f64, Option<f64>, Option<f64>) {
+        match self.inner.binning {
+            Binning::Decimal(resolution) => ("decimal", resolution as f64, None, None),
+            Binning::FixedDa(width) => ("da", width, None, None),
+            Binning::Ppm(ppm, mz_min, mz_max) => ("ppm", ppm, Some(mz_min), Some(mz_max)),
+        }
+    }
+
+    pub fn bin_centers(&self, py: Python) -> Py<PyArray1<f64>> {
+        self.inner.bin_centers().into_pyarray_bound(py).unbind()
     }
 
     #[getter]
-    pub fn values(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.values.clone().into_pyarray_bound(py).unbind()
+    pub fn indices(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.indices, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn values(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.values, slf.as_any()).unbind()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("MzSpectrumVectorized(binning={:?}, peaks={})", self.inner.binning, self.inner.indices.len())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.indices.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyMzSpectrumVectorized { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyMzSpectrumVectorized>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
     }
 }
 
 #[pyclass]
+#[derive(Clone)]
 pub struct PyIndexedMzSpectrum {
     pub inner: IndexedMzSpectrum,
 }
@@ -178,28 +340,28 @@ pub struct PyIndexedMzSpectrum {
 #[pymethods]
 impl PyIndexedMzSpectrum {
     #[new]
-    pub unsafe fn new(index:&Bound<'_, PyArray1<i32>>, mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
+    pub fn new(index:&Bound<'_, PyArray1<i32>>, mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
         Ok(PyIndexedMzSpectrum {
             inner: IndexedMzSpectrum {
-                index: index.as_slice()?.to_vec(),
-                mz_spectrum: MzSpectrum { mz: mz.as_slice()?.to_vec(), intensity:  intensity.as_slice()?.to_vec() },
+                index: index.to_vec()?,
+                mz_spectrum: MzSpectrum { mz: mz.to_vec()?, intensity:  intensity.to_vec()? },
             },
         })
     }
 
     #[getter]
-    pub fn index(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.index.clone().into_pyarray_bound(py).unbind()
+    pub fn index(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.index, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.mz_spectrum.mz.clone().into_pyarray_bound(py).unbind()
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.mz_spectrum.mz, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.mz_spectrum.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.mz_spectrum.intensity, slf.as_any()).unbind()
     }
 
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> PyResult<PyIndexedMzSpectrum> {
@@ -209,6 +371,50 @@ impl PyIndexedMzSpectrum {
         };
         Ok(py_filtered)
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("IndexedMzSpectrum(peaks={}, mz_range={})", self.inner.mz_spectrum.mz.len(), format_mz_range(&self.inner.mz_spectrum.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.mz_spectrum.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyIndexedMzSpectrum { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyIndexedMzSpectrum>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
@@ -220,8 +426,9 @@ pub struct PyTimsSpectrum {
 #[pymethods]
 impl PyTimsSpectrum {
     #[new]
-    pub unsafe fn new(frame_id: i32, scan: i32, retention_time: f64, mobility: f64,
-                      ms_type: i32, index: &Bound<'_, PyArray1<i32>>, mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(frame_id: i32, scan: i32, retention_time: f64, mobility: f64,
+               ms_type: i32, index: &Bound<'_, PyArray1<i32>>, mz: &Bound<'_, PyArray1<f64>>, intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
         Ok(PyTimsSpectrum {
             inner: TimsSpectrum {
                 frame_id,
@@ -230,10 +437,10 @@ impl PyTimsSpectrum {
                 mobility,
                 ms_type: MsType::new(ms_type),
                 spectrum: IndexedMzSpectrum {
-                    index: index.as_slice()?.to_vec(),
+                    index: index.to_vec()?,
                     mz_spectrum: MzSpectrum {
-                        mz: mz.as_slice()?.to_vec(),
-                        intensity: intensity.as_slice()?.to_vec(),
+                        mz: mz.to_vec()?,
+                        intensity: intensity.to_vec()?,
                     },
                 },
             },
@@ -261,18 +468,18 @@ impl PyTimsSpectrum {
     }
 
     #[getter]
-    pub fn index(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.spectrum.index.clone().into_pyarray_bound(py).unbind()
+    pub fn index(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.spectrum.index, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.spectrum.mz_spectrum.mz.clone().into_pyarray_bound(py).unbind()
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.spectrum.mz_spectrum.mz, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.spectrum.mz_spectrum.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.spectrum.mz_spectrum.intensity, slf.as_any()).unbind()
     }
 
     #[getter]
@@ -306,6 +513,81 @@ impl PyTimsSpectrum {
     pub fn __add__(&self, other: PyTimsSpectrum) -> PyResult<Self> {
         Ok(PyTimsSpectrum { inner: self.inner.clone() + other.inner })
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsSpectrum(frame_id={}, scan={}, ms_type={}, retention_time={:.3}, mobility={:.4}, peaks={}, mz_range={})",
+            self.inner.frame_id, self.inner.scan, self.inner.ms_type, self.inner.retention_time, self.inner.mobility,
+            self.inner.spectrum.mz_spectrum.mz.len(), format_mz_range(&self.inner.spectrum.mz_spectrum.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.spectrum.mz_spectrum.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsSpectrum { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsSpectrum>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+#[pyfunction]
+pub fn spectral_entropy(spectrum: PyMzSpectrum) -> f64 {
+    similarity::spectral_entropy(&spectrum.inner)
+}
+
+#[pyfunction]
+#[pyo3(signature = (a, b, resolution=2))]
+pub fn entropy_similarity(a: PyMzSpectrum, b: PyMzSpectrum, resolution: i32) -> f64 {
+    similarity::entropy_similarity(&a.inner, &b.inner, resolution)
+}
+
+/// entropy similarity of the spectra at first[i] and second[i] for every i
+#[pyfunction]
+#[pyo3(signature = (spectra, first, second, resolution=2, num_threads=4))]
+pub fn entropy_similarity_pairs<'py>(py: Python<'py>, spectra: Vec<PyMzSpectrum>, first: Vec<usize>, second: Vec<usize>, resolution: i32, num_threads: usize) -> PyResult<Bound<'py, PyArray1<f64>>> {
+    if first.len() != second.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "got {} first and {} second indices", first.len(), second.len())));
+    }
+    if let Some(&index) = first.iter().chain(&second).find(|&&index| index >= spectra.len()) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "index {} is out of range for {} spectra", index, spectra.len())));
+    }
+    let spectra: Vec<MzSpectrum> = spectra.into_iter().map(|spectrum| spectrum.inner).collect();
+    let pairs: Vec<(usize, usize)> = first.into_iter().zip(second).collect();
+    let scores = py.allow_threads(|| similarity::entropy_similarity_pairs(&spectra, &pairs, resolution, num_threads));
+    Ok(scores.into_pyarray_bound(py))
 }
 
 #[pymodule]
@@ -315,5 +597,8 @@ pub fn py_spectrum(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyMzSpectrumVectorized>()?;
     m.add_class::<PyIndexedMzSpectrum>()?;
     m.add_class::<PyTimsSpectrum>()?;
+    m.add_function(wrap_pyfunction!(spectral_entropy, m)?)?;
+    m.add_function(wrap_pyfunction!(entropy_similarity, m)?)?;
+    m.add_function(wrap_pyfunction!(entropy_similarity_pairs, m)?)?;
     Ok(())
 }
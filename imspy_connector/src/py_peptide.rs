@@ -1,5 +1,11 @@
-use std::collections::{HashMap};
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
+use std::collections::{BTreeMap, HashMap};
 use pyo3::prelude::*;
+use numpy::{IntoPyArray, PyArray1};
+use mscore::algorithm::peptide::peptide_sequence_to_atomic_composition;
+use mscore::chemistry::formulas::Adduct;
+use mscore::chemistry::label::LabelScheme;
 
 use mscore::data::peptide::{FragmentType, PeptideSequence, PeptideProductIon,
                             PeptideProductIonSeries, PeptideProductIonSeriesCollection, PeptideIon};
@@ -7,6 +13,12 @@ use crate::py_annotation::PyMzSpectrumAnnotated;
 
 use crate::py_mz_spectrum::PyMzSpectrum;
 
+/// split (m/z, abundance) pairs into two numpy arrays
+fn distribution_to_arrays(py: Python, distribution: Vec<(f64, f64)>) -> (Py<PyArray1<f64>>, Py<PyArray1<f64>>) {
+    let (mz, abundance): (Vec<f64>, Vec<f64>) = distribution.into_iter().unzip();
+    (mz.into_pyarray_bound(py).unbind(), abundance.into_pyarray_bound(py).unbind())
+}
+
 #[pyclass]
 pub struct PyPeptideIon {
     pub inner: PeptideIon,
@@ -45,6 +57,41 @@ impl PyPeptideIon {
         self.inner.sequence.peptide_id
     }
 
+    #[getter]
+    pub fn label(&self) -> Option<&'static str> {
+        self.inner.label.map(|label| label.name())
+    }
+
+    pub fn with_label(&self, label: &str) -> PyResult<PyPeptideIon> {
+        let label = LabelScheme::from_name(label).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyPeptideIon { inner: self.inner.with_label(label) })
+    }
+
+    #[getter]
+    pub fn adduct(&self) -> Option<String> {
+        self.inner.adduct.as_ref().map(|adduct| adduct.name.clone())
+    }
+
+    pub fn with_adduct(&self, adduct: &str) -> PyResult<PyPeptideIon> {
+        let adduct = Adduct::from_name(adduct).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyPeptideIon { inner: self.inner.with_adduct(adduct) })
+    }
+
+    #[getter]
+    pub fn mono_isotopic_mass(&self) -> f64 {
+        self.inner.mono_isotopic_mass()
+    }
+
+    pub fn atomic_composition(&self) -> HashMap<&str, i32> {
+        // includes the atoms of a stable isotope label
+        peptide_sequence_to_atomic_composition(&self.inner.sequence, self.inner.label)
+    }
+
+    /// m/z and relative abundance of the isotope peaks of the ion
+    pub fn isotope_distribution(&self, py: Python, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> (Py<PyArray1<f64>>, Py<PyArray1<f64>>) {
+        distribution_to_arrays(py, self.inner.calculate_isotope_distribution(mass_tolerance, abundance_threshold, max_result, intensity_min))
+    }
+
     pub fn calculate_isotopic_spectrum(&self, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> PyMzSpectrum {
         PyMzSpectrum { inner: self.inner.calculate_isotopic_spectrum(mass_tolerance, abundance_threshold, max_result, intensity_min) }
     }
@@ -114,10 +161,7 @@ impl PyPeptideProductIonSeriesCollection {
 
     pub fn find_ion_series(&self, charge: i32) -> Option<PyPeptideProductIonSeries> {
         let maybe_ion_series = self.inner.find_ion_series(charge);
-        match maybe_ion_series {
-            Some(ion_series) => Some(PyPeptideProductIonSeries { inner: ion_series.clone() }),
-            None => None,
-        }
+        maybe_ion_series.map(|ion_series| PyPeptideProductIonSeries { inner: ion_series.clone() })
     }
 
     pub fn generate_isotopic_spectrum(&self, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> PyMzSpectrum {
@@ -137,6 +181,9 @@ pub struct PyPeptideSequence {
     pub inner: PeptideSequence,
 }
 
+/// Residue, position and UNIMOD modifications of each token
+type TokenPositions = Vec<(Option<char>, usize, Vec<(u32, f64)>)>;
+
 #[pymethods]
 impl PyPeptideSequence {
     #[new]
@@ -145,6 +192,16 @@ impl PyPeptideSequence {
         PyPeptideSequence { inner: PeptideSequence::new(sequence, peptide_id) }
     }
 
+    #[staticmethod]
+    pub fn from_proforma(proforma: &str) -> PyResult<Self> {
+        let inner = PeptideSequence::from_proforma(proforma).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyPeptideSequence { inner })
+    }
+
+    pub fn to_proforma(&self) -> String {
+        self.inner.to_proforma()
+    }
+
     #[getter]
     pub fn sequence(&self) -> String {
         self.inner.sequence.clone()
@@ -160,6 +217,11 @@ impl PyPeptideSequence {
         self.inner.mono_isotopic_mass()
     }
 
+    #[getter]
+    pub fn average_mass(&self) -> PyResult<f64> {
+        self.inner.average_mass().map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
     pub fn atomic_composition(&self) -> HashMap<&str, i32> {
         self.inner.atomic_composition()
     }
@@ -180,69 +242,51 @@ impl PyPeptideSequence {
         self.inner.amino_acid_count()
     }
 
-    pub fn calculate_product_ion_series(&self, charge: i32, fragment_type: String) -> (Vec<PyPeptideProductIon>, Vec<PyPeptideProductIon>) {
+    /// residue (None for the N-terminus), 1-based position and (unimod id, mass) of the modifications of every token
+    pub fn tokens_with_positions(&self) -> TokenPositions {
+        self.inner.tokens_with_positions().into_iter().map(|token| (token.residue, token.position, token.modifications)).collect()
+    }
+
+    pub fn modification_sites(&self) -> TokenPositions {
+        self.inner.modification_sites().into_iter().map(|token| (token.residue, token.position, token.modifications)).collect()
+    }
+
+    pub fn composition_counts(&self) -> BTreeMap<char, usize> {
+        self.inner.composition_counts()
+    }
 
-        let f_type = match fragment_type.as_str() {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid fragment type"),
-        };
+    pub fn calculate_product_ion_series(&self, charge: i32, fragment_type: String) -> PyResult<(Vec<PyPeptideProductIon>, Vec<PyPeptideProductIon>)> {
+
+        let f_type = FragmentType::from_name(fragment_type.as_str()).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
         let series = self.inner.calculate_product_ion_series(charge, f_type);
         let n_ions: Vec<PyPeptideProductIon> = series.n_ions.iter().map(|ion| PyPeptideProductIon { inner: ion.clone() }).collect();
         let c_ions: Vec<PyPeptideProductIon> = series.c_ions.iter().map(|ion| PyPeptideProductIon { inner: ion.clone() }).collect();
-        (n_ions, c_ions)
+        Ok((n_ions, c_ions))
     }
 
-    pub fn calculate_mono_isotopic_product_ion_spectrum(&self, charge: i32, fragment_type: String) -> PyMzSpectrum {
-        let f_type = match fragment_type.as_str() {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid fragment type"),
-        };
+    pub fn calculate_mono_isotopic_product_ion_spectrum(&self, charge: i32, fragment_type: String) -> PyResult<PyMzSpectrum> {
+        let f_type = FragmentType::from_name(fragment_type.as_str()).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
         let spectrum = self.inner.calculate_mono_isotopic_product_ion_spectrum(charge, f_type);
-        PyMzSpectrum { inner: spectrum }
+        Ok(PyMzSpectrum { inner: spectrum })
     }
 
-    pub fn calculate_mono_isotopic_product_ion_spectrum_annotated(&self, charge: i32, fragment_type: String) -> PyMzSpectrumAnnotated {
-        let f_type = match fragment_type.as_str() {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid fragment type"),
-        };
+    pub fn calculate_mono_isotopic_product_ion_spectrum_annotated(&self, charge: i32, fragment_type: String) -> PyResult<PyMzSpectrumAnnotated> {
+        let f_type = FragmentType::from_name(fragment_type.as_str()).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
         let spectrum = self.inner.calculate_mono_isotopic_product_ion_spectrum_annotated(charge, f_type);
-        PyMzSpectrumAnnotated { inner: spectrum }
+        Ok(PyMzSpectrumAnnotated { inner: spectrum })
     }
 
-    pub fn calculate_isotopic_product_ion_spectrum_annotated(&self, charge: i32, fragment_type: String, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> PyMzSpectrumAnnotated {
-        let f_type = match fragment_type.as_str() {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid fragment type"),
-        };
+    pub fn calculate_isotopic_product_ion_spectrum_annotated(&self, charge: i32, fragment_type: String, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> PyResult<PyMzSpectrumAnnotated> {
+        let f_type = FragmentType::from_name(fragment_type.as_str()).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
         let spectrum = self.inner.calculate_isotopic_product_ion_spectrum_annotated(charge, f_type, mass_tolerance, abundance_threshold, max_result, intensity_min);
-        PyMzSpectrumAnnotated { inner: spectrum }
+        Ok(PyMzSpectrumAnnotated { inner: spectrum })
     }
 
+    #[pyo3(signature = (flat_intensities, charge, fragment_type, normalize, half_charge_one, shape=None))]
     pub fn associate_with_predicted_intensities(
         &self,
         flat_intensities: Vec<f64>,
@@ -250,27 +294,21 @@ impl PyPeptideSequence {
         fragment_type: &str,
         normalize: bool,
         half_charge_one: bool,
-    ) -> PyPeptideProductIonSeriesCollection {
-
-        let fragment_type = match fragment_type {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid fragment type"),
-        };
+        shape: Option<(usize, usize, usize)>,
+    ) -> PyResult<PyPeptideProductIonSeriesCollection> {
+
+        let fragment_type = FragmentType::from_name(fragment_type).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
         let result = self.inner.associate_with_predicted_intensities(
             charge,
             fragment_type,
             flat_intensities,
             normalize,
-            half_charge_one
-        );
+            half_charge_one,
+            shape,
+        ).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
-        PyPeptideProductIonSeriesCollection { inner: result }
+        Ok(PyPeptideProductIonSeriesCollection { inner: result })
     }
 }
 
@@ -284,19 +322,11 @@ pub struct PyPeptideProductIon {
 impl PyPeptideProductIon {
     #[new]
     #[pyo3(signature = (kind, sequence, charge, intensity, peptide_id=None))]
-    pub fn new(kind: &str, sequence: String, charge: i32, intensity: f64, peptide_id: Option<i32>) -> Self {
+    pub fn new(kind: &str, sequence: String, charge: i32, intensity: f64, peptide_id: Option<i32>) -> PyResult<Self> {
 
-        let kind = match kind {
-            "a" => FragmentType::A,
-            "b" => FragmentType::B,
-            "c" => FragmentType::C,
-            "x" => FragmentType::X,
-            "y" => FragmentType::Y,
-            "z" => FragmentType::Z,
-            _ => panic!("Invalid product ion kind"),
-        };
+        let kind = FragmentType::from_name(kind).map_err(pyo3::exceptions::PyValueError::new_err)?;
 
-        PyPeptideProductIon { inner: PeptideProductIon::new(kind, sequence, charge, intensity, peptide_id) }
+        Ok(PyPeptideProductIon { inner: PeptideProductIon::new(kind, sequence, charge, intensity, peptide_id) })
     }
     #[getter]
     pub fn kind(&self) -> String {
@@ -330,6 +360,11 @@ impl PyPeptideProductIon {
         self.inner.mono_isotopic_mass()
     }
 
+    #[getter]
+    pub fn average_mass(&self) -> PyResult<f64> {
+        self.inner.average_mass().map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
     pub fn atomic_composition(&self) -> HashMap<&str, i32> {
         self.inner.atomic_composition()
     }
@@ -338,8 +373,8 @@ impl PyPeptideProductIon {
         serde_json::to_string(&self.inner).unwrap()
     }
 
-    pub fn isotope_distribution(&self, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> Vec<(f64, f64)> {
-        self.inner.isotope_distribution(mass_tolerance, abundance_threshold, max_result, intensity_min)
+    pub fn isotope_distribution(&self, py: Python, mass_tolerance: f64, abundance_threshold: f64, max_result: i32, intensity_min: f64) -> (Py<PyArray1<f64>>, Py<PyArray1<f64>>) {
+        distribution_to_arrays(py, self.inner.isotope_distribution(mass_tolerance, abundance_threshold, max_result, intensity_min))
     }
 }
 
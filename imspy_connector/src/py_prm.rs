@@ -0,0 +1,117 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
+use pyo3::prelude::*;
+use numpy::{IntoPyArray, PyArray1};
+
+use rustdf::data::acquisition::AcquisitionModeMismatch;
+use rustdf::data::handle::TimsData;
+use rustdf::data::prm::{PRMTarget, TimsDatasetPRM};
+use crate::py_tims_frame::PyTimsFrame;
+use crate::py_tims_slice::PyTimsSlice;
+
+#[pyclass]
+pub struct PyTimsDatasetPRM {
+    inner: TimsDatasetPRM,
+}
+
+#[pymethods]
+impl PyTimsDatasetPRM {
+    #[new]
+    pub fn new(data_path: &str, bruker_lib_path: &str, in_memory: bool, use_bruker_sdk: bool) -> PyResult<Self> {
+        let dataset = TimsDatasetPRM::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .map_err(|e| match e.downcast_ref::<AcquisitionModeMismatch>() {
+                Some(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+                None => pyo3::exceptions::PyIOError::new_err(e.to_string()),
+            })?;
+        Ok(PyTimsDatasetPRM { inner: dataset })
+    }
+    pub fn get_frame(&self, py: Python, frame_id: u32) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.get_frame(frame_id)) }
+    }
+
+    pub fn get_slice(&self, py: Python, frame_ids: Vec<u32>, num_threads: usize) -> PyTimsSlice {
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.get_slice(frame_ids, num_threads)) }
+    }
+
+    pub fn get_acquisition_mode(&self) -> String {
+        self.inner.get_acquisition_mode().to_string()
+    }
+
+    pub fn get_frame_count(&self) -> i32 {
+        self.inner.get_frame_count()
+    }
+
+    pub fn get_data_path(&self) -> &str {
+        self.inner.get_data_path()
+    }
+
+    pub fn get_targets(&self) -> Vec<PyPRMTarget> {
+        self.inner.get_targets().into_iter().map(|target| PyPRMTarget { inner: target }).collect()
+    }
+
+    pub fn get_target_fragment_spectra(&self, py: Python, target_id: i64, num_threads: usize) -> Vec<PyTimsFrame> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.get_target_fragment_spectra(target_id, num_threads)).into_iter().map(|frame| PyTimsFrame { inner: frame }).collect()
+    }
+
+    /// retention times and one intensity trace per fragment m/z
+    #[pyo3(signature = (target_id, fragment_mz, tolerance_ppm=10.0, num_threads=4))]
+    pub fn get_target_xic(&self, py: Python, target_id: i64, fragment_mz: Vec<f64>, tolerance_ppm: f64, num_threads: usize) -> (Py<PyArray1<f64>>, Vec<Py<PyArray1<f64>>>) {
+        let inner = &self.inner;
+        let xic = py.allow_threads(|| inner.get_target_xic(target_id, &fragment_mz, tolerance_ppm, num_threads));
+        let traces = xic.intensity.into_iter().map(|trace| trace.into_pyarray_bound(py).unbind()).collect();
+        (xic.retention_time.into_pyarray_bound(py).unbind(), traces)
+    }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsDatasetPRM cannot be pickled, it holds open handles to the raw data, open the dataset from its path in every process instead"))
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyPRMTarget {
+    inner: PRMTarget,
+}
+
+#[pymethods]
+impl PyPRMTarget {
+    #[getter]
+    pub fn target_id(&self) -> i64 { self.inner.target_id }
+
+    #[getter]
+    pub fn external_id(&self) -> Option<String> { self.inner.external_id.clone() }
+
+    #[getter]
+    pub fn description(&self) -> Option<String> { self.inner.description.clone() }
+
+    #[getter]
+    pub fn precursor_mz(&self) -> f64 { self.inner.precursor_mz }
+
+    #[getter]
+    pub fn charge(&self) -> Option<i64> { self.inner.charge }
+
+    #[getter]
+    pub fn one_over_k0(&self) -> f64 { self.inner.one_over_k0 }
+
+    #[getter]
+    pub fn retention_time(&self) -> f64 { self.inner.retention_time }
+
+    #[getter]
+    pub fn rt_begin(&self) -> Option<f64> { self.inner.rt_begin }
+
+    #[getter]
+    pub fn rt_end(&self) -> Option<f64> { self.inner.rt_end }
+
+    #[getter]
+    pub fn frame_ids(&self) -> Vec<u32> { self.inner.frame_ids.clone() }
+}
+
+#[pymodule]
+pub fn py_prm(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyTimsDatasetPRM>()?;
+    m.add_class::<PyPRMTarget>()?;
+    Ok(())
+}
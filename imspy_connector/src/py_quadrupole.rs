@@ -3,7 +3,9 @@ use mscore::timstof::collision::{TimsTofCollisionEnergy, TimsTofCollisionEnergyD
 use pyo3::prelude::*;
 
 use mscore::timstof::quadrupole::{IonTransmission, PASEFMeta, TimsTransmissionDDA, TimsTransmissionDIA};
+use crate::py_dia::PyTimsDatasetDIA;
 use crate::py_mz_spectrum::PyMzSpectrum;
+use crate::py_simulation::PyTimsTofSyntheticsDataHandle;
 use crate::py_tims_frame::PyTimsFrame;
 
 #[pyclass]
@@ -136,6 +138,7 @@ pub struct PyTimsTransmissionDIA {
 impl PyTimsTransmissionDIA {
     #[new]
     #[pyo3(signature = (frame, frame_window_group, window_group, scan_start, scan_end, isolation_mz, isolation_width, k=None))]
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
     pub fn new(frame: Vec<i32>,
                frame_window_group: Vec<i32>,
                window_group: Vec<i32>,
@@ -157,6 +160,31 @@ impl PyTimsTransmissionDIA {
         }
     }
 
+    #[staticmethod]
+    pub fn from_synthetics_handle(handle: &PyTimsTofSyntheticsDataHandle) -> Self {
+        PyTimsTransmissionDIA { inner: handle.inner.get_transmission_dia() }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (dataset, k=None))]
+    pub fn from_dataset(dataset: &PyTimsDatasetDIA, k: Option<f64>) -> Self {
+        PyTimsTransmissionDIA { inner: dataset.inner.get_transmission(k) }
+    }
+
+    pub fn isolation_window_for(&self, window_group: i32, scan: i32) -> Option<(f64, f64)> {
+        self.inner.isolation_window_for(window_group, scan)
+    }
+
+    pub fn window_groups_for_mz(&self, mz: f64, scan: i32) -> Vec<i32> {
+        self.inner.window_groups_for_mz(mz, scan)
+    }
+
+    pub fn all_windows(&self) -> Vec<(i32, i32, i32, f64, f64)> {
+        self.inner.all_windows().into_iter()
+            .map(|w| (w.window_group, w.scan_start, w.scan_end, w.isolation_mz, w.isolation_width))
+            .collect()
+    }
+
     pub fn apply_transmission(&self, frame_id: i32, scan_id: i32, mz: Vec<f64>) -> Vec<f64> {
         self.inner.apply_transmission(frame_id, scan_id, &mz)
     }
@@ -220,6 +248,7 @@ impl PyTimsTransmissionDIA {
 }
 
 #[pyclass]
+#[derive(Clone)]
 pub struct PyTimsTofCollisionEnergyDIA {
     pub inner: TimsTofCollisionEnergyDIA,
 }
@@ -244,6 +273,26 @@ impl PyTimsTofCollisionEnergyDIA {
         }
     }
 
+    #[staticmethod]
+    pub fn with_ramp(frame: Vec<i32>,
+                     frame_window_group: Vec<i32>,
+                     window_group: Vec<i32>,
+                     scan_start: Vec<i32>,
+                     scan_end: Vec<i32>,
+                     ce_start: Vec<f64>,
+                     ce_end: Vec<f64>) -> Self {
+        PyTimsTofCollisionEnergyDIA {
+            inner: TimsTofCollisionEnergyDIA::new_with_ramp(
+            frame,
+            frame_window_group,
+            window_group,
+            scan_start,
+            scan_end,
+            ce_start,
+            ce_end)
+        }
+    }
+
     pub fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64 {
         self.inner.get_collision_energy(frame_id, scan_id)
     }
@@ -255,6 +304,14 @@ impl PyTimsTofCollisionEnergyDIA {
         }
         collision_energies
     }
+
+    pub fn get_collision_energies_for_frame(&self, frame_id: i32, scan_ids: Vec<i32>) -> Vec<f64> {
+        self.inner.get_collision_energies(frame_id, &scan_ids)
+    }
+
+    pub fn export_ce_table(&self) -> Vec<(i32, i32, f64)> {
+        self.inner.export_ce_table()
+    }
 }
 
 #[pyfunction]
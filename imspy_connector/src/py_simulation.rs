@@ -1,17 +1,99 @@
-use std::collections::BTreeMap;
-use mscore::timstof::collision::TimsTofCollisionEnergy;
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use mscore::algorithm::fragmentation::{FlatIntensityModel, FragmentIntensityModel, MobileProtonModel};
+use mscore::data::peptide::PeptideSequence;
+use mscore::data::spectrum::MzSpectrum;
+use mscore::simulation::peptide::PeptideSimulation;
+use mscore::timstof::frame::TimsFrame;
+use mscore::timstof::collision::{CollisionEnergyMobilityRamp, CollisionEnergySource, TimsTofCollisionEnergy};
+use mscore::timstof::quadrupole::{Rectangular, Sigmoid, TransmissionProfile};
 use pyo3::prelude::*;
-use pyo3::types::PyTuple;
-use rustdf::sim::dda::TimsTofSyntheticsFrameBuilderDDA;
-use rustdf::sim::dia::{TimsTofSyntheticsFrameBuilderDIA};
-use rustdf::sim::precursor::{TimsTofSyntheticsPrecursorFrameBuilder};
-use rustdf::sim::handle::TimsTofSyntheticsDataHandle;
+use pyo3::types::{PyDict, PyTuple};
+use rustdf::sim::calibration::TofCalibration;
+use rustdf::sim::containers::{FragmentIonSim, FrameToWindowGroupSim, FramesSim, IonSim, PeptidesSim, ScansSim, WindowGroupSettingsSim};
+use rustdf::sim::dda::{DDASelectionSettings, TimsTofSyntheticsFrameBuilderDDA};
+use rustdf::sim::dia::{FrameBuilderMode, TimsTofSyntheticsFrameBuilderDIA};
+use rustdf::sim::precursor::{FrameBuildError, MissingKind, TimsTofSyntheticsPrecursorFrameBuilder};
+use rustdf::sim::dia_scheme;
+use rustdf::sim::scheduler::{CandidateFrame, PasefScheduler, PrecursorCandidate};
+use rustdf::sim::handle::{DuplicatePeptideStrategy, ElutionParameter, SyntheticsError, TimsTofSyntheticsDataHandle};
+use rustdf::sim::library::SpectralLibraryFormat;
+use rustdf::sim::utility::{FrameBatchIterator, FrameBuildOptions, IntensityQuantization};
 use crate::py_annotation::PyTimsFrameAnnotated;
+use crate::py_chemistry::parse_mobility_model;
+use crate::py_dataset::PyTimsDataset;
 use crate::py_mz_spectrum::PyMzSpectrum;
 use crate::py_peptide::PyPeptideProductIonSeriesCollection;
-use crate::py_quadrupole::PyPasefMeta;
+use crate::py_quadrupole::{PyPasefMeta, PyTimsTofCollisionEnergyDIA};
 use crate::py_tims_frame::PyTimsFrame;
 
+#[pyclass]
+#[derive(Clone)]
+pub struct PyTofCalibration {
+    pub inner: TofCalibration,
+}
+
+#[pymethods]
+impl PyTofCalibration {
+    #[new]
+    #[pyo3(signature = (intercept, slope, curvature=0.0, tof_max_index=400_000))]
+    pub fn new(intercept: f64, slope: f64, curvature: f64, tof_max_index: u32) -> PyResult<Self> {
+        let inner = TofCalibration { intercept, slope, curvature, tof_max_index };
+        if !inner.is_invertible() {
+            return Err(pyo3::exceptions::PyValueError::new_err("m/z must increase with the tof index over the whole tof range"));
+        }
+        Ok(PyTofCalibration { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (mz_min=100.0, mz_max=1700.0, tof_max_index=400_000))]
+    pub fn from_boundaries(mz_min: f64, mz_max: f64, tof_max_index: u32) -> Self {
+        PyTofCalibration { inner: TofCalibration::from_boundaries(mz_min, mz_max, tof_max_index) }
+    }
+
+    /// fit the calibration of a frame of a reference dataset
+    #[staticmethod]
+    #[pyo3(signature = (dataset, frame_id=1, tof_max_index=400_000))]
+    pub fn from_dataset(dataset: &PyTimsDataset, frame_id: u32, tof_max_index: u32) -> Self {
+        PyTofCalibration { inner: TofCalibration::from_index_converter(&dataset.inner, frame_id, tof_max_index) }
+    }
+
+    #[getter]
+    pub fn intercept(&self) -> f64 {
+        self.inner.intercept
+    }
+
+    #[getter]
+    pub fn slope(&self) -> f64 {
+        self.inner.slope
+    }
+
+    #[getter]
+    pub fn curvature(&self) -> f64 {
+        self.inner.curvature
+    }
+
+    #[getter]
+    pub fn tof_max_index(&self) -> u32 {
+        self.inner.tof_max_index
+    }
+
+    pub fn mz_to_tof(&self, mz: Vec<f64>) -> Vec<i32> {
+        self.inner.mz_to_tof(&mz)
+    }
+
+    pub fn tof_to_mz(&self, tof: Vec<i32>) -> Vec<f64> {
+        self.inner.tof_to_mz(&tof)
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("TofCalibration(intercept={}, slope={:e}, curvature={:e}, tof_max_index={})",
+                self.inner.intercept, self.inner.slope, self.inner.curvature, self.inner.tof_max_index)
+    }
+}
+
 #[pyclass]
 pub struct PyTimsTofSyntheticsDataHandle {
     pub inner: TimsTofSyntheticsDataHandle,
@@ -20,51 +102,480 @@ pub struct PyTimsTofSyntheticsDataHandle {
 #[pymethods]
 impl PyTimsTofSyntheticsDataHandle {
     #[new]
-    pub fn new(db_path: &str) -> Self {
+    pub fn new(db_path: &str) -> PyResult<Self> {
         let path = std::path::Path::new(db_path);
-        PyTimsTofSyntheticsDataHandle { inner: TimsTofSyntheticsDataHandle::new(path).unwrap() }
+        let inner = TimsTofSyntheticsDataHandle::new(path)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsTofSyntheticsDataHandle { inner })
+    }
+
+    pub fn read_tof_calibration(&self) -> PyResult<PyTofCalibration> {
+        self.inner.read_tof_calibration()
+            .map(|inner| PyTofCalibration { inner })
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn write_tof_calibration(&self, calibration: &PyTofCalibration) -> PyResult<()> {
+        self.inner.write_tof_calibration(&calibration.inner)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// (1/K0, collision energy) breakpoints of the collision energy ramp, None if collision energies follow the window groups
+    pub fn read_collision_energy_ramp(&self) -> PyResult<Option<Vec<(f64, f64)>>> {
+        self.inner.read_collision_energy_ramp()
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn write_collision_energy_ramp(&self, inv_mobility: Vec<f64>, collision_energy: Vec<f64>) -> PyResult<()> {
+        if inv_mobility.len() != collision_energy.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "got {} mobilities but {} collision energies", inv_mobility.len(), collision_energy.len()
+            )));
+        }
+        let breakpoints: Vec<(f64, f64)> = inv_mobility.into_iter().zip(collision_energy).collect();
+        self.inner.write_collision_energy_ramp(&breakpoints)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn clear_collision_energy_ramp(&self) -> PyResult<()> {
+        self.inner.clear_collision_energy_ramp()
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// ascending fragment frame ids of a window group, empty for unknown window groups
+    pub fn frames_for_window_group(&self, window_group: u32) -> PyResult<Vec<u32>> {
+        self.inner.frames_for_window_group(window_group)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// window group of a fragment frame, None for precursor and unknown frames
+    pub fn window_group_for_frame(&self, frame_id: u32) -> PyResult<Option<u32>> {
+        self.inner.window_group_for_frame(frame_id)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// write a tab separated spectral library (diann or openswath columns), returns the number of precursors
+    #[pyo3(signature = (path, format="diann", include_decoys=false))]
+    pub fn export_spectral_library(&self, path: &str, format: &str, include_decoys: bool) -> PyResult<usize> {
+        let format = format.parse::<SpectralLibraryFormat>().map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.inner.export_spectral_library(std::path::Path::new(path), format, include_decoys)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// storage format of the list and spectrum columns of a table, json or binary
+    pub fn storage_format(&self, table: &str) -> PyResult<String> {
+        self.inner.storage_format(table)
+            .map(|format| format.to_string())
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// delete duplicate peptides with their ions and fragment ions, returns the number of deleted peptides
+    pub fn collapse_duplicate_peptides(&self, strategy: &str) -> PyResult<usize> {
+        let strategy = strategy.parse::<DuplicatePeptideStrategy>().map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.inner.collapse_duplicate_peptides(strategy).map_err(|e| match e {
+            SyntheticsError::DuplicatePeptide(_) => pyo3::exceptions::PyValueError::new_err(e.to_string()),
+            _ => pyo3::exceptions::PyRuntimeError::new_err(e.to_string()),
+        })
+    }
+
+    /// missing tables and columns that are missing or named differently than the frame builders expect
+    pub fn validate_schema(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let report = self.inner.validate_schema()
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        let mismatches = report.column_mismatches.iter().map(|mismatch| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("table", &mismatch.table)?;
+            dict.set_item("index", mismatch.index)?;
+            dict.set_item("expected", &mismatch.expected)?;
+            dict.set_item("found", &mismatch.found)?;
+            Ok(dict)
+        }).collect::<PyResult<Vec<_>>>()?;
+
+        let dict = PyDict::new_bound(py);
+        dict.set_item("valid", report.is_valid())?;
+        dict.set_item("missing_tables", &report.missing_tables)?;
+        dict.set_item("column_mismatches", mismatches)?;
+        Ok(dict.unbind())
     }
 
-    #[pyo3(signature = (num_threads=None, dda=None))]
-    pub fn get_transmitted_ions(&self, num_threads: Option<usize>, dda: Option<bool>) -> (Vec<i32>, Vec<i32>, Vec<String>, Vec<i8>, Vec<f32>) {
+    #[pyo3(signature = (num_threads=None, dda=None, ion_id_filter=None, limit=None))]
+    pub fn get_transmitted_ions(&self, py: Python, num_threads: Option<usize>, dda: Option<bool>, ion_id_filter: Option<Vec<u32>>, limit: Option<usize>) -> PyResult<(Py<PyDict>, usize)> {
         let threads = num_threads.unwrap_or(4);
-        self.inner.get_transmitted_ions(threads, dda.unwrap_or(false))
+        let (ions, num_skipped) = self.inner.get_transmitted_ions(threads, dda.unwrap_or(false), ion_id_filter.as_deref(), limit);
+
+        let columns = PyDict::new_bound(py);
+        columns.set_item("frame_id", ions.iter().map(|ion| ion.frame_id).collect::<Vec<_>>())?;
+        columns.set_item("peptide_id", ions.iter().map(|ion| ion.peptide_id).collect::<Vec<_>>())?;
+        columns.set_item("ion_id", ions.iter().map(|ion| ion.ion_id).collect::<Vec<_>>())?;
+        columns.set_item("sequence", ions.iter().map(|ion| ion.sequence.as_str()).collect::<Vec<_>>())?;
+        columns.set_item("charge", ions.iter().map(|ion| ion.charge).collect::<Vec<_>>())?;
+        columns.set_item("collision_energy", ions.iter().map(|ion| ion.collision_energy).collect::<Vec<_>>())?;
+        columns.set_item("scan_start", ions.iter().map(|ion| ion.scan_start).collect::<Vec<_>>())?;
+        columns.set_item("scan_end", ions.iter().map(|ion| ion.scan_end).collect::<Vec<_>>())?;
+        columns.set_item("mobility", ions.iter().map(|ion| ion.mobility).collect::<Vec<_>>())?;
+        columns.set_item("precursor_mz", ions.iter().map(|ion| ion.precursor_mz).collect::<Vec<_>>())?;
+        columns.set_item("transmitted_fraction", ions.iter().map(|ion| ion.transmitted_fraction).collect::<Vec<_>>())?;
+        Ok((columns.unbind(), num_skipped))
+    }
+
+    #[pyo3(signature = (num_threads, coefficients=None))]
+    pub fn write_ion_mobilities(&self, num_threads: usize, coefficients: Option<Vec<(f64, f64)>>) -> PyResult<()> {
+        let model = parse_mobility_model(coefficients)?;
+        self.inner.write_ion_mobilities(&model, num_threads)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    #[pyo3(signature = (im_sigma, truncation_z, num_threads, im_sigma_high=None))]
+    pub fn write_scan_distributions(&self, im_sigma: f64, truncation_z: f64, num_threads: usize, im_sigma_high: Option<f64>) -> PyResult<usize> {
+        check_scan_distribution_settings(&[im_sigma], im_sigma_high.as_slice(), truncation_z)?;
+        self.inner.write_scan_distributions(im_sigma, im_sigma_high, truncation_z, num_threads)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn update_scan_distributions(&self, ion_ids: Vec<u32>, occurrences: Vec<Vec<u32>>, abundances: Vec<Vec<f32>>) -> PyResult<()> {
+        self.inner.update_scan_distributions(&ion_ids, &occurrences, &abundances)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    pub fn write_frame_distributions(&self, peptide_ids: Vec<u32>, occurrences: Vec<Vec<u32>>, abundances: Vec<Vec<f32>>) -> PyResult<()> {
+        self.inner.write_frame_distributions(&peptide_ids, &occurrences, &abundances)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// simulate the elution profiles of all peptides, a sigma or lambda of None uses the rt_sigma or rt_lambda column,
+    /// returns the number of peptides eluting outside of the run
+    #[pyo3(signature = (target_p, step_size, num_threads, sigma=None, lambda_=None))]
+    pub fn populate_elution_profiles(&self, target_p: f64, step_size: f64, num_threads: usize, sigma: Option<f64>, lambda_: Option<f64>) -> PyResult<usize> {
+        let source = |value: Option<f64>| value.map_or(ElutionParameter::Stored, ElutionParameter::Constant);
+        self.inner.populate_elution_profiles(source(sigma), source(lambda_), target_p, step_size, num_threads)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// create the tables read by the frame builders, existing tables are kept
+    pub fn create_schema(&self) -> PyResult<()> {
+        self.inner.create_schema()
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// append frames given column by column, the insert methods can be called repeatedly to stream a table in chunks
+    pub fn insert_frames(&self, frame_id: Vec<u32>, time: Vec<f32>, ms_type: Vec<i64>) -> PyResult<()> {
+        check_column_lengths(&[("frame_id", frame_id.len()), ("time", time.len()), ("ms_type", ms_type.len())])?;
+        let frames: Vec<FramesSim> = frame_id.into_iter().zip(time).zip(ms_type)
+            .map(|((frame_id, time), ms_type)| FramesSim::new(frame_id, time, ms_type))
+            .collect();
+        self.inner.insert_frames(&frames)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn insert_scans(&self, scan: Vec<u32>, mobility: Vec<f32>) -> PyResult<()> {
+        check_column_lengths(&[("scan", scan.len()), ("mobility", mobility.len())])?;
+        let scans: Vec<ScansSim> = scan.into_iter().zip(mobility).map(|(scan, mobility)| ScansSim::new(scan, mobility)).collect();
+        self.inner.insert_scans(&scans)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn insert_peptides(
+        &self,
+        protein_id: Vec<u32>,
+        peptide_id: Vec<u32>,
+        sequence: Vec<String>,
+        proteins: Vec<String>,
+        decoy: Vec<bool>,
+        missed_cleavages: Vec<i8>,
+        n_term: Vec<Option<bool>>,
+        c_term: Vec<Option<bool>>,
+        mono_isotopic_mass: Vec<f32>,
+        retention_time: Vec<f32>,
+        events: Vec<f32>,
+        frame_start: Vec<u32>,
+        frame_end: Vec<u32>,
+        frame_occurrence: Vec<Vec<u32>>,
+        frame_abundance: Vec<Vec<f32>>,
+    ) -> PyResult<()> {
+        check_column_lengths(&[
+            ("protein_id", protein_id.len()), ("peptide_id", peptide_id.len()), ("sequence", sequence.len()),
+            ("proteins", proteins.len()), ("decoy", decoy.len()), ("missed_cleavages", missed_cleavages.len()),
+            ("n_term", n_term.len()), ("c_term", c_term.len()), ("mono_isotopic_mass", mono_isotopic_mass.len()),
+            ("retention_time", retention_time.len()), ("events", events.len()), ("frame_start", frame_start.len()),
+            ("frame_end", frame_end.len()), ("frame_occurrence", frame_occurrence.len()), ("frame_abundance", frame_abundance.len()),
+        ])?;
+        let mut frame_occurrence = frame_occurrence.into_iter();
+        let mut frame_abundance = frame_abundance.into_iter();
+        let peptides: Vec<PeptidesSim> = sequence.into_iter().zip(proteins).enumerate().map(|(i, (sequence, proteins))| {
+            PeptidesSim::new(
+                protein_id[i], peptide_id[i], sequence, proteins, decoy[i], missed_cleavages[i], n_term[i], c_term[i],
+                mono_isotopic_mass[i], retention_time[i], events[i], frame_start[i], frame_end[i],
+                frame_occurrence.next().unwrap(), frame_abundance.next().unwrap(),
+            )
+        }).collect();
+        self.inner.insert_peptides(&peptides)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// append ions, the simulated spectrum of every ion is given by its mz and intensity values
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn insert_ions(
+        &self,
+        ion_id: Vec<u32>,
+        peptide_id: Vec<u32>,
+        sequence: Vec<String>,
+        charge: Vec<i8>,
+        relative_abundance: Vec<f32>,
+        mobility: Vec<f32>,
+        spectrum_mz: Vec<Vec<f64>>,
+        spectrum_intensity: Vec<Vec<f64>>,
+        scan_occurrence: Vec<Vec<u32>>,
+        scan_abundance: Vec<Vec<f32>>,
+    ) -> PyResult<()> {
+        check_column_lengths(&[
+            ("ion_id", ion_id.len()), ("peptide_id", peptide_id.len()), ("sequence", sequence.len()), ("charge", charge.len()),
+            ("relative_abundance", relative_abundance.len()), ("mobility", mobility.len()), ("spectrum_mz", spectrum_mz.len()),
+            ("spectrum_intensity", spectrum_intensity.len()), ("scan_occurrence", scan_occurrence.len()), ("scan_abundance", scan_abundance.len()),
+        ])?;
+        let mut spectra = spectrum_mz.into_iter().zip(spectrum_intensity);
+        let mut scan_distributions = scan_occurrence.into_iter().zip(scan_abundance);
+        let ions = sequence.into_iter().enumerate().map(|(i, sequence)| {
+            let (mz, intensity) = spectra.next().unwrap();
+            if mz.len() != intensity.len() {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!("spectrum of ion {} has {} mz and {} intensity values", ion_id[i], mz.len(), intensity.len())));
+            }
+            let (occurrence, abundance) = scan_distributions.next().unwrap();
+            Ok(IonSim::new(
+                ion_id[i], peptide_id[i], sequence, charge[i], relative_abundance[i], mobility[i],
+                MzSpectrum::new(mz, intensity), occurrence, abundance,
+            ))
+        }).collect::<PyResult<Vec<IonSim>>>()?;
+        self.inner.insert_ions(&ions)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn insert_fragment_ions(&self, peptide_id: Vec<u32>, ion_id: Vec<u32>, collision_energy: Vec<f64>, charge: Vec<i8>, indices: Vec<Vec<u32>>, values: Vec<Vec<f64>>) -> PyResult<()> {
+        check_column_lengths(&[
+            ("peptide_id", peptide_id.len()), ("ion_id", ion_id.len()), ("collision_energy", collision_energy.len()),
+            ("charge", charge.len()), ("indices", indices.len()), ("values", values.len()),
+        ])?;
+        let fragment_ions: Vec<FragmentIonSim> = indices.into_iter().zip(values).enumerate()
+            .map(|(i, (indices, values))| FragmentIonSim::new(peptide_id[i], ion_id[i], collision_energy[i], charge[i], indices, values))
+            .collect();
+        self.inner.insert_fragment_ions(&fragment_ions)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn insert_window_group_settings(&self, window_group: Vec<u32>, scan_start: Vec<u32>, scan_end: Vec<u32>, isolation_mz: Vec<f32>, isolation_width: Vec<f32>, collision_energy: Vec<f32>) -> PyResult<()> {
+        let settings = window_group_settings(window_group, scan_start, scan_end, isolation_mz, isolation_width, collision_energy)?;
+        self.inner.insert_window_group_settings(&settings)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// replace the DIA window scheme, the window groups of one cycle are assigned to all fragment frames in turn
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn insert_dia_scheme(&self, window_group: Vec<u32>, scan_start: Vec<u32>, scan_end: Vec<u32>, isolation_mz: Vec<f32>, isolation_width: Vec<f32>, collision_energy: Vec<f32>, cycle_window_group: Vec<u32>) -> PyResult<()> {
+        let settings = window_group_settings(window_group, scan_start, scan_end, isolation_mz, isolation_width, collision_energy)?;
+        let cycle: Vec<FrameToWindowGroupSim> = cycle_window_group.into_iter().enumerate()
+            .map(|(i, window_group)| FrameToWindowGroupSim::new(i as u32 + 2, window_group))
+            .collect();
+        self.inner.insert_dia_scheme(&settings, &cycle)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    pub fn insert_frame_to_window_group(&self, frame_id: Vec<u32>, window_group: Vec<u32>) -> PyResult<()> {
+        check_column_lengths(&[("frame_id", frame_id.len()), ("window_group", window_group.len())])?;
+        let entries: Vec<FrameToWindowGroupSim> = frame_id.into_iter().zip(window_group)
+            .map(|(frame_id, window_group)| FrameToWindowGroupSim::new(frame_id, window_group))
+            .collect();
+        self.inner.insert_frame_to_window_group(&entries)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+    }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsTofSyntheticsDataHandle cannot be pickled, it holds an open SQLite connection, create it from the database path in every process instead"))
+    }
+}
+
+/// windows of a DIA scheme from the columns of the dia_ms_ms_windows table
+fn window_group_settings(window_group: Vec<u32>, scan_start: Vec<u32>, scan_end: Vec<u32>, isolation_mz: Vec<f32>, isolation_width: Vec<f32>, collision_energy: Vec<f32>) -> PyResult<Vec<WindowGroupSettingsSim>> {
+    check_column_lengths(&[
+        ("window_group", window_group.len()), ("scan_start", scan_start.len()), ("scan_end", scan_end.len()),
+        ("isolation_mz", isolation_mz.len()), ("isolation_width", isolation_width.len()), ("collision_energy", collision_energy.len()),
+    ])?;
+    Ok((0..window_group.len())
+        .map(|i| WindowGroupSettingsSim::new(window_group[i], scan_start[i], scan_end[i], isolation_mz[i], isolation_width[i], collision_energy[i]))
+        .collect())
+}
+
+/// check that all columns of a table have as many values as the first one
+fn check_column_lengths(columns: &[(&str, usize)]) -> PyResult<()> {
+    let (first, length) = columns[0];
+    match columns.iter().find(|(_, other)| *other != length) {
+        Some((name, other)) => Err(pyo3::exceptions::PyValueError::new_err(format!("column {} has {} values, but {} has {}", name, other, first, length))),
+        None => Ok(()),
+    }
+}
+
+/// check that all ion mobility peak widths and the truncation are positive
+fn check_scan_distribution_settings(im_sigmas_low: &[f64], im_sigmas_high: &[f64], truncation_z: f64) -> PyResult<()> {
+    if let Some(sigma) = im_sigmas_low.iter().chain(im_sigmas_high.iter()).find(|sigma| sigma.is_nan() || **sigma <= 0.0) {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("ion mobility peak widths must be positive, got {}", sigma)));
+    }
+    if truncation_z.is_nan() || truncation_z <= 0.0 {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("truncation_z must be positive, got {}", truncation_z)));
+    }
+    Ok(())
+}
+
+/// Copy a synthetics database to path_out with the list and spectrum columns of ions and fragment ions
+/// stored as binary, returns the number of converted rows
+#[pyfunction]
+pub fn migrate_synthetics_to_binary(db_path: &str, path_out: &str) -> PyResult<usize> {
+    let handle = TimsTofSyntheticsDataHandle::new_read_only(std::path::Path::new(db_path))
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    handle.migrate_to_binary(std::path::Path::new(path_out))
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Occurring scans and their abundances per ion
+type ScanDistributions = (Vec<Vec<u32>>, Vec<Vec<f32>>);
+
+/// Simulate the scan distributions of many ions from their inverse mobility,
+/// returns the scan occurrences and abundances of every ion
+#[pyfunction]
+#[pyo3(signature = (mobilities, im_sigmas, scans, scan_mobilities, truncation_z, num_threads, im_sigmas_high=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn simulate_scan_distributions(py: Python, mobilities: Vec<f64>, im_sigmas: Vec<f64>, scans: Vec<u32>, scan_mobilities: Vec<f32>, truncation_z: f64, num_threads: usize, im_sigmas_high: Option<Vec<f64>>) -> PyResult<ScanDistributions> {
+    let im_sigmas_high = im_sigmas_high.unwrap_or_else(|| im_sigmas.clone());
+    if im_sigmas.len() != mobilities.len() || im_sigmas_high.len() != mobilities.len() || scans.len() != scan_mobilities.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err("mobilities and peak widths, as well as scans and scan mobilities must have the same length"));
+    }
+    check_scan_distribution_settings(&im_sigmas, &im_sigmas_high, truncation_z)?;
+    let scans: Vec<ScansSim> = scans.into_iter().zip(scan_mobilities).map(|(scan, mobility)| ScansSim::new(scan, mobility)).collect();
+    Ok(py.allow_threads(|| rustdf::sim::mobility::simulate_scan_distributions(&mobilities, &im_sigmas, &im_sigmas_high, &scans, truncation_z, num_threads))
+        .into_iter()
+        .unzip())
+}
+
+/// parse a quadrupole transmission profile (rectangular, sigmoid), None falls back to the default sigmoid profile
+fn parse_transmission_profile(profile: Option<&str>, k: Option<f64>) -> PyResult<Option<Arc<dyn TransmissionProfile>>> {
+    match profile.map(|p| p.to_lowercase()) {
+        None => Ok(k.map(|k| Arc::new(Sigmoid { k }) as Arc<dyn TransmissionProfile>)),
+        Some(p) if p == "rectangular" => Ok(Some(Arc::new(Rectangular))),
+        Some(p) if p == "sigmoid" => Ok(Some(Arc::new(Sigmoid { k: k.unwrap_or(15.0) }))),
+        Some(p) => Err(pyo3::exceptions::PyValueError::new_err(format!("unknown transmission profile: {}, expected rectangular or sigmoid", p))),
     }
 }
 
+/// parse a duplicate peptide strategy (keep_first, sum_events, error), None falls back to the default
+fn parse_duplicate_strategy(strategy: Option<&str>) -> PyResult<Option<DuplicatePeptideStrategy>> {
+    strategy
+        .map(|s| s.parse::<DuplicatePeptideStrategy>().map_err(pyo3::exceptions::PyValueError::new_err))
+        .transpose()
+}
+
 #[pyclass]
 pub struct PyTimsTofSyntheticsPrecursorFrameBuilder {
-    pub inner: TimsTofSyntheticsPrecursorFrameBuilder,
+    pub inner: Arc<TimsTofSyntheticsPrecursorFrameBuilder>,
 }
 
 #[pymethods]
 impl PyTimsTofSyntheticsPrecursorFrameBuilder {
     #[new]
-    pub fn new(db_path: &str) -> Self {
+    #[pyo3(signature = (db_path, duplicate_strategy=None, add_detection_noise=false, background_noise_density=0.0, num_threads=None, lazy=false, on_step=None))]
+    pub fn new(db_path: &str, duplicate_strategy: Option<&str>, add_detection_noise: bool, background_noise_density: f64, num_threads: Option<usize>, lazy: bool, on_step: Option<PyObject>) -> PyResult<Self> {
         let path = std::path::Path::new(db_path);
-        PyTimsTofSyntheticsPrecursorFrameBuilder { inner: TimsTofSyntheticsPrecursorFrameBuilder::new(path).unwrap() }
+        let duplicate_strategy = parse_duplicate_strategy(duplicate_strategy)?;
+        let num_threads = num_threads.unwrap_or_else(rayon::current_num_threads);
+        // on_step is called with the step name and its duration in seconds
+        let mut report = |name: &str, elapsed: std::time::Duration| {
+            if let Some(callback) = &on_step {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (name, elapsed.as_secs_f64())) {
+                        e.print(py);
+                    }
+                });
+            }
+        };
+        let mut inner = TimsTofSyntheticsPrecursorFrameBuilder::new_with_timing(path, duplicate_strategy, num_threads, lazy, &mut report)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        inner.noise_settings.detection_noise = add_detection_noise;
+        inner.noise_settings.background_noise_density = background_noise_density;
+        Ok(PyTimsTofSyntheticsPrecursorFrameBuilder { inner: Arc::new(inner) })
     }
 
-    pub fn build_precursor_frame(&self, frame_id: u32, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool) -> PyTimsFrame {
-        PyTimsFrame { inner: self.inner.build_precursor_frame(frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag) }
+    #[getter]
+    pub fn tof_calibration(&self) -> PyTofCalibration {
+        PyTofCalibration { inner: self.inner.tof_calibration }
     }
 
-    pub fn build_precursor_frames(&self, frame_ids: Vec<u32>, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrame> {
-        let frames = self.inner.build_precursor_frames(frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrame { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, seed=None))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_precursor_frame(&self, py: Python, frame_id: u32, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, seed: Option<u64>) -> PyTimsFrame {
+        let inner = &self.inner;
+        PyTimsFrame { inner: py.allow_threads(|| inner.build_precursor_frame(frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, seed)) }
     }
 
-    pub fn build_precursor_frame_annotated(&self, frame_id: u32, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool) -> PyTimsFrameAnnotated {
-        PyTimsFrameAnnotated { inner: self.inner.build_precursor_frame_annotated(frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag) }
+    #[pyo3(signature = (frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads, seed=None))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_precursor_frames(&self, py: Python, frame_ids: Vec<u32>, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, seed: Option<u64>) -> Vec<PyTimsFrame> {
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_precursor_frames(frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads, seed));
+        frames.into_iter().map(|x| PyTimsFrame { inner: x }).collect::<Vec<_>>()
     }
 
-    pub fn build_precursor_frames_annotated(&self, frame_ids: Vec<u32>, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrameAnnotated> {
-        let frames = self.inner.build_precursor_frames_annotated(frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrameAnnotated { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (frame_ids, batch_size, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads=None, seed=None))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn precursor_frame_batches(&self, frame_ids: Vec<u32>, batch_size: usize, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, seed: Option<u64>) -> PyResult<PyPrecursorFrameBatchIterator> {
+        if batch_size == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("batch_size must be greater than 0"));
+        }
+        let options = FrameBuildOptions { mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, seed, ..Default::default() };
+        let builder = Arc::clone(&self.inner);
+        let thread_pool = mscore::parallel::pool(num_threads);
+        let build: PrecursorBatchBuild = Box::new(move |batch: &[u32]| builder.build_precursor_frames_in_pool(&thread_pool, batch, &options));
+        Ok(PyPrecursorFrameBatchIterator { batches: FrameBatchIterator::new(frame_ids, batch_size, build) })
+    }
+
+    #[pyo3(signature = (frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, seed=None))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_precursor_frame_annotated(&self, py: Python, frame_id: u32, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, seed: Option<u64>) -> PyTimsFrameAnnotated {
+        let inner = &self.inner;
+        PyTimsFrameAnnotated { inner: py.allow_threads(|| inner.build_precursor_frame_annotated(frame_id, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, seed)) }
+    }
+
+    #[pyo3(signature = (frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads=None, seed=None))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_precursor_frames_annotated(&self, py: Python, frame_ids: Vec<u32>, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, seed: Option<u64>) -> Vec<PyTimsFrameAnnotated> {
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_precursor_frames_annotated(frame_ids, mz_noise_precursor, uniform, precursor_noise_ppm, right_drag, num_threads, seed));
+        frames.into_iter().map(|x| PyTimsFrameAnnotated { inner: x }).collect::<Vec<_>>()
     }
     pub fn frame_to_abundances(&self) -> BTreeMap<u32, (Vec<u32>, Vec<f32>)> {
         self.inner.frame_to_abundances.clone()
     }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsTofSyntheticsPrecursorFrameBuilder cannot be pickled, it holds the simulation data and an open SQLite connection, create it from the database path in every process instead"))
+    }
+}
+
+type PrecursorBatchBuild = Box<dyn FnMut(&[u32]) -> Vec<TimsFrame> + Send>;
+
+/// Python iterator over batches of precursor frames, a batch is only built when it is requested
+#[pyclass]
+pub struct PyPrecursorFrameBatchIterator {
+    batches: FrameBatchIterator<PrecursorBatchBuild>,
+}
+
+#[pymethods]
+impl PyPrecursorFrameBatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Option<Vec<PyTimsFrame>> {
+        let batches = &mut self.batches;
+        let batch = py.allow_threads(|| batches.next())?;
+        Some(batch.into_iter().map(|x| PyTimsFrame { inner: x }).collect())
+    }
 }
 
 #[pyclass(unsendable)]
@@ -75,29 +586,149 @@ pub struct PyTimsTofSyntheticsFrameBuilderDIA {
 #[pymethods]
 impl PyTimsTofSyntheticsFrameBuilderDIA {
     #[new]
-    pub fn new(db_path: &str, with_annotations: bool, num_threads: usize) -> Self {
+    /// `mode` is full, ms1 or ms2, ms1 skips loading the fragment ions, build_frames only builds frames of the MS level of the mode
+    #[pyo3(signature = (db_path, with_annotations, num_threads, duplicate_strategy=None, legacy_transmission=false, transmission_profile=None, transmission_k=None, add_detection_noise=false, background_noise_density=0.0, mode="full"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn new(db_path: &str, with_annotations: bool, num_threads: usize, duplicate_strategy: Option<&str>, legacy_transmission: bool, transmission_profile: Option<&str>, transmission_k: Option<f64>, add_detection_noise: bool, background_noise_density: f64, mode: &str) -> PyResult<Self> {
         let path = std::path::Path::new(db_path);
-        PyTimsTofSyntheticsFrameBuilderDIA { inner: TimsTofSyntheticsFrameBuilderDIA::new(path, with_annotations, num_threads).unwrap() }
+        let duplicate_strategy = parse_duplicate_strategy(duplicate_strategy)?;
+        let transmission_profile = parse_transmission_profile(transmission_profile, transmission_k)?;
+        let mode = FrameBuilderMode::from_name(mode).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let mut inner = match mode {
+            FrameBuilderMode::Ms1 => TimsTofSyntheticsFrameBuilderDIA::new_ms1_only(path, num_threads, duplicate_strategy),
+            _ => TimsTofSyntheticsFrameBuilderDIA::new(path, with_annotations, num_threads, duplicate_strategy, transmission_profile),
+        }.map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        inner.mode = mode;
+        inner.legacy_transmission = legacy_transmission;
+        inner.precursor_frame_builder.noise_settings.detection_noise = add_detection_noise;
+        inner.precursor_frame_builder.noise_settings.background_noise_density = background_noise_density;
+        Ok(PyTimsTofSyntheticsFrameBuilderDIA { inner })
+    }
+
+    #[getter]
+    pub fn tof_calibration(&self) -> PyTofCalibration {
+        PyTofCalibration { inner: self.inner.precursor_frame_builder.tof_calibration }
+    }
+
+    #[getter]
+    pub fn legacy_transmission(&self) -> bool {
+        self.inner.legacy_transmission
+    }
+
+    #[getter]
+    pub fn mode(&self) -> &'static str {
+        match self.inner.mode {
+            FrameBuilderMode::Full => "full",
+            FrameBuilderMode::Ms1 => "ms1",
+            FrameBuilderMode::Ms2 => "ms2",
+        }
+    }
+
+    #[setter]
+    pub fn set_legacy_transmission(&mut self, legacy_transmission: bool) {
+        self.inner.legacy_transmission = legacy_transmission;
+    }
+
+    /// Replace the model that predicts fragment intensities of ions without precomputed intensities, `model` is
+    /// called with sequence, charge and collision energy in eV and returns a flat Prosit array as list,
+    /// None restores the mobile proton heuristic
+    #[pyo3(signature = (model=None))]
+    pub fn set_fragment_intensity_model(&mut self, model: Option<PyObject>) {
+        let model: Arc<dyn FragmentIntensityModel> = match model {
+            Some(callable) => Arc::new(FlatIntensityModel::new(move |sequence: &PeptideSequence, charge: i32, collision_energy: f64| {
+                Python::with_gil(|py| {
+                    callable.call1(py, (sequence.sequence.as_str(), charge, collision_energy))
+                        .and_then(|flat| flat.extract::<Vec<f64>>(py))
+                        .unwrap_or_else(|e| panic!("fragment intensity model failed: {}", e))
+                })
+            })),
+            None => Arc::new(MobileProtonModel),
+        };
+        self.inner.set_fragment_model(model);
+    }
+
+    #[pyo3(signature = (frame_id, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frame(&self, py: Python, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<PyTimsFrame> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        // frames are built without the GIL, a Python fragment intensity model acquires it from the worker threads
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames(vec![frame_id], 1, &options)).map_err(frame_build_error)?;
+        Ok(PyTimsFrame { inner: frames[0].clone() })
+    }
+
+    #[pyo3(signature = (frame_id, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frame_annotated(&self, py: Python, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<PyTimsFrameAnnotated> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames_annotated(vec![frame_id], 1, &options)).map_err(frame_build_error)?;
+        Ok(PyTimsFrameAnnotated { inner: frames[0].clone() })
+    }
+
+    #[pyo3(signature = (frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frames(&self, py: Python, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<Vec<PyTimsFrame>> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| match inner.mode {
+            FrameBuilderMode::Full => inner.build_frames(frame_ids, num_threads, &options),
+            FrameBuilderMode::Ms1 => inner.build_precursor_frames_only(frame_ids, num_threads, &options),
+            FrameBuilderMode::Ms2 => inner.build_fragment_frames_only(frame_ids, num_threads, &options),
+        }).map_err(frame_build_error)?;
+        Ok(frames.iter().map(|x| PyTimsFrame { inner: x.clone() }).collect::<Vec<_>>())
+    }
+
+    #[pyo3(signature = (window_group, fragmentation, mz_noise_fragment, uniform, fragment_noise_ppm, right_drag, num_threads, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_window_group(&self, py: Python, window_group: u32, fragmentation: bool, mz_noise_fragment: bool, uniform: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<Vec<PyTimsFrame>> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_fragment, uniform, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization, ..Default::default() };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_window_group(window_group, num_threads, &options)).map_err(frame_build_error)?;
+        Ok(frames.into_iter().map(|x| PyTimsFrame { inner: x }).collect::<Vec<_>>())
     }
 
-    pub fn build_frame(&self, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool) -> PyTimsFrame {
-        let frames = self.inner.build_frames(vec![frame_id], fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, 1);
-        PyTimsFrame { inner: frames[0].clone() }
+    pub fn frames_for_window_group(&self, window_group: u32) -> Vec<u32> {
+        self.inner.frames_for_window_group(window_group)
     }
 
-    pub fn build_frame_annotated(&self, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool) -> PyTimsFrameAnnotated {
-        let frames = self.inner.build_frames_annotated(vec![frame_id], fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, 1);
-        PyTimsFrameAnnotated { inner: frames[0].clone() }
+    #[pyo3(signature = (frame_ids, batch_size, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads, callback, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frames_batched(&self, py: Python, frame_ids: Vec<u32>, batch_size: usize, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: usize, callback: PyObject, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<()> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let mut batches = self.inner.frame_batches(frame_ids, batch_size, num_threads, &options);
+        while let Some(batch) = py.allow_threads(|| batches.next()) {
+            let frames = batch.map_err(frame_build_error)?.into_iter().map(|x| PyTimsFrame { inner: x }).collect::<Vec<_>>();
+            callback.call1(py, (frames,))?;
+            // raise a KeyboardInterrupt between batches instead of after the last one
+            py.check_signals()?;
+        }
+        Ok(())
     }
 
-    pub fn build_frames(&self, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrame> {
-        let frames = self.inner.build_frames(frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrame { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads=None, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frames_annotated(&self, py: Python, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<Vec<PyTimsFrameAnnotated>> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames_annotated(frame_ids, num_threads, &options)).map_err(frame_build_error)?;
+        Ok(frames.iter().map(|x| PyTimsFrameAnnotated { inner: x.clone() }).collect::<Vec<_>>())
     }
 
-    pub fn build_frames_annotated(&self, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrameAnnotated> {
-        let frames = self.inner.build_frames_annotated(frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrameAnnotated { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (path, batch_size, num_threads, fragmentation=true, mz_noise_precursor=false, uniform=false, precursor_noise_ppm=0.0, mz_noise_fragment=false, fragment_noise_ppm=0.0, right_drag=false, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn write_dataset(&self, py: Python, path: &str, batch_size: usize, num_threads: usize, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<usize> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        py.allow_threads(|| inner.write_dataset(std::path::Path::new(path), batch_size, num_threads, &options).map_err(|e| e.to_string()))
+            .map_err(pyo3::exceptions::PyRuntimeError::new_err)
     }
 
     pub fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64 {
@@ -112,6 +743,53 @@ impl PyTimsTofSyntheticsFrameBuilderDIA {
         result
     }
 
+    pub fn get_collision_energies_for_frame(&self, frame_id: i32, scan_ids: Vec<i32>) -> Vec<f64> {
+        self.inner.fragmentation_settings.get_collision_energies(frame_id, &scan_ids)
+    }
+
+    pub fn export_ce_table(&self) -> Vec<(i32, i32, f64)> {
+        self.inner.fragmentation_settings.export_ce_table()
+    }
+
+    /// Replace the collision energies read from the database, e.g. by a per window ramp
+    pub fn set_collision_energy(&mut self, collision_energy: &PyTimsTofCollisionEnergyDIA) {
+        self.inner.fragmentation_settings = CollisionEnergySource::WindowGroups(collision_energy.inner.clone());
+    }
+
+    /// Take the collision energies from the inverse mobility of each scan, interpolated between the breakpoints
+    pub fn set_collision_energy_ramp(&mut self, inv_mobility: Vec<f64>, collision_energy: Vec<f64>) -> PyResult<()> {
+        if inv_mobility.len() != collision_energy.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "got {} mobilities but {} collision energies", inv_mobility.len(), collision_energy.len()
+            )));
+        }
+        let scan_to_mobility = &self.inner.precursor_frame_builder.scan_to_mobility;
+        let ramp = CollisionEnergyMobilityRamp::new(
+            inv_mobility.into_iter().zip(collision_energy).collect(),
+            scan_to_mobility.keys().map(|&scan| scan as i32).collect(),
+            scan_to_mobility.values().map(|&mobility| mobility as f64).collect(),
+        ).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        self.inner.fragmentation_settings = CollisionEnergySource::MobilityRamp(ramp);
+        Ok(())
+    }
+
+    /// (1/K0, collision energy) breakpoints of the collision energy ramp, None if collision energies follow the window groups
+    pub fn get_collision_energy_ramp(&self) -> Option<Vec<(f64, f64)>> {
+        match &self.inner.fragmentation_settings {
+            CollisionEnergySource::MobilityRamp(ramp) => Some(ramp.breakpoints().to_vec()),
+            CollisionEnergySource::WindowGroups(_) => None,
+        }
+    }
+
+    pub fn fragment_lookup_stats(&self) -> (usize, usize, usize, usize) {
+        let stats = &self.inner.fragment_lookup_stats;
+        (stats.exact(), stats.interpolated(), stats.missing(), stats.predicted())
+    }
+
+    pub fn reset_fragment_lookup_stats(&self) {
+        self.inner.fragment_lookup_stats.reset()
+    }
+
     pub fn get_ion_transmission_matrix(&self, peptide_id: u32, charge: i8, include_precursor_frames: bool) -> Vec<Vec<f32>> {
         self.inner.get_ion_transmission_matrix(peptide_id, charge, include_precursor_frames)
     }
@@ -122,8 +800,9 @@ impl PyTimsTofSyntheticsFrameBuilderDIA {
         Ok(tuple.into())
     }
 
-    pub fn count_number_transmissions_parallel(&self, peptide_ids: Vec<u32>, charge: Vec<i8>, num_threads: usize) -> Vec<(usize, usize)> {
-        self.inner.count_number_transmissions_parallel(peptide_ids, charge, num_threads)
+    pub fn count_number_transmissions_parallel(&self, py: Python, peptide_ids: Vec<u32>, charge: Vec<i8>, num_threads: usize) -> Vec<(usize, usize)> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.count_number_transmissions_parallel(peptide_ids, charge, num_threads))
     }
 
     pub fn get_fragment_ions_map(&self) -> BTreeMap<(u32, i8, i32), (PyPeptideProductIonSeriesCollection, Vec<PyMzSpectrum>)> {
@@ -132,10 +811,14 @@ impl PyTimsTofSyntheticsFrameBuilderDIA {
             let (peptide_ions, mz_spectra) = value;
             let peptide_ions = PyPeptideProductIonSeriesCollection { inner: peptide_ions.clone() };
             let mz_spectra = mz_spectra.iter().map(|x| PyMzSpectrum { inner: x.clone() }).collect::<Vec<_>>();
-            result.insert(key.clone(), (peptide_ions, mz_spectra));
+            result.insert(*key, (peptide_ions, mz_spectra));
         }
         result
     }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsTofSyntheticsFrameBuilderDIA cannot be pickled, it holds the simulation data and an open SQLite connection, create it from the database path in every process instead"))
+    }
 }
 
 #[pyclass(unsendable)]
@@ -146,29 +829,63 @@ pub struct PyTimsTofSyntheticsFrameBuilderDDA {
 #[pymethods]
 impl PyTimsTofSyntheticsFrameBuilderDDA {
     #[new]
-    pub fn new(db_path: &str, with_annotations: bool, num_threads: usize) -> Self {
+    #[pyo3(signature = (db_path, with_annotations, num_threads, duplicate_strategy=None))]
+    pub fn new(db_path: &str, with_annotations: bool, num_threads: usize, duplicate_strategy: Option<&str>) -> PyResult<Self> {
         let path = std::path::Path::new(db_path);
-        PyTimsTofSyntheticsFrameBuilderDDA { inner: TimsTofSyntheticsFrameBuilderDDA::new(path, with_annotations, num_threads) }
+        let duplicate_strategy = parse_duplicate_strategy(duplicate_strategy)?;
+        Ok(PyTimsTofSyntheticsFrameBuilderDDA { inner: TimsTofSyntheticsFrameBuilderDDA::new(path, with_annotations, num_threads, duplicate_strategy) })
     }
 
-    pub fn build_frame(&self, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool) -> PyTimsFrame {
-        let frames = self.inner.build_frames(vec![frame_id], fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, 1);
-        PyTimsFrame { inner: frames[0].clone() }
+    #[staticmethod]
+    #[pyo3(signature = (db_path, scheduler=None, isolation_width=3.0, collision_energy=30.0, duplicate_strategy=None))]
+    pub fn simulate_selection_scheme(db_path: &str, scheduler: Option<PyRef<PyPasefScheduler>>, isolation_width: f64, collision_energy: f64, duplicate_strategy: Option<&str>) -> PyResult<(usize, usize)> {
+        let path = std::path::Path::new(db_path);
+        let duplicate_strategy = parse_duplicate_strategy(duplicate_strategy)?;
+        let scheduler = scheduler.map(|scheduler| scheduler.inner.clone()).unwrap_or_default();
+        let settings = DDASelectionSettings { scheduler, isolation_width, collision_energy };
+        let (precursors, pasef_meta) = TimsTofSyntheticsFrameBuilderDDA::simulate_selection_scheme(path, &settings, duplicate_strategy)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        Ok((precursors.len(), pasef_meta.len()))
     }
 
-    pub fn build_frame_annotated(&self, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool) -> PyTimsFrameAnnotated {
-        let frames = self.inner.build_frames_annotated(vec![frame_id], fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, 1);
-        PyTimsFrameAnnotated { inner: frames[0].clone() }
+    #[pyo3(signature = (frame_id, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frame(&self, py: Python, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<PyTimsFrame> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames(vec![frame_id], 1, &options));
+        Ok(PyTimsFrame { inner: frames[0].clone() })
     }
 
-    pub fn build_frames(&self, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrame> {
-        let frames = self.inner.build_frames(frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrame { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (frame_id, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frame_annotated(&self, py: Python, frame_id: u32, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<PyTimsFrameAnnotated> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames_annotated(vec![frame_id], 1, &options));
+        Ok(PyTimsFrameAnnotated { inner: frames[0].clone() })
     }
 
-    pub fn build_frames_annotated(&self, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: usize) -> Vec<PyTimsFrameAnnotated> {
-        let frames = self.inner.build_frames_annotated(frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads);
-        frames.iter().map(|x| PyTimsFrameAnnotated { inner: x.clone() }).collect::<Vec<_>>()
+    #[pyo3(signature = (frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads=None, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frames(&self, py: Python, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<Vec<PyTimsFrame>> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames(frame_ids, num_threads, &options));
+        Ok(frames.iter().map(|x| PyTimsFrame { inner: x.clone() }).collect::<Vec<_>>())
+    }
+
+    #[pyo3(signature = (frame_ids, fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, num_threads=None, mz_min=None, mz_max=None, intensity_min=None, intensity_max=None, seed=None, quantization="round"))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn build_frames_annotated(&self, py: Python, frame_ids: Vec<u32>, fragmentation: bool, mz_noise_precursor: bool, uniform: bool, precursor_noise_ppm: f64, mz_noise_fragment: bool, fragment_noise_ppm: f64, right_drag: bool, num_threads: Option<usize>, mz_min: Option<f64>, mz_max: Option<f64>, intensity_min: Option<f64>, intensity_max: Option<f64>, seed: Option<u64>, quantization: &str) -> PyResult<Vec<PyTimsFrameAnnotated>> {
+        let quantization = IntensityQuantization::from_name(quantization).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let options = FrameBuildOptions { fragmentation, mz_noise_precursor, uniform, precursor_noise_ppm, mz_noise_fragment, fragment_noise_ppm, right_drag, mz_min, mz_max, intensity_min, intensity_max, seed, quantization };
+        let inner = &self.inner;
+        let frames = py.allow_threads(|| inner.build_frames_annotated(frame_ids, num_threads, &options));
+        Ok(frames.iter().map(|x| PyTimsFrameAnnotated { inner: x.clone() }).collect::<Vec<_>>())
     }
 
     pub fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64 {
@@ -183,6 +900,15 @@ impl PyTimsTofSyntheticsFrameBuilderDDA {
         result
     }
 
+    pub fn fragment_lookup_stats(&self) -> (usize, usize, usize) {
+        let stats = &self.inner.fragment_lookup_stats;
+        (stats.exact(), stats.interpolated(), stats.missing())
+    }
+
+    pub fn reset_fragment_lookup_stats(&self) {
+        self.inner.fragment_lookup_stats.reset()
+    }
+
     pub fn get_pasef_meta(&self) -> Vec<PyPasefMeta> {
         let pasef_meta = self.inner.transmission_settings.pasef_meta.clone();
         // go over all key, list<value> pairs, extract the values, flatten
@@ -201,7 +927,7 @@ impl PyTimsTofSyntheticsFrameBuilderDDA {
         let pasef_meta = self.inner.transmission_settings.pasef_meta.clone();
         let mut result = Vec::new();
         for (key, _) in pasef_meta.iter() {
-            result.push(key.clone());
+            result.push(*key);
         }
         result
     }
@@ -212,17 +938,192 @@ impl PyTimsTofSyntheticsFrameBuilderDDA {
             let (peptide_ions, mz_spectra) = value;
             let peptide_ions = PyPeptideProductIonSeriesCollection { inner: peptide_ions.clone() };
             let mz_spectra = mz_spectra.iter().map(|x| PyMzSpectrum { inner: x.clone() }).collect::<Vec<_>>();
-            result.insert(key.clone(), (peptide_ions, mz_spectra));
+            result.insert(*key, (peptide_ions, mz_spectra));
         }
         result
     }
+
+    pub fn __reduce__(&self) -> PyResult<PyObject> {
+        Err(pyo3::exceptions::PyTypeError::new_err("TimsTofSyntheticsFrameBuilderDDA cannot be pickled, it holds the simulation data and an open SQLite connection, create it from the database path in every process instead"))
+    }
+}
+
+#[pyclass]
+pub struct PyPasefScheduler {
+    pub inner: PasefScheduler,
+}
+
+/// Columns of the scheduled precursor selections
+type ScheduleColumns = (Vec<u32>, Vec<u32>, Vec<u32>, Vec<f64>, Vec<f64>, Vec<u32>, Vec<u32>, Vec<f64>);
+
+#[pymethods]
+impl PyPasefScheduler {
+    #[new]
+    #[pyo3(signature = (top_n=10, max_precursors_per_frame=12, ms2_frames_per_cycle=4, intensity_threshold=500.0, exclusion_duration=24.0, exclusion_mz_ppm=20.0, exclusion_mobility=0.015))]
+    pub fn new(top_n: usize, max_precursors_per_frame: usize, ms2_frames_per_cycle: usize, intensity_threshold: f64, exclusion_duration: f64, exclusion_mz_ppm: f64, exclusion_mobility: f64) -> Self {
+        PyPasefScheduler {
+            inner: PasefScheduler {
+                top_n,
+                max_precursors_per_frame,
+                ms2_frames_per_cycle,
+                intensity_threshold,
+                exclusion_duration,
+                exclusion_mz_ppm,
+                exclusion_mobility,
+            }
+        }
+    }
+
+    /// Schedule the fragment frames of a run from the candidate ions of its frames, candidates are given as columns
+    /// with the frame they can be selected in, returns the selections as columns (fragment frame, precursor frame,
+    /// peptide, m/z, mobility, first scan, last scan, intensity)
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn schedule(&self, py: Python, frame_ids: Vec<u32>, retention_times: Vec<f64>, candidate_frame_ids: Vec<u32>, peptide_ids: Vec<u32>, mz: Vec<f64>, mobility: Vec<f64>, scan_start: Vec<u32>, scan_end: Vec<u32>, intensity: Vec<f64>) -> PyResult<ScheduleColumns> {
+        if retention_times.len() != frame_ids.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err("expected one retention time per frame"));
+        }
+        let num_candidates = candidate_frame_ids.len();
+        if [peptide_ids.len(), mz.len(), mobility.len(), scan_start.len(), scan_end.len(), intensity.len()].iter().any(|&len| len != num_candidates) {
+            return Err(pyo3::exceptions::PyValueError::new_err("candidate columns must have the same length"));
+        }
+
+        let frame_index: HashMap<u32, usize> = frame_ids.iter().enumerate().map(|(index, &frame_id)| (frame_id, index)).collect();
+        let mut frames: Vec<CandidateFrame> = frame_ids.iter().zip(retention_times.iter())
+            .map(|(&frame_id, &retention_time)| CandidateFrame { frame_id, retention_time, candidates: Vec::new() })
+            .collect();
+        for index in 0..num_candidates {
+            let frame = match frame_index.get(&candidate_frame_ids[index]) {
+                Some(&frame) => frame,
+                None => return Err(pyo3::exceptions::PyValueError::new_err(format!("candidate of unknown frame {}", candidate_frame_ids[index]))),
+            };
+            frames[frame].candidates.push(PrecursorCandidate {
+                peptide_id: peptide_ids[index],
+                mz: mz[index],
+                mobility: mobility[index],
+                scan_start: scan_start[index],
+                scan_end: scan_end[index],
+                intensity: intensity[index],
+            });
+        }
+
+        let inner = &self.inner;
+        let schedule = py.allow_threads(|| inner.schedule(&frames));
+
+        let mut columns: ScheduleColumns = Default::default();
+        for frame in schedule {
+            for selection in frame.selections {
+                let candidate = selection.candidate;
+                columns.0.push(frame.frame_id);
+                columns.1.push(selection.precursor_frame_id);
+                columns.2.push(candidate.peptide_id);
+                columns.3.push(candidate.mz);
+                columns.4.push(candidate.mobility);
+                columns.5.push(candidate.scan_start);
+                columns.6.push(candidate.scan_end);
+                columns.7.push(candidate.intensity);
+            }
+        }
+        Ok(columns)
+    }
+}
+
+/// Columns of the window group settings and the window group of each fragment frame
+type DiaSchemeColumns = ((Vec<u32>, Vec<u32>, Vec<u32>, Vec<f32>, Vec<f32>, Vec<f32>), Vec<u32>);
+
+/// Generate a diaPASEF window scheme, returns the columns window_group, scan_start, scan_end, isolation_mz,
+/// isolation_width and collision_energy of the windows and the window groups of the fragment frames of one cycle
+#[pyfunction]
+#[pyo3(signature = (mz_min, mz_max, window_width, overlap, scan_count, windows_per_frame, mobility_mz_correlation=true))]
+pub fn generate_dia_scheme(mz_min: f64, mz_max: f64, window_width: f64, overlap: f64, scan_count: u32, windows_per_frame: usize, mobility_mz_correlation: bool) -> PyResult<DiaSchemeColumns> {
+    let (settings, cycle) = dia_scheme::generate_dia_scheme(mz_min, mz_max, window_width, overlap, scan_count, windows_per_frame, mobility_mz_correlation)
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+    let columns = (
+        settings.iter().map(|s| s.window_group).collect(),
+        settings.iter().map(|s| s.scan_start).collect(),
+        settings.iter().map(|s| s.scan_end).collect(),
+        settings.iter().map(|s| s.isolation_mz).collect(),
+        settings.iter().map(|s| s.isolation_width).collect(),
+        settings.iter().map(|s| s.collision_energy).collect(),
+    );
+    Ok((columns, cycle.iter().map(|entry| entry.window_group).collect()))
+}
+
+/// Gaps, windows sharing scans and scans not covered by a window group of a DIA scheme, empty for a valid scheme
+#[pyfunction]
+pub fn validate_dia_scheme(window_group: Vec<u32>, scan_start: Vec<u32>, scan_end: Vec<u32>, isolation_mz: Vec<f32>, isolation_width: Vec<f32>, collision_energy: Vec<f32>) -> PyResult<Vec<String>> {
+    let settings = window_group_settings(window_group, scan_start, scan_end, isolation_mz, isolation_width, collision_energy)?;
+    Ok(dia_scheme::validate_scheme(&settings).iter().map(|issue| issue.to_string()).collect())
+}
+
+/// Simulate a single peptide ion end-to-end, returns a dict with the precursor and fragment spectra
+/// as well as the predicted retention time, CCS and inverse mobility.
+/// Supported options: fragment_intensities (flat prosit array), gradient_length, mass_tolerance,
+/// abundance_threshold, max_result, intensity_min
+#[pyfunction]
+#[pyo3(signature = (sequence, charge, collision_energy, options=None))]
+pub fn simulate_peptide(py: Python, sequence: String, charge: i32, collision_energy: f64, options: Option<&Bound<'_, PyDict>>) -> PyResult<Py<PyDict>> {
+
+    fn get_option<'py, T: FromPyObject<'py>>(options: Option<&Bound<'py, PyDict>>, key: &str) -> PyResult<Option<T>> {
+        match options {
+            Some(options) => match options.get_item(key)? {
+                Some(value) if !value.is_none() => Ok(Some(value.extract::<T>()?)),
+                _ => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+
+    let simulation = PeptideSimulation::new(
+        sequence,
+        charge,
+        collision_energy,
+        get_option::<Vec<f64>>(options, "fragment_intensities")?,
+        get_option::<f64>(options, "gradient_length")?,
+        get_option::<f64>(options, "mass_tolerance")?,
+        get_option::<f64>(options, "abundance_threshold")?,
+        get_option::<i32>(options, "max_result")?,
+        get_option::<f64>(options, "intensity_min")?,
+    );
+
+    let result = PyDict::new_bound(py);
+    result.set_item("sequence", simulation.sequence.sequence.clone())?;
+    result.set_item("charge", simulation.charge)?;
+    result.set_item("collision_energy", simulation.collision_energy)?;
+    result.set_item("precursor_mz", simulation.precursor_mz)?;
+    result.set_item("precursor_spectrum", Py::new(py, PyMzSpectrum { inner: simulation.precursor_spectrum })?)?;
+    result.set_item("fragment_ions", Py::new(py, PyPeptideProductIonSeriesCollection { inner: simulation.fragment_ions })?)?;
+    result.set_item("fragment_spectrum", Py::new(py, PyMzSpectrum { inner: simulation.fragment_spectrum })?)?;
+    result.set_item("retention_time", simulation.retention_time)?;
+    result.set_item("ccs", simulation.ccs)?;
+    result.set_item("inverse_mobility", simulation.inverse_mobility)?;
+
+    Ok(result.unbind())
+}
+
+// a RuntimeError whose args are a message naming the first failed frame and a list of (frame_id, missing, scan)
+fn frame_build_error(errors: Vec<FrameBuildError>) -> PyErr {
+    let message = format!("{} frames could not be built, first: {}", errors.len(), errors[0]);
+    let details = errors.iter().map(|e| match e.missing {
+        MissingKind::RetentionTime => (e.frame_id, "retention_time", None),
+        MissingKind::Mobility(scan) => (e.frame_id, "mobility", Some(scan)),
+        MissingKind::FragmentIons => (e.frame_id, "fragment_ions", None),
+    }).collect::<Vec<_>>();
+    pyo3::exceptions::PyRuntimeError::new_err((message, details))
 }
 
 #[pymodule]
 pub fn py_simulation(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyTimsTofSyntheticsDataHandle>()?;
     m.add_class::<PyTimsTofSyntheticsPrecursorFrameBuilder>()?;
+    m.add_class::<PyPrecursorFrameBatchIterator>()?;
     m.add_class::<PyTimsTofSyntheticsFrameBuilderDIA>()?;
     m.add_class::<PyTimsTofSyntheticsFrameBuilderDDA>()?;
+    m.add_class::<PyPasefScheduler>()?;
+    m.add_class::<PyTofCalibration>()?;
+    m.add_function(wrap_pyfunction!(simulate_peptide, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_scan_distributions, m)?)?;
+    m.add_function(wrap_pyfunction!(migrate_synthetics_to_binary, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_dia_scheme, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_dia_scheme, m)?)?;
     Ok(())
 }
\ No newline at end of file
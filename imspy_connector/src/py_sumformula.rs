@@ -1,5 +1,8 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use std::collections::HashMap;
 use pyo3::prelude::*;
+use mscore::chemistry::formulas;
 use mscore::chemistry::sum_formula::SumFormula;
 use crate::py_mz_spectrum::PyMzSpectrum;
 
@@ -11,8 +14,9 @@ pub struct PySumFormula {
 #[pymethods]
 impl PySumFormula {
     #[new]
-    pub fn new(formula: &str) -> Self {
-        PySumFormula { inner: SumFormula::new(formula) }
+    pub fn new(formula: &str) -> PyResult<Self> {
+        let inner = SumFormula::try_new(formula).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PySumFormula { inner })
     }
 
     #[getter]
@@ -35,9 +39,33 @@ impl PySumFormula {
     }
 }
 
+/// atomic composition of an elemental formula, heavy isotopes in brackets, e.g. [13C]2, negative counts for losses
+#[pyfunction]
+pub fn parse_formula(formula: &str) -> PyResult<HashMap<String, i32>> {
+    formulas::parse_formula(formula).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+pub fn to_hill_notation(composition: HashMap<String, i32>) -> String {
+    formulas::to_hill_notation(&composition)
+}
+
+/// monoisotopic or average mass of an elemental formula
+#[pyfunction]
+#[pyo3(signature = (formula, average=false))]
+pub fn formula_mass(formula: &str, average: bool) -> PyResult<f64> {
+    let composition = parse_formula(formula)?;
+    Ok(match average {
+        true => formulas::average_mass_of(&composition),
+        false => formulas::monoisotopic_mass_of(&composition),
+    })
+}
 
 #[pymodule]
 pub fn py_sum_formula(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PySumFormula>()?;
+    m.add_function(wrap_pyfunction!(parse_formula, m)?)?;
+    m.add_function(wrap_pyfunction!(to_hill_notation, m)?)?;
+    m.add_function(wrap_pyfunction!(formula_mass, m)?)?;
     Ok(())
 }
\ No newline at end of file
@@ -1,14 +1,45 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use pyo3::prelude::*;
+use crate::py_buffer::{overwrite_shared, shared_array, shared_arrow_buffer};
 use pyo3::types::PyList;
 use pyo3::types::PyTuple;
-use numpy::{PyArray1, IntoPyArray, PyArrayMethods};
+use pyo3::types::PyDict;
+use pyo3::types::PyBytes;
+use numpy::{PyArray1, PyArray2, IntoPyArray, PyArrayMethods};
+use arrow::error::ArrowError;
+use arrow::pyarrow::ToPyArrow;
+use arrow::record_batch::RecordBatch;
+use mscore::data::serialization::BinarySerializable;
 use mscore::timstof::spectrum::{TimsSpectrum};
-use mscore::data::spectrum::{MsType, ToResolution, Vectorized, };
-use mscore::timstof::frame::{TimsFrame, ImsFrame, TimsFrameVectorized, ImsFrameVectorized, RawTimsFrame};
+use mscore::data::spectrum::{ApproxEq, MsType, MzAxis, ResampleMethod, ToResolution, Vectorized, };
+use mscore::timstof::frame::{FrameSummary, TimsFrame, ImsFrame, TimsFrameVectorized, ImsFrameVectorized, RawTimsFrame, TimsFrameCsr};
+use rustdf::export::arrow::{record_batch_from_buffers, PeakBuffers};
+use rustdf::export::mzml::MzMLSpectrum;
 use crate::py_annotation::PyTimsFrameAnnotated;
-
-
-use crate::py_mz_spectrum::{PyIndexedMzSpectrum, PyTimsSpectrum};
+use crate::py_dataset::PyTimsDataset;
+use rustdf::data::projection::{ToRawTimsFrame, ToTimsFrame};
+use mscore::algorithm::noise::NoiseEstimator;
+use mscore::algorithm::normalization::NormalizationMethod;
+use mscore::algorithm::smoothing::SmoothingMethod;
+
+
+use crate::py_mz_spectrum::{format_mz_range, PyIndexedMzSpectrum, PyTimsSpectrum};
+
+pub fn frame_summary_to_dict(py: Python<'_>, summary: &FrameSummary) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("frame_id", summary.frame_id)?;
+    dict.set_item("ms_type", summary.ms_type.to_string())?;
+    dict.set_item("retention_time", summary.retention_time)?;
+    dict.set_item("num_peaks", summary.num_peaks)?;
+    dict.set_item("total_intensity", summary.total_intensity)?;
+    dict.set_item("max_intensity", summary.max_intensity)?;
+    dict.set_item("median_intensity", summary.median_intensity)?;
+    dict.set_item("mz_range", summary.mz_range)?;
+    dict.set_item("scan_range", summary.scan_range)?;
+    dict.set_item("mobility_range", summary.mobility_range)?;
+    Ok(dict.unbind())
+}
 
 #[pyclass]
 #[derive(Clone)]
@@ -19,36 +50,36 @@ pub struct PyRawTimsFrame {
 #[pymethods]
 impl PyRawTimsFrame {
     #[new]
-    pub unsafe fn new(frame_id: i32,
-                      ms_type: i32,
-                      retention_time: f64,
-                      scan: &Bound<'_, PyArray1<u32>>,
-                      tof: &Bound<'_, PyArray1<u32>>,
-                      intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
+    pub fn new(frame_id: i32,
+               ms_type: i32,
+               retention_time: f64,
+               scan: &Bound<'_, PyArray1<u32>>,
+               tof: &Bound<'_, PyArray1<u32>>,
+               intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
         Ok(PyRawTimsFrame {
             inner: RawTimsFrame {
                 frame_id,
                 retention_time,
                 ms_type: MsType::new(ms_type),
-                scan: scan.as_slice()?.to_vec(),
-                tof: tof.as_slice()?.to_vec(),
-                intensity: intensity.as_slice()?.to_vec(),
+                scan: scan.to_vec()?,
+                tof: tof.to_vec()?,
+                intensity: intensity.to_vec()?,
             },
         })
     }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.intensity, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn scan(&self, py: Python) -> Py<PyArray1<u32>> {
-        self.inner.scan.clone().into_pyarray_bound(py).unbind()
+    pub fn scan(slf: &Bound<'_, Self>) -> Py<PyArray1<u32>> {
+        shared_array(&slf.borrow().inner.scan, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn tof(&self, py: Python) -> Py<PyArray1<u32>> {
-        self.inner.tof.clone().into_pyarray_bound(py).unbind()
+    pub fn tof(slf: &Bound<'_, Self>) -> Py<PyArray1<u32>> {
+        shared_array(&slf.borrow().inner.tof, slf.as_any()).unbind()
     }
 
     #[getter]
@@ -70,6 +101,70 @@ impl PyRawTimsFrame {
     pub fn retention_time(&self) -> f64 {
         self.inner.retention_time
     }
+
+    /// calibrate the frame with the index conversion of the dataset it was read from
+    #[pyo3(signature = (dataset, retention_time=None))]
+    pub fn to_tims_frame(&self, dataset: &PyTimsDataset, retention_time: Option<f64>) -> PyTimsFrame {
+        let converter = dataset.inner.loader.get_index_converter();
+        PyTimsFrame { inner: self.inner.to_tims_frame(converter, retention_time.unwrap_or(self.inner.retention_time)) }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("RawTimsFrame(frame_id={}, ms_type={}, retention_time={:.3}, peaks={})",
+            self.inner.frame_id, self.inner.ms_type, self.inner.retention_time, self.inner.tof.len())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.tof.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyRawTimsFrame { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyRawTimsFrame>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+/// Record batch over the peak buffers of a frame owned by `owner`, only frame id and retention time are filled
+pub fn frame_record_batch(frame: &TimsFrame, owner: &Bound<'_, PyAny>) -> Result<RecordBatch, ArrowError> {
+    let buffers = PeakBuffers {
+        scan: shared_arrow_buffer(&frame.scan, owner),
+        mobility: shared_arrow_buffer(&frame.ims_frame.mobility, owner),
+        tof: shared_arrow_buffer(&frame.tof, owner),
+        mz: shared_arrow_buffer(&frame.ims_frame.mz, owner),
+        intensity: shared_arrow_buffer(&frame.ims_frame.intensity, owner),
+    };
+    record_batch_from_buffers(frame.frame_id, frame.ims_frame.retention_time, buffers)
 }
 
 #[pyclass]
@@ -78,56 +173,85 @@ pub struct PyTimsFrame {
     pub inner: TimsFrame,
 }
 
+/// Scans, positions on the m/z axis and intensities
+type AxisVectorArrays = (Py<PyArray1<i32>>, Py<PyArray1<u32>>, Py<PyArray1<f64>>);
+
+/// Scans and their intensities resampled on a grid
+type ResampledScanArrays = (Py<PyArray1<i32>>, Py<PyArray2<f64>>);
+
+/// Mobilities and intensities of a mobilogram
+type MobilogramArrays = (Py<PyArray1<f64>>, Py<PyArray1<f64>>);
+
 #[pymethods]
 impl PyTimsFrame {
 
     #[new]
-    pub unsafe fn new(frame_id: i32,
-                      ms_type: i32,
-                      retention_time: f64,
-                      scan:&Bound<'_, PyArray1<i32>>,
-                      mobility: &Bound<'_, PyArray1<f64>>,
-                      tof: &Bound<'_, PyArray1<i32>>,
-                      mz: &Bound<'_, PyArray1<f64>>,
-                      intensity: &Bound<'_, PyArray1<f64>>) -> PyResult<Self> {
-        Ok(PyTimsFrame {
+    #[pyo3(signature = (frame_id, ms_type, retention_time, scan, mobility, tof, mz, intensity, validate=true))]
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(frame_id: i32,
+               ms_type: i32,
+               retention_time: f64,
+               scan:&Bound<'_, PyArray1<i32>>,
+               mobility: &Bound<'_, PyArray1<f64>>,
+               tof: &Bound<'_, PyArray1<i32>>,
+               mz: &Bound<'_, PyArray1<f64>>,
+               intensity: &Bound<'_, PyArray1<f64>>,
+               validate: bool) -> PyResult<Self> {
+        let frame = PyTimsFrame {
             inner: TimsFrame {
                 frame_id,
                 ms_type: MsType::new(ms_type),
-                scan: scan.as_slice()?.to_vec(),
-                tof: tof.as_slice()?.to_vec(),
+                scan: scan.to_vec()?,
+                tof: tof.to_vec()?,
                 ims_frame: ImsFrame {
                     retention_time,
-                    mobility: mobility.as_slice()?.to_vec(),
-                    mz: mz.as_slice()?.to_vec(),
-                    intensity: intensity.as_slice()?.to_vec(),
+                    mobility: mobility.to_vec()?,
+                    mz: mz.to_vec()?,
+                    intensity: intensity.to_vec()?,
                 },
             },
-        })
+        };
+        if validate {
+            frame.validate()?;
+        }
+        Ok(frame)
+    }
+
+    /// check equal array lengths, sorted scans, sorted m/z within each scan and non-negative intensities
+    pub fn validate(&self) -> PyResult<()> {
+        self.inner.validate().map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// copy of the frame with all arrays sorted by scan and then m/z
+    pub fn sort_by_scan_then_mz(&self) -> PyTimsFrame {
+        let mut inner = self.inner.clone();
+        inner.sort_by_scan_then_mz();
+        PyTimsFrame { inner }
     }
     #[getter]
-    pub fn mz(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.ims_frame.mz.clone().into_pyarray_bound(py).unbind()
+    pub fn mz(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.ims_frame.mz, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.ims_frame.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.ims_frame.intensity, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn scan(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.scan.clone().into_pyarray_bound(py).unbind()
+    pub fn scan(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.scan, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn mobility(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.ims_frame.mobility.clone().into_pyarray_bound(py).unbind()
+    pub fn mobility(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.ims_frame.mobility, slf.as_any()).unbind()
     }
     #[getter]
-    pub fn tof(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.tof.clone().into_pyarray_bound(py).unbind()
+    pub fn tof(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.tof, slf.as_any()).unbind()
     }
     #[setter]
-    pub unsafe fn set_tof(&mut self, tof: &Bound<'_, PyArray1<i32>>) {
-        self.inner.tof = tof.as_slice().unwrap().to_vec();
+    pub fn set_tof(slf: &Bound<'_, Self>, tof: &Bound<'_, PyArray1<i32>>) -> PyResult<()> {
+        let tof = tof.to_vec()?;
+        overwrite_shared(&mut slf.borrow_mut().inner.tof, tof)
     }
 
     #[getter]
@@ -151,6 +275,13 @@ impl PyTimsFrame {
         PyTimsFrame { inner: self.inner.to_resolution(resolution) }
     }
 
+    pub fn downsample_scans(&self, factor: usize) -> PyResult<PyTimsFrame> {
+        if factor == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("factor must be positive"));
+        }
+        Ok(PyTimsFrame { inner: self.inner.downsample_scans(factor) })
+    }
+
     pub fn get_tims_spectrum(&self, scan_index: i32) -> Option<PyTimsSpectrum> {
         self.inner.get_tims_spectrum(scan_index).map(|spectrum| PyTimsSpectrum { inner: spectrum })
     }
@@ -164,20 +295,51 @@ impl PyTimsFrame {
             list.bind(py).append(py_tims_spectrum)?;
         }
 
-        Ok(list.into())
+        Ok(list)
     }
 
-    pub fn to_windows(&self, py: Python, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> PyResult<Py<PyList>> {
+    /// windows as a list of spectra, with `with_metadata` as a tuple of a dict of window bounds and the list of spectra
+    #[pyo3(signature = (window_length, overlapping, min_peaks, min_intensity, with_metadata=false))]
+    pub fn to_windows(&self, py: Python, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, with_metadata: bool) -> PyResult<PyObject> {
 
-        let windows = self.inner.to_windows(window_length, overlapping, min_peaks, min_intensity);
+        let windows = self.inner.to_indexed_windows(window_length, overlapping, min_peaks, min_intensity);
         let list: Py<PyList> = PyList::empty_bound(py).into();
+        let metadata = PyDict::new_bound(py);
+        metadata.set_item("window_index", windows.iter().map(|w| w.window_index).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("mz_min", windows.iter().map(|w| w.mz_min).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("mz_max", windows.iter().map(|w| w.mz_max).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("scan_min", windows.iter().map(|w| w.scan_min).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("scan_max", windows.iter().map(|w| w.scan_max).collect::<Vec<_>>().into_pyarray_bound(py))?;
 
         for window in windows {
-            let py_mz_spectrum = Py::new(py, PyTimsSpectrum { inner: window })?;
+            let py_mz_spectrum = Py::new(py, PyTimsSpectrum { inner: window.spectrum })?;
             list.bind(py).append(py_mz_spectrum)?;
         }
 
-        Ok(list.into())
+        if with_metadata {
+            Ok(PyTuple::new_bound(py, &[metadata.to_object(py), list.to_object(py)]).to_object(py))
+        } else {
+            Ok(list.to_object(py))
+        }
+    }
+
+    /// windows along the mobility axis as a tuple of a dict of window bounds and a list of frames
+    pub fn to_mobility_windows(&self, py: Python, im_window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> PyResult<(Py<PyDict>, Py<PyList>)> {
+
+        let windows = self.inner.to_mobility_windows(im_window_length, overlapping, min_peaks, min_intensity);
+        let list: Py<PyList> = PyList::empty_bound(py).into();
+        let metadata = PyDict::new_bound(py);
+        metadata.set_item("window_index", windows.iter().map(|w| w.window_index).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("mobility_min", windows.iter().map(|w| w.mobility_min).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("mobility_max", windows.iter().map(|w| w.mobility_max).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("scan_min", windows.iter().map(|w| w.scan_min).collect::<Vec<_>>().into_pyarray_bound(py))?;
+        metadata.set_item("scan_max", windows.iter().map(|w| w.scan_max).collect::<Vec<_>>().into_pyarray_bound(py))?;
+
+        for window in windows {
+            list.bind(py).append(Py::new(py, PyTimsFrame { inner: window.frame })?)?;
+        }
+
+        Ok((metadata.unbind(), list))
     }
 
     pub fn to_indexed_mz_spectrum(&self) -> PyIndexedMzSpectrum {
@@ -186,12 +348,47 @@ impl PyTimsFrame {
 
     pub fn vectorized(&self, resolution: i32) -> PyTimsFrameVectorized {
         let vectorized = self.inner.vectorized(resolution);
-        let py_vectorized = PyTimsFrameVectorized {
+        PyTimsFrameVectorized {
             inner: vectorized,
-        };
-        py_vectorized
+        }
+    }
+
+    /// scans, positions on the m/z axis and intensities of the frame binned on a m/z axis shared by all frames of a run
+    pub fn vectorize_on_axis(&self, py: Python, resolution: i32, mz_min: f64, mz_max: f64) -> AxisVectorArrays {
+        let (scan, index, intensity) = self.inner.vectorize_on_axis(&MzAxis::new(resolution, mz_min, mz_max));
+        (scan.into_pyarray_bound(py).unbind(), index.into_pyarray_bound(py).unbind(), intensity.into_pyarray_bound(py).unbind())
     }
 
+    /// scans and a scans x grid matrix of the spectra of all scans resampled onto a strictly increasing m/z grid
+    #[pyo3(signature = (grid, method="linear", ppm=10.0))]
+    pub fn resample_scans(&self, py: Python, grid: &Bound<'_, PyArray1<f64>>, method: &str, ppm: f64) -> PyResult<ResampledScanArrays> {
+        let method = ResampleMethod::from_name(method, ppm).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let grid = grid.to_vec()?;
+        let inner = &self.inner;
+        let (scans, rows) = py.allow_threads(|| inner.resample_scans(&grid, method)).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let shape = [scans.len(), grid.len()];
+        let matrix = rows.concat().into_pyarray_bound(py).reshape(shape)?.unbind();
+        Ok((scans.into_pyarray_bound(py).unbind(), matrix))
+    }
+
+    /// noise level of every scan holding a peak in ascending scan order, sparse scans use the noise of the frame
+    #[pyo3(signature = (method="mad"))]
+    pub fn estimate_noise(&self, py: Python, method: &str) -> PyResult<Py<PyArray1<f64>>> {
+        let method = NoiseEstimator::from_name(method).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(self.inner.estimate_noise(method).into_pyarray_bound(py).unbind())
+    }
+
+    #[pyo3(signature = (min_snr, method="mad"))]
+    pub fn filter_snr(&self, min_snr: f64, method: &str) -> PyResult<PyTimsFrame> {
+        let method = NoiseEstimator::from_name(method).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyTimsFrame { inner: self.inner.filter_snr(min_snr, method) })
+    }
+
+    pub fn to_csr(&self, resolution: i32) -> PyTimsFrameCsr {
+        PyTimsFrameCsr::new(self.inner.to_csr(resolution))
+    }
+
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64) -> PyTimsFrame {
         PyTimsFrame { inner: self.inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max) }
     }
@@ -204,6 +401,15 @@ impl PyTimsFrame {
         self.inner.get_mobility_mean_and_variance()
     }
 
+    #[pyo3(signature = (charges, temperature=None, mass_gas=None))]
+    pub fn get_ccs(&self, py: Python, charges: Vec<u32>, temperature: Option<f64>, mass_gas: Option<f64>) -> PyResult<Py<PyArray1<f64>>> {
+        if charges.len() != 1 && charges.len() != self.inner.ims_frame.mz.len() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "expected one charge or one charge per peak ({}), got {}", self.inner.ims_frame.mz.len(), charges.len())));
+        }
+        Ok(self.inner.get_ccs(&charges, temperature, mass_gas).into_pyarray_bound(py).unbind())
+    }
+
     #[staticmethod]
     pub fn from_windows(_py: Python, windows: &Bound<'_, PyList>) -> PyResult<Self> {
         let mut spectra: Vec<TimsSpectrum> = Vec::new();
@@ -238,17 +444,114 @@ impl PyTimsFrame {
 
     pub fn to_noise_annotated_tims_frame(&self) -> PyTimsFrameAnnotated {
         let result = self.inner.to_noise_annotated_tims_frame();
-        return PyTimsFrameAnnotated { inner: result }
+        PyTimsFrameAnnotated { inner: result }
     }
 
     pub fn __add__(&self, other: PyTimsFrame) -> PyTimsFrame {
         let result = self.inner.clone() + other.inner.clone();
-        return PyTimsFrame { inner: result }
+        PyTimsFrame { inner: result }
     }
 
     pub fn random_subsample_frame(&self, take_probability: f64) -> PyTimsFrame {
         let result = self.inner.generate_random_sample(take_probability);
-        return PyTimsFrame { inner: result }
+        PyTimsFrame { inner: result }
+    }
+
+    /// pyarrow.RecordBatch sharing the peak buffers of the frame, handed over through the arrow C data interface
+    pub fn to_arrow(slf: &Bound<'_, Self>, py: Python) -> PyResult<PyObject> {
+        let batch = frame_record_batch(&slf.borrow().inner, slf.as_any()).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        batch.to_pyarrow(py)
+    }
+
+    /// `<spectrum>` element of the frame as written to mzML, peaks sorted by m/z with an ion mobility array
+    #[pyo3(signature = (index=0, compress=true))]
+    pub fn to_mzml_spectrum_xml(&self, index: usize, compress: bool) -> String {
+        MzMLSpectrum::from_frame(&self.inner).to_xml(index, compress)
+    }
+
+    /// smooth every m/z bin across adjacent scans, method is moving_average or savitzky_golay
+    #[pyo3(signature = (window_scans=5, method="savitzky_golay", polynomial_order=2, resolution=2))]
+    pub fn smooth_mobility(&self, window_scans: usize, method: &str, polynomial_order: usize, resolution: i32) -> PyResult<PyTimsFrame> {
+        let method = SmoothingMethod::from_name(method, polynomial_order).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyTimsFrame { inner: self.inner.smooth_mobility(window_scans, method, resolution) })
+    }
+
+    /// inverse mobility and intensity per scan of the peaks within ppm of mz, smoothed if window_scans is given
+    #[pyo3(signature = (mz, ppm=10.0, window_scans=None, method="savitzky_golay", polynomial_order=2))]
+    pub fn mobilogram(&self, py: Python, mz: f64, ppm: f64, window_scans: Option<usize>, method: &str, polynomial_order: usize) -> PyResult<MobilogramArrays> {
+        let method = SmoothingMethod::from_name(method, polynomial_order).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let (mobility, intensity) = self.inner.mobilogram(mz, ppm, window_scans.map(|window| (window, method)));
+        Ok((mobility.into_pyarray_bound(py).unbind(), intensity.into_pyarray_bound(py).unbind()))
+    }
+
+    /// copy of the frame with intensities normalized by tic, base_peak, per_scan_tic or quantile
+    #[pyo3(signature = (method="tic", target=1.0))]
+    pub fn normalize(&self, method: &str, target: f64) -> PyResult<PyTimsFrame> {
+        let method = NormalizationMethod::from_name(method, target).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        Ok(PyTimsFrame { inner: self.inner.normalize(method) })
+    }
+
+    pub fn top_n_global(&self, n: usize) -> PyTimsFrame {
+        PyTimsFrame { inner: self.inner.top_n_global(n) }
+    }
+
+    pub fn top_n_per_scan(&self, n: usize) -> PyTimsFrame {
+        PyTimsFrame { inner: self.inner.top_n_per_scan(n) }
+    }
+
+    /// number of peaks, intensity statistics and ranges, ranges are None for empty frames
+    pub fn summary(&self, py: Python) -> PyResult<Py<PyDict>> {
+        frame_summary_to_dict(py, &self.inner.summary())
+    }
+
+    /// raw run-length representation, scan and tof indices missing from the frame are computed by the dataset
+    pub fn to_raw(&self, dataset: &PyTimsDataset) -> PyRawTimsFrame {
+        PyRawTimsFrame { inner: self.inner.to_raw(dataset.inner.loader.get_index_converter()) }
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsFrame { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsFrame>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsFrame(frame_id={}, ms_type={}, retention_time={:.3}, peaks={}, mz_range={})",
+            self.inner.frame_id, self.inner.ms_type, self.inner.ims_frame.retention_time, self.inner.ims_frame.mz.len(), format_mz_range(&self.inner.ims_frame.mz))
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.ims_frame.mz.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
     }
 }
 
@@ -261,40 +564,41 @@ pub struct PyTimsFrameVectorized {
 #[pymethods]
 impl  PyTimsFrameVectorized {
     #[new]
-   pub unsafe fn new(frame_id: i32,
-                     ms_type: i32,
-                     retention_time: f64,
-                     scan: &Bound<'_, PyArray1<i32>>,
-                     mobility: &Bound<'_, PyArray1<f64>>,
-                     tof: &Bound<'_, PyArray1<i32>>,
-                     indices: &Bound<'_, PyArray1<i32>>,
-                     intensity: &Bound<'_, PyArray1<f64>>,
-                    resolution: i32,
-                    ) -> PyResult<Self> {
+   #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+   pub fn new(frame_id: i32,
+              ms_type: i32,
+              retention_time: f64,
+              scan: &Bound<'_, PyArray1<i32>>,
+              mobility: &Bound<'_, PyArray1<f64>>,
+              tof: &Bound<'_, PyArray1<i32>>,
+              indices: &Bound<'_, PyArray1<i32>>,
+              intensity: &Bound<'_, PyArray1<f64>>,
+             resolution: i32,
+             ) -> PyResult<Self> {
        Ok(PyTimsFrameVectorized {
            inner: TimsFrameVectorized {
                frame_id,
                ms_type: MsType::new(ms_type),
-               scan: scan.as_slice()?.to_vec(),
-               tof: tof.as_slice()?.to_vec(),
+               scan: scan.to_vec()?,
+               tof: tof.to_vec()?,
                ims_frame: ImsFrameVectorized {
                    retention_time,
-                   mobility: mobility.as_slice()?.to_vec(),
-                   indices: indices.as_slice()?.to_vec(),
-                   values: intensity.as_slice()?.to_vec(),
+                   mobility: mobility.to_vec()?,
+                   indices: indices.to_vec()?,
+                   values: intensity.to_vec()?,
                    resolution,
                },
            },
        })
    }
    #[getter]
-    pub fn indices(&self, py: Python) -> Py<PyArray1<i32>> {
-         self.inner.ims_frame.indices.clone().into_pyarray_bound(py).unbind()
+    pub fn indices(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+         shared_array(&slf.borrow().inner.ims_frame.indices, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn values(&self, py: Python) -> Py<PyArray1<f64>> {
-         self.inner.ims_frame.values.clone().into_pyarray_bound(py).unbind()
+    pub fn values(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+         shared_array(&slf.borrow().inner.ims_frame.values, slf.as_any()).unbind()
     }
 
     #[getter]
@@ -303,18 +607,18 @@ impl  PyTimsFrameVectorized {
     }
 
     #[getter]
-    pub fn scan(&self, py: Python) -> Py<PyArray1<i32>> {
-         self.inner.scan.clone().into_pyarray_bound(py).unbind()
+    pub fn scan(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+         shared_array(&slf.borrow().inner.scan, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn mobility(&self, py: Python) -> Py<PyArray1<f64>> {
-         self.inner.ims_frame.mobility.clone().into_pyarray_bound(py).unbind()
+    pub fn mobility(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+         shared_array(&slf.borrow().inner.ims_frame.mobility, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn tof(&self, py: Python) -> Py<PyArray1<i32>> {
-         self.inner.tof.clone().into_pyarray_bound(py).unbind()
+    pub fn tof(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+         shared_array(&slf.borrow().inner.tof, slf.as_any()).unbind()
     }
 
     #[getter]
@@ -337,16 +641,191 @@ impl  PyTimsFrameVectorized {
          self.inner.ims_frame.retention_time
     }
 
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64) -> PyTimsFrameVectorized {
-        return PyTimsFrameVectorized { inner: self.inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max) }
+        PyTimsFrameVectorized { inner: self.inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max) }
+    }
+
+    pub fn to_csr(&self) -> PyTimsFrameCsr {
+        PyTimsFrameCsr::new(self.inner.to_csr())
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsFrameVectorized(frame_id={}, ms_type={}, retention_time={:.3}, resolution={}, peaks={})",
+            self.inner.frame_id, self.inner.ms_type, self.inner.ims_frame.retention_time, self.inner.ims_frame.resolution, self.inner.ims_frame.values.len())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.ims_frame.values.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsFrameVectorized { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsFrameVectorized>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyTimsFrameCsr {
+    pub inner: TimsFrameCsr,
+    /// row pointers of `inner` as int64, converted once and shared by every `indptr` array
+    indptr: Vec<i64>,
+}
+
+impl PyTimsFrameCsr {
+    pub fn new(inner: TimsFrameCsr) -> Self {
+        let indptr = inner.scan_ptr.iter().map(|&ptr| ptr as i64).collect();
+        PyTimsFrameCsr { inner, indptr }
+    }
+}
+
+#[pymethods]
+impl PyTimsFrameCsr {
+    #[getter]
+    pub fn indptr(slf: &Bound<'_, Self>) -> Py<PyArray1<i64>> {
+        shared_array(&slf.borrow().indptr, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn indices(slf: &Bound<'_, Self>) -> Py<PyArray1<i64>> {
+        shared_array(&slf.borrow().inner.mz_indices, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn data(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.values, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn tof(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.tof, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn mobility(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.mobility, slf.as_any()).unbind()
+    }
+
+    #[getter]
+    pub fn shape(&self) -> (usize, usize) {
+        self.inner.shape()
+    }
+
+    #[getter]
+    pub fn scan_min(&self) -> i32 {
+        self.inner.scan_min
+    }
+
+    #[getter]
+    pub fn resolution(&self) -> i32 {
+        self.inner.resolution
+    }
+
+    #[getter]
+    pub fn frame_id(&self) -> i32 {
+        self.inner.frame_id
+    }
+
+    #[getter]
+    pub fn ms_type_numeric(&self) -> i32 {
+        self.inner.ms_type.ms_type_numeric()
+    }
+
+    #[getter]
+    pub fn retention_time(&self) -> f64 {
+        self.inner.retention_time
+    }
+
+    pub fn to_tims_frame(&self) -> PyTimsFrame {
+        PyTimsFrame { inner: self.inner.to_tims_frame() }
+    }
+
+    pub fn to_dense_slice(&self, py: Python, scan_min: i32, scan_max: i32, mz_index_min: i64, mz_index_max: i64) -> Py<PyArray1<f64>> {
+        self.inner.to_dense_slice(scan_min..scan_max, mz_index_min..mz_index_max).into_pyarray_bound(py).unbind()
+    }
+
+    pub fn __repr__(&self) -> String {
+        self.inner.to_string()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.values.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
     }
 
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsFrameCsr::new(inner))
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsFrameCsr>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pymodule]
 pub fn py_tims_frame(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyTimsFrame>()?;
     m.add_class::<PyTimsFrameVectorized>()?;
+    m.add_class::<PyTimsFrameCsr>()?;
     m.add_class::<PyRawTimsFrame>()?;
     Ok(())
 }
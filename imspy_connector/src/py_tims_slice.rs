@@ -1,11 +1,39 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use pyo3::prelude::*;
-use mscore::data::spectrum::{MsType};
-use mscore::timstof::slice::{TimsPlane, TimsSlice, TimsSliceVectorized};
-use pyo3::types::{PyList};
-use numpy::{IntoPyArray, PyArray1, PyArrayMethods};
+use crate::py_buffer::{shared_array};
+use mscore::algorithm::normalization::NormalizationMethod;
+use mscore::data::serialization::BinarySerializable;
+use mscore::data::spectrum::{ApproxEq, MsType, MzAxis};
+use mscore::timstof::frame::DenseWindows;
+use mscore::timstof::slice::{SliceSummary, TimsPlane, TimsSlice, TimsSliceVectorized};
+use rustdf::algorithm::feature::{assign_charge_states, detect_features};
+use rustdf::export::arrow::slice_schema;
+use rustdf::export::parquet::{parse_compression, ToParquet};
+use rustdf::data::cache::SliceCache;
+use arrow::pyarrow::ToPyArrow;
+use std::path::Path;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArrayMethods};
 use crate::py_mz_spectrum::{PyTimsSpectrum};
 
-use crate::py_tims_frame::{PyTimsFrame, PyTimsFrameVectorized};
+use crate::py_tims_frame::{frame_record_batch, frame_summary_to_dict, PyTimsFrame, PyTimsFrameVectorized};
+
+pub fn slice_summary_to_dict(py: Python<'_>, summary: &SliceSummary) -> PyResult<Py<PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("num_frames", summary.num_frames)?;
+    dict.set_item("num_peaks", summary.num_peaks)?;
+    dict.set_item("total_intensity", summary.total_intensity)?;
+    dict.set_item("max_intensity", summary.max_intensity)?;
+    dict.set_item("rt_range", summary.rt_range)?;
+    dict.set_item("mz_range", summary.mz_range)?;
+    dict.set_item("scan_range", summary.scan_range)?;
+    dict.set_item("mobility_range", summary.mobility_range)?;
+    dict.set_item("ms_type_counts", summary.ms_type_counts.clone())?;
+    let frames = summary.frames.iter().map(|frame| frame_summary_to_dict(py, frame)).collect::<PyResult<Vec<_>>>()?;
+    dict.set_item("frames", frames)?;
+    Ok(dict.unbind())
+}
 
 #[pyclass]
 #[derive(Clone)]
@@ -14,10 +42,14 @@ pub struct PyTimsSlice {
 }
 
 
+/// Dense intensity matrix and the bin centers of its two axes
+type DenseMatrixArrays = (Py<PyArray2<f64>>, Py<PyArray1<f64>>, Py<PyArray1<f64>>);
+
 #[pymethods]
 impl PyTimsSlice {
     #[new]
-    pub unsafe fn new(
+    #[allow(clippy::too_many_arguments, reason = "the Python constructor takes every field of the wrapped type")]
+    pub fn new(
         _py: Python,
         frame_ids: &Bound<'_, PyArray1<i32>>,
         scans: &Bound<'_, PyArray1<i32>>,
@@ -27,13 +59,13 @@ impl PyTimsSlice {
         mzs: &Bound<'_, PyArray1<f64>>,
         intensities: &Bound<'_, PyArray1<f64>>,
     ) -> PyResult<Self> {
-        let frame_ids_vec = frame_ids.as_slice()?.to_vec();
-        let scans_vec = scans.as_slice()?.to_vec();
-        let tofs_vec = tofs.as_slice()?.to_vec();
-        let retention_times_vec = retention_times.as_slice()?.to_vec();
-        let mobilities_vec = mobilities.as_slice()?.to_vec();
-        let mzs_vec = mzs.as_slice()?.to_vec();
-        let intensities_vec = intensities.as_slice()?.to_vec();
+        let frame_ids_vec = frame_ids.to_vec()?;
+        let scans_vec = scans.to_vec()?;
+        let tofs_vec = tofs.to_vec()?;
+        let retention_times_vec = retention_times.to_vec()?;
+        let mobilities_vec = mobilities.to_vec()?;
+        let mzs_vec = mzs.to_vec()?;
+        let intensities_vec = intensities.to_vec()?;
 
         // Now call your Rust function from_flat_slice
         let tims_slice = TimsSlice::from_flat_slice(
@@ -57,11 +89,15 @@ impl PyTimsSlice {
     #[getter]
     pub fn frame_count(&self) -> i32 { self.inner.frames.len() as i32 }
 
-    pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max, num_threads) }
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn filter_ranged(&self, py: Python, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> PyTimsSlice {
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max, num_threads)) }
     }
 
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
     pub fn filter_ranged_ms_type_specific(&self,
+                                          py: Python,
                                           mz_min_ms1: f64,
                                           mz_max_ms1: f64,
                                           scan_min_ms1: i32,
@@ -79,10 +115,11 @@ impl PyTimsSlice {
                                           intensity_min_ms2: f64,
                                           intensity_max_ms2: f64,
                                           num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.filter_ranged_ms_type_specific(
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.filter_ranged_ms_type_specific(
             mz_min_ms1, mz_max_ms1, scan_min_ms1, scan_max_ms1, inv_mob_min_ms1, inv_mob_max_ms1, intensity_min_ms1, intensity_max_ms1,
             mz_min_ms2, mz_max_ms2, scan_min_ms2, scan_max_ms2, inv_mob_min_ms2, inv_mob_max_ms2, intensity_min_ms2, intensity_max_ms2,
-            num_threads) }
+            num_threads)) }
     }
 
     pub fn get_frames(&self, py: Python) -> PyResult<Py<PyList>> {
@@ -94,7 +131,7 @@ impl PyTimsSlice {
             list.bind(py).append(py_tims_frame)?;
         }
 
-        Ok(list.into())
+        Ok(list)
     }
 
     pub fn get_precursors(&self) -> PyTimsSlice {
@@ -111,7 +148,8 @@ impl PyTimsSlice {
 
     pub fn to_windows(&self, py: Python, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, num_threads: usize) -> PyResult<Py<PyList>> {
 
-        let windows = self.inner.to_windows(window_length, overlapping, min_peaks, min_intensity, num_threads);
+        let inner = &self.inner;
+        let windows = py.allow_threads(|| inner.to_windows(window_length, overlapping, min_peaks, min_intensity, num_threads));
         let list: Py<PyList> = PyList::empty_bound(py).into();
 
         for window in windows {
@@ -119,41 +157,48 @@ impl PyTimsSlice {
             list.bind(py).append(py_mz_spectrum)?;
         }
 
-        Ok(list.into())
+        Ok(list)
     }
 
-    pub fn to_dense_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32, num_threads: usize) -> Vec<(Vec<f64>, Vec<i32>, Vec<i32>, usize, usize)> {
-        self.inner.to_dense_windows(window_length, overlapping, min_peaks, min_intensity, resolution, num_threads)
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn to_dense_windows(&self, py: Python, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32, num_threads: usize) -> Vec<DenseWindows> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.to_dense_windows(window_length, overlapping, min_peaks, min_intensity, resolution, num_threads))
     }
 
     pub fn get_frame_at_index(&self, index: i32) -> PyTimsFrame {
         PyTimsFrame { inner: self.inner.frames[index as usize].clone() }
     }
 
-    pub fn to_resolution(&self, resolution: i32, num_threads: usize) -> PyTimsSlice {
-        PyTimsSlice { inner: self.inner.to_resolution(resolution, num_threads) }
+    #[pyo3(signature = (method="tic", target=1.0, num_threads=4))]
+    pub fn normalize_frames(&self, py: Python, method: &str, target: f64, num_threads: usize) -> PyResult<PyTimsSlice> {
+        let method = NormalizationMethod::from_name(method, target).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let inner = &self.inner;
+        Ok(PyTimsSlice { inner: py.allow_threads(|| inner.normalize_frames(method, num_threads)) })
     }
 
-    fn to_arrays(&self, py: Python) -> PyResult<(PyObject, PyObject, PyObject, PyObject, PyObject, PyObject, PyObject)> {
+    pub fn to_resolution(&self, py: Python, resolution: i32, num_threads: usize) -> PyTimsSlice {
+        let inner = &self.inner;
+        PyTimsSlice { inner: py.allow_threads(|| inner.to_resolution(resolution, num_threads)) }
+    }
 
-        let flat_frame = self.inner.flatten();
+    /// frame id, scan, tof, retention time, mobility, m/z and intensity of all peaks, filtered while flattening
+    #[pyo3(signature = (ms_type=None, intensity_min=None, num_threads=4))]
+    fn to_arrays(&self, py: Python, ms_type: Option<i32>, intensity_min: Option<f64>, num_threads: usize) -> PyResult<(PyObject, PyObject, PyObject, PyObject, PyObject, PyObject, PyObject)> {
 
-        let frame_ids_np = flat_frame.frame_ids.into_pyarray_bound(py).unbind();
-        let scans_np = flat_frame.scans.into_pyarray_bound(py).unbind();
-        let tofs_np = flat_frame.tofs.into_pyarray_bound(py).unbind();
-        let retention_times_np = flat_frame.retention_times.into_pyarray_bound(py).unbind();
-        let mobilities_np = flat_frame.mobilities.into_pyarray_bound(py).unbind();
-        let mzs_np = flat_frame.mzs.into_pyarray_bound(py).unbind();
-        let intensities_np = flat_frame.intensities.into_pyarray_bound(py).unbind();
+        let inner = &self.inner;
+        let columns = py.allow_threads(|| inner.flatten_filtered(ms_type, intensity_min, num_threads));
 
-        Ok((frame_ids_np.to_object(py), scans_np.to_object(py), tofs_np.to_object(py),
-            retention_times_np.to_object(py), mobilities_np.to_object(py), mzs_np.to_object(py),
-            intensities_np.to_object(py)))
+        Ok((columns.frame_id.into_pyarray_bound(py).into_any().unbind(), columns.scan.into_pyarray_bound(py).into_any().unbind(),
+            columns.tof.into_pyarray_bound(py).into_any().unbind(), columns.rt.into_pyarray_bound(py).into_any().unbind(),
+            columns.mobility.into_pyarray_bound(py).into_any().unbind(), columns.mz.into_pyarray_bound(py).into_any().unbind(),
+            columns.intensity.into_pyarray_bound(py).into_any().unbind()))
     }
 
     pub fn to_tims_planes(&self, py: Python, tof_max_value: i32, num_chunks: i32, num_threads: i32) -> PyResult<Py<PyList>> {
 
-        let planes = self.inner.to_tims_planes(tof_max_value, num_chunks, num_threads as usize);
+        let inner = &self.inner;
+        let planes = py.allow_threads(|| inner.to_tims_planes(tof_max_value, num_chunks, num_threads as usize));
         let list: Py<PyList> = PyList::empty_bound(py).into();
 
         for plane in planes {
@@ -161,21 +206,180 @@ impl PyTimsSlice {
             list.bind(py).append(py_plane)?;
         }
 
-        Ok(list.into())
+        Ok(list)
     }
 
-    pub fn vectorized(&self, resolution: i32, num_threads: usize) -> PyTimsSliceVectorized {
-        let vectorized = self.inner.vectorized(resolution, num_threads);
-        let py_vectorized = PyTimsSliceVectorized {
+    /// retention time x mobility planes of m/z windows of ppm around every target, in the order of mz_list
+    #[pyo3(signature = (mz_list, ppm=10.0, num_threads=4))]
+    pub fn get_planes(&self, py: Python, mz_list: Vec<f64>, ppm: f64, num_threads: usize) -> PyResult<Py<PyList>> {
+        let inner = &self.inner;
+        let planes = py.allow_threads(|| inner.to_planes(&mz_list, ppm, num_threads));
+        let list = PyList::empty_bound(py);
+        for plane in planes {
+            list.append(Py::new(py, PyTimsPlane { inner: plane })?)?;
+        }
+        Ok(list.unbind())
+    }
+
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn to_dense_matrix(&self, py: Python, mz_min: f64, mz_max: f64, mz_bin_width: f64, im_min: f64, im_max: f64, im_bin_count: usize, num_threads: usize) -> PyResult<DenseMatrixArrays> {
+
+        let inner = &self.inner;
+        let (matrix, mz_axis, im_axis) = py.allow_threads(|| inner.to_dense_matrix(mz_min, mz_max, mz_bin_width, im_min, im_max, im_bin_count, num_threads))
+            .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let shape = [mz_axis.len(), im_axis.len()];
+
+        let matrix_np = matrix.into_pyarray_bound(py).reshape(shape)?.unbind();
+        let mz_axis_np = mz_axis.into_pyarray_bound(py).unbind();
+        let im_axis_np = im_axis.into_pyarray_bound(py).unbind();
+
+        Ok((matrix_np, mz_axis_np, im_axis_np))
+    }
+
+    #[pyo3(signature = (ppm_tol, im_tol, rt_gap_frames, min_intensity, min_points, num_threads, assign_charges=false, rt_tol=0.0, max_charge=4))]
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn detect_features(&self, py: Python, ppm_tol: f64, im_tol: f64, rt_gap_frames: usize, min_intensity: f64, min_points: usize, num_threads: usize, assign_charges: bool, rt_tol: f64, max_charge: i32) -> PyResult<Py<PyDict>> {
+
+        let inner = &self.inner;
+        let features = py.allow_threads(|| {
+            let mut features = detect_features(inner, ppm_tol, im_tol, rt_gap_frames, min_intensity, min_points, num_threads);
+            if assign_charges {
+                assign_charge_states(&mut features, ppm_tol, rt_tol, im_tol, max_charge, num_threads);
+            }
+            features
+        });
+
+        let apex_mz: Vec<f64> = features.iter().map(|f| f.apex_mz).collect();
+        let apex_retention_time: Vec<f64> = features.iter().map(|f| f.apex_retention_time).collect();
+        let apex_mobility: Vec<f64> = features.iter().map(|f| f.apex_mobility).collect();
+        let apex_frame_id: Vec<i32> = features.iter().map(|f| f.apex_frame_id).collect();
+        let total_intensity: Vec<f64> = features.iter().map(|f| f.total_intensity).collect();
+        // unassigned charge states are reported as 0
+        let charge: Vec<i32> = features.iter().map(|f| f.charge.unwrap_or(0)).collect();
+        let num_points: Vec<u64> = features.iter().map(|f| f.num_points() as u64).collect();
+
+        // member peaks, flattened
+        let peak_feature_index: Vec<u64> = features.iter().enumerate().flat_map(|(i, f)| std::iter::repeat_n(i as u64, f.peaks.len())).collect();
+        let peak_frame_index: Vec<u64> = features.iter().flat_map(|f| f.peaks.iter().map(|p| p.0 as u64)).collect();
+        let peak_index: Vec<u64> = features.iter().flat_map(|f| f.peaks.iter().map(|p| p.1 as u64)).collect();
+
+        let result = PyDict::new_bound(py);
+        result.set_item("apex_mz", apex_mz.into_pyarray_bound(py))?;
+        result.set_item("apex_retention_time", apex_retention_time.into_pyarray_bound(py))?;
+        result.set_item("apex_mobility", apex_mobility.into_pyarray_bound(py))?;
+        result.set_item("apex_frame_id", apex_frame_id.into_pyarray_bound(py))?;
+        result.set_item("total_intensity", total_intensity.into_pyarray_bound(py))?;
+        result.set_item("charge", charge.into_pyarray_bound(py))?;
+        result.set_item("num_points", num_points.into_pyarray_bound(py))?;
+        result.set_item("peak_feature_index", peak_feature_index.into_pyarray_bound(py))?;
+        result.set_item("peak_frame_index", peak_frame_index.into_pyarray_bound(py))?;
+        result.set_item("peak_index", peak_index.into_pyarray_bound(py))?;
+
+        Ok(result.unbind())
+    }
+
+    pub fn vectorized(&self, py: Python, resolution: i32, num_threads: usize) -> PyTimsSliceVectorized {
+        let inner = &self.inner;
+        let vectorized = py.allow_threads(|| inner.vectorized(resolution, num_threads));
+        PyTimsSliceVectorized {
             inner: vectorized,
-        };
-        py_vectorized
+        }
+    }
+
+    /// scans, positions on the m/z axis and intensities of every frame binned on the same m/z axis
+    pub fn vectorize_on_axis(&self, py: Python, resolution: i32, mz_min: f64, mz_max: f64, num_threads: usize) -> Vec<(Vec<i32>, Vec<u32>, Vec<f64>)> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.vectorize_on_axis(&MzAxis::new(resolution, mz_min, mz_max), num_threads))
     }
 
     #[staticmethod]
     pub fn from_frames(frames: Vec<PyTimsFrame>) -> PyTimsSlice {
         PyTimsSlice { inner: TimsSlice::new(frames.iter().map(|frame| frame.inner.clone()).collect()) }
     }
+
+    /// pyarrow.Table with one record batch per frame, sharing the peak buffers of the slice
+    pub fn to_arrow(slf: &Bound<'_, Self>, py: Python) -> PyResult<PyObject> {
+        let batches = slf.borrow().inner.frames.iter()
+            .map(|frame| frame_record_batch(frame, slf.as_any()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let table = py.import_bound("pyarrow")?.getattr("Table")?
+            .call_method1("from_batches", (batches.to_pyarrow(py)?, slice_schema().to_pyarrow(py)?))?;
+        Ok(table.unbind())
+    }
+
+    /// totals and ranges of the slice, counts per MsType and the summary of every frame
+    #[pyo3(signature = (num_threads=4))]
+    pub fn summary(&self, py: Python, num_threads: usize) -> PyResult<Py<PyDict>> {
+        let inner = &self.inner;
+        slice_summary_to_dict(py, &py.allow_threads(|| inner.summary(num_threads)))
+    }
+
+    #[pyo3(signature = (path, compression="zstd"))]
+    pub fn to_parquet(&self, py: Python, path: &str, compression: &str) -> PyResult<()> {
+        let compression = parse_compression(compression).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        let inner = &self.inner;
+        py.allow_threads(|| inner.to_parquet(Path::new(path), compression).map_err(|e| e.to_string()))
+            .map_err(pyo3::exceptions::PyIOError::new_err)
+    }
+
+    pub fn save(&self, py: Python, path: &str) -> PyResult<()> {
+        let inner = &self.inner;
+        py.allow_threads(|| inner.write_cache(Path::new(path)).map_err(|e| e.to_string()))
+            .map_err(pyo3::exceptions::PyIOError::new_err)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (path, num_threads=4))]
+    pub fn load(py: Python, path: &str, num_threads: usize) -> PyResult<Self> {
+        let inner = py.allow_threads(|| TimsSlice::read_cache(Path::new(path), num_threads).map_err(|e| e.to_string()))
+            .map_err(pyo3::exceptions::PyIOError::new_err)?;
+        Ok(PyTimsSlice { inner })
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsSlice { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsSlice>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsSlice(frames={}, peaks={})", self.inner.frames.len(), self.inner.frames.iter().map(|frame| frame.ims_frame.mz.len()).sum::<usize>())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.frames.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
 }
 
 #[pyclass]
@@ -196,7 +400,7 @@ impl PyTimsSliceVectorized {
             list.bind(py).append(py_tims_frame)?;
         }
 
-        Ok(list.into())
+        Ok(list)
     }
 
     pub fn get_frame_at_index(&self, index: i32) -> PyTimsFrameVectorized {
@@ -236,16 +440,63 @@ impl PyTimsSliceVectorized {
             intensities_np.to_object(py)))
     }
 
-    pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> PyTimsSliceVectorized {
-        PyTimsSliceVectorized { inner: self.inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max, num_threads) }
+    #[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+    pub fn filter_ranged(&self, py: Python, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> PyTimsSliceVectorized {
+        let inner = &self.inner;
+        PyTimsSliceVectorized { inner: py.allow_threads(|| inner.filter_ranged(mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max, num_threads)) }
     }
 
     pub fn get_arrays_at_index(&self, index: u32) -> (Vec<u32>, Vec<u32>, Vec<f32>) {
         self.inner.get_vectors_at_index(index).unwrap()
     }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsSliceVectorized(frames={}, peaks={})", self.inner.frames.len(), self.inner.frames.iter().map(|frame| frame.ims_frame.values.len()).sum::<usize>())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.frames.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsSliceVectorized { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsSliceVectorized>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
+    }
 }
 
 #[pyclass]
+#[derive(Clone)]
 pub struct PyTimsPlane {
     pub inner: TimsPlane,
 }
@@ -273,28 +524,83 @@ impl PyTimsPlane {
     }
 
     #[getter]
-    pub fn scans(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.scan.clone().into_pyarray_bound(py).unbind()
+    pub fn scans(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.scan, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn mobilities(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.mobility.clone().into_pyarray_bound(py).unbind()
+    pub fn mobilities(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.mobility, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn frame_ids(&self, py: Python) -> Py<PyArray1<i32>> {
-        self.inner.frame_id.clone().into_pyarray_bound(py).unbind()
+    pub fn frame_ids(slf: &Bound<'_, Self>) -> Py<PyArray1<i32>> {
+        shared_array(&slf.borrow().inner.frame_id, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn retention_times(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.retention_time.clone().into_pyarray_bound(py).unbind()
+    pub fn retention_times(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.retention_time, slf.as_any()).unbind()
     }
 
     #[getter]
-    pub fn intensity(&self, py: Python) -> Py<PyArray1<f64>> {
-        self.inner.intensity.clone().into_pyarray_bound(py).unbind()
+    pub fn intensity(slf: &Bound<'_, Self>) -> Py<PyArray1<f64>> {
+        shared_array(&slf.borrow().inner.intensity, slf.as_any()).unbind()
+    }
+
+    /// dense matrix of shape (rt_bins, im_bins) with the retention time and inverse mobility bin centers
+    #[pyo3(signature = (rt_bins=100, im_bins=100))]
+    pub fn to_dense(&self, py: Python, rt_bins: usize, im_bins: usize) -> PyResult<DenseMatrixArrays> {
+        if rt_bins == 0 || im_bins == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err("rt_bins and im_bins must be positive"));
+        }
+        let (matrix, rt_axis, im_axis) = self.inner.to_dense(rt_bins, im_bins);
+        let matrix_np = matrix.into_pyarray_bound(py).reshape([rt_bins, im_bins])?.unbind();
+        Ok((matrix_np, rt_axis.into_pyarray_bound(py).unbind(), im_axis.into_pyarray_bound(py).unbind()))
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("TimsPlane(mz_mean={:.4}, tof_mean={:.1}, points={})", self.inner.mz_mean, self.inner.tof_mean, self.inner.intensity.len())
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.intensity.len()
+    }
+
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.inner.approx_eq(&other.inner, 0.0)
+    }
+
+    pub fn __ne__(&self, other: &Self) -> bool {
+        !self.__eq__(other)
+    }
+
+    #[pyo3(signature = (other, tolerance=1e-6))]
+    pub fn approx_equal(&self, other: &Self, tolerance: f64) -> bool {
+        self.inner.approx_eq(&other.inner, tolerance)
+    }
+
+    pub fn to_bytes(&self, py: Python) -> Py<PyBytes> {
+        PyBytes::new_bound(py, &self.inner.to_bytes()).unbind()
+    }
+
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let inner = BinarySerializable::from_bytes(bytes).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(PyTimsPlane { inner })
+    }
+
+    pub fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<PyTimsPlane>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.to_bytes(py),)))
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> Self {
+        self.clone()
     }
 }
 
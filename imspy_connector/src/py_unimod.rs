@@ -5,7 +5,7 @@ use mscore::chemistry::unimod::{modification_atomic_composition, unimod_modifica
 #[pyfunction]
 pub fn get_unimod_masses() -> HashMap<String, f64> {
     let map = unimod_modifications_mass();
-    map.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    map.iter().map(|(k, v)| (k.to_string(), *v)).collect()
 }
 
 #[pyfunction]
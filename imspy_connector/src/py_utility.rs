@@ -1,11 +1,16 @@
+#![allow(clippy::useless_conversion, reason = "the pyo3 0.22 #[pymethods] expansion converts the PyErr of every PyResult into itself")]
+
 use std::collections::HashMap;
 use pyo3::prelude::*;
+use numpy::{IntoPyArray, PyArray1, PyArrayMethods};
+use mscore::algorithm::utility::{BoundsError, ElutionProfile, PeakShape, RtDistortion};
 
 #[pyfunction]
 pub fn emg_cdf(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
     mscore::algorithm::utility::emg_function(x, mu, sigma, lambda)
 }
 
+/// probability mass of an EMG in `[lower_limit, upper_limit]` in closed form, `n_steps` is ignored
 #[pyfunction]
 #[pyo3(signature = (lower_limit, upper_limit, mu, sigma, lambda, n_steps=None))]
 pub fn accumulated_cdf_emg(lower_limit: f64, upper_limit: f64, mu: f64, sigma: f64, lambda: f64, n_steps: Option<usize>) -> f64 {
@@ -13,9 +18,11 @@ pub fn accumulated_cdf_emg(lower_limit: f64, upper_limit: f64, mu: f64, sigma: f
 }
 
 #[pyfunction]
-#[pyo3(signature = (mu, sigma, lambda, step_size, target, lower_start, upper_start, n_steps=None))]
-pub fn calculate_bounds_emg(mu: f64, sigma: f64, lambda: f64, step_size: f64, target: f64, lower_start: f64, upper_start: f64, n_steps: Option<usize>) -> (f64, f64) {
-    mscore::algorithm::utility::calculate_bounds_emg(mu, sigma, lambda, step_size, target, lower_start, upper_start, n_steps)
+#[pyo3(signature = (mu, sigma, lambda, step_size, target, lower_start, upper_start, n_steps=None, max_widening=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_bounds_emg(mu: f64, sigma: f64, lambda: f64, step_size: f64, target: f64, lower_start: f64, upper_start: f64, n_steps: Option<usize>, max_widening: Option<f64>) -> PyResult<(f64, f64)> {
+    mscore::algorithm::utility::calculate_bounds_emg(mu, sigma, lambda, step_size, target, lower_start, upper_start, max_widening, n_steps)
+        .map_err(bounds_error)
 }
 
 #[pyfunction]
@@ -28,6 +35,19 @@ pub fn accumulated_cdf_normal(sample_start: f64, sample_end: f64, mean: f64, std
     mscore::algorithm::utility::accumulated_intensity_cdf_normal(sample_start, sample_end, mean, std_dev)
 }
 
+/// same as `accumulated_cdf_emg`, under the name of the rust function, `n_steps` is ignored
+#[pyfunction]
+#[pyo3(signature = (lower_limit, upper_limit, mu, sigma, lambda, n_steps=None))]
+pub fn emg_cdf_range(lower_limit: f64, upper_limit: f64, mu: f64, sigma: f64, lambda: f64, n_steps: Option<usize>) -> f64 {
+    mscore::algorithm::utility::emg_cdf_range(lower_limit, upper_limit, mu, sigma, lambda, n_steps)
+}
+
+/// same as `accumulated_cdf_normal`, under the name of the rust function
+#[pyfunction]
+pub fn accumulated_intensity_cdf_normal(sample_start: f64, sample_end: f64, mean: f64, std_dev: f64) -> f64 {
+    mscore::algorithm::utility::accumulated_intensity_cdf_normal(sample_start, sample_end, mean, std_dev)
+}
+
 #[pyfunction]
 pub fn calculate_bounds_normal(mean: f64, std: f64, z_score: f64) -> (f64, f64) {
     mscore::algorithm::utility::calculate_bounds_normal(mean, std, z_score)
@@ -35,28 +55,182 @@ pub fn calculate_bounds_normal(mean: f64, std: f64, z_score: f64) -> (f64, f64)
 
 #[pyfunction]
 #[pyo3(signature = (retention_times, rt, sigma, lambda_, target_p, step_size, n_steps=None))]
-pub fn calculate_frame_occurrence_emg(retention_times: Vec<f64>, rt: f64, sigma: f64, lambda_: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Vec<i32> {
+pub fn calculate_frame_occurrence_emg(retention_times: Vec<f64>, rt: f64, sigma: f64, lambda_: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> PyResult<Vec<i32>> {
     mscore::algorithm::utility::calculate_frame_occurrence_emg(&retention_times, rt, sigma, lambda_, target_p, step_size, n_steps)
+        .map_err(bounds_error)
 }
 
 #[pyfunction]
 #[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rt, sigma, lambda_, rt_cycle_length, n_steps=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
 pub fn calculate_frame_abundance_emg(frame_ids: Vec<i32>, retention_times: Vec<f64>, frame_occurrences: Vec<i32>, rt: f64, sigma: f64, lambda_: f64, rt_cycle_length: f64, n_steps: Option<usize>) -> Vec<f64> {
     let time_map: HashMap<i32, f64> = frame_ids.iter().zip(retention_times.iter()).map(|(id, rt)| (*id, *rt)).collect();
     mscore::algorithm::utility::calculate_frame_abundance_emg(&time_map, &frame_occurrences, rt, sigma, lambda_, rt_cycle_length, n_steps)
 }
 
 #[pyfunction]
-#[pyo3(signature = (retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads, n_steps=None))]
-pub fn calculate_frame_occurrences_emg_par(retention_times: Vec<f64>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, target_p: f64, step_size: f64, num_threads: usize, n_steps: Option<usize>) -> Vec<Vec<i32>> {
-    mscore::algorithm::utility::calculate_frame_occurrences_emg_par(&retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads, n_steps)
+#[pyo3(signature = (retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_occurrences_emg_par(py: Python, retention_times: Vec<f64>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, target_p: f64, step_size: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Vec<i32>>> {
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
+    py.allow_threads(|| mscore::algorithm::utility::calculate_frame_occurrences_emg_par(&retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads, n_steps, rt_distortion.as_ref()))
+        .map_err(bounds_error)
+}
+
+#[pyfunction]
+#[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_abundances_emg_par(py: Python, frame_ids: Vec<i32>, retention_times: Vec<f64>, frame_occurrences: Vec<Vec<i32>>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, rt_cycle_length: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Vec<f64>>> {
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
+    let time_map: HashMap<i32, f64> = frame_ids.iter().zip(retention_times.iter()).map(|(id, rt)| (*id, *rt)).collect();
+    Ok(py.allow_threads(|| mscore::algorithm::utility::calculate_frame_abundances_emg_par(&time_map, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads, n_steps, rt_distortion.as_ref())))
+}
+
+#[pyfunction]
+#[pyo3(signature = (retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads=None, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_occurrences_emg(py: Python, retention_times: &Bound<'_, PyArray1<f64>>, rts: &Bound<'_, PyArray1<f64>>, sigmas: &Bound<'_, PyArray1<f64>>, lambdas: &Bound<'_, PyArray1<f64>>, target_p: f64, step_size: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Py<PyArray1<i32>>>> {
+    let (rts, sigmas, lambdas) = (rts.to_vec()?, sigmas.to_vec()?, lambdas.to_vec()?);
+    check_peak_lengths(rts.len(), sigmas.len(), lambdas.len())?;
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
+    let retention_times = retention_times.to_vec()?;
+    let occurrences = py.allow_threads(|| mscore::algorithm::utility::calculate_frame_occurrences_emg_par(&retention_times, rts, sigmas, lambdas, target_p, step_size, num_threads, n_steps, rt_distortion.as_ref()))
+        .map_err(bounds_error)?;
+    Ok(occurrences.into_iter().map(|occurrence| occurrence.into_pyarray_bound(py).unbind()).collect())
+}
+
+#[pyfunction]
+#[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads=None, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_abundances_emg(py: Python, frame_ids: &Bound<'_, PyArray1<i32>>, retention_times: &Bound<'_, PyArray1<f64>>, frame_occurrences: Vec<Bound<'_, PyArray1<i32>>>, rts: &Bound<'_, PyArray1<f64>>, sigmas: &Bound<'_, PyArray1<f64>>, lambdas: &Bound<'_, PyArray1<f64>>, rt_cycle_length: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Py<PyArray1<f64>>>> {
+    let (rts, sigmas, lambdas) = (rts.to_vec()?, sigmas.to_vec()?, lambdas.to_vec()?);
+    check_peak_lengths(rts.len(), sigmas.len(), lambdas.len())?;
+    if frame_occurrences.len() != rts.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected one frame occurrence array per peak, got {} for {} peaks", frame_occurrences.len(), rts.len())));
+    }
+    let (frame_ids, retention_times) = (frame_ids.to_vec()?, retention_times.to_vec()?);
+    if frame_ids.len() != retention_times.len() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected one retention time per frame, got {} for {} frames", retention_times.len(), frame_ids.len())));
+    }
+
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
+    let time_map: HashMap<i32, f64> = frame_ids.into_iter().zip(retention_times).collect();
+    let frame_occurrences = frame_occurrences.iter().map(|occurrence| occurrence.to_vec()).collect::<Result<Vec<_>, _>>()?;
+    let abundances = py.allow_threads(|| mscore::algorithm::utility::calculate_frame_abundances_emg_par(&time_map, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads, n_steps, rt_distortion.as_ref()));
+    Ok(abundances.into_iter().map(|abundance| abundance.into_pyarray_bound(py).unbind()).collect())
+}
+
+fn check_peak_lengths(rts: usize, sigmas: usize, lambdas: usize) -> PyResult<()> {
+    if rts != sigmas || rts != lambdas {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "expected arrays of equal length, got {} (rts), {} (sigmas) and {} (lambdas)", rts, sigmas, lambdas)));
+    }
+    Ok(())
+}
+
+fn bounds_error(error: BoundsError) -> PyErr {
+    pyo3::exceptions::PyValueError::new_err(error.to_string())
+}
+
+/// parse the optional rt jitter and gradient warp, None if both are off
+fn parse_rt_distortion(rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Option<RtDistortion>> {
+    if rt_jitter_std.unwrap_or(0.0) == 0.0 && warp_from.is_none() && warp_to.is_none() {
+        return Ok(None);
+    }
+    RtDistortion::new(rt_jitter_std, warp_from.unwrap_or_default(), warp_to.unwrap_or_default(), seed)
+        .map(Some)
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct PyPeakShape {
+    pub inner: PeakShape,
+}
+
+fn check_width(name: &str, value: f64) -> PyResult<()> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(pyo3::exceptions::PyValueError::new_err(format!("{} must be positive, got {}", name, value)))
+    }
+}
+
+#[pymethods]
+impl PyPeakShape {
+    #[staticmethod]
+    pub fn emg(sigma: f64, lambda_: f64) -> PyResult<Self> {
+        check_width("sigma", sigma)?;
+        check_width("lambda_", lambda_)?;
+        Ok(PyPeakShape { inner: PeakShape::Emg { sigma, lambda: lambda_ } })
+    }
+
+    #[staticmethod]
+    pub fn gaussian(sigma: f64) -> PyResult<Self> {
+        check_width("sigma", sigma)?;
+        Ok(PyPeakShape { inner: PeakShape::Gaussian { sigma } })
+    }
+
+    #[staticmethod]
+    pub fn bi_gaussian(sigma_left: f64, sigma_right: f64) -> PyResult<Self> {
+        check_width("sigma_left", sigma_left)?;
+        check_width("sigma_right", sigma_right)?;
+        Ok(PyPeakShape { inner: PeakShape::BiGaussian { sigma_left, sigma_right } })
+    }
+
+    #[pyo3(signature = (lower_limit, upper_limit, mu, n_steps=None))]
+    pub fn cdf_range(&self, lower_limit: f64, upper_limit: f64, mu: f64, n_steps: Option<usize>) -> f64 {
+        self.inner.cdf_range(lower_limit, upper_limit, mu, n_steps)
+    }
+
+    #[pyo3(signature = (mu, target_p, step_size, n_steps=None))]
+    pub fn bounds(&self, mu: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> PyResult<(f64, f64)> {
+        self.inner.bounds(mu, target_p, step_size, n_steps).map_err(bounds_error)
+    }
+
+    pub fn __repr__(&self) -> String {
+        match self.inner {
+            PeakShape::Emg { sigma, lambda } => format!("PeakShape.emg(sigma={}, lambda_={})", sigma, lambda),
+            PeakShape::Gaussian { sigma } => format!("PeakShape.gaussian(sigma={})", sigma),
+            PeakShape::BiGaussian { sigma_left, sigma_right } => format!("PeakShape.bi_gaussian(sigma_left={}, sigma_right={})", sigma_left, sigma_right),
+        }
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (retention_times, rt, shape, target_p, step_size, n_steps=None))]
+pub fn calculate_frame_occurrence(retention_times: Vec<f64>, rt: f64, shape: PyPeakShape, target_p: f64, step_size: f64, n_steps: Option<usize>) -> PyResult<Vec<i32>> {
+    mscore::algorithm::utility::calculate_frame_occurrence(&retention_times, rt, &shape.inner, target_p, step_size, n_steps)
+        .map_err(bounds_error)
+}
+
+#[pyfunction]
+#[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rt, shape, rt_cycle_length, n_steps=None))]
+pub fn calculate_frame_abundance(frame_ids: Vec<i32>, retention_times: Vec<f64>, frame_occurrences: Vec<i32>, rt: f64, shape: PyPeakShape, rt_cycle_length: f64, n_steps: Option<usize>) -> Vec<f64> {
+    let time_map: HashMap<i32, f64> = frame_ids.iter().zip(retention_times.iter()).map(|(id, rt)| (*id, *rt)).collect();
+    mscore::algorithm::utility::calculate_frame_abundance(&time_map, &frame_occurrences, rt, &shape.inner, rt_cycle_length, n_steps)
+}
+
+#[pyfunction]
+#[pyo3(signature = (retention_times, rts, shapes, target_p, step_size, num_threads, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_occurrences_par(py: Python, retention_times: Vec<f64>, rts: Vec<f64>, shapes: Vec<PyPeakShape>, target_p: f64, step_size: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Vec<i32>>> {
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
+    let shapes = shapes.into_iter().map(|shape| shape.inner).collect();
+    py.allow_threads(|| mscore::algorithm::utility::calculate_frame_occurrences_par(&retention_times, rts, shapes, target_p, step_size, num_threads, n_steps, rt_distortion.as_ref()))
+        .map_err(bounds_error)
 }
 
 #[pyfunction]
-#[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads, n_steps=None))]
-pub fn calculate_frame_abundances_emg_par(frame_ids: Vec<i32>, retention_times: Vec<f64>, frame_occurrences: Vec<Vec<i32>>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, rt_cycle_length: f64, num_threads: usize, n_steps: Option<usize>) -> Vec<Vec<f64>> {
+#[pyo3(signature = (frame_ids, retention_times, frame_occurrences, rts, shapes, rt_cycle_length, num_threads, n_steps=None, rt_jitter_std=None, warp_from=None, warp_to=None, seed=None))]
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_frame_abundances_par(py: Python, frame_ids: Vec<i32>, retention_times: Vec<f64>, frame_occurrences: Vec<Vec<i32>>, rts: Vec<f64>, shapes: Vec<PyPeakShape>, rt_cycle_length: f64, num_threads: Option<usize>, n_steps: Option<usize>, rt_jitter_std: Option<f64>, warp_from: Option<Vec<f64>>, warp_to: Option<Vec<f64>>, seed: Option<u64>) -> PyResult<Vec<Vec<f64>>> {
+    let rt_distortion = parse_rt_distortion(rt_jitter_std, warp_from, warp_to, seed)?;
     let time_map: HashMap<i32, f64> = frame_ids.iter().zip(retention_times.iter()).map(|(id, rt)| (*id, *rt)).collect();
-    mscore::algorithm::utility::calculate_frame_abundances_emg_par(&time_map, frame_occurrences, rts, sigmas, lambdas, rt_cycle_length, num_threads, n_steps)
+    let shapes = shapes.into_iter().map(|shape| shape.inner).collect();
+    Ok(py.allow_threads(|| mscore::algorithm::utility::calculate_frame_abundances_par(&time_map, frame_occurrences, rts, shapes, rt_cycle_length, num_threads, n_steps, rt_distortion.as_ref())))
 }
 
 #[pyfunction]
@@ -71,14 +245,16 @@ pub fn calculate_abundance_gaussian(indices: Vec<i32>, times: Vec<f64>, occurren
 }
 
 #[pyfunction]
-pub fn calculate_scan_occurrences_gaussian_par(times: Vec<f64>, means: Vec<f64>, sigmas: Vec<f64>, target_p: f64, step_size: f64, n_lower_start: f64, n_upper_start: f64, num_threads: usize) -> Vec<Vec<i32>> {
-    mscore::algorithm::utility::calculate_scan_occurrences_gaussian_par(&times, means, sigmas, target_p, step_size, n_lower_start, n_upper_start, num_threads)
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_scan_occurrences_gaussian_par(py: Python, times: Vec<f64>, means: Vec<f64>, sigmas: Vec<f64>, target_p: f64, step_size: f64, n_lower_start: f64, n_upper_start: f64, num_threads: usize) -> Vec<Vec<i32>> {
+    py.allow_threads(|| mscore::algorithm::utility::calculate_scan_occurrences_gaussian_par(&times, means, sigmas, target_p, step_size, n_lower_start, n_upper_start, num_threads))
 }
 
 #[pyfunction]
-pub fn calculate_scan_abundances_gaussian_par(indices: Vec<i32>, times: Vec<f64>, occurrences: Vec<Vec<i32>>, means: Vec<f64>, sigmas: Vec<f64>, cycle_length: f64, num_threads: usize) -> Vec<Vec<f64>> {
+#[allow(clippy::too_many_arguments, reason = "mirrors the keyword arguments of the Python API")]
+pub fn calculate_scan_abundances_gaussian_par(py: Python, indices: Vec<i32>, times: Vec<f64>, occurrences: Vec<Vec<i32>>, means: Vec<f64>, sigmas: Vec<f64>, cycle_length: f64, num_threads: usize) -> Vec<Vec<f64>> {
     let time_map: HashMap<i32, f64> = indices.iter().zip(times.iter()).map(|(id, t)| (*id, *t)).collect();
-    mscore::algorithm::utility::calculate_scan_abundances_gaussian_par(&time_map, occurrences, means, sigmas, cycle_length, num_threads)
+    py.allow_threads(|| mscore::algorithm::utility::calculate_scan_abundances_gaussian_par(&time_map, occurrences, means, sigmas, cycle_length, num_threads))
 }
 
 #[pymodule]
@@ -93,6 +269,15 @@ pub fn py_utility(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(calculate_frame_abundance_emg, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_frame_occurrences_emg_par, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_frame_abundances_emg_par, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_frame_occurrences_emg, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_frame_abundances_emg, m)?)?;
+    m.add_function(wrap_pyfunction!(emg_cdf_range, m)?)?;
+    m.add_function(wrap_pyfunction!(accumulated_intensity_cdf_normal, m)?)?;
+    m.add_class::<PyPeakShape>()?;
+    m.add_function(wrap_pyfunction!(calculate_frame_occurrence, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_frame_abundance, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_frame_occurrences_par, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_frame_abundances_par, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_scan_occurrence_gaussian, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_abundance_gaussian, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_scan_abundances_gaussian_par, m)?)?;
@@ -0,0 +1,119 @@
+//! Frame occurrences of EMG elution peaks, closed form CDF against the former trapezoidal integration
+//!
+//! `closed_form` runs `calculate_frame_occurrences_emg_par`, `trapezoidal` the same bounds search and frame
+//! lookup with the 1000-step integration the EMG CDF used before. Peptides elute over a one hour gradient
+//! sampled every 100 ms, with peak shapes around the timsim defaults.
+//!
+//! Run with `cargo bench --bench emg_frame_occurrences`, the number of peptides can be set with
+//! `EMG_BENCH_PEPTIDES`, e.g. 100000 for the size of a full simulation.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mscore::algorithm::utility::{calculate_frame_occurrences_emg_par, erfc};
+use rayon::prelude::*;
+
+const STEP_SIZE: f64 = 0.001;
+const TARGET_P: f64 = 0.999;
+
+fn emg(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
+    let part1 = lambda / 2.0 * (-lambda * (x - mu) + lambda * lambda * sigma * sigma / 2.0).exp();
+    let part2 = erfc((mu + lambda * sigma * sigma - x) / (sigma * 2.0_f64.sqrt()));
+    part1 * part2
+}
+
+fn integrate<F: Fn(f64) -> f64>(f: F, a: f64, b: f64, n: usize) -> f64 {
+    let dx = (b - a) / n as f64;
+    (0..n).map(|i| f(a + i as f64 * dx)).sum::<f64>() * dx
+}
+
+/// bounds search of `calculate_bounds_emg` with every CDF evaluated by 1000-step integration
+fn bounds_trapezoidal(mu: f64, sigma: f64, lambda: f64) -> (f64, f64) {
+    let lower_initial = mu - 20.0 * sigma - 2.0;
+    let upper_initial = mu + 60.0 * sigma;
+    let steps = ((upper_initial - lower_initial) / STEP_SIZE).round() as usize;
+    let search_space = |i: usize| lower_initial + i as f64 * STEP_SIZE;
+    let calc_cdf = |low: usize, high: usize| integrate(|x| emg(x, mu, sigma, lambda), search_space(low), search_space(high), 1000);
+
+    let (mut low, mut high) = (0, steps);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if calc_cdf(0, mid) < TARGET_P {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    let upper_cutoff_index = low;
+
+    low = 0;
+    high = upper_cutoff_index;
+    while low < high {
+        let mid = high - (high - low) / 2;
+        if calc_cdf(mid, upper_cutoff_index) < TARGET_P {
+            high = mid - 1;
+        } else {
+            low = mid;
+        }
+    }
+    (search_space(high), search_space(upper_cutoff_index))
+}
+
+/// frame closest to `rt`, 1-based as in `calculate_frame_occurrence`
+fn closest_frame(retention_times: &[f64], rt: f64) -> usize {
+    retention_times.iter()
+        .enumerate()
+        .min_by(|(_, &a), (_, &b)| (a - rt).abs().partial_cmp(&(b - rt).abs()).unwrap())
+        .map(|(idx, _)| idx + 1)
+        .unwrap_or(0)
+}
+
+fn occurrences_trapezoidal(retention_times: &[f64], rts: &[f64], sigmas: &[f64], lambdas: &[f64]) -> Vec<Vec<i32>> {
+    rts.par_iter().zip(sigmas).zip(lambdas)
+        .map(|((&rt, &sigma), &lambda)| {
+            let (rt_min, rt_max) = bounds_trapezoidal(rt, sigma, lambda);
+            (closest_frame(retention_times, rt_min)..=closest_frame(retention_times, rt_max)).map(|x| x as i32).collect()
+        })
+        .collect()
+}
+
+/// apex, sigma and lambda of every peptide from a linear congruential generator
+fn peaks(num_peptides: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let mut state = 42u64;
+    let mut uniform = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let mut peaks = (Vec::with_capacity(num_peptides), Vec::with_capacity(num_peptides), Vec::with_capacity(num_peptides));
+    for _ in 0..num_peptides {
+        peaks.0.push(60.0 + 3480.0 * uniform());
+        peaks.1.push(1.0 + uniform());
+        peaks.2.push(0.05 + 0.45 * uniform());
+    }
+    peaks
+}
+
+fn bench_emg_frame_occurrences(c: &mut Criterion) {
+    let num_peptides = std::env::var("EMG_BENCH_PEPTIDES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1000);
+    let retention_times: Vec<f64> = (0..36_000).map(|i| i as f64 * 0.1).collect();
+    let (rts, sigmas, lambdas) = peaks(num_peptides);
+
+    let mut group = c.benchmark_group("emg_frame_occurrences");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(num_peptides as u64));
+
+    group.bench_function("closed_form", |b| {
+        b.iter(|| {
+            calculate_frame_occurrences_emg_par(&retention_times, rts.clone(), sigmas.clone(), lambdas.clone(), TARGET_P, STEP_SIZE, None, None, None).unwrap()
+        })
+    });
+    group.bench_function("trapezoidal", |b| {
+        b.iter(|| occurrences_trapezoidal(&retention_times, &rts, &sigmas, &lambdas))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_emg_frame_occurrences);
+criterion_main!(benches);
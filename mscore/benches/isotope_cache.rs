@@ -0,0 +1,73 @@
+//! Isotope distributions of ions with many duplicate atomic compositions
+//!
+//! Every peptide appears with charges 1 to 4 and as a permutation of its residues, so only one in eight ions
+//! has a new composition. `uncached` disables the shared `IsotopeDistributionCache`, `cached` starts every
+//! iteration from an empty cache. Distributions are calculated in parallel, as by the simulation.
+//!
+//! Run with `cargo bench --bench isotope_cache`, the number of peptides can be set with `ISOTOPE_BENCH_PEPTIDES`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use mscore::algorithm::isotope::IsotopeDistributionCache;
+use mscore::data::peptide::PeptideIon;
+use rayon::prelude::*;
+
+const AMINO_ACIDS: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+fn ions(num_peptides: usize) -> Vec<PeptideIon> {
+    let mut ions = Vec::with_capacity(num_peptides * 8);
+    for peptide in 0..num_peptides {
+        // residues from a linear congruential generator seeded with the peptide
+        let mut state = peptide as u64;
+        let sequence: String = (0..8 + peptide % 12)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                AMINO_ACIDS[(state >> 33) as usize % AMINO_ACIDS.len()] as char
+            })
+            .chain(std::iter::once('K'))
+            .collect();
+        let permuted: String = sequence.chars().rev().collect();
+        for sequence in [sequence, permuted] {
+            for charge in 1..=4 {
+                ions.push(PeptideIon::new(sequence.clone(), charge, 1.0, None));
+            }
+        }
+    }
+    ions
+}
+
+fn bench_isotope_cache(c: &mut Criterion) {
+    let num_peptides = std::env::var("ISOTOPE_BENCH_PEPTIDES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(500);
+    let ions = ions(num_peptides);
+    let cache = IsotopeDistributionCache::shared();
+
+    let mut group = c.benchmark_group("isotope_cache");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(ions.len() as u64));
+
+    for (name, max_size) in [("uncached", 0), ("cached", IsotopeDistributionCache::DEFAULT_MAX_SIZE)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    cache.set_max_size(max_size);
+                    cache.clear();
+                },
+                |_| {
+                    ions.par_iter()
+                        .map(|ion| ion.calculate_isotope_distribution(1e-3, 1e-8, 200, 1e-6))
+                        .collect::<Vec<_>>()
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+
+    group.finish();
+    let stats = cache.stats();
+    println!("cached: {} hits, {} misses, {} compositions", stats.hits, stats.misses, stats.entries);
+}
+
+criterion_group!(benches, bench_isotope_cache);
+criterion_main!(benches);
@@ -0,0 +1,231 @@
+use rayon::prelude::*;
+use crate::parallel;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithm::isotope::weight;
+use crate::chemistry::constants::{C13_C12_MASS_DIFFERENCE, MASS_PROTON};
+use crate::data::spectrum::MzSpectrum;
+
+/// maximum number of isotope peaks collected per envelope
+const MAX_ISOTOPES: usize = 10;
+
+/// A deisotoped, charge deconvoluted peak
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeconvolutedPeak {
+    pub mono_mz: f64,
+    pub monoisotopic_mass: f64,
+    pub charge: i32,
+    pub intensity: f64,
+    pub score: f64,
+    pub num_peaks: usize,
+}
+
+struct EnvelopeCandidate {
+    peaks: Vec<usize>,
+    charge: i32,
+    cosine: f64,
+    intensity: f64,
+}
+
+/// find the index of the peak closest to `target` within `ppm` tolerance, `mz` must be sorted
+fn find_peak(mz: &[f64], target: f64, ppm: f64) -> Option<usize> {
+    let tol = target * ppm * 1e-6;
+    let start = mz.partition_point(|&x| x < target - tol);
+
+    mz[start..].iter()
+        .enumerate()
+        .take_while(|(_, &x)| x <= target + tol)
+        .min_by(|(_, a), (_, b)| (*a - target).abs().total_cmp(&(*b - target).abs()))
+        .map(|(i, _)| start + i)
+}
+
+/// cosine similarity of observed envelope intensities and the averagine isotope distribution
+fn averagine_cosine(intensities: &[f64], mass: f64) -> f64 {
+    let expected = weight(mass, (0..intensities.len() as i32).collect(), true);
+
+    let dot: f64 = intensities.iter().zip(expected.iter()).map(|(a, b)| a * b).sum();
+    let norm_observed: f64 = intensities.iter().map(|a| a * a).sum::<f64>().sqrt();
+    let norm_expected: f64 = expected.iter().map(|b| b * b).sum::<f64>().sqrt();
+
+    if norm_observed == 0.0 || norm_expected == 0.0 {
+        return 0.0;
+    }
+
+    dot / (norm_observed * norm_expected)
+}
+
+/// deisotope and charge deconvolute a spectrum
+///
+/// Arguments:
+///
+/// * `spectrum` - centroided spectrum to deisotope
+/// * `max_charge` - maximum charge state to consider
+/// * `ppm` - m/z tolerance for isotope peak matching in ppm
+/// * `min_cosine_to_averagine` - minimum cosine similarity of an envelope to the averagine distribution
+///
+/// Every peak is tried as monoisotopic peak for every charge from 1 to `max_charge`, isotope peaks are collected
+/// with a spacing of `C13_C12_MASS_DIFFERENCE` / z until the first missing peak, envelopes need at least two peaks.
+/// Envelopes are scored by their cosine similarity to the averagine isotope distribution times their summed intensity
+/// and accepted greedily by score, an envelope is rejected if any of its peaks was already assigned.
+/// Peaks that are not part of an accepted envelope are not reported.
+///
+/// Returns:
+///
+/// * `Vec<DeconvolutedPeak>` - deconvoluted peaks, sorted by monoisotopic mass
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::deisotope::deisotope_spectrum;
+/// use mscore::algorithm::isotope::weight;
+/// use mscore::chemistry::constants::{C13_C12_MASS_DIFFERENCE, MASS_PROTON};
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let mass = 1500.0;
+/// let weights = weight(mass, (0..5).collect(), true);
+/// let mz: Vec<f64> = (0..5).map(|k| (mass + k as f64 * C13_C12_MASS_DIFFERENCE) / 2.0 + MASS_PROTON).collect();
+/// let spectrum = MzSpectrum::new(mz, weights.iter().map(|w| w * 1000.0).collect());
+///
+/// let peaks = deisotope_spectrum(&spectrum, 4, 10.0, 0.9);
+/// assert_eq!(peaks.len(), 1);
+/// assert_eq!(peaks[0].charge, 2);
+/// assert!((peaks[0].monoisotopic_mass - mass).abs() < 1e-6);
+/// ```
+pub fn deisotope_spectrum(spectrum: &MzSpectrum, max_charge: i32, ppm: f64, min_cosine_to_averagine: f64) -> Vec<DeconvolutedPeak> {
+    let mut order: Vec<usize> = (0..spectrum.mz.len()).collect();
+    order.sort_by(|&a, &b| spectrum.mz[a].total_cmp(&spectrum.mz[b]));
+    let mz: Vec<f64> = order.iter().map(|&i| spectrum.mz[i]).collect();
+    let intensity: Vec<f64> = order.iter().map(|&i| spectrum.intensity[i]).collect();
+
+    let mut candidates: Vec<EnvelopeCandidate> = Vec::new();
+
+    for mono in 0..mz.len() {
+        for charge in 1..=max_charge {
+            let spacing = C13_C12_MASS_DIFFERENCE / charge as f64;
+            let mut peaks = vec![mono];
+
+            while peaks.len() < MAX_ISOTOPES {
+                match find_peak(&mz, mz[mono] + peaks.len() as f64 * spacing, ppm) {
+                    Some(index) => peaks.push(index),
+                    None => break,
+                }
+            }
+
+            if peaks.len() < 2 {
+                continue;
+            }
+
+            let envelope: Vec<f64> = peaks.iter().map(|&i| intensity[i]).collect();
+            let mass = (mz[mono] - MASS_PROTON) * charge as f64;
+            let cosine = averagine_cosine(&envelope, mass);
+
+            if cosine >= min_cosine_to_averagine {
+                candidates.push(EnvelopeCandidate { peaks, charge, cosine, intensity: envelope.iter().sum() });
+            }
+        }
+    }
+
+    // best envelopes first, ties are resolved by m/z and charge to stay deterministic
+    candidates.sort_by(|a, b| {
+        (b.cosine * b.intensity).total_cmp(&(a.cosine * a.intensity))
+            .then(a.peaks[0].cmp(&b.peaks[0]))
+            .then(a.charge.cmp(&b.charge))
+    });
+
+    let mut used = vec![false; mz.len()];
+    let mut result: Vec<DeconvolutedPeak> = Vec::new();
+
+    for candidate in candidates {
+        if candidate.peaks.iter().any(|&i| used[i]) {
+            continue;
+        }
+        for &i in candidate.peaks.iter() {
+            used[i] = true;
+        }
+
+        let mono_mz = mz[candidate.peaks[0]];
+        result.push(DeconvolutedPeak {
+            mono_mz,
+            monoisotopic_mass: (mono_mz - MASS_PROTON) * candidate.charge as f64,
+            charge: candidate.charge,
+            intensity: candidate.intensity,
+            score: candidate.cosine,
+            num_peaks: candidate.peaks.len(),
+        });
+    }
+
+    result.sort_by(|a, b| a.monoisotopic_mass.total_cmp(&b.monoisotopic_mass));
+    result
+}
+
+/// deisotope and charge deconvolute a list of spectra using multiple threads
+///
+/// Arguments:
+///
+/// * `spectra` - list of centroided spectra
+/// * `max_charge` - maximum charge state to consider
+/// * `ppm` - m/z tolerance for isotope peak matching in ppm
+/// * `min_cosine_to_averagine` - minimum cosine similarity of an envelope to the averagine distribution
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<Vec<DeconvolutedPeak>>` - deconvoluted peaks per spectrum
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::deisotope::deisotope_spectra;
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let spectrum = MzSpectrum::new(vec![500.0, 500.50168, 501.00335], vec![100.0, 60.0, 20.0]);
+/// let peaks = deisotope_spectra(&vec![spectrum.clone(), spectrum], 3, 10.0, 0.5, 2);
+/// assert_eq!(peaks.len(), 2);
+/// assert_eq!(peaks[0][0].charge, 2);
+/// ```
+pub fn deisotope_spectra(
+    spectra: &[MzSpectrum],
+    max_charge: i32,
+    ppm: f64,
+    min_cosine_to_averagine: f64,
+    num_threads: usize,
+) -> Vec<Vec<DeconvolutedPeak>> {
+    let thread_pool = parallel::pool(num_threads);
+
+    thread_pool.install(|| {
+        spectra.par_iter()
+            .map(|spectrum| deisotope_spectrum(spectrum, max_charge, ppm, min_cosine_to_averagine))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(mass: f64, charge: i32, amp: f64) -> (Vec<f64>, Vec<f64>) {
+        let weights = weight(mass, (0..5).collect(), true);
+        let mz = (0..5).map(|k| (mass + k as f64 * C13_C12_MASS_DIFFERENCE) / charge as f64 + MASS_PROTON).collect();
+        (mz, weights.iter().map(|w| w * amp).collect())
+    }
+
+    #[test]
+    fn test_overlapping_two_and_three_plus() {
+        // interleaved envelopes around m/z 751: a 2+ of mass 1500.0 and a 3+ of mass 2250.6
+        let (mut mz, mut intensity) = envelope(1500.0, 2, 1000.0);
+        let (mz_3, intensity_3) = envelope(2250.6, 3, 800.0);
+        mz.extend(mz_3);
+        intensity.extend(intensity_3);
+
+        let peaks = deisotope_spectrum(&MzSpectrum::new(mz, intensity), 4, 10.0, 0.9);
+
+        assert_eq!(peaks.len(), 2);
+        assert_eq!(peaks[0].charge, 2);
+        assert!((peaks[0].monoisotopic_mass - 1500.0).abs() < 1e-6);
+        assert_eq!(peaks[0].num_peaks, 5);
+        assert_eq!(peaks[1].charge, 3);
+        assert!((peaks[1].monoisotopic_mass - 2250.6).abs() < 1e-6);
+        assert_eq!(peaks[1].num_peaks, 5);
+        assert!((peaks[0].intensity - 1000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::chemistry::utility::PROSIT_SHAPE;
+use crate::data::peptide::{FragmentType, PeptideProductIonSeriesCollection, PeptideSequence};
+
+/// Number of values of a flat Prosit intensity array, y and b ions of charge 1 to 3 for 29 cleavage sites
+pub const PROSIT_FLAT_LENGTH: usize = PROSIT_SHAPE.0 * PROSIT_SHAPE.1 * PROSIT_SHAPE.2;
+
+/// Relative cleavage propensity of a bond N-terminal to proline
+pub const PROLINE_ENHANCEMENT: f64 = 5.0;
+
+/// Relative cleavage propensity of a bond C-terminal to aspartate
+pub const ASPARTATE_ENHANCEMENT: f64 = 3.0;
+
+/// Predicts the fragment ion intensities of a precursor ion
+pub trait FragmentIntensityModel: Send + Sync {
+    /// Predict b and y ion series of a precursor
+    ///
+    /// Arguments:
+    ///
+    /// * `sequence` - peptide sequence of the precursor
+    /// * `charge` - charge of the precursor
+    /// * `collision_energy` - collision energy in eV
+    ///
+    /// Returns:
+    ///
+    /// * `PeptideProductIonSeriesCollection` - one b and y ion series per fragment charge, intensities sum to 1
+    ///
+    fn predict(&self, sequence: &PeptideSequence, charge: i32, collision_energy: f64) -> PeptideProductIonSeriesCollection;
+}
+
+/// Parameter-free mobile proton heuristic, every bond fragments equally often except bonds N-terminal to proline
+/// and C-terminal to aspartate, which are enhanced. The share of y ions grows with the precursor charge and
+/// fragments carry at most one charge less than the precursor. The collision energy is not used.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MobileProtonModel;
+
+impl MobileProtonModel {
+    /// Relative cleavage propensity of every bond, bond `i` lies between token `i` and `i + 1`
+    ///
+    /// Arguments:
+    ///
+    /// * `tokens` - residues of the peptide, modifications grouped with their residue
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::fragmentation::MobileProtonModel;
+    ///
+    /// let tokens: Vec<String> = "ADPK".chars().map(|c| c.to_string()).collect();
+    /// assert_eq!(MobileProtonModel::bond_weights(&tokens), vec![1.0, 15.0, 1.0]);
+    /// ```
+    pub fn bond_weights(tokens: &[String]) -> Vec<f64> {
+        tokens.windows(2).map(|pair| {
+            let mut weight = 1.0;
+            if pair[0].starts_with('D') {
+                weight *= ASPARTATE_ENHANCEMENT;
+            }
+            if pair[1].starts_with('P') {
+                weight *= PROLINE_ENHANCEMENT;
+            }
+            weight
+        }).collect()
+    }
+
+    /// Share of the fragment intensity in y ions, the more charges the precursor carries, the more protons are
+    /// mobile and the more y ions of tryptic peptides keep the charge of the C-terminal basic residue
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::fragmentation::MobileProtonModel;
+    ///
+    /// assert_eq!(MobileProtonModel::y_fraction(1), 0.5);
+    /// assert_eq!(MobileProtonModel::y_fraction(3), 0.75);
+    /// ```
+    pub fn y_fraction(charge: i32) -> f64 {
+        let charge = charge.max(1) as f64;
+        charge / (charge + 1.0)
+    }
+}
+
+impl FragmentIntensityModel for MobileProtonModel {
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::fragmentation::{FragmentIntensityModel, MobileProtonModel};
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let sequence = PeptideSequence::new("PEPTIDEK".to_string(), Some(1));
+    /// let prediction = MobileProtonModel.predict(&sequence, 3, 30.0);
+    ///
+    /// // fragments of a 3+ precursor carry one or two charges
+    /// assert_eq!(prediction.peptide_ions.iter().map(|series| series.charge).collect::<Vec<_>>(), vec![1, 2]);
+    /// let total: f64 = prediction.peptide_ions.iter()
+    ///     .flat_map(|series| series.n_ions.iter().chain(series.c_ions.iter()))
+    ///     .map(|ion| ion.ion.intensity)
+    ///     .sum();
+    /// assert!((total - 1.0).abs() < 1e-9);
+    ///
+    /// // y ions dominate, y6 (PTIDEK) is cleaved N-terminal to proline
+    /// let series = prediction.find_ion_series(1).unwrap();
+    /// assert!(series.c_ions[5].ion.intensity > series.c_ions[4].ion.intensity);
+    /// assert!(series.c_ions[5].ion.intensity > series.n_ions[1].ion.intensity);
+    /// ```
+    fn predict(&self, sequence: &PeptideSequence, charge: i32, _collision_energy: f64) -> PeptideProductIonSeriesCollection {
+        let tokens = sequence.to_tokens(true);
+        let bond_weights = MobileProtonModel::bond_weights(&tokens);
+        let num_bonds = bond_weights.len();
+        let y_fraction = MobileProtonModel::y_fraction(charge);
+
+        // at least one proton stays on the fragment that does not carry the others
+        let max_fragment_charge = (charge - 1).clamp(1, 3);
+        let charge_weights: Vec<f64> = (1..=max_fragment_charge).map(|z| 1.0 / z as f64).collect();
+        let total = bond_weights.iter().sum::<f64>() * charge_weights.iter().sum::<f64>();
+
+        let peptide_ions = (1..=max_fragment_charge).zip(charge_weights.iter()).map(|(z, charge_weight)| {
+            let mut series = sequence.calculate_product_ion_series(z, FragmentType::B);
+            for (i, ion) in series.n_ions.iter_mut().enumerate() {
+                ion.ion.intensity = bond_weights[i] * (1.0 - y_fraction) * charge_weight / total;
+            }
+            // y ion i + 1 is cleaved off at the bond i + 1 positions from the C-terminus
+            for (i, ion) in series.c_ions.iter_mut().enumerate() {
+                ion.ion.intensity = bond_weights[num_bonds - 1 - i] * y_fraction * charge_weight / total;
+            }
+            series
+        }).collect();
+
+        PeptideProductIonSeriesCollection::new(peptide_ions)
+    }
+}
+
+/// Model backed by a function that predicts flat Prosit intensity arrays, e.g. a wrapped external predictor,
+/// the arrays are mapped to ion series with `PeptideSequence::associate_with_predicted_intensities`
+pub struct FlatIntensityModel<F> {
+    predict_flat: F,
+}
+
+impl<F> FlatIntensityModel<F>
+where
+    F: Fn(&PeptideSequence, i32, f64) -> Vec<f64> + Send + Sync,
+{
+    /// Arguments:
+    ///
+    /// * `predict_flat` - called with sequence, precursor charge and collision energy in eV, returns
+    ///   `PROSIT_FLAT_LENGTH` intensities in Prosit order, negative values mark impossible fragments
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::fragmentation::{FlatIntensityModel, FragmentIntensityModel, PROSIT_FLAT_LENGTH};
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let model = FlatIntensityModel::new(|_: &PeptideSequence, _: i32, _: f64| vec![1.0; PROSIT_FLAT_LENGTH]);
+    /// let prediction = model.predict(&PeptideSequence::new("PEPTIDEK".to_string(), Some(1)), 2, 30.0);
+    /// assert_eq!(prediction.peptide_ions.len(), 2);
+    /// assert_eq!(prediction.peptide_ions[0].n_ions.len(), 7);
+    /// ```
+    pub fn new(predict_flat: F) -> Self {
+        FlatIntensityModel { predict_flat }
+    }
+}
+
+impl<F> FragmentIntensityModel for FlatIntensityModel<F>
+where
+    F: Fn(&PeptideSequence, i32, f64) -> Vec<f64> + Send + Sync,
+{
+    fn predict(&self, sequence: &PeptideSequence, charge: i32, collision_energy: f64) -> PeptideProductIonSeriesCollection {
+        let flat_intensities = (self.predict_flat)(sequence, charge, collision_energy);
+        sequence.associate_with_predicted_intensities(charge, FragmentType::B, flat_intensities, true, true, None)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+}
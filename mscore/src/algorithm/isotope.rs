@@ -1,10 +1,12 @@
 extern crate statrs;
 
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use crate::parallel;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 
-use crate::chemistry::constants::{MASS_NEUTRON, MASS_PROTON};
+use crate::chemistry::constants::{C13_C12_MASS_DIFFERENCE, MASS_NEUTRON, MASS_PROTON};
 use crate::chemistry::elements::{atoms_isotopic_weights, isotopic_abundance};
 use crate::data::peptide::PeptideIon;
 use crate::data::spectrum::MzSpectrum;
@@ -101,6 +103,10 @@ pub fn convolve(
 /// assert_eq!(result, vec![(200.0, 0.25), (201.0, 0.5), (202.0, 0.25)]);
 /// ```
 pub fn convolve_pow(dist: &Vec<(f64, f64)>, n: i32) -> Vec<(f64, f64)> {
+    convolve_pow_with_tolerance(dist, n, 1e-6)
+}
+
+fn convolve_pow_with_tolerance(dist: &Vec<(f64, f64)>, n: i32, mass_tolerance: f64) -> Vec<(f64, f64)> {
     if n == 0 {
         return vec![(0.0, 1.0)]; // Return the delta distribution
     }
@@ -112,7 +118,7 @@ pub fn convolve_pow(dist: &Vec<(f64, f64)>, n: i32) -> Vec<(f64, f64)> {
     let mut power = 2;
 
     while power <= n {
-        result = convolve(&result, &result, 1e-6, 1e-12, 200); // Square the result to get the next power of 2
+        result = convolve(&result, &result, mass_tolerance, 1e-12, 200); // Square the result to get the next power of 2
         power *= 2;
     }
 
@@ -120,8 +126,8 @@ pub fn convolve_pow(dist: &Vec<(f64, f64)>, n: i32) -> Vec<(f64, f64)> {
     if power / 2 < n {
         result = convolve(
             &result,
-            &convolve_pow(dist, n - power / 2),
-            1e-6,
+            &convolve_pow_with_tolerance(dist, n - power / 2, mass_tolerance),
+            mass_tolerance,
             1e-12,
             200,
         );
@@ -138,6 +144,9 @@ pub fn convolve_pow(dist: &Vec<(f64, f64)>, n: i32) -> Vec<(f64, f64)> {
 /// * `mass_tolerance` - mass tolerance for combining peaks
 /// * `abundance_threshold` - minimum abundance for a peak to be included in the result
 /// * `max_result` - maximum number of peaks to include in the result
+/// * `fine_structure` - if true, isotopologues are only merged below `fine_structure_resolution` and `mass_tolerance` is ignored,
+///   if false (default behavior) peaks within `mass_tolerance` are combined
+/// * `fine_structure_resolution` - mass difference in Da below which isotopologues are merged in fine structure mode, default 1e-5
 ///
 /// Returns:
 ///
@@ -154,14 +163,32 @@ pub fn convolve_pow(dist: &Vec<(f64, f64)>, n: i32) -> Vec<(f64, f64)> {
 /// atomic_composition.insert("H".to_string(), 9);
 /// atomic_composition.insert("N".to_string(), 1);
 /// atomic_composition.insert("O".to_string(), 1);
-/// let result = generate_isotope_distribution(&atomic_composition, 1e-6, 1e-12, 200);
+/// let result = generate_isotope_distribution(&atomic_composition, 1e-6, 1e-12, 200, false, None);
+///
+/// // 13C and 15N isotopologues are 6.3 mDa apart, a coarse tolerance of 0.01 Da merges them
+/// let coarse = generate_isotope_distribution(&atomic_composition, 1e-2, 1e-12, 200, false, None);
+/// let fine = generate_isotope_distribution(&atomic_composition, 1e-2, 1e-12, 200, true, None);
+/// let count_a1 = |d: &Vec<(f64, f64)>| d.iter().filter(|(m, _)| (m - d[0].0 - 1.0).abs() < 0.1).count();
+/// assert_eq!(count_a1(&coarse), 1);
+/// assert!(count_a1(&fine) > 1);
 /// ```
 pub fn generate_isotope_distribution(
     atomic_composition: &HashMap<String, i32>,
     mass_tolerance: f64,
     abundance_threshold: f64,
     max_result: i32,
+    fine_structure: bool,
+    fine_structure_resolution: Option<f64>,
 ) -> Vec<(f64, f64)> {
+    // in coarse mode, the elemental distributions are combined at a fixed tolerance, as before
+    let (element_tolerance, mass_tolerance) = match fine_structure {
+        true => {
+            let resolution = fine_structure_resolution.unwrap_or(1e-5);
+            (resolution, resolution)
+        }
+        false => (1e-6, mass_tolerance),
+    };
+
     let mut cumulative_distribution: Option<Vec<(f64, f64)>> = None;
     let atoms_isotopic_weights: HashMap<String, Vec<f64>> = atoms_isotopic_weights()
         .iter()
@@ -189,7 +216,7 @@ pub fn generate_isotope_distribution(
             .collect();
 
         let element_power_distribution = if count > 1 {
-            convolve_pow(&element_distribution, count)
+            convolve_pow_with_tolerance(&element_distribution, count, element_tolerance)
         } else {
             element_distribution
         };
@@ -232,13 +259,146 @@ pub fn generate_isotope_distribution(
         .keys()
         .map(|&key| key as f64 / 1_000_000.0)
         .collect();
-    let intensity: Vec<f64> = sort_map.values().map(|&intensity| intensity).collect();
+    let intensity: Vec<f64> = sort_map.values().copied().collect();
     mz.iter()
         .zip(intensity.iter())
         .map(|(&mz, &intensity)| (mz, intensity))
         .collect()
 }
 
+/// atomic composition sorted by element, and the bits of mass tolerance, abundance threshold and max result
+type IsotopeDistributionKey = (Vec<(String, i32)>, u64, u64, i32);
+
+type IsotopeDistributions = HashMap<IsotopeDistributionKey, Arc<Vec<(f64, f64)>>>;
+
+/// Hits, misses and size of an `IsotopeDistributionCache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IsotopeDistributionCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+    pub max_size: usize,
+}
+
+/// Memoized coarse isotope distributions of atomic compositions, shared between threads
+///
+/// Ions with the same composition, e.g. charge states of a peptide or compositionally identical sequences, share
+/// one call of `generate_isotope_distribution`. Once `max_size` compositions are cached, distributions of new
+/// compositions are calculated without being stored, a max size of 0 disables the cache.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use mscore::algorithm::isotope::{generate_isotope_distribution, IsotopeDistributionCache};
+///
+/// let cache = IsotopeDistributionCache::new(10);
+/// let composition = HashMap::from([("C".to_string(), 5), ("H".to_string(), 9), ("N".to_string(), 1), ("O".to_string(), 1)]);
+///
+/// let first = cache.get_or_compute(&composition, 1e-3, 1e-8, 200);
+/// let second = cache.get_or_compute(&composition, 1e-3, 1e-8, 200);
+/// assert_eq!(first, second);
+/// assert_eq!(first.len(), generate_isotope_distribution(&composition, 1e-3, 1e-8, 200, false, None).len());
+///
+/// let stats = cache.stats();
+/// assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+/// ```
+pub struct IsotopeDistributionCache {
+    distributions: RwLock<IsotopeDistributions>,
+    max_size: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl IsotopeDistributionCache {
+    /// default number of compositions kept by the shared cache
+    pub const DEFAULT_MAX_SIZE: usize = 100_000;
+
+    pub fn new(max_size: usize) -> Self {
+        IsotopeDistributionCache {
+            distributions: RwLock::new(HashMap::new()),
+            max_size: AtomicUsize::new(max_size),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The cache used by the isotope distributions of `PeptideIon` and `PeptideProductIon`
+    pub fn shared() -> &'static IsotopeDistributionCache {
+        static SHARED: OnceLock<IsotopeDistributionCache> = OnceLock::new();
+        SHARED.get_or_init(|| IsotopeDistributionCache::new(Self::DEFAULT_MAX_SIZE))
+    }
+
+    /// Coarse isotope distribution of an atomic composition, see `generate_isotope_distribution`
+    ///
+    /// Arguments:
+    ///
+    /// * `atomic_composition` - atomic composition
+    /// * `mass_tolerance` - mass tolerance for combining peaks
+    /// * `abundance_threshold` - minimum abundance for a peak to be included in the result
+    /// * `max_result` - maximum number of peaks to include in the result
+    ///
+    /// Returns:
+    ///
+    /// * `Arc<Vec<(f64, f64)>>` - distribution of masses and abundances
+    pub fn get_or_compute(
+        &self,
+        atomic_composition: &HashMap<String, i32>,
+        mass_tolerance: f64,
+        abundance_threshold: f64,
+        max_result: i32,
+    ) -> Arc<Vec<(f64, f64)>> {
+        let mut composition: Vec<(String, i32)> = atomic_composition.iter()
+            .map(|(element, &count)| (element.clone(), count))
+            .collect();
+        composition.sort_unstable();
+        let key = (composition, mass_tolerance.to_bits(), abundance_threshold.to_bits(), max_result);
+
+        if let Some(distribution) = self.distributions.read().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return distribution.clone();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // calculate without holding the lock, concurrent misses of the same composition give the same distribution
+        let composition: HashMap<String, i32> = key.0.iter().cloned().collect();
+        let distribution = Arc::new(generate_isotope_distribution(
+            &composition, mass_tolerance, abundance_threshold, max_result, false, None,
+        ));
+
+        let mut distributions = self.distributions.write().unwrap();
+        if distributions.len() < self.max_size.load(Ordering::Relaxed) {
+            return distributions.entry(key).or_insert(distribution).clone();
+        }
+        distribution
+    }
+
+    /// Change the number of cached compositions, entries beyond the new max size are dropped
+    pub fn set_max_size(&self, max_size: usize) {
+        self.max_size.store(max_size, Ordering::Relaxed);
+        let mut distributions = self.distributions.write().unwrap();
+        if distributions.len() > max_size {
+            distributions.clear();
+        }
+    }
+
+    /// Drop all cached distributions and reset the statistics
+    pub fn clear(&self) {
+        self.distributions.write().unwrap().clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> IsotopeDistributionCacheStats {
+        IsotopeDistributionCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.distributions.read().unwrap().len(),
+            max_size: self.max_size.load(Ordering::Relaxed),
+        }
+    }
+}
+
 /// calculate the normal probability density function
 ///
 /// Arguments:
@@ -347,7 +507,7 @@ pub fn lam(mass: f64, slope: f64, intercept: f64) -> f64 {
 /// * `Vec<f64>` - isotope pattern
 ///
 pub fn iso(
-    x: &Vec<f64>,
+    x: &[f64],
     mass: f64,
     charge: f64,
     sigma: f64,
@@ -406,6 +566,7 @@ pub fn iso(
 ///
 /// let (mzs, intensities) = generate_isotope_pattern(1500.0, 1510.0, 3000.0, 2.0, 1e4, 10, 1.0, 3);
 /// ```
+#[allow(clippy::too_many_arguments, reason = "bounds, mass, charge and sampling of the pattern are exposed separately in the Python API")]
 pub fn generate_isotope_pattern(
     lower_bound: f64,
     upper_bound: f64,
@@ -440,6 +601,11 @@ pub fn generate_isotope_pattern(
 /// * `resolution` - resolution of the isotope pattern
 /// * `centroid` - whether to centroid the spectrum
 /// * `amp` - amplitude of the isotope pattern
+/// * `atomic_composition` - optional explicit atomic composition, e.g. from `PeptideSequence::atomic_composition`,
+///   used for the isotope abundances instead of averagine scaling, which corrects A+2 for sulfur containing peptides
+/// * `fine_structure` - if an atomic composition is given, keep isotopologues separated instead of collapsing them
+///   into nominal-mass peaks, the value is the width (sigma, in m/z) of every isotopologue peak, `Some(0.0)` returns
+///   them as centroided peaks at their exact m/z, `None` keeps the coarse nominal-mass pattern
 ///
 /// Returns:
 ///
@@ -448,10 +614,29 @@ pub fn generate_isotope_pattern(
 /// # Examples
 ///
 /// ```
+/// use std::collections::HashMap;
 /// use mscore::algorithm::isotope::generate_averagine_spectrum;
-///
-/// let spectrum = generate_averagine_spectrum(3000.0, 2, 1, 10, 3, true, None);
+/// use mscore::chemistry::constants::{C13_C12_MASS_DIFFERENCE, MASS_PROTON};
+///
+/// let spectrum = generate_averagine_spectrum(3000.0, 2, 1, 10, 3, true, None, None, None);
+///
+/// // C40H65N11O11S3, mass 971.39 Da, three sulfur atoms raise A+2 compared to averagine
+/// let composition: HashMap<String, i32> = [("C", 40), ("H", 65), ("N", 11), ("O", 11), ("S", 3)]
+///     .iter().map(|(e, n)| (e.to_string(), *n)).collect();
+/// let explicit = generate_averagine_spectrum(971.39, 1, 1, 5, 3, true, None, Some(&composition), None);
+/// let averagine = generate_averagine_spectrum(971.39, 1, 1, 5, 3, true, None, None, None);
+/// let ratio = |s: &mscore::data::spectrum::MzSpectrum| s.intensity[2] / s.intensity[0];
+/// assert!(ratio(&explicit) > 1.5 * ratio(&averagine));
+///
+/// // with fine structure, the 13C and 15N isotopologues of A+1 are 6.3 mDa apart and both resolved
+/// let fine = generate_averagine_spectrum(971.39, 1, 1, 3, 4, true, None, Some(&composition), Some(0.0));
+/// let has_peak = |mz: f64| fine.mz.iter().any(|&m| (m - mz).abs() < 2e-4);
+/// let mono_mz = 971.39 + MASS_PROTON;
+/// assert!(has_peak(mono_mz));
+/// assert!(has_peak(mono_mz + C13_C12_MASS_DIFFERENCE));
+/// assert!(has_peak(mono_mz + 0.99703489));
 /// ```
+#[allow(clippy::too_many_arguments, reason = "averagine parameters are exposed separately in the Python API")]
 pub fn generate_averagine_spectrum(
     mass: f64,
     charge: i32,
@@ -460,27 +645,44 @@ pub fn generate_averagine_spectrum(
     resolution: i32,
     centroid: bool,
     amp: Option<f64>,
+    atomic_composition: Option<&HashMap<String, i32>>,
+    fine_structure: Option<f64>,
 ) -> MzSpectrum {
     let amp = amp.unwrap_or(1e4);
     let lb = mass / charge as f64 - 0.2;
     let ub = mass / charge as f64 + k as f64 + 0.2;
 
-    let (mz, intensities) = generate_isotope_pattern(
-        lb,
-        ub,
-        mass,
-        charge as f64,
-        amp,
-        k as usize,
-        0.008492569002123142,
-        resolution,
-    );
+    let (mz, intensities) = match atomic_composition {
+        Some(composition) => generate_composition_isotope_pattern(
+            lb,
+            ub,
+            mass,
+            composition,
+            charge as f64,
+            amp,
+            k as usize,
+            fine_structure.unwrap_or(0.008492569002123142),
+            resolution,
+            fine_structure.is_some(),
+        ),
+        None => generate_isotope_pattern(
+            lb,
+            ub,
+            mass,
+            charge as f64,
+            amp,
+            k as usize,
+            0.008492569002123142,
+            resolution,
+        ),
+    };
 
     let spectrum = MzSpectrum::new(mz, intensities)
         .to_resolution(resolution)
         .filter_ranged(lb, ub, min_intensity as f64, 1e9);
 
-    if centroid {
+    // isotopologue sticks are already centroided, centroiding would merge the fine structure again
+    if centroid && fine_structure != Some(0.0) {
         spectrum.to_centroid(
             std::cmp::max(min_intensity, 1),
             1.0 / 10f64.powi(resolution - 1),
@@ -491,6 +693,71 @@ pub fn generate_averagine_spectrum(
     }
 }
 
+/// generate the isotope pattern of an explicit atomic composition, sampled like `generate_isotope_pattern`
+///
+/// Peaks are placed relative to `mass`, the mass the bounds are calculated from, a `sigma` of 0 returns
+/// every peak as a single point instead of sampling a normal distribution
+#[allow(clippy::too_many_arguments, reason = "takes the parameters of generate_isotope_pattern and the composition")]
+fn generate_composition_isotope_pattern(
+    lower_bound: f64,
+    upper_bound: f64,
+    mass: f64,
+    atomic_composition: &HashMap<String, i32>,
+    charge: f64,
+    amp: f64,
+    k: usize,
+    sigma: f64,
+    resolution: i32,
+    fine_structure: bool,
+) -> (Vec<f64>, Vec<f64>) {
+    let distribution = generate_isotope_distribution(atomic_composition, 1e-3, 1e-9, 200, fine_structure, None);
+    let mono_mass = distribution.first().map(|&(mass, _)| mass).unwrap_or(0.0);
+
+    // keep the first k nominal isotopes, in coarse mode collapse them into one peak each at their mean mass
+    let mut nominal_peaks: BTreeMap<i64, Vec<(f64, f64)>> = BTreeMap::new();
+    for &(peak_mass, abundance) in distribution.iter() {
+        let nominal = ((peak_mass - mono_mass) / MASS_NEUTRON).round() as i64;
+        if nominal >= 0 && (nominal as usize) < k {
+            nominal_peaks.entry(nominal).or_default().push((peak_mass, abundance));
+        }
+    }
+
+    let peaks: Vec<(f64, f64)> = match fine_structure {
+        true => nominal_peaks.into_values().flatten().collect(),
+        false => nominal_peaks.into_values().map(|peaks| {
+            let abundance: f64 = peaks.iter().map(|&(_, a)| a).sum();
+            let peak_mass = peaks.iter().map(|&(m, a)| m * a).sum::<f64>() / abundance;
+            (peak_mass, abundance)
+        }).collect(),
+    };
+
+    let total: f64 = peaks.iter().map(|&(_, a)| a).sum();
+    let peak_mzs: Vec<f64> = peaks.iter().map(|&(peak_mass, _)| (mass + peak_mass - mono_mass) / charge).collect();
+
+    if sigma == 0.0 {
+        return peaks
+            .iter()
+            .zip(peak_mzs.iter())
+            .map(|(&(_, abundance), &mz)| (mz + MASS_PROTON, abundance / total * amp))
+            .unzip();
+    }
+
+    let step_size = f64::min(sigma / 10.0, 1.0 / 10f64.powi(resolution));
+    let size = ((upper_bound - lower_bound) / step_size).ceil() as usize;
+    let mzs: Vec<f64> = (0..size)
+        .map(|i| lower_bound + step_size * i as f64)
+        .collect();
+
+    let intensities: Vec<f64> = mzs.iter().map(|&x| {
+        peaks.iter().zip(peak_mzs.iter()).map(|(&(_, abundance), &mz)| abundance / total * normal_pdf(x, mz, sigma)).sum::<f64>() * step_size * amp
+    }).collect();
+
+    (
+        mzs.iter().map(|&mz| mz + MASS_PROTON).collect(),
+        intensities,
+    )
+}
+
 /// generate the averagine spectra for a given list of masses and charges
 /// using multiple threads
 ///
@@ -502,7 +769,7 @@ pub fn generate_averagine_spectrum(
 /// * `k` - number of isotopes to consider
 /// * `resolution` - resolution of the isotope pattern
 /// * `centroid` - whether to centroid the spectrum
-/// * `num_threads` - number of threads to use
+/// * `num_threads` - number of threads to use, `None` runs on the shared pool (see `crate::parallel`)
 /// * `amp` - amplitude of the isotope pattern
 ///
 /// Returns:
@@ -518,6 +785,7 @@ pub fn generate_averagine_spectrum(
 /// let charges = vec![2, 3];
 /// let spectra = generate_averagine_spectra(masses, charges, 1, 10, 3, true, 4, None);
 /// ```
+#[allow(clippy::too_many_arguments, reason = "averagine parameters are exposed separately in the Python API")]
 pub fn generate_averagine_spectra(
     masses: Vec<f64>,
     charges: Vec<i32>,
@@ -525,15 +793,12 @@ pub fn generate_averagine_spectra(
     k: i32,
     resolution: i32,
     centroid: bool,
-    num_threads: usize,
+    num_threads: impl Into<Option<usize>>,
     amp: Option<f64>,
 ) -> Vec<MzSpectrum> {
     let amp = amp.unwrap_or(1e5);
     let mut spectra: Vec<MzSpectrum> = Vec::new();
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let thread_pool = parallel::pool(num_threads);
 
     thread_pool.install(|| {
         spectra = masses
@@ -548,6 +813,8 @@ pub fn generate_averagine_spectra(
                     resolution,
                     centroid,
                     Some(amp),
+                    None,
+                    None,
                 )
             })
             .collect();
@@ -556,6 +823,97 @@ pub fn generate_averagine_spectra(
     spectra
 }
 
+/// generate the averagine spectrum of a precursor observed at a given m/z and charge
+///
+/// Arguments:
+///
+/// * `mz` - observed monoisotopic m/z of the precursor
+/// * `charge` - charge of the precursor
+/// * `min_intensity` - minimum intensity for a peak to be included in the result
+/// * `relative_intensity` - if true, `min_intensity` is a fraction of the base peak (e.g. 0.01 for 1%),
+///   otherwise an absolute intensity on the integer scale of `generate_averagine_spectrum`
+/// * `k` - number of isotopes to consider
+/// * `resolution` - resolution of the isotope pattern
+/// * `centroid` - whether to centroid the spectrum
+/// * `amp` - amplitude of the isotope pattern
+///
+/// Returns:
+///
+/// * `(f64, MzSpectrum)` - neutral monoisotopic mass and the averagine spectrum on the m/z axis of the charge
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::isotope::generate_averagine_spectrum_from_mz;
+///
+/// let (mass, spectrum) = generate_averagine_spectrum_from_mz(751.0, 2, 0.05, true, 10, 3, true, None);
+/// assert!((mass - 1499.985447).abs() < 1e-5);
+/// assert!((spectrum.mz[0] - 751.0).abs() < 0.01);
+/// let base = spectrum.intensity.iter().cloned().fold(0.0, f64::max);
+/// assert!(spectrum.intensity.iter().all(|&i| i >= 0.05 * base));
+/// ```
+#[allow(clippy::too_many_arguments, reason = "averagine parameters are exposed separately in the Python API")]
+pub fn generate_averagine_spectrum_from_mz(
+    mz: f64,
+    charge: i32,
+    min_intensity: f64,
+    relative_intensity: bool,
+    k: i32,
+    resolution: i32,
+    centroid: bool,
+    amp: Option<f64>,
+) -> (f64, MzSpectrum) {
+    let mass = (mz - MASS_PROTON) * charge as f64;
+    let absolute_min_intensity = if relative_intensity { 0 } else { min_intensity as i32 };
+    let spectrum = generate_averagine_spectrum(mass, charge, absolute_min_intensity, k, resolution, centroid, amp, None, None);
+
+    if !relative_intensity {
+        return (mass, spectrum);
+    }
+
+    let base_peak = spectrum.intensity.iter().cloned().fold(0.0, f64::max);
+    (mass, spectrum.filter_ranged(0.0, f64::MAX, min_intensity * base_peak, f64::MAX))
+}
+
+/// parallel version of `generate_averagine_spectrum_from_mz`
+///
+/// Arguments:
+///
+/// * `mzs` - list of observed monoisotopic m/z values
+/// * `charges` - list of charges of the precursors
+/// * `min_intensity` - minimum intensity for a peak to be included in the result
+/// * `relative_intensity` - if true, `min_intensity` is a fraction of the base peak of every spectrum
+/// * `k` - number of isotopes to consider
+/// * `resolution` - resolution of the isotope pattern
+/// * `centroid` - whether to centroid the spectrum
+/// * `num_threads` - number of threads to use, `None` runs on the shared pool (see `crate::parallel`)
+/// * `amp` - amplitude of the isotope pattern
+///
+/// Returns:
+///
+/// * `Vec<(f64, MzSpectrum)>` - neutral monoisotopic mass and averagine spectrum of every precursor
+///
+#[allow(clippy::too_many_arguments, reason = "averagine parameters are exposed separately in the Python API")]
+pub fn generate_averagine_spectra_from_mz(
+    mzs: &[f64],
+    charges: &[i32],
+    min_intensity: f64,
+    relative_intensity: bool,
+    k: i32,
+    resolution: i32,
+    centroid: bool,
+    num_threads: impl Into<Option<usize>>,
+    amp: Option<f64>,
+) -> Vec<(f64, MzSpectrum)> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        mzs.par_iter()
+            .zip(charges.par_iter())
+            .map(|(&mz, &charge)| generate_averagine_spectrum_from_mz(mz, charge, min_intensity, relative_intensity, k, resolution, centroid, amp))
+            .collect()
+    })
+}
+
 /// generate the precursor spectrum for a given peptide sequence and charge
 /// using isotope convolutions
 ///
@@ -583,7 +941,7 @@ pub fn generate_precursor_spectrum(
 ///
 /// * `sequences` - list of peptide sequences
 /// * `charges` - list of charges of the peptides
-/// * `num_threads` - number of threads to use
+/// * `num_threads` - number of threads to use, `None` runs on the shared pool (see `crate::parallel`)
 ///
 /// Returns:
 ///
@@ -592,13 +950,10 @@ pub fn generate_precursor_spectrum(
 pub fn generate_precursor_spectra(
     sequences: &Vec<&str>,
     charges: &Vec<i32>,
-    num_threads: usize,
+    num_threads: impl Into<Option<usize>>,
     peptide_ids: Vec<Option<i32>>,
 ) -> Vec<MzSpectrum> {
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let thread_pool = parallel::pool(num_threads);
     // need to zip sequences and charges and peptide_ids
     let result = thread_pool.install(|| {
         sequences
@@ -616,8 +971,8 @@ pub fn generate_precursor_spectra(
 // Calculates the isotope distribution for a fragment given the isotope distribution of the fragment, the isotope distribution of the complementary fragment, and the transmitted precursor isotopes
 // implemented based on OpenMS: "https://github.com/OpenMS/OpenMS/blob/079143800f7ed036a7c68ea6e124fe4f5cfc9569/src/openms/source/CHEMISTRY/ISOTOPEDISTRIBUTION/CoarseIsotopePatternGenerator.cpp#L415"
 pub fn calculate_transmission_dependent_fragment_ion_isotope_distribution(
-    fragment_isotope_dist: &Vec<(f64, f64)>,
-    comp_fragment_isotope_dist: &Vec<(f64, f64)>,
+    fragment_isotope_dist: &[(f64, f64)],
+    comp_fragment_isotope_dist: &[(f64, f64)],
     precursor_isotopes: &HashSet<usize>,
     max_isotope: usize,
 ) -> Vec<(f64, f64)> {
@@ -647,3 +1002,232 @@ pub fn calculate_transmission_dependent_fragment_ion_isotope_distribution(
 
     result
 }
+
+/// Result of fitting an observed isotope envelope against the averagine model
+#[derive(Clone, Debug)]
+pub struct IsotopeFitScore {
+    /// observed over expected relative intensity per isotope, 0 for missing isotopes
+    pub ratios: Vec<f64>,
+    /// sum of squared deviations of relative intensities, divided by the expected relative intensity
+    pub chi_square: f64,
+    pub num_matched: usize,
+    /// chi square of the envelope starting one isotope below `mono_mz`
+    pub shifted_chi_square: f64,
+    pub shifted_num_matched: usize,
+    /// true if the envelope starting one isotope below `mono_mz` is matched and fits better
+    pub is_mispick: bool,
+}
+
+/// intensity of the most intense peak within `ppm` of `target`, `mz` must be sorted
+fn most_intense_peak(mz: &[f64], intensity: &[f64], target: f64, ppm: f64) -> Option<f64> {
+    let tol = target * ppm * 1e-6;
+    let start = mz.partition_point(|&x| x < target - tol);
+    mz[start..]
+        .iter()
+        .zip(intensity[start..].iter())
+        .take_while(|(&x, _)| x <= target + tol)
+        .map(|(_, &i)| i)
+        .max_by(|a, b| a.total_cmp(b))
+}
+
+/// fit the envelope starting at `mono_mz`, returns ratios, chi square, number of matched isotopes
+/// and whether the first isotope was matched
+fn fit_envelope(
+    mz: &[f64],
+    intensity: &[f64],
+    mono_mz: f64,
+    charge: i32,
+    n_isotopes: usize,
+    ppm: f64,
+) -> (Vec<f64>, f64, usize, bool) {
+    let spacing = C13_C12_MASS_DIFFERENCE / charge as f64;
+    let mass = (mono_mz - MASS_PROTON) * charge as f64;
+    let expected = weight(mass, (0..n_isotopes as i32).collect(), true);
+
+    let observed: Vec<Option<f64>> = (0..n_isotopes)
+        .map(|k| most_intense_peak(mz, intensity, mono_mz + k as f64 * spacing, ppm))
+        .collect();
+    let num_matched = observed.iter().filter(|o| o.is_some()).count();
+    let total: f64 = observed.iter().map(|o| o.unwrap_or(0.0)).sum();
+
+    // without any observed intensity every isotope is missing, which gives a chi square of 1
+    let relative: Vec<f64> = observed
+        .iter()
+        .map(|o| match total > 0.0 {
+            true => o.unwrap_or(0.0) / total,
+            false => 0.0,
+        })
+        .collect();
+
+    let ratios = relative.iter().zip(expected.iter()).map(|(o, e)| o / e).collect();
+    let chi_square = relative
+        .iter()
+        .zip(expected.iter())
+        .map(|(o, e)| (o - e).powi(2) / e)
+        .sum();
+
+    (ratios, chi_square, num_matched, observed[0].is_some())
+}
+
+/// score an observed isotope envelope against the averagine isotope distribution
+///
+/// Arguments:
+///
+/// * `observed` - observed spectrum
+/// * `mono_mz` - m/z of the monoisotopic peak of the candidate
+/// * `charge` - charge of the candidate
+/// * `n_isotopes` - number of isotopes to score, including the monoisotopic peak
+/// * `ppm` - m/z tolerance for isotope peak matching in ppm
+///
+/// Observed and expected intensities are both normalized to a sum of 1 over the scored isotopes.
+/// Missing isotopes count as zero intensity, so missing higher isotopes with low expected intensity only raise the
+/// chi square slightly. To detect a monoisotopic peak that was picked one isotope too high, the envelope starting
+/// one isotope below `mono_mz` is scored as well.
+///
+/// Returns:
+///
+/// * `IsotopeFitScore` - ratios, chi square and number of matched isotopes of the envelope and the shifted envelope
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::isotope::{score_isotope_fit, weight};
+/// use mscore::chemistry::constants::{C13_C12_MASS_DIFFERENCE, MASS_PROTON};
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let mono_mz = 1500.0 / 2.0 + MASS_PROTON;
+/// let mz: Vec<f64> = (0..5).map(|k| mono_mz + k as f64 * C13_C12_MASS_DIFFERENCE / 2.0).collect();
+/// let spectrum = MzSpectrum::new(mz, weight(1500.0, (0..5).collect(), true));
+///
+/// let score = score_isotope_fit(&spectrum, mono_mz, 2, 5, 10.0);
+/// assert_eq!(score.num_matched, 5);
+/// assert!(score.chi_square < 1e-9);
+/// assert!(!score.is_mispick);
+///
+/// // missing higher isotopes degrade the score, but keep it finite
+/// let truncated = score_isotope_fit(&spectrum, mono_mz, 2, 8, 10.0);
+/// assert_eq!(truncated.num_matched, 5);
+/// assert!(truncated.chi_square > score.chi_square && truncated.chi_square.is_finite());
+///
+/// // picking the second isotope as monoisotopic peak is detected
+/// let mispick = score_isotope_fit(&spectrum, mono_mz + C13_C12_MASS_DIFFERENCE / 2.0, 2, 4, 10.0);
+/// assert!(mispick.is_mispick);
+/// assert!(mispick.shifted_chi_square < 1e-9);
+/// ```
+pub fn score_isotope_fit(
+    observed: &MzSpectrum,
+    mono_mz: f64,
+    charge: i32,
+    n_isotopes: usize,
+    ppm: f64,
+) -> IsotopeFitScore {
+    let mut order: Vec<usize> = (0..observed.mz.len()).collect();
+    order.sort_by(|&a, &b| observed.mz[a].total_cmp(&observed.mz[b]));
+    let mz: Vec<f64> = order.iter().map(|&i| observed.mz[i]).collect();
+    let intensity: Vec<f64> = order.iter().map(|&i| observed.intensity[i]).collect();
+
+    score_isotope_fit_sorted(&mz, &intensity, mono_mz, charge, n_isotopes, ppm)
+}
+
+fn score_isotope_fit_sorted(
+    mz: &[f64],
+    intensity: &[f64],
+    mono_mz: f64,
+    charge: i32,
+    n_isotopes: usize,
+    ppm: f64,
+) -> IsotopeFitScore {
+    assert!(charge > 0, "Charge state must be positive");
+    assert!(n_isotopes > 0, "At least one isotope must be scored");
+
+    let (ratios, chi_square, num_matched, _) =
+        fit_envelope(mz, intensity, mono_mz, charge, n_isotopes, ppm);
+    let (_, shifted_chi_square, shifted_num_matched, shifted_first_matched) = fit_envelope(
+        mz,
+        intensity,
+        mono_mz - C13_C12_MASS_DIFFERENCE / charge as f64,
+        charge,
+        n_isotopes,
+        ppm,
+    );
+
+    IsotopeFitScore {
+        ratios,
+        chi_square,
+        num_matched,
+        shifted_chi_square,
+        shifted_num_matched,
+        is_mispick: shifted_first_matched && shifted_chi_square < chi_square,
+    }
+}
+
+/// score a list of candidate envelopes against the same observed spectrum using multiple threads
+///
+/// Arguments:
+///
+/// * `observed` - observed spectrum
+/// * `mono_mzs` - m/z of the monoisotopic peak per candidate
+/// * `charges` - charge per candidate
+/// * `n_isotopes` - number of isotopes to score, including the monoisotopic peak
+/// * `ppm` - m/z tolerance for isotope peak matching in ppm
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<IsotopeFitScore>` - one score per candidate, see `score_isotope_fit`
+/// * an error if `mono_mzs` and `charges` differ in length, a charge is not positive or `n_isotopes` is 0
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::isotope::score_isotope_fits;
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let spectrum = MzSpectrum::new(vec![500.0, 500.50168, 501.00335], vec![100.0, 60.0, 20.0]);
+/// let scores = score_isotope_fits(&spectrum, &[500.0, 500.0], &[2, 1], 3, 10.0, 2).unwrap();
+/// assert_eq!(scores[0].num_matched, 3);
+/// assert_eq!(scores[1].num_matched, 2);
+///
+/// assert!(score_isotope_fits(&spectrum, &[500.0], &[2, 1], 3, 10.0, 2).is_err());
+/// assert!(score_isotope_fits(&spectrum, &[500.0], &[0], 3, 10.0, 2).is_err());
+/// assert!(score_isotope_fits(&spectrum, &[500.0], &[2], 0, 10.0, 2).is_err());
+/// ```
+pub fn score_isotope_fits(
+    observed: &MzSpectrum,
+    mono_mzs: &[f64],
+    charges: &[i32],
+    n_isotopes: usize,
+    ppm: f64,
+    num_threads: usize,
+) -> Result<Vec<IsotopeFitScore>, String> {
+    if mono_mzs.len() != charges.len() {
+        return Err(format!(
+            "mono_mzs and charges must have the same length, got {} and {}",
+            mono_mzs.len(),
+            charges.len()
+        ));
+    }
+    if let Some(charge) = charges.iter().find(|&&charge| charge <= 0) {
+        return Err(format!("charges must be positive, got {}", charge));
+    }
+    if n_isotopes == 0 {
+        return Err("at least one isotope must be scored".to_string());
+    }
+
+    let mut order: Vec<usize> = (0..observed.mz.len()).collect();
+    order.sort_by(|&a, &b| observed.mz[a].total_cmp(&observed.mz[b]));
+    let mz: Vec<f64> = order.iter().map(|&i| observed.mz[i]).collect();
+    let intensity: Vec<f64> = order.iter().map(|&i| observed.intensity[i]).collect();
+
+    let thread_pool = parallel::pool(num_threads);
+
+    Ok(thread_pool.install(|| {
+        mono_mzs
+            .par_iter()
+            .zip(charges.par_iter())
+            .map(|(&mono_mz, &charge)| {
+                score_isotope_fit_sorted(&mz, &intensity, mono_mz, charge, n_isotopes, ppm)
+            })
+            .collect()
+    }))
+}
@@ -0,0 +1,132 @@
+use rayon::prelude::*;
+use crate::parallel;
+
+use crate::chemistry::formulas::{calculate_mz, ccs_to_one_over_reduced_mobility};
+use crate::chemistry::constants::STANDARD_TEMPERATURE;
+use crate::chemistry::mobility::{DRIFT_GAS_TEMPERATURE, MASS_N2};
+
+/// Power-law model of the collision cross-section of peptide ions, ccs = a(charge) * mass^b(charge),
+/// derived from the Mason-Schamp relation for ions whose cross-section grows with their volume
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobilityModel {
+    /// (a, b) for charge 1, 2, ..., charges above the last entry use the last entry
+    pub coefficients: Vec<(f64, f64)>,
+    /// mass of the drift gas
+    pub mass_gas: f64,
+    /// temperature of the drift gas in C°
+    pub temp: f64,
+    /// factor to translate from C° to K
+    pub t_diff: f64,
+}
+
+impl Default for MobilityModel {
+    /// rough fit to the charge-wise CCS trend lines of tryptic peptides measured on timsTOF instruments in N2,
+    /// see Meier et al., Nat. Commun. 12, 1185 (2021)
+    fn default() -> Self {
+        MobilityModel {
+            coefficients: vec![(7.6, 0.55), (5.55, 0.6), (3.85, 0.65), (2.81, 0.7)],
+            mass_gas: MASS_N2,
+            temp: DRIFT_GAS_TEMPERATURE,
+            t_diff: STANDARD_TEMPERATURE,
+        }
+    }
+}
+
+impl MobilityModel {
+    /// Create a new MobilityModel with the given coefficients and the default drift gas
+    ///
+    /// Arguments:
+    ///
+    /// * `coefficients` - (a, b) per charge state starting at charge 1, a and b have to be positive
+    ///
+    /// Returns:
+    ///
+    /// * `Result<MobilityModel, String>` - the model or a description of the invalid coefficients
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::mobility::MobilityModel;
+    ///
+    /// assert!(MobilityModel::new(vec![(7.0, 0.5), (5.0, 0.6)]).is_ok());
+    /// assert!(MobilityModel::new(vec![]).is_err());
+    /// assert!(MobilityModel::new(vec![(7.0, -0.5)]).is_err());
+    /// ```
+    pub fn new(coefficients: Vec<(f64, f64)>) -> Result<Self, String> {
+        if coefficients.is_empty() {
+            return Err("at least the coefficients of charge 1 are required".to_string());
+        }
+        if let Some((charge, _)) = coefficients.iter().enumerate().find(|(_, (a, b))| !(*a > 0.0 && *b > 0.0)) {
+            return Err(format!("coefficients of charge {} have to be positive", charge + 1));
+        }
+        Ok(MobilityModel { coefficients, ..MobilityModel::default() })
+    }
+
+    /// collision cross-section in Å² of an ion with the given monoisotopic mass and charge
+    pub fn ccs(&self, mass: f64, charge: i32) -> f64 {
+        let index = (charge.max(1) as usize - 1).min(self.coefficients.len() - 1);
+        let (a, b) = self.coefficients[index];
+        a * mass.powf(b)
+    }
+}
+
+/// predict the inverse reduced ion mobility (1/k0) of a peptide ion from its mass and charge
+///
+/// Arguments:
+///
+/// * `mass` - monoisotopic mass of the uncharged peptide
+/// * `charge` - charge state of the ion
+/// * `coefficients` - mobility model to use
+///
+/// Returns:
+///
+/// * `one_over_k0` - inverse reduced ion mobility in Vs/cm²
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::mobility::{predict_inverse_mobility, MobilityModel};
+///
+/// let model = MobilityModel::default();
+/// let im_2 = predict_inverse_mobility(1000.0, 2, &model);
+/// let im_3 = predict_inverse_mobility(1000.0, 3, &model);
+/// assert!(im_2 > 0.8 && im_2 < 0.95);
+/// // a higher charge moves the ion faster through the gas
+/// assert!(im_3 < im_2);
+/// ```
+pub fn predict_inverse_mobility(mass: f64, charge: i32, coefficients: &MobilityModel) -> f64 {
+    let mz = calculate_mz(mass, charge.max(1));
+    let ccs = coefficients.ccs(mass, charge);
+    ccs_to_one_over_reduced_mobility(ccs, mz, charge.max(1) as u32, coefficients.mass_gas, coefficients.temp, coefficients.t_diff)
+}
+
+/// predict the inverse reduced ion mobility (1/k0) of many peptide ions in parallel
+///
+/// Arguments:
+///
+/// * `masses` - monoisotopic masses of the uncharged peptides
+/// * `charges` - charge states of the ions
+/// * `coefficients` - mobility model to use
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<f64>` - inverse reduced ion mobility of every ion
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::mobility::{predict_inverse_mobilities_par, predict_inverse_mobility, MobilityModel};
+///
+/// let model = MobilityModel::default();
+/// let mobilities = predict_inverse_mobilities_par(&[1000.0, 2000.0], &[2, 3], &model, 2);
+/// assert_eq!(mobilities[1], predict_inverse_mobility(2000.0, 3, &model));
+/// ```
+pub fn predict_inverse_mobilities_par(masses: &[f64], charges: &[i32], coefficients: &MobilityModel, num_threads: usize) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        masses.par_iter().zip(charges.par_iter())
+            .map(|(&mass, &charge)| predict_inverse_mobility(mass, charge, coefficients))
+            .collect()
+    })
+}
@@ -1,3 +1,11 @@
+pub mod deisotope;
+pub mod fragmentation;
 pub mod isotope;
+pub mod mobility;
+pub mod noise;
+pub mod normalization;
+pub mod peak_shape;
 pub mod peptide;
+pub mod similarity;
+pub mod smoothing;
 pub mod utility;
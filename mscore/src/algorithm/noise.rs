@@ -0,0 +1,99 @@
+/// Fewest intensities a local noise estimate is based on, scans or windows with fewer peaks fall back to
+/// the noise level of the whole frame or spectrum
+pub const MIN_NOISE_PEAKS: usize = 10;
+
+/// How `estimate_noise` derives a noise level from intensities
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoiseEstimator {
+    /// median absolute deviation of the intensities from their median
+    MedianAbsoluteDeviation,
+    /// mean of the lowest quartile of the intensities
+    LowestQuartileMean,
+}
+
+impl NoiseEstimator {
+    /// Parse an estimator by name
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - `mad` or `lowest_quartile`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::noise::NoiseEstimator;
+    ///
+    /// assert_eq!(NoiseEstimator::from_name("MAD"), Ok(NoiseEstimator::MedianAbsoluteDeviation));
+    /// assert!(NoiseEstimator::from_name("rms").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "mad" | "median_absolute_deviation" => Ok(NoiseEstimator::MedianAbsoluteDeviation),
+            "lowest_quartile" | "lowest_quartile_mean" => Ok(NoiseEstimator::LowestQuartileMean),
+            _ => Err(format!("unknown noise estimator {}, expected mad or lowest_quartile", name)),
+        }
+    }
+}
+
+/// Median of sorted values
+fn sorted_median(sorted: &[f64]) -> f64 {
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+/// Estimate the noise level of intensities
+///
+/// Arguments:
+///
+/// * `values` - intensities
+/// * `method` - noise estimator
+///
+/// Returns:
+///
+/// * `f64` - noise level, 0 without values
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::noise::{estimate_noise, NoiseEstimator};
+///
+/// let values = [1.0, 2.0, 3.0, 4.0, 100.0];
+/// assert_eq!(estimate_noise(&values, NoiseEstimator::MedianAbsoluteDeviation), 1.0);
+/// assert_eq!(estimate_noise(&values, NoiseEstimator::LowestQuartileMean), 1.5);
+/// assert_eq!(estimate_noise(&[], NoiseEstimator::LowestQuartileMean), 0.0);
+/// ```
+pub fn estimate_noise(values: &[f64], method: NoiseEstimator) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    match method {
+        NoiseEstimator::MedianAbsoluteDeviation => {
+            let median = sorted_median(&sorted);
+            let mut deviations: Vec<f64> = sorted.iter().map(|v| (v - median).abs()).collect();
+            deviations.sort_by(|a, b| a.total_cmp(b));
+            sorted_median(&deviations)
+        }
+        NoiseEstimator::LowestQuartileMean => {
+            let count = sorted.len().div_ceil(4);
+            sorted[..count].iter().sum::<f64>() / count as f64
+        }
+    }
+}
+
+/// Ratio of an intensity to a noise level, infinite for positive intensities without noise
+pub fn signal_to_noise(intensity: f64, noise: f64) -> f64 {
+    if noise > 0.0 {
+        intensity / noise
+    } else if intensity > 0.0 {
+        f64::INFINITY
+    } else {
+        0.0
+    }
+}
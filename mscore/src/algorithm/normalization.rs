@@ -0,0 +1,91 @@
+/// How `normalize` scales intensities
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalizationMethod {
+    /// summed intensity becomes `target`
+    TotalIonCurrent { target: f64 },
+    /// maximum intensity becomes `target`
+    BasePeak { target: f64 },
+    /// summed intensity of every scan becomes `target`, scans are normalized separately
+    PerScanTotalIonCurrent { target: f64 },
+    /// median of the nonzero intensities becomes `target`, robust against a few dominating peaks
+    Quantile { target: f64 },
+}
+
+impl NormalizationMethod {
+    /// Parse a method by name
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - `tic`, `base_peak`, `per_scan_tic` or `quantile`
+    /// * `target` - value the summed, maximum or median intensity is scaled to
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::normalization::NormalizationMethod;
+    ///
+    /// assert_eq!(NormalizationMethod::from_name("base_peak", 1.0), Ok(NormalizationMethod::BasePeak { target: 1.0 }));
+    /// assert!(NormalizationMethod::from_name("z_score", 1.0).is_err());
+    /// ```
+    pub fn from_name(name: &str, target: f64) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "tic" | "total_ion_current" => Ok(NormalizationMethod::TotalIonCurrent { target }),
+            "base_peak" => Ok(NormalizationMethod::BasePeak { target }),
+            "per_scan_tic" => Ok(NormalizationMethod::PerScanTotalIonCurrent { target }),
+            "quantile" => Ok(NormalizationMethod::Quantile { target }),
+            _ => Err(format!("unknown normalization method {}, expected tic, base_peak, per_scan_tic or quantile", name)),
+        }
+    }
+}
+
+/// Median of the nonzero values, None if there are none
+fn nonzero_median(values: &[f64]) -> Option<f64> {
+    let mut nonzero: Vec<f64> = values.iter().copied().filter(|v| *v != 0.0).collect();
+    if nonzero.is_empty() {
+        return None;
+    }
+    nonzero.sort_by(|a, b| a.total_cmp(b));
+    let middle = nonzero.len() / 2;
+    if nonzero.len() % 2 == 0 {
+        Some((nonzero[middle - 1] + nonzero[middle]) / 2.0)
+    } else {
+        Some(nonzero[middle])
+    }
+}
+
+/// Factor the values are multiplied with, None if the reference intensity is zero and the values are kept as they are
+fn scale_factor(values: &[f64], method: NormalizationMethod) -> Option<f64> {
+    let (reference, target) = match method {
+        NormalizationMethod::TotalIonCurrent { target } | NormalizationMethod::PerScanTotalIonCurrent { target } => (values.iter().sum::<f64>(), target),
+        NormalizationMethod::BasePeak { target } => (values.iter().copied().fold(0.0, f64::max), target),
+        NormalizationMethod::Quantile { target } => (nonzero_median(values)?, target),
+    };
+    (reference != 0.0 && reference.is_finite()).then(|| target / reference)
+}
+
+/// Normalize intensities, all values are scaled by the same factor
+///
+/// Arguments:
+///
+/// * `values` - intensities
+/// * `method` - normalization method, `PerScanTotalIonCurrent` behaves like `TotalIonCurrent` without scans
+///
+/// Returns:
+///
+/// * `Vec<f64>` - scaled intensities, unchanged if the sum, maximum or median is zero
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::normalization::{normalize, NormalizationMethod};
+///
+/// assert_eq!(normalize(&[1.0, 3.0], NormalizationMethod::TotalIonCurrent { target: 100.0 }), vec![25.0, 75.0]);
+/// assert_eq!(normalize(&[0.0, 2.0, 4.0, 8.0], NormalizationMethod::Quantile { target: 1.0 }), vec![0.0, 0.5, 1.0, 2.0]);
+/// assert_eq!(normalize(&[0.0, 0.0], NormalizationMethod::BasePeak { target: 1.0 }), vec![0.0, 0.0]);
+/// ```
+pub fn normalize(values: &[f64], method: NormalizationMethod) -> Vec<f64> {
+    match scale_factor(values, method) {
+        Some(factor) => values.iter().map(|v| v * factor).collect(),
+        None => values.to_vec(),
+    }
+}
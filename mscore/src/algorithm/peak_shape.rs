@@ -0,0 +1,191 @@
+use rayon::prelude::*;
+use crate::parallel;
+
+/// Shape of a peak in a mobilogram, e.g. from `TimsFrame::mobilogram`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImPeakEstimate {
+    /// inverse ion mobility of the apex, refined by parabolic interpolation
+    pub apex_mobility: f64,
+    /// full width at half maximum in inverse ion mobility
+    pub fwhm: f64,
+    /// area under the mobilogram, trapezoidal rule
+    pub area: f64,
+    /// width of the half behind the apex divided by the width of the half before it, 1 for a symmetric peak,
+    /// NaN if the apex lies on the first half maximum crossing
+    pub asymmetry: f64,
+    /// the peak does not fall below half maximum before the edge of the scan range, the width is underestimated
+    pub truncated: bool,
+}
+
+/// Mobility where the intensity crosses `level` between a point below and a point at or above it
+fn crossing(below: (f64, f64), above: (f64, f64), level: f64) -> f64 {
+    let (mobility_below, intensity_below) = below;
+    let (mobility_above, intensity_above) = above;
+    mobility_below + (level - intensity_below) / (intensity_above - intensity_below) * (mobility_above - mobility_below)
+}
+
+/// Estimate apex, width, area and asymmetry of the most intense peak of a mobilogram
+///
+/// The apex of a single maximum is refined by a parabola through it and its neighbours, the apex of a plateau of
+/// equal maxima is its center. Half maximum crossings are interpolated linearly, a peak that does not fall below
+/// half maximum before the first or last point is flagged as truncated and the edge is used as crossing.
+///
+/// Arguments:
+///
+/// * `mobility` - inverse ion mobility of every point, in any order
+/// * `intensity` - intensity of every point, negative values are treated as zero
+///
+/// Returns:
+///
+/// * `Option<ImPeakEstimate>` - None if the inputs differ in length or no intensity is positive
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::peak_shape::estimate_im_peak;
+///
+/// let mobility = vec![0.9, 1.0, 1.1, 1.2, 1.3];
+/// let intensity = vec![0.0, 50.0, 100.0, 50.0, 0.0];
+/// let peak = estimate_im_peak(&mobility, &intensity).unwrap();
+/// assert!((peak.apex_mobility - 1.1).abs() < 1e-12);
+/// assert!((peak.fwhm - 0.2).abs() < 1e-12);
+/// assert!((peak.asymmetry - 1.0).abs() < 1e-9);
+/// assert!(!peak.truncated);
+/// ```
+pub fn estimate_im_peak(mobility: &[f64], intensity: &[f64]) -> Option<ImPeakEstimate> {
+    if mobility.len() != intensity.len() {
+        return None;
+    }
+    let mut points: Vec<(f64, f64)> = mobility.iter().zip(intensity)
+        .filter(|(m, i)| m.is_finite() && !i.is_nan())
+        .map(|(&m, &i)| (m, i.max(0.0)))
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let max_intensity = points.iter().map(|p| p.1).fold(0.0, f64::max);
+    if max_intensity <= 0.0 || !max_intensity.is_finite() {
+        return None;
+    }
+
+    // first and last index of the run of equal maxima, a single point unless the peak has a plateau
+    let first = points.iter().position(|p| p.1 == max_intensity)?;
+    let last = first + points[first..].iter().take_while(|p| p.1 == max_intensity).count() - 1;
+
+    let apex_mobility = if last > first {
+        (points[first].0 + points[last].0) / 2.0
+    } else if first > 0 && first + 1 < points.len() {
+        let (y0, y1, y2) = (points[first - 1].1, points[first].1, points[first + 1].1);
+        let curvature = y0 - 2.0 * y1 + y2;
+        let offset = if curvature != 0.0 { (0.5 * (y0 - y2) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+        let spacing = if offset > 0.0 { points[first + 1].0 - points[first].0 } else { points[first].0 - points[first - 1].0 };
+        points[first].0 + offset * spacing
+    } else {
+        points[first].0
+    };
+
+    let half_maximum = max_intensity / 2.0;
+    let mut truncated = false;
+
+    let mut left = first;
+    while left > 0 && points[left - 1].1 >= half_maximum {
+        left -= 1;
+    }
+    let left_mobility = if left == 0 {
+        truncated = true;
+        points[0].0
+    } else {
+        crossing(points[left - 1], points[left], half_maximum)
+    };
+
+    let mut right = last;
+    while right + 1 < points.len() && points[right + 1].1 >= half_maximum {
+        right += 1;
+    }
+    let right_mobility = if right + 1 == points.len() {
+        truncated = true;
+        points[right].0
+    } else {
+        crossing(points[right + 1], points[right], half_maximum)
+    };
+
+    let leading = apex_mobility - left_mobility;
+    let asymmetry = if leading > 0.0 { (right_mobility - apex_mobility) / leading } else { f64::NAN };
+
+    let area = points.windows(2).map(|w| (w[1].0 - w[0].0) * (w[0].1 + w[1].1) / 2.0).sum();
+
+    Some(ImPeakEstimate {
+        apex_mobility,
+        fwhm: right_mobility - left_mobility,
+        area,
+        asymmetry,
+        truncated,
+    })
+}
+
+/// Estimate the peak shape of many mobilograms in parallel, see `estimate_im_peak`
+///
+/// Arguments:
+///
+/// * `mobilograms` - (mobility, intensity) of every mobilogram
+/// * `num_threads` - number of threads
+///
+/// Returns:
+///
+/// * `Vec<Option<ImPeakEstimate>>` - estimate of every mobilogram, in input order
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::peak_shape::{estimate_im_peak, estimate_im_peaks_par};
+///
+/// let mobilograms = vec![
+///     (vec![0.9, 1.0, 1.1], vec![10.0, 40.0, 20.0]),
+///     (vec![0.9, 1.0], vec![0.0, 0.0]),
+/// ];
+/// let peaks = estimate_im_peaks_par(&mobilograms, 2);
+/// assert_eq!(peaks[0], estimate_im_peak(&mobilograms[0].0, &mobilograms[0].1));
+/// assert_eq!(peaks[1], None);
+/// ```
+pub fn estimate_im_peaks_par(mobilograms: &[(Vec<f64>, Vec<f64>)], num_threads: usize) -> Vec<Option<ImPeakEstimate>> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        mobilograms.par_iter()
+            .map(|(mobility, intensity)| estimate_im_peak(mobility, intensity))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_im_peak() {
+        // gaussian sampled off its apex, the reversed order of scans must not matter
+        let (apex, sigma) = (1.003, 0.02);
+        let mobility: Vec<f64> = (0..60).rev().map(|i| 0.7 + i as f64 * 0.01).collect();
+        let intensity: Vec<f64> = mobility.iter().map(|m| 1e4 * (-(m - apex).powi(2) / (2.0 * sigma * sigma)).exp()).collect();
+        let peak = estimate_im_peak(&mobility, &intensity).unwrap();
+        assert!((peak.apex_mobility - apex).abs() < 1e-3);
+        assert!((peak.fwhm - 2.3548 * sigma).abs() < 2e-3);
+        assert!((peak.area - 1e4 * sigma * (2.0 * std::f64::consts::PI).sqrt()).abs() < 1.0);
+        assert!((peak.asymmetry - 1.0).abs() < 0.1);
+        assert!(!peak.truncated);
+
+        // plateau of equal maxima, the apex is its center
+        let peak = estimate_im_peak(&[1.0, 1.1, 1.2, 1.3, 1.4, 1.5], &[0.0, 10.0, 10.0, 10.0, 0.0, -3.0]).unwrap();
+        assert!((peak.apex_mobility - 1.2).abs() < 1e-12);
+        assert!((peak.fwhm - 0.3).abs() < 1e-12);
+        assert!(!peak.truncated);
+
+        // peak cut at the last scan
+        let peak = estimate_im_peak(&[1.0, 1.1, 1.2, 1.3], &[0.0, 2.0, 6.0, 10.0]).unwrap();
+        assert!(peak.truncated);
+        assert!((peak.apex_mobility - 1.3).abs() < 1e-12);
+        assert!((peak.fwhm - 0.125).abs() < 1e-12);
+        assert!(peak.asymmetry.abs() < 1e-12);
+
+        assert_eq!(estimate_im_peak(&[1.0, 1.1], &[1.0]), None);
+        assert_eq!(estimate_im_peak(&[], &[]), None);
+    }
+}
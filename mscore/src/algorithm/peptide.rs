@@ -1,13 +1,15 @@
+use crate::algorithm::mobility::MobilityModel;
 use crate::chemistry::amino_acid::{amino_acid_composition, amino_acid_masses};
 use crate::chemistry::constants::{MASS_CO, MASS_NH3, MASS_PROTON, MASS_WATER};
-use crate::chemistry::formulas::calculate_mz;
+use crate::chemistry::formulas::{average_mass_of, calculate_mz};
+use crate::chemistry::label::LabelScheme;
 use crate::chemistry::unimod::{
     modification_atomic_composition, unimod_modifications_mass_numerical,
 };
 use crate::chemistry::utility::{find_unimod_patterns, unimod_sequence_to_tokens};
 use crate::data::peptide::{FragmentType, PeptideProductIon, PeptideSequence};
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use crate::parallel;
 use regex::Regex;
 use statrs::distribution::{Binomial, Discrete};
 use std::collections::HashMap;
@@ -17,6 +19,7 @@ use std::collections::HashMap;
 /// Arguments:
 ///
 /// * `sequence` - peptide sequence
+/// * `label` - optional stable isotope label of the peptide
 ///
 /// Returns:
 ///
@@ -26,14 +29,23 @@ use std::collections::HashMap;
 ///
 /// ```
 /// use mscore::algorithm::peptide::calculate_peptide_mono_isotopic_mass;
+/// use mscore::chemistry::label::LabelScheme;
 /// use mscore::data::peptide::PeptideSequence;
 ///
 /// let peptide_sequence = PeptideSequence::new("PEPTIDEH".to_string(), Some(1));
-/// let mass = calculate_peptide_mono_isotopic_mass(&peptide_sequence);
+/// let mass = calculate_peptide_mono_isotopic_mass(&peptide_sequence, None);
 /// let mass_quantized = (mass * 1e6).round() as i32;
 /// assert_eq!(mass_quantized, 936418877);
+///
+/// // Lys8 shifts a tryptic peptide by 8.0142 Da, 15N labeling by 0.9970 Da per nitrogen
+/// let tryptic = PeptideSequence::new("PEPTIDEK".to_string(), None);
+/// let light = calculate_peptide_mono_isotopic_mass(&tryptic, None);
+/// let heavy = calculate_peptide_mono_isotopic_mass(&tryptic, Some(LabelScheme::SilacHeavy));
+/// assert!((heavy - light - 8.0142).abs() < 1e-4);
+/// let n15 = calculate_peptide_mono_isotopic_mass(&tryptic, Some(LabelScheme::FullN15));
+/// assert!((n15 - light - 9.0 * 0.99703).abs() < 1e-4);
 /// ```
-pub fn calculate_peptide_mono_isotopic_mass(peptide_sequence: &PeptideSequence) -> f64 {
+pub fn calculate_peptide_mono_isotopic_mass(peptide_sequence: &PeptideSequence, label: Option<LabelScheme>) -> f64 {
     let amino_acid_masses = amino_acid_masses();
     let modifications_mz_numerical = unimod_modifications_mass_numerical();
     let pattern = Regex::new(r"\[UNIMOD:(\d+)]").unwrap();
@@ -67,7 +79,107 @@ pub fn calculate_peptide_mono_isotopic_mass(peptide_sequence: &PeptideSequence)
         .map(|&mod_id| modifications_mz_numerical.get(&mod_id).unwrap_or(&0.0))
         .sum();
 
-    mass_sequence + mass_modifications + MASS_WATER
+    let mass = mass_sequence + mass_modifications + MASS_WATER;
+
+    match label {
+        Some(label) => {
+            let composition = peptide_sequence_to_atomic_composition(peptide_sequence, None);
+            mass + label.mass_shift(sequence.chars(), true, &composition)
+        }
+        None => mass,
+    }
+}
+
+/// calculate the monoisotopic masses of many peptide sequences in parallel
+///
+/// Arguments:
+///
+/// * `sequences` - peptide sequences
+/// * `label` - optional stable isotope label of all peptides
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<f64>` - monoisotopic mass of each peptide
+///
+pub fn calculate_peptide_mono_isotopic_masses_par(sequences: Vec<&str>, label: Option<LabelScheme>, num_threads: usize) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        sequences.par_iter()
+            .map(|sequence| calculate_peptide_mono_isotopic_mass(&PeptideSequence::new(sequence.to_string(), None), label))
+            .collect()
+    })
+}
+
+/// check that every residue and modification of a sequence has an atomic composition, which average masses are
+/// calculated from
+pub(crate) fn check_atomic_composition(sequence: &str) -> Result<(), String> {
+    let aa_compositions = amino_acid_composition();
+    let mod_compositions = modification_atomic_composition();
+    let missing = unimod_sequence_to_tokens(sequence, false).into_iter().find(|token| match token.len() {
+        1 => !aa_compositions.contains_key(&token.chars().next().unwrap()),
+        _ => !mod_compositions.contains_key(token),
+    });
+    match missing {
+        Some(token) => Err(format!("no atomic composition for {} in {}, its average mass is unknown", token, sequence)),
+        None => Ok(()),
+    }
+}
+
+/// calculate the average mass of a peptide sequence from its atomic composition and the natural isotope abundances
+///
+/// Arguments:
+///
+/// * `peptide_sequence` - peptide sequence, modifications in the format [UNIMOD:ID]
+/// * `label` - optional stable isotope label, heavy atoms contribute their own mass
+///
+/// Returns:
+///
+/// * `Result<f64, String>` - average mass of the peptide, an error if a residue or modification has no atomic
+///   composition, see `modification_atomic_composition`
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::peptide::calculate_peptide_average_mass;
+/// use mscore::data::peptide::PeptideSequence;
+///
+/// // C34H53N7O15
+/// let mass = calculate_peptide_average_mass(&PeptideSequence::new("PEPTIDE".to_string(), None), None).unwrap();
+/// assert!((mass - 799.82).abs() < 0.01);
+///
+/// // carbamidomethylation adds 57.05 Da
+/// let plain = calculate_peptide_average_mass(&PeptideSequence::new("PEPCTIDE".to_string(), None), None).unwrap();
+/// let modified = calculate_peptide_average_mass(&PeptideSequence::new("PEPC[UNIMOD:4]TIDE".to_string(), None), None).unwrap();
+/// assert!((modified - plain - 57.05).abs() < 0.01);
+///
+/// assert!(calculate_peptide_average_mass(&PeptideSequence::new("PEPTIDEK[UNIMOD:737]".to_string(), None), None).is_err());
+/// ```
+pub fn calculate_peptide_average_mass(peptide_sequence: &PeptideSequence, label: Option<LabelScheme>) -> Result<f64, String> {
+    check_atomic_composition(peptide_sequence.sequence.as_str())?;
+    Ok(average_mass_of(&peptide_sequence_to_atomic_composition(peptide_sequence, label)))
+}
+
+/// calculate the average masses of many peptide sequences in parallel, see `calculate_peptide_average_mass`
+///
+/// Arguments:
+///
+/// * `sequences` - peptide sequences
+/// * `label` - optional stable isotope label of all peptides
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Result<Vec<f64>, String>` - average mass of each peptide, the error of the first sequence without atomic
+///   composition
+///
+pub fn calculate_peptide_average_masses_par(sequences: Vec<&str>, label: Option<LabelScheme>, num_threads: usize) -> Result<Vec<f64>, String> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        sequences.par_iter()
+            .map(|sequence| calculate_peptide_average_mass(&PeptideSequence::new(sequence.to_string(), None), label))
+            .collect()
+    })
 }
 
 /// calculate the monoisotopic mass of a peptide product ion for a given fragment type
@@ -110,16 +222,14 @@ pub fn calculate_peptide_product_ion_mono_isotopic_mass(sequence: &str, kind: Fr
     // Calculate total mass
     let mass = mass_sequence + mass_modifications + MASS_WATER;
 
-    let mass = match kind {
+    match kind {
         FragmentType::A => mass - MASS_CO - MASS_WATER,
         FragmentType::B => mass - MASS_WATER,
         FragmentType::C => mass + MASS_NH3 - MASS_WATER,
         FragmentType::X => mass + MASS_CO - 2.0 * MASS_PROTON,
         FragmentType::Y => mass,
         FragmentType::Z => mass - MASS_NH3,
-    };
-
-    mass
+    }
 }
 
 /// calculate the monoisotopic m/z of a peptide product ion for a given fragment type and charge
@@ -181,8 +291,36 @@ pub fn calculate_amino_acid_composition(sequence: &str) -> HashMap<String, i32>
 }
 
 /// calculate the atomic composition of a peptide sequence
+///
+/// Arguments:
+///
+/// * `peptide_sequence` - peptide sequence
+/// * `label` - optional stable isotope label, heavy atoms are counted as `2H`, `13C` and `15N`
+///
+/// Returns:
+///
+/// * `composition` - count of atoms per element
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::peptide::peptide_sequence_to_atomic_composition;
+/// use mscore::chemistry::label::LabelScheme;
+/// use mscore::data::peptide::PeptideSequence;
+///
+/// let peptide_sequence = PeptideSequence::new("PEPTIDEK".to_string(), None);
+/// let light = peptide_sequence_to_atomic_composition(&peptide_sequence, None);
+/// let heavy = peptide_sequence_to_atomic_composition(&peptide_sequence, Some(LabelScheme::SilacHeavy));
+/// assert_eq!(heavy.get("13C"), Some(&6));
+/// assert_eq!(heavy.get("15N"), Some(&2));
+/// assert_eq!(heavy["C"] + 6, light["C"]);
+///
+/// let n15 = peptide_sequence_to_atomic_composition(&peptide_sequence, Some(LabelScheme::FullN15));
+/// assert_eq!((n15.get("N"), n15.get("15N")), (None, Some(&9)));
+/// ```
 pub fn peptide_sequence_to_atomic_composition(
     peptide_sequence: &PeptideSequence,
+    label: Option<LabelScheme>,
 ) -> HashMap<&'static str, i32> {
     let token_sequence = unimod_sequence_to_tokens(peptide_sequence.sequence.as_str(), false);
     let mut collection: HashMap<&'static str, i32> = HashMap::new();
@@ -215,6 +353,11 @@ pub fn peptide_sequence_to_atomic_composition(
     *collection.entry("H").or_insert(0) += 2; //
     *collection.entry("O").or_insert(0) += 1; //
 
+    if let Some(label) = label {
+        let (residues, _) = find_unimod_patterns(peptide_sequence.sequence.as_str());
+        label.apply(residues.chars(), true, &mut collection);
+    }
+
     collection
 }
 
@@ -228,7 +371,7 @@ pub fn peptide_sequence_to_atomic_composition(
 ///
 /// * `Vec<(&str, i32)>` - a vector of tuples representing the atomic composition of the product ion
 pub fn atomic_product_ion_composition(product_ion: &PeptideProductIon) -> Vec<(&str, i32)> {
-    let mut composition = peptide_sequence_to_atomic_composition(&product_ion.ion.sequence);
+    let mut composition = peptide_sequence_to_atomic_composition(&product_ion.ion.sequence, None);
 
     match product_ion.kind {
         FragmentType::A => {
@@ -261,6 +404,11 @@ pub fn atomic_product_ion_composition(product_ion: &PeptideProductIon) -> Vec<(&
         }
     }
 
+    if let Some(label) = product_ion.ion.label {
+        let (residues, _) = find_unimod_patterns(product_ion.ion.sequence.sequence.as_str());
+        label.apply(residues.chars(), product_ion.kind.is_n_terminal(), &mut composition);
+    }
+
     composition.iter().map(|(k, v)| (*k, *v)).collect()
 }
 
@@ -269,6 +417,7 @@ pub fn atomic_product_ion_composition(product_ion: &PeptideProductIon) -> Vec<(&
 ///
 /// * `product_ions` - a vector of PeptideProductIon instances
 /// * `num_threads` - an usize representing the number of threads to use
+///
 /// Returns:
 ///
 /// * `Vec<Vec<(String, i32)>>` - a vector of vectors of tuples representing the atomic composition of each product ion
@@ -277,10 +426,7 @@ pub fn fragments_to_composition(
     product_ions: Vec<PeptideProductIon>,
     num_threads: usize,
 ) -> Vec<Vec<(String, i32)>> {
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let thread_pool = parallel::pool(num_threads);
     let result = thread_pool.install(|| {
         product_ions
             .par_iter()
@@ -356,11 +502,9 @@ pub fn simulate_charge_state_for_sequence(
     let num_protonizable_sites = get_num_protonizable_sites(sequence);
     let mut charge_state_probs = vec![0.0; max_charge];
 
-    for charge in 0..max_charge {
+    for (charge, probability) in charge_state_probs.iter_mut().enumerate() {
         let binom = Binomial::new(charged_prob, num_protonizable_sites as u64).unwrap();
-        let prob = binom.pmf(charge as u64);
-
-        charge_state_probs[charge] = prob;
+        *probability = binom.pmf(charge as u64);
     }
 
     charge_state_probs
@@ -394,10 +538,7 @@ pub fn simulate_charge_states_for_sequences(
     max_charge: Option<usize>,
     charged_probability: Option<f64>,
 ) -> Vec<Vec<f64>> {
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let pool = parallel::pool(num_threads);
     pool.install(|| {
         sequences
             .par_iter()
@@ -407,3 +548,113 @@ pub fn simulate_charge_states_for_sequences(
             .collect()
     })
 }
+
+/// calculate a simple hydrophobicity index of a peptide sequence as the sum of per residue
+/// reversed-phase retention coefficients (Guo et al., 1986), modifications are ignored
+///
+/// Arguments:
+///
+/// * `sequence` - peptide sequence, may contain modifications in the format [UNIMOD:ID]
+///
+/// Returns:
+///
+/// * `index` - hydrophobicity index of the sequence
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::peptide::calculate_peptide_hydrophobicity_index;
+///
+/// let index = calculate_peptide_hydrophobicity_index("PEPTIDE");
+/// assert!((index - 14.4).abs() < 1e-9);
+/// ```
+pub fn calculate_peptide_hydrophobicity_index(sequence: &str) -> f64 {
+    let pattern = Regex::new(r"\[UNIMOD:(\d+)]").unwrap();
+    let sequence = pattern.replace_all(sequence, "");
+
+    sequence
+        .chars()
+        .map(|aa| match aa {
+            'W' => 8.8,
+            'F' => 8.1,
+            'L' => 8.1,
+            'I' => 7.4,
+            'M' => 5.5,
+            'V' => 5.0,
+            'Y' => 4.5,
+            'C' | 'U' => 2.6,
+            'P' => 2.0,
+            'A' => 2.0,
+            'E' => 1.1,
+            'T' => 0.6,
+            'D' => 0.2,
+            'Q' => 0.0,
+            'S' => -0.2,
+            'G' => -0.2,
+            'R' => -0.6,
+            'N' => -0.6,
+            'H' => -2.1,
+            'K' => -2.1,
+            _ => 0.0,
+        })
+        .sum()
+}
+
+/// simulate a baseline retention time for a peptide sequence by linearly mapping its hydrophobicity index
+/// onto a gradient, the result is clamped to the gradient length
+///
+/// Arguments:
+///
+/// * `sequence` - peptide sequence, may contain modifications in the format [UNIMOD:ID]
+/// * `gradient_length` - optional length of the gradient in minutes, default 60.0
+///
+/// Returns:
+///
+/// * `retention_time` - retention time in minutes
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::peptide::simulate_retention_time_baseline;
+///
+/// let rt_short = simulate_retention_time_baseline("PEPTIDE", Some(60.0));
+/// let rt_long = simulate_retention_time_baseline("PEPTIDELLLWF", Some(60.0));
+/// assert!(rt_short < rt_long);
+/// assert!(rt_long <= 60.0);
+/// ```
+pub fn simulate_retention_time_baseline(sequence: &str, gradient_length: Option<f64>) -> f64 {
+    let gradient_length = gradient_length.unwrap_or(60.0);
+    // hydrophobicity range that is spread over the full gradient
+    let (index_min, index_max) = (-10.0, 110.0);
+    let index = calculate_peptide_hydrophobicity_index(sequence);
+    let fraction = ((index - index_min) / (index_max - index_min)).clamp(0.0, 1.0);
+    fraction * gradient_length
+}
+
+/// simulate a baseline collision cross-section of a peptide ion from the default power-law `MobilityModel`,
+/// charge states above 4 use the charge 4 coefficients
+///
+/// Arguments:
+///
+/// * `mz` - mass-over-charge of the ion
+/// * `charge` - charge state of the ion
+///
+/// Returns:
+///
+/// * `ccs` - collision cross-section in Å²
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::peptide::simulate_ccs_baseline;
+///
+/// let ccs_2 = simulate_ccs_baseline(600.0, 2);
+/// let ccs_3 = simulate_ccs_baseline(600.0, 3);
+/// assert!(ccs_2 > 350.0 && ccs_2 < 450.0);
+/// assert!(ccs_3 > ccs_2);
+/// ```
+pub fn simulate_ccs_baseline(mz: f64, charge: i32) -> f64 {
+    let charge = charge.max(1);
+    let mass = (mz - MASS_PROTON) * charge as f64;
+    MobilityModel::default().ccs(mass, charge)
+}
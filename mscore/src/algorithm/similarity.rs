@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+use crate::parallel;
+
+use crate::data::spectrum::MzSpectrum;
+
+/// spectra with an entropy below this value get their intensities weighted before scoring
+const ENTROPY_WEIGHT_CUTOFF: f64 = 3.0;
+
+/// Shannon entropy of intensities normalized to a sum of 1, zero intensities are ignored
+fn entropy(intensities: impl Iterator<Item = f64> + Clone) -> f64 {
+    let total: f64 = intensities.clone().filter(|&intensity| intensity > 0.0).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    -intensities
+        .filter(|&intensity| intensity > 0.0)
+        .map(|intensity| {
+            let p = intensity / total;
+            p * p.ln()
+        })
+        .sum::<f64>()
+}
+
+/// sum intensities per m/z bin, the same binning as `MzSpectrum::cosine_similarity`
+fn bin(spectrum: &MzSpectrum, resolution: i32) -> BTreeMap<i64, f64> {
+    let factor = 10f64.powi(resolution);
+    let mut binned: BTreeMap<i64, f64> = BTreeMap::new();
+    for (mz, intensity) in spectrum.mz.iter().zip(&spectrum.intensity) {
+        if *intensity > 0.0 {
+            *binned.entry((mz * factor).round() as i64).or_insert(0.0) += *intensity;
+        }
+    }
+    binned
+}
+
+/// entropy weighted intensities normalized to a sum of 1, and their entropy
+fn weighted(mut binned: BTreeMap<i64, f64>) -> (BTreeMap<i64, f64>, f64) {
+    let mut spectral_entropy = entropy(binned.values().copied());
+    if spectral_entropy < ENTROPY_WEIGHT_CUTOFF {
+        let exponent = 0.25 + 0.25 * spectral_entropy;
+        binned.values_mut().for_each(|intensity| *intensity = intensity.powf(exponent));
+        spectral_entropy = entropy(binned.values().copied());
+    }
+    let total: f64 = binned.values().sum();
+    binned.values_mut().for_each(|intensity| *intensity /= total);
+    (binned, spectral_entropy)
+}
+
+/// Spectral entropy, the Shannon entropy of the intensities normalized to a sum of 1
+///
+/// Arguments:
+///
+/// * `spectrum` - spectrum, peaks without positive intensity are ignored
+///
+/// Returns:
+///
+/// * `f64` - entropy in nats, 0 for spectra without intensity
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::similarity::spectral_entropy;
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let spectrum = MzSpectrum::new(vec![100.0, 200.0, 300.0], vec![1.0, 1.0, 2.0]);
+/// assert!((spectral_entropy(&spectrum) - 1.5 * 2f64.ln()).abs() < 1e-12);
+/// assert_eq!(spectral_entropy(&MzSpectrum::new(vec![], vec![])), 0.0);
+/// ```
+pub fn spectral_entropy(spectrum: &MzSpectrum) -> f64 {
+    entropy(spectrum.intensity.iter().copied())
+}
+
+/// Entropy similarity of two spectra (Li et al., Nature Methods 2021)
+///
+/// Both spectra are binned like `MzSpectrum::cosine_similarity`, intensities of spectra with an entropy below 3
+/// are weighted by raising them to the power 0.25 + 0.25 * entropy. The similarity is
+/// 1 - (2 * S(A + B) - S(A) - S(B)) / ln(4) for the weighted spectra normalized to a sum of 1.
+///
+/// Arguments:
+///
+/// * `a` - first spectrum
+/// * `b` - second spectrum
+/// * `resolution` - decimal places of the m/z bins, peaks in the same bin are summed
+///
+/// Returns:
+///
+/// * `f64` - similarity between 0 for spectra without shared peaks and 1 for identical spectra, 0 if one of the
+///   spectra has no intensity
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::similarity::entropy_similarity;
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let a = MzSpectrum::new(vec![100.0, 200.0], vec![1.0, 1.0]);
+/// let b = MzSpectrum::new(vec![100.001, 300.0], vec![5.0, 5.0]);
+/// assert!((entropy_similarity(&a, &a, 2) - 1.0).abs() < 1e-12);
+/// assert!((entropy_similarity(&a, &b, 2) - 0.5).abs() < 1e-12);
+/// assert_eq!(entropy_similarity(&a, &MzSpectrum::new(vec![], vec![]), 2), 0.0);
+/// ```
+pub fn entropy_similarity(a: &MzSpectrum, b: &MzSpectrum, resolution: i32) -> f64 {
+    let (a, b) = (bin(a, resolution), bin(b, resolution));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (a, entropy_a) = weighted(a);
+    let (b, entropy_b) = weighted(b);
+
+    let mut merged = a;
+    for (key, intensity) in b {
+        *merged.entry(key).or_insert(0.0) += intensity;
+    }
+    let entropy_merged = entropy(merged.values().copied());
+
+    (1.0 - (2.0 * entropy_merged - entropy_a - entropy_b) / 4f64.ln()).clamp(0.0, 1.0)
+}
+
+/// Entropy similarity of many pairs of spectra using multiple threads, e.g. to score PSMs in bulk
+///
+/// Arguments:
+///
+/// * `spectra` - spectra referenced by the pairs
+/// * `pairs` - indices into `spectra` of the two spectra to compare
+/// * `resolution` - decimal places of the m/z bins, see `entropy_similarity`
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<f64>` - similarity of every pair, in the order of `pairs`
+///
+/// Panics if a pair references a spectrum outside of `spectra`.
+///
+/// # Examples
+///
+/// ```
+/// use mscore::algorithm::similarity::entropy_similarity_pairs;
+/// use mscore::data::spectrum::MzSpectrum;
+///
+/// let spectra = vec![
+///     MzSpectrum::new(vec![100.0, 200.0], vec![1.0, 1.0]),
+///     MzSpectrum::new(vec![100.0, 300.0], vec![1.0, 1.0]),
+/// ];
+/// let scores = entropy_similarity_pairs(&spectra, &[(0, 0), (0, 1)], 2, 2);
+/// assert!((scores[0] - 1.0).abs() < 1e-12);
+/// assert!((scores[1] - 0.5).abs() < 1e-12);
+/// ```
+pub fn entropy_similarity_pairs(
+    spectra: &[MzSpectrum],
+    pairs: &[(usize, usize)],
+    resolution: i32,
+    num_threads: usize,
+) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+
+    thread_pool.install(|| {
+        pairs.par_iter()
+            .map(|&(a, b)| entropy_similarity(&spectra[a], &spectra[b], resolution))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_entropy_similarity() {
+        // entropy of (0.75, 0.25) is 0.5623 nats, intensities are weighted with the exponent 0.3906 to
+        // (0.6057, 0.3943), the other spectrum has an entropy of ln(2) and two equal peaks
+        let a = MzSpectrum::new(vec![100.0, 200.0], vec![3.0, 1.0]);
+        let b = MzSpectrum::new(vec![100.0, 300.0], vec![2.0, 2.0]);
+        let (p, q) = (0.605658771438921, 0.39434122856107895);
+        let h = |x: &[f64]| -x.iter().map(|v| v * v.ln()).sum::<f64>();
+        let expected = 1.0 - (2.0 * h(&[(p + 0.5) / 2.0, q / 2.0, 0.25]) - h(&[p, q]) - h(&[0.5, 0.5])) / 4f64.ln();
+
+        assert!((expected - 0.5491821194850659).abs() < 1e-12);
+        assert!((entropy_similarity(&a, &b, 2) - expected).abs() < 1e-12);
+        assert!((entropy_similarity(&b, &a, 2) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_entropy_similarity_bins() {
+        // peaks in the same bin are summed before weighting, disjoint spectra have no similarity
+        let a = MzSpectrum::new(vec![100.0, 100.001, 200.0], vec![1.0, 1.0, 2.0]);
+        let b = MzSpectrum::new(vec![100.0, 200.0], vec![2.0, 2.0]);
+        let c = MzSpectrum::new(vec![150.0, 250.0, 350.0], vec![1.0, 0.0, 4.0]);
+
+        assert!((entropy_similarity(&a, &b, 2) - 1.0).abs() < 1e-12);
+        assert!(entropy_similarity(&a, &b, 4) < 1.0);
+        assert!(entropy_similarity(&a, &c, 2).abs() < 1e-12);
+        assert_eq!(entropy_similarity(&a, &MzSpectrum::new(vec![100.0], vec![0.0]), 2), 0.0);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use nalgebra::DMatrix;
+
+/// How `smooth` combines the values of a window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmoothingMethod {
+    /// unweighted mean of the window
+    MovingAverage,
+    /// value of a least-squares polynomial fit to the window, keeps peak heights better than the mean
+    SavitzkyGolay { polynomial_order: usize },
+}
+
+impl SmoothingMethod {
+    /// Parse a method by name
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - `moving_average` or `savitzky_golay`
+    /// * `polynomial_order` - order of the fitted polynomial, only used by `savitzky_golay`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::smoothing::SmoothingMethod;
+    ///
+    /// assert_eq!(SmoothingMethod::from_name("savitzky_golay", 2), Ok(SmoothingMethod::SavitzkyGolay { polynomial_order: 2 }));
+    /// assert!(SmoothingMethod::from_name("gaussian", 2).is_err());
+    /// ```
+    pub fn from_name(name: &str, polynomial_order: usize) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "moving_average" => Ok(SmoothingMethod::MovingAverage),
+            "savitzky_golay" => Ok(SmoothingMethod::SavitzkyGolay { polynomial_order }),
+            _ => Err(format!("unknown smoothing method {}, expected moving_average or savitzky_golay", name)),
+        }
+    }
+}
+
+/// Weights that turn the values of a window into the smoothed value at offset `left` of the window
+fn window_weights(left: usize, right: usize, method: SmoothingMethod) -> Vec<f64> {
+    let size = left + right + 1;
+    match method {
+        SmoothingMethod::MovingAverage => vec![1.0 / size as f64; size],
+        SmoothingMethod::SavitzkyGolay { polynomial_order } => {
+            // the polynomial is centered on the smoothed position, so its constant term is the smoothed value:
+            // weights = A (A^T A)^-1 e0 with A the vandermonde matrix of the window offsets
+            let degree = polynomial_order.min(size - 1);
+            let a = DMatrix::from_fn(size, degree + 1, |row, col| (row as f64 - left as f64).powi(col as i32));
+            let normal = (a.transpose() * &a).try_inverse().expect("vandermonde matrix of distinct offsets has full rank");
+            (a * normal.column(0)).iter().copied().collect()
+        }
+    }
+}
+
+/// Smooth equally spaced values, windows shrink at the boundaries instead of padding with zeros
+///
+/// Arguments:
+///
+/// * `values` - values to smooth
+/// * `window` - number of values of a full window, centered on the smoothed value, even windows grow by one
+/// * `method` - moving average or Savitzky-Golay
+///
+/// Returns:
+///
+/// * `Vec<f64>` - smoothed values, Savitzky-Golay can produce negative values next to steep peaks
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::smoothing::{smooth, SmoothingMethod};
+///
+/// let smoothed = smooth(&[3.0, 3.0, 6.0, 3.0, 3.0], 3, SmoothingMethod::MovingAverage);
+/// assert_eq!(smoothed, vec![3.0, 4.0, 4.0, 4.0, 3.0]);
+/// ```
+pub fn smooth(values: &[f64], window: usize, method: SmoothingMethod) -> Vec<f64> {
+    let half = window / 2;
+    let mut weights: HashMap<(usize, usize), Vec<f64>> = HashMap::new();
+
+    (0..values.len()).map(|i| {
+        let left = half.min(i);
+        let right = half.min(values.len() - 1 - i);
+        let w = weights.entry((left, right)).or_insert_with(|| window_weights(left, right, method));
+        values[i - left..=i + right].iter().zip(w.iter()).map(|(v, w)| v * w).sum()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_savitzky_golay_keeps_polynomials() {
+        let values: Vec<f64> = (0..12).map(|x| 0.5 * (x * x) as f64 - 3.0 * x as f64 + 7.0).collect();
+        for window in [3, 5, 7] {
+            let smoothed = smooth(&values, window, SmoothingMethod::SavitzkyGolay { polynomial_order: 2 });
+            for (a, b) in smoothed.iter().zip(&values) {
+                assert!((a - b).abs() < 1e-9, "window {window}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_boundaries_shrink_windows() {
+        let constant = vec![2.0; 6];
+        for method in [SmoothingMethod::MovingAverage, SmoothingMethod::SavitzkyGolay { polynomial_order: 3 }] {
+            for (a, b) in smooth(&constant, 5, method).iter().zip(&constant) {
+                assert!((a - b).abs() < 1e-9);
+            }
+        }
+
+        assert_eq!(smooth(&[1.0, 5.0], 1, SmoothingMethod::MovingAverage), vec![1.0, 5.0]);
+        assert!(smooth(&[], 5, SmoothingMethod::MovingAverage).is_empty());
+        // order 0 is the moving average
+        let spiky = [0.0, 9.0, 0.0, 3.0, 0.0];
+        let sg = smooth(&spiky, 3, SmoothingMethod::SavitzkyGolay { polynomial_order: 0 });
+        for (a, b) in sg.iter().zip(smooth(&spiky, 3, SmoothingMethod::MovingAverage)) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+}
@@ -1,10 +1,16 @@
 use std::collections::HashMap;
 use std::f64::consts::SQRT_2;
+use std::fmt::Display;
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use crate::parallel;
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use statrs::distribution::{ContinuousCDF, Normal};
 
 use std::collections::VecDeque;
 
+#[allow(clippy::excessive_precision)]
 fn gauss_kronrod(f: &dyn Fn(f64) -> f64, a: f64, b: f64) -> (f64, f64) {
     let nodes = [
         0.0, 0.20778495500789848, 0.40584515137739717, 0.58608723546769113,
@@ -65,40 +71,127 @@ pub fn adaptive_integration(f: &dyn Fn(f64) -> f64, a: f64, b: f64, epsabs: f64,
 
 
 
-// Numerical integration using the trapezoidal rule
-fn integrate<F>(f: F, a: f64, b: f64, n: usize) -> f64
-    where
-        F: Fn(f64) -> f64,
-{
-    let dx = (b - a) / n as f64;
-    let mut sum = 0.0;
-    for i in 0..n {
-        let x = a + i as f64 * dx;
-        sum += f(x);
-    }
-    sum * dx
-}
 
-// Complementary error function (erfc)
-fn erfc(x: f64) -> f64 {
-    1.0 - erf(x)
+/// Which error function `calerf` evaluates
+#[derive(Clone, Copy, PartialEq)]
+enum ErfKind {
+    Erf,
+    Erfc,
+    Erfcx,
 }
 
-// Error function (erf)
-fn erf(x: f64) -> f64 {
-    let t = 1.0 / (1.0 + 0.5 * x.abs());
-    let tau = t * (-x * x - 1.26551223 + t * (1.00002368 +
-        t * (0.37409196 + t * (0.09678418 + t * (-0.18628806 +
-            t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 +
-                t * (-0.82215223 + t * 0.17087277)))))))))
-        .exp();
-    if x >= 0.0 {
-        1.0 - tau
+// Rational Chebyshev approximations of W. J. Cody, "Rational Chebyshev approximations for the error function",
+// Math. Comp. 23 (1969), coefficients as in his CALERF routine, relative error below 1e-15 over the whole range
+#[allow(clippy::excessive_precision)]
+fn calerf(x: f64, kind: ErfKind) -> f64 {
+    const A: [f64; 5] = [3.16112374387056560e00, 1.13864154151050156e02, 3.77485237685302021e02, 3.20937758913846947e03, 1.85777706184603153e-1];
+    const B: [f64; 4] = [2.36012909523441209e01, 2.44024637934444173e02, 1.28261652607737228e03, 2.84423683343917062e03];
+    const C: [f64; 9] = [
+        5.64188496988670089e-1, 8.88314979438837594e00, 6.61191906371416295e01, 2.98635138197400131e02, 8.81952221241769090e02,
+        1.71204761263407058e03, 2.05107837782607147e03, 1.23033935479799725e03, 2.15311535474403846e-8,
+    ];
+    const D: [f64; 8] = [
+        1.57449261107098347e01, 1.17693950891312499e02, 5.37181101862009858e02, 1.62138957456669019e03,
+        3.29079923573345963e03, 4.36261909014324716e03, 3.43936767414372164e03, 1.23033935480374942e03,
+    ];
+    const P: [f64; 6] = [3.05326634961232344e-1, 3.60344899949804439e-1, 1.25781726111229246e-1, 1.60837851487422766e-2, 6.58749161529837803e-4, 1.63153871373020978e-2];
+    const Q: [f64; 5] = [2.56852019228982242e00, 1.87295284992346725e00, 5.27905102951428412e-1, 6.05183413124413191e-2, 2.33520497626869185e-3];
+    const SQRPI: f64 = 5.6418958354775628695e-1;
+    const THRESH: f64 = 0.46875;
+    const XSMALL: f64 = 1.11e-16;
+    const XBIG: f64 = 26.543;
+    const XHUGE: f64 = 6.71e7;
+    const XMAX: f64 = 2.53e307;
+    const XNEG: f64 = -26.628;
+
+    if x.is_nan() {
+        return f64::NAN;
+    }
+
+    let y = x.abs();
+    // exp(-y^2) split into two factors to keep the rounding error of y^2 small
+    let exp_neg_square = |y: f64| -> f64 {
+        let ysq = (y * 16.0).trunc() / 16.0;
+        let del = (y - ysq) * (y + ysq);
+        (-ysq * ysq).exp() * (-del).exp()
+    };
+
+    if y <= THRESH {
+        let ysq = if y > XSMALL { y * y } else { 0.0 };
+        let mut xnum = A[4] * ysq;
+        let mut xden = ysq;
+        for i in 0..3 {
+            xnum = (xnum + A[i]) * ysq;
+            xden = (xden + B[i]) * ysq;
+        }
+        let erf = x * (xnum + A[3]) / (xden + B[3]);
+        return match kind {
+            ErfKind::Erf => erf,
+            ErfKind::Erfc => 1.0 - erf,
+            ErfKind::Erfcx => (ysq.exp()) * (1.0 - erf),
+        };
+    }
+
+    // scaled erfc of |x|, i.e. exp(y^2) * erfc(y)
+    let scaled = if y <= 4.0 {
+        let mut xnum = C[8] * y;
+        let mut xden = y;
+        for i in 0..7 {
+            xnum = (xnum + C[i]) * y;
+            xden = (xden + D[i]) * y;
+        }
+        (xnum + C[7]) / (xden + D[7])
+    } else if y >= XHUGE {
+        if y >= XMAX { 0.0 } else { SQRPI / y }
     } else {
-        tau - 1.0
+        let ysq = 1.0 / (y * y);
+        let mut xnum = P[5] * ysq;
+        let mut xden = ysq;
+        for i in 0..4 {
+            xnum = (xnum + P[i]) * ysq;
+            xden = (xden + Q[i]) * ysq;
+        }
+        let r = ysq * (xnum + P[4]) / (xden + Q[4]);
+        (SQRPI - r) / y
+    };
+
+    match kind {
+        ErfKind::Erfcx => {
+            if x >= 0.0 {
+                scaled
+            } else if x < XNEG {
+                f64::INFINITY
+            } else {
+                2.0 / exp_neg_square(y) - scaled
+            }
+        }
+        _ => {
+            let erfc_abs = if kind != ErfKind::Erf && y >= XBIG { 0.0 } else { exp_neg_square(y) * scaled };
+            match (kind, x >= 0.0) {
+                (ErfKind::Erf, true) => 1.0 - erfc_abs,
+                (ErfKind::Erf, false) => erfc_abs - 1.0,
+                (_, true) => erfc_abs,
+                (_, false) => 2.0 - erfc_abs,
+            }
+        }
     }
 }
 
+/// Complementary error function (erfc), computed directly to avoid the cancellation of 1 - erf(x) for large x
+pub fn erfc(x: f64) -> f64 {
+    calerf(x, ErfKind::Erfc)
+}
+
+/// Error function (erf)
+pub fn erf(x: f64) -> f64 {
+    calerf(x, ErfKind::Erf)
+}
+
+/// Scaled complementary error function `exp(x^2) * erfc(x)`, finite for large x where erfc underflows
+pub fn erfcx(x: f64) -> f64 {
+    calerf(x, ErfKind::Erfcx)
+}
+
 // Exponentially modified Gaussian function
 fn emg(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
     let part1 = lambda / 2.0 * (-lambda * (x - mu) + lambda * lambda * sigma * sigma / 2.0).exp();
@@ -108,10 +201,15 @@ fn emg(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
 
 pub fn custom_cdf_normal(x: f64, mean: f64, std_dev: f64) -> f64 {
     let z = (x - mean) / std_dev;
-    0.5 * (1.0 + erf(z / SQRT_2))
+    0.5 * erfc(-z / SQRT_2)
 }
 
 pub fn accumulated_intensity_cdf_normal(sample_start: f64, sample_end: f64, mean: f64, std_dev: f64) -> f64 {
+    // right of the mean the difference of survival functions keeps precision in the upper tail
+    if sample_start > mean {
+        let survival = |x: f64| 0.5 * erfc((x - mean) / (std_dev * SQRT_2));
+        return survival(sample_start) - survival(sample_end);
+    }
     let cdf_start = custom_cdf_normal(sample_start, mean, std_dev);
     let cdf_end = custom_cdf_normal(sample_end, mean, std_dev);
     cdf_end - cdf_start
@@ -127,25 +225,191 @@ pub fn emg_function(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
     prefactor * erfc_part
 }
 
-pub fn emg_cdf_range(lower_limit: f64, upper_limit: f64, mu: f64, sigma: f64, lambda: f64, n_steps: Option<usize>) -> f64 {
-    let n_steps = n_steps.unwrap_or(1000);
-    integrate(|x| emg(x, mu, sigma, lambda), lower_limit, upper_limit, n_steps)
+/// Adaptive Simpson integration with a relative tolerance
+fn adaptive_simpson<F: Fn(f64) -> f64>(f: &F, a: f64, b: f64, epsrel: f64, max_depth: usize) -> f64 {
+    fn simpson<F: Fn(f64) -> f64>(f: &F, a: f64, fa: f64, b: f64, fb: f64) -> (f64, f64, f64) {
+        let m = (a + b) / 2.0;
+        let fm = f(m);
+        (m, fm, (b - a) / 6.0 * (fa + 4.0 * fm + fb))
+    }
+
+    #[allow(clippy::too_many_arguments, reason = "the recursion carries the function values of the interval and its midpoint")]
+    fn recurse<F: Fn(f64) -> f64>(f: &F, a: f64, fa: f64, b: f64, fb: f64, m: f64, fm: f64, whole: f64, eps: f64, depth: usize) -> f64 {
+        let (lm, flm, left) = simpson(f, a, fa, m, fm);
+        let (rm, frm, right) = simpson(f, m, fm, b, fb);
+        let delta = left + right - whole;
+        if depth == 0 || delta.abs() <= 15.0 * eps {
+            return left + right + delta / 15.0;
+        }
+        recurse(f, a, fa, m, fm, lm, flm, left, eps / 2.0, depth - 1) + recurse(f, m, fm, b, fb, rm, frm, right, eps / 2.0, depth - 1)
+    }
+
+    let (fa, fb) = (f(a), f(b));
+    let (m, fm, whole) = simpson(f, a, fa, b, fb);
+    let eps = (epsrel * whole.abs()).max(f64::EPSILON);
+    recurse(f, a, fa, b, fb, m, fm, whole, eps, max_depth)
+}
+
+/// Cumulative distribution function of an exponentially modified Gaussian in closed form,
+/// all terms depending only on the parameters are computed once, the closed form is normalized by construction
+/// so `calculate_bounds_emg` builds one `EmgCdf` per peak instead of memoizing a normalization
+#[derive(Clone, Copy, Debug)]
+pub struct EmgCdf {
+    mu: f64,
+    sigma: f64,
+    lambda: f64,
+    lambda_sigma: f64,
+    half_lambda_sigma_squared: f64,
+}
+
+impl EmgCdf {
+    pub fn new(mu: f64, sigma: f64, lambda: f64) -> Self {
+        let lambda_sigma = lambda * sigma;
+        EmgCdf { mu, sigma, lambda, lambda_sigma, half_lambda_sigma_squared: lambda_sigma * lambda_sigma / 2.0 }
+    }
+
+    /// the closed form requires positive, finite parameters, otherwise the density is integrated numerically
+    fn is_stable(&self) -> bool {
+        self.sigma > 0.0 && self.lambda > 0.0 && self.sigma.is_finite() && self.lambda.is_finite()
+    }
+
+    /// `P(X <= x)`, computed as `Phi(u / sigma) - exp(-lambda * u + lambda^2 sigma^2 / 2) * Phi(u / sigma - lambda * sigma)`
+    /// with `u = x - mu`, for large `lambda * sigma` the second term is evaluated through erfcx to avoid overflow
+    pub fn cdf(&self, x: f64) -> f64 {
+        if x == f64::NEG_INFINITY {
+            return 0.0;
+        }
+        if x == f64::INFINITY {
+            return 1.0;
+        }
+        let u = x - self.mu;
+        let gaussian = 0.5 * erfc(-u / (self.sigma * SQRT_2));
+        let z = (self.lambda_sigma - u / self.sigma) / SQRT_2;
+        let tail = if z > 0.0 {
+            0.5 * erfcx(z) * (-u * u / (2.0 * self.sigma * self.sigma)).exp()
+        } else {
+            0.5 * (-self.lambda * u + self.half_lambda_sigma_squared).exp() * erfc(z)
+        };
+        gaussian - tail
+    }
+
+    /// probability mass in `[lower_limit, upper_limit]`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::utility::EmgCdf;
+    ///
+    /// let cdf = EmgCdf::new(10.0, 1.0, 0.5);
+    /// assert!((cdf.cdf_range(f64::NEG_INFINITY, f64::INFINITY) - 1.0).abs() < 1e-12);
+    /// // the exponential tail shifts the mean to mu + 1 / lambda
+    /// assert!(cdf.cdf_range(f64::NEG_INFINITY, 10.0) < 0.5);
+    /// ```
+    pub fn cdf_range(&self, lower_limit: f64, upper_limit: f64) -> f64 {
+        if self.is_stable() {
+            let p = self.cdf(upper_limit) - self.cdf(lower_limit);
+            if p.is_finite() {
+                return p;
+            }
+        }
+        let (mu, sigma, lambda) = (self.mu, self.sigma, self.lambda);
+        adaptive_simpson(&|x| emg(x, mu, sigma, lambda), lower_limit, upper_limit, 1e-10, 50)
+    }
+}
+
+/// Probability mass of an exponentially modified Gaussian in `[lower_limit, upper_limit]`, computed in closed form,
+/// `_n_steps` is ignored and only kept for compatibility with the former trapezoidal integration
+pub fn emg_cdf_range(lower_limit: f64, upper_limit: f64, mu: f64, sigma: f64, lambda: f64, _n_steps: Option<usize>) -> f64 {
+    EmgCdf::new(mu, sigma, lambda).cdf_range(lower_limit, upper_limit)
 }
 
-pub fn calculate_bounds_emg(mu: f64, sigma: f64, lambda: f64, step_size: f64, target: f64, lower_start: f64, upper_start: f64, n_steps: Option<usize>) -> (f64, f64) {
-    assert!(0.0 <= target && target <= 1.0, "target must be in [0, 1]");
+/// Default factor up to which `calculate_bounds_emg` widens its initial search window
+pub const DEFAULT_MAX_WINDOW_WIDENING: f64 = 16.0;
+
+/// The target probability mass of a peak could not be captured within the widest search window
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundsError {
+    pub target: f64,
+    pub achieved: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
 
-    let lower_initial = mu - lower_start * sigma - 2.0;
-    let upper_initial = mu + upper_start * sigma;
+impl Display for BoundsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Target probability mass {} not reached, window [{}, {}] holds only {}",
+            self.target, self.lower, self.upper, self.achieved
+        )
+    }
+}
 
-    let steps = ((upper_initial - lower_initial) / step_size).round() as usize;
-    let search_space: Vec<f64> = (0..=steps).map(|i| lower_initial + i as f64 * step_size).collect();
+impl std::error::Error for BoundsError {}
 
+/// Calculate the bounding interval [lower, upper] of an EMG that captures `target` probability mass,
+/// the search window starts at mu - lower_start * sigma to mu + upper_start * sigma and is doubled up to
+/// `max_widening` times its initial width if it does not hold the target mass
+///
+/// Arguments:
+///
+/// * `mu` - mean of the gaussian component
+/// * `sigma` - standard deviation of the gaussian component
+/// * `lambda` - rate of the exponential component
+/// * `step_size` - resolution of the bounds
+/// * `target` - probability mass to capture, in [0, 1]
+/// * `lower_start` - initial extent of the window below mu in units of sigma
+/// * `upper_start` - initial extent of the window above mu in units of sigma
+/// * `max_widening` - factor up to which the window is widened, defaults to `DEFAULT_MAX_WINDOW_WIDENING`
+/// * `_n_steps` - unused, the EMG CDF is evaluated in closed form
+///
+/// Returns:
+///
+/// * `Result<(f64, f64), BoundsError>` - lower <= upper holding at least `target` mass, or the widest window tried
+///
+/// Example:
+///
+/// ```
+/// use mscore::algorithm::utility::{calculate_bounds_emg, emg_cdf_range};
+///
+/// let (lower, upper) = calculate_bounds_emg(100.0, 1.0, 0.01, 0.001, 0.99, 20.0, 60.0, None, None).unwrap();
+/// assert!(lower <= upper);
+/// assert!(emg_cdf_range(lower, upper, 100.0, 1.0, 0.01, None) >= 0.99);
+///
+/// let error = calculate_bounds_emg(100.0, 1.0, 0.01, 0.001, 0.99, 20.0, 60.0, Some(1.0), None).unwrap_err();
+/// assert!(error.achieved < 0.99);
+/// ```
+#[allow(clippy::too_many_arguments, reason = "peak shape and search parameters are exposed separately in the Python API")]
+pub fn calculate_bounds_emg(mu: f64, sigma: f64, lambda: f64, step_size: f64, target: f64, lower_start: f64, upper_start: f64, max_widening: Option<f64>, _n_steps: Option<usize>) -> Result<(f64, f64), BoundsError> {
+    assert!((0.0..=1.0).contains(&target), "target must be in [0, 1]");
+
+    let max_widening = max_widening.unwrap_or(DEFAULT_MAX_WINDOW_WIDENING).max(1.0);
+    let emg_cdf = EmgCdf::new(mu, sigma, lambda);
+
+    // widen the window until it holds the target mass, the searches below rely on that
+    let mut widening = 1.0;
+    let (lower_initial, steps) = loop {
+        let lower_initial = mu - widening * lower_start * sigma - 2.0;
+        let upper_initial = mu + widening * upper_start * sigma;
+        let steps = ((upper_initial - lower_initial) / step_size).ceil() as usize;
+        let upper_initial = lower_initial + steps as f64 * step_size;
+
+        let achieved = emg_cdf.cdf_range(lower_initial, upper_initial);
+        if achieved >= target {
+            break (lower_initial, steps);
+        }
+        if widening >= max_widening {
+            return Err(BoundsError { target, achieved, lower: lower_initial, upper: upper_initial });
+        }
+        widening = (widening * 2.0).min(max_widening);
+    };
+
+    let search_space = |i: usize| -> f64 { lower_initial + i as f64 * step_size };
     let calc_cdf = |low: usize, high: usize| -> f64 {
-        emg_cdf_range(search_space[low], search_space[high], mu, sigma, lambda, n_steps)
+        emg_cdf.cdf_range(search_space(low), search_space(high))
     };
 
-    // Binary search for cutoff values
+    // smallest upper index with calc_cdf(0, upper) >= target, exists since the whole window holds the target
     let (mut low, mut high) = (0, steps);
     while low < high {
         let mid = low + (high - low) / 2;
@@ -157,51 +421,148 @@ pub fn calculate_bounds_emg(mu: f64, sigma: f64, lambda: f64, step_size: f64, ta
     }
     let upper_cutoff_index = low;
 
-    low = 0;
-    high = upper_cutoff_index;
+    // largest lower index with calc_cdf(lower, upper) >= target, index 0 always qualifies
+    let (mut low, mut high) = (0, upper_cutoff_index);
     while low < high {
-        let mid = high - (high - low) / 2;
-        let prob_mid_to_upper = calc_cdf(mid, upper_cutoff_index);
-
-        if prob_mid_to_upper < target {
-            high = mid - 1;
-        } else {
+        let mid = low + (high - low).div_ceil(2);
+        if calc_cdf(mid, upper_cutoff_index) >= target {
             low = mid;
+        } else {
+            high = mid - 1;
         }
     }
-    let lower_cutoff_index = high;
+    let lower_cutoff_index = low;
 
-    (search_space[lower_cutoff_index], search_space[upper_cutoff_index])
+    Ok((search_space(lower_cutoff_index), search_space(upper_cutoff_index)))
 }
 
-pub fn calculate_frame_occurrence_emg(retention_times: &[f64], rt: f64, sigma: f64, lambda_: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Vec<i32> {
-    let (rt_min, rt_max) = calculate_bounds_emg(rt, sigma, lambda_, step_size, target_p, 20.0, 60.0, n_steps);
+/// Elution profile of a peptide over the retention time axis, centered at the apex `mu`
+pub trait ElutionProfile {
+    /// probability mass of the profile in `[lower_limit, upper_limit]`
+    fn cdf_range(&self, lower_limit: f64, upper_limit: f64, mu: f64, n_steps: Option<usize>) -> f64;
 
-    // Finding the frame closest to rt_min
-    let first_frame = retention_times.iter()
-        .enumerate()
-        .min_by(|(_, &a), (_, &b)| (a - rt_min).abs().partial_cmp(&(b - rt_min).abs()).unwrap())
-        .map(|(idx, _)| idx + 1) // Rust is zero-indexed, so +1 to match Python's 1-indexing
-        .unwrap_or(0); // Fallback in case of an empty slice
+    /// retention time range holding at least `target_p` of the probability mass, lower <= upper
+    fn bounds(&self, mu: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Result<(f64, f64), BoundsError>;
+}
 
-    // Finding the frame closest to rt_max
-    let last_frame = retention_times.iter()
-        .enumerate()
-        .min_by(|(_, &a), (_, &b)| (a - rt_max).abs().partial_cmp(&(b - rt_max).abs()).unwrap())
-        .map(|(idx, _)| idx + 1) // Same adjustment for 1-indexing
-        .unwrap_or(0); // Fallback
+/// Elution peak shape of a peptide
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeakShape {
+    /// exponentially modified Gaussian, tailing peaks
+    Emg { sigma: f64, lambda: f64 },
+    /// symmetric Gaussian, e.g. sharp UHPLC peaks
+    Gaussian { sigma: f64 },
+    /// Gaussian with different widths left and right of the apex, fronting peaks if `sigma_left > sigma_right`
+    BiGaussian { sigma_left: f64, sigma_right: f64 },
+}
+
+impl PeakShape {
+    /// z-score of the central interval of a standard normal distribution holding `target_p`
+    fn central_z_score(target_p: f64) -> f64 {
+        assert!((0.0..1.0).contains(&target_p), "target must be in [0, 1)");
+        Normal::new(0.0, 1.0).unwrap().inverse_cdf(0.5 + target_p / 2.0)
+    }
+}
+
+impl ElutionProfile for PeakShape {
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::utility::{ElutionProfile, PeakShape};
+    ///
+    /// let gaussian = PeakShape::Gaussian { sigma: 2.0 };
+    /// assert!((gaussian.cdf_range(8.0, 12.0, 10.0, None) - 0.6827).abs() < 1e-4);
+    ///
+    /// // a fronting peak holds more mass left of its apex
+    /// let fronting = PeakShape::BiGaussian { sigma_left: 3.0, sigma_right: 1.0 };
+    /// assert!((fronting.cdf_range(f64::NEG_INFINITY, 10.0, 10.0, None) - 0.75).abs() < 1e-6);
+    /// ```
+    fn cdf_range(&self, lower_limit: f64, upper_limit: f64, mu: f64, n_steps: Option<usize>) -> f64 {
+        match *self {
+            PeakShape::Emg { sigma, lambda } => emg_cdf_range(lower_limit, upper_limit, mu, sigma, lambda, n_steps),
+            PeakShape::Gaussian { sigma } => normal_cdf_range(lower_limit, upper_limit, mu, sigma),
+            PeakShape::BiGaussian { sigma_left, sigma_right } => {
+                let total = sigma_left + sigma_right;
+                let cdf = |x: f64| -> f64 {
+                    if x < mu {
+                        2.0 * sigma_left / total * custom_cdf_normal(x, mu, sigma_left)
+                    } else {
+                        sigma_left / total + 2.0 * sigma_right / total * (custom_cdf_normal(x, mu, sigma_right) - 0.5)
+                    }
+                };
+                cdf(upper_limit) - cdf(lower_limit)
+            }
+        }
+    }
+
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::utility::{ElutionProfile, PeakShape};
+    ///
+    /// let shape = PeakShape::BiGaussian { sigma_left: 3.0, sigma_right: 1.0 };
+    /// let (lower, upper) = shape.bounds(100.0, 0.99, 0.001, None).unwrap();
+    /// assert!((shape.cdf_range(lower, upper, 100.0, None) - 0.99).abs() < 1e-6);
+    /// assert!(100.0 - lower > upper - 100.0);
+    /// ```
+    fn bounds(&self, mu: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Result<(f64, f64), BoundsError> {
+        match *self {
+            PeakShape::Emg { sigma, lambda } => calculate_bounds_emg(mu, sigma, lambda, step_size, target_p, 20.0, 60.0, None, n_steps),
+            PeakShape::Gaussian { sigma } => Ok(calculate_bounds_normal(mu, sigma, PeakShape::central_z_score(target_p))),
+            PeakShape::BiGaussian { sigma_left, sigma_right } => {
+                // both halves hold target_p of their own mass
+                let z = PeakShape::central_z_score(target_p);
+                Ok((mu - z * sigma_left, mu + z * sigma_right))
+            }
+        }
+    }
+}
+
+pub fn calculate_frame_occurrence<P: ElutionProfile>(retention_times: &[f64], rt: f64, shape: &P, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Result<Vec<i32>, BoundsError> {
+    frame_occurrence(retention_times, is_ascending(retention_times), rt, shape, target_p, step_size, n_steps)
+}
+
+fn is_ascending(retention_times: &[f64]) -> bool {
+    retention_times.windows(2).all(|w| w[0] <= w[1])
+}
+
+/// 1-based index of the first frame closest to `rt`, 0 for no frames, binary search if the retention times are ascending
+fn closest_frame(retention_times: &[f64], ascending: bool, rt: f64) -> usize {
+    if !ascending {
+        return retention_times.iter()
+            .enumerate()
+            .min_by(|(_, &a), (_, &b)| (a - rt).abs().partial_cmp(&(b - rt).abs()).unwrap())
+            .map(|(idx, _)| idx + 1) // Rust is zero-indexed, so +1 to match Python's 1-indexing
+            .unwrap_or(0); // Fallback in case of an empty slice
+    }
+    let right = retention_times.partition_point(|&t| t < rt);
+    let closest = match (right.checked_sub(1), retention_times.get(right)) {
+        (None, None) => return 0,
+        (None, Some(_)) => right,
+        (Some(left), None) => left,
+        // ties go to the earlier frame, as with the linear search
+        (Some(left), Some(&after)) => if rt - retention_times[left] <= after - rt { left } else { right },
+    };
+    // first of a run of equal retention times
+    retention_times.partition_point(|&t| t < retention_times[closest]) + 1
+}
+
+fn frame_occurrence<P: ElutionProfile>(retention_times: &[f64], ascending: bool, rt: f64, shape: &P, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Result<Vec<i32>, BoundsError> {
+    let (rt_min, rt_max) = shape.bounds(rt, target_p, step_size, n_steps)?;
+    let first_frame = closest_frame(retention_times, ascending, rt_min);
+    let last_frame = closest_frame(retention_times, ascending, rt_max);
 
     // Generating the range of frames
-    (first_frame..=last_frame).map(|x| x as i32).collect()
+    Ok((first_frame..=last_frame).map(|x| x as i32).collect())
 }
 
-pub fn calculate_frame_abundance_emg(time_map: &HashMap<i32, f64>, occurrences: &[i32], rt: f64, sigma: f64, lambda_: f64, rt_cycle_length: f64, n_steps: Option<usize>) -> Vec<f64> {
+pub fn calculate_frame_abundance<P: ElutionProfile>(time_map: &HashMap<i32, f64>, occurrences: &[i32], rt: f64, shape: &P, rt_cycle_length: f64, n_steps: Option<usize>) -> Vec<f64> {
     let mut frame_abundance = Vec::new();
 
     for &occurrence in occurrences {
         if let Some(&time) = time_map.get(&occurrence) {
             let start = time - rt_cycle_length;
-            let i = emg_cdf_range(start, time, rt, sigma, lambda_, n_steps);
+            let i = shape.cdf_range(start, time, rt, n_steps);
             frame_abundance.push(i);
         }
     }
@@ -209,29 +570,152 @@ pub fn calculate_frame_abundance_emg(time_map: &HashMap<i32, f64>, occurrences:
     frame_abundance
 }
 
-// retention_times: &[f64], rt: f64, sigma: f64, lambda_: f64
-pub fn calculate_frame_occurrences_emg_par(retention_times: &[f64], rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, target_p: f64, step_size: f64, num_threads: usize, n_steps: Option<usize>) -> Vec<Vec<i32>> {
-    let thread_pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
-    let result = thread_pool.install(|| {
-        rts.into_par_iter().zip(sigmas.into_par_iter()).zip(lambdas.into_par_iter())
-            .map(|((rt, sigma), lambda)| {
-                calculate_frame_occurrence_emg(retention_times, rt, sigma, lambda, target_p, step_size, n_steps)
+pub fn calculate_frame_occurrence_emg(retention_times: &[f64], rt: f64, sigma: f64, lambda_: f64, target_p: f64, step_size: f64, n_steps: Option<usize>) -> Result<Vec<i32>, BoundsError> {
+    calculate_frame_occurrence(retention_times, rt, &PeakShape::Emg { sigma, lambda: lambda_ }, target_p, step_size, n_steps)
+}
+
+pub fn calculate_frame_abundance_emg(time_map: &HashMap<i32, f64>, occurrences: &[i32], rt: f64, sigma: f64, lambda_: f64, rt_cycle_length: f64, n_steps: Option<usize>) -> Vec<f64> {
+    calculate_frame_abundance(time_map, occurrences, rt, &PeakShape::Emg { sigma, lambda: lambda_ }, rt_cycle_length, n_steps)
+}
+
+/// Distortion of simulated retention times, a monotone piecewise-linear warp of the rt axis (gradient distortion)
+/// followed by a normal jitter of every peptide apex, applied before frame occurrences and abundances are computed
+#[derive(Clone, Debug, Default)]
+pub struct RtDistortion {
+    /// standard deviation of the per peptide apex jitter in seconds, 0 disables the jitter
+    pub jitter_std: f64,
+    /// seed of the jitter, the same seed yields the same jitter for the same peptide order
+    pub seed: u64,
+    /// rt control points of the warp, strictly increasing, empty disables the warp
+    pub warp_from: Vec<f64>,
+    /// warped rt of every control point, strictly increasing
+    pub warp_to: Vec<f64>,
+}
+
+impl RtDistortion {
+    /// Create a new RtDistortion, a jitter requires a seed since occurrences and abundances have to see the same apex
+    ///
+    /// Arguments:
+    ///
+    /// * `jitter_std` - standard deviation of the apex jitter in seconds, None or 0 disables the jitter
+    /// * `warp_from` - rt control points of the warp, at least two, strictly increasing
+    /// * `warp_to` - warped rt of the control points, strictly increasing
+    /// * `seed` - seed of the jitter
+    ///
+    /// Returns:
+    ///
+    /// * `Result<RtDistortion, String>` - the distortion or a description of the invalid setting
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::algorithm::utility::RtDistortion;
+    ///
+    /// // the second half of the gradient is stretched
+    /// let distortion = RtDistortion::new(None, vec![0.0, 600.0, 1200.0], vec![0.0, 600.0, 1500.0], None).unwrap();
+    /// assert_eq!(distortion.warp(300.0), 300.0);
+    /// assert_eq!(distortion.warp(900.0), 1050.0);
+    /// assert_eq!(distortion.distort(&[900.0]), vec![1050.0]);
+    ///
+    /// assert!(RtDistortion::new(Some(2.0), vec![], vec![], None).is_err());
+    /// let jitter = RtDistortion::new(Some(2.0), vec![], vec![], Some(42)).unwrap();
+    /// assert_eq!(jitter.distort(&[100.0, 200.0]), jitter.distort(&[100.0, 200.0]));
+    /// ```
+    pub fn new(jitter_std: Option<f64>, warp_from: Vec<f64>, warp_to: Vec<f64>, seed: Option<u64>) -> Result<Self, String> {
+        let jitter_std = jitter_std.unwrap_or(0.0);
+        if !(jitter_std >= 0.0 && jitter_std.is_finite()) {
+            return Err(format!("rt jitter standard deviation must be finite and non-negative, got {}", jitter_std));
+        }
+        if jitter_std > 0.0 && seed.is_none() {
+            return Err("rt jitter requires a seed".to_string());
+        }
+        if warp_from.len() != warp_to.len() {
+            return Err(format!("warp control points differ in length: {} and {}", warp_from.len(), warp_to.len()));
+        }
+        if warp_from.len() == 1 {
+            return Err("warp requires at least two control points".to_string());
+        }
+        let increasing = |values: &[f64]| values.windows(2).all(|w| w[0] < w[1]);
+        if !increasing(&warp_from) || !increasing(&warp_to) {
+            return Err("warp control points must be strictly increasing".to_string());
+        }
+
+        Ok(RtDistortion { jitter_std, seed: seed.unwrap_or(0), warp_from, warp_to })
+    }
+
+    /// Warp a retention time, outside of the control points the first and last segment are extended
+    pub fn warp(&self, rt: f64) -> f64 {
+        if self.warp_from.is_empty() {
+            return rt;
+        }
+        let last = self.warp_from.len() - 1;
+        let segment = self.warp_from.partition_point(|&x| x <= rt).clamp(1, last) - 1;
+        let (x0, x1) = (self.warp_from[segment], self.warp_from[segment + 1]);
+        let (y0, y1) = (self.warp_to[segment], self.warp_to[segment + 1]);
+        y0 + (rt - x0) * (y1 - y0) / (x1 - x0)
+    }
+
+    /// Warp and jitter the apex retention times of all peptides
+    pub fn distort(&self, rts: &[f64]) -> Vec<f64> {
+        let warped = rts.iter().map(|&rt| self.warp(rt));
+        if self.jitter_std == 0.0 {
+            return warped.collect();
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let normal = Normal::new(0.0, self.jitter_std).unwrap();
+        warped.map(|rt| rt + normal.sample(&mut rng)).collect()
+    }
+}
+
+/// Frame occurrences of many peptides in parallel, every peptide has its own peak shape,
+/// fails with the first peptide whose peak does not reach `target_p`
+#[allow(clippy::too_many_arguments, reason = "one column per peptide plus the sampling parameters, as passed from Python")]
+pub fn calculate_frame_occurrences_par(retention_times: &[f64], rts: Vec<f64>, shapes: Vec<PeakShape>, target_p: f64, step_size: f64, num_threads: impl Into<Option<usize>>, n_steps: Option<usize>, rt_distortion: Option<&RtDistortion>) -> Result<Vec<Vec<i32>>, BoundsError> {
+    let rts = match rt_distortion {
+        Some(distortion) => distortion.distort(&rts),
+        None => rts,
+    };
+    let ascending = is_ascending(retention_times);
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        rts.into_par_iter().zip(shapes.into_par_iter())
+            .map(|(rt, shape)| {
+                frame_occurrence(retention_times, ascending, rt, &shape, target_p, step_size, n_steps)
             })
             .collect()
-    });
-    result
+    })
 }
 
-pub fn calculate_frame_abundances_emg_par(time_map: &HashMap<i32, f64>, occurrences: Vec<Vec<i32>>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, rt_cycle_length: f64, num_threads: usize, n_steps: Option<usize>) -> Vec<Vec<f64>> {
-    let thread_pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
-    let result = thread_pool.install(|| {
-        occurrences.into_par_iter().zip(rts.into_par_iter()).zip(sigmas.into_par_iter()).zip(lambdas.into_par_iter())
-            .map(|(((occurrences, rt), sigma), lambda)| {
-                calculate_frame_abundance_emg(time_map, &occurrences, rt, sigma, lambda, rt_cycle_length, n_steps)
+/// Frame abundances of many peptides in parallel, every peptide has its own peak shape
+#[allow(clippy::too_many_arguments, reason = "one column per peptide plus the sampling parameters, as passed from Python")]
+pub fn calculate_frame_abundances_par(time_map: &HashMap<i32, f64>, occurrences: Vec<Vec<i32>>, rts: Vec<f64>, shapes: Vec<PeakShape>, rt_cycle_length: f64, num_threads: impl Into<Option<usize>>, n_steps: Option<usize>, rt_distortion: Option<&RtDistortion>) -> Vec<Vec<f64>> {
+    let rts = match rt_distortion {
+        Some(distortion) => distortion.distort(&rts),
+        None => rts,
+    };
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        occurrences.into_par_iter().zip(rts.into_par_iter()).zip(shapes.into_par_iter())
+            .map(|((occurrences, rt), shape)| {
+                calculate_frame_abundance(time_map, &occurrences, rt, &shape, rt_cycle_length, n_steps)
             })
             .collect()
-    });
-    result
+    })
+}
+
+fn emg_shapes(sigmas: Vec<f64>, lambdas: Vec<f64>) -> Vec<PeakShape> {
+    sigmas.into_iter().zip(lambdas).map(|(sigma, lambda)| PeakShape::Emg { sigma, lambda }).collect()
+}
+
+// retention_times: &[f64], rt: f64, sigma: f64, lambda_: f64
+#[allow(clippy::too_many_arguments, reason = "one column per peptide plus the sampling parameters, as passed from Python")]
+pub fn calculate_frame_occurrences_emg_par(retention_times: &[f64], rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, target_p: f64, step_size: f64, num_threads: impl Into<Option<usize>>, n_steps: Option<usize>, rt_distortion: Option<&RtDistortion>) -> Result<Vec<Vec<i32>>, BoundsError> {
+    calculate_frame_occurrences_par(retention_times, rts, emg_shapes(sigmas, lambdas), target_p, step_size, num_threads, n_steps, rt_distortion)
+}
+
+#[allow(clippy::too_many_arguments, reason = "one column per peptide plus the sampling parameters, as passed from Python")]
+pub fn calculate_frame_abundances_emg_par(time_map: &HashMap<i32, f64>, occurrences: Vec<Vec<i32>>, rts: Vec<f64>, sigmas: Vec<f64>, lambdas: Vec<f64>, rt_cycle_length: f64, num_threads: impl Into<Option<usize>>, n_steps: Option<usize>, rt_distortion: Option<&RtDistortion>) -> Vec<Vec<f64>> {
+    calculate_frame_abundances_par(time_map, occurrences, rts, emg_shapes(sigmas, lambdas), rt_cycle_length, num_threads, n_steps, rt_distortion)
 }
 
 /// Returns the CDF in the range [sample_start, sample_end] for a Normal(mean, std_dev).
@@ -404,6 +888,7 @@ pub fn calculate_abundance_gaussian(
     frame_abundance
 }
 
+#[allow(clippy::too_many_arguments, reason = "one column per ion plus the sampling parameters, as passed from Python")]
 pub fn calculate_scan_occurrences_gaussian_par(
     times: &[f64],
     means: Vec<f64>,
@@ -414,10 +899,7 @@ pub fn calculate_scan_occurrences_gaussian_par(
     n_upper_start: f64,
     num_threads: usize
 ) -> Vec<Vec<i32>> {
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let thread_pool = parallel::pool(num_threads);
 
     thread_pool.install(|| {
         means.into_par_iter()
@@ -446,10 +928,7 @@ pub fn calculate_scan_abundances_gaussian_par(
     cycle_length: f64,
     num_threads: usize
 ) -> Vec<Vec<f64>> {
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let thread_pool = parallel::pool(num_threads);
 
     thread_pool.install(|| {
         occurrences.into_par_iter()
@@ -476,6 +955,76 @@ mod tests {
         (a - b).abs() < epsilon
     }
 
+    fn relative_error(value: f64, reference: f64) -> f64 {
+        if reference == 0.0 { value.abs() } else { ((value - reference) / reference).abs() }
+    }
+
+    // (x, erf(x), erfc(x)) from the C library
+    const ERF_TABLE: [(f64, f64, f64); 25] = [
+        (-6.0, -1.0, 2.0),
+        (-5.0, -0.9999999999984626, 1.9999999999984626),
+        (-4.0, -0.9999999845827421, 1.999999984582742),
+        (-3.0, -0.9999779095030014, 1.9999779095030015),
+        (-2.5, -0.999593047982555, 1.999593047982555),
+        (-2.0, -0.9953222650189527, 1.9953222650189528),
+        (-1.5, -0.9661051464753108, 1.9661051464753108),
+        (-1.0, -0.8427007929497149, 1.842700792949715),
+        (-0.75, -0.7111556336535151, 1.7111556336535152),
+        (-0.5, -0.5204998778130465, 1.5204998778130465),
+        (-0.25, -0.2763263901682369, 1.276326390168237),
+        (-0.1, -0.1124629160182849, 1.1124629160182848),
+        (0.0, 0.0, 1.0),
+        (0.1, 0.1124629160182849, 0.8875370839817152),
+        (0.25, 0.2763263901682369, 0.7236736098317631),
+        (0.5, 0.5204998778130465, 0.4795001221869535),
+        (0.75, 0.7111556336535151, 0.28884436634648486),
+        (1.0, 0.8427007929497149, 0.15729920705028513),
+        (1.5, 0.9661051464753108, 0.033894853524689274),
+        (2.0, 0.9953222650189527, 0.004677734981047265),
+        (2.5, 0.999593047982555, 0.0004069520174449589),
+        (3.0, 0.9999779095030014, 2.2090496998585438e-05),
+        (4.0, 0.9999999845827421, 1.541725790028002e-08),
+        (5.0, 0.9999999999984626, 1.5374597944280351e-12),
+        (6.0, 1.0, 2.1519736712498916e-17),
+    ];
+
+    #[test]
+    fn test_erf_erfc_tabulated() {
+        for (x, erf_ref, erfc_ref) in ERF_TABLE {
+            assert!(relative_error(erf(x), erf_ref) < 1e-14, "erf({x}) = {}, expected {erf_ref}", erf(x));
+            assert!(relative_error(erfc(x), erfc_ref) < 1e-13, "erfc({x}) = {}, expected {erfc_ref}", erfc(x));
+        }
+    }
+
+    #[test]
+    fn test_erf_properties() {
+        for i in -600..=600 {
+            let x = i as f64 / 100.0;
+            // odd symmetry, erf + erfc = 1 and erfc(-x) = 2 - erfc(x)
+            assert_eq!(erf(-x), -erf(x));
+            assert!(approx_eq(erf(x) + erfc(x), 1.0, 1e-15));
+            assert!(approx_eq(erfc(-x), 2.0 - erfc(x), 1e-15));
+            assert!(relative_error(erfcx(x), (x * x).exp() * erfc(x)) < 1e-13);
+            // erfc is monotone decreasing, strictly until it rounds to 2 below -5, and never exactly zero on [-6, 6]
+            assert!(erfc(x + 0.01) <= erfc(x));
+            assert!(x <= -5.0 || erfc(x + 0.01) < erfc(x));
+            assert!(erfc(x) > 0.0);
+        }
+        // erfcx stays finite where erfc underflows
+        assert!(relative_error(erfcx(30.0), 0.01879588886141769) < 1e-12);
+    }
+
+    #[test]
+    fn test_normal_cdf_tails() {
+        assert!(relative_error(custom_cdf_normal(-8.0, 0.0, 1.0), 6.220960574271819e-16) < 1e-12);
+        assert!(relative_error(custom_cdf_normal(-12.0, 0.0, 1.0), 1.776482112077702e-33) < 1e-12);
+        // both tails keep the mass of an interval far from the mean
+        let lower = accumulated_intensity_cdf_normal(-9.0, -8.0, 0.0, 1.0);
+        let upper = accumulated_intensity_cdf_normal(8.0, 9.0, 0.0, 1.0);
+        assert!(relative_error(lower, 6.219831985865866e-16) < 1e-10);
+        assert!(relative_error(upper, 6.219831985865866e-16) < 1e-10);
+    }
+
     #[test]
     fn test_normal_cdf_range() {
         let mean = 0.0;
@@ -550,7 +1099,7 @@ mod tests {
         // We'll pretend each frame index i runs from i-1 to i in real-time
         let mut time_map = HashMap::new();
         for i in 1..=5 {
-            time_map.insert(i as i32, i as f64);
+            time_map.insert(i, i as f64);
         }
 
         // Suppose we only have two frames to check
@@ -620,4 +1169,115 @@ mod tests {
         );
         assert_eq!(res_abundances.len(), 2, "Should produce 2 sets of abundances");
     }
+
+    /// EMG density, through erfcx where exp(lambda^2 sigma^2 / 2) overflows and erfc underflows
+    fn emg_density(x: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
+        let u = x - mu;
+        let z = (lambda * sigma - u / sigma) / SQRT_2;
+        let density = emg(x, mu, sigma, lambda);
+        if density.is_finite() && z <= 20.0 {
+            density
+        } else {
+            lambda / 2.0 * erfcx(z) * (-u * u / (2.0 * sigma * sigma)).exp()
+        }
+    }
+
+    /// numerical integral of the density, composite adaptive Simpson on pieces narrower than the peak
+    fn emg_mass_reference(lower: f64, upper: f64, mu: f64, sigma: f64, lambda: f64) -> f64 {
+        let width = sigma.min(1.0 / lambda) / 2.0;
+        let pieces = ((upper - lower) / width).ceil().max(1.0) as usize;
+        let step = (upper - lower) / pieces as f64;
+        (0..pieces)
+            .map(|i| {
+                let a = lower + i as f64 * step;
+                adaptive_simpson(&|x| emg_density(x, mu, sigma, lambda), a, a + step, 1e-12, 30)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_emg_cdf_range_matches_integrated_density() {
+        let mut erfcx_branch = 0;
+        for mu in [0.0, 100.0, 1500.0] {
+            for sigma in [0.2, 1.0, 5.0] {
+                // lambda * sigma up to 250, where exp(lambda^2 sigma^2 / 2) overflows
+                for lambda in [0.01, 0.1, 1.0, 10.0, 50.0] {
+                    let cdf = EmgCdf::new(mu, sigma, lambda);
+                    let tail = mu + 1.0 / lambda;
+                    let intervals = [
+                        (mu - 3.0 * sigma, mu),
+                        (mu - sigma, mu + sigma),
+                        (mu, tail + 2.0 * sigma),
+                        (tail, tail + 3.0 / lambda),
+                        (mu - 20.0 * sigma - 2.0, mu + 60.0 * sigma),
+                        (mu - 8.0 * sigma, mu - 4.0 * sigma),
+                    ];
+                    for (lower, upper) in intervals {
+                        if (lambda * sigma - (lower - mu) / sigma) / SQRT_2 > 0.0 {
+                            erfcx_branch += 1;
+                        }
+                        let p = cdf.cdf_range(lower, upper);
+                        let reference = emg_mass_reference(lower, upper, mu, sigma, lambda);
+                        assert!(
+                            approx_eq(p, reference, 1e-6),
+                            "mu {mu}, sigma {sigma}, lambda {lambda}, [{lower}, {upper}]: {p} != {reference}"
+                        );
+                    }
+                }
+            }
+        }
+        assert!(erfcx_branch > 0);
+    }
+
+    #[test]
+    fn test_closest_frame_binary_search_matches_linear_search() {
+        let retention_times = [0.5, 1.0, 1.0, 1.0, 2.0, 3.5, 3.5, 4.0];
+        assert!(is_ascending(&retention_times));
+        for i in -10..=50 {
+            let rt = i as f64 / 10.0;
+            assert_eq!(closest_frame(&retention_times, true, rt), closest_frame(&retention_times, false, rt), "rt {rt}");
+        }
+        assert_eq!(closest_frame(&[], true, 1.0), 0);
+    }
+
+    #[test]
+    fn test_bounds_emg_extreme_lambda() {
+        // a sharp exponential component leaves a gaussian peak
+        let (lower, upper) = calculate_bounds_emg(100.0, 2.0, 1e3, 0.001, 0.99, 20.0, 60.0, None, None).unwrap();
+        assert!(lower <= upper);
+        assert!(emg_cdf_range(lower, upper, 100.0, 2.0, 1e3, None) >= 0.99);
+        assert!(approx_eq(upper, 100.0 + 2.3263 * 2.0, 0.01));
+
+        // a tail of mean 1000 s is out of reach of the initial window of 60 sigma
+        let error = calculate_bounds_emg(100.0, 1.0, 1e-3, 0.001, 0.99, 20.0, 60.0, None, None).unwrap_err();
+        assert!(error.achieved < error.target);
+        assert!(error.lower <= error.upper);
+
+        // but within reach once the window may grow far enough
+        let (lower, upper) = calculate_bounds_emg(100.0, 1.0, 1e-3, 0.001, 0.99, 20.0, 60.0, Some(128.0), None).unwrap();
+        assert!(lower <= upper);
+        assert!(emg_cdf_range(lower, upper, 100.0, 1.0, 1e-3, None) >= 0.99);
+        assert!(upper - 100.0 > 4000.0);
+    }
+
+    #[test]
+    fn test_bounds_emg_trivial_target() {
+        let (lower, upper) = calculate_bounds_emg(100.0, 1.0, 0.5, 0.01, 0.0, 20.0, 60.0, None, None).unwrap();
+        assert_eq!(lower, upper);
+
+        let (lower, upper) = calculate_bounds_emg(100.0, 1.0, 0.5, 0.01, 1.0, 20.0, 60.0, None, None).unwrap();
+        assert!(lower <= upper);
+        assert_eq!(emg_cdf_range(lower, upper, 100.0, 1.0, 0.5, None), 1.0);
+    }
+
+    #[test]
+    fn test_frame_occurrence_emg_propagates_bounds_error() {
+        let retention_times: Vec<f64> = (0..1000).map(|i| i as f64).collect();
+        assert!(calculate_frame_occurrence_emg(&retention_times, 100.0, 1.0, 1e-3, 0.99, 0.001, None).is_err());
+
+        let occurrence = calculate_frame_occurrence_emg(&retention_times, 100.0, 1.0, 1.0, 0.99, 0.001, None).unwrap();
+        assert!(!occurrence.is_empty());
+        assert!(occurrence.windows(2).all(|w| w[1] == w[0] + 1));
+        assert!(occurrence.contains(&101));
+    }
 }
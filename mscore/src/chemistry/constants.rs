@@ -1,6 +1,7 @@
 // Purpose: To store constants that are used in the program
 pub const MASS_PROTON: f64 = 1.007276466621; // Unified atomic mass unit
 pub const MASS_NEUTRON: f64 = 1.00866491595; // Unified atomic mass unit
+pub const C13_C12_MASS_DIFFERENCE: f64 = 1.0033548378; // Spacing of isotope peaks, mass of 13C minus 12C
 pub const MASS_ELECTRON: f64 = 0.00054857990946; // Unified atomic mass unit
 pub const MASS_WATER: f64 = 18.0105646863; // Unified atomic mass unit
 pub const MASS_CO: f64 = 27.994915; // Mass of CO (carbon monoxide)
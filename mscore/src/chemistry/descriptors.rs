@@ -0,0 +1,312 @@
+use rayon::prelude::*;
+use crate::parallel;
+
+use crate::chemistry::utility::unimod_sequence_to_tokens;
+
+/// unimod id of acetylation, removes the charge of the N-terminus
+const UNIMOD_ACETYL: u32 = 1;
+/// unimod id of carbamidomethylation, blocks the thiol of cysteine
+const UNIMOD_CARBAMIDOMETHYL: u32 = 4;
+/// unimod id of phosphorylation, adds a phosphate group with two acidic protons
+const UNIMOD_PHOSPHO: u32 = 21;
+
+/// pKa values of the ionizable groups of a peptide
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PkaSet {
+    pub n_terminus: f64,
+    pub c_terminus: f64,
+    pub lysine: f64,
+    pub arginine: f64,
+    pub histidine: f64,
+    pub aspartate: f64,
+    pub glutamate: f64,
+    pub cysteine: f64,
+    pub tyrosine: f64,
+    /// first and second pKa of a phosphate group
+    pub phosphate: (f64, f64),
+}
+
+impl PkaSet {
+    /// pKa values used by EMBOSS iep
+    pub const EMBOSS: PkaSet = PkaSet {
+        n_terminus: 8.6, c_terminus: 3.6, lysine: 10.8, arginine: 12.5, histidine: 6.5,
+        aspartate: 3.9, glutamate: 4.1, cysteine: 8.5, tyrosine: 10.1, phosphate: (1.2, 6.5),
+    };
+
+    /// pKa values of Lehninger, Principles of Biochemistry
+    pub const LEHNINGER: PkaSet = PkaSet {
+        n_terminus: 9.69, c_terminus: 2.34, lysine: 10.5, arginine: 12.4, histidine: 6.0,
+        aspartate: 3.86, glutamate: 4.25, cysteine: 8.33, tyrosine: 10.0, phosphate: (1.2, 6.5),
+    };
+
+    /// Get a pKa set by name
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - `emboss` or `lehninger`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::descriptors::PkaSet;
+    ///
+    /// assert_eq!(PkaSet::from_name("Lehninger"), Ok(PkaSet::LEHNINGER));
+    /// assert!(PkaSet::from_name("solomon").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "emboss" => Ok(PkaSet::EMBOSS),
+            "lehninger" => Ok(PkaSet::LEHNINGER),
+            _ => Err(format!("unknown pKa set {}, expected emboss or lehninger", name)),
+        }
+    }
+}
+
+impl Default for PkaSet {
+    fn default() -> Self {
+        PkaSet::EMBOSS
+    }
+}
+
+/// Residues of a sequence with their unimod ids, and the unimod ids of the N-terminus
+fn residues(sequence: &str) -> (Vec<(char, Vec<u32>)>, Vec<u32>) {
+    let mut residues = Vec::new();
+    let mut n_terminal_modifications = Vec::new();
+
+    for token in unimod_sequence_to_tokens(sequence, true) {
+        let modifications: Vec<u32> = token.split("[UNIMOD:").skip(1)
+            .filter_map(|part| part.trim_end_matches(']').parse().ok())
+            .collect();
+        match token.chars().next() {
+            Some(residue) if residue.is_ascii_alphabetic() => residues.push((residue, modifications)),
+            // modifications before the first residue are grouped with an empty residue
+            _ => n_terminal_modifications.extend(modifications),
+        }
+    }
+
+    (residues, n_terminal_modifications)
+}
+
+/// Net charge of a peptide at a pH by Henderson–Hasselbalch
+///
+/// Arguments:
+///
+/// * `sequence` - peptide sequence, may contain modifications in the format [UNIMOD:ID]
+/// * `ph` - pH of the solution
+/// * `pka` - pKa values of the ionizable groups
+///
+/// Returns:
+///
+/// * `f64` - net charge, phosphate groups add two acidic groups, carbamidomethylated cysteines and acetylated
+///   N-termini are not ionizable
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::descriptors::{charge_at_ph, PkaSet};
+///
+/// // N-terminus and lysine against C-terminus, two glutamates and one aspartate
+/// assert!((charge_at_ph("PEPTIDEK", 7.0, &PkaSet::EMBOSS) + 2.0).abs() < 0.1);
+/// assert!(charge_at_ph("PEPTIDEK", 1.0, &PkaSet::EMBOSS) > 1.9);
+/// ```
+pub fn charge_at_ph(sequence: &str, ph: f64, pka: &PkaSet) -> f64 {
+    let (residues, n_terminal_modifications) = residues(sequence);
+    let positive = |pka: f64| 1.0 / (1.0 + 10f64.powf(ph - pka));
+    let negative = |pka: f64| -1.0 / (1.0 + 10f64.powf(pka - ph));
+
+    let mut charge = negative(pka.c_terminus);
+    if !n_terminal_modifications.contains(&UNIMOD_ACETYL) {
+        charge += positive(pka.n_terminus);
+    }
+
+    for (residue, modifications) in residues.iter() {
+        let phospho = modifications.contains(&UNIMOD_PHOSPHO);
+        if phospho {
+            charge += negative(pka.phosphate.0) + negative(pka.phosphate.1);
+        }
+        charge += match residue {
+            'K' => positive(pka.lysine),
+            'R' => positive(pka.arginine),
+            'H' => positive(pka.histidine),
+            'D' => negative(pka.aspartate),
+            'E' => negative(pka.glutamate),
+            'C' if !modifications.contains(&UNIMOD_CARBAMIDOMETHYL) => negative(pka.cysteine),
+            'Y' if !phospho => negative(pka.tyrosine),
+            _ => 0.0,
+        };
+    }
+
+    charge
+}
+
+/// Isoelectric point of a peptide with the EMBOSS pKa values, see `isoelectric_point_with_pka`
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::descriptors::isoelectric_point;
+///
+/// // phosphorylation makes a peptide more acidic
+/// assert!(isoelectric_point("PEPT[UNIMOD:21]IDEK") < isoelectric_point("PEPTIDEK"));
+/// assert!(isoelectric_point("PEPTIDEK") < 7.0);
+/// assert!(isoelectric_point("KRAHGK") > 10.0);
+/// ```
+pub fn isoelectric_point(sequence: &str) -> f64 {
+    isoelectric_point_with_pka(sequence, &PkaSet::EMBOSS)
+}
+
+/// Isoelectric point of a peptide, the pH at which its net charge is zero, found by bisection
+///
+/// Arguments:
+///
+/// * `sequence` - peptide sequence, may contain modifications in the format [UNIMOD:ID]
+/// * `pka` - pKa values of the ionizable groups
+///
+/// Returns:
+///
+/// * `f64` - isoelectric point between 0 and 14, accurate to 1e-4
+///
+pub fn isoelectric_point_with_pka(sequence: &str, pka: &PkaSet) -> f64 {
+    let (mut low, mut high) = (0.0, 14.0);
+    // the net charge falls monotonically with the pH
+    while high - low > 1e-4 {
+        let mid = (low + high) / 2.0;
+        if charge_at_ph(sequence, mid, pka) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Kyte-Doolittle hydropathy of a residue, selenocysteine is treated like cysteine
+fn hydropathy(residue: char) -> f64 {
+    match residue {
+        'A' => 1.8, 'R' => -4.5, 'N' => -3.5, 'D' => -3.5, 'C' | 'U' => 2.5,
+        'Q' => -3.5, 'E' => -3.5, 'G' => -0.4, 'H' => -3.2, 'I' => 4.5,
+        'L' => 3.8, 'K' => -3.9, 'M' => 1.9, 'F' => 2.8, 'P' => -1.6,
+        'S' => -0.8, 'T' => -0.7, 'W' => -0.9, 'Y' => -1.3, 'V' => 4.2,
+        _ => 0.0,
+    }
+}
+
+/// Fraction of residues of a sequence that match `predicate`, 0 for empty sequences
+fn residue_fraction(sequence: &str, predicate: impl Fn(char) -> bool) -> f64 {
+    let (residues, _) = residues(sequence);
+    if residues.is_empty() {
+        return 0.0;
+    }
+    residues.iter().filter(|(residue, _)| predicate(*residue)).count() as f64 / residues.len() as f64
+}
+
+/// Grand average of hydropathy (GRAVY), the mean Kyte-Doolittle hydropathy of the residues
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::descriptors::gravy;
+///
+/// assert!((gravy("AIV") - 3.5).abs() < 1e-9);
+/// assert_eq!(gravy("M[UNIMOD:35]K"), gravy("MK"));
+/// ```
+pub fn gravy(sequence: &str) -> f64 {
+    let (residues, _) = residues(sequence);
+    if residues.is_empty() {
+        return 0.0;
+    }
+    residues.iter().map(|(residue, _)| hydropathy(*residue)).sum::<f64>() / residues.len() as f64
+}
+
+/// Aromaticity, the fraction of phenylalanine, tryptophan and tyrosine residues
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::descriptors::aromaticity;
+///
+/// assert_eq!(aromaticity("FWYK"), 0.75);
+/// ```
+pub fn aromaticity(sequence: &str) -> f64 {
+    residue_fraction(sequence, |residue| matches!(residue, 'F' | 'W' | 'Y'))
+}
+
+/// Aliphatic index of Ikai, the relative volume of aliphatic side chains:
+/// 100 * (x(A) + 2.9 * x(V) + 3.9 * (x(I) + x(L))) with x the fraction of each residue
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::descriptors::aliphatic_index;
+///
+/// assert!((aliphatic_index("AVIL") - 292.5).abs() < 1e-9);
+/// ```
+pub fn aliphatic_index(sequence: &str) -> f64 {
+    let fraction = |residue: char| residue_fraction(sequence, |r| r == residue);
+    100.0 * (fraction('A') + 2.9 * fraction('V') + 3.9 * (fraction('I') + fraction('L')))
+}
+
+/// Apply a descriptor to many sequences in parallel
+fn descriptor_par(sequences: Vec<&str>, num_threads: usize, descriptor: impl Fn(&str) -> f64 + Sync + Send) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        sequences.par_iter().map(|sequence| descriptor(sequence)).collect()
+    })
+}
+
+/// Isoelectric points of many peptides in parallel, see `isoelectric_point_with_pka`
+pub fn isoelectric_point_par(sequences: Vec<&str>, pka: &PkaSet, num_threads: usize) -> Vec<f64> {
+    descriptor_par(sequences, num_threads, |sequence| isoelectric_point_with_pka(sequence, pka))
+}
+
+/// GRAVY of many peptides in parallel, see `gravy`
+pub fn gravy_par(sequences: Vec<&str>, num_threads: usize) -> Vec<f64> {
+    descriptor_par(sequences, num_threads, gravy)
+}
+
+/// Aromaticity of many peptides in parallel, see `aromaticity`
+pub fn aromaticity_par(sequences: Vec<&str>, num_threads: usize) -> Vec<f64> {
+    descriptor_par(sequences, num_threads, aromaticity)
+}
+
+/// Aliphatic index of many peptides in parallel, see `aliphatic_index`
+pub fn aliphatic_index_par(sequences: Vec<&str>, num_threads: usize) -> Vec<f64> {
+    descriptor_par(sequences, num_threads, aliphatic_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isoelectric_point_textbook() {
+        let pka = PkaSet::LEHNINGER;
+        let pi = |sequence: &str| isoelectric_point_with_pka(sequence, &pka);
+
+        // without ionizable side chains, the pI is the mean of the terminal pKa values
+        assert!((pi("G") - (2.34 + 9.69) / 2.0).abs() < 1e-3);
+        assert!((pi("GAG") - pi("G")).abs() < 1e-3);
+        // one basic side chain: the mean of the two highest pKa values, one acidic: of the two lowest
+        assert!((pi("K") - (9.69 + 10.5) / 2.0).abs() < 1e-3);
+        assert!((pi("E") - (2.34 + 4.25) / 2.0).abs() < 1e-3);
+        // blocked groups are not ionizable
+        assert!((pi("[UNIMOD:1]GK") - (10.5 + 2.34) / 2.0).abs() < 1e-3);
+        assert!((pi("GC[UNIMOD:4]G") - pi("G")).abs() < 1e-3);
+
+        let phospho = pi("GS[UNIMOD:21]G");
+        assert!(phospho < pi("GSG"));
+        assert!((charge_at_ph("GS[UNIMOD:21]G", phospho, &pka)).abs() < 1e-3);
+
+        let pis = isoelectric_point_par(vec!["G", "K", "E"], &pka, 2);
+        assert_eq!(pis, vec![pi("G"), pi("K"), pi("E")]);
+    }
+
+    #[test]
+    fn test_descriptors_par() {
+        let sequences = vec!["AVIL", "FWYK", "C[UNIMOD:4]PEPTIDEK"];
+        assert_eq!(gravy_par(sequences.clone(), 2), sequences.iter().map(|s| gravy(s)).collect::<Vec<_>>());
+        assert_eq!(aromaticity_par(sequences.clone(), 2), vec![0.0, 0.75, 0.0]);
+        assert_eq!(aliphatic_index_par(sequences.clone(), 2)[1], 0.0);
+        assert_eq!(gravy(""), 0.0);
+    }
+}
@@ -54,6 +54,10 @@ pub fn atomic_weights_mono_isotopic() -> HashMap<&'static str, f64> {
     map.insert("Ge", 73.9211778);
     map.insert("As", 74.9215965);
     map.insert("Se", 79.9165218);
+    // pure heavy isotopes of labeled compositions
+    map.insert("2H", 2.01410177812);
+    map.insert("13C", 13.00335483507);
+    map.insert("15N", 15.00010889888);
     map.insert("Br", 78.9183376);
     map.insert("Kr", 83.911507);
     map.insert("Rb", 84.9117893);
@@ -196,6 +200,10 @@ pub fn atoms_isotopic_weights() -> HashMap<&'static str, Vec<f64>> {
     map.insert("Ge", vec![73.9211778, 71.922075826, 72.923458956, 73.921177761, 75.921402726]);
     map.insert("As", vec![74.9215965]);
     map.insert("Se", vec![79.9165218, 73.9224764, 75.9192136, 76.9199140, 77.9173095, 79.9165218, 81.9166995]);
+    // pure heavy isotopes of labeled compositions
+    map.insert("2H", vec![2.01410177812]);
+    map.insert("13C", vec![13.00335483507]);
+    map.insert("15N", vec![15.00010889888]);
 
     map
 }
@@ -256,6 +264,10 @@ pub fn isotopic_abundance() -> HashMap<&'static str, Vec<f64>> {
     map.insert("Ge", vec![0.2052, 0.2745, 0.0775, 0.3652, 0.0775]);
     map.insert("As", vec![1.0]);
     map.insert("Se", vec![0.0089, 0.0937, 0.0763, 0.2377, 0.4961, 0.0873]);
+    // pure heavy isotopes of labeled compositions, label incorporation is assumed complete
+    map.insert("2H", vec![1.0]);
+    map.insert("13C", vec![1.0]);
+    map.insert("15N", vec![1.0]);
 
     map
 }
\ No newline at end of file
@@ -1,4 +1,10 @@
-use crate::chemistry::constants::MASS_PROTON;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::chemistry::constants::{MASS_ELECTRON, MASS_PROTON};
+use crate::chemistry::elements::{atomic_weights_mono_isotopic, atoms_isotopic_weights, isotopic_abundance};
 
 /// convert 1 over reduced ion mobility (1/k0) to CCS
 ///
@@ -95,4 +101,483 @@ pub fn ccs_to_one_over_reduced_mobility(
 /// ```
 pub fn calculate_mz(monoisotopic_mass: f64, charge: i32) -> f64 {
     (monoisotopic_mass + charge as f64 * MASS_PROTON) / charge as f64
-}
\ No newline at end of file
+}
+
+/// Ion species formed from a neutral molecule M by adding or removing atoms, e.g. [M+Na]+ or [M-H]-
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Adduct {
+    pub name: String,
+    /// atoms added to the neutral molecule, removed atoms have negative counts
+    pub composition: BTreeMap<String, i32>,
+    /// signed charge of the ion
+    pub charge: i32,
+}
+
+impl Adduct {
+    /// Custom adduct from the atoms added to the neutral molecule and the charge of the ion
+    ///
+    /// Arguments:
+    ///
+    /// * `composition` - atoms added to the neutral molecule, removed atoms have negative counts
+    /// * `charge` - signed charge of the ion, must not be zero
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use mscore::chemistry::formulas::Adduct;
+    ///
+    /// let adduct = Adduct::new(BTreeMap::from([("H".to_string(), 1), ("Na".to_string(), 1)]), 2).unwrap();
+    /// assert_eq!(adduct, Adduct::from_name("[M+H+Na]2+").unwrap());
+    /// assert!(Adduct::new(BTreeMap::new(), 0).is_err());
+    /// ```
+    pub fn new(composition: BTreeMap<String, i32>, charge: i32) -> Result<Self, String> {
+        if charge == 0 {
+            return Err("adduct charge must not be zero".to_string());
+        }
+        let atomic_weights = atomic_weights_mono_isotopic();
+        if let Some(element) = composition.keys().find(|element| !atomic_weights.contains_key(element.as_str())) {
+            return Err(format!("Unknown element: {}", element));
+        }
+
+        let composition: BTreeMap<String, i32> = composition.into_iter().filter(|(_, count)| *count != 0).collect();
+        let terms: String = composition.iter().map(|(element, count)| {
+            let sign = if *count > 0 { '+' } else { '-' };
+            match count.abs() {
+                1 => format!("{}{}", sign, element),
+                n => format!("{}{}{}", sign, n, element),
+            }
+        }).collect();
+        let sign = if charge > 0 { '+' } else { '-' };
+        let name = match charge.abs() {
+            1 => format!("[M{}]{}", terms, sign),
+            n => format!("[M{}]{}{}", terms, n, sign),
+        };
+
+        Ok(Adduct { name, composition, charge })
+    }
+
+    /// Parse an adduct in the usual notation, [M, terms of the form +Na, -H or +2H, ], charge and sign
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - adduct notation, e.g. [M+H]+, [M+2H]2+, [M+Na]+, [M+K]+, [M+NH4]+ or [M-H]-
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::formulas::Adduct;
+    ///
+    /// let ammonium = Adduct::from_name("[M+NH4]+").unwrap();
+    /// assert_eq!(ammonium.composition.get("N"), Some(&1));
+    /// assert_eq!(ammonium.composition.get("H"), Some(&4));
+    /// assert_eq!(Adduct::from_name("[M-2H]2-").unwrap().charge, -2);
+    /// assert!(Adduct::from_name("M+H").is_err());
+    /// assert!(Adduct::from_name("[M+Xy]+").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid adduct {}, expected e.g. [M+H]+, [M+2H]2+, [M+Na]+ or [M-H]-", name);
+
+        let (species, charge) = name.trim().strip_prefix('[').and_then(|rest| rest.split_once(']')).ok_or_else(invalid)?;
+        let mut terms = species.strip_prefix('M').ok_or_else(invalid)?;
+
+        let (magnitude, sign) = charge.split_at(charge.len().saturating_sub(1));
+        let sign = match sign {
+            "+" => 1,
+            "-" => -1,
+            _ => return Err(invalid()),
+        };
+        let magnitude: i32 = if magnitude.is_empty() { 1 } else { magnitude.parse().map_err(|_| invalid())? };
+
+        let mut composition = BTreeMap::new();
+        while let Some(term_sign) = terms.chars().next() {
+            let term_sign = match term_sign {
+                '+' => 1,
+                '-' => -1,
+                _ => return Err(invalid()),
+            };
+            let end = terms[1..].find(['+', '-']).map_or(terms.len(), |i| i + 1);
+            let term = &terms[1..end];
+            terms = &terms[end..];
+
+            let digits = term.chars().take_while(|c| c.is_ascii_digit()).count();
+            let multiplier: i32 = if digits == 0 { 1 } else { term[..digits].parse().map_err(|_| invalid())? };
+            if term[digits..].is_empty() {
+                return Err(invalid());
+            }
+            for (element, count) in parse_formula(&term[digits..]).map_err(|e| e.to_string())? {
+                *composition.entry(element).or_insert(0) += term_sign * multiplier * count;
+            }
+        }
+
+        Adduct::new(composition, sign * magnitude)
+    }
+
+    /// [M+zH]z+ for positive and [M-zH]z- for negative charges
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::formulas::Adduct;
+    ///
+    /// assert_eq!(Adduct::protonated(2).name, "[M+2H]2+");
+    /// assert_eq!(Adduct::protonated(-1).name, "[M-H]-");
+    /// ```
+    pub fn protonated(charge: i32) -> Self {
+        Adduct::new(BTreeMap::from([("H".to_string(), charge)]), charge).expect("protonation of a nonzero charge")
+    }
+
+    /// Mass added to the neutral molecule, the mass of the added atoms minus the electrons of the charge
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::constants::MASS_PROTON;
+    /// use mscore::chemistry::formulas::Adduct;
+    ///
+    /// assert!((Adduct::protonated(1).mass_shift() - MASS_PROTON).abs() < 1e-7);
+    /// assert!((Adduct::from_name("[M+Na]+").unwrap().mass_shift() - 22.989221).abs() < 1e-6);
+    /// ```
+    pub fn mass_shift(&self) -> f64 {
+        let atomic_weights = atomic_weights_mono_isotopic();
+        let mass: f64 = self.composition.iter().map(|(element, count)| atomic_weights[element.as_str()] * *count as f64).sum();
+        mass - self.charge as f64 * MASS_ELECTRON
+    }
+}
+
+/// calculate the m/z of an ion formed as an adduct of a neutral molecule
+///
+/// Arguments:
+///
+/// * `monoisotopic_mass` - monoisotopic mass of the neutral molecule
+/// * `adduct` - ion species
+///
+/// Returns:
+///
+/// * `mz` - mass-over-charge of the ion, positive for negative ions as well
+///
+/// # Examples
+///
+/// ```
+/// use mscore::chemistry::formulas::{calculate_mz, calculate_mz_with_adduct, Adduct};
+///
+/// let protonated = calculate_mz_with_adduct(1000.0, &Adduct::protonated(2));
+/// assert!((protonated - calculate_mz(1000.0, 2)).abs() < 1e-7);
+///
+/// // a sodium adduct is 21.9819 Da heavier than the protonated molecule
+/// let sodiated = calculate_mz_with_adduct(1000.0, &Adduct::from_name("[M+Na]+").unwrap());
+/// assert!((sodiated - calculate_mz(1000.0, 1) - 21.98194).abs() < 1e-5);
+/// ```
+pub fn calculate_mz_with_adduct(monoisotopic_mass: f64, adduct: &Adduct) -> f64 {
+    (monoisotopic_mass + adduct.mass_shift()) / adduct.charge.abs() as f64
+}
+
+/// calculate the monoisotopic mass of the neutral molecule from the m/z of an adduct ion
+///
+/// Arguments:
+///
+/// * `mz` - mass-over-charge of the ion
+/// * `adduct` - ion species
+///
+/// Returns:
+///
+/// * `mass` - monoisotopic mass of the neutral molecule
+///
+/// # Examples
+///
+/// ```
+/// use mscore::chemistry::formulas::{calculate_mz_with_adduct, neutral_mass_from_mz, Adduct};
+///
+/// let adduct = Adduct::from_name("[M-2H]2-").unwrap();
+/// let mass = neutral_mass_from_mz(calculate_mz_with_adduct(1000.0, &adduct), &adduct);
+/// assert!((mass - 1000.0).abs() < 1e-9);
+/// ```
+pub fn neutral_mass_from_mz(mz: f64, adduct: &Adduct) -> f64 {
+    mz * adduct.charge.abs() as f64 - adduct.mass_shift()
+}
+
+/// Error of `parse_formula`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormulaError {
+    /// element symbol without mono isotopic weight
+    UnknownElement(String),
+    /// isotope in brackets, e.g. `[13C]`, that is not one of the heavy isotopes `2H`, `13C` or `15N`
+    UnknownIsotope(String),
+    /// character at a byte position of the formula that does not start an element, isotope or count
+    InvalidSyntax { formula: String, position: usize },
+}
+
+impl Display for FormulaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormulaError::UnknownElement(element) => write!(f, "Unknown element: {}", element),
+            FormulaError::UnknownIsotope(isotope) => write!(f, "Unknown isotope: [{}]", isotope),
+            FormulaError::InvalidSyntax { formula, position } => {
+                write!(f, "invalid formula {}: unexpected character at position {}", formula, position)
+            }
+        }
+    }
+}
+
+impl Error for FormulaError {}
+
+/// Parse an elemental formula into an atomic composition
+///
+/// Elements are followed by an optional count, which may be negative for losses, e.g. `H-2O-1`, or be written in
+/// parentheses as in Unimod, e.g. `H(-1)N(-1)O`. Heavy isotopes are written in brackets, e.g. `[13C]2`, and are kept
+/// as separate elements `2H`, `13C` and `15N`, as by `LabelScheme`. Whitespace between elements is ignored, counts of
+/// repeated elements are summed and elements with a total count of 0 are dropped.
+///
+/// Arguments:
+///
+/// * `formula` - elemental formula, e.g. `C6H12O6`
+///
+/// Returns:
+///
+/// * `Result<HashMap<String, i32>, FormulaError>` - atomic composition
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use mscore::chemistry::formulas::{parse_formula, FormulaError};
+///
+/// assert_eq!(parse_formula("C6H12O6").unwrap(), HashMap::from([("C".to_string(), 6), ("H".to_string(), 12), ("O".to_string(), 6)]));
+/// assert_eq!(parse_formula("H-2 O-1").unwrap(), HashMap::from([("H".to_string(), -2), ("O".to_string(), -1)]));
+/// assert_eq!(parse_formula("C-2[13C]2").unwrap(), HashMap::from([("C".to_string(), -2), ("13C".to_string(), 2)]));
+/// assert_eq!(parse_formula("Xy2"), Err(FormulaError::UnknownElement("Xy".to_string())));
+/// assert_eq!(parse_formula("[14C]"), Err(FormulaError::UnknownIsotope("14C".to_string())));
+/// assert!(parse_formula("C6+H").is_err());
+/// ```
+pub fn parse_formula(formula: &str) -> Result<HashMap<String, i32>, FormulaError> {
+    let atomic_weights = atomic_weights_mono_isotopic();
+    let bytes = formula.as_bytes();
+    let invalid = |position: usize| FormulaError::InvalidSyntax { formula: formula.to_string(), position };
+    // end of the run of bytes matching `predicate` starting at `start`
+    let scan = |start: usize, predicate: fn(&u8) -> bool| {
+        start + bytes[start..].iter().take_while(|b| predicate(b)).count()
+    };
+
+    let mut composition: HashMap<String, i32> = HashMap::new();
+    let mut position = 0;
+
+    while position < bytes.len() {
+        if bytes[position].is_ascii_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        // element symbol or isotope in brackets
+        let element = if bytes[position] == b'[' {
+            let mass_number_end = scan(position + 1, u8::is_ascii_digit);
+            if mass_number_end == position + 1 || !bytes.get(mass_number_end).is_some_and(u8::is_ascii_uppercase) {
+                return Err(invalid(mass_number_end));
+            }
+            let symbol_end = scan(mass_number_end + 1, u8::is_ascii_lowercase);
+            if bytes.get(symbol_end) != Some(&b']') {
+                return Err(invalid(symbol_end));
+            }
+            let isotope = &formula[position + 1..symbol_end];
+            if !atomic_weights.contains_key(isotope) {
+                return Err(FormulaError::UnknownIsotope(isotope.to_string()));
+            }
+            position = symbol_end + 1;
+            isotope
+        } else if bytes[position].is_ascii_uppercase() {
+            let symbol_end = scan(position + 1, u8::is_ascii_lowercase);
+            let element = &formula[position..symbol_end];
+            if !atomic_weights.contains_key(element) {
+                return Err(FormulaError::UnknownElement(element.to_string()));
+            }
+            position = symbol_end;
+            element
+        } else {
+            return Err(invalid(position));
+        };
+
+        // optional count, signed and optionally in parentheses
+        let parenthesized = bytes.get(position) == Some(&b'(');
+        let count_start = position + parenthesized as usize;
+        let sign_end = count_start + (bytes.get(count_start) == Some(&b'-')) as usize;
+        let count_end = scan(sign_end, u8::is_ascii_digit);
+        let count = if count_end > sign_end {
+            formula[count_start..count_end].parse::<i32>().map_err(|_| invalid(count_start))?
+        } else if sign_end > count_start || parenthesized {
+            return Err(invalid(count_end));
+        } else {
+            1
+        };
+        position = count_end;
+        if parenthesized {
+            if bytes.get(position) != Some(&b')') {
+                return Err(invalid(position));
+            }
+            position += 1;
+        }
+
+        *composition.entry(element.to_string()).or_insert(0) += count;
+    }
+
+    composition.retain(|_, count| *count != 0);
+    Ok(composition)
+}
+
+/// element symbol and mass number of an element or heavy isotope, 0 for natural elements
+fn element_and_mass_number(element: &str) -> (&str, u32) {
+    let digits = element.chars().take_while(|c| c.is_ascii_digit()).count();
+    (&element[digits..], element[..digits].parse().unwrap_or(0))
+}
+
+/// Formula of an atomic composition in Hill notation
+///
+/// With carbon, C and H come first and all other elements follow alphabetically, without carbon all elements are
+/// ordered alphabetically. Heavy isotopes are written in brackets after their element, counts of 1 are omitted and
+/// elements with a count of 0 are dropped. The formula parses back to the composition with `parse_formula`.
+///
+/// Arguments:
+///
+/// * `composition` - atomic composition, heavy isotopes as separate elements `2H`, `13C` and `15N`
+///
+/// Returns:
+///
+/// * `String` - formula, empty for an empty composition
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use mscore::chemistry::formulas::{parse_formula, to_hill_notation};
+///
+/// assert_eq!(to_hill_notation(&HashMap::from([("O", 6), ("H", 12), ("C", 6)])), "C6H12O6");
+/// assert_eq!(to_hill_notation(&HashMap::from([("O", 1), ("H", 2)])), "H2O");
+/// assert_eq!(to_hill_notation(&HashMap::from([("15N", 2), ("N", -2), ("13C", 6), ("C", -6)])), "C-6[13C]6N-2[15N]2");
+///
+/// let composition = parse_formula("NaCl").unwrap();
+/// assert_eq!(to_hill_notation(&composition), "ClNa");
+/// assert_eq!(parse_formula(&to_hill_notation(&composition)).unwrap(), composition);
+/// ```
+pub fn to_hill_notation<K: AsRef<str>>(composition: &HashMap<K, i32>) -> String {
+    let has_carbon = composition.iter().any(|(element, &count)| count != 0 && element_and_mass_number(element.as_ref()).0 == "C");
+    let rank = |symbol: &str| match (has_carbon, symbol) {
+        (true, "C") => 0,
+        (true, "H") => 1,
+        _ => 2,
+    };
+
+    let mut elements: Vec<(&str, i32)> = composition.iter()
+        .filter(|(_, &count)| count != 0)
+        .map(|(element, &count)| (element.as_ref(), count))
+        .collect();
+    elements.sort_by_key(|&(element, _)| {
+        let (symbol, mass_number) = element_and_mass_number(element);
+        (rank(symbol), symbol, mass_number)
+    });
+
+    elements.into_iter().map(|(element, count)| {
+        let element = match element_and_mass_number(element).1 {
+            0 => element.to_string(),
+            _ => format!("[{}]", element),
+        };
+        match count {
+            1 => element,
+            count => format!("{}{}", element, count),
+        }
+    }).collect()
+}
+
+/// Monoisotopic mass of an atomic composition, heavy isotopes contribute their own mass
+///
+/// Panics if the composition contains an element without mono isotopic weight, compositions of `parse_formula`
+/// only contain known elements.
+///
+/// # Examples
+///
+/// ```
+/// use mscore::chemistry::constants::MASS_WATER;
+/// use mscore::chemistry::formulas::{monoisotopic_mass_of, parse_formula};
+///
+/// assert!((monoisotopic_mass_of(&parse_formula("H2O").unwrap()) - MASS_WATER).abs() < 1e-6);
+/// assert!((monoisotopic_mass_of(&parse_formula("C-6[13C]6").unwrap()) - 6.0 * 1.00335483507).abs() < 1e-9);
+/// ```
+pub fn monoisotopic_mass_of<K: AsRef<str>>(composition: &HashMap<K, i32>) -> f64 {
+    let atomic_weights = atomic_weights_mono_isotopic();
+    composition.iter().map(|(element, &count)| {
+        let weight = atomic_weights.get(element.as_ref())
+            .unwrap_or_else(|| panic!("Unknown element: {}", element.as_ref()));
+        weight * count as f64
+    }).sum()
+}
+
+/// Average mass of an atomic composition from the natural isotope abundances, heavy isotopes contribute their own mass
+///
+/// Panics if the composition contains an element without isotope abundances, compositions of `parse_formula`
+/// only contain known elements.
+///
+/// # Examples
+///
+/// ```
+/// use mscore::chemistry::formulas::{average_mass_of, monoisotopic_mass_of, parse_formula};
+///
+/// let water = parse_formula("H2O").unwrap();
+/// assert!((average_mass_of(&water) - 18.0153).abs() < 1e-3);
+/// assert!(average_mass_of(&water) > monoisotopic_mass_of(&water));
+/// ```
+pub fn average_mass_of<K: AsRef<str>>(composition: &HashMap<K, i32>) -> f64 {
+    let weights = atoms_isotopic_weights();
+    let abundances = isotopic_abundance();
+    composition.iter().map(|(element, &count)| {
+        let element = element.as_ref();
+        let (weights, abundances) = weights.get(element).zip(abundances.get(element))
+            .unwrap_or_else(|| panic!("Unknown element: {}", element));
+        let average: f64 = weights.iter().zip(abundances).map(|(weight, abundance)| weight * abundance).sum();
+        average * count as f64
+    }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formula_round_trip() {
+        for formula in ["C6H12O6", "H2O", "C2H3NO", "CH-2N2O3PS", "C-6[13C]6H-4[2H]4N-2[15N]2", "ClNa", "HO3P"] {
+            let composition = parse_formula(formula).unwrap();
+            assert_eq!(to_hill_notation(&composition), formula);
+            assert_eq!(parse_formula(&to_hill_notation(&composition)).unwrap(), composition);
+        }
+
+        // unimod notation and separated elements parse to the same composition as the compact formula
+        assert_eq!(parse_formula("H(-1) N(-1) O").unwrap(), parse_formula("H-1N-1O").unwrap());
+        assert_eq!(to_hill_notation(&parse_formula("O H2 C H4 C").unwrap()), "C2H6O");
+        assert!(parse_formula("").unwrap().is_empty());
+        assert!(parse_formula("H2H-2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_formula_syntax_errors() {
+        let position = |formula: &str| match parse_formula(formula) {
+            Err(FormulaError::InvalidSyntax { position, .. }) => Some(position),
+            _ => None,
+        };
+        assert_eq!(position("c6"), Some(0));
+        assert_eq!(position("C6-"), Some(2));
+        assert_eq!(position("C(6"), Some(3));
+        assert_eq!(position("C()"), Some(2));
+        assert_eq!(position("[C]"), Some(1));
+        assert_eq!(position("[13C"), Some(4));
+        assert_eq!(position("C99999999999"), Some(1));
+    }
+
+    #[test]
+    fn test_formula_masses() {
+        // monoisotopic and average mass of glucose
+        let glucose = parse_formula("C6H12O6").unwrap();
+        assert!((monoisotopic_mass_of(&glucose) - 180.063388).abs() < 1e-5);
+        assert!((average_mass_of(&glucose) - 180.156).abs() < 1e-2);
+
+        // the heavy isotope of SILAC lysine adds 8.0142 Da
+        let label = parse_formula("C-6[13C]6N-2[15N]2").unwrap();
+        assert!((monoisotopic_mass_of(&label) - 8.014199).abs() < 1e-5);
+        assert!((average_mass_of(&label) - monoisotopic_mass_of(&label)).abs() < 0.1);
+    }
+}
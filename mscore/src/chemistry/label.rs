@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::chemistry::elements::atomic_weights_mono_isotopic;
+
+/// Stable isotope labeling schemes, heavy isotopes are written as separate elements `2H`, `13C` and `15N`
+/// in atomic compositions and replace the same number of atoms of the natural element
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LabelScheme {
+    /// SILAC medium: 4,4,5,5-D4 lysine (Lys4) and 13C6 arginine (Arg6)
+    SilacMedium,
+    /// SILAC heavy: 13C6 15N2 lysine (Lys8) and 13C6 15N4 arginine (Arg10)
+    SilacHeavy,
+    /// every nitrogen is 15N
+    FullN15,
+    /// dimethylation of lysine and the N-terminus with CH2O and NaBH3CN, +28.0313 Da per site
+    DimethylLight,
+    /// dimethylation of lysine and the N-terminus with CD2O and NaBH3CN, +32.0564 Da per site
+    DimethylIntermediate,
+    /// dimethylation of lysine and the N-terminus with 13CD2O and NaBD3CN, +36.0757 Da per site
+    DimethylHeavy,
+}
+
+impl LabelScheme {
+    /// Get a label scheme by name
+    ///
+    /// Arguments:
+    ///
+    /// * `name` - one of `silac_medium`, `silac_heavy`, `15n`, `dimethyl_light`, `dimethyl_intermediate`,
+    ///   `dimethyl_heavy`, case insensitive
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::label::LabelScheme;
+    ///
+    /// assert_eq!(LabelScheme::from_name("SILAC_heavy"), Ok(LabelScheme::SilacHeavy));
+    /// assert_eq!(LabelScheme::from_name(LabelScheme::FullN15.name()), Ok(LabelScheme::FullN15));
+    /// assert!(LabelScheme::from_name("itraq").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "silac_medium" => Ok(LabelScheme::SilacMedium),
+            "silac_heavy" => Ok(LabelScheme::SilacHeavy),
+            "15n" => Ok(LabelScheme::FullN15),
+            "dimethyl_light" => Ok(LabelScheme::DimethylLight),
+            "dimethyl_intermediate" => Ok(LabelScheme::DimethylIntermediate),
+            "dimethyl_heavy" => Ok(LabelScheme::DimethylHeavy),
+            _ => Err(format!("unknown label scheme {}, expected one of silac_medium, silac_heavy, 15n, \
+                dimethyl_light, dimethyl_intermediate or dimethyl_heavy", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LabelScheme::SilacMedium => "silac_medium",
+            LabelScheme::SilacHeavy => "silac_heavy",
+            LabelScheme::FullN15 => "15n",
+            LabelScheme::DimethylLight => "dimethyl_light",
+            LabelScheme::DimethylIntermediate => "dimethyl_intermediate",
+            LabelScheme::DimethylHeavy => "dimethyl_heavy",
+        }
+    }
+
+    /// change of the atomic composition of the N-terminus and of every lysine by dimethylation
+    fn dimethyl_delta(&self) -> &'static [(&'static str, i32)] {
+        match self {
+            LabelScheme::DimethylLight => &[("C", 2), ("H", 4)],
+            LabelScheme::DimethylIntermediate => &[("C", 2), ("2H", 4)],
+            LabelScheme::DimethylHeavy => &[("13C", 2), ("2H", 6), ("H", -2)],
+            _ => &[],
+        }
+    }
+
+    /// change of the atomic composition of a labeled residue
+    fn residue_delta(&self, residue: char) -> &'static [(&'static str, i32)] {
+        match (self, residue) {
+            (LabelScheme::SilacMedium, 'K') => &[("H", -4), ("2H", 4)],
+            (LabelScheme::SilacMedium, 'R') => &[("C", -6), ("13C", 6)],
+            (LabelScheme::SilacHeavy, 'K') => &[("C", -6), ("13C", 6), ("N", -2), ("15N", 2)],
+            (LabelScheme::SilacHeavy, 'R') => &[("C", -6), ("13C", 6), ("N", -4), ("15N", 4)],
+            (_, 'K') => self.dimethyl_delta(),
+            _ => &[],
+        }
+    }
+
+    /// Label an atomic composition
+    ///
+    /// Arguments:
+    ///
+    /// * `residues` - amino acids of the peptide or fragment, without modifications
+    /// * `n_terminus` - whether the peptide or fragment carries the free N-terminus of the peptide
+    /// * `composition` - unlabeled atomic composition, changed in place, elements replaced completely are removed
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use mscore::chemistry::label::LabelScheme;
+    ///
+    /// let mut composition = HashMap::from([("C", 6), ("H", 14), ("N", 2), ("O", 2)]);
+    /// LabelScheme::SilacHeavy.apply("K".chars(), true, &mut composition);
+    /// assert_eq!(composition, HashMap::from([("13C", 6), ("H", 14), ("15N", 2), ("O", 2)]));
+    /// ```
+    pub fn apply(&self, residues: impl Iterator<Item = char>, n_terminus: bool, composition: &mut HashMap<&'static str, i32>) {
+        if n_terminus {
+            for (element, count) in self.dimethyl_delta() {
+                *composition.entry(element).or_insert(0) += count;
+            }
+        }
+
+        for residue in residues {
+            for (element, count) in self.residue_delta(residue) {
+                *composition.entry(element).or_insert(0) += count;
+            }
+        }
+
+        if *self == LabelScheme::FullN15 {
+            if let Some(nitrogen) = composition.remove("N") {
+                *composition.entry("15N").or_insert(0) += nitrogen;
+            }
+        }
+
+        // an element with a count of zero would still be convolved as one atom into isotope distributions
+        composition.retain(|_, count| *count != 0);
+    }
+
+    /// Mass difference between the labeled and the unlabeled molecule
+    ///
+    /// Arguments:
+    ///
+    /// * `residues` - amino acids of the peptide or fragment, without modifications
+    /// * `n_terminus` - whether the peptide or fragment carries the free N-terminus of the peptide
+    /// * `composition` - unlabeled atomic composition, only needed for full labeling
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use mscore::chemistry::label::LabelScheme;
+    ///
+    /// // Lys8 and Arg10
+    /// let shift = |residue: &str| LabelScheme::SilacHeavy.mass_shift(residue.chars(), false, &HashMap::new());
+    /// assert!((shift("K") - 8.0142).abs() < 1e-4);
+    /// assert!((shift("R") - 10.0083).abs() < 1e-4);
+    /// let dimethyl = LabelScheme::DimethylHeavy.mass_shift("GK".chars(), true, &HashMap::new());
+    /// assert!((dimethyl - 2.0 * 36.0757).abs() < 1e-3);
+    /// ```
+    pub fn mass_shift(&self, residues: impl Iterator<Item = char>, n_terminus: bool, composition: &HashMap<&'static str, i32>) -> f64 {
+        let mut labeled = composition.clone();
+        self.apply(residues, n_terminus, &mut labeled);
+        let atomic_weights = atomic_weights_mono_isotopic();
+
+        let mass = |composition: &HashMap<&'static str, i32>| -> f64 {
+            composition.iter().map(|(element, count)| {
+                atomic_weights.get(element).expect("Element not found in atomic weights table") * *count as f64
+            }).sum()
+        };
+        mass(&labeled) - mass(composition)
+    }
+}
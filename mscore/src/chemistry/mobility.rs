@@ -0,0 +1,171 @@
+use rayon::prelude::*;
+use crate::parallel;
+
+use crate::chemistry::constants::STANDARD_TEMPERATURE;
+use crate::chemistry::formulas::{ccs_to_one_over_reduced_mobility, one_over_reduced_mobility_to_ccs};
+
+/// mass of the default drift gas, nitrogen (N2)
+pub const MASS_N2: f64 = 28.013;
+/// default temperature of the drift gas in C°
+pub const DRIFT_GAS_TEMPERATURE: f64 = 31.85;
+
+/// convert the inverse reduced ion mobility (1/k0) of an ion to its collision cross-section
+/// using the Mason-Schamp equation
+///
+/// Arguments:
+///
+/// * `one_over_k0` - inverse reduced ion mobility in Vs/cm²
+/// * `mz` - mass-over-charge of the ion
+/// * `charge` - charge state of the ion
+/// * `temperature` - temperature of the drift gas in C°, defaults to `DRIFT_GAS_TEMPERATURE`
+/// * `mass_gas` - mass of the drift gas, defaults to nitrogen
+///
+/// Returns:
+///
+/// * `ccs` - collision cross-section in Å²
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::mobility::one_over_k0_to_ccs;
+///
+/// // Agilent tune mix ion, 202.96 Å² in nitrogen
+/// let ccs = one_over_k0_to_ccs(0.9915, 622.0290, 1, None, None);
+/// assert!((ccs - 202.96).abs() < 0.05);
+/// ```
+pub fn one_over_k0_to_ccs(one_over_k0: f64, mz: f64, charge: u32, temperature: Option<f64>, mass_gas: Option<f64>) -> f64 {
+    one_over_reduced_mobility_to_ccs(
+        one_over_k0,
+        mz,
+        charge,
+        mass_gas.unwrap_or(MASS_N2),
+        temperature.unwrap_or(DRIFT_GAS_TEMPERATURE),
+        STANDARD_TEMPERATURE,
+    )
+}
+
+/// convert the collision cross-section of an ion to its inverse reduced ion mobility (1/k0)
+/// using the Mason-Schamp equation
+///
+/// Arguments:
+///
+/// * `ccs` - collision cross-section in Å²
+/// * `mz` - mass-over-charge of the ion
+/// * `charge` - charge state of the ion
+/// * `temperature` - temperature of the drift gas in C°, defaults to `DRIFT_GAS_TEMPERATURE`
+/// * `mass_gas` - mass of the drift gas, defaults to nitrogen
+///
+/// Returns:
+///
+/// * `one_over_k0` - inverse reduced ion mobility in Vs/cm²
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::mobility::{ccs_to_one_over_k0, one_over_k0_to_ccs};
+///
+/// let one_over_k0 = ccs_to_one_over_k0(243.64, 922.0098, 1, None, None);
+/// assert!((one_over_k0 - 1.1986).abs() < 1e-3);
+/// assert!((one_over_k0_to_ccs(one_over_k0, 922.0098, 1, None, None) - 243.64).abs() < 1e-9);
+/// ```
+pub fn ccs_to_one_over_k0(ccs: f64, mz: f64, charge: u32, temperature: Option<f64>, mass_gas: Option<f64>) -> f64 {
+    ccs_to_one_over_reduced_mobility(
+        ccs,
+        mz,
+        charge,
+        mass_gas.unwrap_or(MASS_N2),
+        temperature.unwrap_or(DRIFT_GAS_TEMPERATURE),
+        STANDARD_TEMPERATURE,
+    )
+}
+
+/// convert the inverse reduced ion mobility (1/k0) of many ions to collision cross-sections in parallel
+///
+/// Arguments:
+///
+/// * `one_over_k0` - inverse reduced ion mobility of every ion
+/// * `mz` - mass-over-charge of every ion
+/// * `charge` - charge state of every ion
+/// * `temperature` - temperature of the drift gas in C°, defaults to `DRIFT_GAS_TEMPERATURE`
+/// * `mass_gas` - mass of the drift gas, defaults to nitrogen
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<f64>` - collision cross-section of every ion in Å²
+///
+/// Example:
+///
+/// ```
+/// use mscore::chemistry::mobility::{one_over_k0_to_ccs, one_over_k0_to_ccs_par};
+///
+/// let ccs = one_over_k0_to_ccs_par(&[0.9915, 0.85], &[622.0290, 500.0], &[1, 2], None, None, 2);
+/// assert_eq!(ccs[1], one_over_k0_to_ccs(0.85, 500.0, 2, None, None));
+/// ```
+pub fn one_over_k0_to_ccs_par(one_over_k0: &[f64], mz: &[f64], charge: &[u32], temperature: Option<f64>, mass_gas: Option<f64>, num_threads: usize) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        one_over_k0.par_iter().zip(mz.par_iter()).zip(charge.par_iter())
+            .map(|((&one_over_k0, &mz), &charge)| one_over_k0_to_ccs(one_over_k0, mz, charge, temperature, mass_gas))
+            .collect()
+    })
+}
+
+/// convert the collision cross-section of many ions to inverse reduced ion mobility (1/k0) in parallel
+///
+/// Arguments:
+///
+/// * `ccs` - collision cross-section of every ion in Å²
+/// * `mz` - mass-over-charge of every ion
+/// * `charge` - charge state of every ion
+/// * `temperature` - temperature of the drift gas in C°, defaults to `DRIFT_GAS_TEMPERATURE`
+/// * `mass_gas` - mass of the drift gas, defaults to nitrogen
+/// * `num_threads` - number of threads to use
+///
+/// Returns:
+///
+/// * `Vec<f64>` - inverse reduced ion mobility of every ion
+pub fn ccs_to_one_over_k0_par(ccs: &[f64], mz: &[f64], charge: &[u32], temperature: Option<f64>, mass_gas: Option<f64>, num_threads: usize) -> Vec<f64> {
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        ccs.par_iter().zip(mz.par_iter()).zip(charge.par_iter())
+            .map(|((&ccs, &mz), &charge)| ccs_to_one_over_k0(ccs, mz, charge, temperature, mass_gas))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Agilent tune mix ions as (m/z, 1/k0, CCS in N2), single charged,
+    // 1/k0 as used for timsTOF calibration, CCS from drift tube measurements (Stow et al., Anal. Chem. 2017)
+    const TUNE_MIX: [(f64, f64, f64); 3] = [
+        (622.0290, 0.9915, 202.96),
+        (922.0098, 1.1986, 243.64),
+        (1221.9906, 1.3934, 282.20),
+    ];
+
+    #[test]
+    fn test_tune_mix_reference_values() {
+        for (mz, one_over_k0, ccs) in TUNE_MIX {
+            assert!((one_over_k0_to_ccs(one_over_k0, mz, 1, None, None) - ccs).abs() < 0.05, "ccs of {mz}");
+            assert!((ccs_to_one_over_k0(ccs, mz, 1, None, None) - one_over_k0).abs() < 2e-4, "1/k0 of {mz}");
+        }
+    }
+
+    #[test]
+    fn test_parallel_round_trip() {
+        let one_over_k0: Vec<f64> = TUNE_MIX.iter().map(|t| t.1).collect();
+        let mz: Vec<f64> = TUNE_MIX.iter().map(|t| t.0).collect();
+        let charge = vec![1, 2, 3];
+
+        let ccs = one_over_k0_to_ccs_par(&one_over_k0, &mz, &charge, Some(25.0), None, 2);
+        let back = ccs_to_one_over_k0_par(&ccs, &mz, &charge, Some(25.0), None, 2);
+        for (a, b) in one_over_k0.iter().zip(back.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        // the same 1/k0 in a colder drift gas means a larger cross-section
+        assert!(ccs[0] > one_over_k0_to_ccs(one_over_k0[0], mz[0], 1, None, None));
+    }
+}
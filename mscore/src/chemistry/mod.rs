@@ -3,5 +3,8 @@ pub mod amino_acid;
 pub mod unimod;
 pub mod constants;
 pub mod formulas;
+pub mod mobility;
 pub mod utility;
-pub mod sum_formula;
\ No newline at end of file
+pub mod sum_formula;
+pub mod descriptors;
+pub mod label;
\ No newline at end of file
@@ -2142,4 +2142,52 @@ pub fn unimod_modifications_mass_numerical() -> HashMap<u32, f64> {
     map.insert(2028, 2861.000054);
     map.insert(2029, 2352.846);
     map
-}
\ No newline at end of file
+}
+
+/// Unimod Modification Names
+///
+/// # Arguments
+///
+/// None
+///
+/// # Returns
+///
+/// * `HashMap<&'static str, u32>` - a map of common unimod PSI-MS names to their numerical ids
+///
+/// # Example
+///
+/// ```
+/// use mscore::chemistry::unimod::unimod_modification_names;
+///
+/// let names = unimod_modification_names();
+/// assert_eq!(names.get("Phospho"), Some(&21));
+/// ```
+pub fn unimod_modification_names() -> HashMap<&'static str, u32> {
+    let mut map = HashMap::new();
+    map.insert("Acetyl", 1);
+    map.insert("Amidated", 2);
+    map.insert("Biotin", 3);
+    map.insert("Carbamidomethyl", 4);
+    map.insert("Carbamyl", 5);
+    map.insert("Carboxymethyl", 6);
+    map.insert("Deamidated", 7);
+    map.insert("Phospho", 21);
+    map.insert("Dehydrated", 23);
+    map.insert("Propionamide", 24);
+    map.insert("Pyro-carbamidomethyl", 26);
+    map.insert("Glu->pyro-Glu", 27);
+    map.insert("Gln->pyro-Glu", 28);
+    map.insert("Methyl", 34);
+    map.insert("Oxidation", 35);
+    map.insert("Dimethyl", 36);
+    map.insert("Trimethyl", 37);
+    map.insert("Methylthio", 39);
+    map.insert("Sulfo", 40);
+    map.insert("Hex", 41);
+    map.insert("HexNAc", 43);
+    map.insert("iTRAQ4plex", 214);
+    map.insert("Nitro", 354);
+    map.insert("Butyryl", 1289);
+    map.insert("Crotonyl", 1363);
+    map
+}
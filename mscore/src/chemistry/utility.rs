@@ -40,7 +40,7 @@ pub fn unimod_sequence_to_tokens(sequence: &str, group_modifications: bool) -> V
             tokens.extend(aa_sequence.chars().map(|c| c.to_string()));
 
             // Group the last amino acid with the UNIMOD as one token
-            let grouped_mod = format!("{}{}", pre_mod_sequence.chars().last().unwrap_or_default().to_string(), &sequence[mat.start()..mat.end()]);
+            let grouped_mod = format!("{}{}", pre_mod_sequence.chars().last().unwrap_or_default(), &sequence[mat.start()..mat.end()]);
             tokens.push(grouped_mod);
         } else {
             // Extract the amino acids before the current UNIMOD and add them as individual tokens
@@ -76,7 +76,7 @@ pub fn unimod_sequence_to_tokens(sequence: &str, group_modifications: bool) -> V
 /// # Returns
 ///
 /// * `(String, Vec<f64>)` - a tuple of the plain sequence and a vector of f64 representing the mass
-/// of the modification at each position in the sequence
+///   of the modification at each position in the sequence
 ///
 /// # Example
 ///
@@ -139,15 +139,22 @@ fn calculate_modifications(index_list: &[(usize, String)], stripped_sequence: &s
     mods
 }
 
-/// Reshape the flat prosit array into a 3D array of shape (29, 2, 3)
+/// Shape (positions, series, charges) of flat Prosit arrays: 29 cleavage positions, y and b ions, charges 1 to 3
+pub const PROSIT_SHAPE: (usize, usize, usize) = (29, 2, 3);
+
+/// Reshape a flat fragment intensity array into a 3D array of shape (positions, series, charges)
+///
+/// The flat array is ordered by charge, then series, then position, series 0 are y ions and series 1 b ions,
+/// further series of newer models follow in the order the model emits them
 ///
 /// # Arguments
 ///
-/// * `flat_array` - a vector of f64 representing the flat prosit array
+/// * `flat_array` - a vector of f64 representing the flat array
+/// * `shape` - (positions, series, charges) of the array, defaults to `PROSIT_SHAPE`
 ///
 /// # Returns
 ///
-/// * `Vec<Vec<Vec<f64>>>` - a 3D array of shape (29, 2, 3)
+/// * `Result<Vec<Vec<Vec<f64>>>, String>` - the 3D array, an error if the length of the flat array does not match the shape
 ///
 /// # Example
 ///
@@ -155,27 +162,39 @@ fn calculate_modifications(index_list: &[(usize, String)], stripped_sequence: &s
 /// use mscore::chemistry::utility::reshape_prosit_array;
 ///
 /// let flat_array = vec![0.0; 174];
-/// let reshaped_array = reshape_prosit_array(flat_array);
+/// let reshaped_array = reshape_prosit_array(flat_array, None).unwrap();
 /// assert_eq!(reshaped_array.len(), 29);
 /// assert_eq!(reshaped_array[0].len(), 2);
 /// assert_eq!(reshaped_array[0][0].len(), 3);
+///
+/// // a model with 30 positions
+/// let reshaped_array = reshape_prosit_array((0..180).map(|i| i as f64).collect(), Some((30, 2, 3))).unwrap();
+/// assert_eq!(reshaped_array.len(), 30);
+/// assert_eq!(reshaped_array[29][1][0], 59.0);
+/// assert_eq!(reshaped_array[0][0][1], 60.0);
+///
+/// assert!(reshape_prosit_array(vec![0.0; 180], None).is_err());
 /// ```
-pub fn reshape_prosit_array(flat_array: Vec<f64>) -> Vec<Vec<Vec<f64>>> {
-    let mut array_return: Vec<Vec<Vec<f64>>> = vec![vec![vec![0.0; 3]; 2]; 29];
-    let mut ptr = 0;
-
-    for c in 0..3 {
-        for row in 0..29 {
-            // Fill in the Y ion values
-            array_return[row][0][c] = flat_array[ptr];
-            ptr += 1;
-        }
-        for row in 0..29 {
-            // Fill in the B ion values
-            array_return[row][1][c] = flat_array[ptr];
-            ptr += 1;
+pub fn reshape_prosit_array(flat_array: Vec<f64>, shape: Option<(usize, usize, usize)>) -> Result<Vec<Vec<Vec<f64>>>, String> {
+    let (positions, series, charges) = shape.unwrap_or(PROSIT_SHAPE);
+    let expected_length = positions * series * charges;
+    if flat_array.len() != expected_length {
+        return Err(format!(
+            "flat intensity array has {} values, shape ({}, {}, {}) needs {}",
+            flat_array.len(), positions, series, charges, expected_length
+        ));
+    }
+
+    let mut array_return: Vec<Vec<Vec<f64>>> = vec![vec![vec![0.0; charges]; series]; positions];
+    let mut values = flat_array.into_iter();
+
+    for c in 0..charges {
+        for s in 0..series {
+            for row in array_return.iter_mut() {
+                row[s][c] = values.next().unwrap();
+            }
         }
     }
 
-    array_return
+    Ok(array_return)
 }
\ No newline at end of file
@@ -1,2 +1,4 @@
 pub mod spectrum;
-pub mod peptide;
\ No newline at end of file
+pub mod peptide;
+pub mod serialization;
+pub mod proforma;
\ No newline at end of file
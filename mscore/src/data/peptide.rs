@@ -1,12 +1,17 @@
-use std::collections::{HashMap};
+use std::collections::{BTreeMap, HashMap};
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use crate::algorithm::peptide::{calculate_peptide_mono_isotopic_mass, calculate_peptide_product_ion_mono_isotopic_mass, peptide_sequence_to_atomic_composition};
+use crate::algorithm::isotope::IsotopeDistributionCache;
+use crate::algorithm::peptide::{calculate_peptide_average_mass, check_atomic_composition, calculate_peptide_mono_isotopic_mass, calculate_peptide_product_ion_mono_isotopic_mass, peptide_sequence_to_atomic_composition};
 use crate::chemistry::amino_acid::{amino_acid_masses};
-use crate::chemistry::formulas::calculate_mz;
-use crate::chemistry::utility::{find_unimod_patterns, reshape_prosit_array, unimod_sequence_to_tokens};
+use crate::chemistry::constants::MASS_ELECTRON;
+use crate::chemistry::formulas::{average_mass_of, calculate_mz, calculate_mz_with_adduct, Adduct};
+use crate::chemistry::label::LabelScheme;
+use crate::chemistry::unimod::unimod_modifications_mass_numerical;
+use crate::chemistry::utility::{find_unimod_patterns, reshape_prosit_array, unimod_sequence_to_tokens, PROSIT_SHAPE};
+use crate::data::proforma::{parse_proforma, to_proforma, ProformaError};
 use crate::data::spectrum::MzSpectrum;
 use crate::simulation::annotation::{MzSpectrumAnnotated, ContributionSource, SignalAttributes, SourceType, PeakAnnotation};
 
@@ -20,6 +25,11 @@ pub struct PeptideIon {
     pub sequence: PeptideSequence,
     pub charge: i32,
     pub intensity: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<LabelScheme>,
+    /// ion species, None means protonation to `charge`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adduct: Option<Adduct>,
 }
 
 impl PeptideIon {
@@ -28,10 +38,63 @@ impl PeptideIon {
             sequence: PeptideSequence::new(sequence, peptide_id),
             charge,
             intensity,
+            label: None,
+            adduct: None,
         }
     }
+
+    /// Copy of the ion carrying a stable isotope label, mass, m/z and isotope distribution include the label
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::label::LabelScheme;
+    /// use mscore::data::peptide::PeptideIon;
+    ///
+    /// let light = PeptideIon::new("PEPTIDEK".to_string(), 2, 1.0, None);
+    /// let heavy = light.with_label(LabelScheme::SilacHeavy);
+    /// assert!((heavy.mz() - light.mz() - 8.0142 / 2.0).abs() < 1e-4);
+    ///
+    /// // 13C atoms of the label do not add to the natural +1 isotope
+    /// let light_distribution = light.calculate_isotope_distribution(1e-3, 1e-8, 200, 1e-6);
+    /// let heavy_distribution = heavy.calculate_isotope_distribution(1e-3, 1e-8, 200, 1e-6);
+    /// assert!((heavy_distribution[0].0 - light_distribution[0].0 - 8.0142 / 2.0).abs() < 1e-3);
+    /// assert!(heavy_distribution[1].1 / heavy_distribution[0].1 < light_distribution[1].1 / light_distribution[0].1);
+    /// ```
+    pub fn with_label(&self, label: LabelScheme) -> Self {
+        PeptideIon { label: Some(label), ..self.clone() }
+    }
+
+    pub fn mono_isotopic_mass(&self) -> f64 {
+        calculate_peptide_mono_isotopic_mass(&self.sequence, self.label)
+    }
+
+    /// Copy of the ion formed as `adduct` instead of by protonation, the charge becomes the charge of the adduct,
+    /// the atoms of the adduct enter m/z and isotope distribution
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::chemistry::formulas::Adduct;
+    /// use mscore::data::peptide::PeptideIon;
+    ///
+    /// let protonated = PeptideIon::new("PEPTIDEK".to_string(), 1, 1.0, None);
+    /// let sodiated = protonated.with_adduct(Adduct::from_name("[M+Na]+").unwrap());
+    /// assert!((sodiated.mz() - protonated.mz() - 21.98194).abs() < 1e-5);
+    ///
+    /// let deprotonated = protonated.with_adduct(Adduct::protonated(-2));
+    /// assert_eq!(deprotonated.charge, -2);
+    /// assert!((deprotonated.mz() - (protonated.mono_isotopic_mass() - 2.0 * 1.007276) / 2.0).abs() < 1e-5);
+    /// ```
+    pub fn with_adduct(&self, adduct: Adduct) -> Self {
+        PeptideIon { charge: adduct.charge, adduct: Some(adduct), ..self.clone() }
+    }
+
     pub fn mz(&self) -> f64 {
-        calculate_mz(self.sequence.mono_isotopic_mass(), self.charge)
+        match &self.adduct {
+            Some(adduct) => calculate_mz_with_adduct(self.mono_isotopic_mass(), adduct),
+            None => calculate_mz(self.mono_isotopic_mass(), self.charge),
+        }
     }
 
     pub fn calculate_isotope_distribution(
@@ -42,12 +105,25 @@ impl PeptideIon {
         intensity_min: f64,
     ) -> IsotopeDistribution {
 
-        let atomic_composition: HashMap<String, i32> = self.sequence.atomic_composition().iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        let mut atomic_composition: HashMap<String, i32> = peptide_sequence_to_atomic_composition(&self.sequence, self.label).iter().map(|(k, v)| (k.to_string(), *v)).collect();
 
-        let distribution: IsotopeDistribution = crate::algorithm::isotope::generate_isotope_distribution(&atomic_composition, mass_tolerance, abundance_threshold, max_result)
-            .into_iter().filter(|&(_, abundance)| abundance > intensity_min).collect();
+        // the adduct atoms contribute their own isotopes, only the electrons are accounted for afterwards
+        if let Some(adduct) = &self.adduct {
+            for (element, count) in adduct.composition.iter() {
+                *atomic_composition.entry(element.clone()).or_insert(0) += count;
+            }
+            atomic_composition.retain(|_, count| *count != 0);
+        }
+
+        let distribution: IsotopeDistribution = IsotopeDistributionCache::shared().get_or_compute(&atomic_composition, mass_tolerance, abundance_threshold, max_result)
+            .iter().copied().filter(|&(_, abundance)| abundance > intensity_min).collect();
+
+        let ion_mz = |mass: f64| match &self.adduct {
+            Some(adduct) => (mass - adduct.charge as f64 * MASS_ELECTRON) / adduct.charge.abs() as f64,
+            None => calculate_mz(mass, self.charge),
+        };
 
-        let mz_distribution = distribution.iter().map(|(mass, _)| calculate_mz(*mass, self.charge))
+        let mz_distribution = distribution.iter().map(|(mass, _)| ion_mz(*mass))
             .zip(distribution.iter().map(|&(_, abundance)| abundance)).collect();
 
         mz_distribution
@@ -90,6 +166,7 @@ impl PeptideIon {
             let signal_attributes = SignalAttributes {
                 charge_state: self.charge,
                 peptide_id: self.sequence.peptide_id.unwrap_or(-1),
+                ion_id: -1,
                 isotope_peak: isotope_counter,
                 description: None,
             };
@@ -109,7 +186,7 @@ impl PeptideIon {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FragmentType { A, B, C, X, Y, Z, }
 
 // implement to string for fragment type
@@ -126,6 +203,47 @@ impl std::fmt::Display for FragmentType {
     }
 }
 
+impl FragmentType {
+    /// Parse a fragment type from its one letter name, case insensitive
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::FragmentType;
+    ///
+    /// assert_eq!(FragmentType::from_name("Y").unwrap().to_string(), "y");
+    /// assert!(FragmentType::from_name("q").is_err());
+    /// ```
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "a" => Ok(FragmentType::A),
+            "b" => Ok(FragmentType::B),
+            "c" => Ok(FragmentType::C),
+            "x" => Ok(FragmentType::X),
+            "y" => Ok(FragmentType::Y),
+            "z" => Ok(FragmentType::Z),
+            _ => Err(format!("unknown fragment type {}, expected one of a, b, c, x, y, z", name)),
+        }
+    }
+
+    /// whether fragments of this type contain the N-terminus of the peptide
+    pub fn is_n_terminal(&self) -> bool {
+        matches!(self, FragmentType::A | FragmentType::B | FragmentType::C)
+    }
+
+    /// fragment type formed by the same cleavage on the other side of the backbone, e.g. y for b and z for c
+    pub fn complement(&self) -> FragmentType {
+        match self {
+            FragmentType::A => FragmentType::X,
+            FragmentType::B => FragmentType::Y,
+            FragmentType::C => FragmentType::Z,
+            FragmentType::X => FragmentType::A,
+            FragmentType::Y => FragmentType::B,
+            FragmentType::Z => FragmentType::C,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeptideProductIon {
     pub kind: FragmentType,
@@ -140,17 +258,44 @@ impl PeptideProductIon {
                 sequence: PeptideSequence::new(sequence, peptide_id),
                 charge,
                 intensity,
+                label: None,
+                adduct: None,
             },
         }
     }
 
     pub fn mono_isotopic_mass(&self) -> f64 {
-        calculate_peptide_product_ion_mono_isotopic_mass(self.ion.sequence.sequence.as_str(), self.kind)
+        let mass = calculate_peptide_product_ion_mono_isotopic_mass(self.ion.sequence.sequence.as_str(), self.kind);
+        match self.ion.label {
+            Some(label) => {
+                let composition = peptide_sequence_to_atomic_composition(&self.ion.sequence, None);
+                let (residues, _) = find_unimod_patterns(self.ion.sequence.sequence.as_str());
+                mass + label.mass_shift(residues.chars(), self.kind.is_n_terminal(), &composition)
+            }
+            None => mass,
+        }
+    }
+
+    /// Average mass from the atomic composition of the fragment, including the label of the ion
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::{FragmentType, PeptideProductIon};
+    ///
+    /// let y = PeptideProductIon::new(FragmentType::Y, "PEPTIDE".to_string(), 1, 1.0, None);
+    /// let b = PeptideProductIon::new(FragmentType::B, "PEPTIDE".to_string(), 1, 1.0, None);
+    /// assert!((y.average_mass().unwrap() - 799.82).abs() < 0.01);
+    /// assert!((y.average_mass().unwrap() - b.average_mass().unwrap() - 18.015).abs() < 1e-3);
+    /// ```
+    pub fn average_mass(&self) -> Result<f64, String> {
+        check_atomic_composition(self.ion.sequence.sequence.as_str())?;
+        Ok(average_mass_of(&self.atomic_composition()))
     }
 
     pub fn atomic_composition(&self) -> HashMap<&str, i32> {
 
-        let mut composition = peptide_sequence_to_atomic_composition(&self.ion.sequence);
+        let mut composition = peptide_sequence_to_atomic_composition(&self.ion.sequence, None);
 
         match self.kind {
             FragmentType::A => {
@@ -178,15 +323,18 @@ impl PeptideProductIon {
                 *composition.entry("O").or_insert(0) += 1;
             },
 
-            FragmentType::Y => {
-                ()
-            },
+            FragmentType::Y => {},
 
             FragmentType::Z => {
                 *composition.entry("H").or_insert(0) -= 1;
                 *composition.entry("N").or_insert(0) -= 3;
             },
         }
+
+        if let Some(label) = self.ion.label {
+            let (residues, _) = find_unimod_patterns(self.ion.sequence.sequence.as_str());
+            label.apply(residues.chars(), self.kind.is_n_terminal(), &mut composition);
+        }
         composition
     }
 
@@ -194,6 +342,21 @@ impl PeptideProductIon {
         calculate_mz(self.mono_isotopic_mass(), self.ion.charge)
     }
 
+    /// Series label of the ion: fragment type, number of residues and charge, e.g. `c3+2`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::{FragmentType, PeptideProductIon};
+    ///
+    /// let ion = PeptideProductIon::new(FragmentType::C, "[UNIMOD:1]PEP".to_string(), 2, 1.0, None);
+    /// assert_eq!(ion.label(), "c3+2");
+    /// ```
+    pub fn label(&self) -> String {
+        let num_residues = self.ion.sequence.composition_counts().values().sum::<usize>();
+        format!("{}{}+{}", self.kind, num_residues, self.ion.charge)
+    }
+
     pub fn isotope_distribution(
         &self,
         mass_tolerance: f64,
@@ -204,8 +367,8 @@ impl PeptideProductIon {
 
         let atomic_composition: HashMap<String, i32> = self.atomic_composition().iter().map(|(k, v)| (k.to_string(), *v)).collect();
 
-        let distribution: IsotopeDistribution = crate::algorithm::isotope::generate_isotope_distribution(&atomic_composition, mass_tolerance, abundance_threshold, max_result)
-            .into_iter().filter(|&(_, abundance)| abundance > intensity_min).collect();
+        let distribution: IsotopeDistribution = IsotopeDistributionCache::shared().get_or_compute(&atomic_composition, mass_tolerance, abundance_threshold, max_result)
+            .iter().copied().filter(|&(_, abundance)| abundance > intensity_min).collect();
 
         let mz_distribution = distribution.iter().map(|(mass, _)| calculate_mz(*mass, self.ion.charge)).zip(distribution.iter().map(|&(_, abundance)| abundance)).collect();
 
@@ -213,6 +376,27 @@ impl PeptideProductIon {
     }
 }
 
+/// One residue of a peptide sequence with its position and modifications, see `PeptideSequence::tokens_with_positions`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResidueToken {
+    /// one letter code of the amino acid, `None` for the N-terminus
+    pub residue: Option<char>,
+    /// 1-based position of the residue, 0 for the N-terminus
+    pub position: usize,
+    /// unimod ids and masses of the modifications of this residue, in the order of the sequence
+    pub modifications: Vec<(u32, f64)>,
+}
+
+impl ResidueToken {
+    pub fn is_modified(&self) -> bool {
+        !self.modifications.is_empty()
+    }
+
+    pub fn modification_mass(&self) -> f64 {
+        self.modifications.iter().map(|(_, mass)| mass).sum()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
 pub struct PeptideSequence {
     pub sequence: String,
@@ -237,26 +421,137 @@ impl PeptideSequence {
         PeptideSequence { sequence: raw_sequence, peptide_id }
     }
 
+    /// Parse a ProForma 2.0 peptidoform, see `parse_proforma`, a charge suffix is accepted and dropped
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let sequence = PeptideSequence::from_proforma("ELVIS[Phospho]K/2").unwrap();
+    /// assert_eq!(sequence.sequence, "ELVIS[UNIMOD:21]K");
+    /// assert_eq!(sequence.to_proforma(), "ELVIS[Phospho]K");
+    /// ```
+    pub fn from_proforma(s: &str) -> Result<Self, ProformaError> {
+        let (sequence, _) = parse_proforma(s)?;
+        Ok(PeptideSequence { sequence, peptide_id: None })
+    }
+
+    pub fn to_proforma(&self) -> String {
+        to_proforma(&self.sequence, None)
+    }
+
     pub fn mono_isotopic_mass(&self) -> f64 {
-        calculate_peptide_mono_isotopic_mass(self)
+        calculate_peptide_mono_isotopic_mass(self, None)
+    }
+
+    /// Average mass from the atomic composition, see `calculate_peptide_average_mass`
+    pub fn average_mass(&self) -> Result<f64, String> {
+        calculate_peptide_average_mass(self, None)
     }
 
     pub fn atomic_composition(&self) -> HashMap<&str, i32> {
-        peptide_sequence_to_atomic_composition(self)
+        peptide_sequence_to_atomic_composition(self, None)
     }
 
     pub fn to_tokens(&self, group_modifications: bool) -> Vec<String> {
-        unimod_sequence_to_tokens(&*self.sequence, group_modifications)
+        unimod_sequence_to_tokens(&self.sequence, group_modifications)
     }
 
     pub fn to_sage_representation(&self) -> (String, Vec<f64>) {
-        find_unimod_patterns(&*self.sequence)
+        find_unimod_patterns(&self.sequence)
     }
 
     pub fn amino_acid_count(&self) -> usize {
         self.to_tokens(true).len()
     }
 
+    /// Split the sequence into residues with their 1-based positions and modifications
+    ///
+    /// Modifications in front of the first residue are reported as an N-terminal token at position 0,
+    /// which is only present if the N-terminus is modified. C-terminal modifications are stored on the
+    /// last residue (see `parse_proforma`) and therefore reported at its position.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let sequence = PeptideSequence::new("[UNIMOD:1]PEM[UNIMOD:35]K".to_string(), None);
+    /// let tokens = sequence.tokens_with_positions();
+    /// assert_eq!(tokens.len(), 5);
+    /// assert_eq!((tokens[0].residue, tokens[0].position), (None, 0));
+    /// assert_eq!(tokens[0].modifications, vec![(1, 42.010565)]);
+    /// assert_eq!((tokens[3].residue, tokens[3].position), (Some('M'), 3));
+    /// assert_eq!(tokens[3].modifications, vec![(35, 15.994915)]);
+    /// assert!(!tokens[4].is_modified());
+    /// ```
+    pub fn tokens_with_positions(&self) -> Vec<ResidueToken> {
+        let pattern = Regex::new(r"\[UNIMOD:(\d+)]").unwrap();
+        let masses = unimod_modifications_mass_numerical();
+        let mut tokens: Vec<ResidueToken> = Vec::new();
+        let mut num_residues = 0;
+        let mut last_index = 0;
+
+        let mut push_residues = |tokens: &mut Vec<ResidueToken>, residues: &str| {
+            for residue in residues.chars() {
+                num_residues += 1;
+                tokens.push(ResidueToken { residue: Some(residue), position: num_residues, modifications: Vec::new() });
+            }
+        };
+
+        for captures in pattern.captures_iter(&self.sequence) {
+            let tag = captures.get(0).unwrap();
+            push_residues(&mut tokens, &self.sequence[last_index..tag.start()]);
+            // tags in front of the first residue modify the N-terminus
+            if tokens.is_empty() {
+                tokens.push(ResidueToken { residue: None, position: 0, modifications: Vec::new() });
+            }
+            let id: u32 = captures[1].parse().unwrap();
+            tokens.last_mut().unwrap().modifications.push((id, masses.get(&id).copied().unwrap_or(0.0)));
+            last_index = tag.end();
+        }
+        push_residues(&mut tokens, &self.sequence[last_index..]);
+
+        tokens
+    }
+
+    /// Modified residues only, see `tokens_with_positions`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let sequence = PeptideSequence::new("PEPC[UNIMOD:4]TIDES[UNIMOD:21]K".to_string(), None);
+    /// let sites: Vec<(Option<char>, usize)> = sequence.modification_sites().iter().map(|t| (t.residue, t.position)).collect();
+    /// assert_eq!(sites, vec![(Some('C'), 4), (Some('S'), 9)]);
+    /// ```
+    pub fn modification_sites(&self) -> Vec<ResidueToken> {
+        self.tokens_with_positions().into_iter().filter(|token| token.is_modified()).collect()
+    }
+
+    /// Number of occurrences of every amino acid, modifications are ignored
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::PeptideSequence;
+    ///
+    /// let sequence = PeptideSequence::new("PEPTIDE[UNIMOD:1]K".to_string(), None);
+    /// let counts = sequence.composition_counts();
+    /// assert_eq!(counts.get(&'P'), Some(&2));
+    /// assert_eq!(counts.get(&'E'), Some(&2));
+    /// assert_eq!(counts.values().sum::<usize>(), 8);
+    /// ```
+    pub fn composition_counts(&self) -> BTreeMap<char, usize> {
+        let mut counts = BTreeMap::new();
+        for residue in self.tokens_with_positions().into_iter().filter_map(|token| token.residue) {
+            *counts.entry(residue).or_insert(0) += 1;
+        }
+        counts
+    }
+
     pub fn calculate_mono_isotopic_product_ion_spectrum(&self, charge: i32, fragment_type: FragmentType) -> MzSpectrum {
         let product_ions = self.calculate_product_ion_series(charge, fragment_type);
         product_ions.generate_mono_isotopic_spectrum()
@@ -302,6 +597,8 @@ impl PeptideSequence {
                     },
                     charge: target_charge,
                     intensity: 1.0, // Placeholder intensity
+                    label: None,
+                    adduct: None,
                 },
             });
         }
@@ -325,6 +622,8 @@ impl PeptideSequence {
                     },
                     charge: target_charge,
                     intensity: 1.0, // Placeholder intensity
+                    label: None,
+                    adduct: None,
                 },
             });
         }
@@ -332,6 +631,70 @@ impl PeptideSequence {
         PeptideProductIonSeries::new(target_charge, n_terminal_ions, c_terminal_ions)
     }
 
+    /// Calculate the fragment ions of the requested types for all fragment charges up to `max_fragment_charge`
+    ///
+    /// Arguments:
+    ///
+    /// * `fragment_types` - fragment types to generate, N- and C-terminal types can be mixed freely
+    /// * `max_fragment_charge` - highest fragment charge, every ion is generated for charges 1 to this charge
+    ///
+    /// Returns:
+    ///
+    /// * `Vec<PeptideProductIon>` - ions ordered by charge, then by the order of `fragment_types`, then by length
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::{FragmentType, PeptideSequence};
+    ///
+    /// let sequence = PeptideSequence::new("PEPTIDE".to_string(), None);
+    /// let ions = sequence.calculate_fragment_ions(&[FragmentType::C, FragmentType::Z], 2);
+    /// assert_eq!(ions.len(), 2 * 2 * 6);
+    /// assert_eq!(ions[0].label(), "c1+1");
+    /// assert_eq!(ions[6].label(), "z1+1");
+    /// assert_eq!(ions[23].label(), "z6+2");
+    /// ```
+    pub fn calculate_fragment_ions(&self, fragment_types: &[FragmentType], max_fragment_charge: i32) -> Vec<PeptideProductIon> {
+        let mut ions = Vec::new();
+        for charge in 1..=max_fragment_charge.max(1) {
+            for fragment_type in fragment_types {
+                let series = self.calculate_product_ion_series(charge, *fragment_type);
+                ions.extend(if fragment_type.is_n_terminal() { series.n_ions } else { series.c_ions });
+            }
+        }
+        ions
+    }
+
+    /// Associate the b and y ion series of the peptide with predicted fragment intensities
+    ///
+    /// Arguments:
+    ///
+    /// * `charge` - precursor charge, fragment charges up to the charge dimension of `shape` are generated
+    /// * `fragment_type` - type of the N-terminal ions
+    /// * `flat_intensities` - flat intensity array, see `reshape_prosit_array`
+    /// * `normalize` - if true, intensities are divided by the sum of all positive intensities
+    /// * `half_charge_one` - if true, intensities of charge one precursors are halved
+    /// * `shape` - (positions, series, charges) of the flat array, defaults to `PROSIT_SHAPE`
+    ///
+    /// Returns:
+    ///
+    /// * `Result<PeptideProductIonSeriesCollection, String>` - one ion series per fragment charge, ions beyond the
+    ///   positions of the model have zero intensity, an error if the array does not match the shape
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::peptide::{FragmentType, PeptideSequence};
+    ///
+    /// // 34 residues, longer than the 29 cleavage positions of Prosit
+    /// let sequence = PeptideSequence::new("PEPTIDEKPEPTIDEKPEPTIDEKPEPTIDEKAK".to_string(), None);
+    /// let series = sequence.associate_with_predicted_intensities(2, FragmentType::B, vec![1.0; 174], false, false, None).unwrap();
+    /// assert_eq!(series.peptide_ions[0].n_ions.len(), 33);
+    /// assert_eq!(series.peptide_ions[0].n_ions[28].ion.intensity, 1.0);
+    /// assert_eq!(series.peptide_ions[0].n_ions[29].ion.intensity, 0.0);
+    ///
+    /// assert!(sequence.associate_with_predicted_intensities(2, FragmentType::B, vec![1.0; 180], false, false, None).is_err());
+    /// ```
     pub fn associate_with_predicted_intensities(
         &self,
         // TODO: check docs of prosit if charge is meant as precursor charge or max charge of fragments to generate
@@ -340,20 +703,31 @@ impl PeptideSequence {
         flat_intensities: Vec<f64>,
         normalize: bool,
         half_charge_one: bool,
-    ) -> PeptideProductIonSeriesCollection {
+        shape: Option<(usize, usize, usize)>,
+    ) -> Result<PeptideProductIonSeriesCollection, String> {
 
-        let reshaped_intensities = reshape_prosit_array(flat_intensities);
-        let max_charge = std::cmp::min(charge, 3).max(1); // Ensure at least 1 for loop range
+        let (positions, series, charges) = shape.unwrap_or(PROSIT_SHAPE);
+        if series < 2 || charges < 1 {
+            return Err(format!("shape ({}, {}, {}) needs y and b ions and at least one charge", positions, series, charges));
+        }
+
+        let reshaped_intensities = reshape_prosit_array(flat_intensities, shape)?;
+        let max_charge = std::cmp::min(charge, charges as i32).max(1); // Ensure at least 1 for loop range
         let mut sum_intensity = if normalize { 0.0 } else { 1.0 };
         let num_tokens = self.amino_acid_count() - 1; // Full sequence length is not counted as fragment, since nothing is cleaved off, therefore -1
 
+        // ions of long peptides beyond the positions of the model get zero intensity
+        let intensities = |series_index: usize, z: i32| -> Vec<f64> {
+            (0..num_tokens).map(|i| reshaped_intensities.get(i).map_or(0.0, |x| x[series_index][z as usize - 1])).collect()
+        };
+
         let mut peptide_ion_collection = Vec::new();
 
         if normalize {
             for z in 1..=max_charge {
 
-                let intensity_c: Vec<f64> = reshaped_intensities[..num_tokens].iter().map(|x| x[0][z as usize - 1]).filter(|&x| x > 0.0).collect();
-                let intensity_n: Vec<f64> = reshaped_intensities[..num_tokens].iter().map(|x| x[1][z as usize - 1]).filter(|&x| x > 0.0).collect();
+                let intensity_c: Vec<f64> = intensities(0, z).into_iter().filter(|&x| x > 0.0).collect();
+                let intensity_n: Vec<f64> = intensities(1, z).into_iter().filter(|&x| x > 0.0).collect();
 
                 sum_intensity += intensity_n.iter().sum::<f64>() + intensity_c.iter().sum::<f64>();
             }
@@ -362,8 +736,8 @@ impl PeptideSequence {
         for z in 1..=max_charge {
 
             let mut product_ions = self.calculate_product_ion_series(z, fragment_type);
-            let intensity_n: Vec<f64> = reshaped_intensities[..num_tokens].iter().map(|x| x[1][z as usize - 1]).collect();
-            let intensity_c: Vec<f64> = reshaped_intensities[..num_tokens].iter().map(|x| x[0][z as usize - 1]).collect(); // Reverse for y
+            let intensity_n: Vec<f64> = intensities(1, z);
+            let intensity_c: Vec<f64> = intensities(0, z); // Reverse for y
 
             let adjusted_sum_intensity = if max_charge == 1 && half_charge_one { sum_intensity * 2.0 } else { sum_intensity };
 
@@ -377,7 +751,7 @@ impl PeptideSequence {
             peptide_ion_collection.push(PeptideProductIonSeries::new(z, product_ions.n_ions, product_ions.c_ions));
         }
 
-        PeptideProductIonSeriesCollection::new(peptide_ion_collection)
+        Ok(PeptideProductIonSeriesCollection::new(peptide_ion_collection))
     }
 }
 
@@ -418,6 +792,7 @@ impl PeptideProductIonSeries {
             let signal_attributes = SignalAttributes {
                 charge_state: charge,
                 peptide_id: n_ion.ion.sequence.peptide_id.unwrap_or(-1),
+                ion_id: -1,
                 isotope_peak: 0,
                 description: Some(format!("{}_{}_{}", kind, index + 1, 0)),
             };
@@ -442,6 +817,7 @@ impl PeptideProductIonSeries {
             let signal_attributes = SignalAttributes {
                 charge_state: charge,
                 peptide_id: c_ion.ion.sequence.peptide_id.unwrap_or(-1),
+                ion_id: -1,
                 isotope_peak: 0,
                 description: Some(format!("{}_{}_{}", kind, index + 1, 0)),
             };
@@ -500,6 +876,7 @@ impl PeptideProductIonSeries {
                 let signal_attributes = SignalAttributes {
                     charge_state: ion.ion.charge,
                     peptide_id: ion.ion.sequence.peptide_id.unwrap_or(-1),
+                    ion_id: -1,
                     isotope_peak: isotope_counter,
                     // use convention of 1-based indexing for fragment ion enumeration
                     description: Some(format!("{}_{}_{}", ion.kind, index + 1, isotope_counter)),
@@ -535,6 +912,7 @@ impl PeptideProductIonSeries {
                 let signal_attributes = SignalAttributes {
                     charge_state: ion.ion.charge,
                     peptide_id: ion.ion.sequence.peptide_id.unwrap_or(-1),
+                    ion_id: -1,
                     isotope_peak: isotope_counter,
                     description: Some(format!("{}_{}_{}", ion.kind, index + 1, isotope_counter)),
                 };
@@ -599,4 +977,54 @@ impl PeptideProductIonSeriesCollection {
 
         MzSpectrumAnnotated::new(mz_values, intensity_values, annotations)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_mass() {
+        // angiotensin II (C50H71N13O12) and bradykinin (C50H73N15O11)
+        for (sequence, expected) in [("DRVYIHPF", 1046.19), ("RPPGFSPFR", 1060.21)] {
+            let sequence = PeptideSequence::new(sequence.to_string(), None);
+            assert!((sequence.average_mass().unwrap() - expected).abs() < 0.02);
+            assert!(sequence.average_mass().unwrap() > sequence.mono_isotopic_mass() + 0.5);
+        }
+
+        // oxidation adds the average mass of oxygen, the labeled peptide is heavier by the mass of its heavy atoms
+        let plain = PeptideSequence::new("PEPTMIDEK".to_string(), None);
+        let oxidized = PeptideSequence::new("PEPTM[UNIMOD:35]IDEK".to_string(), None);
+        assert!((oxidized.average_mass().unwrap() - plain.average_mass().unwrap() - 15.9994).abs() < 1e-3);
+        let heavy = calculate_peptide_average_mass(&plain, Some(LabelScheme::SilacHeavy)).unwrap();
+        assert!((heavy - plain.average_mass().unwrap() - 7.94).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sodium_adduct_envelope() {
+        let protonated = PeptideIon::new("PEPTIDEK".to_string(), 2, 1.0, None);
+        let sodiated = protonated.with_adduct(Adduct::from_name("[M+H+Na]2+").unwrap());
+
+        let protonated_envelope = protonated.calculate_isotope_distribution(1e-3, 1e-8, 200, 1e-6);
+        let sodiated_envelope = sodiated.calculate_isotope_distribution(1e-3, 1e-8, 200, 1e-6);
+
+        // Na replaces one proton, the whole envelope shifts by (Na - H) / 2
+        let shift = (22.98976928 - 1.00782503) / 2.0;
+        assert!((sodiated_envelope[0].0 - protonated_envelope[0].0 - shift).abs() < 1e-3);
+        assert!((sodiated_envelope[0].0 - sodiated.mz()).abs() < 1e-3);
+
+        // sodium is monoisotopic and the added hydrogen barely matters, the envelope shape is preserved
+        let isotope_peaks = |envelope: &IsotopeDistribution| -> Vec<f64> {
+            let mut peaks = vec![0.0; 4];
+            for (mz, abundance) in envelope.iter() {
+                let isotope = ((mz - envelope[0].0) * 2.0).round() as usize;
+                if isotope < peaks.len() {
+                    peaks[isotope] += abundance;
+                }
+            }
+            peaks
+        };
+        for (sodiated_peak, protonated_peak) in isotope_peaks(&sodiated_envelope).iter().zip(isotope_peaks(&protonated_envelope).iter()) {
+            assert!((sodiated_peak - protonated_peak).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use regex::Regex;
+
+use crate::chemistry::amino_acid::amino_acid_masses;
+use crate::chemistry::unimod::{unimod_modification_names, unimod_modifications_mass_numerical};
+
+/// Reasons why a ProForma string could not be converted to the `[UNIMOD:x]` representation
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProformaError {
+    /// no residues at all
+    Empty,
+    /// character that is not valid at this position, with its position in characters
+    UnexpectedCharacter(usize, char),
+    /// bracket opened at this position is never closed
+    UnclosedBracket(usize),
+    /// residue that is not one of the supported amino acids
+    UnknownResidue(char),
+    /// modification name or accession that is not in the unimod tables
+    UnknownModification(String),
+    /// mass delta that no unimod modification matches at the given precision
+    UnmatchedMassDelta(String),
+    /// charge suffix that is not a nonzero integer
+    InvalidCharge(String),
+    /// valid ProForma that the internal representation cannot express
+    Unsupported(String),
+}
+
+impl Display for ProformaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProformaError::Empty => write!(f, "ProForma sequence has no residues"),
+            ProformaError::UnexpectedCharacter(position, character) => write!(
+                f, "unexpected character {} at position {} of ProForma sequence", character, position),
+            ProformaError::UnclosedBracket(position) => write!(f, "bracket at position {} is never closed", position),
+            ProformaError::UnknownResidue(residue) => write!(f, "unknown residue {}", residue),
+            ProformaError::UnknownModification(modification) => write!(f, "unknown modification {}", modification),
+            ProformaError::UnmatchedMassDelta(delta) => write!(f, "no unimod modification matches mass delta {}", delta),
+            ProformaError::InvalidCharge(charge) => write!(f, "invalid charge {}", charge),
+            ProformaError::Unsupported(feature) => write!(f, "ProForma {} is not supported", feature),
+        }
+    }
+}
+
+impl std::error::Error for ProformaError {}
+
+/// Unimod id of a mass delta, the unimod masses are rounded to the precision of the delta and among several
+/// matches, modifications with a common name and then the closest mass are preferred
+fn match_mass_delta(delta: &str) -> Result<u32, ProformaError> {
+    let unmatched = || ProformaError::UnmatchedMassDelta(delta.to_string());
+    let mass: f64 = delta.parse().map_err(|_| unmatched())?;
+    let decimals = delta.split_once('.').map_or(0, |(_, fraction)| fraction.len()) as i32;
+    let scale = 10f64.powi(decimals);
+    let named: Vec<u32> = unimod_modification_names().into_values().collect();
+
+    unimod_modifications_mass_numerical().into_iter()
+        .filter(|(_, unimod_mass)| (unimod_mass * scale).round() == (mass * scale).round())
+        .min_by(|(a, a_mass), (b, b_mass)| {
+            (!named.contains(a)).cmp(&!named.contains(b))
+                .then((a_mass - mass).abs().total_cmp(&(b_mass - mass).abs()))
+                .then(a.cmp(b))
+        })
+        .map(|(id, _)| id)
+        .ok_or_else(unmatched)
+}
+
+/// Unimod id of the content of a ProForma modification bracket, None for INFO tags
+fn resolve_modification(content: &str) -> Result<Option<u32>, ProformaError> {
+    if content.contains('#') {
+        return Err(ProformaError::Unsupported(format!("cross-link, branch or ambiguity group {}", content)));
+    }
+
+    // synonyms separated by | describe the same modification, the first one that resolves is used
+    let mut first_error = None;
+    for alternative in content.split('|') {
+        let resolved = match alternative.split_once(':') {
+            Some((prefix, value)) => match prefix.to_uppercase().as_str() {
+                "INFO" => return Ok(None),
+                "UNIMOD" => value.parse::<u32>().ok()
+                    .filter(|id| unimod_modifications_mass_numerical().contains_key(id))
+                    .ok_or_else(|| ProformaError::UnknownModification(alternative.to_string())),
+                "U" => resolve_name(value),
+                "OBS" => match_mass_delta(value),
+                "M" | "MOD" | "R" | "RESID" | "X" | "XLMOD" | "G" | "GNO" | "GLYCAN" | "FORMULA" =>
+                    Err(ProformaError::Unsupported(format!("modification {}", alternative))),
+                _ => resolve_name(alternative),
+            },
+            None if alternative.starts_with('+') || alternative.starts_with('-') => match_mass_delta(alternative),
+            None => resolve_name(alternative),
+        };
+        match resolved {
+            Ok(id) => return Ok(Some(id)),
+            Err(error) => { first_error.get_or_insert(error); }
+        }
+    }
+
+    Err(first_error.unwrap_or_else(|| ProformaError::UnknownModification(content.to_string())))
+}
+
+/// Unimod id of a common unimod name, case insensitive
+fn resolve_name(name: &str) -> Result<u32, ProformaError> {
+    unimod_modification_names().into_iter()
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(name))
+        .map(|(_, id)| id)
+        .ok_or_else(|| ProformaError::UnknownModification(name.to_string()))
+}
+
+/// Content of the bracket opening at `start` and the position after its closing bracket, nested brackets are balanced
+fn read_bracket(chars: &[char], start: usize, open: char, close: char) -> Result<(String, usize), ProformaError> {
+    let mut depth = 0;
+    for (position, &c) in chars.iter().enumerate().skip(start) {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Ok((chars[start + 1..position].iter().collect(), position + 1));
+            }
+        }
+    }
+    Err(ProformaError::UnclosedBracket(start))
+}
+
+/// Read consecutive modification brackets starting at `position`, returns the unimod tags and the position after
+fn read_modifications(chars: &[char], mut position: usize) -> Result<(String, usize), ProformaError> {
+    let mut tags = String::new();
+    while chars.get(position) == Some(&'[') {
+        let (content, next) = read_bracket(chars, position, '[', ']')?;
+        if let Some(id) = resolve_modification(&content)? {
+            tags.push_str(&format!("[UNIMOD:{}]", id));
+        }
+        position = next;
+    }
+    Ok((tags, position))
+}
+
+/// Parse a ProForma 2.0 peptidoform into the `[UNIMOD:x]` representation of `PeptideSequence`
+///
+/// Named unimod modifications, `UNIMOD:` accessions, `U:` names, `Obs:` and plain mass deltas, N- and
+/// C-terminal modifications, fixed global modifications such as `<[Carbamidomethyl]@C>`, INFO tags and a
+/// charge suffix are understood. N-terminal modifications become a leading tag, C-terminal modifications
+/// are attached to the last residue, which has the same mass.
+///
+/// Arguments:
+///
+/// * `proforma` - ProForma string, e.g. `[Acetyl]-EM[Oxidation]EVEES[+79.966]PEK/2`
+///
+/// Returns:
+///
+/// * `(String, Option<i32>)` - sequence with `[UNIMOD:x]` tags and the charge of the suffix
+///
+/// # Examples
+///
+/// ```
+/// use mscore::data::proforma::{parse_proforma, ProformaError};
+///
+/// let (sequence, charge) = parse_proforma("[Acetyl]-EM[Oxidation]EVEES[+79.966]PEK/2").unwrap();
+/// assert_eq!(sequence, "[UNIMOD:1]EM[UNIMOD:35]EVEES[UNIMOD:21]PEK");
+/// assert_eq!(charge, Some(2));
+/// assert_eq!(parse_proforma("PEPT[Unknownium]IDE"), Err(ProformaError::UnknownModification("Unknownium".to_string())));
+/// ```
+pub fn parse_proforma(proforma: &str) -> Result<(String, Option<i32>), ProformaError> {
+    let chars: Vec<char> = proforma.trim().chars().collect();
+    let valid_residues = amino_acid_masses();
+    let mut position = 0;
+
+    // fixed modifications apply to every residue of the given types
+    let mut fixed_modifications: HashMap<char, String> = HashMap::new();
+    while chars.get(position) == Some(&'<') {
+        let (content, next) = read_bracket(&chars, position, '<', '>')?;
+        let (modification, residues) = content.split_once('@')
+            .ok_or_else(|| ProformaError::Unsupported(format!("global modification <{}>", content)))?;
+        let modification: Vec<char> = modification.chars().collect();
+        let (tags, end) = read_modifications(&modification, 0)?;
+        if end != modification.len() || tags.is_empty() {
+            return Err(ProformaError::Unsupported(format!("global modification <{}>", content)));
+        }
+        for residue in residues.split(',') {
+            let residue = residue.trim();
+            if residue.len() != 1 {
+                return Err(ProformaError::Unsupported(format!("global modification target {}", residue)));
+            }
+            fixed_modifications.entry(residue.chars().next().unwrap()).or_default().push_str(&tags);
+        }
+        position = next;
+    }
+
+    match chars.get(position) {
+        Some('{') => return Err(ProformaError::Unsupported("labile modification".to_string())),
+        Some('(') if chars.get(position + 1) == Some(&'?') => return Err(ProformaError::Unsupported("ambiguous sequence".to_string())),
+        _ => {}
+    }
+
+    let mut sequence = String::new();
+    if chars.get(position) == Some(&'[') {
+        let (tags, next) = read_modifications(&chars, position)?;
+        match chars.get(next) {
+            Some('-') => {}
+            Some('?') => return Err(ProformaError::Unsupported("modification of unknown position".to_string())),
+            Some(&c) => return Err(ProformaError::UnexpectedCharacter(next, c)),
+            None => return Err(ProformaError::Empty),
+        }
+        sequence.push_str(&tags);
+        position = next + 1;
+    }
+
+    let mut num_residues = 0;
+    let mut charge = None;
+    while let Some(&c) = chars.get(position) {
+        match c {
+            'A'..='Z' => {
+                if !valid_residues.contains_key(c.to_string().as_str()) {
+                    return Err(ProformaError::UnknownResidue(c));
+                }
+                sequence.push(c);
+                num_residues += 1;
+                let (tags, next) = read_modifications(&chars, position + 1)?;
+                sequence.push_str(&tags);
+                if let Some(fixed) = fixed_modifications.get(&c) {
+                    sequence.push_str(fixed);
+                }
+                position = next;
+            }
+            '-' if chars.get(position + 1) == Some(&'[') && num_residues > 0 => {
+                let (tags, next) = read_modifications(&chars, position + 1)?;
+                sequence.push_str(&tags);
+                position = next;
+                if position < chars.len() && chars[position] != '/' {
+                    return Err(ProformaError::UnexpectedCharacter(position, chars[position]));
+                }
+            }
+            '/' => {
+                let suffix: String = chars[position + 1..].iter().collect();
+                if suffix.contains('+') || suffix.contains('[') {
+                    return Err(ProformaError::Unsupported(format!("charge suffix /{}", suffix)));
+                }
+                charge = Some(suffix.parse::<i32>().ok().filter(|z| *z != 0)
+                    .ok_or(ProformaError::InvalidCharge(suffix))?);
+                break;
+            }
+            '+' => return Err(ProformaError::Unsupported("chimeric spectrum".to_string())),
+            '(' => return Err(ProformaError::Unsupported("modification range".to_string())),
+            _ => return Err(ProformaError::UnexpectedCharacter(position, c)),
+        }
+    }
+
+    if num_residues == 0 {
+        return Err(ProformaError::Empty);
+    }
+
+    Ok((sequence, charge))
+}
+
+/// Write a sequence with `[UNIMOD:x]` tags in ProForma 2.0 notation, common modifications by name and others by
+/// accession, a leading tag as N-terminal modification
+///
+/// Arguments:
+///
+/// * `sequence` - peptide sequence with modifications in the format [UNIMOD:ID]
+/// * `charge` - optional charge written as suffix
+///
+/// Returns:
+///
+/// * `String` - ProForma string
+///
+/// # Examples
+///
+/// ```
+/// use mscore::data::proforma::to_proforma;
+///
+/// assert_eq!(to_proforma("[UNIMOD:1]EM[UNIMOD:35]EVEES[UNIMOD:21]PEK", Some(2)), "[Acetyl]-EM[Oxidation]EVEES[Phospho]PEK/2");
+/// assert_eq!(to_proforma("PEPTIDEK[UNIMOD:259]", None), "PEPTIDEK[UNIMOD:259]");
+/// ```
+pub fn to_proforma(sequence: &str, charge: Option<i32>) -> String {
+    let pattern = Regex::new(r"\[UNIMOD:(\d+)]").unwrap();
+    let names: HashMap<u32, &str> = unimod_modification_names().into_iter().map(|(name, id)| (id, name)).collect();
+
+    let named = |tags: &str| -> String {
+        pattern.replace_all(tags, |captures: &regex::Captures| {
+            let id: u32 = captures[1].parse().unwrap();
+            match names.get(&id) {
+                Some(name) => format!("[{}]", name),
+                None => format!("[UNIMOD:{}]", id),
+            }
+        }).to_string()
+    };
+
+    // tags before the first residue modify the N-terminus
+    let n_terminal_end = Regex::new(r"^(\[UNIMOD:\d+])+").unwrap().find(sequence).map_or(0, |m| m.end());
+
+    let mut result = String::new();
+    if n_terminal_end > 0 {
+        result.push_str(&named(&sequence[..n_terminal_end]));
+        result.push('-');
+    }
+    result.push_str(&named(&sequence[n_terminal_end..]));
+    if let Some(charge) = charge {
+        result.push_str(&format!("/{}", charge));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proforma_specification_examples() {
+        let expected = "EM[UNIMOD:35]EVEES[UNIMOD:21]PEK";
+        for example in [
+            "EM[Oxidation]EVEES[Phospho]PEK",
+            "EM[+15.9949]EVEES[+79.9663]PEK",
+            "EM[U:Oxidation]EVEES[U:Phospho]PEK",
+            "EM[UNIMOD:35]EVEES[UNIMOD:21]PEK",
+            "EM[Obs:+15.995]EVEES[Phospho|+79.966331]PEK",
+            "EM[Oxidation]EVEES[Phospho|INFO:newly discovered]PEK",
+        ] {
+            assert_eq!(parse_proforma(example), Ok((expected.to_string(), None)), "{}", example);
+        }
+
+        assert_eq!(parse_proforma("[iTRAQ4plex]-EM[U:Oxidation]EVNES[Phospho]PEK[iTRAQ4plex]-[Methyl]"),
+                   Ok(("[UNIMOD:214]EM[UNIMOD:35]EVNES[UNIMOD:21]PEK[UNIMOD:214][UNIMOD:34]".to_string(), None)));
+        assert_eq!(parse_proforma("<[Carbamidomethyl]@C>ATPEILTCNSIGCLK"),
+                   Ok(("ATPEILTC[UNIMOD:4]NSIGC[UNIMOD:4]LK".to_string(), None)));
+        assert_eq!(parse_proforma("ELV[INFO:xxxxx]IS"), Ok(("ELVIS".to_string(), None)));
+        assert_eq!(parse_proforma("EMEVEESPEK/2"), Ok(("EMEVEESPEK".to_string(), Some(2))));
+        assert_eq!(parse_proforma("PEPTIDE-[+42]/3"), Ok(("PEPTIDE[UNIMOD:1]".to_string(), Some(3))));
+    }
+
+    #[test]
+    fn test_proforma_unsupported_and_invalid() {
+        let unsupported = |proforma: &str| matches!(parse_proforma(proforma), Err(ProformaError::Unsupported(_)));
+        assert!(unsupported("EMEVTK[XLMOD:02001#XL1]SESPEK"));
+        assert!(unsupported("{Glycan:Hex}EM[Oxidation]EVNES[Phospho]PEK"));
+        assert!(unsupported("[Phospho]?EM[Oxidation]EVTSESPEK"));
+        assert!(unsupported("EMEVEESPEK/2+ELVISLIVER/3"));
+        assert!(unsupported("PRT(ESFRMS)[+19.0523]ISK"));
+        assert!(unsupported("EM[MOD:00719]EVEES[MOD:00046]PEK"));
+
+        assert_eq!(parse_proforma(""), Err(ProformaError::Empty));
+        assert_eq!(parse_proforma("[Acetyl]-"), Err(ProformaError::Empty));
+        assert_eq!(parse_proforma("PEPTIDE/0"), Err(ProformaError::InvalidCharge("0".to_string())));
+        assert_eq!(parse_proforma("PEPT[Phospho"), Err(ProformaError::UnclosedBracket(4)));
+        assert_eq!(parse_proforma("PEPTIDEB"), Err(ProformaError::UnknownResidue('B')));
+        assert_eq!(parse_proforma("PEPT[+1234.5678]IDE"), Err(ProformaError::UnmatchedMassDelta("+1234.5678".to_string())));
+    }
+
+    #[test]
+    fn test_proforma_round_trip() {
+        for proforma in [
+            "[Acetyl]-EM[Oxidation]EVEES[Phospho]PEK/2",
+            "ATPEILTC[Carbamidomethyl]NSIGC[Carbamidomethyl]LK",
+            "S[Phospho]EQUENCE",
+        ] {
+            let (sequence, charge) = parse_proforma(proforma).unwrap();
+            assert_eq!(to_proforma(&sequence, charge), proforma);
+        }
+    }
+}
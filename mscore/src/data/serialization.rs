@@ -0,0 +1,173 @@
+use std::fmt::{Display, Formatter};
+use bincode::{Decode, Encode};
+
+/// Version of the binary layout written by `BinarySerializable::to_bytes`, stored as its first byte.
+/// Bump it whenever the layout of a serializable type changes, so old caches are rejected instead of misread.
+pub const BINARY_FORMAT_VERSION: u8 = 1;
+
+/// Reasons why bytes could not be turned back into a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerializationError {
+    /// no bytes at all, not even the version byte
+    Empty,
+    /// bytes were written with a different version of the binary layout
+    UnsupportedVersion(u8),
+    /// bytes do not describe a value of the requested type
+    Decode(String),
+}
+
+impl Display for SerializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerializationError::Empty => write!(f, "cannot decode from empty bytes"),
+            SerializationError::UnsupportedVersion(version) => write!(
+                f, "unsupported binary format version {}, expected {}", version, BINARY_FORMAT_VERSION),
+            SerializationError::Decode(message) => write!(f, "invalid binary data: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+/// Compact binary encoding via bincode, prefixed by `BINARY_FORMAT_VERSION`
+pub trait BinarySerializable: Encode + Decode<()> + Sized {
+    /// Encode the value, the first byte is the version of the binary layout
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::serialization::{BinarySerializable, BINARY_FORMAT_VERSION};
+    /// use mscore::data::spectrum::MzSpectrum;
+    ///
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0], vec![10.0, 20.0]);
+    /// let bytes = spectrum.to_bytes();
+    /// assert_eq!(bytes[0], BINARY_FORMAT_VERSION);
+    /// assert_eq!(MzSpectrum::from_bytes(&bytes).unwrap().mz, spectrum.mz);
+    /// ```
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![BINARY_FORMAT_VERSION];
+        bytes.extend(bincode::encode_to_vec(self, bincode::config::standard()).expect("encoding into memory does not fail"));
+        bytes
+    }
+
+    /// Decode a value written by `to_bytes`
+    ///
+    /// # Errors
+    ///
+    /// * `SerializationError` - if the bytes are empty, of another format version, or do not describe a complete value
+    fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let (version, payload) = bytes.split_first().ok_or(SerializationError::Empty)?;
+        if *version != BINARY_FORMAT_VERSION {
+            return Err(SerializationError::UnsupportedVersion(*version));
+        }
+        let (value, read) = bincode::decode_from_slice(payload, bincode::config::standard())
+            .map_err(|e| SerializationError::Decode(e.to_string()))?;
+        if read != payload.len() {
+            return Err(SerializationError::Decode(format!("{} trailing bytes", payload.len() - read)));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::spectrum::{ApproxEq, IndexedMzSpectrum, MsType, MzSpectrum, ToResolution, Vectorized};
+    use crate::timstof::frame::{RawTimsFrame, TimsFrame, TimsFrameCsr, TimsFrameVectorized};
+    use crate::timstof::slice::{TimsSlice, TimsSliceVectorized};
+    use crate::simulation::annotation::TimsFrameAnnotated;
+    use crate::timstof::spectrum::TimsSpectrum;
+
+    fn frame(frame_id: i32, num_peaks: usize) -> TimsFrame {
+        TimsFrame::new(
+            frame_id,
+            if frame_id % 10 == 1 { MsType::Precursor } else { MsType::FragmentDia },
+            frame_id as f64 * 0.1,
+            (0..num_peaks).map(|i| (i / 100) as i32).collect(),
+            (0..num_peaks).map(|i| 1.6 - (i / 100) as f64 * 0.001).collect(),
+            (0..num_peaks).map(|i| i as i32 * 3).collect(),
+            (0..num_peaks).map(|i| 100.0 + i as f64 * 0.013).collect(),
+            (0..num_peaks).map(|i| (i % 977) as f64).collect(),
+        )
+    }
+
+    fn assert_frame_eq(a: &TimsFrame, b: &TimsFrame) {
+        assert_eq!(a.frame_id, b.frame_id);
+        assert_eq!(a.ms_type, b.ms_type);
+        assert_eq!(a.scan, b.scan);
+        assert_eq!(a.tof, b.tof);
+        assert_eq!(a.ims_frame.retention_time, b.ims_frame.retention_time);
+        assert_eq!(a.ims_frame.mobility, b.ims_frame.mobility);
+        assert_eq!(a.ims_frame.mz, b.ims_frame.mz);
+        assert_eq!(a.ims_frame.intensity, b.ims_frame.intensity);
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        for original in [frame(3, 1_000), TimsFrame::default()] {
+            let decoded = TimsFrame::from_bytes(&original.to_bytes()).unwrap();
+            assert_frame_eq(&decoded, &original);
+        }
+
+        let raw = RawTimsFrame { frame_id: 1, retention_time: 2.5, ms_type: MsType::Precursor, scan: vec![1, 2], tof: vec![10, 20], intensity: vec![5.0, 6.0] };
+        let decoded = RawTimsFrame::from_bytes(&raw.to_bytes()).unwrap();
+        assert_eq!((decoded.scan, decoded.tof, decoded.intensity), (raw.scan, raw.tof, raw.intensity));
+    }
+
+    #[test]
+    fn test_spectrum_round_trip() {
+        let spectrum = TimsSpectrum::new(1, 50, 10.0, 1.1, MsType::Precursor, IndexedMzSpectrum::new(vec![1, 2], vec![100.0, 100.1], vec![5.0, 7.0]));
+        let decoded = TimsSpectrum::from_bytes(&spectrum.to_bytes()).unwrap();
+        assert_eq!((decoded.frame_id, decoded.scan, decoded.mobility), (1, 50, 1.1));
+        assert_eq!(decoded.spectrum.index, spectrum.spectrum.index);
+        assert_eq!(decoded.spectrum.mz_spectrum.mz, spectrum.spectrum.mz_spectrum.mz);
+        assert_eq!(decoded.spectrum.mz_spectrum.intensity, spectrum.spectrum.mz_spectrum.intensity);
+
+        let empty = MzSpectrum::from_bytes(&MzSpectrum::new(vec![], vec![]).to_bytes()).unwrap();
+        assert!(empty.mz.is_empty() && empty.intensity.is_empty());
+    }
+
+    #[test]
+    fn test_large_slice_round_trip() {
+        let slice = TimsSlice::new((1..=50).map(|frame_id| frame(frame_id, 20_000)).collect());
+        let decoded = TimsSlice::from_bytes(&slice.to_bytes()).unwrap();
+
+        assert_eq!(decoded.frames.len(), slice.frames.len());
+        for (a, b) in decoded.frames.iter().zip(slice.frames.iter()) {
+            assert_frame_eq(a, b);
+        }
+        assert!(TimsSlice::from_bytes(&TimsSlice::new(vec![]).to_bytes()).unwrap().frames.is_empty());
+    }
+
+    #[test]
+    fn test_million_peak_frame_round_trip() {
+        let original = frame(7, 1_200_000);
+        let decoded = TimsFrame::from_bytes(&original.to_bytes()).unwrap();
+        assert!(decoded.approx_eq(&original, 0.0));
+    }
+
+    #[test]
+    fn test_derived_types_round_trip() {
+        let vectorized = frame(1, 5_000).vectorized(2);
+        assert!(TimsFrameVectorized::from_bytes(&vectorized.to_bytes()).unwrap().approx_eq(&vectorized, 0.0));
+
+        let csr = frame(2, 5_000).to_csr(2);
+        assert!(TimsFrameCsr::from_bytes(&csr.to_bytes()).unwrap().approx_eq(&csr, 0.0));
+
+        let slice = TimsSlice::new((1..=3).map(|frame_id| frame(frame_id, 1_000)).collect()).vectorized(2, 1);
+        assert!(TimsSliceVectorized::from_bytes(&slice.to_bytes()).unwrap().approx_eq(&slice, 0.0));
+
+        let annotated = frame(3, 1_000).to_resolution(2).to_noise_annotated_tims_frame();
+        assert!(TimsFrameAnnotated::from_bytes(&annotated.to_bytes()).unwrap().approx_eq(&annotated, 0.0));
+    }
+
+    #[test]
+    fn test_invalid_bytes() {
+        let mut bytes = frame(1, 10).to_bytes();
+        assert_eq!(TimsFrame::from_bytes(&[]).unwrap_err(), SerializationError::Empty);
+        assert!(matches!(TimsFrame::from_bytes(&bytes[..bytes.len() - 1]), Err(SerializationError::Decode(_))));
+
+        bytes[0] = BINARY_FORMAT_VERSION + 1;
+        assert_eq!(TimsFrame::from_bytes(&bytes).unwrap_err(), SerializationError::UnsupportedVersion(BINARY_FORMAT_VERSION + 1));
+    }
+}
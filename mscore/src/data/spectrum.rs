@@ -4,12 +4,14 @@ use nalgebra::DVector;
 use std::fmt::{Display, Formatter};
 use bincode::{Decode, Encode};
 use serde::{Serialize, Deserialize};
+use crate::data::serialization::BinarySerializable;
+use crate::algorithm::noise::{estimate_noise, signal_to_noise, NoiseEstimator, MIN_NOISE_PEAKS};
 
 extern crate rand;
 
 use rand::distributions::{Uniform, Distribution};
-use rand::rngs::ThreadRng;
-use statrs::distribution::Normal;
+use rand::Rng;
+use statrs::distribution::{Exp, Normal, Poisson};
 
 /// Represents a vectorized mass spectrum.
 pub trait ToResolution {
@@ -21,18 +23,54 @@ pub trait Vectorized<T> {
     fn vectorized(&self, resolution: i32) -> T;
 }
 
+/// Structural comparison that allows floating point values to differ by an absolute tolerance,
+/// ids, indices, scans and tof values have to match exactly.
+///
+/// A tolerance of 0.0 is exact equality, except that NaN values at the same position are equal.
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool;
+}
+
+/// Compare two floating point values with an absolute tolerance, NaN equals NaN
+///
+/// Example:
+///
+/// ```
+/// use mscore::data::spectrum::approx_eq_value;
+///
+/// assert!(approx_eq_value(1.0, 1.05, 0.1));
+/// assert!(!approx_eq_value(1.0, 1.05, 0.0));
+/// assert!(approx_eq_value(f64::NAN, f64::NAN, 0.0));
+/// assert!(approx_eq_value(f64::INFINITY, f64::INFINITY, 0.0));
+/// ```
+pub fn approx_eq_value(a: f64, b: f64, tolerance: f64) -> bool {
+    a == b || (a - b).abs() <= tolerance || (a.is_nan() && b.is_nan())
+}
+
+/// Compare two sequences of floating point values element wise, see `approx_eq_value`
+pub fn approx_eq_values<T: Copy + Into<f64>>(a: &[T], b: &[T], tolerance: f64) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| approx_eq_value((*x).into(), (*y).into(), tolerance))
+}
+
 /// Represents the type of spectrum.
 ///
 /// # Description
 ///
 /// The `SpecType` enum is used to distinguish between precursor and fragment spectra.
+/// Variants are appended to keep the binary encoding of existing variants stable.
 ///
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Encode, Decode)]
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize, Encode, Decode)]
 pub enum MsType {
     Precursor,
     FragmentDda,
     FragmentDia,
+    #[default]
     Unknown,
+    FragmentPrm,
+    /// diaPASEF frame of a MIDIA run, stored with the same MsMsType as diaPASEF frames
+    FragmentMidia,
+    /// MS1 frame of a MALDI run, stored with the same MsMsType as precursor frames
+    Maldi,
 }
 
 impl MsType {
@@ -40,13 +78,16 @@ impl MsType {
     ///
     /// # Arguments
     ///
-    /// * `ms_type` - An integer value corresponding to the `MsType` enum.
+    /// * `ms_type` - An integer value corresponding to the `MsType` enum, the MsMsType column of a TDF.
+    ///   MIDIA and MALDI frames share their code with diaPASEF and precursor frames and can only
+    ///   be told apart by the acquisition mode of the run.
     ///
     pub fn new(ms_type: i32) -> MsType {
         match ms_type {
             0 => MsType::Precursor,
             8 => MsType::FragmentDda,
             9 => MsType::FragmentDia,
+            10 => MsType::FragmentPrm,
             _ => MsType::Unknown,
         }
     }
@@ -57,23 +98,23 @@ impl MsType {
             MsType::Precursor => 0,
             MsType::FragmentDda => 8,
             MsType::FragmentDia => 9,
+            MsType::FragmentPrm => 10,
+            MsType::FragmentMidia => 9,
+            MsType::Maldi => 0,
             MsType::Unknown => -1,
         }
     }
 }
 
-impl Default for MsType {
-    fn default() -> Self {
-        MsType::Unknown
-    }
-}
-
 impl Display for MsType {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             MsType::Precursor => write!(f, "Precursor"),
             MsType::FragmentDda => write!(f, "FragmentDda"),
             MsType::FragmentDia => write!(f, "FragmentDia"),
+            MsType::FragmentPrm => write!(f, "FragmentPrm"),
+            MsType::FragmentMidia => write!(f, "FragmentMidia"),
+            MsType::Maldi => write!(f, "Maldi"),
             MsType::Unknown => write!(f, "Unknown"),
         }
     }
@@ -86,6 +127,22 @@ pub struct MzSpectrum {
     pub intensity: Vec<f64>,
 }
 
+impl ApproxEq for MzSpectrum {
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::spectrum::{ApproxEq, MzSpectrum};
+    ///
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0], vec![10.0, 20.0]);
+    /// let shifted = MzSpectrum::new(vec![100.0, 200.001], vec![10.0, 20.0]);
+    /// assert!(spectrum.approx_eq(&shifted, 1e-2));
+    /// assert!(!spectrum.approx_eq(&shifted, 0.0));
+    /// ```
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        approx_eq_values(&self.mz, &other.mz, tolerance) && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
 impl MzSpectrum {
     /// Constructs a new `MzSpectrum`.
     ///
@@ -217,7 +274,7 @@ impl MzSpectrum {
                 mean_mz = 0.0;
             }
 
-            mean_mz += current_mz * current_intensity as f64;
+            mean_mz += current_mz * current_intensity;
             sum_i += current_intensity;
             last_mz = current_mz;
         }
@@ -260,7 +317,25 @@ impl MzSpectrum {
 
     pub fn add_mz_noise_uniform(&self, ppm: f64, right_drag: bool) -> Self {
         let mut rng = rand::thread_rng();
-        self.add_mz_noise(ppm, &mut rng, |rng, mz, ppm| {
+        self.add_mz_noise_uniform_with_rng(ppm, right_drag, &mut rng)
+    }
+
+    /// Same as `add_mz_noise_uniform`, but draws from the given random number generator
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MzSpectrum;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0], vec![10.0, 20.0]);
+    /// let a = spectrum.add_mz_noise_uniform_with_rng(5.0, false, &mut StdRng::seed_from_u64(42));
+    /// let b = spectrum.add_mz_noise_uniform_with_rng(5.0, false, &mut StdRng::seed_from_u64(42));
+    /// assert_eq!(a.mz, b.mz);
+    /// ```
+    pub fn add_mz_noise_uniform_with_rng<R: Rng>(&self, ppm: f64, right_drag: bool, rng: &mut R) -> Self {
+        self.add_mz_noise(ppm, rng, |rng, mz, ppm| {
 
             let ppm_mz = match right_drag {
                 true => mz * ppm / 1e6 / 2.0,
@@ -278,16 +353,404 @@ impl MzSpectrum {
 
     pub fn add_mz_noise_normal(&self, ppm: f64) -> Self {
         let mut rng = rand::thread_rng();
-        self.add_mz_noise(ppm, &mut rng, |rng, mz, ppm| {
+        self.add_mz_noise_normal_with_rng(ppm, &mut rng)
+    }
+
+    /// Same as `add_mz_noise_normal`, but draws from the given random number generator
+    pub fn add_mz_noise_normal_with_rng<R: Rng>(&self, ppm: f64, rng: &mut R) -> Self {
+        self.add_mz_noise(ppm, rng, |rng, mz, ppm| {
             let ppm_mz = mz * ppm / 1e6;
             let dist = Normal::new(mz, ppm_mz / 3.0).unwrap();
             dist.sample(rng)
         })
     }
 
-    fn add_mz_noise<F>(&self, ppm: f64, rng: &mut ThreadRng, noise_fn: F) -> Self
+    /// Replace every intensity by a Poisson sample with the intensity as expected number of events,
+    /// peaks without any detected event are removed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MzSpectrum;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0], vec![1000.0, 0.0]);
+    /// let noisy = spectrum.sample_intensity_poisson_with_rng(&mut StdRng::seed_from_u64(42));
+    /// assert_eq!(noisy.mz, vec![100.0]);
+    /// assert!((noisy.intensity[0] - 1000.0).abs() < 200.0);
+    /// ```
+    pub fn sample_intensity_poisson_with_rng<R: Rng>(&self, rng: &mut R) -> Self {
+        let mut mz = Vec::with_capacity(self.mz.len());
+        let mut intensity = Vec::with_capacity(self.intensity.len());
+
+        for (&m, &i) in self.mz.iter().zip(self.intensity.iter()) {
+            if i <= 0.0 {
+                continue;
+            }
+            let events: f64 = Poisson::new(i).unwrap().sample(rng);
+            if events > 0.0 {
+                mz.push(m);
+                intensity.push(events);
+            }
+        }
+
+        MzSpectrum { mz, intensity }
+    }
+
+    /// Create random background peaks, the number of peaks is Poisson distributed with mean `density`,
+    /// m/z values are uniform in [mz_min, mz_max] and intensities exponentially distributed with mean `mean_intensity`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MzSpectrum;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let background = MzSpectrum::random_background_with_rng(50.0, 100.0, 1700.0, 5.0, &mut StdRng::seed_from_u64(1));
+    /// assert!(background.mz.iter().all(|&mz| mz >= 100.0 && mz <= 1700.0));
+    /// assert!(background.intensity.iter().all(|&i| i > 0.0));
+    /// ```
+    pub fn random_background_with_rng<R: Rng>(density: f64, mz_min: f64, mz_max: f64, mean_intensity: f64, rng: &mut R) -> Self {
+        if density <= 0.0 {
+            return MzSpectrum { mz: vec![], intensity: vec![] };
+        }
+
+        let num_peaks: f64 = Poisson::new(density).unwrap().sample(rng);
+        let mz_dist = Uniform::from(mz_min..=mz_max);
+        let intensity_dist = Exp::new(1.0 / mean_intensity).unwrap();
+
+        let mut peaks: Vec<(f64, f64)> = (0..num_peaks as usize)
+            .map(|_| (mz_dist.sample(rng), intensity_dist.sample(rng)))
+            .collect();
+        peaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let (mz, intensity) = peaks.into_iter().unzip();
+        MzSpectrum { mz, intensity }
+    }
+
+    /// Keep the `n` most intense peaks, ties at the cutoff are resolved in favor of the lower m/z.
+    /// The kept peaks stay in their original order
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0, 300.0, 400.0], vec![5.0, 10.0, 5.0, 1.0]);
+    /// let top = spectrum.top_n(2);
+    /// assert_eq!(top.mz, vec![100.0, 200.0]);
+    /// assert_eq!(spectrum.top_n(10).mz, spectrum.mz);
+    /// ```
+    pub fn top_n(&self, n: usize) -> MzSpectrum {
+        let indices = top_n_indices(&self.mz, &self.intensity, 0..self.mz.len(), n);
+        MzSpectrum {
+            mz: indices.iter().map(|&i| self.mz[i]).collect(),
+            intensity: indices.iter().map(|&i| self.intensity[i]).collect(),
+        }
+    }
+
+    /// Keep the peaks with at least `min_fraction_of_base_peak` of the intensity of the most intense peak
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let spectrum = MzSpectrum::new(vec![100.0, 200.0, 300.0], vec![5.0, 100.0, 1.0]);
+    /// let filtered = spectrum.filter_relative_intensity(0.05);
+    /// assert_eq!(filtered.mz, vec![100.0, 200.0]);
+    /// ```
+    pub fn filter_relative_intensity(&self, min_fraction_of_base_peak: f64) -> MzSpectrum {
+        let base_peak = self.intensity.iter().copied().fold(0.0, f64::max);
+        let threshold = base_peak * min_fraction_of_base_peak;
+        let (mz, intensity) = self.mz.iter().zip(&self.intensity)
+            .filter(|(_, &intensity)| intensity >= threshold)
+            .map(|(&mz, &intensity)| (mz, intensity))
+            .unzip();
+        MzSpectrum { mz, intensity }
+    }
+
+    /// Combine two spectra, summing the intensities of peaks that fall into the same m/z bin
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - spectrum to add
+    /// * `resolution` - decimal places of the m/z bins, `+` uses 6 which merges only (numerically) identical m/z values
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let a = MzSpectrum::new(vec![100.01, 200.0], vec![10.0, 20.0]);
+    /// let b = MzSpectrum::new(vec![100.014, 300.0], vec![5.0, 30.0]);
+    /// let sum = a.add_with_resolution(&b, 2);
+    /// assert_eq!(sum.mz, vec![100.01, 200.0, 300.0]);
+    /// assert_eq!(sum.intensity, vec![15.0, 20.0, 30.0]);
+    /// ```
+    pub fn add_with_resolution(&self, other: &MzSpectrum, resolution: i32) -> MzSpectrum {
+        let factor = 10f64.powi(resolution);
+        let mut binned: BTreeMap<i64, f64> = BTreeMap::new();
+
+        for (mz, intensity) in self.mz.iter().zip(&self.intensity).chain(other.mz.iter().zip(&other.intensity)) {
+            *binned.entry((mz * factor).round() as i64).or_insert(0.0) += *intensity;
+        }
+
+        let mz = binned.keys().map(|&key| key as f64 / factor).collect();
+        let intensity = binned.into_values().collect();
+        MzSpectrum { mz, intensity }
+    }
+
+    /// Subtract a background spectrum, every peak loses the intensity of all background peaks
+    /// within `tolerance`, intensities below zero are clamped to zero
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - background spectrum
+    /// * `tolerance` - maximum m/z distance in Th between a peak and the background peaks it loses
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let spectrum = MzSpectrum::new(vec![300.0, 100.0, 200.0], vec![30.0, 10.0, 20.0]);
+    /// let background = MzSpectrum::new(vec![100.002, 200.0, 250.0], vec![4.0, 25.0, 99.0]);
+    /// let cleaned = spectrum.sub_baseline(&background, 0.005);
+    /// assert_eq!(cleaned.mz, vec![100.0, 200.0, 300.0]);
+    /// assert_eq!(cleaned.intensity, vec![6.0, 0.0, 30.0]);
+    /// ```
+    pub fn sub_baseline(&self, other: &MzSpectrum, tolerance: f64) -> MzSpectrum {
+        let mut background: Vec<(f64, f64)> = other.mz.iter().copied().zip(other.intensity.iter().copied()).collect();
+        background.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut peaks: Vec<(f64, f64)> = self.mz.iter().copied().zip(self.intensity.iter().copied()).collect();
+        peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (mz, intensity) = peaks.into_iter().map(|(mz, intensity)| {
+            let start = background.partition_point(|&(b, _)| b < mz - tolerance);
+            let baseline: f64 = background[start..].iter()
+                .take_while(|&&(b, _)| b <= mz + tolerance)
+                .map(|&(_, i)| i)
+                .sum();
+            (mz, (intensity - baseline).max(0.0))
+        }).unzip();
+
+        MzSpectrum { mz, intensity }
+    }
+
+    /// Cosine similarity of two spectra after binning both to the same m/z resolution
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - spectrum to compare with
+    /// * `resolution` - decimal places of the m/z bins, peaks in the same bin are summed
+    ///
+    /// # Returns
+    ///
+    /// * similarity between 0 and 1 for non-negative intensities, 0 if one of the spectra has no intensity
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let a = MzSpectrum::new(vec![100.0, 200.0], vec![3.0, 4.0]);
+    /// let b = MzSpectrum::new(vec![200.001, 100.002, 300.0], vec![8.0, 6.0, 0.0]);
+    /// assert!((a.cosine_similarity(&b, 2) - 1.0).abs() < 1e-12);
+    /// assert_eq!(a.cosine_similarity(&MzSpectrum::new(vec![150.0], vec![1.0]), 2), 0.0);
+    /// assert_eq!(a.cosine_similarity(&MzSpectrum::new(vec![], vec![]), 2), 0.0);
+    /// ```
+    pub fn cosine_similarity(&self, other: &MzSpectrum, resolution: i32) -> f64 {
+        let factor = 10f64.powi(resolution);
+        let bin = |spectrum: &MzSpectrum| {
+            let mut binned: BTreeMap<i64, f64> = BTreeMap::new();
+            for (mz, intensity) in spectrum.mz.iter().zip(&spectrum.intensity) {
+                *binned.entry((mz * factor).round() as i64).or_insert(0.0) += *intensity;
+            }
+            binned
+        };
+        let (a, b) = (bin(self), bin(other));
+
+        let dot: f64 = a.iter().filter_map(|(key, x)| b.get(key).map(|y| x * y)).sum();
+        let norm = |binned: &BTreeMap<i64, f64>| binned.values().map(|x| x * x).sum::<f64>().sqrt();
+        let denominator = norm(&a) * norm(&b);
+        if denominator > 0.0 { dot / denominator } else { 0.0 }
+    }
+
+    /// Vectorize the spectrum with the given binning, intensities of peaks in the same bin are summed.
+    ///
+    /// # Arguments
+    ///
+    /// * `binning` - How m/z values are mapped to bin indices, peaks outside the m/z range of a `Binning::Ppm` are dropped.
+    ///
+    /// # Returns
+    ///
+    /// A `MzSpectrumVectorized` with indices in ascending order that carries the binning, see `MzSpectrumVectorized::bin_centers`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::{Binning, MzSpectrum};
+    /// let spectrum = MzSpectrum::new(vec![500.0, 500.004, 1000.004, 2500.0], vec![1.0, 2.0, 3.0, 4.0]);
+    ///
+    /// let vectorized = spectrum.vectorize(Binning::FixedDa(0.01));
+    /// assert_eq!(vectorized.indices, vec![50000, 100000, 250000]);
+    /// assert_eq!(vectorized.values, vec![3.0, 3.0, 4.0]);
+    ///
+    /// // 20 ppm bins are 0.01 Da wide at 500 and 0.02 Da wide at 1000, m/z above 2000 is dropped
+    /// let vectorized = spectrum.vectorize(Binning::Ppm(20.0, 100.0, 2000.0));
+    /// assert_eq!(vectorized.values.iter().sum::<f64>(), 6.0);
+    /// for (center, mz) in vectorized.bin_centers().iter().zip(&spectrum.mz) {
+    ///     assert!((center - mz).abs() <= mz * 10e-6);
+    /// }
+    /// ```
+    pub fn vectorize(&self, binning: Binning) -> MzSpectrumVectorized {
+        let mut binned: BTreeMap<i32, f64> = BTreeMap::new();
+        for (&mz, &intensity) in self.mz.iter().zip(&self.intensity) {
+            if let Some(index) = binning.index(mz) {
+                *binned.entry(index).or_insert(0.0) += intensity;
+            }
+        }
+
+        MzSpectrumVectorized {
+            resolution: binning.resolution(),
+            binning,
+            indices: binned.keys().copied().collect(),
+            values: binned.into_values().collect(),
+        }
+    }
+
+    /// Resample the spectrum onto a fixed m/z grid, e.g. to featurize spectra for models with a fixed input size
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - m/z values of the grid points in strictly increasing order
+    /// * `method` - how peaks are transferred onto the grid, see `ResampleMethod`
+    ///
+    /// # Returns
+    ///
+    /// * One intensity per grid point, or an error if the grid is not strictly increasing or the method is invalid
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::data::spectrum::{MzSpectrum, ResampleMethod};
+    /// let spectrum = MzSpectrum::new(vec![100.04, 100.16, 100.24], vec![1.0, 2.0, 4.0]);
+    /// let grid = vec![100.0, 100.1, 100.2];
+    ///
+    /// assert_eq!(spectrum.resample(&grid, ResampleMethod::NearestBin), Ok(vec![1.0, 0.0, 2.0]));
+    ///
+    /// let linear = spectrum.resample(&grid, ResampleMethod::Linear).unwrap();
+    /// assert_eq!(linear[0], 0.0);
+    /// assert!((linear[1] - 1.5).abs() < 1e-9);
+    /// assert!((linear[2] - 3.0).abs() < 1e-9);
+    ///
+    /// let gaussian = spectrum.resample(&grid, ResampleMethod::Gaussian { ppm: 500.0 }).unwrap();
+    /// assert_eq!(gaussian.len(), grid.len());
+    /// assert!(gaussian[2] > gaussian[1] && gaussian[1] > gaussian[0]);
+    ///
+    /// assert!(spectrum.resample(&[100.2, 100.1], ResampleMethod::Linear).is_err());
+    /// ```
+    pub fn resample(&self, grid: &[f64], method: ResampleMethod) -> Result<Vec<f64>, String> {
+        if let Some(i) = grid.windows(2).position(|w| w[0].partial_cmp(&w[1]) != Some(std::cmp::Ordering::Less)) {
+            return Err(format!("resampling grid must be strictly increasing, got {} followed by {} at position {}", grid[i], grid[i + 1], i));
+        }
+        let mut resampled = vec![0.0; grid.len()];
+        if grid.is_empty() {
+            return Ok(resampled);
+        }
+        let (first, last) = (grid[0], grid[grid.len() - 1]);
+
+        match method {
+            ResampleMethod::NearestBin => {
+                for (&mz, &intensity) in self.mz.iter().zip(&self.intensity) {
+                    if mz < first || mz > last {
+                        continue;
+                    }
+                    let upper = grid.partition_point(|&g| g < mz);
+                    let index = if upper > 0 && (upper == grid.len() || mz - grid[upper - 1] <= grid[upper] - mz) { upper - 1 } else { upper };
+                    resampled[index] += intensity;
+                }
+            }
+            ResampleMethod::Linear => {
+                let mut peaks: Vec<(f64, f64)> = self.mz.iter().copied().zip(self.intensity.iter().copied()).collect();
+                peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (value, &g) in resampled.iter_mut().zip(grid) {
+                    let upper = peaks.partition_point(|p| p.0 < g);
+                    *value = match (upper.checked_sub(1).map(|i| peaks[i]), peaks.get(upper)) {
+                        (_, Some(&(mz, intensity))) if mz == g => intensity,
+                        (Some((mz_left, left)), Some(&(mz_right, right))) => left + (right - left) * (g - mz_left) / (mz_right - mz_left),
+                        _ => 0.0,
+                    };
+                }
+            }
+            ResampleMethod::Gaussian { ppm } => {
+                if !(ppm > 0.0 && ppm.is_finite()) {
+                    return Err(format!("gaussian peak width in ppm must be positive, got {}", ppm));
+                }
+                for (&mz, &intensity) in self.mz.iter().zip(&self.intensity) {
+                    let sigma = mz * ppm * 1e-6;
+                    // contributions beyond 4 sigma are below 0.04% of the peak and skipped
+                    let start = grid.partition_point(|&g| g < mz - 4.0 * sigma);
+                    let end = grid.partition_point(|&g| g <= mz + 4.0 * sigma);
+                    for (value, &g) in resampled[start..end].iter_mut().zip(&grid[start..end]) {
+                        let z = (g - mz) / sigma;
+                        *value += intensity * (-0.5 * z * z).exp();
+                    }
+                }
+            }
+        }
+        Ok(resampled)
+    }
+
+    /// Signal-to-noise ratio of every peak relative to the noise of the peaks around it
+    ///
+    /// The noise of a peak is estimated from all peaks within `window / 2` Da of its m/z, windows with fewer
+    /// than `MIN_NOISE_PEAKS` peaks use the noise of the whole spectrum instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - width of the sliding window in Da
+    /// * `method` - noise estimator
+    ///
+    /// # Returns
+    ///
+    /// * One ratio per peak in the order of the peaks, infinite for peaks without a positive noise level
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mscore::algorithm::noise::NoiseEstimator;
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let spectrum = MzSpectrum::new(vec![100.0, 101.0, 102.0, 103.0, 104.0], vec![1.0, 2.0, 3.0, 4.0, 100.0]);
+    /// let snr = spectrum.signal_to_noise(50.0, NoiseEstimator::MedianAbsoluteDeviation);
+    /// assert_eq!(snr, vec![1.0, 2.0, 3.0, 4.0, 100.0]);
+    /// ```
+    pub fn signal_to_noise(&self, window: f64, method: NoiseEstimator) -> Vec<f64> {
+        let global_noise = estimate_noise(&self.intensity, method);
+        let mut order: Vec<usize> = (0..self.mz.len()).collect();
+        order.sort_by(|&a, &b| self.mz[a].total_cmp(&self.mz[b]));
+        let sorted_mz: Vec<f64> = order.iter().map(|&i| self.mz[i]).collect();
+        let sorted_intensity: Vec<f64> = order.iter().map(|&i| self.intensity[i]).collect();
+
+        let mut snr = vec![0.0; self.mz.len()];
+        for (position, &index) in order.iter().enumerate() {
+            let mz = sorted_mz[position];
+            let start = sorted_mz.partition_point(|&m| m < mz - window / 2.0);
+            let end = sorted_mz.partition_point(|&m| m <= mz + window / 2.0);
+            let local_noise = if end - start >= MIN_NOISE_PEAKS {
+                estimate_noise(&sorted_intensity[start..end], method)
+            } else {
+                global_noise
+            };
+            let noise = if local_noise > 0.0 { local_noise } else { global_noise };
+            snr[index] = signal_to_noise(self.intensity[index], noise);
+        }
+        snr
+    }
+
+    fn add_mz_noise<R, F>(&self, ppm: f64, rng: &mut R, noise_fn: F) -> Self
         where
-            F: Fn(&mut ThreadRng, f64, f64) -> f64,
+            R: Rng,
+            F: Fn(&mut R, f64, f64) -> f64,
     {
         let mz: Vec<f64> = self.mz.iter().map(|&mz_value| noise_fn(rng, mz_value, ppm)).collect();
         let spectrum = MzSpectrum { mz, intensity: self.intensity.clone()};
@@ -296,13 +759,25 @@ impl MzSpectrum {
     }
 }
 
+/// Indices of the `n` most intense peaks among `candidates` in ascending order, ties at the cutoff
+/// are resolved in favor of the lower m/z so the selection does not depend on the peak order
+pub(crate) fn top_n_indices(mz: &[f64], intensity: &[f64], candidates: impl IntoIterator<Item = usize>, n: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = candidates.into_iter().collect();
+    if indices.len() > n {
+        indices.sort_by(|&a, &b| intensity[b].total_cmp(&intensity[a]).then(mz[a].total_cmp(&mz[b])));
+        indices.truncate(n);
+    }
+    indices.sort_unstable();
+    indices
+}
+
 impl ToResolution for MzSpectrum {
     /// Bins the spectrum's m/z values to a given resolution and sums the intensities.
     ///
     /// # Arguments
     ///
     /// * `resolution` - The desired resolution in terms of decimal places. For instance, a resolution of 2
-    /// would bin m/z values to two decimal places.
+    ///   would bin m/z values to two decimal places.
     ///
     /// # Returns
     ///
@@ -352,22 +827,25 @@ impl Vectorized<MzSpectrumVectorized> for MzSpectrum {
 
         MzSpectrumVectorized {
             resolution,
+            binning: Binning::Decimal(resolution),
             indices,
             values: binned_spectrum.intensity,
         }
     }
 }
 
+impl BinarySerializable for MzSpectrum {}
+
 /// Formats the `MzSpectrum` for display.
 impl Display for MzSpectrum {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
 
         let (mz, i) = self.mz.iter()
             .zip(&self.intensity)
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
             .unwrap();
 
-        write!(f, "MzSpectrum(data points: {}, max  by intensity:({}, {}))", self.mz.len(), format!("{:.3}", mz), i)
+        write!(f, "MzSpectrum(data points: {}, max  by intensity:({:.3}, {}))", self.mz.len(), mz, i)
     }
 }
 
@@ -377,7 +855,7 @@ impl std::ops::Add for MzSpectrum {
     ///
     /// # Description
     /// Each m/z value is quantized to retain at least 6 decimals. If two spectra have m/z values
-    /// that quantize to the same integer value, their intensities are summed.
+    /// that quantize to the same integer value, their intensities are summed, see `add_with_resolution`.
     ///
     /// # Example
     /// ```
@@ -391,48 +869,30 @@ impl std::ops::Add for MzSpectrum {
     /// assert_eq!(combined.intensity, vec![10.0, 15.0, 20.0, 30.0]);
     /// ```
     fn add(self, other: Self) -> MzSpectrum {
-        let mut combined_map: BTreeMap<i64, f64> = BTreeMap::new();
-
-        // Helper to quantize mz to an integer key
-        let quantize = |mz: f64| -> i64 {
-            (mz * 1_000_000.0).round() as i64
-        };
-
-        // Add the m/z and intensities from the first spectrum to the map
-        for (mz, intensity) in self.mz.iter().zip(self.intensity.iter()) {
-            let key = quantize(*mz);
-            combined_map.insert(key, *intensity);
-        }
-
-        // Combine the second spectrum into the map
-        for (mz, intensity) in other.mz.iter().zip(other.intensity.iter()) {
-            let key = quantize(*mz);
-            let entry = combined_map.entry(key).or_insert(0.0);
-            *entry += *intensity;
-        }
-
-        // Convert the combined map back into two Vec<f64>
-        let mz_combined: Vec<f64> = combined_map.keys().map(|&key| key as f64 / 1_000_000.0).collect();
-        let intensity_combined: Vec<f64> = combined_map.values().cloned().collect();
-
-        MzSpectrum { mz: mz_combined, intensity: intensity_combined }
+        self.add_with_resolution(&other, 6)
     }
 }
 
 impl std::ops::Mul<f64> for MzSpectrum {
     type Output = Self;
+    /// Scale all intensities, the m/z values are kept as they are
+    ///
+    /// # Example
+    /// ```
+    /// # use mscore::data::spectrum::MzSpectrum;
+    /// let scaled = MzSpectrum::new(vec![100.0, 200.0], vec![10.0, 20.0]) * 0.5;
+    /// assert_eq!(scaled.intensity, vec![5.0, 10.0]);
+    /// ```
     fn mul(self, scale: f64) -> Self::Output{
-        let mut scaled_intensities: Vec<f64> = vec![0.0; self.intensity.len()];
-        for (idx,intensity) in self.intensity.iter().enumerate(){
-            scaled_intensities[idx] = scale*intensity;
-        }
-        Self{ mz: self.mz.clone(), intensity: scaled_intensities}
-
+        let intensity = self.intensity.iter().map(|intensity| scale * intensity).collect();
+        Self{ mz: self.mz, intensity }
     }
 }
 
 impl std::ops::Sub for MzSpectrum {
     type Output = Self;
+    // m/z values are quantized to integer keys
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn sub(self, other: Self) -> Self::Output {
         let mut combined_map: BTreeMap<i64, f64> = BTreeMap::new();
 
@@ -463,12 +923,18 @@ impl std::ops::Sub for MzSpectrum {
 }
 
 /// Represents a mass spectrum with associated m/z indices, m/z values, and intensities
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct IndexedMzSpectrum {
     pub index: Vec<i32>,
     pub mz_spectrum: MzSpectrum,
 }
 
+impl ApproxEq for IndexedMzSpectrum {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.index == other.index && self.mz_spectrum.approx_eq(&other.mz_spectrum, tolerance)
+    }
+}
+
 // implement default (empty IndexedMzSpectrum) constructor
 impl Default for IndexedMzSpectrum {
     fn default() -> Self {
@@ -570,6 +1036,7 @@ impl IndexedMzSpectrum {
             index: binned_spectrum.index,
             mz_vector: MzSpectrumVectorized {
                 resolution,
+                binning: Binning::Decimal(resolution),
                 indices,
                 values: binned_spectrum.mz_spectrum.intensity,
             }
@@ -592,25 +1059,220 @@ impl IndexedMzSpectrum {
     }
 }
 
+impl BinarySerializable for IndexedMzSpectrum {}
+
 impl Display for IndexedMzSpectrum {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let (mz, i) = self.mz_spectrum.mz.iter()
             .zip(&self.mz_spectrum.intensity)
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
             .unwrap();
 
-        write!(f, "IndexedMzSpectrum(data points: {}, max  by intensity:({}, {}))", self.mz_spectrum.mz.len(), format!("{:.3}", mz), i)
+        write!(f, "IndexedMzSpectrum(data points: {}, max  by intensity:({:.3}, {}))", self.mz_spectrum.mz.len(), mz, i)
     }
 }
 
-#[derive(Clone)]
+/// Mapping of m/z values to integer bin indices used to vectorize spectra
+#[derive(Clone, Copy, Debug, PartialEq, Encode, Decode)]
+pub enum Binning {
+    /// bins of 10^-d Da centered at m/z values rounded to d decimal places, the binning of `to_resolution`
+    Decimal(i32),
+    /// bins of the given width in Da centered at multiples of the width
+    FixedDa(f64),
+    /// bins with a width of the given ppm of their m/z (ppm, mz_min, mz_max), m/z outside of the range is dropped
+    Ppm(f64, f64, f64),
+}
+
+impl Binning {
+    /// Create a binning by name, "decimal", "da" or "ppm"
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the binning
+    /// * `width` - Decimal places for "decimal", bin width in Da for "da", bin width in ppm for "ppm"
+    /// * `mz_min` - Lower bound of the m/z range, only used by "ppm"
+    /// * `mz_max` - Upper bound of the m/z range, only used by "ppm"
+    ///
+    /// # Returns
+    ///
+    /// * The binning, or an error if the name is unknown or the parameters are invalid
+    ///
+    pub fn from_name(name: &str, width: f64, mz_min: f64, mz_max: f64) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "decimal" if width.fract() == 0.0 && (0.0..=9.0).contains(&width) => Ok(Binning::Decimal(width as i32)),
+            "decimal" => Err(format!("decimal binning needs 0 to 9 decimal places, got {}", width)),
+            "da" if width > 0.0 && width.is_finite() => Ok(Binning::FixedDa(width)),
+            "da" => Err(format!("bin width in Da must be positive, got {}", width)),
+            "ppm" if !(width > 0.0 && width.is_finite()) => Err(format!("bin width in ppm must be positive, got {}", width)),
+            "ppm" if !(mz_min > 0.0 && mz_min < mz_max && mz_max.is_finite()) => Err(format!("invalid m/z range ({}, {}) for ppm binning", mz_min, mz_max)),
+            "ppm" => Ok(Binning::Ppm(width, mz_min, mz_max)),
+            _ => Err(format!("unknown binning {}, expected one of decimal, da, ppm", name)),
+        }
+    }
+
+    /// Bin index of a m/z value, None if the binning does not cover it
+    ///
+    /// For `Ppm(ppm, mz_min, _)` the bin edges grow geometrically, edge i is `mz_min * (1 + ppm * 1e-6)^i`, so
+    /// the index of m/z is `floor(ln(mz / mz_min) / ln(1 + ppm * 1e-6))` and every bin is `ppm` wide relative
+    /// to its lower edge.
+    pub fn index(&self, mz: f64) -> Option<i32> {
+        match *self {
+            Binning::Decimal(resolution) => Some((mz * 10f64.powi(resolution)).round() as i32),
+            Binning::FixedDa(width) => Some((mz / width).round() as i32),
+            Binning::Ppm(ppm, mz_min, mz_max) => {
+                (mz >= mz_min && mz <= mz_max).then(|| ((mz / mz_min).ln() / (ppm * 1e-6).ln_1p()).floor() as i32)
+            }
+        }
+    }
+
+    /// m/z at the center of a bin, for `Ppm` the geometric mean of the bin edges
+    pub fn bin_center(&self, index: i32) -> f64 {
+        match *self {
+            Binning::Decimal(resolution) => index as f64 / 10f64.powi(resolution),
+            Binning::FixedDa(width) => index as f64 * width,
+            Binning::Ppm(ppm, mz_min, _) => mz_min * ((index as f64 + 0.5) * (ppm * 1e-6).ln_1p()).exp(),
+        }
+    }
+
+    /// Largest index of the binning, up to m/z 2000 unless the binning has an m/z range
+    pub fn max_index(&self) -> usize {
+        match *self {
+            Binning::Ppm(_, _, mz_max) => self.index(mz_max),
+            _ => self.index(2000.0),
+        }.unwrap_or(0).max(0) as usize
+    }
+
+    /// Decimal places of a `Decimal` binning, 0 for the other binnings
+    pub fn resolution(&self) -> i32 {
+        match *self {
+            Binning::Decimal(resolution) => resolution,
+            _ => 0,
+        }
+    }
+}
+
+/// How `MzSpectrum::resample` transfers peaks onto a m/z grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResampleMethod {
+    /// intensity of every peak is summed into the closest grid point, peaks outside of the grid are dropped
+    NearestBin,
+    /// linear interpolation between the peaks around every grid point, meant for profile spectra
+    Linear,
+    /// every peak is spread as a gaussian with a standard deviation of `ppm` of its m/z, keeping its height
+    Gaussian { ppm: f64 },
+}
+
+impl ResampleMethod {
+    /// Parse a method by name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - `nearest_bin`, `linear` or `gaussian`
+    /// * `ppm` - width of the gaussian peaks, only used by `gaussian`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::ResampleMethod;
+    ///
+    /// assert_eq!(ResampleMethod::from_name("Gaussian", 10.0), Ok(ResampleMethod::Gaussian { ppm: 10.0 }));
+    /// assert!(ResampleMethod::from_name("cubic", 10.0).is_err());
+    /// ```
+    pub fn from_name(name: &str, ppm: f64) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "nearest_bin" => Ok(ResampleMethod::NearestBin),
+            "linear" => Ok(ResampleMethod::Linear),
+            "gaussian" => Ok(ResampleMethod::Gaussian { ppm }),
+            _ => Err(format!("unknown resample method {}, expected one of nearest_bin, linear, gaussian", name)),
+        }
+    }
+}
+
+/// A m/z axis of bins of 10^-resolution Da shared by all frames of a run, such that vectors of
+/// different frames have the same columns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MzAxis {
+    pub resolution: i32,
+    /// center of the first bin
+    pub mz_min: f64,
+    /// center of the last bin
+    pub mz_max: f64,
+    pub n_bins: usize,
+}
+
+impl MzAxis {
+    /// Create an axis covering a m/z range, the bounds are widened to the enclosing bins
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - Decimal places of the bins, as in `Binning::Decimal`
+    /// * `mz_min` - Lower bound of the m/z range
+    /// * `mz_max` - Upper bound of the m/z range
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MzAxis;
+    ///
+    /// let axis = MzAxis::new(1, 100.04, 100.26);
+    /// assert_eq!((axis.mz_min, axis.mz_max, axis.n_bins), (100.0, 100.3, 4));
+    /// assert_eq!(axis.index(100.21), Some(2));
+    /// assert_eq!(axis.index(100.4), None);
+    /// ```
+    pub fn new(resolution: i32, mz_min: f64, mz_max: f64) -> Self {
+        let factor = 10f64.powi(resolution);
+        let first = (mz_min.min(mz_max) * factor).round() as i64;
+        let last = (mz_min.max(mz_max) * factor).round() as i64;
+        MzAxis {
+            resolution,
+            mz_min: first as f64 / factor,
+            mz_max: last as f64 / factor,
+            n_bins: (last - first + 1) as usize,
+        }
+    }
+
+    /// Position of the bin of a m/z value on the axis, None if the axis does not cover it
+    pub fn index(&self, mz: f64) -> Option<u32> {
+        let factor = 10f64.powi(self.resolution);
+        let index = (mz * factor).round() as i64 - (self.mz_min * factor).round() as i64;
+        (index >= 0 && (index as usize) < self.n_bins).then_some(index as u32)
+    }
+
+    /// m/z at the center of every bin of the axis
+    pub fn bin_centers(&self) -> Vec<f64> {
+        let factor = 10f64.powi(self.resolution);
+        let first = (self.mz_min * factor).round() as i64;
+        (0..self.n_bins as i64).map(|i| (first + i) as f64 / factor).collect()
+    }
+}
+
+#[derive(Clone, Encode, Decode)]
 pub struct MzSpectrumVectorized {
+    /// decimal places of a `Binning::Decimal`, 0 for other binnings
     pub resolution: i32,
+    pub binning: Binning,
     pub indices: Vec<i32>,
     pub values: Vec<f64>,
 }
 
+impl BinarySerializable for MzSpectrumVectorized {}
+
+impl ApproxEq for MzSpectrumVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.binning == other.binning && self.indices == other.indices && approx_eq_values(&self.values, &other.values, tolerance)
+    }
+}
+
 impl MzSpectrumVectorized {
+    fn get_max_index(&self) -> usize {
+        self.binning.max_index()
+    }
+
+    /// m/z at the center of the bin of every index, see `Binning::bin_center`
+    pub fn bin_centers(&self) -> Vec<f64> {
+        self.indices.iter().map(|&index| self.binning.bin_center(index)).collect()
+    }
+
     /// Convert the `MzVector` to a dense vector with a specified maximum index.
     ///
     /// The resulting vector has length equal to `max_index + 1` and its values
@@ -619,14 +1281,6 @@ impl MzSpectrumVectorized {
     /// # Arguments
     ///
     /// * `max_index` - The maximum index for the dense vector.
-    
-    fn get_max_index(&self) -> usize {
-        let base: i32 = 10;
-        let max_mz: i32 = 2000;
-        let max_index: usize = (max_mz*base.pow(self.resolution as u32)) as usize;
-        max_index
-    }
-
     pub fn to_dense(&self, max_index: Option<usize>) -> DVector<f64> {
         let max_index = match max_index {
             Some(max_index) => max_index,
@@ -647,18 +1301,123 @@ impl MzSpectrumVectorized {
         };
         let dense_intensities: Vec<f64> = self.to_dense(Some(max_index)).data.into();
         let dense_indices: Vec<i32> = (0..=max_index).map(|i| i as i32).collect();
-        let dense_spectrum: MzSpectrumVectorized = MzSpectrumVectorized { resolution: (self.resolution), indices: (dense_indices), values: (dense_intensities) };
+        let dense_spectrum: MzSpectrumVectorized = MzSpectrumVectorized { resolution: (self.resolution), binning: self.binning, indices: (dense_indices), values: (dense_intensities) };
         dense_spectrum
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
 pub struct IndexedMzSpectrumVectorized {
     pub index: Vec<i32>,
     pub mz_vector: MzSpectrumVectorized,
 }
 
+impl BinarySerializable for IndexedMzSpectrumVectorized {}
+
+impl ApproxEq for IndexedMzSpectrumVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.index == other.index && self.mz_vector.approx_eq(&other.mz_vector, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectra() -> (MzSpectrum, MzSpectrum, MzSpectrum) {
+        (
+            MzSpectrum::new(vec![100.001, 150.0, 200.0], vec![1.0, 2.0, 3.0]),
+            MzSpectrum::new(vec![100.004, 175.5, 200.0, 200.0], vec![4.0, 5.0, 6.0, 7.0]),
+            MzSpectrum::new(vec![99.999, 150.002, 300.0], vec![8.0, 9.0, 10.0]),
+        )
+    }
+
+    #[test]
+    fn test_add_is_commutative_at_fixed_resolution() {
+        let (a, b, c) = spectra();
+        for resolution in [1, 2, 6] {
+            for (x, y) in [(&a, &b), (&a, &c), (&b, &c)] {
+                let xy = x.add_with_resolution(y, resolution);
+                let yx = y.add_with_resolution(x, resolution);
+                assert_eq!(xy.mz, yx.mz, "resolution {resolution}");
+                assert_eq!(xy.intensity, yx.intensity);
+                assert!(xy.mz.windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+        assert_eq!((a.clone() + b.clone()).intensity, (b + a).intensity);
+    }
+
+    #[test]
+    fn test_add_is_associative_at_fixed_resolution() {
+        let (a, b, c) = spectra();
+        for resolution in [1, 2, 6] {
+            let left = a.add_with_resolution(&b, resolution).add_with_resolution(&c, resolution);
+            let right = a.add_with_resolution(&b.add_with_resolution(&c, resolution), resolution);
+            assert_eq!(left.mz, right.mz, "resolution {resolution}");
+            assert_eq!(left.intensity, right.intensity);
+            assert_eq!(left.intensity.iter().sum::<f64>(), 55.0);
+        }
 
+        // at 2 decimals 99.999, 100.001 and 100.004 share a bin
+        let merged = a.add_with_resolution(&b, 2).add_with_resolution(&c, 2);
+        assert_eq!(merged.mz[0], 100.0);
+        assert_eq!(merged.intensity[0], 13.0);
+    }
 
+    #[test]
+    fn test_top_n_is_deterministic() {
+        let (a, b, _) = spectra();
+        assert_eq!(a.top_n(a.mz.len() + 1).mz, a.mz);
+        assert_eq!(a.top_n(a.mz.len()).intensity, a.intensity);
+        assert!(a.top_n(0).mz.is_empty());
+
+        // three peaks tie for two places, the lower m/z values win whatever the input order
+        let ties = MzSpectrum::new(vec![300.0, 100.0, 200.0, 50.0], vec![2.0, 2.0, 2.0, 1.0]);
+        assert_eq!(ties.top_n(2).mz, vec![100.0, 200.0]);
+        assert_eq!(b.top_n(2).intensity, vec![6.0, 7.0]);
+    }
 
+    #[test]
+    fn test_sub_baseline_clamps_and_scales() {
+        let (a, b, _) = spectra();
+        let cleaned = (a.clone() * 2.0).sub_baseline(&b, 0.01);
+        assert_eq!(cleaned.mz, a.mz);
+        assert_eq!(cleaned.intensity, vec![0.0, 4.0, 0.0]);
+        assert_eq!(a.sub_baseline(&MzSpectrum::new(vec![], vec![]), 0.01).intensity, a.intensity);
+    }
 
+    #[test]
+    fn test_vectorize_binnings() {
+        let (a, b, _) = spectra();
+        let merged = a.clone() + b.clone();
+
+        // decimal binning matches the vectorization by resolution
+        let by_resolution = merged.vectorized(2);
+        let by_binning = merged.vectorize(Binning::Decimal(2));
+        assert!(by_binning.approx_eq(&by_resolution, 0.0));
+        assert_eq!(by_binning.bin_centers(), merged.to_resolution(2).mz);
+        assert_eq!(merged.vectorize(Binning::FixedDa(0.01)).indices, by_resolution.indices);
+
+        // bins of a ppm binning contain their m/z, every bin is ppm wide relative to its lower edge
+        let binning = Binning::Ppm(10.0, 50.0, 2000.0);
+        for mz in [50.0, 99.999, 100.004, 175.5, 1999.9] {
+            let index = binning.index(mz).unwrap();
+            let (lower, upper) = (binning.bin_center(index) / 5e-6f64.ln_1p().exp(), binning.bin_center(index) * 5e-6f64.ln_1p().exp());
+            assert!(lower <= mz * (1.0 + 1e-12) && mz <= upper * (1.0 + 1e-12), "{mz}");
+            assert!(((upper - lower) / lower - 10e-6).abs() < 1e-9);
+        }
+        assert_eq!(binning.index(49.9), None);
+        assert_eq!(binning.index(2000.1), None);
+        assert_eq!(binning.max_index(), binning.index(2000.0).unwrap() as usize);
+
+        let vectorized = merged.vectorize(binning);
+        assert_eq!(vectorized.values.iter().sum::<f64>(), merged.intensity.iter().sum::<f64>());
+        assert_eq!(vectorized.to_dense(None).len(), binning.max_index() + 1);
+
+        assert_eq!(Binning::from_name("ppm", 10.0, 50.0, 2000.0), Ok(binning));
+        assert_eq!(Binning::from_name("Da", 0.5, 0.0, 0.0), Ok(Binning::FixedDa(0.5)));
+        assert!(Binning::from_name("ppm", 10.0, 0.0, 2000.0).is_err());
+        assert!(Binning::from_name("decimal", 1.5, 0.0, 0.0).is_err());
+        assert!(Binning::from_name("log", 1.0, 0.0, 0.0).is_err());
+    }
+}
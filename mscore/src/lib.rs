@@ -2,4 +2,5 @@ pub mod data;
 pub mod algorithm;
 pub mod chemistry;
 pub mod timstof;
-pub mod simulation;
\ No newline at end of file
+pub mod simulation;
+pub mod parallel;
\ No newline at end of file
@@ -0,0 +1,123 @@
+//! Shared thread pools for the parallel batch functions of the crate.
+//!
+//! Functions taking a `num_threads` argument no longer build a fresh rayon pool per call,
+//! they run their work through [`pool`], which resolves the pool to use as follows:
+//!
+//! 1. Called from inside a rayon worker thread (e.g. building frames which in turn parallelize
+//!    over spectra), the work runs on the pool of the caller. Pools are never installed
+//!    inside other pools, so nested calls cannot dead-lock or oversubscribe the machine.
+//! 2. An explicit `num_threads` of `Some(n)` with `n > 0` runs on a pool of `n` threads.
+//!    Pools are created once per size and reused by all later calls.
+//! 3. `None` (or `0`) runs on the shared pool configured with [`set_num_threads`].
+//! 4. Without a shared pool, the work runs on the rayon global pool.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+static SHARED_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+fn cached_pool(num_threads: usize) -> Arc<ThreadPool> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS.get_or_init(|| Mutex::new(HashMap::new())).lock().unwrap();
+    pools.entry(num_threads).or_insert_with(|| {
+        Arc::new(ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap())
+    }).clone()
+}
+
+/// Configure the shared pool used by all calls that do not pass an explicit number of threads
+///
+/// # Arguments
+///
+/// * `num_threads` - number of threads of the shared pool, `0` falls back to the rayon global pool
+///
+/// # Examples
+///
+/// ```
+/// use mscore::parallel::{get_num_threads, set_num_threads};
+///
+/// set_num_threads(2);
+/// assert_eq!(get_num_threads(), 2);
+/// set_num_threads(0);
+/// assert_eq!(get_num_threads(), rayon::current_num_threads());
+/// ```
+pub fn set_num_threads(num_threads: usize) {
+    let pool = if num_threads > 0 { Some(cached_pool(num_threads)) } else { None };
+    *SHARED_POOL.write().unwrap() = pool;
+}
+
+/// Get the number of threads used by calls that do not pass an explicit number of threads
+///
+/// # Returns
+///
+/// * `usize` - size of the shared pool if configured, otherwise of the rayon global pool
+///
+pub fn get_num_threads() -> usize {
+    match SHARED_POOL.read().unwrap().as_ref() {
+        Some(pool) => pool.current_num_threads(),
+        None => rayon::current_num_threads(),
+    }
+}
+
+/// Handle to the pool a parallel computation runs on, see [`pool`]
+pub struct PoolHandle {
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl PoolHandle {
+    /// Run `op` on the resolved pool, parallel iterators inside `op` use its threads
+    pub fn install<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Number of threads the computation runs on
+    pub fn current_num_threads(&self) -> usize {
+        match &self.pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+}
+
+/// Resolve the pool to run a parallel computation on, following the precedence rules of this module
+///
+/// # Arguments
+///
+/// * `num_threads` - requested number of threads, `None` or `0` use the shared pool
+///
+/// # Returns
+///
+/// * `PoolHandle` - handle to install the computation on
+///
+/// # Examples
+///
+/// ```
+/// use mscore::parallel::pool;
+/// use rayon::prelude::*;
+///
+/// let outer = pool(2);
+/// assert_eq!(outer.current_num_threads(), 2);
+/// let sums: Vec<u32> = outer.install(|| (0..4u32).into_par_iter().map(|i| {
+///     // nested calls run on the pool of the caller
+///     pool(8).install(|| (0..=i).into_par_iter().sum())
+/// }).collect());
+/// assert_eq!(sums, vec![0, 1, 3, 6]);
+/// ```
+pub fn pool(num_threads: impl Into<Option<usize>>) -> PoolHandle {
+    if rayon::current_thread_index().is_some() {
+        return PoolHandle { pool: None };
+    }
+    let pool = match num_threads.into().filter(|&n| n > 0) {
+        Some(n) => Some(cached_pool(n)),
+        None => SHARED_POOL.read().unwrap().clone(),
+    };
+    PoolHandle { pool }
+}
@@ -1,12 +1,18 @@
 use std::collections::BTreeMap;
 use std::fmt::Display;
+use bincode::{Decode, Encode};
 use itertools::izip;
 use rand::distributions::{Uniform, Distribution};
-use rand::rngs::ThreadRng;
+use rand::Rng;
 use statrs::distribution::Normal;
-use crate::data::spectrum::{MsType, ToResolution, Vectorized};
+use crate::data::serialization::BinarySerializable;
+use crate::data::spectrum::{approx_eq_value, approx_eq_values, ApproxEq, MsType, ToResolution, Vectorized};
 
-#[derive(Clone, Debug)]
+/// Summed intensity, tof and inverse mobility of the peaks merged into one, their annotation and the
+/// number of merged peaks
+type MergedPeak = (f64, u32, f64, PeakAnnotation, i64);
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
 pub struct PeakAnnotation {
     pub contributions: Vec<ContributionSource>,
 }
@@ -26,14 +32,14 @@ impl PeakAnnotation {
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
 pub struct ContributionSource {
     pub intensity_contribution: f64,
     pub source_type: SourceType,
     pub signal_attributes: Option<SignalAttributes>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
 pub enum SourceType {
     Signal,
     ChemicalNoise,
@@ -51,6 +57,15 @@ impl SourceType {
             _ => panic!("Invalid source type"),
         }
     }
+
+    pub fn source_type_numeric(&self) -> i32 {
+        match self {
+            SourceType::Signal => 0,
+            SourceType::ChemicalNoise => 1,
+            SourceType::RandomNoise => 2,
+            SourceType::Unknown => 3,
+        }
+    }
 }
 
 impl Display for SourceType {
@@ -64,21 +79,50 @@ impl Display for SourceType {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
 pub struct SignalAttributes {
     pub charge_state: i32,
     pub peptide_id: i32,
+    /// id of the simulated ion, -1 if unknown
+    pub ion_id: i32,
     pub isotope_peak: i32,
+    /// fragment kind, 1-based ordinal and isotope as `{kind}_{ordinal}_{isotope}`, None for precursor signal
     pub description: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+impl SignalAttributes {
+    /// Split the description into fragment kind and ordinal, precursor signal has kind `precursor` and ordinal 0
+    pub fn fragment_kind_and_ordinal(&self) -> (String, i32) {
+        match &self.description {
+            None => ("precursor".to_string(), 0),
+            Some(description) => {
+                let mut parts = description.split('_');
+                let kind = parts.next().unwrap_or("").to_string();
+                let ordinal = parts.next().and_then(|x| x.parse().ok()).unwrap_or(0);
+                (kind, ordinal)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct MzSpectrumAnnotated {
     pub mz: Vec<f64>,
     pub intensity: Vec<f64>,
     pub annotations: Vec<PeakAnnotation>,
 }
 
+impl BinarySerializable for MzSpectrumAnnotated {}
+
+/// Annotations are compared exactly, the tolerance only applies to m/z and intensity
+impl ApproxEq for MzSpectrumAnnotated {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.annotations == other.annotations
+            && approx_eq_values(&self.mz, &other.mz, tolerance)
+            && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
 impl MzSpectrumAnnotated {
     pub fn new(mz: Vec<f64>, intensity: Vec<f64>, annotations: Vec<PeakAnnotation>) -> Self {
         assert!(mz.len() == intensity.len() && intensity.len() == annotations.len());
@@ -93,6 +137,39 @@ impl MzSpectrumAnnotated {
         }
     }
 
+    /// Set the ion id of all signal contributions, used to keep ion provenance when spectra are merged
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::simulation::annotation::{ContributionSource, MzSpectrumAnnotated, PeakAnnotation, SignalAttributes, SourceType};
+    ///
+    /// let spectrum = |intensity: f64| {
+    ///     let signal = ContributionSource {
+    ///         intensity_contribution: intensity,
+    ///         source_type: SourceType::Signal,
+    ///         signal_attributes: Some(SignalAttributes { charge_state: 2, peptide_id: 7, ion_id: -1, isotope_peak: 0, description: None }),
+    ///     };
+    ///     MzSpectrumAnnotated::new(vec![500.0], vec![intensity], vec![PeakAnnotation { contributions: vec![signal] }])
+    /// };
+    ///
+    /// // peaks at the same m/z are merged, keeping the contributions of both ions
+    /// let merged = spectrum(10.0).with_ion_id(1) + spectrum(5.0).with_ion_id(2);
+    /// assert_eq!(merged.intensity, vec![15.0]);
+    /// let ion_ids: Vec<i32> = merged.annotations[0].contributions.iter().map(|c| c.signal_attributes.as_ref().unwrap().ion_id).collect();
+    /// assert_eq!(ion_ids, vec![1, 2]);
+    /// ```
+    pub fn with_ion_id(mut self, ion_id: i32) -> Self {
+        for annotation in self.annotations.iter_mut() {
+            for contribution in annotation.contributions.iter_mut() {
+                if let Some(signal_attributes) = contribution.signal_attributes.as_mut() {
+                    signal_attributes.ion_id = ion_id;
+                }
+            }
+        }
+        self
+    }
+
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> Self {
         let mut mz_filtered: Vec<f64> = Vec::new();
         let mut intensity_filtered: Vec<f64> = Vec::new();
@@ -117,7 +194,26 @@ impl MzSpectrumAnnotated {
 
     pub fn add_mz_noise_uniform(&self, ppm: f64, right_drag: bool) -> Self {
         let mut rng = rand::thread_rng();
-        self.add_mz_noise(ppm, &mut rng, |rng, mz, ppm| {
+        self.add_mz_noise_uniform_with_rng(ppm, right_drag, &mut rng)
+    }
+
+    /// Same as `add_mz_noise_uniform`, but draws from the given random number generator
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::simulation::annotation::{MzSpectrumAnnotated, PeakAnnotation};
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let annotations = vec![PeakAnnotation::new_random_noise(10.0), PeakAnnotation::new_random_noise(20.0)];
+    /// let spectrum = MzSpectrumAnnotated::new(vec![100.0, 200.0], vec![10.0, 20.0], annotations);
+    /// let a = spectrum.add_mz_noise_uniform_with_rng(5.0, false, &mut StdRng::seed_from_u64(42));
+    /// let b = spectrum.add_mz_noise_uniform_with_rng(5.0, false, &mut StdRng::seed_from_u64(42));
+    /// assert_eq!(a.mz, b.mz);
+    /// ```
+    pub fn add_mz_noise_uniform_with_rng<R: Rng>(&self, ppm: f64, right_drag: bool, rng: &mut R) -> Self {
+        self.add_mz_noise(ppm, rng, |rng, mz, ppm| {
 
             let ppm_mz = match right_drag {
                 true => mz * ppm / 1e6 / 2.0,
@@ -135,16 +231,22 @@ impl MzSpectrumAnnotated {
 
     pub fn add_mz_noise_normal(&self, ppm: f64) -> Self {
         let mut rng = rand::thread_rng();
-        self.add_mz_noise(ppm, &mut rng, |rng, mz, ppm| {
+        self.add_mz_noise_normal_with_rng(ppm, &mut rng)
+    }
+
+    /// Same as `add_mz_noise_normal`, but draws from the given random number generator
+    pub fn add_mz_noise_normal_with_rng<R: Rng>(&self, ppm: f64, rng: &mut R) -> Self {
+        self.add_mz_noise(ppm, rng, |rng, mz, ppm| {
             let ppm_mz = mz * ppm / 1e6;
             let dist = Normal::new(mz, ppm_mz / 3.0).unwrap(); // 3 sigma ? good enough?
             dist.sample(rng)
         })
     }
 
-    fn add_mz_noise<F>(&self, ppm: f64, rng: &mut ThreadRng, noise_fn: F) -> Self
+    fn add_mz_noise<R, F>(&self, ppm: f64, rng: &mut R, noise_fn: F) -> Self
         where
-            F: Fn(&mut ThreadRng, f64, f64) -> f64,
+            R: Rng,
+            F: Fn(&mut R, f64, f64) -> f64,
     {
         let mz: Vec<f64> = self.mz.iter().map(|&mz_value| noise_fn(rng, mz_value, ppm)).collect();
         let spectrum = MzSpectrumAnnotated { mz, intensity: self.intensity.clone(), annotations: self.annotations.clone()};
@@ -204,12 +306,8 @@ impl std::ops::Add for MzSpectrumAnnotated {
         let quantize = |mz: f64| -> i64 { (mz * 1_000_000.0).round() as i64 };
         let mut spec_map: BTreeMap<i64, (f64, PeakAnnotation)> = BTreeMap::new();
 
-        for ((mz, intensity), annotation) in self.mz.iter().zip(self.intensity.iter()).zip(self.annotations.iter()) {
-            let key = quantize(*mz);
-            spec_map.insert(key, (*intensity, annotation.clone()));
-        }
-
-        for ((mz, intensity), annotation) in other.mz.iter().zip(other.intensity.iter()).zip(other.annotations.iter()) {
+        for ((mz, intensity), annotation) in self.mz.iter().zip(self.intensity.iter()).zip(self.annotations.iter())
+            .chain(other.mz.iter().zip(other.intensity.iter()).zip(other.annotations.iter())) {
             let key = quantize(*mz);
             spec_map.entry(key).and_modify(|e| {
                 e.0 += *intensity;
@@ -322,7 +420,7 @@ pub struct MzSpectrumAnnotatedVectorized {
     pub annotations: Vec<PeakAnnotation>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct TimsSpectrumAnnotated {
     pub frame_id: i32,
     pub scan: u32,
@@ -333,6 +431,17 @@ pub struct TimsSpectrumAnnotated {
     pub spectrum: MzSpectrumAnnotated,
 }
 
+impl BinarySerializable for TimsSpectrumAnnotated {}
+
+impl ApproxEq for TimsSpectrumAnnotated {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.scan == other.scan && self.ms_type == other.ms_type && self.tof == other.tof
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_value(self.mobility, other.mobility, tolerance)
+            && self.spectrum.approx_eq(&other.spectrum, tolerance)
+    }
+}
+
 impl TimsSpectrumAnnotated {
     pub fn new(frame_id: i32, scan: u32, retention_time: f64, mobility: f64, ms_type: MsType, tof: Vec<u32>, spectrum: MzSpectrumAnnotated) -> Self {
         assert!(tof.len() == spectrum.mz.len() && spectrum.mz.len() == spectrum.intensity.len() && spectrum.intensity.len() == spectrum.annotations.len());
@@ -418,12 +527,8 @@ impl std::ops::Add for TimsSpectrumAnnotated {
         let quantize = |mz: f64| -> i64 { (mz * 1_000_000.0).round() as i64 };
         let mut spec_map: BTreeMap<i64, (u32, f64, PeakAnnotation, i64)> = BTreeMap::new();
 
-        for (tof, mz, intensity, annotation) in izip!(self.tof.iter(), self.spectrum.mz.iter(), self.spectrum.intensity.iter(), self.spectrum.annotations.iter()) {
-            let key = quantize(*mz);
-            spec_map.insert(key, (*tof, *intensity, annotation.clone(), 1));
-        }
-
-        for (tof, mz, intensity, annotation) in izip!(other.tof.iter(), other.spectrum.mz.iter(), other.spectrum.intensity.iter(), other.spectrum.annotations.iter()) {
+        for (tof, mz, intensity, annotation) in izip!(self.tof.iter(), self.spectrum.mz.iter(), self.spectrum.intensity.iter(), self.spectrum.annotations.iter())
+            .chain(izip!(other.tof.iter(), other.spectrum.mz.iter(), other.spectrum.intensity.iter(), other.spectrum.annotations.iter())) {
             let key = quantize(*mz);
             spec_map.entry(key).and_modify(|e| {
                 e.0 += *tof;
@@ -459,7 +564,7 @@ impl std::ops::Add for TimsSpectrumAnnotated {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Encode, Decode)]
 pub struct TimsFrameAnnotated {
     pub frame_id: i32,
     pub retention_time: f64,
@@ -472,7 +577,21 @@ pub struct TimsFrameAnnotated {
     pub annotations: Vec<PeakAnnotation>,
 }
 
+impl BinarySerializable for TimsFrameAnnotated {}
+
+impl ApproxEq for TimsFrameAnnotated {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.ms_type == other.ms_type && self.tof == other.tof && self.scan == other.scan
+            && self.annotations == other.annotations
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_values(&self.mz, &other.mz, tolerance)
+            && approx_eq_values(&self.inv_mobility, &other.inv_mobility, tolerance)
+            && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
 impl TimsFrameAnnotated {
+    #[allow(clippy::too_many_arguments, reason = "one argument per column of the frame")]
     pub fn new(frame_id: i32, retention_time: f64, ms_type: MsType, tof: Vec<u32>, mz: Vec<f64>, scan: Vec<u32>, inv_mobility: Vec<f64>, intensity: Vec<f64>, annotations: Vec<PeakAnnotation>) -> Self {
         assert!(tof.len() == mz.len() && mz.len() == scan.len() && scan.len() == inv_mobility.len() && inv_mobility.len() == intensity.len() && intensity.len() == annotations.len());
         TimsFrameAnnotated {
@@ -487,6 +606,7 @@ impl TimsFrameAnnotated {
             annotations,
         }
     }
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for every dimension")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, inv_mobility_min: f64, inv_mobility_max: f64, scan_min: u32, scan_max: u32, intensity_min: f64, intensity_max: f64) -> Self {
         let mut tof_filtered: Vec<u32> = Vec::new();
         let mut mz_filtered: Vec<f64> = Vec::new();
@@ -546,7 +666,7 @@ impl TimsFrameAnnotated {
 
     pub fn from_tims_spectra_annotated(spectra: Vec<TimsSpectrumAnnotated>) -> TimsFrameAnnotated {
         let quantize = |mz: f64| -> i64 { (mz * 1_000_000.0).round() as i64 };
-        let mut spec_map: BTreeMap<(u32, i64), (f64, u32, f64, PeakAnnotation, i64)> = BTreeMap::new();
+        let mut spec_map: BTreeMap<(u32, i64), MergedPeak> = BTreeMap::new();
         let mut capacity_count = 0;
 
         for spectrum in &spectra {
@@ -598,6 +718,92 @@ impl TimsFrameAnnotated {
             annotations: annotations_vec,
         }
     }
+
+    /// Flatten the annotations of all peaks into columns, see `AnnotationColumns`
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::simulation::annotation::{ContributionSource, PeakAnnotation, SignalAttributes, SourceType, TimsFrameAnnotated};
+    ///
+    /// let signal = |ion_id: i32, description: Option<&str>| ContributionSource {
+    ///     intensity_contribution: 10.0,
+    ///     source_type: SourceType::Signal,
+    ///     signal_attributes: Some(SignalAttributes { charge_state: 2, peptide_id: 7, ion_id, isotope_peak: 0, description: description.map(|d| d.to_string()) }),
+    /// };
+    /// let annotations = vec![
+    ///     PeakAnnotation { contributions: vec![signal(1, None), signal(2, Some("y_3_0"))] },
+    ///     PeakAnnotation::new_random_noise(5.0),
+    /// ];
+    /// let frame = TimsFrameAnnotated::new(1, 0.0, MsType::FragmentDia, vec![0, 0], vec![500.0, 600.0], vec![1, 1], vec![1.0, 1.0], vec![20.0, 5.0], annotations);
+    ///
+    /// let columns = frame.annotation_columns();
+    /// assert_eq!(columns.offsets, vec![0, 2, 3]);
+    /// assert_eq!(columns.ion_id, vec![1, 2, -1]);
+    /// assert_eq!(columns.fragment_kind, vec!["precursor", "y", ""]);
+    /// assert_eq!(columns.fragment_ordinal, vec![0, 3, 0]);
+    /// assert_eq!(columns.source_type, vec![0, 0, 2]);
+    /// ```
+    pub fn annotation_columns(&self) -> AnnotationColumns {
+        let num_contributions = self.annotations.iter().map(|x| x.contributions.len()).sum();
+        let mut columns = AnnotationColumns {
+            offsets: Vec::with_capacity(self.annotations.len() + 1),
+            intensity_contribution: Vec::with_capacity(num_contributions),
+            source_type: Vec::with_capacity(num_contributions),
+            peptide_id: Vec::with_capacity(num_contributions),
+            ion_id: Vec::with_capacity(num_contributions),
+            charge_state: Vec::with_capacity(num_contributions),
+            isotope_peak: Vec::with_capacity(num_contributions),
+            fragment_kind: Vec::with_capacity(num_contributions),
+            fragment_ordinal: Vec::with_capacity(num_contributions),
+        };
+
+        columns.offsets.push(0);
+        for annotation in &self.annotations {
+            for contribution in &annotation.contributions {
+                columns.intensity_contribution.push(contribution.intensity_contribution);
+                columns.source_type.push(contribution.source_type.source_type_numeric());
+                match &contribution.signal_attributes {
+                    Some(signal_attributes) => {
+                        let (kind, ordinal) = signal_attributes.fragment_kind_and_ordinal();
+                        columns.peptide_id.push(signal_attributes.peptide_id);
+                        columns.ion_id.push(signal_attributes.ion_id);
+                        columns.charge_state.push(signal_attributes.charge_state);
+                        columns.isotope_peak.push(signal_attributes.isotope_peak);
+                        columns.fragment_kind.push(kind);
+                        columns.fragment_ordinal.push(ordinal);
+                    }
+                    None => {
+                        columns.peptide_id.push(-1);
+                        columns.ion_id.push(-1);
+                        columns.charge_state.push(-1);
+                        columns.isotope_peak.push(-1);
+                        columns.fragment_kind.push(String::new());
+                        columns.fragment_ordinal.push(0);
+                    }
+                }
+            }
+            columns.offsets.push(columns.intensity_contribution.len() as u32);
+        }
+
+        columns
+    }
+}
+
+/// Contributions of all peaks of an annotated frame in columnar form, the contributions of peak `i`
+/// are stored at `offsets[i]..offsets[i + 1]`, noise contributions have ids of -1 and an empty kind
+#[derive(Clone, Debug, Default)]
+pub struct AnnotationColumns {
+    pub offsets: Vec<u32>,
+    pub intensity_contribution: Vec<f64>,
+    pub source_type: Vec<i32>,
+    pub peptide_id: Vec<i32>,
+    pub ion_id: Vec<i32>,
+    pub charge_state: Vec<i32>,
+    pub isotope_peak: Vec<i32>,
+    pub fragment_kind: Vec<String>,
+    pub fragment_ordinal: Vec<i32>,
 }
 
 impl std::ops::Add for TimsFrameAnnotated {
@@ -605,16 +811,11 @@ impl std::ops::Add for TimsFrameAnnotated {
     fn add(self, other: Self) -> Self {
 
         let quantize = |mz: f64| -> i64 { (mz * 1_000_000.0).round() as i64 };
-        let mut spec_map: BTreeMap<(u32, i64), (f64, u32, f64, PeakAnnotation, i64)> = BTreeMap::new();
-
-        for (scan, mz, tof, inv_mobility, intensity, annotation) in
-        izip!(self.scan.iter(), self.mz.iter(), self.tof.iter(), self.inv_mobility.iter(), self.intensity.iter(), self.annotations.iter()) {
-            let key = (*scan, quantize(*mz));
-            spec_map.insert(key, (*intensity, *tof, *inv_mobility, annotation.clone(), 1));
-        }
+        let mut spec_map: BTreeMap<(u32, i64), MergedPeak> = BTreeMap::new();
 
         for (scan, mz, tof, inv_mobility, intensity, annotation) in
-        izip!(other.scan.iter(), other.mz.iter(), other.tof.iter(), other.inv_mobility.iter(), other.intensity.iter(), other.annotations.iter()) {
+        izip!(self.scan.iter(), self.mz.iter(), self.tof.iter(), self.inv_mobility.iter(), self.intensity.iter(), self.annotations.iter())
+            .chain(izip!(other.scan.iter(), other.mz.iter(), other.tof.iter(), other.inv_mobility.iter(), other.intensity.iter(), other.annotations.iter())) {
             let key = (*scan, quantize(*mz));
             spec_map.entry(key).and_modify(|e| {
                 e.0 += *intensity;
@@ -1,2 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod simulation;
-pub mod annotation;
\ No newline at end of file
+pub mod annotation;
+pub mod peptide;
\ No newline at end of file
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+use crate::algorithm::fragmentation::{FragmentIntensityModel, MobileProtonModel};
+use crate::algorithm::peptide::{simulate_ccs_baseline, simulate_retention_time_baseline};
+use crate::chemistry::constants::MASS_PROTON;
+use crate::chemistry::mobility::ccs_to_one_over_k0;
+use crate::data::peptide::{FragmentType, PeptideIon, PeptideProductIonSeriesCollection, PeptideSequence};
+use crate::data::spectrum::MzSpectrum;
+
+/// End-to-end simulation of a single peptide ion: precursor isotope spectrum, fragment spectrum,
+/// retention time and inverse mobility, composed from the baseline models of this crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeptideSimulation {
+    pub sequence: PeptideSequence,
+    pub charge: i32,
+    pub collision_energy: f64,
+    pub precursor_mz: f64,
+    pub precursor_spectrum: MzSpectrum,
+    pub fragment_ions: PeptideProductIonSeriesCollection,
+    pub fragment_spectrum: MzSpectrum,
+    pub retention_time: f64,
+    pub ccs: f64,
+    pub inverse_mobility: f64,
+}
+
+impl PeptideSimulation {
+    /// Simulate a single peptide ion
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence` - The peptide sequence, may contain modifications in the format [UNIMOD:ID]
+    /// * `charge` - The precursor charge state
+    /// * `collision_energy` - The collision energy, recorded with the result and passed to the fallback fragment model
+    /// * `fragment_intensities` - Optional flat prosit intensity array (174 values), if None the `MobileProtonModel` is used
+    /// * `gradient_length` - Optional gradient length in minutes used for the retention time, default 60.0
+    /// * `mass_tolerance` - Optional mass tolerance for the isotope calculation, default 1e-3
+    /// * `abundance_threshold` - Optional abundance threshold for the isotope calculation, default 1e-8
+    /// * `max_result` - Optional maximum number of isotope peaks, default 200
+    /// * `intensity_min` - Optional minimum relative intensity of an isotope peak, default 1e-4
+    ///
+    /// # Returns
+    ///
+    /// * `PeptideSimulation` - The simulated peptide ion
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::simulation::peptide::PeptideSimulation;
+    ///
+    /// let simulation = PeptideSimulation::new("PEPTIDEK".to_string(), 2, 30.0, None, None, None, None, None, None);
+    /// assert!((simulation.precursor_spectrum.mz[0] - simulation.precursor_mz).abs() < 1e-3);
+    /// assert!(simulation.inverse_mobility > 0.6 && simulation.inverse_mobility < 1.4);
+    /// ```
+    #[allow(clippy::too_many_arguments, reason = "the optional simulation settings are exposed separately in the Python API")]
+    pub fn new(
+        sequence: String,
+        charge: i32,
+        collision_energy: f64,
+        fragment_intensities: Option<Vec<f64>>,
+        gradient_length: Option<f64>,
+        mass_tolerance: Option<f64>,
+        abundance_threshold: Option<f64>,
+        max_result: Option<i32>,
+        intensity_min: Option<f64>,
+    ) -> Self {
+        assert!(charge > 0, "Charge state must be positive");
+
+        let mass_tolerance = mass_tolerance.unwrap_or(1e-3);
+        let abundance_threshold = abundance_threshold.unwrap_or(1e-8);
+        let max_result = max_result.unwrap_or(200);
+        let intensity_min = intensity_min.unwrap_or(1e-4);
+
+        let ion = PeptideIon::new(sequence, charge, 1.0, None);
+        let peptide_sequence = ion.sequence.clone();
+        let precursor_mz = ion.mz();
+        let precursor_spectrum = ion.calculate_isotopic_spectrum(mass_tolerance, abundance_threshold, max_result, intensity_min);
+
+        let fragment_ions = match fragment_intensities {
+            Some(intensities) => peptide_sequence.associate_with_predicted_intensities(charge, FragmentType::B, intensities, true, true, None)
+                .unwrap_or_else(|e| panic!("{}", e)),
+            None => MobileProtonModel.predict(&peptide_sequence, charge, collision_energy),
+        };
+        let fragment_spectrum = fragment_ions.generate_isotopic_spectrum(mass_tolerance, abundance_threshold, max_result, intensity_min);
+
+        let retention_time = simulate_retention_time_baseline(&peptide_sequence.sequence, gradient_length);
+        let ccs = simulate_ccs_baseline(precursor_mz, charge);
+        let inverse_mobility = ccs_to_one_over_k0(ccs, precursor_mz, charge as u32, None, None);
+
+        PeptideSimulation {
+            sequence: peptide_sequence,
+            charge,
+            collision_energy,
+            precursor_mz,
+            precursor_spectrum,
+            fragment_ions,
+            fragment_spectrum,
+            retention_time,
+            ccs,
+            inverse_mobility,
+        }
+    }
+
+    /// Get the monoisotopic mass of the precursor ion
+    pub fn precursor_mass(&self) -> f64 {
+        self.precursor_mz * self.charge as f64 - self.charge as f64 * MASS_PROTON
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chemistry::formulas::calculate_mz;
+
+    #[test]
+    fn test_precursor_mz_matches_sequence_and_charge() {
+        let simulation = PeptideSimulation::new("PEPTIDEK".to_string(), 3, 25.0, None, None, None, None, None, None);
+        let expected_mz = calculate_mz(simulation.sequence.mono_isotopic_mass(), 3);
+        assert!((simulation.precursor_mz - expected_mz).abs() < 1e-9);
+        assert!((simulation.precursor_mass() - simulation.sequence.mono_isotopic_mass()).abs() < 1e-6);
+        // the monoisotopic peak is the first peak of the isotope envelope
+        assert!((simulation.precursor_spectrum.mz[0] - expected_mz).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fragments_match_series() {
+        let simulation = PeptideSimulation::new("PEPTIDEK".to_string(), 3, 25.0, None, None, None, None, None, None);
+        assert_eq!(simulation.fragment_ions.peptide_ions.len(), 2);
+
+        // every monoisotopic fragment m/z has to be present in the fragment spectrum
+        for series in simulation.fragment_ions.peptide_ions.iter() {
+            for ion in series.n_ions.iter().chain(series.c_ions.iter()) {
+                let mz = ion.mz();
+                assert!(simulation.fragment_spectrum.mz.iter().any(|m| (m - mz).abs() < 1e-3));
+            }
+        }
+
+        let total: f64 = simulation.fragment_ions.peptide_ions.iter()
+            .flat_map(|s| s.n_ions.iter().chain(s.c_ions.iter()))
+            .map(|ion| ion.ion.intensity)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_supplied_intensities() {
+        let mut intensities = vec![0.0; 174];
+        // y1 and b1 for charge one
+        intensities[0] = 1.0;
+        intensities[29] = 1.0;
+        let simulation = PeptideSimulation::new("PEPTIDEK".to_string(), 1, 25.0, Some(intensities), None, None, None, None, None);
+        let series = simulation.fragment_ions.find_ion_series(1).unwrap();
+        assert!(series.c_ions[0].ion.intensity > 0.0);
+        assert!(series.n_ions[0].ion.intensity > 0.0);
+        assert_eq!(series.n_ions[1].ion.intensity, 0.0);
+    }
+}
@@ -4,6 +4,7 @@ pub trait TimsTofCollisionEnergy {
     fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64;
 }
 
+#[derive(Clone, Debug)]
 pub struct TimsTofCollisionEnergyDIA {
     frame_to_window_group: HashMap<i32, i32>,
     window_group_settings: HashMap<(i32, i32), f64>,
@@ -17,6 +18,39 @@ impl TimsTofCollisionEnergyDIA {
         scan_start: Vec<i32>,
         scan_end: Vec<i32>,
         collision_energy: Vec<f64>,
+    ) -> Self {
+        Self::new_with_ramp(frame, frame_window_group, window_group, scan_start, scan_end, collision_energy.clone(), collision_energy)
+    }
+
+    /// Collision energies ramped linearly with the scan within each window
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - fragment frames
+    /// * `frame_window_group` - window group of each frame
+    /// * `window_group` - window group of each window
+    /// * `scan_start` - first scan of each window
+    /// * `scan_end` - last scan of each window, inclusive
+    /// * `ce_start` - collision energy at the first scan of each window
+    /// * `ce_end` - collision energy at the last scan of each window
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::timstof::collision::TimsTofCollisionEnergyDIA;
+    ///
+    /// let ce = TimsTofCollisionEnergyDIA::new_with_ramp(vec![2], vec![1], vec![1], vec![100], vec![200], vec![40.0], vec![20.0]);
+    /// assert_eq!(ce.get_collision_energies(2, &[100, 150, 200, 300]), vec![40.0, 30.0, 20.0, 0.0]);
+    /// assert_eq!(ce.export_ce_table()[50], (1, 150, 30.0));
+    /// ```
+    pub fn new_with_ramp(
+        frame: Vec<i32>,
+        frame_window_group: Vec<i32>,
+        window_group: Vec<i32>,
+        scan_start: Vec<i32>,
+        scan_end: Vec<i32>,
+        ce_start: Vec<f64>,
+        ce_end: Vec<f64>,
     ) -> Self {
         // hashmap from frame to window group
         let frame_to_window_group = frame.iter().zip(frame_window_group.iter()).map(|(&f, &wg)| (f, wg)).collect::<HashMap<i32, i32>>();
@@ -25,10 +59,12 @@ impl TimsTofCollisionEnergyDIA {
         for (index, &wg) in window_group.iter().enumerate() {
             let scan_start = scan_start[index];
             let scan_end = scan_end[index];
-            let collision_energy = collision_energy[index];
+            let (ce_start, ce_end) = (ce_start[index], ce_end[index]);
+            let num_steps = (scan_end - scan_start).max(1) as f64;
 
             for scan in scan_start..scan_end + 1 {
                 let key = (wg, scan);
+                let collision_energy = ce_start + (ce_end - ce_start) * (scan - scan_start) as f64 / num_steps;
                 window_group_settings.insert(key, collision_energy);
             }
         }
@@ -38,6 +74,21 @@ impl TimsTofCollisionEnergyDIA {
             window_group_settings,
         }
     }
+
+    /// Collision energies of a frame at several scans, 0.0 for scans outside the windows of the frame
+    pub fn get_collision_energies(&self, frame_id: i32, scan_ids: &[i32]) -> Vec<f64> {
+        match self.frame_to_window_group.get(&frame_id) {
+            Some(&wg) => scan_ids.iter().map(|&scan| *self.window_group_settings.get(&(wg, scan)).unwrap_or(&0.0)).collect(),
+            None => vec![0.0; scan_ids.len()],
+        }
+    }
+
+    /// Collision energy of every scan covered by a window, as (window group, scan, collision energy) ordered by window group and scan
+    pub fn export_ce_table(&self) -> Vec<(i32, i32, f64)> {
+        let mut table: Vec<(i32, i32, f64)> = self.window_group_settings.iter().map(|(&(wg, scan), &ce)| (wg, scan, ce)).collect();
+        table.sort_by_key(|&(wg, scan, _)| (wg, scan));
+        table
+    }
 }
 
 impl TimsTofCollisionEnergy for TimsTofCollisionEnergyDIA {
@@ -54,4 +105,151 @@ impl TimsTofCollisionEnergy for TimsTofCollisionEnergyDIA {
             None => 0.0,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Collision energy interpolated linearly from the inverse mobility of the scan, the same for every frame
+#[derive(Clone, Debug)]
+pub struct CollisionEnergyMobilityRamp {
+    breakpoints: Vec<(f64, f64)>,
+    scan_to_mobility: HashMap<i32, f64>,
+}
+
+impl CollisionEnergyMobilityRamp {
+    /// Create a ramp from (1/K0, collision energy) breakpoints and the inverse mobility of each scan
+    ///
+    /// # Arguments
+    ///
+    /// * `breakpoints` - (1/K0, collision energy) pairs, in any order
+    /// * `scans` - scans of the frames
+    /// * `mobilities` - inverse mobility of each scan
+    ///
+    /// # Returns
+    ///
+    /// * `Result<CollisionEnergyMobilityRamp, String>` - error without breakpoints, with non-finite or duplicate
+    ///   breakpoints or if scans and mobilities differ in length
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::timstof::collision::{CollisionEnergyMobilityRamp, TimsTofCollisionEnergy};
+    ///
+    /// let ramp = CollisionEnergyMobilityRamp::new(vec![(1.4, 45.0), (0.6, 20.0)], vec![1, 2, 3], vec![1.6, 1.0, 0.5]).unwrap();
+    /// assert_eq!(ramp.breakpoints(), &[(0.6, 20.0), (1.4, 45.0)]);
+    /// assert!((ramp.get_collision_energy(10, 2) - 32.5).abs() < 1e-9);
+    /// assert_eq!(ramp.get_collision_energies(10, &[1, 3, 4]), vec![45.0, 20.0, 0.0]);
+    /// assert!(CollisionEnergyMobilityRamp::new(vec![], vec![1], vec![1.0]).is_err());
+    /// ```
+    pub fn new(breakpoints: Vec<(f64, f64)>, scans: Vec<i32>, mobilities: Vec<f64>) -> Result<Self, String> {
+        if breakpoints.is_empty() {
+            return Err("a collision energy ramp needs at least one breakpoint".to_string());
+        }
+        if breakpoints.iter().any(|(mobility, ce)| !mobility.is_finite() || !ce.is_finite()) {
+            return Err("collision energy ramp breakpoints must be finite".to_string());
+        }
+        if scans.len() != mobilities.len() {
+            return Err(format!("got {} scans but {} mobilities", scans.len(), mobilities.len()));
+        }
+
+        let mut breakpoints = breakpoints;
+        breakpoints.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some(pair) = breakpoints.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("duplicate collision energy ramp breakpoint at 1/K0 {}", pair[0].0));
+        }
+
+        Ok(Self {
+            breakpoints,
+            scan_to_mobility: scans.into_iter().zip(mobilities).collect(),
+        })
+    }
+
+    /// Breakpoints of the ramp, ordered by 1/K0
+    pub fn breakpoints(&self) -> &[(f64, f64)] {
+        &self.breakpoints
+    }
+
+    /// Collision energy at an inverse mobility, held constant beyond the first and last breakpoint
+    pub fn collision_energy_at(&self, one_over_k0: f64) -> f64 {
+        let first = self.breakpoints[0];
+        let last = self.breakpoints[self.breakpoints.len() - 1];
+        if one_over_k0 <= first.0 {
+            return first.1;
+        }
+        if one_over_k0 >= last.0 {
+            return last.1;
+        }
+
+        let upper = self.breakpoints.partition_point(|&(mobility, _)| mobility <= one_over_k0);
+        let (x0, y0) = self.breakpoints[upper - 1];
+        let (x1, y1) = self.breakpoints[upper];
+        y0 + (y1 - y0) * (one_over_k0 - x0) / (x1 - x0)
+    }
+
+    /// Collision energies at several scans, 0.0 for scans without a known mobility
+    pub fn get_collision_energies(&self, frame_id: i32, scan_ids: &[i32]) -> Vec<f64> {
+        scan_ids.iter().map(|&scan| self.get_collision_energy(frame_id, scan)).collect()
+    }
+
+    /// Collision energy of every scan, as (scan, collision energy) ordered by scan
+    pub fn export_ce_table(&self) -> Vec<(i32, f64)> {
+        let mut table: Vec<(i32, f64)> = self.scan_to_mobility.iter()
+            .map(|(&scan, &mobility)| (scan, self.collision_energy_at(mobility)))
+            .collect();
+        table.sort_by_key(|&(scan, _)| scan);
+        table
+    }
+}
+
+impl TimsTofCollisionEnergy for CollisionEnergyMobilityRamp {
+    fn get_collision_energy(&self, _frame_id: i32, scan_id: i32) -> f64 {
+        match self.scan_to_mobility.get(&scan_id) {
+            Some(&mobility) => self.collision_energy_at(mobility),
+            None => 0.0,
+        }
+    }
+}
+
+/// Where the collision energies of simulated fragment frames come from
+#[derive(Clone, Debug)]
+pub enum CollisionEnergySource {
+    /// per scan collision energies of the DIA window groups
+    WindowGroups(TimsTofCollisionEnergyDIA),
+    /// collision energies interpolated from the inverse mobility of the scan
+    MobilityRamp(CollisionEnergyMobilityRamp),
+}
+
+impl CollisionEnergySource {
+    /// Collision energies of a frame at several scans, 0.0 where no collision energy is defined
+    pub fn get_collision_energies(&self, frame_id: i32, scan_ids: &[i32]) -> Vec<f64> {
+        match self {
+            CollisionEnergySource::WindowGroups(ce) => ce.get_collision_energies(frame_id, scan_ids),
+            CollisionEnergySource::MobilityRamp(ce) => ce.get_collision_energies(frame_id, scan_ids),
+        }
+    }
+
+    /// Collision energy table as (window group, scan, collision energy), a mobility ramp is reported as window group 0
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::timstof::collision::{CollisionEnergyMobilityRamp, CollisionEnergySource};
+    ///
+    /// let ramp = CollisionEnergyMobilityRamp::new(vec![(0.6, 20.0), (1.4, 45.0)], vec![2, 1], vec![0.6, 1.4]).unwrap();
+    /// let source = CollisionEnergySource::MobilityRamp(ramp);
+    /// assert_eq!(source.export_ce_table(), vec![(0, 1, 45.0), (0, 2, 20.0)]);
+    /// ```
+    pub fn export_ce_table(&self) -> Vec<(i32, i32, f64)> {
+        match self {
+            CollisionEnergySource::WindowGroups(ce) => ce.export_ce_table(),
+            CollisionEnergySource::MobilityRamp(ce) => ce.export_ce_table().into_iter().map(|(scan, ce)| (0, scan, ce)).collect(),
+        }
+    }
+}
+
+impl TimsTofCollisionEnergy for CollisionEnergySource {
+    fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64 {
+        match self {
+            CollisionEnergySource::WindowGroups(ce) => ce.get_collision_energy(frame_id, scan_id),
+            CollisionEnergySource::MobilityRamp(ce) => ce.get_collision_energy(frame_id, scan_id),
+        }
+    }
+}
@@ -7,12 +7,24 @@ use itertools::izip;
 use ordered_float::OrderedFloat;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use crate::algorithm::noise::{estimate_noise, signal_to_noise, NoiseEstimator, MIN_NOISE_PEAKS};
+use crate::algorithm::normalization::{normalize, NormalizationMethod};
+use crate::algorithm::smoothing::{smooth, SmoothingMethod};
+use crate::chemistry::mobility::one_over_k0_to_ccs;
+use crate::data::serialization::BinarySerializable;
 use crate::timstof::spectrum::TimsSpectrum;
-use crate::data::spectrum::{MsType, MzSpectrum, IndexedMzSpectrum, Vectorized, ToResolution};
+use crate::data::spectrum::{approx_eq_value, approx_eq_values, top_n_indices, ApproxEq, MsType, MzAxis, MzSpectrum, IndexedMzSpectrum, ResampleMethod, Vectorized, ToResolution};
 use crate::simulation::annotation::{PeakAnnotation, TimsFrameAnnotated};
 use crate::timstof::vec_utils::{filter_with_mask, find_sparse_local_maxima_mask};
 
-#[derive(Clone)]
+/// Row-major flat matrix of the windows of a frame, the scan and window index of every row and the number
+/// of rows and columns, see `TimsFrame::to_dense_windows`
+pub type DenseWindows = (Vec<f64>, Vec<i32>, Vec<i32>, usize, usize);
+
+/// Inverse mobility of a scan and its peaks by quantized m/z, with tof and intensity
+type ScanPeaks = (f64, BTreeMap<i64, (i32, f64)>);
+
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct RawTimsFrame {
     pub frame_id: i32,
     pub retention_time: f64,
@@ -22,6 +34,14 @@ pub struct RawTimsFrame {
     pub intensity: Vec<f64>,
 }
 
+impl ApproxEq for RawTimsFrame {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.ms_type == other.ms_type && self.scan == other.scan && self.tof == other.tof
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
 impl RawTimsFrame {
     pub fn smooth(mut self, window: u32) -> Self {
         let mut smooth_intensities: Vec<f64> = self.intensity.clone();
@@ -66,6 +86,15 @@ pub struct ImsFrame {
     pub intensity: Vec<f64>,
 }
 
+impl ApproxEq for ImsFrame {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_values(&self.mobility, &other.mobility, tolerance)
+            && approx_eq_values(&self.mz, &other.mz, tolerance)
+            && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
 impl ImsFrame {
     /// Creates a new `ImsFrame` instance.
     ///
@@ -94,7 +123,7 @@ impl fmt::Display for ImsFrame {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
 pub struct ImsFrameVectorized {
     pub retention_time: f64,
     pub mobility: Vec<f64>,
@@ -103,6 +132,15 @@ pub struct ImsFrameVectorized {
     pub resolution: i32,
 }
 
+impl ApproxEq for ImsFrameVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.resolution == other.resolution && self.indices == other.indices
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_values(&self.mobility, &other.mobility, tolerance)
+            && approx_eq_values(&self.values, &other.values, tolerance)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct TimsFrame {
     pub frame_id: i32,
@@ -112,6 +150,29 @@ pub struct TimsFrame {
     pub ims_frame: ImsFrame,
 }
 
+impl ApproxEq for TimsFrame {
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::data::spectrum::{ApproxEq, MsType};
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1], vec![1.2], vec![1000], vec![500.0], vec![100.0]);
+    /// let mut recalibrated = frame.clone();
+    /// recalibrated.ims_frame.mz[0] += 1e-4;
+    /// assert!(frame.approx_eq(&recalibrated, 1e-3));
+    /// assert!(!frame.approx_eq(&recalibrated, 0.0));
+    /// ```
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.ms_type == other.ms_type && self.scan == other.scan && self.tof == other.tof
+            && self.ims_frame.approx_eq(&other.ims_frame, tolerance)
+    }
+}
+
+impl BinarySerializable for RawTimsFrame {}
+
+impl BinarySerializable for TimsFrame {}
+
 impl Default for TimsFrame {
     fn default() -> Self {
         TimsFrame {
@@ -124,6 +185,97 @@ impl Default for TimsFrame {
     }
 }
 
+/// An m/z window of a single scan of a frame, as produced by `TimsFrame::to_indexed_windows`
+#[derive(Clone, Debug)]
+pub struct TimsWindow {
+    /// key of the window, non-negative for the regular grid, negative for the half-shifted overlapping grid
+    pub window_index: i32,
+    pub mz_min: f64,
+    pub mz_max: f64,
+    pub scan_min: i32,
+    pub scan_max: i32,
+    pub spectrum: TimsSpectrum,
+}
+
+/// A mobility window of a frame spanning several scans, as produced by `TimsFrame::to_mobility_windows`
+#[derive(Clone, Debug)]
+pub struct TimsMobilityWindow {
+    /// key of the window, non-negative for the regular grid, negative for the half-shifted overlapping grid
+    pub window_index: i32,
+    pub mobility_min: f64,
+    pub mobility_max: f64,
+    pub scan_min: i32,
+    pub scan_max: i32,
+    pub frame: TimsFrame,
+}
+
+/// Quick numbers about a frame for quality control, as produced by `TimsFrame::summary`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSummary {
+    pub frame_id: i32,
+    pub ms_type: MsType,
+    pub retention_time: f64,
+    pub num_peaks: usize,
+    pub total_intensity: f64,
+    pub max_intensity: f64,
+    pub median_intensity: f64,
+    /// ranges are `None` for frames without peaks
+    pub mz_range: Option<(f64, f64)>,
+    pub scan_range: Option<(i32, i32)>,
+    pub mobility_range: Option<(f64, f64)>,
+}
+
+/// Smallest and largest value, `None` if there are no values
+pub fn value_range<T: PartialOrd + Copy>(values: impl IntoIterator<Item = T>) -> Option<(T, T)> {
+    values.into_iter().fold(None, |range, value| match range {
+        None => Some((value, value)),
+        Some((min, max)) => Some((
+            if value < min { value } else { min },
+            if value > max { value } else { max },
+        )),
+    })
+}
+
+/// Bounds of a window key, keys of the overlapping grid are negative and shifted by half a window
+fn window_bounds(window_index: i32, window_length: f64) -> (f64, f64) {
+    if window_index >= 0 {
+        (window_index as f64 * window_length, (window_index + 1) as f64 * window_length)
+    } else {
+        let center = -window_index as f64 * window_length;
+        (center - window_length / 2.0, center + window_length / 2.0)
+    }
+}
+
+/// Reasons why the arrays of a `TimsFrame` do not describe a valid frame, see `TimsFrame::validate`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameValidationError {
+    /// array with a different length than scan
+    LengthMismatch { array: &'static str, expected: usize, found: usize },
+    /// scan at this index is smaller than the scan before it
+    UnsortedScan { index: usize },
+    /// m/z at this index is smaller than the m/z before it within the same scan
+    UnsortedMz { index: usize, scan: i32 },
+    /// intensity at this index is negative or NaN
+    NegativeIntensity { index: usize, intensity: f64 },
+}
+
+impl fmt::Display for FrameValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameValidationError::LengthMismatch { array, expected, found } => write!(
+                f, "{} has {} values, expected one per scan value ({})", array, found, expected),
+            FrameValidationError::UnsortedScan { index } => write!(
+                f, "scans are not sorted at index {}, use sort_by_scan_then_mz to repair the frame", index),
+            FrameValidationError::UnsortedMz { index, scan } => write!(
+                f, "m/z values of scan {} are not sorted at index {}, use sort_by_scan_then_mz to repair the frame", scan, index),
+            FrameValidationError::NegativeIntensity { index, intensity } => write!(
+                f, "intensity {} at index {} is not a non-negative number", intensity, index),
+        }
+    }
+}
+
+impl std::error::Error for FrameValidationError {}
+
 impl TimsFrame {
     /// Creates a new `TimsFrame` instance.
     ///
@@ -147,6 +299,7 @@ impl TimsFrame {
     ///
     /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 2], vec![0.1, 0.2], vec![1000, 2000], vec![100.5, 200.5], vec![50.0, 60.0]);
     /// ```
+    #[allow(clippy::too_many_arguments, reason = "one argument per column of the frame")]
     pub fn new(frame_id: i32, ms_type: MsType, retention_time: f64, scan: Vec<i32>, mobility: Vec<f64>, tof: Vec<i32>, mz: Vec<f64>, intensity: Vec<f64>) -> Self {
         TimsFrame { frame_id, ms_type, scan, tof, ims_frame: ImsFrame { retention_time, mobility, mz, intensity } }
     }
@@ -165,6 +318,59 @@ impl TimsFrame {
     /// ```
     pub fn get_ims_frame(&self) -> ImsFrame { self.ims_frame.clone() }
 
+    /// Check that the arrays describe a valid frame: all arrays as long as scan, scans sorted ascending,
+    /// m/z sorted ascending within each scan and intensities non-negative
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::{FrameValidationError, TimsFrame};
+    ///
+    /// let mut frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![2, 1, 1], vec![0.2, 0.1, 0.1], vec![3, 2, 1], vec![300.0, 200.0, 100.0], vec![3.0, 2.0, 1.0]);
+    /// assert_eq!(frame.validate(), Err(FrameValidationError::UnsortedScan { index: 1 }));
+    ///
+    /// frame.sort_by_scan_then_mz();
+    /// assert_eq!(frame.validate(), Ok(()));
+    /// assert_eq!((frame.scan, frame.ims_frame.mz), (vec![1, 1, 2], vec![100.0, 200.0, 300.0]));
+    /// ```
+    pub fn validate(&self) -> Result<(), FrameValidationError> {
+        let expected = self.scan.len();
+        for (array, found) in [("mobility", self.ims_frame.mobility.len()), ("tof", self.tof.len()), ("mz", self.ims_frame.mz.len()), ("intensity", self.ims_frame.intensity.len())] {
+            if found != expected {
+                return Err(FrameValidationError::LengthMismatch { array, expected, found });
+            }
+        }
+
+        for index in 1..expected {
+            let (scan, previous_scan) = (self.scan[index], self.scan[index - 1]);
+            if scan < previous_scan {
+                return Err(FrameValidationError::UnsortedScan { index });
+            }
+            if scan == previous_scan && self.ims_frame.mz[index] < self.ims_frame.mz[index - 1] {
+                return Err(FrameValidationError::UnsortedMz { index, scan });
+            }
+        }
+
+        match self.ims_frame.intensity.iter().position(|intensity| intensity.is_nan() || *intensity < 0.0) {
+            Some(index) => Err(FrameValidationError::NegativeIntensity { index, intensity: self.ims_frame.intensity[index] }),
+            None => Ok(()),
+        }
+    }
+
+    /// Sort all peak arrays together by scan and then m/z, repairing the ordering `validate` checks,
+    /// the arrays must have equal lengths
+    pub fn sort_by_scan_then_mz(&mut self) {
+        let mut order: Vec<usize> = (0..self.scan.len()).collect();
+        order.sort_by(|&a, &b| self.scan[a].cmp(&self.scan[b]).then(self.ims_frame.mz[a].total_cmp(&self.ims_frame.mz[b])));
+
+        self.scan = order.iter().map(|&i| self.scan[i]).collect();
+        self.tof = order.iter().map(|&i| self.tof[i]).collect();
+        self.ims_frame.mobility = order.iter().map(|&i| self.ims_frame.mobility[i]).collect();
+        self.ims_frame.mz = order.iter().map(|&i| self.ims_frame.mz[i]).collect();
+        self.ims_frame.intensity = order.iter().map(|&i| self.ims_frame.intensity[i]).collect();
+    }
+
     ///
     /// Convert a given TimsFrame to a vector of TimsSpectrum.
     ///
@@ -227,6 +433,7 @@ impl TimsFrame {
     /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 2], vec![0.1, 0.2], vec![1000, 2000], vec![100.5, 200.5], vec![50.0, 60.0]);
     /// let filtered_frame = frame.filter_ranged(100.0, 200.0, 1, 2, 0.0, 1.6, 50.0, 60.0);
     /// ```
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for every dimension")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64) -> TimsFrame {
 
         let mut scan_vec = Vec::new();
@@ -262,6 +469,196 @@ impl TimsFrame {
         TimsFrame::new(self.frame_id, self.ms_type.clone(), self.ims_frame.retention_time, scan, mobility, tof, mz, intensity)
     }
 
+    /// Keep the `n` most intense peaks of the frame, all coordinate arrays keep their order.
+    /// Ties at the cutoff are resolved in favor of the lower m/z
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1, 1, 2], vec![1.2, 1.2, 1.1], vec![10, 20, 30], vec![300.0, 200.0, 100.0], vec![4.0, 9.0, 4.0]);
+    /// let top = frame.top_n_global(2);
+    /// assert_eq!(top.scan, vec![1, 2]);
+    /// assert_eq!(top.ims_frame.mz, vec![200.0, 100.0]);
+    /// ```
+    pub fn top_n_global(&self, n: usize) -> TimsFrame {
+        let indices = top_n_indices(&self.ims_frame.mz, &self.ims_frame.intensity, 0..self.ims_frame.mz.len(), n);
+        self.select_peaks(&indices)
+    }
+
+    /// Keep the `n` most intense peaks of every scan, ties at the cutoff are resolved in favor of the lower m/z
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1, 1, 2, 2], vec![1.2, 1.2, 1.1, 1.1], vec![10, 20, 30, 40], vec![100.0, 200.0, 300.0, 400.0], vec![4.0, 9.0, 1.0, 2.0]);
+    /// let top = frame.top_n_per_scan(1);
+    /// assert_eq!(top.scan, vec![1, 2]);
+    /// assert_eq!(top.tof, vec![20, 40]);
+    /// ```
+    pub fn top_n_per_scan(&self, n: usize) -> TimsFrame {
+        let mut scans: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+        for (i, &scan) in self.scan.iter().enumerate() {
+            scans.entry(scan).or_default().push(i);
+        }
+
+        let mut indices: Vec<usize> = scans.into_values()
+            .flat_map(|candidates| top_n_indices(&self.ims_frame.mz, &self.ims_frame.intensity, candidates, n))
+            .collect();
+        indices.sort_unstable();
+        self.select_peaks(&indices)
+    }
+
+    /// mean inverse mobility of every scan that holds at least one peak
+    fn scan_mobilities(&self) -> BTreeMap<i32, f64> {
+        let mut sums: BTreeMap<i32, (f64, usize)> = BTreeMap::new();
+        for (&scan, &mobility) in self.scan.iter().zip(&self.ims_frame.mobility) {
+            let entry = sums.entry(scan).or_insert((0.0, 0));
+            entry.0 += mobility;
+            entry.1 += 1;
+        }
+        sums.into_iter().map(|(scan, (sum, count))| (scan, sum / count as f64)).collect()
+    }
+
+    /// Smooth the intensity of every m/z bin across adjacent scans
+    ///
+    /// Peaks are binned to `resolution` decimal places, scans without a peak count as zero intensity.
+    /// Windows shrink at the first and last scan of the frame. Smoothed intensity is only placed on scans
+    /// that hold a peak in the frame, since only those have a known mobility, and negative values of the
+    /// Savitzky-Golay filter are dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `window_scans` - number of scans of a full window, centered on the smoothed scan
+    /// * `method` - moving average or Savitzky-Golay
+    /// * `resolution` - decimal places of the m/z bins
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::algorithm::smoothing::SmoothingMethod;
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1, 2, 3, 3], vec![1.3, 1.2, 1.1, 1.1], vec![10, 20, 500, 30], vec![300.0, 300.0, 400.0, 300.0], vec![3.0, 9.0, 6.0, 3.0]);
+    /// let smoothed = frame.smooth_mobility(3, SmoothingMethod::MovingAverage, 2);
+    /// assert_eq!(smoothed.scan, vec![1, 2, 2, 3, 3]);
+    /// assert_eq!(smoothed.ims_frame.mz, vec![300.0, 300.0, 400.0, 300.0, 400.0]);
+    /// assert_eq!(smoothed.ims_frame.intensity, vec![6.0, 5.0, 2.0, 6.0, 3.0]);
+    /// ```
+    pub fn smooth_mobility(&self, window_scans: usize, method: SmoothingMethod, resolution: i32) -> TimsFrame {
+        let scan_mobilities = self.scan_mobilities();
+        let (first_scan, last_scan) = match (scan_mobilities.keys().next(), scan_mobilities.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return self.clone(),
+        };
+        let half = (window_scans / 2) as i32;
+        let factor = 10f64.powi(resolution);
+
+        // intensity per scan and the tof values of every m/z bin
+        let mut bins: BTreeMap<i64, (BTreeMap<i32, f64>, f64, usize)> = BTreeMap::new();
+        for (&scan, &tof, &mz, &intensity) in izip!(&self.scan, &self.tof, &self.ims_frame.mz, &self.ims_frame.intensity) {
+            let bin = bins.entry((mz * factor).round() as i64).or_insert_with(|| (BTreeMap::new(), 0.0, 0));
+            *bin.0.entry(scan).or_insert(0.0) += intensity;
+            bin.1 += tof as f64;
+            bin.2 += 1;
+        }
+
+        let mut peaks: Vec<(i32, i64, i32, f64)> = Vec::new();
+        for (key, (intensities, tof_sum, count)) in bins {
+            let (bin_first, bin_last) = (*intensities.keys().next().unwrap(), *intensities.keys().next_back().unwrap());
+            // windows of the scans that can receive intensity have to see the zeros around them
+            let start = (bin_first - 2 * half).max(first_scan);
+            let end = (bin_last + 2 * half).min(last_scan);
+            let dense: Vec<f64> = (start..=end).map(|scan| intensities.get(&scan).copied().unwrap_or(0.0)).collect();
+            let smoothed = smooth(&dense, window_scans, method);
+
+            let tof = (tof_sum / count as f64) as i32;
+            for (&scan, _) in scan_mobilities.range((bin_first - half).max(start)..=(bin_last + half).min(end)) {
+                let intensity = smoothed[(scan - start) as usize];
+                if intensity > 0.0 {
+                    peaks.push((scan, key, tof, intensity));
+                }
+            }
+        }
+        peaks.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        TimsFrame::new(
+            self.frame_id,
+            self.ms_type.clone(),
+            self.ims_frame.retention_time,
+            peaks.iter().map(|p| p.0).collect(),
+            peaks.iter().map(|p| scan_mobilities[&p.0]).collect(),
+            peaks.iter().map(|p| p.2).collect(),
+            peaks.iter().map(|p| p.1 as f64 / factor).collect(),
+            peaks.iter().map(|p| p.3).collect(),
+        )
+    }
+
+    /// Summed intensity of the peaks within `ppm` of `mz` for every scan of the frame that holds a peak
+    ///
+    /// # Arguments
+    ///
+    /// * `mz` - m/z to extract
+    /// * `ppm` - tolerance around `mz`
+    /// * `smoothing` - optional window size in scans and method to smooth the mobilogram with, see `smooth_mobility`
+    ///
+    /// # Returns
+    ///
+    /// * `(Vec<f64>, Vec<f64>)` - inverse mobility and intensity, ordered by scan
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1, 2, 2, 3], vec![1.3, 1.2, 1.2, 1.1], vec![10, 20, 30, 40], vec![500.0, 500.001, 600.0, 700.0], vec![3.0, 9.0, 6.0, 3.0]);
+    /// let (mobility, intensity) = frame.mobilogram(500.0, 10.0, None);
+    /// assert_eq!(mobility, vec![1.3, 1.2, 1.1]);
+    /// assert_eq!(intensity, vec![3.0, 9.0, 0.0]);
+    /// ```
+    pub fn mobilogram(&self, mz: f64, ppm: f64, smoothing: Option<(usize, SmoothingMethod)>) -> (Vec<f64>, Vec<f64>) {
+        let scan_mobilities = self.scan_mobilities();
+        let (first_scan, last_scan) = match (scan_mobilities.keys().next(), scan_mobilities.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return (Vec::new(), Vec::new()),
+        };
+
+        let tolerance = mz * ppm / 1e6;
+        let mut dense = vec![0.0; (last_scan - first_scan + 1) as usize];
+        for (&scan, &peak_mz, &intensity) in izip!(&self.scan, &self.ims_frame.mz, &self.ims_frame.intensity) {
+            if (peak_mz - mz).abs() <= tolerance {
+                dense[(scan - first_scan) as usize] += intensity;
+            }
+        }
+        if let Some((window_scans, method)) = smoothing {
+            dense = smooth(&dense, window_scans, method);
+        }
+
+        scan_mobilities.iter()
+            .map(|(&scan, &mobility)| (mobility, dense[(scan - first_scan) as usize]))
+            .unzip()
+    }
+
+    fn select_peaks(&self, indices: &[usize]) -> TimsFrame {
+        TimsFrame::new(
+            self.frame_id,
+            self.ms_type.clone(),
+            self.ims_frame.retention_time,
+            indices.iter().map(|&i| self.scan[i]).collect(),
+            indices.iter().map(|&i| self.ims_frame.mobility[i]).collect(),
+            indices.iter().map(|&i| self.tof[i]).collect(),
+            indices.iter().map(|&i| self.ims_frame.mz[i]).collect(),
+            indices.iter().map(|&i| self.ims_frame.intensity[i]).collect(),
+        )
+    }
+
     pub fn to_windows_indexed(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> (Vec<i32>, Vec<i32>, Vec<TimsSpectrum>) {
         // split by scan (ion mobility)
         let spectra = self.to_tims_spectra();
@@ -273,7 +670,7 @@ impl TimsFrame {
         let mut scan_indices = Vec::new();
 
         for tree in windows.iter() {
-            for (_, window) in tree {
+            for window in tree.values() {
                 scan_indices.push(window.scan)
             }
         }
@@ -296,6 +693,94 @@ impl TimsFrame {
         widows
     }
 
+    /// Split the frame into m/z windows per scan, keeping the bounds and scan of every window
+    ///
+    /// # Arguments
+    ///
+    /// * `window_length` - The width of a window in m/z.
+    /// * `overlapping` - If true, windows shifted by half a window length are added.
+    /// * `min_peaks` - The minimum number of peaks of a window.
+    /// * `min_intensity` - The minimum highest intensity of a window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 1, 2], vec![1.0, 1.0, 0.99], vec![1000, 1010, 2000], vec![100.5, 101.5, 205.0], vec![50.0, 60.0, 70.0]);
+    /// let windows = frame.to_indexed_windows(10.0, false, 1, 0.0);
+    /// assert_eq!(windows.len(), 2);
+    /// assert_eq!((windows[0].mz_min, windows[0].mz_max, windows[0].scan_min), (100.0, 110.0, 1));
+    /// assert_eq!(windows[0].spectrum.spectrum.mz_spectrum.mz, vec![100.5, 101.5]);
+    /// ```
+    pub fn to_indexed_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> Vec<TimsWindow> {
+        let (scans, window_indices, spectra) = self.to_windows_indexed(window_length, overlapping, min_peaks, min_intensity);
+
+        izip!(scans, window_indices, spectra)
+            .map(|(scan, window_index, spectrum)| {
+                let (mz_min, mz_max) = window_bounds(window_index, window_length);
+                TimsWindow { window_index, mz_min, mz_max, scan_min: scan, scan_max: scan, spectrum }
+            })
+            .collect()
+    }
+
+    /// Split the frame into windows along the mobility axis, every window holds all peaks of its scans
+    ///
+    /// # Arguments
+    ///
+    /// * `im_window_length` - The width of a window in inverse mobility.
+    /// * `overlapping` - If true, windows shifted by half a window length are added.
+    /// * `min_peaks` - The minimum number of peaks of a window.
+    /// * `min_intensity` - The minimum highest intensity of a window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 2, 3], vec![1.02, 1.01, 0.95], vec![1000, 1010, 2000], vec![100.5, 101.5, 205.0], vec![50.0, 60.0, 70.0]);
+    /// let windows = frame.to_mobility_windows(0.05, false, 1, 0.0);
+    /// assert_eq!(windows.len(), 2);
+    /// assert_eq!((windows[1].scan_min, windows[1].scan_max), (1, 2));
+    /// assert_eq!(windows[1].frame.ims_frame.mz, vec![100.5, 101.5]);
+    /// ```
+    pub fn to_mobility_windows(&self, im_window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64) -> Vec<TimsMobilityWindow> {
+        let mut splits: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+
+        for (i, &mobility) in self.ims_frame.mobility.iter().enumerate() {
+            splits.entry((mobility / im_window_length).floor() as i32).or_default().push(i);
+            if overlapping {
+                splits.entry(-((mobility + im_window_length / 2.0) / im_window_length).floor() as i32).or_default().push(i);
+            }
+        }
+
+        splits
+            .into_iter()
+            .filter(|(_, indices)| {
+                indices.len() >= min_peaks
+                    && indices.iter().map(|&i| self.ims_frame.intensity[i]).fold(0.0, f64::max) >= min_intensity
+            })
+            .map(|(window_index, indices)| {
+                let (mobility_min, mobility_max) = window_bounds(window_index, im_window_length);
+                let frame = TimsFrame::new(
+                    self.frame_id,
+                    self.ms_type.clone(),
+                    self.ims_frame.retention_time,
+                    indices.iter().map(|&i| self.scan[i]).collect(),
+                    indices.iter().map(|&i| self.ims_frame.mobility[i]).collect(),
+                    indices.iter().map(|&i| self.tof[i]).collect(),
+                    indices.iter().map(|&i| self.ims_frame.mz[i]).collect(),
+                    indices.iter().map(|&i| self.ims_frame.intensity[i]).collect(),
+                );
+                let scan_min = *frame.scan.iter().min().unwrap();
+                let scan_max = *frame.scan.iter().max().unwrap();
+                TimsMobilityWindow { window_index, mobility_min, mobility_max, scan_min, scan_max, frame }
+            })
+            .collect()
+    }
+
     pub fn from_windows(windows: Vec<TimsSpectrum>) -> TimsFrame {
 
         let first_window = windows.first().unwrap();
@@ -343,7 +828,7 @@ impl TimsFrame {
             _ => 0.0
         };
 
-        let mut frame_map: BTreeMap<i32, (f64, BTreeMap<i64, (i32, f64)>)> = BTreeMap::new();
+        let mut frame_map: BTreeMap<i32, ScanPeaks> = BTreeMap::new();
         let mut capacity_count = 0;
 
         // Step 2: Group by scan and unroll all spectra to a single vector per scan
@@ -378,7 +863,7 @@ impl TimsFrame {
         TimsFrame::new(frame_id, ms_type, retention_time, scan, mobility, tof, mzs, intensity)
     }
 
-    pub fn to_dense_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32) -> (Vec<f64>, Vec<i32>, Vec<i32>, usize, usize) {
+    pub fn to_dense_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32) -> DenseWindows {
         let factor = (10.0f64).powi(resolution);
         let num_colums = ((window_length * factor).round() + 1.0) as usize;
 
@@ -391,7 +876,7 @@ impl TimsFrame {
 
             let vectorized_window_index = match *window_index >= 0 {
                 true => (*window_index as f64 * window_length * factor).round() as i32,
-                false => (((-1.0 * (*window_index as f64)) * window_length - (0.5 * window_length)) * factor).round() as i32,
+                false => ((-(*window_index as f64) * window_length - (0.5 * window_length)) * factor).round() as i32,
             };
 
             for (i, index) in spectrum.vector.mz_vector.indices.iter().enumerate() {
@@ -410,7 +895,7 @@ impl TimsFrame {
 
         // Group by 'tof' with 'mz' and 'intensity'
         for (&tof, (&mz, &intensity)) in self.tof.iter().zip(self.ims_frame.mz.iter().zip(self.ims_frame.intensity.iter())) {
-            grouped_data.entry(tof).or_insert_with(Vec::new).push((mz, intensity));
+            grouped_data.entry(tof).or_default().push((mz, intensity));
         }
 
         let mut index = Vec::new();
@@ -433,7 +918,7 @@ impl TimsFrame {
     }
 
     pub fn generate_random_sample(&self, take_probability: f64) -> TimsFrame {
-        assert!(take_probability >= 0.0 && take_probability <= 1.0);
+        assert!((0.0..=1.0).contains(&take_probability));
 
         let mut rng = rand::thread_rng();
         let mut scan = Vec::new();
@@ -530,6 +1015,53 @@ impl TimsFrame {
         (mean, variance)
     }
 
+    /// Collision cross-section of every peak in Å², converted from its inverse mobility and m/z
+    ///
+    /// # Arguments
+    ///
+    /// * `charges` - assumed charge state of every peak, a single charge applies to all peaks
+    /// * `temperature` - temperature of the drift gas in C°, defaults to `DRIFT_GAS_TEMPERATURE`
+    /// * `mass_gas` - mass of the drift gas, defaults to nitrogen
+    pub fn get_ccs(&self, charges: &[u32], temperature: Option<f64>, mass_gas: Option<f64>) -> Vec<f64> {
+        assert!(charges.len() == 1 || charges.len() == self.ims_frame.mz.len(), "expected one charge or one charge per peak");
+        izip!(&self.ims_frame.mobility, &self.ims_frame.mz)
+            .enumerate()
+            .map(|(i, (&one_over_k0, &mz))| {
+                let charge = if charges.len() == 1 { charges[0] } else { charges[i] };
+                one_over_k0_to_ccs(one_over_k0, mz, charge, temperature, mass_gas)
+            })
+            .collect()
+    }
+
+    /// Bin the frame to the given m/z resolution and store it as a sparse scan x m/z index matrix (CSR)
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - number of decimal places of the m/z bins, m/z indices are 64 bit so high resolutions do not overflow
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 2], vec![1.2, 1.1], vec![1000, 2000], vec![100.5, 200.5], vec![50.0, 60.0]);
+    /// let csr = frame.to_csr(6);
+    /// assert_eq!(csr.mz_indices, vec![100_500_000, 200_500_000]);
+    /// assert_eq!(csr.scan_ptr, vec![0, 1, 2]);
+    /// ```
+    pub fn to_csr(&self, resolution: i32) -> TimsFrameCsr {
+        let factor = 10f64.powi(resolution);
+        TimsFrameCsr::from_binned(
+            self.frame_id,
+            self.ms_type.clone(),
+            self.ims_frame.retention_time,
+            resolution,
+            izip!(&self.scan, &self.ims_frame.mz, &self.ims_frame.mobility, &self.tof, &self.ims_frame.intensity)
+                .map(|(&scan, &mz, &mobility, &tof, &intensity)| (scan, (mz * factor).round() as i64, mobility, tof, intensity)),
+        )
+    }
+
     pub fn get_tims_spectrum(&self, scan_number: i32) -> Option<TimsSpectrum> {
         let mut tof = Vec::new();
         let mut mz = Vec::new();
@@ -561,6 +1093,318 @@ impl TimsFrame {
             spectrum: IndexedMzSpectrum::new(tof, mz, intensity),
         })
     }
+
+    /// Number of peaks, intensity statistics and the ranges covered by the peaks of the frame,
+    /// only the intensities are copied to find the median
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![5, 3, 9], vec![1.1, 1.2, 0.9], vec![10, 20, 30], vec![300.0, 200.0, 400.0], vec![4.0, 1.0, 10.0]);
+    /// let summary = frame.summary();
+    /// assert_eq!((summary.num_peaks, summary.total_intensity, summary.max_intensity, summary.median_intensity), (3, 15.0, 10.0, 4.0));
+    /// assert_eq!(summary.mz_range, Some((200.0, 400.0)));
+    /// assert_eq!(summary.scan_range, Some((3, 9)));
+    /// assert_eq!(TimsFrame::default().summary().mz_range, None);
+    /// ```
+    pub fn summary(&self) -> FrameSummary {
+        let intensity = &self.ims_frame.intensity;
+
+        let median_intensity = if intensity.is_empty() {
+            0.0
+        } else {
+            let mut sorted = intensity.clone();
+            let middle = sorted.len() / 2;
+            let (lower, &mut upper, _) = sorted.select_nth_unstable_by(middle, |a, b| a.total_cmp(b));
+            if intensity.len() % 2 == 0 {
+                let below = lower.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                (below + upper) / 2.0
+            } else {
+                upper
+            }
+        };
+
+        FrameSummary {
+            frame_id: self.frame_id,
+            ms_type: self.ms_type.clone(),
+            retention_time: self.ims_frame.retention_time,
+            num_peaks: intensity.len(),
+            total_intensity: intensity.iter().sum(),
+            max_intensity: intensity.iter().copied().fold(0.0, f64::max),
+            median_intensity,
+            mz_range: value_range(self.ims_frame.mz.iter().copied()),
+            scan_range: value_range(self.scan.iter().copied()),
+            mobility_range: value_range(self.ims_frame.mobility.iter().copied()),
+        }
+    }
+
+    /// Bin the peaks of the frame on a m/z axis shared by all frames of a run, peaks of the same scan
+    /// and bin are summed and peaks outside of the axis are dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The shared m/z axis, see `MzAxis`
+    ///
+    /// # Returns
+    ///
+    /// * Scans, positions on the axis and intensities of the non-empty bins, ordered by scan and position
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::{MsType, MzAxis};
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![2, 1, 1, 1], vec![1.1, 1.2, 1.2, 1.2], vec![0; 4], vec![100.1, 100.21, 100.19, 150.0], vec![1.0, 2.0, 3.0, 4.0]);
+    /// let (scan, index, intensity) = frame.vectorize_on_axis(&MzAxis::new(1, 100.0, 110.0));
+    /// assert_eq!(scan, vec![1, 2]);
+    /// assert_eq!(index, vec![2, 1]);
+    /// assert_eq!(intensity, vec![5.0, 1.0]);
+    /// ```
+    pub fn vectorize_on_axis(&self, axis: &MzAxis) -> (Vec<i32>, Vec<u32>, Vec<f64>) {
+        let mut bins: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+        for (scan, mz, intensity) in izip!(&self.scan, &self.ims_frame.mz, &self.ims_frame.intensity) {
+            if let Some(index) = axis.index(*mz) {
+                *bins.entry((*scan, index)).or_insert(0.0) += intensity;
+            }
+        }
+
+        let mut scans = Vec::with_capacity(bins.len());
+        let mut indices = Vec::with_capacity(bins.len());
+        let mut intensities = Vec::with_capacity(bins.len());
+        for ((scan, index), intensity) in bins {
+            scans.push(scan);
+            indices.push(index);
+            intensities.push(intensity);
+        }
+        (scans, indices, intensities)
+    }
+
+    /// Resample the spectrum of every scan of the frame onto a fixed m/z grid, see `MzSpectrum::resample`
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - m/z values of the grid points in strictly increasing order
+    /// * `method` - how peaks are transferred onto the grid
+    ///
+    /// # Returns
+    ///
+    /// * Scans holding a peak in ascending order and one row of `grid.len()` intensities per scan, or an error
+    ///   if the grid is not strictly increasing or the method is invalid
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::{MsType, ResampleMethod};
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![2, 1, 1], vec![1.1, 1.2, 1.2], vec![0; 3], vec![100.1, 100.0, 100.19], vec![1.0, 2.0, 3.0]);
+    /// let (scans, rows) = frame.resample_scans(&[100.0, 100.1, 100.2], ResampleMethod::NearestBin).unwrap();
+    /// assert_eq!(scans, vec![1, 2]);
+    /// assert_eq!(rows, vec![vec![2.0, 0.0, 3.0], vec![0.0, 1.0, 0.0]]);
+    /// ```
+    pub fn resample_scans(&self, grid: &[f64], method: ResampleMethod) -> Result<(Vec<i32>, Vec<Vec<f64>>), String> {
+        let spectra = self.to_tims_spectra();
+        let mut scans = Vec::with_capacity(spectra.len());
+        let mut rows = Vec::with_capacity(spectra.len());
+        for spectrum in spectra {
+            rows.push(spectrum.spectrum.mz_spectrum.resample(grid, method)?);
+            scans.push(spectrum.scan);
+        }
+        // an empty frame still rejects an invalid grid
+        if rows.is_empty() {
+            MzSpectrum::new(Vec::new(), Vec::new()).resample(grid, method)?;
+        }
+        Ok((scans, rows))
+    }
+
+    /// Normalize the intensities of the frame, e.g. to compare simulated and measured frames
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - normalization method, `PerScanTotalIonCurrent` scales every scan separately
+    ///
+    /// # Returns
+    ///
+    /// * `TimsFrame` - frame with scaled intensities and all other arrays untouched, frames or scans without
+    ///   intensity are returned unchanged
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::algorithm::normalization::NormalizationMethod;
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1, 1, 2], vec![1.2, 1.2, 1.1], vec![10, 20, 30], vec![300.0, 400.0, 300.0], vec![1.0, 3.0, 4.0]);
+    /// let tic = frame.normalize(NormalizationMethod::TotalIonCurrent { target: 1.0 });
+    /// assert_eq!(tic.ims_frame.intensity, vec![0.125, 0.375, 0.5]);
+    /// let per_scan = frame.normalize(NormalizationMethod::PerScanTotalIonCurrent { target: 1.0 });
+    /// assert_eq!(per_scan.ims_frame.intensity, vec![0.25, 0.75, 1.0]);
+    /// assert_eq!(per_scan.tof, frame.tof);
+    /// ```
+    pub fn normalize(&self, method: NormalizationMethod) -> TimsFrame {
+        let intensity = match method {
+            NormalizationMethod::PerScanTotalIonCurrent { target } => {
+                let mut scan_totals: BTreeMap<i32, f64> = BTreeMap::new();
+                for (scan, intensity) in self.scan.iter().zip(&self.ims_frame.intensity) {
+                    *scan_totals.entry(*scan).or_insert(0.0) += intensity;
+                }
+                self.scan.iter().zip(&self.ims_frame.intensity).map(|(scan, intensity)| {
+                    let total = scan_totals[scan];
+                    if total != 0.0 && total.is_finite() { intensity * target / total } else { *intensity }
+                }).collect()
+            }
+            _ => normalize(&self.ims_frame.intensity, method),
+        };
+
+        let mut frame = self.clone();
+        frame.ims_frame.intensity = intensity;
+        frame
+    }
+
+    /// noise level of every scan that holds a peak, scans with too few peaks or without noise use the frame noise
+    fn scan_noise(&self, method: NoiseEstimator) -> BTreeMap<i32, f64> {
+        let global_noise = estimate_noise(&self.ims_frame.intensity, method);
+        let mut scans: BTreeMap<i32, Vec<f64>> = BTreeMap::new();
+        for (&scan, &intensity) in self.scan.iter().zip(&self.ims_frame.intensity) {
+            scans.entry(scan).or_default().push(intensity);
+        }
+        scans.into_iter().map(|(scan, intensities)| {
+            let noise = if intensities.len() >= MIN_NOISE_PEAKS { estimate_noise(&intensities, method) } else { 0.0 };
+            (scan, if noise > 0.0 { noise } else { global_noise })
+        }).collect()
+    }
+
+    /// Estimate the noise level of every scan of the frame
+    ///
+    /// Scans with fewer than `MIN_NOISE_PEAKS` peaks, or without a positive noise level, get the noise level
+    /// of the whole frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - noise estimator
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<f64>` - noise level of every scan holding a peak, in ascending scan order as `to_tims_spectra`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::algorithm::noise::NoiseEstimator;
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let intensity: Vec<f64> = (1..=12).map(|i| i as f64).chain([100.0]).collect();
+    /// let scan: Vec<i32> = vec![1; 12].into_iter().chain([2]).collect();
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, scan, vec![1.0; 13], vec![0; 13], (0..13).map(|i| 100.0 + i as f64).collect(), intensity);
+    /// let noise = frame.estimate_noise(NoiseEstimator::LowestQuartileMean);
+    /// assert_eq!(noise, vec![2.0, 2.5]);
+    /// ```
+    pub fn estimate_noise(&self, method: NoiseEstimator) -> Vec<f64> {
+        self.scan_noise(method).into_values().collect()
+    }
+
+    /// Remove peaks whose intensity is below `min_snr` times the noise level of their scan, see `estimate_noise`
+    ///
+    /// # Arguments
+    ///
+    /// * `min_snr` - smallest signal-to-noise ratio of the kept peaks
+    /// * `method` - noise estimator
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::algorithm::noise::NoiseEstimator;
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let intensity: Vec<f64> = (1..=12).map(|i| i as f64).chain([100.0]).collect();
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![1; 13], vec![1.0; 13], vec![0; 13], (0..13).map(|i| 100.0 + i as f64).collect(), intensity);
+    /// let filtered = frame.filter_snr(5.0, NoiseEstimator::MedianAbsoluteDeviation);
+    /// assert_eq!(filtered.ims_frame.intensity, vec![100.0]);
+    /// ```
+    pub fn filter_snr(&self, min_snr: f64, method: NoiseEstimator) -> TimsFrame {
+        let noise = self.scan_noise(method);
+        let indices: Vec<usize> = (0..self.scan.len())
+            .filter(|&i| signal_to_noise(self.ims_frame.intensity[i], noise[&self.scan[i]]) >= min_snr)
+            .collect();
+        self.select_peaks(&indices)
+    }
+
+    /// Merge every `factor` adjacent scans into one, e.g. to reduce the mobility resolution of simulated frames
+    ///
+    /// Scan `s` becomes scan `s / factor`, peaks that end up in the same scan with the same tof are merged by
+    /// summing their intensities. The mobility of a merged scan is the mean mobility of the scans it contains.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - number of scans merged into one, 0 and 1 return the frame unchanged
+    ///
+    /// # Returns
+    ///
+    /// * `TimsFrame` - frame sorted by scan and m/z with the same total intensity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 10.0, vec![0, 1, 1, 2], vec![1.3, 1.2, 1.2, 1.1], vec![10, 10, 20, 10], vec![300.0, 300.0, 400.0, 300.0], vec![1.0, 2.0, 3.0, 4.0]);
+    /// let merged = frame.downsample_scans(2);
+    /// assert_eq!(merged.scan, vec![0, 0, 1]);
+    /// assert_eq!(merged.tof, vec![10, 20, 10]);
+    /// assert_eq!(merged.ims_frame.intensity, vec![3.0, 3.0, 4.0]);
+    /// assert!((merged.ims_frame.mobility[0] - 1.25).abs() < 1e-12);
+    /// ```
+    pub fn downsample_scans(&self, factor: usize) -> TimsFrame {
+        if factor <= 1 {
+            return self.clone();
+        }
+        let factor = factor as i32;
+
+        // (merged scan, tof) -> (mz, summed intensity), merged scan -> mobility of every contained scan
+        let mut peaks: BTreeMap<(i32, i32), (f64, f64)> = BTreeMap::new();
+        let mut scan_mobilities: BTreeMap<i32, BTreeMap<i32, f64>> = BTreeMap::new();
+
+        for (scan, tof, mz, mobility, intensity) in izip!(&self.scan, &self.tof, &self.ims_frame.mz, &self.ims_frame.mobility, &self.ims_frame.intensity) {
+            let merged_scan = scan.div_euclid(factor);
+            peaks.entry((merged_scan, *tof)).or_insert((*mz, 0.0)).1 += intensity;
+            scan_mobilities.entry(merged_scan).or_default().insert(*scan, *mobility);
+        }
+
+        let merged_mobility: BTreeMap<i32, f64> = scan_mobilities.into_iter()
+            .map(|(scan, mobilities)| (scan, mobilities.values().sum::<f64>() / mobilities.len() as f64))
+            .collect();
+
+        let mut frame = TimsFrame {
+            frame_id: self.frame_id,
+            ms_type: self.ms_type.clone(),
+            scan: Vec::with_capacity(peaks.len()),
+            tof: Vec::with_capacity(peaks.len()),
+            ims_frame: ImsFrame {
+                retention_time: self.ims_frame.retention_time,
+                mobility: Vec::with_capacity(peaks.len()),
+                mz: Vec::with_capacity(peaks.len()),
+                intensity: Vec::with_capacity(peaks.len()),
+            },
+        };
+        for ((scan, tof), (mz, intensity)) in peaks {
+            frame.scan.push(scan);
+            frame.tof.push(tof);
+            frame.ims_frame.mobility.push(merged_mobility[&scan]);
+            frame.ims_frame.mz.push(mz);
+            frame.ims_frame.intensity.push(intensity);
+        }
+        // peaks are ordered by tof, which orders them by m/z within a scan unless m/z was modified
+        frame.sort_by_scan_then_mz();
+        frame
+    }
 }
 
 struct AggregateData {
@@ -623,7 +1467,7 @@ impl std::ops::Add for TimsFrame {
             },
         };
 
-        return frame;
+        frame
     }
 }
 
@@ -632,11 +1476,11 @@ impl fmt::Display for TimsFrame {
 
         let (mz, i) = self.ims_frame.mz.iter()
             .zip(&self.ims_frame.intensity)
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
             .unwrap();
 
-        write!(f, "TimsFrame(id: {}, type: {}, rt: {}, data points: {}, max by intensity: (mz: {}, intensity: {}))",
-               self.frame_id, self.ms_type, self.ims_frame.retention_time, self.scan.len(), format!("{:.3}", mz), i)
+        write!(f, "TimsFrame(id: {}, type: {}, rt: {}, data points: {}, max by intensity: (mz: {:.3}, intensity: {}))",
+               self.frame_id, self.ms_type, self.ims_frame.retention_time, self.scan.len(), mz, i)
     }
 }
 
@@ -646,7 +1490,7 @@ impl Vectorized<TimsFrameVectorized> for TimsFrame {
         // Translate the m/z values into integer indices
         let indices: Vec<i32> = binned_frame.ims_frame.mz.iter().map(|&mz| (mz * 10f64.powi(resolution)).round() as i32).collect();
         // Create a vector of values
-        return TimsFrameVectorized {
+        TimsFrameVectorized {
             frame_id: self.frame_id,
             ms_type: self.ms_type.clone(),
             scan: binned_frame.scan,
@@ -658,13 +1502,27 @@ impl Vectorized<TimsFrameVectorized> for TimsFrame {
                 values: binned_frame.ims_frame.intensity,
                 resolution,
             },
-        };
+        }
     }
 }
 
-///
+/// Peaks merged into one m/z bin of a scan by `to_resolution`, plain sums are used for bins without intensity
+#[derive(Default)]
+struct ResolutionBin {
+    intensity_sum: f64,
+    weighted_tof_sum: f64,
+    weighted_mobility_sum: f64,
+    tof_sum: f64,
+    mobility_sum: f64,
+    count: i32,
+}
+
 /// Convert a given TimsFrame to a vector of TimsSpectrum.
 ///
+/// Peaks of the same scan whose m/z rounds to the same bin are merged, their intensities are summed,
+/// tof and mobility are the intensity weighted means of the merged peaks, plain means if all of them
+/// have zero intensity. The total intensity of the frame is preserved.
+///
 /// # Arguments
 ///
 /// * `resolution` - The resolution to which the m/z values should be rounded.
@@ -678,29 +1536,33 @@ impl Vectorized<TimsFrameVectorized> for TimsFrame {
 /// use mscore::data::spectrum::IndexedMzSpectrum;
 /// use mscore::data::spectrum::ToResolution;
 ///
-/// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 2], vec![0.1, 0.2], vec![1000, 2000], vec![100.5, 200.5], vec![50.0, 60.0]);
+/// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 1], vec![0.1, 0.2], vec![1000, 1004], vec![100.51, 100.54], vec![30.0, 10.0]);
 /// let low_res_frame = frame.to_resolution(1);
+/// assert_eq!(low_res_frame.ims_frame.intensity, vec![40.0]);
+/// assert_eq!(low_res_frame.tof, vec![1001]);
+/// assert!((low_res_frame.ims_frame.mobility[0] - 0.125).abs() < 1e-12);
 /// ```
 impl ToResolution for TimsFrame {
     fn to_resolution(&self, resolution: i32) -> TimsFrame {
         let factor = (10.0f64).powi(resolution);
 
         // Using a tuple of (scan, mz_bin) as a key
-        // Value will store sum of intensities, sum of tofs, sum of mobilities and their count for averaging
-        let mut bin_map: BTreeMap<(i32, i32), (f64, f64, f64, i32)> = BTreeMap::new();
+        let mut bin_map: BTreeMap<(i32, i32), ResolutionBin> = BTreeMap::new();
 
         for i in 0..self.ims_frame.mz.len() {
             let rounded_mz = (self.ims_frame.mz[i] * factor).round() as i32;
             let scan_val = self.scan[i];
-            let intensity_val = self.ims_frame.intensity[i] as f64;
+            let intensity_val = self.ims_frame.intensity[i];
             let tof_val = self.tof[i] as f64;
-            let mobility_val = self.ims_frame.mobility[i] as f64;
-
-            let entry = bin_map.entry((scan_val, rounded_mz)).or_insert((0.0, 0.0, 0.0, 0));
-            entry.0 += intensity_val;
-            entry.1 += tof_val;
-            entry.2 += mobility_val;
-            entry.3 += 1;
+            let mobility_val = self.ims_frame.mobility[i];
+
+            let entry = bin_map.entry((scan_val, rounded_mz)).or_default();
+            entry.intensity_sum += intensity_val;
+            entry.weighted_tof_sum += tof_val * intensity_val;
+            entry.weighted_mobility_sum += mobility_val * intensity_val;
+            entry.tof_sum += tof_val;
+            entry.mobility_sum += mobility_val;
+            entry.count += 1;
         }
 
         let mut new_mz = Vec::with_capacity(bin_map.len());
@@ -709,12 +1571,16 @@ impl ToResolution for TimsFrame {
         let mut new_tof = Vec::with_capacity(bin_map.len());
         let mut new_mobility = Vec::with_capacity(bin_map.len());
 
-        for ((scan, mz_bin), (intensity_sum, tof_sum, mobility_sum, count)) in bin_map {
+        for ((scan, mz_bin), bin) in bin_map {
+            let (tof, mobility) = match bin.intensity_sum > 0.0 {
+                true => (bin.weighted_tof_sum / bin.intensity_sum, bin.weighted_mobility_sum / bin.intensity_sum),
+                false => (bin.tof_sum / bin.count as f64, bin.mobility_sum / bin.count as f64),
+            };
             new_mz.push(mz_bin as f64 / factor);
             new_scan.push(scan);
-            new_intensity.push(intensity_sum);
-            new_tof.push((tof_sum / count as f64) as i32);
-            new_mobility.push(mobility_sum / count as f64);
+            new_intensity.push(bin.intensity_sum);
+            new_tof.push(tof.round() as i32);
+            new_mobility.push(mobility);
         }
 
         TimsFrame {
@@ -732,7 +1598,7 @@ impl ToResolution for TimsFrame {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
 pub struct TimsFrameVectorized {
     pub frame_id: i32,
     pub ms_type: MsType,
@@ -741,7 +1607,17 @@ pub struct TimsFrameVectorized {
     pub ims_frame: ImsFrameVectorized,
 }
 
+impl BinarySerializable for TimsFrameVectorized {}
+
+impl ApproxEq for TimsFrameVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.ms_type == other.ms_type && self.scan == other.scan && self.tof == other.tof
+            && self.ims_frame.approx_eq(&other.ims_frame, tolerance)
+    }
+}
+
 impl TimsFrameVectorized {
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for every dimension")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64) -> TimsFrameVectorized {
         let mut scan_vec = Vec::new();
         let mut mobility_vec = Vec::new();
@@ -782,10 +1658,441 @@ impl fmt::Display for TimsFrameVectorized {
 
         let (mz, i) = self.ims_frame.values.iter()
             .zip(&self.ims_frame.values)
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
             .unwrap();
 
-        write!(f, "TimsFrame(id: {}, type: {}, rt: {}, data points: {}, max by intensity: (mz: {}, intensity: {}))",
-               self.frame_id, self.ms_type, self.ims_frame.retention_time, self.scan.len(), format!("{:.3}", mz), i)
+        write!(f, "TimsFrame(id: {}, type: {}, rt: {}, data points: {}, max by intensity: (mz: {:.3}, intensity: {}))",
+               self.frame_id, self.ms_type, self.ims_frame.retention_time, self.scan.len(), mz, i)
+    }
+}
+
+/// Sparse representation of a frame binned to a given m/z resolution, stored row-wise (CSR) with one row per scan
+///
+/// Row `r` holds scan `scan_min + r`, its entries are `scan_ptr[r]..scan_ptr[r + 1]` of `mz_indices`, `values` and `tof`,
+/// sorted by m/z index. Unlike `TimsFrameVectorized`, memory only grows with the number of non-zero bins.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct TimsFrameCsr {
+    pub frame_id: i32,
+    pub ms_type: MsType,
+    pub retention_time: f64,
+    pub resolution: i32,
+    pub scan_min: i32,
+    pub scan_ptr: Vec<usize>,
+    pub mz_indices: Vec<i64>,
+    pub values: Vec<f64>,
+    pub tof: Vec<i32>,
+    /// inverse mobility of every row, NaN for scans without peaks
+    pub mobility: Vec<f64>,
+}
+
+impl BinarySerializable for TimsFrameCsr {}
+
+impl ApproxEq for TimsFrameCsr {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.ms_type == other.ms_type && self.resolution == other.resolution
+            && self.scan_min == other.scan_min && self.scan_ptr == other.scan_ptr && self.mz_indices == other.mz_indices && self.tof == other.tof
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_values(&self.values, &other.values, tolerance)
+            && approx_eq_values(&self.mobility, &other.mobility, tolerance)
+    }
+}
+
+impl TimsFrameCsr {
+    /// Bin peaks given as (scan, m/z index, mobility, tof, intensity), intensities of a bin are summed, tofs averaged
+    /// weighted by intensity like `TimsFrame::to_resolution`
+    fn from_binned<I: Iterator<Item = (i32, i64, f64, i32, f64)>>(frame_id: i32, ms_type: MsType, retention_time: f64, resolution: i32, peaks: I) -> Self {
+        let mut bin_map: BTreeMap<(i32, i64), (f64, f64, f64, i32)> = BTreeMap::new();
+        let mut row_mobility: BTreeMap<i32, f64> = BTreeMap::new();
+
+        for (scan, mz_index, mobility, tof, intensity) in peaks {
+            let entry = bin_map.entry((scan, mz_index)).or_insert((0.0, 0.0, 0.0, 0));
+            entry.0 += intensity;
+            entry.1 += tof as f64 * intensity;
+            entry.2 += tof as f64;
+            entry.3 += 1;
+            row_mobility.entry(scan).or_insert(mobility);
+        }
+
+        let (scan_min, num_rows) = match (row_mobility.keys().next(), row_mobility.keys().next_back()) {
+            (Some(&min), Some(&max)) => (min, (max - min + 1) as usize),
+            _ => (0, 0),
+        };
+
+        let mut scan_ptr = vec![0; num_rows + 1];
+        let mut mobility = vec![f64::NAN; num_rows];
+        for (scan, row_mobility) in row_mobility {
+            mobility[(scan - scan_min) as usize] = row_mobility;
+        }
+
+        let mut mz_indices = Vec::with_capacity(bin_map.len());
+        let mut values = Vec::with_capacity(bin_map.len());
+        let mut tof = Vec::with_capacity(bin_map.len());
+
+        for ((scan, mz_index), (intensity_sum, weighted_tof, tof_sum, count)) in bin_map {
+            scan_ptr[(scan - scan_min) as usize + 1] += 1;
+            mz_indices.push(mz_index);
+            values.push(intensity_sum);
+            let mean_tof = if intensity_sum > 0.0 { weighted_tof / intensity_sum } else { tof_sum / count as f64 };
+            tof.push(mean_tof.round() as i32);
+        }
+
+        for row in 0..num_rows {
+            scan_ptr[row + 1] += scan_ptr[row];
+        }
+
+        TimsFrameCsr { frame_id, ms_type, retention_time, resolution, scan_min, scan_ptr, mz_indices, values, tof, mobility }
+    }
+
+    /// number of rows (scans) and columns (m/z indices up to and including the largest one) of the matrix
+    pub fn shape(&self) -> (usize, usize) {
+        let num_cols = self.mz_indices.iter().max().map_or(0, |&max| (max + 1).max(0) as usize);
+        (self.scan_ptr.len() - 1, num_cols)
+    }
+
+    /// Convert the sparse frame back to a TimsFrame with m/z values at the bin centers
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 1, 3], vec![1.2, 1.2, 1.1], vec![1000, 1001, 2000], vec![100.51, 100.52, 200.5], vec![50.0, 60.0, 70.0]);
+    /// let csr = frame.to_csr(1);
+    /// assert_eq!(csr.scan_ptr, vec![0, 1, 1, 2]);
+    /// assert_eq!(csr.to_tims_frame().ims_frame.intensity, vec![110.0, 70.0]);
+    /// ```
+    pub fn to_tims_frame(&self) -> TimsFrame {
+        let factor = 10f64.powi(self.resolution);
+        let mut scan = Vec::with_capacity(self.values.len());
+        let mut mobility = Vec::with_capacity(self.values.len());
+
+        for (row, bounds) in self.scan_ptr.windows(2).enumerate() {
+            let num_entries = bounds[1] - bounds[0];
+            scan.extend(std::iter::repeat_n(self.scan_min + row as i32, num_entries));
+            mobility.extend(std::iter::repeat_n(self.mobility[row], num_entries));
+        }
+
+        TimsFrame {
+            frame_id: self.frame_id,
+            ms_type: self.ms_type.clone(),
+            scan,
+            tof: self.tof.clone(),
+            ims_frame: ImsFrame {
+                retention_time: self.retention_time,
+                mobility,
+                mz: self.mz_indices.iter().map(|&index| index as f64 / factor).collect(),
+                intensity: self.values.clone(),
+            },
+        }
+    }
+
+    /// Dense block of the frame, scans outside of the frame and empty bins are zero
+    ///
+    /// # Arguments
+    ///
+    /// * `scan_range` - Scans to extract, one row per scan
+    /// * `mz_index_range` - m/z indices to extract, one column per index
+    ///
+    /// # Returns
+    ///
+    /// * Intensities of the block in row-major order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 100.0, vec![1, 1, 3], vec![1.2, 1.2, 1.1], vec![1000, 1001, 2000], vec![100.5, 100.7, 200.5], vec![50.0, 60.0, 70.0]);
+    /// let csr = frame.to_csr(1);
+    /// let dense = csr.to_dense_slice(0..2, 1005..1008);
+    /// assert_eq!(dense, vec![0.0, 0.0, 0.0, 50.0, 0.0, 60.0]);
+    /// ```
+    pub fn to_dense_slice(&self, scan_range: std::ops::Range<i32>, mz_index_range: std::ops::Range<i64>) -> Vec<f64> {
+        let num_rows = scan_range.len();
+        let num_cols = (mz_index_range.end - mz_index_range.start).max(0) as usize;
+        let mut dense = vec![0.0; num_rows * num_cols];
+
+        for (row, scan) in scan_range.enumerate() {
+            let csr_row = scan - self.scan_min;
+            if csr_row < 0 || csr_row as usize + 1 >= self.scan_ptr.len() {
+                continue;
+            }
+            let start = self.scan_ptr[csr_row as usize];
+            let end = self.scan_ptr[csr_row as usize + 1];
+            let indices = &self.mz_indices[start..end];
+            let first = start + indices.partition_point(|&index| index < mz_index_range.start);
+
+            for k in first..end {
+                let index = self.mz_indices[k];
+                if index >= mz_index_range.end {
+                    break;
+                }
+                dense[row * num_cols + (index - mz_index_range.start) as usize] = self.values[k];
+            }
+        }
+
+        dense
+    }
+}
+
+impl fmt::Display for TimsFrameCsr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "TimsFrameCsr(id: {}, type: {}, rt: {}, resolution: {}, scans: {}, non-zero: {})",
+               self.frame_id, self.ms_type, self.retention_time, self.resolution, self.scan_ptr.len() - 1, self.values.len())
+    }
+}
+
+impl TimsFrameVectorized {
+    /// Sparse CSR layout of the vectorized frame, entries sharing scan and index are summed
+    pub fn to_csr(&self) -> TimsFrameCsr {
+        TimsFrameCsr::from_binned(
+            self.frame_id,
+            self.ms_type.clone(),
+            self.ims_frame.retention_time,
+            self.ims_frame.resolution,
+            izip!(&self.scan, &self.ims_frame.indices, &self.ims_frame.mobility, &self.tof, &self.ims_frame.values)
+                .map(|(&scan, &index, &mobility, &tof, &intensity)| (scan, index as i64, mobility, tof, intensity)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn frame() -> TimsFrame {
+        let mut rng = StdRng::seed_from_u64(1053);
+        let mut peaks: Vec<(i32, f64, f64)> = (0..500)
+            .map(|_| (rng.gen_range(10..60), rng.gen_range(100.0..1700.0), rng.gen_range(1.0..1000.0f64).round()))
+            .collect();
+        peaks.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        TimsFrame::new(
+            1,
+            MsType::Precursor,
+            100.0,
+            peaks.iter().map(|p| p.0).collect(),
+            peaks.iter().map(|p| 1.6 - p.0 as f64 * 0.01).collect(),
+            peaks.iter().map(|p| (p.1 * 100.0) as i32).collect(),
+            peaks.iter().map(|p| p.1).collect(),
+            peaks.iter().map(|p| p.2).collect(),
+        )
+    }
+
+    #[test]
+    fn test_csr_round_trip_matches_binning() {
+        let frame = frame();
+        for resolution in [0, 1, 2, 3] {
+            let csr = frame.to_csr(resolution);
+            assert_eq!(csr.scan_ptr.len(), (csr.shape().0) + 1);
+            assert_eq!(*csr.scan_ptr.last().unwrap(), csr.values.len());
+
+            let expected = frame.to_resolution(resolution);
+            let actual = csr.to_tims_frame();
+            assert_eq!(actual.scan, expected.scan, "resolution {resolution}");
+            assert_eq!(actual.tof, expected.tof);
+            assert_eq!(actual.ims_frame.mz, expected.ims_frame.mz);
+            assert_eq!(actual.ims_frame.intensity, expected.ims_frame.intensity);
+            for (a, b) in actual.ims_frame.mobility.iter().zip(expected.ims_frame.mobility.iter()) {
+                assert!((a - b).abs() < 1e-12);
+            }
+
+            // the vectorized frame of the same resolution ends up in the same layout
+            let from_vectorized = frame.vectorized(resolution).to_csr();
+            assert_eq!(from_vectorized.scan_ptr, csr.scan_ptr);
+            assert_eq!(from_vectorized.values, csr.values);
+        }
+    }
+
+    #[test]
+    fn test_csr_round_trip_lossless_at_high_resolution() {
+        let frame = frame();
+        let actual = frame.to_csr(8).to_tims_frame();
+
+        assert_eq!(actual.scan, frame.scan);
+        assert_eq!(actual.tof, frame.tof);
+        assert_eq!(actual.ims_frame.mobility, frame.ims_frame.mobility);
+        assert_eq!(actual.ims_frame.intensity, frame.ims_frame.intensity);
+        for (a, b) in actual.ims_frame.mz.iter().zip(frame.ims_frame.mz.iter()) {
+            assert!((a - b).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_top_n_keeps_coordinates_consistent() {
+        let frame = frame();
+        let num_peaks = frame.ims_frame.mz.len();
+        for too_many in [num_peaks, num_peaks + 1] {
+            let top = frame.top_n_global(too_many);
+            assert_eq!((top.scan, top.tof, top.ims_frame.mz), (frame.scan.clone(), frame.tof.clone(), frame.ims_frame.mz.clone()));
+            assert_eq!(frame.top_n_per_scan(too_many).ims_frame.intensity, frame.ims_frame.intensity);
+        }
+
+        let top = frame.top_n_global(50);
+        assert_eq!(top.ims_frame.mz.len(), 50);
+        let cutoff = top.ims_frame.intensity.iter().copied().fold(f64::MAX, f64::min);
+        assert!(frame.ims_frame.intensity.iter().filter(|&&i| i > cutoff).count() < 50);
+        for (i, &mz) in top.ims_frame.mz.iter().enumerate() {
+            let j = frame.ims_frame.mz.iter().position(|&m| m == mz).unwrap();
+            assert_eq!((top.scan[i], top.tof[i], top.ims_frame.mobility[i]), (frame.scan[j], frame.tof[j], frame.ims_frame.mobility[j]));
+        }
+
+        let per_scan = frame.top_n_per_scan(2);
+        let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+        for scan in &per_scan.scan {
+            *counts.entry(*scan).or_default() += 1;
+        }
+        assert!(counts.values().all(|&c| c <= 2));
+        assert_eq!(counts.len(), frame.scan.iter().collect::<std::collections::BTreeSet<_>>().len());
+    }
+
+    #[test]
+    fn test_validate_scrambled_frame() {
+        let frame = frame();
+        assert_eq!(frame.validate(), Ok(()));
+
+        let mut scrambled = frame.clone();
+        let order: Vec<usize> = (0..scrambled.scan.len()).rev().collect();
+        scrambled.scan = order.iter().map(|&i| frame.scan[i]).collect();
+        scrambled.tof = order.iter().map(|&i| frame.tof[i]).collect();
+        scrambled.ims_frame.mobility = order.iter().map(|&i| frame.ims_frame.mobility[i]).collect();
+        scrambled.ims_frame.mz = order.iter().map(|&i| frame.ims_frame.mz[i]).collect();
+        scrambled.ims_frame.intensity = order.iter().map(|&i| frame.ims_frame.intensity[i]).collect();
+        assert!(matches!(scrambled.validate(), Err(FrameValidationError::UnsortedScan { .. }) | Err(FrameValidationError::UnsortedMz { .. })));
+
+        scrambled.sort_by_scan_then_mz();
+        assert!(scrambled.approx_eq(&frame, 0.0));
+
+        let mut swapped = frame.clone();
+        let last_in_first_scan = swapped.scan.iter().rposition(|&scan| scan == swapped.scan[0]).unwrap();
+        if last_in_first_scan > 0 {
+            swapped.ims_frame.mz.swap(0, last_in_first_scan);
+            assert_eq!(swapped.validate(), Err(FrameValidationError::UnsortedMz { index: 1, scan: frame.scan[0] }));
+        }
+
+        let mut negative = frame.clone();
+        negative.ims_frame.intensity[3] = -1.0;
+        assert_eq!(negative.validate(), Err(FrameValidationError::NegativeIntensity { index: 3, intensity: -1.0 }));
+        negative.ims_frame.intensity[3] = f64::NAN;
+        assert!(matches!(negative.validate(), Err(FrameValidationError::NegativeIntensity { index: 3, .. })));
+
+        let mut short = frame.clone();
+        short.tof.pop();
+        assert_eq!(short.validate(), Err(FrameValidationError::LengthMismatch { array: "tof", expected: frame.scan.len(), found: frame.scan.len() - 1 }));
+    }
+
+    #[test]
+    fn test_dense_slice_matches_csr() {
+        let csr = frame().to_csr(1);
+        let (scan_range, mz_index_range) = (5..65, 1000..17001);
+        let dense = csr.to_dense_slice(scan_range.clone(), mz_index_range.clone());
+        let num_cols = (mz_index_range.end - mz_index_range.start) as usize;
+
+        assert_eq!(dense.len(), scan_range.len() * num_cols);
+        assert_eq!(dense.iter().sum::<f64>(), csr.values.iter().sum::<f64>());
+
+        let row = (csr.scan_min - scan_range.start) as usize;
+        let col = (csr.mz_indices[0] - mz_index_range.start) as usize;
+        assert_eq!(dense[row * num_cols + col], csr.values[0]);
+
+        assert!(TimsFrame::default().to_csr(2).to_dense_slice(0..3, 0..3).iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let frame = frame();
+        let methods = [
+            NormalizationMethod::TotalIonCurrent { target: 1e6 },
+            NormalizationMethod::BasePeak { target: 1.0 },
+            NormalizationMethod::PerScanTotalIonCurrent { target: 100.0 },
+            NormalizationMethod::Quantile { target: 10.0 },
+        ];
+        for method in methods {
+            let normalized = frame.normalize(method);
+            assert_eq!((&normalized.scan, &normalized.tof, &normalized.ims_frame.mz), (&frame.scan, &frame.tof, &frame.ims_frame.mz));
+
+            let summary = normalized.summary();
+            match method {
+                NormalizationMethod::TotalIonCurrent { target } => assert!((summary.total_intensity - target).abs() < 1e-6),
+                NormalizationMethod::BasePeak { target } => assert!((summary.max_intensity - target).abs() < 1e-12),
+                NormalizationMethod::Quantile { target } => assert!((summary.median_intensity - target).abs() < 1e-9),
+                NormalizationMethod::PerScanTotalIonCurrent { target } => {
+                    for scan in frame.scan.iter().collect::<std::collections::BTreeSet<_>>() {
+                        let total: f64 = normalized.scan.iter().zip(&normalized.ims_frame.intensity).filter(|(s, _)| *s == scan).map(|(_, i)| i).sum();
+                        assert!((total - target).abs() < 1e-9);
+                    }
+                }
+            }
+
+            let empty = TimsFrame::default().normalize(method);
+            assert!(empty.ims_frame.intensity.is_empty());
+            let mut silent = frame.clone();
+            silent.ims_frame.intensity.iter_mut().for_each(|i| *i = 0.0);
+            assert!(silent.normalize(method).ims_frame.intensity.iter().all(|&i| i == 0.0));
+        }
+    }
+
+    #[test]
+    fn test_downsampling_preserves_intensity() {
+        let frame = frame();
+        let total = frame.ims_frame.intensity.iter().sum::<f64>();
+
+        for resolution in [0, 1, 2] {
+            let binned = frame.to_resolution(resolution);
+            assert_eq!(binned.ims_frame.intensity.iter().sum::<f64>(), total);
+            assert!(binned.validate().is_ok());
+            assert!(binned.ims_frame.mobility.iter().all(|m| (1.0..=1.5).contains(m)));
+        }
+
+        for factor in [0, 1, 3, 100] {
+            let merged = frame.downsample_scans(factor);
+            assert_eq!(merged.ims_frame.intensity.iter().sum::<f64>(), total);
+            assert!(merged.validate().is_ok());
+            // every merged scan has a single mobility, the mean of the scans it holds
+            for (scan, mobility) in merged.scan.iter().zip(&merged.ims_frame.mobility) {
+                let first = merged.scan.iter().position(|s| s == scan).unwrap();
+                assert_eq!(*mobility, merged.ims_frame.mobility[first]);
+            }
+        }
+        assert_eq!(frame.downsample_scans(100).scan.iter().collect::<std::collections::BTreeSet<_>>().len(), 1);
+    }
+
+    #[test]
+    fn test_snr_filter_removes_uniform_noise() {
+        let mut rng = rand::thread_rng();
+        // uniform noise of 1 to 100 in 20 scans with 3 signal peaks each, scan 30 holds too few peaks for its own estimate
+        let mut peaks: Vec<(i32, f64, f64, bool)> = Vec::new();
+        for scan in 10..30 {
+            peaks.extend((0..50).map(|_| (scan, rng.gen_range(100.0..1700.0), rng.gen_range(1.0..100.0), false)));
+            peaks.extend((0..3).map(|_| (scan, rng.gen_range(100.0..1700.0), rng.gen_range(5000.0..10000.0), true)));
+        }
+        peaks.extend([(30, 500.0, 50.0, false), (30, 600.0, 5000.0, true)]);
+        peaks.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        let frame = TimsFrame::new(
+            1,
+            MsType::Precursor,
+            100.0,
+            peaks.iter().map(|p| p.0).collect(),
+            peaks.iter().map(|p| 1.6 - p.0 as f64 * 0.01).collect(),
+            peaks.iter().map(|p| (p.1 * 100.0) as i32).collect(),
+            peaks.iter().map(|p| p.1).collect(),
+            peaks.iter().map(|p| p.2).collect(),
+        );
+        let signal: Vec<f64> = peaks.iter().filter(|p| p.3).map(|p| p.2).collect();
+
+        for method in [NoiseEstimator::MedianAbsoluteDeviation, NoiseEstimator::LowestQuartileMean] {
+            let noise = frame.estimate_noise(method);
+            assert_eq!(noise.len(), 21);
+            assert!(noise.iter().all(|n| (5.0..40.0).contains(n)), "{method:?}: {noise:?}");
+
+            let filtered = frame.filter_snr(30.0, method);
+            assert_eq!(filtered.ims_frame.intensity, signal, "{method:?}");
+            assert!(filtered.validate().is_ok());
+        }
     }
 }
@@ -1,5 +1,7 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::f64;
+use std::fmt::Debug;
+use std::sync::Arc;
 use std::f64::consts::E;
 use itertools::izip;
 use crate::data::spectrum::MzSpectrum;
@@ -29,7 +31,7 @@ use crate::timstof::frame::TimsFrame;
 /// |&x| (x * 100.0).round() / 100.0).collect::<Vec<f64>>();
 /// assert_eq!(transmission, vec![0.0, 0.5, 1.0]);
 /// ```
-pub fn smooth_step(x: &Vec<f64>, up_start: f64, up_end: f64, k: f64) -> Vec<f64> {
+pub fn smooth_step(x: &[f64], up_start: f64, up_end: f64, k: f64) -> Vec<f64> {
     let m = (up_start + up_end) / 2.0;
     x.iter().map(|&xi| 1.0 / (1.0 + E.powf(-k * (xi - m)))).collect()
 }
@@ -59,7 +61,7 @@ pub fn smooth_step(x: &Vec<f64>, up_start: f64, up_end: f64, k: f64) -> Vec<f64>
 /// |&x| (x * 100.0).round() / 100.0).collect::<Vec<f64>>();
 /// assert_eq!(transmission, vec![0.0, 1.0, 0.0]);
 /// ```
-pub fn smooth_step_up_down(x: &Vec<f64>, up_start: f64, up_end: f64, down_start: f64, down_end: f64, k: f64) -> Vec<f64> {
+pub fn smooth_step_up_down(x: &[f64], up_start: f64, up_end: f64, down_start: f64, down_end: f64, k: f64) -> Vec<f64> {
     let step_up = smooth_step(x, up_start, up_end, k);
     let step_down = smooth_step(x, down_start, down_end, k);
     step_up.iter().zip(step_down.iter()).map(|(&u, &d)| u - d).collect()
@@ -129,9 +131,85 @@ pub fn apply_transmission(midpoint: f64, window_length: f64, k: f64, mz: Vec<f64
     ion_transition_function_midpoint(midpoint, window_length, k)(mz)
 }
 
+/// Shape of the quadrupole isolation window
+pub trait TransmissionProfile: Debug + Send + Sync {
+    /// Transmission efficiency in [0, 1] for each mz value given an isolation window
+    ///
+    /// Arguments:
+    ///
+    /// * `isolation_mz` - center of the isolation window
+    /// * `isolation_width` - width of the isolation window
+    /// * `mz` - mz values
+    ///
+    /// Returns:
+    ///
+    /// * `Vec<f64>` - transmission efficiency for each mz value
+    ///
+    fn efficiency(&self, isolation_mz: f64, isolation_width: f64, mz: &[f64]) -> Vec<f64>;
+}
+
+/// Hard isolation window, everything within half the isolation width of the center is fully transmitted
+///
+/// # Examples
+///
+/// ```
+/// use mscore::timstof::quadrupole::{Rectangular, TransmissionProfile};
+///
+/// let efficiency = Rectangular.efficiency(500.0, 2.0, &[498.9, 499.0, 500.5, 501.1]);
+/// assert_eq!(efficiency, vec![0.0, 1.0, 1.0, 0.0]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rectangular;
+
+impl TransmissionProfile for Rectangular {
+    fn efficiency(&self, isolation_mz: f64, isolation_width: f64, mz: &[f64]) -> Vec<f64> {
+        let half_width = isolation_width / 2.0;
+        mz.iter().map(|&m| if (m - isolation_mz).abs() <= half_width { 1.0 } else { 0.0 }).collect()
+    }
+}
+
+/// Soft isolation window, efficiency drops with a sigmoid of steepness `k` at both window edges
+///
+/// # Examples
+///
+/// ```
+/// use mscore::timstof::quadrupole::{Sigmoid, TransmissionProfile};
+///
+/// let efficiency = Sigmoid { k: 15.0 }.efficiency(500.0, 2.0, &[497.0, 500.0, 503.0]).iter().map(
+/// |&x| (x * 100.0).round() / 100.0).collect::<Vec<f64>>();
+/// assert_eq!(efficiency, vec![0.0, 1.0, 0.0]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sigmoid {
+    pub k: f64,
+}
+
+impl TransmissionProfile for Sigmoid {
+    fn efficiency(&self, isolation_mz: f64, isolation_width: f64, mz: &[f64]) -> Vec<f64> {
+        apply_transmission(isolation_mz, isolation_width, self.k, mz.to_vec())
+    }
+}
+
 pub trait IonTransmission {
+    #[allow(clippy::ptr_arg)]
     fn apply_transmission(&self, frame_id: i32, scan_id: i32, mz: &Vec<f64>) -> Vec<f64>;
 
+    /// Transmission efficiency in [0, 1] for each mz value, can be used to scale peak intensities
+    ///
+    /// Arguments:
+    ///
+    /// * `frame_id` - frame id
+    /// * `scan_id` - scan id
+    /// * `mz` - mz values
+    ///
+    /// Returns:
+    ///
+    /// * `Vec<f64>` - transmission efficiency for each mz value
+    ///
+    fn transmission_efficiency(&self, frame_id: i32, scan_id: i32, mz: &[f64]) -> Vec<f64> {
+        self.apply_transmission(frame_id, scan_id, &mz.to_vec())
+    }
+
     /// Transmit a spectrum given a frame id and scan id
     ///
     /// Arguments:
@@ -296,6 +374,44 @@ pub trait IonTransmission {
         transmission_probability.iter().any(|&p| p > probability_cutoff)
     }
 
+    /// Calculate the fraction of an isotopic envelope that is transmitted, each peak is checked separately
+    ///
+    /// Arguments:
+    ///
+    /// * `frame_id` - frame id
+    /// * `scan_id` - scan id
+    /// * `mz` - mz values of the isotope peaks
+    /// * `intensity` - intensities of the isotope peaks
+    /// * `min_proba` - minimum probability for transmission
+    ///
+    /// Returns:
+    ///
+    /// * `f64` - sum of transmitted intensity (weighted by transmission probability) divided by total intensity
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::timstof::quadrupole::{IonTransmission, TimsTransmissionDIA};
+    ///
+    /// let transmission = TimsTransmissionDIA::new(vec![2], vec![1], vec![1], vec![0], vec![10], vec![500.5], vec![1.0], None);
+    /// // the monoisotopic peak is outside of the window, the first isotope is inside
+    /// let fraction = transmission.transmitted_fraction(2, 5, &[499.5, 500.5, 501.5], &[0.5, 0.3, 0.2], None);
+    /// assert!((fraction - 0.3).abs() < 1e-3);
+    /// ```
+    fn transmitted_fraction(&self, frame_id: i32, scan_id: i32, mz: &[f64], intensity: &[f64], min_proba: Option<f64>) -> f64 {
+        let total_intensity: f64 = intensity.iter().sum();
+        if total_intensity <= 0.0 {
+            return 0.0;
+        }
+        let probability_cutoff = min_proba.unwrap_or(0.5);
+        let transmission_probability = self.apply_transmission(frame_id, scan_id, &mz.to_vec());
+        let transmitted_intensity: f64 = intensity.iter().zip(transmission_probability.iter())
+            .filter(|&(_, &p)| p > probability_cutoff)
+            .map(|(&i, &p)| i * p)
+            .sum();
+        transmitted_intensity / total_intensity
+    }
+
     /// Transmit a frame given a diaPASEF transmission layout
     fn transmit_tims_frame(&self, frame: &TimsFrame, min_probability: Option<f64>) -> TimsFrame {
         let spectra = frame.to_tims_spectra();
@@ -303,13 +419,13 @@ pub trait IonTransmission {
 
         for mut spectrum in spectra {
             let filtered_spectrum = self.transmit_spectrum(frame.frame_id, spectrum.scan, spectrum.spectrum.mz_spectrum, min_probability);
-            if filtered_spectrum.mz.len() > 0 {
+            if !filtered_spectrum.mz.is_empty() {
                 spectrum.spectrum.mz_spectrum = filtered_spectrum;
                 filtered_spectra.push(spectrum);
             }
         }
 
-        if  filtered_spectra.len() > 0 {
+        if  !filtered_spectra.is_empty() {
             TimsFrame::from_tims_spectra(filtered_spectra)
         } else {
             TimsFrame::new(
@@ -342,13 +458,13 @@ pub trait IonTransmission {
 
         for mut spectrum in spectra {
             let filtered_spectrum = self.transmit_annotated_spectrum(frame.frame_id, spectrum.scan as i32, spectrum.spectrum.clone(), min_probability);
-            if filtered_spectrum.mz.len() > 0 {
+            if !filtered_spectrum.mz.is_empty() {
                 spectrum.spectrum = filtered_spectrum;
                 filtered_spectra.push(spectrum);
             }
         }
 
-        if  filtered_spectra.len() > 0 {
+        if  !filtered_spectra.is_empty() {
             TimsFrameAnnotated::from_tims_spectra_annotated(filtered_spectra)
         } else {
             TimsFrameAnnotated::new(
@@ -368,7 +484,7 @@ pub trait IonTransmission {
     fn isotopes_transmitted(&self, frame_id: i32, scan_id: i32, mz_mono: f64, isotopic_envelope: &Vec<f64>, min_probability: Option<f64>) -> (f64, Vec<(f64, f64)>) {
 
         let probability_cutoff = min_probability.unwrap_or(0.5);
-        let transmission_probability = self.apply_transmission(frame_id, scan_id, &isotopic_envelope);
+        let transmission_probability = self.apply_transmission(frame_id, scan_id, isotopic_envelope);
         let mut result: Vec<(f64, f64)> = Vec::new();
 
         for (mz, p) in isotopic_envelope.iter().zip(transmission_probability.iter()) {
@@ -381,14 +497,36 @@ pub trait IonTransmission {
     }
 }
 
+/// Isolation window of a DIA window group, one row of DiaFrameMsMsWindows
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowDescriptor {
+    pub window_group: i32,
+    /// first scan of the window, inclusive
+    pub scan_start: i32,
+    /// last scan of the window, inclusive
+    pub scan_end: i32,
+    /// center of the isolation window
+    pub isolation_mz: f64,
+    pub isolation_width: f64,
+}
+
+impl WindowDescriptor {
+    /// Whether an ion of the given m/z at the given scan lies within the window
+    pub fn contains(&self, mz: f64, scan: i32) -> bool {
+        (self.scan_start..=self.scan_end).contains(&scan) && (mz - self.isolation_mz).abs() <= self.isolation_width / 2.0
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct TimsTransmissionDIA {
     frame_to_window_group: HashMap<i32, i32>,
     window_group_settings: HashMap<(i32, i32), (f64, f64)>,
-    k: f64,
+    windows: Vec<WindowDescriptor>,
+    profile: Arc<dyn TransmissionProfile>,
 }
 
 impl TimsTransmissionDIA {
+    #[allow(clippy::too_many_arguments, reason = "one column per window of the DIA tables plus the transmission steepness")]
     pub fn new(
         frame: Vec<i32>,
         frame_window_group: Vec<i32>,
@@ -402,6 +540,7 @@ impl TimsTransmissionDIA {
         // hashmap from frame to window group
         let frame_to_window_group = frame.iter().zip(frame_window_group.iter()).map(|(&f, &wg)| (f, wg)).collect::<HashMap<i32, i32>>();
         let mut window_group_settings: HashMap<(i32, i32), (f64, f64)> = HashMap::new();
+        let mut windows = Vec::with_capacity(window_group.len());
 
         for (index, &wg) in window_group.iter().enumerate() {
             let scan_start = scan_start[index];
@@ -415,15 +554,26 @@ impl TimsTransmissionDIA {
                 let key = (wg, scan);
                 window_group_settings.insert(key, value);
             }
+
+            windows.push(WindowDescriptor { window_group: wg, scan_start, scan_end, isolation_mz, isolation_width });
         }
 
+        windows.sort_by_key(|window| (window.window_group, window.scan_start, window.scan_end));
+
         Self {
             frame_to_window_group,
             window_group_settings,
-            k: k.unwrap_or(15.0),
+            windows,
+            profile: Arc::new(Sigmoid { k: k.unwrap_or(15.0) }),
         }
     }
 
+    /// Replace the isolation window shape, the default is a `Sigmoid` profile
+    pub fn with_profile(mut self, profile: Arc<dyn TransmissionProfile>) -> Self {
+        self.profile = profile;
+        self
+    }
+
     pub fn frame_to_window_group(&self, frame_id: i32) -> i32 {
         let window_group = self.frame_to_window_group.get(&frame_id);
         match window_group {
@@ -432,6 +582,35 @@ impl TimsTransmissionDIA {
         }
     }
 
+    /// Fragment frames acquired with a window group
+    ///
+    /// Arguments:
+    ///
+    /// * `window_group` - window group
+    ///
+    /// Returns:
+    ///
+    /// * `Vec<i32>` - ascending frame ids of the window group, empty for unknown window groups
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::timstof::quadrupole::TimsTransmissionDIA;
+    ///
+    /// let transmission = TimsTransmissionDIA::new(vec![4, 2, 3], vec![1, 1, 2], vec![1, 2], vec![0, 0], vec![899, 899],
+    ///     vec![500.0, 700.0], vec![25.0, 25.0], None);
+    /// assert_eq!(transmission.frames_for_window_group(1), vec![2, 4]);
+    /// assert!(transmission.frames_for_window_group(3).is_empty());
+    /// ```
+    pub fn frames_for_window_group(&self, window_group: i32) -> Vec<i32> {
+        let mut frame_ids: Vec<i32> = self.frame_to_window_group.iter()
+            .filter(|(_, &wg)| wg == window_group)
+            .map(|(&frame_id, _)| frame_id)
+            .collect();
+        frame_ids.sort_unstable();
+        frame_ids
+    }
+
     pub fn get_setting(&self, window_group: i32, scan_id: i32) -> Option<&(f64, f64)> {
         let setting = self.window_group_settings.get(&(window_group, scan_id));
         match setting {
@@ -440,6 +619,59 @@ impl TimsTransmissionDIA {
         }
     }
 
+    /// Isolation window a window group uses at a scan
+    ///
+    /// Arguments:
+    ///
+    /// * `window_group` - window group
+    /// * `scan` - scan
+    ///
+    /// Returns:
+    ///
+    /// * `Option<(f64, f64)>` - center and width of the isolation window, None if the window group does not
+    ///   isolate at this scan. If windows of a group overlap, the window listed last is used, as for transmission
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use mscore::timstof::quadrupole::TimsTransmissionDIA;
+    ///
+    /// let transmission = TimsTransmissionDIA::new(vec![2, 3], vec![1, 2], vec![1, 1, 2], vec![0, 400, 0], vec![399, 899, 899],
+    ///     vec![500.0, 700.0, 510.0], vec![25.0, 25.0, 50.0], None);
+    /// assert_eq!(transmission.isolation_window_for(1, 450), Some((700.0, 25.0)));
+    /// assert_eq!(transmission.isolation_window_for(1, 950), None);
+    /// assert_eq!(transmission.window_groups_for_mz(505.0, 100), vec![1, 2]);
+    /// assert_eq!(transmission.window_groups_for_mz(505.0, 500), vec![2]);
+    /// assert_eq!(transmission.all_windows().len(), 3);
+    /// ```
+    pub fn isolation_window_for(&self, window_group: i32, scan: i32) -> Option<(f64, f64)> {
+        self.get_setting(window_group, scan).copied()
+    }
+
+    /// Window groups with an isolation window containing an ion
+    ///
+    /// Arguments:
+    ///
+    /// * `mz` - m/z of the ion
+    /// * `scan` - scan of the ion
+    ///
+    /// Returns:
+    ///
+    /// * `Vec<i32>` - ascending window groups sampling the ion, all of them if windows overlap, empty outside any window
+    pub fn window_groups_for_mz(&self, mz: f64, scan: i32) -> Vec<i32> {
+        let mut window_groups: Vec<i32> = self.windows.iter()
+            .filter(|window| window.contains(mz, scan))
+            .map(|window| window.window_group)
+            .collect();
+        window_groups.dedup();
+        window_groups
+    }
+
+    /// All isolation windows, ordered by window group and first scan
+    pub fn all_windows(&self) -> Vec<WindowDescriptor> {
+        self.windows.clone()
+    }
+
     // check if a frame is a precursor frame
     pub fn is_precursor(&self, frame_id: i32) -> bool {
         // if frame id is in the hashmap, it is not a precursor frame
@@ -458,7 +690,7 @@ impl IonTransmission for TimsTransmissionDIA {
 
         match setting {
             Some((isolation_mz, isolation_width)) => {
-                apply_transmission(*isolation_mz, *isolation_width, self.k, mz.clone())
+                self.profile.efficiency(*isolation_mz, *isolation_width, mz)
             },
             None => match is_precursor {
                 true => vec![1.0; mz.len()],
@@ -504,7 +736,7 @@ impl TimsTransmissionDDA {
     pub fn new(pasef_meta: Vec<PASEFMeta>, k: Option<f64>) -> Self {
         let mut pasef_map: BTreeMap<i32, Vec<PASEFMeta>> = BTreeMap::new();
         for meta in pasef_meta {
-            let entry = pasef_map.entry(meta.frame).or_insert(Vec::new());
+            let entry = pasef_map.entry(meta.frame).or_default();
             entry.push(meta);
         }
         Self {
@@ -1,19 +1,81 @@
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use crate::parallel;
 
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use itertools::multizip;
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 
-use crate::data::spectrum::{MsType, Vectorized, ToResolution};
+use crate::algorithm::normalization::NormalizationMethod;
+use crate::data::serialization::BinarySerializable;
+use crate::data::spectrum::{approx_eq_values, ApproxEq, MsType, MzAxis, Vectorized, ToResolution};
 use crate::timstof::spectrum::{TimsSpectrum};
-use crate::timstof::frame::{ImsFrame, TimsFrame, TimsFrameVectorized};
+use crate::timstof::frame::{value_range, DenseWindows, FrameSummary, ImsFrame, TimsFrame, TimsFrameVectorized};
+
+/// Row-major flat intensity matrix with one row per m/z bin, the m/z and the inverse mobility bin centers,
+/// see `TimsSlice::to_dense_matrix`
+pub type DenseMatrix = (Vec<f64>, Vec<f64>, Vec<f64>);
+
+/// Frame ids, scans and intensities of all peaks that share an m/z index
+pub type IndexColumns = (Vec<u32>, Vec<u32>, Vec<f32>);
+
+/// Frame ids, retention times, scans, mobilities, tofs, m/z and intensities of the peaks of a `TimsPlane`
+type PlaneColumns = (Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<f64>);
+
+/// Quick numbers about a slice for quality control, as produced by `TimsSlice::summary`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceSummary {
+    pub num_frames: usize,
+    pub num_peaks: usize,
+    pub total_intensity: f64,
+    pub max_intensity: f64,
+    /// ranges are `None` if no frame (or, except for retention time, no peak) is part of the slice
+    pub rt_range: Option<(f64, f64)>,
+    pub mz_range: Option<(f64, f64)>,
+    pub scan_range: Option<(i32, i32)>,
+    pub mobility_range: Option<(f64, f64)>,
+    /// number of frames per MsType, keyed by its name
+    pub ms_type_counts: BTreeMap<String, usize>,
+    pub frames: Vec<FrameSummary>,
+}
+
+impl SliceSummary {
+    /// Aggregate frame summaries, e.g. of frames that were summarized chunk by chunk
+    pub fn from_frame_summaries(frames: Vec<FrameSummary>) -> Self {
+        let mut ms_type_counts = BTreeMap::new();
+        for frame in &frames {
+            *ms_type_counts.entry(frame.ms_type.to_string()).or_insert(0) += 1;
+        }
 
-#[derive(Clone)]
+        SliceSummary {
+            num_frames: frames.len(),
+            num_peaks: frames.iter().map(|f| f.num_peaks).sum(),
+            total_intensity: frames.iter().map(|f| f.total_intensity).sum(),
+            max_intensity: frames.iter().map(|f| f.max_intensity).fold(0.0, f64::max),
+            rt_range: value_range(frames.iter().map(|f| f.retention_time)),
+            mz_range: value_range(frames.iter().filter_map(|f| f.mz_range).flat_map(|(min, max)| [min, max])),
+            scan_range: value_range(frames.iter().filter_map(|f| f.scan_range).flat_map(|(min, max)| [min, max])),
+            mobility_range: value_range(frames.iter().filter_map(|f| f.mobility_range).flat_map(|(min, max)| [min, max])),
+            ms_type_counts,
+            frames,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct TimsSlice {
     pub frames: Vec<TimsFrame>,
 }
 
+impl ApproxEq for TimsSlice {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frames.len() == other.frames.len() && self.frames.iter().zip(&other.frames).all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+}
+
+impl BinarySerializable for TimsSlice {}
+
 impl TimsSlice {
 
     /// Create a new TimsSlice from a vector of TimsFrames
@@ -37,6 +99,37 @@ impl TimsSlice {
         TimsSlice { frames }
     }
 
+    /// Summarize every frame in parallel and aggregate the frame summaries
+    ///
+    /// # Arguments
+    ///
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    /// use mscore::timstof::slice::TimsSlice;
+    ///
+    /// let slice = TimsSlice::new(vec![
+    ///     TimsFrame::new(1, MsType::Precursor, 10.0, vec![5, 3], vec![1.1, 1.2], vec![10, 20], vec![300.0, 200.0], vec![4.0, 1.0]),
+    ///     TimsFrame::new(2, MsType::FragmentDia, 10.5, vec![7], vec![1.0], vec![30], vec![600.0], vec![8.0]),
+    /// ]);
+    /// let summary = slice.summary(2);
+    /// assert_eq!((summary.num_frames, summary.num_peaks, summary.total_intensity), (2, 3, 13.0));
+    /// assert_eq!((summary.rt_range, summary.mz_range, summary.scan_range), (Some((10.0, 10.5)), Some((200.0, 600.0)), Some((3, 7))));
+    /// assert_eq!(summary.ms_type_counts["Precursor"], 1);
+    /// assert_eq!(summary.frames[1].max_intensity, 8.0);
+    /// ```
+    pub fn summary(&self, num_threads: usize) -> SliceSummary {
+        let pool = parallel::pool(num_threads);
+        let frames: Vec<FrameSummary> = pool.install(|| {
+            self.frames.par_iter().map(|frame| frame.summary()).collect()
+        });
+        SliceSummary::from_frame_summaries(frames)
+    }
+
     /// Filter the TimsSlice by m/z, scan, and intensity
     ///
     /// # Arguments
@@ -61,9 +154,10 @@ impl TimsSlice {
     /// let slice = TimsSlice::new(vec![]);
     /// let filtered_slice = slice.filter_ranged(400.0, 2000.0, 0, 1000, 0.0, 100000.0, 0.0, 1.6, 4);
     /// ```
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for every dimension")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> TimsSlice {
 
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap(); // Set to the desired number of threads
+        let pool = parallel::pool(num_threads); // Set to the desired number of threads
 
         // Use the thread pool
         let filtered_frames = pool.install(|| {
@@ -76,6 +170,7 @@ impl TimsSlice {
         TimsSlice { frames: filtered_frames }
     }
 
+    #[allow(clippy::too_many_arguments, reason = "bounds for every dimension, separately for precursor and fragment frames")]
     pub fn filter_ranged_ms_type_specific(&self,
                                           mz_min_ms1: f64,
                                           mz_max_ms1: f64,
@@ -95,13 +190,13 @@ impl TimsSlice {
                                           intensity_max_ms2: f64,
                                           num_threads: usize) -> TimsSlice {
 
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap(); // Set to the desired number of threads
+        let pool = parallel::pool(num_threads); // Set to the desired number of threads
 
         // Use the thread pool
         let filtered_frames = pool.install(|| {
             let result: Vec<_> =  self.frames.par_iter()
                 .map(|f| match f.ms_type {
-                    MsType::Precursor => f.filter_ranged(mz_min_ms1, mz_max_ms1, scan_min_ms1, scan_max_ms1, inv_mob_min_ms1, inv_mob_max_ms1, intensity_min_ms1, intensity_max_ms1),
+                    MsType::Precursor | MsType::Maldi => f.filter_ranged(mz_min_ms1, mz_max_ms1, scan_min_ms1, scan_max_ms1, inv_mob_min_ms1, inv_mob_max_ms1, intensity_min_ms1, intensity_max_ms1),
                     _ => f.filter_ranged(mz_min_ms2, mz_max_ms2, scan_min_ms2, scan_max_ms2, inv_mob_min_ms2, inv_mob_max_ms2, intensity_min_ms2, intensity_max_ms2),
                 })
                 .collect();
@@ -123,14 +218,25 @@ impl TimsSlice {
     pub fn get_slice_by_type(&self, t: MsType) -> TimsSlice {
         let filtered_frames = self.frames.iter()
             .filter(|f| f.ms_type == t)
-            .map(|f| f.clone())
+            .cloned()
             .collect();
         TimsSlice { frames: filtered_frames }
     }
 
+    /// Bin the m/z values of every frame to `resolution` decimal places, frames are binned in parallel,
+    /// see `TimsFrame::to_resolution`, intensities of merged peaks are summed
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - The number of decimal places of the m/z bins
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * `TimsSlice` - A TimsSlice with the binned frames, every frame keeps its total intensity
     pub fn to_resolution(&self, resolution: i32, num_threads: usize) -> TimsSlice {
 
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap(); // Set to the desired number of threads
+        let pool = parallel::pool(num_threads); // Set to the desired number of threads
 
         // Use the thread pool
         let result_frames = pool.install(|| {
@@ -143,9 +249,32 @@ impl TimsSlice {
         TimsSlice { frames: result_frames }
     }
 
+    /// Normalize the intensities of every frame separately, see `TimsFrame::normalize`
+    pub fn normalize_frames(&self, method: NormalizationMethod, num_threads: usize) -> TimsSlice {
+        let pool = parallel::pool(num_threads);
+        let frames = pool.install(|| self.frames.par_iter().map(|f| f.normalize(method)).collect());
+        TimsSlice { frames }
+    }
+
+    /// Bin every frame on the same m/z axis in parallel, see `TimsFrame::vectorize_on_axis`
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - The m/z axis shared by all frames
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * Scans, positions on the axis and intensities of every frame, in the order of the frames
+    ///
+    pub fn vectorize_on_axis(&self, axis: &MzAxis, num_threads: usize) -> Vec<(Vec<i32>, Vec<u32>, Vec<f64>)> {
+        let pool = parallel::pool(num_threads);
+        pool.install(|| self.frames.par_iter().map(|f| f.vectorize_on_axis(axis)).collect())
+    }
+
     pub fn vectorized(&self, resolution: i32, num_threads: usize) -> TimsSliceVectorized {
 
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
+        let pool = parallel::pool(num_threads);
 
         // Use the thread pool
         let result_frames = pool.install(|| {
@@ -210,40 +339,97 @@ impl TimsSlice {
         TimsSlice { frames }
     }
 
-    pub fn flatten(&self) -> TimsSliceFlat {
-        let mut frame_ids = Vec::new();
-        let mut scans = Vec::new();
-        let mut tofs = Vec::new();
-        let mut retention_times = Vec::new();
-        let mut mobilities = Vec::new();
-        let mut mzs = Vec::new();
-        let mut intensities = Vec::new();
+    /// All peaks of the slice as one column per value, frames in slice order
+    ///
+    /// # Returns
+    ///
+    /// * `SliceColumns` - one row per peak
+    pub fn flatten(&self) -> SliceColumns {
+        self.flatten_columns(None, None)
+    }
 
-        for frame in &self.frames {
-            let length = frame.scan.len();
-            frame_ids.extend(vec![frame.frame_id; length].into_iter());
-            scans.extend(frame.scan.clone());
-            tofs.extend(frame.tof.clone());
-            retention_times.extend(vec![frame.ims_frame.retention_time; length].into_iter());
-            mobilities.extend(&frame.ims_frame.mobility);
-            mzs.extend(&frame.ims_frame.mz);
-            intensities.extend(&frame.ims_frame.intensity);
-        }
+    /// All peaks of the slice as one column per value, filtering while flattening
+    ///
+    /// The number of peaks kept per frame is counted first, each frame is then written in parallel into its own
+    /// range of the pre-allocated columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms_type` - MsMsType code of the frames to keep, all frames if None. MIDIA and MALDI frames share
+    ///   their code with diaPASEF and precursor frames
+    /// * `intensity_min` - minimum intensity of the peaks to keep, all peaks if None
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * `SliceColumns` - one row per kept peak
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    /// use mscore::timstof::slice::TimsSlice;
+    ///
+    /// let slice = TimsSlice::new(vec![
+    ///     TimsFrame::new(1, MsType::Precursor, 10.0, vec![5, 3], vec![1.1, 1.2], vec![10, 20], vec![300.0, 200.0], vec![4.0, 1.0]),
+    ///     TimsFrame::new(2, MsType::FragmentDia, 10.5, vec![7], vec![1.0], vec![30], vec![600.0], vec![8.0]),
+    ///     TimsFrame::new(3, MsType::Precursor, 11.0, vec![2], vec![1.3], vec![40], vec![700.0], vec![9.0]),
+    /// ]);
+    /// let columns = slice.flatten_filtered(Some(0), Some(2.0), 2);
+    /// assert_eq!(columns.frame_id, vec![1, 3]);
+    /// assert_eq!(columns.rt, vec![10.0, 11.0]);
+    /// assert_eq!(columns.mz, vec![300.0, 700.0]);
+    /// assert_eq!(slice.flatten().len(), 4);
+    /// ```
+    pub fn flatten_filtered(&self, ms_type: Option<i32>, intensity_min: Option<f64>, num_threads: usize) -> SliceColumns {
+        let pool = parallel::pool(num_threads);
+        pool.install(|| self.flatten_columns(ms_type, intensity_min))
+    }
 
-        TimsSliceFlat {
-            frame_ids,
-            scans,
-            tofs,
-            retention_times,
-            mobilities,
-            mzs,
-            intensities,
-        }
+    /// Flatten on the current rayon thread pool
+    fn flatten_columns(&self, ms_type: Option<i32>, intensity_min: Option<f64>) -> SliceColumns {
+        let keep_frame = |frame: &TimsFrame| ms_type.is_none_or(|code| frame.ms_type.ms_type_numeric() == code);
+        let keep_peak = |intensity: f64| intensity_min.is_none_or(|min| intensity >= min);
+
+        let counts: Vec<usize> = self.frames.par_iter().map(|frame| match (keep_frame(frame), intensity_min) {
+            (false, _) => 0,
+            (true, None) => frame.ims_frame.intensity.len(),
+            (true, Some(_)) => frame.ims_frame.intensity.iter().filter(|&&intensity| keep_peak(intensity)).count(),
+        }).collect();
+
+        let mut columns = SliceColumns::zeroed(counts.iter().sum());
+        let chunks = multizip((
+            split_by_counts(&mut columns.frame_id, &counts),
+            split_by_counts(&mut columns.rt, &counts),
+            split_by_counts(&mut columns.scan, &counts),
+            split_by_counts(&mut columns.mobility, &counts),
+            split_by_counts(&mut columns.tof, &counts),
+            split_by_counts(&mut columns.mz, &counts),
+            split_by_counts(&mut columns.intensity, &counts),
+        )).collect::<Vec<_>>();
+
+        self.frames.par_iter().zip(chunks).for_each(|(frame, (frame_id, rt, scan, mobility, tof, mz, intensity))| {
+            frame_id.fill(frame.frame_id as u32);
+            rt.fill(frame.ims_frame.retention_time);
+
+            let peaks = multizip((&frame.scan, &frame.ims_frame.mobility, &frame.tof, &frame.ims_frame.mz, &frame.ims_frame.intensity))
+                .filter(|(_, _, _, _, &peak_intensity)| keep_peak(peak_intensity));
+            for (i, (&peak_scan, &peak_mobility, &peak_tof, &peak_mz, &peak_intensity)) in peaks.take(mz.len()).enumerate() {
+                scan[i] = peak_scan as u32;
+                mobility[i] = peak_mobility;
+                tof[i] = peak_tof as u32;
+                mz[i] = peak_mz;
+                intensity[i] = peak_intensity;
+            }
+        });
+
+        columns
     }
 
     pub fn to_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, num_threads: usize) -> Vec<TimsSpectrum> {
         // Create a thread pool
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap(); // Set to the desired number of threads
+        let pool = parallel::pool(num_threads); // Set to the desired number of threads
 
         // Use the thread pool
         let windows = pool.install(|| {
@@ -256,8 +442,8 @@ impl TimsSlice {
         windows
     }
 
-    pub fn to_dense_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32, num_threads: usize) -> Vec<(Vec<f64>, Vec<i32>, Vec<i32>, usize, usize)> {
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
+    pub fn to_dense_windows(&self, window_length: f64, overlapping: bool, min_peaks: usize, min_intensity: f64, resolution: i32, num_threads: usize) -> Vec<DenseWindows> {
+        let pool = parallel::pool(num_threads);
 
         let result = pool.install(|| {
             let t = self.frames.par_iter().map(|f| f.to_dense_windows(window_length, overlapping, min_peaks, min_intensity, resolution)).collect::<Vec<_>>();
@@ -269,7 +455,7 @@ impl TimsSlice {
 
     pub fn to_tims_planes(&self, tof_max_value: i32, num_chunks: i32, num_threads: usize) -> Vec<TimsPlane> {
 
-        let flat_slice = self.flatten();
+        let flat_slice = self.flatten_filtered(None, None, num_threads);
 
         let chunk_size = (tof_max_value as f64 / num_chunks as f64) as i32;
 
@@ -278,12 +464,13 @@ impl TimsSlice {
             .map(|i| (chunk_size * i, i + 2))
             .collect();
 
-        let mut tof_map: BTreeMap<(i32, i32), (Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        let mut tof_map: BTreeMap<(i32, i32), PlaneColumns> = BTreeMap::new();
 
         // Iterate over the data points using multizip
         for (id, rt, scan, mobility, tof, mz, intensity)
 
-        in multizip((flat_slice.frame_ids, flat_slice.retention_times, flat_slice.scans, flat_slice.mobilities, flat_slice.tofs, flat_slice.mzs, flat_slice.intensities)) {
+        in multizip((flat_slice.frame_id, flat_slice.rt, flat_slice.scan, flat_slice.mobility, flat_slice.tof, flat_slice.mz, flat_slice.intensity)) {
+            let (id, scan, tof) = (id as i32, scan as i32, tof as i32);
 
             for &(switch_point, width) in &range_and_width {
                 if tof < switch_point {
@@ -304,7 +491,7 @@ impl TimsSlice {
         }
 
         // Create a thread pool with the desired number of threads
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
+        let pool = parallel::pool(num_threads);
 
         let tims_planes: Vec<TimsPlane> = pool.install(|| {
             tof_map.par_iter()
@@ -314,19 +501,173 @@ impl TimsSlice {
 
         tims_planes
     }
+
+    /// Extract the retention time x mobility intensity surface of m/z windows, e.g. extracted ion mobilograms
+    /// of target precursors over the slice
+    ///
+    /// # Arguments
+    ///
+    /// * `mz_targets` - center of every m/z window
+    /// * `ppm` - half width of the windows in ppm of their center
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<TimsPlane>` - one plane per target in the order of `mz_targets`, intensities are summed per frame and
+    ///   scan, means and standard deviations of tof and m/z are NaN for targets without peaks
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    /// use mscore::timstof::slice::TimsSlice;
+    ///
+    /// let frame = |id: i32| TimsFrame::new(id, MsType::Precursor, id as f64, vec![1, 1, 2], vec![1.1, 1.1, 1.0], vec![10, 20, 11], vec![500.001, 600.0, 499.999], vec![1.0, 2.0, 4.0]);
+    /// let slice = TimsSlice::new(vec![frame(1), frame(2)]);
+    /// let planes = slice.to_planes(&[500.0, 700.0], 10.0, 2);
+    /// assert_eq!(planes[0].frame_id, vec![1, 1, 2, 2]);
+    /// assert_eq!(planes[0].scan, vec![1, 2, 1, 2]);
+    /// assert_eq!(planes[0].intensity, vec![1.0, 4.0, 1.0, 4.0]);
+    /// assert!(planes[1].intensity.is_empty() && planes[1].mz_mean.is_nan());
+    /// ```
+    pub fn to_planes(&self, mz_targets: &[f64], ppm: f64, num_threads: usize) -> Vec<TimsPlane> {
+        let pool = parallel::pool(num_threads);
+
+        pool.install(|| {
+            // peak indices of every frame sorted by m/z, computed once and shared by all targets
+            let mz_orders: Vec<Vec<usize>> = self.frames.par_iter().map(|frame| {
+                let mz = &frame.ims_frame.mz;
+                let mut order: Vec<usize> = (0..mz.len()).collect();
+                order.sort_by(|&a, &b| mz[a].total_cmp(&mz[b]));
+                order
+            }).collect();
+
+            mz_targets.par_iter().map(|&target| {
+                let tolerance = target * ppm * 1e-6;
+                let mut values = (vec![], vec![], vec![], vec![], vec![], vec![], vec![]);
+
+                for (frame, order) in self.frames.iter().zip(&mz_orders) {
+                    let mz = &frame.ims_frame.mz;
+                    let start = order.partition_point(|&i| mz[i] < target - tolerance);
+                    for &i in order[start..].iter().take_while(|&&i| mz[i] <= target + tolerance) {
+                        values.0.push(frame.frame_id);
+                        values.1.push(frame.ims_frame.retention_time);
+                        values.2.push(frame.scan[i]);
+                        values.3.push(frame.ims_frame.mobility[i]);
+                        values.4.push(frame.tof[i]);
+                        values.5.push(mz[i]);
+                        values.6.push(frame.ims_frame.intensity[i]);
+                    }
+                }
+                collapse_entry(&(0, 0), &values)
+            }).collect()
+        })
+    }
+
+    /// Accumulate the intensities of all frames into a dense m/z x inverse mobility grid
+    ///
+    /// # Arguments
+    ///
+    /// * `mz_min` - The lower bound of the m/z axis
+    /// * `mz_max` - The upper bound of the m/z axis
+    /// * `mz_bin_width` - The width of a single m/z bin
+    /// * `im_min` - The lower bound of the inverse mobility axis
+    /// * `im_max` - The upper bound of the inverse mobility axis
+    /// * `im_bin_count` - The number of inverse mobility bins
+    /// * `num_threads` - The number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * `Result<DenseMatrix, String>` - The row-major flat matrix of shape (mz_axis.len(), im_axis.len()),
+    ///   the m/z bin centers and the inverse mobility bin centers, or an error if a range or bin size is not positive
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::data::spectrum::MsType;
+    /// use mscore::timstof::frame::TimsFrame;
+    /// use mscore::timstof::slice::TimsSlice;
+    ///
+    /// let frame = TimsFrame::new(1, MsType::Precursor, 1.0, vec![1, 2], vec![0.9, 1.3], vec![0, 0], vec![100.5, 101.5], vec![10.0, 20.0]);
+    /// let slice = TimsSlice::new(vec![frame.clone(), frame]);
+    /// let (matrix, mz_axis, im_axis) = slice.to_dense_matrix(100.0, 102.0, 1.0, 0.8, 1.4, 3, 2).unwrap();
+    /// assert_eq!(mz_axis, vec![100.5, 101.5]);
+    /// assert_eq!(im_axis.len(), 3);
+    /// assert_eq!(matrix, vec![20.0, 0.0, 0.0, 0.0, 0.0, 40.0]);
+    /// assert!(slice.to_dense_matrix(102.0, 100.0, 1.0, 0.8, 1.4, 3, 2).is_err());
+    /// ```
+    #[allow(clippy::too_many_arguments, reason = "range and bin size of both axes")]
+    pub fn to_dense_matrix(&self, mz_min: f64, mz_max: f64, mz_bin_width: f64, im_min: f64, im_max: f64, im_bin_count: usize, num_threads: usize) -> Result<DenseMatrix, String> {
+
+        if !(mz_max > mz_min && mz_bin_width > 0.0) {
+            return Err(format!("m/z range and bin width must be positive, got [{}, {}) with width {}", mz_min, mz_max, mz_bin_width));
+        }
+        if !(im_max > im_min && im_bin_count > 0) {
+            return Err(format!("inverse mobility range and bin count must be positive, got [{}, {}) with {} bins", im_min, im_max, im_bin_count));
+        }
+
+        let mz_bin_count = ((mz_max - mz_min) / mz_bin_width).ceil() as usize;
+        let im_bin_width = (im_max - im_min) / im_bin_count as f64;
+
+        let mz_axis: Vec<f64> = (0..mz_bin_count).map(|i| mz_min + (i as f64 + 0.5) * mz_bin_width).collect();
+        let im_axis: Vec<f64> = (0..im_bin_count).map(|i| im_min + (i as f64 + 0.5) * im_bin_width).collect();
+
+        let grid_size = mz_bin_count * im_bin_count;
+
+        let pool = parallel::pool(num_threads);
+
+        // every worker accumulates into its own scratch grid, the grids are summed up at the end
+        let matrix = pool.install(|| {
+            self.frames.par_iter()
+                .fold(|| vec![0.0; grid_size], |mut grid, frame| {
+                    for (mz, mobility, intensity) in multizip((&frame.ims_frame.mz, &frame.ims_frame.mobility, &frame.ims_frame.intensity)) {
+                        if *mz < mz_min || *mz >= mz_max || *mobility < im_min || *mobility >= im_max {
+                            continue;
+                        }
+                        // clamp to the last bin to guard against rounding at the upper edge
+                        let mz_index = (((*mz - mz_min) / mz_bin_width) as usize).min(mz_bin_count - 1);
+                        let im_index = (((*mobility - im_min) / im_bin_width) as usize).min(im_bin_count - 1);
+                        grid[mz_index * im_bin_count + im_index] += *intensity;
+                    }
+                    grid
+                })
+                .reduce(|| vec![0.0; grid_size], |mut left, right| {
+                    for (l, r) in left.iter_mut().zip(right.iter()) {
+                        *l += *r;
+                    }
+                    left
+                })
+        });
+
+        Ok((matrix, mz_axis, im_axis))
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
 pub struct TimsSliceVectorized {
     pub frames: Vec<TimsFrameVectorized>,
-    pub frame_map: BTreeMap<u32, (Vec<u32>, Vec<u32>, Vec<f32>)>
+    pub frame_map: BTreeMap<u32, IndexColumns>
+}
+
+impl BinarySerializable for TimsSliceVectorized {}
+
+impl ApproxEq for TimsSliceVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frames.len() == other.frames.len() && self.frames.iter().zip(&other.frames).all(|(a, b)| a.approx_eq(b, tolerance))
+            && self.frame_map.len() == other.frame_map.len()
+            && self.frame_map.iter().zip(&other.frame_map).all(|((id_a, a), (id_b, b))| {
+                id_a == id_b && a.0 == b.0 && a.1 == b.1 && approx_eq_values(&a.2, &b.2, tolerance)
+            })
+    }
 }
 
 impl TimsSliceVectorized {
 
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for every dimension")]
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, scan_min: i32, scan_max: i32, inv_mob_min: f64, inv_mob_max: f64, intensity_min: f64, intensity_max: f64, num_threads: usize) -> TimsSliceVectorized {
 
-        let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap(); // Set to the desired number of threads
+        let pool = parallel::pool(num_threads); // Set to the desired number of threads
 
         // Use the thread pool
         let filtered_frames = pool.install(|| {
@@ -356,10 +697,10 @@ impl TimsSliceVectorized {
 
         for frame in &self.frames {
             let length = frame.ims_frame.indices.len();
-            frame_ids.extend(vec![frame.frame_id; length].into_iter());
+            frame_ids.extend(vec![frame.frame_id; length]);
             scans.extend(frame.scan.clone());
             tofs.extend(frame.tof.clone());
-            retention_times.extend(vec![frame.ims_frame.retention_time; length].into_iter());
+            retention_times.extend(vec![frame.ims_frame.retention_time; length]);
             mobilities.extend(&frame.ims_frame.mobility);
             indices.extend(&frame.ims_frame.indices);
             intensities.extend(&frame.ims_frame.values);
@@ -377,17 +718,17 @@ impl TimsSliceVectorized {
     }
 }
 
-fn get_index_map(frames: &Vec<TimsFrameVectorized>) -> BTreeMap<u32, (Vec<u32>, Vec<u32>, Vec<f32>)> {
+fn get_index_map(frames: &Vec<TimsFrameVectorized>) -> BTreeMap<u32, IndexColumns> {
     let mut index_map: BTreeMap<u32, Vec<(u32, u32, f32)>> = BTreeMap::new();
 
     for frame in frames {
         for (i, index) in frame.ims_frame.indices.iter().enumerate() {
-            let entry = index_map.entry(*index as u32).or_insert_with(|| vec![]);
+            let entry = index_map.entry(*index as u32).or_default();
             entry.push((frame.frame_id as u32, frame.scan[i] as u32, frame.ims_frame.values[i] as f32));
         }
     }
 
-    let mut result_map: BTreeMap<u32, (Vec<u32>, Vec<u32>, Vec<f32>)> = BTreeMap::new();
+    let mut result_map: BTreeMap<u32, IndexColumns> = BTreeMap::new();
 
     for (index, values) in index_map {
         for (frame_id, scan, intensity) in values {
@@ -401,7 +742,7 @@ fn get_index_map(frames: &Vec<TimsFrameVectorized>) -> BTreeMap<u32, (Vec<u32>,
     result_map
 }
 
-#[derive(Clone)]
+#[derive(Clone, Encode, Decode)]
 pub struct TimsPlane {
     pub tof_mean: f64,
     pub tof_std: f64,
@@ -415,15 +756,76 @@ pub struct TimsPlane {
     pub intensity: Vec<f64>,
 }
 
-fn collapse_entry(_key: &(i32, i32), values: &(Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<i32>, Vec<f64>, Vec<f64>)) -> TimsPlane {
+impl BinarySerializable for TimsPlane {}
+
+impl ApproxEq for TimsPlane {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.scan == other.scan
+            && approx_eq_values(&[self.tof_mean, self.tof_std, self.mz_mean, self.mz_std], &[other.tof_mean, other.tof_std, other.mz_mean, other.mz_std], tolerance)
+            && approx_eq_values(&self.retention_time, &other.retention_time, tolerance)
+            && approx_eq_values(&self.mobility, &other.mobility, tolerance)
+            && approx_eq_values(&self.intensity, &other.intensity, tolerance)
+    }
+}
+
+impl TimsPlane {
+    /// Bin the plane into a dense retention time x inverse mobility grid, e.g. for heatmap export
+    ///
+    /// # Arguments
+    ///
+    /// * `rt_bins` - number of retention time bins spanning the retention times of the plane
+    /// * `im_bins` - number of inverse mobility bins spanning the mobilities of the plane
+    ///
+    /// # Returns
+    ///
+    /// * `(Vec<f64>, Vec<f64>, Vec<f64>)` - The row-major flat matrix of shape (rt_bins, im_bins), the retention
+    ///   time bin centers and the inverse mobility bin centers, all zero for an empty plane
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mscore::timstof::slice::TimsPlane;
+    ///
+    /// let plane = TimsPlane { tof_mean: 0.0, tof_std: 0.0, mz_mean: 500.0, mz_std: 0.0, frame_id: vec![1, 1, 2], retention_time: vec![1.0, 1.0, 3.0], scan: vec![1, 2, 1], mobility: vec![1.2, 1.0, 1.2], intensity: vec![5.0, 1.0, 2.0] };
+    /// let (matrix, rt_axis, im_axis) = plane.to_dense(2, 2);
+    /// assert_eq!(rt_axis, vec![1.5, 2.5]);
+    /// assert!((im_axis[0] - 1.05).abs() < 1e-12 && (im_axis[1] - 1.15).abs() < 1e-12);
+    /// assert_eq!(matrix, vec![1.0, 5.0, 0.0, 2.0]);
+    /// ```
+    pub fn to_dense(&self, rt_bins: usize, im_bins: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        assert!(rt_bins > 0 && im_bins > 0, "bin counts must be positive");
+
+        let axis = |values: &[f64], bins: usize| {
+            let (min, max) = value_range(values.iter().copied()).unwrap_or((0.0, 0.0));
+            let width = (max - min) / bins as f64;
+            let centers: Vec<f64> = (0..bins).map(|i| min + (i as f64 + 0.5) * width).collect();
+            // values at the upper edge are clamped into the last bin
+            let indices: Vec<usize> = values.iter()
+                .map(|&v| if width > 0.0 { (((v - min) / width) as usize).min(bins - 1) } else { 0 })
+                .collect();
+            (centers, indices)
+        };
+        let (rt_axis, rt_indices) = axis(&self.retention_time, rt_bins);
+        let (im_axis, im_indices) = axis(&self.mobility, im_bins);
+
+        let mut matrix = vec![0.0; rt_bins * im_bins];
+        for (rt_index, (im_index, intensity)) in rt_indices.iter().zip(im_indices.iter().zip(&self.intensity)) {
+            matrix[rt_index * im_bins + im_index] += intensity;
+        }
+
+        (matrix, rt_axis, im_axis)
+    }
+}
+
+fn collapse_entry(_key: &(i32, i32), values: &PlaneColumns) -> TimsPlane {
 
     let (frame_ids, retention_times, scans, mobilities, tofs, mzs, intensities) = values;
 
     // 1. Calculate mean and std for tof and mz
     let tof_mean: f64 = tofs.iter().map(|&x| x as f64).sum::<f64>() / tofs.len() as f64;
     let tof_std: f64 = (tofs.iter().map(|&x| (x as f64 - tof_mean).powi(2)).sum::<f64>() / tofs.len() as f64).sqrt();
-    let mz_mean: f64 = mzs.iter().map(|&x| x as f64).sum::<f64>() / mzs.len() as f64;
-    let mz_std: f64 = (mzs.iter().map(|&x| (x as f64 - mz_mean).powi(2)).sum::<f64>() / mzs.len() as f64).sqrt();
+    let mz_mean: f64 = mzs.iter().copied().sum::<f64>() / mzs.len() as f64;
+    let mz_std: f64 = (mzs.iter().map(|&x| (x - mz_mean).powi(2)).sum::<f64>() / mzs.len() as f64).sqrt();
 
     // 2. Aggregate data by frame_id and scan using a BTreeMap for sorted order
     let mut grouped_data: BTreeMap<(i32, i32), (f64, f64, f64)> = BTreeMap::new();
@@ -464,15 +866,48 @@ fn collapse_entry(_key: &(i32, i32), values: &(Vec<i32>, Vec<f64>, Vec<i32>, Vec
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct TimsSliceFlat {
-    pub frame_ids: Vec<i32>,
-    pub scans: Vec<i32>,
-    pub tofs: Vec<i32>,
-    pub retention_times: Vec<f64>,
-    pub mobilities: Vec<f64>,
-    pub mzs: Vec<f64>,
-    pub intensities: Vec<f64>,
+/// Peaks of a slice as struct of arrays, one row per peak, as produced by `TimsSlice::flatten`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SliceColumns {
+    pub frame_id: Vec<u32>,
+    pub rt: Vec<f64>,
+    pub scan: Vec<u32>,
+    pub mobility: Vec<f64>,
+    pub tof: Vec<u32>,
+    pub mz: Vec<f64>,
+    pub intensity: Vec<f64>,
+}
+
+impl SliceColumns {
+    fn zeroed(len: usize) -> Self {
+        SliceColumns {
+            frame_id: vec![0; len],
+            rt: vec![0.0; len],
+            scan: vec![0; len],
+            mobility: vec![0.0; len],
+            tof: vec![0; len],
+            mz: vec![0.0; len],
+            intensity: vec![0.0; len],
+        }
+    }
+
+    /// number of peaks
+    pub fn len(&self) -> usize {
+        self.mz.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mz.is_empty()
+    }
+}
+
+/// Split a column into consecutive chunks of the given lengths
+fn split_by_counts<'a, T>(mut column: &'a mut [T], counts: &[usize]) -> Vec<&'a mut [T]> {
+    counts.iter().map(|&count| {
+        let (chunk, rest) = std::mem::take(&mut column).split_at_mut(count);
+        column = rest;
+        chunk
+    }).collect()
 }
 
 #[derive(Clone, Debug)]
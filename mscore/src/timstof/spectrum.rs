@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use crate::data::spectrum::{IndexedMzSpectrum, IndexedMzSpectrumVectorized, MsType, MzSpectrum};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use crate::data::serialization::BinarySerializable;
+use crate::data::spectrum::{approx_eq_value, ApproxEq, IndexedMzSpectrum, IndexedMzSpectrumVectorized, MsType, MzSpectrum};
 
 #[derive(Clone)]
 pub struct TimsSpectrumVectorized {
@@ -13,7 +16,16 @@ pub struct TimsSpectrumVectorized {
     pub vector: IndexedMzSpectrumVectorized,
 }
 
-#[derive(Clone, Debug)]
+impl ApproxEq for TimsSpectrumVectorized {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.scan == other.scan && self.ms_type == other.ms_type
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_value(self.mobility, other.mobility, tolerance)
+            && self.vector.approx_eq(&other.vector, tolerance)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Encode, Decode)]
 pub struct TimsSpectrum {
     pub frame_id: i32,
     pub scan: i32,
@@ -23,6 +35,15 @@ pub struct TimsSpectrum {
     pub spectrum: IndexedMzSpectrum,
 }
 
+impl ApproxEq for TimsSpectrum {
+    fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        self.frame_id == other.frame_id && self.scan == other.scan && self.ms_type == other.ms_type
+            && approx_eq_value(self.retention_time, other.retention_time, tolerance)
+            && approx_eq_value(self.mobility, other.mobility, tolerance)
+            && self.spectrum.approx_eq(&other.spectrum, tolerance)
+    }
+}
+
 impl TimsSpectrum {
     /// Creates a new `TimsSpectrum` instance.
     ///
@@ -43,7 +64,7 @@ impl TimsSpectrum {
     /// let spectrum = TimsSpectrum::new(1, 1, 100.0, 0.1, MsType::FragmentDda, IndexedMzSpectrum::new(vec![1000, 2000], vec![100.5, 200.5], vec![50.0, 60.0]));
     /// ```
     pub fn new(frame_id: i32, scan_id: i32, retention_time: f64, mobility: f64, ms_type: MsType, spectrum: IndexedMzSpectrum) -> Self {
-        TimsSpectrum { frame_id, scan: scan_id, retention_time, mobility: mobility, ms_type, spectrum }
+        TimsSpectrum { frame_id, scan: scan_id, retention_time, mobility, ms_type, spectrum }
     }
 
     pub fn filter_ranged(&self, mz_min: f64, mz_max: f64, intensity_min: f64, intensity_max: f64) -> Self {
@@ -173,6 +194,8 @@ impl std::ops::Add for TimsSpectrum {
     }
 }
 
+impl BinarySerializable for TimsSpectrum {}
+
 impl Display for TimsSpectrum {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "TimsSpectrum(frame_id: {}, scan_id: {}, retention_time: {}, mobility: {}, spectrum: {})", self.frame_id, self.scan, self.retention_time, self.mobility, self.spectrum)
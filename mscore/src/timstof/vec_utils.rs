@@ -1,6 +1,6 @@
 pub fn find_sparse_local_maxima_mask(
-    indices: &Vec<u32>,
-    values: &Vec<f64>,
+    indices: &[u32],
+    values: &[f64],
     window: u32,
 ) -> Vec<bool> {
     let mut local_maxima: Vec<bool> = vec![true; indices.len()];
@@ -25,7 +25,7 @@ pub fn find_sparse_local_maxima_mask(
     local_maxima
 }
 
-pub fn filter_with_mask<T: Copy>(vec: &Vec<T>, mask: &Vec<bool>) -> Vec<T> {
+pub fn filter_with_mask<T: Copy>(vec: &[T], mask: &[bool]) -> Vec<T> {
     (0..vec.len())
         .filter(|&x| mask[x])
         .map(|x| vec[x])
@@ -0,0 +1,77 @@
+//! Building a dense precursor frame with both ways of assembling frames
+//!
+//! `spectra` creates one `TimsSpectrum` per ion and scan and merges them with `TimsFrame::from_tims_spectra`,
+//! `binned` adds the peaks to a `FrameAssembler`. Both build the same frame from the same peptides.
+//!
+//! Run with `cargo bench --bench frame_assembly`, the number of ions in the frame can be set with
+//! `FRAME_BENCH_IONS`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mscore::data::spectrum::MzSpectrum;
+use rustdf::sim::assembler::FrameAssembly;
+use rustdf::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+
+const PEAKS_PER_ION: usize = 6;
+const SCANS_PER_ION: u32 = 30;
+
+fn num_ions() -> u32 {
+    std::env::var("FRAME_BENCH_IONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(5000)
+}
+
+fn builder(num_ions: u32) -> TimsTofSyntheticsPrecursorFrameBuilder {
+    let peptide_to_ions = (0..num_ions)
+        .map(|peptide_id| {
+            let mz = 400.0 + (peptide_id % 2000) as f64 * 0.377;
+            let spectrum = MzSpectrum::new(
+                (0..PEAKS_PER_ION).map(|peak| mz + peak as f64 * 0.5).collect(),
+                (0..PEAKS_PER_ION).map(|peak| 1.0 / (peak + 1) as f64).collect(),
+            );
+            let first_scan = 100 + peptide_id % 600;
+            let scans: Vec<u32> = (first_scan..first_scan + SCANS_PER_ION).collect();
+            let abundances = vec![1.0 / SCANS_PER_ION as f32; scans.len()];
+            (peptide_id, (vec![1.0], vec![scans], vec![abundances], vec![2], vec![spectrum]))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    TimsTofSyntheticsPrecursorFrameBuilder {
+        ions: BTreeMap::new(),
+        peptides: BTreeMap::new(),
+        scans: vec![],
+        frames: vec![],
+        precursor_frame_id_set: HashSet::from([1]),
+        frame_to_abundances: BTreeMap::from([(1, ((0..num_ions).collect(), vec![1.0; num_ions as usize]))]),
+        peptide_to_ions: OnceLock::from(peptide_to_ions),
+        frame_to_rt: BTreeMap::from([(1, 1.0)]),
+        scan_to_mobility: (1..1000).map(|scan| (scan, 1.6 - scan as f32 * 0.001)).collect(),
+        peptide_to_events: (0..num_ions).map(|peptide_id| (peptide_id, 1e4)).collect(),
+        noise_settings: Default::default(),
+        tof_calibration: Default::default(),
+        frame_assembly: Default::default(),
+    }
+}
+
+fn bench_frame_assembly(c: &mut Criterion) {
+    let num_ions = num_ions();
+    let mut builder = builder(num_ions);
+
+    let mut group = c.benchmark_group("frame_assembly");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(num_ions as u64 * SCANS_PER_ION as u64));
+
+    for (name, assembly) in [("spectra", FrameAssembly::Spectra), ("binned", FrameAssembly::default())] {
+        builder.frame_assembly = assembly;
+        group.bench_function(name, |b| {
+            b.iter(|| builder.build_precursor_frame(1, false, false, 0.0, false, None).ims_frame.mz.len())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_frame_assembly);
+criterion_main!(benches);
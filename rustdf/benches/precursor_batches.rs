@@ -0,0 +1,156 @@
+//! Building many precursor frames at once compared with building them in batches
+//!
+//! `all_at_once` builds every frame with `build_precursor_frames_in_pool` and holds them until the last one is
+//! built, `batched` consumes `precursor_frame_batches`, which is what the Python batch iterator does. The peak of
+//! live heap bytes of both is printed before the timings, peak memory of `batched` only depends on the batch size.
+//!
+//! Run with `cargo bench --bench precursor_batches`, the number of frames can be set with `BATCH_BENCH_FRAMES`
+//! and the batch size with `BATCH_BENCH_BATCH_SIZE`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mscore::data::spectrum::MzSpectrum;
+use mscore::parallel;
+use rustdf::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+use rustdf::sim::utility::FrameBuildOptions;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+const IONS_PER_FRAME: u32 = 500;
+const PEAKS_PER_ION: usize = 6;
+const SCANS_PER_ION: u32 = 30;
+const NUM_THREADS: usize = 4;
+
+/// keeps track of the live and the peak number of allocated bytes
+struct PeakAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+fn track(live: usize) {
+    PEAK_BYTES.fetch_max(live, Ordering::SeqCst);
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track(LIVE_BYTES.fetch_add(layout.size(), Ordering::SeqCst) + layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > layout.size() {
+            track(LIVE_BYTES.fetch_add(new_size - layout.size(), Ordering::SeqCst) + new_size - layout.size());
+        } else {
+            LIVE_BYTES.fetch_sub(layout.size() - new_size, Ordering::SeqCst);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+/// peak of live heap bytes while `f` runs, relative to the live bytes before
+fn peak_bytes<R>(f: impl FnOnce() -> R) -> usize {
+    let before = LIVE_BYTES.load(Ordering::SeqCst);
+    PEAK_BYTES.store(before, Ordering::SeqCst);
+    drop(f());
+    PEAK_BYTES.load(Ordering::SeqCst) - before
+}
+
+fn env_or(name: &str, default: u32) -> u32 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// every frame holds `IONS_PER_FRAME` ions, consecutive frames share most of them like an eluting peptide would
+fn builder(num_frames: u32) -> TimsTofSyntheticsPrecursorFrameBuilder {
+    let num_peptides = num_frames + IONS_PER_FRAME;
+    let peptide_to_ions = (0..num_peptides)
+        .map(|peptide_id| {
+            let mz = 400.0 + (peptide_id % 2000) as f64 * 0.377;
+            let spectrum = MzSpectrum::new(
+                (0..PEAKS_PER_ION).map(|peak| mz + peak as f64 * 0.5).collect(),
+                (0..PEAKS_PER_ION).map(|peak| 1.0 / (peak + 1) as f64).collect(),
+            );
+            let first_scan = 100 + peptide_id % 600;
+            let scans: Vec<u32> = (first_scan..first_scan + SCANS_PER_ION).collect();
+            let abundances = vec![1.0 / SCANS_PER_ION as f32; scans.len()];
+            (peptide_id, (vec![1.0], vec![scans], vec![abundances], vec![2], vec![spectrum]))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let frame_ids = 1..=num_frames;
+    TimsTofSyntheticsPrecursorFrameBuilder {
+        ions: BTreeMap::new(),
+        peptides: BTreeMap::new(),
+        scans: vec![],
+        frames: vec![],
+        precursor_frame_id_set: frame_ids.clone().collect::<HashSet<_>>(),
+        frame_to_abundances: frame_ids
+            .clone()
+            .map(|frame_id| {
+                let peptide_ids = (frame_id..frame_id + IONS_PER_FRAME).collect();
+                (frame_id, (peptide_ids, vec![1.0; IONS_PER_FRAME as usize]))
+            })
+            .collect(),
+        peptide_to_ions: OnceLock::from(peptide_to_ions),
+        frame_to_rt: frame_ids.map(|frame_id| (frame_id, frame_id as f32 * 0.1)).collect(),
+        scan_to_mobility: (1..1000).map(|scan| (scan, 1.6 - scan as f32 * 0.001)).collect(),
+        peptide_to_events: (0..num_peptides).map(|peptide_id| (peptide_id, 1e4)).collect(),
+        noise_settings: Default::default(),
+        tof_calibration: Default::default(),
+        frame_assembly: Default::default(),
+    }
+}
+
+fn bench_precursor_batches(c: &mut Criterion) {
+    let num_frames = env_or("BATCH_BENCH_FRAMES", 200);
+    let batch_size = env_or("BATCH_BENCH_BATCH_SIZE", 16) as usize;
+    let builder = builder(num_frames);
+    let frame_ids: Vec<u32> = (1..=num_frames).collect();
+    let options = FrameBuildOptions { seed: Some(1), ..Default::default() };
+    let thread_pool = parallel::pool(Some(NUM_THREADS));
+
+    let all_at_once = || {
+        builder
+            .build_precursor_frames_in_pool(&thread_pool, &frame_ids, &options)
+            .iter()
+            .map(|frame| frame.ims_frame.mz.len())
+            .sum::<usize>()
+    };
+    let batched = || {
+        builder
+            .precursor_frame_batches(frame_ids.clone(), batch_size, NUM_THREADS, &options)
+            .map(|batch| batch.iter().map(|frame| frame.ims_frame.mz.len()).sum::<usize>())
+            .sum::<usize>()
+    };
+
+    assert_eq!(all_at_once(), batched());
+    let mib = |bytes: usize| bytes as f64 / 1024.0 / 1024.0;
+    println!(
+        "{} frames, peak heap all_at_once: {:.1} MiB, batched ({} frames per batch): {:.1} MiB",
+        num_frames,
+        mib(peak_bytes(all_at_once)),
+        batch_size,
+        mib(peak_bytes(batched))
+    );
+
+    let mut group = c.benchmark_group("precursor_batches");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(num_frames as u64));
+    group.bench_function("all_at_once", |b| b.iter(all_at_once));
+    group.bench_function("batched", |b| b.iter(batched));
+    group.finish();
+}
+
+criterion_group!(benches, bench_precursor_batches);
+criterion_main!(benches);
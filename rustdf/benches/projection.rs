@@ -0,0 +1,75 @@
+//! m/z sweeps over MS1 frames with and without column projection
+//!
+//! `full` decodes every column with `get_slice`, `mz_intensity` and `mz` only decode the requested columns with
+//! `get_slice_projected`, which skips the scan to mobility conversion and, for `mz`, the intensity arrays. All
+//! three run on the lazy and on the in-memory loader and read the same frames.
+//!
+//! Run with `TDF_BENCH_DATA=/path/to/run.d cargo bench --bench projection`, the bundled single frame dataset is
+//! used if the variable is not set. The number of frames read per iteration can be set with `TDF_BENCH_FRAMES`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rustdf::data::dataset::TimsDataset;
+use rustdf::data::handle::TimsData;
+use rustdf::data::projection::FrameColumns;
+
+const NUM_THREADS: usize = 8;
+
+fn data_path() -> String {
+    std::env::var("TDF_BENCH_DATA").unwrap_or_else(|_| {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d").to_string()
+    })
+}
+
+fn num_frames() -> usize {
+    std::env::var("TDF_BENCH_FRAMES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(200)
+}
+
+fn bench_mz_sweep(c: &mut Criterion) {
+    let data_path = data_path();
+    let datasets = [
+        ("lazy", TimsDataset::new("", &data_path, false, false)),
+        ("in_memory", TimsDataset::new("", &data_path, true, false)),
+    ];
+
+    let frame_count = datasets[0].1.get_frame_count() as u32;
+    let frame_ids: Vec<u32> = (0..num_frames()).map(|i| (i as u32 % frame_count) + 1).collect();
+
+    let mut group = c.benchmark_group("mz_sweep");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(frame_ids.len() as u64));
+
+    for (name, dataset) in &datasets {
+        group.bench_function(format!("{}_full", name), |b| {
+            b.iter(|| {
+                dataset
+                    .get_slice(frame_ids.clone(), NUM_THREADS)
+                    .frames
+                    .iter()
+                    .map(|frame| frame.ims_frame.mz.len())
+                    .sum::<usize>()
+            })
+        });
+        for (columns_name, columns) in [
+            ("mz_intensity", FrameColumns::MZ | FrameColumns::INTENSITY),
+            ("mz", FrameColumns::MZ),
+        ] {
+            group.bench_function(format!("{}_{}", name, columns_name), |b| {
+                b.iter(|| {
+                    dataset
+                        .get_slice_projected(frame_ids.clone(), columns, NUM_THREADS)
+                        .iter()
+                        .map(|frame| frame.mz.as_ref().map_or(0, Vec::len))
+                        .sum::<usize>()
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_mz_sweep);
+criterion_main!(benches);
@@ -0,0 +1,160 @@
+//! Read throughput of the simulation database tables
+//!
+//! `single_thread` reads with a default connection and parses on one thread, as before the reads were
+//! chunked, `chunked` uses `TimsTofSyntheticsDataHandle::new_read_only` and the global rayon pool.
+//! `storage_format` compares reading the JSON database with reading it after `migrate_to_binary`,
+//! the file sizes of both are printed before.
+//!
+//! Run with `cargo bench --bench synthetics_read`, the number of ions can be set with `SYNTHETICS_BENCH_IONS`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rusqlite::{params, Connection};
+use rustdf::sim::handle::TimsTofSyntheticsDataHandle;
+use std::path::{Path, PathBuf};
+
+const PEAKS_PER_SPECTRUM: usize = 12;
+const SCANS_PER_ION: u32 = 30;
+
+fn json_list(values: impl Iterator<Item = String>) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(", "))
+}
+
+fn write_database(path: &Path, num_ions: u32) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(
+        "CREATE TABLE ions (
+            ion_id INTEGER, peptide_id INTEGER, sequence TEXT, charge INTEGER, mz REAL,
+            relative_abundance REAL, inv_mobility_gru_predictor REAL, inv_mobility_gru_predictor_std REAL,
+            simulated_spectrum TEXT, scan_occurrence TEXT, scan_abundance TEXT
+         );",
+    )?;
+
+    let transaction = connection.transaction()?;
+    {
+        let mut stmt = transaction.prepare(
+            "INSERT INTO ions VALUES (?1, ?2, 'PEPTIDEK', 2, ?3, 1.0, 1.1, 0.01, ?4, ?5, ?6)",
+        )?;
+        for ion_id in 0..num_ions {
+            let mz = 400.0 + (ion_id % 1000) as f64 * 0.731;
+            let spectrum = format!(
+                "{{\"mz\": {}, \"intensity\": {}}}",
+                json_list(
+                    (0..PEAKS_PER_SPECTRUM).map(|i| format!("{:.6}", mz + i as f64 * 0.5017))
+                ),
+                json_list((0..PEAKS_PER_SPECTRUM).map(|i| format!("{:.6}", 1.0 / (i + 1) as f64))),
+            );
+            let first_scan = ion_id % 800;
+            let scan_occurrence =
+                json_list((first_scan..first_scan + SCANS_PER_ION).map(|scan| scan.to_string()));
+            let scan_abundance = json_list(
+                (0..SCANS_PER_ION)
+                    .map(|i| format!("{:.6}", 1.0 / SCANS_PER_ION as f64 + i as f64 * 1e-4)),
+            );
+            stmt.execute(params![
+                ion_id,
+                ion_id / 3,
+                mz,
+                spectrum,
+                scan_occurrence,
+                scan_abundance
+            ])?;
+        }
+    }
+    transaction.commit()
+}
+
+fn num_ions() -> u32 {
+    std::env::var("SYNTHETICS_BENCH_IONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(100_000)
+}
+
+fn database_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "rustdf_synthetics_{}_{}.db",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn bench_read_ions(c: &mut Criterion) {
+    let num_ions = num_ions();
+    let path = database_path("read");
+    write_database(&path, num_ions).expect("writing benchmark database");
+
+    let mut group = c.benchmark_group("read_ions");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(num_ions as u64));
+
+    // connections are not Sync, so they are opened inside the pool, opening is included in both timings
+    group.bench_function("single_thread", |b| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        b.iter(|| {
+            pool.install(|| {
+                let handle = TimsTofSyntheticsDataHandle {
+                    connection: Connection::open(&path).unwrap(),
+                };
+                handle.read_ions().unwrap()
+            })
+        })
+    });
+
+    group.bench_function("chunked", |b| {
+        b.iter(|| {
+            TimsTofSyntheticsDataHandle::new_read_only(&path)
+                .unwrap()
+                .read_ions()
+                .unwrap()
+        })
+    });
+
+    group.finish();
+    let _ = std::fs::remove_file(&path);
+}
+
+fn bench_storage_format(c: &mut Criterion) {
+    let num_ions = num_ions();
+    let json_path = database_path("json");
+    let binary_path = database_path("binary");
+    write_database(&json_path, num_ions).expect("writing benchmark database");
+    TimsTofSyntheticsDataHandle::new_read_only(&json_path)
+        .unwrap()
+        .migrate_to_binary(&binary_path)
+        .expect("migrating benchmark database");
+
+    let size = |path: &Path| std::fs::metadata(path).unwrap().len() as f64 / 1024.0 / 1024.0;
+    println!(
+        "{} ions, json: {:.1} MiB, binary: {:.1} MiB",
+        num_ions,
+        size(&json_path),
+        size(&binary_path)
+    );
+
+    let mut group = c.benchmark_group("storage_format");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(num_ions as u64));
+
+    for (name, path) in [("json", &json_path), ("binary", &binary_path)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                TimsTofSyntheticsDataHandle::new_read_only(path)
+                    .unwrap()
+                    .read_ions()
+                    .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+    let _ = std::fs::remove_file(&json_path);
+    let _ = std::fs::remove_file(&binary_path);
+}
+
+criterion_group!(benches, bench_read_ions, bench_storage_format);
+criterion_main!(benches);
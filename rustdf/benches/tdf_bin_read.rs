@@ -0,0 +1,74 @@
+//! Random access frame reads of the lazy loader
+//!
+//! `buffered` opens and seeks `analysis.tdf_bin` for every frame, as before the file was mapped, `mapped`
+//! decompresses directly from the memory-mapped file. Both read the same frames in the same shuffled order,
+//! once as raw frames on a single thread, which only reads and decompresses, and once through `get_slice`,
+//! which includes the index conversion.
+//!
+//! Run with `TDF_BENCH_DATA=/path/to/run.d cargo bench --bench tdf_bin_read`, the bundled single frame
+//! dataset is used if the variable is not set, a large run shows the difference much better. The number of
+//! frames read per iteration can be set with `TDF_BENCH_FRAMES`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rustdf::data::dataset::TimsDataset;
+use rustdf::data::handle::{TimsData, TimsDataLoader};
+use rustdf::data::tdf_bin::TdfBinReader;
+
+fn data_path() -> String {
+    std::env::var("TDF_BENCH_DATA").unwrap_or_else(|_| {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d").to_string()
+    })
+}
+
+fn num_frames() -> usize {
+    std::env::var("TDF_BENCH_FRAMES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(200)
+}
+
+fn lazy_dataset(data_path: &str, mapped: bool) -> TimsDataset {
+    let mut dataset = TimsDataset::new("", data_path, false, false);
+    if let TimsDataLoader::Lazy(loader) = &mut dataset.loader {
+        loader.tdf_bin = match mapped {
+            true => TdfBinReader::open(data_path),
+            false => TdfBinReader::open_buffered(data_path),
+        };
+    }
+    dataset
+}
+
+fn bench_random_access(c: &mut Criterion) {
+    let data_path = data_path();
+    let datasets = [("buffered", lazy_dataset(&data_path, false)), ("mapped", lazy_dataset(&data_path, true))];
+
+    let frame_count = datasets[0].1.get_frame_count() as u32;
+    let mut rng = StdRng::seed_from_u64(42);
+    let frame_ids: Vec<u32> = (0..num_frames())
+        .map(|i| (i as u32 % frame_count) + 1)
+        .collect::<Vec<_>>()
+        .choose_multiple(&mut rng, num_frames())
+        .copied()
+        .collect();
+
+    let mut group = c.benchmark_group("tdf_bin_random_access");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(frame_ids.len() as u64));
+
+    for (name, dataset) in &datasets {
+        group.bench_function(format!("{}_raw_single_thread", name), |b| {
+            b.iter(|| frame_ids.iter().map(|&frame_id| dataset.get_raw_frame(frame_id).tof.len()).sum::<usize>())
+        });
+        group.bench_function(format!("{}_get_slice", name), |b| {
+            b.iter(|| dataset.get_slice(frame_ids.clone(), 8).frames.len())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_random_access);
+criterion_main!(benches);
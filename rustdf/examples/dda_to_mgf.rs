@@ -0,0 +1,654 @@
+//! Simulate a small DDA-PASEF run, read it back as a DDA dataset and export its fragment spectra as MGF
+//!
+//! The example places a handful of peptide ions at known retention times and mobilities, selects precursors with
+//! `PasefScheduler`, writes the precursor and fragment frames to a .d folder with `TdfWriter` and adds the DDA
+//! precursor tables. The run is opened with `TimsDatasetDDA`, precursors are linked to their PASEF fragments,
+//! their isolation purity is computed and one spectrum per precursor is written to an MGF file. The MGF file is
+//! read back and every spectrum is checked against the simulated ion. The example exits with a non-zero status if
+//! a check fails.
+//!
+//! ```text
+//! cargo run --release --example dda_to_mgf -- --num-cycles 40 --num-threads 4
+//! ```
+
+use clap::Parser;
+use mscore::data::peptide::{FragmentType, PeptideIon, PeptideSequence};
+use mscore::data::spectrum::{MsType, MzSpectrum};
+use mscore::timstof::frame::{ImsFrame, TimsFrame};
+use rusqlite::{params, Connection};
+use rustdf::data::dda::{PASEFDDAFragment, TimsDatasetDDA};
+use rustdf::data::handle::TimsData;
+use rustdf::data::meta::read_meta_data_sql;
+use rustdf::sim::scheduler::{CandidateFrame, PasefScheduler, PrecursorCandidate, ScheduledFrame};
+use rustdf::sim::writer::TdfWriter;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const SEQUENCES: [&str; 10] = [
+    "PEPTIDEK",
+    "LVNELTEFAK",
+    "AEFVEVTK",
+    "YLYEIAR",
+    "HLVDEPQNLIK",
+    "VPQVSTPTLVEVSR",
+    "LGEYGFQNALIVR",
+    "DAFLGSFLYEYSR",
+    "QTALVELLK",
+    "FKDLGEEHFK",
+];
+
+const NUM_SCANS: u32 = 900;
+const MOBILITY_UPPER: f64 = 1.6;
+const MOBILITY_LOWER: f64 = 0.6;
+const FRAME_TIME: f64 = 0.1;
+const ION_EVENTS: f64 = 1e5;
+const ISOLATION_WIDTH: f64 = 3.0;
+const COLLISION_ENERGY: f64 = 30.0;
+
+/// Simulate a DDA-PASEF run, export its fragment spectra as MGF and validate the export
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Path of the .d folder to write, a temporary folder is used if not set
+    #[arg(short, long)]
+    pub data_path: Option<String>,
+
+    /// Path of the MGF file to write, defaults to the .d folder with an .mgf extension
+    #[arg(short, long)]
+    pub mgf_path: Option<String>,
+
+    /// Number of acquisition cycles, each of one precursor frame followed by the fragment frames
+    #[arg(long, default_value_t = 40)]
+    pub num_cycles: u32,
+
+    /// Number of PASEF fragment frames following every precursor frame
+    #[arg(long, default_value_t = 2)]
+    pub ms2_frames_per_cycle: usize,
+
+    /// Maximum number of precursors selected per precursor frame
+    #[arg(long, default_value_t = 4)]
+    pub top_n: usize,
+
+    /// Number of threads to use
+    #[arg(short, long, default_value_t = 4)]
+    pub num_threads: usize,
+
+    /// Mass tolerance of precursor and fragment matches in ppm
+    #[arg(long, default_value_t = 20.0)]
+    pub ppm: f64,
+
+    /// Minimum isolation purity of every precursor
+    #[arg(long, default_value_t = 0.9)]
+    pub min_purity: f64,
+
+    /// Minimum fraction of the simulated fragment ions found in the MGF spectrum of a precursor
+    #[arg(long, default_value_t = 0.9)]
+    pub min_fragment_recall: f64,
+}
+
+/// A simulated ion with the position it was placed at and its fragment ions
+#[derive(Debug, Clone)]
+pub struct TruthIon {
+    pub sequence: String,
+    pub charge: i32,
+    pub mono_mz: f64,
+    pub scan_center: u32,
+    pub frame_center: u32,
+    pub spectrum: MzSpectrum,
+    pub fragments: MzSpectrum,
+}
+
+/// A spectrum of an MGF file
+#[derive(Debug, Clone)]
+pub struct MgfSpectrum {
+    pub title: String,
+    pub precursor_id: i64,
+    pub precursor_mz: f64,
+    pub precursor_intensity: i64,
+    pub charge: Option<i32>,
+    pub retention_time: f64,
+    pub mobility: f64,
+    pub mz: Vec<f64>,
+    pub intensity: Vec<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub num_ions: usize,
+    pub num_precursors: usize,
+    pub num_spectra: usize,
+    pub min_purity: f64,
+    pub fragment_recall: f64,
+}
+
+fn scan_to_mobility(scan: u32) -> f64 {
+    MOBILITY_UPPER - (MOBILITY_UPPER - MOBILITY_LOWER) * scan as f64 / NUM_SCANS as f64
+}
+
+fn gaussian_weight(x: u32, center: u32, sigma: f64) -> f64 {
+    (-0.5 * ((x as f64 - center as f64) / sigma).powi(2)).exp()
+}
+
+fn is_precursor_frame(frame_id: u32, ms2_frames_per_cycle: usize) -> bool {
+    (frame_id - 1) % (ms2_frames_per_cycle as u32 + 1) == 0
+}
+
+/// Place the peptides evenly along the gradient, every ion elutes over three cycles to either side of its apex
+fn simulate_truth(num_frames: u32, cycle_length: u32) -> Vec<TruthIon> {
+    let step = num_frames / (SEQUENCES.len() as u32 + 1);
+
+    SEQUENCES
+        .iter()
+        .enumerate()
+        .map(|(index, sequence)| {
+            let charge = if index % 3 == 2 { 3 } else { 2 };
+            let ion = PeptideIon::new(sequence.to_string(), charge, 1.0, None);
+            let mono_mz = ion.mz();
+            // apex on a precursor frame, scans far enough apart that isolation windows never overlap in m/z and scan
+            let frame_center = (step * (index as u32 + 1)) / cycle_length * cycle_length + 1;
+
+            TruthIon {
+                sequence: sequence.to_string(),
+                charge,
+                mono_mz,
+                scan_center: 100 + index as u32 * 70,
+                frame_center,
+                spectrum: ion.calculate_isotopic_spectrum(1e-3, 1e-8, 200, 1e-4),
+                fragments: PeptideSequence::new(sequence.to_string(), None)
+                    .calculate_mono_isotopic_product_ion_spectrum(1, FragmentType::B),
+            }
+        })
+        .collect()
+}
+
+fn elution_weight(ion: &TruthIon, frame_id: u32, cycle_length: u32) -> f64 {
+    match (frame_id as i64 - ion.frame_center as i64).abs() <= 3 * cycle_length as i64 {
+        true => gaussian_weight(frame_id, ion.frame_center, cycle_length as f64 * 1.5),
+        false => 0.0,
+    }
+}
+
+/// Candidates of every frame as seen by the instrument, only precursor frames hold candidates
+fn candidate_frames(truth: &[TruthIon], num_frames: u32, args: &Args) -> Vec<CandidateFrame> {
+    let cycle_length = args.ms2_frames_per_cycle as u32 + 1;
+
+    (1..=num_frames)
+        .map(|frame_id| CandidateFrame {
+            frame_id,
+            retention_time: frame_id as f64 * FRAME_TIME,
+            candidates: match is_precursor_frame(frame_id, args.ms2_frames_per_cycle) {
+                true => truth
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, ion)| {
+                        let weight = elution_weight(ion, frame_id, cycle_length);
+                        (weight > 0.0).then(|| PrecursorCandidate {
+                            peptide_id: index as u32,
+                            mz: ion.mono_mz,
+                            mobility: scan_to_mobility(ion.scan_center),
+                            scan_start: ion.scan_center - 10,
+                            scan_end: ion.scan_center + 10,
+                            intensity: ION_EVENTS * weight,
+                        })
+                    })
+                    .collect(),
+                false => Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// Add the peaks of a spectrum to every scan of a range, scaled by a gaussian over the scans
+fn add_peaks(peaks: &mut Vec<(i32, f64, f64)>, spectrum: &MzSpectrum, scan_center: u32, scan_width: u32, scale: f64) {
+    for scan in scan_center - scan_width..=scan_center + scan_width {
+        let weight = scale * gaussian_weight(scan, scan_center, 3.0);
+        for (mz, intensity) in spectrum.mz.iter().zip(spectrum.intensity.iter()) {
+            peaks.push((scan as i32, *mz, intensity * weight));
+        }
+    }
+}
+
+fn to_frame(frame_id: u32, ms_type: MsType, mut peaks: Vec<(i32, f64, f64)>) -> TimsFrame {
+    peaks.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    TimsFrame {
+        frame_id: frame_id as i32,
+        ms_type,
+        scan: peaks.iter().map(|p| p.0).collect(),
+        tof: vec![0; peaks.len()],
+        ims_frame: ImsFrame {
+            retention_time: frame_id as f64 * FRAME_TIME,
+            mobility: peaks.iter().map(|p| scan_to_mobility(p.0 as u32)).collect(),
+            mz: peaks.iter().map(|p| p.1).collect(),
+            intensity: peaks.iter().map(|p| p.2).collect(),
+        },
+    }
+}
+
+/// Precursor frames hold the isotope envelopes of all eluting ions, fragment frames the b and y ions of the
+/// precursors isolated in them
+fn build_frames(truth: &[TruthIon], schedule: &[ScheduledFrame], num_frames: u32, args: &Args) -> Vec<TimsFrame> {
+    let cycle_length = args.ms2_frames_per_cycle as u32 + 1;
+    let scheduled: BTreeMap<u32, &ScheduledFrame> = schedule.iter().map(|frame| (frame.frame_id, frame)).collect();
+
+    (1..=num_frames)
+        .map(|frame_id| {
+            let mut peaks = Vec::new();
+            match scheduled.get(&frame_id) {
+                None => {
+                    for ion in truth {
+                        let weight = elution_weight(ion, frame_id, cycle_length);
+                        if weight > 0.0 {
+                            add_peaks(&mut peaks, &ion.spectrum, ion.scan_center, 10, ION_EVENTS * weight);
+                        }
+                    }
+                    to_frame(frame_id, MsType::Precursor, peaks)
+                }
+                Some(frame) => {
+                    for selection in &frame.selections {
+                        let ion = &truth[selection.candidate.peptide_id as usize];
+                        add_peaks(&mut peaks, &ion.fragments, ion.scan_center, 10, selection.candidate.intensity);
+                    }
+                    to_frame(frame_id, MsType::FragmentDda, peaks)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Write the frames and the Precursors and PasefFrameMsMsInfo tables of the schedule
+fn write_dataset(
+    path: &Path,
+    frames: &[TimsFrame],
+    schedule: &[ScheduledFrame],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let writer = TdfWriter {
+        mz_lower: 100.0,
+        mz_upper: 1700.0,
+        im_lower: MOBILITY_LOWER,
+        im_upper: MOBILITY_UPPER,
+        num_scans: NUM_SCANS,
+        tof_max_index: 400_000,
+        scan_mode: 8,
+        compression_level: 1,
+        window_groups: Vec::new(),
+        frame_to_window_group: Vec::new(),
+    };
+    writer.create(path)?;
+    writer.write_frames(frames, path)?;
+
+    let mut connection = Connection::open(path.join("analysis.tdf"))?;
+    connection.execute_batch(
+        "CREATE TABLE Precursors (
+            Id INTEGER PRIMARY KEY, LargestPeakMz REAL NOT NULL, AverageMz REAL NOT NULL,
+            MonoisotopicMz REAL, Charge INTEGER, ScanNumber REAL NOT NULL, Intensity REAL NOT NULL,
+            Parent INTEGER
+         );
+         CREATE TABLE PasefFrameMsMsInfo (
+            Frame INTEGER NOT NULL, ScanNumBegin INTEGER NOT NULL, ScanNumEnd INTEGER NOT NULL,
+            IsolationMz REAL NOT NULL, IsolationWidth REAL NOT NULL, CollisionEnergy REAL NOT NULL,
+            Precursor INTEGER, PRIMARY KEY (Frame, ScanNumBegin)
+         );",
+    )?;
+
+    let transaction = connection.transaction()?;
+    let selections = schedule.iter().flat_map(|frame| frame.selections.iter());
+    let pasef_meta = PasefScheduler::to_pasef_meta(schedule, ISOLATION_WIDTH, COLLISION_ENERGY);
+    for (selection, meta) in selections.zip(pasef_meta.iter()) {
+        let candidate = &selection.candidate;
+        transaction.execute(
+            "INSERT INTO Precursors VALUES (?1, ?2, ?2, ?2, NULL, ?3, ?4, ?5)",
+            params![
+                meta.precursor,
+                candidate.mz,
+                (candidate.scan_start + candidate.scan_end) as f64 / 2.0,
+                candidate.intensity,
+                selection.precursor_frame_id
+            ],
+        )?;
+        transaction.execute(
+            "INSERT INTO PasefFrameMsMsInfo VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                meta.frame,
+                meta.scan_start,
+                meta.scan_end,
+                meta.isolation_mz,
+                meta.isolation_width,
+                meta.collision_energy,
+                meta.precursor
+            ],
+        )?;
+    }
+    transaction.commit()?;
+
+    Ok(())
+}
+
+/// Store the charge of every precursor, as the instrument software does after it picked the envelope
+fn write_charges(path: &Path, charges: &BTreeMap<i64, i32>) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path.join("analysis.tdf"))?;
+    let transaction = connection.transaction()?;
+    for (precursor_id, charge) in charges {
+        transaction.execute("UPDATE Precursors SET Charge = ?1 WHERE Id = ?2", params![charge, precursor_id])?;
+    }
+    transaction.commit()
+}
+
+/// Sum the peaks of all fragment frames of a precursor by tof index
+fn merge_fragments(fragments: &[&PASEFDDAFragment]) -> (Vec<f64>, Vec<f64>) {
+    let mut merged: BTreeMap<i32, (f64, f64)> = BTreeMap::new();
+    for fragment in fragments {
+        let spectrum = fragment.selected_fragment.to_indexed_mz_spectrum();
+        for (index, (mz, intensity)) in spectrum
+            .index
+            .iter()
+            .zip(spectrum.mz_spectrum.mz.iter().zip(spectrum.mz_spectrum.intensity.iter()))
+        {
+            let peak = merged.entry(*index).or_insert((*mz, 0.0));
+            peak.1 += intensity;
+        }
+    }
+    merged.values().cloned().unzip()
+}
+
+/// Write one spectrum per precursor in the layout read by `imspy.timstof.dbsearch.mgf`
+fn write_mgf(
+    path: &Path,
+    dataset: &TimsDatasetDDA,
+    fragments: &[PASEFDDAFragment],
+    purities: &BTreeMap<i64, f64>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let retention_times: BTreeMap<i64, f64> = read_meta_data_sql(dataset.get_data_path())?
+        .iter()
+        .map(|frame| (frame.id, frame.time))
+        .collect();
+
+    let mut fragments_by_precursor: BTreeMap<i64, Vec<&PASEFDDAFragment>> = BTreeMap::new();
+    for fragment in fragments {
+        fragments_by_precursor.entry(fragment.precursor_id as i64).or_default().push(fragment);
+    }
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    let mut num_spectra = 0;
+    for precursor in dataset.get_selected_precursors() {
+        let (mz, intensity) = match fragments_by_precursor.get(&precursor.precursor_id) {
+            Some(fragments) => merge_fragments(fragments),
+            None => continue,
+        };
+
+        writeln!(file, "BEGIN IONS")?;
+        writeln!(
+            file,
+            "TITLE=index: {}, frame: {}, purity: {:.3}",
+            precursor.precursor_id,
+            precursor.frame_id,
+            purities.get(&precursor.precursor_id).copied().unwrap_or(f64::NAN)
+        )?;
+        writeln!(
+            file,
+            "PEPMASS={:.6} {}",
+            precursor.mono_mz.unwrap_or(precursor.highest_intensity_mz),
+            precursor.precuror_total_intensity.round().max(1.0) as i64
+        )?;
+        if let Some(charge) = precursor.charge {
+            writeln!(file, "CHARGE={}+", charge)?;
+        }
+        writeln!(file, "RTINSECONDS={:.3}", retention_times.get(&precursor.frame_id).copied().unwrap_or(0.0))?;
+        writeln!(file, "ION_MOBILITY=1/K0 {:.4}", precursor.inverse_ion_mobility)?;
+        for (mz, intensity) in mz.iter().zip(intensity.iter()) {
+            writeln!(file, "{:.6}\t{}", mz, intensity.round() as i64)?;
+        }
+        writeln!(file, "END IONS")?;
+        num_spectra += 1;
+    }
+    file.flush()?;
+
+    Ok(num_spectra)
+}
+
+fn parse_field<T: std::str::FromStr>(value: &str, name: &str, line: usize) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("line {}: cannot parse {} from '{}'", line, name, value))
+}
+
+/// Read all spectra of an MGF file, fails on unterminated spectra, peaks outside of a spectrum and spectra
+/// without title or precursor
+pub fn read_mgf(path: &Path) -> Result<Vec<MgfSpectrum>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut spectra = Vec::new();
+    let mut current: Option<MgfSpectrum> = None;
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        let number = index + 1;
+
+        if line.is_empty() {
+            continue;
+        }
+        if line == "BEGIN IONS" {
+            if current.is_some() {
+                return Err(format!("line {}: spectrum started before the previous one ended", number));
+            }
+            current = Some(MgfSpectrum {
+                title: String::new(),
+                precursor_id: -1,
+                precursor_mz: f64::NAN,
+                precursor_intensity: 0,
+                charge: None,
+                retention_time: f64::NAN,
+                mobility: f64::NAN,
+                mz: Vec::new(),
+                intensity: Vec::new(),
+            });
+            continue;
+        }
+
+        let spectrum = current
+            .as_mut()
+            .ok_or_else(|| format!("line {}: '{}' outside of a spectrum", number, line))?;
+
+        if line == "END IONS" {
+            if spectrum.precursor_id < 0 || spectrum.precursor_mz.is_nan() {
+                return Err(format!("line {}: spectrum without title or precursor", number));
+            }
+            spectra.push(current.take().unwrap());
+        } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+            let (mz, intensity) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("line {}: peak without intensity", number))?;
+            spectrum.mz.push(parse_field(mz, "fragment m/z", number)?);
+            spectrum.intensity.push(parse_field(intensity, "fragment intensity", number)?);
+        } else {
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected a key=value pair", number))?;
+            match name {
+                "TITLE" => {
+                    spectrum.title = value.to_string();
+                    let id = value.split(',').next().and_then(|index| index.split(' ').nth(1)).unwrap_or("");
+                    spectrum.precursor_id = parse_field(id, "precursor id", number)?;
+                }
+                "PEPMASS" => {
+                    let (mz, intensity) = value.split_once(' ').unwrap_or((value, "0"));
+                    spectrum.precursor_mz = parse_field(mz, "precursor m/z", number)?;
+                    spectrum.precursor_intensity = parse_field(intensity, "precursor intensity", number)?;
+                }
+                "CHARGE" => spectrum.charge = Some(parse_field(value.trim_end_matches('+'), "charge", number)?),
+                "RTINSECONDS" => spectrum.retention_time = parse_field(value, "retention time", number)?,
+                "ION_MOBILITY" => {
+                    let mobility = value.split(' ').next_back().unwrap_or("");
+                    spectrum.mobility = parse_field(mobility, "ion mobility", number)?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    match current {
+        Some(_) => Err("spectrum at the end of the file is not terminated".to_string()),
+        None => Ok(spectra),
+    }
+}
+
+fn within_ppm(a: f64, b: f64, ppm: f64) -> bool {
+    (a - b).abs() <= b * ppm * 1e-6
+}
+
+/// Simulate, export and validate, returns an error describing the first failed check
+pub fn run(args: &Args) -> Result<Report, String> {
+    let data_path = match &args.data_path {
+        Some(path) => PathBuf::from(path),
+        None => std::env::temp_dir().join(format!("dda_to_mgf_{}.d", std::process::id())),
+    };
+    let mgf_path = match &args.mgf_path {
+        Some(path) => PathBuf::from(path),
+        None => data_path.with_extension("mgf"),
+    };
+
+    let cycle_length = args.ms2_frames_per_cycle as u32 + 1;
+    let num_frames = args.num_cycles * cycle_length;
+
+    let timer = Instant::now();
+    let truth = simulate_truth(num_frames, cycle_length);
+    let scheduler = PasefScheduler {
+        top_n: args.top_n,
+        ms2_frames_per_cycle: args.ms2_frames_per_cycle,
+        ..Default::default()
+    };
+    let schedule = scheduler.schedule(&candidate_frames(&truth, num_frames, args));
+    let frames = build_frames(&truth, &schedule, num_frames, args);
+    write_dataset(&data_path, &frames, &schedule).map_err(|e| format!("writing dataset: {}", e))?;
+
+    // precursor id to the simulated ion it isolates, numbered as in `to_pasef_meta`
+    let selected: BTreeMap<i64, &TruthIon> = schedule
+        .iter()
+        .flat_map(|frame| frame.selections.iter())
+        .enumerate()
+        .map(|(index, selection)| (index as i64 + 1, &truth[selection.candidate.peptide_id as usize]))
+        .collect();
+    let charges = selected.iter().map(|(id, ion)| (*id, ion.charge)).collect();
+    write_charges(&data_path, &charges).map_err(|e| format!("writing charges: {}", e))?;
+    println!(
+        "wrote {} frames with {} precursors of {} ions to {} in {:?}",
+        frames.len(),
+        selected.len(),
+        truth.len(),
+        data_path.display(),
+        timer.elapsed()
+    );
+
+    let timer = Instant::now();
+    let dataset = TimsDatasetDDA::try_new("", data_path.to_str().unwrap(), false, false).map_err(|e| e.to_string())?;
+    let precursors = dataset.get_selected_precursors();
+    let fragments = dataset.get_pasef_fragments(args.num_threads);
+    let purities: BTreeMap<i64, f64> = dataset
+        .compute_precursor_purity(args.ppm, 3, args.num_threads)
+        .iter()
+        .map(|purity| (purity.precursor_id, purity.purity))
+        .collect();
+    let num_spectra =
+        write_mgf(&mgf_path, &dataset, &fragments, &purities).map_err(|e| format!("writing mgf: {}", e))?;
+    println!(
+        "linked {} precursors to {} PASEF fragments and wrote {} spectra to {} in {:?}",
+        precursors.len(),
+        fragments.len(),
+        num_spectra,
+        mgf_path.display(),
+        timer.elapsed()
+    );
+
+    let spectra = read_mgf(&mgf_path)?;
+
+    if args.data_path.is_none() {
+        fs::remove_dir_all(&data_path).map_err(|e| e.to_string())?;
+    }
+    if args.mgf_path.is_none() && args.data_path.is_none() {
+        fs::remove_file(&mgf_path).map_err(|e| e.to_string())?;
+    }
+
+    if precursors.len() != selected.len() {
+        return Err(format!("scheduled {} precursors, read {}", selected.len(), precursors.len()));
+    }
+    if spectra.len() != selected.len() {
+        return Err(format!("expected {} spectra, read {}", selected.len(), spectra.len()));
+    }
+
+    let mut num_expected = 0;
+    let mut num_found = 0;
+    for spectrum in &spectra {
+        let ion = selected
+            .get(&spectrum.precursor_id)
+            .ok_or_else(|| format!("spectrum of unknown precursor {}", spectrum.precursor_id))?;
+
+        if !within_ppm(spectrum.precursor_mz, ion.mono_mz, args.ppm) {
+            return Err(format!(
+                "precursor {} of {} has m/z {:.4}, expected {:.4}",
+                spectrum.precursor_id, ion.sequence, spectrum.precursor_mz, ion.mono_mz
+            ));
+        }
+        if spectrum.charge != Some(ion.charge) {
+            return Err(format!(
+                "precursor {} of {} has charge {:?}, expected {}",
+                spectrum.precursor_id, ion.sequence, spectrum.charge, ion.charge
+            ));
+        }
+        if (spectrum.mobility - scan_to_mobility(ion.scan_center)).abs() > 0.01 {
+            return Err(format!(
+                "precursor {} of {} has mobility {:.4}, expected {:.4}",
+                spectrum.precursor_id,
+                ion.sequence,
+                spectrum.mobility,
+                scan_to_mobility(ion.scan_center)
+            ));
+        }
+
+        // fragments outside of the acquisition range are not written
+        for mz in ion.fragments.mz.iter().filter(|mz| (100.0..1700.0).contains(*mz)) {
+            num_expected += 1;
+            if spectrum.mz.iter().any(|peak| within_ppm(*peak, *mz, args.ppm)) {
+                num_found += 1;
+            }
+        }
+    }
+
+    let report = Report {
+        num_ions: truth.len(),
+        num_precursors: precursors.len(),
+        num_spectra: spectra.len(),
+        min_purity: purities.values().cloned().fold(f64::INFINITY, f64::min),
+        fragment_recall: match num_expected {
+            0 => 0.0,
+            n => num_found as f64 / n as f64,
+        },
+    };
+    println!(
+        "spectra: {}, lowest purity: {:.3}, fragment recall: {:.3} ({}/{})",
+        report.num_spectra, report.min_purity, report.fragment_recall, num_found, num_expected
+    );
+
+    if report.min_purity.is_nan() || report.min_purity < args.min_purity {
+        return Err(format!("purity {:.3} is below {:.3}", report.min_purity, args.min_purity));
+    }
+    if report.fragment_recall < args.min_fragment_recall {
+        return Err(format!(
+            "fragment recall {:.3} is below {:.3}",
+            report.fragment_recall, args.min_fragment_recall
+        ));
+    }
+
+    Ok(report)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = run(&args) {
+        eprintln!("dda_to_mgf failed: {}", e);
+        std::process::exit(1);
+    }
+}
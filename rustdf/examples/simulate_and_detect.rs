@@ -0,0 +1,335 @@
+//! Simulate a small precursor-only experiment and check that the feature detector finds it again
+//!
+//! The example writes a synthetic_data.db with a handful of peptide ions at known retention times and
+//! mobilities, builds the precursor frames from it, runs `detect_features` and `assign_charge_states`
+//! and reports recall and precision against the ground truth. It exits with a non-zero status if one
+//! of them is below the requested threshold.
+//!
+//! ```text
+//! cargo run --release --example simulate_and_detect -- --num-frames 200 --num-threads 4
+//! ```
+
+use clap::Parser;
+use mscore::data::peptide::PeptideIon;
+use mscore::data::spectrum::MzSpectrum;
+use mscore::timstof::slice::TimsSlice;
+use rusqlite::{params, Connection};
+use rustdf::algorithm::feature::{assign_charge_states, detect_features, Feature};
+use rustdf::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const SEQUENCES: [&str; 12] = [
+    "PEPTIDEK",
+    "LVNELTEFAK",
+    "AEFVEVTK",
+    "YLYEIAR",
+    "HLVDEPQNLIK",
+    "VPQVSTPTLVEVSR",
+    "EACFAVEGPK",
+    "LGEYGFQNALIVR",
+    "SLHTLFGDELCK",
+    "DAFLGSFLYEYSR",
+    "QTALVELLK",
+    "FKDLGEEHFK",
+];
+
+const NUM_SCANS: u32 = 900;
+const MOBILITY_UPPER: f64 = 1.6;
+const MOBILITY_LOWER: f64 = 0.6;
+const FRAME_TIME: f64 = 0.1;
+
+/// Simulate a small experiment and score feature detection against the ground truth
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Path of the simulation database to write, a temporary file is used if not set
+    #[arg(short, long)]
+    pub db_path: Option<String>,
+
+    /// Number of precursor frames to simulate
+    #[arg(long, default_value_t = 200)]
+    pub num_frames: u32,
+
+    /// Number of threads to use
+    #[arg(short, long, default_value_t = 4)]
+    pub num_threads: usize,
+
+    /// Mass tolerance of the feature detector in ppm
+    #[arg(long, default_value_t = 10.0)]
+    pub ppm: f64,
+
+    /// Minimum recall of simulated ions
+    #[arg(long, default_value_t = 0.9)]
+    pub min_recall: f64,
+
+    /// Minimum precision of detected features
+    #[arg(long, default_value_t = 0.9)]
+    pub min_precision: f64,
+}
+
+/// A simulated ion with the position it was placed at
+#[derive(Debug, Clone)]
+pub struct TruthIon {
+    pub sequence: String,
+    pub charge: i32,
+    pub mono_mz: f64,
+    pub mobility: f64,
+    pub frame_start: u32,
+    pub frame_end: u32,
+    pub spectrum: MzSpectrum,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub num_ions: usize,
+    pub num_features: usize,
+    pub recall: f64,
+    pub precision: f64,
+}
+
+fn scan_to_mobility(scan: u32) -> f64 {
+    MOBILITY_UPPER - (MOBILITY_UPPER - MOBILITY_LOWER) * scan as f64 / NUM_SCANS as f64
+}
+
+fn gaussian_profile(center: u32, width: u32, sigma: f64) -> (Vec<u32>, Vec<f32>) {
+    let occurrence: Vec<u32> = (center.saturating_sub(width).max(1)..=center + width).collect();
+    let weights: Vec<f64> = occurrence
+        .iter()
+        .map(|&x| (-0.5 * ((x as f64 - center as f64) / sigma).powi(2)).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    (
+        occurrence,
+        weights.iter().map(|w| (w / total) as f32).collect(),
+    )
+}
+
+/// Place the peptides evenly along the gradient, every second peptide also gets a 3+ ion
+fn simulate_truth(num_frames: u32) -> Vec<TruthIon> {
+    let step = num_frames / (SEQUENCES.len() as u32 + 1);
+    let mut truth = Vec::new();
+
+    for (index, sequence) in SEQUENCES.iter().enumerate() {
+        let frame_center = step * (index as u32 + 1);
+        let charges: &[i32] = if index % 2 == 0 { &[2, 3] } else { &[2] };
+
+        for &charge in charges {
+            let ion = PeptideIon::new(sequence.to_string(), charge, 1.0, None);
+            let mono_mz = ion.mz();
+            let scan_center = 150 + ((mono_mz * charge as f64) as u32 * 7 + charge as u32 * 97) % 600;
+
+            truth.push(TruthIon {
+                sequence: sequence.to_string(),
+                charge,
+                mono_mz,
+                mobility: scan_to_mobility(scan_center),
+                frame_start: frame_center.saturating_sub(6).max(1),
+                frame_end: (frame_center + 6).min(num_frames),
+                spectrum: ion.calculate_isotopic_spectrum(1e-3, 1e-8, 200, 1e-4),
+            });
+        }
+    }
+    truth
+}
+
+/// Write frames, scans, peptides and ions tables in the layout read by `TimsTofSyntheticsDataHandle`
+fn write_database(path: &Path, num_frames: u32, truth: &[TruthIon]) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(
+        "CREATE TABLE frames (frame_id INTEGER, time REAL, ms_type INTEGER);
+         CREATE TABLE scans (scan INTEGER, mobility REAL);
+         CREATE TABLE peptides (
+            protein_id INTEGER, peptide_id INTEGER, sequence TEXT, proteins TEXT, decoy BOOLEAN,
+            missed_cleavages INTEGER, n_term BOOLEAN, c_term BOOLEAN, monoisotopic_mass REAL,
+            retention_time REAL, events REAL, frame_occurrence_start INTEGER, frame_occurrence_end INTEGER,
+            frame_start INTEGER, frame_end INTEGER, frame_occurrence TEXT, frame_abundance TEXT
+         );
+         CREATE TABLE ions (
+            ion_id INTEGER, peptide_id INTEGER, sequence TEXT, charge INTEGER, mz REAL,
+            relative_abundance REAL, inv_mobility_gru_predictor REAL, inv_mobility_gru_predictor_std REAL,
+            simulated_spectrum TEXT, scan_occurrence TEXT, scan_abundance TEXT
+         );",
+    )?;
+
+    let transaction = connection.transaction()?;
+
+    for frame_id in 1..=num_frames {
+        transaction.execute(
+            "INSERT INTO frames VALUES (?1, ?2, 0)",
+            params![frame_id, frame_id as f64 * FRAME_TIME],
+        )?;
+    }
+
+    for scan in 0..=NUM_SCANS {
+        transaction.execute(
+            "INSERT INTO scans VALUES (?1, ?2)",
+            params![scan, scan_to_mobility(scan)],
+        )?;
+    }
+
+    let mut peptide_ids: Vec<(String, u32)> = Vec::new();
+
+    for (ion_id, ion) in truth.iter().enumerate() {
+        let frame_center = (ion.frame_start + ion.frame_end) / 2;
+
+        let peptide_id = match peptide_ids.iter().find(|(s, _)| *s == ion.sequence) {
+            Some((_, id)) => *id,
+            None => {
+                let id = peptide_ids.len() as u32 + 1;
+                let (occurrence, abundance) = gaussian_profile(frame_center, 6, 2.0);
+                transaction.execute(
+                    "INSERT INTO peptides VALUES (0, ?1, ?2, 'P1', 0, 0, 0, 0, ?3, ?4, 1e6, 0, 0, ?5, ?6, ?7, ?8)",
+                    params![
+                        id,
+                        ion.sequence,
+                        (ion.mono_mz - 1.007276466621) * ion.charge as f64,
+                        frame_center as f64 * FRAME_TIME,
+                        ion.frame_start,
+                        ion.frame_end,
+                        serde_json::to_string(&occurrence).unwrap(),
+                        serde_json::to_string(&abundance).unwrap(),
+                    ],
+                )?;
+                peptide_ids.push((ion.sequence.clone(), id));
+                id
+            }
+        };
+
+        let scan_center = ((MOBILITY_UPPER - ion.mobility) / (MOBILITY_UPPER - MOBILITY_LOWER)
+            * NUM_SCANS as f64)
+            .round() as u32;
+        let (scan_occurrence, scan_abundance) = gaussian_profile(scan_center, 5, 2.0);
+
+        transaction.execute(
+            "INSERT INTO ions VALUES (?1, ?2, ?3, ?4, ?5, 1.0, ?6, 0.0, ?7, ?8, ?9)",
+            params![
+                ion_id as u32 + 1,
+                peptide_id,
+                ion.sequence,
+                ion.charge,
+                ion.mono_mz,
+                ion.mobility,
+                serde_json::to_string(&ion.spectrum).unwrap(),
+                serde_json::to_string(&scan_occurrence).unwrap(),
+                serde_json::to_string(&scan_abundance).unwrap(),
+            ],
+        )?;
+    }
+
+    transaction.commit()
+}
+
+fn within_ppm(a: f64, b: f64, ppm: f64) -> bool {
+    (a - b).abs() <= b * ppm * 1e-6
+}
+
+fn feature_in_ion(feature: &Feature, ion: &TruthIon, mobility_tol: f64) -> bool {
+    let frame = feature.apex_frame_id as u32;
+    frame >= ion.frame_start
+        && frame <= ion.frame_end
+        && (feature.apex_mobility - ion.mobility).abs() <= mobility_tol
+}
+
+/// Simulate, detect and score, returns an error describing the first failed check
+pub fn run(args: &Args) -> Result<Report, String> {
+    let db_path = match &args.db_path {
+        Some(path) => PathBuf::from(path),
+        None => std::env::temp_dir().join(format!("simulate_and_detect_{}.db", std::process::id())),
+    };
+    if db_path.exists() {
+        std::fs::remove_file(&db_path).map_err(|e| e.to_string())?;
+    }
+
+    let timer = Instant::now();
+    let truth = simulate_truth(args.num_frames);
+    write_database(&db_path, args.num_frames, &truth).map_err(|e| format!("writing database: {}", e))?;
+    println!("wrote {} ions to {} in {:?}", truth.len(), db_path.display(), timer.elapsed());
+
+    let timer = Instant::now();
+    let builder = TimsTofSyntheticsPrecursorFrameBuilder::new(&db_path, None, args.num_threads)
+        .map_err(|e| format!("reading database: {}", e))?;
+    let frames = builder.build_precursor_frames(
+        (1..=args.num_frames).collect(),
+        false,
+        false,
+        0.0,
+        false,
+        args.num_threads,
+        None,
+    );
+    if frames.len() != args.num_frames as usize {
+        return Err(format!("expected {} frames, built {}", args.num_frames, frames.len()));
+    }
+    println!("built {} frames in {:?}", frames.len(), timer.elapsed());
+
+    let timer = Instant::now();
+    let slice = TimsSlice::new(frames);
+    let mut features = detect_features(&slice, args.ppm, 0.01, 1, 1.0, 3, args.num_threads);
+    assign_charge_states(&mut features, args.ppm, 2.0 * FRAME_TIME, 0.01, 4, args.num_threads);
+    println!("detected {} features in {:?}", features.len(), timer.elapsed());
+
+    let mobility_tol = 0.02;
+
+    // an ion is recovered if its monoisotopic peak is found with the simulated charge
+    let recovered = truth
+        .iter()
+        .filter(|ion| {
+            features.iter().any(|f| {
+                within_ppm(f.apex_mz, ion.mono_mz, args.ppm)
+                    && f.charge == Some(ion.charge)
+                    && feature_in_ion(f, ion, mobility_tol)
+            })
+        })
+        .count();
+
+    // a feature is correct if it is one of the simulated isotope peaks
+    let correct = features
+        .iter()
+        .filter(|f| {
+            truth.iter().any(|ion| {
+                feature_in_ion(f, ion, mobility_tol)
+                    && ion.spectrum.mz.iter().any(|&mz| within_ppm(f.apex_mz, mz, args.ppm))
+            })
+        })
+        .count();
+
+    if db_path.exists() && args.db_path.is_none() {
+        std::fs::remove_file(&db_path).map_err(|e| e.to_string())?;
+    }
+
+    let report = Report {
+        num_ions: truth.len(),
+        num_features: features.len(),
+        recall: recovered as f64 / truth.len() as f64,
+        precision: match features.len() {
+            0 => 0.0,
+            n => correct as f64 / n as f64,
+        },
+    };
+    println!(
+        "recall: {:.3} ({}/{}), precision: {:.3} ({}/{})",
+        report.recall, recovered, report.num_ions, report.precision, correct, report.num_features
+    );
+
+    if report.recall < args.min_recall {
+        return Err(format!("recall {:.3} is below {:.3}", report.recall, args.min_recall));
+    }
+    if report.precision < args.min_precision {
+        return Err(format!(
+            "precision {:.3} is below {:.3}",
+            report.precision, args.min_precision
+        ));
+    }
+
+    Ok(report)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = run(&args) {
+        eprintln!("simulate_and_detect failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::data::handle::TimsData;
+use crate::data::meta::read_meta_data_sql;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Monotone piecewise-linear mapping of retention times from run a to run b
+///
+/// Between knots retention times are interpolated linearly, outside of the knots they are shifted by the offset
+/// of the closest knot. Without knots the mapping is the identity.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RtWarp {
+    /// retention times of the knots in run a, strictly increasing
+    pub rt_a: Vec<f64>,
+    /// retention times of the knots in run b, strictly increasing
+    pub rt_b: Vec<f64>,
+}
+
+/// Piecewise-linear interpolation of `x` from knots `xs` to `ys`, both strictly increasing
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    match xs.len() {
+        0 => x,
+        _ if x <= xs[0] => x + ys[0] - xs[0],
+        n if x >= xs[n - 1] => x + ys[n - 1] - xs[n - 1],
+        _ => {
+            let i = xs.partition_point(|&knot| knot <= x);
+            let t = (x - xs[i - 1]) / (xs[i] - xs[i - 1]);
+            ys[i - 1] + t * (ys[i] - ys[i - 1])
+        }
+    }
+}
+
+impl RtWarp {
+    /// Create a warp from knots, pairs are sorted by `rt_a`
+    ///
+    /// # Arguments
+    ///
+    /// * `knots` - (retention time in run a, retention time in run b) pairs
+    ///
+    /// # Returns
+    ///
+    /// * The warp, or an error if the knots are not strictly increasing in both runs
+    ///
+    pub fn new(mut knots: Vec<(f64, f64)>) -> Result<Self, String> {
+        knots.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if knots.windows(2).any(|w| w[1].0 <= w[0].0 || w[1].1 <= w[0].1) {
+            return Err("knots of a retention time warp must be strictly increasing in both runs".to_string());
+        }
+        Ok(RtWarp {
+            rt_a: knots.iter().map(|k| k.0).collect(),
+            rt_b: knots.iter().map(|k| k.1).collect(),
+        })
+    }
+
+    /// Map a retention time of run a to run b
+    pub fn map(&self, rt: f64) -> f64 {
+        interpolate(&self.rt_a, &self.rt_b, rt)
+    }
+
+    /// Map a retention time of run b to run a, the inverse of `map`
+    pub fn inverse(&self, rt: f64) -> f64 {
+        interpolate(&self.rt_b, &self.rt_a, rt)
+    }
+
+    pub fn num_knots(&self) -> usize {
+        self.rt_a.len()
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/// Fit a monotone warp through matched retention times
+///
+/// Matches are split into about sqrt(n) bins of equal size along run a, every bin contributes the median retention
+/// time of both runs as knot, which keeps the fit robust against wrong matches. Knots that break monotonicity are
+/// pooled with their neighbours (pool adjacent violators, weighted by the number of matches).
+///
+/// # Arguments
+///
+/// * `matches` - (retention time in run a, retention time in run b) of matched features
+///
+/// # Returns
+///
+/// * The warp, the identity if there are no matches
+///
+pub fn fit_rt_warp(matches: &[(f64, f64)]) -> RtWarp {
+    let mut matches: Vec<(f64, f64)> = matches.iter().copied().filter(|(a, b)| a.is_finite() && b.is_finite()).collect();
+    if matches.is_empty() {
+        return RtWarp::default();
+    }
+    matches.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let num_bins = (matches.len() as f64).sqrt().ceil() as usize;
+    let bin_size = matches.len().div_ceil(num_bins);
+
+    // (weight, rt a, rt b) of every block, pooled while the block before maps to a later or equal rt in run b
+    let mut blocks: Vec<(f64, f64, f64)> = Vec::with_capacity(num_bins);
+    for bin in matches.chunks(bin_size) {
+        let mut rt_a: Vec<f64> = bin.iter().map(|m| m.0).collect();
+        let mut rt_b: Vec<f64> = bin.iter().map(|m| m.1).collect();
+        let mut block = (bin.len() as f64, median(&mut rt_a), median(&mut rt_b));
+
+        while let Some(&(weight, a, b)) = blocks.last() {
+            if b < block.2 && a < block.1 {
+                break;
+            }
+            blocks.pop();
+            let total = weight + block.0;
+            block = (total, (a * weight + block.1 * block.0) / total, (b * weight + block.2 * block.0) / total);
+        }
+        blocks.push(block);
+    }
+
+    RtWarp {
+        rt_a: blocks.iter().map(|b| b.1).collect(),
+        rt_b: blocks.iter().map(|b| b.2).collect(),
+    }
+}
+
+/// Align the retention times of two runs by anchor features found in both
+///
+/// Every anchor of run a is matched to the anchor of run b closest in m/z, anchors with more than one candidate
+/// within tolerance are ambiguous and skipped. The warp is fitted through the matches, see `fit_rt_warp`.
+///
+/// # Arguments
+///
+/// * `anchors_a` - (retention time, m/z) of the anchors of run a
+/// * `anchors_b` - (retention time, m/z) of the anchors of run b
+/// * `ppm` - The m/z tolerance of a match in ppm
+///
+/// # Returns
+///
+/// * The warp from run a to run b
+///
+/// # Example
+///
+/// ```
+/// use rustdf::algorithm::align::align_rt;
+///
+/// let anchors_a: Vec<(f64, f64)> = (0..100).map(|i| (i as f64 * 10.0, 400.0 + i as f64 * 7.3)).collect();
+/// let anchors_b: Vec<(f64, f64)> = anchors_a.iter().map(|&(rt, mz)| (rt * 1.1 + 20.0, mz)).collect();
+/// let warp = align_rt(&anchors_a, &anchors_b, 10.0);
+/// assert!((warp.map(500.0) - 570.0).abs() < 1e-9);
+/// assert!((warp.inverse(570.0) - 500.0).abs() < 1e-9);
+/// ```
+pub fn align_rt(anchors_a: &[(f64, f64)], anchors_b: &[(f64, f64)], ppm: f64) -> RtWarp {
+    let mut order_b: Vec<usize> = (0..anchors_b.len()).collect();
+    order_b.sort_by(|&x, &y| anchors_b[x].1.total_cmp(&anchors_b[y].1));
+    let sorted_mz: Vec<f64> = order_b.iter().map(|&i| anchors_b[i].1).collect();
+
+    let matches: Vec<(f64, f64)> = anchors_a.iter().filter_map(|&(rt, mz)| {
+        let tol = mz * ppm * 1e-6;
+        let start = sorted_mz.partition_point(|&x| x < mz - tol);
+        let end = sorted_mz.partition_point(|&x| x <= mz + tol);
+        (end == start + 1).then(|| (rt, anchors_b[order_b[start]].0))
+    }).collect();
+
+    fit_rt_warp(&matches)
+}
+
+/// Most intense MS1 peaks of a run as alignment anchors
+///
+/// Precursor frames are grouped into retention time bins, per bin the `top_k` most intense peaks are kept, peaks
+/// within `ppm` of a more intense peak of the same bin are skipped to avoid several anchors per feature.
+///
+/// # Arguments
+///
+/// * `data` - The run
+/// * `rt_bin_width` - The width of a retention time bin in seconds
+/// * `top_k` - The number of anchors per bin
+/// * `ppm` - The m/z tolerance in ppm within which only the most intense peak is kept
+/// * `num_threads` - The number of threads used to load frames
+///
+/// # Returns
+///
+/// * (retention time, m/z) of every anchor, ordered by retention time bin and decreasing intensity
+///
+pub fn extract_rt_anchors(data: &dyn TimsData, rt_bin_width: f64, top_k: usize, ppm: f64, num_threads: usize) -> Result<Vec<(f64, f64)>, Box<dyn Error>> {
+    if rt_bin_width <= 0.0 {
+        return Err("rt_bin_width must be positive".into());
+    }
+    let mut bins: BTreeMap<i64, Vec<u32>> = BTreeMap::new();
+    for frame in read_meta_data_sql(data.get_data_path())?.into_iter().filter(|frame| frame.ms_ms_type == 0) {
+        bins.entry((frame.time / rt_bin_width).floor() as i64).or_default().push(frame.id as u32);
+    }
+
+    let mut anchors = Vec::with_capacity(bins.len() * top_k);
+    for frame_ids in bins.into_values() {
+        let slice = data.get_slice(frame_ids, num_threads);
+        let mut peaks: Vec<(f64, f64, f64)> = slice.frames.iter().flat_map(|frame| {
+            frame.ims_frame.mz.iter().zip(&frame.ims_frame.intensity)
+                .map(move |(&mz, &intensity)| (intensity, frame.ims_frame.retention_time, mz))
+        }).collect();
+        peaks.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut selected: Vec<(f64, f64)> = Vec::with_capacity(top_k);
+        for (_, rt, mz) in peaks {
+            if selected.len() == top_k {
+                break;
+            }
+            if selected.iter().all(|&(_, other)| (other - mz).abs() > other * ppm * 1e-6) {
+                selected.push((rt, mz));
+            }
+        }
+        anchors.extend(selected);
+    }
+    Ok(anchors)
+}
+
+/// Align the retention times of two runs, e.g. a simulated run and a real acquisition or two replicates
+///
+/// # Arguments
+///
+/// * `a` - The run mapped from
+/// * `b` - The run mapped to
+/// * `rt_bin_width` - The width of a retention time bin in seconds, see `extract_rt_anchors`
+/// * `top_k` - The number of anchors per bin
+/// * `ppm` - The m/z tolerance in ppm of anchor extraction and matching
+/// * `num_threads` - The number of threads used to load frames
+///
+/// # Returns
+///
+/// * The warp from run a to run b
+///
+pub fn align_datasets(a: &dyn TimsData, b: &dyn TimsData, rt_bin_width: f64, top_k: usize, ppm: f64, num_threads: usize) -> Result<RtWarp, Box<dyn Error>> {
+    let anchors_a = extract_rt_anchors(a, rt_bin_width, top_k, ppm, num_threads)?;
+    let anchors_b = extract_rt_anchors(b, rt_bin_width, top_k, ppm, num_threads)?;
+    Ok(align_rt(&anchors_a, &anchors_b, ppm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rt_warp() {
+        let warp = RtWarp::new(vec![(100.0, 110.0), (0.0, 5.0), (200.0, 230.0)]).unwrap();
+        assert_eq!(warp.map(50.0), 57.5);
+        assert_eq!(warp.map(-10.0), -5.0);
+        assert_eq!(warp.map(300.0), 330.0);
+        assert_eq!(warp.inverse(170.0), 150.0);
+        assert!(RtWarp::new(vec![(0.0, 5.0), (1.0, 5.0)]).is_err());
+        assert_eq!(RtWarp::default().map(42.0), 42.0);
+    }
+
+    #[test]
+    fn test_fit_rt_warp_is_monotone_and_robust() {
+        // shifted by 30 seconds, every tenth match is wrong
+        let matches: Vec<(f64, f64)> = (0..400)
+            .map(|i| i as f64 * 5.0)
+            .enumerate()
+            .map(|(i, rt)| if i % 10 == 3 { (rt, 2000.0 - rt) } else { (rt, rt + 30.0) })
+            .collect();
+        let warp = fit_rt_warp(&matches);
+
+        assert!(warp.num_knots() > 1);
+        assert!(warp.rt_a.windows(2).all(|w| w[0] < w[1]));
+        assert!(warp.rt_b.windows(2).all(|w| w[0] < w[1]));
+        assert!((warp.map(1000.0) - 1030.0).abs() < 1.0);
+        assert_eq!(fit_rt_warp(&[]), RtWarp::default());
+    }
+
+    #[test]
+    fn test_align_rt_skips_ambiguous_anchors() {
+        let anchors_a = vec![(10.0, 500.0), (20.0, 600.0), (30.0, 700.0)];
+        // two candidates for 600 within tolerance, the match is skipped
+        let anchors_b = vec![(15.0, 500.001), (22.0, 600.0), (90.0, 600.002), (35.0, 700.0)];
+        let warp = align_rt(&anchors_a, &anchors_b, 10.0);
+        assert_eq!(warp.num_knots(), 2);
+        assert_eq!(warp.map(20.0), 25.0);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+use mscore::timstof::slice::TimsSlice;
+use rayon::prelude::*;
+use mscore::chemistry::constants::C13_C12_MASS_DIFFERENCE;
+use mscore::parallel;
+
+/// A feature in retention time, inverse mobility and m/z, found by clustering peaks of a TimsSlice
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub apex_mz: f64,
+    pub apex_retention_time: f64,
+    pub apex_mobility: f64,
+    pub apex_frame_id: i32,
+    pub total_intensity: f64,
+    pub charge: Option<i32>,
+    /// member peaks as (frame index within the slice, peak index within the frame)
+    pub peaks: Vec<(usize, usize)>,
+}
+
+impl Feature {
+    pub fn num_points(&self) -> usize {
+        self.peaks.len()
+    }
+}
+
+/// peaks of a single frame that passed the intensity filter, sorted by m/z
+struct FramePeaks {
+    frame_index: usize,
+    mz: Vec<f64>,
+    mobility: Vec<f64>,
+    intensity: Vec<f64>,
+    peak_index: Vec<usize>,
+}
+
+impl FramePeaks {
+    fn from_slice(slice: &TimsSlice, frame_index: usize, min_intensity: f64) -> Self {
+        let frame = &slice.frames[frame_index];
+        let mut order: Vec<usize> = (0..frame.ims_frame.mz.len())
+            .filter(|&i| frame.ims_frame.intensity[i] >= min_intensity)
+            .collect();
+        order.sort_by(|&a, &b| frame.ims_frame.mz[a].total_cmp(&frame.ims_frame.mz[b]).then(a.cmp(&b)));
+
+        FramePeaks {
+            frame_index,
+            mz: order.iter().map(|&i| frame.ims_frame.mz[i]).collect(),
+            mobility: order.iter().map(|&i| frame.ims_frame.mobility[i]).collect(),
+            intensity: order.iter().map(|&i| frame.ims_frame.intensity[i]).collect(),
+            peak_index: order,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.mz.len()
+    }
+}
+
+/// find all pairs of peaks between two frames that are within m/z and mobility tolerance,
+/// if both frames are the same, every pair is only reported once
+fn link_frames(a: &FramePeaks, b: &FramePeaks, same_frame: bool, ppm_tol: f64, im_tol: f64) -> Vec<(usize, usize)> {
+    let mut links = Vec::new();
+
+    for i in 0..a.len() {
+        let mz = a.mz[i];
+        let tol = mz * ppm_tol * 1e-6;
+        let start = b.mz.partition_point(|&x| x < mz - tol);
+
+        for j in start..b.len() {
+            if b.mz[j] > mz + tol {
+                break;
+            }
+            if same_frame && j <= i {
+                continue;
+            }
+            if (a.mobility[i] - b.mobility[j]).abs() <= im_tol {
+                links.push((i, j));
+            }
+        }
+    }
+
+    links
+}
+
+fn find_root(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+/// Detect features in a TimsSlice by connected-component clustering of tolerance-linked peaks
+///
+/// Two peaks are linked if their m/z is within `ppm_tol`, their inverse mobility is within `im_tol`
+/// and their frames are at most `rt_gap_frames + 1` frames apart (frames are ordered by retention time).
+/// Linking is done in parallel over frame pairs, the result does not depend on the number of threads.
+/// Charge states are not assigned, see `assign_charge_states`.
+///
+/// # Arguments
+///
+/// * `slice` - The TimsSlice to search
+/// * `ppm_tol` - The m/z tolerance in ppm
+/// * `im_tol` - The inverse mobility tolerance
+/// * `rt_gap_frames` - The number of frames a feature may be missing in between two linked peaks
+/// * `min_intensity` - The minimum intensity of a peak to be considered
+/// * `min_points` - The minimum number of peaks of a feature
+/// * `num_threads` - The number of threads to use
+///
+/// # Returns
+///
+/// * `Vec<Feature>` - The features, sorted by apex retention time and apex m/z
+///
+/// # Example
+///
+/// ```
+/// use mscore::data::spectrum::MsType;
+/// use mscore::timstof::frame::TimsFrame;
+/// use mscore::timstof::slice::TimsSlice;
+/// use rustdf::algorithm::feature::detect_features;
+///
+/// let frames = (0..3).map(|i| TimsFrame::new(i + 1, MsType::Precursor, i as f64,
+///     vec![10, 11, 50], vec![1.0, 1.001, 1.2], vec![0, 0, 0], vec![500.0, 500.001, 800.0],
+///     vec![10.0, 20.0 * (i + 1) as f64, 5.0])).collect();
+/// let slice = TimsSlice::new(frames);
+/// let features = detect_features(&slice, 10.0, 0.01, 0, 1.0, 4, 2);
+/// assert_eq!(features.len(), 1);
+/// assert_eq!(features[0].num_points(), 6);
+/// assert_eq!(features[0].apex_frame_id, 3);
+/// ```
+pub fn detect_features(
+    slice: &TimsSlice,
+    ppm_tol: f64,
+    im_tol: f64,
+    rt_gap_frames: usize,
+    min_intensity: f64,
+    min_points: usize,
+    num_threads: usize,
+) -> Vec<Feature> {
+    let pool = parallel::pool(num_threads);
+
+    // frames ordered by retention time, ties broken by frame id
+    let mut frame_order: Vec<usize> = (0..slice.frames.len()).collect();
+    frame_order.sort_by(|&a, &b| {
+        let fa = &slice.frames[a];
+        let fb = &slice.frames[b];
+        fa.ims_frame.retention_time.total_cmp(&fb.ims_frame.retention_time).then(fa.frame_id.cmp(&fb.frame_id))
+    });
+
+    let frame_peaks: Vec<FramePeaks> = pool.install(|| {
+        frame_order.par_iter().map(|&i| FramePeaks::from_slice(slice, i, min_intensity)).collect()
+    });
+
+    let mut offsets = Vec::with_capacity(frame_peaks.len() + 1);
+    offsets.push(0);
+    for peaks in frame_peaks.iter() {
+        offsets.push(offsets.last().unwrap() + peaks.len());
+    }
+    let num_peaks = *offsets.last().unwrap();
+
+    let frame_pairs: Vec<(usize, usize)> = (0..frame_peaks.len())
+        .flat_map(|a| (a..frame_peaks.len().min(a + rt_gap_frames + 2)).map(move |b| (a, b)))
+        .collect();
+
+    let links: Vec<Vec<(usize, usize)>> = pool.install(|| {
+        frame_pairs.par_iter().map(|&(a, b)| {
+            link_frames(&frame_peaks[a], &frame_peaks[b], a == b, ppm_tol, im_tol)
+                .into_iter()
+                .map(|(i, j)| (offsets[a] + i, offsets[b] + j))
+                .collect()
+        }).collect()
+    });
+
+    // union-find, the smaller index always becomes the root to keep the result deterministic
+    let mut parent: Vec<usize> = (0..num_peaks).collect();
+    for (x, y) in links.into_iter().flatten() {
+        let rx = find_root(&mut parent, x);
+        let ry = find_root(&mut parent, y);
+        if rx != ry {
+            let (low, high) = if rx < ry { (rx, ry) } else { (ry, rx) };
+            parent[high] = low;
+        }
+    }
+
+    let mut components: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+    for (frame_position, peaks) in frame_peaks.iter().enumerate() {
+        for i in 0..peaks.len() {
+            let root = find_root(&mut parent, offsets[frame_position] + i);
+            components.entry(root).or_default().push((frame_position, i));
+        }
+    }
+
+    let components: Vec<Vec<(usize, usize)>> = components.into_values()
+        .filter(|members| members.len() >= min_points)
+        .collect();
+
+    let mut features: Vec<Feature> = pool.install(|| {
+        components.par_iter().map(|members| build_feature(slice, &frame_peaks, members)).collect()
+    });
+
+    features.sort_by(|a, b| {
+        a.apex_retention_time.total_cmp(&b.apex_retention_time).then(a.apex_mz.total_cmp(&b.apex_mz))
+    });
+
+    features
+}
+
+/// build a feature from its member peaks, the apex is the most intense peak,
+/// apex m/z and mobility are intensity weighted means over the member peaks of the apex frame
+fn build_feature(slice: &TimsSlice, frame_peaks: &[FramePeaks], members: &[(usize, usize)]) -> Feature {
+    let mut total_intensity = 0.0;
+    let mut apex = members[0];
+
+    for &(f, i) in members.iter() {
+        let intensity = frame_peaks[f].intensity[i];
+        total_intensity += intensity;
+        if intensity > frame_peaks[apex.0].intensity[apex.1] {
+            apex = (f, i);
+        }
+    }
+
+    let apex_peaks = &frame_peaks[apex.0];
+    let mut weight_sum = 0.0;
+    let mut mz_sum = 0.0;
+    let mut mobility_sum = 0.0;
+
+    for &(f, i) in members.iter().filter(|(f, _)| *f == apex.0) {
+        let intensity = frame_peaks[f].intensity[i];
+        weight_sum += intensity;
+        mz_sum += frame_peaks[f].mz[i] * intensity;
+        mobility_sum += frame_peaks[f].mobility[i] * intensity;
+    }
+
+    let apex_frame = &slice.frames[apex_peaks.frame_index];
+    let mut peaks: Vec<(usize, usize)> = members.iter()
+        .map(|&(f, i)| (frame_peaks[f].frame_index, frame_peaks[f].peak_index[i]))
+        .collect();
+    peaks.sort();
+
+    Feature {
+        apex_mz: mz_sum / weight_sum,
+        apex_retention_time: apex_frame.ims_frame.retention_time,
+        apex_mobility: mobility_sum / weight_sum,
+        apex_frame_id: apex_frame.frame_id,
+        total_intensity,
+        charge: None,
+        peaks,
+    }
+}
+
+/// Assign charge states to features by looking for co-eluting features in isotope spacing
+///
+/// For every feature, charges from `max_charge` down to 1 are tried, the first charge for which
+/// another feature is found at +/- one isotope spacing within all tolerances is assigned.
+/// Features without such a partner keep their charge unassigned.
+///
+/// # Arguments
+///
+/// * `features` - The features, e.g. as returned by `detect_features`
+/// * `ppm_tol` - The m/z tolerance in ppm
+/// * `rt_tol` - The maximum apex retention time difference
+/// * `im_tol` - The maximum apex inverse mobility difference
+/// * `max_charge` - The maximum charge state to consider
+/// * `num_threads` - The number of threads to use
+///
+/// # Example
+///
+/// ```
+/// use rustdf::algorithm::feature::{assign_charge_states, Feature};
+///
+/// let feature = |mz: f64| Feature { apex_mz: mz, apex_retention_time: 10.0, apex_mobility: 1.0, apex_frame_id: 1,
+///     total_intensity: 1.0, charge: None, peaks: vec![] };
+/// let mut features = vec![feature(500.0), feature(500.5016774), feature(700.0)];
+/// assign_charge_states(&mut features, 10.0, 1.0, 0.01, 4, 2);
+/// assert_eq!(features[0].charge, Some(2));
+/// assert_eq!(features[1].charge, Some(2));
+/// assert_eq!(features[2].charge, None);
+/// ```
+pub fn assign_charge_states(
+    features: &mut [Feature],
+    ppm_tol: f64,
+    rt_tol: f64,
+    im_tol: f64,
+    max_charge: i32,
+    num_threads: usize,
+) {
+    let pool = parallel::pool(num_threads);
+
+    let mut order: Vec<usize> = (0..features.len()).collect();
+    order.sort_by(|&a, &b| features[a].apex_mz.total_cmp(&features[b].apex_mz));
+    let sorted_mz: Vec<f64> = order.iter().map(|&i| features[i].apex_mz).collect();
+
+    let charges: Vec<Option<i32>> = pool.install(|| {
+        features.par_iter().map(|feature| {
+            (1..=max_charge).rev().find(|&charge| {
+                let spacing = C13_C12_MASS_DIFFERENCE / charge as f64;
+                [feature.apex_mz + spacing, feature.apex_mz - spacing].iter().any(|&target| {
+                    let tol = target * ppm_tol * 1e-6;
+                    let start = sorted_mz.partition_point(|&x| x < target - tol);
+                    sorted_mz[start..].iter()
+                        .take_while(|&&x| x <= target + tol)
+                        .enumerate()
+                        .any(|(k, _)| {
+                            let other = &features[order[start + k]];
+                            (other.apex_retention_time - feature.apex_retention_time).abs() <= rt_tol
+                                && (other.apex_mobility - feature.apex_mobility).abs() <= im_tol
+                        })
+                })
+            })
+        }).collect()
+    });
+
+    for (feature, charge) in features.iter_mut().zip(charges) {
+        feature.charge = charge;
+    }
+}
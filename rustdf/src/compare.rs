@@ -0,0 +1,236 @@
+use crate::data::handle::TimsData;
+use crate::data::meta::read_meta_data_sql;
+use mscore::data::spectrum::MzSpectrum;
+use mscore::timstof::frame::TimsFrame;
+use rayon::prelude::*;
+use mscore::parallel;
+use std::error::Error;
+
+/// Settings of `compare_runs`
+#[derive(Debug, Clone)]
+pub struct RunComparisonParams {
+    /// MsMsType of the compared frames, 0 compares precursor frames
+    pub ms_ms_type: i64,
+    /// maximum retention time difference in seconds of matched frames
+    pub rt_tolerance: f64,
+    /// decimal places of the m/z bins of the spectral similarity
+    pub mz_resolution: i32,
+    /// number of frame pairs loaded at once
+    pub batch_size: usize,
+    pub num_threads: usize,
+}
+
+impl Default for RunComparisonParams {
+    fn default() -> Self {
+        RunComparisonParams {
+            ms_ms_type: 0,
+            rt_tolerance: 1.0,
+            mz_resolution: 1,
+            batch_size: 256,
+            num_threads: 4,
+        }
+    }
+}
+
+/// Similarity of two runs, one entry per matched frame pair ordered by the retention time of the real run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunComparison {
+    pub real_frame_id: Vec<u32>,
+    pub synthetic_frame_id: Vec<u32>,
+    pub real_retention_time: Vec<f64>,
+    pub synthetic_retention_time: Vec<f64>,
+    pub real_tic: Vec<f64>,
+    pub synthetic_tic: Vec<f64>,
+    /// cosine similarity of the frames summed over scans, see `MzSpectrum::cosine_similarity`
+    pub spectral_similarity: Vec<f64>,
+    /// Pearson correlation of the intensity summed per scan, NaN if a frame has the same intensity on all scans
+    pub mobility_correlation: Vec<f64>,
+    /// Pearson correlation of the TIC of matched frames over the run, NaN if fewer than two frames matched
+    pub tic_correlation: f64,
+}
+
+impl RunComparison {
+    pub fn len(&self) -> usize {
+        self.real_frame_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.real_frame_id.is_empty()
+    }
+}
+
+/// Pearson correlation coefficient, NaN if one of the series is constant or they are shorter than two values
+pub fn pearson_correlation(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len().min(y.len());
+    if n < 2 {
+        return f64::NAN;
+    }
+    let (x, y) = (&x[..n], &y[..n]);
+    let mean_x = x.iter().sum::<f64>() / n as f64;
+    let mean_y = y.iter().sum::<f64>() / n as f64;
+
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        covariance += (a - mean_x) * (b - mean_y);
+        variance_x += (a - mean_x).powi(2);
+        variance_y += (b - mean_y).powi(2);
+    }
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return f64::NAN;
+    }
+    covariance / (variance_x * variance_y).sqrt()
+}
+
+/// Match every frame of the real run to the synthetic frame closest in retention time
+///
+/// # Arguments
+///
+/// * `real` - (frame id, retention time) of the real run
+/// * `synthetic` - (frame id, retention time) of the synthetic run
+/// * `rt_tolerance` - maximum retention time difference of a match
+///
+/// # Returns
+///
+/// * Indices into `real` and `synthetic` of matched frames, ordered by real retention time, a synthetic frame
+///   can be matched by several real frames
+///
+pub fn match_frames_by_retention_time(real: &[(u32, f64)], synthetic: &[(u32, f64)], rt_tolerance: f64) -> Vec<(usize, usize)> {
+    let mut synthetic_order: Vec<usize> = (0..synthetic.len()).collect();
+    synthetic_order.sort_by(|&a, &b| synthetic[a].1.total_cmp(&synthetic[b].1));
+    let mut real_order: Vec<usize> = (0..real.len()).collect();
+    real_order.sort_by(|&a, &b| real[a].1.total_cmp(&real[b].1));
+
+    real_order.into_iter().filter_map(|r| {
+        let rt = real[r].1;
+        let position = synthetic_order.partition_point(|&s| synthetic[s].1 < rt);
+        let closest = [position.checked_sub(1), Some(position)].into_iter()
+            .flatten()
+            .filter_map(|p| synthetic_order.get(p).copied())
+            .min_by(|&a, &b| (synthetic[a].1 - rt).abs().total_cmp(&(synthetic[b].1 - rt).abs()))?;
+        ((synthetic[closest].1 - rt).abs() <= rt_tolerance).then_some((r, closest))
+    }).collect()
+}
+
+/// Intensity summed per scan, indexed by scan
+fn scan_profile(frame: &TimsFrame, num_scans: usize) -> Vec<f64> {
+    let mut profile = vec![0.0; num_scans];
+    for (scan, intensity) in frame.scan.iter().zip(&frame.ims_frame.intensity) {
+        profile[(*scan).max(0) as usize] += intensity;
+    }
+    profile
+}
+
+/// Real TIC, synthetic TIC, spectral similarity and mobility profile correlation of a frame pair
+pub fn compare_frames(real: &TimsFrame, synthetic: &TimsFrame, mz_resolution: i32) -> (f64, f64, f64, f64) {
+    let real_spectrum = MzSpectrum::new(real.ims_frame.mz.clone(), real.ims_frame.intensity.clone());
+    let synthetic_spectrum = MzSpectrum::new(synthetic.ims_frame.mz.clone(), synthetic.ims_frame.intensity.clone());
+
+    let num_scans = real.scan.iter().chain(&synthetic.scan).map(|&scan| scan.max(0) as usize + 1).max().unwrap_or(0);
+    (
+        real.ims_frame.intensity.iter().sum(),
+        synthetic.ims_frame.intensity.iter().sum(),
+        real_spectrum.cosine_similarity(&synthetic_spectrum, mz_resolution),
+        pearson_correlation(&scan_profile(real, num_scans), &scan_profile(synthetic, num_scans)),
+    )
+}
+
+/// Compare a simulated run with a real acquisition of the same sample, e.g. to calibrate the simulator
+///
+/// Frames are matched by retention time, see `match_frames_by_retention_time`, and loaded batch by batch
+///
+/// # Arguments
+///
+/// * `real` - the acquired run
+/// * `synthetic` - the simulated run
+/// * `params` - frame selection, tolerances and parallelism, see `RunComparisonParams`
+///
+/// # Returns
+///
+/// * Per frame pair metrics and the TIC correlation over the run
+///
+pub fn compare_runs(real: &dyn TimsData, synthetic: &dyn TimsData, params: &RunComparisonParams) -> Result<RunComparison, Box<dyn Error>> {
+    let frame_times = |data: &dyn TimsData| -> Result<Vec<(u32, f64)>, Box<dyn Error>> {
+        Ok(read_meta_data_sql(data.get_data_path())?
+            .into_iter()
+            .filter(|frame| frame.ms_ms_type == params.ms_ms_type)
+            .map(|frame| (frame.id as u32, frame.time))
+            .collect())
+    };
+    let real_times = frame_times(real)?;
+    let synthetic_times = frame_times(synthetic)?;
+    let matches = match_frames_by_retention_time(&real_times, &synthetic_times, params.rt_tolerance);
+
+    let pool = parallel::pool(params.num_threads);
+    let mut comparison = RunComparison::default();
+
+    for batch in matches.chunks(params.batch_size.max(1)) {
+        let real_frames = real.get_slice(batch.iter().map(|&(r, _)| real_times[r].0).collect(), params.num_threads).frames;
+        let synthetic_frames = synthetic.get_slice(batch.iter().map(|&(_, s)| synthetic_times[s].0).collect(), params.num_threads).frames;
+
+        let metrics: Vec<(f64, f64, f64, f64)> = pool.install(|| {
+            real_frames.par_iter().zip(synthetic_frames.par_iter())
+                .map(|(real_frame, synthetic_frame)| compare_frames(real_frame, synthetic_frame, params.mz_resolution))
+                .collect()
+        });
+
+        for (&(r, s), (real_tic, synthetic_tic, similarity, mobility_correlation)) in batch.iter().zip(metrics) {
+            comparison.real_frame_id.push(real_times[r].0);
+            comparison.synthetic_frame_id.push(synthetic_times[s].0);
+            comparison.real_retention_time.push(real_times[r].1);
+            comparison.synthetic_retention_time.push(synthetic_times[s].1);
+            comparison.real_tic.push(real_tic);
+            comparison.synthetic_tic.push(synthetic_tic);
+            comparison.spectral_similarity.push(similarity);
+            comparison.mobility_correlation.push(mobility_correlation);
+        }
+    }
+    comparison.tic_correlation = pearson_correlation(&comparison.real_tic, &comparison.synthetic_tic);
+
+    Ok(comparison)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mscore::data::spectrum::MsType;
+
+    #[test]
+    fn test_match_frames_by_retention_time() {
+        let real = [(3, 2.0), (1, 0.1), (2, 1.05), (4, 9.0)];
+        let synthetic = [(10, 1.0), (11, 0.0), (12, 2.2)];
+
+        let matches = match_frames_by_retention_time(&real, &synthetic, 0.5);
+        let ids: Vec<(u32, u32)> = matches.iter().map(|&(r, s)| (real[r].0, synthetic[s].0)).collect();
+        assert_eq!(ids, vec![(1, 11), (2, 10), (3, 12)]);
+        assert!(match_frames_by_retention_time(&real, &[], 0.5).is_empty());
+    }
+
+    #[test]
+    fn test_compare_frames() {
+        let frame = |scan: Vec<i32>, mz: Vec<f64>, intensity: Vec<f64>| {
+            let n = scan.len();
+            TimsFrame::new(1, MsType::Precursor, 1.0, scan, vec![1.0; n], vec![0; n], mz, intensity)
+        };
+        let real = frame(vec![1, 2, 3], vec![500.0, 600.0, 700.0], vec![10.0, 20.0, 10.0]);
+        let synthetic = frame(vec![1, 2, 3], vec![500.01, 600.02, 700.0], vec![20.0, 40.0, 20.0]);
+
+        let (real_tic, synthetic_tic, similarity, mobility_correlation) = compare_frames(&real, &synthetic, 1);
+        assert_eq!((real_tic, synthetic_tic), (40.0, 80.0));
+        assert!((similarity - 1.0).abs() < 1e-12);
+        assert!((mobility_correlation - 1.0).abs() < 1e-12);
+
+        let shifted = frame(vec![5, 6], vec![800.0, 900.0], vec![1.0, 1.0]);
+        let (_, _, similarity, mobility_correlation) = compare_frames(&real, &shifted, 1);
+        assert_eq!(similarity, 0.0);
+        assert!(mobility_correlation < 0.0);
+        assert!(compare_frames(&real, &TimsFrame::default(), 1).3.is_nan());
+    }
+
+    #[test]
+    fn test_pearson_correlation() {
+        assert!((pearson_correlation(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.5]) - 0.99795).abs() < 1e-5);
+        assert!((pearson_correlation(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]) + 1.0).abs() < 1e-12);
+        assert!(pearson_correlation(&[1.0], &[1.0]).is_nan());
+        assert!(pearson_correlation(&[1.0, 1.0], &[1.0, 2.0]).is_nan());
+    }
+}
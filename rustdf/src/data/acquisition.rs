@@ -1,19 +1,27 @@
+use mscore::data::spectrum::MsType;
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum AcquisitionMode {
     PRECURSOR,
     DDA,
     DIA,
+    PRM,
+    MIDIA,
+    MALDI,
     Unknown,
 }
 
 impl AcquisitionMode {
+    /// MsMsType of the frames that characterize the mode, MIDIA and MALDI share theirs with DIA and PRECURSOR
     pub fn to_i32(&self) -> i32 {
         match self {
             AcquisitionMode::PRECURSOR => 0,
             AcquisitionMode::DDA => 8,
             AcquisitionMode::DIA => 9,
+            AcquisitionMode::PRM => 10,
+            AcquisitionMode::MIDIA => 9,
+            AcquisitionMode::MALDI => 0,
             AcquisitionMode::Unknown => -1,
         }
     }
@@ -23,17 +31,107 @@ impl AcquisitionMode {
             AcquisitionMode::PRECURSOR => "PRECURSOR",
             AcquisitionMode::DDA => "DDA",
             AcquisitionMode::DIA => "DIA",
+            AcquisitionMode::PRM => "PRM",
+            AcquisitionMode::MIDIA => "MIDIA",
+            AcquisitionMode::MALDI => "MALDI",
             AcquisitionMode::Unknown => "UNKNOWN",
         }
     }
+
+    /// Detect the acquisition mode of a run
+    ///
+    /// # Arguments
+    ///
+    /// * `table_names` - Tables of the analysis.tdf, runs of the special modes write their own metadata tables:
+    ///   `MaldiFrameInfo` for MALDI, `PrmFrameMsMsInfo` for PRM and `MidiaFrameMsMsInfo` for MIDIA
+    /// * `scan_mode` - ScanMode of the first frame, 8 for DDA, 9 for DIA and 10 for PRM
+    ///
+    pub fn detect(table_names: &[String], scan_mode: i64) -> AcquisitionMode {
+        let has_table = |name: &str| table_names.iter().any(|table| table == name);
+
+        if has_table("MaldiFrameInfo") {
+            AcquisitionMode::MALDI
+        } else if has_table("PrmFrameMsMsInfo") {
+            AcquisitionMode::PRM
+        } else if has_table("MidiaFrameMsMsInfo") {
+            AcquisitionMode::MIDIA
+        } else {
+            match scan_mode {
+                8 => AcquisitionMode::DDA,
+                9 => AcquisitionMode::DIA,
+                10 => AcquisitionMode::PRM,
+                _ => AcquisitionMode::Unknown,
+            }
+        }
+    }
+
+    /// Type of a frame of a run acquired in this mode
+    ///
+    /// # Arguments
+    ///
+    /// * `ms_ms_type` - MsMsType of the frame, MIDIA fragment frames and MALDI frames only differ
+    ///   from diaPASEF and precursor frames by the mode of their run
+    ///
+    pub fn frame_ms_type(&self, ms_ms_type: i64) -> MsType {
+        match (self, MsType::new(ms_ms_type as i32)) {
+            (AcquisitionMode::MIDIA, MsType::FragmentDia) => MsType::FragmentMidia,
+            (AcquisitionMode::MALDI, MsType::Precursor) => MsType::Maldi,
+            (_, ms_type) => ms_type,
+        }
+    }
+
+    /// Make sure a dataset type is used for a run of a mode it can handle, runs of unknown mode are accepted
+    ///
+    /// # Arguments
+    ///
+    /// * `accepted` - Modes the dataset type can handle
+    /// * `data_path` - Path of the run, used in the error
+    ///
+    pub fn check(&self, accepted: &[AcquisitionMode], data_path: &str) -> Result<(), AcquisitionModeMismatch> {
+        if *self == AcquisitionMode::Unknown || accepted.contains(self) {
+            Ok(())
+        } else {
+            Err(AcquisitionModeMismatch {
+                data_path: data_path.to_string(),
+                expected: accepted.to_vec(),
+                found: self.clone(),
+            })
+        }
+    }
+}
+
+/// A run was opened with a dataset type that does not support its acquisition mode
+#[derive(Debug, Clone)]
+pub struct AcquisitionModeMismatch {
+    pub data_path: String,
+    pub expected: Vec<AcquisitionMode>,
+    pub found: AcquisitionMode,
+}
+
+impl Display for AcquisitionModeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected: Vec<&str> = self.expected.iter().map(|mode| mode.to_str()).collect();
+        write!(
+            f,
+            "{} was acquired in {} mode, expected {}",
+            self.data_path,
+            self.found,
+            expected.join(" or ")
+        )
+    }
 }
 
+impl std::error::Error for AcquisitionModeMismatch {}
+
 impl Display for AcquisitionMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AcquisitionMode::PRECURSOR => write!(f, "PRECURSOR"),
             AcquisitionMode::DDA => write!(f, "DDA"),
             AcquisitionMode::DIA => write!(f, "DIA"),
+            AcquisitionMode::PRM => write!(f, "PRM"),
+            AcquisitionMode::MIDIA => write!(f, "MIDIA"),
+            AcquisitionMode::MALDI => write!(f, "MALDI"),
             AcquisitionMode::Unknown => write!(f, "UNKNOWN"),
         }
     }
@@ -45,6 +143,7 @@ impl From<i32> for AcquisitionMode {
             0 => AcquisitionMode::PRECURSOR,
             8 => AcquisitionMode::DDA,
             9 => AcquisitionMode::DIA,
+            10 => AcquisitionMode::PRM,
             _ => AcquisitionMode::Unknown,
         }
     }
@@ -56,7 +155,45 @@ impl From<&str> for AcquisitionMode {
             "PRECURSOR" => AcquisitionMode::PRECURSOR,
             "DDA" => AcquisitionMode::DDA,
             "DIA" => AcquisitionMode::DIA,
+            "PRM" => AcquisitionMode::PRM,
+            "MIDIA" => AcquisitionMode::MIDIA,
+            "MALDI" => AcquisitionMode::MALDI,
             _ => AcquisitionMode::Unknown,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_detect_acquisition_mode() {
+        let base = ["Frames", "GlobalMetadata"];
+        assert_eq!(AcquisitionMode::detect(&tables(&base), 8), AcquisitionMode::DDA);
+        assert_eq!(AcquisitionMode::detect(&tables(&base), 9), AcquisitionMode::DIA);
+        assert_eq!(AcquisitionMode::detect(&tables(&base), 0), AcquisitionMode::Unknown);
+        assert_eq!(AcquisitionMode::detect(&tables(&["Frames", "PrmFrameMsMsInfo"]), 8), AcquisitionMode::PRM);
+        assert_eq!(AcquisitionMode::detect(&tables(&["Frames", "DiaFrameMsMsWindows", "MidiaFrameMsMsInfo"]), 9), AcquisitionMode::MIDIA);
+        assert_eq!(AcquisitionMode::detect(&tables(&["Frames", "MaldiFrameInfo"]), 0), AcquisitionMode::MALDI);
+    }
+
+    #[test]
+    fn test_frame_ms_type_and_check() {
+        assert_eq!(AcquisitionMode::MIDIA.frame_ms_type(9), MsType::FragmentMidia);
+        assert_eq!(AcquisitionMode::MIDIA.frame_ms_type(0), MsType::Precursor);
+        assert_eq!(AcquisitionMode::MALDI.frame_ms_type(0), MsType::Maldi);
+        assert_eq!(AcquisitionMode::PRM.frame_ms_type(10), MsType::FragmentPrm);
+        assert_eq!(AcquisitionMode::DIA.frame_ms_type(9), MsType::FragmentDia);
+
+        let dia = [AcquisitionMode::DIA, AcquisitionMode::MIDIA];
+        assert!(AcquisitionMode::MIDIA.check(&dia, "run.d").is_ok());
+        assert!(AcquisitionMode::Unknown.check(&dia, "run.d").is_ok());
+        let error = AcquisitionMode::PRM.check(&dia, "run.d").unwrap_err();
+        assert_eq!(error.to_string(), "run.d was acquired in PRM mode, expected DIA or MIDIA");
+    }
+}
@@ -0,0 +1,447 @@
+use mscore::data::serialization::BinarySerializable;
+use mscore::timstof::frame::TimsFrame;
+use mscore::timstof::slice::TimsSlice;
+use rayon::prelude::*;
+use mscore::parallel;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// first bytes of every slice cache file
+pub const SLICE_CACHE_MAGIC: &[u8; 8] = b"TIMSSLC\0";
+
+/// version of the layout written by `SliceCache::write_cache`
+pub const SLICE_CACHE_VERSION: u32 = 1;
+
+const HEADER_SIZE: u64 = 8 + 4 + 8;
+const TABLE_ENTRY_SIZE: u64 = 4 + 8 + 8 + 8 + 8 + 4;
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Reasons why a slice cache could not be written or read
+#[derive(Debug)]
+pub enum SliceCacheError {
+    Io(std::io::Error),
+    /// the file does not start with `SLICE_CACHE_MAGIC`
+    NotASliceCache(PathBuf),
+    UnsupportedVersion(u32),
+    /// the frame table does not match its checksum
+    CorruptedTable,
+    /// the peak block of a frame does not match its checksum
+    CorruptedBlock {
+        frame_id: i32,
+        expected: u32,
+        found: u32,
+    },
+    /// a block matched its checksum but could not be decoded
+    Decode {
+        frame_id: i32,
+        message: String,
+    },
+    UnknownFrame(i32),
+}
+
+impl Display for SliceCacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SliceCacheError::Io(e) => write!(f, "slice cache io error: {}", e),
+            SliceCacheError::NotASliceCache(path) => {
+                write!(f, "{} is not a slice cache file", path.display())
+            }
+            SliceCacheError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported slice cache version {}, expected {}",
+                version, SLICE_CACHE_VERSION
+            ),
+            SliceCacheError::CorruptedTable => {
+                write!(f, "slice cache is corrupted: frame table checksum mismatch")
+            }
+            SliceCacheError::CorruptedBlock {
+                frame_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "slice cache is corrupted: checksum of frame {} is {:08x}, expected {:08x}",
+                frame_id, found, expected
+            ),
+            SliceCacheError::Decode { frame_id, message } => {
+                write!(
+                    f,
+                    "slice cache frame {} could not be decoded: {}",
+                    frame_id, message
+                )
+            }
+            SliceCacheError::UnknownFrame(frame_id) => {
+                write!(f, "frame {} is not part of the slice cache", frame_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SliceCacheError {}
+
+impl From<std::io::Error> for SliceCacheError {
+    fn from(e: std::io::Error) -> Self {
+        SliceCacheError::Io(e)
+    }
+}
+
+/// location of the peak block of a frame inside a slice cache file
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceCacheEntry {
+    pub frame_id: i32,
+    pub retention_time: f64,
+    pub num_peaks: u64,
+    pub offset: u64,
+    pub length: u64,
+    pub checksum: u32,
+}
+
+impl SliceCacheEntry {
+    fn write_to(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.frame_id.to_le_bytes());
+        buffer.extend_from_slice(&self.retention_time.to_le_bytes());
+        buffer.extend_from_slice(&self.num_peaks.to_le_bytes());
+        buffer.extend_from_slice(&self.offset.to_le_bytes());
+        buffer.extend_from_slice(&self.length.to_le_bytes());
+        buffer.extend_from_slice(&self.checksum.to_le_bytes());
+    }
+
+    fn read_from(bytes: &[u8]) -> Self {
+        let u64_at = |start: usize| u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap());
+        SliceCacheEntry {
+            frame_id: i32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            retention_time: f64::from_le_bytes(bytes[4..12].try_into().unwrap()),
+            num_peaks: u64_at(12),
+            offset: u64_at(20),
+            length: u64_at(28),
+            checksum: u32::from_le_bytes(bytes[36..40].try_into().unwrap()),
+        }
+    }
+}
+
+/// Save slices to disk and load them again without going back to the raw data
+///
+/// The file starts with `SLICE_CACHE_MAGIC`, the format version and the number of frames, followed by
+/// the frame table (frame id, retention time, number of peaks, offset, length and crc32 of the block)
+/// and its own crc32. Every frame is stored as a zstd compressed block of its binary encoding.
+pub trait SliceCache: Sized {
+    /// Write the slice to a cache file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to create, an existing file is replaced
+    ///
+    fn write_cache(&self, path: &Path) -> Result<(), SliceCacheError>;
+
+    /// Read all frames of a cache file written by `write_cache`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Cache file
+    /// * `num_threads` - Number of threads blocks are decompressed with
+    ///
+    fn read_cache(path: &Path, num_threads: usize) -> Result<Self, SliceCacheError>;
+}
+
+impl SliceCache for TimsSlice {
+    fn write_cache(&self, path: &Path) -> Result<(), SliceCacheError> {
+        let mut blocks = Vec::with_capacity(self.frames.len());
+        let mut entries = Vec::with_capacity(self.frames.len());
+        let mut offset = HEADER_SIZE + TABLE_ENTRY_SIZE * self.frames.len() as u64 + 4;
+
+        for frame in &self.frames {
+            let block = zstd::encode_all(frame.to_bytes().as_slice(), COMPRESSION_LEVEL)?;
+            entries.push(SliceCacheEntry {
+                frame_id: frame.frame_id,
+                retention_time: frame.ims_frame.retention_time,
+                num_peaks: frame.ims_frame.mz.len() as u64,
+                offset,
+                length: block.len() as u64,
+                checksum: crc32fast::hash(&block),
+            });
+            offset += block.len() as u64;
+            blocks.push(block);
+        }
+
+        let mut table = Vec::with_capacity((TABLE_ENTRY_SIZE as usize) * entries.len());
+        for entry in &entries {
+            entry.write_to(&mut table);
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(SLICE_CACHE_MAGIC)?;
+        writer.write_all(&SLICE_CACHE_VERSION.to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        writer.write_all(&table)?;
+        writer.write_all(&crc32fast::hash(&table).to_le_bytes())?;
+        for block in &blocks {
+            writer.write_all(block)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn read_cache(path: &Path, num_threads: usize) -> Result<Self, SliceCacheError> {
+        let reader = SliceCacheReader::open(path)?;
+        let entries: Vec<&SliceCacheEntry> = reader.entries().iter().collect();
+        Ok(TimsSlice {
+            frames: reader.read_entries(&entries, num_threads)?,
+        })
+    }
+}
+
+/// Lazy access to a slice cache file, only the frame table is read on open
+pub struct SliceCacheReader {
+    path: PathBuf,
+    entries: Vec<SliceCacheEntry>,
+}
+
+impl SliceCacheReader {
+    /// Read and verify the header and frame table of a cache file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Cache file written by `SliceCache::write_cache`
+    ///
+    pub fn open(path: &Path) -> Result<Self, SliceCacheError> {
+        let mut file = File::open(path)?;
+
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| SliceCacheError::NotASliceCache(path.to_path_buf()))?;
+        if &header[0..8] != SLICE_CACHE_MAGIC {
+            return Err(SliceCacheError::NotASliceCache(path.to_path_buf()));
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != SLICE_CACHE_VERSION {
+            return Err(SliceCacheError::UnsupportedVersion(version));
+        }
+        let num_frames = u64::from_le_bytes(header[12..20].try_into().unwrap());
+
+        // a corrupted frame count must not turn into a huge allocation
+        let file_size = file.metadata()?.len();
+        let table_size = num_frames
+            .checked_mul(TABLE_ENTRY_SIZE)
+            .filter(|&size| size + 4 <= file_size)
+            .ok_or(SliceCacheError::CorruptedTable)?;
+        let mut table = vec![0u8; table_size as usize + 4];
+        file.read_exact(&mut table)
+            .map_err(|_| SliceCacheError::CorruptedTable)?;
+        let (table, checksum) = table.split_at(table_size as usize);
+        if crc32fast::hash(table) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(SliceCacheError::CorruptedTable);
+        }
+
+        let entries = table
+            .chunks_exact(TABLE_ENTRY_SIZE as usize)
+            .map(SliceCacheEntry::read_from)
+            .collect();
+
+        Ok(SliceCacheReader {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// frames of the cache in the order they were written
+    pub fn entries(&self) -> &[SliceCacheEntry] {
+        &self.entries
+    }
+
+    /// Read, verify and decode the peak block of a single frame
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - Id of a frame of the cache
+    ///
+    pub fn read_frame(&self, frame_id: i32) -> Result<TimsFrame, SliceCacheError> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.frame_id == frame_id)
+            .ok_or(SliceCacheError::UnknownFrame(frame_id))?;
+        self.read_entry(&mut File::open(&self.path)?, entry)
+    }
+
+    /// Read the blocks of several frames in parallel, every thread uses its own file handle
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - Ids of frames of the cache, the result has the same order
+    /// * `num_threads` - Number of threads blocks are decompressed with
+    ///
+    pub fn read_frames(
+        &self,
+        frame_ids: &[i32],
+        num_threads: usize,
+    ) -> Result<Vec<TimsFrame>, SliceCacheError> {
+        let entries = frame_ids
+            .iter()
+            .map(|&frame_id| {
+                self.entries
+                    .iter()
+                    .find(|e| e.frame_id == frame_id)
+                    .ok_or(SliceCacheError::UnknownFrame(frame_id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.read_entries(&entries, num_threads)
+    }
+
+    fn read_entries(
+        &self,
+        entries: &[&SliceCacheEntry],
+        num_threads: usize,
+    ) -> Result<Vec<TimsFrame>, SliceCacheError> {
+        let pool = parallel::pool(num_threads);
+        pool.install(|| {
+            entries
+                .par_chunks(entries.len().div_ceil(num_threads.max(1)).max(1))
+                .map(|chunk| {
+                    let mut file = File::open(&self.path)?;
+                    chunk
+                        .iter()
+                        .map(|entry| self.read_entry(&mut file, entry))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(|chunks| chunks.into_iter().flatten().collect())
+        })
+    }
+
+    fn read_entry(
+        &self,
+        file: &mut File,
+        entry: &SliceCacheEntry,
+    ) -> Result<TimsFrame, SliceCacheError> {
+        let corrupted = |found| SliceCacheError::CorruptedBlock {
+            frame_id: entry.frame_id,
+            expected: entry.checksum,
+            found,
+        };
+
+        if entry.offset + entry.length > file.metadata()?.len() {
+            return Err(corrupted(0));
+        }
+        let mut block = vec![0u8; entry.length as usize];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.read_exact(&mut block)?;
+
+        let checksum = crc32fast::hash(&block);
+        if checksum != entry.checksum {
+            return Err(corrupted(checksum));
+        }
+
+        let decode_error = |message: String| SliceCacheError::Decode {
+            frame_id: entry.frame_id,
+            message,
+        };
+        let bytes = zstd::decode_all(block.as_slice()).map_err(|e| decode_error(e.to_string()))?;
+        TimsFrame::from_bytes(&bytes).map_err(|e| decode_error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mscore::data::spectrum::MsType;
+
+    fn frame(frame_id: i32, num_peaks: usize) -> TimsFrame {
+        TimsFrame::new(
+            frame_id,
+            if frame_id % 2 == 1 {
+                MsType::Precursor
+            } else {
+                MsType::FragmentDda
+            },
+            frame_id as f64 * 0.1,
+            (0..num_peaks).map(|i| (i / 10) as i32).collect(),
+            (0..num_peaks)
+                .map(|i| 1.6 - (i / 10) as f64 * 0.001)
+                .collect(),
+            (0..num_peaks).map(|i| i as i32 * 7).collect(),
+            (0..num_peaks).map(|i| 100.0 + i as f64 * 0.017).collect(),
+            (0..num_peaks).map(|i| (i % 311) as f64).collect(),
+        )
+    }
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rustdf_{}_{}.slice", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_slice_cache_round_trip() {
+        let slice = TimsSlice {
+            frames: vec![frame(1, 500), frame(2, 0), frame(3, 1200), frame(4, 37)],
+        };
+        let path = cache_path("round_trip");
+        slice.write_cache(&path).unwrap();
+
+        let read = TimsSlice::read_cache(&path, 3).unwrap();
+        assert_eq!(read.frames.len(), slice.frames.len());
+        for (a, b) in read.frames.iter().zip(&slice.frames) {
+            assert_eq!(a.frame_id, b.frame_id);
+            assert_eq!(a.ms_type, b.ms_type);
+            assert_eq!((&a.scan, &a.tof), (&b.scan, &b.tof));
+            assert_eq!(a.ims_frame.retention_time, b.ims_frame.retention_time);
+            assert_eq!(a.ims_frame.mobility, b.ims_frame.mobility);
+            assert_eq!(a.ims_frame.mz, b.ims_frame.mz);
+            assert_eq!(a.ims_frame.intensity, b.ims_frame.intensity);
+        }
+
+        let reader = SliceCacheReader::open(&path).unwrap();
+        assert_eq!(reader.entries()[2].num_peaks, 1200);
+        assert_eq!(
+            reader.read_frame(3).unwrap().ims_frame.mz,
+            slice.frames[2].ims_frame.mz
+        );
+        let frames = reader.read_frames(&[4, 1], 2).unwrap();
+        assert_eq!((frames[0].frame_id, frames[1].frame_id), (4, 1));
+        assert!(matches!(
+            reader.read_frame(9),
+            Err(SliceCacheError::UnknownFrame(9))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_slice_cache_detects_corruption() {
+        let slice = TimsSlice {
+            frames: vec![frame(1, 300), frame(2, 300)],
+        };
+        let path = cache_path("corruption");
+        slice.write_cache(&path).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let second = SliceCacheReader::open(&path).unwrap().entries()[1].clone();
+        bytes[second.offset as usize + 5] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let reader = SliceCacheReader::open(&path).unwrap();
+        assert!(reader.read_frame(1).is_ok());
+        let error = reader.read_frame(2).unwrap_err();
+        assert!(matches!(
+            error,
+            SliceCacheError::CorruptedBlock { frame_id: 2, .. }
+        ));
+        assert!(error.to_string().contains("frame 2"));
+        assert!(TimsSlice::read_cache(&path, 2).is_err());
+
+        bytes[HEADER_SIZE as usize + 1] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            SliceCacheReader::open(&path),
+            Err(SliceCacheError::CorruptedTable)
+        ));
+
+        std::fs::write(&path, b"not a cache").unwrap();
+        assert!(matches!(
+            SliceCacheReader::open(&path),
+            Err(SliceCacheError::NotASliceCache(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
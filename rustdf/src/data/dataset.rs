@@ -1,8 +1,13 @@
 use crate::data::acquisition::AcquisitionMode;
 use crate::data::handle::{IndexConverter, TimsData, TimsDataLoader};
 use crate::data::meta::{read_global_meta_sql, read_meta_data_sql};
+use crate::data::projection::{FrameColumns, PartialTimsFrame};
+use mscore::data::spectrum::MzAxis;
 use mscore::timstof::frame::{RawTimsFrame, TimsFrame};
+use rayon::prelude::*;
 use mscore::timstof::slice::TimsSlice;
+use mscore::timstof::spectrum::TimsSpectrum;
+use std::ops::Range;
 
 pub struct TimsDataset {
     pub loader: TimsDataLoader,
@@ -54,6 +59,94 @@ impl TimsDataset {
 
         TimsDataset { loader }
     }
+
+    /// Get a m/z axis covering the whole run, so frames vectorized on it share their columns,
+    /// see `TimsFrame::vectorize_on_axis`
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - Decimal places of the m/z bins
+    ///
+    /// # Returns
+    ///
+    /// * The axis of the acquisition range stored in the global metadata, if the metadata has none,
+    ///   the range of the m/z values of all frames, which are scanned in parallel
+    ///
+    pub fn global_mz_axis(&self, resolution: i32) -> MzAxis {
+        let global_meta_data = read_global_meta_sql(self.get_data_path()).unwrap();
+        let (mz_lower, mz_upper) = (
+            global_meta_data.mz_acquisition_range_lower,
+            global_meta_data.mz_acquisition_range_upper,
+        );
+        if mz_lower >= 0.0 && mz_lower < mz_upper {
+            return MzAxis::new(resolution, mz_lower, mz_upper);
+        }
+
+        let (mz_min, mz_max) = (1..=self.get_frame_count() as u32)
+            .into_par_iter()
+            .map(|frame_id| {
+                let mz = self
+                    .get_frame_projected(frame_id, FrameColumns::MZ)
+                    .mz
+                    .unwrap_or_default();
+                mz.iter().fold((f64::MAX, f64::MIN), |(min, max), &mz| {
+                    (min.min(mz), max.max(mz))
+                })
+            })
+            .reduce(
+                || (f64::MAX, f64::MIN),
+                |(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)),
+            );
+        match mz_min <= mz_max {
+            true => MzAxis::new(resolution, mz_min, mz_max),
+            // no peaks at all
+            false => MzAxis::new(resolution, 0.0, 0.0),
+        }
+    }
+
+    /// Get the spectrum of a single scan of a frame, see `get_spectra`
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - The id of the frame
+    /// * `scan` - The zero based scan
+    ///
+    /// # Returns
+    ///
+    /// * The spectrum, None if the scan has no peaks or the frame does not exist
+    ///
+    pub fn get_spectrum(&self, frame_id: u32, scan: u32) -> Option<TimsSpectrum> {
+        self.get_spectra(frame_id, scan..scan.saturating_add(1), 1).pop()
+    }
+
+    /// Get the spectra of a range of scans of a frame, peaks of other scans are not decoded
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - The id of the frame
+    /// * `scans` - The zero based scans, scans beyond the last scan of the frame are ignored
+    /// * `min_peaks` - The minimum number of peaks of a returned spectrum
+    ///
+    /// # Returns
+    ///
+    /// * One spectrum per scan with at least `min_peaks` peaks ordered by scan, empty if the frame does not exist
+    ///
+    pub fn get_spectra(&self, frame_id: u32, scans: Range<u32>, min_peaks: usize) -> Vec<TimsSpectrum> {
+        if frame_id == 0 || frame_id as i32 > self.get_frame_count() {
+            return Vec::new();
+        }
+        let frame: TimsFrame = self
+            .loader
+            .decode_scans(frame_id, scans)
+            .project(self.loader.get_index_converter(), FrameColumns::ALL)
+            .into();
+
+        frame
+            .to_tims_spectra()
+            .into_iter()
+            .filter(|spectrum| spectrum.spectrum.index.len() >= min_peaks.max(1))
+            .collect()
+    }
 }
 
 impl TimsData for TimsDataset {
@@ -65,10 +158,23 @@ impl TimsData for TimsDataset {
     fn get_raw_frame(&self, frame_id: u32) -> RawTimsFrame {
         self.loader.get_raw_frame(frame_id)
     }
+    // Get only the requested columns of a frame
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.loader.get_frame_projected(frame_id, columns)
+    }
     // Get a collection of frames by their ids
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
         self.loader.get_slice(frame_ids, num_threads)
     }
+    // Get only the requested columns of a collection of frames
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        self.loader.get_slice_projected(frame_ids, columns, num_threads)
+    }
     // Get the acquisition mode, DDA or DIA
     fn get_acquisition_mode(&self) -> AcquisitionMode {
         self.loader.get_acquisition_mode().clone()
@@ -79,7 +185,7 @@ impl TimsData for TimsDataset {
     }
     // Get the path to the data
     fn get_data_path(&self) -> &str {
-        &self.loader.get_data_path()
+        self.loader.get_data_path()
     }
 }
 
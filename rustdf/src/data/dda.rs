@@ -1,23 +1,251 @@
-use crate::data::acquisition::AcquisitionMode;
+use crate::data::acquisition::{AcquisitionMode, AcquisitionModeMismatch};
 use crate::data::handle::{IndexConverter, TimsData, TimsDataLoader};
 use crate::data::meta::{
     read_dda_precursor_meta, read_global_meta_sql, read_meta_data_sql, read_pasef_frame_ms_ms_info,
-    DDAPrecursor, PasefMsMsMeta,
+    read_table_names, DDAPrecursor, PasefMsMsMeta,
 };
+use crate::data::projection::{FrameColumns, PartialTimsFrame};
+use mscore::algorithm::isotope::score_isotope_fit;
+use mscore::chemistry::constants::C13_C12_MASS_DIFFERENCE;
+use mscore::data::spectrum::MzSpectrum;
 use mscore::timstof::frame::{RawTimsFrame, TimsFrame};
 use mscore::timstof::slice::TimsSlice;
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel;
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 
 #[derive(Clone)]
 pub struct PASEFDDAFragment {
     pub frame_id: u32,
     pub precursor_id: u32,
     pub collision_energy: f64,
+    /// isolation window of the PASEF event, NaN if unknown
+    pub isolation_mz: f64,
+    pub isolation_width: f64,
+    /// first and last scan of the PASEF event, the selected fragment spans 5% more on either side
+    pub scan_begin: u32,
+    pub scan_end: u32,
     pub selected_fragment: TimsFrame,
 }
 
+impl PASEFDDAFragment {
+    /// Create a fragment without PASEF metadata, the isolation window is NaN and the scan range empty
+    pub fn new(frame_id: u32, precursor_id: u32, collision_energy: f64, selected_fragment: TimsFrame) -> Self {
+        PASEFDDAFragment {
+            frame_id,
+            precursor_id,
+            collision_energy,
+            isolation_mz: f64::NAN,
+            isolation_width: f64::NAN,
+            scan_begin: 0,
+            scan_end: 0,
+            selected_fragment,
+        }
+    }
+}
+
+/// Isolation purity of a DDA precursor in its parent MS1 frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrecursorPurity {
+    pub precursor_id: i64,
+    /// fraction of the intensity in the isolation window that belongs to the isotope envelope of the precursor,
+    /// NaN if the window is empty
+    pub purity: f64,
+    /// summed intensity of all peaks within the m/z and scan range of the isolation window
+    pub total_window_intensity: f64,
+    /// summed intensity of the window peaks matched to an isotope of the precursor
+    pub envelope_intensity: f64,
+}
+
+/// Isolation window and isotope envelope of a precursor
+struct PurityTarget {
+    precursor_id: i64,
+    envelope_mz: f64,
+    charge: Option<i64>,
+    mz_lower: f64,
+    mz_upper: f64,
+    scan_begin: i32,
+    scan_end: i32,
+}
+
+impl PurityTarget {
+    /// Sum the intensity within the isolation window and of the window peaks within `ppm` of an isotope
+    ///
+    /// Isotopes are expected at `envelope_mz + k * C13_C12_MASS_DIFFERENCE / charge` for k below `num_isotopes`,
+    /// without a charge only peaks at `envelope_mz` itself are matched.
+    fn purity(&self, frame: &PartialTimsFrame, ppm: f64, num_isotopes: usize) -> PrecursorPurity {
+        let (Some(scan), Some(mz), Some(intensity)) = (&frame.scan, &frame.mz, &frame.intensity) else {
+            return self.empty();
+        };
+        let isotopes: Vec<f64> = match self.charge {
+            Some(charge) if charge > 0 => (0..num_isotopes.max(1))
+                .map(|k| self.envelope_mz + k as f64 * C13_C12_MASS_DIFFERENCE / charge as f64)
+                .collect(),
+            _ => vec![self.envelope_mz],
+        };
+
+        let mut total_window_intensity = 0.0;
+        let mut envelope_intensity = 0.0;
+        for ((&scan, &mz), &intensity) in scan.iter().zip(mz).zip(intensity) {
+            if scan < self.scan_begin || scan > self.scan_end || mz < self.mz_lower || mz > self.mz_upper {
+                continue;
+            }
+            total_window_intensity += intensity;
+            if isotopes.iter().any(|&isotope| (mz - isotope).abs() <= isotope * ppm * 1e-6) {
+                envelope_intensity += intensity;
+            }
+        }
+
+        PrecursorPurity {
+            precursor_id: self.precursor_id,
+            purity: match total_window_intensity > 0.0 {
+                true => envelope_intensity / total_window_intensity,
+                false => f64::NAN,
+            },
+            total_window_intensity,
+            envelope_intensity,
+        }
+    }
+
+    fn empty(&self) -> PrecursorPurity {
+        PrecursorPurity {
+            precursor_id: self.precursor_id,
+            purity: f64::NAN,
+            total_window_intensity: 0.0,
+            envelope_intensity: 0.0,
+        }
+    }
+}
+
+/// Number of isotopes scored per candidate envelope when refining precursors
+const REFINE_NUM_ISOTOPES: usize = 4;
+
+/// Outcome of re-estimating the monoisotopic m/z and charge of a precursor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefinementStatus {
+    /// the best envelope has the monoisotopic m/z and charge of the precursor table
+    Confirmed,
+    /// the best envelope differs from the precursor table
+    Refined,
+    /// different envelopes fit equally well, the original annotation is kept
+    Ambiguous,
+    /// no envelope with a matched monoisotopic peak and at least one further isotope, the original annotation is kept
+    NoEnvelope,
+}
+
+impl Display for RefinementStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RefinementStatus::Confirmed => "confirmed",
+            RefinementStatus::Refined => "refined",
+            RefinementStatus::Ambiguous => "ambiguous",
+            RefinementStatus::NoEnvelope => "no_envelope",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Monoisotopic m/z and charge of a DDA precursor re-estimated from its isotope envelope
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefinedPrecursor {
+    pub precursor_id: i64,
+    /// values of the precursor table, a charge of 0 is reported as None
+    pub original_mono_mz: Option<f64>,
+    pub original_charge: Option<i64>,
+    /// the best envelope, the original values unless the status is `Confirmed` or `Refined`
+    pub mono_mz: Option<f64>,
+    pub charge: Option<i64>,
+    /// chi square of the best envelope against the averagine model, see `score_isotope_fit`, NaN without envelope
+    pub score: f64,
+    pub num_matched: usize,
+    pub status: RefinementStatus,
+}
+
+/// Precursor and the scans of its PASEF events whose envelope is re-estimated
+struct RefineTarget {
+    precursor_id: i64,
+    mono_mz: Option<f64>,
+    charge: Option<i64>,
+    /// the monoisotopic m/z if known, otherwise the most intense m/z
+    reference_mz: f64,
+    scan_begin: i32,
+    scan_end: i32,
+}
+
+impl RefineTarget {
+    /// Score envelopes of charge 1 to `max_charge` whose monoisotopic peak lies 0 to 2 isotopes below the
+    /// reference m/z against the peaks of the precursor scans, summed per tof index
+    ///
+    /// Candidates need a matched monoisotopic peak and at least two matched isotopes, the one matching the most
+    /// isotopes wins, ties are broken by the chi square of the fit.
+    fn refine(&self, frame: &PartialTimsFrame, ppm: f64, max_charge: i32) -> RefinedPrecursor {
+        let mut peaks: BTreeMap<i32, (f64, f64)> = BTreeMap::new();
+        if let (Some(scan), Some(tof), Some(mz), Some(intensity)) = (&frame.scan, &frame.tof, &frame.mz, &frame.intensity) {
+            let mz_lower = self.reference_mz - 2.0 * C13_C12_MASS_DIFFERENCE - 1.0;
+            let mz_upper = self.reference_mz + REFINE_NUM_ISOTOPES as f64 * C13_C12_MASS_DIFFERENCE + 1.0;
+            for (((&scan, &tof), &mz), &intensity) in scan.iter().zip(tof).zip(mz).zip(intensity) {
+                if scan >= self.scan_begin && scan <= self.scan_end && mz >= mz_lower && mz <= mz_upper {
+                    peaks.entry(tof).or_insert((mz, 0.0)).1 += intensity;
+                }
+            }
+        }
+        let (mz, intensity): (Vec<f64>, Vec<f64>) = peaks.into_values().unzip();
+        let spectrum = MzSpectrum::new(mz, intensity);
+
+        // (charge, shift, num_matched, chi square) of every candidate with a matched monoisotopic peak
+        let mut candidates: Vec<(i64, i32, usize, f64)> = Vec::new();
+        for charge in 1..=max_charge {
+            for shift in -2..=0 {
+                let mono_mz = self.reference_mz + shift as f64 * C13_C12_MASS_DIFFERENCE / charge as f64;
+                let fit = score_isotope_fit(&spectrum, mono_mz, charge, REFINE_NUM_ISOTOPES, ppm);
+                if fit.ratios[0] > 0.0 && fit.num_matched >= 2 {
+                    candidates.push((charge as i64, shift, fit.num_matched, fit.chi_square));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.2.cmp(&a.2).then(a.3.total_cmp(&b.3)));
+
+        let original = self.unrefined();
+        let Some(&(charge, shift, num_matched, chi_square)) = candidates.first() else {
+            return original;
+        };
+        let tied = candidates
+            .get(1)
+            .is_some_and(|second| second.2 == num_matched && (second.3 - chi_square).abs() <= 1e-9);
+        if tied {
+            return RefinedPrecursor { score: chi_square, num_matched, status: RefinementStatus::Ambiguous, ..original };
+        }
+
+        let is_original = shift == 0 && self.mono_mz.is_some() && self.charge == Some(charge);
+        RefinedPrecursor {
+            mono_mz: Some(self.reference_mz + shift as f64 * C13_C12_MASS_DIFFERENCE / charge as f64),
+            charge: Some(charge),
+            score: chi_square,
+            num_matched,
+            status: match is_original {
+                true => RefinementStatus::Confirmed,
+                false => RefinementStatus::Refined,
+            },
+            ..original
+        }
+    }
+
+    /// The original annotation without envelope
+    fn unrefined(&self) -> RefinedPrecursor {
+        RefinedPrecursor {
+            precursor_id: self.precursor_id,
+            original_mono_mz: self.mono_mz,
+            original_charge: self.charge,
+            mono_mz: self.mono_mz,
+            charge: self.charge,
+            score: f64::NAN,
+            num_matched: 0,
+            status: RefinementStatus::NoEnvelope,
+        }
+    }
+}
+
 pub struct TimsDatasetDDA {
     pub loader: TimsDataLoader,
 }
@@ -29,10 +257,25 @@ impl TimsDatasetDDA {
         in_memory: bool,
         use_bruker_sdk: bool,
     ) -> Self {
+        Self::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Open a dataset, fails if the run was acquired in a mode this dataset type cannot handle
+    pub fn try_new(
+        bruker_lib_path: &str,
+        data_path: &str,
+        in_memory: bool,
+        use_bruker_sdk: bool,
+    ) -> Result<Self, AcquisitionModeMismatch> {
         // TODO: error handling
         let global_meta_data = read_global_meta_sql(data_path).unwrap();
         let meta_data = read_meta_data_sql(data_path).unwrap();
 
+        let table_names = read_table_names(data_path).unwrap();
+        AcquisitionMode::detect(&table_names, meta_data[0].scan_mode)
+            .check(&[AcquisitionMode::DDA], data_path)?;
+
         let scan_max_index = meta_data.iter().map(|x| x.num_scans).max().unwrap() as u32;
         let im_lower = global_meta_data.one_over_k0_range_lower;
         let im_upper = global_meta_data.one_over_k0_range_upper;
@@ -65,7 +308,7 @@ impl TimsDatasetDDA {
                 mz_upper,
             ),
         };
-        TimsDatasetDDA { loader }
+        Ok(TimsDatasetDDA { loader })
     }
 
     /*
@@ -86,12 +329,12 @@ impl TimsDatasetDDA {
          */
 
     pub fn get_selected_precursors(&self) -> Vec<DDAPrecursor> {
-        let precursor_meta = read_dda_precursor_meta(&self.loader.get_data_path()).unwrap();
+        let precursor_meta = read_dda_precursor_meta(self.loader.get_data_path()).unwrap();
         let pasef_meta = self.get_pasef_frame_ms_ms_info();
 
         let precursor_id_to_pasef_meta: BTreeMap<i64, &PasefMsMsMeta> = pasef_meta
             .iter()
-            .map(|x| (x.precursor_id as i64, x))
+            .map(|x| (x.precursor_id, x))
             .collect();
 
         // go over all precursors and get the precursor meta data
@@ -130,7 +373,7 @@ impl TimsDatasetDDA {
         num_threads: usize,
     ) -> Vec<TimsFrame> {
         // get all precursor frames
-        let meta_data = read_meta_data_sql(&self.loader.get_data_path()).unwrap();
+        let meta_data = read_meta_data_sql(self.loader.get_data_path()).unwrap();
 
         // get the precursor frames
         let precursor_frames = meta_data.iter().filter(|x| x.ms_ms_type == 0);
@@ -152,18 +395,181 @@ impl TimsDatasetDDA {
     }
 
     pub fn get_pasef_frame_ms_ms_info(&self) -> Vec<PasefMsMsMeta> {
-        read_pasef_frame_ms_ms_info(&self.loader.get_data_path()).unwrap()
+        read_pasef_frame_ms_ms_info(self.loader.get_data_path()).unwrap()
+    }
+
+    /// (mz lower, mz upper, first scan, last scan) of every precursor over all of its PASEF events
+    fn get_isolation_windows(&self) -> BTreeMap<i64, (f64, f64, i32, i32)> {
+        let mut windows: BTreeMap<i64, (f64, f64, i32, i32)> = BTreeMap::new();
+        for info in self.get_pasef_frame_ms_ms_info() {
+            let half_width = info.isolation_width / 2.0;
+            let window = windows.entry(info.precursor_id).or_insert((
+                info.isolation_mz - half_width,
+                info.isolation_mz + half_width,
+                i32::MAX,
+                i32::MIN,
+            ));
+            window.2 = window.2.min(info.scan_num_begin as i32);
+            window.3 = window.3.max(info.scan_num_end as i32);
+        }
+        windows
+    }
+
+    /// Isolation purity of every selected precursor
+    ///
+    /// The isolation window of a precursor spans its isolation m/z and width and the scans of all its PASEF
+    /// events. Within the window of its parent MS1 frame, peaks within `ppm` of the isotopes of the monoisotopic
+    /// m/z are counted as envelope, precursors without monoisotopic m/z fall back to the most intense m/z. Every
+    /// parent frame is loaded once for all of its precursors.
+    ///
+    /// # Arguments
+    ///
+    /// * `ppm` - The m/z tolerance of an isotope match
+    /// * `num_isotopes` - The number of isotopes of the envelope, including the monoisotopic peak
+    /// * `num_threads` - The number of threads
+    ///
+    /// # Returns
+    ///
+    /// * The purity of every precursor ordered by precursor id, NaN for empty windows and missing parent frames
+    ///
+    pub fn compute_precursor_purity(
+        &self,
+        ppm: f64,
+        num_isotopes: usize,
+        num_threads: usize,
+    ) -> Vec<PrecursorPurity> {
+        let precursor_meta = read_dda_precursor_meta(self.loader.get_data_path()).unwrap();
+        let windows = self.get_isolation_windows();
+
+        let frame_count = self.get_frame_count() as i64;
+        let mut targets_by_frame: BTreeMap<u32, Vec<PurityTarget>> = BTreeMap::new();
+        let mut purities = Vec::with_capacity(precursor_meta.len());
+        for precursor in &precursor_meta {
+            let (mz_lower, mz_upper, scan_begin, scan_end) = windows
+                .get(&precursor.precursor_id)
+                .copied()
+                .unwrap_or((f64::NAN, f64::NAN, 0, -1));
+            let target = PurityTarget {
+                precursor_id: precursor.precursor_id,
+                envelope_mz: precursor
+                    .precursor_mz_monoisotopic
+                    .unwrap_or(precursor.precursor_mz_highest_intensity),
+                charge: precursor
+                    .precursor_mz_monoisotopic
+                    .and(precursor.precursor_charge),
+                mz_lower,
+                mz_upper,
+                scan_begin,
+                scan_end,
+            };
+            if (1..=frame_count).contains(&precursor.precursor_frame_id) {
+                targets_by_frame
+                    .entry(precursor.precursor_frame_id as u32)
+                    .or_default()
+                    .push(target);
+            } else {
+                purities.push(target.empty());
+            }
+        }
+
+        let pool = parallel::pool(num_threads);
+        let columns = FrameColumns::SCAN | FrameColumns::MZ | FrameColumns::INTENSITY;
+        purities.extend(pool.install(|| {
+            targets_by_frame
+                .par_iter()
+                .flat_map_iter(|(&frame_id, targets)| {
+                    let frame = self.get_frame_projected(frame_id, columns);
+                    targets
+                        .iter()
+                        .map(|target| target.purity(&frame, ppm, num_isotopes))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        }));
+
+        purities.sort_by_key(|purity| purity.precursor_id);
+        purities
+    }
+
+    /// Re-estimate monoisotopic m/z and charge of every selected precursor from its isotope envelope
+    ///
+    /// Within the scans of its PASEF events in its parent MS1 frame, envelopes of charge 1 to `max_charge`
+    /// starting 0 to 2 isotopes below the monoisotopic m/z of the precursor table are fitted against the
+    /// averagine model, precursors without monoisotopic m/z start from the most intense m/z. Every parent
+    /// frame is loaded once for all of its precursors.
+    ///
+    /// # Arguments
+    ///
+    /// * `ppm` - The m/z tolerance of an isotope match
+    /// * `max_charge` - The highest charge of a candidate envelope
+    /// * `num_threads` - The number of threads
+    ///
+    /// # Returns
+    ///
+    /// * The best envelope and the original values of every precursor ordered by precursor id, see `RefinementStatus`
+    ///
+    pub fn refine_precursors(
+        &self,
+        ppm: f64,
+        max_charge: i32,
+        num_threads: usize,
+    ) -> Vec<RefinedPrecursor> {
+        let precursor_meta = read_dda_precursor_meta(self.loader.get_data_path()).unwrap();
+        let windows = self.get_isolation_windows();
+
+        let frame_count = self.get_frame_count() as i64;
+        let mut targets_by_frame: BTreeMap<u32, Vec<RefineTarget>> = BTreeMap::new();
+        let mut refined = Vec::with_capacity(precursor_meta.len());
+        for precursor in &precursor_meta {
+            let (_, _, scan_begin, scan_end) = windows
+                .get(&precursor.precursor_id)
+                .copied()
+                .unwrap_or((f64::NAN, f64::NAN, 0, -1));
+            let target = RefineTarget {
+                precursor_id: precursor.precursor_id,
+                mono_mz: precursor.precursor_mz_monoisotopic,
+                charge: precursor.precursor_charge.filter(|&charge| charge > 0),
+                reference_mz: precursor
+                    .precursor_mz_monoisotopic
+                    .unwrap_or(precursor.precursor_mz_highest_intensity),
+                scan_begin,
+                scan_end,
+            };
+            if (1..=frame_count).contains(&precursor.precursor_frame_id) {
+                targets_by_frame
+                    .entry(precursor.precursor_frame_id as u32)
+                    .or_default()
+                    .push(target);
+            } else {
+                refined.push(target.unrefined());
+            }
+        }
+
+        let pool = parallel::pool(num_threads);
+        let columns = FrameColumns::SCAN | FrameColumns::TOF | FrameColumns::MZ | FrameColumns::INTENSITY;
+        refined.extend(pool.install(|| {
+            targets_by_frame
+                .par_iter()
+                .flat_map_iter(|(&frame_id, targets)| {
+                    let frame = self.get_frame_projected(frame_id, columns);
+                    targets
+                        .iter()
+                        .map(|target| target.refine(&frame, ppm, max_charge))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        }));
+
+        refined.sort_by_key(|precursor| precursor.precursor_id);
+        refined
     }
 
     /// Get the fragment spectra for all PASEF selected precursors
-    pub fn get_pasef_fragments(&self, num_threads: usize) -> Vec<PASEFDDAFragment> {
+    pub fn get_pasef_fragments(&self, num_threads: impl Into<Option<usize>>) -> Vec<PASEFDDAFragment> {
         // extract fragment spectra information
         let pasef_info = self.get_pasef_frame_ms_ms_info();
 
-        let pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let pool = parallel::pool(num_threads);
 
         let filtered_frames = pool.install(|| {
             let result: Vec<_> = pasef_info
@@ -191,6 +597,10 @@ impl TimsDatasetDDA {
                         frame_id: pasef_info.frame_id as u32,
                         precursor_id: pasef_info.precursor_id as u32,
                         collision_energy: pasef_info.collision_energy,
+                        isolation_mz: pasef_info.isolation_mz,
+                        isolation_width: pasef_info.isolation_width,
+                        scan_begin: pasef_info.scan_num_begin as u32,
+                        scan_end: pasef_info.scan_num_end as u32,
                         // flatten the spectrum
                         selected_fragment: filtered_frame,
                     }
@@ -213,10 +623,23 @@ impl TimsData for TimsDatasetDDA {
         self.loader.get_raw_frame(frame_id)
     }
 
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.loader.get_frame_projected(frame_id, columns)
+    }
+
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
         self.loader.get_slice(frame_ids, num_threads)
     }
 
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        self.loader.get_slice_projected(frame_ids, columns, num_threads)
+    }
+
     fn get_acquisition_mode(&self) -> AcquisitionMode {
         self.loader.get_acquisition_mode().clone()
     }
@@ -226,7 +649,7 @@ impl TimsData for TimsDatasetDDA {
     }
 
     fn get_data_path(&self) -> &str {
-        &self.loader.get_data_path()
+        self.loader.get_data_path()
     }
 }
 
@@ -259,3 +682,109 @@ impl IndexConverter for TimsDatasetDDA {
             .inverse_mobility_to_scan(frame_id, inverse_mobility_values)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mscore::algorithm::isotope::weight;
+    use mscore::chemistry::constants::MASS_PROTON;
+    use mscore::data::spectrum::MsType;
+
+    #[test]
+    fn test_precursor_purity() {
+        let mono = 600.0;
+        let frame = PartialTimsFrame {
+            frame_id: 1,
+            ms_type: MsType::Precursor,
+            retention_time: 0.0,
+            // three isotopes of a doubly charged precursor, an interfering peak, a peak outside of the scan
+            // range and one outside of the m/z range
+            scan: Some(vec![100, 101, 102, 100, 300, 100]),
+            mobility: None,
+            tof: None,
+            mz: Some(vec![mono, mono + C13_C12_MASS_DIFFERENCE / 2.0, mono + C13_C12_MASS_DIFFERENCE, 600.8, mono, 603.0]),
+            intensity: Some(vec![100.0, 80.0, 40.0, 30.0, 1000.0, 1000.0]),
+        };
+        let target = PurityTarget {
+            precursor_id: 7,
+            envelope_mz: mono,
+            charge: Some(2),
+            mz_lower: 599.0,
+            mz_upper: 602.0,
+            scan_begin: 90,
+            scan_end: 110,
+        };
+
+        let purity = target.purity(&frame, 10.0, 3);
+        assert_eq!(purity.total_window_intensity, 250.0);
+        assert_eq!(purity.envelope_intensity, 220.0);
+        assert_eq!(purity.purity, 220.0 / 250.0);
+
+        // two isotopes only, without charge only the envelope m/z itself
+        assert_eq!(target.purity(&frame, 10.0, 2).envelope_intensity, 180.0);
+        let uncharged = PurityTarget { charge: None, ..target };
+        assert_eq!(uncharged.purity(&frame, 10.0, 3).envelope_intensity, 100.0);
+
+        let empty = PurityTarget { scan_begin: 0, scan_end: 10, ..uncharged };
+        let purity = empty.purity(&frame, 10.0, 3);
+        assert!(purity.purity.is_nan());
+        assert_eq!(purity.total_window_intensity, 0.0);
+    }
+
+    #[test]
+    fn test_refine_precursor() {
+        let mono = 600.0;
+        let mass = (mono - MASS_PROTON) * 2.0;
+        let expected = weight(mass, (0..4).collect(), true);
+        // a doubly charged envelope split over two scans, the peaks of one tof are summed
+        let mut scan = Vec::new();
+        let mut tof = Vec::new();
+        let mut mz = Vec::new();
+        let mut intensity = Vec::new();
+        for s in [100, 101] {
+            for (k, e) in expected.iter().enumerate() {
+                scan.push(s);
+                tof.push(1000 + k as i32);
+                mz.push(mono + k as f64 * C13_C12_MASS_DIFFERENCE / 2.0);
+                intensity.push(e * 500.0);
+            }
+        }
+        let frame = PartialTimsFrame {
+            frame_id: 1,
+            ms_type: MsType::Precursor,
+            retention_time: 0.0,
+            scan: Some(scan),
+            mobility: None,
+            tof: Some(tof),
+            mz: Some(mz),
+            intensity: Some(intensity),
+        };
+
+        // the second isotope was reported as monoisotopic peak with charge 0
+        let mispicked = RefineTarget {
+            precursor_id: 3,
+            mono_mz: Some(mono + C13_C12_MASS_DIFFERENCE / 2.0),
+            charge: None,
+            reference_mz: mono + C13_C12_MASS_DIFFERENCE / 2.0,
+            scan_begin: 90,
+            scan_end: 110,
+        };
+        let refined = mispicked.refine(&frame, 10.0, 4);
+        assert_eq!(refined.status, RefinementStatus::Refined);
+        assert_eq!(refined.charge, Some(2));
+        assert!((refined.mono_mz.unwrap() - mono).abs() < 1e-9);
+        assert_eq!(refined.num_matched, 4);
+        assert!(refined.score < 1e-9);
+        assert_eq!(refined.original_mono_mz, Some(mono + C13_C12_MASS_DIFFERENCE / 2.0));
+
+        let correct = RefineTarget { mono_mz: Some(mono), charge: Some(2), reference_mz: mono, ..mispicked };
+        assert_eq!(correct.refine(&frame, 10.0, 4).status, RefinementStatus::Confirmed);
+
+        // outside of the scans of the precursor there is no envelope, the original values are kept
+        let elsewhere = RefineTarget { scan_begin: 0, scan_end: 10, ..correct };
+        let refined = elsewhere.refine(&frame, 10.0, 4);
+        assert_eq!(refined.status, RefinementStatus::NoEnvelope);
+        assert_eq!((refined.mono_mz, refined.charge), (Some(mono), Some(2)));
+        assert!(refined.score.is_nan());
+    }
+}
@@ -1,10 +1,12 @@
-use crate::data::acquisition::AcquisitionMode;
+use crate::data::acquisition::{AcquisitionMode, AcquisitionModeMismatch};
 use crate::data::handle::{IndexConverter, TimsData, TimsDataLoader};
 use crate::data::meta::{
     read_dia_ms_ms_info, read_dia_ms_ms_windows, read_global_meta_sql, read_meta_data_sql,
-    DiaMsMisInfo, DiaMsMsWindow, FrameMeta, GlobalMetaData,
+    read_table_names, DiaMsMisInfo, DiaMsMsWindow, FrameMeta, GlobalMetaData,
 };
+use crate::data::projection::{FrameColumns, PartialTimsFrame};
 use mscore::timstof::frame::{RawTimsFrame, TimsFrame};
+use mscore::timstof::quadrupole::TimsTransmissionDIA;
 use mscore::timstof::slice::TimsSlice;
 use rand::prelude::IteratorRandom;
 
@@ -23,9 +25,24 @@ impl TimsDatasetDIA {
         in_memory: bool,
         use_bruker_sdk: bool,
     ) -> Self {
+        Self::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Open a dataset, fails if the run was acquired in a mode this dataset type cannot handle
+    pub fn try_new(
+        bruker_lib_path: &str,
+        data_path: &str,
+        in_memory: bool,
+        use_bruker_sdk: bool,
+    ) -> Result<Self, AcquisitionModeMismatch> {
         // TODO: error handling
         let global_meta_data = read_global_meta_sql(data_path).unwrap();
         let meta_data = read_meta_data_sql(data_path).unwrap();
+
+        let table_names = read_table_names(data_path).unwrap();
+        AcquisitionMode::detect(&table_names, meta_data[0].scan_mode)
+            .check(&[AcquisitionMode::DIA, AcquisitionMode::MIDIA], data_path)?;
         let dia_ms_mis_info = read_dia_ms_ms_info(data_path).unwrap();
         let dia_ms_ms_windows = read_dia_ms_ms_windows(data_path).unwrap();
 
@@ -62,13 +79,31 @@ impl TimsDatasetDIA {
             ),
         };
 
-        TimsDatasetDIA {
+        Ok(TimsDatasetDIA {
             loader,
             global_meta_data,
             meta_data,
             dia_ms_mis_info,
             dia_ms_ms_windows,
-        }
+        })
+    }
+
+    /// Quadrupole isolation windows of the run as stored in DiaFrameMsMsInfo and DiaFrameMsMsWindows
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - steepness of the sigmoid window edges, 15.0 if None
+    pub fn get_transmission(&self, k: Option<f64>) -> TimsTransmissionDIA {
+        TimsTransmissionDIA::new(
+            self.dia_ms_mis_info.iter().map(|info| info.frame_id as i32).collect(),
+            self.dia_ms_mis_info.iter().map(|info| info.window_group as i32).collect(),
+            self.dia_ms_ms_windows.iter().map(|window| window.window_group as i32).collect(),
+            self.dia_ms_ms_windows.iter().map(|window| window.scan_num_begin as i32).collect(),
+            self.dia_ms_ms_windows.iter().map(|window| window.scan_num_end as i32).collect(),
+            self.dia_ms_ms_windows.iter().map(|window| window.isolation_mz).collect(),
+            self.dia_ms_ms_windows.iter().map(|window| window.isolation_width).collect(),
+            k,
+        )
     }
 
     pub fn sample_precursor_signal(
@@ -159,9 +194,22 @@ impl TimsData for TimsDatasetDIA {
         self.loader.get_raw_frame(frame_id)
     }
 
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.loader.get_frame_projected(frame_id, columns)
+    }
+
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
         self.loader.get_slice(frame_ids, num_threads)
     }
+
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        self.loader.get_slice_projected(frame_ids, columns, num_threads)
+    }
     fn get_acquisition_mode(&self) -> AcquisitionMode {
         self.loader.get_acquisition_mode().clone()
     }
@@ -171,7 +219,7 @@ impl TimsData for TimsDatasetDIA {
     }
 
     fn get_data_path(&self) -> &str {
-        &self.loader.get_data_path()
+        self.loader.get_data_path()
     }
 }
 
@@ -1,21 +1,31 @@
-use crate::data::meta::{read_global_meta_sql, read_meta_data_sql, FrameMeta, GlobalMetaData};
+use crate::data::meta::{
+    read_global_meta_sql, read_meta_data_sql, read_table_names, FrameMeta, GlobalMetaData,
+};
+use crate::data::projection::{DecodedFrame, FrameColumns, PartialTimsFrame};
 use crate::data::raw::BrukerTimsDataLibrary;
+use crate::data::tdf_bin::TdfBinReader;
 use crate::data::utility::{
-    flatten_scan_values, parse_decompressed_bruker_binary_data, zstd_decompress,
+    flatten_scan_values, parse_decompressed_bruker_binary_data,
+    parse_decompressed_bruker_binary_scans, with_zstd_decompressed,
 };
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 use mscore::data::spectrum::MsType;
-use mscore::timstof::frame::{ImsFrame, RawTimsFrame, TimsFrame};
+use mscore::timstof::frame::{RawTimsFrame, TimsFrame};
 use mscore::timstof::slice::TimsSlice;
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read};
+use std::ops::Range;
 use std::path::PathBuf;
 
 use crate::data::acquisition::AcquisitionMode;
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel;
 
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
 
 fn lzf_decompress(data: &[u8], max_output_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
     let decompressed_data = lzf::decompress(data, max_output_size)
@@ -81,8 +91,7 @@ impl TimsRawDataLayout {
         // get the max scan count
         let max_scan_count = frame_meta_data.iter().map(|x| x.num_scans).max().unwrap();
 
-        let mut frame_id_ptr: Vec<i64> = Vec::new();
-        frame_id_ptr.resize(frame_meta_data.len() + 1, 0);
+        let mut frame_id_ptr: Vec<i64> = vec![0; frame_meta_data.len() + 1];
 
         // get the frame id_ptr values
         for (i, row) in frame_meta_data.iter().enumerate() {
@@ -96,11 +105,9 @@ impl TimsRawDataLayout {
             .collect::<Vec<i64>>();
 
         // get the acquisition mode
-        let acquisition_mode = match frame_meta_data[0].scan_mode {
-            8 => AcquisitionMode::DDA,
-            9 => AcquisitionMode::DIA,
-            _ => AcquisitionMode::Unknown,
-        };
+        let table_names = read_table_names(data_path).unwrap();
+        let acquisition_mode =
+            AcquisitionMode::detect(&table_names, frame_meta_data[0].scan_mode);
 
         TimsRawDataLayout {
             raw_data_path: data_path.to_string(),
@@ -118,11 +125,27 @@ pub trait TimsData {
     fn get_frame(&self, frame_id: u32) -> TimsFrame;
     fn get_raw_frame(&self, frame_id: u32) -> RawTimsFrame;
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice;
+    /// decode only the requested columns of a frame, the default decodes the full frame and drops the rest
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        PartialTimsFrame::from_frame(self.get_frame(frame_id), columns)
+    }
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        _num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        frame_ids
+            .into_iter()
+            .map(|f| self.get_frame_projected(f, columns))
+            .collect()
+    }
     fn get_acquisition_mode(&self) -> AcquisitionMode;
     fn get_frame_count(&self) -> i32;
     fn get_data_path(&self) -> &str;
 }
 
+#[allow(clippy::ptr_arg)]
 pub trait IndexConverter {
     fn tof_to_mz(&self, frame_id: u32, tof_values: &Vec<u32>) -> Vec<f64>;
     fn mz_to_tof(&self, frame_id: u32, mz_values: &Vec<f64>) -> Vec<u32>;
@@ -134,6 +157,116 @@ pub trait IndexConverter {
     ) -> Vec<u32>;
 }
 
+/// group values by frame, convert every group with a single call and scatter the results back
+/// into the order of the input
+fn convert_batched<T, U, F>(
+    frame_ids: &[u32],
+    values: &[T],
+    num_threads: usize,
+    convert: F,
+) -> Vec<U>
+where
+    T: Copy + Sync,
+    U: Copy + Default + Send,
+    F: Fn(u32, &Vec<T>) -> Vec<U> + Sync,
+{
+    assert_eq!(
+        frame_ids.len(),
+        values.len(),
+        "frame ids and values must have the same length"
+    );
+
+    let mut order: Vec<usize> = (0..frame_ids.len()).collect();
+    order.sort_by_key(|&i| frame_ids[i]);
+    let groups: Vec<&[usize]> = order
+        .chunk_by(|&a, &b| frame_ids[a] == frame_ids[b])
+        .collect();
+
+    let pool = parallel::pool(num_threads);
+    let converted: Vec<Vec<U>> = pool.install(|| {
+        groups
+            .par_iter()
+            .map(|group| {
+                let group_values = group.iter().map(|&i| values[i]).collect();
+                convert(frame_ids[group[0]], &group_values)
+            })
+            .collect()
+    });
+
+    let mut result = vec![U::default(); values.len()];
+    for (group, group_result) in groups.iter().zip(converted) {
+        for (&i, value) in group.iter().zip(group_result) {
+            result[i] = value;
+        }
+    }
+    result
+}
+
+/// conversions of values spread over many frames, e.g. the columns of a feature table,
+/// with one call to the underlying converter per distinct frame instead of one per value
+pub trait BatchedIndexConverter: IndexConverter + Sync {
+    /// translate tof to mz values, `frame_ids` holds the frame of every value
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - A slice of u32 that holds the frame id of every value
+    /// * `tof_values` - A slice of u32 that holds the tof values
+    /// * `num_threads` - Number of threads frames are converted with
+    ///
+    /// # Returns
+    ///
+    /// * `mz_values` - A vector of f64 in the order of the input
+    ///
+    fn tof_to_mz_batched(
+        &self,
+        frame_ids: &[u32],
+        tof_values: &[u32],
+        num_threads: usize,
+    ) -> Vec<f64> {
+        convert_batched(frame_ids, tof_values, num_threads, |frame_id, tof| {
+            self.tof_to_mz(frame_id, tof)
+        })
+    }
+
+    fn mz_to_tof_batched(
+        &self,
+        frame_ids: &[u32],
+        mz_values: &[f64],
+        num_threads: usize,
+    ) -> Vec<u32> {
+        convert_batched(frame_ids, mz_values, num_threads, |frame_id, mz| {
+            self.mz_to_tof(frame_id, mz)
+        })
+    }
+
+    fn scan_to_inverse_mobility_batched(
+        &self,
+        frame_ids: &[u32],
+        scan_values: &[u32],
+        num_threads: usize,
+    ) -> Vec<f64> {
+        convert_batched(frame_ids, scan_values, num_threads, |frame_id, scan| {
+            self.scan_to_inverse_mobility(frame_id, scan)
+        })
+    }
+
+    fn inverse_mobility_to_scan_batched(
+        &self,
+        frame_ids: &[u32],
+        inverse_mobility_values: &[f64],
+        num_threads: usize,
+    ) -> Vec<u32> {
+        convert_batched(
+            frame_ids,
+            inverse_mobility_values,
+            num_threads,
+            |frame_id, inv_mob| self.inverse_mobility_to_scan(frame_id, inv_mob),
+        )
+    }
+}
+
+impl<C: IndexConverter + Sync + ?Sized> BatchedIndexConverter for C {}
+
 pub struct BrukerLibTimsDataConverter {
     pub bruker_lib: BrukerTimsDataLibrary,
 }
@@ -301,44 +434,35 @@ impl IndexConverter for TimsIndexConverter {
 pub struct TimsLazyLoder {
     pub raw_data_layout: TimsRawDataLayout,
     pub index_converter: TimsIndexConverter,
+    pub tdf_bin: TdfBinReader,
 }
 
-impl TimsData for TimsLazyLoder {
-    fn get_frame(&self, frame_id: u32) -> TimsFrame {
+impl TimsLazyLoder {
+    /// compressed block of a frame, borrowed from the mapped tdf_bin or read from disk
+    fn frame_block(&self, frame_index: usize) -> Cow<'_, [u8]> {
+        let offset = self.raw_data_layout.tims_offset_values[frame_index] as usize;
+        self.tdf_bin.read_block(offset).unwrap()
+    }
+
+    /// read and decompress a frame from disk, without any index conversion
+    fn decode_frame(&self, frame_id: u32) -> DecodedFrame {
         let frame_index = (frame_id - 1) as usize;
 
         // turns out, there can be empty frames in the data, check for that, if so, return an empty frame
         let num_peaks = self.raw_data_layout.frame_meta_data[frame_index].num_peaks;
 
         if num_peaks == 0 {
-            return TimsFrame {
-                frame_id: frame_id as i32,
+            return DecodedFrame {
+                frame_id,
                 ms_type: MsType::Unknown,
+                retention_time: self.raw_data_layout.frame_meta_data[(frame_id - 1) as usize].time,
                 scan: Vec::new(),
                 tof: Vec::new(),
-                ims_frame: ImsFrame {
-                    retention_time: self.raw_data_layout.frame_meta_data[(frame_id - 1) as usize]
-                        .time,
-                    mobility: Vec::new(),
-                    mz: Vec::new(),
-                    intensity: Vec::new(),
-                },
+                intensity: Vec::new(),
             };
         }
 
-        let offset = self.raw_data_layout.tims_offset_values[frame_index] as u64;
-
-        let mut file_path = PathBuf::from(&self.raw_data_layout.raw_data_path);
-        file_path.push("analysis.tdf_bin");
-        let mut infile = File::open(&file_path).unwrap();
-
-        infile.seek(SeekFrom::Start(offset)).unwrap();
-
-        let mut bin_buffer = [0u8; 4];
-        infile.read_exact(&mut bin_buffer).unwrap();
-        let bin_size = Cursor::new(bin_buffer).read_i32::<LittleEndian>().unwrap();
-
-        infile.read_exact(&mut bin_buffer).unwrap();
+        let block = self.frame_block(frame_index);
 
         match self.raw_data_layout.global_meta_data.tims_compression_type {
             1 => {
@@ -347,12 +471,9 @@ impl TimsData for TimsLazyLoder {
                 let num_peaks = num_peaks as usize;
                 let compression_offset = 8 + (scan_count + 1) * 4;
 
-                let mut scan_offsets_buffer = vec![0u8; (scan_count + 1) * 4];
-                infile.read_exact(&mut scan_offsets_buffer).unwrap();
-
                 let mut scan_offsets = Vec::with_capacity(scan_count + 1);
                 {
-                    let mut rdr = Cursor::new(&scan_offsets_buffer);
+                    let mut rdr = Cursor::new(&block[8..compression_offset]);
                     for _ in 0..(scan_count + 1) {
                         scan_offsets.push(rdr.read_i32::<LittleEndian>().unwrap());
                     }
@@ -362,9 +483,7 @@ impl TimsData for TimsLazyLoder {
                     *offs -= compression_offset as i32;
                 }
 
-                let remaining_size = (bin_size as usize - compression_offset) as usize;
-                let mut compressed_data = vec![0u8; remaining_size];
-                infile.read_exact(&mut compressed_data).unwrap();
+                let compressed_data = &block[compression_offset..];
 
                 let mut scan_indices_ = vec![0i64; scan_count];
                 let mut tof_indices_ = vec![0u32; num_peaks];
@@ -397,85 +516,45 @@ impl TimsData for TimsLazyLoder {
 
                 // Create a flat scan vector to match what flatten_scan_values expects
                 let mut scan = Vec::with_capacity(num_peaks);
-                {
-                    let mut current_scan_index = 0u32;
-                    for &size in &scan_indices_ {
-                        let sz = size as usize;
-                        for _ in 0..sz {
-                            scan.push(current_scan_index);
-                        }
-                        current_scan_index += 1;
-                    }
+                for (current_scan_index, &size) in scan_indices_.iter().enumerate() {
+                    scan.extend(std::iter::repeat_n(current_scan_index as u32, size as usize));
                 }
 
                 let intensity_dbl = intensities_.iter().map(|&x| x as f64).collect::<Vec<f64>>();
-                let tof_i32 = tof_indices_.iter().map(|&x| x as i32).collect::<Vec<i32>>();
-
-                let mz = self.index_converter.tof_to_mz(frame_id, &tof_indices_);
-                let inv_mobility = self
-                    .index_converter
-                    .scan_to_inverse_mobility(frame_id, &scan);
 
                 let ms_type_raw = self.raw_data_layout.frame_meta_data[frame_index].ms_ms_type;
-                let ms_type = match ms_type_raw {
-                    0 => MsType::Precursor,
-                    8 => MsType::FragmentDda,
-                    9 => MsType::FragmentDia,
-                    _ => MsType::Unknown,
-                };
+                let ms_type = self.raw_data_layout.acquisition_mode.frame_ms_type(ms_type_raw);
 
-                TimsFrame {
-                    frame_id: frame_id as i32,
+                DecodedFrame {
+                    frame_id,
                     ms_type,
-                    scan: scan.iter().map(|&x| x as i32).collect(),
-                    tof: tof_i32,
-                    ims_frame: ImsFrame {
-                        retention_time: self.raw_data_layout.frame_meta_data[frame_index].time,
-                        mobility: inv_mobility,
-                        mz,
-                        intensity: intensity_dbl,
-                    },
+                    retention_time: self.raw_data_layout.frame_meta_data[frame_index].time,
+                    scan,
+                    tof: tof_indices_,
+                    intensity: intensity_dbl,
                 }
             }
 
             // Existing handling of Type 2
             2 => {
-                let mut compressed_data = vec![0u8; bin_size as usize - 8];
-                infile.read_exact(&mut compressed_data).unwrap();
-
-                let decompressed_bytes = zstd_decompress(&compressed_data).unwrap();
-
                 let (scan, tof, intensity) =
-                    parse_decompressed_bruker_binary_data(&decompressed_bytes).unwrap();
+                    with_zstd_decompressed(&block[8..], parse_decompressed_bruker_binary_data)
+                        .unwrap()
+                        .unwrap();
                 let intensity_dbl = intensity.iter().map(|&x| x as f64).collect();
-                let tof_i32 = tof.iter().map(|&x| x as i32).collect();
                 let scan = flatten_scan_values(&scan, true);
 
-                let mz = self.index_converter.tof_to_mz(frame_id, &tof);
-                let inv_mobility = self
-                    .index_converter
-                    .scan_to_inverse_mobility(frame_id, &scan);
-
                 let ms_type_raw = self.raw_data_layout.frame_meta_data[frame_index].ms_ms_type;
 
-                let ms_type = match ms_type_raw {
-                    0 => MsType::Precursor,
-                    8 => MsType::FragmentDda,
-                    9 => MsType::FragmentDia,
-                    _ => MsType::Unknown,
-                };
+                let ms_type = self.raw_data_layout.acquisition_mode.frame_ms_type(ms_type_raw);
 
-                TimsFrame {
-                    frame_id: frame_id as i32,
+                DecodedFrame {
+                    frame_id,
                     ms_type,
-                    scan: scan.iter().map(|&x| x as i32).collect(),
-                    tof: tof_i32,
-                    ims_frame: ImsFrame {
-                        retention_time: self.raw_data_layout.frame_meta_data[frame_index].time,
-                        mobility: inv_mobility,
-                        mz,
-                        intensity: intensity_dbl,
-                    },
+                    retention_time: self.raw_data_layout.frame_meta_data[frame_index].time,
+                    scan,
+                    tof,
+                    intensity: intensity_dbl,
                 }
             }
 
@@ -485,9 +564,114 @@ impl TimsData for TimsLazyLoder {
         }
     }
 
+}
+
+impl TimsLazyLoder {
+    /// read and decompress the peaks of a range of scans of a frame, without any index conversion
+    ///
+    /// LZF compressed frames store every scan separately and only the requested scans are decompressed,
+    /// ZSTD compressed frames are decompressed as a whole but only the requested peaks are decoded
+    fn decode_scans(&self, frame_id: u32, scans: Range<u32>) -> DecodedFrame {
+        let frame_index = (frame_id - 1) as usize;
+        let meta = &self.raw_data_layout.frame_meta_data[frame_index];
+        let ms_type = self
+            .raw_data_layout
+            .acquisition_mode
+            .frame_ms_type(meta.ms_ms_type);
+
+        let mut frame = DecodedFrame {
+            frame_id,
+            ms_type,
+            retention_time: meta.time,
+            scan: Vec::new(),
+            tof: Vec::new(),
+            intensity: Vec::new(),
+        };
+        if meta.num_peaks == 0 || scans.is_empty() {
+            return frame;
+        }
+
+        let block = self.frame_block(frame_index);
+
+        match self.raw_data_layout.global_meta_data.tims_compression_type {
+            1 => {
+                let scan_count = meta.num_scans as usize;
+                let compression_offset = 8 + (scan_count + 1) * 4;
+                let scan_offset = |scan: usize| {
+                    LittleEndian::read_i32(&block[8 + scan * 4..12 + scan * 4]) as usize
+                        - compression_offset
+                };
+                let compressed_data = &block[compression_offset..];
+
+                let end = (scans.end as usize).min(scan_count);
+                let begin = (scans.start as usize).min(end);
+                let max_output_size = meta.num_peaks as usize * 8;
+
+                let mut scan_sizes = vec![0i64; scan_count];
+                let mut tof = vec![0u32; meta.num_peaks as usize];
+                let mut intensity = vec![0u16; meta.num_peaks as usize];
+                let mut num_peaks = 0usize;
+
+                for scan_index in begin..end {
+                    let (start, stop) = (scan_offset(scan_index), scan_offset(scan_index + 1));
+                    if start == stop {
+                        continue;
+                    }
+                    let decompressed_bytes =
+                        lzf_decompress(&compressed_data[start..stop], max_output_size)
+                            .expect("LZF decompression failed.");
+                    let scan_size = parse_decompressed_bruker_binary_type1(
+                        &decompressed_bytes,
+                        &mut scan_sizes,
+                        &mut tof,
+                        &mut intensity,
+                        num_peaks,
+                        scan_index,
+                    );
+                    frame
+                        .scan
+                        .extend(std::iter::repeat_n(scan_index as u32, scan_size));
+                    num_peaks += scan_size;
+                }
+
+                tof.truncate(num_peaks);
+                frame.tof = tof;
+                frame.intensity = intensity[..num_peaks].iter().map(|&x| x as f64).collect();
+            }
+
+            2 => {
+                let (scan, tof, intensity) = with_zstd_decompressed(&block[8..], |bytes| {
+                    parse_decompressed_bruker_binary_scans(bytes, scans)
+                })
+                .unwrap()
+                .unwrap();
+                frame.scan = scan;
+                frame.tof = tof;
+                frame.intensity = intensity.iter().map(|&x| x as f64).collect();
+            }
+
+            _ => {
+                panic!("TimsCompressionType is not 1 or 2.")
+            }
+        }
+
+        frame
+    }
+}
+
+impl TimsData for TimsLazyLoder {
+    fn get_frame(&self, frame_id: u32) -> TimsFrame {
+        self.decode_frame(frame_id)
+            .project(&self.index_converter, FrameColumns::ALL)
+            .into()
+    }
+
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.decode_frame(frame_id).project(&self.index_converter, columns)
+    }
+
     fn get_raw_frame(&self, frame_id: u32) -> RawTimsFrame {
         let frame_index = (frame_id - 1) as usize;
-        let offset = self.raw_data_layout.tims_offset_values[frame_index] as u64;
 
         // turns out, there can be empty frames in the data, check for that, if so, return an empty frame
         let num_peaks = self.raw_data_layout.frame_meta_data[frame_index].num_peaks;
@@ -503,17 +687,7 @@ impl TimsData for TimsLazyLoder {
             };
         }
 
-        let mut file_path = PathBuf::from(&self.raw_data_layout.raw_data_path);
-        file_path.push("analysis.tdf_bin");
-        let mut infile = File::open(&file_path).unwrap();
-
-        infile.seek(SeekFrom::Start(offset)).unwrap();
-
-        let mut bin_buffer = [0u8; 4];
-        infile.read_exact(&mut bin_buffer).unwrap();
-        let bin_size = Cursor::new(bin_buffer).read_i32::<LittleEndian>().unwrap();
-
-        infile.read_exact(&mut bin_buffer).unwrap();
+        let block = self.frame_block(frame_index);
 
         match self.raw_data_layout.global_meta_data.tims_compression_type {
             _ if self.raw_data_layout.global_meta_data.tims_compression_type == 1 => {
@@ -522,22 +696,14 @@ impl TimsData for TimsLazyLoder {
 
             // Extract from ZSTD compressed binary
             _ if self.raw_data_layout.global_meta_data.tims_compression_type == 2 => {
-                let mut compressed_data = vec![0u8; bin_size as usize - 8];
-                infile.read_exact(&mut compressed_data).unwrap();
-
-                let decompressed_bytes = zstd_decompress(&compressed_data).unwrap();
-
                 let (scan, tof, intensity) =
-                    parse_decompressed_bruker_binary_data(&decompressed_bytes).unwrap();
+                    with_zstd_decompressed(&block[8..], parse_decompressed_bruker_binary_data)
+                        .unwrap()
+                        .unwrap();
 
                 let ms_type_raw = self.raw_data_layout.frame_meta_data[frame_index].ms_ms_type;
 
-                let ms_type = match ms_type_raw {
-                    0 => MsType::Precursor,
-                    8 => MsType::FragmentDda,
-                    9 => MsType::FragmentDia,
-                    _ => MsType::Unknown,
-                };
+                let ms_type = self.raw_data_layout.acquisition_mode.frame_ms_type(ms_type_raw);
 
                 let frame = RawTimsFrame {
                     frame_id: frame_id as i32,
@@ -549,7 +715,7 @@ impl TimsData for TimsLazyLoder {
                     intensity: intensity.iter().map(|&x| x as f64).collect(),
                 };
 
-                return frame;
+                frame
             }
 
             // Error on unknown compression algorithm
@@ -559,10 +725,31 @@ impl TimsData for TimsLazyLoder {
         }
     }
 
-    fn get_slice(&self, frame_ids: Vec<u32>, _num_threads: usize) -> TimsSlice {
-        let result: Vec<TimsFrame> = frame_ids.into_iter().map(|f| self.get_frame(f)).collect();
+    fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
+        let pool = parallel::pool(num_threads);
+        let frames = pool.install(|| {
+            frame_ids
+                .par_iter()
+                .map(|&frame_id| self.get_frame(frame_id))
+                .collect()
+        });
 
-        TimsSlice { frames: result }
+        TimsSlice { frames }
+    }
+
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        let pool = parallel::pool(num_threads);
+        pool.install(|| {
+            frame_ids
+                .par_iter()
+                .map(|&frame_id| self.get_frame_projected(frame_id, columns))
+                .collect()
+        })
     }
 
     fn get_acquisition_mode(&self) -> AcquisitionMode {
@@ -584,8 +771,9 @@ pub struct TimsInMemoryLoader {
     compressed_data: Vec<u8>,
 }
 
-impl TimsData for TimsInMemoryLoader {
-    fn get_frame(&self, frame_id: u32) -> TimsFrame {
+impl TimsInMemoryLoader {
+    /// decompress a frame, DDA fragment frames are smoothed and centroided, no index conversion is done
+    fn decode_frame(&self, frame_id: u32) -> DecodedFrame {
         let raw_frame = self.get_raw_frame(frame_id);
 
         let raw_frame = match raw_frame.ms_type {
@@ -593,33 +781,68 @@ impl TimsData for TimsInMemoryLoader {
             _ => raw_frame,
         };
 
-        // if raw frame is empty, return an empty frame
-        if raw_frame.scan.is_empty() {
-            return TimsFrame::default();
-        }
-
-        let tof_i32 = raw_frame.tof.iter().map(|&x| x as i32).collect();
         let scan = flatten_scan_values(&raw_frame.scan, true);
 
-        let mz = self.index_converter.tof_to_mz(frame_id, &raw_frame.tof);
-        let inverse_mobility = self
-            .index_converter
-            .scan_to_inverse_mobility(frame_id, &scan);
-
-        let ims_frame = ImsFrame {
+        DecodedFrame {
+            frame_id,
+            ms_type: raw_frame.ms_type,
             retention_time: raw_frame.retention_time,
-            mz,
+            scan,
+            tof: raw_frame.tof,
             intensity: raw_frame.intensity,
-            mobility: inverse_mobility,
+        }
+    }
+}
+
+impl TimsInMemoryLoader {
+    /// decompress the peaks of a range of scans of a frame, DDA fragment frames are not centroided
+    fn decode_scans(&self, frame_id: u32, scans: Range<u32>) -> DecodedFrame {
+        let frame_index = (frame_id - 1) as usize;
+        let meta = &self.raw_data_layout.frame_meta_data[frame_index];
+        let ms_type = self
+            .raw_data_layout
+            .acquisition_mode
+            .frame_ms_type(meta.ms_ms_type);
+
+        let (scan, tof, intensity) = if meta.num_peaks == 0 || scans.is_empty() {
+            (Vec::new(), Vec::new(), Vec::new())
+        } else {
+            let offset = self.raw_data_layout.tims_offset_values[frame_index] as usize;
+            let bin_size = LittleEndian::read_i32(&self.compressed_data[offset..offset + 4]) as usize;
+            let frame_data = &self.compressed_data[offset + 8..offset + bin_size];
+
+            with_zstd_decompressed(frame_data, |bytes| {
+                parse_decompressed_bruker_binary_scans(bytes, scans)
+            })
+            .unwrap()
+            .unwrap()
         };
 
-        TimsFrame {
-            frame_id: frame_id as i32,
-            ms_type: raw_frame.ms_type,
-            scan: scan.iter().map(|&x| x as i32).collect(),
-            tof: tof_i32,
-            ims_frame,
+        DecodedFrame {
+            frame_id,
+            ms_type,
+            retention_time: meta.time,
+            scan,
+            tof,
+            intensity: intensity.iter().map(|&x| x as f64).collect(),
+        }
+    }
+}
+
+impl TimsData for TimsInMemoryLoader {
+    fn get_frame(&self, frame_id: u32) -> TimsFrame {
+        let decoded = self.decode_frame(frame_id);
+
+        // if raw frame is empty, return an empty frame
+        if decoded.scan.is_empty() {
+            return TimsFrame::default();
         }
+
+        decoded.project(&self.index_converter, FrameColumns::ALL).into()
+    }
+
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.decode_frame(frame_id).project(&self.index_converter, columns)
     }
 
     fn get_raw_frame(&self, frame_id: u32) -> RawTimsFrame {
@@ -634,19 +857,14 @@ impl TimsData for TimsInMemoryLoader {
         let data_offset = bin_size_offset + 4; // Adjust based on actual structure
         let frame_data = &self.compressed_data[data_offset..data_offset + bin_size as usize - 8];
 
-        let decompressed_bytes = zstd_decompress(&frame_data).unwrap();
-
         let (scan, tof, intensity) =
-            parse_decompressed_bruker_binary_data(&decompressed_bytes).unwrap();
+            with_zstd_decompressed(frame_data, parse_decompressed_bruker_binary_data)
+                .unwrap()
+                .unwrap();
 
         let ms_type_raw = self.raw_data_layout.frame_meta_data[frame_index].ms_ms_type;
 
-        let ms_type = match ms_type_raw {
-            0 => MsType::Precursor,
-            8 => MsType::FragmentDda,
-            9 => MsType::FragmentDia,
-            _ => MsType::Unknown,
-        };
+        let ms_type = self.raw_data_layout.acquisition_mode.frame_ms_type(ms_type_raw);
 
         let raw_frame = RawTimsFrame {
             frame_id: frame_id as i32,
@@ -661,10 +879,7 @@ impl TimsData for TimsInMemoryLoader {
     }
 
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
-        let pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let pool = parallel::pool(num_threads);
         let frames = pool.install(|| {
             frame_ids
                 .par_iter()
@@ -675,6 +890,21 @@ impl TimsData for TimsInMemoryLoader {
         TimsSlice { frames }
     }
 
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        let pool = parallel::pool(num_threads);
+        pool.install(|| {
+            frame_ids
+                .par_iter()
+                .map(|&frame_id| self.get_frame_projected(frame_id, columns))
+                .collect()
+        })
+    }
+
     fn get_acquisition_mode(&self) -> AcquisitionMode {
         self.raw_data_layout.acquisition_mode.clone()
     }
@@ -694,6 +924,7 @@ pub enum TimsDataLoader {
 }
 
 impl TimsDataLoader {
+    #[allow(clippy::too_many_arguments, reason = "SDK, data path and the index to m/z and mobility calibration bounds")]
     pub fn new_lazy(
         bruker_lib_path: &str,
         data_path: &str,
@@ -725,9 +956,11 @@ impl TimsDataLoader {
         TimsDataLoader::Lazy(TimsLazyLoder {
             raw_data_layout,
             index_converter,
+            tdf_bin: TdfBinReader::open(data_path),
         })
     }
 
+    #[allow(clippy::too_many_arguments, reason = "SDK, data path and the index to m/z and mobility calibration bounds")]
     pub fn new_in_memory(
         bruker_lib_path: &str,
         data_path: &str,
@@ -768,7 +1001,15 @@ impl TimsDataLoader {
             compressed_data: data,
         })
     }
-    pub fn get_index_converter(&self) -> &dyn IndexConverter {
+    /// decode the peaks of a range of zero based scans of a frame, other scans are not decoded
+    pub(crate) fn decode_scans(&self, frame_id: u32, scans: Range<u32>) -> DecodedFrame {
+        match self {
+            TimsDataLoader::InMemory(loader) => loader.decode_scans(frame_id, scans),
+            TimsDataLoader::Lazy(loader) => loader.decode_scans(frame_id, scans),
+        }
+    }
+
+    pub fn get_index_converter(&self) -> &(dyn IndexConverter + Sync) {
         match self {
             TimsDataLoader::InMemory(loader) => &loader.index_converter,
             TimsDataLoader::Lazy(loader) => &loader.index_converter,
@@ -790,6 +1031,13 @@ impl TimsData for TimsDataLoader {
         }
     }
 
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        match self {
+            TimsDataLoader::InMemory(loader) => loader.get_frame_projected(frame_id, columns),
+            TimsDataLoader::Lazy(loader) => loader.get_frame_projected(frame_id, columns),
+        }
+    }
+
     fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
         match self {
             TimsDataLoader::InMemory(loader) => loader.get_slice(frame_ids, num_threads),
@@ -797,6 +1045,22 @@ impl TimsData for TimsDataLoader {
         }
     }
 
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        match self {
+            TimsDataLoader::InMemory(loader) => {
+                loader.get_slice_projected(frame_ids, columns, num_threads)
+            }
+            TimsDataLoader::Lazy(loader) => {
+                loader.get_slice_projected(frame_ids, columns, num_threads)
+            }
+        }
+    }
+
     fn get_acquisition_mode(&self) -> AcquisitionMode {
         match self {
             TimsDataLoader::InMemory(loader) => loader.get_acquisition_mode(),
@@ -862,8 +1126,7 @@ impl IndexConverter for SimpleIndexConverter {
     }
 
     fn mz_to_tof(&self, _frame_id: u32, _mz_values: &Vec<f64>) -> Vec<u32> {
-        let mut tof_values: Vec<u32> = Vec::new();
-        tof_values.resize(_mz_values.len(), 0);
+        let mut tof_values: Vec<u32> = vec![0; _mz_values.len()];
 
         for (i, &val) in _mz_values.iter().enumerate() {
             tof_values[i] = ((val.sqrt() - self.tof_intercept) / self.tof_slope) as u32;
@@ -888,8 +1151,7 @@ impl IndexConverter for SimpleIndexConverter {
         _frame_id: u32,
         _inverse_mobility_values: &Vec<f64>,
     ) -> Vec<u32> {
-        let mut scan_values: Vec<u32> = Vec::new();
-        scan_values.resize(_inverse_mobility_values.len(), 0);
+        let mut scan_values: Vec<u32> = vec![0; _inverse_mobility_values.len()];
 
         for (i, &val) in _inverse_mobility_values.iter().enumerate() {
             scan_values[i] = ((val - self.scan_intercept) / self.scan_slope) as u32;
@@ -898,3 +1160,228 @@ impl IndexConverter for SimpleIndexConverter {
         scan_values
     }
 }
+
+/// Decode items in parallel and hand them to `consumer` in input order, with at most
+/// `max_in_flight` items decoded but not yet consumed at any time
+///
+/// A worker only starts on item `i` once item `i - max_in_flight` was consumed, so a slow item
+/// stalls the workers instead of letting finished items pile up behind it.
+pub(crate) fn ordered_bounded_map<T, D, C>(
+    num_items: usize,
+    num_threads: usize,
+    max_in_flight: usize,
+    decode: D,
+    mut consumer: C,
+) where
+    T: Send,
+    D: Fn(usize) -> T + Sync,
+    C: FnMut(T),
+{
+    let max_in_flight = max_in_flight.max(1);
+    // (next item to claim, number of consumed items)
+    let progress = (Mutex::new((0usize, 0usize)), Condvar::new());
+    let (sender, receiver) = mpsc::sync_channel::<(usize, T)>(max_in_flight);
+
+    /// releases waiting workers if the consumer panics, they then fail to send and stop
+    struct Release<'a>(&'a (Mutex<(usize, usize)>, Condvar));
+
+    impl Drop for Release<'_> {
+        fn drop(&mut self) {
+            if let Ok(mut progress) = self.0 .0.lock() {
+                progress.1 = usize::MAX / 2;
+            }
+            self.0 .1.notify_all();
+        }
+    }
+
+    thread::scope(|scope| {
+        for _ in 0..num_threads.max(1) {
+            let sender = sender.clone();
+            let (progress, consumed) = (&progress.0, &progress.1);
+            let decode = &decode;
+            scope.spawn(move || loop {
+                let index = {
+                    let mut state = progress.lock().unwrap();
+                    while state.0 < num_items && state.0 >= state.1 + max_in_flight {
+                        state = consumed.wait(state).unwrap();
+                    }
+                    if state.0 >= num_items {
+                        return;
+                    }
+                    state.0 += 1;
+                    state.0 - 1
+                };
+                if sender.send((index, decode(index))).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+
+        let release = Release(&progress);
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, item) in receiver {
+            pending.insert(index, item);
+            while let Some(item) = pending.remove(&next) {
+                consumer(item);
+                next += 1;
+                progress.0.lock().unwrap().1 = next;
+                progress.1.notify_all();
+            }
+        }
+        drop(release);
+    });
+}
+
+/// Decode frames in parallel and hand them to `consumer` in the order of `frame_ids`
+///
+/// Unlike `get_slice`, at most `max_in_flight_frames` decoded frames exist at the same time besides
+/// those the consumer keeps, so runs of any size can be processed frame by frame.
+///
+/// # Arguments
+///
+/// * `data` - The dataset to read from
+/// * `frame_ids` - The frames to decode
+/// * `num_threads` - The number of decoding threads
+/// * `max_in_flight_frames` - The maximum number of decoded frames waiting for the consumer
+/// * `consumer` - Called once per frame id
+///
+pub fn for_each_frame<T, C>(
+    data: &T,
+    frame_ids: &[u32],
+    num_threads: usize,
+    max_in_flight_frames: usize,
+    consumer: C,
+) where
+    T: TimsData + Sync + ?Sized,
+    C: FnMut(TimsFrame),
+{
+    ordered_bounded_map(
+        frame_ids.len(),
+        num_threads,
+        max_in_flight_frames,
+        |index| data.get_frame(frame_ids[index]),
+        consumer,
+    );
+}
+
+/// Like `get_slice`, but decodes through `for_each_frame` with at most `max_in_flight_frames`
+/// frames between decoding and the result
+///
+/// The result is not memory-bounded, the returned slice still holds every frame of `frame_ids`,
+/// use `for_each_frame` or decode in batches to bound the memory of large runs.
+///
+/// # Arguments
+///
+/// * `data` - The dataset to read from
+/// * `frame_ids` - The frames to decode
+/// * `num_threads` - The number of decoding threads
+/// * `max_in_flight_frames` - The maximum number of decoded frames waiting to be collected
+///
+pub fn get_slice_bounded<T>(
+    data: &T,
+    frame_ids: Vec<u32>,
+    num_threads: usize,
+    max_in_flight_frames: usize,
+) -> TimsSlice
+where
+    T: TimsData + Sync + ?Sized,
+{
+    let mut frames = Vec::with_capacity(frame_ids.len());
+    for_each_frame(data, &frame_ids, num_threads, max_in_flight_frames, |frame| {
+        frames.push(frame)
+    });
+    TimsSlice { frames }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// converter whose result depends on the frame, to catch values converted with the wrong frame
+    struct FrameOffsetConverter;
+
+    impl IndexConverter for FrameOffsetConverter {
+        fn tof_to_mz(&self, frame_id: u32, tof_values: &Vec<u32>) -> Vec<f64> {
+            tof_values
+                .iter()
+                .map(|&t| (frame_id * 1000 + t) as f64)
+                .collect()
+        }
+        fn mz_to_tof(&self, frame_id: u32, mz_values: &Vec<f64>) -> Vec<u32> {
+            mz_values
+                .iter()
+                .map(|&m| m as u32 - frame_id * 1000)
+                .collect()
+        }
+        fn scan_to_inverse_mobility(&self, frame_id: u32, scan_values: &Vec<u32>) -> Vec<f64> {
+            self.tof_to_mz(frame_id, scan_values)
+        }
+        fn inverse_mobility_to_scan(
+            &self,
+            frame_id: u32,
+            inverse_mobility_values: &Vec<f64>,
+        ) -> Vec<u32> {
+            self.mz_to_tof(frame_id, inverse_mobility_values)
+        }
+    }
+
+    #[test]
+    fn test_batched_conversion_keeps_input_order() {
+        let frame_ids = vec![3, 1, 3, 2, 1, 3, 2];
+        let tof = vec![10, 20, 30, 40, 50, 60, 70];
+
+        let mz = FrameOffsetConverter.tof_to_mz_batched(&frame_ids, &tof, 4);
+        assert_eq!(
+            mz,
+            vec![3010.0, 1020.0, 3030.0, 2040.0, 1050.0, 3060.0, 2070.0]
+        );
+        assert_eq!(
+            FrameOffsetConverter.mz_to_tof_batched(&frame_ids, &mz, 2),
+            tof
+        );
+
+        let im = FrameOffsetConverter.scan_to_inverse_mobility_batched(&frame_ids, &tof, 1);
+        assert_eq!(im, mz);
+        assert_eq!(
+            FrameOffsetConverter.inverse_mobility_to_scan_batched(&frame_ids, &im, 3),
+            tof
+        );
+
+        assert!(FrameOffsetConverter
+            .tof_to_mz_batched(&[], &[], 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_ordered_bounded_map() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        for max_in_flight in [1, 3, 16] {
+            let alive = AtomicUsize::new(0);
+            let peak = AtomicUsize::new(0);
+            let mut consumed = Vec::new();
+
+            ordered_bounded_map(
+                50,
+                4,
+                max_in_flight,
+                |index| {
+                    let now = alive.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    // later items finish first, so they have to wait for the earlier ones
+                    thread::sleep(std::time::Duration::from_micros((50 - index as u64) * 20));
+                    index
+                },
+                |index| {
+                    alive.fetch_sub(1, Ordering::SeqCst);
+                    consumed.push(index)
+                },
+            );
+
+            assert_eq!(consumed, (0..50).collect::<Vec<_>>());
+            assert!(peak.load(Ordering::SeqCst) <= max_in_flight);
+        }
+    }
+}
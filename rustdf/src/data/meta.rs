@@ -30,6 +30,28 @@ pub struct PasefMsMsMeta {
     pub precursor_id: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct PrmMsMsMeta {
+    pub frame_id: i64,
+    pub scan_num_begin: i64,
+    pub scan_num_end: i64,
+    pub isolation_mz: f64,
+    pub isolation_width: f64,
+    pub collision_energy: f64,
+    pub target_id: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrmTargetMeta {
+    pub target_id: i64,
+    pub external_id: Option<String>,
+    pub time: f64,
+    pub one_over_k0: f64,
+    pub mono_mz: f64,
+    pub charge: Option<i64>,
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct DDAPrecursorMeta {
     pub precursor_id: i64,
@@ -195,6 +217,86 @@ pub fn read_pasef_frame_ms_ms_info(
     Ok(frames_rows?)
 }
 
+// Read the prm-PASEF frame information, which scans of which frame were isolated for which target
+pub fn read_prm_frame_ms_ms_info(
+    bruker_d_folder_name: &str,
+) -> Result<Vec<PrmMsMsMeta>, Box<dyn std::error::Error>> {
+    // Connect to the database
+    let db_path = Path::new(bruker_d_folder_name).join("analysis.tdf");
+    let conn = Connection::open(db_path)?;
+
+    // prepare the query
+    let rows: Vec<&str> = vec![
+        "Frame",
+        "ScanNumBegin",
+        "ScanNumEnd",
+        "IsolationMz",
+        "IsolationWidth",
+        "CollisionEnergy",
+        "Target",
+    ];
+    let query = format!("SELECT {} FROM PrmFrameMsMsInfo", rows.join(", "));
+
+    // execute the query
+    let frames_rows: Result<Vec<PrmMsMsMeta>, _> = conn
+        .prepare(&query)?
+        .query_map([], |row| {
+            Ok(PrmMsMsMeta {
+                frame_id: row.get(0)?,
+                scan_num_begin: row.get(1)?,
+                scan_num_end: row.get(2)?,
+                isolation_mz: row.get(3)?,
+                isolation_width: row.get(4)?,
+                collision_energy: row.get(5)?,
+                target_id: row.get(6)?,
+            })
+        })?
+        .collect();
+
+    // return the frames
+    Ok(frames_rows?)
+}
+
+// Read the target list of a prm-PASEF acquisition
+pub fn read_prm_targets(
+    bruker_d_folder_name: &str,
+) -> Result<Vec<PrmTargetMeta>, Box<dyn std::error::Error>> {
+    // Connect to the database
+    let db_path = Path::new(bruker_d_folder_name).join("analysis.tdf");
+    let conn = Connection::open(db_path)?;
+
+    // prepare the query
+    let rows: Vec<&str> = vec![
+        "Id",
+        "ExternalId",
+        "Time",
+        "OneOverK0",
+        "MonoisotopicMz",
+        "Charge",
+        "Description",
+    ];
+    let query = format!("SELECT {} FROM PrmTargets", rows.join(", "));
+
+    // execute the query
+    let target_rows: Result<Vec<PrmTargetMeta>, _> = conn
+        .prepare(&query)?
+        .query_map([], |row| {
+            Ok(PrmTargetMeta {
+                target_id: row.get(0)?,
+                external_id: row.get(1)?,
+                time: row.get(2)?,
+                one_over_k0: row.get(3)?,
+                mono_mz: row.get(4)?,
+                charge: row.get(5)?,
+                description: row.get(6)?,
+            })
+        })?
+        .collect();
+
+    // return the targets
+    Ok(target_rows?)
+}
+
 // Read the global meta data from the analysis.tdf file
 pub fn read_global_meta_sql(
     bruker_d_folder_name: &str,
@@ -271,6 +373,18 @@ pub fn read_global_meta_sql(
     Ok(global_meta)
 }
 
+// Read the names of all tables of the analysis.tdf file
+pub fn read_table_names(bruker_d_folder_name: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let db_path = Path::new(bruker_d_folder_name).join("analysis.tdf");
+    let conn = Connection::open(db_path)?;
+
+    let names: Result<Vec<String>, _> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?
+        .query_map([], |row| row.get(0))?
+        .collect();
+    Ok(names?)
+}
+
 // Read the frame meta data from the analysis.tdf file
 pub fn read_meta_data_sql(
     bruker_d_folder_name: &str,
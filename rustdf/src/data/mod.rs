@@ -1,8 +1,12 @@
 pub mod acquisition;
+pub mod cache;
 pub mod dataset;
 pub mod dda;
 pub mod dia;
 pub mod handle;
 pub mod meta;
+pub mod prm;
+pub mod projection;
 pub mod raw;
+pub mod tdf_bin;
 pub mod utility;
@@ -0,0 +1,443 @@
+use crate::data::acquisition::AcquisitionMode;
+use crate::data::handle::{IndexConverter, TimsData, TimsDataLoader};
+use crate::data::meta::{
+    read_global_meta_sql, read_meta_data_sql, read_prm_frame_ms_ms_info, read_prm_targets,
+    read_table_names, FrameMeta, PrmMsMsMeta, PrmTargetMeta,
+};
+use crate::data::projection::{FrameColumns, PartialTimsFrame};
+use mscore::timstof::frame::{RawTimsFrame, TimsFrame};
+use mscore::timstof::slice::TimsSlice;
+use rayon::prelude::*;
+use mscore::parallel;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// A target of a prm-PASEF acquisition together with the frames it was fragmented in
+#[derive(Debug, Clone)]
+pub struct PRMTarget {
+    pub target_id: i64,
+    pub external_id: Option<String>,
+    pub description: Option<String>,
+    pub precursor_mz: f64,
+    pub charge: Option<i64>,
+    pub one_over_k0: f64,
+    /// scheduled retention time of the target
+    pub retention_time: f64,
+    /// retention time of the first and last frame the target was fragmented in, None if it never was
+    pub rt_begin: Option<f64>,
+    pub rt_end: Option<f64>,
+    pub frame_ids: Vec<u32>,
+}
+
+/// Extracted ion chromatograms of fragment m/z values of a target, one trace per fragment
+#[derive(Debug, Clone)]
+pub struct PRMXic {
+    pub target_id: i64,
+    pub fragment_mz: Vec<f64>,
+    pub retention_time: Vec<f64>,
+    /// summed intensity per fragment and frame, `intensity[i][j]` belongs to `fragment_mz[i]` and `retention_time[j]`
+    pub intensity: Vec<Vec<f64>>,
+}
+
+pub struct TimsDatasetPRM {
+    pub loader: TimsDataLoader,
+    pub meta_data: Vec<FrameMeta>,
+    pub prm_ms_ms_info: Vec<PrmMsMsMeta>,
+    pub targets: Vec<PrmTargetMeta>,
+}
+
+impl TimsDatasetPRM {
+    pub fn new(
+        bruker_lib_path: &str,
+        data_path: &str,
+        in_memory: bool,
+        use_bruker_sdk: bool,
+    ) -> Self {
+        Self::try_new(bruker_lib_path, data_path, in_memory, use_bruker_sdk)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Open a dataset, fails if the meta data cannot be read, the run has no frames or was not acquired in
+    /// prm-PASEF mode, the latter with an `AcquisitionModeMismatch`
+    pub fn try_new(
+        bruker_lib_path: &str,
+        data_path: &str,
+        in_memory: bool,
+        use_bruker_sdk: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let global_meta_data = read_global_meta_sql(data_path)?;
+        let meta_data = read_meta_data_sql(data_path)?;
+        let first_frame = meta_data
+            .first()
+            .ok_or_else(|| format!("{} has no frames", data_path))?;
+
+        let table_names = read_table_names(data_path)?;
+        AcquisitionMode::detect(&table_names, first_frame.scan_mode)
+            .check(&[AcquisitionMode::PRM], data_path)?;
+
+        let prm_ms_ms_info = read_prm_frame_ms_ms_info(data_path)?;
+        let targets = read_prm_targets(data_path)?;
+
+        let scan_max_index = meta_data.iter().map(|x| x.num_scans).max().unwrap_or(0) as u32;
+        let im_lower = global_meta_data.one_over_k0_range_lower;
+        let im_upper = global_meta_data.one_over_k0_range_upper;
+
+        let tof_max_index = global_meta_data.tof_max_index;
+        let mz_lower = global_meta_data.mz_acquisition_range_lower;
+        let mz_upper = global_meta_data.mz_acquisition_range_upper;
+
+        let loader = match in_memory {
+            true => TimsDataLoader::new_in_memory(
+                bruker_lib_path,
+                data_path,
+                use_bruker_sdk,
+                scan_max_index,
+                im_lower,
+                im_upper,
+                tof_max_index,
+                mz_lower,
+                mz_upper,
+            ),
+            false => TimsDataLoader::new_lazy(
+                bruker_lib_path,
+                data_path,
+                use_bruker_sdk,
+                scan_max_index,
+                im_lower,
+                im_upper,
+                tof_max_index,
+                mz_lower,
+                mz_upper,
+            ),
+        };
+
+        Ok(TimsDatasetPRM {
+            loader,
+            meta_data,
+            prm_ms_ms_info,
+            targets,
+        })
+    }
+
+    /// Get all targets with the frames they were fragmented in
+    pub fn get_targets(&self) -> Vec<PRMTarget> {
+        map_targets_to_frames(&self.targets, &self.prm_ms_ms_info, &self.meta_data)
+    }
+
+    /// Get the fragment spectra of a target, every frame it was fragmented in restricted to the isolated scan range
+    ///
+    /// # Arguments
+    ///
+    /// * `target_id` - Id of the target in the PrmTargets table
+    /// * `num_threads` - Number of threads used to read frames
+    ///
+    /// # Returns
+    ///
+    /// * Fragment frames ordered by frame id, empty if the target is unknown
+    ///
+    pub fn get_target_fragment_spectra(&self, target_id: i64, num_threads: usize) -> Vec<TimsFrame> {
+        let mut prm_info: Vec<&PrmMsMsMeta> = self
+            .prm_ms_ms_info
+            .iter()
+            .filter(|info| info.target_id == target_id)
+            .collect();
+        prm_info.sort_by_key(|info| info.frame_id);
+
+        let pool = parallel::pool(num_threads);
+
+        pool.install(|| {
+            prm_info
+                .par_iter()
+                .map(|prm_info| {
+                    let frame = self.loader.get_frame(prm_info.frame_id as u32);
+
+                    // get five percent of the scan range
+                    let scan_margin = (prm_info.scan_num_end - prm_info.scan_num_begin) / 20;
+
+                    frame.filter_ranged(
+                        0.0,
+                        2000.0,
+                        (prm_info.scan_num_begin - scan_margin) as i32,
+                        (prm_info.scan_num_end + scan_margin) as i32,
+                        0.0,
+                        5.0,
+                        0.0,
+                        1e9,
+                    )
+                })
+                .collect()
+        })
+    }
+
+    /// Get extracted ion chromatograms of fragment m/z values over all fragment spectra of a target
+    ///
+    /// # Arguments
+    ///
+    /// * `target_id` - Id of the target in the PrmTargets table
+    /// * `fragment_mz` - Fragment m/z values to extract
+    /// * `tolerance_ppm` - Peaks within this tolerance of a fragment m/z are summed up
+    /// * `num_threads` - Number of threads used to read frames
+    ///
+    pub fn get_target_xic(
+        &self,
+        target_id: i64,
+        fragment_mz: &[f64],
+        tolerance_ppm: f64,
+        num_threads: usize,
+    ) -> PRMXic {
+        let frames = self.get_target_fragment_spectra(target_id, num_threads);
+        extract_xic(target_id, &frames, fragment_mz, tolerance_ppm)
+    }
+}
+
+fn map_targets_to_frames(
+    targets: &[PrmTargetMeta],
+    prm_ms_ms_info: &[PrmMsMsMeta],
+    meta_data: &[FrameMeta],
+) -> Vec<PRMTarget> {
+    let frame_times: BTreeMap<i64, f64> = meta_data.iter().map(|x| (x.id, x.time)).collect();
+
+    let mut target_frames: BTreeMap<i64, Vec<u32>> = BTreeMap::new();
+    for info in prm_ms_ms_info {
+        target_frames
+            .entry(info.target_id)
+            .or_default()
+            .push(info.frame_id as u32);
+    }
+
+    targets
+        .iter()
+        .map(|target| {
+            let mut frame_ids = target_frames.remove(&target.target_id).unwrap_or_default();
+            frame_ids.sort();
+            frame_ids.dedup();
+
+            let times: Vec<f64> = frame_ids
+                .iter()
+                .filter_map(|id| frame_times.get(&(*id as i64)).copied())
+                .collect();
+
+            PRMTarget {
+                target_id: target.target_id,
+                external_id: target.external_id.clone(),
+                description: target.description.clone(),
+                precursor_mz: target.mono_mz,
+                charge: target.charge,
+                one_over_k0: target.one_over_k0,
+                retention_time: target.time,
+                rt_begin: times.iter().cloned().reduce(f64::min),
+                rt_end: times.iter().cloned().reduce(f64::max),
+                frame_ids,
+            }
+        })
+        .collect()
+}
+
+fn extract_xic(target_id: i64, frames: &[TimsFrame], fragment_mz: &[f64], tolerance_ppm: f64) -> PRMXic {
+    let intensity = fragment_mz
+        .iter()
+        .map(|&mz| {
+            let tolerance = mz * tolerance_ppm / 1e6;
+            frames
+                .iter()
+                .map(|frame| {
+                    frame
+                        .ims_frame
+                        .mz
+                        .iter()
+                        .zip(frame.ims_frame.intensity.iter())
+                        .filter(|(peak_mz, _)| (**peak_mz - mz).abs() <= tolerance)
+                        .map(|(_, intensity)| intensity)
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+
+    PRMXic {
+        target_id,
+        fragment_mz: fragment_mz.to_vec(),
+        retention_time: frames.iter().map(|frame| frame.ims_frame.retention_time).collect(),
+        intensity,
+    }
+}
+
+impl TimsData for TimsDatasetPRM {
+    fn get_frame(&self, frame_id: u32) -> TimsFrame {
+        self.loader.get_frame(frame_id)
+    }
+
+    fn get_raw_frame(&self, frame_id: u32) -> RawTimsFrame {
+        self.loader.get_raw_frame(frame_id)
+    }
+
+    fn get_frame_projected(&self, frame_id: u32, columns: FrameColumns) -> PartialTimsFrame {
+        self.loader.get_frame_projected(frame_id, columns)
+    }
+
+    fn get_slice(&self, frame_ids: Vec<u32>, num_threads: usize) -> TimsSlice {
+        self.loader.get_slice(frame_ids, num_threads)
+    }
+
+    fn get_slice_projected(
+        &self,
+        frame_ids: Vec<u32>,
+        columns: FrameColumns,
+        num_threads: usize,
+    ) -> Vec<PartialTimsFrame> {
+        self.loader.get_slice_projected(frame_ids, columns, num_threads)
+    }
+
+    fn get_acquisition_mode(&self) -> AcquisitionMode {
+        self.loader.get_acquisition_mode().clone()
+    }
+
+    fn get_frame_count(&self) -> i32 {
+        self.loader.get_frame_count()
+    }
+
+    fn get_data_path(&self) -> &str {
+        self.loader.get_data_path()
+    }
+}
+
+impl IndexConverter for TimsDatasetPRM {
+    fn tof_to_mz(&self, frame_id: u32, tof_values: &Vec<u32>) -> Vec<f64> {
+        self.loader
+            .get_index_converter()
+            .tof_to_mz(frame_id, tof_values)
+    }
+
+    fn mz_to_tof(&self, frame_id: u32, mz_values: &Vec<f64>) -> Vec<u32> {
+        self.loader
+            .get_index_converter()
+            .mz_to_tof(frame_id, mz_values)
+    }
+
+    fn scan_to_inverse_mobility(&self, frame_id: u32, scan_values: &Vec<u32>) -> Vec<f64> {
+        self.loader
+            .get_index_converter()
+            .scan_to_inverse_mobility(frame_id, scan_values)
+    }
+
+    fn inverse_mobility_to_scan(
+        &self,
+        frame_id: u32,
+        inverse_mobility_values: &Vec<f64>,
+    ) -> Vec<u32> {
+        self.loader
+            .get_index_converter()
+            .inverse_mobility_to_scan(frame_id, inverse_mobility_values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::acquisition::AcquisitionModeMismatch;
+    use mscore::data::spectrum::MsType;
+
+    fn frame_meta(id: i64, time: f64) -> FrameMeta {
+        FrameMeta {
+            id,
+            time,
+            polarity: "+".to_string(),
+            scan_mode: 10,
+            ms_ms_type: 10,
+            tims_id: 0,
+            max_intensity: 0.0,
+            sum_intensity: 0.0,
+            num_scans: 918,
+            num_peaks: 0,
+            mz_calibration: 1,
+            t_1: 0.0,
+            t_2: 0.0,
+            tims_calibration: 1,
+            property_group: 1,
+            accumulation_time: 100.0,
+            ramp_time: 100.0,
+        }
+    }
+
+    fn prm_info(frame_id: i64, target_id: i64) -> PrmMsMsMeta {
+        PrmMsMsMeta {
+            frame_id,
+            scan_num_begin: 400,
+            scan_num_end: 450,
+            isolation_mz: 500.0,
+            isolation_width: 2.0,
+            collision_energy: 30.0,
+            target_id,
+        }
+    }
+
+    fn target(target_id: i64) -> PrmTargetMeta {
+        PrmTargetMeta {
+            target_id,
+            external_id: Some(format!("PEPTIDE{}", target_id)),
+            time: 120.0,
+            one_over_k0: 1.0,
+            mono_mz: 500.25,
+            charge: Some(2),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_map_targets_to_frames() {
+        let meta_data: Vec<FrameMeta> = (1..=6).map(|id| frame_meta(id, id as f64 * 0.1)).collect();
+        let info = vec![prm_info(5, 1), prm_info(2, 1), prm_info(3, 2), prm_info(2, 1)];
+        let targets = map_targets_to_frames(&[target(1), target(2), target(3)], &info, &meta_data);
+
+        assert_eq!(targets[0].frame_ids, vec![2, 5]);
+        assert_eq!((targets[0].rt_begin, targets[0].rt_end), (Some(0.2), Some(0.5)));
+        assert_eq!(targets[1].frame_ids, vec![3]);
+        assert!(targets[2].frame_ids.is_empty() && targets[2].rt_begin.is_none());
+        assert_eq!(targets[0].external_id.as_deref(), Some("PEPTIDE1"));
+    }
+
+    #[test]
+    fn test_extract_xic() {
+        let frame = |frame_id: i32, intensity: Vec<f64>| {
+            TimsFrame::new(frame_id, MsType::FragmentPrm, frame_id as f64, vec![1, 1, 2], vec![1.0; 3], vec![1, 2, 3], vec![400.0, 400.002, 600.0], intensity)
+        };
+        let frames = vec![frame(1, vec![1.0, 2.0, 3.0]), frame(2, vec![4.0, 5.0, 6.0])];
+        let xic = extract_xic(7, &frames, &[400.001, 600.0, 700.0], 10.0);
+
+        assert_eq!(xic.retention_time, vec![1.0, 2.0]);
+        assert_eq!(xic.intensity, vec![vec![3.0, 9.0], vec![3.0, 6.0], vec![0.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_try_new_reports_errors() {
+        let missing = std::env::temp_dir().join(format!("prm_missing_{}.d", std::process::id()));
+        assert!(TimsDatasetPRM::try_new("", missing.to_str().unwrap(), false, false).is_err());
+
+        // a run without PRM tables in DIA mode is rejected with a mode mismatch
+        let path = std::env::temp_dir().join(format!("prm_dia_{}.d", std::process::id()));
+        let writer = crate::sim::writer::TdfWriter {
+            mz_lower: 100.0,
+            mz_upper: 1700.0,
+            im_lower: 0.6,
+            im_upper: 1.6,
+            num_scans: 100,
+            tof_max_index: 400_000,
+            scan_mode: 9,
+            compression_level: 1,
+            window_groups: Vec::new(),
+            frame_to_window_group: Vec::new(),
+        };
+        writer.create(&path).unwrap();
+
+        // without frames the mode cannot be detected
+        let error = TimsDatasetPRM::try_new("", path.to_str().unwrap(), false, false).err().unwrap();
+        assert!(error.to_string().contains("has no frames"));
+
+        let frame = TimsFrame::new(1, MsType::Precursor, 0.1, vec![1], vec![1.5], vec![0], vec![500.0], vec![10.0]);
+        writer.write_frames(&[frame], &path).unwrap();
+        let error = TimsDatasetPRM::try_new("", path.to_str().unwrap(), false, false).err().unwrap();
+        assert!(error.downcast_ref::<AcquisitionModeMismatch>().is_some());
+
+        std::fs::remove_dir_all(&path).ok();
+    }
+}
@@ -0,0 +1,346 @@
+use std::ops::{BitAnd, BitOr};
+
+use mscore::data::spectrum::MsType;
+use mscore::timstof::frame::{ImsFrame, RawTimsFrame, TimsFrame};
+
+use crate::data::handle::IndexConverter;
+use crate::data::utility::flatten_scan_values;
+
+/// Set of frame columns to decode, can be combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameColumns(u8);
+
+impl FrameColumns {
+    pub const NONE: FrameColumns = FrameColumns(0);
+    pub const SCAN: FrameColumns = FrameColumns(1);
+    pub const MOBILITY: FrameColumns = FrameColumns(1 << 1);
+    pub const TOF: FrameColumns = FrameColumns(1 << 2);
+    pub const MZ: FrameColumns = FrameColumns(1 << 3);
+    pub const INTENSITY: FrameColumns = FrameColumns(1 << 4);
+    pub const ALL: FrameColumns = FrameColumns(0b11111);
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        FrameColumns(bits & FrameColumns::ALL.0)
+    }
+
+    pub fn contains(&self, other: FrameColumns) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Parse a column name (scan, mobility, tof, mz, intensity)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "scan" => Some(FrameColumns::SCAN),
+            "mobility" | "inv_mobility" => Some(FrameColumns::MOBILITY),
+            "tof" => Some(FrameColumns::TOF),
+            "mz" => Some(FrameColumns::MZ),
+            "intensity" => Some(FrameColumns::INTENSITY),
+            _ => None,
+        }
+    }
+}
+
+impl BitOr for FrameColumns {
+    type Output = FrameColumns;
+
+    fn bitor(self, rhs: FrameColumns) -> FrameColumns {
+        FrameColumns(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for FrameColumns {
+    type Output = FrameColumns;
+
+    fn bitand(self, rhs: FrameColumns) -> FrameColumns {
+        FrameColumns(self.0 & rhs.0)
+    }
+}
+
+/// A frame where only the requested columns are decoded, columns that were not requested are None
+#[derive(Debug, Clone)]
+pub struct PartialTimsFrame {
+    pub frame_id: i32,
+    pub ms_type: MsType,
+    pub retention_time: f64,
+    pub scan: Option<Vec<i32>>,
+    pub mobility: Option<Vec<f64>>,
+    pub tof: Option<Vec<i32>>,
+    pub mz: Option<Vec<f64>>,
+    pub intensity: Option<Vec<f64>>,
+}
+
+impl PartialTimsFrame {
+    /// Project a fully decoded frame onto the requested columns
+    pub fn from_frame(frame: TimsFrame, columns: FrameColumns) -> Self {
+        PartialTimsFrame {
+            frame_id: frame.frame_id,
+            ms_type: frame.ms_type,
+            retention_time: frame.ims_frame.retention_time,
+            scan: columns.contains(FrameColumns::SCAN).then_some(frame.scan),
+            mobility: columns.contains(FrameColumns::MOBILITY).then_some(frame.ims_frame.mobility),
+            tof: columns.contains(FrameColumns::TOF).then_some(frame.tof),
+            mz: columns.contains(FrameColumns::MZ).then_some(frame.ims_frame.mz),
+            intensity: columns.contains(FrameColumns::INTENSITY).then_some(frame.ims_frame.intensity),
+        }
+    }
+
+    pub fn columns(&self) -> FrameColumns {
+        let mut columns = FrameColumns::NONE;
+        if self.scan.is_some() {
+            columns = columns | FrameColumns::SCAN;
+        }
+        if self.mobility.is_some() {
+            columns = columns | FrameColumns::MOBILITY;
+        }
+        if self.tof.is_some() {
+            columns = columns | FrameColumns::TOF;
+        }
+        if self.mz.is_some() {
+            columns = columns | FrameColumns::MZ;
+        }
+        if self.intensity.is_some() {
+            columns = columns | FrameColumns::INTENSITY;
+        }
+        columns
+    }
+}
+
+impl From<PartialTimsFrame> for TimsFrame {
+    /// Columns that were not decoded are left empty
+    fn from(frame: PartialTimsFrame) -> Self {
+        TimsFrame {
+            frame_id: frame.frame_id,
+            ms_type: frame.ms_type,
+            scan: frame.scan.unwrap_or_default(),
+            tof: frame.tof.unwrap_or_default(),
+            ims_frame: ImsFrame {
+                retention_time: frame.retention_time,
+                mobility: frame.mobility.unwrap_or_default(),
+                mz: frame.mz.unwrap_or_default(),
+                intensity: frame.intensity.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// Decompressed frame data before any index conversion
+pub(crate) struct DecodedFrame {
+    pub frame_id: u32,
+    pub ms_type: MsType,
+    pub retention_time: f64,
+    /// scan index per peak
+    pub scan: Vec<u32>,
+    pub tof: Vec<u32>,
+    pub intensity: Vec<f64>,
+}
+
+impl DecodedFrame {
+    /// Convert the requested columns, tof to m/z and scan to inverse mobility are only called if needed
+    pub fn project<C: IndexConverter + ?Sized>(self, index_converter: &C, columns: FrameColumns) -> PartialTimsFrame {
+        // empty frames never reach the converter
+        let is_empty = self.tof.is_empty();
+        let mz = columns.contains(FrameColumns::MZ).then(|| match is_empty {
+            true => Vec::new(),
+            false => index_converter.tof_to_mz(self.frame_id, &self.tof),
+        });
+        let mobility = columns.contains(FrameColumns::MOBILITY).then(|| match is_empty {
+            true => Vec::new(),
+            false => index_converter.scan_to_inverse_mobility(self.frame_id, &self.scan),
+        });
+
+        PartialTimsFrame {
+            frame_id: self.frame_id as i32,
+            ms_type: self.ms_type,
+            retention_time: self.retention_time,
+            scan: columns
+                .contains(FrameColumns::SCAN)
+                .then(|| self.scan.iter().map(|&x| x as i32).collect()),
+            mobility,
+            tof: columns
+                .contains(FrameColumns::TOF)
+                .then(|| self.tof.iter().map(|&x| x as i32).collect()),
+            mz,
+            intensity: columns.contains(FrameColumns::INTENSITY).then_some(self.intensity),
+        }
+    }
+}
+
+/// Calibrate a raw frame, the same conversion the loaders apply when decoding a frame
+pub trait ToTimsFrame {
+    /// Expand the scan run lengths to one scan index per peak and convert tof indices to m/z
+    /// and scan indices to inverse mobility
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - Calibration of the run the frame belongs to
+    /// * `retention_time` - Retention time of the calibrated frame
+    ///
+    fn to_tims_frame(&self, converter: &dyn IndexConverter, retention_time: f64) -> TimsFrame;
+}
+
+impl ToTimsFrame for RawTimsFrame {
+    fn to_tims_frame(&self, converter: &dyn IndexConverter, retention_time: f64) -> TimsFrame {
+        DecodedFrame {
+            frame_id: self.frame_id as u32,
+            ms_type: self.ms_type.clone(),
+            retention_time,
+            scan: flatten_scan_values(&self.scan, true),
+            tof: self.tof.clone(),
+            intensity: self.intensity.clone(),
+        }
+        .project(converter, FrameColumns::ALL)
+        .into()
+    }
+}
+
+/// Turn a calibrated frame back into the raw run-length representation of a TDF frame
+pub trait ToRawTimsFrame {
+    /// Peaks are ordered by scan and tof index, the scan column of the raw frame holds the number of
+    /// peaks per scan, from scan 0 up to the highest scan with a peak. Scan and tof indices stored
+    /// in the frame are kept as they are, the converter only computes them from inverse mobility
+    /// and m/z if the frame does not carry them
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - Calibration of the run the frame belongs to
+    ///
+    fn to_raw(&self, converter: &dyn IndexConverter) -> RawTimsFrame;
+}
+
+impl ToRawTimsFrame for TimsFrame {
+    fn to_raw(&self, converter: &dyn IndexConverter) -> RawTimsFrame {
+        let num_peaks = self.ims_frame.intensity.len();
+        let frame_id = self.frame_id as u32;
+
+        let scan: Vec<u32> = match self.scan.len() == num_peaks {
+            true => self.scan.iter().map(|&x| x as u32).collect(),
+            false => converter.inverse_mobility_to_scan(frame_id, &self.ims_frame.mobility),
+        };
+        let tof: Vec<u32> = match self.tof.len() == num_peaks {
+            true => self.tof.iter().map(|&x| x as u32).collect(),
+            false => converter.mz_to_tof(frame_id, &self.ims_frame.mz),
+        };
+
+        let mut order: Vec<usize> = (0..num_peaks).collect();
+        order.sort_by_key(|&i| (scan[i], tof[i]));
+
+        let mut scan_counts = vec![0u32; scan.iter().max().map_or(0, |&max| max as usize + 1)];
+        for &s in &scan {
+            scan_counts[s as usize] += 1;
+        }
+
+        RawTimsFrame {
+            frame_id: self.frame_id,
+            retention_time: self.ims_frame.retention_time,
+            ms_type: self.ms_type.clone(),
+            scan: scan_counts,
+            tof: order.iter().map(|&i| tof[i]).collect(),
+            intensity: order.iter().map(|&i| self.ims_frame.intensity[i]).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::handle::SimpleIndexConverter;
+
+    fn decoded() -> DecodedFrame {
+        DecodedFrame {
+            frame_id: 1,
+            ms_type: MsType::Precursor,
+            retention_time: 12.5,
+            scan: vec![0, 0, 5, 9],
+            tof: vec![100, 2000, 150, 300000],
+            intensity: vec![10.0, 20.0, 30.0, 40.0],
+        }
+    }
+
+    #[test]
+    fn test_projected_columns_equal_full_decode() {
+        let converter = SimpleIndexConverter::from_boundaries(100.0, 1700.0, 400000, 0.6, 1.6, 927);
+        let full = decoded().project(&converter, FrameColumns::ALL);
+        assert_eq!(full.columns(), FrameColumns::ALL);
+
+        for columns in [
+            FrameColumns::MZ | FrameColumns::INTENSITY,
+            FrameColumns::SCAN | FrameColumns::INTENSITY,
+            FrameColumns::MOBILITY,
+            FrameColumns::TOF | FrameColumns::SCAN,
+        ] {
+            let projected = decoded().project(&converter, columns);
+            assert_eq!(projected.columns(), columns);
+            assert_eq!(projected.retention_time, full.retention_time);
+            if let Some(mz) = &projected.mz {
+                assert_eq!(mz, full.mz.as_ref().unwrap());
+            }
+            if let Some(mobility) = &projected.mobility {
+                assert_eq!(mobility, full.mobility.as_ref().unwrap());
+            }
+            if let Some(scan) = &projected.scan {
+                assert_eq!(scan, full.scan.as_ref().unwrap());
+            }
+            if let Some(tof) = &projected.tof {
+                assert_eq!(tof, full.tof.as_ref().unwrap());
+            }
+            if let Some(intensity) = &projected.intensity {
+                assert_eq!(intensity, full.intensity.as_ref().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_frame_matches_decoded_projection() {
+        let converter = SimpleIndexConverter::from_boundaries(100.0, 1700.0, 400000, 0.6, 1.6, 927);
+        let frame: TimsFrame = decoded().project(&converter, FrameColumns::ALL).into();
+        let columns = FrameColumns::MZ | FrameColumns::INTENSITY;
+        let from_frame = PartialTimsFrame::from_frame(frame, columns);
+        let projected = decoded().project(&converter, columns);
+        assert_eq!(from_frame.mz, projected.mz);
+        assert_eq!(from_frame.intensity, projected.intensity);
+        assert!(from_frame.scan.is_none() && from_frame.mobility.is_none() && from_frame.tof.is_none());
+    }
+
+    #[test]
+    fn test_raw_frame_round_trip() {
+        let converter = SimpleIndexConverter::from_boundaries(100.0, 1700.0, 400000, 0.6, 1.6, 927);
+        let raw = RawTimsFrame {
+            frame_id: 1,
+            retention_time: 0.0,
+            ms_type: MsType::Precursor,
+            scan: vec![2, 0, 1, 0],
+            tof: vec![100, 2000, 150],
+            intensity: vec![10.0, 20.0, 30.0],
+        };
+
+        let frame = raw.to_tims_frame(&converter, 12.5);
+        assert_eq!(frame.scan, vec![0, 0, 2]);
+        assert_eq!(frame.ims_frame.retention_time, 12.5);
+        assert_eq!(frame.ims_frame.mz, converter.tof_to_mz(1, &raw.tof));
+
+        // trailing empty scans are not part of the reconstructed run lengths
+        let reconstructed = frame.to_raw(&converter);
+        assert_eq!(reconstructed.scan, vec![2, 0, 1]);
+        assert_eq!(reconstructed.tof, raw.tof);
+        assert_eq!(reconstructed.intensity, raw.intensity);
+
+        // without scan and tof columns, indices come from the calibration
+        let calibrated_only = TimsFrame { scan: vec![], tof: vec![], ..frame.clone() };
+        let reconstructed = calibrated_only.to_raw(&converter);
+        assert_eq!(reconstructed.scan.iter().sum::<u32>(), 3);
+        assert_eq!(reconstructed.tof, converter.mz_to_tof(1, &frame.ims_frame.mz));
+    }
+
+    #[test]
+    fn test_frame_columns() {
+        let columns = FrameColumns::MZ | FrameColumns::INTENSITY;
+        assert!(columns.contains(FrameColumns::MZ));
+        assert!(!columns.contains(FrameColumns::SCAN));
+        assert_eq!(FrameColumns::from_name("Intensity"), Some(FrameColumns::INTENSITY));
+        assert_eq!(FrameColumns::from_bits(0xff), FrameColumns::ALL);
+    }
+}
@@ -44,8 +44,7 @@ impl BrukerTimsDataLibrary {
             let func: Symbol<unsafe extern "C" fn(*const c_char, u32) -> u64> =
                 lib.get(b"tims_open")?;
             let path = std::ffi::CString::new(data_path)?;
-            let handle = func(path.as_ptr(), 0);
-            handle
+            func(path.as_ptr(), 0)
         };
 
         // return the BrukerTimsDataLibrary struct
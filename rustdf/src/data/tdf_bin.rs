@@ -0,0 +1,93 @@
+use byteorder::{ByteOrder, LittleEndian};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Access to the compressed frame blocks of `analysis.tdf_bin`
+///
+/// Every block starts with its size in bytes and the number of scans, both as little endian i32,
+/// followed by the compressed peaks. Both variants can be shared between threads.
+pub enum TdfBinReader {
+    /// the file mapped into memory, blocks are borrowed from the mapping without copying
+    Mapped(Mmap),
+    /// the file is opened and read at the block offset for every block, used where mapping fails,
+    /// e.g. on some network mounts
+    Buffered(PathBuf),
+}
+
+impl TdfBinReader {
+    /// Map the `analysis.tdf_bin` of a dataset into memory, fall back to buffered reads if that fails
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The path to the .d folder
+    ///
+    pub fn open(data_path: &str) -> Self {
+        let file_path = Path::new(data_path).join("analysis.tdf_bin");
+        // the mapping is only valid as long as no other process truncates the file, acquisition software
+        // does not modify a finished tdf_bin
+        match File::open(&file_path).and_then(|file| unsafe { Mmap::map(&file) }) {
+            Ok(mmap) => TdfBinReader::Mapped(mmap),
+            Err(_) => TdfBinReader::Buffered(file_path),
+        }
+    }
+
+    /// Read the `analysis.tdf_bin` of a dataset with buffered IO only
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The path to the .d folder
+    ///
+    pub fn open_buffered(data_path: &str) -> Self {
+        TdfBinReader::Buffered(Path::new(data_path).join("analysis.tdf_bin"))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, TdfBinReader::Mapped(_))
+    }
+
+    /// Read the block of a frame
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset of the block, the TimsId of the frame in the Frames table
+    ///
+    /// # Returns
+    ///
+    /// * The whole block including size and scan count, borrowed from the mapping if the file is mapped
+    ///
+    pub fn read_block(&self, offset: usize) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            TdfBinReader::Mapped(mmap) => {
+                let block = |size: usize| offset.checked_add(size).and_then(|end| mmap.get(offset..end)).ok_or_else(|| out_of_range(offset));
+                let size = block_size(block(8)?)?;
+                block(size).map(Cow::Borrowed)
+            }
+            TdfBinReader::Buffered(file_path) => {
+                let mut infile = File::open(file_path)?;
+                infile.seek(SeekFrom::Start(offset as u64))?;
+
+                let mut block = vec![0u8; 8];
+                infile.read_exact(&mut block)?;
+                let size = block_size(&block)?;
+                block.resize(size, 0);
+                infile.read_exact(&mut block[8..])?;
+                Ok(Cow::Owned(block))
+            }
+        }
+    }
+}
+
+fn block_size(header: &[u8]) -> io::Result<usize> {
+    let size = LittleEndian::read_i32(&header[..4]);
+    if size < 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid frame block size {}", size)));
+    }
+    Ok(size as usize)
+}
+
+fn out_of_range(offset: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, format!("frame block at offset {} exceeds analysis.tdf_bin", offset))
+}
@@ -2,9 +2,11 @@ use byteorder::{ByteOrder, LittleEndian};
 use mscore::timstof::frame::TimsFrame;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel;
+use std::cell::RefCell;
 use std::io;
 use std::io::{Read, Write};
+use std::ops::Range;
 
 /// Decompresses a ZSTD compressed byte array
 ///
@@ -23,6 +25,49 @@ pub fn zstd_decompress(compressed_data: &[u8]) -> io::Result<Vec<u8>> {
     Ok(decompressed_data)
 }
 
+thread_local! {
+    /// ZSTD context and output buffer of the current thread, reused by `with_zstd_decompressed`
+    static ZSTD_SCRATCH: RefCell<Option<(zstd::bulk::Decompressor<'static>, Vec<u8>)>> = const { RefCell::new(None) };
+}
+
+/// Decompresses a ZSTD compressed byte array into a buffer owned by the current thread
+///
+/// Decoding many frames on the same thread reuses the ZSTD context and the output buffer, which only
+/// grows to the largest frame seen, instead of allocating both for every frame like `zstd_decompress`.
+/// `f` must not call `with_zstd_decompressed` itself.
+///
+/// # Arguments
+///
+/// * `compressed_data` - A byte slice that holds the compressed data
+/// * `f` - Called with the decompressed data, which is only valid during the call
+///
+/// # Returns
+///
+/// * The result of `f`
+///
+pub fn with_zstd_decompressed<R>(compressed_data: &[u8], f: impl FnOnce(&[u8]) -> R) -> io::Result<R> {
+    ZSTD_SCRATCH.with(|scratch| {
+        let mut scratch = scratch.borrow_mut();
+        if scratch.is_none() {
+            *scratch = Some((zstd::bulk::Decompressor::new()?, Vec::new()));
+        }
+        let (decompressor, buffer) = scratch.as_mut().unwrap();
+
+        buffer.clear();
+        match zstd::zstd_safe::get_frame_content_size(compressed_data) {
+            Ok(Some(size)) => {
+                buffer.reserve(size as usize);
+                decompressor.decompress_to_buffer(compressed_data, buffer)?;
+            }
+            // frames written without their size are streamed
+            _ => {
+                zstd::Decoder::new(compressed_data)?.read_to_end(buffer)?;
+            }
+        }
+        Ok(f(buffer))
+    })
+}
+
 /// Compresses a byte array using ZSTD
 ///
 /// # Arguments
@@ -91,10 +136,7 @@ pub fn compress_collection(
     compression_level: i32,
     num_threads: usize,
 ) -> Vec<Vec<u8>> {
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let pool = parallel::pool(num_threads);
 
     let result = pool.install(|| {
         frames
@@ -120,6 +162,9 @@ pub fn compress_collection(
     result
 }
 
+/// Scan, tof and intensity columns of decompressed bruker binary data
+pub type RawPeaks = (Vec<u32>, Vec<u32>, Vec<u32>);
+
 /// Parses the decompressed bruker binary data
 ///
 /// # Arguments
@@ -134,46 +179,33 @@ pub fn compress_collection(
 ///
 pub fn parse_decompressed_bruker_binary_data(
     decompressed_bytes: &[u8],
-) -> Result<(Vec<u32>, Vec<u32>, Vec<u32>), Box<dyn std::error::Error>> {
-    let mut buffer_u32 = Vec::new();
-
-    for i in 0..(decompressed_bytes.len() / 4) {
-        let value = LittleEndian::read_u32(&[
+) -> Result<RawPeaks, Box<dyn std::error::Error>> {
+    // the bytes of every u32 are stored in four planes, values are assembled on the fly
+    // instead of transposing the whole buffer first
+    let quarter = decompressed_bytes.len() / 4;
+    let value = |i: usize| {
+        LittleEndian::read_u32(&[
             decompressed_bytes[i],
-            decompressed_bytes[i + (decompressed_bytes.len() / 4)],
-            decompressed_bytes[i + (2 * decompressed_bytes.len() / 4)],
-            decompressed_bytes[i + (3 * decompressed_bytes.len() / 4)],
-        ]);
-        buffer_u32.push(value);
-    }
+            decompressed_bytes[i + quarter],
+            decompressed_bytes[i + 2 * quarter],
+            decompressed_bytes[i + 3 * quarter],
+        ])
+    };
 
     // get the number of scans
-    let scan_count = buffer_u32[0] as usize;
+    let scan_count = value(0) as usize;
 
     // get the scan indices
-    let mut scan_indices: Vec<u32> = buffer_u32[..scan_count].to_vec();
-    for index in &mut scan_indices {
-        *index /= 2;
-    }
+    let mut scan_indices: Vec<u32> = (0..scan_count).map(|i| value(i) / 2).collect();
 
     // first scan index is always 0?
     scan_indices[0] = 0;
 
     // get the tof indices, which are the first half of the buffer after the scan indices
-    let mut tof_indices: Vec<u32> = buffer_u32
-        .iter()
-        .skip(scan_count)
-        .step_by(2)
-        .cloned()
-        .collect();
+    let mut tof_indices: Vec<u32> = (scan_count..quarter).step_by(2).map(value).collect();
 
     // get the intensities, which are the second half of the buffer
-    let intensities: Vec<u32> = buffer_u32
-        .iter()
-        .skip(scan_count + 1)
-        .step_by(2)
-        .cloned()
-        .collect();
+    let intensities: Vec<u32> = (scan_count + 1..quarter).step_by(2).map(value).collect();
 
     // calculate the last scan before moving scan indices
     let last_scan = intensities.len() as u32 - scan_indices[1..].iter().sum::<u32>();
@@ -203,6 +235,73 @@ pub fn parse_decompressed_bruker_binary_data(
     Ok((scan_indices, adjusted_tof_indices, intensities))
 }
 
+/// Parses the peaks of a range of scans from decompressed bruker binary data
+///
+/// Only the peak counts of all scans are read, tof and intensity values are decoded for the
+/// requested scans only.
+///
+/// # Arguments
+///
+/// * `decompressed_bytes` - A byte slice that holds the decompressed data
+/// * `scans` - The zero based scans to decode, scans beyond the scan count of the frame are ignored
+///
+/// # Returns
+///
+/// * `scan` - A vector of u32 that holds the zero based scan of every peak
+/// * `tof_indices` - A vector of u32 that holds the tof indices
+/// * `intensities` - A vector of u32 that holds the intensities
+///
+pub fn parse_decompressed_bruker_binary_scans(
+    decompressed_bytes: &[u8],
+    scans: Range<u32>,
+) -> Result<RawPeaks, Box<dyn std::error::Error>> {
+    let quarter = decompressed_bytes.len() / 4;
+    let value = |i: usize| {
+        LittleEndian::read_u32(&[
+            decompressed_bytes[i],
+            decompressed_bytes[i + quarter],
+            decompressed_bytes[i + 2 * quarter],
+            decompressed_bytes[i + 3 * quarter],
+        ])
+    };
+
+    let scan_count = if quarter == 0 { 0 } else { value(0) as usize };
+    if scan_count > quarter {
+        return Err(format!("scan count {} exceeds the frame data", scan_count).into());
+    }
+    let num_peaks = (quarter - scan_count) / 2;
+
+    // peaks per scan, every scan but the last stores twice its count, the last one gets the rest
+    let mut peak_counts: Vec<usize> = (1..scan_count).map(|i| value(i) as usize / 2).collect();
+    let counted = peak_counts.iter().sum::<usize>();
+    if scan_count > 0 {
+        peak_counts.push(num_peaks.checked_sub(counted).ok_or("peak counts exceed the frame data")?);
+    }
+
+    let end = (scans.end as usize).min(scan_count);
+    let begin = (scans.start as usize).min(end);
+    let mut peak = peak_counts[..begin].iter().sum::<usize>();
+
+    let capacity = peak_counts[begin..end].iter().sum::<usize>();
+    let mut scan = Vec::with_capacity(capacity);
+    let mut tof_indices = Vec::with_capacity(capacity);
+    let mut intensities = Vec::with_capacity(capacity);
+
+    for (scan_index, &count) in (begin..end).zip(&peak_counts[begin..end]) {
+        // tof indices are stored as differences within a scan, starting at 1
+        let mut tof = 0u32;
+        for _ in 0..count {
+            tof += value(scan_count + 2 * peak);
+            scan.push(scan_index as u32);
+            tof_indices.push(tof - 1);
+            intensities.push(value(scan_count + 2 * peak + 1));
+            peak += 1;
+        }
+    }
+
+    Ok((scan, tof_indices, intensities))
+}
+
 pub fn get_peak_cnts(total_scans: u32, scans: &[u32]) -> Vec<u32> {
     let mut peak_cnts = vec![total_scans];
     let mut ii = 0;
@@ -252,26 +351,26 @@ pub fn get_realdata_loop(back_data: &[u8]) -> Vec<u8> {
     let mut real_data = vec![0u8; back_data.len()];
     let mut reminder = 0;
     let mut bd_idx = 0;
-    for rd_idx in 0..back_data.len() {
+    for value in real_data.iter_mut() {
         if bd_idx >= back_data.len() {
             reminder += 1;
             bd_idx = reminder;
         }
-        real_data[rd_idx] = back_data[bd_idx];
+        *value = back_data[bd_idx];
         bd_idx += 4;
     }
     real_data
 }
 
 pub fn get_data_for_compression(
-    tofs: &Vec<u32>,
-    scans: &Vec<u32>,
-    intensities: &Vec<u32>,
+    tofs: &[u32],
+    scans: &[u32],
+    intensities: &[u32],
     max_scans: u32,
 ) -> Vec<u8> {
-    let mut tof_copy = tofs.clone();
-    modify_tofs(&mut tof_copy, &scans);
-    let peak_cnts = get_peak_cnts(max_scans, &scans);
+    let mut tof_copy = tofs.to_vec();
+    modify_tofs(&mut tof_copy, scans);
+    let peak_cnts = get_peak_cnts(max_scans, scans);
     let interleaved: Vec<u32> = tofs
         .iter()
         .zip(intensities.iter())
@@ -288,10 +387,7 @@ pub fn get_data_for_compression_par(
     max_scans: u32,
     num_threads: usize,
 ) -> Vec<Vec<u8>> {
-    let pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    let pool = parallel::pool(num_threads);
 
     let result = pool.install(|| {
         tofs.par_iter()
@@ -306,7 +402,7 @@ pub fn get_data_for_compression_par(
     result
 }
 
-pub fn flatten_scan_values(scan: &Vec<u32>, zero_indexed: bool) -> Vec<u32> {
+pub fn flatten_scan_values(scan: &[u32], zero_indexed: bool) -> Vec<u32> {
     let add = if zero_indexed { 0 } else { 1 };
     scan.iter()
         .enumerate()
@@ -0,0 +1,168 @@
+use arrow_array::{ArrayRef, Float64Array, Int32Array, RecordBatch};
+use arrow_buffer::ScalarBuffer;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use mscore::timstof::frame::TimsFrame;
+use mscore::timstof::slice::TimsSlice;
+use std::sync::Arc;
+
+/// Columns shared by every peak table: retention time, scan, mobility, tof, m/z and intensity
+pub fn peak_fields() -> Vec<Field> {
+    vec![
+        Field::new("rt", DataType::Float64, false),
+        Field::new("scan", DataType::Int32, false),
+        Field::new("mobility", DataType::Float64, false),
+        Field::new("tof", DataType::Int32, false),
+        Field::new("mz", DataType::Float64, false),
+        Field::new("intensity", DataType::Float64, false),
+    ]
+}
+
+/// Schema of frames and slices: frame_id followed by the peak columns
+pub fn slice_schema() -> SchemaRef {
+    let mut fields = vec![Field::new("frame_id", DataType::Int32, false)];
+    fields.extend(peak_fields());
+    Arc::new(Schema::new(fields))
+}
+
+/// Buffers of the per peak columns of a frame, either taken over from a frame or borrowed from memory owned
+/// elsewhere, e.g. by a Python object
+#[derive(Clone, Debug)]
+pub struct PeakBuffers {
+    pub scan: ScalarBuffer<i32>,
+    pub mobility: ScalarBuffer<f64>,
+    pub tof: ScalarBuffer<i32>,
+    pub mz: ScalarBuffer<f64>,
+    pub intensity: ScalarBuffer<f64>,
+}
+
+impl From<TimsFrame> for PeakBuffers {
+    /// take over the peak vectors of the frame without copying them
+    fn from(frame: TimsFrame) -> Self {
+        PeakBuffers {
+            scan: ScalarBuffer::from(frame.scan),
+            mobility: ScalarBuffer::from(frame.ims_frame.mobility),
+            tof: ScalarBuffer::from(frame.tof),
+            mz: ScalarBuffer::from(frame.ims_frame.mz),
+            intensity: ScalarBuffer::from(frame.ims_frame.intensity),
+        }
+    }
+}
+
+/// Peak columns of a frame with the given retention time, the arrays wrap the buffers without copying them
+pub fn peak_columns_from_buffers(retention_time: f64, buffers: PeakBuffers) -> Vec<ArrayRef> {
+    let num_peaks = buffers.scan.len();
+    vec![
+        Arc::new(Float64Array::from(vec![retention_time; num_peaks])),
+        Arc::new(Int32Array::new(buffers.scan, None)),
+        Arc::new(Float64Array::new(buffers.mobility, None)),
+        Arc::new(Int32Array::new(buffers.tof, None)),
+        Arc::new(Float64Array::new(buffers.mz, None)),
+        Arc::new(Float64Array::new(buffers.intensity, None)),
+    ]
+}
+
+/// Peak columns of a frame, the arrays take over the vectors of the frame without copying them
+pub fn peak_columns(frame: TimsFrame) -> Vec<ArrayRef> {
+    peak_columns_from_buffers(frame.ims_frame.retention_time, PeakBuffers::from(frame))
+}
+
+/// Record batch with the schema of `slice_schema` over the peak buffers of a frame
+///
+/// # Arguments
+///
+/// * `frame_id` - Id of the frame, repeated for every peak
+/// * `retention_time` - Retention time of the frame, repeated for every peak
+/// * `buffers` - Peak buffers, the batch wraps them without copying, all need to have the same length
+///
+/// # Returns
+///
+/// * Record batch with one row per peak, an error if the buffers differ in length
+///
+pub fn record_batch_from_buffers(
+    frame_id: i32,
+    retention_time: f64,
+    buffers: PeakBuffers,
+) -> Result<RecordBatch, ArrowError> {
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(vec![frame_id; buffers.scan.len()]))];
+    columns.extend(peak_columns_from_buffers(retention_time, buffers));
+    RecordBatch::try_new(slice_schema(), columns)
+}
+
+/// Turn a frame into a record batch with the schema of `slice_schema`
+///
+/// # Arguments
+///
+/// * `frame` - Frame to convert, its peak vectors become the buffers of the batch without being copied
+///
+/// # Returns
+///
+/// * Record batch with one row per peak
+///
+pub fn frame_into_record_batch(frame: TimsFrame) -> Result<RecordBatch, ArrowError> {
+    let (frame_id, retention_time) = (frame.frame_id, frame.ims_frame.retention_time);
+    record_batch_from_buffers(frame_id, retention_time, PeakBuffers::from(frame))
+}
+
+/// Turn a slice into one record batch per frame, see `frame_into_record_batch`
+pub fn slice_into_record_batches(slice: TimsSlice) -> Result<Vec<RecordBatch>, ArrowError> {
+    slice.frames.into_iter().map(frame_into_record_batch).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, Int32Type};
+    use mscore::data::spectrum::MsType;
+
+    fn frame(frame_id: i32, num_peaks: usize) -> TimsFrame {
+        TimsFrame::new(
+            frame_id,
+            MsType::Precursor,
+            frame_id as f64 * 0.1,
+            (0..num_peaks as i32).collect(),
+            vec![1.2; num_peaks],
+            (0..num_peaks as i32).collect(),
+            (0..num_peaks).map(|i| 100.0 + i as f64).collect(),
+            vec![10.0; num_peaks],
+        )
+    }
+
+    #[test]
+    fn test_record_batch_shares_frame_buffers() {
+        let frame = frame(1, 1_000_000);
+        let mz = frame.ims_frame.mz.as_ptr();
+        let intensity = frame.ims_frame.intensity.as_ptr();
+        let scan = frame.scan.as_ptr();
+
+        let batch = frame_into_record_batch(frame).unwrap();
+        assert_eq!(batch.num_rows(), 1_000_000);
+        assert_eq!(batch.schema(), slice_schema());
+
+        // the columns point at the memory of the frame, nothing was copied
+        assert_eq!(batch.column_by_name("mz").unwrap().as_primitive::<Float64Type>().values().as_ptr(), mz);
+        assert_eq!(batch.column_by_name("intensity").unwrap().as_primitive::<Float64Type>().values().as_ptr(), intensity);
+        assert_eq!(batch.column_by_name("scan").unwrap().as_primitive::<Int32Type>().values().as_ptr(), scan);
+    }
+
+    #[test]
+    fn test_record_batch_wraps_given_buffers() {
+        let buffers = PeakBuffers::from(frame(2, 4));
+        let mz = buffers.mz.as_ptr();
+        let batch = record_batch_from_buffers(7, 1.5, buffers.clone()).unwrap();
+        assert_eq!(batch.column_by_name("mz").unwrap().as_primitive::<Float64Type>().values().as_ptr(), mz);
+        assert_eq!(batch.column(0).as_primitive::<Int32Type>().values().to_vec(), vec![7; 4]);
+        assert_eq!(batch.column_by_name("rt").unwrap().as_primitive::<Float64Type>().values().to_vec(), vec![1.5; 4]);
+
+        // columns of different length do not make a batch
+        let truncated = PeakBuffers { intensity: buffers.intensity.slice(0, 3), ..buffers };
+        assert!(record_batch_from_buffers(7, 1.5, truncated).is_err());
+    }
+
+    #[test]
+    fn test_slice_record_batches() {
+        let batches = slice_into_record_batches(TimsSlice::new(vec![frame(1, 3), frame(2, 0), frame(3, 2)])).unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(), vec![3, 0, 2]);
+        assert_eq!(batches[2].column(0).as_primitive::<Int32Type>().values().to_vec(), vec![3, 3]);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod arrow;
+pub mod mzml;
+pub mod parquet;
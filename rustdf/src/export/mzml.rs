@@ -0,0 +1,517 @@
+use crate::data::dda::{PASEFDDAFragment, TimsDatasetDDA};
+use crate::data::handle::TimsData;
+use crate::data::meta::{read_meta_data_sql, DDAPrecursor};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::write::ZlibEncoder;
+use mscore::data::spectrum::MsType;
+use mscore::timstof::frame::TimsFrame;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Precursor of an MS2 spectrum as written to its `precursorList`
+#[derive(Clone, Debug)]
+pub struct MzMLPrecursor {
+    pub isolation_mz: f64,
+    pub isolation_width: f64,
+    pub selected_mz: f64,
+    pub charge: Option<i64>,
+    pub collision_energy: f64,
+    pub inverse_mobility: Option<f64>,
+}
+
+/// A single spectrum of an mzML file, peaks sorted by m/z
+#[derive(Clone, Debug)]
+pub struct MzMLSpectrum {
+    /// native id, e.g. `frame=12`
+    pub id: String,
+    pub ms_level: u8,
+    /// retention time in seconds
+    pub retention_time: f64,
+    pub mz: Vec<f64>,
+    pub intensity: Vec<f64>,
+    /// inverse reduced ion mobility of every peak, written as an additional binary array
+    pub mobility: Option<Vec<f64>>,
+    pub precursor: Option<MzMLPrecursor>,
+}
+
+fn cv_param(xml: &mut String, indent: usize, accession: &str, name: &str, value: &str) {
+    writeln!(xml, "{:indent$}<cvParam cvRef=\"MS\" accession=\"{}\" name=\"{}\" value=\"{}\"/>", "", accession, name, value, indent = indent).unwrap();
+}
+
+fn cv_param_with_unit(xml: &mut String, indent: usize, accession: &str, name: &str, value: &str, unit: (&str, &str)) {
+    let unit_cv = unit.0.split(':').next().unwrap();
+    writeln!(
+        xml,
+        "{:indent$}<cvParam cvRef=\"MS\" accession=\"{}\" name=\"{}\" value=\"{}\" unitCvRef=\"{}\" unitAccession=\"{}\" unitName=\"{}\"/>",
+        "", accession, name, value, unit_cv, unit.0, unit.1, indent = indent
+    )
+    .unwrap();
+}
+
+const UNIT_MZ: (&str, &str) = ("MS:1000040", "m/z");
+const UNIT_COUNTS: (&str, &str) = ("MS:1000131", "number of detector counts");
+const UNIT_SECOND: (&str, &str) = ("UO:0000010", "second");
+const UNIT_ELECTRONVOLT: (&str, &str) = ("UO:0000266", "electronvolt");
+const UNIT_INVERSE_MOBILITY: (&str, &str) = ("MS:1002814", "volt-second per square centimeter");
+
+/// Little endian 64 bit floats, optionally zlib compressed, base64 encoded
+fn encode_binary(values: &[f64], compress: bool) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    if compress {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        BASE64.encode(encoder.finish().unwrap())
+    } else {
+        BASE64.encode(bytes)
+    }
+}
+
+fn binary_data_array(xml: &mut String, values: &[f64], compress: bool, array: (&str, &str), unit: (&str, &str)) {
+    let encoded = encode_binary(values, compress);
+    writeln!(xml, "    <binaryDataArray encodedLength=\"{}\">", encoded.len()).unwrap();
+    cv_param(xml, 6, "MS:1000523", "64-bit float", "");
+    match compress {
+        true => cv_param(xml, 6, "MS:1000574", "zlib compression", ""),
+        false => cv_param(xml, 6, "MS:1000576", "no compression", ""),
+    }
+    cv_param_with_unit(xml, 6, array.0, array.1, "", unit);
+    writeln!(xml, "      <binary>{}</binary>", encoded).unwrap();
+    writeln!(xml, "    </binaryDataArray>").unwrap();
+}
+
+impl MzMLSpectrum {
+    /// Spectrum of all peaks of a frame, every peak keeps its inverse mobility in an ion mobility array
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to convert, precursor frames become MS1 spectra, all others MS2 spectra without precursor
+    ///
+    pub fn from_frame(frame: &TimsFrame) -> Self {
+        let mut order: Vec<usize> = (0..frame.ims_frame.mz.len()).collect();
+        order.sort_by(|&a, &b| frame.ims_frame.mz[a].total_cmp(&frame.ims_frame.mz[b]));
+
+        MzMLSpectrum {
+            id: format!("frame={}", frame.frame_id),
+            ms_level: if frame.ms_type == MsType::Precursor { 1 } else { 2 },
+            retention_time: frame.ims_frame.retention_time,
+            mz: order.iter().map(|&i| frame.ims_frame.mz[i]).collect(),
+            intensity: order.iter().map(|&i| frame.ims_frame.intensity[i]).collect(),
+            mobility: Some(order.iter().map(|&i| frame.ims_frame.mobility[i]).collect()),
+            precursor: None,
+        }
+    }
+
+    /// MS2 spectrum of one precursor, fragment peaks of all given PASEF frames are summed up per tof index
+    ///
+    /// # Arguments
+    ///
+    /// * `fragments` - Fragment spectra of the precursor, at least one
+    /// * `precursor` - Metadata of the precursor, if known
+    ///
+    pub fn from_pasef_fragments(fragments: &[&PASEFDDAFragment], precursor: Option<&DDAPrecursor>) -> Self {
+        let first = fragments[0];
+        let mut merged = TimsFrame::default();
+        for fragment in fragments {
+            let frame = &fragment.selected_fragment;
+            merged.tof.extend(&frame.tof);
+            merged.ims_frame.mz.extend(&frame.ims_frame.mz);
+            merged.ims_frame.intensity.extend(&frame.ims_frame.intensity);
+        }
+        let spectrum = merged.to_indexed_mz_spectrum().mz_spectrum;
+
+        let id = match fragments.len() {
+            1 => format!("frame={} precursor={}", first.frame_id, first.precursor_id),
+            _ => format!("precursor={}", first.precursor_id),
+        };
+
+        MzMLSpectrum {
+            id,
+            ms_level: 2,
+            retention_time: first.selected_fragment.ims_frame.retention_time,
+            mz: spectrum.mz,
+            intensity: spectrum.intensity,
+            mobility: None,
+            precursor: precursor.map(|p| MzMLPrecursor {
+                isolation_mz: p.isolation_mz,
+                isolation_width: p.isolation_width,
+                selected_mz: p.mono_mz.unwrap_or(p.highest_intensity_mz),
+                charge: p.charge,
+                collision_energy: first.collision_energy,
+                inverse_mobility: Some(p.inverse_ion_mobility),
+            }),
+        }
+    }
+
+    /// `<spectrum>` element of the spectrum
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Position of the spectrum in the spectrum list
+    /// * `compress` - Whether binary arrays are zlib compressed
+    ///
+    pub fn to_xml(&self, index: usize, compress: bool) -> String {
+        let mut xml = String::new();
+        writeln!(xml, "<spectrum index=\"{}\" id=\"{}\" defaultArrayLength=\"{}\">", index, self.id, self.mz.len()).unwrap();
+        cv_param(&mut xml, 2, "MS:1000511", "ms level", &self.ms_level.to_string());
+        match self.ms_level {
+            1 => cv_param(&mut xml, 2, "MS:1000579", "MS1 spectrum", ""),
+            _ => cv_param(&mut xml, 2, "MS:1000580", "MSn spectrum", ""),
+        }
+        cv_param(&mut xml, 2, "MS:1000130", "positive scan", "");
+        cv_param(&mut xml, 2, "MS:1000127", "centroid spectrum", "");
+        cv_param_with_unit(&mut xml, 2, "MS:1000285", "total ion current", &self.intensity.iter().sum::<f64>().to_string(), UNIT_COUNTS);
+        if let (Some(first), Some(last)) = (self.mz.first(), self.mz.last()) {
+            cv_param_with_unit(&mut xml, 2, "MS:1000528", "lowest observed m/z", &first.to_string(), UNIT_MZ);
+            cv_param_with_unit(&mut xml, 2, "MS:1000527", "highest observed m/z", &last.to_string(), UNIT_MZ);
+        }
+
+        writeln!(xml, "  <scanList count=\"1\">").unwrap();
+        cv_param(&mut xml, 4, "MS:1000795", "no combination", "");
+        writeln!(xml, "    <scan>").unwrap();
+        cv_param_with_unit(&mut xml, 6, "MS:1000016", "scan start time", &self.retention_time.to_string(), UNIT_SECOND);
+        writeln!(xml, "    </scan>").unwrap();
+        writeln!(xml, "  </scanList>").unwrap();
+
+        if let Some(precursor) = &self.precursor {
+            let half_width = (precursor.isolation_width / 2.0).to_string();
+            writeln!(xml, "  <precursorList count=\"1\">").unwrap();
+            writeln!(xml, "    <precursor>").unwrap();
+            writeln!(xml, "      <isolationWindow>").unwrap();
+            cv_param_with_unit(&mut xml, 8, "MS:1000827", "isolation window target m/z", &precursor.isolation_mz.to_string(), UNIT_MZ);
+            cv_param_with_unit(&mut xml, 8, "MS:1000828", "isolation window lower offset", &half_width, UNIT_MZ);
+            cv_param_with_unit(&mut xml, 8, "MS:1000829", "isolation window upper offset", &half_width, UNIT_MZ);
+            writeln!(xml, "      </isolationWindow>").unwrap();
+            writeln!(xml, "      <selectedIonList count=\"1\">").unwrap();
+            writeln!(xml, "        <selectedIon>").unwrap();
+            cv_param_with_unit(&mut xml, 10, "MS:1000744", "selected ion m/z", &precursor.selected_mz.to_string(), UNIT_MZ);
+            if let Some(charge) = precursor.charge {
+                cv_param(&mut xml, 10, "MS:1000041", "charge state", &charge.to_string());
+            }
+            if let Some(mobility) = precursor.inverse_mobility {
+                cv_param_with_unit(&mut xml, 10, "MS:1002815", "inverse reduced ion mobility", &mobility.to_string(), UNIT_INVERSE_MOBILITY);
+            }
+            writeln!(xml, "        </selectedIon>").unwrap();
+            writeln!(xml, "      </selectedIonList>").unwrap();
+            writeln!(xml, "      <activation>").unwrap();
+            cv_param(&mut xml, 8, "MS:1000133", "collision-induced dissociation", "");
+            cv_param_with_unit(&mut xml, 8, "MS:1000045", "collision energy", &precursor.collision_energy.to_string(), UNIT_ELECTRONVOLT);
+            writeln!(xml, "      </activation>").unwrap();
+            writeln!(xml, "    </precursor>").unwrap();
+            writeln!(xml, "  </precursorList>").unwrap();
+        }
+
+        let num_arrays = if self.mobility.is_some() { 3 } else { 2 };
+        writeln!(xml, "  <binaryDataArrayList count=\"{}\">", num_arrays).unwrap();
+        binary_data_array(&mut xml, &self.mz, compress, ("MS:1000514", "m/z array"), UNIT_MZ);
+        binary_data_array(&mut xml, &self.intensity, compress, ("MS:1000515", "intensity array"), UNIT_COUNTS);
+        if let Some(mobility) = &self.mobility {
+            binary_data_array(&mut xml, mobility, compress, ("MS:1003008", "raw inverse reduced ion mobility array"), UNIT_INVERSE_MOBILITY);
+        }
+        writeln!(xml, "  </binaryDataArrayList>").unwrap();
+        xml.push_str("</spectrum>");
+        xml
+    }
+}
+
+/// Streaming writer of indexed mzML files, spectra are written as they come,
+/// the index with the byte offset of every spectrum and the SHA-1 checksum of the file are appended by `finish`
+pub struct MzMLWriter {
+    out: BufWriter<File>,
+    hasher: Sha1,
+    position: u64,
+    offsets: Vec<(String, u64)>,
+    num_spectra: usize,
+    compress: bool,
+}
+
+const SPECTRUM_INDENT: &str = "        ";
+
+impl MzMLWriter {
+    /// Create the file and write everything up to the start of the spectrum list
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to create, an existing file is replaced
+    /// * `run_id` - Id of the run, e.g. the name of the raw data
+    /// * `num_spectra` - Number of spectra that will be written
+    /// * `compress` - Whether binary arrays are zlib compressed
+    ///
+    pub fn create(path: &Path, run_id: &str, num_spectra: usize, compress: bool) -> io::Result<Self> {
+        let mut writer = MzMLWriter {
+            out: BufWriter::new(File::create(path)?),
+            hasher: Sha1::new(),
+            position: 0,
+            offsets: Vec::with_capacity(num_spectra),
+            num_spectra,
+            compress,
+        };
+
+        let mut header = String::new();
+        header.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        header.push_str("<indexedmzML xmlns=\"http://psi.hupo.org/ms/mzml\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://psi.hupo.org/ms/mzml http://psidev.info/files/ms/mzML/xsd/mzML1.1.2_idx.xsd\">\n");
+        writeln!(header, "  <mzML xmlns=\"http://psi.hupo.org/ms/mzml\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"http://psi.hupo.org/ms/mzml http://psidev.info/files/ms/mzML/xsd/mzML1.1.0.xsd\" id=\"{}\" version=\"1.1.0\">", run_id).unwrap();
+        header.push_str("    <cvList count=\"2\">\n");
+        header.push_str("      <cv id=\"MS\" fullName=\"Proteomics Standards Initiative Mass Spectrometry Ontology\" version=\"4.1.30\" URI=\"https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo\"/>\n");
+        header.push_str("      <cv id=\"UO\" fullName=\"Unit Ontology\" version=\"09:04:2014\" URI=\"https://raw.githubusercontent.com/bio-ontology-research-group/unit-ontology/master/unit.obo\"/>\n");
+        header.push_str("    </cvList>\n");
+        header.push_str("    <fileDescription>\n      <fileContent>\n");
+        cv_param(&mut header, 8, "MS:1000579", "MS1 spectrum", "");
+        cv_param(&mut header, 8, "MS:1000580", "MSn spectrum", "");
+        header.push_str("      </fileContent>\n    </fileDescription>\n");
+        writeln!(header, "    <softwareList count=\"1\">\n      <software id=\"rustdf\" version=\"{}\">", env!("CARGO_PKG_VERSION")).unwrap();
+        cv_param(&mut header, 8, "MS:1000799", "custom unreleased software tool", "rustdf");
+        header.push_str("      </software>\n    </softwareList>\n");
+        header.push_str("    <instrumentConfigurationList count=\"1\">\n      <instrumentConfiguration id=\"IC1\">\n");
+        cv_param(&mut header, 8, "MS:1000122", "Bruker Daltonics instrument model", "");
+        header.push_str("      </instrumentConfiguration>\n    </instrumentConfigurationList>\n");
+        header.push_str("    <dataProcessingList count=\"1\">\n      <dataProcessing id=\"rustdf_export\">\n        <processingMethod order=\"0\" softwareRef=\"rustdf\">\n");
+        cv_param(&mut header, 10, "MS:1000544", "Conversion to mzML", "");
+        header.push_str("        </processingMethod>\n      </dataProcessing>\n    </dataProcessingList>\n");
+        writeln!(header, "    <run id=\"{}\" defaultInstrumentConfigurationRef=\"IC1\">", run_id).unwrap();
+        writeln!(header, "      <spectrumList count=\"{}\" defaultDataProcessingRef=\"rustdf_export\">", num_spectra).unwrap();
+
+        writer.emit(&header)?;
+        Ok(writer)
+    }
+
+    fn emit(&mut self, text: &str) -> io::Result<()> {
+        self.out.write_all(text.as_bytes())?;
+        self.hasher.update(text.as_bytes());
+        self.position += text.len() as u64;
+        Ok(())
+    }
+
+    /// Append the next spectrum to the spectrum list
+    pub fn write_spectrum(&mut self, spectrum: &MzMLSpectrum) -> io::Result<()> {
+        if self.offsets.len() == self.num_spectra {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("all {} announced spectra were written already", self.num_spectra)));
+        }
+        let xml = spectrum.to_xml(self.offsets.len(), self.compress);
+        let mut indented = String::with_capacity(xml.len() + SPECTRUM_INDENT.len() * 32);
+        for line in xml.lines() {
+            writeln!(indented, "{}{}", SPECTRUM_INDENT, line).unwrap();
+        }
+        self.offsets.push((spectrum.id.clone(), self.position + SPECTRUM_INDENT.len() as u64));
+        self.emit(&indented)
+    }
+
+    /// Close the spectrum list and write the index and the checksum of the file
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.offsets.len() != self.num_spectra {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("announced {} spectra but {} were written", self.num_spectra, self.offsets.len()),
+            ));
+        }
+        self.emit("      </spectrumList>\n    </run>\n  </mzML>\n")?;
+
+        let index_offset = self.position + 2;
+        let mut index = String::new();
+        index.push_str("  <indexList count=\"1\">\n    <index name=\"spectrum\">\n");
+        for (id, offset) in &self.offsets {
+            writeln!(index, "      <offset idRef=\"{}\">{}</offset>", id, offset).unwrap();
+        }
+        index.push_str("    </index>\n  </indexList>\n");
+        writeln!(index, "  <indexListOffset>{}</indexListOffset>", index_offset).unwrap();
+        // the checksum covers the file up to and including the opening tag of the checksum element
+        index.push_str("  <fileChecksum>");
+        self.emit(&index)?;
+
+        let checksum: String = self.hasher.clone().finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        self.out.write_all(format!("{}</fileChecksum>\n</indexedmzML>\n", checksum).as_bytes())?;
+        self.out.flush()
+    }
+}
+
+/// Write the precursor frames and PASEF fragment spectra of a DDA dataset to an indexed mzML file,
+/// ordered by frame id
+///
+/// # Arguments
+///
+/// * `dataset` - DDA dataset to export
+/// * `path` - File to create, an existing file is replaced
+/// * `merge_precursors` - Merge the fragments of all PASEF frames of a precursor into one spectrum,
+///   placed at the first of these frames, instead of writing one spectrum per frame and precursor
+/// * `compress` - Whether binary arrays are zlib compressed
+/// * `num_threads` - Number of threads used to read frames
+///
+/// # Returns
+///
+/// * Number of written spectra
+///
+pub fn write_dda_mzml(
+    dataset: &TimsDatasetDDA,
+    path: &Path,
+    merge_precursors: bool,
+    compress: bool,
+    num_threads: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let data_path = dataset.loader.get_data_path();
+    let precursor_frame_ids: Vec<u32> = read_meta_data_sql(data_path)?
+        .iter()
+        .filter(|frame| frame.ms_ms_type == 0)
+        .map(|frame| frame.id as u32)
+        .collect();
+    let precursors: BTreeMap<i64, DDAPrecursor> = dataset
+        .get_selected_precursors()
+        .into_iter()
+        .map(|precursor| (precursor.precursor_id, precursor))
+        .collect();
+    let fragments = dataset.get_pasef_fragments(num_threads);
+
+    // spectra are keyed by (frame id, precursor id), precursor frames use 0 as precursor id
+    let mut fragment_groups: BTreeMap<(u32, u32), Vec<&PASEFDDAFragment>> = BTreeMap::new();
+    if merge_precursors {
+        let mut by_precursor: BTreeMap<u32, Vec<&PASEFDDAFragment>> = BTreeMap::new();
+        for fragment in &fragments {
+            by_precursor.entry(fragment.precursor_id).or_default().push(fragment);
+        }
+        for (precursor_id, group) in by_precursor {
+            let first_frame = group.iter().map(|f| f.frame_id).min().unwrap();
+            fragment_groups.insert((first_frame, precursor_id), group);
+        }
+    } else {
+        for fragment in &fragments {
+            fragment_groups.entry((fragment.frame_id, fragment.precursor_id)).or_default().push(fragment);
+        }
+    }
+
+    let mut order: Vec<(u32, u32)> = precursor_frame_ids.iter().map(|&id| (id, 0)).collect();
+    order.extend(fragment_groups.keys().cloned());
+    order.sort();
+
+    let run_id = Path::new(&data_path).file_stem().and_then(|name| name.to_str()).unwrap_or("run").to_string();
+    let mut writer = MzMLWriter::create(path, &run_id, order.len(), compress)?;
+
+    let precursor_frames: BTreeMap<u32, TimsFrame> = dataset
+        .get_slice(precursor_frame_ids, num_threads)
+        .frames
+        .into_iter()
+        .map(|frame| (frame.frame_id as u32, frame))
+        .collect();
+
+    for key in &order {
+        let spectrum = match key {
+            (frame_id, 0) => MzMLSpectrum::from_frame(&precursor_frames[frame_id]),
+            _ => {
+                let group = &fragment_groups[key];
+                MzMLSpectrum::from_pasef_fragments(group, precursors.get(&(key.1 as i64)))
+            }
+        };
+        writer.write_spectrum(&spectrum)?;
+    }
+    writer.finish()?;
+
+    Ok(order.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    fn frame() -> TimsFrame {
+        TimsFrame::new(7, MsType::Precursor, 12.5, vec![100, 200, 300], vec![1.2, 1.1, 1.0], vec![3000, 1000, 2000], vec![600.3, 400.1, 500.2], vec![30.0, 10.0, 20.0])
+    }
+
+    fn fragment(frame_id: u32, tof: Vec<i32>, mz: Vec<f64>, intensity: Vec<f64>) -> PASEFDDAFragment {
+        let n = tof.len();
+        PASEFDDAFragment::new(frame_id, 4, 35.0, TimsFrame::new(frame_id as i32, MsType::FragmentDda, frame_id as f64, vec![1; n], vec![1.0; n], tof, mz, intensity))
+    }
+
+    fn precursor() -> DDAPrecursor {
+        DDAPrecursor {
+            frame_id: 1,
+            precursor_id: 4,
+            mono_mz: Some(650.33),
+            highest_intensity_mz: 650.8,
+            average_mz: 651.0,
+            charge: Some(2),
+            inverse_ion_mobility: 0.95,
+            collision_energy: 35.0,
+            precuror_total_intensity: 1e5,
+            isolation_mz: 650.5,
+            isolation_width: 3.0,
+        }
+    }
+
+    fn decode(xml: &str, nth: usize) -> Vec<f64> {
+        let start = xml.match_indices("<binary>").nth(nth).unwrap().0 + "<binary>".len();
+        let end = start + xml[start..].find("</binary>").unwrap();
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(&BASE64.decode(&xml[start..end]).unwrap()[..]).read_to_end(&mut bytes).unwrap();
+        bytes.chunks(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_frame_spectrum_xml() {
+        let xml = MzMLSpectrum::from_frame(&frame()).to_xml(0, true);
+        assert!(xml.starts_with("<spectrum index=\"0\" id=\"frame=7\" defaultArrayLength=\"3\">"));
+        assert!(xml.contains("accession=\"MS:1000511\" name=\"ms level\" value=\"1\""));
+        assert!(xml.contains("name=\"scan start time\" value=\"12.5\""));
+        assert!(!xml.contains("precursorList"));
+
+        // peaks are sorted by m/z, the mobility array follows them
+        assert_eq!(decode(&xml, 0), vec![400.1, 500.2, 600.3]);
+        assert_eq!(decode(&xml, 1), vec![10.0, 20.0, 30.0]);
+        assert_eq!(decode(&xml, 2), vec![1.1, 1.0, 1.2]);
+    }
+
+    #[test]
+    fn test_merged_fragment_spectrum() {
+        let first = fragment(2, vec![10, 20], vec![200.0, 300.0], vec![1.0, 2.0]);
+        let second = fragment(3, vec![20, 30], vec![300.0, 400.0], vec![4.0, 8.0]);
+        let spectrum = MzMLSpectrum::from_pasef_fragments(&[&first, &second], Some(&precursor()));
+
+        assert_eq!(spectrum.id, "precursor=4");
+        assert_eq!(spectrum.mz, vec![200.0, 300.0, 400.0]);
+        assert_eq!(spectrum.intensity, vec![1.0, 6.0, 8.0]);
+
+        let xml = spectrum.to_xml(3, false);
+        assert!(xml.contains("name=\"selected ion m/z\" value=\"650.33\""));
+        assert!(xml.contains("name=\"charge state\" value=\"2\""));
+        assert!(xml.contains("name=\"isolation window lower offset\" value=\"1.5\""));
+        assert!(xml.contains("name=\"collision energy\" value=\"35\""));
+        assert!(xml.contains("name=\"no compression\""));
+    }
+
+    #[test]
+    fn test_indexed_mzml_offsets_and_checksum() {
+        let path = std::env::temp_dir().join(format!("mzml_writer_test_{}.mzML", std::process::id()));
+        let fragment = fragment(8, vec![10], vec![200.0], vec![1.0]);
+        let spectra = vec![MzMLSpectrum::from_frame(&frame()), MzMLSpectrum::from_pasef_fragments(&[&fragment], Some(&precursor()))];
+
+        let mut writer = MzMLWriter::create(&path, "test", spectra.len(), true).unwrap();
+        for spectrum in &spectra {
+            writer.write_spectrum(spectrum).unwrap();
+        }
+        assert!(writer.write_spectrum(&spectra[0]).is_err());
+        writer.finish().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for id in ["frame=7", "frame=8 precursor=4"] {
+            let tag = format!("<offset idRef=\"{}\">", id);
+            let start = content.find(&tag).unwrap() + tag.len();
+            let offset: usize = content[start..start + content[start..].find('<').unwrap()].parse().unwrap();
+            assert!(content[offset..].starts_with(&format!("<spectrum index=\"{}\" id=\"{}\"", if id == "frame=7" { 0 } else { 1 }, id)));
+        }
+
+        let start = content.find("<indexListOffset>").unwrap() + "<indexListOffset>".len();
+        let offset: usize = content[start..start + content[start..].find('<').unwrap()].parse().unwrap();
+        assert!(content[offset..].starts_with("<indexList count=\"1\">"));
+
+        let checked = content.find("<fileChecksum>").unwrap() + "<fileChecksum>".len();
+        let expected: String = Sha1::digest(&content.as_bytes()[..checked]).iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(&content[checked..checked + 40], expected);
+        assert!(content.ends_with("</fileChecksum>\n</indexedmzML>\n"));
+    }
+}
@@ -0,0 +1,293 @@
+use crate::compare::RunComparison;
+use crate::data::dda::PASEFDDAFragment;
+use crate::data::meta::DDAPrecursor;
+use crate::export::arrow::{frame_into_record_batch, peak_columns, peak_fields, slice_schema};
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use mscore::timstof::slice::TimsSlice;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Parse the name of a parquet compression codec
+///
+/// # Arguments
+///
+/// * `name` - One of `uncompressed`, `snappy` or `zstd`, case insensitive
+///
+/// # Returns
+///
+/// * The codec, zstd at its default level
+///
+pub fn parse_compression(name: &str) -> Result<Compression, ParquetError> {
+    match name.to_lowercase().as_str() {
+        "uncompressed" | "none" => Ok(Compression::UNCOMPRESSED),
+        "snappy" => Ok(Compression::SNAPPY),
+        "zstd" => Ok(Compression::ZSTD(ZstdLevel::default())),
+        _ => Err(ParquetError::General(format!(
+            "unknown compression {}, expected one of uncompressed, snappy, zstd",
+            name
+        ))),
+    }
+}
+
+fn create_writer(path: &Path, schema: SchemaRef, compression: Compression) -> Result<ArrowWriter<File>, ParquetError> {
+    let properties = WriterProperties::builder().set_compression(compression).build();
+    ArrowWriter::try_new(File::create(path)?, schema, Some(properties))
+}
+
+/// Write peak data as a parquet table, one record batch per frame so memory does not grow with the data
+pub trait ToParquet {
+    /// Write all peaks to a parquet file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to create, an existing file is replaced
+    /// * `compression` - Compression of the column chunks, see `parse_compression`
+    ///
+    fn to_parquet(&self, path: &Path, compression: Compression) -> Result<(), ParquetError>;
+}
+
+impl ToParquet for TimsSlice {
+    /// Columns are frame_id, rt, scan, mobility, tof, mz and intensity, see `slice_schema`
+    fn to_parquet(&self, path: &Path, compression: Compression) -> Result<(), ParquetError> {
+        let mut writer = create_writer(path, slice_schema(), compression)?;
+        for frame in &self.frames {
+            writer.write(&frame_into_record_batch(frame.clone())?)?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+}
+
+impl ToParquet for RunComparison {
+    /// One row per frame pair with the columns named like the fields, the TIC correlation is stored as
+    /// `tic_correlation` in the key value metadata of the file
+    fn to_parquet(&self, path: &Path, compression: Compression) -> Result<(), ParquetError> {
+        let u32_columns = [("real_frame_id", &self.real_frame_id), ("synthetic_frame_id", &self.synthetic_frame_id)];
+        let f64_columns = [
+            ("real_retention_time", &self.real_retention_time),
+            ("synthetic_retention_time", &self.synthetic_retention_time),
+            ("real_tic", &self.real_tic),
+            ("synthetic_tic", &self.synthetic_tic),
+            ("spectral_similarity", &self.spectral_similarity),
+            ("mobility_correlation", &self.mobility_correlation),
+        ];
+
+        let mut fields: Vec<Field> = u32_columns.iter().map(|(name, _)| Field::new(*name, DataType::Int64, false)).collect();
+        fields.extend(f64_columns.iter().map(|(name, _)| Field::new(*name, DataType::Float64, false)));
+        let schema: SchemaRef = Arc::new(Schema::new(fields));
+
+        let mut columns: Vec<ArrayRef> = u32_columns.iter()
+            .map(|(_, values)| Arc::new(Int64Array::from_iter_values(values.iter().map(|&v| v as i64))) as ArrayRef)
+            .collect();
+        columns.extend(f64_columns.iter().map(|(_, values)| Arc::new(Float64Array::from((*values).clone())) as ArrayRef));
+
+        let mut writer = create_writer(path, schema.clone(), compression)?;
+        writer.append_key_value_metadata(KeyValue::new("tic_correlation".to_string(), self.tic_correlation.to_string()));
+        writer.write(&RecordBatch::try_new(schema, columns)?)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Write the fragment peaks of PASEF selected precursors with the metadata of their precursor joined onto every peak
+///
+/// # Arguments
+///
+/// * `fragments` - Fragment spectra as extracted by `TimsDatasetDDA::get_pasef_fragments`
+/// * `precursor_meta` - Selected precursors as returned by `TimsDatasetDDA::get_selected_precursors`
+/// * `path` - File to create, an existing file is replaced
+/// * `compression` - Compression of the column chunks, see `parse_compression`
+///
+/// # Returns
+///
+/// * Columns of the fragment (frame_id, precursor_id, collision_energy), of its precursor
+///   (precursor_frame_id, precursor_mono_mz, precursor_highest_intensity_mz, precursor_average_mz,
+///   precursor_charge, precursor_mobility, precursor_total_intensity, isolation_mz, isolation_width)
+///   and of the peak (rt, scan, mobility, tof, mz, intensity). Precursor columns are null if the
+///   precursor is unknown or did not get a value assigned, like the charge of unresolved isotope patterns
+///
+pub fn write_pasef_fragments_parquet(
+    fragments: &[PASEFDDAFragment],
+    precursor_meta: &[DDAPrecursor],
+    path: &Path,
+    compression: Compression,
+) -> Result<(), ParquetError> {
+    let mut fields = vec![
+        Field::new("frame_id", DataType::Int64, false),
+        Field::new("precursor_id", DataType::Int64, false),
+        Field::new("collision_energy", DataType::Float64, false),
+        Field::new("precursor_frame_id", DataType::Int64, true),
+        Field::new("precursor_mono_mz", DataType::Float64, true),
+        Field::new("precursor_highest_intensity_mz", DataType::Float64, true),
+        Field::new("precursor_average_mz", DataType::Float64, true),
+        Field::new("precursor_charge", DataType::Int64, true),
+        Field::new("precursor_mobility", DataType::Float64, true),
+        Field::new("precursor_total_intensity", DataType::Float64, true),
+        Field::new("isolation_mz", DataType::Float64, true),
+        Field::new("isolation_width", DataType::Float64, true),
+    ];
+    fields.extend(peak_fields());
+    let schema: SchemaRef = Arc::new(Schema::new(fields));
+
+    let precursors: BTreeMap<i64, &DDAPrecursor> = precursor_meta
+        .iter()
+        .map(|precursor| (precursor.precursor_id, precursor))
+        .collect();
+
+    let mut writer = create_writer(path, schema.clone(), compression)?;
+    for fragment in fragments {
+        let num_peaks = fragment.selected_fragment.scan.len();
+        let precursor = precursors.get(&(fragment.precursor_id as i64));
+        let repeat_f64 = |value: Option<f64>| -> ArrayRef { Arc::new(Float64Array::from(vec![value; num_peaks])) };
+        let repeat_i64 = |value: Option<i64>| -> ArrayRef { Arc::new(Int64Array::from(vec![value; num_peaks])) };
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![fragment.frame_id as i64; num_peaks])),
+            Arc::new(Int64Array::from(vec![fragment.precursor_id as i64; num_peaks])),
+            Arc::new(Float64Array::from(vec![fragment.collision_energy; num_peaks])),
+            repeat_i64(precursor.map(|p| p.frame_id)),
+            repeat_f64(precursor.and_then(|p| p.mono_mz)),
+            repeat_f64(precursor.map(|p| p.highest_intensity_mz)),
+            repeat_f64(precursor.map(|p| p.average_mz)),
+            repeat_i64(precursor.and_then(|p| p.charge)),
+            repeat_f64(precursor.map(|p| p.inverse_ion_mobility)),
+            repeat_f64(precursor.map(|p| p.precuror_total_intensity)),
+            repeat_f64(precursor.map(|p| p.isolation_mz)),
+            repeat_f64(precursor.map(|p| p.isolation_width)),
+        ];
+        columns.extend(peak_columns(fragment.selected_fragment.clone()));
+        writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use mscore::data::spectrum::MsType;
+    use mscore::timstof::frame::TimsFrame;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn frame(frame_id: i32, num_peaks: usize) -> TimsFrame {
+        TimsFrame::new(
+            frame_id,
+            MsType::FragmentDda,
+            frame_id as f64 * 0.1,
+            (0..num_peaks as i32).collect(),
+            (0..num_peaks).map(|i| 1.6 - i as f64 * 0.001).collect(),
+            (0..num_peaks as i32).map(|i| i * 10).collect(),
+            (0..num_peaks).map(|i| 100.0 + i as f64).collect(),
+            (0..num_peaks).map(|i| i as f64 * 2.0).collect(),
+        )
+    }
+
+    fn read(path: &Path) -> Vec<RecordBatch> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        reader.map(|batch| batch.unwrap()).collect()
+    }
+
+    fn column<T: arrow_array::ArrowPrimitiveType>(batches: &[RecordBatch], name: &str) -> Vec<T::Native> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let array = batch.column_by_name(name).unwrap().as_any().downcast_ref::<arrow_array::PrimitiveArray<T>>().unwrap().clone();
+                array.values().to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slice_to_parquet() {
+        let path = std::env::temp_dir().join(format!("slice_parquet_test_{}.parquet", std::process::id()));
+        let slice = TimsSlice::new(vec![frame(1, 3), frame(2, 0), frame(3, 2)]);
+        slice.to_parquet(&path, parse_compression("zstd").unwrap()).unwrap();
+
+        let batches = read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        assert_eq!(column::<arrow_array::types::Int32Type>(&batches, "frame_id"), vec![1, 1, 1, 3, 3]);
+        assert_eq!(column::<arrow_array::types::Int32Type>(&batches, "tof"), vec![0, 10, 20, 0, 10]);
+        assert_eq!(column::<arrow_array::types::Float64Type>(&batches, "mz"), vec![100.0, 101.0, 102.0, 100.0, 101.0]);
+        assert_eq!(column::<arrow_array::types::Float64Type>(&batches, "rt"), vec![0.1, 0.1, 0.1, 3.0 * 0.1, 3.0 * 0.1]);
+        assert!(parse_compression("lzma").is_err());
+    }
+
+    #[test]
+    fn test_fragments_to_parquet() {
+        let path = std::env::temp_dir().join(format!("fragments_parquet_test_{}.parquet", std::process::id()));
+        let fragments = vec![
+            PASEFDDAFragment::new(2, 7, 30.0, frame(2, 2)),
+            PASEFDDAFragment::new(3, 8, 42.0, frame(3, 1)),
+        ];
+        let precursors = vec![DDAPrecursor {
+            frame_id: 1,
+            precursor_id: 7,
+            mono_mz: None,
+            highest_intensity_mz: 500.3,
+            average_mz: 500.5,
+            charge: Some(2),
+            inverse_ion_mobility: 1.1,
+            collision_energy: 30.0,
+            precuror_total_intensity: 1e4,
+            isolation_mz: 500.4,
+            isolation_width: 2.0,
+        }];
+        write_pasef_fragments_parquet(&fragments, &precursors, &path, Compression::SNAPPY).unwrap();
+
+        let batches = read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(column::<arrow_array::types::Int64Type>(&batches, "precursor_id"), vec![7, 7, 8]);
+        assert_eq!(column::<arrow_array::types::Float64Type>(&batches, "collision_energy"), vec![30.0, 30.0, 42.0]);
+        assert_eq!(column::<arrow_array::types::Float64Type>(&batches, "intensity"), vec![0.0, 2.0, 0.0]);
+
+        // precursor 8 is unknown, precursor 7 has no monoisotopic m/z
+        let charge: Vec<Option<i64>> = batches
+            .iter()
+            .flat_map(|b| b.column_by_name("precursor_charge").unwrap().as_any().downcast_ref::<Int64Array>().unwrap().iter().collect::<Vec<_>>())
+            .collect();
+        assert_eq!(charge, vec![Some(2), Some(2), None]);
+        assert!(batches.iter().all(|b| b.column_by_name("precursor_mono_mz").unwrap().null_count() == b.num_rows()));
+    }
+
+    #[test]
+    fn test_run_comparison_to_parquet() {
+        let path = std::env::temp_dir().join(format!("comparison_parquet_test_{}.parquet", std::process::id()));
+        let comparison = RunComparison {
+            real_frame_id: vec![1, 3],
+            synthetic_frame_id: vec![2, 2],
+            real_retention_time: vec![0.1, 0.3],
+            synthetic_retention_time: vec![0.2, 0.2],
+            real_tic: vec![10.0, 20.0],
+            synthetic_tic: vec![12.0, 18.0],
+            spectral_similarity: vec![0.9, 0.8],
+            mobility_correlation: vec![0.7, f64::NAN],
+            tic_correlation: 1.0,
+        };
+        comparison.to_parquet(&path, parse_compression("snappy").unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata().key_value_metadata().unwrap().clone();
+        let batches = read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(metadata.iter().any(|kv| kv.key == "tic_correlation" && kv.value.as_deref() == Some("1")));
+        assert_eq!(column::<arrow_array::types::Int64Type>(&batches, "synthetic_frame_id"), vec![2, 2]);
+        assert_eq!(column::<arrow_array::types::Float64Type>(&batches, "spectral_similarity"), vec![0.9, 0.8]);
+        assert!(column::<arrow_array::types::Float64Type>(&batches, "mobility_correlation")[1].is_nan());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod algorithm;
+pub mod compare;
 pub mod data;
+pub mod export;
 pub mod sim;
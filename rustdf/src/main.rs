@@ -1,5 +1,6 @@
 use clap::Parser;
 use rustdf::sim::dia::TimsTofSyntheticsFrameBuilderDIA;
+use rustdf::sim::utility::FrameBuildOptions;
 use std::path::Path;
 
 /// Create synthetic DIA proteomics experiment data
@@ -35,29 +36,33 @@ fn main() {
     let num_threads = args.num_threads;
     let fragment = args.fragment;
 
-    let experiment = TimsTofSyntheticsFrameBuilderDIA::new(path, false, 4).unwrap();
+    let experiment = TimsTofSyntheticsFrameBuilderDIA::new(path, false, 4, None, None).unwrap();
     let first_frames = experiment
         .precursor_frame_builder
         .frames
         .iter()
-        .map(|x| x.frame_id.clone())
+        .map(|x| x.frame_id)
         .skip(100)
         .take(args.num_frames)
         .collect::<Vec<_>>();
 
+    let options = FrameBuildOptions {
+        fragmentation: fragment,
+        uniform: true,
+        precursor_noise_ppm: 5.0,
+        fragment_noise_ppm: 5.0,
+        ..Default::default()
+    };
+
     // go over the frames in batches of 256
     for frame_batch in first_frames.chunks(args.batch_size) {
-        let frames = experiment.build_frames(
-            frame_batch.to_vec(),
-            fragment,
-            false,
-            true,
-            5.0,
-            false,
-            5.0,
-            false,
-            num_threads,
-        );
+        let frames = experiment.build_frames(frame_batch.to_vec(), num_threads, &options);
+        let frames = frames.unwrap_or_else(|errors| {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            std::process::exit(1);
+        });
 
         for frame in frames {
             println!("frame_id: {}", frame.frame_id);
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+
+use mscore::data::spectrum::{IndexedMzSpectrum, MsType, MzSpectrum};
+use mscore::timstof::frame::TimsFrame;
+use mscore::timstof::spectrum::TimsSpectrum;
+
+use crate::sim::calibration::TofCalibration;
+
+/// How the frame builders merge the peaks of all ions in a scan into a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameAssembly {
+    /// one `TimsSpectrum` per ion and scan, merged by `TimsFrame::from_tims_spectra`
+    Spectra,
+    /// peaks are merged into m/z bins of `resolution` decimal digits as they are added, see `FrameAssembler`
+    Binned { resolution: i32 },
+}
+
+impl Default for FrameAssembly {
+    /// binned with the resolution `TimsFrame::from_tims_spectra` merges peaks at, so both produce the same frames
+    fn default() -> Self {
+        FrameAssembly::Binned { resolution: 6 }
+    }
+}
+
+/// Peaks of one scan as (m/z bin, tof index, intensity) in the order they were added
+struct ScanPeaks {
+    mobility: f64,
+    peaks: Vec<(i64, i32, f64)>,
+}
+
+/// Accumulates the peaks of a frame per scan and emits the sorted frame at the end
+///
+/// Peaks are binned by m/z as they are added and appended to their scan, without creating a spectrum per
+/// ion. When the frame is emitted, every scan is sorted once and peaks of the same m/z bin are merged,
+/// their intensities are summed in the order they were added and the tof index of the last peak is kept.
+/// The frame holds the bin centers as m/z values, with a resolution of 6 decimal digits it is identical
+/// to one built by `TimsFrame::from_tims_spectra` from the same peaks.
+pub struct FrameAssembler {
+    factor: f64,
+    calibration: TofCalibration,
+    scans: BTreeMap<i32, ScanPeaks>,
+    peaks: usize,
+}
+
+impl FrameAssembler {
+    /// Create an empty assembler
+    ///
+    /// # Arguments
+    ///
+    /// * `resolution` - Decimal digits of the m/z bins
+    /// * `calibration` - Converts the m/z values of added peaks to tof indices
+    ///
+    pub fn new(resolution: i32, calibration: TofCalibration) -> Self {
+        FrameAssembler {
+            factor: 10f64.powi(resolution),
+            calibration,
+            scans: BTreeMap::new(),
+            peaks: 0,
+        }
+    }
+
+    /// True if no peak was added
+    pub fn is_empty(&self) -> bool {
+        self.peaks == 0
+    }
+
+    fn scan(&mut self, scan: i32, mobility: f64) -> &mut ScanPeaks {
+        self.scans.entry(scan).or_insert_with(|| ScanPeaks { mobility, peaks: Vec::new() })
+    }
+
+    /// Add peaks with known tof indices to a scan, e.g. the background noise spectra of a frame
+    pub fn add_spectrum(&mut self, spectrum: &TimsSpectrum) {
+        let factor = self.factor;
+        let indexed = &spectrum.spectrum;
+        let peaks = self.scan(spectrum.scan, spectrum.mobility);
+        for ((&mz, &intensity), &tof) in indexed.mz_spectrum.mz.iter().zip(indexed.mz_spectrum.intensity.iter()).zip(indexed.index.iter()) {
+            peaks.peaks.push(((mz * factor).round() as i64, tof, intensity));
+        }
+        self.peaks += indexed.mz_spectrum.mz.len();
+    }
+
+    /// Add the peaks of a spectrum to a scan, with intensities multiplied by `scale`
+    pub fn add(&mut self, scan: i32, mobility: f64, spectrum: &MzSpectrum, scale: f64) {
+        self.add_ranged(scan, mobility, spectrum, scale, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY);
+    }
+
+    /// Add the peaks of a spectrum to a scan, with intensities multiplied by `scale`, peaks outside the m/z
+    /// range or with a scaled intensity outside the intensity range are skipped
+    #[allow(clippy::too_many_arguments, reason = "a lower and an upper bound for m/z and intensity")]
+    pub fn add_ranged(
+        &mut self,
+        scan: i32,
+        mobility: f64,
+        spectrum: &MzSpectrum,
+        scale: f64,
+        mz_min: f64,
+        mz_max: f64,
+        intensity_min: f64,
+        intensity_max: f64,
+    ) {
+        let (factor, calibration) = (self.factor, self.calibration);
+        let peaks = self.scan(scan, mobility);
+        let mut added = 0;
+        for (&mz, &intensity) in spectrum.mz.iter().zip(spectrum.intensity.iter()) {
+            let intensity = scale * intensity;
+            if mz < mz_min || mz > mz_max || intensity < intensity_min || intensity > intensity_max {
+                continue;
+            }
+            peaks.peaks.push(((mz * factor).round() as i64, calibration.mz_to_tof_index(mz), intensity));
+            added += 1;
+        }
+        self.peaks += added;
+    }
+
+    /// Emit the frame, ordered by scan and m/z
+    pub fn into_frame(self, frame_id: i32, ms_type: MsType, retention_time: f64) -> TimsFrame {
+        self.into_frame_ranged(
+            frame_id, ms_type, retention_time, f64::NEG_INFINITY, f64::INFINITY, i32::MIN, i32::MAX,
+            f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY,
+        )
+    }
+
+    /// Emit the frame with the merged peaks inside the bounds, the same as `into_frame` followed by
+    /// `TimsFrame::filter_ranged` without copying the frame
+    #[allow(clippy::too_many_arguments, reason = "frame metadata and a lower and an upper bound for every dimension")]
+    pub fn into_frame_ranged(
+        self,
+        frame_id: i32,
+        ms_type: MsType,
+        retention_time: f64,
+        mz_min: f64,
+        mz_max: f64,
+        scan_min: i32,
+        scan_max: i32,
+        inv_mob_min: f64,
+        inv_mob_max: f64,
+        intensity_min: f64,
+        intensity_max: f64,
+    ) -> TimsFrame {
+        let mut scan = Vec::with_capacity(self.peaks);
+        let mut mobility = Vec::with_capacity(self.peaks);
+        let mut tof = Vec::with_capacity(self.peaks);
+        let mut mz = Vec::with_capacity(self.peaks);
+        let mut intensity = Vec::with_capacity(self.peaks);
+
+        let keep = |scan: i32, mobility: f64, mz: f64, intensity: f64| {
+            mz >= mz_min && mz <= mz_max && scan >= scan_min && scan <= scan_max && mobility >= inv_mob_min
+                && mobility <= inv_mob_max && intensity >= intensity_min && intensity <= intensity_max
+        };
+
+        for (scan_id, mut scan_peaks) in self.scans {
+            // a stable sort keeps peaks of the same bin in the order they were added
+            scan_peaks.peaks.sort_by_key(|&(bin, _, _)| bin);
+            let mut merged = scan_peaks.peaks.chunk_by(|a, b| a.0 == b.0).map(|bin_peaks| {
+                let (bin, peak_tof, _) = bin_peaks[bin_peaks.len() - 1];
+                let peak_intensity = bin_peaks[1..].iter().fold(bin_peaks[0].2, |sum, peak| sum + peak.2);
+                (bin as f64 / self.factor, peak_tof, peak_intensity)
+            });
+            for (peak_mz, peak_tof, peak_intensity) in &mut merged {
+                if !keep(scan_id, scan_peaks.mobility, peak_mz, peak_intensity) {
+                    continue;
+                }
+                scan.push(scan_id);
+                mobility.push(scan_peaks.mobility);
+                tof.push(peak_tof);
+                mz.push(peak_mz);
+                intensity.push(peak_intensity);
+            }
+        }
+
+        TimsFrame::new(frame_id, ms_type, retention_time, scan, mobility, tof, mz, intensity)
+    }
+}
+
+/// Peaks of a frame that is being built, collected the way the builder's `FrameAssembly` asks for
+pub(crate) enum FramePeaks {
+    Spectra {
+        spectra: Vec<TimsSpectrum>,
+        calibration: TofCalibration,
+    },
+    Binned(FrameAssembler),
+}
+
+impl FramePeaks {
+    /// Start a frame with its background noise spectra
+    pub(crate) fn new(assembly: FrameAssembly, calibration: TofCalibration, background: Vec<TimsSpectrum>) -> Self {
+        match assembly {
+            FrameAssembly::Spectra => FramePeaks::Spectra { spectra: background, calibration },
+            FrameAssembly::Binned { resolution } => {
+                let mut assembler = FrameAssembler::new(resolution, calibration);
+                for spectrum in &background {
+                    assembler.add_spectrum(spectrum);
+                }
+                FramePeaks::Binned(assembler)
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            FramePeaks::Spectra { spectra, .. } => spectra.is_empty(),
+            FramePeaks::Binned(assembler) => assembler.is_empty(),
+        }
+    }
+
+    /// Add the peaks of an ion in a scan with intensities multiplied by `scale`, see `FrameAssembler::add_ranged`
+    /// for `range`, given as (mz_min, mz_max, intensity_min, intensity_max)
+    #[allow(clippy::too_many_arguments, reason = "the peak coordinates of TimsSpectrum plus scale and bounds")]
+    pub(crate) fn add(
+        &mut self,
+        frame_id: i32,
+        scan: i32,
+        retention_time: f64,
+        mobility: f64,
+        ms_type: &MsType,
+        spectrum: &MzSpectrum,
+        scale: f64,
+        range: Option<(f64, f64, f64, f64)>,
+    ) {
+        match self {
+            FramePeaks::Spectra { spectra, calibration } => {
+                let scaled = spectrum.clone() * scale;
+                let indexed = IndexedMzSpectrum::new(calibration.mz_to_tof(&scaled.mz), scaled.mz, scaled.intensity);
+                let indexed = match range {
+                    Some((mz_min, mz_max, intensity_min, intensity_max)) => {
+                        indexed.filter_ranged(mz_min, mz_max, intensity_min, intensity_max)
+                    }
+                    None => indexed,
+                };
+                spectra.push(TimsSpectrum::new(frame_id, scan, retention_time, mobility, ms_type.clone(), indexed));
+            }
+            FramePeaks::Binned(assembler) => match range {
+                Some((mz_min, mz_max, intensity_min, intensity_max)) => {
+                    assembler.add_ranged(scan, mobility, spectrum, scale, mz_min, mz_max, intensity_min, intensity_max)
+                }
+                None => assembler.add(scan, mobility, spectrum, scale),
+            },
+        }
+    }
+
+    /// The frame with the merged peaks inside the bounds, see `FrameAssembler::into_frame_ranged`
+    #[allow(clippy::too_many_arguments, reason = "frame metadata and a lower and an upper bound for every dimension")]
+    pub(crate) fn into_frame_ranged(
+        self,
+        frame_id: i32,
+        ms_type: MsType,
+        retention_time: f64,
+        mz_min: f64,
+        mz_max: f64,
+        scan_min: i32,
+        scan_max: i32,
+        inv_mob_min: f64,
+        inv_mob_max: f64,
+        intensity_min: f64,
+        intensity_max: f64,
+    ) -> TimsFrame {
+        match self {
+            FramePeaks::Spectra { spectra, .. } => TimsFrame::from_tims_spectra(spectra).filter_ranged(
+                mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max, intensity_min, intensity_max,
+            ),
+            FramePeaks::Binned(assembler) => assembler.into_frame_ranged(
+                frame_id, ms_type, retention_time, mz_min, mz_max, scan_min, scan_max, inv_mob_min, inv_mob_max,
+                intensity_min, intensity_max,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_assembler_matches_from_tims_spectra() {
+        let calibration = TofCalibration::default();
+        let mut rng = StdRng::seed_from_u64(3);
+        let contributions: Vec<(i32, MzSpectrum, f64)> = (0..500)
+            .map(|_| {
+                let scan = rng.gen_range(1..40);
+                // few distinct m/z values, so peaks of different ions share bins
+                let mz = (0..8).map(|_| 400.0 + rng.gen_range(0..200) as f64 * 0.005).collect();
+                let intensity = (0..8).map(|_| rng.gen_range(0.0..100.0)).collect();
+                (scan, MzSpectrum::new(mz, intensity), rng.gen_range(0.1..3.0))
+            })
+            .collect();
+        let mobility = |scan: i32| 1.5 - scan as f64 * 0.01;
+
+        let mut assembler = FrameAssembler::new(6, calibration);
+        let mut spectra = Vec::new();
+        for (scan, spectrum, scale) in &contributions {
+            assembler.add_ranged(*scan, mobility(*scan), spectrum, *scale, 400.2, 400.8, 5.0, 250.0);
+            let scaled = (spectrum.clone() * *scale).filter_ranged(400.2, 400.8, 5.0, 250.0);
+            spectra.push(TimsSpectrum::new(
+                7, *scan, 12.5, mobility(*scan), MsType::FragmentDia,
+                IndexedMzSpectrum::new(calibration.mz_to_tof(&scaled.mz), scaled.mz, scaled.intensity),
+            ));
+        }
+
+        let expected = TimsFrame::from_tims_spectra(spectra);
+        let frame = assembler.into_frame(7, MsType::FragmentDia, 12.5);
+        assert!(frame.ims_frame.mz.len() < 500 * 8);
+        assert_eq!(frame.scan, expected.scan);
+        assert_eq!(frame.ims_frame.mobility, expected.ims_frame.mobility);
+        assert_eq!(frame.tof, expected.tof);
+        assert_eq!(frame.ims_frame.mz, expected.ims_frame.mz);
+        assert_eq!(frame.ims_frame.intensity, expected.ims_frame.intensity);
+    }
+
+    #[test]
+    fn test_assembler_bins() {
+        let mut assembler = FrameAssembler::new(2, TofCalibration::default());
+        assert!(assembler.is_empty());
+        assembler.add(3, 1.0, &MzSpectrum::new(vec![500.004, 500.001, 600.0], vec![1.0, 2.0, 4.0]), 2.0);
+        assembler.add(1, 1.1, &MzSpectrum::new(vec![700.0], vec![1.0]), 1.0);
+
+        let frame = assembler.into_frame(1, MsType::Precursor, 0.0);
+        assert_eq!(frame.scan, vec![1, 3, 3]);
+        assert_eq!(frame.ims_frame.mz, vec![700.0, 500.0, 600.0]);
+        assert_eq!(frame.ims_frame.intensity, vec![1.0, 6.0, 8.0]);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::data::handle::IndexConverter;
+
+/// Number of tof indices a calibration is fitted to, see `TofCalibration::from_index_converter`
+const FIT_POINTS: u32 = 64;
+
+/// Invertible tof calibration of simulated frames, sqrt(m/z) = intercept + slope * tof + curvature * tof^2
+///
+/// Without curvature this is the model `SimpleIndexConverter` uses. The calibration is invertible as long as
+/// sqrt(m/z) increases monotonically over the tof range, see `is_invertible`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TofCalibration {
+    pub intercept: f64,
+    pub slope: f64,
+    pub curvature: f64,
+    /// number of tof indices, valid tof indices range from 0 to tof_max_index - 1
+    pub tof_max_index: u32,
+}
+
+impl Default for TofCalibration {
+    /// Same m/z and tof range as the `TdfWriter`
+    fn default() -> Self {
+        TofCalibration::from_boundaries(100.0, 1700.0, 400_000)
+    }
+}
+
+impl TofCalibration {
+    /// Linear calibration spanning an m/z range
+    ///
+    /// # Arguments
+    ///
+    /// * `mz_min` - m/z at tof index 0
+    /// * `mz_max` - m/z at tof index `tof_max_index`
+    /// * `tof_max_index` - number of tof indices
+    ///
+    pub fn from_boundaries(mz_min: f64, mz_max: f64, tof_max_index: u32) -> Self {
+        let intercept = mz_min.sqrt();
+        TofCalibration {
+            intercept,
+            slope: (mz_max.sqrt() - intercept) / tof_max_index as f64,
+            curvature: 0.0,
+            tof_max_index,
+        }
+    }
+
+    /// Quadratic calibration fitted to the tof to m/z conversion of a reference, e.g. a `TimsDataset`
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - converter of the reference
+    /// * `frame_id` - frame whose calibration is used
+    /// * `tof_max_index` - number of tof indices of the reference
+    ///
+    /// # Returns
+    ///
+    /// * least squares fit of sqrt(m/z) at evenly spaced tof indices, exact for a `SimpleIndexConverter`
+    ///
+    pub fn from_index_converter(converter: &dyn IndexConverter, frame_id: u32, tof_max_index: u32) -> Self {
+        let tof: Vec<u32> = (0..FIT_POINTS).map(|i| (i as u64 * tof_max_index as u64 / (FIT_POINTS - 1) as u64) as u32).collect();
+        let sqrt_mz: Vec<f64> = converter.tof_to_mz(frame_id, &tof).iter().map(|mz| mz.sqrt()).collect();
+
+        // normal equations of the fit on tof scaled to [0, 1], solved by Cramer's rule
+        let x: Vec<f64> = tof.iter().map(|&t| t as f64 / tof_max_index as f64).collect();
+        let moment = |power: i32| x.iter().map(|x| x.powi(power)).sum::<f64>();
+        let weighted = |power: i32| x.iter().zip(sqrt_mz.iter()).map(|(x, y)| x.powi(power) * y).sum::<f64>();
+        let a = [
+            [moment(0), moment(1), moment(2)],
+            [moment(1), moment(2), moment(3)],
+            [moment(2), moment(3), moment(4)],
+        ];
+        let b = [weighted(0), weighted(1), weighted(2)];
+
+        let determinant = |m: &[[f64; 3]; 3]| {
+            m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+        };
+        let det = determinant(&a);
+        let coefficient = |column: usize| {
+            let mut m = a;
+            for row in 0..3 {
+                m[row][column] = b[row];
+            }
+            determinant(&m) / det
+        };
+
+        let scale = tof_max_index as f64;
+        TofCalibration {
+            intercept: coefficient(0),
+            slope: coefficient(1) / scale,
+            curvature: coefficient(2) / (scale * scale),
+            tof_max_index,
+        }
+    }
+
+    /// Whether m/z increases with the tof index over the whole tof range, so that every m/z maps to one tof
+    pub fn is_invertible(&self) -> bool {
+        let derivative = |tof: f64| self.slope + 2.0 * self.curvature * tof;
+        derivative(0.0) > 0.0 && derivative(self.tof_max_index as f64) > 0.0
+    }
+
+    /// m/z at a possibly fractional tof index
+    pub fn tof_to_mz_exact(&self, tof: f64) -> f64 {
+        (self.intercept + self.slope * tof + self.curvature * tof * tof).powi(2)
+    }
+
+    /// Fractional tof index of an m/z, the inverse of `tof_to_mz_exact`
+    pub fn mz_to_tof_exact(&self, mz: f64) -> f64 {
+        // root of curvature * tof^2 + slope * tof - (sqrt(mz) - intercept), in a form that is stable for small curvature
+        let offset = mz.sqrt() - self.intercept;
+        2.0 * offset / (self.slope + (self.slope * self.slope + 4.0 * self.curvature * offset).sqrt())
+    }
+
+    /// m/z of tof indices
+    pub fn tof_to_mz(&self, tof: &[i32]) -> Vec<f64> {
+        tof.iter().map(|&tof| self.tof_to_mz_exact(tof as f64)).collect()
+    }
+
+    /// Nearest tof index of an m/z value, clamped to the tof range
+    pub fn mz_to_tof_index(&self, mz: f64) -> i32 {
+        let tof_max = self.tof_max_index.saturating_sub(1) as f64;
+        self.mz_to_tof_exact(mz).round().clamp(0.0, tof_max) as i32
+    }
+
+    /// Nearest tof indices of m/z values, clamped to the tof range
+    pub fn mz_to_tof(&self, mz: &[f64]) -> Vec<i32> {
+        mz.iter().map(|&mz| self.mz_to_tof_index(mz)).collect()
+    }
+
+    /// Same as `mz_to_tof`, for annotated frames which store tof indices unsigned
+    pub fn mz_to_tof_unsigned(&self, mz: &[f64]) -> Vec<u32> {
+        self.mz_to_tof(mz).into_iter().map(|tof| tof as u32).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::handle::SimpleIndexConverter;
+
+    #[test]
+    fn test_round_trip() {
+        let calibrations = [
+            TofCalibration::default(),
+            TofCalibration { curvature: 2e-12, ..TofCalibration::default() },
+            TofCalibration { curvature: -2e-11, ..TofCalibration::default() },
+        ];
+        for calibration in calibrations {
+            assert!(calibration.is_invertible());
+            for mz in [100.0, 150.5, 499.98765, 1234.5678, 1699.9] {
+                let round_trip = calibration.tof_to_mz_exact(calibration.mz_to_tof_exact(mz));
+                assert!((round_trip - mz).abs() / mz < 1e-6, "{} became {}", mz, round_trip);
+            }
+            // the nearest tof index is off by at most half an index
+            let tof = calibration.mz_to_tof(&[500.0]);
+            assert!((calibration.mz_to_tof_exact(500.0) - tof[0] as f64).abs() <= 0.5);
+        }
+        assert!(!TofCalibration { curvature: -1e-10, ..TofCalibration::default() }.is_invertible());
+    }
+
+    #[test]
+    fn test_fit_index_converter() {
+        let converter = SimpleIndexConverter::from_boundaries(150.0, 1600.0, 300_000, 0.6, 1.6, 900);
+        let calibration = TofCalibration::from_index_converter(&converter, 1, 300_000);
+        let reference = TofCalibration::from_boundaries(150.0, 1600.0, 300_000);
+
+        assert!(calibration.curvature.abs() < 1e-15);
+        for tof in [0, 1000, 123_456, 299_999] {
+            let mz = calibration.tof_to_mz(&[tof])[0];
+            assert!((mz - reference.tof_to_mz(&[tof])[0]).abs() / mz < 1e-9);
+        }
+        assert_eq!(calibration.mz_to_tof(&[50.0, 2000.0]), vec![0, 299_999]);
+    }
+}
@@ -80,6 +80,7 @@ pub struct PeptidesSim {
 }
 
 impl PeptidesSim {
+    #[allow(clippy::too_many_arguments, reason = "one argument per column of the peptides table")]
     pub fn new(
         protein_id: u32,
         peptide_id: u32,
@@ -180,6 +181,7 @@ pub struct IonSim {
 }
 
 impl IonSim {
+    #[allow(clippy::too_many_arguments, reason = "one argument per column of the ions table")]
     pub fn new(
         ion_id: u32,
         peptide_id: u32,
@@ -238,15 +240,24 @@ impl FramesSim {
         }
     }
     pub fn parse_ms_type(&self) -> MsType {
-        match self.ms_type {
-            0 => MsType::Precursor,
-            8 => MsType::FragmentDda,
-            9 => MsType::FragmentDia,
-            _ => MsType::Unknown,
-        }
+        MsType::new(self.ms_type as i32)
     }
 }
 
+/// A precursor selected for fragmentation in a simulated DDA-PASEF run, mirrors the Precursors table of a TDF
+#[derive(Debug, Clone)]
+pub struct DDAPrecursorSim {
+    pub id: u32,
+    pub ion_id: u32,
+    pub largest_peak_mz: f64,
+    pub average_mz: f64,
+    pub monoisotopic_mz: f64,
+    pub charge: i8,
+    pub scan_number: f64,
+    pub intensity: f64,
+    pub parent: u32,
+}
+
 pub struct FragmentIonSim {
     pub peptide_id: u32,
     pub ion_id: u32,
@@ -1,38 +1,222 @@
-use mscore::data::peptide::{PeptideIon, PeptideProductIonSeriesCollection};
+use mscore::data::peptide::PeptideIon;
 use mscore::data::spectrum::{IndexedMzSpectrum, MsType, MzSpectrum};
 use mscore::simulation::annotation::{
     MzSpectrumAnnotated, TimsFrameAnnotated, TimsSpectrumAnnotated,
 };
 use mscore::timstof::frame::TimsFrame;
-use mscore::timstof::quadrupole::{IonTransmission, TimsTransmissionDDA};
+use mscore::timstof::quadrupole::{IonTransmission, PASEFMeta, TimsTransmissionDDA};
 use mscore::timstof::spectrum::TimsSpectrum;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
-use crate::sim::handle::TimsTofSyntheticsDataHandle;
+use mscore::parallel;
+use crate::sim::containers::{DDAPrecursorSim, FramesSim, IonSim};
+use crate::sim::handle::{DuplicatePeptideStrategy, FragmentIonMap, SyntheticsError, TimsTofSyntheticsDataHandle};
 use crate::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+use crate::sim::scheduler::{CandidateFrame, PasefScheduler, PrecursorCandidate};
+use crate::sim::utility::{
+    add_mz_noise, add_mz_noise_annotated, lookup_fragment_spectra, noise_rng, quantization_rng,
+    quantize_collision_energy, FragmentLookupStats, FrameBuildOptions,
+};
 
 pub struct TimsTofSyntheticsFrameBuilderDDA {
     pub path: String,
     pub precursor_frame_builder: TimsTofSyntheticsPrecursorFrameBuilder,
     pub transmission_settings: TimsTransmissionDDA,
-    pub fragment_ions:
-        Option<BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrum>)>>,
-    pub fragment_ions_annotated: Option<
-        BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrumAnnotated>)>,
-    >,
+    pub fragment_ions: Option<FragmentIonMap<MzSpectrum>>,
+    pub fragment_ions_annotated: Option<FragmentIonMap<MzSpectrumAnnotated>>,
+    /// counts of exact and interpolated fragment ion lookups by collision energy
+    pub fragment_lookup_stats: FragmentLookupStats,
+}
+
+/// Settings of the simulated topN precursor selection of a DDA-PASEF run
+#[derive(Debug, Clone)]
+pub struct DDASelectionSettings {
+    /// topN selection with dynamic exclusion, the candidates are the simulated ions of every precursor frame
+    pub scheduler: PasefScheduler,
+    pub isolation_width: f64,
+    pub collision_energy: f64,
+}
+
+impl Default for DDASelectionSettings {
+    fn default() -> Self {
+        DDASelectionSettings {
+            scheduler: PasefScheduler::default(),
+            isolation_width: 3.0,
+            collision_energy: 30.0,
+        }
+    }
+}
+
+/// Schedule a DDA-PASEF run from the simulated precursor signal
+///
+/// Every ion of a precursor frame becomes a `PrecursorCandidate` with its simulated intensity (frame abundance,
+/// relative ion abundance, events and summed isotope intensity), the scans it occupies and the mobility of its
+/// abundance weighted mean scan, selection is done by `PasefScheduler::schedule`. Each selected ion is isolated
+/// at its monoisotopic m/z over its scans.
+///
+/// # Arguments
+///
+/// * `precursor_frame_builder` - The precursor frame builder holding the simulated ions
+/// * `settings` - The selection settings
+///
+/// # Returns
+///
+/// * The frames with updated ms types (0 for precursor, 8 for fragment frames), the selected precursors and the
+///   PASEF scheduling, precursors are numbered in fragment frame and scan order
+///
+pub fn schedule_dda_pasef(
+    precursor_frame_builder: &TimsTofSyntheticsPrecursorFrameBuilder,
+    settings: &DDASelectionSettings,
+) -> (Vec<FramesSim>, Vec<DDAPrecursorSim>, Vec<PASEFMeta>) {
+    let mut frames = precursor_frame_builder.frames.clone();
+    frames.sort_by_key(|frame| frame.frame_id);
+
+    // selected candidates are mapped back to their ion by precursor frame, peptide and m/z
+    let mut ions: HashMap<(u32, u32, u64), (&IonSim, f64)> = HashMap::new();
+    let mut candidate_frames: Vec<CandidateFrame> = Vec::with_capacity(frames.len());
+
+    for cycle in frames.chunks_mut(settings.scheduler.ms2_frames_per_cycle + 1) {
+        let (ms1, ms2) = cycle.split_at_mut(1);
+        ms1[0].ms_type = 0;
+        for frame in ms2.iter_mut() {
+            frame.ms_type = 8;
+        }
+
+        let ms1_frame_id = ms1[0].frame_id;
+        let mut candidates = Vec::new();
+        if let Some((peptide_ids, abundances)) = precursor_frame_builder.frame_to_abundances.get(&ms1_frame_id) {
+            for (peptide_id, abundance) in peptide_ids.iter().zip(abundances.iter()) {
+                let (peptide_ions, events) = match (
+                    precursor_frame_builder.ions.get(peptide_id),
+                    precursor_frame_builder.peptide_to_events.get(peptide_id),
+                ) {
+                    (Some(ions), Some(events)) => (ions, events),
+                    _ => continue,
+                };
+                for ion in peptide_ions.iter() {
+                    if ion.scan_distribution.occurrence.is_empty() || ion.simulated_spectrum.mz.is_empty() {
+                        continue;
+                    }
+                    let spectrum_intensity: f64 = ion.simulated_spectrum.intensity.iter().sum();
+                    let intensity = (*abundance * ion.relative_abundance * *events) as f64 * spectrum_intensity;
+                    let scan_number = mean_scan(ion);
+                    let mobility = precursor_frame_builder.scan_to_mobility
+                        .get(&(scan_number.round() as u32))
+                        .copied()
+                        .unwrap_or_default() as f64;
+                    let mz = ion.simulated_spectrum.mz[0];
+
+                    ions.insert((ms1_frame_id, *peptide_id, mz.to_bits()), (ion, scan_number));
+                    candidates.push(PrecursorCandidate {
+                        peptide_id: *peptide_id,
+                        mz,
+                        mobility,
+                        scan_start: *ion.scan_distribution.occurrence.iter().min().unwrap(),
+                        scan_end: *ion.scan_distribution.occurrence.iter().max().unwrap(),
+                        intensity,
+                    });
+                }
+            }
+        }
+
+        candidate_frames.push(CandidateFrame { frame_id: ms1_frame_id, retention_time: ms1[0].time as f64, candidates });
+        candidate_frames.extend(ms2.iter().map(|frame| CandidateFrame {
+            frame_id: frame.frame_id,
+            retention_time: frame.time as f64,
+            candidates: Vec::new(),
+        }));
+    }
+
+    let schedule = settings.scheduler.schedule(&candidate_frames);
+    let pasef_meta = PasefScheduler::to_pasef_meta(&schedule, settings.isolation_width, settings.collision_energy);
+
+    let precursors = schedule.iter()
+        .flat_map(|frame| frame.selections.iter())
+        .zip(pasef_meta.iter())
+        .map(|(selection, meta)| {
+            let candidate = &selection.candidate;
+            let (ion, scan_number) = ions[&(selection.precursor_frame_id, candidate.peptide_id, candidate.mz.to_bits())];
+            let spectrum = &ion.simulated_spectrum;
+            let spectrum_intensity: f64 = spectrum.intensity.iter().sum();
+            let (largest_index, _) = spectrum
+                .intensity
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .unwrap();
+            let average_mz = spectrum.mz.iter().zip(spectrum.intensity.iter()).map(|(mz, i)| mz * i).sum::<f64>()
+                / spectrum_intensity;
+
+            DDAPrecursorSim {
+                id: meta.precursor as u32,
+                ion_id: ion.ion_id,
+                largest_peak_mz: spectrum.mz[largest_index],
+                average_mz,
+                monoisotopic_mz: candidate.mz,
+                charge: ion.charge,
+                scan_number,
+                intensity: candidate.intensity,
+                parent: selection.precursor_frame_id,
+            }
+        })
+        .collect();
+
+    (frames, precursors, pasef_meta)
+}
+
+/// Abundance weighted mean scan of an ion
+fn mean_scan(ion: &IonSim) -> f64 {
+    let scan_abundance: f32 = ion.scan_distribution.abundance.iter().sum();
+    ion.scan_distribution
+        .occurrence
+        .iter()
+        .zip(ion.scan_distribution.abundance.iter())
+        .map(|(&scan, &abundance)| scan as f64 * abundance as f64)
+        .sum::<f64>()
+        / scan_abundance as f64
 }
 
 impl TimsTofSyntheticsFrameBuilderDDA {
-    pub fn new(path: &Path, with_annotations: bool, num_threads: usize) -> Self {
+    /// Simulate the DDA-PASEF precursor selection and write it to the synthetics database
+    ///
+    /// Updates the ms types of the frames table and replaces the precursors and pasef_meta tables,
+    /// see `schedule_dda_pasef`. A builder created from the database afterwards fragments the selected precursors.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the synthetics database
+    /// * `settings` - The selection settings
+    /// * `duplicate_strategy` - How duplicate peptides are handled, defaults to `DuplicatePeptideStrategy::Error`
+    ///
+    /// # Returns
+    ///
+    /// * The selected precursors and the PASEF scheduling
+    ///
+    pub fn simulate_selection_scheme(
+        path: &Path,
+        settings: &DDASelectionSettings,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+    ) -> Result<(Vec<DDAPrecursorSim>, Vec<PASEFMeta>), SyntheticsError> {
+        let precursor_frame_builder = TimsTofSyntheticsPrecursorFrameBuilder::new_lazy(path, duplicate_strategy, rayon::current_num_threads())?;
+        let (frames, precursors, pasef_meta) = schedule_dda_pasef(&precursor_frame_builder, settings);
+
+        let handle = TimsTofSyntheticsDataHandle::new(path)?;
+        handle.update_frame_types(&frames)?;
+        handle.write_dda_precursors(&precursors)?;
+        handle.write_pasef_meta(&pasef_meta)?;
+
+        Ok((precursors, pasef_meta))
+    }
+
+    pub fn new(path: &Path, with_annotations: bool, num_threads: usize, duplicate_strategy: Option<DuplicatePeptideStrategy>) -> Self {
 
-        let handle = TimsTofSyntheticsDataHandle::new(path).unwrap();
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(path).unwrap();
         let fragment_ions = handle.read_fragment_ions().unwrap();
         let transmission_settings = handle.get_transmission_dda();
 
-        let synthetics = TimsTofSyntheticsPrecursorFrameBuilder::new(path).unwrap();
+        let synthetics = TimsTofSyntheticsPrecursorFrameBuilder::new(path, duplicate_strategy, num_threads).unwrap();
 
         match with_annotations {
             true => {
@@ -48,6 +232,7 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                     transmission_settings,
                     fragment_ions: None,
                     fragment_ions_annotated: fragment_ions,
+                    fragment_lookup_stats: FragmentLookupStats::default(),
                 }
             }
             false => {
@@ -62,6 +247,7 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                     transmission_settings,
                     fragment_ions,
                     fragment_ions_annotated: None,
+                    fragment_lookup_stats: FragmentLookupStats::default(),
                 }
             }
         }
@@ -71,78 +257,35 @@ impl TimsTofSyntheticsFrameBuilderDDA {
     /// # Arguments
     ///
     /// * `frame_id` - The frame id
-    /// * `fragmentation` - A boolean indicating if fragmentation is enabled, if false, the frame has same mz distribution as the precursor frame but will be quadrupole filtered
+    /// * `options` - Noise, m/z and intensity limits, seed and intensity quantization, see `FrameBuildOptions`,
+    ///   without fragmentation a fragment frame has the m/z distribution of the precursor frame but is
+    ///   quadrupole filtered
     ///
     /// # Returns
     ///
     /// A TimsFrame
     ///
-    pub fn build_frame(
-        &self,
-        frame_id: u32,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
+    pub fn build_frame(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrame {
         // determine if the frame is a precursor frame
         match self
             .precursor_frame_builder
             .precursor_frame_id_set
             .contains(&frame_id)
         {
-            true => self.build_ms1_frame(
-                frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_noise_ppm,
-                right_drag,
-            ),
-            false => self.build_ms2_frame(
-                frame_id,
-                fragmentation,
-                mz_noise_fragment,
-                uniform,
-                fragment_noise_ppm,
-                right_drag,
-            ),
+            true => self.build_ms1_frame(frame_id, options),
+            false => self.build_ms2_frame(frame_id, options),
         }
     }
 
-    pub fn build_frame_annotated(
-        &self,
-        frame_id: u32,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrameAnnotated {
+    /// Build a frame with peak annotations, see `build_frame` for the options
+    pub fn build_frame_annotated(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrameAnnotated {
         match self
             .precursor_frame_builder
             .precursor_frame_id_set
             .contains(&frame_id)
         {
-            true => self.build_ms1_frame_annotated(
-                frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_noise_ppm,
-                right_drag,
-            ),
-            false => self.build_ms2_frame_annotated(
-                frame_id,
-                fragmentation,
-                mz_noise_fragment,
-                uniform,
-                fragment_noise_ppm,
-                right_drag,
-            ),
+            true => self.build_ms1_frame_annotated(frame_id, options),
+            false => self.build_ms2_frame_annotated(frame_id, options),
         }
     }
 
@@ -170,238 +313,164 @@ impl TimsTofSyntheticsFrameBuilderDDA {
     pub fn build_frames(
         &self,
         frame_ids: Vec<u32>,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-        num_threads: usize,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
     ) -> Vec<TimsFrame> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let thread_pool = parallel::pool(num_threads);
         let mut tims_frames: Vec<TimsFrame> = Vec::new();
 
         thread_pool.install(|| {
             tims_frames = frame_ids
                 .par_iter()
-                .map(|frame_id| {
-                    self.build_frame(
-                        *frame_id,
-                        fragmentation,
-                        mz_noise_precursor,
-                        uniform,
-                        precursor_noise_ppm,
-                        mz_noise_fragment,
-                        fragment_noise_ppm,
-                        right_drag,
-                    )
-                })
+                .map(|frame_id| self.build_frame(*frame_id, options))
                 .collect();
         });
 
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
+        tims_frames.sort_by_key(|a| a.frame_id);
 
         tims_frames
     }
     pub fn build_frames_annotated(
         &self,
         frame_ids: Vec<u32>,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-        num_threads: usize,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
     ) -> Vec<TimsFrameAnnotated> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let thread_pool = parallel::pool(num_threads);
         let mut tims_frames: Vec<TimsFrameAnnotated> = Vec::new();
 
         thread_pool.install(|| {
             tims_frames = frame_ids
                 .par_iter()
-                .map(|frame_id| {
-                    self.build_frame_annotated(
-                        *frame_id,
-                        fragmentation,
-                        mz_noise_precursor,
-                        uniform,
-                        precursor_noise_ppm,
-                        mz_noise_fragment,
-                        fragment_noise_ppm,
-                        right_drag,
-                    )
-                })
+                .map(|frame_id| self.build_frame_annotated(*frame_id, options))
                 .collect();
         });
 
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
+        tims_frames.sort_by_key(|a| a.frame_id);
 
         tims_frames
     }
 
-    fn build_ms1_frame(
-        &self,
-        frame_id: u32,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
-        let mut tims_frame = self.precursor_frame_builder.build_precursor_frame(
-            frame_id,
-            mz_noise_precursor,
-            uniform,
-            precursor_ppm,
-            right_drag,
+    fn build_ms1_frame(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrame {
+        // defaults are the limits the precursor frame builder already applies
+        let mut tims_frame = self
+            .precursor_frame_builder
+            .build_precursor_frame(
+                frame_id,
+                options.mz_noise_precursor,
+                options.uniform,
+                options.precursor_noise_ppm,
+                options.right_drag,
+                options.seed,
+            )
+            .filter_ranged(
+                options.mz_min.unwrap_or(0.0),
+                options.mz_max.unwrap_or(10000.0),
+                0,
+                2000,
+                0.0,
+                10.0,
+                options.intensity_min.unwrap_or(1.0),
+                options.intensity_max.unwrap_or(1e9),
+            );
+        tims_frame.ims_frame.intensity = options.quantization.quantize(
+            &tims_frame.ims_frame.intensity,
+            quantization_rng(options.seed, frame_id).as_mut(),
         );
-        let intensities_rounded = tims_frame
-            .ims_frame
-            .intensity
-            .iter()
-            .map(|x| x.round())
-            .collect::<Vec<_>>();
-        tims_frame.ims_frame.intensity = intensities_rounded;
         tims_frame
     }
 
-    fn build_ms1_frame_annotated(
-        &self,
-        frame_id: u32,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrameAnnotated {
+    fn build_ms1_frame_annotated(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrameAnnotated {
+        // same limits, noise and quantization as build_ms1_frame
         let mut tims_frame = self
             .precursor_frame_builder
             .build_precursor_frame_annotated(
                 frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_ppm,
-                right_drag,
+                options.mz_noise_precursor,
+                options.uniform,
+                options.precursor_noise_ppm,
+                options.right_drag,
+                options.seed,
+            )
+            .filter_ranged(
+                options.mz_min.unwrap_or(0.0),
+                options.mz_max.unwrap_or(10000.0),
+                0.0,
+                10.0,
+                0,
+                2000,
+                options.intensity_min.unwrap_or(1.0),
+                options.intensity_max.unwrap_or(1e9),
             );
-        let intensities_rounded = tims_frame
-            .intensity
-            .iter()
-            .map(|x| x.round())
-            .collect::<Vec<_>>();
-        tims_frame.intensity = intensities_rounded;
+        tims_frame.intensity = options.quantization.quantize(
+            &tims_frame.intensity,
+            quantization_rng(options.seed, frame_id).as_mut(),
+        );
         tims_frame
     }
 
-    fn build_ms2_frame(
-        &self,
-        frame_id: u32,
-        fragmentation: bool,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
-        match fragmentation {
+    /// Options of the precursor frame a fragment frame without fragmentation is filtered from,
+    /// it gets the m/z noise of the fragment frames
+    fn unfragmented_options(options: &FrameBuildOptions) -> FrameBuildOptions {
+        FrameBuildOptions {
+            mz_noise_precursor: options.mz_noise_fragment,
+            precursor_noise_ppm: options.fragment_noise_ppm,
+            ..*options
+        }
+    }
+
+    fn build_ms2_frame(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrame {
+        match options.fragmentation {
             false => {
                 let mut frame = self.transmission_settings.transmit_tims_frame(
-                    &self.build_ms1_frame(
-                        frame_id,
-                        mz_noise_fragment,
-                        uniform,
-                        fragment_ppm,
-                        right_drag,
-                    ),
+                    &self.build_ms1_frame(frame_id, &Self::unfragmented_options(options)),
                     None,
                 );
-                let intensities_rounded = frame
-                    .ims_frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.ims_frame.intensity = intensities_rounded;
+                // the precursor frame was quantized with the generator of this frame, draw from another one
+                let mut rng = quantization_rng(options.seed.map(|seed| !seed), frame_id);
+                frame.ims_frame.intensity =
+                    options.quantization.quantize(&frame.ims_frame.intensity, rng.as_mut());
                 frame.ms_type = MsType::FragmentDia;
                 frame
             }
             true => {
                 let mut frame = self.build_fragment_frame(
                     frame_id,
-                    &self.fragment_ions.as_ref().unwrap(),
-                    mz_noise_fragment,
-                    uniform,
-                    fragment_ppm,
-                    None,
-                    None,
-                    None,
-                    Some(right_drag),
+                    self.fragment_ions.as_ref().unwrap(),
+                    options,
+                );
+                frame.ims_frame.intensity = options.quantization.quantize(
+                    &frame.ims_frame.intensity,
+                    quantization_rng(options.seed, frame_id).as_mut(),
                 );
-                let intensities_rounded = frame
-                    .ims_frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.ims_frame.intensity = intensities_rounded;
                 frame
             }
         }
     }
 
-    fn build_ms2_frame_annotated(
-        &self,
-        frame_id: u32,
-        fragmentation: bool,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrameAnnotated {
-        match fragmentation {
+    fn build_ms2_frame_annotated(&self, frame_id: u32, options: &FrameBuildOptions) -> TimsFrameAnnotated {
+        match options.fragmentation {
             false => {
                 let mut frame = self.transmission_settings.transmit_tims_frame_annotated(
-                    &self.build_ms1_frame_annotated(
-                        frame_id,
-                        mz_noise_fragment,
-                        uniform,
-                        fragment_ppm,
-                        right_drag,
-                    ),
+                    &self.build_ms1_frame_annotated(frame_id, &Self::unfragmented_options(options)),
                     None,
                 );
-                let intensities_rounded = frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.intensity = intensities_rounded;
+                // the precursor frame was quantized with the generator of this frame, draw from another one
+                let mut rng = quantization_rng(options.seed.map(|seed| !seed), frame_id);
+                frame.intensity = options.quantization.quantize(&frame.intensity, rng.as_mut());
                 frame.ms_type = MsType::FragmentDia;
                 frame
             }
             true => {
                 let mut frame = self.build_fragment_frame_annotated(
                     frame_id,
-                    &self.fragment_ions_annotated.as_ref().unwrap(),
-                    mz_noise_fragment,
-                    uniform,
-                    fragment_ppm,
-                    None,
-                    None,
-                    None,
-                    Some(right_drag),
+                    self.fragment_ions_annotated.as_ref().unwrap(),
+                    options,
+                );
+                frame.intensity = options.quantization.quantize(
+                    &frame.intensity,
+                    quantization_rng(options.seed, frame_id).as_mut(),
                 );
-                let intensities_rounded = frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.intensity = intensities_rounded;
                 frame
             }
         }
@@ -412,9 +481,8 @@ impl TimsTofSyntheticsFrameBuilderDDA {
     /// # Arguments
     ///
     /// * `frame_id` - The frame id
-    /// * `mz_min` - The minimum m/z value in fragment spectrum
-    /// * `mz_max` - The maximum m/z value in fragment spectrum
-    /// * `intensity_min` - The minimum intensity value in fragment spectrum
+    /// * `fragment_ions` - The fragment ions of all peptide ions
+    /// * `options` - Fragment noise, seed and limits, m/z defaults to 100.0 - 1700.0 and intensity to 1.0 - 1e9
     ///
     /// # Returns
     ///
@@ -423,18 +491,14 @@ impl TimsTofSyntheticsFrameBuilderDDA {
     fn build_fragment_frame(
         &self,
         frame_id: u32,
-        fragment_ions: &BTreeMap<
-            (u32, i8, i32),
-            (PeptideProductIonSeriesCollection, Vec<MzSpectrum>),
-        >,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        mz_min: Option<f64>,
-        mz_max: Option<f64>,
-        intensity_min: Option<f64>,
-        right_drag: Option<bool>,
+        fragment_ions: &FragmentIonMap<MzSpectrum>,
+        options: &FrameBuildOptions,
     ) -> TimsFrame {
+        let mz_min = options.mz_min.unwrap_or(100.0);
+        let mz_max = options.mz_max.unwrap_or(1700.0);
+        let intensity_min = options.intensity_min.unwrap_or(1.0);
+        let intensity_max = options.intensity_max.unwrap_or(1e9);
+
         // check frame id
         let ms_type = match self
             .precursor_frame_builder
@@ -481,8 +545,8 @@ impl TimsTofSyntheticsFrameBuilderDDA {
             // jump to next peptide if the peptide_id is not in the peptide_to_ions map
             if !self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .contains_key(&peptide_id)
+                .peptide_to_ions()
+                .contains_key(peptide_id)
             {
                 continue;
             }
@@ -490,8 +554,8 @@ impl TimsTofSyntheticsFrameBuilderDDA {
             // get all the ions for the peptide
             let (ion_abundances, scan_occurrences, scan_abundances, charges, spectra) = self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .get(&peptide_id)
+                .peptide_to_ions()
+                .get(peptide_id)
                 .unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
@@ -520,7 +584,7 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                     let total_events = self
                         .precursor_frame_builder
                         .peptide_to_events
-                        .get(&peptide_id)
+                        .get(peptide_id)
                         .unwrap();
                     let fraction_events =
                         frame_abundance * scan_abundance * ion_abundance * total_events;
@@ -539,32 +603,39 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                         None => 0.0
                     };
 
-                    let collision_energy_quantized = (collision_energy * 1e1).round() as i32;
+                    let collision_energy_quantized = quantize_collision_energy(collision_energy);
 
                     // get charge state for the ion
                     let charge_state = charges.get(index).unwrap();
                     // extract fragment ions for the peptide, charge state and collision energy
-                    let maybe_value = fragment_ions.get(&(
+                    let maybe_value = lookup_fragment_spectra(
+                        fragment_ions,
                         *peptide_id,
                         *charge_state,
                         collision_energy_quantized,
-                    ));
+                        &self.fragment_lookup_stats,
+                    );
 
-                    // jump to next peptide if the fragment_ions is None (can this happen?)
+                    // jump to next peptide if no collision energy was precomputed for the peptide ion
                     if maybe_value.is_none() {
                         continue;
                     }
 
+                    // one generator per ion and scan, fragment ion series draw from it in order
+                    let mut rng = noise_rng(options.seed, frame_id, *peptide_id, *charge_state, *scan);
+
                     // for each fragment ion series, create a spectrum and add it to the tims_spectra
-                    for fragment_ion_series in maybe_value.unwrap().1.iter() {
-                        let scaled_spec = fragment_ion_series.clone() * fraction_events as f64;
-                        let right_drag = right_drag.unwrap_or(false);
-
-                        let mz_spectrum = if mz_noise_fragment {
-                            match uniform {
-                                true => scaled_spec.add_mz_noise_uniform(fragment_ppm, right_drag),
-                                false => scaled_spec.add_mz_noise_normal(fragment_ppm),
-                            }
+                    for fragment_ion_series in maybe_value.unwrap() {
+                        let scaled_spec = fragment_ion_series * fraction_events as f64;
+
+                        let mz_spectrum = if options.mz_noise_fragment {
+                            add_mz_noise(
+                                scaled_spec,
+                                options.uniform,
+                                options.fragment_noise_ppm,
+                                options.right_drag,
+                                rng.as_mut(),
+                            )
                         } else {
                             scaled_spec
                         };
@@ -580,15 +651,15 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                             *self
                                 .precursor_frame_builder
                                 .scan_to_mobility
-                                .get(&scan)
+                                .get(scan)
                                 .unwrap() as f64,
                             ms_type.clone(),
                             IndexedMzSpectrum::new(
-                                vec![0; mz_spectrum.mz.len()],
+                                self.precursor_frame_builder.tof_calibration.mz_to_tof(&mz_spectrum.mz),
                                 mz_spectrum.mz,
                                 mz_spectrum.intensity,
                             )
-                                .filter_ranged(100.0, 1700.0, 1.0, 1e9),
+                                .filter_ranged(mz_min, mz_max, intensity_min, intensity_max),
                         ));
                     }
                 }
@@ -614,31 +685,23 @@ impl TimsTofSyntheticsFrameBuilderDDA {
 
         let tims_frame = TimsFrame::from_tims_spectra(tims_spectra);
         tims_frame.filter_ranged(
-            mz_min.unwrap_or(100.0),
-            mz_max.unwrap_or(1700.0),
+            mz_min,
+            mz_max,
             0,
             1000,
             0.0,
             10.0,
-            intensity_min.unwrap_or(1.0),
-            1e9,
+            intensity_min,
+            intensity_max,
         )
     }
 
+    /// Build a fragment frame with peak annotations, see `build_fragment_frame` for the options
     pub fn build_fragment_frame_annotated(
         &self,
         frame_id: u32,
-        fragment_ions: &BTreeMap<
-            (u32, i8, i32),
-            (PeptideProductIonSeriesCollection, Vec<MzSpectrumAnnotated>),
-        >,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        mz_min: Option<f64>,
-        mz_max: Option<f64>,
-        intensity_min: Option<f64>,
-        right_drag: Option<bool>,
+        fragment_ions: &FragmentIonMap<MzSpectrumAnnotated>,
+        options: &FrameBuildOptions,
     ) -> TimsFrameAnnotated {
         let ms_type = match self
             .precursor_frame_builder
@@ -682,16 +745,16 @@ impl TimsTofSyntheticsFrameBuilderDDA {
         for (peptide_id, frame_abundance) in peptide_ids.iter().zip(frame_abundances.iter()) {
             if !self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .contains_key(&peptide_id)
+                .peptide_to_ions()
+                .contains_key(peptide_id)
             {
                 continue;
             }
 
             let (ion_abundances, scan_occurrences, scan_abundances, charges, _) = self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .get(&peptide_id)
+                .peptide_to_ions()
+                .get(peptide_id)
                 .unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
@@ -727,7 +790,7 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                     let total_events = self
                         .precursor_frame_builder
                         .peptide_to_events
-                        .get(&peptide_id)
+                        .get(peptide_id)
                         .unwrap();
                     let fraction_events =
                         frame_abundance * scan_abundance * ion_abundance * total_events;
@@ -746,28 +809,34 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                         None => 0.0
                     };
 
-                    let collision_energy_quantized = (collision_energy * 1e1).round() as i32;
+                    let collision_energy_quantized = quantize_collision_energy(collision_energy);
 
                     let charge_state = charges.get(index).unwrap();
-                    let maybe_value = fragment_ions.get(&(
+                    let maybe_value = lookup_fragment_spectra(
+                        fragment_ions,
                         *peptide_id,
                         *charge_state,
                         collision_energy_quantized,
-                    ));
+                        &self.fragment_lookup_stats,
+                    );
 
                     if maybe_value.is_none() {
                         continue;
                     }
 
-                    for fragment_ion_series in maybe_value.unwrap().1.iter() {
-                        let scaled_spec = fragment_ion_series.clone() * fraction_events as f64;
-                        let right_drag = right_drag.unwrap_or(false);
+                    let mut rng = noise_rng(options.seed, frame_id, *peptide_id, *charge_state, *scan);
+
+                    for fragment_ion_series in maybe_value.unwrap() {
+                        let scaled_spec = fragment_ion_series * fraction_events as f64;
 
-                        let mz_spectrum = if mz_noise_fragment {
-                            match uniform {
-                                true => scaled_spec.add_mz_noise_uniform(fragment_ppm, right_drag),
-                                false => scaled_spec.add_mz_noise_normal(fragment_ppm),
-                            }
+                        let mz_spectrum = if options.mz_noise_fragment {
+                            add_mz_noise_annotated(
+                                scaled_spec,
+                                options.uniform,
+                                options.fragment_noise_ppm,
+                                options.right_drag,
+                                rng.as_mut(),
+                            )
                         } else {
                             scaled_spec
                         };
@@ -783,10 +852,10 @@ impl TimsTofSyntheticsFrameBuilderDDA {
                             *self
                                 .precursor_frame_builder
                                 .scan_to_mobility
-                                .get(&scan)
+                                .get(scan)
                                 .unwrap() as f64,
                             ms_type.clone(),
-                            vec![0; mz_spectrum.mz.len()],
+                            self.precursor_frame_builder.tof_calibration.mz_to_tof_unsigned(&mz_spectrum.mz),
                             mz_spectrum,
                         ));
                     }
@@ -815,14 +884,14 @@ impl TimsTofSyntheticsFrameBuilderDDA {
         let tims_frame = TimsFrameAnnotated::from_tims_spectra_annotated(tims_spectra);
 
         tims_frame.filter_ranged(
-            mz_min.unwrap_or(100.0),
-            mz_max.unwrap_or(1700.0),
+            options.mz_min.unwrap_or(100.0),
+            options.mz_max.unwrap_or(1700.0),
             0.0,
             10.0,
             0,
             1000,
-            intensity_min.unwrap_or(1.0),
-            1e9,
+            options.intensity_min.unwrap_or(1.0),
+            options.intensity_max.unwrap_or(1e9),
         )
     }
 
@@ -839,4 +908,81 @@ impl TimsTofSyntheticsFrameBuilderDDA {
         }
         collision_energies
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::OnceLock;
+
+    /// one peptide present in frames 1 to 10 with two ions, ion 1 (m/z 500) is twice as intense as ion 2
+    /// (m/z 333.67), both occupy scans 10 to 12
+    fn precursor_frame_builder() -> TimsTofSyntheticsPrecursorFrameBuilder {
+        let frame_ids: Vec<u32> = (1..=10).collect();
+        let ion = |ion_id: u32, charge: i8, mz: f64, abundance: f32| {
+            IonSim::new(
+                ion_id,
+                1,
+                "PEPTIDEK".to_string(),
+                charge,
+                abundance,
+                1.0,
+                MzSpectrum::new(vec![mz, mz + 1.0 / charge as f64], vec![1.0, 0.5]),
+                vec![10, 11, 12],
+                vec![0.25, 0.5, 0.25],
+            )
+        };
+        TimsTofSyntheticsPrecursorFrameBuilder {
+            ions: BTreeMap::from([(1, vec![ion(1, 2, 500.0, 0.6), ion(2, 3, 333.67, 0.3)])]),
+            peptides: BTreeMap::new(),
+            scans: vec![],
+            frames: frame_ids.iter().map(|&f| FramesSim::new(f, f as f32, -1)).collect(),
+            precursor_frame_id_set: HashSet::new(),
+            frame_to_abundances: frame_ids.iter().map(|&f| (f, (vec![1], vec![0.1]))).collect(),
+            peptide_to_ions: OnceLock::from(BTreeMap::new()),
+            frame_to_rt: frame_ids.iter().map(|&f| (f, f as f32)).collect(),
+            scan_to_mobility: (0..20).map(|scan| (scan, 1.4 - scan as f32 * 0.01)).collect(),
+            peptide_to_events: BTreeMap::from([(1, 1e5)]),
+            noise_settings: Default::default(),
+            tof_calibration: Default::default(),
+            frame_assembly: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_simulated_ions_are_scheduled() {
+        let settings = DDASelectionSettings {
+            scheduler: PasefScheduler {
+                top_n: 1,
+                intensity_threshold: 100.0,
+                exclusion_duration: 5.0,
+                ms2_frames_per_cycle: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (frames, precursors, pasef_meta) = schedule_dda_pasef(&precursor_frame_builder(), &settings);
+
+        let ms1: Vec<u32> = frames.iter().filter(|f| f.ms_type == 0).map(|f| f.frame_id).collect();
+        assert_eq!(ms1, vec![1, 4, 7, 10]);
+        assert!(frames.iter().filter(|f| f.ms_type != 0).all(|f| f.ms_type == 8));
+
+        // ion 1 is excluded for 5 seconds after it was picked in frame 1, ion 2 fills in,
+        // the last cycle has no fragment frames left
+        let selected: Vec<(u32, u32)> = precursors.iter().map(|p| (p.parent, p.ion_id)).collect();
+        assert_eq!(selected, vec![(1, 1), (4, 2), (7, 1)]);
+
+        assert_eq!(pasef_meta.len(), 3);
+        assert_eq!(pasef_meta[0].frame, 2);
+        assert_eq!((pasef_meta[0].scan_start, pasef_meta[0].scan_end), (10, 12));
+        assert_eq!(pasef_meta[0].isolation_mz, 500.0);
+        assert_eq!(pasef_meta[0].precursor, 1);
+        assert_eq!(precursors.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        assert_eq!(precursors[0].charge, 2);
+        assert_eq!(precursors[0].monoisotopic_mz, 500.0);
+        assert!((precursors[0].scan_number - 11.0).abs() < 1e-9);
+        assert!((precursors[0].average_mz - (500.0 + 500.5 * 0.5) / 1.5).abs() < 1e-9);
+        assert!((precursors[0].intensity - 0.1 * 0.6 * 1e5 * 1.5).abs() < 1e-3);
+    }
+}
@@ -1,44 +1,97 @@
-use mscore::data::peptide::{PeptideIon, PeptideProductIonSeriesCollection};
-use mscore::data::spectrum::{IndexedMzSpectrum, MsType, MzSpectrum};
+use mscore::algorithm::fragmentation::{FragmentIntensityModel, MobileProtonModel};
+use mscore::data::peptide::PeptideIon;
+use mscore::data::spectrum::{MsType, MzSpectrum};
 use mscore::simulation::annotation::{
     MzSpectrumAnnotated, TimsFrameAnnotated, TimsSpectrumAnnotated,
 };
-use mscore::timstof::collision::{TimsTofCollisionEnergy, TimsTofCollisionEnergyDIA};
+use mscore::timstof::collision::{CollisionEnergySource, TimsTofCollisionEnergy};
 use mscore::timstof::frame::TimsFrame;
-use mscore::timstof::quadrupole::{IonTransmission, TimsTransmissionDIA};
-use mscore::timstof::spectrum::TimsSpectrum;
-use std::collections::{BTreeMap, HashSet};
+use mscore::timstof::quadrupole::{IonTransmission, TimsTransmissionDIA, TransmissionProfile};
+use std::collections::HashSet;
+use std::error::Error;
 use std::path::Path;
+use std::sync::Arc;
 
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel::{self, PoolHandle};
+
+use crate::sim::assembler::FramePeaks;
+use crate::sim::handle::{DuplicatePeptideStrategy, FragmentIonMap, SyntheticsError, TimsTofSyntheticsDataHandle};
+use crate::sim::precursor::{FrameBuildError, MissingKind, TimsTofSyntheticsPrecursorFrameBuilder};
+use crate::sim::utility::{
+    add_detection_noise, add_mz_noise, add_mz_noise_annotated, lookup_fragment_spectra, noise_rng,
+    quantization_rng, quantize_collision_energy, FragmentLookupStats, FrameBatchIterator,
+    FrameBuildOptions, PredictedFragmentIons,
+};
+use crate::sim::writer::TdfWriter;
+
+/// Frames of a batch, or the errors of all frames of the batch that could not be built
+pub type FrameBatch = Result<Vec<TimsFrame>, Vec<FrameBuildError>>;
+
+/// Which frames a DIA frame builder was created for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameBuilderMode {
+    /// precursor and fragment frames
+    Full,
+    /// precursor frames only, fragment ions are not loaded
+    Ms1,
+    /// fragment frames only, everything is loaded as for `Full`
+    Ms2,
+}
 
-use crate::sim::handle::TimsTofSyntheticsDataHandle;
-use crate::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+impl FrameBuilderMode {
+    /// Parse a mode by name, `full`, `ms1` or `ms2`
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "full" => Ok(FrameBuilderMode::Full),
+            "ms1" => Ok(FrameBuilderMode::Ms1),
+            "ms2" => Ok(FrameBuilderMode::Ms2),
+            _ => Err(format!("unknown frame builder mode {}, expected full, ms1 or ms2", name)),
+        }
+    }
+}
 
 pub struct TimsTofSyntheticsFrameBuilderDIA {
     pub path: String,
+    /// frames the builder was created for, builders in `Ms1` mode have no fragment ions
+    pub mode: FrameBuilderMode,
     pub precursor_frame_builder: TimsTofSyntheticsPrecursorFrameBuilder,
     pub transmission_settings: TimsTransmissionDIA,
-    pub fragmentation_settings: TimsTofCollisionEnergyDIA,
-    pub fragment_ions:
-        Option<BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrum>)>>,
-    pub fragment_ions_annotated: Option<
-        BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrumAnnotated>)>,
-    >,
+    pub fragmentation_settings: CollisionEnergySource,
+    pub fragment_ions: Option<FragmentIonMap<MzSpectrum>>,
+    pub fragment_ions_annotated: Option<FragmentIonMap<MzSpectrumAnnotated>>,
+    /// if true, a precursor is fully fragmented as soon as any isotope peak is transmitted,
+    /// otherwise fragment intensities are scaled by the transmitted fraction of the isotopic envelope
+    pub legacy_transmission: bool,
+    /// counts of exact and interpolated fragment ion lookups by collision energy
+    pub fragment_lookup_stats: FragmentLookupStats,
+    /// fragment ions of ions without precomputed intensities, predicted by a fragment intensity model
+    pub predicted_fragment_ions: PredictedFragmentIons<MzSpectrum>,
+    pub predicted_fragment_ions_annotated: PredictedFragmentIons<MzSpectrumAnnotated>,
 }
 
 impl TimsTofSyntheticsFrameBuilderDIA {
-    pub fn new(path: &Path, with_annotations: bool, num_threads: usize) -> rusqlite::Result<Self> {
-        let synthetics = TimsTofSyntheticsPrecursorFrameBuilder::new(path)?;
-        let handle = TimsTofSyntheticsDataHandle::new(path)?;
-
-        let fragment_ions = handle.read_fragment_ions()?;
+    pub fn new(
+        path: &Path,
+        with_annotations: bool,
+        num_threads: usize,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+        transmission_profile: Option<Arc<dyn TransmissionProfile>>,
+    ) -> Result<Self, SyntheticsError> {
+        let synthetics = TimsTofSyntheticsPrecursorFrameBuilder::new(path, duplicate_strategy, num_threads)?;
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(path)?;
+
+        // without precomputed intensities, all fragment ions are predicted by the fragment intensity model
+        let fragment_ions = match handle.has_table("fragment_ions")? {
+            true => handle.read_fragment_ions()?,
+            false => Vec::new(),
+        };
+        let fragment_model: Arc<dyn FragmentIntensityModel> = Arc::new(MobileProtonModel);
 
-        // get collision energy settings per window group
-        let fragmentation_settings = handle.get_collision_energy_dia();
+        // collision energies per window group, or from the mobility ramp if the database has one
+        let fragmentation_settings = handle.get_collision_energy_source()?;
         // get ion transmission settings per window group
-        let transmission_settings = handle.get_transmission_dia();
+        let transmission_settings = handle.get_transmission_dia_with_profile(transmission_profile);
 
         match with_annotations {
             true => {
@@ -50,11 +103,16 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                     ));
                 Ok(Self {
                     path: path.to_str().unwrap().to_string(),
+                    mode: FrameBuilderMode::Full,
                     precursor_frame_builder: synthetics,
                     transmission_settings,
                     fragmentation_settings,
                     fragment_ions: None,
                     fragment_ions_annotated: fragment_ions,
+                    legacy_transmission: false,
+                    fragment_lookup_stats: FragmentLookupStats::default(),
+                    predicted_fragment_ions: PredictedFragmentIons::new(fragment_model.clone()),
+                    predicted_fragment_ions_annotated: PredictedFragmentIons::new(fragment_model),
                 })
             }
 
@@ -66,93 +124,116 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                 ));
                 Ok(Self {
                     path: path.to_str().unwrap().to_string(),
+                    mode: FrameBuilderMode::Full,
                     precursor_frame_builder: synthetics,
                     transmission_settings,
                     fragmentation_settings,
                     fragment_ions,
                     fragment_ions_annotated: None,
+                    legacy_transmission: false,
+                    fragment_lookup_stats: FragmentLookupStats::default(),
+                    predicted_fragment_ions: PredictedFragmentIons::new(fragment_model.clone()),
+                    predicted_fragment_ions_annotated: PredictedFragmentIons::new(fragment_model),
                 })
             }
         }
     }
 
+    /// Create a builder for precursor frames only, fragment ions are neither read nor annotated, which
+    /// saves the startup time and memory they take
+    ///
+    /// Fragment frames can still be built without fragmentation, building them with fragmentation fails
+    /// with `MissingKind::FragmentIons`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the synthetics database
+    /// * `num_threads` - The number of threads used to build the lookup maps
+    /// * `duplicate_strategy` - How duplicate peptides are handled, `DuplicatePeptideStrategy::KeepFirst` if None
+    ///
+    pub fn new_ms1_only(
+        path: &Path,
+        num_threads: usize,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+    ) -> Result<Self, SyntheticsError> {
+        let synthetics = TimsTofSyntheticsPrecursorFrameBuilder::new(path, duplicate_strategy, num_threads)?;
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(path)?;
+        let fragment_model: Arc<dyn FragmentIntensityModel> = Arc::new(MobileProtonModel);
+
+        Ok(Self {
+            path: path.to_str().unwrap().to_string(),
+            mode: FrameBuilderMode::Ms1,
+            precursor_frame_builder: synthetics,
+            transmission_settings: handle.get_transmission_dia_with_profile(None),
+            fragmentation_settings: handle.get_collision_energy_source()?,
+            fragment_ions: None,
+            fragment_ions_annotated: None,
+            legacy_transmission: false,
+            fragment_lookup_stats: FragmentLookupStats::default(),
+            predicted_fragment_ions: PredictedFragmentIons::new(fragment_model.clone()),
+            predicted_fragment_ions_annotated: PredictedFragmentIons::new(fragment_model),
+        })
+    }
+
+    /// Replace the fragment intensity model used for ions without precomputed intensities, the default is
+    /// the `MobileProtonModel`, predictions of the previous model are dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - The fragment intensity model
+    ///
+    pub fn set_fragment_model(&mut self, model: Arc<dyn FragmentIntensityModel>) {
+        self.predicted_fragment_ions = PredictedFragmentIons::new(model.clone());
+        self.predicted_fragment_ions_annotated = PredictedFragmentIons::new(model);
+    }
+
     /// Build a frame for DIA synthetic experiment
     ///
     /// # Arguments
     ///
     /// * `frame_id` - The frame id
-    /// * `fragmentation` - A boolean indicating if fragmentation is enabled, if false, the frame has same mz distribution as the precursor frame but will be quadrupole filtered
+    /// * `options` - Noise, m/z and intensity limits, seed and intensity quantization, see `FrameBuildOptions`
     ///
     /// # Returns
     ///
-    /// A TimsFrame
+    /// A TimsFrame, a FrameBuildError if the synthetics database has no retention time for the frame
+    /// or no mobility for one of the scans an ion occurs in
     ///
     pub fn build_frame(
         &self,
         frame_id: u32,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrame, FrameBuildError> {
         // determine if the frame is a precursor frame
         match self
             .precursor_frame_builder
             .precursor_frame_id_set
             .contains(&frame_id)
         {
-            true => self.build_ms1_frame(
-                frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_noise_ppm,
-                right_drag,
-            ),
-            false => self.build_ms2_frame(
-                frame_id,
-                fragmentation,
-                mz_noise_fragment,
-                uniform,
-                fragment_noise_ppm,
-                right_drag,
-            ),
+            true => self.build_ms1_frame(frame_id, options),
+            false => self.build_ms2_frame(frame_id, options),
         }
     }
 
+    /// Build a frame with peak annotations, see `build_frame` for the options
+    ///
+    /// # Returns
+    ///
+    /// A TimsFrameAnnotated, a FrameBuildError with `MissingKind::FragmentIons` for a fragment frame with
+    /// fragmentation if the builder was not created with annotations, e.g. with `new_ms1_only`
+    ///
     pub fn build_frame_annotated(
         &self,
         frame_id: u32,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrameAnnotated {
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrameAnnotated, FrameBuildError> {
         match self
             .precursor_frame_builder
             .precursor_frame_id_set
             .contains(&frame_id)
         {
-            true => self.build_ms1_frame_annotated(
-                frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_noise_ppm,
-                right_drag,
-            ),
-            false => self.build_ms2_frame_annotated(
-                frame_id,
-                fragmentation,
-                mz_noise_fragment,
-                uniform,
-                fragment_noise_ppm,
-                right_drag,
-            ),
+            true => Ok(self.build_ms1_frame_annotated(frame_id, options)),
+            false => self.build_ms2_frame_annotated(frame_id, options),
         }
     }
 
@@ -177,190 +258,349 @@ impl TimsTofSyntheticsFrameBuilderDIA {
         result
     }
 
+    /// Build a collection of frames in parallel, see `build_frame` for the options
+    ///
+    /// All frames are built even if some fail, the errors of all failed frames are returned sorted by frame id
     pub fn build_frames(
         &self,
         frame_ids: Vec<u32>,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
+    ) -> Result<Vec<TimsFrame>, Vec<FrameBuildError>> {
+        let thread_pool = parallel::pool(num_threads);
+
+        let mut tims_frames = collect_frames(self.build_frames_in_pool(&thread_pool, &frame_ids, options))?;
+
+        tims_frames.sort_by_key(|a| a.frame_id);
+
+        Ok(tims_frames)
+    }
+
+    /// Build the precursor frames among `frame_ids` in parallel, fragment frames are skipped, see `build_frame`
+    /// for the options
+    ///
+    /// Works with builders of every mode, including builders created with `new_ms1_only`
+    pub fn build_precursor_frames_only(
+        &self,
+        frame_ids: Vec<u32>,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
+    ) -> Result<Vec<TimsFrame>, Vec<FrameBuildError>> {
+        let precursor_frame_ids = &self.precursor_frame_builder.precursor_frame_id_set;
+        let frame_ids = frame_ids
+            .into_iter()
+            .filter(|frame_id| precursor_frame_ids.contains(frame_id))
+            .collect();
+
+        self.build_frames(frame_ids, num_threads, options)
+    }
+
+    /// Build the fragment frames among `frame_ids` in parallel, precursor frames are skipped, see `build_frame`
+    /// for the options
+    ///
+    /// With fragmentation, a builder without fragment ions, e.g. one created with `new_ms1_only`, fails for
+    /// every fragment frame with `MissingKind::FragmentIons` before anything is built
+    pub fn build_fragment_frames_only(
+        &self,
+        frame_ids: Vec<u32>,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
+    ) -> Result<Vec<TimsFrame>, Vec<FrameBuildError>> {
+        let precursor_frame_ids = &self.precursor_frame_builder.precursor_frame_id_set;
+        let frame_ids: Vec<u32> = frame_ids
+            .into_iter()
+            .filter(|frame_id| !precursor_frame_ids.contains(frame_id))
+            .collect();
+
+        if options.fragmentation && self.fragment_ions.is_none() && !frame_ids.is_empty() {
+            return Err(frame_ids
+                .iter()
+                .map(|&frame_id| FrameBuildError { frame_id, missing: MissingKind::FragmentIons })
+                .collect());
+        }
+
+        self.build_frames(frame_ids, num_threads, options)
+    }
+
+    /// Build all fragment frames of a window group in parallel, see `build_fragment_frames_only`
+    ///
+    /// # Returns
+    ///
+    /// * The frames of the window group sorted by frame id, empty for unknown window groups
+    ///
+    pub fn build_window_group(
+        &self,
+        window_group: u32,
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
+    ) -> Result<Vec<TimsFrame>, Vec<FrameBuildError>> {
+        let frame_ids = self.frames_for_window_group(window_group);
+        self.build_fragment_frames_only(frame_ids, num_threads, options)
+    }
+
+    /// Ascending fragment frame ids of a window group, empty for unknown window groups
+    pub fn frames_for_window_group(&self, window_group: u32) -> Vec<u32> {
+        match i32::try_from(window_group) {
+            Ok(window_group) => self
+                .transmission_settings
+                .frames_for_window_group(window_group)
+                .into_iter()
+                .map(|frame_id| frame_id as u32)
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Build frames in batches of `batch_size` frames, handing every finished batch to `callback`
+    ///
+    /// Only one batch is alive at a time, so peak memory is bounded by the batch size and not by the
+    /// number of requested frames. Frames are built in parallel within a batch, batches are ordered by
+    /// frame id and the concatenation of all batches is identical to `build_frames`. Building stops after
+    /// the first batch with frames that could not be built, its errors are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - The frame ids to build
+    /// * `batch_size` - The number of frames per batch
+    /// * `num_threads` - The number of threads
+    /// * `options` - See `build_frame`
+    /// * `callback` - A closure that receives every batch of frames, sorted by frame id
+    ///
+    pub fn build_frames_batched<F>(
+        &self,
+        frame_ids: Vec<u32>,
+        batch_size: usize,
         num_threads: usize,
-    ) -> Vec<TimsFrame> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
-        let mut tims_frames: Vec<TimsFrame> = Vec::new();
+        options: &FrameBuildOptions,
+        mut callback: F,
+    ) -> Result<(), Vec<FrameBuildError>>
+    where
+        F: FnMut(Vec<TimsFrame>),
+    {
+        for batch in self.frame_batches(frame_ids, batch_size, num_threads, options) {
+            callback(batch?);
+        }
+        Ok(())
+    }
 
+    /// Create an iterator that lazily builds frames in batches of `batch_size` frames
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - The frame ids to build
+    /// * `batch_size` - The number of frames per batch
+    /// * `num_threads` - The number of threads
+    /// * `options` - See `build_frame`
+    ///
+    /// # Returns
+    ///
+    /// * A FrameBatchIterator yielding vectors of TimsFrame instances sorted by frame id, or the errors
+    ///   of the frames of a batch that could not be built
+    ///
+    pub fn frame_batches(
+        &self,
+        frame_ids: Vec<u32>,
+        batch_size: usize,
+        num_threads: usize,
+        options: &FrameBuildOptions,
+    ) -> FrameBatchIterator<impl FnMut(&[u32]) -> FrameBatch + '_> {
+        let thread_pool = parallel::pool(num_threads);
+        let options = *options;
+        FrameBatchIterator::new(frame_ids, batch_size, move |batch: &[u32]| {
+            collect_frames(self.build_frames_in_pool(&thread_pool, batch, &options))
+        })
+    }
+
+    fn build_frames_in_pool(
+        &self,
+        thread_pool: &PoolHandle,
+        frame_ids: &[u32],
+        options: &FrameBuildOptions,
+    ) -> Vec<Result<TimsFrame, FrameBuildError>> {
         thread_pool.install(|| {
-            tims_frames = frame_ids
+            frame_ids
                 .par_iter()
-                .map(|frame_id| {
-                    self.build_frame(
-                        *frame_id,
-                        fragmentation,
-                        mz_noise_precursor,
-                        uniform,
-                        precursor_noise_ppm,
-                        mz_noise_fragment,
-                        fragment_noise_ppm,
-                        right_drag,
-                    )
-                })
-                .collect();
-        });
+                .map(|frame_id| self.build_frame(*frame_id, options))
+                .collect()
+        })
+    }
 
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
+    /// Simulate all frames of the run and write them to a Bruker .d folder, frames are built and
+    /// written batch by batch so the run is never held in memory as a whole
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the .d folder, existing analysis files are replaced
+    /// * `batch_size` - Number of frames built and written at once
+    /// * `num_threads` - Number of threads used for building and compressing frames
+    /// * `options` - Noise, limits, seed and quantization of the frames, the m/z limits are also
+    ///   the acquisition range written to the dataset, see `TdfWriter::new`
+    ///
+    /// # Returns
+    ///
+    /// * The number of written frames
+    ///
+    pub fn write_dataset(
+        &self,
+        path: &Path,
+        batch_size: usize,
+        num_threads: usize,
+        options: &FrameBuildOptions,
+    ) -> Result<usize, Box<dyn Error>> {
+        let writer = TdfWriter::from_path(Path::new(&self.path), options)?;
+        writer.create(path)?;
+
+        let frame_ids: Vec<u32> = self
+            .precursor_frame_builder
+            .frames
+            .iter()
+            .map(|f| f.frame_id)
+            .collect();
+        let num_frames = frame_ids.len();
+
+        let batches = self.frame_batches(frame_ids, batch_size.max(1), num_threads, options);
+        let thread_pool = parallel::pool(num_threads);
+        for frames in batches {
+            let frames = frames.map_err(|errors| {
+                format!("{} frames could not be built, first: {}", errors.len(), errors[0])
+            })?;
+            thread_pool
+                .install(|| writer.write_frames(&frames, path).map_err(|e| e.to_string()))?;
+        }
 
-        tims_frames
+        Ok(num_frames)
     }
 
+    /// Build a collection of frames with peak annotations in parallel, see `build_frame_annotated`
+    ///
+    /// All frames are built even if some fail, the errors of all failed frames are returned sorted by frame id
     pub fn build_frames_annotated(
         &self,
         frame_ids: Vec<u32>,
-        fragmentation: bool,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_noise_ppm: f64,
-        mz_noise_fragment: bool,
-        fragment_noise_ppm: f64,
-        right_drag: bool,
-        num_threads: usize,
-    ) -> Vec<TimsFrameAnnotated> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
-        let mut tims_frames: Vec<TimsFrameAnnotated> = Vec::new();
+        num_threads: impl Into<Option<usize>>,
+        options: &FrameBuildOptions,
+    ) -> Result<Vec<TimsFrameAnnotated>, Vec<FrameBuildError>> {
+        let thread_pool = parallel::pool(num_threads);
 
-        thread_pool.install(|| {
-            tims_frames = frame_ids
+        let results = thread_pool.install(|| {
+            frame_ids
                 .par_iter()
-                .map(|frame_id| {
-                    self.build_frame_annotated(
-                        *frame_id,
-                        fragmentation,
-                        mz_noise_precursor,
-                        uniform,
-                        precursor_noise_ppm,
-                        mz_noise_fragment,
-                        fragment_noise_ppm,
-                        right_drag,
-                    )
-                })
-                .collect();
+                .map(|frame_id| self.build_frame_annotated(*frame_id, options))
+                .collect()
         });
 
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
+        let mut tims_frames = collect_frames(results)?;
+
+        tims_frames.sort_by_key(|a| a.frame_id);
 
-        tims_frames
+        Ok(tims_frames)
     }
 
     fn build_ms1_frame(
         &self,
         frame_id: u32,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
-        let mut tims_frame = self.precursor_frame_builder.build_precursor_frame(
-            frame_id,
-            mz_noise_precursor,
-            uniform,
-            precursor_ppm,
-            right_drag,
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrame, FrameBuildError> {
+        // defaults are the limits the precursor frame builder already applies
+        let mut tims_frame = self
+            .precursor_frame_builder
+            .try_build_precursor_frame(
+                frame_id,
+                options.mz_noise_precursor,
+                options.uniform,
+                options.precursor_noise_ppm,
+                options.right_drag,
+                options.seed,
+            )?
+            .filter_ranged(
+                options.mz_min.unwrap_or(0.0),
+                options.mz_max.unwrap_or(10000.0),
+                0,
+                2000,
+                0.0,
+                10.0,
+                options.intensity_min.unwrap_or(1.0),
+                options.intensity_max.unwrap_or(1e9),
+            );
+        tims_frame.ims_frame.intensity = options.quantization.quantize(
+            &tims_frame.ims_frame.intensity,
+            quantization_rng(options.seed, frame_id).as_mut(),
         );
-        let intensities_rounded = tims_frame
-            .ims_frame
-            .intensity
-            .iter()
-            .map(|x| x.round())
-            .collect::<Vec<_>>();
-        tims_frame.ims_frame.intensity = intensities_rounded;
-        tims_frame
+        Ok(tims_frame)
     }
 
     fn build_ms1_frame_annotated(
         &self,
         frame_id: u32,
-        mz_noise_precursor: bool,
-        uniform: bool,
-        precursor_ppm: f64,
-        right_drag: bool,
+        options: &FrameBuildOptions,
     ) -> TimsFrameAnnotated {
+        // same limits, noise and quantization as build_ms1_frame
         let mut tims_frame = self
             .precursor_frame_builder
             .build_precursor_frame_annotated(
                 frame_id,
-                mz_noise_precursor,
-                uniform,
-                precursor_ppm,
-                right_drag,
+                options.mz_noise_precursor,
+                options.uniform,
+                options.precursor_noise_ppm,
+                options.right_drag,
+                options.seed,
+            )
+            .filter_ranged(
+                options.mz_min.unwrap_or(0.0),
+                options.mz_max.unwrap_or(10000.0),
+                0.0,
+                10.0,
+                0,
+                2000,
+                options.intensity_min.unwrap_or(1.0),
+                options.intensity_max.unwrap_or(1e9),
             );
-        let intensities_rounded = tims_frame
-            .intensity
-            .iter()
-            .map(|x| x.round())
-            .collect::<Vec<_>>();
-        tims_frame.intensity = intensities_rounded;
+        tims_frame.intensity = options.quantization.quantize(
+            &tims_frame.intensity,
+            quantization_rng(options.seed, frame_id).as_mut(),
+        );
         tims_frame
     }
 
+    /// Options of the precursor frame a fragment frame without fragmentation is filtered from,
+    /// it gets the m/z noise of the fragment frames
+    fn unfragmented_options(options: &FrameBuildOptions) -> FrameBuildOptions {
+        FrameBuildOptions {
+            mz_noise_precursor: options.mz_noise_fragment,
+            precursor_noise_ppm: options.fragment_noise_ppm,
+            ..*options
+        }
+    }
+
     fn build_ms2_frame(
         &self,
         frame_id: u32,
-        fragmentation: bool,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrame {
-        match fragmentation {
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrame, FrameBuildError> {
+        match options.fragmentation {
             false => {
                 let mut frame = self.transmission_settings.transmit_tims_frame(
-                    &self.build_ms1_frame(
-                        frame_id,
-                        mz_noise_fragment,
-                        uniform,
-                        fragment_ppm,
-                        right_drag,
-                    ),
+                    &self.build_ms1_frame(frame_id, &Self::unfragmented_options(options))?,
                     None,
                 );
-                let intensities_rounded = frame
-                    .ims_frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.ims_frame.intensity = intensities_rounded;
+                // the precursor frame was quantized with the generator of this frame, draw from another one
+                let mut rng = quantization_rng(options.seed.map(|seed| !seed), frame_id);
+                frame.ims_frame.intensity =
+                    options.quantization.quantize(&frame.ims_frame.intensity, rng.as_mut());
                 frame.ms_type = MsType::FragmentDia;
-                frame
+                Ok(frame)
             }
             true => {
-                let mut frame = self.build_fragment_frame(
+                let fragment_ions = self.fragment_ions.as_ref().ok_or(FrameBuildError {
                     frame_id,
-                    &self.fragment_ions.as_ref().unwrap(),
-                    mz_noise_fragment,
-                    uniform,
-                    fragment_ppm,
-                    None,
-                    None,
-                    None,
-                    Some(right_drag),
+                    missing: MissingKind::FragmentIons,
+                })?;
+                let mut frame = self.build_fragment_frame(frame_id, fragment_ions, options)?;
+                frame.ims_frame.intensity = options.quantization.quantize(
+                    &frame.ims_frame.intensity,
+                    quantization_rng(options.seed, frame_id).as_mut(),
                 );
-                let intensities_rounded = frame
-                    .ims_frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.ims_frame.intensity = intensities_rounded;
-                frame
+                Ok(frame)
             }
         }
     }
@@ -368,84 +608,101 @@ impl TimsTofSyntheticsFrameBuilderDIA {
     fn build_ms2_frame_annotated(
         &self,
         frame_id: u32,
-        fragmentation: bool,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        right_drag: bool,
-    ) -> TimsFrameAnnotated {
-        match fragmentation {
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrameAnnotated, FrameBuildError> {
+        match options.fragmentation {
             false => {
                 let mut frame = self.transmission_settings.transmit_tims_frame_annotated(
-                    &self.build_ms1_frame_annotated(
-                        frame_id,
-                        mz_noise_fragment,
-                        uniform,
-                        fragment_ppm,
-                        right_drag,
-                    ),
+                    &self.build_ms1_frame_annotated(frame_id, &Self::unfragmented_options(options)),
                     None,
                 );
-                let intensities_rounded = frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.intensity = intensities_rounded;
+                // the precursor frame was quantized with the generator of this frame, draw from another one
+                let mut rng = quantization_rng(options.seed.map(|seed| !seed), frame_id);
+                frame.intensity = options.quantization.quantize(&frame.intensity, rng.as_mut());
                 frame.ms_type = MsType::FragmentDia;
-                frame
+                Ok(frame)
             }
             true => {
-                let mut frame = self.build_fragment_frame_annotated(
+                let fragment_ions = self.fragment_ions_annotated.as_ref().ok_or(FrameBuildError {
                     frame_id,
-                    &self.fragment_ions_annotated.as_ref().unwrap(),
-                    mz_noise_fragment,
-                    uniform,
-                    fragment_ppm,
-                    None,
-                    None,
-                    None,
-                    Some(right_drag),
+                    missing: MissingKind::FragmentIons,
+                })?;
+                let mut frame = self.build_fragment_frame_annotated(frame_id, fragment_ions, options);
+                frame.intensity = options.quantization.quantize(
+                    &frame.intensity,
+                    quantization_rng(options.seed, frame_id).as_mut(),
                 );
-                let intensities_rounded = frame
-                    .intensity
-                    .iter()
-                    .map(|x| x.round())
-                    .collect::<Vec<_>>();
-                frame.intensity = intensities_rounded;
-                frame
+                Ok(frame)
             }
         }
     }
 
+    /// Fraction of a precursor isotopic envelope that is transmitted by the quadrupole
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - The frame id
+    /// * `scan` - The scan id
+    /// * `mz` - The m/z values of the isotope peaks
+    /// * `intensity` - The intensities of the isotope peaks
+    ///
+    /// # Returns
+    ///
+    /// A value between 0.0 and 1.0, with `legacy_transmission` either 0.0 or 1.0
+    ///
+    fn precursor_transmitted_fraction(
+        &self,
+        frame_id: u32,
+        scan: u32,
+        mz: &[f64],
+        intensity: &[f64],
+    ) -> f64 {
+        match self.legacy_transmission {
+            true => match self.transmission_settings.any_transmitted(
+                frame_id as i32,
+                scan as i32,
+                &mz.to_vec(),
+                None,
+            ) {
+                true => 1.0,
+                false => 0.0,
+            },
+            false => self.transmission_settings.transmitted_fraction(
+                frame_id as i32,
+                scan as i32,
+                mz,
+                intensity,
+                None,
+            ),
+        }
+    }
+
     /// Build a fragment frame
     ///
     /// # Arguments
     ///
     /// * `frame_id` - The frame id
-    /// * `mz_min` - The minimum m/z value in fragment spectrum
-    /// * `mz_max` - The maximum m/z value in fragment spectrum
-    /// * `intensity_min` - The minimum intensity value in fragment spectrum
+    /// * `fragment_ions` - The fragment ions of all peptide ions
+    /// * `options` - Fragment noise, seed and limits, m/z defaults to 100.0 - 1700.0 and intensity to 1.0 - 1e9
     ///
     /// # Returns
     ///
-    /// A TimsFrame
+    /// A TimsFrame, a FrameBuildError if the synthetics database does not cover the frame or one of its scans
     ///
     fn build_fragment_frame(
         &self,
         frame_id: u32,
-        fragment_ions: &BTreeMap<
-            (u32, i8, i32),
-            (PeptideProductIonSeriesCollection, Vec<MzSpectrum>),
-        >,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        mz_min: Option<f64>,
-        mz_max: Option<f64>,
-        intensity_min: Option<f64>,
-        right_drag: Option<bool>,
-    ) -> TimsFrame {
+        fragment_ions: &FragmentIonMap<MzSpectrum>,
+        options: &FrameBuildOptions,
+    ) -> Result<TimsFrame, FrameBuildError> {
+        let mz_min = options.mz_min.unwrap_or(100.0);
+        let mz_max = options.mz_max.unwrap_or(1700.0);
+        let intensity_min = options.intensity_min.unwrap_or(1.0);
+        let intensity_max = options.intensity_max.unwrap_or(1e9);
+        let (mz_noise_fragment, uniform, fragment_ppm, seed) =
+            (options.mz_noise_fragment, options.uniform, options.fragment_noise_ppm, options.seed);
+        let detection_noise = self.precursor_frame_builder.noise_settings.detection_noise;
+
         // check frame id
         let ms_type = match self
             .precursor_frame_builder
@@ -456,44 +713,48 @@ impl TimsTofSyntheticsFrameBuilderDIA {
             true => MsType::Unknown,
         };
 
-        let mut tims_spectra: Vec<TimsSpectrum> = Vec::new();
+        let retention_time = self.precursor_frame_builder.retention_time(frame_id)?;
+        let mut peaks = FramePeaks::new(
+            self.precursor_frame_builder.frame_assembly,
+            self.precursor_frame_builder.tof_calibration,
+            self.precursor_frame_builder
+                .build_background_noise(frame_id, &ms_type, seed)?,
+        );
 
         // Frame might not have any peptides
         if !self
             .precursor_frame_builder
             .frame_to_abundances
             .contains_key(&frame_id)
+            && peaks.is_empty()
         {
-            return TimsFrame::new(
+            return Ok(TimsFrame::new(
                 frame_id as i32,
                 ms_type.clone(),
-                *self
-                    .precursor_frame_builder
-                    .frame_to_rt
-                    .get(&frame_id)
-                    .unwrap() as f64,
+                retention_time,
                 vec![],
                 vec![],
                 vec![],
                 vec![],
                 vec![],
-            );
+            ));
         }
 
-        // Get the peptide ids and abundances for the frame, should now save to unwrap since we checked if the frame is in the map
+        // Get the peptide ids and abundances for the frame, a frame with only background noise has none
+        let no_peptides = (Vec::new(), Vec::new());
         let (peptide_ids, frame_abundances) = self
             .precursor_frame_builder
             .frame_to_abundances
             .get(&frame_id)
-            .unwrap();
+            .unwrap_or(&no_peptides);
 
         // Go over all peptides in the frame with their respective abundances
         for (peptide_id, frame_abundance) in peptide_ids.iter().zip(frame_abundances.iter()) {
             // jump to next peptide if the peptide_id is not in the peptide_to_ions map
             if !self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .contains_key(&peptide_id)
+                .peptide_to_ions()
+                .contains_key(peptide_id)
             {
                 continue;
             }
@@ -501,8 +762,8 @@ impl TimsTofSyntheticsFrameBuilderDIA {
             // get all the ions for the peptide
             let (ion_abundances, scan_occurrences, scan_abundances, charges, spectra) = self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .get(&peptide_id)
+                .peptide_to_ions()
+                .get(peptide_id)
                 .unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
@@ -517,13 +778,14 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                 for (scan, scan_abundance) in
                     all_scan_occurrence.iter().zip(all_scan_abundance.iter())
                 {
-                    // first, check if precursor is transmitted
-                    if !self.transmission_settings.any_transmitted(
-                        frame_id as i32,
-                        *scan as i32,
+                    // first, check which fraction of the precursor envelope is transmitted
+                    let transmitted_fraction = self.precursor_transmitted_fraction(
+                        frame_id,
+                        *scan,
                         &spectrum.mz,
-                        None,
-                    ) {
+                        &spectrum.intensity,
+                    );
+                    if transmitted_fraction <= 0.0 {
                         continue;
                     }
 
@@ -531,115 +793,122 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                     let total_events = self
                         .precursor_frame_builder
                         .peptide_to_events
-                        .get(&peptide_id)
+                        .get(peptide_id)
                         .unwrap();
-                    let fraction_events =
-                        frame_abundance * scan_abundance * ion_abundance * total_events;
+                    let fraction_events = frame_abundance
+                        * scan_abundance
+                        * ion_abundance
+                        * total_events
+                        * transmitted_fraction as f32;
 
                     // get collision energy for the ion
                     let collision_energy = self
                         .fragmentation_settings
                         .get_collision_energy(frame_id as i32, *scan as i32);
-                    let collision_energy_quantized = (collision_energy * 1e1).round() as i32;
+                    let collision_energy_quantized = quantize_collision_energy(collision_energy);
 
                     // get charge state for the ion
                     let charge_state = charges.get(index).unwrap();
-                    // extract fragment ions for the peptide, charge state and collision energy
-                    let maybe_value = fragment_ions.get(&(
+                    // extract fragment ions for the peptide, charge state and collision energy,
+                    // predict them if no collision energy was precomputed for the peptide ion
+                    let fragment_spectra = lookup_fragment_spectra(
+                        fragment_ions,
                         *peptide_id,
                         *charge_state,
                         collision_energy_quantized,
-                    ));
-
-                    // jump to next peptide if the fragment_ions is None (can this happen?)
-                    if maybe_value.is_none() {
-                        continue;
-                    }
-
-                    // for each fragment ion series, create a spectrum and add it to the tims_spectra
-                    for fragment_ion_series in maybe_value.unwrap().1.iter() {
-                        let scaled_spec = fragment_ion_series.clone() * fraction_events as f64;
-                        let right_drag = right_drag.unwrap_or(false);
+                        &self.fragment_lookup_stats,
+                    )
+                    .unwrap_or_else(|| {
+                        self.predicted_fragment_ions.get_or_predict(
+                            *peptide_id,
+                            &self.precursor_frame_builder.peptides[peptide_id].sequence,
+                            *charge_state,
+                            collision_energy_quantized,
+                            &self.fragment_lookup_stats,
+                            |prediction| {
+                                prediction
+                                    .peptide_ions
+                                    .iter()
+                                    .map(|ion_series| {
+                                        ion_series.generate_isotopic_spectrum(1e-2, 1e-3, 100, 1e-5)
+                                    })
+                                    .collect()
+                            },
+                        )
+                    });
+
+                    let mobility = self.precursor_frame_builder.mobility(frame_id, *scan)?;
+
+                    // one generator per ion and scan, fragment ion series draw from it in order
+                    let mut rng = noise_rng(seed, frame_id, *peptide_id, *charge_state, *scan);
+
+                    let range = Some((mz_min, mz_max, intensity_min, intensity_max));
+
+                    // for each fragment ion series, add its peaks to the frame
+                    for fragment_ion_series in fragment_spectra {
+                        // without noise, the spectrum is scaled while its peaks are added
+                        if !mz_noise_fragment && !detection_noise {
+                            peaks.add(frame_id as i32, *scan as i32, retention_time, mobility, &ms_type, &fragment_ion_series, fraction_events as f64, range);
+                            continue;
+                        }
+
+                        let scaled_spec = fragment_ion_series * fraction_events as f64;
 
                         let mz_spectrum = if mz_noise_fragment {
-                            match uniform {
-                                true => scaled_spec.add_mz_noise_uniform(fragment_ppm, right_drag),
-                                false => scaled_spec.add_mz_noise_normal(fragment_ppm),
-                            }
+                            add_mz_noise(scaled_spec, uniform, fragment_ppm, options.right_drag, rng.as_mut())
                         } else {
                             scaled_spec
                         };
 
-                        tims_spectra.push(TimsSpectrum::new(
-                            frame_id as i32,
-                            *scan as i32,
-                            *self
-                                .precursor_frame_builder
-                                .frame_to_rt
-                                .get(&frame_id)
-                                .unwrap() as f64,
-                            *self
-                                .precursor_frame_builder
-                                .scan_to_mobility
-                                .get(&scan)
-                                .unwrap() as f64,
-                            ms_type.clone(),
-                            IndexedMzSpectrum::new(
-                                vec![0; mz_spectrum.mz.len()],
-                                mz_spectrum.mz,
-                                mz_spectrum.intensity,
-                            )
-                            .filter_ranged(100.0, 1700.0, 1.0, 1e9),
-                        ));
+                        let mz_spectrum = match detection_noise {
+                            true => add_detection_noise(mz_spectrum, rng.as_mut()),
+                            false => mz_spectrum,
+                        };
+
+                        peaks.add(frame_id as i32, *scan as i32, retention_time, mobility, &ms_type, &mz_spectrum, 1.0, range);
                     }
                 }
             }
         }
 
-        if tims_spectra.is_empty() {
-            return TimsFrame::new(
+        if peaks.is_empty() {
+            return Ok(TimsFrame::new(
                 frame_id as i32,
                 ms_type.clone(),
-                *self
-                    .precursor_frame_builder
-                    .frame_to_rt
-                    .get(&frame_id)
-                    .unwrap() as f64,
+                retention_time,
                 vec![],
                 vec![],
                 vec![],
                 vec![],
                 vec![],
-            );
+            ));
         }
 
-        let tims_frame = TimsFrame::from_tims_spectra(tims_spectra);
-        tims_frame.filter_ranged(
-            mz_min.unwrap_or(100.0),
-            mz_max.unwrap_or(1700.0),
+        Ok(peaks.into_frame_ranged(
+            frame_id as i32,
+            ms_type,
+            retention_time,
+            mz_min,
+            mz_max,
             0,
             1000,
             0.0,
             10.0,
-            intensity_min.unwrap_or(1.0),
-            1e9,
-        )
+            intensity_min,
+            intensity_max,
+        ))
     }
 
+    /// Build a fragment frame with peak annotations, see `build_fragment_frame` for the options
+    ///
+    /// m/z noise is drawn from the per ion and scan generators of `build_fragment_frame`,
+    /// detection and background noise are not simulated for annotated frames
+    ///
     pub fn build_fragment_frame_annotated(
         &self,
         frame_id: u32,
-        fragment_ions: &BTreeMap<
-            (u32, i8, i32),
-            (PeptideProductIonSeriesCollection, Vec<MzSpectrumAnnotated>),
-        >,
-        mz_noise_fragment: bool,
-        uniform: bool,
-        fragment_ppm: f64,
-        mz_min: Option<f64>,
-        mz_max: Option<f64>,
-        intensity_min: Option<f64>,
-        right_drag: Option<bool>,
+        fragment_ions: &FragmentIonMap<MzSpectrumAnnotated>,
+        options: &FrameBuildOptions,
     ) -> TimsFrameAnnotated {
         let ms_type = match self
             .precursor_frame_builder
@@ -683,16 +952,16 @@ impl TimsTofSyntheticsFrameBuilderDIA {
         for (peptide_id, frame_abundance) in peptide_ids.iter().zip(frame_abundances.iter()) {
             if !self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .contains_key(&peptide_id)
+                .peptide_to_ions()
+                .contains_key(peptide_id)
             {
                 continue;
             }
 
             let (ion_abundances, scan_occurrences, scan_abundances, charges, _) = self
                 .precursor_frame_builder
-                .peptide_to_ions
-                .get(&peptide_id)
+                .peptide_to_ions()
+                .get(peptide_id)
                 .unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
@@ -710,54 +979,87 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                     *ion_abundance as f64,
                     Some(*peptide_id as i32),
                 );
+                let ion_id = self
+                    .precursor_frame_builder
+                    .ions
+                    .get(peptide_id)
+                    .and_then(|ions| ions.get(index))
+                    .map_or(-1, |ion| ion.ion_id as i32);
                 // TODO: make this configurable
                 let spectrum = ion.calculate_isotopic_spectrum_annotated(1e-3, 1e-8, 200, 1e-4);
 
                 for (scan, scan_abundance) in
                     all_scan_occurrence.iter().zip(all_scan_abundance.iter())
                 {
-                    if !self.transmission_settings.any_transmitted(
-                        frame_id as i32,
-                        *scan as i32,
+                    let transmitted_fraction = self.precursor_transmitted_fraction(
+                        frame_id,
+                        *scan,
                         &spectrum.mz,
-                        None,
-                    ) {
+                        &spectrum.intensity,
+                    );
+                    if transmitted_fraction <= 0.0 {
                         continue;
                     }
 
                     let total_events = self
                         .precursor_frame_builder
                         .peptide_to_events
-                        .get(&peptide_id)
+                        .get(peptide_id)
                         .unwrap();
-                    let fraction_events =
-                        frame_abundance * scan_abundance * ion_abundance * total_events;
+                    let fraction_events = frame_abundance
+                        * scan_abundance
+                        * ion_abundance
+                        * total_events
+                        * transmitted_fraction as f32;
 
                     let collision_energy = self
                         .fragmentation_settings
                         .get_collision_energy(frame_id as i32, *scan as i32);
-                    let collision_energy_quantized = (collision_energy * 1e1).round() as i32;
+                    let collision_energy_quantized = quantize_collision_energy(collision_energy);
 
                     let charge_state = charges.get(index).unwrap();
-                    let maybe_value = fragment_ions.get(&(
+                    let fragment_spectra = lookup_fragment_spectra(
+                        fragment_ions,
                         *peptide_id,
                         *charge_state,
                         collision_energy_quantized,
-                    ));
-
-                    if maybe_value.is_none() {
-                        continue;
-                    }
-
-                    for fragment_ion_series in maybe_value.unwrap().1.iter() {
-                        let scaled_spec = fragment_ion_series.clone() * fraction_events as f64;
-                        let right_drag = right_drag.unwrap_or(false);
-
-                        let mz_spectrum = if mz_noise_fragment {
-                            match uniform {
-                                true => scaled_spec.add_mz_noise_uniform(fragment_ppm, right_drag),
-                                false => scaled_spec.add_mz_noise_normal(fragment_ppm),
-                            }
+                        &self.fragment_lookup_stats,
+                    )
+                    .unwrap_or_else(|| {
+                        self.predicted_fragment_ions_annotated.get_or_predict(
+                            *peptide_id,
+                            &peptide.sequence,
+                            *charge_state,
+                            collision_energy_quantized,
+                            &self.fragment_lookup_stats,
+                            |prediction| {
+                                prediction
+                                    .peptide_ions
+                                    .iter()
+                                    .map(|ion_series| {
+                                        ion_series.generate_isotopic_spectrum_annotated(
+                                            1e-2, 1e-3, 100, 1e-5,
+                                        )
+                                    })
+                                    .collect()
+                            },
+                        )
+                    });
+
+                    let mut rng = noise_rng(options.seed, frame_id, *peptide_id, *charge_state, *scan);
+
+                    for fragment_ion_series in fragment_spectra {
+                        let scaled_spec =
+                            fragment_ion_series.with_ion_id(ion_id) * fraction_events as f64;
+
+                        let mz_spectrum = if options.mz_noise_fragment {
+                            add_mz_noise_annotated(
+                                scaled_spec,
+                                options.uniform,
+                                options.fragment_noise_ppm,
+                                options.right_drag,
+                                rng.as_mut(),
+                            )
                         } else {
                             scaled_spec
                         };
@@ -773,10 +1075,10 @@ impl TimsTofSyntheticsFrameBuilderDIA {
                             *self
                                 .precursor_frame_builder
                                 .scan_to_mobility
-                                .get(&scan)
+                                .get(scan)
                                 .unwrap() as f64,
                             ms_type.clone(),
-                            vec![0; mz_spectrum.mz.len()],
+                            self.precursor_frame_builder.tof_calibration.mz_to_tof_unsigned(&mz_spectrum.mz),
                             mz_spectrum,
                         ));
                     }
@@ -805,14 +1107,14 @@ impl TimsTofSyntheticsFrameBuilderDIA {
         let tims_frame = TimsFrameAnnotated::from_tims_spectra_annotated(tims_spectra);
 
         tims_frame.filter_ranged(
-            mz_min.unwrap_or(100.0),
-            mz_max.unwrap_or(1700.0),
+            options.mz_min.unwrap_or(100.0),
+            options.mz_max.unwrap_or(1700.0),
             0.0,
             10.0,
             0,
             1000,
-            intensity_min.unwrap_or(1.0),
-            1e9,
+            options.intensity_min.unwrap_or(1.0),
+            options.intensity_max.unwrap_or(1e9),
         )
     }
 
@@ -946,10 +1248,7 @@ impl TimsTofSyntheticsFrameBuilderDIA {
         charge: Vec<i8>,
         num_threads: usize,
     ) -> Vec<(usize, usize)> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let thread_pool = parallel::pool(num_threads);
         let result: Vec<(usize, usize)> = thread_pool.install(|| {
             peptide_ids
                 .par_iter()
@@ -962,9 +1261,173 @@ impl TimsTofSyntheticsFrameBuilderDIA {
     }
 }
 
+/// Split built frames from the errors of frames that could not be built, the errors keep the frame order
+fn collect_frames<T>(
+    results: Vec<Result<T, FrameBuildError>>,
+) -> Result<Vec<T>, Vec<FrameBuildError>> {
+    let mut frames = Vec::with_capacity(results.len());
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(frame) => frames.push(frame),
+            Err(error) => errors.push(error),
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(frames),
+        false => {
+            errors.sort_by_key(|e| e.frame_id);
+            Err(errors)
+        }
+    }
+}
+
 impl TimsTofCollisionEnergy for TimsTofSyntheticsFrameBuilderDIA {
     fn get_collision_energy(&self, frame_id: i32, scan_id: i32) -> f64 {
         self.fragmentation_settings
             .get_collision_energy(frame_id, scan_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::assembler::FrameAssembly;
+    use crate::sim::fixtures::{insert_dia_run, SyntheticsFile};
+    use crate::sim::utility::IntensityQuantization;
+
+    /// synthetics database file with precursor frame 1 and fragment frame 2, see `insert_dia_run`
+    fn fixture_database(name: &str) -> SyntheticsFile {
+        let file = SyntheticsFile::new(name);
+        insert_dia_run(&file.handle());
+        file
+    }
+
+    #[test]
+    fn test_build_frames_by_ms_level() {
+        let file = fixture_database("dia");
+
+        let options = FrameBuildOptions { seed: Some(1), ..Default::default() };
+        let build_precursor = |builder: &TimsTofSyntheticsFrameBuilderDIA| {
+            builder.build_precursor_frames_only(vec![1, 2], 1, &options)
+        };
+        let build_fragment = |builder: &TimsTofSyntheticsFrameBuilderDIA, fragmentation: bool| {
+            builder.build_fragment_frames_only(vec![1, 2], 1, &FrameBuildOptions { fragmentation, ..options })
+        };
+        let frame_ids = |frames: Vec<TimsFrame>| frames.iter().map(|f| f.frame_id).collect::<Vec<_>>();
+
+        let full = TimsTofSyntheticsFrameBuilderDIA::new(&file.path, false, 1, None, None).unwrap();
+        assert_eq!(full.mode, FrameBuilderMode::Full);
+        assert_eq!(frame_ids(build_precursor(&full).unwrap()), vec![1]);
+        assert_eq!(frame_ids(build_fragment(&full, true).unwrap()), vec![2]);
+
+        let ms1 = TimsTofSyntheticsFrameBuilderDIA::new_ms1_only(&file.path, 1, None).unwrap();
+        assert_eq!(ms1.mode, FrameBuilderMode::Ms1);
+        assert!(ms1.fragment_ions.is_none());
+        let precursor_frames = build_precursor(&ms1).unwrap();
+        assert!(!precursor_frames[0].ims_frame.mz.is_empty());
+        assert_eq!(precursor_frames[0].ims_frame.mz, build_precursor(&full).unwrap()[0].ims_frame.mz);
+
+        // fragment frames need the fragment ions, unless they are only quadrupole filtered
+        assert_eq!(
+            build_fragment(&ms1, true).unwrap_err(),
+            vec![FrameBuildError { frame_id: 2, missing: MissingKind::FragmentIons }]
+        );
+        assert_eq!(frame_ids(build_fragment(&ms1, false).unwrap()), vec![2]);
+
+        // fragment frames are the same with both ways of assembling them, both are built by the same builder
+        // as fragment isotope patterns can differ slightly between builders
+        let mut full = full;
+        let assembled = build_fragment(&full, true).unwrap();
+        full.precursor_frame_builder.frame_assembly = FrameAssembly::Spectra;
+        let expected = build_fragment(&full, true).unwrap();
+        assert!(!assembled[0].ims_frame.mz.is_empty());
+        assert_eq!(assembled[0].scan, expected[0].scan);
+        assert_eq!(assembled[0].tof, expected[0].tof);
+        assert_eq!(assembled[0].ims_frame.mz, expected[0].ims_frame.mz);
+        assert_eq!(assembled[0].ims_frame.intensity, expected[0].ims_frame.intensity);
+    }
+
+    #[test]
+    fn test_window_group_lookup() {
+        let file = fixture_database("dia_window_group");
+
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(&file.path).unwrap();
+        assert_eq!(handle.frames_for_window_group(1).unwrap(), vec![2]);
+        assert!(handle.frames_for_window_group(7).unwrap().is_empty());
+        assert_eq!(handle.window_group_for_frame(2).unwrap(), Some(1));
+        assert_eq!(handle.window_group_for_frame(1).unwrap(), None);
+
+        let builder = TimsTofSyntheticsFrameBuilderDIA::new(&file.path, false, 1, None, None).unwrap();
+        let build = |window_group: u32| {
+            builder.build_window_group(window_group, 1, &FrameBuildOptions { seed: Some(1), ..Default::default() })
+        };
+        let frames = build(1).unwrap();
+        assert_eq!(frames.iter().map(|f| f.frame_id).collect::<Vec<_>>(), vec![2]);
+        assert!(build(7).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_annotated_frames_use_build_options() {
+        let file = fixture_database("dia_annotated");
+        let builder = TimsTofSyntheticsFrameBuilderDIA::new(&file.path, true, 1, None, None).unwrap();
+
+        let build = |frame_id: u32, options: FrameBuildOptions| builder.build_frame_annotated(frame_id, &options).unwrap();
+        let unquantized = FrameBuildOptions { quantization: IntensityQuantization::None, ..Default::default() };
+
+        for frame_id in [1, 2] {
+            // intensities are quantized after filtering, the peaks stay the same
+            let float = build(frame_id, unquantized);
+            let floor = build(frame_id, FrameBuildOptions { quantization: IntensityQuantization::Floor, ..unquantized });
+            assert!(!float.mz.is_empty());
+            assert_eq!(floor.mz, float.mz);
+            assert_eq!(floor.intensity, float.intensity.iter().map(|i| i.floor()).collect::<Vec<_>>());
+
+            // seeded noise and stochastic rounding are reproducible
+            let noisy = FrameBuildOptions {
+                mz_noise_precursor: true,
+                precursor_noise_ppm: 10.0,
+                mz_noise_fragment: true,
+                fragment_noise_ppm: 10.0,
+                seed: Some(7),
+                quantization: IntensityQuantization::Stochastic,
+                ..Default::default()
+            };
+            let first = build(frame_id, noisy);
+            assert_eq!(first.mz, build(frame_id, noisy).mz);
+            assert_eq!(first.intensity, build(frame_id, noisy).intensity);
+            assert_ne!(first.mz, float.mz);
+        }
+
+        let limited = build(1, FrameBuildOptions { mz_max: Some(465.0), ..unquantized });
+        assert!(!limited.mz.is_empty());
+        assert!(limited.mz.iter().all(|&mz| mz <= 465.0));
+        assert!(limited.mz.len() < build(1, unquantized).mz.len());
+    }
+
+    #[test]
+    fn test_annotated_fragment_frames_need_annotations() {
+        let file = fixture_database("dia_ms1_annotated");
+
+        let options = FrameBuildOptions::default();
+        let missing = FrameBuildError { frame_id: 2, missing: MissingKind::FragmentIons };
+        for builder in [
+            TimsTofSyntheticsFrameBuilderDIA::new_ms1_only(&file.path, 1, None).unwrap(),
+            TimsTofSyntheticsFrameBuilderDIA::new(&file.path, false, 1, None, None).unwrap(),
+        ] {
+            assert!(builder.build_frame_annotated(1, &options).is_ok());
+            assert_eq!(builder.build_frame_annotated(2, &options).unwrap_err(), missing);
+            assert_eq!(builder.build_frames_annotated(vec![1, 2], 1, &options).unwrap_err(), vec![missing]);
+            // without fragmentation the precursor frame is only quadrupole filtered
+            let unfragmented = FrameBuildOptions { fragmentation: false, ..options };
+            assert!(builder.build_frame_annotated(2, &unfragmented).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_frame_builder_mode_from_name() {
+        assert_eq!(FrameBuilderMode::from_name("MS1"), Ok(FrameBuilderMode::Ms1));
+        assert_eq!(FrameBuilderMode::from_name("full"), Ok(FrameBuilderMode::Full));
+        assert!(FrameBuilderMode::from_name("ms3").is_err());
+    }
+}
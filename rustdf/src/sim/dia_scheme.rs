@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::sim::containers::{FrameToWindowGroupSim, WindowGroupSettingsSim};
+
+/// Collision energy of a generated window, rising linearly with its isolation m/z as larger precursors need
+/// more energy to fragment
+pub fn default_collision_energy(isolation_mz: f64) -> f32 {
+    (5.0 + 0.045 * isolation_mz) as f32
+}
+
+/// Generate a diaPASEF window scheme
+///
+/// The m/z range is tiled by windows of `window_width` Da, consecutive windows share `overlap` Da. Windows are
+/// distributed over window groups (one per fragment frame) in turn, such that every group isolates
+/// `windows_per_frame` windows spread over the whole m/z range, each along its own scan range. As peptides
+/// of higher m/z tend to have a higher inverse mobility and therefore lower scan numbers, the windows of higher
+/// m/z are isolated at lower scans.
+///
+/// With `mobility_mz_correlation`, the scan ranges follow a diagonal from (mz_min, last scan) to (mz_max, scan 0)
+/// and the scans between two windows are split where the diagonal crosses the m/z between their centers.
+/// Without, every window of a group gets a scan range of the same size.
+///
+/// # Arguments
+///
+/// * `mz_min` - lower bound of the first window
+/// * `mz_max` - m/z covered by the last window
+/// * `window_width` - isolation width of the windows in Da
+/// * `overlap` - m/z shared by consecutive windows in Da, smaller than `window_width`
+/// * `scan_count` - number of scans of a frame, scans are numbered from 0
+/// * `windows_per_frame` - number of windows isolated in one fragment frame
+/// * `mobility_mz_correlation` - place the scan ranges along the m/z-mobility diagonal
+///
+/// # Returns
+///
+/// * The windows of all groups, with groups numbered from 1, and the window group of the fragment frames of one
+///   cycle, frame 1 being the precursor frame, or an error if the parameters do not describe a scheme
+///
+/// # Examples
+///
+/// ```
+/// use rustdf::sim::dia_scheme::{generate_dia_scheme, validate_scheme};
+///
+/// let (settings, cycle) = generate_dia_scheme(400.0, 1000.0, 25.0, 1.0, 918, 3, true).unwrap();
+/// assert_eq!(settings.len(), 25);
+/// assert_eq!(cycle.len(), 9);
+/// assert_eq!((cycle[0].frame_id, cycle[0].window_group), (2, 1));
+/// assert!(validate_scheme(&settings).is_empty());
+/// ```
+pub fn generate_dia_scheme(
+    mz_min: f64,
+    mz_max: f64,
+    window_width: f64,
+    overlap: f64,
+    scan_count: u32,
+    windows_per_frame: usize,
+    mobility_mz_correlation: bool,
+) -> Result<(Vec<WindowGroupSettingsSim>, Vec<FrameToWindowGroupSim>), String> {
+    if !(mz_min < mz_max && mz_min.is_finite() && mz_max.is_finite()) {
+        return Err(format!("invalid m/z range ({}, {})", mz_min, mz_max));
+    }
+    if !(window_width > 0.0 && overlap >= 0.0 && overlap < window_width) {
+        return Err(format!("windows need a positive width larger than their overlap, got width {} and overlap {}", window_width, overlap));
+    }
+    if windows_per_frame == 0 || windows_per_frame > scan_count as usize {
+        return Err(format!("expected 1 to {} windows per frame, one scan each at least, got {}", scan_count, windows_per_frame));
+    }
+
+    let step = window_width - overlap;
+    let window_count = ((mz_max - mz_min - overlap) / step).ceil().max(1.0) as usize;
+    let group_count = window_count.div_ceil(windows_per_frame);
+    let last_scan = (scan_count - 1) as f64;
+    let diagonal_scan = |mz: f64| (last_scan * (mz_max - mz) / (mz_max - mz_min)).round().clamp(0.0, last_scan) as u32;
+
+    let mut settings = Vec::with_capacity(window_count);
+    for group in 0..group_count {
+        let centers: Vec<f64> = (group..window_count).step_by(group_count)
+            .map(|window| mz_min + window as f64 * step + window_width / 2.0)
+            .collect();
+        let k = centers.len() as u32;
+
+        // scan ranges from the window of the highest m/z at scan 0 to the window of the lowest m/z at the last scan
+        let mut boundaries: Vec<u32> = (1..k).map(|slot| {
+            if mobility_mz_correlation {
+                let (lower, upper) = (centers[(k - 1 - slot) as usize], centers[(k - slot) as usize]);
+                diagonal_scan((lower + upper) / 2.0)
+            } else {
+                slot * scan_count / k
+            }
+        }).collect();
+        boundaries.insert(0, 0);
+        boundaries.push(scan_count);
+        // every window keeps at least one scan, also where windows beyond mz_max meet at scan 0
+        for position in 1..k as usize {
+            boundaries[position] = boundaries[position].max(boundaries[position - 1] + 1).min(scan_count - (k - position as u32));
+        }
+
+        for (slot, center) in centers.iter().enumerate() {
+            let position = k as usize - 1 - slot;
+            let (scan_start, scan_end) = (boundaries[position], boundaries[position + 1] - 1);
+            settings.push(WindowGroupSettingsSim::new(
+                group as u32 + 1,
+                scan_start,
+                scan_end,
+                *center as f32,
+                window_width as f32,
+                default_collision_energy(*center),
+            ));
+        }
+    }
+
+    let cycle = (1..=group_count as u32)
+        .map(|group| FrameToWindowGroupSim::new(group + 1, group))
+        .collect();
+    Ok((settings, cycle))
+}
+
+/// Problem of a DIA window scheme found by `validate_scheme`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemeIssue {
+    /// the scheme has no windows
+    NoWindows,
+    /// window at this index ends before it starts or has no isolation width
+    InvalidWindow { index: usize },
+    /// two windows of a group share scans, the quadrupole can only isolate one of them at a time
+    ScanOverlap { window_group: u32, first: usize, second: usize },
+    /// scans within the scan range of the scheme that no window of the group covers
+    UncoveredScans { window_group: u32, scan_start: u32, scan_end: u32 },
+    /// m/z between the windows that no window of any group isolates
+    Gap { mz_start: f64, mz_end: f64 },
+}
+
+impl fmt::Display for SchemeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemeIssue::NoWindows => write!(f, "the scheme has no windows"),
+            SchemeIssue::InvalidWindow { index } => write!(
+                f, "window {} ends before it starts or has no isolation width", index),
+            SchemeIssue::ScanOverlap { window_group, first, second } => write!(
+                f, "windows {} and {} of window group {} share scans", first, second, window_group),
+            SchemeIssue::UncoveredScans { window_group, scan_start, scan_end } => write!(
+                f, "scans {} to {} are not covered by any window of window group {}", scan_start, scan_end, window_group),
+            SchemeIssue::Gap { mz_start, mz_end } => write!(
+                f, "m/z {:.3} to {:.3} is not isolated by any window", mz_start, mz_end),
+        }
+    }
+}
+
+impl std::error::Error for SchemeIssue {}
+
+/// Check a DIA window scheme for gaps, windows sharing scans and scans a window group does not cover
+///
+/// # Arguments
+///
+/// * `settings` - windows of all window groups
+///
+/// # Returns
+///
+/// * `Vec<SchemeIssue>` - all problems found, empty for a valid scheme, indices refer to `settings`
+///
+/// # Examples
+///
+/// ```
+/// use rustdf::sim::containers::WindowGroupSettingsSim;
+/// use rustdf::sim::dia_scheme::{validate_scheme, SchemeIssue};
+///
+/// let settings = vec![
+///     WindowGroupSettingsSim::new(1, 0, 49, 525.0, 50.0, 30.0),
+///     WindowGroupSettingsSim::new(1, 40, 99, 425.0, 25.0, 25.0),
+/// ];
+/// assert_eq!(validate_scheme(&settings), vec![
+///     SchemeIssue::ScanOverlap { window_group: 1, first: 0, second: 1 },
+///     SchemeIssue::Gap { mz_start: 437.5, mz_end: 500.0 },
+/// ]);
+/// ```
+pub fn validate_scheme(settings: &[WindowGroupSettingsSim]) -> Vec<SchemeIssue> {
+    if settings.is_empty() {
+        return vec![SchemeIssue::NoWindows];
+    }
+    let mut issues = Vec::new();
+    let valid: Vec<usize> = (0..settings.len()).filter(|&i| {
+        let window = &settings[i];
+        let is_valid = window.scan_start <= window.scan_end && window.isolation_width > 0.0 && window.isolation_width.is_finite();
+        if !is_valid {
+            issues.push(SchemeIssue::InvalidWindow { index: i });
+        }
+        is_valid
+    }).collect();
+    if valid.is_empty() {
+        return issues;
+    }
+
+    let scheme_start = valid.iter().map(|&i| settings[i].scan_start).min().unwrap();
+    let scheme_end = valid.iter().map(|&i| settings[i].scan_end).max().unwrap();
+    let mut groups: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+    for &i in &valid {
+        groups.entry(settings[i].window_group).or_default().push(i);
+    }
+    for (window_group, mut windows) in groups {
+        windows.sort_by_key(|&i| (settings[i].scan_start, settings[i].scan_end));
+        let mut covered_until = scheme_start;
+        // window reaching the highest scan so far, the one later windows may overlap with
+        let mut reaching: Option<usize> = None;
+        for i in windows {
+            let window = &settings[i];
+            if let Some(previous) = reaching {
+                if window.scan_start <= settings[previous].scan_end {
+                    issues.push(SchemeIssue::ScanOverlap { window_group, first: previous.min(i), second: previous.max(i) });
+                }
+            }
+            if window.scan_start > covered_until {
+                issues.push(SchemeIssue::UncoveredScans { window_group, scan_start: covered_until, scan_end: window.scan_start - 1 });
+            }
+            if window.scan_end + 1 > covered_until {
+                covered_until = window.scan_end + 1;
+                reaching = Some(i);
+            }
+        }
+        if covered_until <= scheme_end {
+            issues.push(SchemeIssue::UncoveredScans { window_group, scan_start: covered_until, scan_end: scheme_end });
+        }
+    }
+
+    let mut ranges: Vec<(f64, f64)> = valid.iter().map(|&i| {
+        let (mz, width) = (settings[i].isolation_mz as f64, settings[i].isolation_width as f64);
+        (mz - width / 2.0, mz + width / 2.0)
+    }).collect();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut covered_until = ranges[0].1;
+    for (start, end) in ranges.into_iter().skip(1) {
+        if start > covered_until {
+            issues.push(SchemeIssue::Gap { mz_start: covered_until, mz_end: start });
+        }
+        covered_until = covered_until.max(end);
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_schemes_are_valid() {
+        for mobility_mz_correlation in [false, true] {
+            for (width, overlap, windows_per_frame) in [(25.0, 1.0, 2), (26.0, 0.0, 4), (12.5, 0.5, 7)] {
+                let (settings, cycle) = generate_dia_scheme(350.0, 1250.0, width, overlap, 918, windows_per_frame, mobility_mz_correlation).unwrap();
+                assert!(validate_scheme(&settings).is_empty(), "{width} {overlap} {windows_per_frame}");
+
+                // the windows tile the m/z range and every group uses the scans of a frame once
+                let first = settings.iter().map(|s| s.isolation_mz - s.isolation_width / 2.0).fold(f32::MAX, f32::min);
+                let last = settings.iter().map(|s| s.isolation_mz + s.isolation_width / 2.0).fold(f32::MIN, f32::max);
+                assert_eq!(first, 350.0);
+                assert!(last >= 1250.0);
+                for group in cycle.iter().map(|entry| entry.window_group) {
+                    let windows: Vec<&WindowGroupSettingsSim> = settings.iter().filter(|s| s.window_group == group).collect();
+                    assert!(windows.len() <= windows_per_frame);
+                    assert_eq!(windows.iter().map(|s| s.scan_end - s.scan_start + 1).sum::<u32>(), 918);
+                    // higher m/z at lower scans
+                    for pair in windows.windows(2) {
+                        assert!(pair[0].isolation_mz < pair[1].isolation_mz && pair[0].scan_start > pair[1].scan_end);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagonal_scheme_follows_mz() {
+        let (settings, _) = generate_dia_scheme(400.0, 1200.0, 50.0, 0.0, 800, 4, true).unwrap();
+        // every window is isolated around the scan the diagonal assigns to its m/z
+        for window in &settings {
+            let diagonal = 799.0 * (1200.0 - window.isolation_mz as f64) / 800.0;
+            assert!(window.scan_start as f64 <= diagonal + 1.0 && diagonal <= window.scan_end as f64 + 1.0);
+        }
+
+        let (plain, _) = generate_dia_scheme(400.0, 1200.0, 50.0, 0.0, 800, 4, false).unwrap();
+        assert!(plain.iter().all(|s| s.scan_end - s.scan_start + 1 == 200));
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(generate_dia_scheme(1000.0, 400.0, 25.0, 1.0, 918, 3, true).is_err());
+        assert!(generate_dia_scheme(400.0, 1000.0, 25.0, 25.0, 918, 3, true).is_err());
+        assert!(generate_dia_scheme(400.0, 1000.0, 25.0, 1.0, 918, 0, true).is_err());
+        assert!(generate_dia_scheme(400.0, 1000.0, 25.0, 1.0, 2, 3, false).is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_issues() {
+        assert_eq!(validate_scheme(&[]), vec![SchemeIssue::NoWindows]);
+        let settings = vec![
+            WindowGroupSettingsSim::new(1, 0, 49, 425.0, 50.0, 30.0),
+            WindowGroupSettingsSim::new(1, 50, 99, 475.0, 50.0, 30.0),
+            WindowGroupSettingsSim::new(2, 20, 99, 450.0, 50.0, 30.0),
+            WindowGroupSettingsSim::new(2, 80, 10, 450.0, 50.0, 30.0),
+        ];
+        assert_eq!(validate_scheme(&settings), vec![
+            SchemeIssue::InvalidWindow { index: 3 },
+            SchemeIssue::UncoveredScans { window_group: 2, scan_start: 0, scan_end: 19 },
+        ]);
+        assert_eq!(SchemeIssue::InvalidWindow { index: 3 }.to_string(), "window 3 ends before it starts or has no isolation width");
+    }
+}
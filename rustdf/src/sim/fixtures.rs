@@ -0,0 +1,81 @@
+//! Synthetics databases for the tests of the simulation modules, all created with `create_schema`
+
+use std::path::PathBuf;
+
+use mscore::data::spectrum::MzSpectrum;
+use rusqlite::Connection;
+
+use crate::sim::containers::{
+    FragmentIonSim, FrameToWindowGroupSim, FramesSim, IonSim, PeptidesSim, ScansSim, WindowGroupSettingsSim,
+};
+use crate::sim::handle::TimsTofSyntheticsDataHandle;
+
+/// In-memory synthetics database with empty tables
+pub(crate) fn in_memory_handle() -> TimsTofSyntheticsDataHandle {
+    let handle = TimsTofSyntheticsDataHandle { connection: Connection::open_in_memory().unwrap() };
+    handle.create_schema().unwrap();
+    handle
+}
+
+/// Synthetics database file in the temp dir with empty tables, removed together with its WAL files on drop
+pub(crate) struct SyntheticsFile {
+    pub path: PathBuf,
+}
+
+impl SyntheticsFile {
+    /// `name` keeps the files of tests running in parallel apart
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rustdf_{}_{}.db", name, std::process::id()));
+        let file = SyntheticsFile { path };
+        file.remove();
+        file.handle().create_schema().unwrap();
+        file
+    }
+
+    pub fn handle(&self) -> TimsTofSyntheticsDataHandle {
+        TimsTofSyntheticsDataHandle::new(&self.path).unwrap()
+    }
+
+    fn remove(&self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
+}
+
+impl Drop for SyntheticsFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// PEPTIDEK of protein P1 eluting over `frames` with `abundance`
+pub(crate) fn peptide(peptide_id: u32, events: f32, frames: Vec<u32>, abundance: Vec<f32>) -> PeptidesSim {
+    let (frame_start, frame_end) = (frames.first().copied().unwrap_or(0), frames.last().copied().unwrap_or(0));
+    PeptidesSim::new(
+        0, peptide_id, "PEPTIDEK".to_string(), "P1".to_string(), false, 0, Some(true), None,
+        927.45, 10.0, events, frame_start, frame_end, frames, abundance,
+    )
+}
+
+/// Ion of PEPTIDEK with a single peak at `mz` occurring in `scans` with `abundance`
+pub(crate) fn ion(ion_id: u32, peptide_id: u32, charge: i8, mz: f64, mobility: f32, scans: Vec<u32>, abundance: Vec<f32>) -> IonSim {
+    IonSim::new(
+        ion_id, peptide_id, "PEPTIDEK".to_string(), charge, 1.0, mobility,
+        MzSpectrum::new(vec![mz], vec![1.0]), scans, abundance,
+    )
+}
+
+/// DIA run with precursor frame 1 and fragment frame 2 in window group 1, both covering ion 1 of PEPTIDEK
+/// in scans 1 to 3
+pub(crate) fn insert_dia_run(handle: &TimsTofSyntheticsDataHandle) {
+    handle.insert_frames(&[FramesSim::new(1, 0.5, 0), FramesSim::new(2, 0.6, 9)]).unwrap();
+    handle.insert_scans(&[ScansSim::new(1, 1.1), ScansSim::new(2, 1.0), ScansSim::new(3, 0.9)]).unwrap();
+    handle.insert_peptides(&[peptide(1, 1000.0, vec![1, 2], vec![0.5, 0.5])]).unwrap();
+    handle.insert_ions(&[ion(1, 1, 2, 464.73, 1.0, vec![1, 2], vec![0.5, 0.5])]).unwrap();
+    handle.insert_fragment_ions(&[FragmentIonSim::new(1, 1, 30.0, 2, vec![2, 30], vec![1.0, 0.2])]).unwrap();
+    handle
+        .insert_window_group_settings(&[WindowGroupSettingsSim::new(1, 1, 3, 464.73, 25.0, 30.0)])
+        .unwrap();
+    handle.insert_frame_to_window_group(&[FrameToWindowGroupSim::new(2, 1)]).unwrap();
+}
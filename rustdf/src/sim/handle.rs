@@ -1,17 +1,451 @@
+use crate::sim::calibration::TofCalibration;
+use crate::sim::dia_scheme::validate_scheme;
 use crate::sim::containers::{
-    FragmentIonSim, FrameToWindowGroupSim, FramesSim, IonSim, PeptidesSim, ScansSim,
+    DDAPrecursorSim, FragmentIonSim, FrameToWindowGroupSim, FramesSim, IonSim, PeptidesSim, ScansSim,
     SignalDistribution, WindowGroupSettingsSim,
 };
+use crate::sim::library::{LibraryPrecursor, SpectralLibraryFormat, SpectralLibraryWriter};
+use crate::sim::mobility::simulate_scan_distributions;
+use crate::sim::storage::{
+    format_key, json_to_binary, ColumnCodec, ColumnDecodeError, StorageFormat, META_TABLE,
+};
+use crate::sim::utility::quantize_collision_energy;
+use mscore::algorithm::mobility::{predict_inverse_mobilities_par, MobilityModel};
+use mscore::algorithm::utility::{
+    calculate_frame_abundances_emg_par, calculate_frame_occurrences_emg_par,
+};
+use mscore::chemistry::constants::MASS_PROTON;
 use mscore::data::peptide::{FragmentType, PeptideProductIonSeriesCollection, PeptideSequence};
 use mscore::data::spectrum::{MsType, MzSpectrum};
 use mscore::simulation::annotation::MzSpectrumAnnotated;
-use mscore::timstof::collision::{TimsTofCollisionEnergy, TimsTofCollisionEnergyDIA};
-use mscore::timstof::quadrupole::{IonTransmission, PASEFMeta, TimsTransmissionDDA, TimsTransmissionDIA};
+use mscore::timstof::collision::{CollisionEnergyMobilityRamp, CollisionEnergySource, TimsTofCollisionEnergy, TimsTofCollisionEnergyDIA};
+use mscore::timstof::quadrupole::{
+    IonTransmission, PASEFMeta, TimsTransmissionDDA, TimsTransmissionDIA, TransmissionProfile,
+};
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
-use rusqlite::Connection;
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use mscore::parallel;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, ToSql};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Per peptide: relative abundance, scan occurrences, scan abundances, charge and spectrum of every ion
+pub type PeptideToIons = BTreeMap<
+    u32,
+    (
+        Vec<f32>,
+        Vec<Vec<u32>>,
+        Vec<Vec<f32>>,
+        Vec<i8>,
+        Vec<MzSpectrum>,
+    ),
+>;
+
+/// Fragment ion series and their isotopic spectra by peptide id, charge and quantized collision energy,
+/// see `quantize_collision_energy`
+pub type FragmentIonMap<S> = BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<S>)>;
+
+/// Build a map per chunk of `items` in parallel and merge the partial maps in chunk order
+fn merge_chunked<T: Sync, V: Send>(
+    items: &[T],
+    num_threads: usize,
+    build: impl Fn(&[T]) -> BTreeMap<u32, V> + Sync,
+    merge: impl Fn(&mut V, V),
+) -> BTreeMap<u32, V> {
+    let chunk_size = items.len().div_ceil(num_threads.max(1)).max(1);
+    let thread_pool = parallel::pool(num_threads);
+    let partial_maps: Vec<BTreeMap<u32, V>> =
+        thread_pool.install(|| items.par_chunks(chunk_size).map(&build).collect());
+
+    let mut merged = BTreeMap::new();
+    for partial in partial_maps {
+        for (key, value) in partial {
+            match merged.entry(key) {
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+                std::collections::btree_map::Entry::Occupied(mut entry) => {
+                    merge(entry.get_mut(), value)
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Strategy to handle duplicate peptide rows, i.e. rows with the same sequence and decoy flag but different ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePeptideStrategy {
+    /// keep the first row, drop all later duplicates
+    KeepFirst,
+    /// merge duplicates into the first row, summing events and aligning frame abundances by frame id
+    SumEvents,
+    /// fail if any duplicate is found
+    #[default]
+    Error,
+}
+
+impl FromStr for DuplicatePeptideStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep_first" | "keepfirst" => Ok(DuplicatePeptideStrategy::KeepFirst),
+            "sum_events" | "sumevents" => Ok(DuplicatePeptideStrategy::SumEvents),
+            "error" => Ok(DuplicatePeptideStrategy::Error),
+            _ => Err(format!("Unknown duplicate peptide strategy: {}", s)),
+        }
+    }
+}
+
+/// Two peptide rows with the same sequence and decoy flag, see `DuplicatePeptideStrategy::Error`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePeptideError {
+    pub sequence: String,
+    pub decoy: bool,
+    /// id of the first row and of the duplicate
+    pub peptide_ids: (u32, u32),
+}
+
+impl Display for DuplicatePeptideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Duplicate peptide {} (decoy: {}) with ids {} and {}",
+            self.sequence, self.decoy, self.peptide_ids.0, self.peptide_ids.1
+        )
+    }
+}
+
+impl std::error::Error for DuplicatePeptideError {}
+
+/// Source of the width or skewness of the elution peaks in `TimsTofSyntheticsDataHandle::populate_elution_profiles`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ElutionParameter {
+    /// the per peptide value of the `rt_sigma` or `rt_lambda` column of the peptides table
+    Stored,
+    /// the same value for every peptide
+    Constant(f64),
+}
+
+/// A precursor ion transmitted into a fragment frame, there is one per frame, ion and collision energy
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransmittedIon {
+    pub frame_id: u32,
+    pub peptide_id: u32,
+    pub ion_id: u32,
+    pub sequence: String,
+    pub charge: i8,
+    /// collision energy in eV, quantized like the fragment ion table
+    pub collision_energy: f32,
+    /// first and last scan the ion was transmitted in
+    pub scan_start: u32,
+    pub scan_end: u32,
+    pub mobility: f32,
+    pub precursor_mz: f64,
+    /// fraction of the isotope envelope intensity that was transmitted, averaged over the transmitted
+    /// scans weighted by the scan abundance of the ion
+    pub transmitted_fraction: f64,
+}
+
+/// Columns of the simulation tables by position, `None` where imspy versions use different names,
+/// rows are read by position, so only the names of the other columns are checked
+const EXPECTED_SCHEMA: &[(&str, bool, &[Option<&str>])] = &[
+    (
+        "frames",
+        true,
+        &[Some("frame_id"), Some("time"), Some("ms_type")],
+    ),
+    ("scans", true, &[Some("scan"), Some("mobility")]),
+    (
+        "peptides",
+        true,
+        &[
+            Some("protein_id"),
+            Some("peptide_id"),
+            Some("sequence"),
+            None,
+            Some("decoy"),
+            Some("missed_cleavages"),
+            Some("n_term"),
+            Some("c_term"),
+            None,
+            None,
+            Some("events"),
+            None,
+            None,
+            None,
+            None,
+            Some("frame_occurrence"),
+            Some("frame_abundance"),
+        ],
+    ),
+    (
+        "ions",
+        true,
+        &[
+            Some("ion_id"),
+            Some("peptide_id"),
+            Some("sequence"),
+            Some("charge"),
+            Some("mz"),
+            Some("relative_abundance"),
+            Some("inv_mobility_gru_predictor"),
+            Some("inv_mobility_gru_predictor_std"),
+            Some("simulated_spectrum"),
+            Some("scan_occurrence"),
+            Some("scan_abundance"),
+        ],
+    ),
+    // only written for fragmented acquisitions
+    (
+        "fragment_ions",
+        false,
+        &[
+            Some("peptide_id"),
+            Some("ion_id"),
+            Some("collision_energy"),
+            Some("charge"),
+            Some("indices"),
+            Some("values"),
+        ],
+    ),
+    // only written if the simulation uses a calibration other than the default
+    (
+        "tof_calibration",
+        false,
+        &[Some("intercept"), Some("slope"), Some("curvature"), Some("tof_max_index")],
+    ),
+    // only written if collision energies follow the ion mobility instead of the window groups
+    (
+        "collision_energy_ramp",
+        false,
+        &[Some("inv_mobility"), Some("collision_energy")],
+    ),
+];
+
+/// Tables created by `TimsTofSyntheticsDataHandle::create_schema`, named as written by imspy
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS frames (frame_id INTEGER, time REAL, ms_type INTEGER);
+    CREATE TABLE IF NOT EXISTS scans (scan INTEGER, mobility REAL);
+    CREATE TABLE IF NOT EXISTS peptides (
+        protein_id INTEGER, peptide_id INTEGER, sequence TEXT, protein TEXT, decoy INTEGER,
+        missed_cleavages INTEGER, n_term INTEGER, c_term INTEGER, \"monoisotopic-mass\" REAL,
+        retention_time_gru_predictor REAL, events REAL, rt_sigma REAL, rt_lambda REAL,
+        frame_occurrence_start INTEGER, frame_occurrence_end INTEGER, frame_occurrence TEXT, frame_abundance TEXT
+    );
+    CREATE TABLE IF NOT EXISTS ions (
+        ion_id INTEGER, peptide_id INTEGER, sequence TEXT, charge INTEGER, mz REAL,
+        relative_abundance REAL, inv_mobility_gru_predictor REAL, inv_mobility_gru_predictor_std REAL,
+        simulated_spectrum TEXT, scan_occurrence TEXT, scan_abundance TEXT
+    );
+    CREATE TABLE IF NOT EXISTS fragment_ions (
+        peptide_id INTEGER, ion_id INTEGER, collision_energy REAL, charge INTEGER, indices TEXT, \"values\" TEXT
+    );
+    CREATE TABLE IF NOT EXISTS dia_ms_ms_windows (
+        window_group INTEGER, scan_start INTEGER, scan_end INTEGER, isolation_mz REAL,
+        isolation_width REAL, collision_energy REAL
+    );
+    CREATE TABLE IF NOT EXISTS dia_ms_ms_info (frame INTEGER, window_group INTEGER);
+";
+
+/// Converts the JSON of a column to its binary encoding
+type JsonToBinary = fn(&[u8]) -> Result<Vec<u8>, ColumnDecodeError>;
+
+/// List and spectrum columns that `migrate_to_binary` stores as binary, tables need an `ion_id` column
+const BINARY_COLUMNS: &[(&str, &[(&str, JsonToBinary)])] = &[
+    (
+        "ions",
+        &[
+            ("simulated_spectrum", json_to_binary::<MzSpectrum>),
+            ("scan_occurrence", json_to_binary::<Vec<u32>>),
+            ("scan_abundance", json_to_binary::<Vec<f32>>),
+        ],
+    ),
+    (
+        "fragment_ions",
+        &[
+            ("indices", json_to_binary::<Vec<u32>>),
+            ("values", json_to_binary::<Vec<f64>>),
+        ],
+    ),
+];
+
+/// Rows of a table are handed to the parser in chunks of this size
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Bytes of the database file that are memory mapped
+const MMAP_SIZE: i64 = 1 << 30;
+
+/// A column that is missing or has an unexpected name, see `TimsTofSyntheticsDataHandle::validate_schema`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMismatch {
+    pub table: String,
+    pub index: usize,
+    /// `None` if any name is accepted
+    pub expected: Option<String>,
+    /// `None` if the table has fewer columns
+    pub found: Option<String>,
+}
+
+impl Display for ColumnMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected = self.expected.as_deref().unwrap_or("any name");
+        match &self.found {
+            Some(found) => write!(
+                f,
+                "{} column {}: expected {}, found {}",
+                self.table, self.index, expected, found
+            ),
+            None => write!(
+                f,
+                "{} column {} ({}) is missing",
+                self.table, self.index, expected
+            ),
+        }
+    }
+}
+
+/// Tables and columns of a simulation database that do not match what the readers expect
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaReport {
+    pub missing_tables: Vec<String>,
+    pub column_mismatches: Vec<ColumnMismatch>,
+}
+
+impl SchemaReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing_tables.is_empty() && self.column_mismatches.is_empty()
+    }
+}
+
+impl Display for SchemaReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_valid() {
+            return write!(f, "simulation database schema is valid");
+        }
+        write!(f, "invalid simulation database schema")?;
+        if !self.missing_tables.is_empty() {
+            write!(f, ", missing tables: {}", self.missing_tables.join(", "))?;
+        }
+        for mismatch in &self.column_mismatches {
+            write!(f, ", {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaReport {}
+
+/// A list or spectrum column of a simulation table that could not be parsed
+#[derive(Debug)]
+pub struct ColumnParseError {
+    pub table: &'static str,
+    pub column: &'static str,
+    /// id of the peptide or ion the row belongs to
+    pub id: u32,
+    pub source: ColumnDecodeError,
+}
+
+impl Display for ColumnParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "could not parse {}.{} of row with id {}: {}",
+            self.table, self.column, self.id, self.source
+        )
+    }
+}
+
+impl std::error::Error for ColumnParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Errors of reading the tables or collapsing the peptides of a simulation database
+#[derive(Debug)]
+pub enum SyntheticsError {
+    Sql(rusqlite::Error),
+    ColumnParse(ColumnParseError),
+    DuplicatePeptide(DuplicatePeptideError),
+    Schema(SchemaReport),
+}
+
+impl Display for SyntheticsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyntheticsError::Sql(e) => write!(f, "{}", e),
+            SyntheticsError::ColumnParse(e) => write!(f, "{}", e),
+            SyntheticsError::DuplicatePeptide(e) => write!(f, "{}", e),
+            SyntheticsError::Schema(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SyntheticsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SyntheticsError::Sql(e) => Some(e),
+            SyntheticsError::ColumnParse(e) => Some(e),
+            SyntheticsError::DuplicatePeptide(e) => Some(e),
+            SyntheticsError::Schema(e) => Some(e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SyntheticsError {
+    fn from(e: rusqlite::Error) -> Self {
+        SyntheticsError::Sql(e)
+    }
+}
+
+/// Text or blob of column `index`, list and spectrum columns are stored as either
+fn column_bytes(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Vec<u8>> {
+    row.get_ref(index)?
+        .as_bytes()
+        .map(<[u8]>::to_vec)
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Blob, Box::new(e))
+        })
+}
+
+/// Parse a column of a row stored in `format`, errors name table, column and row id
+fn parse_column<T: ColumnCodec>(
+    value: &[u8],
+    format: StorageFormat,
+    table: &'static str,
+    column: &'static str,
+    id: u32,
+) -> Result<T, SyntheticsError> {
+    T::decode(value, format).map_err(|source| {
+        SyntheticsError::ColumnParse(ColumnParseError {
+            table,
+            column,
+            id,
+            source,
+        })
+    })
+}
+
+/// Distributions are written for every id, so all arguments need the same length
+fn check_distribution_lengths(ids: usize, occurrences: usize, abundances: usize) -> rusqlite::Result<()> {
+    match ids == occurrences && ids == abundances {
+        true => Ok(()),
+        false => Err(rusqlite::Error::ToSqlConversionFailure(
+            format!(
+                "expected one distribution per id, got {} occurrences and {} abundances for {} ids",
+                occurrences, abundances, ids
+            )
+            .into(),
+        )),
+    }
+}
 
 #[derive(Debug)]
 pub struct TimsTofSyntheticsDataHandle {
@@ -19,11 +453,454 @@ pub struct TimsTofSyntheticsDataHandle {
 }
 
 impl TimsTofSyntheticsDataHandle {
+    /// Open a simulation database for reading and writing, it is switched to WAL mode, so tables
+    /// can be read while results are written
     pub fn new(path: &Path) -> rusqlite::Result<Self> {
         let connection = Connection::open(path)?;
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Self::set_read_pragmas(&connection)?;
         Ok(Self { connection })
     }
 
+    /// Open a simulation database read-only, as done by the frame builders
+    pub fn new_read_only(path: &Path) -> rusqlite::Result<Self> {
+        let connection = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX
+                | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        Self::set_read_pragmas(&connection)?;
+        Ok(Self { connection })
+    }
+
+    /// Memory map the database and keep temporary b-trees in memory
+    fn set_read_pragmas(connection: &Connection) -> rusqlite::Result<()> {
+        connection
+            .pragma_update_and_check(None, "mmap_size", MMAP_SIZE, |row| row.get::<_, i64>(0))?;
+        connection.pragma_update(None, "temp_store", "MEMORY")?;
+        Ok(())
+    }
+
+    pub fn has_table(&self, table: &str) -> rusqlite::Result<bool> {
+        self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )
+    }
+
+    /// Storage format of the list and spectrum columns of `table`
+    ///
+    /// # Returns
+    ///
+    /// * The format stored in the meta table, `StorageFormat::Json` if there is no entry for `table`
+    ///
+    pub fn storage_format(&self, table: &str) -> rusqlite::Result<StorageFormat> {
+        if !self.has_table(META_TABLE)? {
+            return Ok(StorageFormat::Json);
+        }
+        let value: Option<String> = self
+            .connection
+            .query_row(
+                &format!("SELECT value FROM {} WHERE key = ?1", META_TABLE),
+                [format_key(table)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match value {
+            None => Ok(StorageFormat::Json),
+            Some(value) => value.parse().map_err(|e: String| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+            }),
+        }
+    }
+
+    /// Copy the database to `path_out` with the list and spectrum columns of `ions` and `fragment_ions`
+    /// stored as binary, which is smaller and faster to read, JSON databases can still be read as before
+    ///
+    /// # Arguments
+    ///
+    /// * `path_out` - Path of the new database, must not exist
+    ///
+    /// # Returns
+    ///
+    /// * The number of converted rows, tables that are already binary are copied as they are
+    ///
+    pub fn migrate_to_binary(&self, path_out: &Path) -> Result<usize, SyntheticsError> {
+        if path_out.exists() {
+            return Err(rusqlite::Error::InvalidPath(path_out.to_path_buf()).into());
+        }
+        self.connection
+            .execute("VACUUM INTO ?1", [path_out.to_string_lossy()])?;
+
+        let result = Connection::open(path_out).map_err(SyntheticsError::from).and_then(|mut target| {
+            let num_rows = self.write_binary_columns(&mut target)?;
+            // reclaim the space of the JSON text
+            target.execute_batch("VACUUM")?;
+            Ok(num_rows)
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(path_out);
+        }
+        result
+    }
+
+    /// Convert the JSON columns of this database and write them to the same rows of `target`
+    fn write_binary_columns(&self, target: &mut Connection) -> Result<usize, SyntheticsError> {
+        let transaction = target.transaction()?;
+        transaction.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY, value TEXT)",
+            META_TABLE
+        ))?;
+
+        let mut num_rows = 0;
+        for &(table, columns) in BINARY_COLUMNS {
+            if !self.has_table(table)? || self.storage_format(table)? == StorageFormat::Binary {
+                continue;
+            }
+            // quoted, `values` is a keyword
+            let names: Vec<String> = columns.iter().map(|(name, _)| format!("\"{}\"", name)).collect();
+
+            let converted = self.read_chunked(
+                &format!("SELECT rowid, ion_id, {} FROM {}", names.join(", "), table),
+                |row| {
+                    let values = (0..columns.len())
+                        .map(|i| column_bytes(row, i + 2))
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, u32>(1)?, values))
+                },
+                |(rowid, id, values)| {
+                    let blobs = values
+                        .iter()
+                        .zip(columns.iter())
+                        .map(|(value, &(column, convert))| {
+                            convert(value).map_err(|source| {
+                                SyntheticsError::ColumnParse(ColumnParseError {
+                                    table,
+                                    column,
+                                    id,
+                                    source,
+                                })
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok((rowid, blobs))
+                },
+            )?;
+
+            let assignments: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| format!("{} = ?{}", name, i + 1))
+                .collect();
+            let mut stmt = transaction.prepare(&format!(
+                "UPDATE {} SET {} WHERE rowid = ?{}",
+                table,
+                assignments.join(", "),
+                names.len() + 1
+            ))?;
+            for (rowid, blobs) in &converted {
+                let mut params: Vec<&dyn ToSql> = blobs.iter().map(|blob| blob as &dyn ToSql).collect();
+                params.push(rowid);
+                stmt.execute(params.as_slice())?;
+            }
+            transaction.execute(
+                &format!("INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)", META_TABLE),
+                [format_key(table), StorageFormat::Binary.to_string()],
+            )?;
+            num_rows += converted.len();
+        }
+
+        transaction.commit()?;
+        Ok(num_rows)
+    }
+
+    /// Names of the columns of `table` in table order, empty if there is no such table
+    fn column_names(&self, table: &str) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self
+            .connection
+            .prepare_cached("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+        let names = stmt.query_map([table], |row| row.get(0))?;
+        names.collect()
+    }
+
+    /// Check that all tables the frame builders read exist and have the expected columns
+    ///
+    /// # Returns
+    ///
+    /// * A report of missing tables and missing or renamed columns, `fragment_ions` is only checked if present
+    ///
+    pub fn validate_schema(&self) -> rusqlite::Result<SchemaReport> {
+        let mut report = SchemaReport::default();
+        let mut stmt = self
+            .connection
+            .prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+
+        for (table, required, expected_columns) in EXPECTED_SCHEMA {
+            let columns: Vec<String> = stmt
+                .query_map([table], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            if columns.is_empty() {
+                if *required {
+                    report.missing_tables.push(table.to_string());
+                }
+                continue;
+            }
+
+            for (index, expected) in expected_columns.iter().enumerate() {
+                let found = columns.get(index);
+                let matches = match (expected, found) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(expected), Some(found)) => expected == found,
+                };
+                if !matches {
+                    report.column_mismatches.push(ColumnMismatch {
+                        table: table.to_string(),
+                        index,
+                        expected: expected.map(str::to_string),
+                        found: found.cloned(),
+                    });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Same as `validate_schema`, but an invalid schema is returned as error
+    pub fn check_schema(&self) -> Result<(), SyntheticsError> {
+        let report = self.validate_schema()?;
+        if report.is_valid() {
+            Ok(())
+        } else {
+            Err(SyntheticsError::Schema(report))
+        }
+    }
+
+    /// Create the tables the frame builders read with the column names and types imspy writes,
+    /// existing tables are left as they are, so a partially written database can be completed
+    pub fn create_schema(&self) -> rusqlite::Result<()> {
+        self.connection.execute_batch(SCHEMA)
+    }
+
+    /// Append rows to `table` in a single transaction with one prepared statement, `insert` binds
+    /// the values of a row and executes the statement
+    fn insert_rows<T>(
+        &self,
+        table: &str,
+        num_columns: usize,
+        rows: &[T],
+        insert: impl Fn(&mut rusqlite::Statement, &T) -> rusqlite::Result<usize>,
+    ) -> rusqlite::Result<()> {
+        let placeholders: Vec<String> = (1..=num_columns).map(|i| format!("?{}", i)).collect();
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare(&format!(
+                "INSERT INTO {} VALUES ({})",
+                table,
+                placeholders.join(", ")
+            ))?;
+            for row in rows {
+                insert(&mut stmt, row)?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Append frames to the frames table, see `create_schema`
+    pub fn insert_frames(&self, frames: &[FramesSim]) -> rusqlite::Result<()> {
+        self.insert_rows("frames", 3, frames, |stmt, frame| {
+            stmt.execute(rusqlite::params![frame.frame_id, frame.time, frame.ms_type])
+        })
+    }
+
+    /// Append scans to the scans table, see `create_schema`
+    pub fn insert_scans(&self, scans: &[ScansSim]) -> rusqlite::Result<()> {
+        self.insert_rows("scans", 2, scans, |stmt, scan| {
+            stmt.execute(rusqlite::params![scan.scan, scan.mobility])
+        })
+    }
+
+    /// Append peptides to the peptides table, see `create_schema`, rt_sigma and rt_lambda are left empty
+    pub fn insert_peptides(&self, peptides: &[PeptidesSim]) -> rusqlite::Result<()> {
+        self.insert_rows("peptides", 17, peptides, |stmt, peptide| {
+            let distribution = &peptide.frame_distribution;
+            stmt.execute(rusqlite::params![
+                peptide.protein_id,
+                peptide.peptide_id,
+                peptide.sequence.sequence,
+                peptide.proteins,
+                peptide.decoy,
+                peptide.missed_cleavages,
+                peptide.n_term,
+                peptide.c_term,
+                peptide.mono_isotopic_mass,
+                peptide.retention_time,
+                peptide.events,
+                None::<f64>,
+                None::<f64>,
+                peptide.frame_start,
+                peptide.frame_end,
+                distribution.occurrence.encode(StorageFormat::Json),
+                distribution.abundance.encode(StorageFormat::Json),
+            ])
+        })
+    }
+
+    /// Append ions to the ions table, see `create_schema`, the mz is calculated from sequence and charge
+    /// and the list and spectrum columns are stored in the format of the table
+    pub fn insert_ions(&self, ions: &[IonSim]) -> rusqlite::Result<()> {
+        let format = self.storage_format("ions")?;
+        // the charge states of a peptide share their sequence, the mass is calculated once
+        let mut masses: HashMap<&str, f64> = HashMap::new();
+        for ion in ions {
+            masses.entry(ion.sequence.as_str()).or_insert_with(|| {
+                PeptideSequence::new(ion.sequence.clone(), Some(ion.peptide_id as i32)).mono_isotopic_mass()
+            });
+        }
+        self.insert_rows("ions", 11, ions, |stmt, ion| {
+            let mass = masses[ion.sequence.as_str()];
+            let mz = (mass + ion.charge as f64 * MASS_PROTON) / ion.charge as f64;
+            stmt.execute(rusqlite::params![
+                ion.ion_id,
+                ion.peptide_id,
+                ion.sequence,
+                ion.charge,
+                mz,
+                ion.relative_abundance,
+                ion.mobility,
+                None::<f64>,
+                ion.simulated_spectrum.encode(format),
+                ion.scan_distribution.occurrence.encode(format),
+                ion.scan_distribution.abundance.encode(format),
+            ])
+        })
+    }
+
+    /// Append fragment intensities to the fragment_ions table, see `create_schema`, indices and values
+    /// are stored in the format of the table
+    pub fn insert_fragment_ions(&self, fragment_ions: &[FragmentIonSim]) -> rusqlite::Result<()> {
+        let format = self.storage_format("fragment_ions")?;
+        self.insert_rows("fragment_ions", 6, fragment_ions, |stmt, fragment_ion| {
+            stmt.execute(rusqlite::params![
+                fragment_ion.peptide_id,
+                fragment_ion.ion_id,
+                fragment_ion.collision_energy,
+                fragment_ion.charge,
+                fragment_ion.indices.encode(format),
+                fragment_ion.values.encode(format),
+            ])
+        })
+    }
+
+    /// Append DIA window groups to the dia_ms_ms_windows table, see `create_schema`
+    pub fn insert_window_group_settings(&self, settings: &[WindowGroupSettingsSim]) -> rusqlite::Result<()> {
+        self.insert_rows("dia_ms_ms_windows", 6, settings, |stmt, setting| {
+            stmt.execute(rusqlite::params![
+                setting.window_group,
+                setting.scan_start,
+                setting.scan_end,
+                setting.isolation_mz,
+                setting.isolation_width,
+                setting.collision_energy,
+            ])
+        })
+    }
+
+    /// Append the window group of fragment frames to the dia_ms_ms_info table, see `create_schema`
+    pub fn insert_frame_to_window_group(&self, frame_to_window_group: &[FrameToWindowGroupSim]) -> rusqlite::Result<()> {
+        self.insert_rows("dia_ms_ms_info", 2, frame_to_window_group, |stmt, entry| {
+            stmt.execute(rusqlite::params![entry.frame_id, entry.window_group])
+        })
+    }
+
+    /// Replace the DIA window scheme of the run, e.g. one designed with `generate_dia_scheme`
+    ///
+    /// The window groups of `cycle` are assigned in turn to the fragment frames of the frames table, ordered
+    /// by frame id, so every cycle of the run repeats the cycle of the scheme. The scheme is rejected with the
+    /// first issue found by `validate_scheme`, or if `cycle` is empty or refers to a window group without windows.
+    pub fn insert_dia_scheme(&self, settings: &[WindowGroupSettingsSim], cycle: &[FrameToWindowGroupSim]) -> rusqlite::Result<()> {
+        if let Some(issue) = validate_scheme(settings).into_iter().next() {
+            return Err(rusqlite::Error::ToSqlConversionFailure(Box::new(issue)));
+        }
+        let groups: HashSet<u32> = settings.iter().map(|setting| setting.window_group).collect();
+        if cycle.is_empty() || cycle.iter().any(|entry| !groups.contains(&entry.window_group)) {
+            return Err(rusqlite::Error::ToSqlConversionFailure(
+                "the cycle needs at least one fragment frame and only window groups of the scheme".into(),
+            ));
+        }
+
+        let mut fragment_frames: Vec<u32> = self.read_frames()?.into_iter()
+            .filter(|frame| frame.ms_type != 0)
+            .map(|frame| frame.frame_id)
+            .collect();
+        fragment_frames.sort_unstable();
+        let frame_to_window_group: Vec<FrameToWindowGroupSim> = fragment_frames.into_iter().enumerate()
+            .map(|(i, frame_id)| FrameToWindowGroupSim::new(frame_id, cycle[i % cycle.len()].window_group))
+            .collect();
+
+        self.connection.execute_batch("DELETE FROM dia_ms_ms_windows; DELETE FROM dia_ms_ms_info;")?;
+        self.insert_window_group_settings(settings)?;
+        self.insert_frame_to_window_group(&frame_to_window_group)
+    }
+
+    /// Run `sql` and parse the rows in parallel: `read` copies the columns of a row, chunks of rows
+    /// are parsed by `parse` in the current rayon pool while the next chunk is read
+    fn read_chunked<R, T, F>(
+        &self,
+        sql: &str,
+        read: impl Fn(&rusqlite::Row) -> rusqlite::Result<R>,
+        parse: F,
+    ) -> Result<Vec<T>, SyntheticsError>
+    where
+        R: Send,
+        T: Send,
+        F: Fn(R) -> Result<T, SyntheticsError> + Sync,
+    {
+        let mut stmt = self.connection.prepare_cached(sql)?;
+        let mut rows = stmt.query([])?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let parse = &parse;
+
+        let read_result = rayon::in_place_scope(|scope| -> rusqlite::Result<()> {
+            let mut chunk = Vec::with_capacity(READ_CHUNK_SIZE);
+            let mut chunk_index = 0;
+            loop {
+                let row = rows.next()?;
+                let done = row.is_none();
+                if let Some(row) = row {
+                    chunk.push(read(row)?);
+                }
+                if chunk.len() == READ_CHUNK_SIZE || (done && !chunk.is_empty()) {
+                    let raw = std::mem::replace(&mut chunk, Vec::with_capacity(READ_CHUNK_SIZE));
+                    let sender = sender.clone();
+                    scope.spawn(move |_| {
+                        let parsed: Result<Vec<T>, SyntheticsError> = raw.into_iter().map(parse).collect();
+                        let _ = sender.send((chunk_index, parsed));
+                    });
+                    chunk_index += 1;
+                }
+                if done {
+                    return Ok(());
+                }
+            }
+        });
+        drop(sender);
+        read_result?;
+
+        let mut chunks: Vec<(usize, Result<Vec<T>, SyntheticsError>)> = receiver.into_iter().collect();
+        chunks.sort_by_key(|(index, _)| *index);
+        let mut result = Vec::new();
+        for (_, chunk) in chunks {
+            result.extend(chunk?);
+        }
+        Ok(result)
+    }
+
     pub fn read_frames(&self) -> rusqlite::Result<Vec<FramesSim>> {
         let mut stmt = self.connection.prepare("SELECT * FROM frames")?;
         let frames_iter = stmt.query_map([], |row| {
@@ -45,114 +922,229 @@ impl TimsTofSyntheticsDataHandle {
         }
         Ok(scans)
     }
-    pub fn read_peptides(&self) -> rusqlite::Result<Vec<PeptidesSim>> {
-        let mut stmt = self.connection.prepare("SELECT * FROM peptides")?;
-        let peptides_iter = stmt.query_map([], |row| {
-            let frame_occurrence_str: String = row.get(15)?;
-            let frame_abundance_str: String = row.get(16)?;
-
-            let frame_occurrence: Vec<u32> = match serde_json::from_str(&frame_occurrence_str) {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        15,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
+
+    pub fn read_peptides(&self) -> Result<Vec<PeptidesSim>, SyntheticsError> {
+        self.read_chunked(
+            "SELECT * FROM peptides",
+            |row| {
+                let peptide = PeptidesSim {
+                    protein_id: row.get(0)?,
+                    peptide_id: row.get(1)?,
+                    sequence: PeptideSequence::new(row.get(2)?, row.get(1)?),
+                    proteins: row.get(3)?,
+                    decoy: row.get(4)?,
+                    missed_cleavages: row.get(5)?,
+                    n_term: row.get(6)?,
+                    c_term: row.get(7)?,
+                    mono_isotopic_mass: row.get(8)?,
+                    retention_time: row.get(9)?,
+                    events: row.get(10)?,
+                    frame_start: row.get(13)?,
+                    frame_end: row.get(14)?,
+                    frame_distribution: SignalDistribution::new(0.0, 0.0, 0.0, vec![], vec![]),
+                };
+                Ok((
+                    peptide,
+                    row.get::<_, String>(15)?,
+                    row.get::<_, String>(16)?,
+                ))
+            },
+            |(mut peptide, frame_occurrence, frame_abundance)| {
+                let frame_occurrence: Vec<u32> = parse_column(
+                    frame_occurrence.as_bytes(),
+                    StorageFormat::Json,
+                    "peptides",
+                    "frame_occurrence",
+                    peptide.peptide_id,
+                )?;
+                // if the frame abundance is not available, set it to 0
+                let frame_abundance: Vec<f32> = serde_json::from_str(&frame_abundance)
+                    .unwrap_or_else(|_| vec![0.0; frame_occurrence.len()]);
+                peptide.frame_distribution =
+                    SignalDistribution::new(0.0, 0.0, 0.0, frame_occurrence, frame_abundance);
+                Ok(peptide)
+            },
+        )
+    }
+
+    /// Read the peptides table, collapsing duplicate peptides with the same sequence and decoy flag
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - How duplicates are handled, see `DuplicatePeptideStrategy`
+    ///
+    /// # Returns
+    ///
+    /// * The peptides in table order without duplicates, or an error if a duplicate is found with `DuplicatePeptideStrategy::Error`
+    ///
+    pub fn read_peptides_deduplicated(
+        &self,
+        strategy: DuplicatePeptideStrategy,
+    ) -> Result<Vec<PeptidesSim>, SyntheticsError> {
+        let peptides = self.read_peptides()?;
+        TimsTofSyntheticsDataHandle::deduplicate_peptides(peptides, strategy)
+    }
+
+    /// Collapse peptides with the same sequence and decoy flag, the first occurrence keeps its id and metadata
+    pub fn deduplicate_peptides(
+        peptides: Vec<PeptidesSim>,
+        strategy: DuplicatePeptideStrategy,
+    ) -> Result<Vec<PeptidesSim>, SyntheticsError> {
+        let mut first_index: HashMap<(String, bool), usize> = HashMap::new();
+        let mut result: Vec<PeptidesSim> = Vec::with_capacity(peptides.len());
+
+        for peptide in peptides {
+            let key = (peptide.sequence.sequence.clone(), peptide.decoy);
+
+            match first_index.get(&key) {
+                None => {
+                    first_index.insert(key, result.len());
+                    result.push(peptide);
                 }
-            };
+                Some(&index) => match strategy {
+                    DuplicatePeptideStrategy::KeepFirst => {}
+                    DuplicatePeptideStrategy::SumEvents => {
+                        TimsTofSyntheticsDataHandle::merge_duplicate_peptide(&mut result[index], &peptide);
+                    }
+                    DuplicatePeptideStrategy::Error => {
+                        return Err(SyntheticsError::DuplicatePeptide(DuplicatePeptideError {
+                            sequence: key.0,
+                            decoy: key.1,
+                            peptide_ids: (result[index].peptide_id, peptide.peptide_id),
+                        }));
+                    }
+                },
+            }
+        }
 
-            // if the frame abundance is not available, set it to 0
-            let frame_abundance: Vec<f32> = match serde_json::from_str(&frame_abundance_str) {
-                Ok(value) => value,
-                Err(_e) => vec![0.0; frame_occurrence.len()],
-            };
+        Ok(result)
+    }
 
-            let frame_distribution =
-                SignalDistribution::new(0.0, 0.0, 0.0, frame_occurrence, frame_abundance);
-
-            Ok(PeptidesSim {
-                protein_id: row.get(0)?,
-                peptide_id: row.get(1)?,
-                sequence: PeptideSequence::new(row.get(2)?, row.get(1)?),
-                proteins: row.get(3)?,
-                decoy: row.get(4)?,
-                missed_cleavages: row.get(5)?,
-                n_term: row.get(6)?,
-                c_term: row.get(7)?,
-                mono_isotopic_mass: row.get(8)?,
-                retention_time: row.get(9)?,
-                events: row.get(10)?,
-                frame_start: row.get(13)?,
-                frame_end: row.get(14)?,
-                frame_distribution,
-            })
-        })?;
-        let mut peptides = Vec::new();
-        for peptide in peptides_iter {
-            peptides.push(peptide?);
+    /// Merge a duplicate into a peptide, events are summed and the frame abundances are aligned by frame id
+    /// and weighted by events, such that abundance times events per frame is the sum of both peptides
+    fn merge_duplicate_peptide(target: &mut PeptidesSim, duplicate: &PeptidesSim) {
+        let total_events = target.events + duplicate.events;
+        let mut frame_to_signal: BTreeMap<u32, f32> = BTreeMap::new();
+
+        for peptide in [&*target, duplicate] {
+            // without events, abundances are summed unweighted
+            let weight = if total_events > 0.0 { peptide.events } else { 1.0 };
+            let distribution = &peptide.frame_distribution;
+            for (frame_id, abundance) in distribution.occurrence.iter().zip(distribution.abundance.iter()) {
+                *frame_to_signal.entry(*frame_id).or_insert(0.0) += abundance * weight;
+            }
         }
-        Ok(peptides)
+
+        let normalization = if total_events > 0.0 { total_events } else { 1.0 };
+        target.frame_distribution.occurrence = frame_to_signal.keys().cloned().collect();
+        target.frame_distribution.abundance = frame_to_signal.values().map(|signal| signal / normalization).collect();
+        target.frame_start = target.frame_start.min(duplicate.frame_start);
+        target.frame_end = target.frame_end.max(duplicate.frame_end);
+        target.events = total_events;
     }
 
-    pub fn read_ions(&self) -> rusqlite::Result<Vec<IonSim>> {
-        let mut stmt = self.connection.prepare("SELECT * FROM ions")?;
-        let ions_iter = stmt.query_map([], |row| {
-            let simulated_spectrum_str: String = row.get(8)?;
-            let scan_occurrence_str: String = row.get(9)?;
-            let scan_abundance_str: String = row.get(10)?;
+    /// Collapse duplicate peptides in the database, the rows of dropped peptides are deleted together with
+    /// their ions and fragment ions in a single transaction, so no ion refers to a missing peptide
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - How duplicates are handled, see `DuplicatePeptideStrategy`, nothing is written on error
+    ///
+    /// # Returns
+    ///
+    /// * The number of deleted peptide rows
+    ///
+    pub fn collapse_duplicate_peptides(&self, strategy: DuplicatePeptideStrategy) -> Result<usize, SyntheticsError> {
+        let peptides = self.read_peptides()?;
+        let all_ids: Vec<u32> = peptides.iter().map(|peptide| peptide.peptide_id).collect();
+        let kept = TimsTofSyntheticsDataHandle::deduplicate_peptides(peptides, strategy)?;
+        let kept_ids: HashSet<u32> = kept.iter().map(|peptide| peptide.peptide_id).collect();
+        let dropped_ids: Vec<u32> = all_ids.into_iter().filter(|id| !kept_ids.contains(id)).collect();
+        if dropped_ids.is_empty() {
+            return Ok(0);
+        }
 
-            let simulated_spectrum: MzSpectrum = match serde_json::from_str(&simulated_spectrum_str)
-            {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        8,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
-                }
-            };
+        // columns are addressed by position as in `read_peptides`, their names differ between imspy versions
+        let columns = self.column_names("peptides")?;
+        let column = |index: usize| format!("\"{}\"", columns[index]);
+        let has_fragment_ions = self.has_table("fragment_ions")?;
+
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut update = transaction.prepare(&format!(
+                "UPDATE peptides SET {} = ?1, {} = ?2, {} = ?3, {} = ?4, {} = ?5 WHERE peptide_id = ?6",
+                column(10), column(13), column(14), column(15), column(16),
+            ))?;
+            for peptide in &kept {
+                let distribution = &peptide.frame_distribution;
+                update.execute(rusqlite::params![
+                    peptide.events,
+                    peptide.frame_start,
+                    peptide.frame_end,
+                    distribution.occurrence.encode(StorageFormat::Json),
+                    distribution.abundance.encode(StorageFormat::Json),
+                    peptide.peptide_id,
+                ])?;
+            }
 
-            let scan_occurrence: Vec<u32> = match serde_json::from_str(&scan_occurrence_str) {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        9,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
-                }
+            let mut delete_peptide = transaction.prepare("DELETE FROM peptides WHERE peptide_id = ?1")?;
+            let mut delete_ions = transaction.prepare("DELETE FROM ions WHERE peptide_id = ?1")?;
+            let mut delete_fragment_ions = match has_fragment_ions {
+                true => Some(transaction.prepare("DELETE FROM fragment_ions WHERE peptide_id = ?1")?),
+                false => None,
             };
-
-            let scan_abundance: Vec<f32> = match serde_json::from_str(&scan_abundance_str) {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        10,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
+            for peptide_id in &dropped_ids {
+                delete_peptide.execute([peptide_id])?;
+                delete_ions.execute([peptide_id])?;
+                if let Some(stmt) = delete_fragment_ions.as_mut() {
+                    stmt.execute([peptide_id])?;
                 }
-            };
-
-            Ok(IonSim::new(
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-                row.get(5)?,
-                row.get(6)?,
-                simulated_spectrum,
-                scan_occurrence,
-                scan_abundance,
-            ))
-        })?;
-        let mut ions = Vec::new();
-        for ion in ions_iter {
-            ions.push(ion?);
+            }
         }
-        Ok(ions)
+        transaction.commit()?;
+
+        Ok(dropped_ids.len())
+    }
+
+    pub fn read_ions(&self) -> Result<Vec<IonSim>, SyntheticsError> {
+        let format = self.storage_format("ions")?;
+        self.read_chunked(
+            "SELECT * FROM ions",
+            |row| {
+                let ion = IonSim::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    MzSpectrum::new(vec![], vec![]),
+                    vec![],
+                    vec![],
+                );
+                Ok((
+                    ion,
+                    column_bytes(row, 8)?,
+                    column_bytes(row, 9)?,
+                    column_bytes(row, 10)?,
+                ))
+            },
+            |(mut ion, simulated_spectrum, scan_occurrence, scan_abundance)| {
+                let id = ion.ion_id;
+                ion.simulated_spectrum = parse_column(
+                    &simulated_spectrum,
+                    format,
+                    "ions",
+                    "simulated_spectrum",
+                    id,
+                )?;
+                ion.scan_distribution.occurrence =
+                    parse_column(&scan_occurrence, format, "ions", "scan_occurrence", id)?;
+                ion.scan_distribution.abundance =
+                    parse_column(&scan_abundance, format, "ions", "scan_abundance", id)?;
+                Ok(ion)
+            },
+        )
     }
 
     pub fn read_window_group_settings(&self) -> rusqlite::Result<Vec<WindowGroupSettingsSim>> {
@@ -188,6 +1180,40 @@ impl TimsTofSyntheticsDataHandle {
         Ok(frame_to_window_groups)
     }
 
+    /// Fragment frames acquired with a window group
+    ///
+    /// # Returns
+    ///
+    /// * The ascending frame ids of the window group in the dia_ms_ms_info table, empty for unknown window groups
+    ///
+    pub fn frames_for_window_group(&self, window_group: u32) -> rusqlite::Result<Vec<u32>> {
+        if !self.has_table("dia_ms_ms_info")? {
+            return Ok(Vec::new());
+        }
+        let mut stmt = self
+            .connection
+            .prepare("SELECT frame FROM dia_ms_ms_info WHERE window_group = ?1 ORDER BY frame")?;
+        let frame_ids = stmt
+            .query_map([window_group], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<u32>>>()?;
+        Ok(frame_ids)
+    }
+
+    /// Window group a frame was acquired with
+    ///
+    /// # Returns
+    ///
+    /// * The window group of the frame in the dia_ms_ms_info table, `None` for precursor and unknown frames
+    ///
+    pub fn window_group_for_frame(&self, frame_id: u32) -> rusqlite::Result<Option<u32>> {
+        if !self.has_table("dia_ms_ms_info")? {
+            return Ok(None);
+        }
+        self.connection
+            .query_row("SELECT window_group FROM dia_ms_ms_info WHERE frame = ?1", [frame_id], |row| row.get(0))
+            .optional()
+    }
+
     pub fn read_pasef_meta(&self) -> rusqlite::Result<Vec<PASEFMeta>> {
         let mut stmt = self.connection.prepare("SELECT * FROM pasef_meta")?;
         let pasef_meta_iter = stmt.query_map([], |row| {
@@ -210,58 +1236,558 @@ impl TimsTofSyntheticsDataHandle {
         Ok(pasef_meta)
     }
 
-    pub fn read_fragment_ions(&self) -> rusqlite::Result<Vec<FragmentIonSim>> {
-        let mut stmt = self.connection.prepare("SELECT * FROM fragment_ions")?;
+    /// Replace the pasef_meta table with the given PASEF scheduling
+    /// Tof calibration of the simulated frames
+    ///
+    /// # Returns
+    ///
+    /// * The calibration stored in the tof_calibration table, `TofCalibration::default()` if there is none
+    ///
+    pub fn read_tof_calibration(&self) -> rusqlite::Result<TofCalibration> {
+        if !self.has_table("tof_calibration")? {
+            return Ok(TofCalibration::default());
+        }
+        self.connection.query_row(
+            "SELECT intercept, slope, curvature, tof_max_index FROM tof_calibration",
+            [],
+            |row| {
+                Ok(TofCalibration {
+                    intercept: row.get(0)?,
+                    slope: row.get(1)?,
+                    curvature: row.get(2)?,
+                    tof_max_index: row.get(3)?,
+                })
+            },
+        )
+    }
+
+    /// Replace the tof calibration of the simulated frames, builders created afterwards use it
+    pub fn write_tof_calibration(&self, calibration: &TofCalibration) -> rusqlite::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute_batch(
+            "DROP TABLE IF EXISTS tof_calibration;
+             CREATE TABLE tof_calibration (intercept REAL, slope REAL, curvature REAL, tof_max_index INTEGER);",
+        )?;
+        transaction.execute(
+            "INSERT INTO tof_calibration VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                calibration.intercept,
+                calibration.slope,
+                calibration.curvature,
+                calibration.tof_max_index,
+            ],
+        )?;
+        transaction.commit()
+    }
 
-        let fragment_ion_sim_iter = stmt.query_map([], |row| {
-            let indices_string: String = row.get(4)?;
-            let values_string: String = row.get(5)?;
+    /// Collision energy ramp over the inverse mobility
+    ///
+    /// # Returns
+    ///
+    /// * The (1/K0, collision energy) breakpoints stored in the collision_energy_ramp table, ordered by 1/K0,
+    ///   `None` if there is none
+    ///
+    pub fn read_collision_energy_ramp(&self) -> rusqlite::Result<Option<Vec<(f64, f64)>>> {
+        if !self.has_table("collision_energy_ramp")? {
+            return Ok(None);
+        }
+        let mut stmt = self.connection.prepare(
+            "SELECT inv_mobility, collision_energy FROM collision_energy_ramp ORDER BY inv_mobility",
+        )?;
+        let breakpoints = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(f64, f64)>>>()?;
+        Ok(Some(breakpoints))
+    }
 
-            let indices: Vec<u32> = match serde_json::from_str(&indices_string) {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        4,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
-                }
-            };
+    /// Replace the collision energy ramp, DIA builders created afterwards take their collision energies
+    /// from the inverse mobility of the scan instead of the window groups
+    pub fn write_collision_energy_ramp(&self, breakpoints: &[(f64, f64)]) -> rusqlite::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute_batch(
+            "DROP TABLE IF EXISTS collision_energy_ramp;
+             CREATE TABLE collision_energy_ramp (inv_mobility REAL, collision_energy REAL);",
+        )?;
+        {
+            let mut stmt = transaction.prepare("INSERT INTO collision_energy_ramp VALUES (?1, ?2)")?;
+            for (inv_mobility, collision_energy) in breakpoints {
+                stmt.execute(rusqlite::params![inv_mobility, collision_energy])?;
+            }
+        }
+        transaction.commit()
+    }
 
-            let values: Vec<f64> = match serde_json::from_str(&values_string) {
-                Ok(value) => value,
-                Err(e) => {
-                    return Err(rusqlite::Error::FromSqlConversionFailure(
-                        5,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    ))
-                }
-            };
+    /// Remove the collision energy ramp, DIA builders created afterwards use the window group collision energies again
+    pub fn clear_collision_energy_ramp(&self) -> rusqlite::Result<()> {
+        self.connection.execute_batch("DROP TABLE IF EXISTS collision_energy_ramp;")
+    }
 
-            Ok(FragmentIonSim::new(
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-                indices,
-                values,
-            ))
-        })?;
+    pub fn write_pasef_meta(&self, pasef_meta: &[PASEFMeta]) -> rusqlite::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute_batch(
+            "DROP TABLE IF EXISTS pasef_meta;
+             CREATE TABLE pasef_meta (
+                frame INTEGER, scan_start INTEGER, scan_end INTEGER, isolation_mz REAL,
+                isolation_width REAL, collision_energy REAL, precursor INTEGER
+             );",
+        )?;
+        {
+            let mut stmt = transaction.prepare("INSERT INTO pasef_meta VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            for meta in pasef_meta {
+                stmt.execute(rusqlite::params![
+                    meta.frame,
+                    meta.scan_start,
+                    meta.scan_end,
+                    meta.isolation_mz,
+                    meta.isolation_width,
+                    meta.collision_energy,
+                    meta.precursor,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
 
-        let mut fragment_ion_sim = Vec::new();
-        for fragment_ion in fragment_ion_sim_iter {
-            fragment_ion_sim.push(fragment_ion?);
+    /// Replace the precursors table with the given selected DDA precursors
+    pub fn write_dda_precursors(&self, precursors: &[DDAPrecursorSim]) -> rusqlite::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute_batch(
+            "DROP TABLE IF EXISTS precursors;
+             CREATE TABLE precursors (
+                id INTEGER, ion_id INTEGER, largest_peak_mz REAL, average_mz REAL, monoisotopic_mz REAL,
+                charge INTEGER, scan_number REAL, intensity REAL, parent INTEGER
+             );",
+        )?;
+        {
+            let mut stmt = transaction.prepare("INSERT INTO precursors VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?;
+            for precursor in precursors {
+                stmt.execute(rusqlite::params![
+                    precursor.id,
+                    precursor.ion_id,
+                    precursor.largest_peak_mz,
+                    precursor.average_mz,
+                    precursor.monoisotopic_mz,
+                    precursor.charge,
+                    precursor.scan_number,
+                    precursor.intensity,
+                    precursor.parent,
+                ])?;
+            }
         }
+        transaction.commit()
+    }
 
-        Ok(fragment_ion_sim)
+    /// Set the ms type of the given frames in the frames table
+    pub fn update_frame_types(&self, frames: &[FramesSim]) -> rusqlite::Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare("UPDATE frames SET ms_type = ?1 WHERE frame_id = ?2")?;
+            for frame in frames {
+                stmt.execute(rusqlite::params![frame.ms_type, frame.frame_id])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Predict the inverse mobility of every ion from its mass and charge and write it to the ions table,
+    /// replacing any previously predicted mobility
+    ///
+    /// # Arguments
+    ///
+    /// * `model` - Mobility model mapping mass and charge to a collision cross-section
+    /// * `num_threads` - Number of threads to use
+    ///
+    pub fn write_ion_mobilities(&self, model: &MobilityModel, num_threads: usize) -> rusqlite::Result<()> {
+        let ions: Vec<(u32, f64, i32)> = {
+            let mut stmt = self.connection.prepare("SELECT ion_id, mz, charge FROM ions")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let masses: Vec<f64> = ions.iter().map(|&(_, mz, charge)| (mz - MASS_PROTON) * charge as f64).collect();
+        let charges: Vec<i32> = ions.iter().map(|&(_, _, charge)| charge).collect();
+        let mobilities = predict_inverse_mobilities_par(&masses, &charges, model, num_threads);
+
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare("UPDATE ions SET inv_mobility_gru_predictor = ?1 WHERE ion_id = ?2")?;
+            for ((ion_id, _, _), mobility) in ions.iter().zip(mobilities.iter()) {
+                stmt.execute(rusqlite::params![mobility, ion_id])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Simulate the scan distribution of every ion from its inverse mobility and write it to the ions table,
+    /// replacing any precomputed scan occurrences and abundances
+    ///
+    /// # Arguments
+    ///
+    /// * `im_sigma` - Width of the ion mobility peaks, towards lower inverse mobility for asymmetric peaks
+    /// * `im_sigma_high` - Optional width towards higher inverse mobility, defaults to `im_sigma`
+    /// * `truncation_z` - Peaks are cut off at `truncation_z` standard deviations around their apex
+    /// * `num_threads` - Number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * The number of ions whose peak lies outside of the scanned mobility range and got no scans
+    ///
+    pub fn write_scan_distributions(
+        &self,
+        im_sigma: f64,
+        im_sigma_high: Option<f64>,
+        truncation_z: f64,
+        num_threads: usize,
+    ) -> rusqlite::Result<usize> {
+        let scans = self.read_scans()?;
+
+        let (ion_ids, mobilities): (Vec<u32>, Vec<f64>) = {
+            let mut stmt = self.connection.prepare("SELECT ion_id, inv_mobility_gru_predictor FROM ions")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, f64>(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?.into_iter().unzip()
+        };
+
+        let sigmas_low = vec![im_sigma; mobilities.len()];
+        let sigmas_high = vec![im_sigma_high.unwrap_or(im_sigma); mobilities.len()];
+        let (occurrences, abundances): (Vec<Vec<u32>>, Vec<Vec<f32>>) = simulate_scan_distributions(
+            &mobilities, &sigmas_low, &sigmas_high, &scans, truncation_z, num_threads,
+        )
+        .into_iter()
+        .unzip();
+
+        self.update_scan_distributions(&ion_ids, &occurrences, &abundances)?;
+        Ok(occurrences.iter().filter(|occurrence| occurrence.is_empty()).count())
+    }
+
+    /// Write scan occurrences and abundances of ions to the ions table in a single transaction,
+    /// the columns are added if the table has none yet
+    ///
+    /// # Arguments
+    ///
+    /// * `ion_ids` - Ids of the ions to update
+    /// * `occurrences` - Scans of every ion
+    /// * `abundances` - Abundance of every ion in each of its scans
+    ///
+    pub fn update_scan_distributions(
+        &self,
+        ion_ids: &[u32],
+        occurrences: &[Vec<u32>],
+        abundances: &[Vec<f32>],
+    ) -> rusqlite::Result<()> {
+        check_distribution_lengths(ion_ids.len(), occurrences.len(), abundances.len())?;
+        let columns = self.column_names("ions")?;
+
+        let format = self.storage_format("ions")?;
+        let transaction = self.connection.unchecked_transaction()?;
+        for column in ["scan_occurrence", "scan_abundance"] {
+            if !columns.iter().any(|name| name == column) {
+                transaction.execute_batch(&format!("ALTER TABLE ions ADD COLUMN {} TEXT", column))?;
+            }
+        }
+        {
+            let mut stmt = transaction
+                .prepare("UPDATE ions SET scan_occurrence = ?1, scan_abundance = ?2 WHERE ion_id = ?3")?;
+            for ((ion_id, occurrence), abundance) in ion_ids.iter().zip(occurrences).zip(abundances) {
+                stmt.execute(rusqlite::params![
+                    occurrence.encode(format),
+                    abundance.encode(format),
+                    ion_id,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Write frame occurrences and abundances of peptides to the peptides table in a single transaction,
+    /// the first and last frame of every peptide are set from its occurrences, 0 if it has none
+    ///
+    /// # Arguments
+    ///
+    /// * `peptide_ids` - Ids of the peptides to update
+    /// * `occurrences` - Frames of every peptide
+    /// * `abundances` - Abundance of every peptide in each of its frames
+    ///
+    pub fn write_frame_distributions(
+        &self,
+        peptide_ids: &[u32],
+        occurrences: &[Vec<u32>],
+        abundances: &[Vec<f32>],
+    ) -> rusqlite::Result<()> {
+        check_distribution_lengths(peptide_ids.len(), occurrences.len(), abundances.len())?;
+        // imspy versions name the columns of the first and last frame differently, see EXPECTED_SCHEMA
+        let columns = self.column_names("peptides")?;
+        let (Some(frame_start), Some(frame_end)) = (columns.get(13), columns.get(14)) else {
+            return Err(rusqlite::Error::InvalidColumnIndex(14));
+        };
+
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare(&format!(
+                "UPDATE peptides SET {} = ?1, {} = ?2, frame_occurrence = ?3, frame_abundance = ?4 WHERE peptide_id = ?5",
+                frame_start, frame_end
+            ))?;
+            for ((peptide_id, occurrence), abundance) in peptide_ids.iter().zip(occurrences).zip(abundances) {
+                stmt.execute(rusqlite::params![
+                    occurrence.first().copied().unwrap_or(0),
+                    occurrence.last().copied().unwrap_or(0),
+                    occurrence.encode(StorageFormat::Json),
+                    abundance.encode(StorageFormat::Json),
+                    peptide_id,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Simulate the elution profile of every peptide as exponentially modified Gaussian over the frames
+    /// of the run and write its frame occurrences and abundances to the peptides table
+    ///
+    /// # Arguments
+    ///
+    /// * `sigma_source` - Width of the Gaussian part of the elution peaks in seconds
+    /// * `lambda_source` - Rate of the exponential tail of the elution peaks
+    /// * `target_p` - Probability mass of a peak that is covered by its frames
+    /// * `step_size` - Step size of the search for the peak bounds in seconds
+    /// * `num_threads` - Number of threads to use
+    ///
+    /// # Returns
+    ///
+    /// * The number of peptides that elute outside of the run, their abundance is 0 in all frames
+    ///
+    pub fn populate_elution_profiles(
+        &self,
+        sigma_source: ElutionParameter,
+        lambda_source: ElutionParameter,
+        target_p: f64,
+        step_size: f64,
+        num_threads: usize,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut frames = self.read_frames()?;
+        frames.sort_by_key(|frame| frame.frame_id);
+        if frames.len() < 2 {
+            return Err("at least two frames are needed to simulate elution profiles".into());
+        }
+        let retention_times: Vec<f64> = frames.iter().map(|frame| frame.time as f64).collect();
+        // frames are spaced by one cycle
+        let rt_cycle_length = (retention_times[retention_times.len() - 1] - retention_times[0])
+            / (retention_times.len() - 1) as f64;
+        // occurrences are one based positions into `retention_times`
+        let time_map: HashMap<i32, f64> = retention_times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| (index as i32 + 1, *time))
+            .collect();
+
+        // retention time, rt_sigma and rt_lambda are read by position, see EXPECTED_SCHEMA
+        let columns = self.column_names("peptides")?;
+        let (Some(rt), Some(sigma), Some(lambda)) = (columns.get(9), columns.get(11), columns.get(12))
+        else {
+            return Err("the peptides table has no retention time, rt_sigma and rt_lambda columns".into());
+        };
+        let (peptide_ids, rts, sigmas, lambdas) = {
+            let mut stmt = self.connection.prepare(&format!(
+                "SELECT peptide_id, {}, {}, {} FROM peptides",
+                rt, sigma, lambda
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                ))
+            })?;
+            let mut columns = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+            for row in rows {
+                let (peptide_id, rt, stored_sigma, stored_lambda) = row?;
+                let parameter = |source: ElutionParameter, stored: Option<f64>, name: &str| match source {
+                    ElutionParameter::Constant(value) => Ok(value),
+                    ElutionParameter::Stored => stored
+                        .ok_or_else(|| format!("peptide {} has no stored {}", peptide_id, name)),
+                };
+                columns.0.push(peptide_id);
+                columns.1.push(rt);
+                columns.2.push(parameter(sigma_source, stored_sigma, sigma)?);
+                columns.3.push(parameter(lambda_source, stored_lambda, lambda)?);
+            }
+            columns
+        };
+
+        let occurrences = calculate_frame_occurrences_emg_par(
+            &retention_times,
+            rts.clone(),
+            sigmas.clone(),
+            lambdas.clone(),
+            target_p,
+            step_size,
+            num_threads,
+            None,
+            None,
+        )?;
+        let abundances = calculate_frame_abundances_emg_par(
+            &time_map,
+            occurrences.clone(),
+            rts,
+            sigmas,
+            lambdas,
+            rt_cycle_length,
+            num_threads,
+            None,
+            None,
+        );
+
+        let frame_ids: Vec<Vec<u32>> = occurrences
+            .iter()
+            .map(|occurrence| {
+                occurrence
+                    .iter()
+                    .map(|&index| frames[index as usize - 1].frame_id)
+                    .collect()
+            })
+            .collect();
+        let abundances: Vec<Vec<f32>> = abundances
+            .iter()
+            .map(|abundance| abundance.iter().map(|&a| a as f32).collect())
+            .collect();
+        self.write_frame_distributions(&peptide_ids, &frame_ids, &abundances)?;
+
+        Ok(abundances
+            .iter()
+            .filter(|abundance| abundance.iter().all(|a| *a == 0.0))
+            .count())
+    }
+
+    pub fn read_fragment_ions(&self) -> Result<Vec<FragmentIonSim>, SyntheticsError> {
+        let format = self.storage_format("fragment_ions")?;
+        self.read_chunked(
+            "SELECT * FROM fragment_ions",
+            |row| {
+                let fragment_ion = FragmentIonSim::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    vec![],
+                    vec![],
+                );
+                Ok((fragment_ion, column_bytes(row, 4)?, column_bytes(row, 5)?))
+            },
+            |(mut fragment_ion, indices, values)| {
+                let id = fragment_ion.ion_id;
+                fragment_ion.indices =
+                    parse_column(&indices, format, "fragment_ions", "indices", id)?;
+                fragment_ion.values =
+                    parse_column(&values, format, "fragment_ions", "values", id)?;
+                Ok(fragment_ion)
+            },
+        )
+    }
+
+    /// Write a spectral library of all precursors with fragment intensities, e.g. for DIA-NN or OpenSWATH
+    ///
+    /// Fragment m/z are calculated from the peptide sequence and joined with the stored intensities, which are
+    /// averaged over the collision energies of a precursor. Fragment rows are streamed to the file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to create, an existing file is replaced
+    /// * `format` - Column layout, see `SpectralLibraryFormat`
+    /// * `include_decoys` - Whether decoy peptides are written, marked in the decoy column
+    ///
+    /// # Returns
+    ///
+    /// * Number of written precursors
+    ///
+    pub fn export_spectral_library(
+        &self,
+        path: &Path,
+        format: SpectralLibraryFormat,
+        include_decoys: bool,
+    ) -> Result<usize, Box<dyn Error>> {
+        let peptides: HashMap<u32, PeptidesSim> = self
+            .read_peptides()?
+            .into_iter()
+            .filter(|peptide| include_decoys || !peptide.decoy)
+            .map(|peptide| (peptide.peptide_id, peptide))
+            .collect();
+
+        // precursor m/z and mobility are only needed from the ions table, so it is read without the spectra
+        let mut stmt = self.connection.prepare("SELECT * FROM ions")?;
+        let ions: HashMap<u32, (u32, i8, f64, f64)> = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, (row.get(1)?, row.get(3)?, row.get(4)?, row.get(6)?)))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let format_fragments = self.storage_format("fragment_ions")?;
+        let mut stmt = self
+            .connection
+            .prepare("SELECT * FROM fragment_ions ORDER BY ion_id, collision_energy")?;
+        let mut rows = stmt.query([])?;
+
+        let mut writer = SpectralLibraryWriter::new(BufWriter::new(File::create(path)?), format)?;
+        let mut num_precursors = 0;
+        // dense intensities of the current ion summed over collision energies, with the number of summed rows
+        let mut current: Option<(u32, Vec<f64>, usize)> = None;
+
+        loop {
+            let row = rows.next()?;
+            let ion_id: Option<u32> = row.map(|row| row.get(1)).transpose()?;
+
+            if current.as_ref().map(|(id, _, _)| Some(*id)) != Some(ion_id) {
+                if let Some((id, summed, count)) = current.take() {
+                    let ion = ions.get(&id);
+                    if let Some((peptide, &(_, charge, mz, mobility))) = ion.and_then(|ion| Some((peptides.get(&ion.0)?, ion))) {
+                        let mean = summed.into_iter().map(|value| value / count as f64).collect();
+                        let fragments = peptide
+                            .sequence
+                            .associate_with_predicted_intensities(charge as i32, FragmentType::B, mean, false, false, None)?;
+                        let precursor = LibraryPrecursor {
+                            sequence: peptide.sequence.sequence.clone(),
+                            decoy: peptide.decoy,
+                            mz,
+                            charge: charge as i32,
+                            retention_time: peptide.retention_time as f64,
+                            mobility,
+                        };
+                        if writer.write_precursor(&precursor, &fragments)? > 0 {
+                            num_precursors += 1;
+                        }
+                    }
+                }
+            }
+
+            let (Some(row), Some(ion_id)) = (row, ion_id) else {
+                break;
+            };
+            let indices: Vec<u32> = parse_column(&column_bytes(row, 4)?, format_fragments, "fragment_ions", "indices", ion_id)?;
+            let values: Vec<f64> = parse_column(&column_bytes(row, 5)?, format_fragments, "fragment_ions", "values", ion_id)?;
+            let (_, summed, count) = current.get_or_insert_with(|| (ion_id, vec![0.0; 174], 0));
+            for (index, value) in indices.iter().zip(values) {
+                if let Some(summed) = summed.get_mut(*index as usize) {
+                    *summed += value;
+                }
+            }
+            *count += 1;
+        }
+        writer.finish()?;
+
+        Ok(num_precursors)
     }
 
     pub fn get_transmission_dia(&self) -> TimsTransmissionDIA {
+        self.get_transmission_dia_with_profile(None)
+    }
+
+    /// Build the DIA transmission settings, `profile` selects the isolation window shape (default sigmoid)
+    pub fn get_transmission_dia_with_profile(
+        &self,
+        profile: Option<Arc<dyn TransmissionProfile>>,
+    ) -> TimsTransmissionDIA {
         let frame_to_window_group = self.read_frame_to_window_group().unwrap();
         let window_group_settings = self.read_window_group_settings().unwrap();
 
-        TimsTransmissionDIA::new(
+        let transmission = TimsTransmissionDIA::new(
             frame_to_window_group
                 .iter()
                 .map(|x| x.frame_id as i32)
@@ -291,7 +1817,12 @@ impl TimsTofSyntheticsDataHandle {
                 .map(|x| x.isolation_width as f64)
                 .collect(),
             None,
-        )
+        );
+
+        match profile {
+            Some(profile) => transmission.with_profile(profile),
+            None => transmission,
+        }
     }
 
     pub fn get_transmission_dda(&self) -> TimsTransmissionDDA {
@@ -302,6 +1833,24 @@ impl TimsTofSyntheticsDataHandle {
         )
     }
 
+    /// Collision energies of the fragment frames, the mobility ramp if one is stored, the window group
+    /// collision energies otherwise
+    pub fn get_collision_energy_source(&self) -> rusqlite::Result<CollisionEnergySource> {
+        match self.read_collision_energy_ramp()? {
+            Some(breakpoints) => {
+                let scans = self.read_scans()?;
+                CollisionEnergyMobilityRamp::new(
+                    breakpoints,
+                    scans.iter().map(|x| x.scan as i32).collect(),
+                    scans.iter().map(|x| x.mobility as f64).collect(),
+                )
+                .map(CollisionEnergySource::MobilityRamp)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Real, e.into()))
+            }
+            None => Ok(CollisionEnergySource::WindowGroups(self.get_collision_energy_dia())),
+        }
+    }
+
     pub fn get_collision_energy_dia(&self) -> TimsTofCollisionEnergyDIA {
         let frame_to_window_group = self.read_frame_to_window_group().unwrap();
         let window_group_settings = self.read_window_group_settings().unwrap();
@@ -334,105 +1883,144 @@ impl TimsTofSyntheticsDataHandle {
         )
     }
 
-    fn ion_map_fn_dda(
-        ion: IonSim,
+    /// Frames, scans and collision energies an ion is transmitted in
+    ///
+    /// A scan counts if `IonTransmission::transmitted_fraction` of the isotope envelope is above zero, the
+    /// fraction the frame builders scale the fragment intensities with.
+    ///
+    /// # Arguments
+    ///
+    /// * `ion` - The precursor ion
+    /// * `peptide_map` - Peptides by id, for the frames the ion occurs in
+    /// * `precursor_frames` - Ids of precursor frames, which are skipped
+    /// * `transmission` - Quadrupole settings of the acquisition
+    /// * `collision_energy` - Collision energy of a frame and scan
+    ///
+    /// # Returns
+    ///
+    /// * One `TransmittedIon` per frame and quantized collision energy, `None` if the peptide is missing
+    ///
+    fn transmitted_ion<T: IonTransmission>(
+        ion: &IonSim,
         peptide_map: &BTreeMap<u32, PeptidesSim>,
         precursor_frames: &HashSet<u32>,
-        transmission: &TimsTransmissionDDA,
-    ) -> BTreeSet<(u32, u32, String, i8, i32)> {
-        let peptide = peptide_map.get(&ion.peptide_id).unwrap();
-        let mut ret_tree: BTreeSet<(u32, u32, String, i8, i32)> = BTreeSet::new();
+        transmission: &T,
+        collision_energy: impl Fn(i32, i32) -> f64,
+    ) -> Option<Vec<TransmittedIon>> {
+        let peptide = peptide_map.get(&ion.peptide_id)?;
+        let envelope = &ion.simulated_spectrum;
+
+        // (frame, quantized energy) -> first scan, last scan, weighted fraction, weight
+        let mut windows: BTreeMap<(u32, i32), (u32, u32, f64, f64)> = BTreeMap::new();
 
-        // go over all frames the ion occurs in
+        // go over all fragment frames and scans the ion occurs in
         for frame in peptide.frame_distribution.occurrence.iter() {
-            // only consider fragment frames
-            if !precursor_frames.contains(frame) {
-                // go over all scans the ion occurs in
-                for scan in &ion.scan_distribution.occurrence {
-                    // check transmission for all precursor ion peaks of the isotopic envelope
-
-                    let precursor_spec = &ion.simulated_spectrum;
-
-                    if transmission.any_transmitted(
-                        *frame as i32,
-                        *scan as i32,
-                        &precursor_spec.mz,
-                        Some(0.5),
-                    ) {
-                        let collision_energy =
-                            transmission.get_collision_energy(*frame as i32, *scan as i32).unwrap_or(0.0);
-
-                        let quantized_energy = (collision_energy * 100.0).round() as i32;
-
-                        ret_tree.insert((
-                            ion.peptide_id,
-                            ion.ion_id,
-                            peptide.sequence.sequence.clone(),
-                            ion.charge,
-                            quantized_energy,
-                        ));
-                    }
+            if precursor_frames.contains(frame) {
+                continue;
+            }
+            for (scan, scan_abundance) in ion
+                .scan_distribution
+                .occurrence
+                .iter()
+                .zip(ion.scan_distribution.abundance.iter())
+            {
+                // same cutoff as the frame builders: only peaks with a transmission probability above 0.5 count
+                let fraction = transmission.transmitted_fraction(
+                    *frame as i32,
+                    *scan as i32,
+                    &envelope.mz,
+                    &envelope.intensity,
+                    None,
+                );
+                if fraction <= 0.0 {
+                    continue;
                 }
+
+                let energy =
+                    quantize_collision_energy(collision_energy(*frame as i32, *scan as i32));
+                let weight = *scan_abundance as f64;
+
+                let window = windows
+                    .entry((*frame, energy))
+                    .or_insert((*scan, *scan, 0.0, 0.0));
+                window.0 = window.0.min(*scan);
+                window.1 = window.1.max(*scan);
+                window.2 += fraction * weight;
+                window.3 += weight;
             }
         }
-        ret_tree
-    }
 
-    fn ion_map_fn_dia(
-        ion: IonSim,
-        peptide_map: &BTreeMap<u32, PeptidesSim>,
-        precursor_frames: &HashSet<u32>,
-        transmission: &TimsTransmissionDIA,
-        collision_energy: &TimsTofCollisionEnergyDIA,
-    ) -> BTreeSet<(u32, u32, String, i8, i32)> {
-        let peptide = peptide_map.get(&ion.peptide_id).unwrap();
-        let mut ret_tree: BTreeSet<(u32, u32, String, i8, i32)> = BTreeSet::new();
+        let precursor_mz = (peptide.mono_isotopic_mass as f64 + ion.charge as f64 * MASS_PROTON)
+            / ion.charge as f64;
+
+        Some(
+            windows
+                .into_iter()
+                .map(
+                    |((frame_id, energy), (scan_start, scan_end, weighted_fraction, weight))| {
+                        TransmittedIon {
+                            frame_id,
+                            peptide_id: ion.peptide_id,
+                            ion_id: ion.ion_id,
+                            sequence: peptide.sequence.sequence.clone(),
+                            charge: ion.charge,
+                            collision_energy: energy as f32 / 100.0,
+                            scan_start,
+                            scan_end,
+                            mobility: ion.mobility,
+                            precursor_mz,
+                            transmitted_fraction: if weight > 0.0 {
+                                weighted_fraction / weight
+                            } else {
+                                0.0
+                            },
+                        }
+                    },
+                )
+                .collect(),
+        )
+    }
 
-        // go over all frames the ion occurs in
-        for frame in peptide.frame_distribution.occurrence.iter() {
-            // only consider fragment frames
-            if !precursor_frames.contains(frame) {
-                // go over all scans the ion occurs in
-                for scan in &ion.scan_distribution.occurrence {
-                    // check transmission for all precursor ion peaks of the isotopic envelope
-
-                    let precursor_spec = &ion.simulated_spectrum;
-
-                    if transmission.any_transmitted(
-                        *frame as i32,
-                        *scan as i32,
-                        &precursor_spec.mz,
-                        Some(0.5),
-                    ) {
-                        let collision_energy =
-                            collision_energy.get_collision_energy(*frame as i32, *scan as i32);
-                        let quantized_energy = (collision_energy * 100.0).round() as i32;
-
-                        ret_tree.insert((
-                            ion.peptide_id,
-                            ion.ion_id,
-                            peptide.sequence.sequence.clone(),
-                            ion.charge,
-                            quantized_energy,
-                        ));
-                    }
-                }
+    /// collect the transmitted ions of a chunk, ions whose peptide is missing are counted as skipped
+    fn collect_transmitted_chunk<F>(chunk: &[IonSim], map_fn: &F) -> (Vec<TransmittedIon>, usize)
+    where
+        F: Fn(&IonSim) -> Option<Vec<TransmittedIon>>,
+    {
+        let mut transmitted: Vec<TransmittedIon> = Vec::new();
+        let mut skipped = 0;
+        for ion in chunk {
+            match map_fn(ion) {
+                Some(ion_transmitted) => transmitted.extend(ion_transmitted),
+                None => skipped += 1,
             }
         }
-        ret_tree
+        (transmitted, skipped)
     }
 
-    // TODO: take isotopic envelope into account
+    /// Method to get all ions that are transmitted into at least one fragment frame
+    ///
+    /// # Arguments
+    ///
+    /// * `num_threads` - The number of threads to use
+    /// * `dda_mode` - If true, transmission is read from the DDA PASEF meta table, otherwise from the DIA windows
+    /// * `ion_id_filter` - Optional ion ids, only these ions are considered
+    /// * `limit` - Optional maximum number of ions to consider, applied after `ion_id_filter`
+    ///
+    /// # Returns
+    ///
+    /// * The transmitted ions, one per fragment frame, ion and collision energy, ordered by frame, peptide id
+    ///   and charge, followed by the number of ions that were skipped because their peptide id is not in the
+    ///   peptide table
+    ///
     pub fn get_transmitted_ions(
         &self,
         num_threads: usize,
         dda_mode: bool,
-    ) -> (Vec<i32>, Vec<i32>, Vec<String>, Vec<i8>, Vec<f32>) {
+        ion_id_filter: Option<&[u32]>,
+        limit: Option<usize>,
+    ) -> (Vec<TransmittedIon>, usize) {
 
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let thread_pool = parallel::pool(num_threads);
 
         let peptides = self.read_peptides().unwrap();
 
@@ -441,73 +2029,80 @@ impl TimsTofSyntheticsDataHandle {
         let precursor_frames =
             TimsTofSyntheticsDataHandle::build_precursor_frame_id_set(&self.read_frames().unwrap());
 
-        let ions = self.read_ions().unwrap();
+        let mut ions = self.read_ions().unwrap();
+
+        if let Some(ion_ids) = ion_id_filter {
+            let ion_ids: HashSet<u32> = ion_ids.iter().cloned().collect();
+            ions.retain(|ion| ion_ids.contains(&ion.ion_id));
+        }
+
+        if let Some(limit) = limit {
+            ions.truncate(limit);
+        }
+
+        // a few chunks per thread, each chunk collects into its own set
+        let chunk_size = std::cmp::max(ions.len() / (num_threads.max(1) * 4), 1);
 
-        let trees = match dda_mode {
+        let chunks = match dda_mode {
             true => {
                 let transmission = self.get_transmission_dda();
+                let map_fn = |ion: &IonSim| {
+                    TimsTofSyntheticsDataHandle::transmitted_ion(
+                        ion,
+                        &peptide_map,
+                        &precursor_frames,
+                        &transmission,
+                        |frame, scan| {
+                            transmission
+                                .get_collision_energy(frame, scan)
+                                .unwrap_or(0.0)
+                        },
+                    )
+                };
                 thread_pool.install(|| {
-                    ions.par_iter()
-                        .map(|ion| {
-                            TimsTofSyntheticsDataHandle::ion_map_fn_dda(
-                                ion.clone(),
-                                &peptide_map,
-                                &precursor_frames,
-                                &transmission,
-                            )
-                        })
+                    ions.par_chunks(chunk_size)
+                        .map(|chunk| TimsTofSyntheticsDataHandle::collect_transmitted_chunk(chunk, &map_fn))
                         .collect::<Vec<_>>()
-            })
-        },
+                })
+            },
             false => {
                 let transmission = self.get_transmission_dia();
                 let collision_energy = self.get_collision_energy_dia();
+                let map_fn = |ion: &IonSim| {
+                    TimsTofSyntheticsDataHandle::transmitted_ion(
+                        ion,
+                        &peptide_map,
+                        &precursor_frames,
+                        &transmission,
+                        |frame, scan| collision_energy.get_collision_energy(frame, scan),
+                    )
+                };
                 thread_pool.install(|| {
-                    ions.par_iter()
-                        .map(|ion| {
-                            TimsTofSyntheticsDataHandle::ion_map_fn_dia(
-                                ion.clone(),
-                                &peptide_map,
-                                &precursor_frames,
-                                &transmission,
-                                &collision_energy,
-                            )
-                        })
+                    ions.par_chunks(chunk_size)
+                        .map(|chunk| TimsTofSyntheticsDataHandle::collect_transmitted_chunk(chunk, &map_fn))
                         .collect::<Vec<_>>()
                 })
             },
         };
 
-        let mut ret_tree: BTreeSet<(u32, u32, String, i8, i32)> = BTreeSet::new();
-        for tree in trees {
-            ret_tree.extend(tree);
+        let mut transmitted: Vec<TransmittedIon> = Vec::new();
+        let mut num_skipped = 0;
+        for (chunk, skipped) in chunks {
+            transmitted.extend(chunk);
+            num_skipped += skipped;
         }
 
-        let mut ret_peptide_id = Vec::new();
-        let mut ret_ion_id = Vec::new();
-        let mut ret_sequence = Vec::new();
-        let mut ret_charge = Vec::new();
-        let mut ret_energy = Vec::new();
-
-        for (peptide_id, ion_id, sequence, charge, energy) in ret_tree {
-            ret_peptide_id.push(peptide_id as i32);
-            ret_ion_id.push(ion_id as i32);
-            ret_sequence.push(sequence);
-            ret_charge.push(charge);
-            ret_energy.push(energy as f32 / 100.0);
-        }
+        transmitted.sort_by(|a, b| {
+            (a.frame_id, a.peptide_id, a.charge, a.ion_id)
+                .cmp(&(b.frame_id, b.peptide_id, b.charge, b.ion_id))
+                .then(a.collision_energy.total_cmp(&b.collision_energy))
+        });
 
-        (
-            ret_peptide_id,
-            ret_ion_id,
-            ret_sequence,
-            ret_charge,
-            ret_energy,
-        )
+        (transmitted, num_skipped)
     }
 
     /// Method to build a map from peptide id to ions
-    pub fn build_peptide_to_ion_map(ions: &Vec<IonSim>) -> BTreeMap<u32, Vec<IonSim>> {
+    pub fn build_peptide_to_ion_map(ions: &[IonSim]) -> BTreeMap<u32, Vec<IonSim>> {
         let mut ion_map = BTreeMap::new();
         for ion in ions.iter() {
             let ions = ion_map.entry(ion.peptide_id).or_insert_with(Vec::new);
@@ -517,7 +2112,7 @@ impl TimsTofSyntheticsDataHandle {
     }
 
     /// Method to build a map from peptide id to events (absolute number of events in the simulation)
-    pub fn build_peptide_map(peptides: &Vec<PeptidesSim>) -> BTreeMap<u32, PeptidesSim> {
+    pub fn build_peptide_map(peptides: &[PeptidesSim]) -> BTreeMap<u32, PeptidesSim> {
         let mut peptide_map = BTreeMap::new();
         for peptide in peptides.iter() {
             peptide_map.insert(peptide.peptide_id, peptide.clone());
@@ -526,7 +2121,7 @@ impl TimsTofSyntheticsDataHandle {
     }
 
     /// Method to build a set of precursor frame ids, can be used to check if a frame is a precursor frame
-    pub fn build_precursor_frame_id_set(frames: &Vec<FramesSim>) -> HashSet<u32> {
+    pub fn build_precursor_frame_id_set(frames: &[FramesSim]) -> HashSet<u32> {
         frames
             .iter()
             .filter(|frame| frame.parse_ms_type() == MsType::Precursor)
@@ -535,7 +2130,7 @@ impl TimsTofSyntheticsDataHandle {
     }
 
     // Method to build a map from peptide id to events (absolute number of events in the simulation)
-    pub fn build_peptide_to_events(peptides: &Vec<PeptidesSim>) -> BTreeMap<u32, f32> {
+    pub fn build_peptide_to_events(peptides: &[PeptidesSim]) -> BTreeMap<u32, f32> {
         let mut peptide_to_events = BTreeMap::new();
         for peptide in peptides.iter() {
             peptide_to_events.insert(peptide.peptide_id, peptide.events);
@@ -544,7 +2139,7 @@ impl TimsTofSyntheticsDataHandle {
     }
 
     // Method to build a map from frame id to retention time
-    pub fn build_frame_to_rt(frames: &Vec<FramesSim>) -> BTreeMap<u32, f32> {
+    pub fn build_frame_to_rt(frames: &[FramesSim]) -> BTreeMap<u32, f32> {
         let mut frame_to_rt = BTreeMap::new();
         for frame in frames.iter() {
             frame_to_rt.insert(frame.frame_id, frame.time);
@@ -553,7 +2148,7 @@ impl TimsTofSyntheticsDataHandle {
     }
 
     // Method to build a map from scan id to mobility
-    pub fn build_scan_to_mobility(scans: &Vec<ScansSim>) -> BTreeMap<u32, f32> {
+    pub fn build_scan_to_mobility(scans: &[ScansSim]) -> BTreeMap<u32, f32> {
         let mut scan_to_mobility = BTreeMap::new();
         for scan in scans.iter() {
             scan_to_mobility.insert(scan.scan, scan.mobility);
@@ -561,7 +2156,7 @@ impl TimsTofSyntheticsDataHandle {
         scan_to_mobility
     }
     pub fn build_frame_to_abundances(
-        peptides: &Vec<PeptidesSim>,
+        peptides: &[PeptidesSim],
     ) -> BTreeMap<u32, (Vec<u32>, Vec<f32>)> {
         let mut frame_to_abundances = BTreeMap::new();
 
@@ -585,21 +2180,14 @@ impl TimsTofSyntheticsDataHandle {
 
         frame_to_abundances
     }
-    pub fn build_peptide_to_ions(
-        ions: &Vec<IonSim>,
-    ) -> BTreeMap<
-        u32,
-        (
-            Vec<f32>,
-            Vec<Vec<u32>>,
-            Vec<Vec<f32>>,
-            Vec<i8>,
-            Vec<MzSpectrum>,
-        ),
-    > {
+    pub fn build_peptide_to_ions(ions: &[IonSim]) -> PeptideToIons {
+        Self::build_peptide_to_ions_from(ions.iter())
+    }
+
+    fn build_peptide_to_ions_from<'a>(ions: impl Iterator<Item = &'a IonSim>) -> PeptideToIons {
         let mut peptide_to_ions = BTreeMap::new();
 
-        for ion in ions.iter() {
+        for ion in ions {
             let peptide_id = ion.peptide_id;
             let abundance = ion.relative_abundance;
             let scan_occurrence = ion.scan_distribution.occurrence.clone();
@@ -619,23 +2207,60 @@ impl TimsTofSyntheticsDataHandle {
 
         peptide_to_ions
     }
+
+    /// Same as `build_frame_to_abundances`, peptides are split into one chunk per thread and the
+    /// partial maps are merged in chunk order, so the peptide order within a frame does not change
+    pub fn build_frame_to_abundances_par(
+        peptides: &[PeptidesSim],
+        num_threads: usize,
+    ) -> BTreeMap<u32, (Vec<u32>, Vec<f32>)> {
+        merge_chunked(
+            peptides,
+            num_threads,
+            Self::build_frame_to_abundances,
+            |merged, (occurrences, abundances)| {
+                merged.0.extend(occurrences);
+                merged.1.extend(abundances);
+            },
+        )
+    }
+
+    /// Same as `build_peptide_to_ions`, built chunk-wise in parallel like `build_frame_to_abundances_par`
+    pub fn build_peptide_to_ions_par(ions: &[IonSim], num_threads: usize) -> PeptideToIons {
+        merge_chunked(
+            ions,
+            num_threads,
+            Self::build_peptide_to_ions,
+            |merged, partial| {
+                merged.0.extend(partial.0);
+                merged.1.extend(partial.1);
+                merged.2.extend(partial.2);
+                merged.3.extend(partial.3);
+                merged.4.extend(partial.4);
+            },
+        )
+    }
+
+    /// `build_peptide_to_ions` from the ions grouped by peptide, as stored by the precursor frame builder
+    pub fn build_peptide_to_ions_from_map(ions: &BTreeMap<u32, Vec<IonSim>>) -> PeptideToIons {
+        Self::build_peptide_to_ions_from(ions.values().flatten())
+    }
+
     pub fn build_fragment_ions(
         peptides_sim: &BTreeMap<u32, PeptidesSim>,
         fragment_ions: &Vec<FragmentIonSim>,
         num_threads: usize,
-    ) -> BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrum>)> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+    ) -> FragmentIonMap<MzSpectrum> {
+        let thread_pool = parallel::pool(num_threads);
         let fragment_ion_map = thread_pool.install(|| {
             fragment_ions
                 .par_iter()
                 .map(|fragment_ion| {
+                    // fragment ion collision energies are stored normalized to eV / 100
                     let key = (
                         fragment_ion.peptide_id,
                         fragment_ion.charge,
-                        (fragment_ion.collision_energy * 1e3).round() as i32,
+                        quantize_collision_energy(fragment_ion.collision_energy * 1e2),
                     );
 
                     let value = peptides_sim
@@ -648,7 +2273,9 @@ impl TimsTofSyntheticsDataHandle {
                             fragment_ion.to_dense(174),
                             true,
                             true,
-                        );
+                            None,
+                        )
+                        .expect("dense fragment ion intensities have the prosit length");
 
                     let fragment_ions: Vec<MzSpectrum> = value
                         .peptide_ions
@@ -669,20 +2296,17 @@ impl TimsTofSyntheticsDataHandle {
         peptides_sim: &BTreeMap<u32, PeptidesSim>,
         fragment_ions: &Vec<FragmentIonSim>,
         num_threads: usize,
-    ) -> BTreeMap<(u32, i8, i32), (PeptideProductIonSeriesCollection, Vec<MzSpectrumAnnotated>)>
-    {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+    ) -> FragmentIonMap<MzSpectrumAnnotated> {
+        let thread_pool = parallel::pool(num_threads);
         let fragment_ion_map = thread_pool.install(|| {
             fragment_ions
                 .par_iter()
                 .map(|fragment_ion| {
+                    // fragment ion collision energies are stored normalized to eV / 100
                     let key = (
                         fragment_ion.peptide_id,
                         fragment_ion.charge,
-                        (fragment_ion.collision_energy * 1e3).round() as i32,
+                        quantize_collision_energy(fragment_ion.collision_energy * 1e2),
                     );
 
                     let value = peptides_sim
@@ -695,7 +2319,9 @@ impl TimsTofSyntheticsDataHandle {
                             fragment_ion.to_dense(174),
                             true,
                             true,
-                        );
+                            None,
+                        )
+                        .expect("dense fragment ion intensities have the prosit length");
 
                     let fragment_ions: Vec<MzSpectrumAnnotated> = value
                         .peptide_ions
@@ -712,3 +2338,689 @@ impl TimsTofSyntheticsDataHandle {
         fragment_ion_map
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::fixtures::{in_memory_handle, ion, peptide, SyntheticsFile};
+    use mscore::algorithm::mobility::predict_inverse_mobility;
+
+    /// peptides table with PEPTIDEK twice (ids 1 and 3) and a decoy PEPTIDEK that is not a duplicate
+    fn fixture_with_duplicate() -> TimsTofSyntheticsDataHandle {
+        let handle = in_memory_handle();
+        handle
+            .insert_peptides(&[
+                peptide(1, 100.0, vec![1, 2, 3], vec![0.25, 0.5, 0.25]),
+                PeptidesSim { decoy: true, ..peptide(2, 100.0, vec![1, 2, 3], vec![0.25, 0.5, 0.25]) },
+                PeptidesSim {
+                    protein_id: 1,
+                    proteins: "P2".to_string(),
+                    retention_time: 10.5,
+                    ..peptide(3, 300.0, vec![2, 3, 4], vec![0.5, 0.25, 0.25])
+                },
+            ])
+            .unwrap();
+        handle
+    }
+
+    #[test]
+    fn test_duplicates_keep_first() {
+        let peptides = fixture_with_duplicate()
+            .read_peptides_deduplicated(DuplicatePeptideStrategy::KeepFirst)
+            .unwrap();
+        assert_eq!(peptides.iter().map(|p| p.peptide_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(peptides[0].events, 100.0);
+        assert_eq!(peptides[0].frame_distribution.occurrence, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_duplicates_sum_events() {
+        let peptides = fixture_with_duplicate()
+            .read_peptides_deduplicated(DuplicatePeptideStrategy::SumEvents)
+            .unwrap();
+        assert_eq!(peptides.iter().map(|p| p.peptide_id).collect::<Vec<_>>(), vec![1, 2]);
+
+        let merged = &peptides[0];
+        assert_eq!(merged.events, 400.0);
+        assert_eq!((merged.frame_start, merged.frame_end), (1, 4));
+        assert_eq!(merged.frame_distribution.occurrence, vec![1, 2, 3, 4]);
+
+        // abundance times events per frame equals the summed signal of both rows
+        let signal: Vec<f32> = merged.frame_distribution.abundance.iter().map(|a| a * merged.events).collect();
+        let expected = [25.0, 50.0 + 150.0, 25.0 + 75.0, 75.0];
+        for (s, e) in signal.iter().zip(expected.iter()) {
+            assert!((s - e).abs() < 1e-3);
+        }
+
+        // the decoy is untouched
+        assert_eq!(peptides[1].events, 100.0);
+    }
+
+    #[test]
+    fn test_duplicates_error() {
+        let handle = fixture_with_duplicate();
+        let result = handle.read_peptides_deduplicated(DuplicatePeptideStrategy::Error);
+        match result {
+            Err(SyntheticsError::DuplicatePeptide(e)) => assert_eq!(e.peptide_ids, (1, 3)),
+            other => panic!("expected a duplicate peptide error, got {:?}", other.map(|p| p.len())),
+        }
+        // reading without deduplication still returns all rows
+        assert_eq!(handle.read_peptides().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_collapse_duplicate_peptides() {
+        let handle = fixture_with_duplicate();
+        handle
+            .insert_ions(&[(1, 1), (2, 2), (3, 3), (4, 3)].map(|(ion_id, peptide_id)| {
+                ion(ion_id, peptide_id, 2, 464.73, 1.0, vec![], vec![])
+            }))
+            .unwrap();
+        handle
+            .insert_fragment_ions(&[(1, 1), (3, 3), (3, 4)].map(|(peptide_id, ion_id)| {
+                FragmentIonSim::new(peptide_id, ion_id, 0.3, 2, vec![], vec![])
+            }))
+            .unwrap();
+        let count = |sql: &str| handle.connection.query_row(sql, [], |row| row.get::<_, u32>(0)).unwrap();
+
+        // nothing is written if duplicates are an error
+        assert!(matches!(
+            handle.collapse_duplicate_peptides(DuplicatePeptideStrategy::Error),
+            Err(SyntheticsError::DuplicatePeptide(_))
+        ));
+        assert_eq!(count("SELECT COUNT(*) FROM ions"), 4);
+
+        assert_eq!(handle.collapse_duplicate_peptides(DuplicatePeptideStrategy::SumEvents).unwrap(), 1);
+        let peptides = handle.read_peptides().unwrap();
+        assert_eq!(peptides.iter().map(|p| p.peptide_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(peptides[0].events, 400.0);
+        assert_eq!(peptides[0].frame_distribution.occurrence, vec![1, 2, 3, 4]);
+        assert_eq!(count("SELECT COUNT(*) FROM ions WHERE peptide_id = 3"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM ions"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM fragment_ions"), 1);
+
+        // the table no longer has duplicates
+        assert_eq!(handle.collapse_duplicate_peptides(DuplicatePeptideStrategy::Error).unwrap(), 0);
+    }
+
+    /// scans 1 to 5 from 1.25 down to 0.75 1/K0, exact as f32 so ions at 1.0 are centered in scan 3
+    fn fixture_scans() -> Vec<ScansSim> {
+        (1..=5).map(|scan| ScansSim::new(scan, 1.375 - 0.125 * scan as f32)).collect()
+    }
+
+    #[test]
+    fn test_write_scan_distributions() {
+        let handle = in_memory_handle();
+        handle.insert_scans(&fixture_scans()).unwrap();
+        handle
+            .insert_ions(&[ion(1, 1, 2, 464.7, 1.0, vec![], vec![]), ion(2, 1, 3, 310.1, 2.0, vec![], vec![])])
+            .unwrap();
+
+        assert_eq!(handle.write_scan_distributions(0.05, None, 3.0, 1).unwrap(), 1);
+
+        let ions = handle.read_ions().unwrap();
+        assert_eq!(ions[0].scan_distribution.occurrence, vec![2, 3, 4]);
+        let abundance = &ions[0].scan_distribution.abundance;
+        assert!((abundance.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!((abundance[0] - abundance[2]).abs() < 1e-5 && abundance[1] > abundance[0]);
+        assert!(ions[1].scan_distribution.occurrence.is_empty());
+    }
+
+    /// frames every second and peptides eluting early, in the middle and after the run
+    fn fixture_elution() -> TimsTofSyntheticsDataHandle {
+        let handle = in_memory_handle();
+        let frames: Vec<FramesSim> = (1..=100).map(|frame_id| FramesSim::new(frame_id, frame_id as f32, 0)).collect();
+        handle.insert_frames(&frames).unwrap();
+        handle
+            .insert_peptides(&[(1, 20.0), (2, 60.0), (3, 500.0)].map(|(peptide_id, retention_time)| {
+                PeptidesSim { retention_time, ..peptide(peptide_id, 100.0, vec![], vec![]) }
+            }))
+            .unwrap();
+        // the elution widths are left empty by insert_peptides
+        handle
+            .connection
+            .execute_batch(
+                "UPDATE peptides SET rt_sigma = 1.0, rt_lambda = 2.0;
+                 UPDATE peptides SET rt_sigma = 2.0, rt_lambda = 1.0 WHERE peptide_id = 2;",
+            )
+            .unwrap();
+        handle
+    }
+
+    #[test]
+    fn test_write_frame_distributions() {
+        let handle = fixture_elution();
+        handle
+            .write_frame_distributions(&[1, 3], &[vec![4, 5, 6], vec![]], &[vec![0.25, 0.5, 0.25], vec![]])
+            .unwrap();
+
+        let peptides = handle.read_peptides().unwrap();
+        assert_eq!((peptides[0].frame_start, peptides[0].frame_end), (4, 6));
+        assert_eq!(peptides[0].frame_distribution.occurrence, vec![4, 5, 6]);
+        assert_eq!(peptides[0].frame_distribution.abundance, vec![0.25, 0.5, 0.25]);
+        assert!(peptides[2].frame_distribution.occurrence.is_empty());
+
+        assert!(handle.write_frame_distributions(&[1], &[vec![4]], &[]).is_err());
+        assert!(handle.update_scan_distributions(&[1, 2], &[vec![4]], &[vec![1.0]]).is_err());
+    }
+
+    #[test]
+    fn test_populate_elution_profiles() {
+        let handle = fixture_elution();
+        let num_empty = handle
+            .populate_elution_profiles(ElutionParameter::Stored, ElutionParameter::Stored, 0.99, 0.01, 2)
+            .unwrap();
+        assert_eq!(num_empty, 1);
+
+        let peptides = handle.read_peptides().unwrap();
+        for (peptide, apex) in peptides.iter().zip([20, 60]) {
+            let distribution = &peptide.frame_distribution;
+            assert!(distribution.occurrence.contains(&apex));
+            assert_eq!(distribution.occurrence.len(), distribution.abundance.len());
+            assert_eq!(peptide.frame_start, distribution.occurrence[0]);
+            let total: f32 = distribution.abundance.iter().sum();
+            assert!(total > 0.95 && total <= 1.0 + 1e-4);
+        }
+        // peptide 3 elutes after the last frame
+        assert!(peptides[2].frame_distribution.abundance.iter().all(|a| *a == 0.0));
+
+        // a constant width replaces the stored one
+        handle
+            .populate_elution_profiles(ElutionParameter::Constant(4.0), ElutionParameter::Stored, 0.99, 0.01, 1)
+            .unwrap();
+        let wider = handle.read_peptides().unwrap();
+        assert!(wider[1].frame_distribution.occurrence.len() > peptides[1].frame_distribution.occurrence.len());
+    }
+
+    #[test]
+    fn test_create_schema_and_insert() {
+        let handle = in_memory_handle();
+        // creating the schema again keeps the tables
+        handle.insert_scans(&[ScansSim::new(1, 1.3), ScansSim::new(2, 1.2)]).unwrap();
+        handle.create_schema().unwrap();
+        assert!(handle.validate_schema().unwrap().is_valid());
+
+        // rows are appended chunk by chunk
+        handle.insert_frames(&[FramesSim::new(1, 0.5, 0)]).unwrap();
+        handle.insert_frames(&[FramesSim::new(2, 1.0, 9)]).unwrap();
+        handle
+            .insert_peptides(&[PeptidesSim::new(
+                0, 1, "PEPTIDEK".to_string(), "P1".to_string(), false, 0, Some(true), None,
+                927.45, 10.0, 100.0, 1, 2, vec![1, 2], vec![0.25, 0.75],
+            )])
+            .unwrap();
+        handle
+            .insert_ions(&[IonSim::new(
+                1, 1, "PEPTIDEK".to_string(), 2, 1.0, 1.05,
+                MzSpectrum::new(vec![464.73], vec![1.0]), vec![1, 2], vec![0.5, 0.5],
+            )])
+            .unwrap();
+        handle.insert_fragment_ions(&[FragmentIonSim::new(1, 1, 30.0, 2, vec![2, 30], vec![1.0, 0.2])]).unwrap();
+        handle
+            .insert_window_group_settings(&[WindowGroupSettingsSim::new(1, 10, 20, 500.0, 25.0, 30.0)])
+            .unwrap();
+        handle.insert_frame_to_window_group(&[FrameToWindowGroupSim::new(2, 1)]).unwrap();
+
+        assert_eq!(handle.read_frames().unwrap().iter().map(|f| f.ms_type).collect::<Vec<_>>(), vec![0, 9]);
+        assert_eq!(handle.read_scans().unwrap().len(), 2);
+
+        let peptides = handle.read_peptides().unwrap();
+        assert_eq!(peptides[0].sequence.sequence, "PEPTIDEK");
+        assert_eq!((peptides[0].n_term, peptides[0].c_term), (Some(true), None));
+        assert_eq!((peptides[0].frame_start, peptides[0].frame_end), (1, 2));
+        assert_eq!(peptides[0].frame_distribution.abundance, vec![0.25, 0.75]);
+
+        let ions = handle.read_ions().unwrap();
+        assert_eq!(ions[0].simulated_spectrum.mz, vec![464.73]);
+        assert_eq!(ions[0].scan_distribution.occurrence, vec![1, 2]);
+        let mz: f64 = handle.connection.query_row("SELECT mz FROM ions", [], |row| row.get(0)).unwrap();
+        assert!((mz - 464.73).abs() < 0.01);
+
+        let fragment_ions = handle.read_fragment_ions().unwrap();
+        assert_eq!((fragment_ions[0].indices.clone(), fragment_ions[0].values.clone()), (vec![2, 30], vec![1.0, 0.2]));
+        assert_eq!(handle.read_window_group_settings().unwrap()[0].isolation_mz, 500.0);
+        assert_eq!(handle.read_frame_to_window_group().unwrap()[0].frame_id, 2);
+    }
+
+    #[test]
+    fn test_insert_dia_scheme() {
+        let handle = in_memory_handle();
+        let frames: Vec<FramesSim> = (1..=9).map(|id| FramesSim::new(id, id as f32, if id % 4 == 1 { 0 } else { 9 })).collect();
+        handle.insert_frames(&frames).unwrap();
+        handle.insert_window_group_settings(&[WindowGroupSettingsSim::new(7, 0, 10, 500.0, 25.0, 30.0)]).unwrap();
+
+        let (settings, cycle) = crate::sim::dia_scheme::generate_dia_scheme(400.0, 1000.0, 50.0, 0.0, 100, 4, true).unwrap();
+        assert_eq!(cycle.len(), 3);
+        handle.insert_dia_scheme(&settings, &cycle).unwrap();
+
+        assert_eq!(handle.read_window_group_settings().unwrap().len(), settings.len());
+        let assigned: Vec<(u32, u32)> = handle.read_frame_to_window_group().unwrap().iter().map(|e| (e.frame_id, e.window_group)).collect();
+        assert_eq!(assigned, vec![(2, 1), (3, 2), (4, 3), (6, 1), (7, 2), (8, 3)]);
+
+        let mut broken = settings.clone();
+        broken[1].scan_start = broken[0].scan_start;
+        assert!(handle.insert_dia_scheme(&broken, &cycle).is_err());
+        assert!(handle.insert_dia_scheme(&settings, &[FrameToWindowGroupSim::new(2, 42)]).is_err());
+        assert_eq!(handle.read_window_group_settings().unwrap().len(), settings.len());
+    }
+
+    #[test]
+    fn test_collision_energy_ramp() {
+        let handle = in_memory_handle();
+        handle.insert_scans(&[ScansSim::new(1, 1.4), ScansSim::new(2, 1.0), ScansSim::new(3, 0.6)]).unwrap();
+        assert_eq!(handle.read_collision_energy_ramp().unwrap(), None);
+        assert!(matches!(handle.get_collision_energy_source().unwrap(), CollisionEnergySource::WindowGroups(_)));
+
+        handle.write_collision_energy_ramp(&[(1.4, 45.0), (0.6, 20.0)]).unwrap();
+        assert!(handle.validate_schema().unwrap().is_valid());
+        assert_eq!(handle.read_collision_energy_ramp().unwrap(), Some(vec![(0.6, 20.0), (1.4, 45.0)]));
+        let source = handle.get_collision_energy_source().unwrap();
+        assert!(matches!(source, CollisionEnergySource::MobilityRamp(_)));
+        let energies = source.get_collision_energies(5, &[1, 2, 3]);
+        assert!((energies[0] - 45.0).abs() < 1e-4 && (energies[1] - 32.5).abs() < 1e-4 && (energies[2] - 20.0).abs() < 1e-4);
+
+        // an empty ramp cannot be turned into collision energies
+        handle.write_collision_energy_ramp(&[]).unwrap();
+        assert!(handle.get_collision_energy_source().is_err());
+
+        handle.clear_collision_energy_ramp().unwrap();
+        assert_eq!(handle.read_collision_energy_ramp().unwrap(), None);
+    }
+
+    #[test]
+    fn test_write_ion_mobilities() {
+        let handle = in_memory_handle();
+        handle
+            .insert_ions(&[ion(1, 1, 2, 464.73, 0.0, vec![], vec![]), ion(2, 1, 3, 310.16, 0.0, vec![], vec![])])
+            .unwrap();
+        let model = MobilityModel::default();
+        handle.write_ion_mobilities(&model, 1).unwrap();
+
+        let mobilities: Vec<f64> = handle
+            .connection
+            .prepare("SELECT inv_mobility_gru_predictor FROM ions ORDER BY ion_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|mobility| mobility.unwrap())
+            .collect();
+
+        // both ions of the same peptide, the higher charge is more mobile
+        let mass = PeptideSequence::new("PEPTIDEK".to_string(), None).mono_isotopic_mass();
+        assert!((mobilities[0] - predict_inverse_mobility(mass, 2, &model)).abs() < 1e-6);
+        assert!(mobilities[1] < mobilities[0]);
+    }
+
+    #[test]
+    fn test_parallel_lookup_maps_match_sequential() {
+        let peptides = fixture_with_duplicate().read_peptides().unwrap();
+        for num_threads in [1, 2, 4] {
+            assert_eq!(
+                TimsTofSyntheticsDataHandle::build_frame_to_abundances_par(&peptides, num_threads),
+                TimsTofSyntheticsDataHandle::build_frame_to_abundances(&peptides)
+            );
+        }
+
+        let ions: Vec<IonSim> = (0..7)
+            .map(|i| {
+                IonSim::new(
+                    i,
+                    i % 3,
+                    "PEPTIDEK".to_string(),
+                    (i % 2 + 2) as i8,
+                    0.1 * i as f32,
+                    1.0,
+                    MzSpectrum::new(vec![400.0 + i as f64], vec![1.0]),
+                    vec![i, i + 1],
+                    vec![0.5, 0.5],
+                )
+            })
+            .collect();
+        let sequential = TimsTofSyntheticsDataHandle::build_peptide_to_ions(&ions);
+        for num_threads in [1, 2, 4] {
+            let parallel =
+                TimsTofSyntheticsDataHandle::build_peptide_to_ions_par(&ions, num_threads);
+            assert_eq!(
+                parallel.keys().collect::<Vec<_>>(),
+                sequential.keys().collect::<Vec<_>>()
+            );
+            for (a, b) in parallel.values().zip(sequential.values()) {
+                assert_eq!((&a.0, &a.1, &a.2, &a.3), (&b.0, &b.1, &b.2, &b.3));
+                assert_eq!(
+                    a.4.iter().map(|s| s.mz.clone()).collect::<Vec<_>>(),
+                    b.4.iter().map(|s| s.mz.clone()).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    /// ions table with `num_ions` ions, ion `broken_id` has invalid JSON as scan occurrence
+    fn fixture_ions(num_ions: u32, broken_id: Option<u32>) -> TimsTofSyntheticsDataHandle {
+        let handle = in_memory_handle();
+        let ions: Vec<IonSim> = (0..num_ions)
+            .map(|ion_id| {
+                ion(ion_id, ion_id / 3, 2, 400.0 + ion_id as f64, 1.0, vec![ion_id, ion_id + 1], vec![0.5, 0.5])
+            })
+            .collect();
+        handle.insert_ions(&ions).unwrap();
+        if let Some(ion_id) = broken_id {
+            handle
+                .connection
+                .execute("UPDATE ions SET scan_occurrence = '[1, 2' WHERE ion_id = ?1", [ion_id])
+                .unwrap();
+        }
+        handle
+    }
+
+    #[test]
+    fn test_read_ions_keeps_table_order() {
+        let num_ions = 3 * READ_CHUNK_SIZE as u32 + 17;
+        let ions = fixture_ions(num_ions, None).read_ions().unwrap();
+        assert_eq!(ions.len(), num_ions as usize);
+        for (i, ion) in ions.iter().enumerate() {
+            assert_eq!(ion.ion_id, i as u32);
+            assert_eq!(
+                ion.scan_distribution.occurrence,
+                vec![i as u32, i as u32 + 1]
+            );
+            assert_eq!(ion.simulated_spectrum.mz, vec![400.0 + i as f64]);
+        }
+    }
+
+    #[test]
+    fn test_read_ions_reports_broken_row() {
+        let error = fixture_ions(5000, Some(4321)).read_ions().unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("ions.scan_occurrence"), "{message}");
+        assert!(message.contains("id 4321"), "{message}");
+        match error {
+            SyntheticsError::ColumnParse(e) => assert_eq!((e.table, e.column, e.id), ("ions", "scan_occurrence", 4321)),
+            other => panic!("expected a column parse error, got {other}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let handle = in_memory_handle();
+        assert!(handle.validate_schema().unwrap().is_valid());
+
+        handle.connection.execute_batch("DROP TABLE frames; DROP TABLE scans; DROP TABLE ions;").unwrap();
+        let report = handle.validate_schema().unwrap();
+        assert_eq!(report.missing_tables, vec!["frames", "scans", "ions"]);
+        assert!(report.column_mismatches.is_empty());
+        assert!(handle
+            .check_schema()
+            .unwrap_err()
+            .to_string()
+            .contains("frames, scans, ions"));
+
+        // tables written with other columns are left as they are when the schema is completed
+        handle
+            .connection
+            .execute_batch(
+                "DROP TABLE fragment_ions;
+                 CREATE TABLE fragment_ions (peptide_id INTEGER, ion_id INTEGER, collision_energy REAL);
+                 CREATE TABLE ions (
+                    ion_id INTEGER, peptide_id INTEGER, sequence TEXT, charge INTEGER, mz REAL,
+                    relative_abundance REAL, inv_mobility_gru_predictor REAL, inv_mobility_gru_predictor_std REAL,
+                    scan_occurrence TEXT, simulated_spectrum TEXT, scan_abundance TEXT
+                 );",
+            )
+            .unwrap();
+        handle.create_schema().unwrap();
+        let report = handle.validate_schema().unwrap();
+        assert!(report.missing_tables.is_empty());
+        let mismatches: Vec<String> = report
+            .column_mismatches
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                "ions column 8: expected simulated_spectrum, found scan_occurrence",
+                "ions column 9: expected scan_occurrence, found simulated_spectrum",
+                "fragment_ions column 3 (charge) is missing",
+                "fragment_ions column 4 (indices) is missing",
+                "fragment_ions column 5 (values) is missing",
+            ]
+        );
+
+        handle.connection.execute_batch("DROP TABLE peptides;").unwrap();
+        assert_eq!(handle.validate_schema().unwrap().missing_tables, vec!["peptides"]);
+    }
+
+    #[test]
+    fn test_open_file_database() {
+        let file = SyntheticsFile::new("handle");
+        {
+            let handle = file.handle();
+            let journal_mode: String = handle
+                .connection
+                .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(journal_mode, "wal");
+            handle.insert_scans(&[ScansSim::new(1, 1.3)]).unwrap();
+        }
+
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(&file.path).unwrap();
+        assert_eq!(handle.read_scans().unwrap().len(), 1);
+        assert!(handle
+            .connection
+            .execute("INSERT INTO scans VALUES (2, 1.2)", [])
+            .is_err());
+    }
+
+    #[test]
+    fn test_export_spectral_library() {
+        let handle = fixture_with_duplicate();
+        handle
+            .insert_ions(&[
+                ion(10, 1, 2, 464.73, 1.05, vec![], vec![]),
+                ion(20, 2, 2, 464.73, 1.05, vec![], vec![]),
+                ion(30, 3, 3, 310.16, 0.85, vec![], vec![]),
+            ])
+            .unwrap();
+        handle
+            .insert_fragment_ions(&[
+                FragmentIonSim::new(1, 10, 0.3, 2, vec![2, 30], vec![1.0, 0.2]),
+                FragmentIonSim::new(2, 20, 0.3, 2, vec![2], vec![1.0]),
+                FragmentIonSim::new(1, 10, 0.2, 2, vec![2, 30], vec![0.0, 0.6]),
+                FragmentIonSim::new(3, 30, 0.3, 3, vec![], vec![]),
+            ])
+            .unwrap();
+        let mz: f64 = handle.connection.query_row("SELECT mz FROM ions WHERE ion_id = 10", [], |row| row.get(0)).unwrap();
+        let path = std::env::temp_dir().join(format!("rustdf_library_{}.tsv", std::process::id()));
+
+        // the ion without fragment intensities is not written
+        assert_eq!(handle.export_spectral_library(&path, SpectralLibraryFormat::DiaNn, false).unwrap(), 1);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let rows: Vec<Vec<&str>> = text.lines().skip(1).map(|line| line.split('\t').collect()).collect();
+        // intensities are averaged over both collision energies, y3 0.5 and b2 0.4
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0][7], rows[0][9], rows[0][6]), ("b", "2", "0.800000"));
+        assert_eq!((rows[1][7], rows[1][9], rows[1][6]), ("y", "3", "1.000000"));
+        assert_eq!(rows[1][1], format!("{:.6}", mz));
+        assert_eq!((rows[1][2], rows[1][3], rows[1][4]), ("2", "10.0000", "1.050000"));
+
+        assert_eq!(handle.export_spectral_library(&path, SpectralLibraryFormat::OpenSwath, true).unwrap(), 2);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("ModifiedPeptideSequence"));
+        assert_eq!(text.lines().filter(|line| line.ends_with("\t1")).count(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_to_binary() {
+        let handle = fixture_ions(5000, None);
+        handle.insert_scans(&fixture_scans()).unwrap();
+        handle.insert_fragment_ions(&[FragmentIonSim::new(1, 3, 0.3, 1, vec![2, 7], vec![0.75, 0.25])]).unwrap();
+        let path = std::env::temp_dir().join(format!("rustdf_binary_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(handle.migrate_to_binary(&path).unwrap(), 5001);
+        assert!(handle.migrate_to_binary(&path).is_err());
+
+        let migrated = TimsTofSyntheticsDataHandle::new(&path).unwrap();
+        assert_eq!(handle.storage_format("ions").unwrap(), StorageFormat::Json);
+        assert_eq!(migrated.storage_format("ions").unwrap(), StorageFormat::Binary);
+        assert_eq!(migrated.storage_format("fragment_ions").unwrap(), StorageFormat::Binary);
+
+        let (json, binary) = (handle.read_ions().unwrap(), migrated.read_ions().unwrap());
+        assert_eq!(json.len(), binary.len());
+        for (a, b) in json.iter().zip(binary.iter()) {
+            assert_eq!(a.ion_id, b.ion_id);
+            assert_eq!(a.simulated_spectrum.mz, b.simulated_spectrum.mz);
+            assert_eq!(a.scan_distribution.occurrence, b.scan_distribution.occurrence);
+            assert_eq!(a.scan_distribution.abundance, b.scan_distribution.abundance);
+        }
+        let fragment_ions = migrated.read_fragment_ions().unwrap();
+        assert_eq!((fragment_ions[0].indices.clone(), fragment_ions[0].values.clone()), (vec![2, 7], vec![0.75, 0.25]));
+
+        // scan distributions are written in the format of the table
+        migrated.write_scan_distributions(0.05, None, 3.0, 1).unwrap();
+        let ions = migrated.read_ions().unwrap();
+        assert_eq!(ions[0].scan_distribution.occurrence, vec![2, 3, 4]);
+
+        drop(migrated);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        // nothing is left behind if a row cannot be converted
+        let broken = fixture_ions(10, Some(4));
+        let error = broken.migrate_to_binary(&path).unwrap_err();
+        assert!(error.to_string().contains("id 4"), "{error}");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_duplicate_strategy_from_str() {
+        assert_eq!("keep_first".parse::<DuplicatePeptideStrategy>(), Ok(DuplicatePeptideStrategy::KeepFirst));
+        assert_eq!("SumEvents".parse::<DuplicatePeptideStrategy>(), Ok(DuplicatePeptideStrategy::SumEvents));
+        assert!("sum".parse::<DuplicatePeptideStrategy>().is_err());
+        assert_eq!(DuplicatePeptideStrategy::default(), DuplicatePeptideStrategy::Error);
+    }
+
+    /// transmits m/z below 500 in scans 2 and 3 of every frame
+    struct LowMassWindow;
+
+    impl IonTransmission for LowMassWindow {
+        fn apply_transmission(&self, _frame_id: i32, scan_id: i32, mz: &Vec<f64>) -> Vec<f64> {
+            mz.iter()
+                .map(|&mz| {
+                    if (2..=3).contains(&scan_id) && mz < 500.0 {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_transmitted_ion() {
+        let peptide_map: BTreeMap<u32, PeptidesSim> = fixture_with_duplicate()
+            .read_peptides()
+            .unwrap()
+            .into_iter()
+            .map(|peptide| (peptide.peptide_id, peptide))
+            .collect();
+        let precursor_frames: HashSet<u32> = HashSet::from([1]);
+        let ion = |peptide_id| {
+            IonSim::new(
+                7,
+                peptide_id,
+                "PEPTIDEK".to_string(),
+                2,
+                1.0,
+                1.1,
+                MzSpectrum::new(vec![464.7, 465.2, 600.0], vec![2.0, 1.0, 1.0]),
+                vec![1, 2, 3],
+                vec![0.2, 0.5, 0.3],
+            )
+        };
+
+        let transmitted = TimsTofSyntheticsDataHandle::transmitted_ion(
+            &ion(1),
+            &peptide_map,
+            &precursor_frames,
+            &LowMassWindow,
+            |frame, _| frame as f64 * 10.0,
+        )
+        .unwrap();
+
+        // frame 1 is a precursor frame, scan 1 transmits nothing
+        assert_eq!(
+            transmitted.iter().map(|t| t.frame_id).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(transmitted[0].collision_energy, 20.0);
+        assert_eq!(transmitted[1].collision_energy, 30.0);
+        for t in &transmitted {
+            assert_eq!((t.peptide_id, t.ion_id, t.charge), (1, 7, 2));
+            assert_eq!((t.scan_start, t.scan_end), (2, 3));
+            assert_eq!(t.mobility, 1.1);
+            assert!((t.precursor_mz - (927.45 + 2.0 * MASS_PROTON) / 2.0).abs() < 1e-3);
+            assert!((t.transmitted_fraction - 0.75).abs() < 1e-9);
+        }
+
+        assert!(TimsTofSyntheticsDataHandle::transmitted_ion(
+            &ion(42),
+            &peptide_map,
+            &precursor_frames,
+            &LowMassWindow,
+            |_, _| 0.0,
+        )
+        .is_none());
+    }
+
+    /// transmits m/z below 465 in scan 2 with probability 0.9, everything else below 500 in scans 2 and 3 with 0.4,
+    /// the edge of the window
+    struct SoftEdgeWindow;
+
+    impl IonTransmission for SoftEdgeWindow {
+        fn apply_transmission(&self, _frame_id: i32, scan_id: i32, mz: &Vec<f64>) -> Vec<f64> {
+            mz.iter()
+                .map(|&mz| match (scan_id, mz) {
+                    (2, mz) if mz < 465.0 => 0.9,
+                    (2..=3, mz) if mz < 500.0 => 0.4,
+                    _ => 0.0,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_transmitted_ion_matches_frame_builder_at_window_edge() {
+        let peptide_map: BTreeMap<u32, PeptidesSim> = fixture_with_duplicate()
+            .read_peptides()
+            .unwrap()
+            .into_iter()
+            .map(|peptide| (peptide.peptide_id, peptide))
+            .collect();
+        let envelope = MzSpectrum::new(vec![464.7, 465.2, 600.0], vec![2.0, 1.0, 1.0]);
+        let ion = IonSim::new(7, 1, "PEPTIDEK".to_string(), 2, 1.0, 1.1, envelope.clone(), vec![1, 2, 3], vec![0.2, 0.5, 0.3]);
+
+        let transmitted = TimsTofSyntheticsDataHandle::transmitted_ion(
+            &ion,
+            &peptide_map,
+            &HashSet::from([1]),
+            &SoftEdgeWindow,
+            |_, _| 30.0,
+        )
+        .unwrap();
+
+        // scan 3 only holds peaks at the edge, the frame builders drop it as well
+        for (scan, expected) in [(2, 0.45), (3, 0.0)] {
+            let fraction = SoftEdgeWindow.transmitted_fraction(2, scan, &envelope.mz, &envelope.intensity, None);
+            assert!((fraction - expected).abs() < 1e-9);
+        }
+        assert_eq!(transmitted.iter().map(|t| t.frame_id).collect::<Vec<_>>(), vec![2, 3]);
+        for t in &transmitted {
+            assert_eq!((t.scan_start, t.scan_end), (2, 2));
+            assert!((t.transmitted_fraction - 0.45).abs() < 1e-9);
+        }
+    }
+}
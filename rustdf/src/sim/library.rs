@@ -0,0 +1,165 @@
+use mscore::data::peptide::{PeptideProductIon, PeptideProductIonSeriesCollection};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Column layout of an exported spectral library, both are tab separated with one row per fragment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectralLibraryFormat {
+    /// DIA-NN column names
+    DiaNn,
+    /// OpenSWATH assay library column names
+    OpenSwath,
+}
+
+impl FromStr for SpectralLibraryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "diann" | "dia-nn" | "tsv" => Ok(SpectralLibraryFormat::DiaNn),
+            "openswath" => Ok(SpectralLibraryFormat::OpenSwath),
+            _ => Err(format!("unknown spectral library format {}, expected diann or openswath", s)),
+        }
+    }
+}
+
+impl SpectralLibraryFormat {
+    /// Column names, both formats hold the same values in the same order
+    pub fn header(&self) -> [&'static str; 11] {
+        match self {
+            SpectralLibraryFormat::DiaNn => [
+                "ModifiedPeptide", "PrecursorMz", "PrecursorCharge", "Tr_recalibrated", "IonMobility", "FragmentMz",
+                "RelativeIntensity", "FragmentType", "FragmentCharge", "FragmentSeriesNumber", "Decoy",
+            ],
+            SpectralLibraryFormat::OpenSwath => [
+                "ModifiedPeptideSequence", "PrecursorMz", "PrecursorCharge", "NormalizedRetentionTime",
+                "PrecursorIonMobility", "ProductMz", "LibraryIntensity", "FragmentType", "FragmentCharge",
+                "FragmentSeriesNumber", "Decoy",
+            ],
+        }
+    }
+}
+
+/// Precursor values shared by all fragment rows of a library entry
+#[derive(Debug, Clone)]
+pub struct LibraryPrecursor {
+    /// sequence in UNIMOD notation, e.g. PEPTC[UNIMOD:4]IDEK
+    pub sequence: String,
+    pub decoy: bool,
+    pub mz: f64,
+    pub charge: i32,
+    pub retention_time: f64,
+    pub mobility: f64,
+}
+
+/// Sequence with modifications written as (UniMod:id), the notation both DIA-NN and OpenSWATH read
+pub fn library_sequence(sequence: &str) -> String {
+    sequence.replace("[UNIMOD:", "(UniMod:").replace(']', ")")
+}
+
+/// Writes a spectral library row by row, so libraries of any size are never held in memory
+pub struct SpectralLibraryWriter<W: Write> {
+    writer: W,
+    num_rows: usize,
+}
+
+impl<W: Write> SpectralLibraryWriter<W> {
+    /// Create a writer and write the header of `format`
+    pub fn new(mut writer: W, format: SpectralLibraryFormat) -> io::Result<Self> {
+        writeln!(writer, "{}", format.header().join("\t"))?;
+        Ok(SpectralLibraryWriter { writer, num_rows: 0 })
+    }
+
+    /// Write one row per fragment with positive intensity, intensities are relative to the most intense fragment
+    ///
+    /// # Arguments
+    ///
+    /// * `precursor` - precursor of the fragments
+    /// * `fragments` - b and y ion series per fragment charge, e.g. from `associate_with_predicted_intensities`
+    ///
+    /// # Returns
+    ///
+    /// * Number of written rows, zero if no fragment has intensity
+    ///
+    pub fn write_precursor(&mut self, precursor: &LibraryPrecursor, fragments: &PeptideProductIonSeriesCollection) -> io::Result<usize> {
+        // b and y ions are numbered from their terminus, which is their position in the series
+        let ions: Vec<(usize, &PeptideProductIon)> = fragments.peptide_ions.iter()
+            .flat_map(|series| series.n_ions.iter().enumerate().chain(series.c_ions.iter().enumerate()))
+            .filter(|(_, ion)| ion.ion.intensity > 0.0)
+            .collect();
+        let max_intensity = ions.iter().map(|(_, ion)| ion.ion.intensity).fold(0.0, f64::max);
+
+        let sequence = library_sequence(&precursor.sequence);
+        for (index, ion) in &ions {
+            writeln!(
+                self.writer,
+                "{}\t{:.6}\t{}\t{:.4}\t{:.6}\t{:.6}\t{:.6}\t{}\t{}\t{}\t{}",
+                sequence,
+                precursor.mz,
+                precursor.charge,
+                precursor.retention_time,
+                precursor.mobility,
+                ion.mz(),
+                ion.ion.intensity / max_intensity,
+                ion.kind,
+                ion.ion.charge,
+                index + 1,
+                precursor.decoy as u8,
+            )?;
+        }
+        self.num_rows += ions.len();
+        Ok(ions.len())
+    }
+
+    /// Flush the underlying writer
+    ///
+    /// # Returns
+    ///
+    /// * Number of written fragment rows, without the header
+    ///
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.writer.flush()?;
+        Ok(self.num_rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mscore::data::peptide::{FragmentType, PeptideSequence};
+
+    #[test]
+    fn test_write_precursor() {
+        let sequence = PeptideSequence::new("PEPC[UNIMOD:4]IDEK".to_string(), None);
+        // y3 and b2 at charge one, y3 is twice as intense
+        let mut intensities = vec![0.0; 174];
+        intensities[2] = 0.5;
+        intensities[30] = 0.25;
+        intensities[60] = -1.0;
+        let fragments = sequence.associate_with_predicted_intensities(2, FragmentType::B, intensities, false, false, None).unwrap();
+        let precursor = LibraryPrecursor {
+            sequence: sequence.sequence.clone(),
+            decoy: false,
+            mz: 475.2,
+            charge: 2,
+            retention_time: 600.0,
+            mobility: 1.05,
+        };
+
+        let mut writer = SpectralLibraryWriter::new(Vec::new(), SpectralLibraryFormat::DiaNn).unwrap();
+        assert_eq!(writer.write_precursor(&precursor, &fragments).unwrap(), 2);
+        let text = String::from_utf8(writer.writer.clone()).unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        let lines: Vec<Vec<&str>> = text.lines().map(|line| line.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0][0], "ModifiedPeptide");
+        assert_eq!(&lines[1][..3], &["PEPC(UniMod:4)IDEK", "475.200000", "2"]);
+        assert_eq!((lines[1][6], lines[1][7], lines[1][8], lines[1][9]), ("0.500000", "b", "1", "2"));
+        assert_eq!((lines[2][6], lines[2][7], lines[2][9], lines[2][10]), ("1.000000", "y", "3", "0"));
+
+        let y3 = sequence.calculate_product_ion_series(1, FragmentType::B).c_ions[2].mz();
+        assert!((lines[2][5].parse::<f64>().unwrap() - y3).abs() < 1e-5);
+        assert_eq!("openswath".parse::<SpectralLibraryFormat>().unwrap().header()[5], "ProductMz");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::sim::containers::ScansSim;
+use crate::sim::handle::TimsTofSyntheticsDataHandle;
+use mscore::algorithm::utility::{ElutionProfile, PeakShape};
+use rayon::prelude::*;
+use mscore::parallel;
+
+/// Mobility range covered by every scan, bounded by the midpoints to its neighbouring scans
+///
+/// # Arguments
+///
+/// * `scans` - Scans of the acquisition, in any order
+///
+/// # Returns
+///
+/// * Scan ids with the lower and upper mobility of their bin, sorted by ascending mobility
+///
+fn scan_bins(scans: &[ScansSim]) -> Vec<(u32, f64, f64)> {
+    let mut by_mobility: Vec<(f64, u32)> = TimsTofSyntheticsDataHandle::build_scan_to_mobility(scans)
+        .into_iter()
+        .map(|(scan, mobility)| (mobility as f64, scan))
+        .collect();
+    by_mobility.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let n = by_mobility.len();
+    let half_gap = |i: usize| (by_mobility[i + 1].0 - by_mobility[i].0) / 2.0;
+    (0..n)
+        .map(|i| {
+            let (mobility, scan) = by_mobility[i];
+            // the outer scans are as wide towards the outside as towards their neighbour, a single scan takes it all
+            let (half_below, half_above) = match n {
+                1 => (f64::INFINITY, f64::INFINITY),
+                _ if i == 0 => (half_gap(0), half_gap(0)),
+                _ if i == n - 1 => (half_gap(n - 2), half_gap(n - 2)),
+                _ => (half_gap(i - 1), half_gap(i)),
+            };
+            (scan, mobility - half_below, mobility + half_above)
+        })
+        .collect()
+}
+
+fn scan_distribution(
+    mobility: f64,
+    im_sigma_low: f64,
+    im_sigma_high: f64,
+    bins: &[(u32, f64, f64)],
+    truncation_z: f64,
+) -> (Vec<u32>, Vec<f32>) {
+    let shape = PeakShape::BiGaussian { sigma_left: im_sigma_low, sigma_right: im_sigma_high };
+    let window_low = mobility - truncation_z * im_sigma_low;
+    let window_high = mobility + truncation_z * im_sigma_high;
+
+    let mut distribution: Vec<(u32, f64)> = bins
+        .iter()
+        .filter(|(_, low, high)| *high > window_low && *low < window_high)
+        .map(|&(scan, low, high)| (scan, shape.cdf_range(low.max(window_low), high.min(window_high), mobility, None)))
+        .collect();
+    distribution.sort_by_key(|(scan, _)| *scan);
+
+    let total: f64 = distribution.iter().map(|(_, abundance)| abundance).sum();
+    if total <= 0.0 {
+        return (Vec::new(), Vec::new());
+    }
+
+    distribution
+        .into_iter()
+        .map(|(scan, abundance)| (scan, (abundance / total) as f32))
+        .unzip()
+}
+
+/// Place a Gaussian ion mobility peak over the scans of an acquisition
+///
+/// # Arguments
+///
+/// * `mobility` - Inverse ion mobility at the apex of the peak
+/// * `im_sigma` - Standard deviation of the peak in inverse mobility units
+/// * `scans` - Scans of the acquisition with their inverse mobility
+/// * `truncation_z` - Peak is cut off at `truncation_z` standard deviations around the apex
+///
+/// # Returns
+///
+/// * Ascending scan ids covered by the peak and their abundances, summing up to 1,
+///   both empty if the peak lies outside of the scanned mobility range
+///
+pub fn simulate_scan_distribution(
+    mobility: f64,
+    im_sigma: f64,
+    scans: &[ScansSim],
+    truncation_z: f64,
+) -> (Vec<u32>, Vec<f32>) {
+    simulate_scan_distribution_asymmetric(mobility, im_sigma, im_sigma, scans, truncation_z)
+}
+
+/// Place an asymmetric Gaussian ion mobility peak over the scans of an acquisition,
+/// the two halves of the peak have their own width
+///
+/// # Arguments
+///
+/// * `mobility` - Inverse ion mobility at the apex of the peak
+/// * `im_sigma_low` - Standard deviation of the half towards lower inverse mobility
+/// * `im_sigma_high` - Standard deviation of the half towards higher inverse mobility
+/// * `scans` - Scans of the acquisition with their inverse mobility
+/// * `truncation_z` - Peak is cut off at `truncation_z` standard deviations of either half around the apex
+///
+/// # Returns
+///
+/// * Ascending scan ids covered by the peak and their abundances, summing up to 1
+///
+pub fn simulate_scan_distribution_asymmetric(
+    mobility: f64,
+    im_sigma_low: f64,
+    im_sigma_high: f64,
+    scans: &[ScansSim],
+    truncation_z: f64,
+) -> (Vec<u32>, Vec<f32>) {
+    scan_distribution(mobility, im_sigma_low, im_sigma_high, &scan_bins(scans), truncation_z)
+}
+
+/// Scan distributions of many ions in parallel
+///
+/// # Arguments
+///
+/// * `mobilities` - Inverse ion mobility of every ion
+/// * `im_sigmas_low` - Peak width of every ion towards lower inverse mobility
+/// * `im_sigmas_high` - Peak width of every ion towards higher inverse mobility
+/// * `scans` - Scans of the acquisition with their inverse mobility
+/// * `truncation_z` - Peaks are cut off at `truncation_z` standard deviations around their apex
+/// * `num_threads` - Number of threads to use
+///
+/// # Returns
+///
+/// * Scan occurrences and abundances of every ion, in the order of `mobilities`
+///
+pub fn simulate_scan_distributions(
+    mobilities: &[f64],
+    im_sigmas_low: &[f64],
+    im_sigmas_high: &[f64],
+    scans: &[ScansSim],
+    truncation_z: f64,
+    num_threads: usize,
+) -> Vec<(Vec<u32>, Vec<f32>)> {
+    let bins = scan_bins(scans);
+    let thread_pool = parallel::pool(num_threads);
+    thread_pool.install(|| {
+        mobilities
+            .par_iter()
+            .zip(im_sigmas_low.par_iter())
+            .zip(im_sigmas_high.par_iter())
+            .map(|((&mobility, &sigma_low), &sigma_high)| {
+                scan_distribution(mobility, sigma_low, sigma_high, &bins, truncation_z)
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 scans, mobility decreasing from 1.6 to 0.61 with the scan id like on a timsTOF
+    fn scans() -> Vec<ScansSim> {
+        (1..=100).map(|scan| ScansSim::new(scan, 1.6 - (scan - 1) as f32 * 0.01)).collect()
+    }
+
+    #[test]
+    fn test_symmetric_scan_distribution() {
+        let (occurrence, abundance) = simulate_scan_distribution(1.1, 0.02, &scans(), 3.0);
+
+        assert_eq!(occurrence.len(), abundance.len());
+        assert!(occurrence.windows(2).all(|w| w[1] == w[0] + 1));
+        assert!((abundance.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+
+        // apex at scan 51, mirrored scans hold the same abundance
+        let apex = occurrence.iter().position(|&scan| scan == 51).unwrap();
+        assert_eq!(abundance.iter().cloned().fold(f32::MIN, f32::max), abundance[apex]);
+        assert!((abundance[apex - 2] - abundance[apex + 2]).abs() < 1e-5);
+        assert_eq!((occurrence[0], occurrence[occurrence.len() - 1]), (45, 57));
+    }
+
+    #[test]
+    fn test_asymmetric_scan_distribution() {
+        // broader towards low mobility, i.e. towards higher scan ids
+        let (occurrence, abundance) = simulate_scan_distribution_asymmetric(1.1, 0.04, 0.01, &scans(), 3.0);
+        let apex = occurrence.iter().position(|&scan| scan == 51).unwrap();
+        assert!(occurrence.len() - apex > apex + 1);
+        assert!(abundance[apex + 2] > abundance[apex - 2]);
+        assert!((abundance.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_scan_distribution_outside_of_range() {
+        let (occurrence, abundance) = simulate_scan_distribution(2.5, 0.01, &scans(), 3.0);
+        assert!(occurrence.is_empty() && abundance.is_empty());
+
+        let batch = simulate_scan_distributions(&[1.1, 2.5], &[0.02, 0.01], &[0.02, 0.01], &scans(), 3.0, 2);
+        assert_eq!(batch[0], simulate_scan_distribution(1.1, 0.02, &scans(), 3.0));
+        assert!(batch[1].0.is_empty());
+    }
+}
@@ -1,6 +1,16 @@
+pub mod assembler;
+pub mod calibration;
 pub mod containers;
 pub mod dia;
+pub mod dia_scheme;
+#[cfg(test)]
+pub(crate) mod fixtures;
 pub mod handle;
+pub mod library;
+pub mod mobility;
 pub mod precursor;
+pub mod scheduler;
+pub mod storage;
 pub mod utility;
 pub mod dda;
+pub mod writer;
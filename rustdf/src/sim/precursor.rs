@@ -5,14 +5,64 @@ use mscore::simulation::annotation::{
 };
 use mscore::timstof::frame::TimsFrame;
 use mscore::timstof::spectrum::TimsSpectrum;
-use rusqlite::Result;
 use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
 use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
+use crate::sim::assembler::{FrameAssembly, FramePeaks};
+use crate::sim::calibration::TofCalibration;
 use crate::sim::containers::{FramesSim, IonSim, PeptidesSim, ScansSim};
-use crate::sim::handle::TimsTofSyntheticsDataHandle;
+use crate::sim::handle::{DuplicatePeptideStrategy, PeptideToIons, SyntheticsError, TimsTofSyntheticsDataHandle};
+use crate::sim::utility::{
+    add_detection_noise, add_mz_noise, add_mz_noise_annotated, background_noise_rng, noise_rng,
+    random_background_peaks, FrameBatchIterator, FrameBuildOptions,
+};
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel::{self, PoolHandle};
+
+/// A lookup into the synthetics database that is not covered by its frames or scans table, or into
+/// data the builder did not load
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingKind {
+    /// the frames table has no retention time for the frame
+    RetentionTime,
+    /// the scans table has no mobility for the scan
+    Mobility(u32),
+    /// the builder has no fragment ions of the requested kind, it was created for precursor frames only, with
+    /// annotations for plain frames or without annotations for annotated frames
+    FragmentIons,
+}
+
+/// A frame that could not be built because the synthetics database has a gap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameBuildError {
+    pub frame_id: u32,
+    pub missing: MissingKind,
+}
+
+impl Display for FrameBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.missing {
+            MissingKind::RetentionTime => {
+                write!(f, "Frame {}: no retention time in the frames table", self.frame_id)
+            }
+            MissingKind::Mobility(scan) => write!(
+                f,
+                "Frame {}: no mobility for scan {} in the scans table",
+                self.frame_id, scan
+            ),
+            MissingKind::FragmentIons => write!(
+                f,
+                "Frame {}: no fragment ions loaded, the builder was created for precursor frames only or with a different annotation setting",
+                self.frame_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameBuildError {}
 
 pub struct TimsTofSyntheticsPrecursorFrameBuilder {
     pub ions: BTreeMap<u32, Vec<IonSim>>,
@@ -21,19 +71,41 @@ pub struct TimsTofSyntheticsPrecursorFrameBuilder {
     pub frames: Vec<FramesSim>,
     pub precursor_frame_id_set: HashSet<u32>,
     pub frame_to_abundances: BTreeMap<u32, (Vec<u32>, Vec<f32>)>,
-    pub peptide_to_ions: BTreeMap<
-        u32,
-        (
-            Vec<f32>,
-            Vec<Vec<u32>>,
-            Vec<Vec<f32>>,
-            Vec<i8>,
-            Vec<MzSpectrum>,
-        ),
-    >,
+    /// built on first access by `peptide_to_ions()` if the builder was created with `new_lazy`
+    pub peptide_to_ions: OnceLock<PeptideToIons>,
     pub frame_to_rt: BTreeMap<u32, f32>,
     pub scan_to_mobility: BTreeMap<u32, f32>,
     pub peptide_to_events: BTreeMap<u32, f32>,
+    pub noise_settings: FrameNoiseSettings,
+    /// converts the simulated m/z values to the tof indices of the frames
+    pub tof_calibration: TofCalibration,
+    /// how the peaks of all ions are merged into a frame, also used by the DIA fragment frames
+    pub frame_assembly: FrameAssembly,
+}
+
+/// Intensity noise applied when frames are built, both stages are off by default
+#[derive(Clone, Debug)]
+pub struct FrameNoiseSettings {
+    /// sample every peak intensity from a Poisson distribution with the expected events as mean
+    pub detection_noise: bool,
+    /// expected number of random chemical background peaks per frame, 0.0 disables background noise
+    pub background_noise_density: f64,
+    /// mean of the exponentially distributed background peak intensities
+    pub background_noise_intensity: f64,
+    pub background_mz_min: f64,
+    pub background_mz_max: f64,
+}
+
+impl Default for FrameNoiseSettings {
+    fn default() -> Self {
+        FrameNoiseSettings {
+            detection_noise: false,
+            background_noise_density: 0.0,
+            background_noise_intensity: 5.0,
+            background_mz_min: 100.0,
+            background_mz_max: 1700.0,
+        }
+    }
 }
 
 impl TimsTofSyntheticsPrecursorFrameBuilder {
@@ -42,31 +114,128 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
     /// # Arguments
     ///
     /// * `path` - A reference to a Path
+    /// * `duplicate_strategy` - How duplicate peptides are handled, defaults to `DuplicatePeptideStrategy::Error`
+    /// * `num_threads` - Number of threads the lookup maps are built with
     ///
     /// # Returns
     ///
     /// * A Result containing the TimsTofSynthetics instance
     ///
-    pub fn new(path: &Path) -> Result<Self> {
-        let handle = TimsTofSyntheticsDataHandle::new(path)?;
-        let ions = handle.read_ions()?;
-        let peptides = handle.read_peptides()?;
+    pub fn new(
+        path: &Path,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+        num_threads: usize,
+    ) -> Result<Self, SyntheticsError> {
+        Self::new_with_timing(path, duplicate_strategy, num_threads, false, &mut |_, _| {})
+    }
+
+    /// Same as `new`, but `peptide_to_ions` is only built when a frame is built for the first time,
+    /// so the builder is ready sooner, e.g. to schedule DDA precursors from `ions` and `frame_to_abundances`
+    pub fn new_lazy(
+        path: &Path,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+        num_threads: usize,
+    ) -> Result<Self, SyntheticsError> {
+        Self::new_with_timing(path, duplicate_strategy, num_threads, true, &mut |_, _| {})
+    }
+
+    /// Create a new instance of TimsTofSynthetics, reporting the time every construction step took
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to a Path
+    /// * `duplicate_strategy` - How duplicate peptides are handled, defaults to `DuplicatePeptideStrategy::Error`
+    /// * `num_threads` - Number of threads the lookup maps are built with
+    /// * `lazy` - Defer building `peptide_to_ions`, see `new_lazy`
+    /// * `on_step` - Called with the name and duration of every step: `read`, `frame_to_abundances`,
+    ///   `peptide_to_ions` (unless lazy) and `lookup_maps`
+    ///
+    pub fn new_with_timing(
+        path: &Path,
+        duplicate_strategy: Option<DuplicatePeptideStrategy>,
+        num_threads: usize,
+        lazy: bool,
+        on_step: &mut dyn FnMut(&str, Duration),
+    ) -> Result<Self, SyntheticsError> {
+        let mut start = Instant::now();
+        let mut step = |name: &str| {
+            on_step(name, start.elapsed());
+            start = Instant::now();
+        };
+
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(path)?;
+        handle.check_schema()?;
+        let mut ions = handle.read_ions()?;
+        let peptides = handle.read_peptides_deduplicated(duplicate_strategy.unwrap_or_default())?;
+        // ions of collapsed duplicates would otherwise be built without their peptide
+        let peptide_ids: HashSet<u32> = peptides.iter().map(|peptide| peptide.peptide_id).collect();
+        ions.retain(|ion| peptide_ids.contains(&ion.peptide_id));
         let scans = handle.read_scans()?;
         let frames = handle.read_frames()?;
-        Ok(Self {
+        let tof_calibration = handle.read_tof_calibration()?;
+        step("read");
+
+        let frame_to_abundances =
+            TimsTofSyntheticsDataHandle::build_frame_to_abundances_par(&peptides, num_threads);
+        step("frame_to_abundances");
+
+        let peptide_to_ions = OnceLock::new();
+        if !lazy {
+            let _ = peptide_to_ions.set(TimsTofSyntheticsDataHandle::build_peptide_to_ions_par(
+                &ions,
+                num_threads,
+            ));
+            step("peptide_to_ions");
+        }
+
+        let builder = Self {
             ions: TimsTofSyntheticsDataHandle::build_peptide_to_ion_map(&ions),
             peptides: TimsTofSyntheticsDataHandle::build_peptide_map(&peptides),
-            scans: scans.clone(),
-            frames: frames.clone(),
             precursor_frame_id_set: TimsTofSyntheticsDataHandle::build_precursor_frame_id_set(
                 &frames,
             ),
-            frame_to_abundances: TimsTofSyntheticsDataHandle::build_frame_to_abundances(&peptides),
-            peptide_to_ions: TimsTofSyntheticsDataHandle::build_peptide_to_ions(&ions),
+            frame_to_abundances,
+            peptide_to_ions,
             frame_to_rt: TimsTofSyntheticsDataHandle::build_frame_to_rt(&frames),
             scan_to_mobility: TimsTofSyntheticsDataHandle::build_scan_to_mobility(&scans),
             peptide_to_events: TimsTofSyntheticsDataHandle::build_peptide_to_events(&peptides),
-        })
+            scans,
+            frames,
+            noise_settings: FrameNoiseSettings::default(),
+            tof_calibration,
+            frame_assembly: FrameAssembly::default(),
+        };
+        step("lookup_maps");
+
+        Ok(builder)
+    }
+
+    /// Ions of every peptide, built from `ions` on first access if the builder was created with `new_lazy`
+    pub fn peptide_to_ions(&self) -> &PeptideToIons {
+        self.peptide_to_ions
+            .get_or_init(|| TimsTofSyntheticsDataHandle::build_peptide_to_ions_from_map(&self.ions))
+    }
+
+    /// Retention time of a frame, an error if the frames table does not cover the frame
+    pub fn retention_time(&self, frame_id: u32) -> Result<f64, FrameBuildError> {
+        self.frame_to_rt
+            .get(&frame_id)
+            .map(|rt| *rt as f64)
+            .ok_or(FrameBuildError {
+                frame_id,
+                missing: MissingKind::RetentionTime,
+            })
+    }
+
+    /// Inverse mobility of a scan, an error naming the frame if the scans table does not cover the scan
+    pub fn mobility(&self, frame_id: u32, scan: u32) -> Result<f64, FrameBuildError> {
+        self.scan_to_mobility
+            .get(&scan)
+            .map(|mobility| *mobility as f64)
+            .ok_or(FrameBuildError {
+                frame_id,
+                missing: MissingKind::Mobility(scan),
+            })
     }
 
     /// Build a precursor frame
@@ -74,10 +243,16 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
     /// # Arguments
     ///
     /// * `frame_id` - A u32 representing the frame id
+    /// * `seed` - An optional u64, if set the m/z noise is deterministic, see `utility::noise_seed`
     ///
     /// # Returns
     ///
     /// * A TimsFrame instance
+    ///
+    /// # Panics
+    ///
+    /// If the synthetics database has no retention time for the frame or no mobility for one of its scans,
+    /// see `try_build_precursor_frame`
     pub fn build_precursor_frame(
         &self,
         frame_id: u32,
@@ -85,85 +260,177 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
         uniform: bool,
         precursor_noise_ppm: f64,
         right_drag: bool,
+        seed: Option<u64>,
     ) -> TimsFrame {
+        self.try_build_precursor_frame(
+            frame_id,
+            mz_noise_precursor,
+            uniform,
+            precursor_noise_ppm,
+            right_drag,
+            seed,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Build a precursor frame, see `build_precursor_frame`
+    ///
+    /// # Returns
+    ///
+    /// * A TimsFrame instance, a FrameBuildError if the synthetics database does not cover the frame
+    ///   or one of its scans
+    pub fn try_build_precursor_frame(
+        &self,
+        frame_id: u32,
+        mz_noise_precursor: bool,
+        uniform: bool,
+        precursor_noise_ppm: f64,
+        right_drag: bool,
+        seed: Option<u64>,
+    ) -> Result<TimsFrame, FrameBuildError> {
         let ms_type = match self.precursor_frame_id_set.contains(&frame_id) {
             true => MsType::Precursor,
             false => MsType::Unknown,
         };
 
-        let mut tims_spectra: Vec<TimsSpectrum> = Vec::new();
+        let retention_time = self.retention_time(frame_id)?;
+        let mut peaks = FramePeaks::new(
+            self.frame_assembly,
+            self.tof_calibration,
+            self.build_background_noise(frame_id, &ms_type, seed)?,
+        );
 
         // Frame might not have any peptides
-        if !self.frame_to_abundances.contains_key(&frame_id) {
-            return TimsFrame::new(
+        if !self.frame_to_abundances.contains_key(&frame_id) && peaks.is_empty() {
+            return Ok(TimsFrame::new(
                 frame_id as i32,
                 ms_type.clone(),
-                *self.frame_to_rt.get(&frame_id).unwrap() as f64,
+                retention_time,
                 vec![],
                 vec![],
                 vec![],
                 vec![],
                 vec![],
-            );
+            ));
         }
-        // Get the peptide ids and abundances for the frame, should now save to unwrap since we checked if the frame is in the map
-        let (peptide_ids, abundances) = self.frame_to_abundances.get(&frame_id).unwrap();
+        // Get the peptide ids and abundances for the frame, a frame with only background noise has none
+        let no_peptides = (Vec::new(), Vec::new());
+        let (peptide_ids, abundances) = self
+            .frame_to_abundances
+            .get(&frame_id)
+            .unwrap_or(&no_peptides);
 
         // go over all peptides and their abundances in the frame
         for (peptide_id, abundance) in peptide_ids.iter().zip(abundances.iter()) {
             // jump to next peptide if the peptide_id is not in the peptide_to_ions map
-            if !self.peptide_to_ions.contains_key(&peptide_id) {
+            if !self.peptide_to_ions().contains_key(peptide_id) {
                 continue;
             }
 
             // one peptide can have multiple ions, occurring in multiple scans
-            let (ion_abundances, scan_occurrences, scan_abundances, _, spectra) =
-                self.peptide_to_ions.get(&peptide_id).unwrap();
+            let (ion_abundances, scan_occurrences, scan_abundances, charges, spectra) =
+                self.peptide_to_ions().get(peptide_id).unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
                 let scan_occurrence = scan_occurrences.get(index).unwrap();
                 let scan_abundance = scan_abundances.get(index).unwrap();
                 let spectrum = spectra.get(index).unwrap();
+                let charge = *charges.get(index).unwrap();
 
                 for (scan, scan_abu) in scan_occurrence.iter().zip(scan_abundance.iter()) {
                     let abundance_factor = abundance
                         * ion_abundance
                         * scan_abu
-                        * self.peptide_to_events.get(&peptide_id).unwrap();
+                        * self.peptide_to_events.get(peptide_id).unwrap();
                     let scan_id = *scan;
+                    let mobility = self.mobility(frame_id, scan_id)?;
+
+                    // without noise, the spectrum is scaled while its peaks are added
+                    if !mz_noise_precursor && !self.noise_settings.detection_noise {
+                        peaks.add(frame_id as i32, *scan as i32, retention_time, mobility, &ms_type, spectrum, abundance_factor as f64, None);
+                        continue;
+                    }
+
                     let scaled_spec: MzSpectrum = spectrum.clone() * abundance_factor as f64;
 
+                    let mut rng = noise_rng(seed, frame_id, *peptide_id, charge, scan_id);
+
                     let mz_spectrum = if mz_noise_precursor {
-                        match uniform {
-                            true => {
-                                scaled_spec.add_mz_noise_uniform(precursor_noise_ppm, right_drag)
-                            }
-                            false => scaled_spec.add_mz_noise_normal(precursor_noise_ppm),
-                        }
+                        add_mz_noise(
+                            scaled_spec,
+                            uniform,
+                            precursor_noise_ppm,
+                            right_drag,
+                            rng.as_mut(),
+                        )
                     } else {
                         scaled_spec
                     };
 
-                    let tims_spec = TimsSpectrum::new(
-                        frame_id as i32,
-                        *scan as i32,
-                        *self.frame_to_rt.get(&frame_id).unwrap() as f64,
-                        *self.scan_to_mobility.get(&scan_id).unwrap() as f64,
-                        ms_type.clone(),
-                        IndexedMzSpectrum::new(
-                            vec![0; mz_spectrum.mz.len()],
-                            mz_spectrum.mz,
-                            mz_spectrum.intensity,
-                        ),
-                    );
-                    tims_spectra.push(tims_spec);
+                    let mz_spectrum = match self.noise_settings.detection_noise {
+                        true => add_detection_noise(mz_spectrum, rng.as_mut()),
+                        false => mz_spectrum,
+                    };
+
+                    peaks.add(frame_id as i32, *scan as i32, retention_time, mobility, &ms_type, &mz_spectrum, 1.0, None);
                 }
             }
         }
 
-        let tims_frame = TimsFrame::from_tims_spectra(tims_spectra);
+        Ok(peaks.into_frame_ranged(
+            frame_id as i32, ms_type, retention_time, 0.0, 10000.0, 0, 2000, 0.0, 10.0, 1.0, 1e9,
+        ))
+    }
 
-        tims_frame.filter_ranged(0.0, 10000.0, 0, 2000, 0.0, 10.0, 1.0, 1e9)
+    /// Build random chemical background peaks for a frame, spread uniformly over all scans
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - A u32 representing the frame id
+    /// * `ms_type` - The MsType of the frame
+    /// * `seed` - An optional u64, if set the background is deterministic
+    ///
+    /// # Returns
+    ///
+    /// * A vector of TimsSpectrum instances, empty if background noise is disabled, a FrameBuildError
+    ///   if the frame has no retention time
+    ///
+    pub fn build_background_noise(
+        &self,
+        frame_id: u32,
+        ms_type: &MsType,
+        seed: Option<u64>,
+    ) -> Result<Vec<TimsSpectrum>, FrameBuildError> {
+        let settings = &self.noise_settings;
+        if settings.background_noise_density <= 0.0 || self.scan_to_mobility.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let scans: Vec<u32> = self.scan_to_mobility.keys().cloned().collect();
+        let peaks = match background_noise_rng(seed, frame_id) {
+            Some(mut rng) => random_background_peaks(settings, &scans, &mut rng),
+            None => random_background_peaks(settings, &scans, &mut rand::thread_rng()),
+        };
+
+        // background peaks are only assigned to scans with a mobility
+        let retention_time = self.retention_time(frame_id)?;
+        Ok(peaks
+            .into_iter()
+            .map(|(scan, spectrum)| {
+                TimsSpectrum::new(
+                    frame_id as i32,
+                    scan as i32,
+                    retention_time,
+                    self.scan_to_mobility[&scan] as f64,
+                    ms_type.clone(),
+                    IndexedMzSpectrum::new(
+                        self.tof_calibration.mz_to_tof(&spectrum.mz),
+                        spectrum.mz,
+                        spectrum.intensity,
+                    ),
+                )
+            })
+            .collect())
     }
 
     /// Build a collection of precursor frames in parallel
@@ -171,12 +438,14 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
     /// # Arguments
     ///
     /// * `frame_ids` - A vector of u32 representing the frame ids
-    /// * `num_threads` - A usize representing the number of threads
+    /// * `num_threads` - Number of threads, `None` runs on the shared pool (see `mscore::parallel`)
+    /// * `seed` - An optional u64, if set the m/z noise is deterministic and independent of `num_threads`
     ///
     /// # Returns
     ///
     /// * A vector of TimsFrame instances
     ///
+    #[allow(clippy::too_many_arguments, reason = "noise settings mirror the keyword arguments of the Python frame builder")]
     pub fn build_precursor_frames(
         &self,
         frame_ids: Vec<u32>,
@@ -184,34 +453,165 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
         uniform: bool,
         precursor_noise_ppm: f64,
         right_drag: bool,
-        num_threads: usize,
+        num_threads: impl Into<Option<usize>>,
+        seed: Option<u64>,
     ) -> Vec<TimsFrame> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
-        let mut tims_frames: Vec<TimsFrame> = Vec::new();
+        let thread_pool = parallel::pool(num_threads);
+
+        let options = FrameBuildOptions {
+            mz_noise_precursor,
+            uniform,
+            precursor_noise_ppm,
+            right_drag,
+            seed,
+            ..Default::default()
+        };
+        let mut tims_frames = self.build_precursor_frames_in_pool(&thread_pool, &frame_ids, &options);
+
+        tims_frames.sort_by_key(|frame| frame.frame_id);
+
+        tims_frames
+    }
 
+    /// Build precursor frames in batches of `batch_size` frames, handing every finished batch to `callback`
+    ///
+    /// Only one batch is alive at a time, so peak memory is bounded by the batch size and not by the
+    /// number of requested frames. Frames are built in parallel within a batch, batches are ordered by
+    /// frame id and the concatenation of all batches is identical to `build_precursor_frames`.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - A vector of u32 representing the frame ids
+    /// * `batch_size` - A usize representing the number of frames per batch
+    /// * `num_threads` - A usize representing the number of threads
+    /// * `options` - The noise settings and seed, see `FrameBuildOptions`
+    /// * `callback` - A closure that receives every batch of frames, sorted by frame id
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::{BTreeMap, HashSet};
+    /// use std::sync::OnceLock;
+    /// use mscore::data::spectrum::MzSpectrum;
+    /// use rustdf::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+    /// use rustdf::sim::utility::FrameBuildOptions;
+    ///
+    /// let frame_ids: Vec<u32> = (1..=10).collect();
+    /// let builder = TimsTofSyntheticsPrecursorFrameBuilder {
+    ///     ions: BTreeMap::new(),
+    ///     peptides: BTreeMap::new(),
+    ///     scans: vec![],
+    ///     frames: vec![],
+    ///     precursor_frame_id_set: frame_ids.iter().cloned().collect::<HashSet<u32>>(),
+    ///     frame_to_abundances: frame_ids.iter().map(|&f| (f, (vec![1], vec![f as f32]))).collect(),
+    ///     peptide_to_ions: OnceLock::from(BTreeMap::from([(1, (vec![1.0], vec![vec![5, 6]], vec![vec![0.5, 0.5]], vec![2],
+    ///         vec![MzSpectrum::new(vec![500.0, 500.5], vec![1.0, 0.5])]))])),
+    ///     frame_to_rt: frame_ids.iter().map(|&f| (f, f as f32)).collect(),
+    ///     scan_to_mobility: BTreeMap::from([(5, 1.0), (6, 0.99)]),
+    ///     peptide_to_events: BTreeMap::from([(1, 100.0)]),
+    ///     noise_settings: Default::default(),
+    ///     tof_calibration: Default::default(),
+    ///     frame_assembly: Default::default(),
+    /// };
+    ///
+    /// let all_frames = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 2, None);
+    /// let mut batched_frames = Vec::new();
+    /// builder.build_precursor_frames_batched(frame_ids, 3, 2, &FrameBuildOptions::default(), |batch| {
+    ///     assert!(batch.len() <= 3);
+    ///     batched_frames.extend(batch);
+    /// });
+    ///
+    /// assert_eq!(all_frames.len(), batched_frames.len());
+    /// for (a, b) in all_frames.iter().zip(batched_frames.iter()) {
+    ///     assert_eq!(a.frame_id, b.frame_id);
+    ///     assert_eq!(a.ims_frame.mz, b.ims_frame.mz);
+    ///     assert_eq!(a.ims_frame.intensity, b.ims_frame.intensity);
+    /// }
+    /// ```
+    pub fn build_precursor_frames_batched<F>(
+        &self,
+        frame_ids: Vec<u32>,
+        batch_size: usize,
+        num_threads: usize,
+        options: &FrameBuildOptions,
+        mut callback: F,
+    ) where
+        F: FnMut(Vec<TimsFrame>),
+    {
+        for batch in self.precursor_frame_batches(frame_ids, batch_size, num_threads, options) {
+            callback(batch);
+        }
+    }
+
+    /// Create an iterator that lazily builds precursor frames in batches of `batch_size` frames
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_ids` - A vector of u32 representing the frame ids
+    /// * `batch_size` - A usize representing the number of frames per batch
+    /// * `num_threads` - A usize representing the number of threads
+    /// * `options` - The noise settings and seed, see `FrameBuildOptions`
+    ///
+    /// # Returns
+    ///
+    /// * A FrameBatchIterator yielding vectors of TimsFrame instances, sorted by frame id
+    ///
+    pub fn precursor_frame_batches(
+        &self,
+        frame_ids: Vec<u32>,
+        batch_size: usize,
+        num_threads: usize,
+        options: &FrameBuildOptions,
+    ) -> FrameBatchIterator<impl FnMut(&[u32]) -> Vec<TimsFrame> + '_> {
+        let thread_pool = parallel::pool(num_threads);
+        let options = *options;
+        FrameBatchIterator::new(frame_ids, batch_size, move |batch: &[u32]| {
+            self.build_precursor_frames_in_pool(&thread_pool, batch, &options)
+        })
+    }
+
+    /// Build precursor frames in parallel on `thread_pool`, frames keep the order of `frame_ids`
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_pool` - The pool to build on, see `mscore::parallel::pool`
+    /// * `frame_ids` - The frame ids
+    /// * `options` - The noise settings and seed, see `FrameBuildOptions`
+    ///
+    /// # Returns
+    ///
+    /// * A vector of TimsFrame instances
+    ///
+    pub fn build_precursor_frames_in_pool(
+        &self,
+        thread_pool: &PoolHandle,
+        frame_ids: &[u32],
+        options: &FrameBuildOptions,
+    ) -> Vec<TimsFrame> {
         thread_pool.install(|| {
-            tims_frames = frame_ids
+            frame_ids
                 .par_iter()
                 .map(|frame_id| {
                     self.build_precursor_frame(
                         *frame_id,
-                        mz_noise_precursor,
-                        uniform,
-                        precursor_noise_ppm,
-                        right_drag,
+                        options.mz_noise_precursor,
+                        options.uniform,
+                        options.precursor_noise_ppm,
+                        options.right_drag,
+                        options.seed,
                     )
                 })
-                .collect();
-        });
-
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
-
-        tims_frames
+                .collect()
+        })
     }
 
+    /// Build a precursor frame with peak annotations, see `build_precursor_frame`
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_id` - A u32 representing the frame id
+    /// * `seed` - An optional u64, if set the m/z noise is deterministic and drawn as in `build_precursor_frame`
+    ///
     pub fn build_precursor_frame_annotated(
         &self,
         frame_id: u32,
@@ -219,6 +619,7 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
         uniform: bool,
         precursor_noise_ppm: f64,
         right_drag: bool,
+        seed: Option<u64>,
     ) -> TimsFrameAnnotated {
         let ms_type = match self.precursor_frame_id_set.contains(&frame_id) {
             true => MsType::Precursor,
@@ -245,12 +646,12 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
 
         for (peptide_id, abundance) in peptide_ids.iter().zip(abundances.iter()) {
             // jump to next peptide if the peptide_id is not in the peptide_to_ions map
-            if !self.peptide_to_ions.contains_key(&peptide_id) {
+            if !self.peptide_to_ions().contains_key(peptide_id) {
                 continue;
             }
 
             let (ion_abundances, scan_occurrences, scan_abundances, charges, _) =
-                self.peptide_to_ions.get(&peptide_id).unwrap();
+                self.peptide_to_ions().get(peptide_id).unwrap();
 
             for (index, ion_abundance) in ion_abundances.iter().enumerate() {
                 let scan_occurrence = scan_occurrences.get(index).unwrap();
@@ -263,25 +664,28 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
                     *ion_abundance as f64,
                     Some(*peptide_id as i32),
                 );
+                let ion_id = self
+                    .ions
+                    .get(peptide_id)
+                    .and_then(|ions| ions.get(index))
+                    .map_or(-1, |ion| ion.ion_id as i32);
                 // TODO: make this configurable
-                let spectrum = ion.calculate_isotopic_spectrum_annotated(1e-3, 1e-8, 200, 1e-4);
+                let spectrum = ion
+                    .calculate_isotopic_spectrum_annotated(1e-3, 1e-8, 200, 1e-4)
+                    .with_ion_id(ion_id);
 
                 for (scan, scan_abu) in scan_occurrence.iter().zip(scan_abundance.iter()) {
                     let abundance_factor = abundance
                         * ion_abundance
                         * scan_abu
-                        * self.peptide_to_events.get(&peptide_id).unwrap();
+                        * self.peptide_to_events.get(peptide_id).unwrap();
                     let scan_id = *scan;
                     let scaled_spec: MzSpectrumAnnotated =
                         spectrum.clone() * abundance_factor as f64;
 
                     let mz_spectrum = if mz_noise_precursor {
-                        match uniform {
-                            true => {
-                                scaled_spec.add_mz_noise_uniform(precursor_noise_ppm, right_drag)
-                            }
-                            false => scaled_spec.add_mz_noise_normal(precursor_noise_ppm),
-                        }
+                        let mut rng = noise_rng(seed, frame_id, *peptide_id, *charge, scan_id);
+                        add_mz_noise_annotated(scaled_spec, uniform, precursor_noise_ppm, right_drag, rng.as_mut())
                     } else {
                         scaled_spec
                     };
@@ -292,7 +696,7 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
                         *self.frame_to_rt.get(&frame_id).unwrap() as f64,
                         *self.scan_to_mobility.get(&scan_id).unwrap() as f64,
                         ms_type.clone(),
-                        vec![0; mz_spectrum.mz.len()],
+                        self.tof_calibration.mz_to_tof_unsigned(&mz_spectrum.mz),
                         mz_spectrum,
                     );
                     tims_spectra.push(tims_spec);
@@ -323,15 +727,13 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
                             .partial_cmp(&b.intensity_contribution)
                             .unwrap()
                     });
-                    PeakAnnotation {
-                        contributions,
-                        ..*x
-                    }
+                    PeakAnnotation { contributions }
                 })
                 .collect::<Vec<PeakAnnotation>>(),
         }
     }
 
+    #[allow(clippy::too_many_arguments, reason = "noise settings mirror the keyword arguments of the Python frame builder")]
     pub fn build_precursor_frames_annotated(
         &self,
         frame_ids: Vec<u32>,
@@ -339,12 +741,10 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
         uniform: bool,
         precursor_noise_ppm: f64,
         right_drag: bool,
-        num_threads: usize,
+        num_threads: impl Into<Option<usize>>,
+        seed: Option<u64>,
     ) -> Vec<TimsFrameAnnotated> {
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build()
-            .unwrap();
+        let thread_pool = parallel::pool(num_threads);
         let mut tims_frames: Vec<TimsFrameAnnotated> = Vec::new();
 
         thread_pool.install(|| {
@@ -357,13 +757,159 @@ impl TimsTofSyntheticsPrecursorFrameBuilder {
                         uniform,
                         precursor_noise_ppm,
                         right_drag,
+                        seed,
                     )
                 })
                 .collect();
         });
 
-        tims_frames.sort_by(|a, b| a.frame_id.cmp(&b.frame_id));
+        tims_frames.sort_by_key(|frame| frame.frame_id);
 
         tims_frames
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(frame_ids: &[u32]) -> TimsTofSyntheticsPrecursorFrameBuilder {
+        TimsTofSyntheticsPrecursorFrameBuilder {
+            ions: BTreeMap::new(),
+            peptides: BTreeMap::new(),
+            scans: vec![],
+            frames: vec![],
+            precursor_frame_id_set: frame_ids.iter().cloned().collect::<HashSet<u32>>(),
+            frame_to_abundances: frame_ids
+                .iter()
+                .map(|&f| (f, (vec![1, 2], vec![f as f32, 1.0])))
+                .collect(),
+            peptide_to_ions: OnceLock::from(BTreeMap::from([
+                (
+                    1,
+                    (
+                        vec![0.6, 0.4],
+                        vec![vec![5, 6], vec![5]],
+                        vec![vec![0.5, 0.5], vec![1.0]],
+                        vec![2, 3],
+                        vec![
+                            MzSpectrum::new(vec![500.0, 500.5], vec![1.0, 0.5]),
+                            MzSpectrum::new(vec![333.67, 334.0], vec![1.0, 0.5]),
+                        ],
+                    ),
+                ),
+                (
+                    2,
+                    (
+                        vec![1.0],
+                        vec![vec![6]],
+                        vec![vec![1.0]],
+                        vec![2],
+                        vec![MzSpectrum::new(vec![650.3, 650.8], vec![1.0, 0.6])],
+                    ),
+                ),
+            ])),
+            frame_to_rt: frame_ids.iter().map(|&f| (f, f as f32)).collect(),
+            scan_to_mobility: BTreeMap::from([(5, 1.0), (6, 0.99)]),
+            peptide_to_events: BTreeMap::from([(1, 100.0), (2, 500.0)]),
+            noise_settings: FrameNoiseSettings::default(),
+            tof_calibration: TofCalibration::default(),
+            frame_assembly: FrameAssembly::default(),
+        }
+    }
+
+    #[test]
+    fn test_seeded_noise_is_reproducible() {
+        let frame_ids: Vec<u32> = (1..=8).collect();
+        let builder = builder(&frame_ids);
+
+        for uniform in [true, false] {
+            let a = builder.build_precursor_frames(frame_ids.clone(), true, uniform, 10.0, false, 1, Some(42));
+            // thread count and frame order must not change the noise
+            let mut reversed = frame_ids.clone();
+            reversed.reverse();
+            let b = builder.build_precursor_frames(reversed, true, uniform, 10.0, false, 4, Some(42));
+            let c = builder.build_precursor_frames(frame_ids.clone(), true, uniform, 10.0, false, 4, Some(7));
+
+            assert_eq!(a.len(), b.len());
+            for (x, y) in a.iter().zip(b.iter()) {
+                assert_eq!(x.frame_id, y.frame_id);
+                assert_eq!(x.scan, y.scan);
+                assert_eq!(x.ims_frame.mz, y.ims_frame.mz);
+                assert_eq!(x.ims_frame.intensity, y.ims_frame.intensity);
+            }
+            assert!(a.iter().zip(c.iter()).any(|(x, y)| x.ims_frame.mz != y.ims_frame.mz));
+        }
+    }
+
+    #[test]
+    fn test_detection_and_background_noise() {
+        let frame_ids: Vec<u32> = (1..=4).collect();
+        let mut builder = builder(&frame_ids);
+        let clean = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 2, Some(1));
+
+        // detection noise only keeps the peak positions but changes intensities
+        builder.noise_settings.detection_noise = true;
+        let detected = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 2, Some(1));
+        let detected_again = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 1, Some(1));
+        for ((x, y), z) in clean.iter().zip(detected.iter()).zip(detected_again.iter()) {
+            assert!(y.ims_frame.mz.iter().all(|mz| x.ims_frame.mz.contains(mz)));
+            assert_ne!(x.ims_frame.intensity, y.ims_frame.intensity);
+            assert_eq!(y.ims_frame.intensity, z.ims_frame.intensity);
+        }
+
+        // background noise only adds peaks, also to frames without peptides
+        builder.noise_settings.detection_noise = false;
+        builder.noise_settings.background_noise_density = 100.0;
+        builder.noise_settings.background_noise_intensity = 50.0;
+        builder.frame_to_abundances.remove(&4);
+        let background = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 2, Some(1));
+        let background_again = builder.build_precursor_frames(frame_ids.clone(), false, false, 0.0, false, 4, Some(1));
+        for (x, y) in background.iter().zip(background_again.iter()) {
+            assert_eq!(x.ims_frame.mz, y.ims_frame.mz);
+            assert_eq!(x.ims_frame.intensity, y.ims_frame.intensity);
+        }
+        assert!(background[..3].iter().zip(clean.iter()).all(|(x, y)| x.ims_frame.mz.len() > y.ims_frame.mz.len()));
+        assert!(!background[3].ims_frame.mz.is_empty());
+    }
+
+    #[test]
+    fn test_frame_assembly_matches_spectra() {
+        let frame_ids: Vec<u32> = (1..=6).collect();
+        let mut builder = builder(&frame_ids);
+        builder.noise_settings.background_noise_density = 20.0;
+
+        for mz_noise in [false, true] {
+            builder.frame_assembly = FrameAssembly::Spectra;
+            let expected = builder.build_precursor_frames(frame_ids.clone(), mz_noise, false, 10.0, false, 2, Some(5));
+            builder.frame_assembly = FrameAssembly::default();
+            let assembled = builder.build_precursor_frames(frame_ids.clone(), mz_noise, false, 10.0, false, 2, Some(5));
+
+            for (x, y) in expected.iter().zip(assembled.iter()) {
+                assert_eq!((x.frame_id, x.ims_frame.retention_time), (y.frame_id, y.ims_frame.retention_time));
+                assert_eq!(x.scan, y.scan);
+                assert_eq!(x.tof, y.tof);
+                assert_eq!(x.ims_frame.mz, y.ims_frame.mz);
+                assert_eq!(x.ims_frame.intensity, y.ims_frame.intensity);
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_lookups_are_errors() {
+        let mut builder = builder(&[1, 2]);
+        builder.frame_to_rt.remove(&2);
+        builder.scan_to_mobility.remove(&6);
+
+        assert_eq!(
+            builder.try_build_precursor_frame(1, false, false, 0.0, false, None).unwrap_err(),
+            FrameBuildError { frame_id: 1, missing: MissingKind::Mobility(6) }
+        );
+        let error = builder.try_build_precursor_frame(2, false, false, 0.0, false, None).unwrap_err();
+        assert_eq!(error.missing, MissingKind::RetentionTime);
+        assert_eq!(error.to_string(), "Frame 2: no retention time in the frames table");
+
+        builder.scan_to_mobility.insert(6, 0.99);
+        assert!(builder.try_build_precursor_frame(1, false, false, 0.0, false, None).is_ok());
+    }
+}
@@ -0,0 +1,285 @@
+use mscore::timstof::quadrupole::PASEFMeta;
+
+/// Ion that can be selected for fragmentation in a precursor frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrecursorCandidate {
+    pub peptide_id: u32,
+    pub mz: f64,
+    /// inverse ion mobility 1/K0
+    pub mobility: f64,
+    /// first scan of the ion, inclusive
+    pub scan_start: u32,
+    /// last scan of the ion, inclusive
+    pub scan_end: u32,
+    pub intensity: f64,
+}
+
+/// Frame of the run with the ions that could be selected if it is acquired as precursor frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct CandidateFrame {
+    pub frame_id: u32,
+    /// retention time in seconds
+    pub retention_time: f64,
+    pub candidates: Vec<PrecursorCandidate>,
+}
+
+/// Precursor selected in a precursor frame and isolated over its scans in a fragment frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrecursorSelection {
+    pub precursor_frame_id: u32,
+    pub candidate: PrecursorCandidate,
+}
+
+/// Fragment frame of the schedule with the precursors isolated in it, ordered by first scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledFrame {
+    pub frame_id: u32,
+    pub selections: Vec<PrecursorSelection>,
+}
+
+/// TopN precursor selection of a DDA-PASEF run with dynamic exclusion
+///
+/// Frames are split into cycles of one precursor frame followed by `ms2_frames_per_cycle` fragment frames.
+/// In every precursor frame, candidates are ranked by intensity, candidates below the intensity threshold or within
+/// the re-pick tolerances of an ion selected less than `exclusion_duration` seconds before are skipped. Up to
+/// `top_n` candidates are placed on the fragment frames of the cycle, precursors sharing a fragment frame must not
+/// overlap in scans and a fragment frame holds at most `max_precursors_per_frame` precursors.
+/// The scheduler works on candidates provided by the caller and identifies ions by m/z and mobility, as the
+/// instrument does, `schedule_dda_pasef` feeds it the simulated ions of a run.
+#[derive(Debug, Clone)]
+pub struct PasefScheduler {
+    /// maximum number of precursors selected per precursor frame
+    pub top_n: usize,
+    /// maximum number of precursors isolated in one fragment frame, i.e. along one PASEF scan line
+    pub max_precursors_per_frame: usize,
+    /// number of PASEF fragment frames following every precursor frame
+    pub ms2_frames_per_cycle: usize,
+    /// minimum intensity of a candidate to be selected
+    pub intensity_threshold: f64,
+    /// seconds an ion is excluded from selection after it was selected
+    pub exclusion_duration: f64,
+    /// candidates within this m/z tolerance in ppm of an excluded ion are not picked again
+    pub exclusion_mz_ppm: f64,
+    /// candidates within this 1/K0 tolerance of an excluded ion are not picked again
+    pub exclusion_mobility: f64,
+}
+
+impl Default for PasefScheduler {
+    fn default() -> Self {
+        PasefScheduler {
+            top_n: 10,
+            max_precursors_per_frame: 12,
+            ms2_frames_per_cycle: 4,
+            intensity_threshold: 500.0,
+            exclusion_duration: 24.0,
+            exclusion_mz_ppm: 20.0,
+            exclusion_mobility: 0.015,
+        }
+    }
+}
+
+/// Ion selected at a retention time, excluded from selection until the exclusion duration has passed
+struct ExcludedIon {
+    mz: f64,
+    mobility: f64,
+    retention_time: f64,
+}
+
+impl PasefScheduler {
+    /// Schedule the fragment frames of a run
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - all frames of the run, in acquisition order
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<ScheduledFrame>` - every fragment frame of the run in acquisition order, frames without
+    ///   selected precursors included
+    ///
+    pub fn schedule(&self, frames: &[CandidateFrame]) -> Vec<ScheduledFrame> {
+        let mut schedule = Vec::new();
+        let mut excluded: Vec<ExcludedIon> = Vec::new();
+
+        for cycle in frames.chunks(self.ms2_frames_per_cycle + 1) {
+            let (ms1, ms2) = cycle.split_at(1);
+            let ms1 = &ms1[0];
+            excluded.retain(|ion| ms1.retention_time - ion.retention_time < self.exclusion_duration);
+
+            // ties are broken by peptide, m/z and mobility to keep the schedule independent of the input order
+            let mut candidates: Vec<&PrecursorCandidate> = ms1.candidates.iter()
+                .filter(|candidate| candidate.intensity >= self.intensity_threshold)
+                .collect();
+            candidates.sort_by(|a, b| b.intensity.total_cmp(&a.intensity)
+                .then(a.peptide_id.cmp(&b.peptide_id))
+                .then(a.mz.total_cmp(&b.mz))
+                .then(a.mobility.total_cmp(&b.mobility)));
+
+            let mut scheduled: Vec<ScheduledFrame> = ms2.iter()
+                .map(|frame| ScheduledFrame { frame_id: frame.frame_id, selections: Vec::new() })
+                .collect();
+            let mut num_selected = 0;
+
+            for candidate in candidates {
+                if num_selected >= self.top_n {
+                    break;
+                }
+                if excluded.iter().any(|ion| self.within_exclusion(ion, candidate)) {
+                    continue;
+                }
+
+                let slot = scheduled.iter_mut().find(|frame| {
+                    frame.selections.len() < self.max_precursors_per_frame
+                        && frame.selections.iter().all(|selection| {
+                            candidate.scan_end < selection.candidate.scan_start || candidate.scan_start > selection.candidate.scan_end
+                        })
+                });
+                let slot = match slot {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                slot.selections.push(PrecursorSelection { precursor_frame_id: ms1.frame_id, candidate: candidate.clone() });
+
+                excluded.push(ExcludedIon { mz: candidate.mz, mobility: candidate.mobility, retention_time: ms1.retention_time });
+                num_selected += 1;
+            }
+
+            for frame in scheduled.iter_mut() {
+                frame.selections.sort_by_key(|selection| selection.candidate.scan_start);
+            }
+            schedule.extend(scheduled);
+        }
+
+        schedule
+    }
+
+    /// PASEF scheduling of a schedule, precursors are numbered from 1 in schedule order
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` - schedule created by `schedule`
+    /// * `isolation_width` - isolation width in m/z
+    /// * `collision_energy` - collision energy in eV
+    ///
+    pub fn to_pasef_meta(schedule: &[ScheduledFrame], isolation_width: f64, collision_energy: f64) -> Vec<PASEFMeta> {
+        schedule.iter()
+            .flat_map(|frame| frame.selections.iter().map(move |selection| (frame.frame_id, selection)))
+            .enumerate()
+            .map(|(index, (frame_id, selection))| PASEFMeta::new(
+                frame_id as i32,
+                selection.candidate.scan_start as i32,
+                selection.candidate.scan_end as i32,
+                selection.candidate.mz,
+                isolation_width,
+                collision_energy,
+                index as i32 + 1,
+            ))
+            .collect()
+    }
+
+    fn within_exclusion(&self, ion: &ExcludedIon, candidate: &PrecursorCandidate) -> bool {
+        (candidate.mz - ion.mz).abs() / ion.mz * 1e6 <= self.exclusion_mz_ppm
+            && (candidate.mobility - ion.mobility).abs() <= self.exclusion_mobility
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(peptide_id: u32, mz: f64, scan_start: u32, intensity: f64) -> PrecursorCandidate {
+        PrecursorCandidate {
+            peptide_id,
+            mz,
+            mobility: 1.6 - scan_start as f64 * 0.001,
+            scan_start,
+            scan_end: scan_start + 20,
+            intensity,
+        }
+    }
+
+    /// frames 1 to `num_frames` one second apart, all with the same candidates
+    fn frames(num_frames: u32, candidates: Vec<PrecursorCandidate>) -> Vec<CandidateFrame> {
+        (1..=num_frames).map(|frame_id| CandidateFrame {
+            frame_id,
+            retention_time: frame_id as f64,
+            candidates: candidates.clone(),
+        }).collect()
+    }
+
+    fn selected(schedule: &[ScheduledFrame]) -> Vec<(u32, u32, u32)> {
+        schedule.iter()
+            .flat_map(|frame| frame.selections.iter().map(|s| (frame.frame_id, s.precursor_frame_id, s.candidate.peptide_id)))
+            .collect()
+    }
+
+    #[test]
+    fn test_top_n_and_threshold() {
+        let scheduler = PasefScheduler { top_n: 2, ms2_frames_per_cycle: 1, intensity_threshold: 100.0, exclusion_duration: 0.0, ..Default::default() };
+        let candidates = vec![
+            candidate(1, 500.0, 100, 1e3),
+            candidate(2, 600.0, 200, 1e4),
+            candidate(3, 700.0, 300, 1e5),
+            candidate(4, 800.0, 400, 50.0),
+        ];
+        let schedule = scheduler.schedule(&frames(4, candidates));
+
+        // the two most intense candidates share a fragment frame, ordered by scan, the one below the threshold is never picked
+        assert_eq!(schedule.iter().map(|f| f.frame_id).collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(selected(&schedule), vec![(2, 1, 2), (2, 1, 3), (4, 3, 2), (4, 3, 3)]);
+    }
+
+    #[test]
+    fn test_dynamic_exclusion() {
+        let scheduler = PasefScheduler { top_n: 1, ms2_frames_per_cycle: 1, intensity_threshold: 0.0, exclusion_duration: 3.0, ..Default::default() };
+        let schedule = scheduler.schedule(&frames(10, vec![candidate(1, 500.0, 100, 1e4), candidate(2, 600.0, 200, 1e3)]));
+
+        // peptide 1 is excluded for 3 seconds after it was picked in frame 1, peptide 2 fills in
+        let parents: Vec<(u32, u32)> = selected(&schedule).iter().map(|&(_, parent, peptide)| (parent, peptide)).collect();
+        assert_eq!(parents, vec![(1, 1), (3, 2), (5, 1), (7, 2), (9, 1)]);
+    }
+
+    #[test]
+    fn test_exclusion_tolerances() {
+        let scheduler = PasefScheduler { top_n: 1, ms2_frames_per_cycle: 1, intensity_threshold: 0.0, exclusion_duration: 10.0, exclusion_mz_ppm: 10.0, ..Default::default() };
+        let mut frames = frames(4, vec![candidate(1, 500.0, 100, 1e3)]);
+        // the same ion, shifted by 4 ppm, is excluded, an ion 40 ppm apart is not
+        frames[2].candidates = vec![candidate(2, 500.002, 100, 1e3), candidate(3, 500.02, 100, 1e2)];
+
+        assert_eq!(selected(&scheduler.schedule(&frames)), vec![(2, 1, 1), (4, 3, 3)]);
+
+        // a shift in mobility beyond the tolerance makes it another ion
+        frames[2].candidates[0].mobility += 0.05;
+        assert_eq!(selected(&scheduler.schedule(&frames)), vec![(2, 1, 1), (4, 3, 2)]);
+    }
+
+    #[test]
+    fn test_scan_overlap_and_frame_capacity() {
+        let candidates = vec![
+            candidate(1, 500.0, 100, 1e3),
+            candidate(2, 600.0, 110, 1e3),
+            candidate(3, 700.0, 300, 1e3),
+        ];
+        let scheduler = PasefScheduler { top_n: 3, ms2_frames_per_cycle: 2, intensity_threshold: 0.0, ..Default::default() };
+        let schedule = scheduler.schedule(&frames(3, candidates.clone()));
+        // peptide 2 overlaps peptide 1 in scans and moves to the second fragment frame
+        assert_eq!(selected(&schedule), vec![(2, 1, 1), (2, 1, 3), (3, 1, 2)]);
+
+        let scheduler = PasefScheduler { max_precursors_per_frame: 1, ..scheduler };
+        assert_eq!(selected(&scheduler.schedule(&frames(3, candidates))), vec![(2, 1, 1), (3, 1, 2)]);
+    }
+
+    #[test]
+    fn test_deterministic_and_pasef_meta() {
+        let mut candidates = vec![candidate(2, 600.0, 200, 1e3), candidate(1, 500.0, 100, 1e3)];
+        let scheduler = PasefScheduler { ms2_frames_per_cycle: 1, intensity_threshold: 0.0, ..Default::default() };
+        let schedule = scheduler.schedule(&frames(2, candidates.clone()));
+        candidates.reverse();
+        assert_eq!(scheduler.schedule(&frames(2, candidates)), schedule);
+
+        let pasef_meta = PasefScheduler::to_pasef_meta(&schedule, 2.0, 35.0);
+        assert_eq!(pasef_meta.len(), 2);
+        assert_eq!((pasef_meta[1].frame, pasef_meta[1].scan_start, pasef_meta[1].scan_end), (2, 200, 220));
+        assert_eq!((pasef_meta[1].isolation_mz, pasef_meta[1].precursor), (600.0, 2));
+    }
+}
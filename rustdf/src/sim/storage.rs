@@ -0,0 +1,171 @@
+use mscore::data::serialization::BinarySerializable;
+use mscore::data::spectrum::MzSpectrum;
+use rusqlite::types::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Error of a column value that could not be decoded
+pub type ColumnDecodeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Name of the table that stores the storage format of the other tables, one `<table>_format` key per table
+pub const META_TABLE: &str = "meta";
+
+/// How the list and spectrum columns of a table are stored, tables without an entry in `meta` are JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageFormat {
+    /// JSON text, as written by imspy
+    #[default]
+    Json,
+    /// little-endian arrays, spectra as `BinarySerializable` bytes
+    Binary,
+}
+
+impl FromStr for StorageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(StorageFormat::Json),
+            "binary" => Ok(StorageFormat::Binary),
+            _ => Err(format!(
+                "unknown storage format {}, expected json or binary",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for StorageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageFormat::Json => write!(f, "json"),
+            StorageFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Key of the storage format of `table` in the meta table
+pub fn format_key(table: &str) -> String {
+    format!("{}_format", table)
+}
+
+/// A column value that is stored either as JSON or as binary
+pub trait ColumnCodec: Serialize + DeserializeOwned + Sized {
+    fn to_binary(&self) -> Vec<u8>;
+
+    fn from_binary(bytes: &[u8]) -> Result<Self, ColumnDecodeError>;
+
+    /// Decode a value stored in `format`
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The text or blob of the column
+    /// * `format` - The storage format of the table
+    ///
+    fn decode(bytes: &[u8], format: StorageFormat) -> Result<Self, ColumnDecodeError> {
+        match format {
+            StorageFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            StorageFormat::Binary => Self::from_binary(bytes),
+        }
+    }
+
+    /// Encode the value as text for JSON tables and as blob for binary tables
+    fn encode(&self, format: StorageFormat) -> Value {
+        match format {
+            StorageFormat::Json => Value::Text(
+                serde_json::to_string(self).expect("lists and spectra serialize to JSON"),
+            ),
+            StorageFormat::Binary => Value::Blob(self.to_binary()),
+        }
+    }
+}
+
+/// Implement `ColumnCodec` for a vector of numbers stored as little-endian array
+macro_rules! impl_little_endian_codec {
+    ($t:ty) => {
+        impl ColumnCodec for Vec<$t> {
+            fn to_binary(&self) -> Vec<u8> {
+                self.iter().flat_map(|value| value.to_le_bytes()).collect()
+            }
+
+            fn from_binary(bytes: &[u8]) -> Result<Self, ColumnDecodeError> {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                if bytes.len() % SIZE != 0 {
+                    return Err(format!(
+                        "{} bytes are no array of {}",
+                        bytes.len(),
+                        stringify!($t)
+                    )
+                    .into());
+                }
+                Ok(bytes
+                    .chunks_exact(SIZE)
+                    .map(|chunk| <$t>::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect())
+            }
+        }
+    };
+}
+
+impl_little_endian_codec!(u32);
+impl_little_endian_codec!(f32);
+impl_little_endian_codec!(f64);
+
+impl ColumnCodec for MzSpectrum {
+    fn to_binary(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    fn from_binary(bytes: &[u8]) -> Result<Self, ColumnDecodeError> {
+        Ok(MzSpectrum::from_bytes(bytes)?)
+    }
+}
+
+/// Convert a JSON column value to its binary encoding, fails if the JSON is no `T`
+pub fn json_to_binary<T: ColumnCodec>(json: &[u8]) -> Result<Vec<u8>, ColumnDecodeError> {
+    Ok(T::decode(json, StorageFormat::Json)?.to_binary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let occurrence: Vec<u32> = vec![1, 2, 300_000];
+        let abundance: Vec<f32> = vec![0.25, 0.5, 0.25];
+        let spectrum = MzSpectrum::new(vec![500.1, 500.6], vec![1.0, 0.4]);
+
+        for format in [StorageFormat::Json, StorageFormat::Binary] {
+            let bytes = |value: Value| match value {
+                Value::Text(text) => text.into_bytes(),
+                Value::Blob(blob) => blob,
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                Vec::<u32>::decode(&bytes(occurrence.encode(format)), format).unwrap(),
+                occurrence
+            );
+            assert_eq!(
+                Vec::<f32>::decode(&bytes(abundance.encode(format)), format).unwrap(),
+                abundance
+            );
+            let decoded = MzSpectrum::decode(&bytes(spectrum.encode(format)), format).unwrap();
+            assert_eq!(
+                (decoded.mz, decoded.intensity),
+                (spectrum.mz.clone(), spectrum.intensity.clone())
+            );
+        }
+
+        assert_eq!(occurrence.to_binary().len(), 12);
+        assert_eq!(
+            json_to_binary::<Vec<u32>>(b"[1, 2, 300000]").unwrap(),
+            occurrence.to_binary()
+        );
+        assert!(json_to_binary::<Vec<u32>>(b"[1, 2.5]").is_err());
+        assert!(Vec::<f64>::from_binary(&[0; 12]).is_err());
+        assert_eq!("Binary".parse::<StorageFormat>(), Ok(StorageFormat::Binary));
+    }
+}
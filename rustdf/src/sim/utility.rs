@@ -1,40 +1,40 @@
-use mscore::data::peptide::{FragmentType, PeptideSequence};
+use mscore::algorithm::fragmentation::FragmentIntensityModel;
+use mscore::data::peptide::{FragmentType, PeptideProductIonSeriesCollection, PeptideSequence};
+use mscore::data::spectrum::MzSpectrum;
+use mscore::simulation::annotation::MzSpectrumAnnotated;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
-use rayon::ThreadPoolBuilder;
+use mscore::parallel;
 use serde_json::to_string;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound::{Excluded, Included};
+use std::ops::{Add, Mul};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::sim::precursor::FrameNoiseSettings;
 
 /// helper function to reshape the flat prosit predicted intensity array into a 3D array where:
-/// 1st dimension: 29 rows for every potential ion since prosit allows precursor sequences up to 30 amino acids
-/// 2nd dimension: 2 columns for B and Y ions
-/// 3rd dimension: 3 channels for charge 1, 2, and 3
+/// 1st dimension: one row for every potential ion, 29 for prosit since it allows precursor sequences up to 30 amino acids
+/// 2nd dimension: one column per ion series, Y and B ions for prosit
+/// 3rd dimension: one channel per fragment charge, 1, 2, and 3 for prosit
 ///
 /// # Arguments
 ///
 /// * `array` - A vector of f64 representing the flat prosit array
+/// * `shape` - The (positions, series, charges) of the array, None for the prosit shape (29, 2, 3)
 ///
 /// # Returns
 ///
-/// * A 3D vector of f64 representing the reshaped prosit array
+/// * A 3D vector of f64 representing the reshaped prosit array, an error if the array length does not match the shape
 ///
-pub fn reshape_prosit_array(array: Vec<f64>) -> Vec<Vec<Vec<f64>>> {
-    let mut array_return: Vec<Vec<Vec<f64>>> = vec![vec![vec![0.0; 3]; 2]; 29];
-    let mut ptr = 0;
-
-    for c in 0..3 {
-        for row in 0..29 {
-            // Fill in the Y ion values
-            array_return[row][0][c] = array[ptr];
-            ptr += 1;
-        }
-        for row in 0..29 {
-            // Fill in the B ion values
-            array_return[row][1][c] = array[ptr];
-            ptr += 1;
-        }
-    }
-
-    array_return
+pub fn reshape_prosit_array(
+    array: Vec<f64>,
+    shape: Option<(usize, usize, usize)>,
+) -> Result<Vec<Vec<Vec<f64>>>, String> {
+    mscore::chemistry::utility::reshape_prosit_array(array, shape)
 }
 
 /// helper function to convert a peptide ion to all possible ions and serialize the result to a json string
@@ -46,30 +46,61 @@ pub fn reshape_prosit_array(array: Vec<f64>) -> Vec<Vec<Vec<f64>>> {
 /// * `intensity_pred_flat` - A vector of f64 representing the flat prosit predicted intensity array
 /// * `normalize` - A bool indicating whether to normalize the intensity values
 /// * `half_charge_one` - A bool indicating whether to use half charge one
+/// * `fragment_types` - Fragment types to include, `[B, Y]` for prosit; a and c ions reuse the predicted b
+///   intensities, x and z ions the predicted y intensities, e.g. `[B, Y, C, Z]` for EThcD
 ///
 /// # Returns
 ///
-/// * A json string representing the peptide ions ready to pe put into a database
+/// * A json string representing the peptide ions ready to pe put into a database, an error if the intensity array
+///   is no flat prosit array or no fragment type is given
 ///
 pub fn sequence_to_all_ions(
     sequence: &str,
     charge: i32,
-    intensity_pred_flat: &Vec<f64>, // Assuming this is the reshaped intensity predictions array
+    intensity_pred_flat: &[f64], // Assuming this is the reshaped intensity predictions array
     normalize: bool,
     half_charge_one: bool,
     peptide_id: Option<i32>,
-) -> String {
+    fragment_types: &[FragmentType],
+) -> Result<String, String> {
+    if fragment_types.is_empty() {
+        return Err("at least one fragment type is required".to_string());
+    }
+
+    // one pair of complementary series per cleavage, ions of types that were not requested are dropped
+    let mut n_terminal_types: Vec<FragmentType> = Vec::new();
+    for fragment_type in fragment_types {
+        let n_terminal = if fragment_type.is_n_terminal() { *fragment_type } else { fragment_type.complement() };
+        if !n_terminal_types.contains(&n_terminal) {
+            n_terminal_types.push(n_terminal);
+        }
+    }
+
     let peptide_sequence = PeptideSequence::new(sequence.to_string(), peptide_id);
-    let fragments = peptide_sequence.associate_with_predicted_intensities(
-        charge,
-        FragmentType::B,
-        intensity_pred_flat.clone(),
-        normalize,
-        half_charge_one,
-    );
-    to_string(&fragments).unwrap()
+    let mut series = Vec::new();
+    for n_terminal in n_terminal_types {
+        let fragments = peptide_sequence.associate_with_predicted_intensities(
+            charge,
+            n_terminal,
+            intensity_pred_flat.to_vec(),
+            normalize,
+            half_charge_one,
+            None,
+        )?;
+        for mut ion_series in fragments.peptide_ions {
+            if !fragment_types.contains(&n_terminal) {
+                ion_series.n_ions.clear();
+            }
+            if !fragment_types.contains(&n_terminal.complement()) {
+                ion_series.c_ions.clear();
+            }
+            series.push(ion_series);
+        }
+    }
+    Ok(to_string(&PeptideProductIonSeriesCollection::new(series)).unwrap())
 }
 
+#[allow(clippy::too_many_arguments, reason = "one column per peptide ion plus the conversion settings, as passed from Python")]
 pub fn sequence_to_all_ions_par(
     sequences: Vec<&str>,
     charges: Vec<i32>,
@@ -78,11 +109,9 @@ pub fn sequence_to_all_ions_par(
     half_charge_one: bool,
     num_threads: usize,
     peptide_ids: Vec<Option<i32>>,
-) -> Vec<String> {
-    let thread_pool = ThreadPoolBuilder::new()
-        .num_threads(num_threads)
-        .build()
-        .unwrap();
+    fragment_types: &[FragmentType],
+) -> Result<Vec<String>, String> {
+    let thread_pool = parallel::pool(num_threads);
 
     let result = thread_pool.install(|| {
         sequences
@@ -98,6 +127,7 @@ pub fn sequence_to_all_ions_par(
                     normalize,
                     half_charge_one,
                     *peptide_id,
+                    fragment_types,
                 )
             })
             .collect()
@@ -105,3 +135,642 @@ pub fn sequence_to_all_ions_par(
 
     result
 }
+
+/// helper function to derive a sub-seed for the m/z noise of one ion in one scan of a frame,
+/// the result only depends on its arguments, so noise is reproducible regardless of thread count or frame order
+///
+/// # Arguments
+///
+/// * `seed` - A u64 representing the global seed
+/// * `frame_id` - A u32 representing the frame id
+/// * `peptide_id` - A u32 representing the peptide id
+/// * `charge` - An i8 representing the charge of the ion
+/// * `scan` - A u32 representing the scan
+///
+/// # Returns
+///
+/// * A u64 representing the sub-seed
+///
+pub fn noise_seed(seed: u64, frame_id: u32, peptide_id: u32, charge: i8, scan: u32) -> u64 {
+    // splitmix64 finalizer, applied once per component
+    let mix = |mut z: u64| -> u64 {
+        z = z.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    };
+    [frame_id as u64, peptide_id as u64, charge as u8 as u64, scan as u64]
+        .iter()
+        .fold(mix(seed), |acc, &value| mix(acc ^ value))
+}
+
+/// helper function to create the m/z noise generator of one ion in one scan of a frame, None if no seed is set
+pub fn noise_rng(seed: Option<u64>, frame_id: u32, peptide_id: u32, charge: i8, scan: u32) -> Option<StdRng> {
+    seed.map(|seed| StdRng::seed_from_u64(noise_seed(seed, frame_id, peptide_id, charge, scan)))
+}
+
+/// helper function to add m/z noise to a spectrum, with a generator the noise is drawn from it,
+/// otherwise from the thread local generator
+///
+/// # Arguments
+///
+/// * `spectrum` - A MzSpectrum to add noise to
+/// * `uniform` - A bool indicating whether to use uniform noise, otherwise normal noise is used
+/// * `ppm` - A f64 representing the noise in ppm
+/// * `right_drag` - A bool indicating whether uniform noise is shifted to the right
+/// * `rng` - An optional seeded generator, see `noise_rng`
+///
+/// # Returns
+///
+/// * A MzSpectrum with noisy m/z values
+///
+pub fn add_mz_noise(
+    spectrum: MzSpectrum,
+    uniform: bool,
+    ppm: f64,
+    right_drag: bool,
+    rng: Option<&mut StdRng>,
+) -> MzSpectrum {
+    match (rng, uniform) {
+        (Some(rng), true) => spectrum.add_mz_noise_uniform_with_rng(ppm, right_drag, rng),
+        (Some(rng), false) => spectrum.add_mz_noise_normal_with_rng(ppm, rng),
+        (None, true) => spectrum.add_mz_noise_uniform(ppm, right_drag),
+        (None, false) => spectrum.add_mz_noise_normal(ppm),
+    }
+}
+
+/// helper function to add m/z noise to an annotated spectrum, see `add_mz_noise`
+pub fn add_mz_noise_annotated(
+    spectrum: MzSpectrumAnnotated,
+    uniform: bool,
+    ppm: f64,
+    right_drag: bool,
+    rng: Option<&mut StdRng>,
+) -> MzSpectrumAnnotated {
+    match (rng, uniform) {
+        (Some(rng), true) => spectrum.add_mz_noise_uniform_with_rng(ppm, right_drag, rng),
+        (Some(rng), false) => spectrum.add_mz_noise_normal_with_rng(ppm, rng),
+        (None, true) => spectrum.add_mz_noise_uniform(ppm, right_drag),
+        (None, false) => spectrum.add_mz_noise_normal(ppm),
+    }
+}
+
+/// helper function to create the background noise generator of a frame, None if no seed is set
+pub fn background_noise_rng(seed: Option<u64>, frame_id: u32) -> Option<StdRng> {
+    // the inverted seed keeps background draws apart from the per ion generators
+    seed.map(|seed| StdRng::seed_from_u64(noise_seed(!seed, frame_id, 0, 0, 0)))
+}
+
+/// helper function to sample detector counts, every intensity is replaced by a Poisson sample
+///
+/// # Arguments
+///
+/// * `spectrum` - A MzSpectrum holding expected numbers of events as intensities
+/// * `rng` - An optional seeded generator, see `noise_rng`
+///
+/// # Returns
+///
+/// * A MzSpectrum with sampled intensities, peaks without events are removed
+///
+pub fn add_detection_noise(spectrum: MzSpectrum, rng: Option<&mut StdRng>) -> MzSpectrum {
+    match rng {
+        Some(rng) => spectrum.sample_intensity_poisson_with_rng(rng),
+        None => spectrum.sample_intensity_poisson_with_rng(&mut rand::thread_rng()),
+    }
+}
+
+/// Policy that turns simulated intensities, expected numbers of detector events, into stored intensities
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntensityQuantization {
+    /// round to the nearest integer, the behaviour of previous releases
+    #[default]
+    Round,
+    /// truncate to the integer below
+    Floor,
+    /// round up with a probability equal to the fractional part, preserves the total intensity in expectation
+    Stochastic,
+    /// keep the floating point intensities
+    None,
+}
+
+impl IntensityQuantization {
+    /// Parse a policy from its name, one of `round`, `floor`, `stochastic` or `none`
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "round" => Ok(IntensityQuantization::Round),
+            "floor" => Ok(IntensityQuantization::Floor),
+            "stochastic" => Ok(IntensityQuantization::Stochastic),
+            "none" => Ok(IntensityQuantization::None),
+            _ => Err(format!(
+                "Unknown intensity quantization: {}, expected one of round, floor, stochastic or none",
+                name
+            )),
+        }
+    }
+
+    /// Quantize intensities, stochastic rounding draws from `rng` or from the thread local generator
+    ///
+    /// # Arguments
+    ///
+    /// * `intensity` - The intensities to quantize
+    /// * `rng` - An optional seeded generator, see `quantization_rng`
+    ///
+    /// # Returns
+    ///
+    /// * A vector of quantized intensities
+    ///
+    pub fn quantize(&self, intensity: &[f64], rng: Option<&mut StdRng>) -> Vec<f64> {
+        match self {
+            IntensityQuantization::Round => intensity.iter().map(|x| x.round()).collect(),
+            IntensityQuantization::Floor => intensity.iter().map(|x| x.floor()).collect(),
+            IntensityQuantization::None => intensity.to_vec(),
+            IntensityQuantization::Stochastic => match rng {
+                Some(rng) => stochastic_round(intensity, rng),
+                None => stochastic_round(intensity, &mut rand::thread_rng()),
+            },
+        }
+    }
+}
+
+fn stochastic_round<R: Rng>(intensity: &[f64], rng: &mut R) -> Vec<f64> {
+    intensity
+        .iter()
+        .map(|x| {
+            let floor = x.floor();
+            match rng.gen::<f64>() < x - floor {
+                true => floor + 1.0,
+                false => floor,
+            }
+        })
+        .collect()
+}
+
+/// helper function to create the generator for stochastic intensity rounding of a frame, None if no seed is set
+pub fn quantization_rng(seed: Option<u64>, frame_id: u32) -> Option<StdRng> {
+    // distinct from the per ion and the background generators
+    seed.map(|seed| StdRng::seed_from_u64(noise_seed(!seed, frame_id, 0, 0, 1)))
+}
+
+/// Noise, limit and quantization options shared by all frame building calls of the frame builders
+///
+/// Precursor frames use the precursor noise settings, fragment frames the fragment noise settings,
+/// the precursor frame builder only reads the precursor noise settings and the seed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameBuildOptions {
+    /// if false, fragment frames have the m/z distribution of the precursor frame but are quadrupole filtered
+    pub fragmentation: bool,
+    pub mz_noise_precursor: bool,
+    /// uniform instead of normal m/z noise
+    pub uniform: bool,
+    pub precursor_noise_ppm: f64,
+    pub mz_noise_fragment: bool,
+    pub fragment_noise_ppm: f64,
+    /// shift uniform m/z noise to the right
+    pub right_drag: bool,
+    /// minimum m/z, default 100.0 for fragment frames, precursor frames are not limited by default
+    pub mz_min: Option<f64>,
+    /// maximum m/z, default 1700.0 for fragment frames, precursor frames are not limited by default
+    pub mz_max: Option<f64>,
+    /// minimum intensity, default 1.0
+    pub intensity_min: Option<f64>,
+    /// maximum intensity, default 1e9
+    pub intensity_max: Option<f64>,
+    /// seed for m/z noise, detection noise and stochastic rounding, None draws from the thread local generator
+    pub seed: Option<u64>,
+    pub quantization: IntensityQuantization,
+}
+
+impl Default for FrameBuildOptions {
+    /// fragmented frames without noise, default limits and rounded intensities
+    fn default() -> Self {
+        FrameBuildOptions {
+            fragmentation: true,
+            mz_noise_precursor: false,
+            uniform: false,
+            precursor_noise_ppm: 0.0,
+            mz_noise_fragment: false,
+            fragment_noise_ppm: 0.0,
+            right_drag: false,
+            mz_min: None,
+            mz_max: None,
+            intensity_min: None,
+            intensity_max: None,
+            seed: None,
+            quantization: IntensityQuantization::Round,
+        }
+    }
+}
+
+/// Iterator that lazily builds frames in batches of at most `batch_size` frames, ordered by frame id
+///
+/// Only the batch handed out last is alive, so peak memory is bounded by the batch size and not by the
+/// number of requested frames. `build` turns the frame ids of a batch into the batch.
+pub struct FrameBatchIterator<F> {
+    frame_ids: Vec<u32>,
+    batch_size: usize,
+    position: usize,
+    build: F,
+}
+
+impl<F> FrameBatchIterator<F> {
+    /// Create an iterator over the sorted `frame_ids`, `batch_size` has to be greater than 0
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustdf::sim::utility::FrameBatchIterator;
+    ///
+    /// let batches: Vec<Vec<u32>> = FrameBatchIterator::new(vec![5, 1, 4, 2, 3], 2, |ids: &[u32]| ids.to_vec()).collect();
+    /// assert_eq!(batches, vec![vec![1, 2], vec![3, 4], vec![5]]);
+    /// ```
+    pub fn new(frame_ids: Vec<u32>, batch_size: usize, build: F) -> Self {
+        assert!(batch_size > 0, "Batch size must be greater than 0");
+
+        let mut frame_ids = frame_ids;
+        frame_ids.sort();
+
+        FrameBatchIterator { frame_ids, batch_size, position: 0, build }
+    }
+}
+
+impl<F, T> Iterator for FrameBatchIterator<F>
+where
+    F: FnMut(&[u32]) -> T,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.frame_ids.len() {
+            return None;
+        }
+
+        let end = (self.position + self.batch_size).min(self.frame_ids.len());
+        let batch = (self.build)(&self.frame_ids[self.position..end]);
+        self.position = end;
+
+        Some(batch)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.frame_ids.len() - self.position).div_ceil(self.batch_size);
+        (remaining, Some(remaining))
+    }
+}
+
+/// helper function to draw random background peaks of a frame and assign each peak to a random scan
+///
+/// # Arguments
+///
+/// * `settings` - The FrameNoiseSettings holding density, mean intensity and m/z range
+/// * `scans` - The scans a peak can be assigned to, must not be empty
+/// * `rng` - The random number generator
+///
+/// # Returns
+///
+/// * A map from scan to the background peaks of that scan
+///
+pub fn random_background_peaks<R: Rng>(
+    settings: &FrameNoiseSettings,
+    scans: &[u32],
+    rng: &mut R,
+) -> BTreeMap<u32, MzSpectrum> {
+    let background = MzSpectrum::random_background_with_rng(
+        settings.background_noise_density,
+        settings.background_mz_min,
+        settings.background_mz_max,
+        settings.background_noise_intensity,
+        rng,
+    );
+
+    let mut peaks: BTreeMap<u32, MzSpectrum> = BTreeMap::new();
+    for (mz, intensity) in background.mz.into_iter().zip(background.intensity) {
+        let scan = scans[rng.gen_range(0..scans.len())];
+        let spectrum = peaks
+            .entry(scan)
+            .or_insert_with(|| MzSpectrum::new(Vec::new(), Vec::new()));
+        spectrum.mz.push(mz);
+        spectrum.intensity.push(intensity);
+    }
+    peaks
+}
+
+/// helper function to quantize a collision energy in eV to the integer key of the fragment ion maps (centi-eV)
+pub fn quantize_collision_energy(collision_energy: f64) -> i32 {
+    (collision_energy * 1e2).round() as i32
+}
+
+/// Counts of fragment ion lookups by collision energy, shared by all threads building frames
+#[derive(Debug, Default)]
+pub struct FragmentLookupStats {
+    exact: AtomicUsize,
+    interpolated: AtomicUsize,
+    missing: AtomicUsize,
+    predicted: AtomicUsize,
+}
+
+impl FragmentLookupStats {
+    /// number of lookups that hit a precomputed collision energy
+    pub fn exact(&self) -> usize {
+        self.exact.load(Ordering::Relaxed)
+    }
+
+    /// number of lookups that were interpolated, or taken from the nearest collision energy if outside the precomputed range
+    pub fn interpolated(&self) -> usize {
+        self.interpolated.load(Ordering::Relaxed)
+    }
+
+    /// number of lookups without any precomputed collision energy for the peptide and charge
+    pub fn missing(&self) -> usize {
+        self.missing.load(Ordering::Relaxed)
+    }
+
+    /// number of missing lookups that were answered by a fragment intensity model
+    pub fn predicted(&self) -> usize {
+        self.predicted.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.exact.store(0, Ordering::Relaxed);
+        self.interpolated.store(0, Ordering::Relaxed);
+        self.missing.store(0, Ordering::Relaxed);
+        self.predicted.store(0, Ordering::Relaxed);
+    }
+}
+
+/// helper function to get the fragment ion spectra of a peptide ion at a collision energy, if the collision energy
+/// was not precomputed, intensities are interpolated linearly between the two nearest precomputed collision energies
+///
+/// # Arguments
+///
+/// * `fragment_ions` - A map from (peptide id, charge, quantized collision energy) to fragment ion spectra
+/// * `peptide_id` - A u32 representing the peptide id
+/// * `charge` - An i8 representing the charge of the precursor
+/// * `collision_energy` - An i32 representing the quantized collision energy, see `quantize_collision_energy`
+/// * `stats` - The FragmentLookupStats the lookup is counted in
+///
+/// # Returns
+///
+/// * The fragment ion spectra, None if no collision energy was precomputed for the peptide and charge
+///
+pub fn lookup_fragment_spectra<C, T>(
+    fragment_ions: &BTreeMap<(u32, i8, i32), (C, Vec<T>)>,
+    peptide_id: u32,
+    charge: i8,
+    collision_energy: i32,
+    stats: &FragmentLookupStats,
+) -> Option<Vec<T>>
+where
+    T: Clone + Add<Output = T> + Mul<f64, Output = T>,
+{
+    let key = (peptide_id, charge, collision_energy);
+    if let Some((_, spectra)) = fragment_ions.get(&key) {
+        stats.exact.fetch_add(1, Ordering::Relaxed);
+        return Some(spectra.clone());
+    }
+
+    let lower = fragment_ions
+        .range((Included((peptide_id, charge, i32::MIN)), Excluded(key)))
+        .next_back();
+    let upper = fragment_ions
+        .range((Excluded(key), Included((peptide_id, charge, i32::MAX))))
+        .next();
+
+    let spectra = match (lower, upper) {
+        (Some(((_, _, lower_energy), (_, lower))), Some(((_, _, upper_energy), (_, upper)))) => {
+            let weight =
+                (collision_energy - lower_energy) as f64 / (upper_energy - lower_energy) as f64;
+            lower
+                .iter()
+                .zip(upper.iter())
+                .map(|(l, u)| l.clone() * (1.0 - weight) + u.clone() * weight)
+                .collect()
+        }
+        (Some((_, (_, nearest))), None) | (None, Some((_, (_, nearest)))) => nearest.clone(),
+        (None, None) => {
+            stats.missing.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+    };
+
+    stats.interpolated.fetch_add(1, Ordering::Relaxed);
+    Some(spectra)
+}
+
+/// Fragment ion spectra of a `FragmentIntensityModel`, used for ions without precomputed intensities,
+/// predictions are cached by peptide id, charge and quantized collision energy
+pub struct PredictedFragmentIons<T> {
+    model: Arc<dyn FragmentIntensityModel>,
+    cache: RwLock<HashMap<(u32, i8, i32), Vec<T>>>,
+}
+
+impl<T: Clone> PredictedFragmentIons<T> {
+    pub fn new(model: Arc<dyn FragmentIntensityModel>) -> Self {
+        PredictedFragmentIons {
+            model,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn model(&self) -> &Arc<dyn FragmentIntensityModel> {
+        &self.model
+    }
+
+    /// Get the fragment ion spectra of a peptide ion, predicting them on the first request
+    ///
+    /// # Arguments
+    ///
+    /// * `peptide_id` - A u32 representing the peptide id
+    /// * `sequence` - The sequence of the peptide
+    /// * `charge` - An i8 representing the charge of the precursor
+    /// * `collision_energy` - An i32 representing the quantized collision energy, see `quantize_collision_energy`
+    /// * `stats` - The FragmentLookupStats the prediction is counted in
+    /// * `to_spectra` - Turns the predicted ion series into spectra, one per fragment charge
+    ///
+    /// # Returns
+    ///
+    /// * The fragment ion spectra
+    ///
+    pub fn get_or_predict<F>(
+        &self,
+        peptide_id: u32,
+        sequence: &PeptideSequence,
+        charge: i8,
+        collision_energy: i32,
+        stats: &FragmentLookupStats,
+        to_spectra: F,
+    ) -> Vec<T>
+    where
+        F: Fn(&PeptideProductIonSeriesCollection) -> Vec<T>,
+    {
+        stats.predicted.fetch_add(1, Ordering::Relaxed);
+        let key = (peptide_id, charge, collision_energy);
+        if let Some(spectra) = self.cache.read().unwrap().get(&key) {
+            return spectra.clone();
+        }
+
+        // predict without holding the lock, concurrent predictions of the same key give the same spectra
+        let prediction = self
+            .model
+            .predict(sequence, charge as i32, collision_energy as f64 / 1e2);
+        let spectra = to_spectra(&prediction);
+        self.cache
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_insert(spectra)
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_fragment_spectra_interpolates() {
+        let spectrum = |intensity: f64| MzSpectrum::new(vec![500.0], vec![intensity]);
+        let fragment_ions = BTreeMap::from([
+            ((1, 2, 2000), ((), vec![spectrum(10.0)])),
+            ((1, 2, 3000), ((), vec![spectrum(30.0)])),
+        ]);
+        let stats = FragmentLookupStats::default();
+
+        let exact = lookup_fragment_spectra(&fragment_ions, 1, 2, 2000, &stats).unwrap();
+        assert_eq!(exact[0].intensity, vec![10.0]);
+
+        let interpolated = lookup_fragment_spectra(&fragment_ions, 1, 2, 2500, &stats).unwrap();
+        assert_eq!(interpolated[0].mz, vec![500.0]);
+        assert!((interpolated[0].intensity[0] - 20.0).abs() < 1e-9);
+
+        let nearest = lookup_fragment_spectra(&fragment_ions, 1, 2, 4000, &stats).unwrap();
+        assert_eq!(nearest[0].intensity, vec![30.0]);
+
+        // other charge states of the same peptide must not be used
+        assert!(lookup_fragment_spectra(&fragment_ions, 1, 3, 2500, &stats).is_none());
+
+        assert_eq!((stats.exact(), stats.interpolated(), stats.missing()), (1, 2, 1));
+        stats.reset();
+        assert_eq!((stats.exact(), stats.interpolated(), stats.missing()), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_lookup_fragment_spectra_with_mobility_ramp() {
+        use mscore::timstof::collision::{CollisionEnergyMobilityRamp, CollisionEnergySource, TimsTofCollisionEnergy};
+
+        let spectrum = |intensity: f64| MzSpectrum::new(vec![500.0], vec![intensity]);
+        let fragment_ions = BTreeMap::from([
+            ((1, 2, 2000), ((), vec![spectrum(10.0)])),
+            ((1, 2, 4000), ((), vec![spectrum(30.0)])),
+        ]);
+        let ramp = CollisionEnergyMobilityRamp::new(vec![(0.6, 20.0), (1.4, 40.0)], vec![1, 2], vec![1.4, 0.9]).unwrap();
+        let source = CollisionEnergySource::MobilityRamp(ramp);
+        let stats = FragmentLookupStats::default();
+
+        // scan 2 at 1/K0 0.9 is fragmented at 27.5 eV, between the precomputed collision energies
+        let collision_energy = quantize_collision_energy(source.get_collision_energy(3, 2));
+        assert_eq!(collision_energy, 2750);
+        let spectra = lookup_fragment_spectra(&fragment_ions, 1, 2, collision_energy, &stats).unwrap();
+        assert!((spectra[0].intensity[0] - 17.5).abs() < 1e-9);
+
+        let collision_energy = quantize_collision_energy(source.get_collision_energy(3, 1));
+        let spectra = lookup_fragment_spectra(&fragment_ions, 1, 2, collision_energy, &stats).unwrap();
+        assert_eq!(spectra[0].intensity, vec![30.0]);
+        assert_eq!((stats.exact(), stats.interpolated()), (1, 1));
+    }
+
+    #[test]
+    fn test_sequence_to_all_ions_fragment_types() {
+        let intensities = vec![1.0; 174];
+        let kinds = |fragment_types: &[FragmentType]| -> Vec<(usize, usize, String)> {
+            let json = sequence_to_all_ions("PEPTIDEK", 2, &intensities, true, false, None, fragment_types).unwrap();
+            let collection: PeptideProductIonSeriesCollection = serde_json::from_str(&json).unwrap();
+            collection.peptide_ions.iter().map(|series| {
+                let kind = series.n_ions.first().or(series.c_ions.first()).unwrap().kind.to_string();
+                (series.n_ions.len(), series.c_ions.len(), kind)
+            }).collect()
+        };
+
+        // b and y ions of both fragment charges, as before fragment types could be chosen
+        assert_eq!(kinds(&[FragmentType::B, FragmentType::Y]), vec![(7, 7, "b".to_string()), (7, 7, "b".to_string())]);
+        // c and z ions are added as series of their own, z without c keeps only the C-terminal ions
+        assert_eq!(kinds(&[FragmentType::Y, FragmentType::B, FragmentType::C, FragmentType::Z]).len(), 4);
+        assert_eq!(kinds(&[FragmentType::Z]), vec![(0, 7, "z".to_string()), (0, 7, "z".to_string())]);
+        assert!(sequence_to_all_ions("PEPTIDEK", 2, &intensities, true, false, None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_predicted_fragment_ions_are_cached() {
+        use mscore::algorithm::fragmentation::MobileProtonModel;
+
+        let predicted: PredictedFragmentIons<MzSpectrum> =
+            PredictedFragmentIons::new(Arc::new(MobileProtonModel));
+        let sequence = PeptideSequence::new("PEPTIDEK".to_string(), Some(1));
+        let stats = FragmentLookupStats::default();
+        let calls = AtomicUsize::new(0);
+        let to_spectra = |prediction: &PeptideProductIonSeriesCollection| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            prediction
+                .peptide_ions
+                .iter()
+                .map(|series| series.generate_mono_isotopic_spectrum())
+                .collect::<Vec<_>>()
+        };
+
+        let first = predicted.get_or_predict(1, &sequence, 2, 3000, &stats, to_spectra);
+        let second = predicted.get_or_predict(1, &sequence, 2, 3000, &stats, to_spectra);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].mz, second[0].mz);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        predicted.get_or_predict(1, &sequence, 3, 3000, &stats, to_spectra);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.predicted(), 3);
+        assert_eq!(stats.missing(), 0);
+    }
+
+    #[test]
+    fn test_intensity_quantization() {
+        let intensity = vec![0.3, 1.5, 2.7, 10.0];
+        let round = IntensityQuantization::Round.quantize(&intensity, None);
+        assert_eq!(round, vec![0.0, 2.0, 3.0, 10.0]);
+        let floor = IntensityQuantization::Floor.quantize(&intensity, None);
+        assert_eq!(floor, vec![0.0, 1.0, 2.0, 10.0]);
+        assert_eq!(IntensityQuantization::None.quantize(&intensity, None), intensity);
+        assert_eq!(IntensityQuantization::from_name("Stochastic"), Ok(IntensityQuantization::Stochastic));
+        assert!(IntensityQuantization::from_name("ceil").is_err());
+
+        // every value is rounded to one of its neighbouring integers, reproducibly for a seed
+        let stochastic = IntensityQuantization::Stochastic;
+        let first = stochastic.quantize(&intensity, quantization_rng(Some(7), 3).as_mut());
+        let second = stochastic.quantize(&intensity, quantization_rng(Some(7), 3).as_mut());
+        assert_eq!(first, second);
+        for (x, q) in intensity.iter().zip(first) {
+            assert!(q == x.floor() || q == x.ceil());
+        }
+    }
+
+    #[test]
+    fn test_stochastic_quantization_preserves_total_intensity() {
+        let intensity: Vec<f64> = (0..1000).map(|i| 0.05 + (i % 20) as f64 * 0.37).collect();
+        let total: f64 = intensity.iter().sum();
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let repeats = 200;
+        let mean = (0..repeats)
+            .map(|_| {
+                IntensityQuantization::Stochastic
+                    .quantize(&intensity, Some(&mut rng))
+                    .iter()
+                    .sum::<f64>()
+            })
+            .sum::<f64>()
+            / repeats as f64;
+
+        // the standard deviation of one total is below sqrt(1000 / 4), of the mean below 1.2
+        assert!((mean - total).abs() < 5.0, "mean {} vs total {}", mean, total);
+
+        // rounding to the nearest integer is biased for this intensity distribution
+        let rounded: f64 = IntensityQuantization::Round.quantize(&intensity, None).iter().sum();
+        assert!((rounded - total).abs() > 5.0);
+    }
+}
@@ -0,0 +1,445 @@
+use crate::data::handle::{IndexConverter, SimpleIndexConverter};
+use crate::data::utility::reconstruct_compressed_data;
+use crate::sim::containers::{FrameToWindowGroupSim, WindowGroupSettingsSim};
+use crate::sim::handle::TimsTofSyntheticsDataHandle;
+use crate::sim::utility::FrameBuildOptions;
+use mscore::timstof::frame::TimsFrame;
+use rayon::prelude::*;
+use rusqlite::{params, Connection};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Writes simulated frames to a Bruker .d folder, consisting of an analysis.tdf sqlite database
+/// holding the meta data and an analysis.tdf_bin holding the zstd compressed peak data.
+///
+/// Since there is no instrument calibration for simulated data, tof and scan indices are derived
+/// with the same linear model `SimpleIndexConverter` uses, so that a `TimsDataset` opened without
+/// the Bruker SDK reads back the simulated m/z and mobility values.
+#[derive(Debug, Clone)]
+pub struct TdfWriter {
+    pub mz_lower: f64,
+    pub mz_upper: f64,
+    pub im_lower: f64,
+    pub im_upper: f64,
+    pub num_scans: u32,
+    pub tof_max_index: u32,
+    /// scan mode written to the Frames table, 8 for DDA-PASEF and 9 for DIA-PASEF
+    pub scan_mode: i64,
+    pub compression_level: i32,
+    pub window_groups: Vec<WindowGroupSettingsSim>,
+    pub frame_to_window_group: Vec<FrameToWindowGroupSim>,
+}
+
+/// peak data and Frames table values of a single compressed frame
+struct CompressedFrame {
+    frame_id: i64,
+    retention_time: f64,
+    ms_ms_type: i64,
+    max_intensity: u32,
+    summed_intensity: u64,
+    num_peaks: usize,
+    max_peaks_per_scan: usize,
+    data: Vec<u8>,
+}
+
+impl TdfWriter {
+    /// Create a writer from the content of a synthetics database
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - A handle to the synthetics database, scans and frames are used to derive the
+    ///   mobility range and the scan mode, DIA runs also provide their window groups
+    /// * `options` - The options the frames are built with, their m/z limits are the acquisition
+    ///   range of the run and default to 100.0 - 1700.0 as for fragment frames
+    ///
+    pub fn new(handle: &TimsTofSyntheticsDataHandle, options: &FrameBuildOptions) -> rusqlite::Result<Self> {
+        let frames = handle.read_frames()?;
+        let mut scans = handle.read_scans()?;
+        scans.sort_by_key(|s| s.scan);
+
+        let num_scans = scans.last().map(|s| s.scan + 1).unwrap_or(0);
+
+        // fit the linear scan to mobility model from the outermost scans
+        let (im_upper, im_lower) = match (scans.first(), scans.last()) {
+            (Some(first), Some(last)) if last.scan > first.scan => {
+                let slope =
+                    (last.mobility - first.mobility) as f64 / (last.scan - first.scan) as f64;
+                let intercept = first.mobility as f64 - slope * first.scan as f64;
+                (intercept, intercept + slope * num_scans as f64)
+            }
+            (Some(first), _) => (first.mobility as f64, first.mobility as f64),
+            _ => (0.0, 0.0),
+        };
+
+        let is_dia = frames.iter().any(|f| f.ms_type == 9);
+
+        let (window_groups, frame_to_window_group) = match is_dia {
+            true => (
+                handle.read_window_group_settings()?,
+                handle.read_frame_to_window_group()?,
+            ),
+            false => (Vec::new(), Vec::new()),
+        };
+
+        Ok(TdfWriter {
+            mz_lower: options.mz_min.unwrap_or(100.0),
+            mz_upper: options.mz_max.unwrap_or(1700.0),
+            im_lower,
+            im_upper,
+            num_scans,
+            tof_max_index: 400_000,
+            scan_mode: if is_dia { 9 } else { 8 },
+            compression_level: 1,
+            window_groups,
+            frame_to_window_group,
+        })
+    }
+
+    /// Create a writer from a synthetics database on disk, see `new`
+    pub fn from_path(path: &Path, options: &FrameBuildOptions) -> rusqlite::Result<Self> {
+        let handle = TimsTofSyntheticsDataHandle::new_read_only(path)?;
+        Self::new(&handle, options)
+    }
+
+    fn index_converter(&self) -> SimpleIndexConverter {
+        SimpleIndexConverter::from_boundaries(
+            self.mz_lower,
+            self.mz_upper,
+            self.tof_max_index,
+            self.im_lower,
+            self.im_upper,
+            self.num_scans,
+        )
+    }
+
+    /// Create the .d folder with an empty analysis.tdf_bin and an analysis.tdf holding the
+    /// GlobalMetadata, DIA window tables and an empty Frames table, existing files are replaced
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the .d folder
+    ///
+    pub fn create(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(path)?;
+
+        let tdf_path = path.join("analysis.tdf");
+        if tdf_path.exists() {
+            fs::remove_file(&tdf_path)?;
+        }
+        fs::File::create(path.join("analysis.tdf_bin"))?;
+
+        let mut connection = Connection::open(&tdf_path)?;
+        connection.execute_batch(
+            "CREATE TABLE GlobalMetadata (Key TEXT PRIMARY KEY, Value TEXT);
+            CREATE TABLE Frames (
+                Id INTEGER PRIMARY KEY, Time REAL NOT NULL, Polarity CHAR(1) NOT NULL,
+                ScanMode INTEGER NOT NULL, MsMsType INTEGER NOT NULL, TimsId INTEGER,
+                MaxIntensity INTEGER NOT NULL, SummedIntensities INTEGER NOT NULL,
+                NumScans INTEGER NOT NULL, NumPeaks INTEGER NOT NULL,
+                MzCalibration INTEGER NOT NULL, T1 REAL NOT NULL, T2 REAL NOT NULL,
+                TimsCalibration INTEGER NOT NULL, PropertyGroup INTEGER,
+                AccumulationTime REAL NOT NULL, RampTime REAL NOT NULL
+            );
+            CREATE TABLE DiaFrameMsMsInfo (Frame INTEGER PRIMARY KEY, WindowGroup INTEGER NOT NULL);
+            CREATE TABLE DiaFrameMsMsWindows (
+                WindowGroup INTEGER NOT NULL, ScanNumBegin INTEGER NOT NULL,
+                ScanNumEnd INTEGER NOT NULL, IsolationMz REAL NOT NULL,
+                IsolationWidth REAL NOT NULL, CollisionEnergy REAL NOT NULL,
+                PRIMARY KEY (WindowGroup, ScanNumBegin)
+            );",
+        )?;
+
+        let global_meta: Vec<(&str, String)> = vec![
+            ("SchemaType", "TDF".to_string()),
+            ("SchemaVersionMajor", "3".to_string()),
+            ("SchemaVersionMinor", "7".to_string()),
+            ("AcquisitionSoftwareVendor", "rustdf".to_string()),
+            ("InstrumentVendor", "Bruker".to_string()),
+            ("ClosedProperly", "1".to_string()),
+            ("TimsCompressionType", "2".to_string()),
+            ("MaxNumPeaksPerScan", "0".to_string()),
+            ("MzAcqRangeLower", self.mz_lower.to_string()),
+            ("MzAcqRangeUpper", self.mz_upper.to_string()),
+            ("OneOverK0AcqRangeLower", self.im_lower.to_string()),
+            ("OneOverK0AcqRangeUpper", self.im_upper.to_string()),
+            // the reader adds one to the number of samples to get the tof max index
+            ("DigitizerNumSamples", (self.tof_max_index - 1).to_string()),
+        ];
+
+        let transaction = connection.transaction()?;
+        for (key, value) in global_meta {
+            transaction.execute(
+                "INSERT INTO GlobalMetadata (Key, Value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+        }
+        for window in &self.window_groups {
+            transaction.execute(
+                "INSERT INTO DiaFrameMsMsWindows VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    window.window_group,
+                    window.scan_start,
+                    window.scan_end,
+                    window.isolation_mz,
+                    window.isolation_width,
+                    window.collision_energy
+                ],
+            )?;
+        }
+        for info in &self.frame_to_window_group {
+            transaction.execute(
+                "INSERT INTO DiaFrameMsMsInfo VALUES (?1, ?2)",
+                params![info.frame_id, info.window_group],
+            )?;
+        }
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Append frames to a .d folder created with `create`, frames are compressed in parallel on the
+    /// current rayon pool, so a run can be written batch by batch as it is simulated
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - Frames to write, ids need to continue the frames already written without gaps
+    /// * `path` - Path of the .d folder
+    ///
+    pub fn write_frames(&self, frames: &[TimsFrame], path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open(path.join("analysis.tdf"))?;
+
+        // the reader locates frames by their position in the Frames table
+        let num_written: i64 =
+            connection.query_row("SELECT COUNT(*) FROM Frames", [], |row| row.get(0))?;
+        for (i, frame) in frames.iter().enumerate() {
+            let expected = num_written + i as i64 + 1;
+            if frame.frame_id as i64 != expected {
+                return Err(format!(
+                    "frames need to be written in order, expected frame {} but got {}",
+                    expected, frame.frame_id
+                )
+                .into());
+            }
+        }
+
+        let index_converter = self.index_converter();
+        let compressed: Vec<CompressedFrame> = frames
+            .par_iter()
+            .map(|frame| self.compress_frame(frame, &index_converter))
+            .collect::<Result<_, _>>()?;
+
+        let mut bin_file = OpenOptions::new()
+            .append(true)
+            .open(path.join("analysis.tdf_bin"))?;
+        let mut offset = bin_file.metadata()?.len() as i64;
+
+        let transaction = connection.transaction()?;
+        let mut max_peaks_per_scan = 0;
+        for frame in &compressed {
+            bin_file.write_all(&frame.data)?;
+            transaction.execute(
+                "INSERT INTO Frames VALUES
+                    (?1, ?2, '+', ?3, ?4, ?5, ?6, ?7, ?8, ?9, 1, 0.0, 0.0, 1, 1, 100.0, 100.0)",
+                params![
+                    frame.frame_id,
+                    frame.retention_time,
+                    self.scan_mode,
+                    frame.ms_ms_type,
+                    offset,
+                    frame.max_intensity,
+                    frame.summed_intensity as i64,
+                    self.num_scans,
+                    frame.num_peaks as i64
+                ],
+            )?;
+            offset += frame.data.len() as i64;
+            max_peaks_per_scan = max_peaks_per_scan.max(frame.max_peaks_per_scan);
+        }
+        transaction.execute(
+            "UPDATE GlobalMetadata SET Value = MAX(CAST(Value AS INTEGER), ?1)
+                WHERE Key = 'MaxNumPeaksPerScan'",
+            params![max_peaks_per_scan as i64],
+        )?;
+        bin_file.flush()?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// convert a frame to tof and scan indices, peaks outside of the m/z range or scan range are
+    /// dropped and peaks falling into the same tof bin of a scan are summed
+    fn compress_frame(
+        &self,
+        frame: &TimsFrame,
+        index_converter: &SimpleIndexConverter,
+    ) -> Result<CompressedFrame, String> {
+        let mz = &frame.ims_frame.mz;
+        let tof = index_converter.mz_to_tof(frame.frame_id as u32, mz);
+
+        let mut peaks: Vec<(u32, u32, f64)> = (0..mz.len())
+            .filter(|&i| {
+                mz[i] >= self.mz_lower
+                    && mz[i] < self.mz_upper
+                    && frame.scan[i] >= 0
+                    && (frame.scan[i] as u32) < self.num_scans
+            })
+            .map(|i| (frame.scan[i] as u32, tof[i], frame.ims_frame.intensity[i]))
+            .collect();
+        peaks.sort_by_key(|p| (p.0, p.1));
+
+        let mut merged: Vec<(u32, u32, f64)> = Vec::with_capacity(peaks.len());
+        for (scan, tof, intensity) in peaks {
+            match merged.last_mut() {
+                Some(last) if last.0 == scan && last.1 == tof => last.2 += intensity,
+                _ => merged.push((scan, tof, intensity)),
+            }
+        }
+
+        // intensities are stored as integers, peaks rounding to zero are dropped
+        let mut scans: Vec<u32> = Vec::with_capacity(merged.len());
+        let mut tofs: Vec<u32> = Vec::with_capacity(merged.len());
+        let mut intensities: Vec<u32> = Vec::with_capacity(merged.len());
+        for (scan, tof, intensity) in merged {
+            let rounded = intensity.round().min(u32::MAX as f64) as u32;
+            if rounded > 0 {
+                scans.push(scan);
+                tofs.push(tof);
+                intensities.push(rounded);
+            }
+        }
+
+        let mut max_peaks_per_scan = 0;
+        let mut current = 0;
+        for (i, scan) in scans.iter().enumerate() {
+            current = if i > 0 && scans[i - 1] == *scan { current + 1 } else { 1 };
+            max_peaks_per_scan = max_peaks_per_scan.max(current);
+        }
+
+        let num_peaks = scans.len();
+        let max_intensity = intensities.iter().cloned().max().unwrap_or(0);
+        let summed_intensity = intensities.iter().map(|&x| x as u64).sum();
+
+        let data = reconstruct_compressed_data(
+            scans,
+            tofs,
+            intensities,
+            self.num_scans,
+            self.compression_level,
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(CompressedFrame {
+            frame_id: frame.frame_id as i64,
+            retention_time: frame.ims_frame.retention_time,
+            ms_ms_type: frame.ms_type.ms_type_numeric() as i64,
+            max_intensity,
+            summed_intensity,
+            num_peaks,
+            max_peaks_per_scan,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::dataset::TimsDataset;
+    use crate::data::handle::TimsData;
+    use crate::sim::containers::{FrameToWindowGroupSim, FramesSim, ScansSim, WindowGroupSettingsSim};
+    use crate::sim::fixtures::in_memory_handle;
+    use mscore::data::spectrum::MsType;
+
+    fn synthetics_fixture() -> TimsTofSyntheticsDataHandle {
+        let handle = in_memory_handle();
+        handle.insert_frames(&[FramesSim::new(1, 0.1, 0), FramesSim::new(2, 0.2, 9), FramesSim::new(3, 0.3, 0)]).unwrap();
+        handle.insert_scans(&[ScansSim::new(0, 1.6), ScansSim::new(1, 1.599), ScansSim::new(999, 0.601)]).unwrap();
+        handle
+            .insert_window_group_settings(&[WindowGroupSettingsSim::new(1, 100, 400, 500.0, 25.0, 30.0)])
+            .unwrap();
+        handle.insert_frame_to_window_group(&[FrameToWindowGroupSim::new(2, 1)]).unwrap();
+        handle
+    }
+
+    fn frame(frame_id: i32, ms_type: MsType, scan: Vec<i32>, mz: Vec<f64>, intensity: Vec<f64>) -> TimsFrame {
+        let mobility = scan.iter().map(|&s| 1.6 - 0.001 * s as f64).collect();
+        let tof = vec![0; scan.len()];
+        TimsFrame::new(frame_id, ms_type, frame_id as f64 * 0.1, scan, mobility, tof, mz, intensity)
+    }
+
+    #[test]
+    fn test_write_frames_round_trip() {
+        let writer = TdfWriter::new(&synthetics_fixture(), &FrameBuildOptions::default()).unwrap();
+        assert_eq!(writer.scan_mode, 9);
+        assert_eq!(writer.num_scans, 1000);
+
+        let path = std::env::temp_dir().join(format!("tdf_writer_test_{}.d", std::process::id()));
+        writer.create(&path).unwrap();
+
+        let frames = vec![
+            frame(1, MsType::Precursor, vec![10, 10, 500], vec![500.25, 800.5, 1200.0], vec![10.0, 20.4, 5.0]),
+            // the second peak is out of the m/z range, the last one rounds to zero intensity
+            frame(2, MsType::FragmentDia, vec![150, 200, 300], vec![300.0, 2000.0, 600.0], vec![3.0, 5.0, 0.2]),
+        ];
+        writer.write_frames(&frames, &path).unwrap();
+        writer
+            .write_frames(&[frame(3, MsType::Precursor, vec![], vec![], vec![])], &path)
+            .unwrap();
+
+        // frames need to continue the written ones
+        assert!(writer
+            .write_frames(&[frame(5, MsType::Precursor, vec![1], vec![500.0], vec![1.0])], &path)
+            .is_err());
+
+        let dataset = TimsDataset::new("", path.to_str().unwrap(), false, false);
+        assert_eq!(dataset.get_frame_count(), 3);
+
+        let first = dataset.get_frame(1);
+        assert_eq!(first.scan, vec![10, 10, 500]);
+        assert_eq!(first.ims_frame.intensity, vec![10.0, 20.0, 5.0]);
+        for (read, written) in first.ims_frame.mz.iter().zip([500.25, 800.5, 1200.0]) {
+            assert!((read - written).abs() < 0.01);
+        }
+        for (read, written) in first.ims_frame.mobility.iter().zip([1.59, 1.59, 1.1]) {
+            assert!((read - written).abs() < 1e-3);
+        }
+
+        let second = dataset.get_frame(2);
+        assert_eq!(second.ms_type, MsType::FragmentDia);
+        assert_eq!(second.scan, vec![150]);
+        assert!((second.ims_frame.mz[0] - 300.0).abs() < 0.01);
+
+        assert!(dataset.get_frame(3).ims_frame.mz.is_empty());
+
+        let in_memory = TimsDataset::new("", path.to_str().unwrap(), true, false);
+        assert_eq!(in_memory.get_frame(1).ims_frame.intensity, vec![10.0, 20.0, 5.0]);
+
+        fs::remove_dir_all(&path).ok();
+    }
+
+    #[test]
+    fn test_mz_range_from_options() {
+        let options = FrameBuildOptions {
+            mz_min: Some(150.0),
+            mz_max: Some(2000.0),
+            ..Default::default()
+        };
+        let writer = TdfWriter::new(&synthetics_fixture(), &options).unwrap();
+        assert_eq!((writer.mz_lower, writer.mz_upper), (150.0, 2000.0));
+
+        let path = std::env::temp_dir().join(format!("tdf_writer_range_test_{}.d", std::process::id()));
+        writer.create(&path).unwrap();
+        // the first peak is below the m/z range of the options, the last one above the default range
+        let frames = vec![frame(1, MsType::Precursor, vec![10, 20, 30], vec![120.0, 500.0, 1800.0], vec![1.0, 2.0, 3.0])];
+        writer.write_frames(&frames, &path).unwrap();
+
+        let dataset = TimsDataset::new("", path.to_str().unwrap(), false, false);
+        let first = dataset.get_frame(1);
+        assert_eq!(first.scan, vec![20, 30]);
+        assert!((first.ims_frame.mz[1] - 1800.0).abs() < 0.01);
+
+        fs::remove_dir_all(&path).ok();
+    }
+}
@@ -0,0 +1,78 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rustdf::data::dataset::TimsDataset;
+use rustdf::data::handle::{get_slice_bounded, TimsData};
+use rustdf::data::meta::read_meta_data_sql;
+use rustdf::data::tdf_bin::TdfBinReader;
+use rustdf::data::utility::{parse_decompressed_bruker_binary_data, with_zstd_decompressed, zstd_decompress};
+
+/// counts allocations and allocated bytes, this binary holds a single test so nothing else allocates meanwhile
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure<R>(f: impl FnOnce() -> R) -> (usize, usize, R) {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::SeqCst), ALLOCATED_BYTES.load(Ordering::SeqCst));
+    let result = f();
+    (
+        ALLOCATIONS.load(Ordering::SeqCst) - allocations,
+        ALLOCATED_BYTES.load(Ordering::SeqCst) - bytes,
+        result,
+    )
+}
+
+#[test]
+fn scratch_decompression_allocates_less() {
+    let data_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d");
+    let offset = read_meta_data_sql(data_path).unwrap()[0].tims_id as usize;
+    let tdf_bin = TdfBinReader::open(data_path);
+    let block = tdf_bin.read_block(offset).unwrap();
+    let compressed = &block[8..];
+    const REPEATS: usize = 20;
+
+    let (fresh_allocations, fresh_bytes, expected) = measure(|| {
+        (0..REPEATS)
+            .fold(None, |_, _| Some(parse_decompressed_bruker_binary_data(&zstd_decompress(compressed).unwrap()).unwrap()))
+            .unwrap()
+    });
+    // the first call of the thread sets up context and buffer
+    with_zstd_decompressed(compressed, |bytes| bytes.len()).unwrap();
+    let (scratch_allocations, scratch_bytes, parsed) = measure(|| {
+        (0..REPEATS)
+            .fold(None, |_, _| Some(with_zstd_decompressed(compressed, parse_decompressed_bruker_binary_data).unwrap().unwrap()))
+            .unwrap()
+    });
+    assert_eq!(parsed, expected);
+    assert!(scratch_allocations < fresh_allocations);
+    assert!(scratch_bytes * 2 < fresh_bytes);
+
+    // the bounded pipeline decodes the same frames as get_slice
+    let dataset = TimsDataset::new("", data_path, false, false);
+    let bounded = get_slice_bounded(&dataset, vec![1; 6], 3, 2);
+    let frame = dataset.get_frame(1);
+    assert_eq!(bounded.frames.len(), 6);
+    assert!(bounded.frames.iter().all(|f| f.tof == frame.tof && f.ims_frame.intensity == frame.ims_frame.intensity));
+}
@@ -0,0 +1,59 @@
+use clap::Parser;
+
+#[allow(dead_code)]
+#[path = "../examples/dda_to_mgf.rs"]
+mod dda_to_mgf;
+
+#[allow(dead_code)]
+#[path = "../examples/simulate_and_detect.rs"]
+mod simulate_and_detect;
+
+#[test]
+fn simulate_and_detect_finds_simulated_ions() {
+    let db_path = std::env::temp_dir().join(format!("simulate_and_detect_test_{}.db", std::process::id()));
+    let args = simulate_and_detect::Args::parse_from([
+        "simulate_and_detect",
+        "--db-path",
+        db_path.to_str().unwrap(),
+        "--num-frames",
+        "200",
+        "--num-threads",
+        "2",
+    ]);
+
+    let report = simulate_and_detect::run(&args);
+    std::fs::remove_file(&db_path).ok();
+
+    let report = report.unwrap();
+    assert_eq!(report.num_ions, 18);
+    assert!(report.recall >= 0.9);
+    assert!(report.precision >= 0.9);
+}
+
+#[test]
+fn dda_to_mgf_exports_scheduled_precursors() {
+    let data_path = std::env::temp_dir().join(format!("dda_to_mgf_test_{}.d", std::process::id()));
+    let mgf_path = data_path.with_extension("mgf");
+    let args = dda_to_mgf::Args::parse_from([
+        "dda_to_mgf",
+        "--data-path",
+        data_path.to_str().unwrap(),
+        "--mgf-path",
+        mgf_path.to_str().unwrap(),
+        "--num-cycles",
+        "40",
+        "--num-threads",
+        "2",
+    ]);
+
+    let report = dda_to_mgf::run(&args);
+    std::fs::remove_dir_all(&data_path).ok();
+    std::fs::remove_file(&mgf_path).ok();
+
+    let report = report.unwrap();
+    assert_eq!(report.num_ions, 10);
+    assert_eq!(report.num_spectra, report.num_precursors);
+    assert_eq!(report.num_spectra, 10);
+    assert!(report.min_purity >= 0.9);
+    assert!(report.fragment_recall >= 0.9);
+}
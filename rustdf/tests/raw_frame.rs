@@ -0,0 +1,104 @@
+use rustdf::data::dataset::TimsDataset;
+use rustdf::data::handle::{BatchedIndexConverter, TimsData};
+use rustdf::data::meta::read_meta_data_sql;
+use rustdf::data::projection::{ToRawTimsFrame, ToTimsFrame};
+use rustdf::data::tdf_bin::TdfBinReader;
+
+fn native_dataset(in_memory: bool) -> TimsDataset {
+    let data_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d");
+    TimsDataset::new("", data_path, in_memory, false)
+}
+
+#[test]
+fn raw_frame_round_trips_on_real_data() {
+    for in_memory in [false, true] {
+        let dataset = native_dataset(in_memory);
+        let converter = dataset.loader.get_index_converter();
+
+        let raw = dataset.get_raw_frame(1);
+        let frame = dataset.get_frame(1);
+        assert!(raw.intensity.len() > 100_000);
+
+        // calibrating the raw frame gives exactly the frame the loader decodes
+        let calibrated = raw.to_tims_frame(converter, raw.retention_time);
+        assert_eq!(calibrated.scan, frame.scan);
+        assert_eq!(calibrated.tof, frame.tof);
+        assert_eq!(calibrated.ims_frame.mz, frame.ims_frame.mz);
+        assert_eq!(calibrated.ims_frame.mobility, frame.ims_frame.mobility);
+        assert_eq!(calibrated.ims_frame.intensity, frame.ims_frame.intensity);
+
+        // and going back restores the run lengths, only empty scans at the end are dropped
+        let reconstructed = frame.to_raw(converter);
+        assert_eq!(reconstructed.tof, raw.tof);
+        assert_eq!(reconstructed.intensity, raw.intensity);
+        assert_eq!(reconstructed.scan[..], raw.scan[..reconstructed.scan.len()]);
+        assert!(raw.scan[reconstructed.scan.len()..].iter().all(|&count| count == 0));
+    }
+}
+
+#[test]
+fn batched_conversion_through_loader() {
+    let dataset = native_dataset(false);
+    let converter = dataset.loader.get_index_converter();
+    let frame = dataset.get_frame(1);
+    let tof: Vec<u32> = frame.tof.iter().take(100).map(|&tof| tof as u32).collect();
+
+    let mz = converter.tof_to_mz_batched(&vec![1; tof.len()], &tof, 2);
+    assert_eq!(mz, converter.tof_to_mz(1, &tof));
+}
+
+#[test]
+fn mapped_and_buffered_reads_agree() {
+    let data_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d");
+    let mapped = TdfBinReader::open(data_path);
+    let buffered = TdfBinReader::open_buffered(data_path);
+    assert!(mapped.is_mapped());
+    assert!(!buffered.is_mapped());
+
+    let offset = read_meta_data_sql(data_path).unwrap()[0].tims_id as usize;
+    let block = mapped.read_block(offset).unwrap();
+    assert_eq!(block, buffered.read_block(offset).unwrap());
+    assert_eq!(i32::from_le_bytes(block[..4].try_into().unwrap()) as usize, block.len());
+    assert!(mapped.read_block(usize::MAX - 4).is_err());
+
+    // the lazy loader reads through the mapping and decodes the same frame as the in-memory loader
+    let lazy = native_dataset(false);
+    let in_memory = native_dataset(true);
+    let frames = lazy.get_slice(vec![1, 1, 1], 3).frames;
+    for frame in frames {
+        assert_eq!(frame.tof, in_memory.get_frame(1).tof);
+        assert_eq!(frame.ims_frame.intensity, in_memory.get_frame(1).ims_frame.intensity);
+    }
+}
+
+#[test]
+fn spectra_of_scan_ranges_match_the_frame() {
+    for in_memory in [false, true] {
+        let dataset = native_dataset(in_memory);
+        let spectra = dataset.get_frame(1).to_tims_spectra();
+        assert_eq!(dataset.get_spectra(1, 0..u32::MAX, 1).len(), spectra.len());
+
+        let middle = &spectra[spectra.len() / 2];
+        let scan = middle.scan as u32;
+        let spectrum = dataset.get_spectrum(1, scan).unwrap();
+        assert_eq!(spectrum.spectrum.index, middle.spectrum.index);
+        assert_eq!(spectrum.spectrum.mz_spectrum.mz, middle.spectrum.mz_spectrum.mz);
+        assert_eq!(spectrum.spectrum.mz_spectrum.intensity, middle.spectrum.mz_spectrum.intensity);
+        assert_eq!(spectrum.mobility, middle.mobility);
+
+        let expected: Vec<i32> = spectra.iter().map(|s| s.scan).filter(|&s| (scan as i32..scan as i32 + 50).contains(&s)).collect();
+        let ranged = dataset.get_spectra(1, scan..scan + 50, 1);
+        assert_eq!(ranged.iter().map(|s| s.scan).collect::<Vec<_>>(), expected);
+        assert!(dataset.get_spectra(1, scan..scan + 50, 20).iter().all(|s| s.spectrum.index.len() >= 20));
+
+        // empty scans, scans beyond the frame and missing frames give nothing instead of panicking
+        let empty_scan = (0..scan).find(|&s| !spectra.iter().any(|x| x.scan == s as i32));
+        if let Some(empty_scan) = empty_scan {
+            assert!(dataset.get_spectrum(1, empty_scan).is_none());
+        }
+        assert!(dataset.get_spectrum(1, 100_000).is_none());
+        assert!(dataset.get_spectra(0, 0..10, 1).is_empty());
+        assert!(dataset.get_spectra(1000, 0..10, 1).is_empty());
+        assert!(dataset.get_spectra(1, 10..10, 1).is_empty());
+    }
+}
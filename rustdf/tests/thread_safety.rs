@@ -0,0 +1,40 @@
+use std::thread;
+
+use rustdf::data::dataset::TimsDataset;
+use rustdf::data::dda::TimsDatasetDDA;
+use rustdf::data::dia::TimsDatasetDIA;
+use rustdf::data::handle::TimsData;
+use rustdf::data::prm::TimsDatasetPRM;
+use rustdf::sim::dda::TimsTofSyntheticsFrameBuilderDDA;
+use rustdf::sim::dia::TimsTofSyntheticsFrameBuilderDIA;
+use rustdf::sim::precursor::TimsTofSyntheticsPrecursorFrameBuilder;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+// the python bindings read through shared references while the GIL is released
+#[test]
+fn handles_are_send_and_sync() {
+    assert_send_sync::<TimsDataset>();
+    assert_send_sync::<TimsDatasetDDA>();
+    assert_send_sync::<TimsDatasetDIA>();
+    assert_send_sync::<TimsDatasetPRM>();
+    assert_send_sync::<TimsTofSyntheticsFrameBuilderDIA>();
+    assert_send_sync::<TimsTofSyntheticsFrameBuilderDDA>();
+    assert_send_sync::<TimsTofSyntheticsPrecursorFrameBuilder>();
+}
+
+#[test]
+fn concurrent_reads_match_sequential_reads() {
+    let data_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../imspy/imspy/simulation/resources/NATIVE.d");
+    let dataset = TimsDataset::new("", data_path, false, false);
+    let expected = dataset.get_frame(1);
+
+    thread::scope(|scope| {
+        let readers = (0..2).map(|_| scope.spawn(|| dataset.get_frame(1))).collect::<Vec<_>>();
+        for reader in readers {
+            let frame = reader.join().unwrap();
+            assert_eq!(frame.tof, expected.tof);
+            assert_eq!(frame.ims_frame.intensity, expected.ims_frame.intensity);
+        }
+    });
+}